@@ -0,0 +1,50 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use rustak_limits::Limits;
+use rustak_wire::{
+    decode_payload_for_format, encode_payload_for_format, WireFormat, WireFrameCodec,
+};
+
+const FRAMES_PER_STREAM: usize = 256;
+
+fn encoded_stream(format: WireFormat, payload: &[u8]) -> Vec<u8> {
+    let codec = WireFrameCodec::from_limits(format, &Limits::default());
+    let encoded = encode_payload_for_format(payload, format).expect("encode payload");
+    let mut stream = Vec::new();
+    for _ in 0..FRAMES_PER_STREAM {
+        block_on(codec.write_frame(&mut stream, &encoded)).expect("write frame");
+    }
+    stream
+}
+
+fn decode_stream(codec: &WireFrameCodec, mut stream: &[u8]) -> usize {
+    let mut decoded_bytes = 0;
+    for _ in 0..FRAMES_PER_STREAM {
+        let frame = block_on(codec.read_frame(&mut stream)).expect("read frame");
+        let payload = decode_payload_for_format(&frame, codec.format()).expect("decode payload");
+        decoded_bytes += payload.len();
+    }
+    decoded_bytes
+}
+
+fn bench_decode_pipeline(criterion: &mut Criterion) {
+    let payload = b"<event version=\"2.0\" uid=\"bench-decode\" type=\"a-f-G-U-C\" how=\"m-g\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:05:00Z\"><point lat=\"51.5\" lon=\"-0.12\" hae=\"11\" ce=\"9.9\" le=\"9.9\"/></event>";
+    let mut group = criterion.benchmark_group("decode_pipeline");
+    group.throughput(Throughput::Elements(FRAMES_PER_STREAM as u64));
+
+    for (name, format) in [
+        ("xml_stream_frame_and_decode", WireFormat::Xml),
+        ("tak_v1_stream_frame_and_decode", WireFormat::TakProtocolV1),
+    ] {
+        let stream = encoded_stream(format, payload);
+        let codec = WireFrameCodec::from_limits(format, &Limits::default());
+        group.bench_function(name, |bench| {
+            bench.iter(|| black_box(decode_stream(&codec, black_box(&stream))));
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode_pipeline);
+criterion_main!(benches);
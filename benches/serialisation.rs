@@ -2,7 +2,7 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rustak_wire::{decode_payload_for_format, encode_payload_for_format, WireFormat};
 
 fn bench_serialisation(criterion: &mut Criterion) {
-    let payload = b"<event uid=\"bench-serialisation\" type=\"a-f-G-U-C\"/>".to_vec();
+    let payload = b"<event version=\"2.0\" uid=\"bench-serialisation\" type=\"a-f-G-U-C\" how=\"m-g\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:05:00Z\"><point lat=\"51.5\" lon=\"-0.12\" hae=\"11\" ce=\"9.9\" le=\"9.9\"/></event>".to_vec();
     let mut group = criterion.benchmark_group("serialisation");
 
     group.bench_function("xml_round_trip_passthrough", |bench| {
@@ -1,8 +1,15 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rustak_transport::{
-    OutboundSendQueue, QueuePriority, SendQueueClassifier, SendQueueConfig, SendQueueMode,
+    OutboundSendQueue, Protocol, QueuePriority, SendQueueClassifier, SendQueueConfig,
+    SendQueueMode, TransportConfig, UdpBatchConfig, UdpTarget, UdpTransport,
 };
 
+const UDP_BURST_DATAGRAMS: usize = 64;
+const UDP_DATAGRAM_BYTES: usize = 512;
+
 #[derive(Clone)]
 struct BenchPacket {
     uid: String,
@@ -33,6 +40,7 @@ fn make_queue() -> OutboundSendQueue<BenchPacket, BenchClassifier> {
             max_messages: 8_192,
             max_bytes: 16 * 1024 * 1024,
             mode: SendQueueMode::Priority,
+            shaping: None,
         },
         BenchClassifier,
     )
@@ -72,5 +80,72 @@ fn bench_transport_throughput(criterion: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_transport_throughput);
+/// A receiving transport that drains up to `max_datagrams` per syscall, and
+/// a std socket sending to it.
+fn udp_pair(max_datagrams: usize) -> (UdpSocket, UdpTransport) {
+    let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+    let receiver = UdpTransport::bind(&TransportConfig {
+        protocol: Protocol::Udp {
+            bind_addr: loopback,
+            target: UdpTarget::Unicast(loopback),
+        },
+        ..TransportConfig::default()
+    })
+    .expect("bind receiver")
+    .with_batch_config(UdpBatchConfig {
+        max_datagrams,
+        max_datagram_bytes: UDP_DATAGRAM_BYTES,
+    })
+    .expect("batch config should be valid");
+    let sender = UdpSocket::bind("127.0.0.1:0").expect("bind sender");
+    sender
+        .connect(receiver.socket().local_addr().expect("receiver addr"))
+        .expect("connect sender");
+    (sender, receiver)
+}
+
+// Only the receive side is timed; the send burst just primes the socket.
+fn send_burst(sender: &UdpSocket, payload: &[u8]) {
+    for _ in 0..UDP_BURST_DATAGRAMS {
+        sender.send(payload).expect("send datagram");
+    }
+}
+
+fn bench_udp_receive(criterion: &mut Criterion) {
+    let payload = vec![b'x'; UDP_DATAGRAM_BYTES];
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .expect("runtime");
+    let _entered = runtime.enter();
+    let mut group = criterion.benchmark_group("udp_receive");
+
+    for (name, max_datagrams) in [
+        ("recv_one_per_syscall", 1),
+        ("recv_batch", UDP_BURST_DATAGRAMS),
+    ] {
+        group.bench_function(name, |bench| {
+            let (sender, mut receiver) = udp_pair(max_datagrams);
+            bench.iter_custom(|iterations| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iterations {
+                    send_burst(&sender, &payload);
+                    let started = Instant::now();
+                    runtime.block_on(async {
+                        for _ in 0..UDP_BURST_DATAGRAMS {
+                            let envelope = receiver.recv().await.expect("recv datagram");
+                            black_box(envelope);
+                        }
+                    });
+                    elapsed += started.elapsed();
+                }
+                elapsed
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_transport_throughput, bench_udp_receive);
 criterion_main!(benches);
@@ -1,8 +1,11 @@
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct AdminConfig {
     pub enabled: bool,
     pub bind: SocketAddr,
@@ -12,6 +15,33 @@ pub struct AdminConfig {
     pub reload_path: Option<String>,
     pub allow_reload: bool,
     pub allow_non_loopback_bind: bool,
+    /// When set, HTTP requests must send `Authorization: Bearer <token>`.
+    pub bearer_token: Option<String>,
+    /// Bound on reading a request and on writing its response.
+    pub request_timeout: Duration,
+    /// Connections served at once; further ones wait to be accepted.
+    pub max_connections: usize,
+}
+
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("enabled", &self.enabled)
+            .field("bind", &self.bind)
+            .field("health_path", &self.health_path)
+            .field("metrics_path", &self.metrics_path)
+            .field("diagnostics_path", &self.diagnostics_path)
+            .field("reload_path", &self.reload_path)
+            .field("allow_reload", &self.allow_reload)
+            .field("allow_non_loopback_bind", &self.allow_non_loopback_bind)
+            .field(
+                "bearer_token",
+                &self.bearer_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("request_timeout", &self.request_timeout)
+            .field("max_connections", &self.max_connections)
+            .finish()
+    }
 }
 
 impl Default for AdminConfig {
@@ -25,6 +55,9 @@ impl Default for AdminConfig {
             reload_path: None,
             allow_reload: false,
             allow_non_loopback_bind: false,
+            bearer_token: None,
+            request_timeout: Duration::from_secs(5),
+            max_connections: 16,
         }
     }
 }
@@ -84,6 +117,18 @@ impl AdminConfig {
             }
         }
 
+        if let Some(token) = &self.bearer_token {
+            if token.is_empty() || token.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(AdminConfigError::InvalidBearerToken);
+            }
+        }
+        if self.request_timeout.is_zero() {
+            return Err(AdminConfigError::ZeroRequestTimeout);
+        }
+        if self.max_connections == 0 {
+            return Err(AdminConfigError::ZeroMaxConnections);
+        }
+
         if self.enabled && !self.allow_non_loopback_bind && !self.bind.ip().is_loopback() {
             return Err(AdminConfigError::NonLoopbackBindDisallowed { bind: self.bind });
         }
@@ -112,6 +157,154 @@ pub enum AdminConfigError {
         "admin bind address must be loopback unless allow_non_loopback_bind=true (got {bind})"
     )]
     NonLoopbackBindDisallowed { bind: SocketAddr },
+    #[error("bearer_token must be non-empty and contain no whitespace")]
+    InvalidBearerToken,
+    #[error("request_timeout must be greater than zero")]
+    ZeroRequestTimeout,
+    #[error("max_connections must be greater than zero")]
+    ZeroMaxConnections,
+}
+
+impl CodedError for AdminConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyPath { .. } => ErrorCode::new("ADMIN", 1),
+            Self::PathMustStartWithSlash { .. } => ErrorCode::new("ADMIN", 2),
+            Self::RootPathNotAllowed { .. } => ErrorCode::new("ADMIN", 3),
+            Self::DuplicatePath { .. } => ErrorCode::new("ADMIN", 4),
+            Self::ReloadPathRequiresEnable => ErrorCode::new("ADMIN", 5),
+            Self::NonLoopbackBindDisallowed { .. } => ErrorCode::new("ADMIN", 6),
+            Self::InvalidBearerToken => ErrorCode::new("ADMIN", 7),
+            Self::ZeroRequestTimeout => ErrorCode::new("ADMIN", 8),
+            Self::ZeroMaxConnections => ErrorCode::new("ADMIN", 9),
+        }
+    }
+}
+
+/// Where the `webhooks` feature's `WebhookNotifier` posts events.
+#[derive(Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    /// `http://` or `https://` endpoints; every event is posted to each.
+    pub urls: Vec<String>,
+    /// Extra header sent with every post, e.g. `Authorization: Bearer ...`.
+    pub auth_header: Option<String>,
+    /// Bound on each delivery, connecting and the TLS handshake included.
+    pub timeout: Duration,
+}
+
+impl fmt::Debug for WebhookConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookConfig")
+            .field("urls", &self.urls)
+            .field(
+                "auth_header",
+                &self.auth_header.as_ref().map(|_| "<redacted>"),
+            )
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            auth_header: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<(), WebhookConfigError> {
+        for url in &self.urls {
+            validate_webhook_url(url)?;
+        }
+        if let Some(header) = &self.auth_header {
+            parse_webhook_header(header)?;
+        }
+        if self.timeout.is_zero() {
+            return Err(WebhookConfigError::ZeroTimeout);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum WebhookConfigError {
+    #[error("webhook url {url} must use http:// or https://")]
+    UnsupportedScheme { url: String },
+    #[error("webhook url {url} is invalid: {reason}")]
+    InvalidUrl { url: String, reason: &'static str },
+    #[error("webhook auth_header must look like 'Name: value' on a single line")]
+    InvalidAuthHeader,
+    #[error("webhook timeout must be > 0")]
+    ZeroTimeout,
+}
+
+impl CodedError for WebhookConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnsupportedScheme { .. } => ErrorCode::new("ADMIN", 401),
+            Self::InvalidUrl { .. } => ErrorCode::new("ADMIN", 402),
+            Self::InvalidAuthHeader => ErrorCode::new("ADMIN", 403),
+            Self::ZeroTimeout => ErrorCode::new("ADMIN", 404),
+        }
+    }
+}
+
+fn validate_webhook_url(url: &str) -> Result<(), WebhookConfigError> {
+    let invalid = |reason| WebhookConfigError::InvalidUrl {
+        url: url.to_owned(),
+        reason,
+    };
+    let rest = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))
+        .ok_or_else(|| WebhookConfigError::UnsupportedScheme {
+            url: url.to_owned(),
+        })?;
+    let (authority, path) = rest
+        .find('/')
+        .map_or((rest, "/"), |index| (&rest[..index], &rest[index..]));
+    if authority.contains('@') {
+        return Err(invalid("credentials belong in auth_header"));
+    }
+    // Bracketed IPv6 literals keep their colons inside `[...]`.
+    let port_separator = authority
+        .rfind(':')
+        .filter(|index| authority[*index..].find(']').is_none());
+    let host = match port_separator {
+        Some(index) => {
+            authority[index + 1..]
+                .parse::<u16>()
+                .map_err(|_| invalid("invalid port"))?;
+            &authority[..index]
+        }
+        None => authority,
+    };
+    if host.is_empty() {
+        return Err(invalid("missing host"));
+    }
+    if path.chars().any(char::is_whitespace) {
+        return Err(invalid("path must not contain whitespace"));
+    }
+    Ok(())
+}
+
+/// Splits `Name: value`, refusing anything that could inject a header.
+pub(crate) fn parse_webhook_header(header: &str) -> Result<(&str, &str), WebhookConfigError> {
+    if header.contains(['\r', '\n']) {
+        return Err(WebhookConfigError::InvalidAuthHeader);
+    }
+    let (name, value) = header
+        .split_once(':')
+        .ok_or(WebhookConfigError::InvalidAuthHeader)?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(WebhookConfigError::InvalidAuthHeader);
+    }
+    Ok((name, value.trim()))
 }
 
 fn validate_path(field: &'static str, path: &str) -> Result<(), AdminConfigError> {
@@ -131,7 +324,7 @@ fn validate_path(field: &'static str, path: &str) -> Result<(), AdminConfigError
 mod tests {
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
-    use crate::config::{AdminConfig, AdminConfigError};
+    use crate::config::{AdminConfig, AdminConfigError, WebhookConfig, WebhookConfigError};
 
     #[test]
     fn defaults_are_secure_and_valid() {
@@ -174,6 +367,24 @@ mod tests {
         assert_eq!(error, AdminConfigError::ReloadPathRequiresEnable);
     }
 
+    #[test]
+    fn bearer_token_is_validated_and_redacted() {
+        let config = AdminConfig {
+            bearer_token: Some("s3cret token".to_owned()),
+            ..AdminConfig::default()
+        };
+        assert_eq!(config.validate(), Err(AdminConfigError::InvalidBearerToken));
+
+        let config = AdminConfig {
+            bearer_token: Some("s3cret".to_owned()),
+            ..AdminConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        let debug = format!("{config:?}");
+        assert!(!debug.contains("s3cret"), "{debug}");
+        assert!(debug.contains("<redacted>"));
+    }
+
     #[test]
     fn rejects_duplicate_diagnostics_path() {
         let config = AdminConfig {
@@ -193,4 +404,50 @@ mod tests {
             }
         ));
     }
+
+    #[test]
+    fn webhook_urls_accept_http_and_https_only() {
+        for url in [
+            "http://alerts.example/hook",
+            "https://alerts.example:8443/hook",
+            "https://[::1]:9000",
+        ] {
+            let config = WebhookConfig {
+                urls: vec![url.to_owned()],
+                ..WebhookConfig::default()
+            };
+            assert_eq!(config.validate(), Ok(()), "{url}");
+        }
+
+        let ftp = WebhookConfig {
+            urls: vec!["ftp://alerts.example/hook".to_owned()],
+            ..WebhookConfig::default()
+        };
+        assert!(matches!(
+            ftp.validate(),
+            Err(WebhookConfigError::UnsupportedScheme { .. })
+        ));
+        let credentials = WebhookConfig {
+            urls: vec!["https://user:pw@alerts.example/hook".to_owned()],
+            ..WebhookConfig::default()
+        };
+        assert!(matches!(
+            credentials.validate(),
+            Err(WebhookConfigError::InvalidUrl { .. })
+        ));
+
+        let header = WebhookConfig {
+            auth_header: Some("X-Token: a\r\nX-Injected: b".to_owned()),
+            ..WebhookConfig::default()
+        };
+        assert_eq!(
+            header.validate(),
+            Err(WebhookConfigError::InvalidAuthHeader)
+        );
+        let secret = WebhookConfig {
+            auth_header: Some("Authorization: Bearer s3cret".to_owned()),
+            ..WebhookConfig::default()
+        };
+        assert!(!format!("{secret:?}").contains("s3cret"));
+    }
 }
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_transport::{FaultController, FaultSnapshot};
+use thiserror::Error;
+
+use crate::handlers::{AdminResponse, AdminState};
+
+/// Fault endpoints live under this prefix, e.g. `/faults/drop_writes/3`.
+pub const FAULTS_PATH_PREFIX: &str = "/faults/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultCommand {
+    DropWrites(u32),
+    DelayReads(Duration),
+    NegotiationTimeout,
+    FlushError,
+    Clear,
+    Status,
+}
+
+impl FaultCommand {
+    /// Parses the path segment after [`FAULTS_PATH_PREFIX`].
+    pub fn parse(command: &str) -> Result<Self, FaultInjectionError> {
+        let invalid = || FaultInjectionError::InvalidCommand {
+            command: command.to_owned(),
+        };
+        let mut parts = command.trim_matches('/').split('/');
+        let parsed = match (parts.next(), parts.next()) {
+            (Some("drop_writes"), Some(count)) => {
+                Self::DropWrites(count.parse().map_err(|_| invalid())?)
+            }
+            (Some("delay_reads_ms"), Some(millis)) => Self::DelayReads(Duration::from_millis(
+                millis.parse().map_err(|_| invalid())?,
+            )),
+            (Some("negotiation_timeout"), None) => Self::NegotiationTimeout,
+            (Some("flush_error"), None) => Self::FlushError,
+            (Some("clear"), None) => Self::Clear,
+            (Some("status"), None) => Self::Status,
+            _ => return Err(invalid()),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(parsed)
+    }
+
+    pub fn apply(self, controller: &FaultController) {
+        match self {
+            Self::DropWrites(count) => controller.drop_next_writes(count),
+            Self::DelayReads(delay) => controller.delay_reads(delay),
+            Self::NegotiationTimeout => controller.force_negotiation_timeout(),
+            Self::FlushError => controller.fail_next_flush(),
+            Self::Clear => controller.clear(),
+            Self::Status => {}
+        }
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum FaultInjectionError {
+    #[error("fault injection is not wired to a transport")]
+    Unavailable,
+    #[error("unknown fault command: {command}")]
+    InvalidCommand { command: String },
+}
+
+impl CodedError for FaultInjectionError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Unavailable => ErrorCode::new("ADMIN", 301),
+            Self::InvalidCommand { .. } => ErrorCode::new("ADMIN", 302),
+        }
+    }
+}
+
+pub fn handle_fault<S: AdminState>(
+    state: &S,
+    command: FaultCommand,
+) -> Result<AdminResponse, FaultInjectionError> {
+    let controller = state
+        .fault_controller()
+        .ok_or(FaultInjectionError::Unavailable)?;
+    command.apply(controller);
+
+    Ok(AdminResponse {
+        status_code: 200,
+        content_type: "application/json",
+        body: fault_snapshot_json(&controller.snapshot()),
+    })
+}
+
+fn fault_snapshot_json(snapshot: &FaultSnapshot) -> String {
+    format!(
+        "{{\"pending_write_drops\":{},\"read_delay_ms\":{},\"negotiation_timeout_armed\":{},\"flush_error_armed\":{},\"writes_dropped\":{},\"reads_delayed\":{},\"flush_errors\":{}}}",
+        snapshot.pending_write_drops,
+        snapshot.read_delay.map_or(0, |delay| delay.as_millis()),
+        snapshot.negotiation_timeout_armed,
+        snapshot.flush_error_armed,
+        snapshot.writes_dropped,
+        snapshot.reads_delayed,
+        snapshot.flush_errors,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FaultCommand, FaultInjectionError};
+
+    #[test]
+    fn parses_fault_commands() {
+        assert_eq!(
+            FaultCommand::parse("drop_writes/3"),
+            Ok(FaultCommand::DropWrites(3))
+        );
+        assert_eq!(
+            FaultCommand::parse("delay_reads_ms/250"),
+            Ok(FaultCommand::DelayReads(Duration::from_millis(250)))
+        );
+        assert_eq!(
+            FaultCommand::parse("negotiation_timeout"),
+            Ok(FaultCommand::NegotiationTimeout)
+        );
+        assert!(matches!(
+            FaultCommand::parse("drop_writes/many"),
+            Err(FaultInjectionError::InvalidCommand { .. })
+        ));
+        assert!(matches!(
+            FaultCommand::parse("clear/now"),
+            Err(FaultInjectionError::InvalidCommand { .. })
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -14,6 +15,11 @@ pub trait AdminState {
     fn diagnostics_snapshot(&self) -> DiagnosticsSnapshot {
         DiagnosticsSnapshot::default()
     }
+    /// Transport fault controller exposed under `/faults/` in test builds.
+    #[cfg(feature = "fault-injection")]
+    fn fault_controller(&self) -> Option<&rustak_transport::FaultController> {
+        None
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,7 +31,7 @@ pub enum DiagnosticLevel {
 }
 
 impl DiagnosticLevel {
-    fn as_str(self) -> &'static str {
+    pub(crate) fn as_str(self) -> &'static str {
         match self {
             Self::Ok => "ok",
             Self::Warn => "warn",
@@ -41,6 +47,29 @@ pub struct DiagnosticsSnapshot {
     pub negotiation: DiagnosticLevel,
     pub bridge: DiagnosticLevel,
     pub notes: Vec<String>,
+    /// Redacted `path: default -> effective` lines for non-default config.
+    pub config_diff: Vec<String>,
+    /// Pre-rendered worst-case memory lines, one per subsystem.
+    pub memory_budget: Vec<String>,
+    /// Pre-rendered byte quota usage lines, one per limited window and
+    /// direction (see `QuotaMeter::diagnostic_lines` in `rustak-transport`).
+    pub quota: Vec<String>,
+    /// Recent link-health events, oldest first (see `TransportEvent` in
+    /// `rustak-transport`, whose `Display` renders one line each).
+    pub link_events: Vec<String>,
+    /// Pre-rendered pinned and declared SAPIENT versions, one per sensor
+    /// node (see `SapientRegistry::diagnostic_lines` in `rustak-sapient`).
+    pub sapient_versions: Vec<String>,
+    /// Recent failures as `RTK-<DOMAIN>-<NNNN>: message` lines; see
+    /// [`DiagnosticsSnapshot::record_error`].
+    pub errors: Vec<String>,
+}
+
+impl DiagnosticsSnapshot {
+    /// Appends `error` to [`Self::errors`] with its stable code.
+    pub fn record_error(&mut self, error: &impl CodedError) {
+        self.errors.push(error.coded_message());
+    }
 }
 
 impl Default for DiagnosticsSnapshot {
@@ -50,6 +79,12 @@ impl Default for DiagnosticsSnapshot {
             negotiation: DiagnosticLevel::Unknown,
             bridge: DiagnosticLevel::Unknown,
             notes: Vec::new(),
+            config_diff: Vec::new(),
+            memory_budget: Vec::new(),
+            quota: Vec::new(),
+            link_events: Vec::new(),
+            sapient_versions: Vec::new(),
+            errors: Vec::new(),
         }
     }
 }
@@ -62,6 +97,15 @@ pub enum ReloadError {
     Failed { reason: String },
 }
 
+impl CodedError for ReloadError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Disabled => ErrorCode::new("ADMIN", 201),
+            Self::Failed { .. } => ErrorCode::new("ADMIN", 202),
+        }
+    }
+}
+
 #[must_use]
 pub fn handle_health<S: AdminState>(state: &S) -> AdminResponse {
     AdminResponse {
@@ -95,27 +139,42 @@ pub fn handle_reload<S: AdminState>(state: &S) -> Result<AdminResponse, ReloadEr
 #[must_use]
 pub fn handle_diagnostics<S: AdminState>(state: &S) -> AdminResponse {
     let snapshot = state.diagnostics_snapshot();
-    let notes = snapshot
-        .notes
-        .iter()
-        .map(|value| format!("\"{}\"", escape_json_string(value)))
-        .collect::<Vec<_>>()
-        .join(",");
+    let notes = json_string_array(&snapshot.notes);
+    let config_diff = json_string_array(&snapshot.config_diff);
+    let memory_budget = json_string_array(&snapshot.memory_budget);
+    let quota = json_string_array(&snapshot.quota);
+    let link_events = json_string_array(&snapshot.link_events);
+    let sapient_versions = json_string_array(&snapshot.sapient_versions);
+    let errors = json_string_array(&snapshot.errors);
 
     AdminResponse {
         status_code: 200,
         content_type: "application/json",
         body: format!(
-            "{{\"transport\":\"{}\",\"negotiation\":\"{}\",\"bridge\":\"{}\",\"notes\":[{}]}}",
+            "{{\"transport\":\"{}\",\"negotiation\":\"{}\",\"bridge\":\"{}\",\"notes\":[{}],\"config_diff\":[{}],\"memory_budget\":[{}],\"quota\":[{}],\"link_events\":[{}],\"sapient_versions\":[{}],\"errors\":[{}]}}",
             snapshot.transport.as_str(),
             snapshot.negotiation.as_str(),
             snapshot.bridge.as_str(),
             notes,
+            config_diff,
+            memory_budget,
+            quota,
+            link_events,
+            sapient_versions,
+            errors,
         ),
     }
 }
 
-fn escape_json_string(value: &str) -> String {
+fn json_string_array(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| format!("\"{}\"", escape_json_string(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub(crate) fn escape_json_string(value: &str) -> String {
     let mut escaped = String::with_capacity(value.len());
 
     for character in value.chars() {
@@ -164,12 +223,26 @@ mod tests {
         }
 
         fn diagnostics_snapshot(&self) -> DiagnosticsSnapshot {
-            DiagnosticsSnapshot {
+            let mut snapshot = DiagnosticsSnapshot {
                 transport: DiagnosticLevel::Warn,
                 negotiation: DiagnosticLevel::Error,
                 bridge: DiagnosticLevel::Ok,
                 notes: vec!["line1\nline2\t\"quoted\"\\slash\r".to_owned()],
-            }
+                config_diff: vec!["transport.read_timeout: 15s -> 30s".to_owned()],
+                memory_budget: vec![
+                    "memory_budget component=transport.send_queue worst_case_bytes=1024".to_owned(),
+                ],
+                quota: vec![
+                    "quota window=day direction=send used_bytes=10 limit_bytes=100 resets_in_secs=60 exhausted=false".to_owned(),
+                ],
+                link_events: vec!["reconnect_scheduled attempt=1 delay_ms=500".to_owned()],
+                sapient_versions: vec![
+                    "sapient_version node_id=radar-1 pinned=\"v2.0\" declared=\"v2.0\"".to_owned(),
+                ],
+                errors: Vec::new(),
+            };
+            snapshot.record_error(&ReloadError::Disabled);
+            snapshot
         }
     }
 
@@ -182,5 +255,23 @@ mod tests {
         assert!(response.body.contains("\\\\slash"));
         assert!(response.body.contains("\\r"));
         assert!(!response.body.contains("line1\nline2\t"));
+        assert!(response
+            .body
+            .contains("\"config_diff\":[\"transport.read_timeout: 15s -> 30s\"]"));
+        assert!(response.body.contains(
+            "\"memory_budget\":[\"memory_budget component=transport.send_queue worst_case_bytes=1024\"]"
+        ));
+        assert!(response.body.contains(
+            "\"quota\":[\"quota window=day direction=send used_bytes=10 limit_bytes=100 resets_in_secs=60 exhausted=false\"]"
+        ));
+        assert!(response
+            .body
+            .contains("\"link_events\":[\"reconnect_scheduled attempt=1 delay_ms=500\"]"));
+        assert!(response.body.contains(
+            "\"sapient_versions\":[\"sapient_version node_id=radar-1 pinned=\\\"v2.0\\\" declared=\\\"v2.0\\\"\"]"
+        ));
+        assert!(response
+            .body
+            .contains("\"errors\":[\"RTK-ADMIN-0201: reload is disabled\"]"));
     }
 }
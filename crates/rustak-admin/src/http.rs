@@ -0,0 +1,542 @@
+//! HTTP/1.1 listener for [`AdminServer`], built on hyper.
+//!
+//! [`AdminHttpServer`] binds [`AdminConfig::bind`](crate::AdminConfig) and
+//! answers one request per connection, then closes it. At most
+//! [`AdminConfig::max_connections`](crate::AdminConfig) connections are
+//! served at once; further ones wait in the listen backlog. The request
+//! head, the request body and the whole exchange are each bounded by
+//! [`AdminConfig::request_timeout`](crate::AdminConfig); a client that has
+//! not sent its head in time is disconnected without a response. When a
+//! bearer token is configured every path, health included, requires it.
+//!
+//! Read endpoints accept `GET` and `HEAD`; reload and fault commands change
+//! state and accept only `POST`. Failures are answered with a JSON body
+//! carrying the stable error code:
+//! `{"error":{"code":"RTK-ADMIN-0102","message":"..."}}`.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{
+    HeaderName, HeaderValue, ALLOW, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE,
+};
+use hyper::http::request::Parts;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioIo, TokioTimer};
+use rustak_limits::CodedError;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::timeout;
+
+use crate::handlers::{escape_json_string, AdminResponse, AdminState, ReloadError};
+use crate::server::{AdminServer, AdminServerError};
+
+/// Largest request line plus headers accepted; larger requests get 431.
+pub const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+
+/// Largest request body read before responding; larger bodies get 413.
+/// Admin endpoints ignore bodies.
+pub const MAX_REQUEST_BODY_BYTES: usize = 8 * 1024;
+
+/// Pause after a failed accept, so descriptor exhaustion does not spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub struct AdminHttpServer<S: AdminState> {
+    server: Arc<AdminServer<S>>,
+    listener: TcpListener,
+}
+
+impl<S> AdminHttpServer<S>
+where
+    S: AdminState + Send + Sync + 'static,
+{
+    /// Binds the configured address. A disabled server is refused rather
+    /// than bound.
+    pub async fn bind(server: AdminServer<S>) -> Result<Self, AdminServerError> {
+        if !server.config().enabled {
+            return Err(AdminServerError::Disabled);
+        }
+        let addr = server.config().bind;
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|source| AdminServerError::Bind { addr, source })?;
+        Ok(Self {
+            server: Arc::new(server),
+            listener,
+        })
+    }
+
+    /// Bound address; differs from the configured one when it used port 0.
+    pub fn local_addr(&self) -> Result<SocketAddr, AdminServerError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serves until the task is dropped.
+    pub async fn serve(self) {
+        self.serve_until(std::future::pending()).await;
+    }
+
+    /// Serves until `shutdown` completes. Connections already accepted run
+    /// to completion, bounded by the request timeout.
+    pub async fn serve_until<F>(self, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let slots = Arc::new(Semaphore::new(self.server.config().max_connections));
+        tokio::pin!(shutdown);
+        loop {
+            // Accept only with a free slot, so excess clients queue in the
+            // kernel backlog instead of each costing a task.
+            let permit = tokio::select! {
+                () = &mut shutdown => return,
+                permit = Arc::clone(&slots).acquire_owned() => match permit {
+                    Ok(permit) => permit,
+                    Err(_) => return,
+                },
+            };
+            tokio::select! {
+                () = &mut shutdown => return,
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        tokio::spawn(serve_connection(Arc::clone(&self.server), stream, permit));
+                    }
+                    Err(_) => tokio::time::sleep(ACCEPT_BACKOFF).await,
+                },
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Reply {
+    status: StatusCode,
+    content_type: &'static str,
+    body: String,
+    headers: Vec<(HeaderName, &'static str)>,
+}
+
+impl Reply {
+    fn plain(status: StatusCode) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", status.canonical_reason().unwrap_or_default()),
+            headers: Vec::new(),
+        }
+    }
+
+    fn with_header(mut self, name: HeaderName, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    fn into_response(self) -> Response<Full<Bytes>> {
+        let mut response = Response::new(Full::new(Bytes::from(self.body)));
+        *response.status_mut() = self.status;
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(self.content_type));
+        for (name, value) in self.headers {
+            headers.insert(name, HeaderValue::from_static(value));
+        }
+        response
+    }
+}
+
+impl From<AdminResponse> for Reply {
+    fn from(response: AdminResponse) -> Self {
+        Self {
+            status: StatusCode::from_u16(response.status_code)
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            content_type: response.content_type,
+            body: response.body,
+            headers: Vec::new(),
+        }
+    }
+}
+
+async fn serve_connection<S>(
+    server: Arc<AdminServer<S>>,
+    stream: TcpStream,
+    _permit: OwnedSemaphorePermit,
+) where
+    S: AdminState + Send + Sync + 'static,
+{
+    let request_timeout = server.config().request_timeout;
+    let service = service_fn(move |request| {
+        let server = Arc::clone(&server);
+        async move { Ok::<_, Infallible>(handle(&server, request).await.into_response()) }
+    });
+    let connection = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(request_timeout)
+        .max_buf_size(MAX_REQUEST_HEAD_BYTES)
+        .keep_alive(false)
+        .title_case_headers(true)
+        .serve_connection(TokioIo::new(stream), service);
+    // The peer going away mid-exchange, or stalling past the deadline, is
+    // not ours to report.
+    let _ = timeout(request_timeout.saturating_mul(2), connection).await;
+}
+
+async fn handle<S: AdminState>(server: &AdminServer<S>, request: Request<Incoming>) -> Reply {
+    let (parts, body) = request.into_parts();
+    let body = Limited::new(body, MAX_REQUEST_BODY_BYTES).collect();
+    match timeout(server.config().request_timeout, body).await {
+        Err(_) => Reply::plain(StatusCode::REQUEST_TIMEOUT),
+        Ok(Err(error)) if error.is::<LengthLimitError>() => {
+            Reply::plain(StatusCode::PAYLOAD_TOO_LARGE)
+        }
+        Ok(Err(_)) => Reply::plain(StatusCode::BAD_REQUEST),
+        Ok(Ok(_)) => respond(server, &parts),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Route {
+    Read,
+    Control,
+    Unknown,
+}
+
+fn route<S: AdminState>(server: &AdminServer<S>, path: &str) -> Route {
+    let config = server.config();
+    if path == config.health_path || path == config.metrics_path || path == config.diagnostics_path
+    {
+        return Route::Read;
+    }
+    if config.reload_path.as_deref() == Some(path) {
+        return Route::Control;
+    }
+    #[cfg(feature = "fault-injection")]
+    if path.starts_with(crate::faults::FAULTS_PATH_PREFIX) {
+        return Route::Control;
+    }
+    Route::Unknown
+}
+
+fn respond<S: AdminState>(server: &AdminServer<S>, request: &Parts) -> Reply {
+    if let Some(token) = &server.config().bearer_token {
+        let presented = request
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        if !presented.is_some_and(|presented| constant_time_eq(presented, token)) {
+            return Reply::plain(StatusCode::UNAUTHORIZED).with_header(WWW_AUTHENTICATE, "Bearer");
+        }
+    }
+
+    let path = request.uri.path();
+    match (route(server, path), &request.method) {
+        (Route::Read, &Method::GET | &Method::HEAD)
+        | (Route::Control, &Method::POST)
+        | (Route::Unknown, _) => {}
+        (Route::Read, _) => {
+            return Reply::plain(StatusCode::METHOD_NOT_ALLOWED).with_header(ALLOW, "GET, HEAD")
+        }
+        (Route::Control, _) => {
+            return Reply::plain(StatusCode::METHOD_NOT_ALLOWED).with_header(ALLOW, "POST")
+        }
+    }
+
+    match server.dispatch(path) {
+        Ok(response) => response.into(),
+        Err(error) => error_reply(&error),
+    }
+}
+
+fn error_reply(error: &AdminServerError) -> Reply {
+    let status = match error {
+        AdminServerError::Disabled => StatusCode::SERVICE_UNAVAILABLE,
+        AdminServerError::UnknownPath { .. } => StatusCode::NOT_FOUND,
+        AdminServerError::ReloadDisabled | AdminServerError::Reload(ReloadError::Disabled) => {
+            StatusCode::FORBIDDEN
+        }
+        #[cfg(feature = "fault-injection")]
+        AdminServerError::Fault(_) => StatusCode::BAD_REQUEST,
+        AdminServerError::Reload(ReloadError::Failed { .. })
+        | AdminServerError::Bind { .. }
+        | AdminServerError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    Reply {
+        status,
+        content_type: "application/json",
+        body: format!(
+            "{{\"error\":{{\"code\":\"{}\",\"message\":\"{}\"}}}}",
+            error.code(),
+            escape_json_string(&error.to_string())
+        ),
+        headers: Vec::new(),
+    }
+}
+
+/// Compares without returning early, so response timing does not reveal
+/// how much of the token matched.
+fn constant_time_eq(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (presented.as_bytes(), expected.as_bytes());
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0_u8, |difference, (left, right)| {
+                difference | (left ^ right)
+            })
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    use super::{AdminHttpServer, MAX_REQUEST_BODY_BYTES};
+    use crate::{AdminConfig, AdminServer, AdminServerError, AdminState, ReloadError};
+
+    #[derive(Debug, Default)]
+    struct CountingState {
+        reloads: AtomicUsize,
+    }
+
+    impl AdminState for CountingState {
+        fn uptime_seconds(&self) -> u64 {
+            42
+        }
+
+        fn metrics_snapshot(&self) -> String {
+            "rustak_frames_total 7\n".to_owned()
+        }
+
+        fn request_reload(&self) -> Result<(), ReloadError> {
+            self.reloads.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn config() -> AdminConfig {
+        AdminConfig {
+            enabled: true,
+            bind: "127.0.0.1:0".parse().expect("loopback"),
+            reload_path: Some("/reload".to_owned()),
+            allow_reload: true,
+            bearer_token: Some("s3cret".to_owned()),
+            ..AdminConfig::default()
+        }
+    }
+
+    async fn start(
+        config: AdminConfig,
+        state: Arc<CountingState>,
+    ) -> (SocketAddr, oneshot::Sender<()>) {
+        let server = AdminServer::new(config, state).expect("valid config");
+        let http = AdminHttpServer::bind(server).await.expect("bind");
+        let addr = http.local_addr().expect("local addr");
+        let (stop, stopped) = oneshot::channel::<()>();
+        tokio::spawn(http.serve_until(async {
+            let _ = stopped.await;
+        }));
+        (addr, stop)
+    }
+
+    async fn exchange(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .expect("send request");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("read response");
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_to_authorized_scrapers() {
+        let (addr, _stop) = start(config(), Arc::default()).await;
+
+        let response = exchange(
+            addr,
+            "GET /metrics HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer s3cret\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+        assert!(response.ends_with("\r\n\r\nrustak_frames_total 7\n"));
+
+        let head = exchange(
+            addr,
+            "HEAD /healthz?probe=1 HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n",
+        )
+        .await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+        assert!(head.ends_with("\r\n\r\n"), "HEAD carries no body: {head}");
+
+        for authorization in [
+            "",
+            "Authorization: Bearer wrong\r\n",
+            "Authorization: s3cret\r\n",
+        ] {
+            let denied = exchange(
+                addr,
+                &format!("GET /healthz HTTP/1.1\r\n{authorization}\r\n"),
+            )
+            .await;
+            assert!(denied.starts_with("HTTP/1.1 401 "), "{denied}");
+            assert!(denied
+                .to_ascii_lowercase()
+                .contains("www-authenticate: bearer\r\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_requires_post_and_errors_carry_codes() {
+        let state = Arc::new(CountingState::default());
+        let (addr, _stop) = start(config(), Arc::clone(&state)).await;
+        let auth = "Authorization: Bearer s3cret\r\n";
+
+        let wrong_method = exchange(addr, &format!("GET /reload HTTP/1.1\r\n{auth}\r\n")).await;
+        assert!(wrong_method.starts_with("HTTP/1.1 405 "), "{wrong_method}");
+        assert!(wrong_method.contains("Allow: POST\r\n"));
+        assert_eq!(state.reloads.load(Ordering::SeqCst), 0);
+
+        let reloaded = exchange(
+            addr,
+            &format!("POST /reload HTTP/1.1\r\n{auth}Content-Length: 2\r\n\r\n{{}}"),
+        )
+        .await;
+        assert!(reloaded.starts_with("HTTP/1.1 200 "), "{reloaded}");
+        assert!(reloaded.ends_with("{\"reloaded\":true}"));
+        assert_eq!(state.reloads.load(Ordering::SeqCst), 1);
+
+        let wrong_method = exchange(addr, &format!("POST /metrics HTTP/1.1\r\n{auth}\r\n")).await;
+        assert!(
+            wrong_method.contains("Allow: GET, HEAD\r\n"),
+            "{wrong_method}"
+        );
+
+        let missing = exchange(addr, &format!("GET /nope HTTP/1.1\r\n{auth}\r\n")).await;
+        assert!(missing.starts_with("HTTP/1.1 404 "), "{missing}");
+        assert!(missing.ends_with(
+            "{\"error\":{\"code\":\"RTK-ADMIN-0102\",\"message\":\"unknown admin path: /nope\"}}"
+        ));
+
+        let malformed = exchange(addr, "NOT-HTTP\r\n\r\n").await;
+        assert!(malformed.starts_with("HTTP/1.1 400 "), "{malformed}");
+
+        let oversized = exchange(
+            addr,
+            &format!(
+                "POST /reload HTTP/1.1\r\n{auth}Content-Length: {}\r\n\r\n{}",
+                MAX_REQUEST_BODY_BYTES + 1,
+                "x".repeat(MAX_REQUEST_BODY_BYTES + 1)
+            ),
+        )
+        .await;
+        assert!(oversized.starts_with("HTTP/1.1 413 "), "{oversized}");
+        assert_eq!(state.reloads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn connections_beyond_the_cap_wait_for_a_free_slot() {
+        let (addr, _stop) = start(
+            AdminConfig {
+                max_connections: 1,
+                ..config()
+            },
+            Arc::default(),
+        )
+        .await;
+
+        let mut holder = TcpStream::connect(addr).await.expect("connect");
+        holder
+            .write_all(b"GET /healthz HTTP/1.1\r\n")
+            .await
+            .expect("partial");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut waiting = TcpStream::connect(addr).await.expect("connect");
+        waiting
+            .write_all(b"GET /healthz HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n")
+            .await
+            .expect("request");
+        let mut byte = [0_u8; 1];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), waiting.read(&mut byte))
+                .await
+                .is_err(),
+            "second connection must not be served while the first holds the slot"
+        );
+
+        drop(holder);
+        let mut response = String::new();
+        waiting
+            .read_to_string(&mut response)
+            .await
+            .expect("read response");
+        assert!(response.starts_with("HTTP/1.1 200 "), "{response}");
+    }
+
+    #[tokio::test]
+    async fn slow_clients_time_out_and_disabled_servers_do_not_bind() {
+        let (addr, stop) = start(
+            AdminConfig {
+                request_timeout: Duration::from_millis(50),
+                ..config()
+            },
+            Arc::default(),
+        )
+        .await;
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"GET /healthz HTTP/1.1\r\n")
+            .await
+            .expect("partial");
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+            .await
+            .expect("slow client is disconnected")
+            .ok();
+        assert!(response.is_empty(), "{response}");
+
+        let mut stream = TcpStream::connect(addr).await.expect("connect");
+        stream
+            .write_all(b"POST /reload HTTP/1.1\r\nAuthorization: Bearer s3cret\r\nContent-Length: 8\r\n\r\n{}")
+            .await
+            .expect("partial body");
+        let mut response = String::new();
+        stream
+            .read_to_string(&mut response)
+            .await
+            .expect("read response");
+        assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+
+        stop.send(()).expect("server running");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(TcpStream::connect(addr).await.is_err(), "listener closed");
+
+        let disabled = AdminServer::new(AdminConfig::default(), Arc::new(CountingState::default()))
+            .expect("default config is valid");
+        assert!(matches!(
+            AdminHttpServer::bind(disabled).await,
+            Err(AdminServerError::Disabled)
+        ));
+    }
+}
@@ -1,19 +1,31 @@
 pub mod config;
 
-pub use config::{AdminConfig, AdminConfigError};
+pub use config::{AdminConfig, AdminConfigError, WebhookConfig, WebhookConfigError};
 
+#[cfg(feature = "fault-injection")]
+pub mod faults;
 #[cfg(feature = "admin-server")]
 pub mod handlers;
 #[cfg(feature = "admin-server")]
+pub mod http;
+#[cfg(feature = "admin-server")]
 pub mod server;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
 
+#[cfg(feature = "fault-injection")]
+pub use faults::{handle_fault, FaultCommand, FaultInjectionError, FAULTS_PATH_PREFIX};
 #[cfg(feature = "admin-server")]
 pub use handlers::{
     handle_diagnostics, handle_health, handle_metrics, handle_reload, AdminResponse, AdminState,
     DiagnosticLevel, DiagnosticsSnapshot, ReloadError,
 };
 #[cfg(feature = "admin-server")]
+pub use http::{AdminHttpServer, MAX_REQUEST_BODY_BYTES, MAX_REQUEST_HEAD_BYTES};
+#[cfg(feature = "admin-server")]
 pub use server::{AdminServer, AdminServerError};
+#[cfg(feature = "webhooks")]
+pub use webhooks::{WebhookError, WebhookEvent, WebhookNotifier};
 
 #[cfg(all(test, feature = "admin-server"))]
 mod server_tests {
@@ -89,6 +101,12 @@ mod server_tests {
                 negotiation: DiagnosticLevel::Warn,
                 bridge: DiagnosticLevel::Unknown,
                 notes: vec!["link flap recovered".to_owned()],
+                config_diff: Vec::new(),
+                memory_budget: Vec::new(),
+                quota: Vec::new(),
+                link_events: Vec::new(),
+                sapient_versions: Vec::new(),
+                errors: Vec::new(),
             },
             true,
         ));
@@ -119,6 +137,9 @@ mod server_tests {
         assert!(diagnostics
             .body
             .contains("\"notes\":[\"link flap recovered\"]"));
+        assert!(diagnostics.body.contains("\"config_diff\":[]"));
+        assert!(diagnostics.body.contains("\"memory_budget\":[]"));
+        assert!(diagnostics.body.contains("\"quota\":[]"));
     }
 
     #[test]
@@ -161,4 +182,62 @@ mod server_tests {
             .expect_err("disabled admin server must reject requests");
         assert!(matches!(error, AdminServerError::Disabled));
     }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn fault_endpoints_drive_transport_controller() {
+        use rustak_transport::FaultController;
+
+        struct FaultState {
+            controller: FaultController,
+        }
+
+        impl AdminState for FaultState {
+            fn uptime_seconds(&self) -> u64 {
+                0
+            }
+
+            fn metrics_snapshot(&self) -> String {
+                String::new()
+            }
+
+            fn request_reload(&self) -> Result<(), ReloadError> {
+                Err(ReloadError::Disabled)
+            }
+
+            fn fault_controller(&self) -> Option<&FaultController> {
+                Some(&self.controller)
+            }
+        }
+
+        let controller = FaultController::new();
+        let config = AdminConfig {
+            enabled: true,
+            ..AdminConfig::default()
+        };
+        let server = AdminServer::new(
+            config,
+            Arc::new(FaultState {
+                controller: controller.clone(),
+            }),
+        )
+        .expect("server should construct");
+
+        let response = server
+            .dispatch("/faults/drop_writes/2")
+            .expect("fault endpoint should succeed");
+        assert_eq!(response.status_code, 200);
+        assert!(response.body.contains("\"pending_write_drops\":2"));
+        assert_eq!(controller.snapshot().pending_write_drops, 2);
+
+        server
+            .dispatch("/faults/clear")
+            .expect("clear should succeed");
+        assert_eq!(controller.snapshot().pending_write_drops, 0);
+
+        let error = server
+            .dispatch("/faults/explode")
+            .expect_err("unknown fault command must fail");
+        assert!(matches!(error, AdminServerError::Fault(_)));
+    }
 }
@@ -1,5 +1,8 @@
+use std::io;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 use crate::{
@@ -20,6 +23,32 @@ pub enum AdminServerError {
     ReloadDisabled,
     #[error(transparent)]
     Reload(#[from] ReloadError),
+    #[error("failed to bind admin listener on {addr}: {source}")]
+    Bind {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+    #[error("admin listener failed: {0}")]
+    Io(#[from] io::Error),
+    #[cfg(feature = "fault-injection")]
+    #[error(transparent)]
+    Fault(#[from] crate::faults::FaultInjectionError),
+}
+
+impl CodedError for AdminServerError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Disabled => ErrorCode::new("ADMIN", 101),
+            Self::UnknownPath { .. } => ErrorCode::new("ADMIN", 102),
+            Self::ReloadDisabled => ErrorCode::new("ADMIN", 103),
+            Self::Reload(error) => error.code(),
+            Self::Bind { .. } => ErrorCode::new("ADMIN", 104),
+            Self::Io(_) => ErrorCode::new("ADMIN", 105),
+            #[cfg(feature = "fault-injection")]
+            Self::Fault(error) => error.code(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -61,6 +90,12 @@ impl<S: AdminState> AdminServer<S> {
             }
         }
 
+        #[cfg(feature = "fault-injection")]
+        if let Some(command) = path.strip_prefix(crate::faults::FAULTS_PATH_PREFIX) {
+            let command = crate::faults::FaultCommand::parse(command)?;
+            return Ok(crate::faults::handle_fault(self.state.as_ref(), command)?);
+        }
+
         Err(AdminServerError::UnknownPath {
             path: path.to_owned(),
         })
@@ -0,0 +1,500 @@
+//! Connection and health events posted as JSON to HTTP(S) endpoints.
+//!
+//! [`WebhookNotifier`] posts each [`WebhookEvent`] to every URL in its
+//! [`WebhookConfig`] over hyper, with rustls and the webpki root store for
+//! `https://` endpoints. [`WebhookNotifier::forward_transport_events`] turns
+//! a connection manager's `TransportEvent`s into `connection_up` and
+//! `connection_down` posts; negotiation fallbacks and health changes come
+//! from the application through [`WebhookNotifier::notify`].
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use hyper::{Method, Request, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_transport::{TransportEvent, TransportEvents};
+use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+use crate::config::{parse_webhook_header, WebhookConfig, WebhookConfigError};
+use crate::handlers::{escape_json_string, DiagnosticLevel, DiagnosticsSnapshot};
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum WebhookError {
+    #[error("webhook {url} delivery failed: {reason}")]
+    Delivery { url: String, reason: String },
+    #[error("webhook {url} responded with status {status}")]
+    Status { url: String, status: u16 },
+}
+
+impl CodedError for WebhookError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Delivery { .. } => ErrorCode::new("ADMIN", 501),
+            Self::Status { .. } => ErrorCode::new("ADMIN", 502),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEvent {
+    ConnectionUp {
+        peer: String,
+    },
+    /// `peer` is the address last reported up, when there was one.
+    ConnectionDown {
+        peer: Option<String>,
+        reason: Option<String>,
+    },
+    NegotiationFallback {
+        peer: String,
+        reason: String,
+    },
+    HealthChanged {
+        component: &'static str,
+        from: DiagnosticLevel,
+        to: DiagnosticLevel,
+    },
+}
+
+impl WebhookEvent {
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ConnectionUp { .. } => "connection_up",
+            Self::ConnectionDown { .. } => "connection_down",
+            Self::NegotiationFallback { .. } => "negotiation_fallback",
+            Self::HealthChanged { .. } => "health_changed",
+        }
+    }
+
+    /// JSON body posted for this event.
+    #[must_use]
+    pub fn to_json(&self, at: SystemTime) -> String {
+        let at_unix_ms = at
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let fields = match self {
+            Self::ConnectionUp { peer } => {
+                format!("\"peer\":\"{}\"", escape_json_string(peer))
+            }
+            Self::ConnectionDown { peer, reason } => format!(
+                "\"peer\":{},\"reason\":{}",
+                json_string_or_null(peer.as_deref()),
+                json_string_or_null(reason.as_deref())
+            ),
+            Self::NegotiationFallback { peer, reason } => format!(
+                "\"peer\":\"{}\",\"reason\":\"{}\"",
+                escape_json_string(peer),
+                escape_json_string(reason)
+            ),
+            Self::HealthChanged {
+                component,
+                from,
+                to,
+            } => format!(
+                "\"component\":\"{component}\",\"from\":\"{}\",\"to\":\"{}\"",
+                from.as_str(),
+                to.as_str()
+            ),
+        };
+        format!(
+            "{{\"event\":\"{}\",\"at_unix_ms\":{at_unix_ms},{fields}}}",
+            self.kind()
+        )
+    }
+}
+
+fn json_string_or_null(value: Option<&str>) -> String {
+    value.map_or_else(
+        || "null".to_owned(),
+        |value| format!("\"{}\"", escape_json_string(value)),
+    )
+}
+
+/// Posts [`WebhookEvent`]s as JSON to every configured endpoint.
+///
+/// Delivery is best-effort: each post is bounded by the configured timeout
+/// and failures are returned rather than retried.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    endpoints: Vec<(String, Uri)>,
+    auth_header: Option<(HeaderName, HeaderValue)>,
+    timeout: Duration,
+    last_health: Mutex<Option<[DiagnosticLevel; 3]>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookConfig) -> Result<Self, WebhookConfigError> {
+        config.validate()?;
+        let endpoints = config
+            .urls
+            .iter()
+            .map(|url| {
+                let uri = url
+                    .parse::<Uri>()
+                    .map_err(|_| WebhookConfigError::InvalidUrl {
+                        url: url.clone(),
+                        reason: "not a valid URI",
+                    })?;
+                Ok((url.clone(), uri))
+            })
+            .collect::<Result<_, _>>()?;
+        let auth_header = config
+            .auth_header
+            .as_deref()
+            .map(|header| {
+                let (name, value) = parse_webhook_header(header)?;
+                let name = HeaderName::try_from(name)
+                    .map_err(|_| WebhookConfigError::InvalidAuthHeader)?;
+                let mut value = HeaderValue::try_from(value)
+                    .map_err(|_| WebhookConfigError::InvalidAuthHeader)?;
+                value.set_sensitive(true);
+                Ok((name, value))
+            })
+            .transpose()?;
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_webpki_roots(Arc::new(rustls::crypto::ring::default_provider()))
+            .expect("ring supports the default TLS protocol versions")
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            endpoints,
+            auth_header,
+            timeout: config.timeout,
+            last_health: Mutex::new(None),
+        })
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.endpoints.is_empty()
+    }
+
+    /// Posts `event` to each endpoint, returning one result per endpoint in
+    /// configuration order (`Ok` carries the response status).
+    pub async fn notify(&self, event: &WebhookEvent) -> Vec<Result<u16, WebhookError>> {
+        let body = Bytes::from(event.to_json(SystemTime::now()));
+        let mut results = Vec::with_capacity(self.endpoints.len());
+        for (url, uri) in &self.endpoints {
+            results.push(self.post(url, uri, body.clone()).await);
+        }
+        results
+    }
+
+    /// Posts `connection_up` and `connection_down` for the connection
+    /// events published on `events` until every sender is dropped. Other
+    /// transport events are not forwarded, and delivery failures are
+    /// dropped.
+    pub fn forward_transport_events(self: Arc<Self>, events: &TransportEvents) -> JoinHandle<()> {
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            let mut peer = None;
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                };
+                let event = match event {
+                    TransportEvent::Connected { peer: up } => {
+                        peer = Some(up.to_string());
+                        WebhookEvent::ConnectionUp {
+                            peer: up.to_string(),
+                        }
+                    }
+                    TransportEvent::Disconnected { reason } => WebhookEvent::ConnectionDown {
+                        peer: peer.take(),
+                        reason: Some(reason),
+                    },
+                    _ => continue,
+                };
+                self.notify(&event).await;
+            }
+        })
+    }
+
+    /// Compares component levels against the previous snapshot and returns a
+    /// `HealthChanged` event for each transition. The first snapshot only
+    /// seeds the baseline.
+    pub fn health_transitions(&self, snapshot: &DiagnosticsSnapshot) -> Vec<WebhookEvent> {
+        let current = [snapshot.transport, snapshot.negotiation, snapshot.bridge];
+        let previous = self
+            .last_health
+            .lock()
+            .expect("webhook health mutex poisoned")
+            .replace(current);
+        let Some(previous) = previous else {
+            return Vec::new();
+        };
+
+        ["transport", "negotiation", "bridge"]
+            .into_iter()
+            .zip(previous.into_iter().zip(current))
+            .filter(|(_, (from, to))| from != to)
+            .map(|(component, (from, to))| WebhookEvent::HealthChanged {
+                component,
+                from,
+                to,
+            })
+            .collect()
+    }
+
+    async fn post(&self, url: &str, uri: &Uri, body: Bytes) -> Result<u16, WebhookError> {
+        let delivery = |reason: String| WebhookError::Delivery {
+            url: url.to_owned(),
+            reason,
+        };
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(uri.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(body))
+            .map_err(|error| delivery(error.to_string()))?;
+        if let Some((name, value)) = &self.auth_header {
+            request.headers_mut().insert(name.clone(), value.clone());
+        }
+        let response = timeout(self.timeout, self.client.request(request))
+            .await
+            .map_err(|_| delivery(format!("timed out after {:?}", self.timeout)))?
+            .map_err(|error| delivery(error_chain(&error)))?;
+        let status = response.status().as_u16();
+        if !response.status().is_success() {
+            return Err(WebhookError::Status {
+                url: url.to_owned(),
+                status,
+            });
+        }
+        Ok(status)
+    }
+}
+
+/// The error and its sources, since the client's own message is often
+/// just "client error (Connect)".
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut reason = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        reason.push_str(": ");
+        reason.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use rustak_transport::{TransportEvent, TransportEvents};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    use super::{WebhookConfig, WebhookError, WebhookEvent, WebhookNotifier};
+    use crate::handlers::{DiagnosticLevel, DiagnosticsSnapshot};
+
+    /// Answers `requests` posts with `status_line` and returns what was sent.
+    async fn serve(
+        status_line: &'static str,
+        requests: usize,
+    ) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind loopback");
+        let url = format!(
+            "http://{}/hooks/rustak",
+            listener.local_addr().expect("addr")
+        );
+        let handle = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for _ in 0..requests {
+                let (mut stream, _) = listener.accept().await.expect("accept");
+                let mut request = Vec::new();
+                let mut buffer = [0_u8; 1024];
+                loop {
+                    let read = stream.read(&mut buffer).await.expect("read request");
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .and_then(|value| value.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break;
+                        }
+                    }
+                }
+                stream
+                    .write_all(
+                        format!("{status_line}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                            .as_bytes(),
+                    )
+                    .await
+                    .expect("write response");
+                received.push(String::from_utf8(request).expect("utf8 request"));
+            }
+            received
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn posts_json_event_with_auth_and_host_headers() {
+        let (url, server) = serve("HTTP/1.1 204 No Content", 1).await;
+        let authority = url
+            .trim_start_matches("http://")
+            .trim_end_matches("/hooks/rustak")
+            .to_owned();
+        let notifier = WebhookNotifier::new(&WebhookConfig {
+            urls: vec![url],
+            auth_header: Some("Authorization: Bearer s3cret".to_owned()),
+            ..WebhookConfig::default()
+        })
+        .expect("valid config");
+        assert!(!format!("{notifier:?}").contains("s3cret"));
+
+        let results = notifier
+            .notify(&WebhookEvent::NegotiationFallback {
+                peer: "10.0.0.5:8089".to_owned(),
+                reason: "timeout".to_owned(),
+            })
+            .await;
+        assert_eq!(results, vec![Ok(204)]);
+
+        let request = server.await.expect("server task").remove(0);
+        let lower = request.to_ascii_lowercase();
+        assert!(
+            request.starts_with("POST /hooks/rustak HTTP/1.1\r\n"),
+            "{request}"
+        );
+        assert!(
+            lower.contains(&format!("\r\nhost: {authority}\r\n")),
+            "{request}"
+        );
+        assert!(lower.contains("\r\nauthorization: bearer s3cret\r\n"));
+        assert!(request.contains("\"event\":\"negotiation_fallback\""));
+        assert!(request.ends_with("\"peer\":\"10.0.0.5:8089\",\"reason\":\"timeout\"}"));
+    }
+
+    #[tokio::test]
+    async fn non_success_status_and_failed_handshakes_are_reported() {
+        let (url, server) = serve("HTTP/1.1 503 Service Unavailable", 1).await;
+        let notifier = WebhookNotifier::new(&WebhookConfig {
+            urls: vec![url.clone()],
+            ..WebhookConfig::default()
+        })
+        .expect("valid config");
+        let results = notifier
+            .notify(&WebhookEvent::ConnectionUp {
+                peer: "tak.example:8089".to_owned(),
+            })
+            .await;
+        server.await.expect("server task");
+        assert_eq!(
+            results,
+            vec![Err(WebhookError::Status { url, status: 503 })]
+        );
+
+        // A plain HTTP listener cannot complete a TLS handshake.
+        let (url, _server) = serve("HTTP/1.1 204 No Content", 1).await;
+        let https = url.replacen("http://", "https://", 1);
+        let notifier = WebhookNotifier::new(&WebhookConfig {
+            urls: vec![https.clone()],
+            timeout: Duration::from_millis(200),
+            ..WebhookConfig::default()
+        })
+        .expect("https is accepted");
+        let results = notifier
+            .notify(&WebhookEvent::ConnectionUp {
+                peer: "tak.example:8089".to_owned(),
+            })
+            .await;
+        assert!(
+            matches!(&results[..], [Err(WebhookError::Delivery { url, .. })] if *url == https),
+            "{results:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn forwards_connection_events_from_the_transport() {
+        let (url, server) = serve("HTTP/1.1 200 OK", 2).await;
+        let notifier = Arc::new(
+            WebhookNotifier::new(&WebhookConfig {
+                urls: vec![url],
+                ..WebhookConfig::default()
+            })
+            .expect("valid config"),
+        );
+        let events = TransportEvents::default();
+        let forwarding = Arc::clone(&notifier).forward_transport_events(&events);
+
+        events.publish(TransportEvent::Connected {
+            peer: "10.0.0.7:8089".parse().expect("peer"),
+        });
+        events.publish(TransportEvent::ReconnectScheduled {
+            attempt: 1,
+            delay: Duration::from_millis(100),
+        });
+        events.publish(TransportEvent::Disconnected {
+            reason: "eof".to_owned(),
+        });
+        let received = server.await.expect("server task");
+        drop(events);
+        forwarding.await.expect("forwarding ends with the senders");
+
+        assert!(received[0].contains("\"event\":\"connection_up\""));
+        assert!(received[0].ends_with("\"peer\":\"10.0.0.7:8089\"}"));
+        assert!(received[1].contains("\"event\":\"connection_down\""));
+        assert!(received[1].ends_with("\"peer\":\"10.0.0.7:8089\",\"reason\":\"eof\"}"));
+    }
+
+    #[test]
+    fn health_transitions_follow_snapshot_changes() {
+        let notifier = WebhookNotifier::new(&WebhookConfig::default()).expect("valid config");
+        let mut snapshot = DiagnosticsSnapshot {
+            transport: DiagnosticLevel::Ok,
+            ..DiagnosticsSnapshot::default()
+        };
+        assert!(notifier.health_transitions(&snapshot).is_empty());
+
+        snapshot.transport = DiagnosticLevel::Error;
+        let events = notifier.health_transitions(&snapshot);
+        assert_eq!(
+            events,
+            vec![WebhookEvent::HealthChanged {
+                component: "transport",
+                from: DiagnosticLevel::Ok,
+                to: DiagnosticLevel::Error,
+            }]
+        );
+        assert_eq!(
+            events[0].to_json(UNIX_EPOCH + Duration::from_millis(1500)),
+            "{\"event\":\"health_changed\",\"at_unix_ms\":1500,\"component\":\"transport\",\"from\":\"ok\",\"to\":\"error\"}"
+        );
+        assert_eq!(
+            WebhookEvent::ConnectionDown {
+                peer: None,
+                reason: None
+            }
+            .to_json(UNIX_EPOCH),
+            "{\"event\":\"connection_down\",\"at_unix_ms\":0,\"peer\":null,\"reason\":null}"
+        );
+        assert!(notifier.health_transitions(&snapshot).is_empty());
+    }
+}
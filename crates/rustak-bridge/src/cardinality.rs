@@ -0,0 +1,290 @@
+//! Guards TAK clients against sensor storms.
+//!
+//! A [`TrackCardinalityGuard`] counts the distinct UIDs forwarded within
+//! [`TrackCardinalityConfig::active_window`]. Past `coalesce_above` active
+//! tracks, updates to low-priority tracks are coalesced to one per
+//! `coalesce_interval`, so each still moves but at a slower cadence. Past
+//! `sample_above`, only one in `sample_one_in` new low-priority tracks is
+//! admitted, and past `max_active_tracks` none are. Tracks whose CoT type
+//! starts with one of `high_priority_type_prefixes` are always forwarded.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use crate::BridgeConfigError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackCardinalityConfig {
+    /// A track counts as active until this long after it was last forwarded.
+    pub active_window: Duration,
+    /// Active tracks beyond which low-priority updates are coalesced.
+    pub coalesce_above: usize,
+    /// Least time between forwarded updates of a coalesced track.
+    pub coalesce_interval: Duration,
+    /// Active tracks beyond which new low-priority tracks are sampled.
+    pub sample_above: usize,
+    /// One new low-priority track is admitted per this many while sampling.
+    pub sample_one_in: u32,
+    /// Active tracks beyond which new low-priority tracks are dropped.
+    pub max_active_tracks: usize,
+    /// CoT type prefixes that are never coalesced, sampled or dropped.
+    pub high_priority_type_prefixes: Vec<String>,
+}
+
+impl Default for TrackCardinalityConfig {
+    fn default() -> Self {
+        Self {
+            active_window: Duration::from_secs(60),
+            coalesce_above: 2_000,
+            coalesce_interval: Duration::from_secs(5),
+            sample_above: 5_000,
+            sample_one_in: 10,
+            max_active_tracks: 10_000,
+            high_priority_type_prefixes: vec!["a-h-".to_owned()],
+        }
+    }
+}
+
+impl TrackCardinalityConfig {
+    pub(crate) fn validate(&self) -> Result<(), BridgeConfigError> {
+        if self.active_window.is_zero() || self.coalesce_interval.is_zero() {
+            return Err(BridgeConfigError::ZeroTrackCardinalityWindow);
+        }
+        if self.sample_one_in == 0 {
+            return Err(BridgeConfigError::ZeroTrackCardinalitySampleRate);
+        }
+        if self.coalesce_above > self.sample_above || self.sample_above > self.max_active_tracks {
+            return Err(BridgeConfigError::TrackCardinalityThresholdsOutOfOrder);
+        }
+        if self
+            .high_priority_type_prefixes
+            .iter()
+            .any(|prefix| prefix.trim().is_empty())
+        {
+            return Err(BridgeConfigError::EmptyHighPriorityTypePrefix);
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn is_high_priority(&self, cot_type: &str) -> bool {
+        self.high_priority_type_prefixes
+            .iter()
+            .any(|prefix| cot_type.starts_with(prefix.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardinalityDecision {
+    Forward,
+    /// Held back because the track was forwarded within the coalesce
+    /// interval; a later update carries its latest state.
+    Coalesced,
+    /// A new track left out by sampling or the active-track cap.
+    Sampled,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveTrack {
+    last_forwarded: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackCardinalityGuard {
+    config: TrackCardinalityConfig,
+    active: HashMap<String, ActiveTrack>,
+    new_low_priority: u64,
+    next_prune: Option<SystemTime>,
+}
+
+impl TrackCardinalityGuard {
+    pub fn new(config: TrackCardinalityConfig) -> Result<Self, BridgeConfigError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            active: HashMap::new(),
+            new_low_priority: 0,
+            next_prune: None,
+        })
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &TrackCardinalityConfig {
+        &self.config
+    }
+
+    /// Distinct UIDs forwarded within the active window, as of the last
+    /// [`Self::admit`].
+    #[must_use]
+    pub fn active_tracks(&self) -> usize {
+        self.active.len()
+    }
+
+    /// Decides whether an update to `uid` at `observed_at` is forwarded.
+    pub fn admit(
+        &mut self,
+        uid: &str,
+        cot_type: &str,
+        observed_at: SystemTime,
+    ) -> CardinalityDecision {
+        self.prune(observed_at);
+        let high_priority = self.config.is_high_priority(cot_type);
+        let active = self.active.len();
+
+        if let Some(track) = self.active.get_mut(uid) {
+            let since_forwarded = observed_at
+                .duration_since(track.last_forwarded)
+                .unwrap_or_default();
+            if !high_priority
+                && active > self.config.coalesce_above
+                && since_forwarded < self.config.coalesce_interval
+            {
+                return CardinalityDecision::Coalesced;
+            }
+            track.last_forwarded = track.last_forwarded.max(observed_at);
+            return CardinalityDecision::Forward;
+        }
+
+        if !high_priority && active >= self.config.sample_above {
+            if active >= self.config.max_active_tracks {
+                return CardinalityDecision::Sampled;
+            }
+            let admitted = self.new_low_priority % u64::from(self.config.sample_one_in) == 0;
+            self.new_low_priority = self.new_low_priority.wrapping_add(1);
+            if !admitted {
+                return CardinalityDecision::Sampled;
+            }
+        }
+
+        self.active.insert(
+            uid.to_owned(),
+            ActiveTrack {
+                last_forwarded: observed_at,
+            },
+        );
+        CardinalityDecision::Forward
+    }
+
+    /// Forgets tracks idle past the active window, scanning at most once a
+    /// second so storms do not pay for a full pass per update.
+    fn prune(&mut self, now: SystemTime) {
+        if self.next_prune.is_some_and(|next| now < next) {
+            return;
+        }
+        let window = self.config.active_window;
+        self.active.retain(|_, track| {
+            now.duration_since(track.last_forwarded)
+                .map_or(true, |idle| idle <= window)
+        });
+        self.next_prune = now.checked_add(Duration::from_secs(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{CardinalityDecision, TrackCardinalityConfig, TrackCardinalityGuard};
+    use crate::BridgeConfigError;
+
+    fn at(millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(millis)
+    }
+
+    fn guard() -> TrackCardinalityGuard {
+        TrackCardinalityGuard::new(TrackCardinalityConfig {
+            active_window: Duration::from_secs(10),
+            coalesce_above: 2,
+            coalesce_interval: Duration::from_secs(1),
+            sample_above: 4,
+            sample_one_in: 2,
+            max_active_tracks: 6,
+            ..TrackCardinalityConfig::default()
+        })
+        .expect("valid config")
+    }
+
+    #[test]
+    fn coalesces_then_samples_low_priority_tracks_as_load_grows() {
+        let mut guard = guard();
+        for uid in ["a", "b", "c"] {
+            assert_eq!(
+                guard.admit(uid, "a-u-G", at(0)),
+                CardinalityDecision::Forward
+            );
+        }
+        assert_eq!(
+            guard.admit("a", "a-u-G", at(500)),
+            CardinalityDecision::Coalesced
+        );
+        assert_eq!(
+            guard.admit("a", "a-u-G", at(1_000)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(
+            guard.admit("a", "a-h-G", at(1_100)),
+            CardinalityDecision::Forward,
+            "hostile tracks are never coalesced"
+        );
+
+        assert_eq!(
+            guard.admit("d", "a-u-G", at(1_200)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(guard.active_tracks(), 4);
+        assert_eq!(
+            guard.admit("e", "a-u-G", at(1_300)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(
+            guard.admit("f", "a-u-G", at(1_400)),
+            CardinalityDecision::Sampled
+        );
+        assert_eq!(
+            guard.admit("g", "a-u-G", at(1_500)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(guard.active_tracks(), 6);
+        assert_eq!(
+            guard.admit("h", "a-u-G", at(1_600)),
+            CardinalityDecision::Sampled,
+            "the cap refuses new low-priority tracks outright"
+        );
+        assert_eq!(
+            guard.admit("i", "a-h-A", at(1_700)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(guard.active_tracks(), 7);
+    }
+
+    #[test]
+    fn idle_tracks_leave_the_active_set() {
+        let mut guard = guard();
+        for uid in ["a", "b", "c", "d", "e"] {
+            guard.admit(uid, "a-u-G", at(0));
+        }
+        assert_eq!(
+            guard.admit("f", "a-u-G", at(5_000)),
+            CardinalityDecision::Sampled
+        );
+        assert_eq!(
+            guard.admit("f", "a-u-G", at(10_001)),
+            CardinalityDecision::Forward
+        );
+        assert_eq!(guard.active_tracks(), 1);
+    }
+
+    #[test]
+    fn rejects_thresholds_out_of_order() {
+        let config = TrackCardinalityConfig {
+            coalesce_above: 10,
+            sample_above: 5,
+            ..TrackCardinalityConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(BridgeConfigError::TrackCardinalityThresholdsOutOfOrder)
+        );
+        assert!(TrackCardinalityConfig::default().validate().is_ok());
+    }
+}
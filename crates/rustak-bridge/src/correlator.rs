@@ -1,5 +1,7 @@
 use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +14,10 @@ pub enum UidPolicy {
 pub struct CorrelatorConfig {
     pub uid_policy: UidPolicy,
     pub uid_prefix: String,
+    /// Correlations kept at most; the least recently seen is evicted first.
+    pub max_entries: usize,
+    /// Correlations not seen for longer than this are evicted.
+    pub max_idle: Option<Duration>,
 }
 
 impl Default for CorrelatorConfig {
@@ -19,21 +25,43 @@ impl Default for CorrelatorConfig {
         Self {
             uid_policy: UidPolicy::StablePerObject,
             uid_prefix: "trk".to_owned(),
+            max_entries: 4_096,
+            max_idle: Some(Duration::from_secs(600)),
         }
     }
 }
 
+impl CorrelatorConfig {
+    pub fn validate(&self) -> Result<(), CorrelatorError> {
+        if self.uid_prefix.trim().is_empty() {
+            return Err(CorrelatorError::EmptyUidPrefix);
+        }
+        if self.max_entries == 0 {
+            return Err(CorrelatorError::ZeroMaxEntries);
+        }
+        if self.max_idle.is_some_and(|max_idle| max_idle.is_zero()) {
+            return Err(CorrelatorError::ZeroMaxIdle);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CorrelationInput {
     pub node_id: String,
     pub object_id: Option<String>,
     pub detection_id: Option<String>,
+    /// ICD version the node declared when it registered, kept as provenance.
+    pub sapient_version: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CorrelationEntry {
     pub key: String,
     pub uid: String,
+    pub last_seen: SystemTime,
+    /// Last SAPIENT version declared by the node that reported this track.
+    pub sapient_version: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -41,11 +69,34 @@ pub struct CorrelatorSnapshot {
     pub entries: Vec<CorrelationEntry>,
 }
 
+/// Occupancy and eviction counters since the correlator was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CorrelatorMetrics {
+    pub entries: usize,
+    pub peak_entries: usize,
+    pub max_entries: usize,
+    pub evicted_capacity: u64,
+    pub evicted_idle: u64,
+}
+
+impl CorrelatorMetrics {
+    #[must_use]
+    pub fn evicted(&self) -> u64 {
+        self.evicted_capacity + self.evicted_idle
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum CorrelatorError {
     #[error("uid_prefix must not be empty")]
     EmptyUidPrefix,
 
+    #[error("correlator.max_entries must be > 0")]
+    ZeroMaxEntries,
+
+    #[error("correlator.max_idle must be > 0 when set")]
+    ZeroMaxIdle,
+
     #[error("node_id must not be empty")]
     EmptyNodeId,
 
@@ -56,59 +107,199 @@ pub enum CorrelatorError {
     MissingDetectionId,
 }
 
+impl CodedError for CorrelatorError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyUidPrefix => ErrorCode::new("BRIDGE", 201),
+            Self::ZeroMaxEntries => ErrorCode::new("BRIDGE", 202),
+            Self::ZeroMaxIdle => ErrorCode::new("BRIDGE", 203),
+            Self::EmptyNodeId => ErrorCode::new("BRIDGE", 204),
+            Self::MissingObjectId => ErrorCode::new("BRIDGE", 205),
+            Self::MissingDetectionId => ErrorCode::new("BRIDGE", 206),
+        }
+    }
+}
+
+struct Correlation {
+    uid: String,
+    recency: Recency,
+    sapient_version: Option<String>,
+}
+
+/// Orders correlations by when they were last seen; the counter breaks ties
+/// between equal timestamps.
+type Recency = (SystemTime, u64);
+
+/// Maps sensor object/detection IDs to CoT UIDs.
+///
+/// UIDs are derived from the policy key alone, so a correlation that was
+/// evicted and later reappears is mapped to the same UID it had before. The
+/// only exception is a hash collision with a live correlation, which is
+/// resolved by salting.
 pub struct Correlator {
     config: CorrelatorConfig,
-    key_to_uid: BTreeMap<String, String>,
+    key_to_uid: BTreeMap<String, Correlation>,
     uid_to_key: BTreeMap<String, String>,
+    recency: BTreeMap<Recency, String>,
+    touches: u64,
+    metrics: CorrelatorMetrics,
 }
 
 impl Correlator {
     pub fn new(config: CorrelatorConfig) -> Result<Self, CorrelatorError> {
-        if config.uid_prefix.trim().is_empty() {
-            return Err(CorrelatorError::EmptyUidPrefix);
-        }
+        config.validate()?;
 
         Ok(Self {
+            metrics: CorrelatorMetrics {
+                max_entries: config.max_entries,
+                ..CorrelatorMetrics::default()
+            },
             config,
             key_to_uid: BTreeMap::new(),
             uid_to_key: BTreeMap::new(),
+            recency: BTreeMap::new(),
+            touches: 0,
         })
     }
 
-    pub fn correlate(&mut self, input: &CorrelationInput) -> Result<String, CorrelatorError> {
+    /// Returns the UID for `input`, evicting correlations idle at
+    /// `observed_at` and, once full, the least recently seen one.
+    pub fn correlate(
+        &mut self,
+        input: &CorrelationInput,
+        observed_at: SystemTime,
+    ) -> Result<String, CorrelatorError> {
         let key = input.canonical_key(self.config.uid_policy)?;
-        if let Some(existing_uid) = self.key_to_uid.get(&key) {
-            return Ok(existing_uid.clone());
+        self.evict_idle(observed_at);
+
+        let recency = self.next_recency(observed_at);
+        if let Some(existing) = self.key_to_uid.get_mut(&key) {
+            // Out-of-order observations never make a correlation look older.
+            let recency = recency.max(existing.recency);
+            self.recency.remove(&existing.recency);
+            self.recency.insert(recency, key);
+            existing.recency = recency;
+            if input.sapient_version.is_some() {
+                existing.sapient_version.clone_from(&input.sapient_version);
+            }
+            return Ok(existing.uid.clone());
         }
 
         let uid = self.allocate_uid(&key);
-        self.key_to_uid.insert(key.clone(), uid.clone());
-        self.uid_to_key.insert(uid.clone(), key);
+        self.insert(key, uid.clone(), recency, input.sapient_version.clone());
+        self.evict_over_capacity();
         Ok(uid)
     }
 
+    #[must_use]
+    pub fn metrics(&self) -> CorrelatorMetrics {
+        self.metrics
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.key_to_uid.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.key_to_uid.is_empty()
+    }
+
     #[must_use]
     pub fn snapshot(&self) -> CorrelatorSnapshot {
         CorrelatorSnapshot {
             entries: self
                 .key_to_uid
                 .iter()
-                .map(|(key, uid)| CorrelationEntry {
+                .map(|(key, correlation)| CorrelationEntry {
                     key: key.clone(),
-                    uid: uid.clone(),
+                    uid: correlation.uid.clone(),
+                    last_seen: correlation.recency.0,
+                    sapient_version: correlation.sapient_version.clone(),
                 })
                 .collect(),
         }
     }
 
+    /// Replaces the current correlations; entries beyond `max_entries` are
+    /// evicted least recently seen first.
     pub fn restore_from_snapshot(&mut self, snapshot: &CorrelatorSnapshot) {
         self.key_to_uid.clear();
         self.uid_to_key.clear();
+        self.recency.clear();
 
         for entry in &snapshot.entries {
-            self.key_to_uid.insert(entry.key.clone(), entry.uid.clone());
-            self.uid_to_key.insert(entry.uid.clone(), entry.key.clone());
+            let recency = self.next_recency(entry.last_seen);
+            self.insert(
+                entry.key.clone(),
+                entry.uid.clone(),
+                recency,
+                entry.sapient_version.clone(),
+            );
+        }
+        self.evict_over_capacity();
+    }
+
+    fn next_recency(&mut self, observed_at: SystemTime) -> Recency {
+        self.touches += 1;
+        (observed_at, self.touches)
+    }
+
+    fn insert(
+        &mut self,
+        key: String,
+        uid: String,
+        recency: Recency,
+        sapient_version: Option<String>,
+    ) {
+        if let Some(replaced) = self.key_to_uid.remove(&key) {
+            self.uid_to_key.remove(&replaced.uid);
+            self.recency.remove(&replaced.recency);
         }
+        self.uid_to_key.insert(uid.clone(), key.clone());
+        self.recency.insert(recency, key.clone());
+        self.key_to_uid.insert(
+            key,
+            Correlation {
+                uid,
+                recency,
+                sapient_version,
+            },
+        );
+        self.metrics.entries = self.key_to_uid.len();
+        self.metrics.peak_entries = self.metrics.peak_entries.max(self.metrics.entries);
+    }
+
+    fn evict_idle(&mut self, observed_at: SystemTime) {
+        let Some(max_idle) = self.config.max_idle else {
+            return;
+        };
+        while let Some((&(last_seen, _), _)) = self.recency.first_key_value() {
+            match observed_at.duration_since(last_seen) {
+                Ok(idle) if idle > max_idle => {
+                    self.evict_oldest();
+                    self.metrics.evicted_idle += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn evict_over_capacity(&mut self) {
+        while self.key_to_uid.len() > self.config.max_entries {
+            self.evict_oldest();
+            self.metrics.evicted_capacity += 1;
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((_, key)) = self.recency.pop_first() {
+            if let Some(evicted) = self.key_to_uid.remove(&key) {
+                self.uid_to_key.remove(&evicted.uid);
+            }
+        }
+        self.metrics.entries = self.key_to_uid.len();
     }
 
     fn allocate_uid(&self, key: &str) -> String {
@@ -177,26 +368,49 @@ fn fnv1a64(input: &[u8]) -> u64 {
 
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, SystemTime};
+
     use crate::{CorrelationInput, Correlator, CorrelatorConfig, CorrelatorError, UidPolicy};
 
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + seconds)
+    }
+
+    fn object(object_id: &str) -> CorrelationInput {
+        CorrelationInput {
+            node_id: "sensor-a".to_owned(),
+            object_id: Some(object_id.to_owned()),
+            detection_id: None,
+            sapient_version: None,
+        }
+    }
+
     #[test]
     fn stable_per_object_reuses_uid_for_same_object_across_detections() {
         let mut correlator =
             Correlator::new(CorrelatorConfig::default()).expect("default config should be valid");
 
         let first_uid = correlator
-            .correlate(&CorrelationInput {
-                node_id: "sensor-a".to_owned(),
-                object_id: Some("obj-001".to_owned()),
-                detection_id: Some("det-100".to_owned()),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: "sensor-a".to_owned(),
+                    object_id: Some("obj-001".to_owned()),
+                    detection_id: Some("det-100".to_owned()),
+                    sapient_version: None,
+                },
+                at(0),
+            )
             .expect("correlation should succeed");
         let second_uid = correlator
-            .correlate(&CorrelationInput {
-                node_id: "sensor-a".to_owned(),
-                object_id: Some("obj-001".to_owned()),
-                detection_id: Some("det-101".to_owned()),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: "sensor-a".to_owned(),
+                    object_id: Some("obj-001".to_owned()),
+                    detection_id: Some("det-101".to_owned()),
+                    sapient_version: None,
+                },
+                at(0),
+            )
             .expect("correlation should succeed");
 
         assert_eq!(first_uid, second_uid);
@@ -207,22 +421,31 @@ mod tests {
         let mut correlator = Correlator::new(CorrelatorConfig {
             uid_policy: UidPolicy::StablePerDetection,
             uid_prefix: "trk".to_owned(),
+            ..CorrelatorConfig::default()
         })
         .expect("config should be valid");
 
         let first_uid = correlator
-            .correlate(&CorrelationInput {
-                node_id: "sensor-a".to_owned(),
-                object_id: Some("obj-001".to_owned()),
-                detection_id: Some("det-100".to_owned()),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: "sensor-a".to_owned(),
+                    object_id: Some("obj-001".to_owned()),
+                    detection_id: Some("det-100".to_owned()),
+                    sapient_version: None,
+                },
+                at(0),
+            )
             .expect("correlation should succeed");
         let second_uid = correlator
-            .correlate(&CorrelationInput {
-                node_id: "sensor-a".to_owned(),
-                object_id: Some("obj-001".to_owned()),
-                detection_id: Some("det-101".to_owned()),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: "sensor-a".to_owned(),
+                    object_id: Some("obj-001".to_owned()),
+                    detection_id: Some("det-101".to_owned()),
+                    sapient_version: None,
+                },
+                at(0),
+            )
             .expect("correlation should succeed");
 
         assert_ne!(first_uid, second_uid);
@@ -234,6 +457,7 @@ mod tests {
             node_id: "sensor-a".to_owned(),
             object_id: Some("obj-001".to_owned()),
             detection_id: Some("det-100".to_owned()),
+            sapient_version: None,
         };
 
         let mut first =
@@ -241,9 +465,11 @@ mod tests {
         let mut second =
             Correlator::new(CorrelatorConfig::default()).expect("config should be valid");
 
-        let first_uid = first.correlate(&input).expect("correlation should succeed");
+        let first_uid = first
+            .correlate(&input, at(0))
+            .expect("correlation should succeed");
         let second_uid = second
-            .correlate(&input)
+            .correlate(&input, at(0))
             .expect("correlation should succeed");
         assert_eq!(first_uid, second_uid);
     }
@@ -256,16 +482,32 @@ mod tests {
             node_id: "sensor-a".to_owned(),
             object_id: Some("obj-001".to_owned()),
             detection_id: Some("det-100".to_owned()),
+            sapient_version: Some("BSI Flex 335 v2.0".to_owned()),
         };
-        let expected_uid = first.correlate(&input).expect("correlation should succeed");
+        let expected_uid = first
+            .correlate(&input, at(0))
+            .expect("correlation should succeed");
+        first
+            .correlate(
+                &CorrelationInput {
+                    sapient_version: None,
+                    ..input.clone()
+                },
+                at(1),
+            )
+            .expect("an undeclared version keeps the recorded one");
         let snapshot = first.snapshot();
+        assert_eq!(
+            snapshot.entries[0].sapient_version.as_deref(),
+            Some("BSI Flex 335 v2.0")
+        );
 
         let mut restored =
             Correlator::new(CorrelatorConfig::default()).expect("config should be valid");
         restored.restore_from_snapshot(&snapshot);
 
         let restored_uid = restored
-            .correlate(&input)
+            .correlate(&input, at(0))
             .expect("restored correlator should keep deterministic uid");
         assert_eq!(restored_uid, expected_uid);
     }
@@ -276,13 +518,119 @@ mod tests {
             Correlator::new(CorrelatorConfig::default()).expect("config should be valid");
 
         let error = correlator
-            .correlate(&CorrelationInput {
-                node_id: "sensor-a".to_owned(),
-                object_id: None,
-                detection_id: Some("det-100".to_owned()),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: "sensor-a".to_owned(),
+                    object_id: None,
+                    detection_id: Some("det-100".to_owned()),
+                    sapient_version: None,
+                },
+                at(0),
+            )
             .expect_err("stable_per_object should require object id");
 
         assert_eq!(error, CorrelatorError::MissingObjectId);
     }
+
+    #[test]
+    fn capacity_evicts_least_recently_seen_and_remaps_deterministically() {
+        let mut correlator = Correlator::new(CorrelatorConfig {
+            max_entries: 2,
+            max_idle: None,
+            ..CorrelatorConfig::default()
+        })
+        .expect("config should be valid");
+
+        let first = correlator
+            .correlate(&object("obj-1"), at(0))
+            .expect("obj-1");
+        correlator
+            .correlate(&object("obj-2"), at(1))
+            .expect("obj-2");
+        correlator
+            .correlate(&object("obj-1"), at(2))
+            .expect("refresh obj-1");
+        correlator
+            .correlate(&object("obj-3"), at(3))
+            .expect("obj-3");
+
+        let keys: Vec<String> = correlator
+            .snapshot()
+            .entries
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect();
+        assert_eq!(
+            keys,
+            ["node=sensor-a;object=obj-1", "node=sensor-a;object=obj-3"]
+        );
+        let metrics = correlator.metrics();
+        assert_eq!(metrics.entries, 2);
+        assert_eq!(metrics.peak_entries, 3);
+        assert_eq!(metrics.evicted_capacity, 1);
+
+        let mut fresh = Correlator::new(CorrelatorConfig::default()).expect("config");
+        let expected = fresh.correlate(&object("obj-2"), at(0)).expect("fresh");
+        assert_eq!(
+            correlator
+                .correlate(&object("obj-2"), at(4))
+                .expect("reappear"),
+            expected
+        );
+        assert_eq!(
+            correlator
+                .correlate(&object("obj-1"), at(5))
+                .expect("obj-1"),
+            first
+        );
+    }
+
+    #[test]
+    fn idle_correlations_expire_and_reappear_with_same_uid() {
+        let mut correlator = Correlator::new(CorrelatorConfig {
+            max_idle: Some(Duration::from_secs(60)),
+            ..CorrelatorConfig::default()
+        })
+        .expect("config should be valid");
+
+        let transient = correlator
+            .correlate(&object("transient"), at(0))
+            .expect("transient");
+        correlator
+            .correlate(&object("steady"), at(50))
+            .expect("steady");
+        correlator
+            .correlate(&object("steady"), at(100))
+            .expect("steady");
+
+        assert_eq!(correlator.len(), 1);
+        assert_eq!(correlator.metrics().evicted_idle, 1);
+        assert_eq!(
+            correlator
+                .correlate(&object("transient"), at(120))
+                .expect("reappear"),
+            transient
+        );
+        assert_eq!(correlator.metrics().evicted(), 1);
+    }
+
+    #[test]
+    fn rejects_unbounded_or_degenerate_eviction_config() {
+        let zero_entries = CorrelatorConfig {
+            max_entries: 0,
+            ..CorrelatorConfig::default()
+        };
+        assert_eq!(
+            Correlator::new(zero_entries).err(),
+            Some(CorrelatorError::ZeroMaxEntries)
+        );
+        let zero_idle = CorrelatorConfig {
+            max_idle: Some(Duration::ZERO),
+            ..CorrelatorConfig::default()
+        };
+        assert_eq!(
+            Correlator::new(zero_idle).err(),
+            Some(CorrelatorError::ZeroMaxIdle)
+        );
+    }
 }
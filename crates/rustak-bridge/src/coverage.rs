@@ -0,0 +1,440 @@
+//! Sensor location and field-of-view CoT rendering for SAPIENT status
+//! reports, so TAK users can see where sensors are and what they cover.
+
+#[cfg(feature = "geo")]
+use rustak_core::{CotEvent, DetailNode, Position, TimestampUtc};
+#[cfg(feature = "geo")]
+use rustak_geo::{destination_point, GeoError};
+#[cfg(feature = "geo")]
+use rustak_limits::{CodedError, ErrorCode};
+#[cfg(feature = "geo")]
+use thiserror::Error;
+
+use crate::BridgeConfigError;
+#[cfg(feature = "geo")]
+use crate::ResolvedCotTimes;
+
+/// CoT type for TAK freeform drawings; used for the coverage polygon.
+pub const FIELD_OF_VIEW_COT_TYPE: &str = "u-d-f";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverageStyle {
+    /// ARGB, as TAK clients store drawing colours.
+    pub stroke_color: u32,
+    /// ARGB; the alpha channel controls fill transparency.
+    pub fill_color: u32,
+    pub stroke_weight: u8,
+}
+
+impl Default for CoverageStyle {
+    fn default() -> Self {
+        Self {
+            stroke_color: 0xFF_FF_A5_00,
+            fill_color: 0x40_FF_A5_00,
+            stroke_weight: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensorCoverageConfig {
+    pub emit_sensor_location: bool,
+    pub emit_field_of_view: bool,
+    pub sensor_cot_type: String,
+    pub uid_prefix: String,
+    /// Straight segments used to approximate a sector's arc.
+    pub arc_segments: u16,
+    pub style: CoverageStyle,
+}
+
+impl Default for SensorCoverageConfig {
+    fn default() -> Self {
+        Self {
+            emit_sensor_location: false,
+            emit_field_of_view: false,
+            sensor_cot_type: "a-f-G-E-S".to_owned(),
+            uid_prefix: "sapient-sensor".to_owned(),
+            arc_segments: 16,
+            style: CoverageStyle::default(),
+        }
+    }
+}
+
+impl SensorCoverageConfig {
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.emit_sensor_location || self.emit_field_of_view
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), BridgeConfigError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.uid_prefix.trim().is_empty() {
+            return Err(BridgeConfigError::EmptySensorCoverageUidPrefix);
+        }
+        if self.emit_sensor_location && self.sensor_cot_type.trim().is_empty() {
+            return Err(BridgeConfigError::EmptySensorCotType);
+        }
+        if self.emit_field_of_view && self.arc_segments == 0 {
+            return Err(BridgeConfigError::ZeroSensorCoverageArcSegments);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "geo")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldOfView {
+    /// Range/bearing cone projected onto the ground.
+    Sector {
+        azimuth_degrees: f64,
+        horizontal_extent_degrees: f64,
+        range_meters: f64,
+    },
+    /// Explicit coverage outline.
+    Polygon(Vec<Position>),
+}
+
+/// Location and coverage carried by a SAPIENT status report.
+#[cfg(feature = "geo")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorStatus {
+    pub node_id: String,
+    pub location: Position,
+    pub field_of_view: Option<FieldOfView>,
+}
+
+#[cfg(feature = "geo")]
+#[derive(Debug, Error, PartialEq)]
+pub enum CoverageError {
+    #[error("sensor node_id must not be empty")]
+    EmptyNodeId,
+
+    #[error("sector range_meters must be finite and > 0, got {range_meters}")]
+    InvalidSectorRange { range_meters: f64 },
+
+    #[error("sector horizontal_extent_degrees must be in (0, 360], got {extent}")]
+    InvalidSectorExtent { extent: f64 },
+
+    #[error("field-of-view polygon needs at least 3 vertices, got {vertices}")]
+    TooFewPolygonVertices { vertices: usize },
+
+    #[error(transparent)]
+    Geo(#[from] GeoError),
+}
+
+#[cfg(feature = "geo")]
+impl CodedError for CoverageError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyNodeId => ErrorCode::new("BRIDGE", 601),
+            Self::InvalidSectorRange { .. } => ErrorCode::new("BRIDGE", 602),
+            Self::InvalidSectorExtent { .. } => ErrorCode::new("BRIDGE", 603),
+            Self::TooFewPolygonVertices { .. } => ErrorCode::new("BRIDGE", 604),
+            Self::Geo(error) => error.code(),
+        }
+    }
+}
+
+/// Renders the sensor-location and field-of-view CoT events enabled in
+/// `config`. Returns no events when coverage rendering is disabled.
+#[cfg(feature = "geo")]
+pub fn render_sensor_coverage(
+    status: &SensorStatus,
+    config: &SensorCoverageConfig,
+    times: &ResolvedCotTimes,
+) -> Result<Vec<String>, CoverageError> {
+    if status.node_id.trim().is_empty() {
+        return Err(CoverageError::EmptyNodeId);
+    }
+
+    let sensor_uid = format!("{}-{}", config.uid_prefix, status.node_id);
+    let mut events = Vec::new();
+
+    if config.emit_sensor_location {
+        let contact = DetailNode::new("contact").with_attribute("callsign", &status.node_id);
+        events.push(render_event(
+            &sensor_uid,
+            &config.sensor_cot_type,
+            &status.location,
+            times,
+            vec![contact],
+        ));
+    }
+
+    if config.emit_field_of_view {
+        if let Some(field_of_view) = &status.field_of_view {
+            let outline = field_of_view_outline(&status.location, field_of_view, config)?;
+            let style = &config.style;
+            let value =
+                |name: &str, value: String| DetailNode::new(name).with_attribute("value", value);
+            let mut detail: Vec<DetailNode> = outline
+                .iter()
+                .map(|vertex| {
+                    DetailNode::new("link").with_attribute(
+                        "point",
+                        format!("{},{}", vertex.latitude(), vertex.longitude()),
+                    )
+                })
+                .collect();
+            detail.extend([
+                value("strokeColor", argb_to_tak(style.stroke_color).to_string()),
+                value("strokeWeight", style.stroke_weight.to_string()),
+                value("fillColor", argb_to_tak(style.fill_color).to_string()),
+                DetailNode::new("contact")
+                    .with_attribute("callsign", format!("{} FOV", status.node_id)),
+                value("labels_on", "false".to_owned()),
+            ]);
+            events.push(render_event(
+                &format!("{sensor_uid}-fov"),
+                FIELD_OF_VIEW_COT_TYPE,
+                &status.location,
+                times,
+                detail,
+            ));
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(feature = "geo")]
+fn field_of_view_outline(
+    location: &Position,
+    field_of_view: &FieldOfView,
+    config: &SensorCoverageConfig,
+) -> Result<Vec<Position>, CoverageError> {
+    match field_of_view {
+        FieldOfView::Sector {
+            azimuth_degrees,
+            horizontal_extent_degrees,
+            range_meters,
+        } => {
+            if !range_meters.is_finite() || *range_meters <= 0.0 {
+                return Err(CoverageError::InvalidSectorRange {
+                    range_meters: *range_meters,
+                });
+            }
+            let extent = *horizontal_extent_degrees;
+            if !extent.is_finite() || extent <= 0.0 || extent > 360.0 {
+                return Err(CoverageError::InvalidSectorExtent { extent });
+            }
+
+            let full_circle = extent >= 360.0;
+            let segments = usize::from(config.arc_segments.max(1));
+            let start = azimuth_degrees - extent / 2.0;
+            let step = extent / segments as f64;
+
+            let mut outline = Vec::with_capacity(segments + 3);
+            if !full_circle {
+                outline.push(location.clone());
+            }
+            for index in 0..=segments {
+                outline.push(destination_point(
+                    location,
+                    start + step * index as f64,
+                    *range_meters,
+                )?);
+            }
+            if !full_circle {
+                outline.push(location.clone());
+            }
+            Ok(outline)
+        }
+        FieldOfView::Polygon(vertices) => {
+            if vertices.len() < 3 {
+                return Err(CoverageError::TooFewPolygonVertices {
+                    vertices: vertices.len(),
+                });
+            }
+            let mut outline = vertices.clone();
+            if outline.first() != outline.last() {
+                outline.push(vertices[0].clone());
+            }
+            Ok(outline)
+        }
+    }
+}
+
+#[cfg(feature = "geo")]
+fn render_event(
+    uid: &str,
+    cot_type: &str,
+    point: &Position,
+    times: &ResolvedCotTimes,
+    detail: Vec<DetailNode>,
+) -> String {
+    let mut event = CotEvent::new(
+        uid,
+        cot_type,
+        TimestampUtc::from_system_time(times.time),
+        TimestampUtc::from_system_time(times.stale),
+        point.clone(),
+    );
+    event.start = TimestampUtc::from_system_time(times.start);
+    event.how = Some("m-g".to_owned());
+    event.detail = detail;
+    event.to_xml()
+}
+
+/// TAK stores colours as signed 32-bit ARGB integers.
+#[cfg(feature = "geo")]
+fn argb_to_tak(argb: u32) -> i32 {
+    i32::from_ne_bytes(argb.to_ne_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BridgeConfig, BridgeConfigError, SensorCoverageConfig};
+
+    #[test]
+    fn disabled_coverage_skips_validation() {
+        let config = BridgeConfig {
+            sensor_coverage: SensorCoverageConfig {
+                uid_prefix: String::new(),
+                ..SensorCoverageConfig::default()
+            },
+            ..BridgeConfig::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn enabled_field_of_view_requires_arc_segments() {
+        let config = BridgeConfig {
+            sensor_coverage: SensorCoverageConfig {
+                emit_field_of_view: true,
+                arc_segments: 0,
+                ..SensorCoverageConfig::default()
+            },
+            ..BridgeConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(BridgeConfigError::ZeroSensorCoverageArcSegments)
+        );
+    }
+
+    #[cfg(feature = "geo")]
+    mod rendering {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use rustak_core::{CotEvent, Position};
+
+        use crate::{
+            render_sensor_coverage, CoverageError, FieldOfView, ResolvedCotTimes,
+            SensorCoverageConfig, SensorStatus,
+        };
+
+        fn times() -> ResolvedCotTimes {
+            let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+            ResolvedCotTimes {
+                time,
+                start: time,
+                stale: time + Duration::from_secs(60),
+            }
+        }
+
+        fn enabled() -> SensorCoverageConfig {
+            SensorCoverageConfig {
+                emit_sensor_location: true,
+                emit_field_of_view: true,
+                arc_segments: 4,
+                ..SensorCoverageConfig::default()
+            }
+        }
+
+        fn status(field_of_view: Option<FieldOfView>) -> SensorStatus {
+            SensorStatus {
+                node_id: "radar-1".to_owned(),
+                location: Position::new(51.0, -1.0).expect("location"),
+                field_of_view,
+            }
+        }
+
+        #[test]
+        fn renders_location_and_closed_sector_polygon() {
+            let events = render_sensor_coverage(
+                &status(Some(FieldOfView::Sector {
+                    azimuth_degrees: 90.0,
+                    horizontal_extent_degrees: 60.0,
+                    range_meters: 2_000.0,
+                })),
+                &enabled(),
+                &times(),
+            )
+            .expect("render");
+
+            assert_eq!(events.len(), 2);
+            assert!(events[0].contains("uid=\"sapient-sensor-radar-1\" type=\"a-f-G-E-S\""));
+            let fov = &events[1];
+            assert!(fov.contains("uid=\"sapient-sensor-radar-1-fov\" type=\"u-d-f\""));
+            // Apex, five arc points, apex again.
+            assert_eq!(fov.matches("<link point=").count(), 7);
+            assert!(fov.contains("<strokeColor value=\"-23296\"/>"));
+            assert!(fov.contains("<fillColor value=\"1090495744\"/>"));
+        }
+
+        #[test]
+        fn node_ids_are_escaped_in_rendered_events() {
+            let status = SensorStatus {
+                node_id: "radar\"&<1".to_owned(),
+                ..status(Some(FieldOfView::Polygon(vec![
+                    Position::new(51.0, -1.0).expect("a"),
+                    Position::new(51.1, -1.0).expect("b"),
+                    Position::new(51.1, -0.9).expect("c"),
+                ])))
+            };
+            let events = render_sensor_coverage(&status, &enabled(), &times()).expect("render");
+            let limits = rustak_limits::Limits::conservative_defaults();
+            let location = CotEvent::from_xml(&events[0], &limits).expect("location event");
+            assert_eq!(location.uid, "sapient-sensor-radar\"&<1");
+            let fov = CotEvent::from_xml(&events[1], &limits).expect("fov event");
+            assert_eq!(
+                fov.detail_element("contact")
+                    .and_then(|contact| contact.attribute("callsign")),
+                Some("radar\"&<1 FOV")
+            );
+            assert_eq!(fov.point, status.location);
+        }
+
+        #[test]
+        fn only_enabled_graphics_are_emitted() {
+            let config = SensorCoverageConfig {
+                emit_sensor_location: false,
+                ..enabled()
+            };
+            let events = render_sensor_coverage(
+                &status(Some(FieldOfView::Polygon(vec![
+                    Position::new(51.0, -1.0).expect("a"),
+                    Position::new(51.1, -1.0).expect("b"),
+                    Position::new(51.1, -0.9).expect("c"),
+                ]))),
+                &config,
+                &times(),
+            )
+            .expect("render");
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].matches("<link point=").count(), 4);
+
+            let events = render_sensor_coverage(&status(None), &config, &times()).expect("render");
+            assert!(events.is_empty());
+        }
+
+        #[test]
+        fn rejects_degenerate_field_of_view() {
+            let error = render_sensor_coverage(
+                &status(Some(FieldOfView::Sector {
+                    azimuth_degrees: 0.0,
+                    horizontal_extent_degrees: 0.0,
+                    range_meters: 100.0,
+                })),
+                &enabled(),
+                &times(),
+            )
+            .expect_err("zero extent");
+            assert_eq!(error, CoverageError::InvalidSectorExtent { extent: 0.0 });
+        }
+    }
+}
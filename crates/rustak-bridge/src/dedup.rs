@@ -2,6 +2,7 @@ use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
 use std::time::{Duration, SystemTime};
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -46,6 +47,16 @@ pub enum DedupConfigError {
     },
 }
 
+impl CodedError for DedupConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroWindow => ErrorCode::new("BRIDGE", 101),
+            Self::ZeroMaxKeys => ErrorCode::new("BRIDGE", 102),
+            Self::MaxKeysExceedLimits { .. } => ErrorCode::new("BRIDGE", 103),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DedupDecision {
     Accepted,
@@ -1,29 +1,55 @@
 use std::time::Duration;
 
-use rustak_limits::{Limits, LimitsError};
+use rustak_limits::{CodedError, ErrorCode, Limits, LimitsError};
 use thiserror::Error;
 
+pub mod cardinality;
 pub mod correlator;
+pub mod coverage;
 pub mod dedup;
 pub mod mapping;
+pub mod normalize;
+pub mod pipeline;
+pub mod quality;
 pub mod time_policy;
 
-pub use correlator::{CorrelationInput, Correlator, CorrelatorConfig, CorrelatorError, UidPolicy};
+pub use cardinality::{CardinalityDecision, TrackCardinalityConfig, TrackCardinalityGuard};
+pub use correlator::{
+    CorrelationEntry, CorrelationInput, Correlator, CorrelatorConfig, CorrelatorError,
+    CorrelatorMetrics, CorrelatorSnapshot, UidPolicy,
+};
+#[cfg(feature = "geo")]
+pub use coverage::{render_sensor_coverage, CoverageError, FieldOfView, SensorStatus};
+pub use coverage::{CoverageStyle, SensorCoverageConfig, FIELD_OF_VIEW_COT_TYPE};
 pub use dedup::{DedupConfig, DedupConfigError, DedupDecision, Deduplicator};
 pub use mapping::{BehaviourMapping, MappingSeverity, MappingTables, MappingValidationError};
 #[cfg(feature = "geo")]
 pub use mapping::{GeoMappingError, GeoProximityPolicy};
+pub use normalize::{
+    AngleUnit, BearingReference, DatumOffset, NormalizationConfig, NormalizationError,
+    NormalizedReading, RangeUnit, SensorLocation, SensorNormalization, SensorReading,
+};
+pub use pipeline::{DetectionPipeline, PipelineError, PipelineMetrics};
+pub use quality::{TrackQuality, TrackQualityConfig, TRACK_QUALITY_DETAIL};
 pub use time_policy::{ResolvedCotTimes, TimePolicy, TimePolicyMode};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BridgeConfig {
     pub limits: Limits,
     pub cot_stale_seconds: u32,
     pub max_clock_skew_seconds: u32,
     pub time_policy: TimePolicyMode,
     pub dedup: DedupConfig,
+    pub correlator: CorrelatorConfig,
     pub emitter: EmitterConfig,
     pub validation: BridgeValidationConfig,
+    pub sensor_coverage: SensorCoverageConfig,
+    pub normalization: NormalizationConfig,
+    pub track_quality: TrackQualityConfig,
+    /// Caps on distinct active tracks during sensor storms.
+    pub cardinality: TrackCardinalityConfig,
+    /// Classification and behaviour tables applied by [`DetectionPipeline`].
+    pub mappings: MappingTables,
 }
 
 impl Default for BridgeConfig {
@@ -38,12 +64,18 @@ impl Default for BridgeConfig {
                 window: Duration::from_millis(500),
                 max_keys: limits.max_queue_messages,
             },
+            correlator: CorrelatorConfig::default(),
             emitter: EmitterConfig {
                 max_updates_per_second: 20,
                 min_separation: Duration::from_millis(100),
                 max_pending_events: limits.max_queue_messages,
             },
             validation: BridgeValidationConfig::default(),
+            sensor_coverage: SensorCoverageConfig::default(),
+            normalization: NormalizationConfig::default(),
+            track_quality: TrackQualityConfig::default(),
+            cardinality: TrackCardinalityConfig::default(),
+            mappings: MappingTables::default(),
         }
     }
 }
@@ -59,6 +91,7 @@ impl BridgeConfig {
             return Err(BridgeConfigError::ZeroMaxClockSkewSeconds);
         }
         self.dedup.validate(self.limits.max_queue_messages)?;
+        self.correlator.validate()?;
         if self.emitter.max_updates_per_second == 0 {
             return Err(BridgeConfigError::ZeroEmitterRateLimit);
         }
@@ -75,6 +108,11 @@ impl BridgeConfig {
             });
         }
         self.validation.validate()?;
+        self.sensor_coverage.validate()?;
+        self.normalization
+            .validate(self.validation.strict_startup)?;
+        self.track_quality.validate()?;
+        self.cardinality.validate()?;
 
         Ok(())
     }
@@ -152,6 +190,9 @@ pub enum BridgeConfigError {
     #[error(transparent)]
     InvalidDedup(#[from] DedupConfigError),
 
+    #[error(transparent)]
+    InvalidCorrelator(#[from] CorrelatorError),
+
     #[error(transparent)]
     InvalidMappings(#[from] MappingValidationError),
 
@@ -186,6 +227,82 @@ pub enum BridgeConfigError {
 
     #[error("validation.behaviour_mapping_entries must be > 0 in strict startup mode")]
     ZeroBehaviourMappingCoverage,
+
+    #[error("sensor_coverage.uid_prefix must not be empty when coverage is enabled")]
+    EmptySensorCoverageUidPrefix,
+
+    #[error("sensor_coverage.sensor_cot_type must not be empty when sensor location is emitted")]
+    EmptySensorCotType,
+
+    #[error("sensor_coverage.arc_segments must be > 0 when field of view is emitted")]
+    ZeroSensorCoverageArcSegments,
+
+    #[error("normalization.sensors contains an empty sensor id")]
+    EmptyNormalizationSensorId,
+
+    #[error("normalization.sensors is missing required sensor '{sensor}' in strict startup mode")]
+    MissingSensorNormalization { sensor: String },
+
+    #[error(
+        "normalization for '{sensor}': magnetic declination must be finite and within [-180, 180]"
+    )]
+    InvalidMagneticDeclination { sensor: String },
+
+    #[error("normalization for '{sensor}': datum offset must be finite and within latitude/longitude bounds")]
+    InvalidDatumOffset { sensor: String },
+
+    #[error("track_quality.half_life must be > 0")]
+    ZeroTrackQualityHalfLife,
+
+    #[error("track_quality ce/le growth must be finite and >= 0")]
+    InvalidTrackQualityGrowth,
+
+    #[error("cardinality.active_window and cardinality.coalesce_interval must be > 0")]
+    ZeroTrackCardinalityWindow,
+
+    #[error("cardinality.sample_one_in must be > 0")]
+    ZeroTrackCardinalitySampleRate,
+
+    #[error(
+        "cardinality thresholds must satisfy coalesce_above <= sample_above <= max_active_tracks"
+    )]
+    TrackCardinalityThresholdsOutOfOrder,
+
+    #[error("cardinality.high_priority_type_prefixes contains an empty prefix")]
+    EmptyHighPriorityTypePrefix,
+}
+
+impl CodedError for BridgeConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidLimits(error) => error.code(),
+            Self::InvalidDedup(error) => error.code(),
+            Self::InvalidCorrelator(error) => error.code(),
+            Self::InvalidMappings(error) => error.code(),
+            Self::ZeroCotStaleSeconds => ErrorCode::new("BRIDGE", 1),
+            Self::ZeroMaxClockSkewSeconds => ErrorCode::new("BRIDGE", 2),
+            Self::ZeroEmitterRateLimit => ErrorCode::new("BRIDGE", 3),
+            Self::ZeroEmitterMinSeparation => ErrorCode::new("BRIDGE", 4),
+            Self::ZeroEmitterPendingEvents => ErrorCode::new("BRIDGE", 5),
+            Self::EmitterPendingEventsExceedLimits { .. } => ErrorCode::new("BRIDGE", 6),
+            Self::EmptyUnknownClassFallback => ErrorCode::new("BRIDGE", 7),
+            Self::ZeroClassificationMappingCoverage => ErrorCode::new("BRIDGE", 8),
+            Self::ZeroBehaviourMappingCoverage => ErrorCode::new("BRIDGE", 9),
+            Self::EmptySensorCoverageUidPrefix => ErrorCode::new("BRIDGE", 10),
+            Self::EmptySensorCotType => ErrorCode::new("BRIDGE", 11),
+            Self::ZeroSensorCoverageArcSegments => ErrorCode::new("BRIDGE", 12),
+            Self::EmptyNormalizationSensorId => ErrorCode::new("BRIDGE", 13),
+            Self::MissingSensorNormalization { .. } => ErrorCode::new("BRIDGE", 14),
+            Self::InvalidMagneticDeclination { .. } => ErrorCode::new("BRIDGE", 15),
+            Self::InvalidDatumOffset { .. } => ErrorCode::new("BRIDGE", 16),
+            Self::ZeroTrackQualityHalfLife => ErrorCode::new("BRIDGE", 17),
+            Self::InvalidTrackQualityGrowth => ErrorCode::new("BRIDGE", 18),
+            Self::ZeroTrackCardinalityWindow => ErrorCode::new("BRIDGE", 19),
+            Self::ZeroTrackCardinalitySampleRate => ErrorCode::new("BRIDGE", 20),
+            Self::TrackCardinalityThresholdsOutOfOrder => ErrorCode::new("BRIDGE", 21),
+            Self::EmptyHighPriorityTypePrefix => ErrorCode::new("BRIDGE", 22),
+        }
+    }
 }
 
 #[cfg(test)]
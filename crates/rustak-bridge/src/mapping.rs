@@ -2,8 +2,10 @@ use std::collections::BTreeMap;
 
 #[cfg(feature = "geo")]
 use rustak_core::Position;
+use rustak_core::{CotType, CotTypeError};
 #[cfg(feature = "geo")]
 use rustak_geo::haversine_distance_meters;
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 use crate::BridgeValidationConfig;
@@ -15,6 +17,17 @@ pub enum MappingSeverity {
     Critical,
 }
 
+impl MappingSeverity {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Critical => "critical",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BehaviourMapping {
     pub detail_key: String,
@@ -60,6 +73,16 @@ pub enum GeoMappingError {
     EmptyNearOverrideCotType,
 }
 
+#[cfg(feature = "geo")]
+impl CodedError for GeoMappingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidNearThresholdMeters { .. } => ErrorCode::new("BRIDGE", 401),
+            Self::EmptyNearOverrideCotType => ErrorCode::new("BRIDGE", 402),
+        }
+    }
+}
+
 impl MappingTables {
     pub fn validate_with_policy(
         &self,
@@ -96,6 +119,12 @@ impl MappingTables {
                     classification: classification.clone(),
                 });
             }
+            if let Err(source) = CotType::parse(cot_type) {
+                return Err(MappingValidationError::InvalidCotType {
+                    classification: classification.clone(),
+                    source,
+                });
+            }
         }
 
         for (behaviour, mapping) in &self.behaviour_to_detail {
@@ -162,6 +191,12 @@ pub enum MappingValidationError {
     #[error("classification mapping for '{classification}' must provide a non-empty CoT type")]
     EmptyCotType { classification: String },
 
+    #[error("classification mapping for '{classification}' must be an atom CoT type: {source}")]
+    InvalidCotType {
+        classification: String,
+        source: CotTypeError,
+    },
+
     #[error("behaviour mapping contains an empty behaviour key")]
     EmptyBehaviourKey,
 
@@ -169,6 +204,21 @@ pub enum MappingValidationError {
     EmptyBehaviourDetailKey { behaviour: String },
 }
 
+impl CodedError for MappingValidationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyUnknownClassFallback => ErrorCode::new("BRIDGE", 301),
+            Self::InsufficientClassificationCoverage { .. } => ErrorCode::new("BRIDGE", 302),
+            Self::InsufficientBehaviourCoverage { .. } => ErrorCode::new("BRIDGE", 303),
+            Self::EmptyClassificationKey => ErrorCode::new("BRIDGE", 304),
+            Self::EmptyCotType { .. } => ErrorCode::new("BRIDGE", 305),
+            Self::EmptyBehaviourKey => ErrorCode::new("BRIDGE", 306),
+            Self::EmptyBehaviourDetailKey { .. } => ErrorCode::new("BRIDGE", 307),
+            Self::InvalidCotType { .. } => ErrorCode::new("BRIDGE", 308),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -263,6 +313,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn strict_mode_rejects_non_atom_cot_types() {
+        let mut tables = valid_tables();
+        tables.class_to_cot = [("UAS/Multirotor".to_owned(), "b-t-f".to_owned())]
+            .into_iter()
+            .collect();
+
+        let error = tables
+            .validate_with_policy(&strict_policy())
+            .expect_err("strict startup should reject non-atom CoT types");
+        assert!(matches!(
+            error,
+            MappingValidationError::InvalidCotType { ref classification, .. }
+                if classification == "UAS/Multirotor"
+        ));
+    }
+
     #[test]
     fn non_strict_mode_allows_incomplete_tables() {
         let tables = MappingTables::default();
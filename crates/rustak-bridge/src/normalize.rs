@@ -0,0 +1,446 @@
+//! Per-sensor unit and datum normalisation for SAPIENT readings.
+//!
+//! Sensors report ranges, bearings and locations in their own units and
+//! local datums. Readings pass through [`NormalizationConfig::normalize`]
+//! before mapping so everything downstream sees metres, degrees true and
+//! WGS84 coordinates.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use rustak_limits::{CodedError, ErrorCode};
+use thiserror::Error;
+
+use crate::BridgeConfigError;
+
+/// Label used in validation errors for the fallback conversion.
+pub const FALLBACK_SENSOR_LABEL: &str = "<fallback>";
+
+const METERS_PER_FOOT: f64 = 0.3048;
+const METERS_PER_YARD: f64 = 0.9144;
+const METERS_PER_KILOMETER: f64 = 1_000.0;
+const METERS_PER_STATUTE_MILE: f64 = 1_609.344;
+const METERS_PER_NAUTICAL_MILE: f64 = 1_852.0;
+/// NATO mils: 6400 per full circle.
+const MILS_PER_CIRCLE: f64 = 6_400.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RangeUnit {
+    #[default]
+    Meters,
+    Kilometers,
+    Feet,
+    Yards,
+    StatuteMiles,
+    NauticalMiles,
+}
+
+impl RangeUnit {
+    #[must_use]
+    pub fn to_meters(self, value: f64) -> f64 {
+        match self {
+            Self::Meters => value,
+            Self::Kilometers => value * METERS_PER_KILOMETER,
+            Self::Feet => value * METERS_PER_FOOT,
+            Self::Yards => value * METERS_PER_YARD,
+            Self::StatuteMiles => value * METERS_PER_STATUTE_MILE,
+            Self::NauticalMiles => value * METERS_PER_NAUTICAL_MILE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AngleUnit {
+    #[default]
+    Degrees,
+    Radians,
+    Mils,
+}
+
+impl AngleUnit {
+    #[must_use]
+    pub fn to_degrees(self, value: f64) -> f64 {
+        match self {
+            Self::Degrees => value,
+            Self::Radians => value * 180.0 / PI,
+            Self::Mils => value * 360.0 / MILS_PER_CIRCLE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BearingReference {
+    #[default]
+    True,
+    /// Bearings relative to magnetic north. Declination is east-positive, so
+    /// true = magnetic + declination.
+    Magnetic { declination_degrees: f64 },
+}
+
+/// Fixed shift from a sensor's local datum to WGS84, added to every
+/// reported location.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DatumOffset {
+    pub latitude_degrees: f64,
+    pub longitude_degrees: f64,
+    pub altitude_meters: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SensorNormalization {
+    pub range_unit: RangeUnit,
+    pub bearing_unit: AngleUnit,
+    pub bearing_reference: BearingReference,
+    pub datum_offset: DatumOffset,
+}
+
+impl SensorNormalization {
+    fn validate(&self, sensor: &str) -> Result<(), BridgeConfigError> {
+        if let BearingReference::Magnetic {
+            declination_degrees,
+        } = self.bearing_reference
+        {
+            if !declination_degrees.is_finite() || declination_degrees.abs() > 180.0 {
+                return Err(BridgeConfigError::InvalidMagneticDeclination {
+                    sensor: sensor.to_owned(),
+                });
+            }
+        }
+        let offset = &self.datum_offset;
+        let finite = offset.latitude_degrees.is_finite()
+            && offset.longitude_degrees.is_finite()
+            && offset.altitude_meters.is_finite();
+        if !finite || offset.latitude_degrees.abs() > 90.0 || offset.longitude_degrees.abs() > 180.0
+        {
+            return Err(BridgeConfigError::InvalidDatumOffset {
+                sensor: sensor.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizationConfig {
+    /// Conversions keyed by SAPIENT node_id.
+    pub sensors: BTreeMap<String, SensorNormalization>,
+    /// Sensors that must have an explicit entry in `sensors` when
+    /// `validation.strict_startup` is set.
+    pub required_sensors: Vec<String>,
+    /// Applied to sensors without an entry; `None` rejects their readings.
+    pub fallback: Option<SensorNormalization>,
+}
+
+impl Default for NormalizationConfig {
+    fn default() -> Self {
+        Self {
+            sensors: BTreeMap::new(),
+            required_sensors: Vec::new(),
+            fallback: Some(SensorNormalization::default()),
+        }
+    }
+}
+
+impl NormalizationConfig {
+    pub(crate) fn validate(&self, strict_startup: bool) -> Result<(), BridgeConfigError> {
+        for (sensor, normalization) in &self.sensors {
+            if sensor.trim().is_empty() {
+                return Err(BridgeConfigError::EmptyNormalizationSensorId);
+            }
+            normalization.validate(sensor)?;
+        }
+        if let Some(fallback) = &self.fallback {
+            fallback.validate(FALLBACK_SENSOR_LABEL)?;
+        }
+        if strict_startup {
+            if let Some(sensor) = self
+                .required_sensors
+                .iter()
+                .find(|sensor| !self.sensors.contains_key(sensor.as_str()))
+            {
+                return Err(BridgeConfigError::MissingSensorNormalization {
+                    sensor: sensor.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn for_sensor(&self, node_id: &str) -> Option<&SensorNormalization> {
+        self.sensors.get(node_id).or(self.fallback.as_ref())
+    }
+
+    /// Converts `reading` from `node_id`'s configured units and datum.
+    pub fn normalize(
+        &self,
+        node_id: &str,
+        reading: &SensorReading,
+    ) -> Result<NormalizedReading, NormalizationError> {
+        let normalization =
+            self.for_sensor(node_id)
+                .ok_or_else(|| NormalizationError::UnconfiguredSensor {
+                    sensor: node_id.to_owned(),
+                })?;
+
+        let range_meters = reading
+            .range
+            .map(|range| {
+                let meters = normalization.range_unit.to_meters(finite("range", range)?);
+                if meters < 0.0 {
+                    return Err(NormalizationError::NegativeRange { range });
+                }
+                Ok(meters)
+            })
+            .transpose()?;
+
+        let bearing_true_degrees = reading
+            .bearing
+            .map(|bearing| {
+                let degrees = normalization
+                    .bearing_unit
+                    .to_degrees(finite("bearing", bearing)?);
+                let declination = match normalization.bearing_reference {
+                    BearingReference::True => 0.0,
+                    BearingReference::Magnetic {
+                        declination_degrees,
+                    } => declination_degrees,
+                };
+                Ok((degrees + declination).rem_euclid(360.0))
+            })
+            .transpose()?;
+
+        let location = reading
+            .location
+            .map(|location| {
+                let offset = &normalization.datum_offset;
+                let latitude = finite("latitude", location.latitude)? + offset.latitude_degrees;
+                if !(-90.0..=90.0).contains(&latitude) {
+                    return Err(NormalizationError::LatitudeOutOfRange { latitude });
+                }
+                let longitude = finite("longitude", location.longitude)? + offset.longitude_degrees;
+                let altitude_meters = location
+                    .altitude_meters
+                    .map(|altitude| Ok(finite("altitude", altitude)? + offset.altitude_meters))
+                    .transpose()?;
+                Ok(SensorLocation {
+                    latitude,
+                    longitude: wrap_longitude(longitude),
+                    altitude_meters,
+                })
+            })
+            .transpose()?;
+
+        Ok(NormalizedReading {
+            range_meters,
+            bearing_true_degrees,
+            location,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude_meters: Option<f64>,
+}
+
+/// A reading as reported by the sensor, in its configured units and datum.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SensorReading {
+    pub range: Option<f64>,
+    pub bearing: Option<f64>,
+    pub location: Option<SensorLocation>,
+}
+
+/// A reading in metres, degrees true in `[0, 360)` and WGS84.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NormalizedReading {
+    pub range_meters: Option<f64>,
+    pub bearing_true_degrees: Option<f64>,
+    pub location: Option<SensorLocation>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum NormalizationError {
+    #[error("sensor '{sensor}' has no normalization entry and no fallback is configured")]
+    UnconfiguredSensor { sensor: String },
+
+    #[error("{field} must be finite, got {value}")]
+    NonFinite { field: &'static str, value: f64 },
+
+    #[error("range must be >= 0, got {range}")]
+    NegativeRange { range: f64 },
+
+    #[error("latitude after datum offset must be within [-90, 90], got {latitude}")]
+    LatitudeOutOfRange { latitude: f64 },
+}
+
+impl CodedError for NormalizationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnconfiguredSensor { .. } => ErrorCode::new("BRIDGE", 501),
+            Self::NonFinite { .. } => ErrorCode::new("BRIDGE", 502),
+            Self::NegativeRange { .. } => ErrorCode::new("BRIDGE", 503),
+            Self::LatitudeOutOfRange { .. } => ErrorCode::new("BRIDGE", 504),
+        }
+    }
+}
+
+fn finite(field: &'static str, value: f64) -> Result<f64, NormalizationError> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(NormalizationError::NonFinite { field, value })
+    }
+}
+
+fn wrap_longitude(longitude: f64) -> f64 {
+    (longitude + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AngleUnit, BearingReference, DatumOffset, NormalizationConfig, NormalizationError,
+        RangeUnit, SensorLocation, SensorNormalization, SensorReading,
+    };
+    use crate::BridgeConfigError;
+
+    fn approx(left: f64, right: f64) -> bool {
+        (left - right).abs() < 1e-9
+    }
+
+    fn radar() -> SensorNormalization {
+        SensorNormalization {
+            range_unit: RangeUnit::NauticalMiles,
+            bearing_unit: AngleUnit::Mils,
+            bearing_reference: BearingReference::Magnetic {
+                declination_degrees: -10.0,
+            },
+            datum_offset: DatumOffset {
+                latitude_degrees: 0.001,
+                longitude_degrees: 0.5,
+                altitude_meters: -30.0,
+            },
+        }
+    }
+
+    #[test]
+    fn converts_units_bearing_reference_and_datum() {
+        let config = NormalizationConfig {
+            sensors: [("radar-1".to_owned(), radar())].into_iter().collect(),
+            ..NormalizationConfig::default()
+        };
+        let reading = SensorReading {
+            range: Some(2.0),
+            bearing: Some(160.0),
+            location: Some(SensorLocation {
+                latitude: 51.0,
+                longitude: 179.8,
+                altitude_meters: Some(100.0),
+            }),
+        };
+
+        let normalized = config.normalize("radar-1", &reading).expect("normalize");
+        assert_eq!(normalized.range_meters, Some(3_704.0));
+        // 160 mils = 9 degrees magnetic; 9 - 10 wraps to 359 true.
+        assert!(approx(
+            normalized.bearing_true_degrees.expect("bearing"),
+            359.0
+        ));
+        let location = normalized.location.expect("location");
+        assert!(approx(location.latitude, 51.001));
+        assert!(approx(location.longitude, -179.7));
+        assert_eq!(location.altitude_meters, Some(70.0));
+    }
+
+    #[test]
+    fn unconfigured_sensors_use_fallback_or_are_rejected() {
+        let mut config = NormalizationConfig::default();
+        let reading = SensorReading {
+            range: Some(120.0),
+            ..SensorReading::default()
+        };
+        assert_eq!(
+            config
+                .normalize("eo-2", &reading)
+                .expect("identity fallback")
+                .range_meters,
+            Some(120.0)
+        );
+
+        config.fallback = None;
+        assert_eq!(
+            config.normalize("eo-2", &reading),
+            Err(NormalizationError::UnconfiguredSensor {
+                sensor: "eo-2".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn strict_startup_requires_explicit_entries_for_required_sensors() {
+        let config = NormalizationConfig {
+            required_sensors: vec!["radar-1".to_owned()],
+            ..NormalizationConfig::default()
+        };
+        assert_eq!(
+            config.validate(true),
+            Err(BridgeConfigError::MissingSensorNormalization {
+                sensor: "radar-1".to_owned(),
+            })
+        );
+        assert_eq!(config.validate(false), Ok(()));
+
+        let invalid = NormalizationConfig {
+            sensors: [(
+                "radar-1".to_owned(),
+                SensorNormalization {
+                    bearing_reference: BearingReference::Magnetic {
+                        declination_degrees: f64::INFINITY,
+                    },
+                    ..SensorNormalization::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..config
+        };
+        assert_eq!(
+            invalid.validate(false),
+            Err(BridgeConfigError::InvalidMagneticDeclination {
+                sensor: "radar-1".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_and_out_of_range_readings() {
+        let config = NormalizationConfig::default();
+        assert_eq!(
+            config.normalize(
+                "eo-1",
+                &SensorReading {
+                    bearing: Some(f64::INFINITY),
+                    ..SensorReading::default()
+                }
+            ),
+            Err(NormalizationError::NonFinite {
+                field: "bearing",
+                value: f64::INFINITY,
+            })
+        );
+        assert_eq!(
+            config.normalize(
+                "eo-1",
+                &SensorReading {
+                    range: Some(-1.0),
+                    ..SensorReading::default()
+                }
+            ),
+            Err(NormalizationError::NegativeRange { range: -1.0 })
+        );
+    }
+}
@@ -0,0 +1,525 @@
+//! SAPIENT detection reports to CoT tracks.
+//!
+//! Each report is deduplicated by node and report ID, correlated to a stable
+//! UID, normalised, classified through [`BridgeConfig::mappings`], stamped
+//! per the time policy and annotated with its track quality. Past the
+//! [`BridgeConfig::cardinality`] thresholds, low-priority tracks are
+//! coalesced or sampled before they reach TAK clients.
+//!
+//! Registrations are checked against the pipeline's [`SapientRegistry`],
+//! and the version each node declared is kept as correlation provenance.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rustak_core::{CoreError, CotEvent, DetailNode, Position, TimestampUtc};
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_sapient::message::Timestamp;
+use rustak_sapient::{SapientMessage, SapientRegistrationError, SapientRegistry};
+use thiserror::Error;
+
+use crate::{
+    BridgeConfig, BridgeConfigError, CardinalityDecision, CorrelationInput, Correlator,
+    CorrelatorError, DedupDecision, Deduplicator, NormalizationError, SensorLocation,
+    SensorReading, TimePolicy, TrackCardinalityGuard,
+};
+
+/// `how` of emitted tracks: machine-reported.
+pub const DETECTION_HOW: &str = "m-r";
+
+/// Counters since the pipeline was built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PipelineMetrics {
+    pub received: u64,
+    pub emitted: u64,
+    /// Reports already seen within `dedup.window`.
+    pub duplicates: u64,
+    /// Registrations accepted by the version pins.
+    pub registrations: u64,
+    /// Messages other than registrations and detection reports.
+    pub ignored: u64,
+    /// Reports that could not be mapped to a track and refused
+    /// registrations.
+    pub rejected: u64,
+    /// Track updates held back by cardinality coalescing.
+    pub coalesced: u64,
+    /// New tracks left out by cardinality sampling.
+    pub sampled: u64,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PipelineError {
+    #[error(transparent)]
+    Correlation(#[from] CorrelatorError),
+
+    #[error(transparent)]
+    Normalization(#[from] NormalizationError),
+
+    #[error(transparent)]
+    Registration(#[from] SapientRegistrationError),
+
+    #[error("detection report has no location")]
+    MissingLocation,
+
+    #[error("invalid CoT event: {0}")]
+    InvalidEvent(#[from] CoreError),
+}
+
+impl CodedError for PipelineError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Correlation(error) => error.code(),
+            Self::Normalization(error) => error.code(),
+            Self::Registration(error) => error.code(),
+            Self::MissingLocation => ErrorCode::new("BRIDGE", 701),
+            Self::InvalidEvent(_) => ErrorCode::new("BRIDGE", 702),
+        }
+    }
+}
+
+pub struct DetectionPipeline {
+    config: BridgeConfig,
+    time_policy: TimePolicy,
+    correlator: Correlator,
+    dedup: Deduplicator<String>,
+    cardinality: TrackCardinalityGuard,
+    registry: SapientRegistry,
+    metrics: PipelineMetrics,
+}
+
+impl DetectionPipeline {
+    /// Validates `config` together with its mapping tables, so incomplete
+    /// tables are refused under `validation.strict_startup`.
+    pub fn new(config: BridgeConfig) -> Result<Self, BridgeConfigError> {
+        config.validate_with_mappings(&config.mappings)?;
+        Ok(Self {
+            time_policy: config.build_time_policy(),
+            correlator: Correlator::new(config.correlator.clone())?,
+            dedup: Deduplicator::new(config.dedup, config.limits.max_queue_messages)?,
+            cardinality: TrackCardinalityGuard::new(config.cardinality.clone())?,
+            registry: SapientRegistry::default(),
+            config,
+            metrics: PipelineMetrics::default(),
+        })
+    }
+
+    /// Enforces the version pins in `registry`, typically from
+    /// `SapientConfig::registry`. Without one, any version is accepted.
+    #[must_use]
+    pub fn with_registry(mut self, registry: SapientRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Pinned and declared SAPIENT versions per node.
+    #[must_use]
+    pub fn registry(&self) -> &SapientRegistry {
+        &self.registry
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &BridgeConfig {
+        &self.config
+    }
+
+    #[must_use]
+    pub fn metrics(&self) -> PipelineMetrics {
+        self.metrics
+    }
+
+    /// Distinct tracks forwarded within `cardinality.active_window`.
+    #[must_use]
+    pub fn active_tracks(&self) -> usize {
+        self.cardinality.active_tracks()
+    }
+
+    /// Maps `message`, received at `observed_at`, to a CoT track. Returns
+    /// `None` for registrations, duplicates, updates held back by the
+    /// cardinality guard and messages that are not detection reports. A
+    /// registration that contradicts its node's version pin is an error.
+    pub fn process(
+        &mut self,
+        message: &SapientMessage,
+        observed_at: SystemTime,
+    ) -> Result<Option<CotEvent>, PipelineError> {
+        self.metrics.received += 1;
+        let result = self.map(message, observed_at);
+        match &result {
+            Ok(Some(_)) => self.metrics.emitted += 1,
+            Ok(None) => {}
+            Err(_) => self.metrics.rejected += 1,
+        }
+        result
+    }
+
+    fn map(
+        &mut self,
+        message: &SapientMessage,
+        observed_at: SystemTime,
+    ) -> Result<Option<CotEvent>, PipelineError> {
+        let node_id = message.node_id.as_deref().unwrap_or_default();
+        if let Some(registration) = &message.registration {
+            self.registry.register(node_id, registration)?;
+            self.metrics.registrations += 1;
+            return Ok(None);
+        }
+        let Some(report) = &message.detection_report else {
+            self.metrics.ignored += 1;
+            return Ok(None);
+        };
+
+        if let Some(report_id) = &report.report_id {
+            let key = format!("{node_id}/{report_id}");
+            if self.dedup.observe(key, observed_at) == DedupDecision::Duplicate {
+                self.metrics.duplicates += 1;
+                return Ok(None);
+            }
+        }
+
+        let uid = self.correlator.correlate(
+            &CorrelationInput {
+                node_id: node_id.to_owned(),
+                object_id: report.object_id.clone(),
+                detection_id: report.report_id.clone(),
+                sapient_version: self
+                    .registry
+                    .declared_version(node_id.trim())
+                    .map(str::to_owned),
+            },
+            observed_at,
+        )?;
+
+        let reading = SensorReading {
+            range: report
+                .range_bearing
+                .and_then(|range_bearing| range_bearing.range),
+            bearing: report
+                .range_bearing
+                .and_then(|range_bearing| range_bearing.azimuth),
+            location: report.location.and_then(|location| {
+                Some(SensorLocation {
+                    latitude: location.y?,
+                    longitude: location.x?,
+                    altitude_meters: location.z,
+                })
+            }),
+        };
+        let location = self
+            .config
+            .normalization
+            .normalize(node_id, &reading)?
+            .location
+            .ok_or(PipelineError::MissingLocation)?;
+        let mut point = Position::new(location.latitude, location.longitude)?;
+        if let Some(altitude) = location.altitude_meters {
+            point = point.with_hae(altitude)?;
+        }
+
+        let classification = report
+            .primary_classification()
+            .and_then(|classification| classification.r#type.as_deref())
+            .unwrap_or_default();
+        let cot_type = self.config.mappings.map_classification(
+            classification,
+            &self.config.validation.unknown_class_fallback,
+        );
+
+        let message_time = message.timestamp.and_then(timestamp_to_system_time);
+        let times = self.time_policy.resolve(message_time, observed_at);
+        let mut event = CotEvent::new(
+            uid,
+            cot_type,
+            TimestampUtc::from_system_time(times.time),
+            TimestampUtc::from_system_time(times.stale),
+            point,
+        );
+        event.start = TimestampUtc::from_system_time(times.start);
+        event.how = Some(DETECTION_HOW.to_owned());
+
+        for behaviour in &report.behaviour {
+            let Some(mapping) = behaviour
+                .r#type
+                .as_deref()
+                .and_then(|name| self.config.mappings.behaviour_to_detail.get(name))
+            else {
+                continue;
+            };
+            let mut node = DetailNode::new(mapping.detail_key.clone())
+                .with_attribute("severity", mapping.severity.as_str());
+            if let Some(confidence) = behaviour.confidence {
+                node = node.with_attribute("confidence", format!("{confidence:.3}"));
+            }
+            event.detail.push(node);
+        }
+
+        let age = observed_at.duration_since(times.time).unwrap_or_default();
+        let confidence = report.detection_confidence.map_or(1.0, f64::from);
+        let quality = self.config.track_quality.assess(confidence, age);
+        self.config.track_quality.apply(&quality, &mut event)?;
+
+        match self
+            .cardinality
+            .admit(&event.uid, &event.cot_type, observed_at)
+        {
+            CardinalityDecision::Forward => Ok(Some(event)),
+            CardinalityDecision::Coalesced => {
+                self.metrics.coalesced += 1;
+                Ok(None)
+            }
+            CardinalityDecision::Sampled => {
+                self.metrics.sampled += 1;
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn timestamp_to_system_time(timestamp: Timestamp) -> Option<SystemTime> {
+    let seconds = u64::try_from(timestamp.seconds).ok()?;
+    let nanos = u32::try_from(timestamp.nanos).ok()?;
+    UNIX_EPOCH.checked_add(Duration::new(seconds, nanos.min(999_999_999)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use rustak_sapient::message::{
+        Behaviour, DetectionReport, DetectionReportClassification, Location, Registration,
+        Timestamp,
+    };
+    use rustak_sapient::{SapientMessage, SapientRegistrationError, SapientRegistry};
+
+    use super::{DetectionPipeline, PipelineError, DETECTION_HOW};
+    use crate::{
+        BehaviourMapping, BridgeConfig, BridgeConfigError, MappingSeverity, MappingTables,
+        MappingValidationError, TrackCardinalityConfig, TRACK_QUALITY_DETAIL,
+    };
+
+    fn mapped_config() -> BridgeConfig {
+        BridgeConfig {
+            mappings: MappingTables {
+                class_to_cot: [("UAV".to_owned(), "a-h-A-M-F-Q".to_owned())]
+                    .into_iter()
+                    .collect(),
+                behaviour_to_detail: [(
+                    "Loitering".to_owned(),
+                    BehaviourMapping {
+                        detail_key: "loitering".to_owned(),
+                        severity: MappingSeverity::Warning,
+                    },
+                )]
+                .into_iter()
+                .collect(),
+            },
+            ..BridgeConfig::default()
+        }
+    }
+
+    fn detection(report_id: &str, class: &str) -> SapientMessage {
+        SapientMessage {
+            timestamp: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+            node_id: Some("radar-1".to_owned()),
+            destination_id: None,
+            registration: None,
+            detection_report: Some(DetectionReport {
+                report_id: Some(report_id.to_owned()),
+                object_id: Some("obj-7".to_owned()),
+                location: Some(Location {
+                    x: Some(-0.1),
+                    y: Some(51.5),
+                    z: Some(120.0),
+                }),
+                detection_confidence: Some(0.9),
+                classification: vec![DetectionReportClassification {
+                    r#type: Some(class.to_owned()),
+                    confidence: Some(0.8),
+                }],
+                behaviour: vec![Behaviour {
+                    r#type: Some("Loitering".to_owned()),
+                    confidence: Some(0.5),
+                }],
+                ..DetectionReport::default()
+            }),
+        }
+    }
+
+    fn observed() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_001)
+    }
+
+    #[test]
+    fn maps_detection_reports_to_stable_tracks() {
+        let mut pipeline = DetectionPipeline::new(mapped_config()).expect("pipeline");
+
+        let first = pipeline
+            .process(&detection("r-1", "UAV"), observed())
+            .expect("mapped")
+            .expect("emitted");
+        assert!(first.uid.starts_with("trk-"));
+        assert_eq!(first.cot_type, "a-h-A-M-F-Q");
+        assert_eq!(first.how.as_deref(), Some(DETECTION_HOW));
+        assert_eq!(first.point.latitude(), 51.5);
+        assert_eq!(first.point.hae(), Some(120.0));
+        assert_eq!(
+            first
+                .detail_element("loitering")
+                .and_then(|node| node.attribute("severity")),
+            Some("warning")
+        );
+        assert!(first.detail_element(TRACK_QUALITY_DETAIL).is_some());
+
+        let unknown = pipeline
+            .process(&detection("r-2", "Bird"), observed())
+            .expect("mapped")
+            .expect("emitted");
+        assert_eq!(unknown.uid, first.uid);
+        assert_eq!(
+            unknown.cot_type,
+            pipeline.config().validation.unknown_class_fallback
+        );
+
+        let duplicate = pipeline
+            .process(&detection("r-2", "Bird"), observed())
+            .expect("duplicate is not an error");
+        assert!(duplicate.is_none());
+
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.received, 3);
+        assert_eq!(metrics.emitted, 2);
+        assert_eq!(metrics.duplicates, 1);
+    }
+
+    #[test]
+    fn ignores_other_messages_and_rejects_unplaceable_reports() {
+        let mut pipeline = DetectionPipeline::new(mapped_config()).expect("pipeline");
+        let status = SapientMessage {
+            node_id: Some("radar-1".to_owned()),
+            ..SapientMessage::default()
+        };
+        assert_eq!(pipeline.process(&status, observed()), Ok(None));
+
+        let mut no_location = detection("r-3", "UAV");
+        if let Some(report) = no_location.detection_report.as_mut() {
+            report.location = None;
+        }
+        assert_eq!(
+            pipeline.process(&no_location, observed()),
+            Err(PipelineError::MissingLocation)
+        );
+
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.ignored, 1);
+        assert_eq!(metrics.rejected, 1);
+        assert_eq!(metrics.emitted, 0);
+    }
+
+    #[test]
+    fn pinned_versions_gate_registrations_and_record_provenance() {
+        let pins = [("radar-1".to_owned(), "BSI Flex 335 v2.0".to_owned())]
+            .into_iter()
+            .collect();
+        let mut pipeline = DetectionPipeline::new(mapped_config())
+            .expect("pipeline")
+            .with_registry(SapientRegistry::new(pins));
+        let register = |version: &str| SapientMessage {
+            node_id: Some("radar-1".to_owned()),
+            registration: Some(Registration {
+                icd_version: Some(version.to_owned()),
+                name: None,
+            }),
+            ..SapientMessage::default()
+        };
+
+        assert_eq!(
+            pipeline.process(&register("BSI Flex 335 v1.0"), observed()),
+            Err(PipelineError::Registration(
+                SapientRegistrationError::VersionMismatch {
+                    node_id: "radar-1".to_owned(),
+                    expected: "BSI Flex 335 v2.0".to_owned(),
+                    declared: "BSI Flex 335 v1.0".to_owned(),
+                }
+            ))
+        );
+        assert_eq!(
+            pipeline.process(&register("BSI Flex 335 v2.0"), observed()),
+            Ok(None)
+        );
+        pipeline
+            .process(&detection("r-1", "UAV"), observed())
+            .expect("mapped")
+            .expect("emitted");
+
+        let snapshot = pipeline.correlator.snapshot();
+        assert_eq!(
+            snapshot.entries[0].sapient_version.as_deref(),
+            Some("BSI Flex 335 v2.0")
+        );
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.registrations, 1);
+        assert_eq!(metrics.rejected, 1);
+        assert_eq!(metrics.emitted, 1);
+    }
+
+    #[test]
+    fn storms_of_unknown_tracks_are_sampled_but_hostile_tracks_pass() {
+        let mut config = mapped_config();
+        config.cardinality = TrackCardinalityConfig {
+            coalesce_above: 1,
+            sample_above: 2,
+            sample_one_in: 1_000,
+            max_active_tracks: 4,
+            ..TrackCardinalityConfig::default()
+        };
+        let mut pipeline = DetectionPipeline::new(config).expect("pipeline");
+
+        let mut forwarded = 0;
+        for index in 0..20 {
+            let mut message = detection(&format!("r-{index}"), "Bird");
+            if let Some(report) = message.detection_report.as_mut() {
+                report.object_id = Some(format!("obj-{index}"));
+            }
+            if pipeline
+                .process(&message, observed())
+                .expect("mapped")
+                .is_some()
+            {
+                forwarded += 1;
+            }
+        }
+        assert_eq!(forwarded, 3, "two below the threshold, then one sampled");
+        assert_eq!(pipeline.active_tracks(), 3);
+
+        let mut hostile = detection("r-uav", "UAV");
+        if let Some(report) = hostile.detection_report.as_mut() {
+            report.object_id = Some("obj-uav".to_owned());
+        }
+        assert!(pipeline
+            .process(&hostile, observed())
+            .expect("mapped")
+            .is_some());
+
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.sampled, 17);
+        assert_eq!(metrics.emitted, 4);
+    }
+
+    #[test]
+    fn strict_startup_refuses_empty_mapping_tables() {
+        let error = DetectionPipeline::new(BridgeConfig::default())
+            .err()
+            .expect("empty tables must be refused");
+        assert!(matches!(
+            error,
+            BridgeConfigError::InvalidMappings(
+                MappingValidationError::InsufficientClassificationCoverage { .. }
+            )
+        ));
+
+        let mut relaxed = BridgeConfig::default();
+        relaxed.validation.strict_startup = false;
+        assert!(DetectionPipeline::new(relaxed).is_ok());
+    }
+}
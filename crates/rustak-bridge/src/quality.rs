@@ -0,0 +1,215 @@
+//! Confidence decay between detections and the track quality it implies in
+//! emitted CoT.
+//!
+//! A track's confidence halves every [`TrackQualityConfig::half_life`] after
+//! its last detection while its position error grows, so TAK users can tell
+//! a fresh track from one that is coasting on an old fix.
+
+use std::time::Duration;
+
+use rustak_core::{CoreError, CotEvent, DetailNode};
+
+use crate::BridgeConfigError;
+
+/// Detail element carrying a track's quality in emitted CoT.
+pub const TRACK_QUALITY_DETAIL: &str = "track_quality";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackQualityConfig {
+    /// Time for a track's confidence to halve without a new detection.
+    pub half_life: Duration,
+    /// Circular error (`ce`) added per second since the last detection.
+    pub ce_growth_meters_per_second: f64,
+    /// Linear error (`le`) added per second since the last detection.
+    pub le_growth_meters_per_second: f64,
+    /// Adds a [`TRACK_QUALITY_DETAIL`] element to emitted tracks.
+    pub emit_detail: bool,
+}
+
+impl Default for TrackQualityConfig {
+    fn default() -> Self {
+        Self {
+            half_life: Duration::from_secs(30),
+            ce_growth_meters_per_second: 10.0,
+            le_growth_meters_per_second: 2.0,
+            emit_detail: true,
+        }
+    }
+}
+
+impl TrackQualityConfig {
+    pub(crate) fn validate(&self) -> Result<(), BridgeConfigError> {
+        if self.half_life.is_zero() {
+            return Err(BridgeConfigError::ZeroTrackQualityHalfLife);
+        }
+        for growth in [
+            self.ce_growth_meters_per_second,
+            self.le_growth_meters_per_second,
+        ] {
+            if !growth.is_finite() || growth < 0.0 {
+                return Err(BridgeConfigError::InvalidTrackQualityGrowth);
+            }
+        }
+        Ok(())
+    }
+
+    /// Quality of a track last detected `age` ago with `confidence`, which
+    /// is clamped to `[0, 1]`; a non-finite confidence counts as 0.
+    #[must_use]
+    pub fn assess(&self, confidence: f64, age: Duration) -> TrackQuality {
+        let confidence = if confidence.is_finite() {
+            confidence.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let seconds = age.as_secs_f64();
+        TrackQuality {
+            confidence: confidence * 0.5_f64.powf(seconds / self.half_life.as_secs_f64()),
+            age,
+            ce_growth_meters: self.ce_growth_meters_per_second * seconds,
+            le_growth_meters: self.le_growth_meters_per_second * seconds,
+        }
+    }
+
+    /// Widens `event`'s known `ce`/`le` by the quality's error growth and,
+    /// when [`Self::emit_detail`] is set, replaces its quality detail.
+    /// Unknown errors stay unknown.
+    pub fn apply(&self, quality: &TrackQuality, event: &mut CotEvent) -> Result<(), CoreError> {
+        let mut point = event.point.clone();
+        if let Some(ce) = point.ce() {
+            point = point.with_ce(ce + quality.ce_growth_meters)?;
+        }
+        if let Some(le) = point.le() {
+            point = point.with_le(le + quality.le_growth_meters)?;
+        }
+        event.point = point;
+
+        if self.emit_detail {
+            event
+                .detail
+                .retain(|node| node.name != TRACK_QUALITY_DETAIL);
+            event.detail.push(quality.to_detail());
+        }
+        Ok(())
+    }
+}
+
+/// How much a track can be trusted at emission time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackQuality {
+    /// Decayed detection confidence in `[0, 1]`.
+    pub confidence: f64,
+    /// Time since the last detection.
+    pub age: Duration,
+    pub ce_growth_meters: f64,
+    pub le_growth_meters: f64,
+}
+
+impl TrackQuality {
+    /// Confidence as a 0-100 score, for styling rules in TAK clients.
+    #[must_use]
+    pub fn score(&self) -> u8 {
+        (self.confidence * 100.0).round() as u8
+    }
+
+    /// `<track_quality score=".." confidence=".." age=".."/>`, with `age` in
+    /// seconds.
+    #[must_use]
+    pub fn to_detail(&self) -> DetailNode {
+        DetailNode::new(TRACK_QUALITY_DETAIL)
+            .with_attribute("score", self.score().to_string())
+            .with_attribute("confidence", format!("{:.3}", self.confidence))
+            .with_attribute("age", format!("{:.1}", self.age.as_secs_f64()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::{CotEvent, Position, TimestampUtc};
+
+    use super::{TrackQualityConfig, TRACK_QUALITY_DETAIL};
+    use crate::BridgeConfigError;
+
+    fn track(point: Position) -> CotEvent {
+        let time = TimestampUtc::from_unix_seconds(1_700_000_000);
+        CotEvent::new(
+            "trk-1",
+            "a-h-A-M-F-Q",
+            time,
+            TimestampUtc::from_unix_seconds(1_700_000_015),
+            point,
+        )
+    }
+
+    #[test]
+    fn confidence_halves_every_half_life() {
+        let config = TrackQualityConfig {
+            half_life: Duration::from_secs(10),
+            ..TrackQualityConfig::default()
+        };
+
+        let fresh = config.assess(0.8, Duration::ZERO);
+        assert_eq!(fresh.confidence, 0.8);
+        assert_eq!(fresh.score(), 80);
+        assert_eq!(fresh.ce_growth_meters, 0.0);
+
+        let aging = config.assess(0.8, Duration::from_secs(20));
+        assert!((aging.confidence - 0.2).abs() < 1e-12);
+        assert_eq!(aging.score(), 20);
+        assert_eq!(aging.ce_growth_meters, 200.0);
+        assert_eq!(aging.le_growth_meters, 40.0);
+
+        assert_eq!(config.assess(f64::NAN, Duration::ZERO).confidence, 0.0);
+        assert_eq!(config.assess(3.0, Duration::ZERO).confidence, 1.0);
+    }
+
+    #[test]
+    fn apply_grows_known_errors_and_replaces_quality_detail() {
+        let config = TrackQualityConfig::default();
+        let point = Position::new(51.5, -0.1)
+            .and_then(|point| point.with_ce(25.0))
+            .expect("point");
+        let mut event = track(point);
+        event
+            .detail
+            .push(config.assess(1.0, Duration::ZERO).to_detail());
+
+        let quality = config.assess(1.0, Duration::from_secs(30));
+        config.apply(&quality, &mut event).expect("apply");
+
+        assert_eq!(event.point.ce(), Some(325.0));
+        assert_eq!(event.point.le(), None);
+        let details: Vec<_> = event
+            .detail
+            .iter()
+            .filter(|node| node.name == TRACK_QUALITY_DETAIL)
+            .collect();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].attribute("score"), Some("50"));
+        assert_eq!(details[0].attribute("confidence"), Some("0.500"));
+        assert_eq!(details[0].attribute("age"), Some("30.0"));
+    }
+
+    #[test]
+    fn validate_rejects_zero_half_life_and_bad_growth() {
+        let zero = TrackQualityConfig {
+            half_life: Duration::ZERO,
+            ..TrackQualityConfig::default()
+        };
+        assert_eq!(
+            zero.validate(),
+            Err(BridgeConfigError::ZeroTrackQualityHalfLife)
+        );
+
+        let negative = TrackQualityConfig {
+            le_growth_meters_per_second: -1.0,
+            ..TrackQualityConfig::default()
+        };
+        assert_eq!(
+            negative.validate(),
+            Err(BridgeConfigError::InvalidTrackQualityGrowth)
+        );
+    }
+}
@@ -0,0 +1,314 @@
+//! `rustak bridge`: SAPIENT sensor detections forwarded to TAK as CoT tracks.
+
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use clap::Args;
+use rustak::RustakError;
+use rustak_bridge::DetectionPipeline;
+use rustak_sapient::SapientMessage;
+use rustak_transport::{
+    ConnectionManager, Protocol, TransportConfig, TransportConnection, TransportFraming,
+};
+use rustak_wire::{DowngradePolicy, WireFormat};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    load_optional_config, parse_endpoint, validate_sapient_defaults, validate_transport_defaults,
+    with_tls_connector, CliError,
+};
+
+#[derive(Debug, Args)]
+pub struct BridgeArgs {
+    #[arg(
+        long,
+        help = "TCP address to accept SAPIENT connections on (for example 0.0.0.0:19000)"
+    )]
+    pub sapient: Option<String>,
+    #[arg(
+        long,
+        help = "TAK server address; the config's tls protocol keeps its server name"
+    )]
+    pub tak: Option<String>,
+    #[arg(long, help = "Stop after forwarding this many tracks")]
+    pub count: Option<u64>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+pub(crate) fn run_bridge(args: BridgeArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_sapient_defaults()?;
+    validate_transport_defaults()?;
+    let sapient_addr = parse_endpoint(
+        args.sapient
+            .as_deref()
+            .ok_or(CliError::BridgeSapientRequired)?,
+    )?;
+    let sapient = config
+        .as_ref()
+        .map(rustak_config::RustakConfig::resolve_sapient)
+        .transpose()
+        .map_err(|source| CliError::Facade(RustakError::Config(source)))?
+        .flatten()
+        .unwrap_or_default();
+    let transport = bridge_transport(&args, config.as_ref())?;
+    let bridge = config
+        .as_ref()
+        .and_then(|config| config.bridge.clone())
+        .unwrap_or_default();
+    let mut pipeline = DetectionPipeline::new(bridge)
+        .map_err(|source| CliError::Facade(RustakError::Bridge(source)))?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let bridged = runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(sapient_addr)
+            .await
+            .map_err(|source| CliError::Bind {
+                addr: sapient_addr,
+                source,
+            })?;
+        tracing::info!(sapient = %sapient_addr, "bridge_listening");
+
+        let tls = matches!(transport.protocol, Protocol::Tls { .. });
+        let mut manager = ConnectionManager::new(transport, DowngradePolicy::FailOpen)?;
+        if tls {
+            manager = with_tls_connector(manager, config.as_ref())?;
+        }
+        let mut tak = manager.connect().await?;
+        tracing::info!(framing = ?tak.framing(), "bridge_connected");
+
+        let bridged = tokio::select! {
+            result = bridge_sapient(listener, &sapient, &mut pipeline, &mut tak, args.count) => result,
+            signal = tokio::signal::ctrl_c() => {
+                signal.map_err(|source| CliError::Runtime { source })?;
+                tracing::info!("bridge_interrupted");
+                Ok(())
+            }
+        };
+        // Close the TAK stream cleanly even when the SAPIENT side failed.
+        let closed = tak
+            .into_inner()
+            .shutdown()
+            .await
+            .map_err(|error| CliError::Transport(error.into()));
+        bridged.and(closed)
+    });
+
+    let metrics = pipeline.metrics();
+    println!(
+        "bridge received={} emitted={} duplicates={} ignored={} rejected={}",
+        metrics.received, metrics.emitted, metrics.duplicates, metrics.ignored, metrics.rejected
+    );
+    bridged
+}
+
+/// Resolves `--tak`, falling back to the loaded config's TCP or TLS
+/// protocol. `--tak` replaces a TLS protocol's address but keeps its server
+/// name.
+fn bridge_transport(
+    args: &BridgeArgs,
+    config: Option<&rustak_config::RustakConfig>,
+) -> Result<TransportConfig, CliError> {
+    let base = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let tak = args.tak.as_deref().map(parse_endpoint).transpose()?;
+    let protocol = match (tak, &base.protocol) {
+        (Some(addr), Protocol::Tls { server_name, .. }) if config.is_some() => Protocol::Tls {
+            addr,
+            server_name: server_name.clone(),
+        },
+        (Some(addr), _) => Protocol::Tcp { addr },
+        (None, Protocol::Tcp { .. } | Protocol::Tls { .. }) if config.is_some() => {
+            base.protocol.clone()
+        }
+        (None, _) => return Err(CliError::BridgeTakRequired),
+    };
+    Ok(TransportConfig { protocol, ..base })
+}
+
+/// SAPIENT half of `rustak bridge`: accepts sensor connections one at a
+/// time, maps each detection report through `pipeline` and forwards the
+/// resulting tracks on `tak`, until `count` tracks were forwarded. A sensor
+/// that disconnects is logged and the next one accepted; undecodable and
+/// unmappable messages are logged and skipped. A failed TAK write ends the
+/// bridge.
+pub async fn bridge_sapient<IO>(
+    listener: tokio::net::TcpListener,
+    sapient: &rustak_sapient::SapientConfig,
+    pipeline: &mut DetectionPipeline,
+    tak: &mut TransportConnection<IO>,
+    count: Option<u64>,
+) -> Result<(), CliError>
+where
+    IO: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let codec = sapient.codec();
+    let wire_format = match tak.framing() {
+        TransportFraming::XmlNewlineDelimited => WireFormat::Xml,
+        TransportFraming::TakProtocolU32LengthPrefixed
+        | TransportFraming::TakProtocolMeshHeader => WireFormat::TakProtocolV1,
+    };
+    while count.is_none_or(|count| pipeline.metrics().emitted < count) {
+        let (mut stream, peer) = listener
+            .accept()
+            .await
+            .map_err(|source| CliError::Runtime { source })?;
+        if sapient.tcp_nodelay {
+            stream
+                .set_nodelay(true)
+                .map_err(|source| CliError::Runtime { source })?;
+        }
+        tracing::info!(peer = %peer, "bridge_sensor_connected");
+
+        while count.is_none_or(|count| pipeline.metrics().emitted < count) {
+            let payload = match codec.read_message(&mut stream).await {
+                Ok(payload) => payload,
+                Err(error) => {
+                    tracing::info!(peer = %peer, reason = %error, "bridge_sensor_disconnected");
+                    break;
+                }
+            };
+            let message = match SapientMessage::decode_payload(&payload) {
+                Ok(message) => message,
+                Err(error) => {
+                    tracing::warn!(peer = %peer, error = %error, "bridge_decode_error");
+                    continue;
+                }
+            };
+            match pipeline.process(&message, SystemTime::now()) {
+                Ok(Some(event)) => {
+                    let payload = rustak_wire::encode_payload_for_format(
+                        event.to_xml().as_bytes(),
+                        wire_format,
+                    )?;
+                    tak.send_frame(&payload).await?;
+                }
+                Ok(None) => {}
+                Err(error) => tracing::warn!(peer = %peer, error = %error, "bridge_rejected"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_transport::{Protocol, TransportConfig};
+
+    use super::{bridge_sapient, bridge_transport, BridgeArgs};
+    use crate::CliError;
+
+    #[tokio::test]
+    async fn bridge_forwards_sapient_detections_as_cot_tracks() {
+        use rustak_sapient::message::{DetectionReport, DetectionReportClassification, Location};
+        use tokio::io::AsyncBufReadExt;
+
+        let tak_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind tak");
+        let tak_addr = tak_listener.local_addr().expect("tak addr");
+        let tak_server = tokio::spawn(async move {
+            let (stream, _) = tak_listener.accept().await.expect("accept bridge");
+            let mut line = String::new();
+            tokio::io::BufReader::new(stream)
+                .read_line(&mut line)
+                .await
+                .expect("read track");
+            line
+        });
+        let mut tak = rustak_transport::ConnectionManager::new(
+            TransportConfig {
+                protocol: Protocol::Tcp { addr: tak_addr },
+                ..TransportConfig::default()
+            },
+            rustak_wire::DowngradePolicy::FailOpen,
+        )
+        .expect("manager")
+        .connect()
+        .await
+        .expect("connect");
+
+        let sapient_listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind sapient");
+        let sapient_addr = sapient_listener.local_addr().expect("sapient addr");
+        let sapient = rustak_sapient::SapientConfig::default();
+        let codec = sapient.codec();
+        tokio::spawn(async move {
+            let mut sensor = tokio::net::TcpStream::connect(sapient_addr)
+                .await
+                .expect("sensor connects");
+            let detection = rustak_sapient::SapientMessage {
+                node_id: Some("radar-1".to_owned()),
+                detection_report: Some(DetectionReport {
+                    report_id: Some("r-1".to_owned()),
+                    object_id: Some("obj-1".to_owned()),
+                    location: Some(Location {
+                        x: Some(-0.1),
+                        y: Some(51.5),
+                        z: None,
+                    }),
+                    classification: vec![DetectionReportClassification {
+                        r#type: Some("UAV".to_owned()),
+                        confidence: Some(0.9),
+                    }],
+                    ..DetectionReport::default()
+                }),
+                ..rustak_sapient::SapientMessage::default()
+            };
+            codec
+                .write_message(&mut sensor, b"not protobuf")
+                .await
+                .expect("write garbage");
+            codec
+                .write_message(&mut sensor, &detection.encode_payload())
+                .await
+                .expect("write detection");
+        });
+
+        let mut bridge = rustak_bridge::BridgeConfig::default();
+        bridge.validation.strict_startup = false;
+        bridge
+            .mappings
+            .class_to_cot
+            .insert("UAV".to_owned(), "a-h-A-M-F-Q".to_owned());
+        let mut pipeline = rustak_bridge::DetectionPipeline::new(bridge).expect("pipeline");
+        bridge_sapient(sapient_listener, &sapient, &mut pipeline, &mut tak, Some(1))
+            .await
+            .expect("bridge");
+
+        let track = tak_server.await.expect("tak server");
+        assert!(track.starts_with("<event"));
+        assert!(track.contains("type=\"a-h-A-M-F-Q\""));
+        assert!(track.contains("uid=\"trk-"));
+        let metrics = pipeline.metrics();
+        assert_eq!(metrics.received, 1);
+        assert_eq!(metrics.emitted, 1);
+    }
+
+    #[test]
+    fn bridge_transport_needs_a_tak_target() {
+        let args = BridgeArgs {
+            sapient: Some("127.0.0.1:19000".to_owned()),
+            tak: None,
+            count: None,
+            config: None,
+        };
+        assert!(matches!(
+            bridge_transport(&args, None),
+            Err(CliError::BridgeTakRequired)
+        ));
+
+        let args = BridgeArgs {
+            tak: Some("127.0.0.1:8087".to_owned()),
+            ..args
+        };
+        let transport = bridge_transport(&args, None).expect("transport");
+        assert!(matches!(transport.protocol, Protocol::Tcp { addr } if addr.port() == 8087));
+    }
+}
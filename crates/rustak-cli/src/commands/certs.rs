@@ -0,0 +1,522 @@
+//! `rustak certs`: identity inspection, verification, pinning, conversion and
+//! enrollment.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use clap::{Args, Subcommand};
+use rustak_core::time::TimestampUtc;
+
+use crate::{load_optional_config, write_output_bytes, CliError};
+
+#[derive(Debug, Args)]
+pub struct CertsArgs {
+    #[command(subcommand)]
+    pub action: CertsAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CertsAction {
+    /// Show names, validity, key type and SPKI pin of each certificate.
+    Inspect(CertsIdentityArgs),
+    /// Check the client chain against the CA bundle and the key against the
+    /// client certificate.
+    Verify(CertsIdentityArgs),
+    /// Print the SPKI pin of a certificate for `crypto.server_spki_pin`.
+    Pin(CertsPinArgs),
+    /// Convert an identity from PKCS#12 to PEM files or back.
+    Convert(CertsConvertArgs),
+    /// Request a client certificate from a TAK Server's enrollment API.
+    Enroll(CertsEnrollArgs),
+}
+
+/// Where a `certs` action finds the identity: a PKCS#12 archive, PEM files,
+/// or the `certificates` section of a config.
+#[derive(Debug, Args)]
+pub struct CertsIdentityArgs {
+    #[arg(
+        long,
+        conflicts_with_all = ["cert", "key", "ca"],
+        help = "PKCS#12 archive holding the identity"
+    )]
+    pub p12: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "VAR",
+        help = "Environment variable holding the PKCS#12 password"
+    )]
+    pub password_env: Option<String>,
+    #[arg(
+        long,
+        help = "PEM client certificate, optionally followed by intermediates"
+    )]
+    pub cert: Option<PathBuf>,
+    #[arg(long, help = "PEM private key of the client certificate")]
+    pub key: Option<PathBuf>,
+    #[arg(long, help = "PEM CA bundle")]
+    pub ca: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "rustak YAML config whose `certificates` section is used when no files are given"
+    )]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct CertsPinArgs {
+    #[arg(long, help = "PEM certificate to pin; the first block is used")]
+    pub cert: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct CertsConvertArgs {
+    #[command(flatten)]
+    pub identity: CertsIdentityArgs,
+    #[arg(
+        long,
+        help = "From PKCS#12: directory for client.pem, client.key and ca.pem. \
+                From PEM: path of the archive to write"
+    )]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct CertsEnrollArgs {
+    #[arg(
+        long,
+        value_name = "HOST[:PORT]",
+        help = "TAK Server enrollment endpoint; the port defaults to 8446"
+    )]
+    pub server: String,
+    #[arg(long)]
+    pub username: String,
+    #[arg(
+        long,
+        value_name = "VAR",
+        help = "Environment variable holding the enrollment password"
+    )]
+    pub password_env: String,
+    #[arg(
+        long,
+        help = "PEM CA bundle trusted for the enrollment endpoint's certificate"
+    )]
+    pub ca: PathBuf,
+    #[arg(
+        long,
+        help = "clientUid reported to the server; defaults to the username"
+    )]
+    pub uid: Option<String>,
+    #[arg(long, help = "Directory for client.pem, client.key and ca.pem")]
+    pub output: PathBuf,
+    #[arg(long, default_value_t = 30)]
+    pub timeout_secs: u64,
+}
+
+pub(crate) fn run_certs_inspect(args: &CertsIdentityArgs) -> Result<(), CliError> {
+    let report = load_certs_identity(args)?.inspect()?;
+    for line in certificate_lines(&report, SystemTime::now()) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub(crate) fn run_certs_verify(args: &CertsIdentityArgs) -> Result<(), CliError> {
+    let identity = load_certs_identity(args)?;
+    rustak_crypto::verify_client_chain(
+        &identity.ca_cert_pem,
+        &identity.client_cert_pem,
+        SystemTime::now(),
+    )?;
+    let report = identity.inspect()?;
+    if report.key_matches_certificate == Some(false) {
+        return Err(CliError::CertsKeyMismatch);
+    }
+    let client = &report.client_chain[0];
+    println!(
+        "certs_verify result=ok subject={:?} issuer={:?} key={}",
+        client.subject,
+        client.issuer,
+        key_match_label(report.key_matches_certificate)
+    );
+    Ok(())
+}
+
+pub(crate) fn run_certs_pin(args: &CertsPinArgs) -> Result<(), CliError> {
+    let pem = fs::read_to_string(&args.cert).map_err(|source| CliError::InputRead {
+        path: args.cert.display().to_string(),
+        source,
+    })?;
+    let certificates = rustak_crypto::certs::pem_certificates("cert", &pem)?;
+    let certificate = rustak_crypto::CertificateInfo::from_der(&certificates[0])?;
+    println!("{}", certificate.spki_pin());
+    tracing::info!(
+        subject = ?certificate.subject,
+        not_after = %TimestampUtc::from_system_time(certificate.not_after).to_rfc3339_millis(),
+        "certs_pin"
+    );
+    Ok(())
+}
+
+pub(crate) fn run_certs_convert(args: &CertsConvertArgs) -> Result<(), CliError> {
+    let identity = load_certs_identity(&args.identity)?;
+    if args.identity.p12.is_some() {
+        let written = write_pem_identity(&identity, &args.output)?;
+        println!("certs_convert to=pem {written}");
+    } else {
+        let password = password_from_env(args.identity.password_env.as_deref())?;
+        let archive = rustak_crypto::encode_pkcs12(&identity, &password.unwrap_or_default())?;
+        write_output_bytes(&archive, Some(&args.output))?;
+        println!("certs_convert to=p12 output={}", args.output.display());
+    }
+    Ok(())
+}
+
+pub(crate) fn run_certs_enroll(args: &CertsEnrollArgs) -> Result<(), CliError> {
+    let trust_ca_pem = fs::read_to_string(&args.ca).map_err(|source| CliError::InputRead {
+        path: args.ca.display().to_string(),
+        source,
+    })?;
+    let (host, port) = enrollment_endpoint(&args.server)?;
+    let client = rustak_server::EnrollmentClient::new(rustak_server::EnrollmentConfig {
+        host,
+        port,
+        username: args.username.clone(),
+        password: password_from_env(Some(&args.password_env))?.unwrap_or_default(),
+        client_uid: args.uid.clone().unwrap_or_else(|| args.username.clone()),
+        trust_ca_pem,
+        server_spki_pin: None,
+        timeout: Duration::from_secs(args.timeout_secs),
+    })?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let identity = runtime.block_on(client.enroll())?;
+    let report = identity.inspect()?;
+    let written = write_pem_identity(&identity, &args.output)?;
+    println!(
+        "certs_enroll subject={:?} not_after={} {written}",
+        report.client_chain[0].subject,
+        TimestampUtc::from_system_time(report.client_chain[0].not_after).to_rfc3339_millis()
+    );
+    Ok(())
+}
+
+/// `host[:port]`, with IPv6 literals in brackets when a port is given.
+fn enrollment_endpoint(server: &str) -> Result<(String, u16), CliError> {
+    let invalid = || CliError::InvalidEnrollmentServer {
+        server: server.to_owned(),
+    };
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.starts_with('[') => {
+            (host, port.parse().map_err(|_| invalid())?)
+        }
+        _ => (server, rustak_server::enrollment::ENROLLMENT_PORT),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host.to_owned(), port))
+}
+
+/// Writes `client.pem`, `client.key` and, when present, `ca.pem` into `dir`
+/// and returns the `key=path` fields naming them.
+fn write_pem_identity(
+    identity: &rustak_crypto::PemIdentity,
+    dir: &Path,
+) -> Result<String, CliError> {
+    fs::create_dir_all(dir).map_err(|source| CliError::OutputWrite {
+        path: dir.display().to_string(),
+        source,
+    })?;
+    let client_cert = dir.join("client.pem");
+    let client_key = dir.join("client.key");
+    write_output_bytes(identity.client_cert_pem.as_bytes(), Some(&client_cert))?;
+    write_private_key(&identity.client_key_pem, &client_key)?;
+    let ca_cert = if identity.ca_cert_pem.is_empty() {
+        "<none>".to_owned()
+    } else {
+        let ca_cert = dir.join("ca.pem");
+        write_output_bytes(identity.ca_cert_pem.as_bytes(), Some(&ca_cert))?;
+        ca_cert.display().to_string()
+    };
+    Ok(format!(
+        "client_cert={} client_key={} ca_cert={ca_cert}",
+        client_cert.display(),
+        client_key.display()
+    ))
+}
+
+/// Resolves `--p12`, then the PEM file flags, then the config's
+/// `certificates` section. PEM files that are not given stay empty, so
+/// `inspect` can look at a lone certificate.
+fn load_certs_identity(args: &CertsIdentityArgs) -> Result<rustak_crypto::PemIdentity, CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    if let Some(archive_path) = &args.p12 {
+        let source = rustak_crypto::IdentitySource::Pkcs12File {
+            archive_path: archive_path.clone(),
+            password: password_from_env(args.password_env.as_deref())?,
+        };
+        return Ok(source.load()?.to_pem()?);
+    }
+    if args.cert.is_some() || args.key.is_some() || args.ca.is_some() {
+        let read = |path: Option<&PathBuf>| {
+            path.map_or(Ok(String::new()), |path| {
+                fs::read_to_string(path).map_err(|source| CliError::InputRead {
+                    path: path.display().to_string(),
+                    source,
+                })
+            })
+        };
+        return Ok(rustak_crypto::PemIdentity {
+            ca_cert_pem: read(args.ca.as_ref())?,
+            client_cert_pem: read(args.cert.as_ref())?,
+            client_key_pem: read(args.key.as_ref())?,
+        });
+    }
+    let Some(certificates) = config
+        .as_ref()
+        .and_then(|config| config.certificates.as_ref())
+    else {
+        return Err(CliError::CertsIdentityRequired);
+    };
+    let source = rustak_crypto::IdentitySource::PemFiles {
+        ca_cert_path: PathBuf::from(&certificates.ca_cert),
+        client_cert_path: PathBuf::from(&certificates.client_cert),
+        client_key_path: PathBuf::from(&certificates.client_key),
+    };
+    Ok(source.load()?.to_pem()?)
+}
+
+fn password_from_env(name: Option<&str>) -> Result<Option<String>, CliError> {
+    name.map(|name| {
+        std::env::var(name).map_err(|_| CliError::PasswordEnvUnset {
+            name: name.to_owned(),
+        })
+    })
+    .transpose()
+}
+
+/// Writes key material readable by its owner only, where the platform
+/// supports it.
+fn write_private_key(pem: &str, path: &Path) -> Result<(), CliError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(pem.as_bytes()))
+        .map_err(|source| CliError::OutputWrite {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+fn certificate_lines(report: &rustak_crypto::IdentityReport, now: SystemTime) -> Vec<String> {
+    let certificates = report
+        .client_chain
+        .iter()
+        .enumerate()
+        .map(|(index, certificate)| {
+            let role = if index == 0 { "client" } else { "intermediate" };
+            (role, certificate)
+        })
+        .chain(
+            report
+                .ca_certificates
+                .iter()
+                .map(|certificate| ("ca", certificate)),
+        );
+    let mut lines = certificates
+        .map(|(role, certificate)| {
+            let expires_in_days = match certificate.not_after.duration_since(now) {
+                Ok(remaining) => (remaining.as_secs() / 86_400) as i64,
+                Err(expired) => -((expired.duration().as_secs() / 86_400) as i64) - 1,
+            };
+            let subject_alt_names = certificate
+                .subject_alt_names
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                "certificate role={role} subject={:?} issuer={:?} serial={} not_before={} \
+                 not_after={} expires_in_days={expires_in_days} key_type={} ca={} san={:?} \
+                 spki_pin={}",
+                certificate.subject,
+                certificate.issuer,
+                certificate.serial,
+                TimestampUtc::from_system_time(certificate.not_before).to_rfc3339_millis(),
+                TimestampUtc::from_system_time(certificate.not_after).to_rfc3339_millis(),
+                certificate.key_type,
+                certificate.is_ca,
+                subject_alt_names,
+                certificate.spki_pin()
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!(
+        "certs_inspect certificates={} key={}",
+        lines.len(),
+        key_match_label(report.key_matches_certificate)
+    ));
+    lines
+}
+
+fn key_match_label(matches: Option<bool>) -> &'static str {
+    match matches {
+        Some(true) => "matches",
+        Some(false) => "mismatch",
+        None => "unchecked",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rustak_limits::CodedError;
+
+    use super::{certificate_lines, enrollment_endpoint};
+    use crate::{execute_command, Cli, CliError, ExitStatus};
+
+    #[test]
+    fn certs_convert_round_trips_an_identity_that_verifies() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_certs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let ca_key = rcgen::KeyPair::generate().expect("ca key");
+        let mut ca_params = rcgen::CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).expect("ca cert");
+        let client_key = rcgen::KeyPair::generate().expect("client key");
+        let client = rcgen::CertificateParams::new(vec!["rustak-client".to_owned()])
+            .expect("client params")
+            .signed_by(&client_key, &ca, &ca_key)
+            .expect("client cert");
+        let paths = ["ca.pem", "client.pem", "client.key"].map(|name| dir.join(name));
+        for (path, pem) in paths
+            .iter()
+            .zip([ca.pem(), client.pem(), client_key.serialize_pem()])
+        {
+            std::fs::write(path, pem).expect("write pem");
+        }
+        let archive = dir.join("client.p12");
+        let unpacked = dir.join("unpacked");
+        std::env::set_var("RUSTAK_CLI_CERTS_TEST_PASSWORD", "atak");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "certs",
+            "convert",
+            "--ca",
+            paths[0].to_str().expect("utf8 path"),
+            "--cert",
+            paths[1].to_str().expect("utf8 path"),
+            "--key",
+            paths[2].to_str().expect("utf8 path"),
+            "--password-env",
+            "RUSTAK_CLI_CERTS_TEST_PASSWORD",
+            "--output",
+            archive.to_str().expect("utf8 path"),
+        ])
+        .expect("convert to p12 args parse");
+        execute_command(cli.command).expect("convert to p12 succeeds");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "certs",
+            "convert",
+            "--p12",
+            archive.to_str().expect("utf8 path"),
+            "--password-env",
+            "RUSTAK_CLI_CERTS_TEST_PASSWORD",
+            "--output",
+            unpacked.to_str().expect("utf8 path"),
+        ])
+        .expect("convert to pem args parse");
+        execute_command(cli.command).expect("convert to pem succeeds");
+        assert_eq!(
+            std::fs::read_to_string(unpacked.join("client.pem")).expect("client cert"),
+            client.pem()
+        );
+        assert_eq!(
+            std::fs::read_to_string(unpacked.join("ca.pem")).expect("ca cert"),
+            ca.pem()
+        );
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "certs",
+            "verify",
+            "--p12",
+            archive.to_str().expect("utf8 path"),
+            "--password-env",
+            "RUSTAK_CLI_CERTS_TEST_PASSWORD",
+        ])
+        .expect("verify args parse");
+        execute_command(cli.command).expect("round-tripped identity verifies");
+
+        let identity = rustak_crypto::PemIdentity {
+            ca_cert_pem: ca.pem(),
+            client_cert_pem: client.pem(),
+            client_key_pem: client_key.serialize_pem(),
+        };
+        let lines = certificate_lines(
+            &identity.inspect().expect("inspect"),
+            std::time::SystemTime::now(),
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("certificate role=client "));
+        assert!(lines[0].contains(" key_type=ecdsa-p256 ca=false san=\"DNS:rustak-client\" "));
+        assert!(lines[1].starts_with("certificate role=ca "));
+        assert_eq!(lines[2], "certs_inspect certificates=2 key=matches");
+    }
+
+    #[test]
+    fn certs_need_an_identity_source() {
+        let cli = Cli::try_parse_from(["rustak", "certs", "inspect"]).expect("inspect args parse");
+        let error = execute_command(cli.command).expect_err("nothing to inspect");
+        assert!(matches!(error, CliError::CertsIdentityRequired));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "certs",
+            "inspect",
+            "--p12",
+            "client.p12",
+            "--password-env",
+            "RUSTAK_CLI_CERTS_TEST_UNSET",
+        ])
+        .expect("inspect args parse");
+        let error = execute_command(cli.command).expect_err("password variable unset");
+        assert!(matches!(error, CliError::PasswordEnvUnset { .. }));
+        assert_eq!(error.code().to_string(), "RTK-CLI-0036");
+    }
+
+    #[test]
+    fn enrollment_endpoints_default_to_the_enrollment_port() {
+        assert_eq!(
+            enrollment_endpoint("tak.example.com").expect("host"),
+            ("tak.example.com".to_owned(), 8446)
+        );
+        assert_eq!(
+            enrollment_endpoint("10.0.0.5:9446").expect("host and port"),
+            ("10.0.0.5".to_owned(), 9446)
+        );
+        assert_eq!(
+            enrollment_endpoint("[fd00::5]:8446").expect("bracketed ipv6"),
+            ("fd00::5".to_owned(), 8446)
+        );
+        assert_eq!(
+            enrollment_endpoint("fd00::5").expect("bare ipv6"),
+            ("fd00::5".to_owned(), 8446)
+        );
+        let error = enrollment_endpoint("tak.example.com:https").expect_err("named port");
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+    }
+}
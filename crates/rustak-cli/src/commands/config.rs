@@ -0,0 +1,173 @@
+//! `rustak config`: memory budgets, the reference document and field
+//! explanations.
+
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+use rustak::RustakError;
+
+use crate::{validate_optional_config, CliError};
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub action: ConfigAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Report worst-case memory per subsystem from configured limits.
+    Budget(ConfigBudgetArgs),
+    /// Render the config reference generated from the schema and validators.
+    Docs(ConfigDocsArgs),
+    /// Show type, default, constraints and description of one config field.
+    Explain(ConfigExplainArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigDocsArgs {
+    #[arg(long, help = "Write the Markdown here instead of stdout")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigExplainArgs {
+    /// Dotted field path, for example `transport.reconnect.jitter`.
+    pub path: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigBudgetArgs {
+    #[arg(
+        long,
+        help = "Optional path to rustak YAML config; defaults are used otherwise"
+    )]
+    pub config: Option<PathBuf>,
+}
+
+pub(crate) fn run_config_budget(args: ConfigBudgetArgs) -> Result<(), CliError> {
+    validate_optional_config(args.config.as_deref())?;
+    let config = match args.config.as_deref() {
+        Some(path) => rustak_config::RustakConfig::load(path),
+        None => Ok(rustak_config::RustakConfig::default()),
+    }
+    .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    let budget = config
+        .memory_budget()
+        .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    for line in memory_budget_lines(&budget) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+pub(crate) fn run_config_explain(args: &ConfigExplainArgs) -> Result<(), CliError> {
+    for line in config_explain_lines(&args.path)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// `config explain` output; objects also list their direct child fields.
+fn config_explain_lines(path: &str) -> Result<Vec<String>, CliError> {
+    let reference = rustak_config::config_reference();
+    let field = reference
+        .iter()
+        .find(|field| field.path == path)
+        .ok_or_else(|| CliError::UnknownConfigField {
+            path: path.to_owned(),
+        })?;
+    let mut lines = vec![
+        format!("path: {}", field.path),
+        format!("type: {}", field.kind),
+    ];
+    match (&field.default, field.required) {
+        (Some(default), _) => lines.push(format!("default: {default}")),
+        (None, true) => lines.push("default: none (required)".to_owned()),
+        (None, false) => {}
+    }
+    if !field.constraints.is_empty() {
+        lines.push(format!("constraints: {}", field.constraints.join("; ")));
+    }
+    if let Some(description) = &field.description {
+        lines.push(format!("description: {description}"));
+    }
+    let children = reference
+        .iter()
+        .filter_map(|child| {
+            let rest = child.path.strip_prefix(path)?;
+            let name = rest
+                .strip_prefix('.')
+                .or_else(|| rest.strip_prefix("[]."))?;
+            (!name.contains('.')).then_some(child.path.as_str())
+        })
+        .collect::<Vec<_>>();
+    if !children.is_empty() {
+        lines.push(format!("fields: {}", children.join(", ")));
+    }
+    Ok(lines)
+}
+
+fn memory_budget_lines(budget: &rustak_config::MemoryBudget) -> Vec<String> {
+    let mut lines = budget.report_lines();
+    let unbounded = budget.unbounded_components();
+    lines.push(format!(
+        "memory_budget total_bounded_bytes={} unbounded={}",
+        budget.bounded_total_bytes(),
+        if unbounded.is_empty() {
+            "none".to_owned()
+        } else {
+            unbounded.join(",")
+        }
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::{config_explain_lines, memory_budget_lines};
+    use crate::{Cli, CliError, ExitStatus};
+
+    #[test]
+    fn config_budget_reports_components_and_total() {
+        assert!(Cli::try_parse_from(["rustak", "config", "budget"]).is_ok());
+
+        let budget = rustak_config::RustakConfig::default()
+            .memory_budget()
+            .expect("budget");
+        let lines = memory_budget_lines(&budget);
+        let summary = lines.last().expect("summary line");
+        assert!(summary.starts_with(&format!(
+            "memory_budget total_bounded_bytes={} ",
+            budget.bounded_total_bytes()
+        )));
+        assert!(summary.ends_with("unbounded=none"));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("memory_budget component=transport.send_queue ")));
+    }
+
+    #[test]
+    fn config_explain_describes_fields_and_lists_children() {
+        assert!(Cli::try_parse_from(["rustak", "config", "docs"]).is_ok());
+
+        let lines = config_explain_lines("transport.send_queue.max_messages").expect("explain");
+        assert_eq!(lines[0], "path: transport.send_queue.max_messages");
+        assert!(lines.contains(&"default: 1024".to_owned()));
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("constraints: must be > 0; ")));
+
+        let lines = config_explain_lines("transport.keepalive").expect("explain");
+        assert_eq!(
+            lines.last().map(String::as_str),
+            Some("fields: transport.keepalive.interval, transport.keepalive.timeout")
+        );
+
+        let error = config_explain_lines("transport.nope").expect_err("unknown field");
+        assert!(matches!(error, CliError::UnknownConfigField { .. }));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+    }
+}
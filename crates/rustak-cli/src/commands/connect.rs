@@ -0,0 +1,394 @@
+//! `rustak connect`: an interactive TAK Server streaming session.
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use rustak_server::{StreamingClient, StreamingConnection};
+use rustak_transport::{
+    Protocol, TransportConfig, TransportFraming, TransportReceiver, TransportSender,
+};
+use rustak_wire::negotiation::events::state_code;
+use rustak_wire::{DowngradePolicy, WireFormat};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use crate::{load_optional_config, tls_crypto_config, CliError, ConvertFormat};
+
+#[derive(Debug, Args)]
+pub struct ConnectArgs {
+    #[arg(long, help = "TAK Server host; overrides the config's stream address")]
+    pub host: Option<String>,
+    #[arg(
+        long,
+        help = "Streaming port; overrides the config's (default 8087, 8089 for TLS)"
+    )]
+    pub port: Option<u16>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format; tak-v1 negotiates the upgrade from XML. Defaults to the config's"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds to wait for the server's protocol announcement before staying on XML"
+    )]
+    pub negotiation_timeout: u64,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// TAK Server streaming port used when `--host` is given without a port
+/// and the config has no stream endpoint: 8087 for TCP, 8089 for TLS.
+const DEFAULT_TCP_STREAMING_PORT: u16 = 8087;
+const DEFAULT_TLS_STREAMING_PORT: u16 = 8089;
+
+pub(crate) fn run_connect(args: ConnectArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    let client = StreamingClient::new(connect_client_config(&args, config.as_ref())?)
+        .map_err(CliError::ServerConfig)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    runtime.block_on(async move {
+        let stream = client.open().await?;
+        let negotiation = stream.negotiation.as_ref().map_or_else(
+            || "not_attempted".to_owned(),
+            |outcome| state_code(outcome.state),
+        );
+        tracing::info!(
+            endpoint = %stream.session.endpoint,
+            framing = ?stream.session.framing,
+            negotiation = %negotiation,
+            "connect_established"
+        );
+        connect_session(
+            stream,
+            &client.config().transport,
+            tokio::io::BufReader::new(tokio::io::stdin()),
+            io::stdout(),
+        )
+        .await
+    })
+}
+
+/// Builds the streaming client config from the YAML config's TCP/TLS
+/// protocol, with `--host`/`--port` replacing its address. Without a config
+/// the stream is plain TCP.
+fn connect_client_config(
+    args: &ConnectArgs,
+    config: Option<&rustak_config::RustakConfig>,
+) -> Result<rustak_server::ServerClientConfig, CliError> {
+    let mut transport = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let (configured_addr, server_name) = match &transport.protocol {
+        Protocol::Tcp { addr } if config.is_some() => (Some(*addr), None),
+        Protocol::Tls { addr, server_name } => (Some(*addr), Some(server_name.clone())),
+        _ => (None, None),
+    };
+    let tls = server_name.is_some();
+
+    let addr = match (args.host.as_deref(), configured_addr) {
+        (Some(host), configured) => {
+            let port = args
+                .port
+                .or(configured.map(|addr| addr.port()))
+                .unwrap_or(if tls {
+                    DEFAULT_TLS_STREAMING_PORT
+                } else {
+                    DEFAULT_TCP_STREAMING_PORT
+                });
+            resolve_host(host, port)?
+        }
+        (None, Some(mut addr)) => {
+            if let Some(port) = args.port {
+                addr.set_port(port);
+            }
+            addr
+        }
+        (None, None) => return Err(CliError::ConnectEndpointRequired),
+    };
+    let host = args
+        .host
+        .clone()
+        .or(server_name)
+        .unwrap_or_else(|| addr.ip().to_string());
+    transport.protocol = if tls {
+        Protocol::Tls {
+            addr,
+            server_name: host.clone(),
+        }
+    } else {
+        Protocol::Tcp { addr }
+    };
+    if let Some(format) = args.format {
+        transport.wire_format = WireFormat::from(format);
+    }
+
+    Ok(rustak_server::ServerClientConfig {
+        endpoint: format!(
+            "{}://{host}:{}",
+            if tls { "https" } else { "http" },
+            addr.port()
+        ),
+        transport,
+        crypto: config.and_then(tls_crypto_config),
+        negotiation: rustak_wire::NegotiationConfig {
+            streaming_timeout: Duration::from_secs(args.negotiation_timeout),
+            downgrade_policy: DowngradePolicy::FailOpen,
+            ..rustak_wire::NegotiationConfig::default()
+        },
+        ..rustak_server::ServerClientConfig::default()
+    })
+}
+
+fn resolve_host(host: &str, port: u16) -> Result<SocketAddr, CliError> {
+    let resolve_error = |source| CliError::ResolveHost {
+        host: host.to_owned(),
+        source,
+    };
+    (host, port)
+        .to_socket_addrs()
+        .map_err(resolve_error)?
+        .next()
+        .ok_or_else(|| resolve_error(io::Error::from(io::ErrorKind::NotFound)))
+}
+
+/// Interactive half of `rustak connect`: prints every received event (and
+/// any that arrived during negotiation) as one line of CoT XML on `out`,
+/// and sends each non-empty line of `input` as an event. Ends when the
+/// server closes the stream; once `input` is exhausted the write side is
+/// shut down and remaining events are still printed.
+pub async fn connect_session<R, W>(
+    stream: StreamingConnection,
+    transport: &TransportConfig,
+    input: R,
+    mut out: W,
+) -> Result<(), CliError>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+    W: Write,
+{
+    let wire_format = match stream.session.framing {
+        TransportFraming::XmlNewlineDelimited => WireFormat::Xml,
+        TransportFraming::TakProtocolU32LengthPrefixed
+        | TransportFraming::TakProtocolMeshHeader => WireFormat::TakProtocolV1,
+    };
+    let framed = TransportConfig {
+        wire_format,
+        ..transport.clone()
+    };
+    for event in stream
+        .negotiation
+        .into_iter()
+        .flat_map(|outcome| outcome.early_events)
+    {
+        connect_print(&mut out, &event)?;
+    }
+
+    let (reader, writer) = tokio::io::split(stream.connection.into_inner());
+    let mut receiver = TransportReceiver::new(reader, &framed)?;
+    let mut sender = TransportSender::new(writer, &framed)?;
+
+    let receive = async {
+        loop {
+            let frame = match receiver.recv_frame().await {
+                Ok(frame) => frame,
+                Err(error) => {
+                    tracing::info!(reason = %error, "connect_closed");
+                    return Ok(());
+                }
+            };
+            match rustak_wire::decode_payload_for_format(&frame, wire_format) {
+                Ok(cot_xml) => connect_print(&mut out, &cot_xml)?,
+                Err(error) => tracing::warn!(error = %error, "connect_decode_error"),
+            }
+        }
+    };
+    let send = async {
+        let mut lines = input.lines();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|source| CliError::StdinRead { source })?
+        {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match rustak_wire::encode_payload_for_format(line.as_bytes(), wire_format) {
+                Ok(payload) => {
+                    sender.send_frame(&payload).await?;
+                    sender.flush().await?;
+                }
+                Err(error) => tracing::warn!(error = %error, "connect_encode_error"),
+            }
+        }
+        sender
+            .into_inner()
+            .shutdown()
+            .await
+            .map_err(|error| CliError::Transport(error.into()))
+    };
+
+    tokio::pin!(receive);
+    tokio::select! {
+        result = &mut receive => result,
+        result = send => {
+            result?;
+            receive.await
+        }
+    }
+}
+
+fn connect_print<W: Write>(out: &mut W, cot_xml: &[u8]) -> Result<(), CliError> {
+    out.write_all(cot_xml)
+        .and_then(|()| out.write_all(b"\n"))
+        .and_then(|()| out.flush())
+        .map_err(|source| CliError::StdoutWrite { source })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+    use rustak_core::time::TimestampUtc;
+    use rustak_server::StreamingClient;
+    use rustak_transport::{Protocol, TransportConfig, TransportReceiver, TransportSender};
+    use rustak_wire::WireFormat;
+
+    use super::{connect_client_config, connect_session, ConnectArgs};
+    use crate::tests::replay_event;
+    use crate::{Cli, CliError, Command};
+
+    fn connect_args(argv: &[&str]) -> ConnectArgs {
+        let cli =
+            Cli::try_parse_from(["rustak", "connect"].iter().chain(argv)).expect("connect args");
+        let Command::Connect(args) = cli.command else {
+            panic!("expected connect");
+        };
+        args
+    }
+
+    #[test]
+    fn connect_overrides_config_endpoint_with_host_and_port() {
+        let error =
+            connect_client_config(&connect_args(&[]), None).expect_err("connect needs an endpoint");
+        assert!(matches!(error, CliError::ConnectEndpointRequired));
+
+        let config = connect_client_config(
+            &connect_args(&["--host", "127.0.0.1", "--format", "tak-v1"]),
+            None,
+        )
+        .expect("config");
+        assert_eq!(config.endpoint, "http://127.0.0.1:8087");
+        assert_eq!(
+            config.transport.protocol,
+            Protocol::Tcp {
+                addr: "127.0.0.1:8087".parse().expect("addr")
+            }
+        );
+        assert_eq!(config.transport.wire_format, WireFormat::TakProtocolV1);
+        assert_eq!(config.negotiation.streaming_timeout, Duration::from_secs(5));
+
+        let mut file = rustak_config::RustakConfig::default();
+        file.transport.protocol = Protocol::Tls {
+            addr: "10.0.0.1:8089".parse().expect("addr"),
+            server_name: "tak.example".to_owned(),
+        };
+        let config =
+            connect_client_config(&connect_args(&["--port", "9000"]), Some(&file)).expect("config");
+        assert_eq!(config.endpoint, "https://tak.example:9000");
+        assert_eq!(
+            config.transport.protocol,
+            Protocol::Tls {
+                addr: "10.0.0.1:9000".parse().expect("addr"),
+                server_name: "tak.example".to_owned(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn connect_session_streams_events_both_ways_after_negotiation() {
+        use rustak_wire::negotiation::events::TakControlMessage;
+        use rustak_wire::TakProtocolVersion;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr").to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let control = |message: TakControlMessage| {
+                let mut line = message.encode("server", TimestampUtc::now());
+                line.push(b'\n');
+                line
+            };
+            stream
+                .write_all(&control(TakControlMessage::ProtocolSupport {
+                    version: Some(TakProtocolVersion::V1),
+                }))
+                .await
+                .expect("announce");
+            while stream.read_u8().await.expect("request") != b'\n' {}
+            stream
+                .write_all(&control(TakControlMessage::Response { accepted: true }))
+                .await
+                .expect("respond");
+
+            let tak = TransportConfig {
+                wire_format: WireFormat::TakProtocolV1,
+                ..TransportConfig::default()
+            };
+            let (reader, writer) = tokio::io::split(stream);
+            let mut sender = TransportSender::new(writer, &tak).expect("sender");
+            let payload = rustak_wire::encode_payload_for_format(
+                &replay_event("srv", "2023-11-14T22:13:20.000Z"),
+                WireFormat::TakProtocolV1,
+            )
+            .expect("encode");
+            sender.send_frame(&payload).await.expect("send");
+            sender.flush().await.expect("flush");
+
+            let mut receiver = TransportReceiver::new(reader, &tak).expect("receiver");
+            let frame = receiver.recv_frame().await.expect("client event");
+            drop(sender);
+            rustak_wire::decode_payload_for_format(&frame, WireFormat::TakProtocolV1)
+                .expect("decode")
+        });
+
+        let args = connect_args(&["--host", "127.0.0.1", "--format", "tak-v1"]);
+        let mut config = connect_client_config(&args, None).expect("config");
+        config.transport.protocol = Protocol::Tcp {
+            addr: addr.parse().expect("addr"),
+        };
+        let client = StreamingClient::new(config).expect("client");
+        let stream = client.open().await.expect("open");
+
+        let client_event = replay_event("cli", "2023-11-14T22:13:20.000Z");
+        let mut out = Vec::new();
+        connect_session(
+            stream,
+            &client.config().transport,
+            &[b"\n".as_slice(), &client_event, b"\n"].concat()[..],
+            &mut out,
+        )
+        .await
+        .expect("session");
+
+        assert_eq!(server.await.expect("server"), client_event);
+        let mut expected = replay_event("srv", "2023-11-14T22:13:20.000Z");
+        expected.push(b'\n');
+        assert_eq!(out, expected);
+    }
+}
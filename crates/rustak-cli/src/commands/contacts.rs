@@ -0,0 +1,179 @@
+//! `rustak contacts`: contact directory export and import.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use clap::{Args, Subcommand};
+use rustak::RustakError;
+use rustak_commo::ContactTracker;
+use rustak_record::TakrecReader;
+
+use crate::{config_limits, load_optional_config, parse_event, write_output_bytes, CliError};
+
+#[derive(Debug, Args)]
+pub struct ContactsArgs {
+    #[command(subcommand)]
+    pub action: ContactsAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ContactsAction {
+    /// Build a UID to callsign/team directory from a takrec recording.
+    Export(ContactsExportArgs),
+    /// Load a directory file and list its contacts.
+    Import(ContactsImportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ContactsExportArgs {
+    #[arg(long, help = "Takrec recording to scan for contact details")]
+    pub recording: PathBuf,
+    #[arg(long, help = "Directory JSON path; defaults to stdout when omitted")]
+    pub output: Option<PathBuf>,
+    #[arg(long, default_value_t = 10_000)]
+    pub max_contacts: usize,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ContactsImportArgs {
+    #[arg(long, help = "Directory JSON written by `contacts export`")]
+    pub input: PathBuf,
+    #[arg(long, default_value_t = 10_000)]
+    pub max_contacts: usize,
+}
+
+pub(crate) fn run_contacts_export(args: ContactsExportArgs) -> Result<(), CliError> {
+    let limits = config_limits(load_optional_config(args.config.as_deref())?.as_ref());
+    let source = fs::File::open(&args.recording).map_err(|source| CliError::InputRead {
+        path: args.recording.display().to_string(),
+        source,
+    })?;
+    let record = |source| CliError::Facade(RustakError::Record(source));
+    let mut reader = TakrecReader::new(io::BufReader::new(source)).map_err(record)?;
+
+    let mut tracker = ContactTracker::new(args.max_contacts)?;
+    // Chunks without a recorded capture time share one timestamp so later
+    // chunks win ties and the directory reflects the end of the recording.
+    let now = SystemTime::now();
+    while let Some(chunk) = reader.next_chunk() {
+        let (_, payload) = chunk.map_err(record)?;
+        if let Some(event) = parse_event(&payload, &limits) {
+            tracker.observe_event(&event, reader.chunk_captured().unwrap_or(now));
+        }
+    }
+
+    let mut json = tracker.export_json();
+    json.push('\n');
+    write_output_bytes(json.as_bytes(), args.output.as_deref())?;
+    tracing::info!(
+        chunks = reader.chunks_read(),
+        contacts = tracker.len(),
+        "contacts_export"
+    );
+    Ok(())
+}
+
+pub(crate) fn run_contacts_import(args: ContactsImportArgs) -> Result<(), CliError> {
+    let json = fs::read_to_string(&args.input).map_err(|source| CliError::InputRead {
+        path: args.input.display().to_string(),
+        source,
+    })?;
+    let mut tracker = ContactTracker::new(args.max_contacts)?;
+    let imported = tracker.import_json(&json)?;
+    for line in contact_lines(&tracker) {
+        println!("{line}");
+    }
+    println!(
+        "contacts_import entries={imported} contacts={}",
+        tracker.len()
+    );
+    Ok(())
+}
+
+fn contact_lines(tracker: &ContactTracker) -> Vec<String> {
+    tracker
+        .entries()
+        .into_iter()
+        .map(|entry| {
+            format!(
+                "contact uid={} callsign={:?} team={:?} role={:?}",
+                entry.uid,
+                entry.callsign.as_deref().unwrap_or(""),
+                entry.group.as_ref().map_or("", |group| group.name.as_str()),
+                entry.group.as_ref().map_or("", |group| group.role.as_str()),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::contact_lines;
+    use crate::tests::replay_event;
+    use crate::{execute_command, Cli};
+
+    #[test]
+    fn contacts_export_then_import_resolves_callsigns() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_contacts_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let recording = dir.join("in.takrec");
+        let directory = dir.join("contacts.json");
+
+        let mut writer =
+            rustak_record::TakrecWriter::new(Vec::new(), rustak_record::TakrecHeader::default())
+                .expect("writer");
+        for (uid, detail) in [
+            ("ANDROID-1", "<contact callsign=\"Old\"/>"),
+            (
+                "ANDROID-1",
+                "<contact callsign=\"Red Fox\"/><__group name=\"Cyan\" role=\"Medic\"/>",
+            ),
+            ("sensor-1", ""),
+        ] {
+            let chunk = String::from_utf8(replay_event(uid, "2024-01-01T00:00:00Z"))
+                .expect("utf8")
+                .replace("</event>", &format!("<detail>{detail}</detail></event>"));
+            writer.append_chunk(chunk.as_bytes()).expect("chunk");
+        }
+        std::fs::write(&recording, writer.into_inner().expect("inner")).expect("write input");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "contacts",
+            "export",
+            "--recording",
+            recording.to_str().expect("utf8 path"),
+            "--output",
+            directory.to_str().expect("utf8 path"),
+        ])
+        .expect("export args parse");
+        execute_command(cli.command).expect("export succeeds");
+
+        let mut tracker = rustak_commo::ContactTracker::new(16).expect("capacity");
+        let json = std::fs::read_to_string(&directory).expect("directory written");
+        assert_eq!(tracker.import_json(&json), Ok(1));
+        assert_eq!(
+            contact_lines(&tracker),
+            vec![
+                "contact uid=ANDROID-1 callsign=\"Red Fox\" team=\"Cyan\" role=\"Medic\""
+                    .to_owned()
+            ]
+        );
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "contacts",
+            "import",
+            "--input",
+            directory.to_str().expect("utf8 path"),
+        ])
+        .expect("import args parse");
+        execute_command(cli.command).expect("import succeeds");
+    }
+}
@@ -0,0 +1,362 @@
+//! `rustak convert`: CoT between XML and TAK protocol v1, for one file or a
+//! directory tree.
+
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use clap::Args;
+use rustak_limits::{CodedError, Limits};
+
+use crate::{
+    config_limits, convert_with_warnings, load_optional_config, read_input_bytes, report_warnings,
+    validate_wire_defaults, write_output_bytes, CliError, ConvertFormat, FailOn,
+};
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    #[arg(long, value_enum)]
+    pub from: ConvertFormat,
+    #[arg(long, value_enum)]
+    pub to: ConvertFormat,
+    #[arg(long, help = "Input file path; defaults to stdin when omitted")]
+    pub input: Option<PathBuf>,
+    #[arg(long, help = "Output file path; defaults to stdout when omitted")]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        conflicts_with_all = ["input", "output"],
+        requires = "output_dir",
+        help = "Convert every file under this directory tree"
+    )]
+    pub input_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "input_dir",
+        help = "Where `--input-dir` outputs go, at the same relative paths"
+    )]
+    pub output_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "input_dir",
+        help = "Files converted in parallel; defaults to the available cores"
+    )]
+    pub jobs: Option<NonZeroUsize>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = FailOn::Errors)]
+    pub fail_on: FailOn,
+}
+
+pub(crate) fn run_convert(args: ConvertArgs) -> Result<(), CliError> {
+    let limits = config_limits(load_optional_config(args.config.as_deref())?.as_ref());
+    validate_wire_defaults()?;
+    if let Some(input_dir) = &args.input_dir {
+        return run_convert_batch(&args, input_dir, &limits);
+    }
+    let payload = read_input_bytes(args.input.as_deref())?;
+    let (converted, warnings) = convert_with_warnings(&payload, args.from, args.to, &limits)?;
+    write_output_bytes(&converted, args.output.as_deref())?;
+    report_warnings("convert", &warnings, args.fail_on)
+}
+
+/// `convert --input-dir`: converts every regular file under `input_dir`
+/// with `--jobs` worker threads, then prints one `convert_summary` line.
+/// Per-file failures are reported and do not stop the batch.
+fn run_convert_batch(
+    args: &ConvertArgs,
+    input_dir: &Path,
+    limits: &Limits,
+) -> Result<(), CliError> {
+    let output_dir = args
+        .output_dir
+        .as_deref()
+        .ok_or(CliError::ConvertOutputDirRequired)?;
+    fs::create_dir_all(output_dir).map_err(|source| CliError::OutputWrite {
+        path: output_dir.display().to_string(),
+        source,
+    })?;
+    let input_root = canonical_dir(input_dir)?;
+    let output_root = canonical_dir(output_dir)?;
+    if input_root == output_root {
+        return Err(CliError::ConvertOutputDirOverlapsInput {
+            path: output_dir.display().to_string(),
+        });
+    }
+
+    let files = batch_input_files(&input_root, &output_root)?;
+    let jobs = args
+        .jobs
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get);
+    let outcomes = convert_files_in_parallel(&files, &input_root, output_dir, args, jobs, limits);
+
+    let mut warnings = 0;
+    let mut failed = 0;
+    for (relative, outcome) in files.iter().zip(&outcomes) {
+        match outcome {
+            Ok(file_warnings) => {
+                for warning in file_warnings {
+                    tracing::warn!(
+                        path = %relative.display(),
+                        warning = %warning,
+                        "convert_warning"
+                    );
+                }
+                warnings += file_warnings.len();
+            }
+            Err(error) => {
+                failed += 1;
+                tracing::warn!(
+                    path = %relative.display(),
+                    error = %error.coded_message(),
+                    "convert_failed"
+                );
+            }
+        }
+    }
+    println!(
+        "convert_summary files={} converted={} failed={failed} warnings={warnings} jobs={jobs}",
+        files.len(),
+        files.len() - failed
+    );
+
+    if failed > 0 {
+        return Err(CliError::ConvertBatchFailed {
+            failed,
+            total: files.len(),
+        });
+    }
+    if args.fail_on == FailOn::Warnings && warnings > 0 {
+        return Err(CliError::WarningThreshold {
+            command: "convert",
+            warnings,
+        });
+    }
+    Ok(())
+}
+
+fn canonical_dir(path: &Path) -> Result<PathBuf, CliError> {
+    fs::canonicalize(path).map_err(|source| CliError::InputDirRead {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+/// Regular files under `root`, relative to it and sorted. Symlinks are not
+/// followed, and `skip` (the output directory) is left out when nested.
+fn batch_input_files(root: &Path, skip: &Path) -> Result<Vec<PathBuf>, CliError> {
+    let read_error = |path: &Path| {
+        let path = path.display().to_string();
+        move |source| CliError::InputDirRead { path, source }
+    };
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).map_err(read_error(&dir))? {
+            let entry = entry.map_err(read_error(&dir))?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(read_error(&path))?;
+            if file_type.is_dir() && path != skip {
+                pending.push(path);
+            } else if file_type.is_file() {
+                if let Ok(relative) = path.strip_prefix(root) {
+                    files.push(relative.to_path_buf());
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Converts `files` on up to `jobs` threads; results are in `files` order.
+fn convert_files_in_parallel(
+    files: &[PathBuf],
+    input_root: &Path,
+    output_dir: &Path,
+    args: &ConvertArgs,
+    jobs: usize,
+    limits: &Limits,
+) -> Vec<Result<Vec<String>, CliError>> {
+    let next = AtomicUsize::new(0);
+    let mut outcomes = std::thread::scope(|scope| {
+        let workers = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    while let Some(relative) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let outcome = convert_file(
+                            &input_root.join(relative),
+                            &output_dir.join(relative),
+                            args.from,
+                            args.to,
+                            limits,
+                        );
+                        done.push((relative, outcome));
+                    }
+                    done
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Vec<_>>()
+    });
+    outcomes.sort_by_key(|(relative, _)| *relative);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn convert_file(
+    input: &Path,
+    output: &Path,
+    from: ConvertFormat,
+    to: ConvertFormat,
+    limits: &Limits,
+) -> Result<Vec<String>, CliError> {
+    let payload = read_input_bytes(Some(input))?;
+    let (converted, warnings) = convert_with_warnings(&payload, from, to, limits)?;
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|source| CliError::OutputWrite {
+            path: parent.display().to_string(),
+            source,
+        })?;
+    }
+    write_output_bytes(&converted, Some(output))?;
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use clap::Parser;
+
+    use rustak_limits::Limits;
+
+    use super::ConvertArgs;
+    use crate::tests::replay_event;
+    use crate::{
+        convert_with_warnings, execute_command, Cli, CliError, Command, ConvertFormat, ExitStatus,
+        FailOn,
+    };
+
+    #[test]
+    fn convert_round_trip_between_xml_and_tak_v1_is_lossless() {
+        let xml = replay_event("unit-test", "2023-11-14T22:13:20.000Z");
+        let limits = Limits::default();
+        let (encoded, _) =
+            convert_with_warnings(&xml, ConvertFormat::Xml, ConvertFormat::TakV1, &limits)
+                .expect("xml->tak conversion should succeed");
+        let (decoded, _) =
+            convert_with_warnings(&encoded, ConvertFormat::TakV1, ConvertFormat::Xml, &limits)
+                .expect("tak->xml conversion should succeed");
+        assert_eq!(decoded, xml);
+    }
+
+    #[test]
+    fn convert_warnings_parse_under_the_given_limits() {
+        let xml = String::from_utf8(replay_event("unit-test", "2023-11-14T22:13:20.000Z"))
+            .expect("utf8")
+            .replace("</event>", "<detail><remarks/><link/></detail></event>");
+        let (_, warnings) = convert_with_warnings(
+            xml.as_bytes(),
+            ConvertFormat::Xml,
+            ConvertFormat::Xml,
+            &Limits::default(),
+        )
+        .expect("convert");
+        assert!(warnings.is_empty(), "{warnings:?}");
+
+        let tight = Limits {
+            max_detail_elements: 1,
+            ..Limits::default()
+        };
+        let (_, warnings) = convert_with_warnings(
+            xml.as_bytes(),
+            ConvertFormat::Xml,
+            ConvertFormat::Xml,
+            &tight,
+        )
+        .expect("convert");
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("kind=invalid_event "),
+            "{warnings:?}"
+        );
+    }
+
+    #[test]
+    fn convert_input_dir_mirrors_paths_and_reports_failures() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_convert_{}", std::process::id()));
+        let input_dir = dir.join("captures");
+        let output_dir = dir.join("converted");
+        std::fs::create_dir_all(input_dir.join("site-a/day-1")).expect("input tree");
+        for (path, uid) in [("one.xml", "one"), ("site-a/day-1/two.xml", "two")] {
+            std::fs::write(
+                input_dir.join(path),
+                replay_event(uid, "2023-11-14T22:13:20.000Z"),
+            )
+            .expect("write input");
+        }
+        std::fs::write(input_dir.join("site-a/broken.xml"), b"").expect("write input");
+
+        let convert = |jobs| {
+            execute_command(Command::Convert(ConvertArgs {
+                from: ConvertFormat::Xml,
+                to: ConvertFormat::TakV1,
+                input: None,
+                output: None,
+                input_dir: Some(input_dir.clone()),
+                output_dir: Some(output_dir.clone()),
+                jobs: NonZeroUsize::new(jobs),
+                config: None,
+                fail_on: FailOn::Errors,
+            }))
+        };
+        let error = convert(4).expect_err("the empty file fails");
+        assert!(matches!(
+            error,
+            CliError::ConvertBatchFailed {
+                failed: 1,
+                total: 3
+            }
+        ));
+        assert_eq!(error.exit_status(), ExitStatus::Validation);
+
+        let converted =
+            std::fs::read(output_dir.join("site-a/day-1/two.xml")).expect("mirrored output");
+        let (decoded, _) = convert_with_warnings(
+            &converted,
+            ConvertFormat::TakV1,
+            ConvertFormat::Xml,
+            &Limits::default(),
+        )
+        .expect("output decodes");
+        assert_eq!(decoded, replay_event("two", "2023-11-14T22:13:20.000Z"));
+        assert!(output_dir.join("one.xml").is_file());
+        assert!(!output_dir.join("site-a/broken.xml").exists());
+
+        std::fs::remove_file(input_dir.join("site-a/broken.xml")).expect("remove broken");
+        convert(1).expect("remaining files convert");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "convert",
+            "--from",
+            "xml",
+            "--to",
+            "tak-v1",
+            "--input-dir",
+            "captures",
+        ]);
+        assert!(cli.is_err(), "--input-dir requires --output-dir");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
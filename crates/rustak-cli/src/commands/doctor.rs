@@ -0,0 +1,569 @@
+//! `rustak doctor`: environment checks to run before going live.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::Args;
+use rustak_core::time::TimestampUtc;
+use rustak_transport::{Protocol, TransportConfig, UdpTarget, UdpTransport};
+
+use crate::{load_optional_config, CliError, FailOn};
+
+#[derive(Debug, Args)]
+pub struct DoctorArgs {
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Directory recordings will be written to (default: current directory)"
+    )]
+    pub record_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DAYS",
+        default_value_t = 30,
+        help = "Warn when a certificate expires within DAYS days"
+    )]
+    pub cert_warn_days: u64,
+    #[arg(
+        long,
+        value_name = "MIB",
+        default_value_t = 1024,
+        help = "Warn when the recording directory has less than MIB MiB free"
+    )]
+    pub min_free_mb: u64,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds to wait for each reachability check"
+    )]
+    pub timeout: u64,
+    #[arg(long, value_enum, default_value_t = FailOn::Errors)]
+    pub fail_on: FailOn,
+}
+
+/// Outcome of one `rustak doctor` check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DoctorCheck {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    /// `doctor check=<name> status=<pass|warn|fail> detail="..."`
+    #[must_use]
+    pub fn line(&self) -> String {
+        format!(
+            "doctor check={} status={} detail=\"{}\"",
+            self.name,
+            self.status.as_str(),
+            self.detail.replace('"', "'")
+        )
+    }
+}
+
+/// Thresholds for the `doctor` checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoctorOptions {
+    pub record_dir: PathBuf,
+    pub cert_warn_within: Duration,
+    pub min_free_bytes: u64,
+    pub connect_timeout: Duration,
+}
+
+pub(crate) fn run_doctor(args: DoctorArgs) -> Result<(), CliError> {
+    let (config, mut checks) = match load_optional_config(args.config.as_deref()) {
+        Ok(Some(config)) => (
+            Some(config),
+            vec![DoctorCheck::new("config", CheckStatus::Pass, "valid")],
+        ),
+        Ok(None) => (
+            None,
+            vec![DoctorCheck::new(
+                "config",
+                CheckStatus::Pass,
+                "no --config given; checking built-in defaults",
+            )],
+        ),
+        Err(error) => (
+            None,
+            vec![DoctorCheck::new(
+                "config",
+                CheckStatus::Fail,
+                error.to_string(),
+            )],
+        ),
+    };
+    let options = DoctorOptions {
+        record_dir: args.record_dir.unwrap_or_else(|| PathBuf::from(".")),
+        cert_warn_within: Duration::from_secs(args.cert_warn_days * 86_400),
+        min_free_bytes: args.min_free_mb * 1024 * 1024,
+        connect_timeout: Duration::from_secs(args.timeout),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    checks.extend(runtime.block_on(doctor_checks(
+        config.as_ref(),
+        &options,
+        TimestampUtc::now(),
+    )));
+
+    let mut out = io::stdout().lock();
+    for check in &checks {
+        writeln!(out, "{}", check.line()).map_err(|source| CliError::StdoutWrite { source })?;
+    }
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let (failures, warnings) = (count(CheckStatus::Fail), count(CheckStatus::Warn));
+    writeln!(
+        out,
+        "doctor pass={} warn={warnings} fail={failures}",
+        count(CheckStatus::Pass)
+    )
+    .map_err(|source| CliError::StdoutWrite { source })?;
+
+    if failures > 0 {
+        return Err(CliError::DoctorFailed { failures });
+    }
+    if args.fail_on == FailOn::Warnings && warnings > 0 {
+        return Err(CliError::WarningThreshold {
+            command: "doctor",
+            warnings,
+        });
+    }
+    Ok(())
+}
+
+/// Runs every environment check other than config loading against `config`
+/// (built-in defaults when `None`).
+pub async fn doctor_checks(
+    config: Option<&rustak_config::RustakConfig>,
+    options: &DoctorOptions,
+    now: TimestampUtc,
+) -> Vec<DoctorCheck> {
+    let transport = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let mut checks = Vec::new();
+    if let Some(certificates) = config.and_then(|config| config.certificates.as_ref()) {
+        for (field, path) in [
+            ("ca_cert", &certificates.ca_cert),
+            ("client_cert", &certificates.client_cert),
+        ] {
+            checks.push(doctor_certificate(field, Path::new(path), options, now));
+        }
+        checks.push(doctor_private_key(Path::new(&certificates.client_key)));
+    }
+    checks.extend(doctor_endpoint(&transport, options.connect_timeout).await);
+    checks.push(doctor_multicast(&transport));
+    checks.push(doctor_crypto_provider(
+        config.and_then(|config| config.crypto.as_ref()),
+    ));
+    checks.push(doctor_disk(&options.record_dir, options.min_free_bytes));
+    checks
+}
+
+fn doctor_certificate(
+    field: &'static str,
+    path: &Path,
+    options: &DoctorOptions,
+    now: TimestampUtc,
+) -> DoctorCheck {
+    let name = format!("certificate.{field}");
+    let pem = match fs::read_to_string(path) {
+        Ok(pem) => pem,
+        Err(error) => {
+            return DoctorCheck::new(
+                name,
+                CheckStatus::Fail,
+                format!("{}: {error}", path.display()),
+            )
+        }
+    };
+    certificate_expiry_check(name, path, &pem, options.cert_warn_within, now)
+}
+
+fn certificate_expiry_check(
+    name: String,
+    path: &Path,
+    pem: &str,
+    warn_within: Duration,
+    now: TimestampUtc,
+) -> DoctorCheck {
+    let validity =
+        match rustak_crypto::certs::pem_certificates("certificate", pem).and_then(|certificates| {
+            certificates
+                .iter()
+                .map(|certificate| rustak_crypto::CertificateInfo::from_der(certificate))
+                .collect::<Result<Vec<_>, _>>()
+        }) {
+            Ok(validity) => validity,
+            Err(error) => {
+                return DoctorCheck::new(
+                    name,
+                    CheckStatus::Fail,
+                    format!("{}: {error}", path.display()),
+                )
+            }
+        };
+    let Some(not_after) = validity
+        .iter()
+        .map(|certificate| TimestampUtc::from_system_time(certificate.not_after))
+        .min()
+    else {
+        return DoctorCheck::new(name, CheckStatus::Fail, "no certificates");
+    };
+    let expires = not_after.to_rfc3339_millis();
+    if let Some(not_before) = validity
+        .iter()
+        .map(|certificate| TimestampUtc::from_system_time(certificate.not_before))
+        .filter(|not_before| *not_before > now)
+        .max()
+    {
+        return DoctorCheck::new(
+            name,
+            CheckStatus::Fail,
+            format!("not valid until {}", not_before.to_rfc3339_millis()),
+        );
+    }
+    if not_after <= now {
+        return DoctorCheck::new(name, CheckStatus::Fail, format!("expired {expires}"));
+    }
+    let remaining_days = (not_after.unix_nanos() - now.unix_nanos()) / 86_400_000_000_000;
+    let status = if not_after.unix_nanos() - now.unix_nanos()
+        <= i128::try_from(warn_within.as_nanos()).unwrap_or(i128::MAX)
+    {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    };
+    DoctorCheck::new(
+        name,
+        status,
+        format!("expires {expires} ({remaining_days} days)"),
+    )
+}
+
+fn doctor_private_key(path: &Path) -> DoctorCheck {
+    let name = "certificate.client_key";
+    match fs::read_to_string(path) {
+        Ok(pem) if pem.contains("PRIVATE KEY-----") => {
+            DoctorCheck::new(name, CheckStatus::Pass, "readable")
+        }
+        Ok(_) => DoctorCheck::new(
+            name,
+            CheckStatus::Fail,
+            format!("{}: no PRIVATE KEY block", path.display()),
+        ),
+        Err(error) => DoctorCheck::new(
+            name,
+            CheckStatus::Fail,
+            format!("{}: {error}", path.display()),
+        ),
+    }
+}
+
+/// DNS resolution of the TLS server name and a TCP connect to the stream
+/// address. UDP and WebSocket transports have no stream endpoint to probe.
+async fn doctor_endpoint(transport: &TransportConfig, timeout: Duration) -> Vec<DoctorCheck> {
+    let (addr, server_name) = match &transport.protocol {
+        Protocol::Tcp { addr } => (*addr, None),
+        Protocol::Tls { addr, server_name } => (*addr, Some(server_name.as_str())),
+        Protocol::Udp { .. } => {
+            return vec![DoctorCheck::new(
+                "endpoint",
+                CheckStatus::Pass,
+                "udp transport; no stream endpoint",
+            )]
+        }
+        Protocol::WebSocket { url } => {
+            return vec![DoctorCheck::new(
+                "endpoint",
+                CheckStatus::Warn,
+                format!("{url}: websocket reachability is not checked"),
+            )]
+        }
+    };
+
+    let mut checks = Vec::new();
+    if let Some(server_name) = server_name {
+        checks.push(
+            match tokio::net::lookup_host((server_name, addr.port())).await {
+                Ok(mut resolved) => match resolved.next() {
+                    Some(resolved) => DoctorCheck::new(
+                        "endpoint.dns",
+                        CheckStatus::Pass,
+                        format!("{server_name} -> {}", resolved.ip()),
+                    ),
+                    None => DoctorCheck::new(
+                        "endpoint.dns",
+                        CheckStatus::Warn,
+                        format!("{server_name} has no addresses"),
+                    ),
+                },
+                Err(error) => DoctorCheck::new(
+                    "endpoint.dns",
+                    CheckStatus::Warn,
+                    format!("{server_name}: {error}"),
+                ),
+            },
+        );
+    }
+    checks.push(
+        match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => DoctorCheck::new("endpoint.tcp", CheckStatus::Pass, format!("{addr}")),
+            Ok(Err(error)) => DoctorCheck::new(
+                "endpoint.tcp",
+                CheckStatus::Fail,
+                format!("{addr}: {error}"),
+            ),
+            Err(_) => DoctorCheck::new(
+                "endpoint.tcp",
+                CheckStatus::Fail,
+                format!("{addr}: no answer within {}s", timeout.as_secs()),
+            ),
+        },
+    );
+    checks
+}
+
+/// Binds the configured multicast socket, which joins the group on the
+/// interfaces the transport would use.
+fn doctor_multicast(transport: &TransportConfig) -> DoctorCheck {
+    let Protocol::Udp {
+        target: UdpTarget::Multicast { group, port },
+        ..
+    } = &transport.protocol
+    else {
+        return DoctorCheck::new("multicast", CheckStatus::Pass, "no multicast target");
+    };
+    match UdpTransport::bind(transport) {
+        Ok(udp) => {
+            let (joined, failed): (Vec<_>, Vec<_>) =
+                udp.multicast_joins().iter().partition(|join| join.joined());
+            let joined = joined
+                .iter()
+                .map(|join| join.interface.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if failed.is_empty() {
+                return DoctorCheck::new(
+                    "multicast",
+                    CheckStatus::Pass,
+                    format!("joined {group}:{port} on {joined}"),
+                );
+            }
+            let failed = failed
+                .iter()
+                .map(|join| {
+                    format!(
+                        "{} ({})",
+                        join.interface,
+                        join.error.as_deref().unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            DoctorCheck::new(
+                "multicast",
+                CheckStatus::Warn,
+                format!("joined {group}:{port} on {joined}; failed on {failed}"),
+            )
+        }
+        Err(error) => DoctorCheck::new(
+            "multicast",
+            CheckStatus::Fail,
+            format!("{group}:{port}: {error}"),
+        ),
+    }
+}
+
+fn doctor_crypto_provider(crypto: Option<&rustak_config::CryptoConfig>) -> DoctorCheck {
+    let name = "crypto_provider";
+    let Some(crypto) = crypto else {
+        return DoctorCheck::new(name, CheckStatus::Pass, "no crypto section");
+    };
+    let (mode, label) = match crypto.provider {
+        rustak_config::CryptoProvider::Ring => (rustak_crypto::CryptoProviderMode::Ring, "ring"),
+        rustak_config::CryptoProvider::AwsLcRs => {
+            (rustak_crypto::CryptoProviderMode::AwsLcRs, "aws-lc-rs")
+        }
+        rustak_config::CryptoProvider::AwsLcRsFips => (
+            rustak_crypto::CryptoProviderMode::AwsLcRsFips,
+            "aws-lc-rs-fips",
+        ),
+    };
+    if crypto_provider_available(mode) {
+        DoctorCheck::new(name, CheckStatus::Pass, format!("{label} available"))
+    } else {
+        DoctorCheck::new(
+            name,
+            CheckStatus::Fail,
+            format!("{label} is not available in this build"),
+        )
+    }
+}
+
+#[cfg(feature = "tls")]
+fn crypto_provider_available(mode: rustak_crypto::CryptoProviderMode) -> bool {
+    rustak_transport::provider_available(mode)
+}
+
+#[cfg(not(feature = "tls"))]
+fn crypto_provider_available(_mode: rustak_crypto::CryptoProviderMode) -> bool {
+    false
+}
+
+/// The recording directory must accept a file and have `min_free_bytes`
+/// available.
+fn doctor_disk(dir: &Path, min_free_bytes: u64) -> DoctorCheck {
+    let name = "disk";
+    let probe = dir.join(format!(".rustak-doctor-{}", std::process::id()));
+    if let Err(error) = fs::write(&probe, b"rustak doctor") {
+        return DoctorCheck::new(
+            name,
+            CheckStatus::Fail,
+            format!("{} is not writable: {error}", dir.display()),
+        );
+    }
+    // Best effort: a leftover probe file is harmless.
+    let _ = fs::remove_file(&probe);
+
+    match available_bytes(dir) {
+        Some(available) => {
+            let detail = format!(
+                "{} has {} MiB free",
+                dir.display(),
+                available / (1024 * 1024)
+            );
+            if available < min_free_bytes {
+                DoctorCheck::new(name, CheckStatus::Warn, detail)
+            } else {
+                DoctorCheck::new(name, CheckStatus::Pass, detail)
+            }
+        }
+        None => DoctorCheck::new(
+            name,
+            CheckStatus::Warn,
+            format!("{} is writable; free space unknown", dir.display()),
+        ),
+    }
+}
+
+#[cfg(unix)]
+fn available_bytes(dir: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is a valid out-pointer
+    // that statvfs fully initialises when it returns 0.
+    let stats = unsafe {
+        if libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stats.assume_init()
+    };
+    #[allow(clippy::useless_conversion)]
+    let available = u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize));
+    Some(available)
+}
+
+#[cfg(not(unix))]
+fn available_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::time::TimestampUtc;
+    use rustak_transport::Protocol;
+
+    use super::{doctor_checks, CheckStatus, DoctorOptions};
+
+    #[tokio::test]
+    async fn doctor_reports_pass_warn_and_fail_checks() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let mut config = rustak_config::RustakConfig::default();
+        config.transport.protocol = Protocol::Tcp {
+            addr: listener.local_addr().expect("addr"),
+        };
+        config.certificates = Some(rustak_config::CertificatesConfig {
+            ca_cert: "/nonexistent/ca.pem".to_owned(),
+            client_cert: "/nonexistent/client.pem".to_owned(),
+            client_key: "/nonexistent/client.key".to_owned(),
+        });
+        let options = DoctorOptions {
+            record_dir: std::env::temp_dir(),
+            cert_warn_within: Duration::from_secs(30 * 86_400),
+            min_free_bytes: u64::MAX,
+            connect_timeout: Duration::from_secs(1),
+        };
+
+        let checks = doctor_checks(Some(&config), &options, TimestampUtc::now()).await;
+        let status = |name: &str| {
+            checks
+                .iter()
+                .find(|check| check.name == name)
+                .unwrap_or_else(|| panic!("missing check {name}"))
+                .status
+        };
+        assert_eq!(status("certificate.ca_cert"), CheckStatus::Fail);
+        assert_eq!(status("certificate.client_key"), CheckStatus::Fail);
+        assert_eq!(status("endpoint.tcp"), CheckStatus::Pass);
+        assert_eq!(status("multicast"), CheckStatus::Pass);
+        assert_eq!(status("crypto_provider"), CheckStatus::Pass);
+        assert_eq!(status("disk"), CheckStatus::Warn);
+        assert!(checks
+            .iter()
+            .find(|check| check.name == "disk")
+            .expect("disk")
+            .line()
+            .starts_with("doctor check=disk status=warn detail=\""));
+
+        drop(listener);
+        config.certificates = None;
+        let checks = doctor_checks(Some(&config), &options, TimestampUtc::now()).await;
+        assert!(checks
+            .iter()
+            .any(|check| check.name == "endpoint.tcp" && check.status == CheckStatus::Fail));
+    }
+}
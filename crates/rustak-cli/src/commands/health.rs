@@ -0,0 +1,479 @@
+//! `rustak health`: a staged probe of a TAK streaming endpoint.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use rustak_core::time::TimestampUtc;
+use rustak_transport::{render_ping, ConnectionManager, Protocol, TransportFraming, PING_COT_TYPE};
+use rustak_wire::negotiation::events::state_code;
+use rustak_wire::{DowngradePolicy, NegotiationState, TakProtocolVersion, WireFormat};
+
+use crate::commands::doctor::CheckStatus;
+use crate::{
+    load_optional_config, parse_event, stream_transport, with_tls_connector, CliError, FailOn,
+};
+
+#[derive(Debug, Args)]
+pub struct HealthArgs {
+    #[arg(
+        long,
+        help = "Stream address to probe (HOST:PORT); TLS when the config's protocol is tls"
+    )]
+    pub target: Option<String>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds the whole probe may take before the pending stage fails"
+    )]
+    pub timeout: u64,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 5,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds to wait for the server's protocol announcement before staying on XML"
+    )]
+    pub negotiation_timeout: u64,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = FailOn::Errors)]
+    pub fail_on: FailOn,
+}
+
+/// Node uid `rustak health` pings as (`rustak-health-ping` on the wire).
+const HEALTH_UID: &str = "rustak-health";
+
+/// Stages of `rustak health`, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStage {
+    Connect,
+    Negotiate,
+    Ping,
+    Response,
+}
+
+impl HealthStage {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Connect => "connect",
+            Self::Negotiate => "negotiate",
+            Self::Ping => "ping",
+            Self::Response => "response",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthStageResult {
+    pub stage: HealthStage,
+    pub status: CheckStatus,
+    pub elapsed: Duration,
+    pub detail: String,
+}
+
+/// Stages `rustak health` ran against `target`. Probing stops at the first
+/// failed stage, so it is always the last one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    pub target: String,
+    pub stages: Vec<HealthStageResult>,
+}
+
+impl HealthReport {
+    fn record(
+        &mut self,
+        stage: HealthStage,
+        status: CheckStatus,
+        since: tokio::time::Instant,
+        detail: impl Into<String>,
+    ) {
+        self.stages.push(HealthStageResult {
+            stage,
+            status,
+            elapsed: since.elapsed(),
+            detail: detail.into(),
+        });
+    }
+
+    #[must_use]
+    pub fn failed_stage(&self) -> Option<HealthStage> {
+        self.stages
+            .iter()
+            .find(|result| result.status == CheckStatus::Fail)
+            .map(|result| result.stage)
+    }
+
+    #[must_use]
+    pub fn warnings(&self) -> usize {
+        self.stages
+            .iter()
+            .filter(|result| result.status == CheckStatus::Warn)
+            .count()
+    }
+
+    /// The worst stage status; `fail` when no stage ran.
+    #[must_use]
+    pub fn status(&self) -> CheckStatus {
+        self.stages
+            .iter()
+            .map(|result| result.status)
+            .max()
+            .unwrap_or(CheckStatus::Fail)
+    }
+
+    /// `{"target":...,"status":...,"failed_stage":...,"elapsed_ms":...,"stages":[...]}`
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        let millis = |elapsed: Duration| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+        serde_json::json!({
+            "target": self.target,
+            "status": self.status().as_str(),
+            "failed_stage": self.failed_stage().map(HealthStage::as_str),
+            "elapsed_ms": millis(self.stages.iter().map(|result| result.elapsed).sum()),
+            "stages": self.stages.iter().map(|result| serde_json::json!({
+                "stage": result.stage.as_str(),
+                "status": result.status.as_str(),
+                "elapsed_ms": millis(result.elapsed),
+                "detail": result.detail,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Connects through `manager`, negotiates the TAK protocol upgrade, sends a
+/// ping and waits for any frame back, all within `timeout`. A stream that
+/// stays on legacy XML is a warning; anything else that goes wrong fails
+/// its stage and ends the probe.
+pub async fn health_probe(
+    manager: &mut ConnectionManager,
+    timeout: Duration,
+    negotiation_timeout: Duration,
+) -> HealthReport {
+    let mut report = HealthReport {
+        target: match &manager.config().protocol {
+            Protocol::Tcp { addr } => format!("tcp://{addr}"),
+            Protocol::Tls { addr, server_name } => format!("tls://{server_name}@{addr}"),
+            Protocol::Udp { .. } => "udp".to_owned(),
+            Protocol::WebSocket { url } => url.clone(),
+        },
+        stages: Vec::new(),
+    };
+    let deadline = tokio::time::Instant::now() + timeout;
+    let expired = || format!("no answer within {}ms", timeout.as_millis());
+
+    let started = tokio::time::Instant::now();
+    let mut connection = match tokio::time::timeout_at(deadline, manager.connect()).await {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(error)) => {
+            report.record(
+                HealthStage::Connect,
+                CheckStatus::Fail,
+                started,
+                error.to_string(),
+            );
+            return report;
+        }
+        Err(_) => {
+            report.record(HealthStage::Connect, CheckStatus::Fail, started, expired());
+            return report;
+        }
+    };
+    report.record(
+        HealthStage::Connect,
+        CheckStatus::Pass,
+        started,
+        "connected",
+    );
+
+    let started = tokio::time::Instant::now();
+    let remaining = deadline.saturating_duration_since(started);
+    match connection
+        .negotiate(TakProtocolVersion::V1, negotiation_timeout.min(remaining))
+        .await
+    {
+        Ok(outcome) => {
+            let (status, detail) = match outcome.state {
+                NegotiationState::Upgraded(_) => (CheckStatus::Pass, state_code(outcome.state)),
+                NegotiationState::Terminated { .. } => {
+                    (CheckStatus::Fail, state_code(outcome.state))
+                }
+                NegotiationState::LegacyXml | NegotiationState::AwaitingResponse => (
+                    CheckStatus::Warn,
+                    format!("{}; no TAK protocol upgrade", state_code(outcome.state)),
+                ),
+            };
+            report.record(HealthStage::Negotiate, status, started, detail);
+            if status == CheckStatus::Fail {
+                return report;
+            }
+        }
+        Err(error) => {
+            report.record(
+                HealthStage::Negotiate,
+                CheckStatus::Fail,
+                started,
+                error.to_string(),
+            );
+            return report;
+        }
+    }
+
+    let started = tokio::time::Instant::now();
+    let ping = render_ping(HEALTH_UID, TimestampUtc::now(), timeout);
+    let payload = match connection.framing() {
+        TransportFraming::XmlNewlineDelimited => Ok(ping.into_bytes()),
+        TransportFraming::TakProtocolU32LengthPrefixed
+        | TransportFraming::TakProtocolMeshHeader => {
+            rustak_wire::encode_payload_for_format(ping.as_bytes(), WireFormat::TakProtocolV1)
+                .map_err(|error| error.to_string())
+        }
+    };
+    let sent = match payload {
+        Ok(payload) => tokio::time::timeout_at(deadline, connection.send_frame(&payload))
+            .await
+            .map_err(|_| expired())
+            .and_then(|sent| sent.map_err(|error| error.to_string())),
+        Err(error) => Err(error),
+    };
+    if let Err(detail) = sent {
+        report.record(HealthStage::Ping, CheckStatus::Fail, started, detail);
+        return report;
+    }
+    report.record(HealthStage::Ping, CheckStatus::Pass, started, PING_COT_TYPE);
+
+    let started = tokio::time::Instant::now();
+    match tokio::time::timeout_at(deadline, connection.recv_frame()).await {
+        Ok(Ok(frame)) => {
+            let wire_format = match connection.framing() {
+                TransportFraming::XmlNewlineDelimited => WireFormat::Xml,
+                TransportFraming::TakProtocolU32LengthPrefixed
+                | TransportFraming::TakProtocolMeshHeader => WireFormat::TakProtocolV1,
+            };
+            let cot_type = rustak_wire::decode_payload_for_format(&frame, wire_format)
+                .ok()
+                .and_then(|xml| parse_event(&xml, &manager.config().limits))
+                .map(|event| event.cot_type)
+                .unwrap_or_else(|| "unknown".to_owned());
+            report.record(
+                HealthStage::Response,
+                CheckStatus::Pass,
+                started,
+                format!("{cot_type} ({} bytes)", frame.len()),
+            );
+        }
+        Ok(Err(error)) => {
+            report.record(
+                HealthStage::Response,
+                CheckStatus::Fail,
+                started,
+                error.to_string(),
+            );
+        }
+        Err(_) => report.record(HealthStage::Response, CheckStatus::Fail, started, expired()),
+    }
+    report
+}
+
+pub(crate) fn run_health(args: HealthArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    let mut transport = stream_transport(
+        args.target.as_deref(),
+        config.as_ref(),
+        CliError::HealthTargetRequired,
+    )?;
+    // One dial attempt: a probe reports the failure rather than backing off.
+    transport.reconnect_policy.enabled = false;
+    let timeout = Duration::from_secs(args.timeout);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let report = runtime.block_on(async {
+        let mut manager = ConnectionManager::new(transport.clone(), DowngradePolicy::FailOpen)?;
+        if matches!(transport.protocol, Protocol::Tls { .. }) {
+            manager = with_tls_connector(manager, config.as_ref())?;
+        }
+        Ok::<_, CliError>(
+            health_probe(
+                &mut manager,
+                timeout,
+                Duration::from_secs(args.negotiation_timeout),
+            )
+            .await,
+        )
+    })?;
+    println!("{}", report.to_json());
+
+    if let Some(stage) = report.failed_stage() {
+        return Err(CliError::HealthCheckFailed {
+            stage: stage.as_str(),
+        });
+    }
+    if args.fail_on == FailOn::Warnings && report.warnings() > 0 {
+        return Err(CliError::WarningThreshold {
+            command: "health",
+            warnings: report.warnings(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::time::TimestampUtc;
+    use rustak_limits::CodedError;
+    use rustak_transport::{
+        ConnectionManager, Protocol, TransportConfig, TransportReceiver, TransportSender,
+    };
+    use rustak_wire::{DowngradePolicy, WireFormat};
+
+    use super::{health_probe, HealthStage};
+    use crate::commands::doctor::CheckStatus;
+    use crate::tests::replay_event;
+    use crate::{CliError, ExitStatus};
+
+    /// A health probe against `addr` with a one second budget.
+    async fn probe(
+        addr: std::net::SocketAddr,
+        negotiation_timeout: Duration,
+    ) -> super::HealthReport {
+        let mut transport = TransportConfig {
+            protocol: Protocol::Tcp { addr },
+            ..TransportConfig::default()
+        };
+        transport.reconnect_policy.enabled = false;
+        let mut manager =
+            ConnectionManager::new(transport, DowngradePolicy::FailOpen).expect("manager");
+        health_probe(&mut manager, Duration::from_secs(1), negotiation_timeout).await
+    }
+
+    #[tokio::test]
+    async fn health_negotiates_pings_and_reports_every_stage() {
+        use rustak_wire::negotiation::events::TakControlMessage;
+        use rustak_wire::TakProtocolVersion;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let control = |message: TakControlMessage| {
+                let mut line = message.encode("server", TimestampUtc::now());
+                line.push(b'\n');
+                line
+            };
+            stream
+                .write_all(&control(TakControlMessage::ProtocolSupport {
+                    version: Some(TakProtocolVersion::V1),
+                }))
+                .await
+                .expect("announce");
+            while stream.read_u8().await.expect("request") != b'\n' {}
+            stream
+                .write_all(&control(TakControlMessage::Response { accepted: true }))
+                .await
+                .expect("respond");
+
+            let tak = TransportConfig {
+                wire_format: WireFormat::TakProtocolV1,
+                ..TransportConfig::default()
+            };
+            let (reader, writer) = tokio::io::split(stream);
+            let mut receiver = TransportReceiver::new(reader, &tak).expect("receiver");
+            let ping = receiver.recv_frame().await.expect("ping");
+            let mut sender = TransportSender::new(writer, &tak).expect("sender");
+            let pong = rustak_wire::encode_payload_for_format(
+                &replay_event("srv", "2023-11-14T22:13:20.000Z"),
+                WireFormat::TakProtocolV1,
+            )
+            .expect("encode");
+            sender.send_frame(&pong).await.expect("pong");
+            sender.flush().await.expect("flush");
+            rustak_wire::decode_payload_for_format(&ping, WireFormat::TakProtocolV1)
+                .expect("decode ping")
+        });
+
+        let report = probe(addr, Duration::from_secs(1)).await;
+        let ping = String::from_utf8(server.await.expect("server")).expect("utf8");
+        assert!(ping.contains("uid=\"rustak-health-ping\""), "{ping}");
+        assert!(ping.contains("type=\"t-x-c-t\""), "{ping}");
+
+        let stages = report
+            .stages
+            .iter()
+            .map(|result| (result.stage, result.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stages,
+            [
+                (HealthStage::Connect, CheckStatus::Pass),
+                (HealthStage::Negotiate, CheckStatus::Pass),
+                (HealthStage::Ping, CheckStatus::Pass),
+                (HealthStage::Response, CheckStatus::Pass),
+            ]
+        );
+        let json = report.to_json();
+        assert_eq!(json["status"], "pass");
+        assert!(json["failed_stage"].is_null());
+        assert_eq!(json["stages"][1]["detail"], "upgraded:v1");
+        assert_eq!(
+            json["stages"][3]["detail"]
+                .as_str()
+                .map(|detail| detail.starts_with("a-f-G ")),
+            Some(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn health_reports_the_stage_that_failed() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        // A legacy XML server: no upgrade offered, but the ping is answered.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("accept");
+            let mut stream = tokio::io::BufReader::new(stream);
+            let mut ping = String::new();
+            stream.read_line(&mut ping).await.expect("ping");
+            stream.write_all(b"<event/>\n").await.expect("reply");
+        });
+        let report = probe(addr, Duration::from_millis(50)).await;
+        server.await.expect("server");
+        assert_eq!(report.failed_stage(), None);
+        assert_eq!(report.warnings(), 1);
+        assert_eq!(report.stages[1].status, CheckStatus::Warn);
+        assert_eq!(report.stages[3].detail, "unknown (8 bytes)");
+
+        // A server that hangs up before negotiating.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            drop(listener.accept().await.expect("accept"));
+        });
+        let report = probe(addr, Duration::from_secs(1)).await;
+        server.await.expect("server");
+        assert_eq!(report.failed_stage(), Some(HealthStage::Negotiate));
+        assert_eq!(report.stages.len(), 2);
+        let json = report.to_json();
+        assert_eq!(json["status"], "fail");
+        assert_eq!(json["failed_stage"], "negotiate");
+
+        let error = CliError::HealthCheckFailed { stage: "negotiate" };
+        assert_eq!(error.code().to_string(), "RTK-CLI-0049");
+        assert_eq!(error.exit_status(), ExitStatus::Connection);
+    }
+}
@@ -0,0 +1,733 @@
+//! `rustak listen`: received events printed one line each.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use clap::Args;
+use futures::future::BoxFuture;
+use rustak_core::time::TimestampUtc;
+use rustak_io::layers::{MetricsLayer, MetricsSnapshot};
+use rustak_io::{IoError, MessageEnvelope, MessageSink};
+use rustak_limits::Limits;
+use rustak_transport::{Protocol, TransportConfig, TransportReceiver, UdpTarget, UdpTransport};
+use rustak_wire::WireFormat;
+
+use crate::{
+    config_limits, load_optional_config, mesh_body, parse_endpoint, parse_event, udp_target,
+    validate_transport_defaults, CliError, ConvertFormat,
+};
+
+#[derive(Debug, Args)]
+pub struct ListenArgs {
+    #[arg(long, help = "UDP endpoint to listen on (for example 239.2.3.1:6969)")]
+    pub udp: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "udp",
+        help = "TCP address to accept streaming clients on (for example 0.0.0.0:8087)"
+    )]
+    pub tcp: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format of received frames; defaults to the config's wire_format or xml"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(long, help = "Exit after printing this many events")]
+    pub count: Option<u64>,
+    #[arg(
+        long,
+        help = "Print one aligned line per event with a 2525 classification column"
+    )]
+    pub pretty: bool,
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Print a traffic summary line every SECS seconds"
+    )]
+    pub stats: Option<u64>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = LISTEN_IDLE_HINT_SECS,
+        help = "Over UDP, print a hint after SECS seconds without an event; 0 disables"
+    )]
+    pub idle_hint: u64,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// Default `listen --idle-hint` interval.
+pub const LISTEN_IDLE_HINT_SECS: u64 = 10;
+
+/// Where `rustak listen` receives from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenEndpoint {
+    Udp {
+        bind_addr: SocketAddr,
+        target: UdpTarget,
+    },
+    Tcp(SocketAddr),
+}
+
+impl ListenEndpoint {
+    /// Resolves `--udp`/`--tcp`, falling back to a UDP protocol in the
+    /// loaded config. Multicast addresses join the group.
+    pub fn resolve(
+        args: &ListenArgs,
+        config: Option<&rustak_config::RustakConfig>,
+    ) -> Result<Self, CliError> {
+        if let Some(udp) = args.udp.as_deref() {
+            let addr = parse_endpoint(udp)?;
+            return Ok(Self::Udp {
+                bind_addr: addr,
+                target: udp_target(addr),
+            });
+        }
+        if let Some(tcp) = args.tcp.as_deref() {
+            return parse_endpoint(tcp).map(Self::Tcp);
+        }
+        match config.map(|config| &config.transport.protocol) {
+            Some(Protocol::Udp { bind_addr, target }) => Ok(Self::Udp {
+                bind_addr: *bind_addr,
+                target: target.clone(),
+            }),
+            _ => Err(CliError::ListenEndpointRequired),
+        }
+    }
+}
+
+pub(crate) fn run_listen(args: ListenArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    let endpoint = ListenEndpoint::resolve(&args, config.as_ref())?;
+    let format = args.format.unwrap_or(match config.as_ref() {
+        Some(config) if config.transport.wire_format == WireFormat::TakProtocolV1 => {
+            ConvertFormat::TakV1
+        }
+        _ => ConvertFormat::Xml,
+    });
+    let options = ListenOptions {
+        format,
+        limits: config_limits(config.as_ref()),
+        pretty: args.pretty,
+        stats_interval: args.stats.map(Duration::from_secs),
+        idle_hint: (args.idle_hint > 0).then(|| Duration::from_secs(args.idle_hint)),
+        count: args.count,
+    };
+    let transport = TransportConfig {
+        wire_format: WireFormat::from(format),
+        ..config.map(|config| config.transport).unwrap_or_default()
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    runtime.block_on(async move {
+        let printer = MetricsLayer::new(ListenPrinter::new(io::stdout(), &options));
+        match endpoint {
+            ListenEndpoint::Udp { bind_addr, target } => {
+                let transport = TransportConfig {
+                    protocol: Protocol::Udp { bind_addr, target },
+                    ..transport
+                };
+                let udp = UdpTransport::bind(&transport)?;
+                tracing::info!(protocol = "udp", addr = %bind_addr, "listen_bound");
+                for join in udp.multicast_joins() {
+                    match &join.error {
+                        None => tracing::info!(
+                            group = %join.group,
+                            interface = %join.interface,
+                            address = %join.address,
+                            "listen_multicast_join"
+                        ),
+                        Some(error) => tracing::warn!(
+                            group = %join.group,
+                            interface = %join.interface,
+                            address = %join.address,
+                            error = %error,
+                            "listen_multicast_join"
+                        ),
+                    }
+                }
+                listen_udp(udp, &printer, &options).await
+            }
+            ListenEndpoint::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr)
+                    .await
+                    .map_err(|source| CliError::Bind { addr, source })?;
+                tracing::info!(protocol = "tcp", addr = %addr, "listen_bound");
+                listen_tcp(listener, &transport, &printer, &options).await
+            }
+        }
+    })
+}
+
+/// Settings shared by the UDP and TCP listen loops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenOptions {
+    pub format: ConvertFormat,
+    /// Bounds received events are parsed under for `--pretty` and `--stats`.
+    pub limits: Limits,
+    pub pretty: bool,
+    pub stats_interval: Option<Duration>,
+    /// Silence after which the UDP loop prints a [`listen_idle_line`].
+    pub idle_hint: Option<Duration>,
+    pub count: Option<u64>,
+}
+
+/// Decode sink behind `rustak listen`: turns received frames into CoT XML
+/// and prints one line per event with its receive time and peer.
+pub struct ListenPrinter<W> {
+    out: Mutex<W>,
+    format: ConvertFormat,
+    limits: Limits,
+    pretty: bool,
+    stats: Option<Mutex<ListenStats>>,
+}
+
+impl<W> ListenPrinter<W> {
+    pub fn new(out: W, options: &ListenOptions) -> Self {
+        Self {
+            out: Mutex::new(out),
+            format: options.format,
+            limits: options.limits.clone(),
+            pretty: options.pretty,
+            stats: options
+                .stats_interval
+                .map(|_| Mutex::new(ListenStats::new(options.limits.clone()))),
+        }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> W {
+        self.out
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn decode(&self, frame: &[u8]) -> Result<String, IoError> {
+        let cot_xml = match self.format {
+            ConvertFormat::Xml => frame.to_vec(),
+            ConvertFormat::TakV1 => {
+                // UDP mesh datagrams carry the mesh header ahead of the
+                // protobuf body; stream frames arrive without it.
+                rustak_wire::decode_payload_for_format(mesh_body(frame), WireFormat::TakProtocolV1)
+                    .map_err(|error| IoError::Other(error.to_string()))?
+            }
+        };
+        let cot_xml = String::from_utf8(cot_xml)
+            .map_err(|_| IoError::Other("event is not valid UTF-8".to_owned()))?;
+        let cot_xml = cot_xml.trim();
+        if !cot_xml.starts_with("<event") && !cot_xml.starts_with("<?xml") {
+            return Err(IoError::Other("frame is not a CoT event".to_owned()));
+        }
+        Ok(cot_xml.to_owned())
+    }
+
+    fn stats_line(&self, metrics: MetricsSnapshot, elapsed: Duration) -> Option<String> {
+        let stats = self.stats.as_ref()?;
+        Some(
+            stats
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .interval_line(metrics, elapsed),
+        )
+    }
+}
+
+impl<W: Write + Send> MessageSink<Bytes> for ListenPrinter<W> {
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+        self.send_envelope(MessageEnvelope::new(msg))
+    }
+
+    fn send_envelope(&self, env: MessageEnvelope<Bytes>) -> BoxFuture<'_, Result<(), IoError>> {
+        let result = self.decode(&env.message).and_then(|cot_xml| {
+            if let Some(stats) = &self.stats {
+                let decoded = MessageEnvelope {
+                    observed: env.observed.clone(),
+                    peer: env.peer,
+                    raw_frame: Some(env.message.clone()),
+                    message: cot_xml.as_bytes(),
+                };
+                stats
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .observe(&decoded);
+            }
+            let line = listen_event_line(&env, &cot_xml, self.pretty, &self.limits);
+            let mut out = self
+                .out
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            writeln!(out, "{line}")
+                .and_then(|()| out.flush())
+                .map_err(IoError::from)
+        });
+        Box::pin(async move { result })
+    }
+}
+
+/// One `listen` output line: receive time, peer, then either the raw event
+/// or the `--pretty` columns, parsed under `limits`.
+#[must_use]
+pub fn listen_event_line<T>(
+    envelope: &MessageEnvelope<T>,
+    cot_xml: &str,
+    pretty: bool,
+    limits: &Limits,
+) -> String {
+    let received = TimestampUtc::from_system_time(envelope.observed.wall).to_rfc3339_millis();
+    let peer = envelope
+        .peer
+        .map_or_else(|| "<unknown>".to_owned(), |peer| peer.to_string());
+    let event = if pretty {
+        listen_pretty_line(cot_xml, limits)
+    } else {
+        cot_xml.to_owned()
+    };
+    format!("{received} {peer:<21} {event}")
+}
+
+/// Feeds one received frame through the printer, logging decode errors, and
+/// logs a stats line when the interval elapsed. Returns `true` once `count`
+/// events were printed.
+async fn listen_deliver<W: Write + Send>(
+    printer: &MetricsLayer<ListenPrinter<W>>,
+    envelope: MessageEnvelope<Bytes>,
+    options: &ListenOptions,
+    interval_started: &mut Instant,
+) -> bool {
+    let peer = envelope.peer;
+    if let Err(error) = printer.send_envelope(envelope).await {
+        let peer = peer.map_or_else(|| "<unknown>".to_owned(), |peer| peer.to_string());
+        tracing::warn!(peer = %peer, error = %error, "listen_decode_error");
+    }
+    if let Some(interval) = options.stats_interval {
+        let elapsed = interval_started.elapsed();
+        if elapsed >= interval {
+            if let Some(line) = printer.inner().stats_line(printer.snapshot(), elapsed) {
+                tracing::info!("{line}");
+            }
+            *interval_started = Instant::now();
+        }
+    }
+    options
+        .count
+        .is_some_and(|count| printer.snapshot().sent >= count)
+}
+
+/// Receives datagrams until `options.count` events were printed, printing a
+/// [`listen_idle_line`] whenever `options.idle_hint` passes without one.
+pub async fn listen_udp<W: Write + Send>(
+    mut udp: UdpTransport,
+    printer: &MetricsLayer<ListenPrinter<W>>,
+    options: &ListenOptions,
+) -> Result<(), CliError> {
+    let mut interval_started = Instant::now();
+    loop {
+        let received = match options.idle_hint {
+            Some(idle) => match tokio::time::timeout(idle, udp.recv()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    let line = listen_idle_line(
+                        idle,
+                        udp.datagrams_received(),
+                        printer.snapshot().sent,
+                        !udp.multicast_joins().is_empty(),
+                    );
+                    tracing::warn!("{line}");
+                    continue;
+                }
+            },
+            None => udp.recv().await,
+        };
+        let envelope = received?;
+        if listen_deliver(printer, envelope, options, &mut interval_started).await {
+            return Ok(());
+        }
+    }
+}
+
+/// Hint printed after `idle` without a UDP event. Socket-level datagrams
+/// and decoded events are totals since bind, so an operator can tell a
+/// missing feed from one that arrives but does not decode.
+#[must_use]
+pub fn listen_idle_line(idle: Duration, datagrams: u64, decoded: u64, multicast: bool) -> String {
+    let hint = if datagrams == 0 && multicast {
+        "no datagrams reached the socket; check the multicast_join lines, firewall rules and the sender's TTL"
+    } else if datagrams == 0 {
+        "no datagrams reached the socket; check the address, port and firewall rules"
+    } else if decoded == 0 {
+        "datagrams arrive but none decoded; check --format"
+    } else {
+        "traffic stopped; the sender may be idle"
+    };
+    format!(
+        "listen_idle secs={} datagrams={datagrams} decoded={decoded} hint=\"{hint}\"",
+        idle.as_secs()
+    )
+}
+
+/// Accepts streaming clients one at a time and prints their events until
+/// `options.count` events were printed. A client that disconnects or sends
+/// an unframeable stream is logged and the next client is accepted.
+pub async fn listen_tcp<W: Write + Send>(
+    listener: tokio::net::TcpListener,
+    transport: &TransportConfig,
+    printer: &MetricsLayer<ListenPrinter<W>>,
+    options: &ListenOptions,
+) -> Result<(), CliError> {
+    let mut interval_started = Instant::now();
+    loop {
+        let (stream, peer) = listener
+            .accept()
+            .await
+            .map_err(|source| CliError::Runtime { source })?;
+        tracing::info!(peer = %peer, "listen_connected");
+        let mut receiver = TransportReceiver::new(stream, transport)?;
+        loop {
+            match receiver.recv_envelope().await {
+                Ok(envelope) => {
+                    let envelope = envelope.with_peer(peer);
+                    if listen_deliver(printer, envelope, options, &mut interval_started).await {
+                        return Ok(());
+                    }
+                }
+                Err(error) => {
+                    tracing::info!(peer = %peer, reason = %error, "listen_disconnected");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Formats one received CoT event for `listen --pretty`: uid, type and a
+/// readable classification derived from the type string.
+#[must_use]
+pub fn listen_pretty_line(cot_xml: &str, limits: &Limits) -> String {
+    let event = parse_event(cot_xml.as_bytes(), limits);
+    let uid = event
+        .as_ref()
+        .map_or("<no-uid>", |event| event.uid.as_str());
+    let cot_type = event
+        .as_ref()
+        .map_or("<no-type>", |event| event.cot_type.as_str());
+    let classification =
+        rustak_core::describe_cot_type(cot_type).unwrap_or_else(|| "Unclassified".to_owned());
+    format!("{uid:<36} {cot_type:<16} {classification}")
+}
+
+/// Number of peers listed in the `listen --stats` top talkers column.
+pub const LISTEN_STATS_TOP_TALKERS: usize = 3;
+
+/// Per-interval accumulator behind `listen --stats`.
+///
+/// Frame and decode error counts come from the `MetricsLayer` wrapped around
+/// the listen decode sink; bytes, UIDs and talkers come from a tap on the
+/// received envelopes. Every summary line resets the interval.
+#[derive(Debug, Default)]
+pub struct ListenStats {
+    limits: Limits,
+    bytes: u64,
+    uids: HashSet<String>,
+    talkers: HashMap<String, u64>,
+    last_metrics: MetricsSnapshot,
+}
+
+impl ListenStats {
+    /// Stats whose events are parsed under `limits`.
+    #[must_use]
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub fn observe<T: AsRef<[u8]>>(&mut self, envelope: &MessageEnvelope<T>) {
+        let frame = envelope
+            .raw_frame
+            .as_deref()
+            .unwrap_or_else(|| envelope.message.as_ref());
+        self.bytes = self.bytes.saturating_add(frame.len() as u64);
+        if let Some(event) = parse_event(envelope.message.as_ref(), &self.limits) {
+            self.uids.insert(event.uid);
+        }
+        let talker = envelope
+            .peer
+            .map_or_else(|| "<unknown>".to_owned(), |peer| peer.to_string());
+        *self.talkers.entry(talker).or_default() += 1;
+    }
+
+    /// Renders the summary for the interval ending at `metrics` and starts a
+    /// new one.
+    pub fn interval_line(&mut self, metrics: MetricsSnapshot, elapsed: Duration) -> String {
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        let frames = metrics
+            .attempted
+            .saturating_sub(self.last_metrics.attempted);
+        let decode_errors = metrics.errors.saturating_sub(self.last_metrics.errors);
+
+        let mut talkers = self.talkers.drain().collect::<Vec<_>>();
+        talkers.sort_by(|(left_peer, left), (right_peer, right)| {
+            right.cmp(left).then_with(|| left_peer.cmp(right_peer))
+        });
+        let top_talkers = if talkers.is_empty() {
+            "none".to_owned()
+        } else {
+            talkers
+                .iter()
+                .take(LISTEN_STATS_TOP_TALKERS)
+                .map(|(peer, frames)| format!("{peer}={frames}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let line = format!(
+            "listen_stats interval_s={:.1} frames_per_s={:.1} bytes_per_s={:.0} decode_errors={decode_errors} unique_uids={} top_talkers={top_talkers}",
+            elapsed.as_secs_f64(),
+            frames as f64 / seconds,
+            self.bytes as f64 / seconds,
+            self.uids.len(),
+        );
+        self.bytes = 0;
+        self.uids.clear();
+        self.last_metrics = metrics;
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rustak_io::layers::MetricsLayer;
+    use rustak_limits::Limits;
+
+    use super::{
+        listen_idle_line, listen_pretty_line, listen_tcp, listen_udp, ListenArgs, ListenEndpoint,
+        ListenOptions, ListenPrinter, ListenStats, LISTEN_IDLE_HINT_SECS,
+    };
+    use crate::tests::replay_event;
+    use crate::{execute_command, Cli, CliError, Command, ConvertFormat, ExitStatus};
+
+    #[test]
+    fn listen_requires_an_endpoint() {
+        let error = execute_command(Command::Listen(ListenArgs {
+            udp: None,
+            tcp: None,
+            format: None,
+            count: None,
+            pretty: false,
+            stats: None,
+            idle_hint: LISTEN_IDLE_HINT_SECS,
+            config: None,
+        }))
+        .expect_err("listen needs somewhere to listen");
+        assert!(matches!(error, CliError::ListenEndpointRequired));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+
+        let cli = Cli::try_parse_from(["rustak", "listen", "--udp", "239.2.3.1:6969"])
+            .expect("listen args parse");
+        let Command::Listen(args) = cli.command else {
+            panic!("expected listen");
+        };
+        assert_eq!(
+            ListenEndpoint::resolve(&args, None).expect("endpoint"),
+            ListenEndpoint::Udp {
+                bind_addr: "239.2.3.1:6969".parse().expect("addr"),
+                target: rustak_transport::UdpTarget::Multicast {
+                    group: std::net::Ipv4Addr::new(239, 2, 3, 1),
+                    port: 6969,
+                },
+            }
+        );
+    }
+
+    fn listen_options(format: ConvertFormat, count: u64) -> ListenOptions {
+        ListenOptions {
+            format,
+            limits: Limits::default(),
+            pretty: true,
+            stats_interval: None,
+            idle_hint: None,
+            count: Some(count),
+        }
+    }
+
+    #[tokio::test]
+    async fn listen_udp_prints_decoded_tak_v1_events_with_peer() {
+        let loopback: std::net::SocketAddr = "127.0.0.1:0".parse().expect("addr");
+        let udp = rustak_transport::UdpTransport::bind(&rustak_transport::TransportConfig {
+            protocol: rustak_transport::Protocol::Udp {
+                bind_addr: loopback,
+                target: rustak_transport::UdpTarget::Unicast(loopback),
+            },
+            ..rustak_transport::TransportConfig::default()
+        })
+        .expect("bind");
+        let listen_addr = udp.socket().local_addr().expect("local addr");
+
+        let sender = tokio::net::UdpSocket::bind(loopback).await.expect("sender");
+        let sender_addr = sender.local_addr().expect("sender addr");
+        let mut datagram = vec![0xbf, 0x01, 0xbf];
+        let event = replay_event("ANDROID-1", "2023-11-14T22:13:20.000Z");
+        datagram.extend(rustak_proto::encode_v1_payload(&event).expect("encode"));
+        sender
+            .send_to(b"not cot", listen_addr)
+            .await
+            .expect("send junk");
+        sender.send_to(&datagram, listen_addr).await.expect("send");
+
+        let options = listen_options(ConvertFormat::TakV1, 1);
+        let printer = MetricsLayer::new(ListenPrinter::new(Vec::new(), &options));
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            listen_udp(udp, &printer, &options),
+        )
+        .await
+        .expect("event arrives")
+        .expect("listen");
+
+        assert_eq!(printer.snapshot().errors, 1);
+        let output = String::from_utf8(printer.into_inner().into_inner()).expect("utf8");
+        let line = output.lines().next().expect("one line");
+        assert!(line.contains(&format!(" {sender_addr} ")), "{line}");
+        assert!(line.ends_with(&listen_pretty_line(
+            std::str::from_utf8(&event).expect("utf8"),
+            &Limits::default()
+        )));
+    }
+
+    #[test]
+    fn listen_idle_hint_separates_silence_from_undecodable_traffic() {
+        let idle = std::time::Duration::from_secs(10);
+        let silent = listen_idle_line(idle, 0, 0, true);
+        assert!(silent.starts_with("listen_idle secs=10 datagrams=0 decoded=0 "));
+        assert!(silent.contains("multicast_join"), "{silent}");
+        assert!(!listen_idle_line(idle, 0, 0, false).contains("multicast_join"));
+        assert!(listen_idle_line(idle, 4, 0, true).contains("check --format"));
+        assert!(listen_idle_line(idle, 4, 4, true).contains("sender may be idle"));
+
+        let cli = Cli::try_parse_from(["rustak", "listen", "--udp", "239.2.3.1:6969"])
+            .expect("listen args");
+        let Command::Listen(args) = cli.command else {
+            panic!("expected listen");
+        };
+        assert_eq!(args.idle_hint, LISTEN_IDLE_HINT_SECS);
+    }
+
+    #[tokio::test]
+    async fn listen_tcp_prints_xml_events_from_streaming_client() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let client = tokio::spawn(async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.expect("connect");
+            stream
+                .write_all(b"<event uid=\"a\"/>\n<event uid=\"b\"/>\n")
+                .await
+                .expect("write");
+            stream
+        });
+
+        let options = ListenOptions {
+            pretty: false,
+            ..listen_options(ConvertFormat::Xml, 2)
+        };
+        let printer = MetricsLayer::new(ListenPrinter::new(Vec::new(), &options));
+        listen_tcp(
+            listener,
+            &rustak_transport::TransportConfig::default(),
+            &printer,
+            &options,
+        )
+        .await
+        .expect("listen");
+        drop(client.await.expect("client"));
+
+        let output = String::from_utf8(printer.into_inner().into_inner()).expect("utf8");
+        let events = output
+            .lines()
+            .map(|line| line.rsplit(' ').next().expect("event"))
+            .collect::<Vec<_>>();
+        assert_eq!(events, ["uid=\"a\"/>", "uid=\"b\"/>"]);
+    }
+
+    #[test]
+    fn listen_pretty_line_includes_classification() {
+        let limits = Limits::default();
+        let line = listen_pretty_line(
+            "<?xml version='1.0'?><event type='a-h-G-U-C-A' how='m-g' version='2.0' uid='T-1' time='2024-01-01T00:00:00Z' start='2024-01-01T00:00:00Z' stale='2024-01-01T00:05:00Z'><point lat='1' lon='2' hae='0' ce='5' le='5'/></event>",
+            &limits,
+        );
+        assert!(line.starts_with("T-1 "));
+        assert!(line.contains("a-h-G-U-C-A"));
+        assert!(line.ends_with("Hostile Ground Unit — Armor"));
+
+        let chat = String::from_utf8(replay_event("msg", "2024-01-01T00:00:00Z"))
+            .expect("utf8")
+            .replace("a-f-G", "b-t-f");
+        assert!(listen_pretty_line(&chat, &limits).ends_with("Unclassified"));
+
+        let unparsed = listen_pretty_line("<event uid=\"msg\" type=\"b-t-f\"/>", &limits);
+        assert!(unparsed.starts_with("<no-uid> "));
+
+        let detailed = chat.replace("</event>", "<detail><remarks/><link/></detail></event>");
+        assert!(listen_pretty_line(&detailed, &limits).starts_with("msg "));
+        let tight = Limits {
+            max_detail_elements: 1,
+            ..Limits::default()
+        };
+        assert!(listen_pretty_line(&detailed, &tight).starts_with("<no-uid> "));
+    }
+
+    #[test]
+    fn listen_stats_line_summarises_interval_and_resets() {
+        use std::time::Duration;
+
+        use rustak_io::layers::MetricsSnapshot;
+        use rustak_io::MessageEnvelope;
+
+        let cli = Cli::try_parse_from(["rustak", "listen", "--stats", "5"]).expect("parses");
+        assert!(matches!(
+            cli.command,
+            Command::Listen(ListenArgs { stats: Some(5), .. })
+        ));
+
+        let flooder = "10.0.0.9:6969".parse().expect("addr");
+        let quiet = "10.0.0.5:6969".parse().expect("addr");
+        let mut stats = ListenStats::default();
+        for (uid, peer) in [("A", flooder), ("B", flooder), ("A", flooder), ("C", quiet)] {
+            let xml = replay_event(uid, "2024-01-01T00:00:00Z");
+            stats.observe(&MessageEnvelope::new(xml).with_peer(peer));
+        }
+        let metrics = MetricsSnapshot {
+            attempted: 5,
+            sent: 4,
+            dropped: 0,
+            errors: 1,
+        };
+
+        let line = stats.interval_line(metrics, Duration::from_secs(2));
+        assert_eq!(
+            line,
+            "listen_stats interval_s=2.0 frames_per_s=2.5 bytes_per_s=384 decode_errors=1 unique_uids=3 top_talkers=10.0.0.9:6969=3,10.0.0.5:6969=1"
+        );
+
+        let idle = stats.interval_line(metrics, Duration::from_secs(2));
+        assert!(idle.contains("frames_per_s=0.0 bytes_per_s=0 decode_errors=0 unique_uids=0"));
+        assert!(idle.ends_with("top_talkers=none"));
+    }
+}
@@ -0,0 +1,18 @@
+//! One module per `rustak` subcommand; shared plumbing stays in the crate
+//! root.
+
+pub mod bridge;
+pub mod certs;
+pub mod config;
+pub mod connect;
+pub mod contacts;
+pub mod convert;
+pub mod doctor;
+pub mod health;
+pub mod listen;
+pub mod record;
+pub mod replay;
+pub mod send;
+pub mod sim;
+pub mod stress;
+pub mod validate;
@@ -0,0 +1,1224 @@
+//! `rustak record`: takrec capture and the `scrub`, `stats` and `import`
+//! actions.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use clap::{Args, Subcommand};
+use rustak::RustakError;
+use rustak_core::time::TimestampUtc;
+use rustak_record::{
+    append_envelope_chunk, chain_sidecar_path, import_pcap, recording_stats, scrub_recording,
+    ChunkCompression, CoordinateOffset, DecodeStatus, DetachedSigner, ImportTransport,
+    ImportedFrame, ImportedFraming, IntegrityChainBuilder, KeySummary, PcapImportConfig,
+    PcapImportReport, RecordEnvelope, RetentionPolicy, RotatingTakrecConfig, RotatingTakrecWriter,
+    RotationPolicy, ScrubConfig, ScrubReport, StatsConfig, StatsReport, TakrecHeader, TakrecWriter,
+    DEFAULT_TAK_PORTS,
+};
+use rustak_transport::{
+    ConnectionManager, Protocol, TransportConfig, TransportConnection, UdpTarget, UdpTransport,
+};
+use rustak_wire::{DowngradePolicy, WireFormat};
+
+use crate::{
+    load_optional_config, mesh_body, parse_endpoint, read_key_pem, udp_target,
+    validate_transport_defaults, with_tls_connector, CliError, ConvertFormat,
+};
+
+#[derive(Debug, Args)]
+#[command(group(
+    clap::ArgGroup::new("rotation")
+        .multiple(true)
+        .args(["rotate_mb", "rotate_secs"])
+))]
+pub struct RecordArgs {
+    #[command(subcommand)]
+    pub action: Option<RecordAction>,
+    #[arg(long, help = "UDP endpoint to capture (for example 239.2.3.1:6969)")]
+    pub source: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "source",
+        help = "TCP stream to connect to and capture (for example 10.0.0.5:8087)"
+    )]
+    pub tcp: Option<String>,
+    #[arg(long, help = "Takrec file to write")]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format of captured frames; defaults to the config's wire_format or xml"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(
+        long,
+        value_name = "MIB",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Write timestamped <stem>-<time>-<n>.takrec files beside <output>, starting a new one once a file reaches MIB MiB"
+    )]
+    pub rotate_mb: Option<u64>,
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Like --rotate-mb, starting a new file once one has been open for SECONDS"
+    )]
+    pub rotate_secs: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FILES",
+        requires = "rotation",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Delete the oldest rotated files to keep at most FILES"
+    )]
+    pub retain_files: Option<u64>,
+    #[arg(
+        long,
+        value_name = "MIB",
+        requires = "rotation",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Delete the oldest rotated files to keep their total under MIB MiB"
+    )]
+    pub retain_mb: Option<u64>,
+    #[arg(long, help = "Stop after recording this many frames")]
+    pub count: Option<u64>,
+    #[arg(
+        long,
+        value_name = "KEY_PEM",
+        help = "Ed25519 or ECDSA P-256 private key; writes a signed <file>.chain beside every takrec"
+    )]
+    pub sign: Option<PathBuf>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RecordAction {
+    /// Rewrite a takrec with identifying details removed.
+    Scrub(ScrubArgs),
+    /// Summarise a takrec in constant memory: counts, rates and gaps.
+    Stats(RecordStatsArgs),
+    /// Convert a packet capture of TAK traffic into a takrec.
+    Import(RecordImportArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct RecordImportArgs {
+    #[arg(long, help = "libpcap capture to import (pcapng is not supported)")]
+    pub input: PathBuf,
+    #[arg(long, help = "Path for the takrec")]
+    pub output: PathBuf,
+    #[arg(
+        long = "port",
+        value_name = "PORT",
+        help = "TAK port to import; repeat for several (default 4242, 6969, 8087 and 17012)"
+    )]
+    pub ports: Vec<u16>,
+    #[arg(
+        long,
+        help = "Print a line per frame with its framing and decode status"
+    )]
+    pub frames: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RecordStatsArgs {
+    #[arg(help = "Takrec file to summarise")]
+    pub file: PathBuf,
+    #[arg(long, help = "Print the report as JSON")]
+    pub json: bool,
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "Most frequent types and UIDs to list"
+    )]
+    pub top: usize,
+    #[arg(
+        long,
+        default_value_t = 1_024,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Distinct types and UIDs tracked; counts become upper bounds beyond this"
+    )]
+    pub max_keys: u64,
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Rate histogram window in seconds"
+    )]
+    pub window_secs: u64,
+    #[arg(
+        long,
+        default_value_t = 30,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Report silences longer than this many seconds as gaps"
+    )]
+    pub gap_secs: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct ScrubArgs {
+    #[arg(long, help = "Takrec file to scrub")]
+    pub input: PathBuf,
+    #[arg(long, help = "Path for the scrubbed takrec")]
+    pub output: PathBuf,
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub offset_lat: f64,
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    pub offset_lon: f64,
+    #[arg(long)]
+    pub rename_uids: bool,
+    #[arg(long)]
+    pub rename_callsigns: bool,
+    #[arg(long)]
+    pub drop_chat: bool,
+    #[arg(
+        long,
+        help = "Drop chunks that are not CoT XML instead of copying them"
+    )]
+    pub drop_opaque: bool,
+}
+
+/// Where `rustak record` captures from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordSource {
+    Udp {
+        bind_addr: SocketAddr,
+        target: UdpTarget,
+    },
+    /// A TCP or TLS stream to dial.
+    Stream(Protocol),
+}
+
+impl RecordSource {
+    /// Resolves `--source` (UDP) or `--tcp`, falling back to the loaded
+    /// config's UDP, TCP or TLS protocol.
+    pub fn resolve(
+        args: &RecordArgs,
+        config: Option<&rustak_config::RustakConfig>,
+    ) -> Result<Self, CliError> {
+        if let Some(udp) = args.source.as_deref() {
+            let addr = parse_endpoint(udp)?;
+            return Ok(Self::Udp {
+                bind_addr: addr,
+                target: udp_target(addr),
+            });
+        }
+        if let Some(tcp) = args.tcp.as_deref() {
+            return Ok(Self::Stream(Protocol::Tcp {
+                addr: parse_endpoint(tcp)?,
+            }));
+        }
+        match config.map(|config| &config.transport.protocol) {
+            Some(Protocol::Udp { bind_addr, target }) => Ok(Self::Udp {
+                bind_addr: *bind_addr,
+                target: target.clone(),
+            }),
+            Some(protocol @ (Protocol::Tcp { .. } | Protocol::Tls { .. })) => {
+                Ok(Self::Stream(protocol.clone()))
+            }
+            _ => Err(CliError::RecordSourceRequired),
+        }
+    }
+}
+
+/// Appends captured envelopes to `output`, or with a rotation policy to
+/// timestamped `<stem>-<UTC time>-<n>.takrec` files beside it, pruned by the
+/// retention policy (see [`RotatingTakrecWriter`]). Every chunk is flushed at
+/// its boundary, so an interrupted capture recovers without a truncated tail.
+/// With a signer each file also gets a `<file>.chain` sidecar that is
+/// extended as chunks are appended (see [`IntegrityChainBuilder`]).
+#[derive(Debug)]
+pub struct TakrecRecorder {
+    sink: RecorderSink,
+    chain: Option<RecorderChain>,
+    frames: u64,
+}
+
+#[derive(Debug)]
+struct RecorderChain {
+    path: PathBuf,
+    builder: IntegrityChainBuilder<fs::File, DetachedSigner>,
+}
+
+#[derive(Debug)]
+enum RecorderSink {
+    Single {
+        path: PathBuf,
+        writer: TakrecWriter<fs::File>,
+    },
+    Rotating(Box<RotatingTakrecWriter>),
+}
+
+/// What a finished capture wrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordSummary {
+    pub frames: u64,
+    pub bytes: u64,
+    pub files: Vec<PathBuf>,
+    /// Files the retention policy removed, this capture's or earlier ones.
+    pub deleted: Vec<PathBuf>,
+}
+
+impl TakrecRecorder {
+    pub fn create(
+        output: PathBuf,
+        header: TakrecHeader,
+        rotation: RotationPolicy,
+        retention: RetentionPolicy,
+    ) -> Result<Self, CliError> {
+        let sink = if rotation == RotationPolicy::default() {
+            RecorderSink::Single {
+                writer: create_takrec(&output, header)?,
+                path: output,
+            }
+        } else {
+            let directory = match output.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let prefix = output.file_stem().map_or_else(
+                || "capture".into(),
+                |stem| stem.to_string_lossy().into_owned(),
+            );
+            let mut config = RotatingTakrecConfig::new(directory, prefix);
+            config.rotation = rotation;
+            config.retention = retention;
+            RecorderSink::Rotating(Box::new(RotatingTakrecWriter::create(config, header)?))
+        };
+        Ok(Self {
+            sink,
+            chain: None,
+            frames: 0,
+        })
+    }
+
+    /// Chains and signs every chunk recorded from now on.
+    pub fn with_signer(mut self, signer: DetachedSigner) -> Result<Self, CliError> {
+        let path = chain_sidecar_path(self.current_path());
+        let sidecar = create_chain_sidecar(&path)?;
+        let builder = IntegrityChainBuilder::with_signer(sidecar, signer)
+            .map_err(|source| chain_write_error(&path, source))?;
+        self.chain = Some(RecorderChain { path, builder });
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The takrec file chunks are currently appended to.
+    #[must_use]
+    pub fn current_path(&self) -> &Path {
+        match &self.sink {
+            RecorderSink::Single { path, .. } => path,
+            RecorderSink::Rotating(writer) => writer.current_path(),
+        }
+    }
+
+    pub fn record(&mut self, envelope: &RecordEnvelope<Bytes>) -> Result<(), CliError> {
+        match &mut self.sink {
+            RecorderSink::Single { writer, .. } => {
+                append_envelope_chunk(writer, envelope)
+                    .map_err(|source| CliError::Facade(RustakError::Record(source)))?;
+            }
+            RecorderSink::Rotating(writer) => {
+                let previous = writer.current_path().to_path_buf();
+                writer.append_envelope(envelope)?;
+                if writer.current_path() != previous {
+                    tracing::info!(
+                        from = %previous.display(),
+                        to = %writer.current_path().display(),
+                        "record_rotated"
+                    );
+                    if let Some(chain) = &mut self.chain {
+                        let path = chain_sidecar_path(writer.current_path());
+                        let sidecar = create_chain_sidecar(&path)?;
+                        let finished = chain
+                            .builder
+                            .restart(sidecar)
+                            .map_err(|source| chain_write_error(&chain.path, source))?;
+                        let finished_path = std::mem::replace(&mut chain.path, path);
+                        finished
+                            .sync_all()
+                            .map_err(|source| chain_write_error(&finished_path, source))?;
+                    }
+                }
+            }
+        }
+        if let Some(chain) = &mut self.chain {
+            let payload = envelope.raw_frame.as_deref().unwrap_or(&envelope.message);
+            chain
+                .builder
+                .push(payload)
+                .map_err(|source| chain_write_error(&chain.path, source))?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flushes and syncs the current file and its chain sidecar, and removes
+    /// the sidecars of files the retention policy deleted.
+    pub fn finish(self) -> Result<RecordSummary, CliError> {
+        if let Some(chain) = self.chain {
+            chain
+                .builder
+                .finish()
+                .and_then(|sidecar| sidecar.sync_all())
+                .map_err(|source| chain_write_error(&chain.path, source))?;
+        }
+        let summary = match self.sink {
+            RecorderSink::Single { path, writer } => {
+                let bytes = writer.bytes_written();
+                sync_takrec(writer, &path)?;
+                RecordSummary {
+                    frames: self.frames,
+                    bytes,
+                    files: vec![path],
+                    deleted: Vec::new(),
+                }
+            }
+            RecorderSink::Rotating(writer) => {
+                let summary = writer.finish()?;
+                RecordSummary {
+                    frames: self.frames,
+                    bytes: summary.bytes,
+                    files: summary.files,
+                    deleted: summary.deleted,
+                }
+            }
+        };
+        for deleted in &summary.deleted {
+            let sidecar = chain_sidecar_path(deleted);
+            if sidecar.exists() {
+                fs::remove_file(&sidecar).map_err(|source| chain_write_error(&sidecar, source))?;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn create_chain_sidecar(path: &Path) -> Result<fs::File, CliError> {
+    fs::File::create(path).map_err(|source| chain_write_error(path, source))
+}
+
+fn chain_write_error(path: &Path, source: io::Error) -> CliError {
+    CliError::OutputWrite {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+fn create_takrec(path: &Path, header: TakrecHeader) -> Result<TakrecWriter<fs::File>, CliError> {
+    let file = fs::File::create(path).map_err(|source| CliError::OutputWrite {
+        path: path.display().to_string(),
+        source,
+    })?;
+    TakrecWriter::with_capture_times(file, header, ChunkCompression::None)
+        .map_err(|source| CliError::Facade(RustakError::Record(source)))
+}
+
+fn sync_takrec(writer: TakrecWriter<fs::File>, path: &Path) -> Result<(), CliError> {
+    let file = writer
+        .into_inner()
+        .map_err(|source| CliError::Facade(RustakError::Record(source)))?;
+    file.sync_all().map_err(|source| CliError::OutputWrite {
+        path: path.display().to_string(),
+        source,
+    })
+}
+
+pub(crate) fn run_record(args: RecordArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    let source = RecordSource::resolve(&args, config.as_ref())?;
+    let output = args.output.clone().ok_or(CliError::RecordOutputRequired)?;
+    let format = args.format.unwrap_or(match config.as_ref() {
+        Some(config) if config.transport.wire_format == WireFormat::TakProtocolV1 => {
+            ConvertFormat::TakV1
+        }
+        _ => ConvertFormat::Xml,
+    });
+    let transport = TransportConfig {
+        wire_format: WireFormat::from(format),
+        ..config
+            .as_ref()
+            .map(|config| config.transport.clone())
+            .unwrap_or_default()
+    };
+    let header = TakrecHeader::new(
+        "rustak",
+        env!("CARGO_PKG_VERSION"),
+        match format {
+            ConvertFormat::Xml => "xml",
+            ConvertFormat::TakV1 => "tak-v1",
+        },
+        "default",
+    );
+    let rotation = RotationPolicy {
+        max_file_bytes: args.rotate_mb.map(|mebibytes| mebibytes * 1024 * 1024),
+        max_file_age: args.rotate_secs.map(Duration::from_secs),
+    };
+    let retention = RetentionPolicy {
+        max_files: args
+            .retain_files
+            .map(|files| usize::try_from(files).unwrap_or(usize::MAX)),
+        max_total_bytes: args.retain_mb.map(|mebibytes| mebibytes * 1024 * 1024),
+    };
+    let signer = args
+        .sign
+        .as_deref()
+        .map(|path| Ok::<_, CliError>(DetachedSigner::from_pem(&read_key_pem(path)?)?))
+        .transpose()?;
+    let mut recorder = TakrecRecorder::create(output, header, rotation, retention)?;
+    let algorithm = signer.as_ref().map(DetachedSigner::algorithm);
+    if let Some(signer) = signer {
+        recorder = recorder.with_signer(signer)?;
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let captured = runtime.block_on(async {
+        let capture = async {
+            match source {
+                RecordSource::Udp { bind_addr, target } => {
+                    let udp = UdpTransport::bind(&TransportConfig {
+                        protocol: Protocol::Udp { bind_addr, target },
+                        ..transport
+                    })?;
+                    tracing::info!(protocol = "udp", addr = %bind_addr, "record_bound");
+                    record_udp(udp, &mut recorder, args.count).await
+                }
+                RecordSource::Stream(protocol) => {
+                    let tls = matches!(protocol, Protocol::Tls { .. });
+                    let transport = TransportConfig {
+                        protocol,
+                        ..transport
+                    };
+                    let mut manager = ConnectionManager::new(transport, DowngradePolicy::FailOpen)?;
+                    if tls {
+                        manager = with_tls_connector(manager, config.as_ref())?;
+                    }
+                    let connection = manager.connect().await?;
+                    tracing::info!(framing = ?connection.framing(), "record_connected");
+                    record_stream(connection, &mut recorder, args.count).await
+                }
+            }
+        };
+        tokio::select! {
+            result = capture => result,
+            signal = tokio::signal::ctrl_c() => {
+                signal.map_err(|source| CliError::Runtime { source })?;
+                tracing::info!("record_interrupted");
+                Ok(())
+            }
+        }
+    });
+    // Flush whatever was captured even when the source failed.
+    let summary = recorder.finish()?;
+    if let Some(algorithm) = algorithm {
+        for path in summary.files.iter().filter(|path| path.exists()) {
+            println!(
+                "record_signed file={} chain={} algorithm={algorithm}",
+                path.display(),
+                chain_sidecar_path(path).display()
+            );
+        }
+    }
+    println!(
+        "record frames={} bytes={} files={} deleted={}",
+        summary.frames,
+        summary.bytes,
+        summary
+            .files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        summary.deleted.len()
+    );
+    captured
+}
+
+/// Records datagrams until `count` frames were captured.
+pub async fn record_udp(
+    mut udp: UdpTransport,
+    recorder: &mut TakrecRecorder,
+    count: Option<u64>,
+) -> Result<(), CliError> {
+    while count.is_none_or(|count| recorder.frames() < count) {
+        let envelope = udp.recv().await?;
+        recorder.record(&envelope)?;
+    }
+    Ok(())
+}
+
+/// Records stream frames until `count` frames were captured or the source
+/// closes the stream.
+pub async fn record_stream<IO>(
+    mut connection: TransportConnection<IO>,
+    recorder: &mut TakrecRecorder,
+    count: Option<u64>,
+) -> Result<(), CliError>
+where
+    IO: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    while count.is_none_or(|count| recorder.frames() < count) {
+        match connection.recv_envelope().await {
+            Ok(envelope) => recorder.record(&envelope)?,
+            Err(error) => {
+                tracing::info!(reason = %error, "record_source_closed");
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn run_record_scrub(args: ScrubArgs) -> Result<(), CliError> {
+    let config = ScrubConfig {
+        coordinate_offset: (args.offset_lat != 0.0 || args.offset_lon != 0.0).then_some(
+            CoordinateOffset {
+                lat_deg: args.offset_lat,
+                lon_deg: args.offset_lon,
+            },
+        ),
+        rename_uids: args.rename_uids,
+        rename_callsigns: args.rename_callsigns,
+        drop_chat_bodies: args.drop_chat,
+        drop_opaque_chunks: args.drop_opaque,
+        ..ScrubConfig::default()
+    };
+
+    let source = fs::File::open(&args.input).map_err(|source| CliError::InputRead {
+        path: args.input.display().to_string(),
+        source,
+    })?;
+    let sink = fs::File::create(&args.output).map_err(|source| CliError::OutputWrite {
+        path: args.output.display().to_string(),
+        source,
+    })?;
+    let report = scrub_recording(io::BufReader::new(source), sink, &config)?;
+    println!("{}", scrub_summary_line(&report));
+    Ok(())
+}
+
+fn scrub_summary_line(report: &ScrubReport) -> String {
+    let chain_head = report
+        .integrity
+        .links
+        .last()
+        .map(|link| {
+            link.chain_hash
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        })
+        .unwrap_or_else(|| "<empty>".to_owned());
+    format!(
+        "record_scrub chunks_read={} chunks_written={} rewritten={} opaque={} opaque_dropped={} uids={} callsigns={} truncated_tail={} chain_head={chain_head}",
+        report.chunks_read,
+        report.chunks_written,
+        report.chunks_rewritten,
+        report.opaque_chunks,
+        report.opaque_chunks_dropped,
+        report.uids_renamed,
+        report.callsigns_renamed,
+        report.truncated_tail,
+    )
+}
+
+pub(crate) fn run_record_import(args: RecordImportArgs) -> Result<(), CliError> {
+    let config = PcapImportConfig {
+        ports: if args.ports.is_empty() {
+            DEFAULT_TAK_PORTS.to_vec()
+        } else {
+            args.ports
+        },
+        ..PcapImportConfig::default()
+    };
+    let source = fs::File::open(&args.input).map_err(|source| CliError::InputRead {
+        path: args.input.display().to_string(),
+        source,
+    })?;
+    let sink = fs::File::create(&args.output).map_err(|source| CliError::OutputWrite {
+        path: args.output.display().to_string(),
+        source,
+    })?;
+    let header = TakrecHeader::new("rustak", env!("CARGO_PKG_VERSION"), "mixed", "default");
+    let report = import_pcap(
+        io::BufReader::new(source),
+        io::BufWriter::new(sink),
+        header,
+        &config,
+    )?;
+    if args.frames {
+        for frame in &report.frames {
+            println!("{}", import_frame_line(frame));
+        }
+    }
+    println!("{}", import_summary_line(&report));
+    Ok(())
+}
+
+fn import_summary_line(report: &PcapImportReport) -> String {
+    format!(
+        "record_import packets={} skipped_packets={} tcp_streams={} abandoned_streams={} frames={} decoded={} malformed={} opaque={} chunks={}",
+        report.packets,
+        report.skipped_packets,
+        report.tcp_streams,
+        report.abandoned_streams,
+        report.frames.len(),
+        report.count(DecodeStatus::Decoded),
+        report.count(DecodeStatus::Malformed),
+        report.count(DecodeStatus::Opaque),
+        report.chunks(),
+    )
+}
+
+fn import_frame_line(frame: &ImportedFrame) -> String {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_micros(frame.timestamp_micros);
+    let transport = match frame.transport {
+        ImportTransport::Udp => "udp",
+        ImportTransport::Tcp => "tcp",
+    };
+    let framing = match frame.framing {
+        ImportedFraming::Xml => "xml",
+        ImportedFraming::MeshHeader => "mesh",
+        ImportedFraming::StreamHeader => "stream",
+        ImportedFraming::U32LengthPrefixed => "u32-length",
+        ImportedFraming::Unknown => "unknown",
+    };
+    let status = match frame.decode_status {
+        DecodeStatus::Decoded => "decoded",
+        DecodeStatus::Malformed => "malformed",
+        DecodeStatus::Opaque => "opaque",
+    };
+    format!(
+        "import_frame time={} transport={transport} source={} destination={} framing={framing} status={status} bytes={} chunk={}",
+        TimestampUtc::from_system_time(time).to_rfc3339_millis(),
+        frame.source,
+        frame.destination,
+        frame.len,
+        frame
+            .chunk
+            .map_or_else(|| "-".to_owned(), |chunk| chunk.to_string()),
+    )
+}
+
+pub(crate) fn run_record_stats(args: RecordStatsArgs) -> Result<(), CliError> {
+    let config = StatsConfig {
+        max_tracked_keys: usize::try_from(args.max_keys).unwrap_or(usize::MAX),
+        rate_window: Duration::from_secs(args.window_secs),
+        gap_threshold: Duration::from_secs(args.gap_secs),
+        ..StatsConfig::default()
+    };
+    let source = fs::File::open(&args.file).map_err(|source| CliError::InputRead {
+        path: args.file.display().to_string(),
+        source,
+    })?;
+    let report = recording_stats(
+        io::BufReader::new(source),
+        config,
+        args.top,
+        stats_event_xml,
+    )?;
+    if args.json {
+        println!("{}", record_stats_json(&report));
+    } else {
+        for line in record_stats_lines(&report) {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+/// Chunks recorded from XML sources start with markup; anything else is
+/// tried as TAK protocol v1 with or without the mesh header.
+fn stats_event_xml(payload: &[u8]) -> Option<String> {
+    let cot_xml = if payload.trim_ascii_start().starts_with(b"<") {
+        payload.to_vec()
+    } else {
+        rustak_wire::decode_payload_for_format(mesh_body(payload), WireFormat::TakProtocolV1)
+            .ok()?
+    };
+    String::from_utf8(cot_xml).ok()
+}
+
+fn stats_time(time: Option<SystemTime>) -> String {
+    time.map_or_else(
+        || "-".to_owned(),
+        |time| TimestampUtc::from_system_time(time).to_rfc3339_millis(),
+    )
+}
+
+fn record_stats_lines(report: &StatsReport) -> Vec<String> {
+    let mut lines = vec![format!(
+        "record_stats frames={} bytes={} undecodable={} undated={} first={} last={} span_ms={} truncated_tail={}",
+        report.frames,
+        report.bytes,
+        report.undecodable,
+        report.undated,
+        stats_time(report.first_time),
+        stats_time(report.last_time),
+        report.span().as_millis(),
+        report.truncated_tail,
+    )];
+    for (label, summary) in [("type", &report.types), ("uid", &report.uids)] {
+        lines.push(format!(
+            "record_stats_{label}s tracked={} exact={}",
+            summary.tracked, summary.exact
+        ));
+        for key in &summary.top {
+            lines.push(format!(
+                "record_stats_{label} {label}={} count={} overcount={}",
+                key.key, key.count, key.overcount
+            ));
+        }
+    }
+    lines.push(format!(
+        "record_stats_rates window_ms={} peak={}",
+        report.rates.window.as_millis(),
+        report.rates.peak_messages_per_window
+    ));
+    for bin in report.rates.bins.iter().filter(|bin| bin.windows > 0) {
+        let max = bin
+            .max_messages
+            .map_or_else(|| "inf".to_owned(), |max| max.to_string());
+        lines.push(format!(
+            "record_stats_rate min={} max={max} windows={}",
+            bin.min_messages, bin.windows
+        ));
+    }
+    lines.push(format!(
+        "record_stats_gaps threshold_ms={} count={} total_ms={}",
+        report.gaps.threshold.as_millis(),
+        report.gaps.count,
+        report.gaps.total.as_millis()
+    ));
+    for gap in &report.gaps.longest {
+        lines.push(format!(
+            "record_stats_gap sequence={} start={} duration_ms={}",
+            gap.sequence,
+            stats_time(Some(gap.start)),
+            gap.duration.as_millis()
+        ));
+    }
+    lines
+}
+
+fn record_stats_json(report: &StatsReport) -> serde_json::Value {
+    let keys = |summary: &KeySummary| {
+        serde_json::json!({
+            "tracked": summary.tracked,
+            "exact": summary.exact,
+            "top": summary.top.iter().map(|key| serde_json::json!({
+                "key": key.key,
+                "count": key.count,
+                "overcount": key.overcount,
+            })).collect::<Vec<_>>(),
+        })
+    };
+    let time = |time: Option<SystemTime>| {
+        time.map(|time| TimestampUtc::from_system_time(time).to_rfc3339_millis())
+    };
+    serde_json::json!({
+        "frames": report.frames,
+        "bytes": report.bytes,
+        "undecodable": report.undecodable,
+        "undated": report.undated,
+        "first_time": time(report.first_time),
+        "last_time": time(report.last_time),
+        "span_ms": u64::try_from(report.span().as_millis()).unwrap_or(u64::MAX),
+        "truncated_tail": report.truncated_tail,
+        "types": keys(&report.types),
+        "uids": keys(&report.uids),
+        "rates": {
+            "window_ms": u64::try_from(report.rates.window.as_millis()).unwrap_or(u64::MAX),
+            "peak_messages_per_window": report.rates.peak_messages_per_window,
+            "bins": report.rates.bins.iter().map(|bin| serde_json::json!({
+                "min_messages": bin.min_messages,
+                "max_messages": bin.max_messages,
+                "windows": bin.windows,
+            })).collect::<Vec<_>>(),
+        },
+        "gaps": {
+            "threshold_ms": u64::try_from(report.gaps.threshold.as_millis()).unwrap_or(u64::MAX),
+            "count": report.gaps.count,
+            "total_ms": u64::try_from(report.gaps.total.as_millis()).unwrap_or(u64::MAX),
+            "longest": report.gaps.longest.iter().map(|gap| serde_json::json!({
+                "sequence": gap.sequence,
+                "start": time(Some(gap.start)),
+                "duration_ms": u64::try_from(gap.duration.as_millis()).unwrap_or(u64::MAX),
+            })).collect::<Vec<_>>(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rustak_record::{
+        chain_sidecar_path, import_pcap, DetachedSigner, PcapImportConfig, RetentionPolicy,
+        RotationPolicy, TakrecHeader,
+    };
+    use rustak_transport::{Protocol, TransportConfig};
+
+    use super::{
+        import_frame_line, import_summary_line, record_stats_json, record_stats_lines,
+        record_stream, record_udp, stats_event_xml, RecordArgs, RecordSource, TakrecRecorder,
+    };
+    use crate::tests::replay_event;
+    use crate::{execute_command, Cli, CliError, Command, ExitStatus, TAK_MESH};
+
+    #[test]
+    fn record_scrub_rewrites_takrec_file() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_scrub_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let input = dir.join("in.takrec");
+        let output = dir.join("out.takrec");
+
+        let mut writer =
+            rustak_record::TakrecWriter::new(Vec::new(), rustak_record::TakrecHeader::default())
+                .expect("writer");
+        writer
+            .append_chunk(&replay_event("secret", "2024-01-01T00:00:00Z"))
+            .expect("chunk");
+        std::fs::write(&input, writer.into_inner().expect("inner")).expect("write input");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "record",
+            "scrub",
+            "--input",
+            input.to_str().expect("utf8 path"),
+            "--output",
+            output.to_str().expect("utf8 path"),
+            "--offset-lat",
+            "-0.5",
+            "--rename-uids",
+        ])
+        .expect("scrub args parse");
+        execute_command(cli.command).expect("scrub succeeds");
+
+        let (_, payloads) = rustak_record::recover_chunk_payloads(
+            std::fs::File::open(&output).expect("output exists"),
+        )
+        .expect("scrubbed takrec recovers");
+        assert_eq!(payloads.len(), 1);
+        let event = rustak_core::CotEvent::from_xml(
+            std::str::from_utf8(&payloads[0]).expect("utf8"),
+            &rustak_limits::Limits::default(),
+        )
+        .expect("scrubbed event parses");
+        assert_eq!(event.uid, "uid-1");
+        assert_eq!(event.point.latitude(), 0.5);
+        assert_eq!(event.point.longitude(), 2.0);
+    }
+
+    #[test]
+    fn record_stats_summarises_xml_and_mesh_chunks() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_stats_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let input = dir.join("session.takrec");
+
+        let event =
+            |uid: &str, time: &str| String::from_utf8(replay_event(uid, time)).expect("utf8");
+        let mut mesh = TAK_MESH.header().to_vec();
+        mesh.extend(
+            rustak_proto::encode_v1_payload(event("beta", "2024-01-01T00:01:00.000Z").as_bytes())
+                .expect("encode"),
+        );
+        let mut writer =
+            rustak_record::TakrecWriter::new(Vec::new(), rustak_record::TakrecHeader::default())
+                .expect("writer");
+        writer
+            .append_chunk(event("alpha", "2024-01-01T00:00:00.000Z").as_bytes())
+            .expect("chunk");
+        writer.append_chunk(&mesh).expect("chunk");
+        writer.append_chunk(b"\x00\x01").expect("chunk");
+        std::fs::write(&input, writer.into_inner().expect("inner")).expect("write input");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "record",
+            "stats",
+            input.to_str().expect("utf8 path"),
+            "--json",
+        ])
+        .expect("stats args parse");
+        execute_command(cli.command).expect("stats succeeds");
+
+        let report = rustak_record::recording_stats(
+            std::fs::File::open(&input).expect("input exists"),
+            rustak_record::StatsConfig::default(),
+            10,
+            stats_event_xml,
+        )
+        .expect("stats");
+        let lines = record_stats_lines(&report);
+        assert!(lines[0].starts_with("record_stats frames=3 "));
+        assert!(lines[0].contains(" undecodable=1 "));
+        assert!(lines.contains(&"record_stats_uid uid=alpha count=1 overcount=0".to_owned()));
+        assert!(lines.contains(&"record_stats_uid uid=beta count=1 overcount=0".to_owned()));
+        assert!(lines
+            .contains(&"record_stats_gaps threshold_ms=30000 count=1 total_ms=60000".to_owned()));
+
+        let json = record_stats_json(&report);
+        assert_eq!(json["types"]["top"][0]["key"], "a-f-G");
+        assert_eq!(json["types"]["top"][0]["count"], 2);
+        assert_eq!(json["gaps"]["longest"][0]["sequence"], 1);
+        assert_eq!(json["first_time"], "2024-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn record_import_converts_a_pcap_into_a_takrec() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_import_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let input = dir.join("capture.pcap");
+        let output = dir.join("capture.takrec");
+
+        // Raw IPv4 link type: one UDP datagram from 10.0.0.2:40000 to
+        // 239.2.3.1:6969.
+        let event = replay_event("alpha", "2024-01-01T00:00:00.000Z");
+        let mut datagram = vec![0x45, 0];
+        datagram.extend_from_slice(&(28 + event.len() as u16).to_be_bytes());
+        datagram.extend_from_slice(&[0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 2, 239, 2, 3, 1]);
+        datagram.extend_from_slice(&40_000u16.to_be_bytes());
+        datagram.extend_from_slice(&6_969u16.to_be_bytes());
+        datagram.extend_from_slice(&(8 + event.len() as u16).to_be_bytes());
+        datagram.extend_from_slice(&[0, 0]);
+        datagram.extend_from_slice(&event);
+        let mut pcap = 0xA1B2_C3D4u32.to_le_bytes().to_vec();
+        pcap.extend_from_slice(&[2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        pcap.extend_from_slice(&65_535u32.to_le_bytes());
+        pcap.extend_from_slice(&228u32.to_le_bytes());
+        pcap.extend_from_slice(&1_704_067_200u32.to_le_bytes());
+        pcap.extend_from_slice(&250_000u32.to_le_bytes());
+        pcap.extend_from_slice(&(datagram.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&(datagram.len() as u32).to_le_bytes());
+        pcap.extend_from_slice(&datagram);
+        std::fs::write(&input, &pcap).expect("write pcap");
+
+        let cli = Cli::try_parse_from([
+            "rustak",
+            "record",
+            "import",
+            "--input",
+            input.to_str().expect("utf8 path"),
+            "--output",
+            output.to_str().expect("utf8 path"),
+            "--port",
+            "6969",
+            "--frames",
+        ])
+        .expect("import args parse");
+        execute_command(cli.command).expect("import succeeds");
+        let (_, payloads) = rustak_record::recover_chunk_payloads(
+            std::fs::File::open(&output).expect("takrec exists"),
+        )
+        .expect("recover");
+        assert_eq!(payloads, [event]);
+
+        let report = import_pcap(
+            pcap.as_slice(),
+            Vec::new(),
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect("import");
+        assert_eq!(
+            import_summary_line(&report),
+            "record_import packets=1 skipped_packets=0 tcp_streams=0 abandoned_streams=0 frames=1 decoded=1 malformed=0 opaque=0 chunks=1"
+        );
+        assert_eq!(
+            import_frame_line(&report.frames[0]),
+            "import_frame time=2024-01-01T00:00:00.250Z transport=udp source=10.0.0.2:40000 destination=239.2.3.1:6969 framing=xml status=decoded bytes=208 chunk=0"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    fn record_args(argv: &[&str]) -> RecordArgs {
+        let cli =
+            Cli::try_parse_from(["rustak", "record"].iter().chain(argv)).expect("record args");
+        let Command::Record(args) = cli.command else {
+            panic!("expected record");
+        };
+        args
+    }
+
+    #[test]
+    fn record_requires_source_and_output() {
+        let error = execute_command(Command::Record(record_args(&["--output", "x.takrec"])))
+            .expect_err("record needs a source");
+        assert!(matches!(error, CliError::RecordSourceRequired));
+        let error = execute_command(Command::Record(record_args(&["--source", "127.0.0.1:0"])))
+            .expect_err("record needs an output");
+        assert!(matches!(error, CliError::RecordOutputRequired));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+        assert!(
+            Cli::try_parse_from(["rustak", "record", "--retain-files", "3"]).is_err(),
+            "retention needs a rotation policy"
+        );
+        let args = record_args(&["--rotate-secs", "60", "--retain-mb", "10"]);
+        assert_eq!((args.rotate_secs, args.retain_mb), (Some(60), Some(10)));
+
+        assert_eq!(
+            RecordSource::resolve(&record_args(&["--tcp", "127.0.0.1:8087"]), None)
+                .expect("tcp source"),
+            RecordSource::Stream(Protocol::Tcp {
+                addr: "127.0.0.1:8087".parse().expect("addr")
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn record_udp_rotates_files_that_recover_without_truncation() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_record_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let output = dir.join("session.takrec");
+
+        let loopback: std::net::SocketAddr = "127.0.0.1:0".parse().expect("addr");
+        let udp = rustak_transport::UdpTransport::bind(&TransportConfig {
+            protocol: Protocol::Udp {
+                bind_addr: loopback,
+                target: rustak_transport::UdpTarget::Unicast(loopback),
+            },
+            ..TransportConfig::default()
+        })
+        .expect("bind");
+        let record_addr = udp.socket().local_addr().expect("local addr");
+        let sender = tokio::net::UdpSocket::bind(loopback).await.expect("sender");
+        for uid in ["a", "b", "c"] {
+            sender
+                .send_to(format!("<event uid=\"{uid}\"/>").as_bytes(), record_addr)
+                .await
+                .expect("send");
+        }
+
+        let recorder = TakrecRecorder::create(
+            output.clone(),
+            rustak_record::TakrecHeader::new("rustak", "test", "xml", "default"),
+            RotationPolicy {
+                max_file_bytes: Some(40),
+                max_file_age: None,
+            },
+            RetentionPolicy {
+                max_files: Some(2),
+                max_total_bytes: None,
+            },
+        )
+        .expect("recorder");
+        let key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ED25519).expect("key");
+        let signer = DetachedSigner::from_pem(&key.serialize_pem()).expect("signer");
+        let verifier = signer.verifier();
+        let mut recorder = recorder.with_signer(signer).expect("sidecar");
+        record_udp(udp, &mut recorder, Some(3))
+            .await
+            .expect("record");
+        let summary = recorder.finish().expect("finish");
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.files.len(), 3);
+        assert!(!output.exists(), "rotated captures are named by time");
+        for (index, path) in summary.files.iter().enumerate() {
+            let name = path.file_name().expect("name").to_string_lossy();
+            assert_eq!(path.parent(), Some(dir.as_path()));
+            assert!(name.starts_with("session-"), "{name}");
+            assert!(name.ends_with(&format!("-{index:04}.takrec")), "{name}");
+        }
+        assert_eq!(summary.deleted, [summary.files[0].clone()]);
+        assert!(!chain_sidecar_path(&summary.files[0]).exists());
+
+        let mut uids = Vec::new();
+        for path in &summary.files[1..] {
+            let (report, payloads) = rustak_record::recover_chunk_payloads(
+                std::fs::File::open(path).expect("capture exists"),
+            )
+            .expect("recover");
+            assert!(!report.truncated_tail);
+            assert_eq!(report.header.protocol_hint, "xml");
+            let sidecar = std::fs::read_to_string(chain_sidecar_path(path)).expect("sidecar");
+            let chain = rustak_record::IntegrityChain::from_sidecar(&sidecar).expect("chain");
+            rustak_record::verify_integrity_chain(&payloads, &chain, Some(&verifier), true)
+                .expect("each file carries its own signed chain");
+            uids.extend(payloads);
+        }
+        assert_eq!(
+            uids,
+            [
+                b"<event uid=\"b\"/>".to_vec(),
+                b"<event uid=\"c\"/>".to_vec()
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn record_stream_captures_frames_until_source_closes() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            stream
+                .write_all(b"<event uid=\"a\"/>\n<event uid=\"b\"/>\n")
+                .await
+                .expect("write");
+        });
+
+        let connection = rustak_transport::ConnectionManager::new(
+            TransportConfig {
+                protocol: Protocol::Tcp { addr },
+                ..TransportConfig::default()
+            },
+            rustak_wire::DowngradePolicy::FailOpen,
+        )
+        .expect("manager")
+        .connect()
+        .await
+        .expect("connect");
+
+        let dir =
+            std::env::temp_dir().join(format!("rustak_cli_record_tcp_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let output = dir.join("stream.takrec");
+        let mut recorder = TakrecRecorder::create(
+            output.clone(),
+            rustak_record::TakrecHeader::default(),
+            RotationPolicy::default(),
+            RetentionPolicy::default(),
+        )
+        .expect("recorder");
+        record_stream(connection, &mut recorder, None)
+            .await
+            .expect("record");
+        assert_eq!(
+            recorder.finish().expect("finish").files,
+            vec![output.clone()]
+        );
+
+        let (report, payloads) = rustak_record::recover_chunk_payloads(
+            std::fs::File::open(&output).expect("capture exists"),
+        )
+        .expect("recover");
+        assert!(!report.truncated_tail);
+        assert_eq!(
+            payloads,
+            [
+                b"<event uid=\"a\"/>".to_vec(),
+                b"<event uid=\"b\"/>".to_vec()
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
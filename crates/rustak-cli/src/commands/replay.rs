@@ -0,0 +1,843 @@
+//! `rustak replay`: takrec captures retransmitted with their recorded timing.
+
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::Bytes;
+use clap::Args;
+use rustak::RustakError;
+use rustak_core::time::TimestampUtc;
+use rustak_io::ObservedTime;
+use rustak_limits::Limits;
+use rustak_record::{replay_digest, RecordEnvelope, ReplayDigest, TakrecReader};
+use rustak_transport::{
+    ConnectionManager, ManagedStream, Protocol, TransportConfig, TransportConnection,
+    UdpSendDecision, UdpTransport, UdpTransportError,
+};
+use rustak_wire::{DowngradePolicy, MeshFrameCodec, WireFormat};
+
+use crate::{
+    config_limits, load_optional_config, mesh_body, parse_endpoint, parse_event, udp_target,
+    validate_transport_defaults, with_tls_connector, CliError, ConvertFormat, TAK_MESH,
+};
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    #[arg(long, help = "takrec capture to replay")]
+    pub input: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "UDP endpoint to retransmit to (for example 239.2.3.1:6969)"
+    )]
+    pub target: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "target",
+        help = "TCP address to stream the replay to (for example 10.0.0.5:8087)"
+    )]
+    pub tcp: Option<String>,
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        help = "Playback speed multiplier; 0 replays as fast as possible"
+    )]
+    pub speed: f64,
+    #[arg(long = "loop", help = "Start over from the first frame after the last")]
+    pub repeat: bool,
+    #[arg(
+        long,
+        value_name = "RFC3339",
+        help = "Skip frames timed before this (for example 2024-01-01T12:00:00Z)"
+    )]
+    pub start: Option<String>,
+    #[arg(
+        long,
+        value_name = "RFC3339",
+        help = "Skip frames timed at or after this"
+    )]
+    pub end: Option<String>,
+    #[arg(long, help = "Print frame statistics without transmitting")]
+    pub dry_run: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["target", "tcp", "repeat", "start", "end", "dry_run"],
+        help = "Print a deterministic digest of the capture's events and timestamps without transmitting"
+    )]
+    pub digest: bool,
+    #[arg(
+        long,
+        value_name = "TAKREC",
+        requires = "digest",
+        help = "Digest this capture too and fail unless it matches --input"
+    )]
+    pub compare: Option<PathBuf>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// A takrec capture with its inter-frame timing reconstructed.
+///
+/// Each frame's [`ObservedTime`] is the chunk's recorded capture time when
+/// the file stores one. Older files carry none, so it comes from the CoT
+/// event `time` attribute (XML, or TAK protocol v1 with or without the mesh
+/// header). Frames without one, and events stamped earlier than their
+/// predecessor, keep the previous frame's monotonic time so they go out
+/// back to back instead of stalling the replay.
+#[derive(Debug, Clone)]
+pub struct ReplayTimeline {
+    pub frames: Vec<RecordEnvelope<Bytes>>,
+    pub format: ConvertFormat,
+    /// Frames whose timing was inherited from the previous frame.
+    pub undated: u64,
+    pub truncated_tail: bool,
+}
+
+impl ReplayTimeline {
+    /// Reads the committed chunks of a takrec capture whose wall time is in
+    /// `start..end`, either bound optional; undated frames go with the
+    /// frame they inherit timing from. The header's `protocol_hint` selects
+    /// how frames are decoded for their timestamp. `start` seeks the reader
+    /// rather than reading the chunks before it, except for TAK protocol v1
+    /// files without capture times, whose event times the reader cannot see.
+    pub fn read<R: Read + Seek>(
+        source: R,
+        start: Option<SystemTime>,
+        end: Option<SystemTime>,
+        limits: &Limits,
+    ) -> Result<Self, CliError> {
+        let record = |source| CliError::Facade(RustakError::Record(source));
+        let mut reader = TakrecReader::new(source).map_err(record)?;
+        let format = if reader.header().protocol_hint == "tak-v1" {
+            ConvertFormat::TakV1
+        } else {
+            ConvertFormat::Xml
+        };
+        let mut frames = Vec::new();
+        let seekable = reader.capture_times() || format == ConvertFormat::Xml;
+        let exhausted = match start.filter(|_| seekable) {
+            Some(start) => reader.seek_to_time(start).map_err(record)?.is_none(),
+            None => false,
+        };
+        let mut previous = None;
+        while let Some(chunk) = (!exhausted).then(|| reader.next_chunk()).flatten() {
+            let (_, payload) = chunk.map_err(record)?;
+            let time = if reader.capture_times() {
+                reader.chunk_captured()
+            } else {
+                replay_event_time(&payload, format, limits)
+            };
+            let wall = time.or(previous);
+            previous = wall;
+            let outside = wall.is_some_and(|wall| {
+                start.is_some_and(|start| wall < start) || end.is_some_and(|end| wall >= end)
+            });
+            if !outside {
+                frames.push((payload, time));
+            }
+        }
+        let mut timeline = Self::from_timed_frames(frames, format, Instant::now());
+        timeline.truncated_tail = reader.truncated_tail();
+        Ok(timeline)
+    }
+
+    /// Stamps `frames` from their CoT event times, parsed under `limits`,
+    /// relative to `origin`, the monotonic time of the first frame.
+    #[must_use]
+    pub fn from_frames(
+        frames: Vec<Vec<u8>>,
+        format: ConvertFormat,
+        origin: Instant,
+        limits: &Limits,
+    ) -> Self {
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let time = replay_event_time(&frame, format, limits);
+                (Bytes::from(frame), time)
+            })
+            .collect();
+        Self::from_timed_frames(frames, format, origin)
+    }
+
+    fn from_timed_frames(
+        frames: Vec<(Bytes, Option<SystemTime>)>,
+        format: ConvertFormat,
+        origin: Instant,
+    ) -> Self {
+        let start = frames.iter().find_map(|(_, time)| *time);
+        let mut previous = ObservedTime::new(start.unwrap_or(SystemTime::UNIX_EPOCH), origin);
+        let mut undated = 0;
+        let frames = frames
+            .into_iter()
+            .map(|(frame, time)| {
+                let observed = match (time, start) {
+                    (Some(wall), Some(start)) => {
+                        let offset = wall.duration_since(start).unwrap_or_default();
+                        ObservedTime::new(wall, previous.monotonic.max(origin + offset))
+                    }
+                    _ => {
+                        undated += 1;
+                        previous.clone()
+                    }
+                };
+                previous = observed.clone();
+                RecordEnvelope::new(frame).with_observed(observed)
+            })
+            .collect();
+        Self {
+            frames,
+            format,
+            undated,
+            truncated_tail: false,
+        }
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.frames
+            .iter()
+            .map(|frame| frame.message.len() as u64)
+            .sum()
+    }
+
+    /// Capture time between the first and last frame.
+    #[must_use]
+    pub fn span(&self) -> Duration {
+        match (self.frames.first(), self.frames.last()) {
+            (Some(first), Some(last)) => last
+                .observed
+                .monotonic
+                .saturating_duration_since(first.observed.monotonic),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// The `replay --dry-run` summary; `speed` 0 has no expected duration.
+    #[must_use]
+    pub fn stats_line(&self, speed: f64) -> String {
+        let expected = if speed > 0.0 {
+            format!("{}", (self.span().as_secs_f64() * 1000.0 / speed).round())
+        } else {
+            "0".to_owned()
+        };
+        format!(
+            "replay_dry_run frames={} bytes={} undated={} span_ms={} speed={speed} expected_ms={expected} truncated_tail={}",
+            self.frames.len(),
+            self.bytes(),
+            self.undated,
+            self.span().as_millis(),
+            self.truncated_tail,
+        )
+    }
+}
+
+fn replay_event_time(frame: &[u8], format: ConvertFormat, limits: &Limits) -> Option<SystemTime> {
+    let cot_xml = match format {
+        ConvertFormat::Xml => frame.to_vec(),
+        ConvertFormat::TakV1 => {
+            rustak_wire::decode_payload_for_format(mesh_body(frame), WireFormat::TakProtocolV1)
+                .ok()?
+        }
+    };
+    parse_event(&cot_xml, limits)?.time.to_system_time().ok()
+}
+
+/// Where `rustak replay` retransmits frames.
+#[derive(Debug)]
+pub enum ReplaySink {
+    Udp(UdpTransport),
+    Stream(TransportConnection<ManagedStream>),
+}
+
+impl ReplaySink {
+    /// Binds the UDP socket or dials the TCP/TLS stream in `transport`.
+    pub async fn open(
+        transport: &TransportConfig,
+        config: Option<&rustak_config::RustakConfig>,
+    ) -> Result<Self, CliError> {
+        match &transport.protocol {
+            Protocol::Udp { .. } => Ok(Self::Udp(UdpTransport::bind(transport)?)),
+            Protocol::Tcp { .. } | Protocol::Tls { .. } => {
+                let mut manager =
+                    ConnectionManager::new(transport.clone(), DowngradePolicy::FailOpen)?;
+                if matches!(transport.protocol, Protocol::Tls { .. }) {
+                    manager = with_tls_connector(manager, config)?;
+                }
+                Ok(Self::Stream(manager.connect().await?))
+            }
+            Protocol::WebSocket { .. } => Err(CliError::UnsupportedSendProtocol {
+                protocol: "websocket",
+            }),
+        }
+    }
+
+    /// Sends one recorded frame. TAK protocol v1 frames gain or lose the
+    /// mesh header to match the target; oversize datagrams are reported and
+    /// skipped so one frame cannot abort the replay.
+    pub async fn send(&mut self, frame: &[u8], format: ConvertFormat) -> Result<(), CliError> {
+        match self {
+            Self::Udp(udp) => {
+                let datagram;
+                let frame =
+                    if format == ConvertFormat::TakV1 && !MeshFrameCodec::is_mesh_frame(frame) {
+                        datagram = TAK_MESH.encode(frame).map_err(UdpTransportError::from)?;
+                        datagram.as_slice()
+                    } else {
+                        frame
+                    };
+                match udp.send(frame).await? {
+                    UdpSendDecision::SendDatagrams(_) => {}
+                    UdpSendDecision::SendTruncated { original_bytes, .. } => tracing::warn!(
+                        kind = "truncated",
+                        original_bytes,
+                        destination = %udp.destination(),
+                        "replay_warning"
+                    ),
+                    UdpSendDecision::RerouteToStream { payload_bytes }
+                    | UdpSendDecision::DropOversize { payload_bytes, .. } => tracing::warn!(
+                        kind = "oversize",
+                        payload_bytes,
+                        destination = %udp.destination(),
+                        "replay_warning"
+                    ),
+                }
+                Ok(())
+            }
+            Self::Stream(connection) => {
+                let frame = match format {
+                    ConvertFormat::TakV1 => mesh_body(frame),
+                    ConvertFormat::Xml => frame,
+                };
+                Ok(connection.send_frame(frame).await?)
+            }
+        }
+    }
+}
+
+/// Sends every frame of `timeline` once, holding each back until its
+/// capture offset divided by `speed` has elapsed; `speed` 0 sends as fast
+/// as possible. Returns the bytes sent.
+pub async fn replay_timeline(
+    sink: &mut ReplaySink,
+    timeline: &ReplayTimeline,
+    speed: f64,
+) -> Result<u64, CliError> {
+    let Some(first) = timeline.frames.first() else {
+        return Ok(0);
+    };
+    let start = tokio::time::Instant::now();
+    let mut bytes = 0;
+    for frame in &timeline.frames {
+        if speed > 0.0 {
+            let offset = frame
+                .observed
+                .monotonic
+                .saturating_duration_since(first.observed.monotonic);
+            if let Some(deadline) = Duration::try_from_secs_f64(offset.as_secs_f64() / speed)
+                .ok()
+                .and_then(|delay| start.checked_add(delay))
+            {
+                tokio::time::sleep_until(deadline).await;
+            }
+        }
+        sink.send(&frame.message, timeline.format).await?;
+        bytes += frame.message.len() as u64;
+    }
+    Ok(bytes)
+}
+
+/// Resolves `--target` (UDP) or `--tcp`, falling back to the loaded
+/// config's protocol.
+fn replay_transport(
+    args: &ReplayArgs,
+    config: Option<&rustak_config::RustakConfig>,
+    format: ConvertFormat,
+) -> Result<TransportConfig, CliError> {
+    let base = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let protocol = if let Some(udp) = args.target.as_deref() {
+        let addr = parse_endpoint(udp)?;
+        let bind_ip = if addr.is_ipv4() {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        };
+        Protocol::Udp {
+            bind_addr: SocketAddr::new(bind_ip, 0),
+            target: udp_target(addr),
+        }
+    } else if let Some(tcp) = args.tcp.as_deref() {
+        Protocol::Tcp {
+            addr: parse_endpoint(tcp)?,
+        }
+    } else if config.is_some() {
+        base.protocol.clone()
+    } else {
+        return Err(CliError::ReplayTargetRequired);
+    };
+    Ok(TransportConfig {
+        protocol,
+        wire_format: WireFormat::from(format),
+        ..base
+    })
+}
+
+/// Parses `--start` and `--end`, which must leave a non-empty window.
+fn replay_window(args: &ReplayArgs) -> Result<(Option<SystemTime>, Option<SystemTime>), CliError> {
+    let parse = |flag: &'static str, value: Option<&str>| {
+        value
+            .map(|value| {
+                TimestampUtc::parse_rfc3339(value)
+                    .ok()
+                    .and_then(|time| time.to_system_time().ok())
+                    .ok_or_else(|| CliError::ReplayTimeInvalid {
+                        flag,
+                        value: value.to_owned(),
+                    })
+            })
+            .transpose()
+    };
+    let start = parse("--start", args.start.as_deref())?;
+    let end = parse("--end", args.end.as_deref())?;
+    if let (Some(start_time), Some(end_time)) = (start, end) {
+        if start_time >= end_time {
+            return Err(CliError::ReplayWindowEmpty {
+                start: args.start.clone().unwrap_or_default(),
+                end: args.end.clone().unwrap_or_default(),
+            });
+        }
+    }
+    Ok((start, end))
+}
+
+pub(crate) fn run_replay(args: ReplayArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    if !args.speed.is_finite() || args.speed < 0.0 {
+        return Err(CliError::ReplaySpeedInvalid { speed: args.speed });
+    }
+    let input = args.input.as_deref().ok_or(CliError::ReplayInputRequired)?;
+    let limits = config_limits(config.as_ref());
+    if args.digest {
+        return run_replay_digest(input, args.compare.as_deref(), &limits);
+    }
+    let (start, end) = replay_window(&args)?;
+    let source = fs::File::open(input).map_err(|source| CliError::InputRead {
+        path: input.display().to_string(),
+        source,
+    })?;
+    let timeline = ReplayTimeline::read(io::BufReader::new(source), start, end, &limits)?;
+    if args.dry_run {
+        println!("{}", timeline.stats_line(args.speed));
+        return Ok(());
+    }
+    let transport = replay_transport(&args, config.as_ref(), timeline.format)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let started = Instant::now();
+    let mut passes = 0_u64;
+    let mut bytes = 0_u64;
+    let replayed = runtime.block_on(async {
+        let mut sink = ReplaySink::open(&transport, config.as_ref()).await?;
+        tracing::info!(
+            frames = timeline.frames.len(),
+            speed = args.speed,
+            repeat = args.repeat,
+            "replay_started"
+        );
+        let replay = async {
+            loop {
+                bytes += replay_timeline(&mut sink, &timeline, args.speed).await?;
+                passes += 1;
+                if !args.repeat || timeline.frames.is_empty() {
+                    return Ok(());
+                }
+            }
+        };
+        tokio::select! {
+            result = replay => result,
+            signal = tokio::signal::ctrl_c() => {
+                signal.map_err(|source| CliError::Runtime { source })?;
+                tracing::info!("replay_interrupted");
+                Ok(())
+            }
+        }
+    });
+    println!(
+        "replay frames={} bytes={bytes} passes={passes} elapsed_ms={}",
+        timeline.frames.len() as u64 * passes,
+        started.elapsed().as_millis()
+    );
+    replayed
+}
+
+/// `rustak replay --digest`: prints one `replay_digest` line per capture and
+/// with `--compare` fails unless both digests match.
+fn run_replay_digest(
+    input: &Path,
+    compare: Option<&Path>,
+    limits: &Limits,
+) -> Result<(), CliError> {
+    let digest = capture_digest(input, limits)?;
+    println!("{}", replay_digest_line(input, &digest));
+    let Some(compare) = compare else {
+        return Ok(());
+    };
+    let other = capture_digest(compare, limits)?;
+    println!("{}", replay_digest_line(compare, &other));
+    if digest.sha256 != other.sha256 {
+        return Err(CliError::ReplayDigestMismatch {
+            input: input.display().to_string(),
+            compare: compare.display().to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn capture_digest(path: &Path, limits: &Limits) -> Result<ReplayDigest, CliError> {
+    let source = fs::File::open(path).map_err(|source| CliError::InputRead {
+        path: path.display().to_string(),
+        source,
+    })?;
+    replay_digest(io::BufReader::new(source), limits.clone())
+        .map_err(|source| CliError::Facade(RustakError::Record(source)))
+}
+
+fn replay_digest_line(path: &Path, digest: &ReplayDigest) -> String {
+    format!(
+        "replay_digest input={} sha256={digest} chunks={} decoded={} undecodable={} truncated_tail={}",
+        path.display(),
+        digest.chunks,
+        digest.decoded,
+        digest.undecodable,
+        digest.truncated_tail
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use clap::Parser;
+    use rustak_core::time::TimestampUtc;
+    use rustak_core::CotEvent;
+    use rustak_limits::Limits;
+    use rustak_record::{ChunkCompression, TakrecHeader, TakrecWriter};
+    use rustak_transport::Protocol;
+    use rustak_wire::WireFormat;
+
+    use super::{replay_timeline, replay_transport, ReplayArgs, ReplaySink, ReplayTimeline};
+    use crate::tests::replay_event;
+    use crate::{execute_command, Cli, CliError, Command, ConvertFormat, ExitStatus, TAK_MESH};
+
+    fn replay_args(argv: &[&str]) -> ReplayArgs {
+        let cli =
+            Cli::try_parse_from(["rustak", "replay"].iter().chain(argv)).expect("replay args");
+        let Command::Replay(args) = cli.command else {
+            panic!("expected replay");
+        };
+        args
+    }
+
+    #[test]
+    fn replay_timeline_reconstructs_gaps_from_event_times() {
+        let origin = Instant::now();
+        let timeline = ReplayTimeline::from_frames(
+            vec![
+                replay_event("a", "2023-11-14T22:13:20.000Z"),
+                replay_event("b", "2023-11-14T22:13:20.500Z"),
+                b"not a cot event".to_vec(),
+                replay_event("c", "2023-11-14T22:13:22.000Z"),
+                replay_event("d", "2023-11-14T22:13:21.000Z"),
+            ],
+            ConvertFormat::Xml,
+            origin,
+            &Limits::default(),
+        );
+        let offsets = timeline
+            .frames
+            .iter()
+            .map(|frame| frame.observed.monotonic.duration_since(origin).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 500, 500, 2000, 2000]);
+        assert_eq!(timeline.undated, 1);
+        assert_eq!(
+            timeline.frames[4].observed.wall,
+            time("2023-11-14T22:13:21Z")
+        );
+        assert_eq!(timeline.span(), Duration::from_secs(2));
+        let stats = timeline.stats_line(2.0);
+        assert!(stats.starts_with("replay_dry_run frames=5 "), "{stats}");
+        assert!(
+            stats.contains(" span_ms=2000 speed=2 expected_ms=1000 "),
+            "{stats}"
+        );
+        assert!(timeline.stats_line(0.0).contains(" expected_ms=0 "));
+    }
+
+    fn time(value: &str) -> SystemTime {
+        TimestampUtc::parse_rfc3339(value)
+            .expect("time")
+            .to_system_time()
+            .expect("system time")
+    }
+
+    fn frame_uids(timeline: &ReplayTimeline) -> Vec<String> {
+        timeline
+            .frames
+            .iter()
+            .map(|frame| {
+                let xml = String::from_utf8_lossy(&frame.message);
+                CotEvent::from_xml(&xml, &Limits::default())
+                    .map_or_else(|_| xml.into_owned(), |event| event.uid)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_timeline_reads_only_the_requested_window() {
+        let mut writer = TakrecWriter::new(
+            Vec::new(),
+            TakrecHeader::new("rustak", "0.1.0", "cot-xml", "default"),
+        )
+        .expect("writer");
+        for frame in [
+            replay_event("a", "2023-11-14T22:13:20.000Z"),
+            replay_event("b", "2023-11-14T22:13:20.500Z"),
+            b"not a cot event".to_vec(),
+            replay_event("c", "2023-11-14T22:13:22.000Z"),
+            replay_event("d", "2023-11-14T22:13:21.000Z"),
+        ] {
+            writer.append_chunk(&frame).expect("append");
+        }
+        let recording = writer.into_inner().expect("finish");
+
+        let window = ReplayTimeline::read(
+            Cursor::new(recording),
+            Some(time("2023-11-14T22:13:20.500Z")),
+            Some(time("2023-11-14T22:13:22Z")),
+            &Limits::default(),
+        )
+        .expect("timeline");
+        assert_eq!(frame_uids(&window), ["b", "not a cot event", "d"]);
+        assert_eq!(window.undated, 1);
+        assert!(!window.truncated_tail);
+    }
+
+    #[test]
+    fn replay_timeline_prefers_recorded_capture_times() {
+        let mut writer = TakrecWriter::with_capture_times(
+            Vec::new(),
+            TakrecHeader::new("rustak", "0.1.0", "cot-xml", "default"),
+            ChunkCompression::None,
+        )
+        .expect("writer");
+        let captured = time("2024-01-01T00:00:00Z");
+        for (offset, uid) in [(0, "a"), (250, "b"), (1000, "c"), (1500, "d")] {
+            writer
+                .append_chunk_at(
+                    &replay_event(uid, "2023-11-14T22:13:20.000Z"),
+                    captured + Duration::from_millis(offset),
+                )
+                .expect("append");
+        }
+        writer
+            .append_chunk_at(b"not a cot event", captured + Duration::from_millis(2000))
+            .expect("append");
+        let recording = writer.into_inner().expect("finish");
+
+        let timeline = ReplayTimeline::read(
+            Cursor::new(recording.clone()),
+            None,
+            None,
+            &Limits::default(),
+        )
+        .expect("timeline");
+        assert_eq!(timeline.undated, 0);
+        assert_eq!(timeline.span(), Duration::from_secs(2));
+        assert_eq!(
+            timeline.frames[1].observed.wall,
+            captured + Duration::from_millis(250)
+        );
+
+        let window = ReplayTimeline::read(
+            Cursor::new(recording),
+            Some(captured + Duration::from_millis(250)),
+            Some(captured + Duration::from_millis(1500)),
+            &Limits::default(),
+        )
+        .expect("timeline");
+        assert_eq!(frame_uids(&window), ["b", "c"]);
+        assert_eq!(window.span(), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn replay_requires_input_target_and_a_valid_speed() {
+        let error =
+            execute_command(Command::Replay(replay_args(&[]))).expect_err("replay needs an input");
+        assert!(matches!(error, CliError::ReplayInputRequired));
+        let error = execute_command(Command::Replay(replay_args(&[
+            "--input",
+            "x.takrec",
+            "--speed=-1",
+        ])))
+        .expect_err("negative speed");
+        assert!(matches!(error, CliError::ReplaySpeedInvalid { .. }));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+        let error = execute_command(Command::Replay(replay_args(&[
+            "--input",
+            "x.takrec",
+            "--start",
+            "yesterday",
+        ])))
+        .expect_err("bad start time");
+        assert!(matches!(
+            error,
+            CliError::ReplayTimeInvalid {
+                flag: "--start",
+                ..
+            }
+        ));
+        let error = execute_command(Command::Replay(replay_args(&[
+            "--input",
+            "x.takrec",
+            "--start",
+            "2024-01-01T00:00:01Z",
+            "--end",
+            "2024-01-01T00:00:00Z",
+        ])))
+        .expect_err("empty window");
+        assert!(matches!(error, CliError::ReplayWindowEmpty { .. }));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+
+        let args = replay_args(&["--input", "x.takrec"]);
+        assert!(matches!(
+            replay_transport(&args, None, ConvertFormat::Xml),
+            Err(CliError::ReplayTargetRequired)
+        ));
+        let transport = replay_transport(
+            &replay_args(&["--tcp", "127.0.0.1:8087"]),
+            None,
+            ConvertFormat::TakV1,
+        )
+        .expect("tcp target");
+        assert_eq!(
+            transport.protocol,
+            Protocol::Tcp {
+                addr: "127.0.0.1:8087".parse().expect("addr")
+            }
+        );
+        assert_eq!(transport.wire_format, WireFormat::TakProtocolV1);
+    }
+
+    #[test]
+    fn replay_digest_compares_captures_across_framings() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_digest_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let write = |name: &str, payloads: &[Vec<u8>]| {
+            let mut writer =
+                TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
+            for payload in payloads {
+                writer.append_chunk(payload).expect("append");
+            }
+            let path = dir.join(name);
+            std::fs::write(&path, writer.into_inner().expect("finish")).expect("write capture");
+            path.display().to_string()
+        };
+        let alpha = replay_event("a", "2024-01-01T00:00:00.000Z");
+        let beta = replay_event("b", "2024-01-01T00:00:01.000Z");
+        let mut mesh = TAK_MESH.header().to_vec();
+        mesh.extend(rustak_proto::encode_v1_payload(&beta).expect("encode"));
+        let recorded = write("recorded.takrec", &[alpha.clone(), beta.clone()]);
+        let replayed = write("replayed.takrec", &[alpha.clone(), mesh]);
+        let reordered = write("reordered.takrec", &[beta, alpha]);
+
+        execute_command(Command::Replay(replay_args(&[
+            "--input",
+            &recorded,
+            "--digest",
+            "--compare",
+            &replayed,
+        ])))
+        .expect("equivalent captures");
+        let error = execute_command(Command::Replay(replay_args(&[
+            "--input",
+            &recorded,
+            "--digest",
+            "--compare",
+            &reordered,
+        ])))
+        .expect_err("reordered capture");
+        assert!(matches!(error, CliError::ReplayDigestMismatch { .. }));
+        assert_eq!(error.exit_status(), ExitStatus::Validation);
+        assert!(Cli::try_parse_from([
+            "rustak",
+            "replay",
+            "--input",
+            "x",
+            "--digest",
+            "--target",
+            "127.0.0.1:1"
+        ])
+        .is_err());
+        assert!(
+            Cli::try_parse_from(["rustak", "replay", "--input", "x", "--compare", "y"]).is_err()
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn replay_retransmits_capture_over_udp_with_mesh_header() {
+        let receiver = tokio::net::UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let target = receiver.local_addr().expect("addr").to_string();
+
+        let mut writer = TakrecWriter::new(
+            Vec::new(),
+            TakrecHeader::new("rustak", "0.1.0", "tak-v1", "default"),
+        )
+        .expect("writer");
+        let stream_frame =
+            rustak_proto::encode_v1_payload(&replay_event("a", "2023-11-14T22:13:20.000Z"))
+                .expect("encode");
+        let mut mesh_frame = TAK_MESH.header().to_vec();
+        mesh_frame.extend(
+            rustak_proto::encode_v1_payload(&replay_event("b", "2023-11-14T22:13:20.010Z"))
+                .expect("encode"),
+        );
+        writer.append_chunk(&stream_frame).expect("append");
+        writer.append_chunk(&mesh_frame).expect("append");
+        let recording = writer.into_inner().expect("finish");
+
+        let timeline = ReplayTimeline::read(Cursor::new(recording), None, None, &Limits::default())
+            .expect("timeline");
+        assert_eq!(timeline.format, ConvertFormat::TakV1);
+        assert_eq!(timeline.undated, 0);
+        assert_eq!(timeline.span(), Duration::from_millis(10));
+
+        let transport =
+            replay_transport(&replay_args(&["--target", &target]), None, timeline.format)
+                .expect("transport");
+        let mut sink = ReplaySink::open(&transport, None).await.expect("sink");
+        let bytes = replay_timeline(&mut sink, &timeline, 1.0)
+            .await
+            .expect("replay");
+        assert_eq!(bytes, timeline.bytes());
+
+        let mut datagram = [0_u8; 2048];
+        let (len, _) = receiver.recv_from(&mut datagram).await.expect("first");
+        assert_eq!(&datagram[..3], TAK_MESH.header().as_slice());
+        assert_eq!(&datagram[3..len], stream_frame.as_slice());
+        let (len, _) = receiver.recv_from(&mut datagram).await.expect("second");
+        assert_eq!(&datagram[..len], mesh_frame.as_slice());
+    }
+}
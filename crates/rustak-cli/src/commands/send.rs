@@ -0,0 +1,434 @@
+//! `rustak send`: one CoT event over UDP, TCP or TLS.
+
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+
+use clap::Args;
+use rustak_core::time::TimestampUtc;
+use rustak_core::{
+    clock_annotation, ClockStatus, ClockThresholds, Position, StatusFileClock, SystemClock,
+    TimeSource,
+};
+use rustak_transport::{
+    ConnectionManager, Protocol, TransportConfig, TransportFraming, UdpSendDecision, UdpTransport,
+    UdpTransportError,
+};
+use rustak_wire::{DowngradePolicy, WireFormat};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    load_optional_config, parse_endpoint, udp_target, validate_transport_defaults,
+    with_tls_connector, CliError, ConvertFormat, TAK_MESH,
+};
+
+#[derive(Debug, Args)]
+pub struct SendArgs {
+    #[arg(long = "type", help = "CoT type string to emit (default a-f-G-U-C)")]
+    pub cot_type: Option<String>,
+    #[arg(
+        long,
+        allow_negative_numbers = true,
+        help = "Latitude in decimal degrees"
+    )]
+    pub lat: Option<f64>,
+    #[arg(
+        long,
+        allow_negative_numbers = true,
+        help = "Longitude in decimal degrees"
+    )]
+    pub lon: Option<f64>,
+    #[arg(
+        long,
+        allow_negative_numbers = true,
+        help = "Height above the WGS84 ellipsoid in metres"
+    )]
+    pub alt: Option<f64>,
+    #[arg(long, help = "Event UID (default rustak-cli-<pid>)")]
+    pub uid: Option<String>,
+    #[arg(long, help = "Callsign to put in the event's contact detail")]
+    pub callsign: Option<String>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds until the event goes stale"
+    )]
+    pub stale: u64,
+    #[arg(long, help = "UDP destination (for example 239.2.3.1:6969)")]
+    pub udp: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "udp",
+        help = "TCP streaming endpoint (for example 127.0.0.1:8087)"
+    )]
+    pub tcp: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format to encode with; defaults to the config's wire_format or xml"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "`chronyc tracking` or `ntpq -c rv` output used to flag a poorly synchronised clock"
+    )]
+    pub clock_status: Option<PathBuf>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// CoT type `send` emits without `--type`: a friendly ground unit.
+pub const DEFAULT_SEND_COT_TYPE: &str = "a-f-G-U-C";
+
+/// `hae`/`ce`/`le` value CoT uses for "unknown".
+const UNKNOWN_POINT_METERS: f64 = 9_999_999.0;
+
+pub(crate) fn run_send(args: SendArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    let clock: Box<dyn TimeSource> = match &args.clock_status {
+        Some(path) => Box::new(StatusFileClock::new(path, ClockThresholds::default())),
+        None => Box::new(SystemClock),
+    };
+    let status = clock.status();
+    let mut event = SendEvent::from_args(&args, clock.now())?;
+    if status.is_poor() {
+        tracing::warn!(
+            kind = "clock_confidence",
+            confidence = status.confidence.as_str(),
+            estimated_error_ms = %status.estimated_error.map_or_else(
+                || "unknown".to_owned(),
+                |error| format!("{:.1}", error.as_secs_f64() * 1_000.0)
+            ),
+            source = %status.source,
+            "send_warning"
+        );
+        event = event.with_clock_status(&status);
+    }
+    let transport = send_transport(&args, config.as_ref())?;
+    let payload = event.encode(&transport)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    runtime.block_on(send_payload(&payload, &transport, config.as_ref()))?;
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "send uid={} bytes={}", event.uid, payload.len())
+        .map_err(|source| CliError::StdoutWrite { source })
+}
+
+/// Resolves `--udp`/`--tcp`, falling back to the loaded config's protocol.
+/// `--format` overrides the config's `wire_format`.
+fn send_transport(
+    args: &SendArgs,
+    config: Option<&rustak_config::RustakConfig>,
+) -> Result<TransportConfig, CliError> {
+    let base = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let protocol = if let Some(udp) = args.udp.as_deref() {
+        let addr = parse_endpoint(udp)?;
+        let bind_ip = if addr.is_ipv4() {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        };
+        Protocol::Udp {
+            bind_addr: SocketAddr::new(bind_ip, 0),
+            target: udp_target(addr),
+        }
+    } else if let Some(tcp) = args.tcp.as_deref() {
+        Protocol::Tcp {
+            addr: parse_endpoint(tcp)?,
+        }
+    } else if config.is_some() {
+        base.protocol.clone()
+    } else {
+        return Err(CliError::SendEndpointRequired);
+    };
+    Ok(TransportConfig {
+        protocol,
+        wire_format: args.format.map_or(base.wire_format, WireFormat::from),
+        ..base
+    })
+}
+
+/// The CoT event `rustak send` transmits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendEvent {
+    pub uid: String,
+    pub cot_xml: String,
+}
+
+impl SendEvent {
+    /// Builds a human-entered (`how="h-e"`) event at `--lat`/`--lon`,
+    /// timestamped `now` and stale `--stale` seconds later.
+    pub fn from_args(args: &SendArgs, now: TimestampUtc) -> Result<Self, CliError> {
+        let (Some(lat), Some(lon)) = (args.lat, args.lon) else {
+            return Err(CliError::SendPositionRequired);
+        };
+        let mut position = Position::new(lat, lon).map_err(CliError::InvalidPosition)?;
+        if let Some(alt) = args.alt {
+            position = position.with_hae(alt).map_err(CliError::InvalidPosition)?;
+        }
+
+        let uid = args
+            .uid
+            .clone()
+            .unwrap_or_else(|| format!("rustak-cli-{}", std::process::id()));
+        let cot_type = args.cot_type.as_deref().unwrap_or(DEFAULT_SEND_COT_TYPE);
+        let time = now.to_rfc3339_millis();
+        let stale_nanos = i128::from(args.stale).saturating_mul(1_000_000_000);
+        let stale = TimestampUtc::from_unix_nanos(now.unix_nanos().saturating_add(stale_nanos))
+            .to_rfc3339_millis();
+        let detail = args
+            .callsign
+            .as_deref()
+            .map(|callsign| format!("<contact callsign=\"{}\"/>", escape_xml_attr(callsign)))
+            .unwrap_or_default();
+
+        let cot_xml = format!(
+            "<event version=\"2.0\" uid=\"{uid}\" type=\"{cot_type}\" how=\"h-e\" time=\"{time}\" start=\"{time}\" stale=\"{stale}\">\
+<point lat=\"{lat}\" lon=\"{lon}\" hae=\"{hae}\" ce=\"{ce}\" le=\"{le}\"/>\
+<detail>{detail}</detail></event>",
+            uid = escape_xml_attr(&uid),
+            cot_type = escape_xml_attr(cot_type),
+            lat = position.latitude(),
+            lon = position.longitude(),
+            hae = position.hae().unwrap_or(UNKNOWN_POINT_METERS),
+            ce = position.ce().unwrap_or(UNKNOWN_POINT_METERS),
+            le = position.le().unwrap_or(UNKNOWN_POINT_METERS),
+        );
+        Ok(Self { uid, cot_xml })
+    }
+
+    /// Adds a `<__clock>` detail element when `status` is poor so receivers
+    /// know the timestamps may be off.
+    #[must_use]
+    pub fn with_clock_status(mut self, status: &ClockStatus) -> Self {
+        if let Some(annotation) = clock_annotation(status) {
+            self.cot_xml = self
+                .cot_xml
+                .replacen("</detail>", &format!("{annotation}</detail>"), 1);
+        }
+        self
+    }
+
+    /// Encodes for `transport.wire_format`. TAK protocol v1 over UDP gets
+    /// the mesh header; streams frame the bare payload.
+    pub fn encode(&self, transport: &TransportConfig) -> Result<Vec<u8>, CliError> {
+        let payload =
+            rustak_wire::encode_payload_for_format(self.cot_xml.as_bytes(), transport.wire_format)?;
+        if TransportFraming::for_config(transport) == TransportFraming::TakProtocolMeshHeader {
+            return Ok(TAK_MESH.encode(&payload).map_err(UdpTransportError::from)?);
+        }
+        Ok(payload)
+    }
+}
+
+/// Transmits one encoded payload over `transport.protocol`. Streams are
+/// dialled through [`ConnectionManager`] and shut down once the frame is
+/// written; TLS takes its identity from the config's `crypto` and
+/// `certificates` sections.
+pub async fn send_payload(
+    payload: &[u8],
+    transport: &TransportConfig,
+    config: Option<&rustak_config::RustakConfig>,
+) -> Result<(), CliError> {
+    match &transport.protocol {
+        Protocol::Udp { .. } => {
+            let udp = UdpTransport::bind(transport)?;
+            match udp.send(payload).await? {
+                UdpSendDecision::SendDatagrams(_) => Ok(()),
+                UdpSendDecision::SendTruncated { original_bytes, .. } => {
+                    tracing::warn!(
+                        kind = "truncated",
+                        original_bytes,
+                        destination = %udp.destination(),
+                        "send_warning"
+                    );
+                    Ok(())
+                }
+                UdpSendDecision::RerouteToStream { payload_bytes }
+                | UdpSendDecision::DropOversize { payload_bytes, .. } => {
+                    Err(CliError::SendOversize { payload_bytes })
+                }
+            }
+        }
+        Protocol::Tcp { .. } | Protocol::Tls { .. } => {
+            let mut manager = ConnectionManager::new(transport.clone(), DowngradePolicy::FailOpen)?;
+            if matches!(transport.protocol, Protocol::Tls { .. }) {
+                manager = with_tls_connector(manager, config)?;
+            }
+            let mut connection = manager.connect().await?;
+            connection.send_frame(payload).await?;
+            connection
+                .into_inner()
+                .shutdown()
+                .await
+                .map_err(|error| CliError::Transport(error.into()))
+        }
+        Protocol::WebSocket { .. } => Err(CliError::UnsupportedSendProtocol {
+            protocol: "websocket",
+        }),
+    }
+}
+
+fn escape_xml_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use rustak_core::time::TimestampUtc;
+    use rustak_limits::Limits;
+
+    use super::{send_payload, send_transport, SendArgs, SendEvent};
+    use crate::{cot_warnings, execute_command, Cli, CliError, Command, ExitStatus};
+
+    fn send_args(argv: &[&str]) -> SendArgs {
+        let cli = Cli::try_parse_from(["rustak", "send"].iter().chain(argv)).expect("send args");
+        let Command::Send(args) = cli.command else {
+            panic!("expected send");
+        };
+        args
+    }
+
+    #[test]
+    fn send_builds_event_and_requires_position_and_endpoint() {
+        let error = execute_command(Command::Send(send_args(&["--lat", "1"])))
+            .expect_err("send needs a longitude");
+        assert!(matches!(error, CliError::SendPositionRequired));
+        assert_eq!(error.exit_status(), ExitStatus::Usage);
+
+        let error = execute_command(Command::Send(send_args(&["--lat", "91", "--lon", "0"])))
+            .expect_err("latitude out of range");
+        assert!(matches!(error, CliError::InvalidPosition(_)));
+
+        let error = execute_command(Command::Send(send_args(&["--lat", "1", "--lon", "2"])))
+            .expect_err("send needs somewhere to send");
+        assert!(matches!(error, CliError::SendEndpointRequired));
+
+        let args = send_args(&[
+            "--lat",
+            "38.5",
+            "--lon",
+            "-77.25",
+            "--alt",
+            "12",
+            "--uid",
+            "CLI-1",
+            "--callsign",
+            "Alpha & Co",
+            "--stale",
+            "30",
+        ]);
+        let event = SendEvent::from_args(&args, TimestampUtc::from_unix_seconds(1_700_000_000))
+            .expect("event");
+        assert_eq!(event.uid, "CLI-1");
+        assert_eq!(
+            event.cot_xml,
+            "<event version=\"2.0\" uid=\"CLI-1\" type=\"a-f-G-U-C\" how=\"h-e\" time=\"2023-11-14T22:13:20.000Z\" start=\"2023-11-14T22:13:20.000Z\" stale=\"2023-11-14T22:13:50.000Z\">\
+<point lat=\"38.5\" lon=\"-77.25\" hae=\"12\" ce=\"9999999\" le=\"9999999\"/>\
+<detail><contact callsign=\"Alpha &amp; Co\"/></detail></event>"
+        );
+        assert!(cot_warnings(event.cot_xml.as_bytes(), &Limits::default()).is_empty());
+
+        let degraded = rustak_core::ClockStatus {
+            confidence: rustak_core::ClockConfidence::Degraded,
+            estimated_error: Some(std::time::Duration::from_millis(250)),
+            source: "chrony",
+        };
+        let annotated = event.with_clock_status(&degraded);
+        assert!(annotated.cot_xml.ends_with(
+            "<contact callsign=\"Alpha &amp; Co\"/><__clock confidence=\"degraded\" error_ms=\"250.0\" source=\"chrony\"/></detail></event>"
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_udp_emits_mesh_tak_v1_datagram() {
+        let loopback: std::net::SocketAddr = "127.0.0.1:0".parse().expect("addr");
+        let mut receiver =
+            rustak_transport::UdpTransport::bind(&rustak_transport::TransportConfig {
+                protocol: rustak_transport::Protocol::Udp {
+                    bind_addr: loopback,
+                    target: rustak_transport::UdpTarget::Unicast(loopback),
+                },
+                ..rustak_transport::TransportConfig::default()
+            })
+            .expect("bind");
+        let addr = receiver
+            .socket()
+            .local_addr()
+            .expect("local addr")
+            .to_string();
+
+        let args = send_args(&[
+            "--lat", "1", "--lon", "2", "--uid", "U-1", "--udp", &addr, "--format", "tak-v1",
+        ]);
+        let event = SendEvent::from_args(&args, TimestampUtc::now()).expect("event");
+        let transport = send_transport(&args, None).expect("transport");
+        let payload = event.encode(&transport).expect("encode");
+        send_payload(&payload, &transport, None)
+            .await
+            .expect("send");
+
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("datagram arrives")
+            .expect("recv");
+        let body = received
+            .message
+            .strip_prefix(&[0xbf, 0x01, 0xbf])
+            .expect("mesh header");
+        let limits = rustak_limits::Limits::conservative_defaults();
+        assert_eq!(
+            rustak_proto::decode_v1_event(body, &limits).expect("decode"),
+            rustak_core::CotEvent::from_xml(&event.cot_xml, &limits).expect("parse")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_tcp_streams_one_xml_frame() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr").to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.expect("read");
+            received
+        });
+
+        let args = send_args(&["--lat", "1", "--lon", "2", "--tcp", &addr]);
+        let event = SendEvent::from_args(&args, TimestampUtc::now()).expect("event");
+        let transport = send_transport(&args, None).expect("transport");
+        let payload = event.encode(&transport).expect("encode");
+        send_payload(&payload, &transport, None)
+            .await
+            .expect("send");
+
+        let received = server.await.expect("server");
+        assert_eq!(received, format!("{}\n", event.cot_xml).into_bytes());
+    }
+}
@@ -0,0 +1,659 @@
+//! `rustak sim`: simulated tracks driven by a scenario file.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use rustak_core::time::TimestampUtc;
+use rustak_core::Position;
+use rustak_sim::{
+    Route, RouteFollower, RouteMode, TrackCotEmitter, TrackEmitterConfig, TruthEngine,
+    TruthEngineConfig, TruthState, Waypoint,
+};
+use rustak_transport::{Protocol, TransportConfig};
+use rustak_wire::WireFormat;
+
+use crate::commands::replay::ReplaySink;
+use crate::{
+    load_optional_config, parse_endpoint, udp_target, validate_transport_defaults, CliError,
+    ConvertFormat,
+};
+
+#[derive(Debug, Args)]
+pub struct SimArgs {
+    #[arg(long, help = "Scenario YAML describing the simulated tracks")]
+    pub scenario: Option<PathBuf>,
+    #[arg(long, help = "UDP endpoint to send to (for example 239.2.3.1:6969)")]
+    pub target: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "target",
+        help = "TCP address to stream to (for example 10.0.0.5:8087)"
+    )]
+    pub tcp: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format to encode with; defaults to the config's wire_format or xml"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(
+        long,
+        value_name = "MS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Simulation step in milliseconds; overrides the scenario's step_millis"
+    )]
+    pub tick_millis: Option<u64>,
+    #[arg(
+        long,
+        default_value_t = 1.0,
+        help = "Playback speed multiplier; 0 runs the scenario as fast as possible"
+    )]
+    pub speed: f64,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// `step_millis` of a scenario that does not set one.
+pub const DEFAULT_SIM_STEP_MILLIS: u64 = 1_000;
+
+/// `stale_secs` of a scenario that does not set one.
+pub const DEFAULT_SIM_STALE_SECS: u64 = 30;
+
+/// Top speed of an entity that does not set `max_speed_mps`.
+const DEFAULT_SIM_MAX_SPEED_MPS: f64 = 300.0;
+
+/// A `rustak sim --scenario` file: entities start at metre offsets from
+/// `origin` and move at their initial velocity, perturbed by up to
+/// `jitter_mps` each step, unless they fly a `route` of waypoints.
+///
+/// ```yaml
+/// name: convoy
+/// seed: 7
+/// duration_ticks: 600
+/// step_millis: 1000
+/// origin: { lat: 51.5, lon: -0.12, hae: 30.0 }
+/// entities:
+///   - uid: sim-lead
+///     callsign: LEAD
+///     cot_type: a-f-G-U-C
+///     east_mps: 4.0
+///     north_mps: 1.5
+///     jitter_mps: 0.05
+///   - uid: sim-orbit
+///     cot_type: a-h-A-M-F-Q
+///     route:
+///       mode: ping-pong
+///       waypoints:
+///         - { lat: 51.5, lon: -0.12, speed_mps: 40.0, dwell_secs: 10 }
+///         - { lat: 51.52, lon: -0.1, hae: 300.0, speed_mps: 40.0 }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimScenario {
+    pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    pub duration_ticks: u64,
+    #[serde(default = "default_sim_step_millis")]
+    pub step_millis: u64,
+    pub origin: SimOrigin,
+    #[serde(default = "default_sim_stale_secs")]
+    pub stale_secs: u64,
+    pub entities: Vec<SimEntity>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimOrigin {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default)]
+    pub hae: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimEntity {
+    pub uid: String,
+    pub cot_type: String,
+    #[serde(default)]
+    pub callsign: Option<String>,
+    #[serde(default)]
+    pub east_m: f64,
+    #[serde(default)]
+    pub north_m: f64,
+    #[serde(default)]
+    pub east_mps: f64,
+    #[serde(default)]
+    pub north_mps: f64,
+    #[serde(default)]
+    pub jitter_mps: f64,
+    #[serde(default = "default_sim_max_speed_mps")]
+    pub max_speed_mps: f64,
+    /// Waypoints the entity flies instead of its offset and velocity.
+    #[serde(default)]
+    pub route: Option<SimRoute>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimRoute {
+    #[serde(default)]
+    pub mode: SimRouteMode,
+    pub waypoints: Vec<SimWaypoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SimRouteMode {
+    #[default]
+    Once,
+    Loop,
+    PingPong,
+}
+
+impl From<SimRouteMode> for RouteMode {
+    fn from(mode: SimRouteMode) -> Self {
+        match mode {
+            SimRouteMode::Once => Self::Once,
+            SimRouteMode::Loop => Self::Loop,
+            SimRouteMode::PingPong => Self::PingPong,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimWaypoint {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default)]
+    pub hae: Option<f64>,
+    /// Speed on the leg leaving this waypoint.
+    pub speed_mps: f64,
+    #[serde(default)]
+    pub dwell_secs: u64,
+}
+
+impl SimRoute {
+    fn follower(&self) -> Result<RouteFollower, CliError> {
+        let waypoints = self
+            .waypoints
+            .iter()
+            .map(|waypoint| {
+                let position = sim_position(waypoint.lat, waypoint.lon, waypoint.hae)?;
+                Ok(Waypoint::new(position, waypoint.speed_mps)
+                    .with_dwell(Duration::from_secs(waypoint.dwell_secs)))
+            })
+            .collect::<Result<_, CliError>>()?;
+        Ok(RouteFollower::new(Route::new(waypoints, self.mode.into()))?)
+    }
+}
+
+fn default_sim_step_millis() -> u64 {
+    DEFAULT_SIM_STEP_MILLIS
+}
+
+fn default_sim_stale_secs() -> u64 {
+    DEFAULT_SIM_STALE_SECS
+}
+
+fn default_sim_max_speed_mps() -> f64 {
+    DEFAULT_SIM_MAX_SPEED_MPS
+}
+
+impl SimScenario {
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let text = fs::read_to_string(path).map_err(|source| CliError::InputRead {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&text, &path.display().to_string())
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, CliError> {
+        Self::parse(text, "<inline>")
+    }
+
+    fn parse(text: &str, path: &str) -> Result<Self, CliError> {
+        let scenario: Self =
+            serde_yaml::from_str(text).map_err(|error| CliError::SimScenarioParse {
+                path: path.to_owned(),
+                message: error.to_string(),
+            })?;
+        rustak_sim::Scenario::new(&scenario.name, scenario.seed, scenario.duration_ticks)
+            .validate()?;
+        if scenario.entities.is_empty() {
+            return Err(CliError::SimNoEntities);
+        }
+        Ok(scenario)
+    }
+}
+
+pub(crate) fn sim_position(lat: f64, lon: f64, hae: Option<f64>) -> Result<Position, CliError> {
+    let mut position = Position::new(lat, lon).map_err(CliError::InvalidPosition)?;
+    if let Some(hae) = hae {
+        position = position.with_hae(hae).map_err(CliError::InvalidPosition)?;
+    }
+    Ok(position)
+}
+
+/// Metres, or metres per second, in the truth engine's millimetre units.
+fn sim_millimetres(metres: f64) -> f64 {
+    (metres * 1_000.0).round()
+}
+
+/// Truth engines and emitters for every entity of a scenario, stepped in
+/// lock-step.
+#[derive(Debug, Clone)]
+pub struct SimRun {
+    tracks: Vec<SimTrack>,
+    start: TimestampUtc,
+    duration_ticks: u64,
+    tick: u64,
+}
+
+impl SimRun {
+    /// Entity `n` is seeded with the scenario seed plus `n`, so entities
+    /// with the same motion still diverge. Event times count from `start`.
+    pub fn new(
+        scenario: &SimScenario,
+        step_millis: u64,
+        start: TimestampUtc,
+    ) -> Result<Self, CliError> {
+        let origin = sim_position(
+            scenario.origin.lat,
+            scenario.origin.lon,
+            scenario.origin.hae,
+        )?;
+        let tracks = scenario
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| {
+                let engine = TruthEngine::new(
+                    scenario.seed.wrapping_add(index as u64),
+                    TruthState {
+                        x_mm: sim_millimetres(entity.east_m) as i64,
+                        y_mm: sim_millimetres(entity.north_m) as i64,
+                        vx_mm_per_s: sim_millimetres(entity.east_mps) as i32,
+                        vy_mm_per_s: sim_millimetres(entity.north_mps) as i32,
+                    },
+                    TruthEngineConfig {
+                        step_millis,
+                        velocity_limit_mm_per_s: sim_millimetres(entity.max_speed_mps) as i32,
+                        velocity_jitter_mm_per_s: sim_millimetres(entity.jitter_mps) as i32,
+                    },
+                )?;
+                let emitter = TrackCotEmitter::new(TrackEmitterConfig {
+                    uid: entity.uid.clone(),
+                    cot_type: entity.cot_type.clone(),
+                    callsign: entity.callsign.clone(),
+                    origin: origin.clone(),
+                    stale: Duration::from_secs(scenario.stale_secs),
+                })?;
+                let route = entity.route.as_ref().map(SimRoute::follower).transpose()?;
+                Ok(SimTrack {
+                    engine,
+                    emitter,
+                    route,
+                })
+            })
+            .collect::<Result<_, CliError>>()?;
+        Ok(Self {
+            tracks,
+            start,
+            duration_ticks: scenario.duration_ticks,
+            tick: 0,
+        })
+    }
+
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Advances every entity one step and renders its event; `None` once
+    /// `duration_ticks` have run.
+    pub fn step(&mut self) -> Result<Option<Vec<rustak_core::CotEvent>>, CliError> {
+        if self.tick >= self.duration_ticks {
+            return Ok(None);
+        }
+        self.tick += 1;
+        let events = self
+            .tracks
+            .iter_mut()
+            .map(|track| track.step(self.start))
+            .collect::<Result<_, CliError>>()?;
+        Ok(Some(events))
+    }
+}
+
+/// One entity of a [`SimRun`]; `route`, when set, places it instead of the
+/// engine's state, which then only keeps the clock.
+#[derive(Debug, Clone)]
+struct SimTrack {
+    engine: TruthEngine,
+    emitter: TrackCotEmitter,
+    route: Option<RouteFollower>,
+}
+
+impl SimTrack {
+    fn step(&mut self, start: TimestampUtc) -> Result<rustak_core::CotEvent, CliError> {
+        let snapshot = self.engine.advance();
+        Ok(match &self.route {
+            Some(route) => {
+                self.emitter
+                    .emit_route_sample(&route.follow(&snapshot)?, &snapshot, start)?
+            }
+            None => self.emitter.emit(&snapshot, start)?,
+        })
+    }
+}
+
+/// What a finished `rustak sim` run sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SimSummary {
+    pub ticks: u64,
+    pub events: u64,
+    pub bytes: u64,
+}
+
+/// Runs `run` to completion, sending each tick's events `step` apart
+/// divided by `speed`; `speed` 0 sends as fast as possible.
+pub async fn sim_run(
+    sink: &mut ReplaySink,
+    run: &mut SimRun,
+    format: ConvertFormat,
+    step: Duration,
+    speed: f64,
+) -> Result<SimSummary, CliError> {
+    let start = tokio::time::Instant::now();
+    let mut summary = SimSummary::default();
+    while let Some(events) = run.step()? {
+        if speed > 0.0 {
+            let offset = step.saturating_mul(u32::try_from(run.tick() - 1).unwrap_or(u32::MAX));
+            if let Some(deadline) = Duration::try_from_secs_f64(offset.as_secs_f64() / speed)
+                .ok()
+                .and_then(|delay| start.checked_add(delay))
+            {
+                tokio::time::sleep_until(deadline).await;
+            }
+        }
+        for event in events {
+            let payload = rustak_wire::encode_payload_for_format(
+                event.to_xml().as_bytes(),
+                WireFormat::from(format),
+            )?;
+            sink.send(&payload, format).await?;
+            summary.events += 1;
+            summary.bytes += payload.len() as u64;
+        }
+        summary.ticks += 1;
+    }
+    Ok(summary)
+}
+
+/// Resolves `--target` (UDP) or `--tcp`, falling back to the loaded
+/// config's protocol.
+fn sim_transport(
+    args: &SimArgs,
+    config: Option<&rustak_config::RustakConfig>,
+    format: ConvertFormat,
+) -> Result<TransportConfig, CliError> {
+    let base = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let protocol = if let Some(udp) = args.target.as_deref() {
+        let addr = parse_endpoint(udp)?;
+        let bind_ip = if addr.is_ipv4() {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        };
+        Protocol::Udp {
+            bind_addr: SocketAddr::new(bind_ip, 0),
+            target: udp_target(addr),
+        }
+    } else if let Some(tcp) = args.tcp.as_deref() {
+        Protocol::Tcp {
+            addr: parse_endpoint(tcp)?,
+        }
+    } else if config.is_some() {
+        base.protocol.clone()
+    } else {
+        return Err(CliError::SimTargetRequired);
+    };
+    Ok(TransportConfig {
+        protocol,
+        wire_format: WireFormat::from(format),
+        ..base
+    })
+}
+
+pub(crate) fn run_sim(args: SimArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    if !args.speed.is_finite() || args.speed < 0.0 {
+        return Err(CliError::ReplaySpeedInvalid { speed: args.speed });
+    }
+    let path = args
+        .scenario
+        .as_deref()
+        .ok_or(CliError::SimScenarioRequired)?;
+    let scenario = SimScenario::load(path)?;
+    let format = args.format.unwrap_or(match config.as_ref() {
+        Some(config) if config.transport.wire_format == WireFormat::TakProtocolV1 => {
+            ConvertFormat::TakV1
+        }
+        _ => ConvertFormat::Xml,
+    });
+    let transport = sim_transport(&args, config.as_ref(), format)?;
+    let step_millis = args.tick_millis.unwrap_or(scenario.step_millis);
+    let mut run = SimRun::new(&scenario, step_millis, TimestampUtc::now())?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let started = Instant::now();
+    let summary = runtime.block_on(async {
+        let mut sink = ReplaySink::open(&transport, config.as_ref()).await?;
+        tracing::info!(
+            scenario = %scenario.name,
+            entities = scenario.entities.len(),
+            ticks = scenario.duration_ticks,
+            step_ms = step_millis,
+            speed = args.speed,
+            "sim_started"
+        );
+        tokio::select! {
+            result = sim_run(
+                &mut sink,
+                &mut run,
+                format,
+                Duration::from_millis(step_millis),
+                args.speed,
+            ) => result,
+            signal = tokio::signal::ctrl_c() => {
+                signal.map_err(|source| CliError::Runtime { source })?;
+                tracing::info!(tick = run.tick(), "sim_interrupted");
+                Ok(SimSummary::default())
+            }
+        }
+    })?;
+    println!(
+        "sim ticks={} events={} bytes={} elapsed_ms={}",
+        summary.ticks,
+        summary.events,
+        summary.bytes,
+        started.elapsed().as_millis()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+    use rustak_core::time::TimestampUtc;
+    use rustak_core::Track;
+
+    use super::{
+        sim_run, sim_transport, SimArgs, SimRouteMode, SimRun, SimScenario, DEFAULT_SIM_STALE_SECS,
+    };
+    use crate::commands::replay::ReplaySink;
+    use crate::{execute_command, Cli, CliError, Command, ConvertFormat};
+
+    const SIM_SCENARIO: &str = "
+name: pair
+seed: 3
+duration_ticks: 3
+step_millis: 500
+origin: { lat: 51.5, lon: -0.12, hae: 30.0 }
+entities:
+  - uid: sim-lead
+    callsign: LEAD
+    cot_type: a-f-G-U-C
+    east_mps: 4.0
+  - uid: sim-drone
+    cot_type: a-h-A-M-F-Q
+    north_m: 100.0
+    north_mps: -10.0
+    jitter_mps: 0.5
+";
+
+    fn sim_args(argv: &[&str]) -> SimArgs {
+        let cli = Cli::try_parse_from(["rustak", "sim"].iter().chain(argv)).expect("sim args");
+        let Command::Sim(args) = cli.command else {
+            panic!("expected sim");
+        };
+        args
+    }
+
+    #[test]
+    fn sim_scenarios_step_every_entity_until_their_duration() {
+        let scenario = SimScenario::from_yaml(SIM_SCENARIO).expect("scenario");
+        assert_eq!(scenario.stale_secs, DEFAULT_SIM_STALE_SECS);
+        let start = TimestampUtc::from_unix_seconds(1_700_000_000);
+        let mut run = SimRun::new(&scenario, scenario.step_millis, start).expect("run");
+
+        let first = run.step().expect("step").expect("tick 1");
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].uid, "sim-lead");
+        assert_eq!(first[0].time.unix_nanos(), start.unix_nanos() + 500_000_000);
+        assert!(first[0].point.longitude() > -0.12);
+        assert_eq!(first[1].cot_type, "a-h-A-M-F-Q");
+        assert!(first[1].point.latitude() > 51.5);
+        assert!(run.step().expect("step").is_some());
+        assert!(run.step().expect("step").is_some());
+        assert!(run.step().expect("step").is_none());
+
+        assert!(matches!(
+            SimScenario::from_yaml(
+                "name: empty\nduration_ticks: 1\norigin: {lat: 0, lon: 0}\nentities: []"
+            ),
+            Err(CliError::SimNoEntities)
+        ));
+        assert!(matches!(
+            SimScenario::from_yaml(&SIM_SCENARIO.replace("duration_ticks: 3", "duration_ticks: 0")),
+            Err(CliError::Scenario(
+                rustak_sim::ScenarioError::ZeroDurationTicks
+            ))
+        ));
+        assert!(matches!(
+            SimScenario::from_yaml(&SIM_SCENARIO.replace("jitter_mps", "jitter")),
+            Err(CliError::SimScenarioParse { .. })
+        ));
+        let bad_type = SimScenario::from_yaml(&SIM_SCENARIO.replace("a-h-A-M-F-Q", "b-t-f"))
+            .expect("scenario");
+        assert!(matches!(
+            SimRun::new(&bad_type, 500, start),
+            Err(CliError::TrackEmitter(_))
+        ));
+        assert!(matches!(
+            execute_command(Command::Sim(sim_args(&["--target", "127.0.0.1:6969"]))),
+            Err(CliError::SimScenarioRequired)
+        ));
+    }
+
+    #[test]
+    fn sim_entities_with_routes_fly_their_waypoints() {
+        let routed = format!(
+            "{SIM_SCENARIO}  - uid: sim-orbit
+    cot_type: a-h-A-M-F-Q
+    route:
+      mode: ping-pong
+      waypoints:
+        - {{ lat: 51.5, lon: -0.12, speed_mps: 100.0 }}
+        - {{ lat: 51.5, lon: -0.11, hae: 300.0, speed_mps: 100.0, dwell_secs: 1 }}
+"
+        );
+        let scenario = SimScenario::from_yaml(&routed).expect("scenario");
+        let route = scenario.entities[2].route.as_ref().expect("route");
+        assert_eq!(route.mode, SimRouteMode::PingPong);
+        let start = TimestampUtc::from_unix_seconds(1_700_000_000);
+        let mut run = SimRun::new(&scenario, 500, start).expect("run");
+
+        let orbit = &run.step().expect("step").expect("tick 1")[2];
+        assert_eq!(orbit.uid, "sim-orbit");
+        assert!(orbit.point.longitude() > -0.12);
+        assert!((orbit.point.latitude() - 51.5).abs() < 1e-3);
+        // Unset waypoint altitudes fall back to the scenario origin's.
+        assert_eq!(orbit.point.hae(), Some(30.0));
+        let track = orbit.extension::<Track>().expect("track").expect("valid");
+        assert_eq!(track.kinematics().speed(), Some(100.0));
+
+        let one_waypoint = routed.replace(
+            "        - { lat: 51.5, lon: -0.11, hae: 300.0, speed_mps: 100.0, dwell_secs: 1 }\n",
+            "",
+        );
+        let scenario = SimScenario::from_yaml(&one_waypoint).expect("scenario");
+        assert!(matches!(
+            SimRun::new(&scenario, 500, start),
+            Err(CliError::Route(rustak_sim::RouteError::TooFewWaypoints {
+                count: 1
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn sim_sends_each_tick_to_the_udp_target() {
+        let receiver = tokio::net::UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let target = receiver.local_addr().expect("addr").to_string();
+        let args = sim_args(&["--target", &target, "--speed", "0"]);
+        let transport = sim_transport(&args, None, ConvertFormat::Xml).expect("transport");
+        assert!(matches!(
+            sim_transport(&sim_args(&[]), None, ConvertFormat::Xml),
+            Err(CliError::SimTargetRequired)
+        ));
+
+        let scenario = SimScenario::from_yaml(SIM_SCENARIO).expect("scenario");
+        let mut run = SimRun::new(&scenario, 500, TimestampUtc::now()).expect("run");
+        let mut sink = ReplaySink::open(&transport, None).await.expect("sink");
+        let summary = sim_run(
+            &mut sink,
+            &mut run,
+            ConvertFormat::Xml,
+            Duration::from_millis(500),
+            0.0,
+        )
+        .await
+        .expect("sim");
+        assert_eq!(summary.ticks, 3);
+        assert_eq!(summary.events, 6);
+
+        let mut datagram = [0_u8; 2048];
+        let (len, _) = receiver.recv_from(&mut datagram).await.expect("first");
+        let xml = std::str::from_utf8(&datagram[..len]).expect("utf8");
+        assert!(xml.contains("uid=\"sim-lead\""), "{xml}");
+        assert!(xml.contains("<contact callsign=\"LEAD\"/>"), "{xml}");
+        assert!(xml.contains("<track "), "{xml}");
+    }
+}
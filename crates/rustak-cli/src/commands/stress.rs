@@ -0,0 +1,650 @@
+//! `rustak stress`: synthetic track load through the send queue.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use clap::{Args, ValueEnum};
+use rustak_core::time::TimestampUtc;
+use rustak_core::Position;
+use rustak_transport::{
+    ConnectionManager, CotPriorityClassifier, ManagedStream, OutboundSendQueue, Protocol,
+    QueueDriver, TransportConfig, TransportConnection, TransportSender,
+};
+use rustak_wire::{DowngradePolicy, WireFormat};
+
+use crate::commands::send::DEFAULT_SEND_COT_TYPE;
+use crate::commands::sim::{sim_position, SimOrigin};
+use crate::{
+    load_optional_config, stream_transport, validate_transport_defaults, with_tls_connector,
+    CliError, ConvertFormat,
+};
+
+#[derive(Debug, Args)]
+pub struct StressArgs {
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Unique-UID tracks, each reporting once a second"
+    )]
+    pub count: Option<u32>,
+    #[arg(
+        long,
+        help = "How long to generate load, e.g. 300s, 5m or 1h (default 60s)"
+    )]
+    pub duration: Option<String>,
+    #[arg(
+        long,
+        help = "TAK Server streaming address (for example 10.0.0.5:8087); TLS comes from the config"
+    )]
+    pub target: Option<String>,
+    #[arg(
+        long,
+        value_enum,
+        help = "Wire format to encode with; defaults to the config's wire_format or xml"
+    )]
+    pub format: Option<ConvertFormat>,
+    #[arg(long, value_enum, help = "How the track count climbs to --count")]
+    pub ramp: Option<StressRamp>,
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds the ramp takes to reach --count (default the whole run)"
+    )]
+    pub ramp_secs: Option<u64>,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Increments of a step ramp (default 4)"
+    )]
+    pub ramp_steps: Option<u32>,
+    #[arg(long, help = "Stress profile YAML; flags override its fields")]
+    pub profile: Option<PathBuf>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+}
+
+/// `rustak stress` run length without `--duration`.
+pub const DEFAULT_STRESS_DURATION: Duration = Duration::from_secs(60);
+
+/// Increments of a step ramp without `--ramp-steps`.
+pub const DEFAULT_STRESS_RAMP_STEPS: u32 = 4;
+
+/// How long after each report a stress track goes stale.
+const STRESS_STALE: Duration = Duration::from_secs(30);
+
+/// Spacing, in degrees, of the grid stress tracks are laid out on.
+const STRESS_GRID_DEGREES: f64 = 0.001;
+
+/// How `rustak stress` climbs to `count` tracks. Every profile reports at
+/// least one track from the first second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StressRamp {
+    /// Every track from the first second.
+    #[default]
+    None,
+    /// An equal share more each second until `ramp_secs`.
+    Linear,
+    /// `ramp_steps` equal increments spread over `ramp_secs`.
+    Step,
+}
+
+impl StressRamp {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Linear => "linear",
+            Self::Step => "step",
+        }
+    }
+}
+
+/// A `rustak stress --profile` file. Every field is optional and the
+/// matching flag wins; tracks are laid out on a grid from `origin`.
+///
+/// ```yaml
+/// count: 500
+/// duration: 5m
+/// ramp: step
+/// ramp_secs: 120
+/// ramp_steps: 5
+/// cot_type: a-h-A-M-F-Q
+/// origin: { lat: 51.5, lon: -0.12 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StressProfile {
+    #[serde(default)]
+    pub count: Option<u32>,
+    #[serde(default)]
+    pub duration: Option<String>,
+    #[serde(default)]
+    pub ramp: Option<StressRamp>,
+    #[serde(default)]
+    pub ramp_secs: Option<u64>,
+    #[serde(default)]
+    pub ramp_steps: Option<u32>,
+    #[serde(default)]
+    pub cot_type: Option<String>,
+    #[serde(default)]
+    pub origin: Option<SimOrigin>,
+}
+
+impl StressProfile {
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let text = fs::read_to_string(path).map_err(|source| CliError::InputRead {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&text, &path.display().to_string())
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, CliError> {
+        Self::parse(text, "<inline>")
+    }
+
+    fn parse(text: &str, path: &str) -> Result<Self, CliError> {
+        serde_yaml::from_str(text).map_err(|error| CliError::StressProfileParse {
+            path: path.to_owned(),
+            message: error.to_string(),
+        })
+    }
+}
+
+/// The load a `rustak stress` run generates, from its flags over its
+/// profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StressPlan {
+    pub count: u32,
+    pub duration: Duration,
+    pub ramp: StressRamp,
+    pub ramp_secs: u64,
+    pub ramp_steps: u32,
+    pub cot_type: String,
+    pub origin: Position,
+}
+
+impl StressPlan {
+    pub fn resolve(args: &StressArgs, profile: Option<&StressProfile>) -> Result<Self, CliError> {
+        let profile = profile.cloned().unwrap_or_default();
+        let count = args
+            .count
+            .or(profile.count)
+            .ok_or(CliError::StressCountRequired)?;
+        let duration = args
+            .duration
+            .as_deref()
+            .or(profile.duration.as_deref())
+            .map_or(Ok(DEFAULT_STRESS_DURATION), parse_stress_duration)?;
+        let ramp_secs = args
+            .ramp_secs
+            .or(profile.ramp_secs)
+            .unwrap_or_else(|| duration.as_secs().max(1));
+        let ramp_steps = args
+            .ramp_steps
+            .or(profile.ramp_steps)
+            .unwrap_or(DEFAULT_STRESS_RAMP_STEPS);
+        for (field, value) in [
+            ("count", u64::from(count)),
+            ("ramp_secs", ramp_secs),
+            ("ramp_steps", u64::from(ramp_steps)),
+        ] {
+            if value == 0 {
+                return Err(CliError::StressProfileInvalid { field });
+            }
+        }
+        let origin = profile.origin.unwrap_or(SimOrigin {
+            lat: 0.0,
+            lon: 0.0,
+            hae: None,
+        });
+        Ok(Self {
+            count,
+            duration,
+            ramp: args.ramp.or(profile.ramp).unwrap_or_default(),
+            ramp_secs,
+            ramp_steps,
+            cot_type: profile
+                .cot_type
+                .unwrap_or_else(|| DEFAULT_SEND_COT_TYPE.to_owned()),
+            origin: sim_position(origin.lat, origin.lon, origin.hae)?,
+        })
+    }
+
+    /// Tracks reporting during second `second` of the run.
+    #[must_use]
+    pub fn active_tracks(&self, second: u64) -> u32 {
+        let count = u64::from(self.count);
+        let active = match self.ramp {
+            StressRamp::None => count,
+            StressRamp::Linear => count * (second + 1) / self.ramp_secs,
+            StressRamp::Step => {
+                let steps = u64::from(self.ramp_steps);
+                let step = (second * steps / self.ramp_secs + 1).min(steps);
+                count * step / steps
+            }
+        };
+        active.clamp(1, count) as u32
+    }
+
+    /// When, from the start of the run, each report is due and which track
+    /// sends it. A second's reports are spread evenly across it.
+    pub fn schedule(&self) -> impl Iterator<Item = (Duration, u32)> + '_ {
+        let seconds = self.duration.as_millis().div_ceil(1_000) as u64;
+        (0..seconds)
+            .flat_map(move |second| {
+                let active = self.active_tracks(second);
+                (0..active).map(move |track| {
+                    (
+                        Duration::from_secs(second) + Duration::from_secs(1) * track / active,
+                        track,
+                    )
+                })
+            })
+            .take_while(move |(offset, _)| *offset < self.duration)
+    }
+
+    /// Position report of `track` at `now`.
+    pub fn event(&self, track: u32, now: TimestampUtc) -> Result<rustak_core::CotEvent, CliError> {
+        let mut point = Position::new(
+            self.origin.latitude() + f64::from(track / 100) * STRESS_GRID_DEGREES,
+            self.origin.longitude() + f64::from(track % 100) * STRESS_GRID_DEGREES,
+        )
+        .map_err(CliError::InvalidPosition)?;
+        if let Some(hae) = self.origin.hae() {
+            point = point.with_hae(hae).map_err(CliError::InvalidPosition)?;
+        }
+        let stale = TimestampUtc::from_unix_nanos(
+            now.unix_nanos()
+                .saturating_add(STRESS_STALE.as_nanos() as i128),
+        );
+        let mut event = rustak_core::CotEvent::new(
+            format!("stress-{track:06}"),
+            &self.cot_type,
+            now,
+            stale,
+            point,
+        );
+        event.how = Some(rustak_sim::emitter::SIMULATED_HOW.to_owned());
+        Ok(event)
+    }
+}
+
+/// `300s`, `5m`, `1h`, `250ms` or bare seconds; must be non-zero.
+fn parse_stress_duration(text: &str) -> Result<Duration, CliError> {
+    let text = text.trim();
+    let (digits, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => text.split_at(split),
+        None => (text, "s"),
+    };
+    let value = digits.parse::<u64>().ok();
+    let duration = match unit.trim() {
+        "ms" => value.map(Duration::from_millis),
+        "s" => value.map(Duration::from_secs),
+        "m" => value
+            .and_then(|minutes| minutes.checked_mul(60))
+            .map(Duration::from_secs),
+        "h" => value
+            .and_then(|hours| hours.checked_mul(3_600))
+            .map(Duration::from_secs),
+        _ => None,
+    };
+    duration
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| CliError::StressDurationInvalid {
+            value: text.to_owned(),
+        })
+}
+
+/// An encoded stress report and when it entered the send queue.
+#[derive(Debug, Clone)]
+pub struct StressFrame {
+    payload: Vec<u8>,
+    queued: Instant,
+}
+
+impl AsRef<[u8]> for StressFrame {
+    fn as_ref(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// What a finished `rustak stress` run generated and sent. `latency` holds
+/// each sent report's time from enqueue to write, sorted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StressReport {
+    pub events: u64,
+    pub sent: u64,
+    pub bytes: u64,
+    /// Reports the send queue shed under pressure.
+    pub dropped: u64,
+    /// Reports replaced in the queue by a newer one from the same track.
+    pub coalesced: u64,
+    pub reconnects: u64,
+    pub elapsed: Duration,
+    pub latency: Vec<Duration>,
+}
+
+impl StressReport {
+    /// Latency at or below which `percent` of the sent reports were
+    /// written; zero when nothing was sent.
+    #[must_use]
+    pub fn latency_percentile(&self, percent: f64) -> Duration {
+        let Some(last) = self.latency.len().checked_sub(1) else {
+            return Duration::ZERO;
+        };
+        let rank = ((percent / 100.0) * last as f64).round() as usize;
+        self.latency[rank.min(last)]
+    }
+
+    #[must_use]
+    pub fn line(&self) -> String {
+        let micros = |percent| self.latency_percentile(percent).as_micros();
+        let elapsed = self.elapsed.as_secs_f64();
+        format!(
+            "stress events={} sent={} bytes={} dropped={} coalesced={} reconnects={} elapsed_ms={} rate_eps={:.1} latency_p50_us={} latency_p90_us={} latency_p99_us={} latency_max_us={}",
+            self.events,
+            self.sent,
+            self.bytes,
+            self.dropped,
+            self.coalesced,
+            self.reconnects,
+            self.elapsed.as_millis(),
+            if elapsed > 0.0 { self.sent as f64 / elapsed } else { 0.0 },
+            micros(50.0),
+            micros(90.0),
+            micros(99.0),
+            micros(100.0),
+        )
+    }
+}
+
+/// Generates `plan`'s reports into the transport's send queue while a
+/// [`QueueDriver`] writes them to `connection`. A failed write redials
+/// through `manager` and carries on with what is still queued. `stop` ends
+/// generation early; queued reports are still sent.
+pub async fn stress_run(
+    manager: &mut ConnectionManager,
+    connection: TransportConnection<ManagedStream>,
+    plan: &StressPlan,
+    format: ConvertFormat,
+    stop: impl std::future::Future<Output = ()>,
+) -> Result<StressReport, CliError> {
+    let transport = manager.config().clone();
+    let latency = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&latency);
+    let mut driver = QueueDriver::new(OutboundSendQueue::new(
+        transport.send_queue.clone(),
+        CotPriorityClassifier,
+    )?)
+    .on_sent(move |frame: &StressFrame| {
+        recorder
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(frame.queued.elapsed());
+    });
+    let handle = driver.handle();
+    let started = tokio::time::Instant::now();
+
+    let producer = async {
+        let mut events = 0;
+        let generate = async {
+            for (offset, track) in plan.schedule() {
+                tokio::time::sleep_until(started + offset).await;
+                let payload = rustak_wire::encode_payload_for_format(
+                    plan.event(track, TimestampUtc::now())?.to_xml().as_bytes(),
+                    WireFormat::from(format),
+                )?;
+                handle.enqueue(StressFrame {
+                    payload,
+                    queued: Instant::now(),
+                });
+                events += 1;
+            }
+            Ok::<_, CliError>(())
+        };
+        let result = tokio::select! {
+            result = generate => result,
+            () = stop => Ok(()),
+        };
+        handle.close();
+        result.map(|()| events)
+    };
+    let writer = async {
+        let mut reconnects = 0;
+        let mut sender = TransportSender::new(connection.into_inner(), &transport)?;
+        loop {
+            match driver.run(&mut sender).await {
+                Ok(stats) => return Ok::<_, CliError>((stats, reconnects)),
+                Err(error) => {
+                    reconnects += 1;
+                    tracing::warn!(attempt = reconnects, reason = %error, "stress_reconnect");
+                    let connection = manager.reconnect(error.to_string()).await?;
+                    sender = TransportSender::new(connection.into_inner(), &transport)?;
+                }
+            }
+        }
+    };
+    let (events, (stats, reconnects)) = tokio::try_join!(producer, writer)?;
+
+    let mut latency = std::mem::take(&mut *latency.lock().unwrap_or_else(PoisonError::into_inner));
+    latency.sort_unstable();
+    Ok(StressReport {
+        events,
+        sent: stats.sent_messages,
+        bytes: stats.sent_bytes,
+        dropped: stats.dropped_messages,
+        coalesced: stats.coalesced,
+        reconnects,
+        elapsed: started.elapsed(),
+        latency,
+    })
+}
+
+fn stress_transport(
+    args: &StressArgs,
+    config: Option<&rustak_config::RustakConfig>,
+    format: ConvertFormat,
+) -> Result<TransportConfig, CliError> {
+    Ok(TransportConfig {
+        wire_format: WireFormat::from(format),
+        ..stream_transport(
+            args.target.as_deref(),
+            config,
+            CliError::StressTargetRequired,
+        )?
+    })
+}
+
+pub(crate) fn run_stress(args: StressArgs) -> Result<(), CliError> {
+    let config = load_optional_config(args.config.as_deref())?;
+    validate_transport_defaults()?;
+    let profile = args
+        .profile
+        .as_deref()
+        .map(StressProfile::load)
+        .transpose()?;
+    let plan = StressPlan::resolve(&args, profile.as_ref())?;
+    let format = args.format.unwrap_or(match config.as_ref() {
+        Some(config) if config.transport.wire_format == WireFormat::TakProtocolV1 => {
+            ConvertFormat::TakV1
+        }
+        _ => ConvertFormat::Xml,
+    });
+    let transport = stress_transport(&args, config.as_ref(), format)?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|source| CliError::Runtime { source })?;
+    let report = runtime.block_on(async {
+        let mut manager = ConnectionManager::new(transport.clone(), DowngradePolicy::FailOpen)?;
+        if matches!(transport.protocol, Protocol::Tls { .. }) {
+            manager = with_tls_connector(manager, config.as_ref())?;
+        }
+        let connection = manager.connect().await?;
+        tracing::info!(
+            tracks = plan.count,
+            duration_ms = plan.duration.as_millis(),
+            ramp = plan.ramp.as_str(),
+            ramp_secs = plan.ramp_secs,
+            ramp_steps = plan.ramp_steps,
+            "stress_started"
+        );
+        let stop = async {
+            match tokio::signal::ctrl_c().await {
+                Ok(()) => tracing::info!("stress_interrupted"),
+                Err(_) => std::future::pending().await,
+            }
+        };
+        stress_run(&mut manager, connection, &plan, format, stop).await
+    })?;
+    println!("{}", report.line());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::Parser;
+    use rustak_core::time::TimestampUtc;
+    use rustak_transport::ConnectionManager;
+    use rustak_wire::DowngradePolicy;
+
+    use super::{stress_run, stress_transport, StressArgs, StressPlan, StressProfile};
+    use crate::{Cli, CliError, Command, ConvertFormat};
+
+    fn stress_args(argv: &[&str]) -> StressArgs {
+        let cli =
+            Cli::try_parse_from(["rustak", "stress"].iter().chain(argv)).expect("stress args");
+        let Command::Stress(args) = cli.command else {
+            panic!("expected stress");
+        };
+        args
+    }
+
+    #[test]
+    fn stress_plans_ramp_tracks_up_to_the_count() {
+        let profile = StressProfile::from_yaml(
+            "count: 100\nduration: 2m\nramp: step\nramp_steps: 5\ncot_type: a-h-A-M-F-Q",
+        )
+        .expect("profile");
+        let plan = StressPlan::resolve(&stress_args(&["--ramp-secs", "50"]), Some(&profile))
+            .expect("plan");
+        assert_eq!(plan.duration, Duration::from_secs(120));
+        assert_eq!(
+            [0, 9, 10, 49, 50, 119].map(|second| plan.active_tracks(second)),
+            [20, 20, 40, 100, 100, 100]
+        );
+        let event = plan
+            .event(7, TimestampUtc::from_unix_seconds(1_700_000_000))
+            .expect("event");
+        assert_eq!(event.uid, "stress-000007");
+        assert_eq!(event.cot_type, "a-h-A-M-F-Q");
+
+        let linear = StressPlan::resolve(
+            &stress_args(&["--count", "10", "--duration", "4s", "--ramp", "linear"]),
+            Some(&profile),
+        )
+        .expect("plan");
+        assert_eq!(linear.count, 10);
+        assert_eq!(linear.ramp_secs, 4);
+        assert_eq!(
+            [0, 1, 2, 3].map(|second| linear.active_tracks(second)),
+            [2, 5, 7, 10]
+        );
+        assert_eq!(linear.schedule().count(), 24);
+
+        let flat = StressPlan::resolve(
+            &stress_args(&["--count", "4", "--duration", "1500ms"]),
+            None,
+        )
+        .expect("plan");
+        let schedule = flat.schedule().collect::<Vec<_>>();
+        assert_eq!(schedule.len(), 6);
+        assert_eq!(schedule[1], (Duration::from_millis(250), 1));
+        assert_eq!(schedule[5], (Duration::from_millis(1_250), 1));
+
+        assert!(matches!(
+            StressPlan::resolve(&stress_args(&[]), None),
+            Err(CliError::StressCountRequired)
+        ));
+        for duration in ["0s", "5 days", "m"] {
+            assert!(matches!(
+                StressPlan::resolve(
+                    &stress_args(&["--count", "1", "--duration", duration]),
+                    None
+                ),
+                Err(CliError::StressDurationInvalid { .. })
+            ));
+        }
+        assert!(matches!(
+            StressPlan::resolve(
+                &stress_args(&[]),
+                Some(&StressProfile::from_yaml("count: 5\nramp_steps: 0").expect("profile"))
+            ),
+            Err(CliError::StressProfileInvalid {
+                field: "ramp_steps"
+            })
+        ));
+        assert!(matches!(
+            StressProfile::from_yaml("count: 5\nrate: 9"),
+            Err(CliError::StressProfileParse { .. })
+        ));
+        assert!(matches!(
+            stress_transport(&stress_args(&["--count", "1"]), None, ConvertFormat::Xml),
+            Err(CliError::StressTargetRequired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn stress_streams_reports_through_the_send_queue() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let target = listener.local_addr().expect("addr").to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).await.expect("read");
+            received
+        });
+
+        let args = stress_args(&["--count", "40", "--duration", "250ms", "--target", &target]);
+        let plan = StressPlan::resolve(&args, None).expect("plan");
+        let transport = stress_transport(&args, None, ConvertFormat::Xml).expect("transport");
+        let mut manager =
+            ConnectionManager::new(transport, DowngradePolicy::FailOpen).expect("manager");
+        let connection = manager.connect().await.expect("connect");
+        let report = stress_run(
+            &mut manager,
+            connection,
+            &plan,
+            ConvertFormat::Xml,
+            std::future::pending(),
+        )
+        .await
+        .expect("stress");
+        drop(manager);
+
+        assert_eq!(report.events, 10);
+        assert_eq!(report.sent, 10);
+        assert_eq!(report.reconnects, 0);
+        assert_eq!(report.latency.len(), 10);
+        assert!(report.latency_percentile(50.0) <= report.latency_percentile(100.0));
+        let line = report.line();
+        assert!(line.starts_with("stress events=10 sent=10 "), "{line}");
+        assert!(line.contains(" latency_p99_us="), "{line}");
+
+        let received = String::from_utf8(server.await.expect("server")).expect("utf8");
+        assert_eq!(received.matches("<event ").count(), 10, "{received}");
+        assert!(received.contains("uid=\"stress-000009\""), "{received}");
+    }
+}
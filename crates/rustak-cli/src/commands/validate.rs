@@ -0,0 +1,319 @@
+//! `rustak validate`: payload, config and takrec checks.
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use rustak::RustakError;
+use rustak_record::{
+    chain_sidecar_path, verify_integrity_prefix, DetachedVerifier, IntegrityChain,
+};
+use rustak_wire::WireFormat;
+
+use crate::{
+    config_limits, cot_warnings, ensure_non_empty, load_optional_config, read_input_bytes,
+    read_key_pem, report_warnings, validate_optional_config, validate_sapient_defaults,
+    validate_wire_defaults, validate_wire_payload, CliError, FailOn,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ValidationFormat {
+    Xml,
+    TakV1,
+    Sapient,
+    Config,
+    Takrec,
+}
+
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    #[arg(long, value_enum)]
+    pub format: ValidationFormat,
+    #[arg(long, help = "Input file path; defaults to stdin when omitted")]
+    pub input: Option<PathBuf>,
+    #[arg(long, help = "Optional path to rustak YAML config")]
+    pub config: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = FailOn::Errors)]
+    pub fail_on: FailOn,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Integrity chain of a takrec; defaults to <input>.chain when that exists"
+    )]
+    pub chain: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PEM",
+        help = "Public key or certificate that must have signed every link of the takrec's chain"
+    )]
+    pub verify_key: Option<PathBuf>,
+}
+
+pub(crate) fn run_validate(args: ValidateArgs) -> Result<(), CliError> {
+    let limits = config_limits(load_optional_config(args.config.as_deref())?.as_ref());
+
+    match args.format {
+        ValidationFormat::Config => {
+            let Some(path) = args.input.as_deref() else {
+                return Err(CliError::ConfigFormatRequiresInputPath);
+            };
+            validate_optional_config(Some(path))
+        }
+        ValidationFormat::Xml => {
+            validate_wire_defaults()?;
+            let payload = read_input_bytes(args.input.as_deref())?;
+            validate_wire_payload(&payload, WireFormat::Xml)?;
+            report_warnings("validate", &cot_warnings(&payload, &limits), args.fail_on)
+        }
+        ValidationFormat::TakV1 => {
+            validate_wire_defaults()?;
+            let payload = read_input_bytes(args.input.as_deref())?;
+            validate_wire_payload(&payload, WireFormat::TakProtocolV1)?;
+            report_warnings("validate", &cot_warnings(&payload, &limits), args.fail_on)
+        }
+        ValidationFormat::Sapient => {
+            validate_sapient_defaults()?;
+            let payload = read_input_bytes(args.input.as_deref())?;
+            ensure_non_empty(&payload)?;
+            rustak_sapient::SapientConfig::default()
+                .codec()
+                .validate_payload(&payload)
+                .map_err(CliError::SapientCodec)
+        }
+        ValidationFormat::Takrec => validate_takrec(&args),
+    }
+}
+
+/// `validate --format takrec`: checks chunk checksums and, when a chain
+/// sidecar is found, every link of it. With `--verify-key` the chain's last
+/// link must carry a valid signature from that key. Chunks past the chain's
+/// last checkpoint, left by an interrupted recording, are warnings.
+fn validate_takrec(args: &ValidateArgs) -> Result<(), CliError> {
+    let recording = read_input_bytes(args.input.as_deref())?;
+    let (report, payloads) = rustak_record::recover_chunk_payloads(recording.as_slice())
+        .map_err(|source| CliError::Facade(RustakError::Record(source)))?;
+    let verifier = args
+        .verify_key
+        .as_deref()
+        .map(|path| Ok::<_, CliError>(DetachedVerifier::from_pem(&read_key_pem(path)?)?))
+        .transpose()?;
+    let sidecar = args.chain.clone().or_else(|| {
+        args.input
+            .as_deref()
+            .map(chain_sidecar_path)
+            .filter(|path| path.exists())
+    });
+
+    let mut warnings = Vec::new();
+    let (chain, signatures) = match (sidecar, &verifier) {
+        (Some(path), _) => {
+            let text = fs::read_to_string(&path).map_err(|source| CliError::InputRead {
+                path: path.display().to_string(),
+                source,
+            })?;
+            let chain = IntegrityChain::from_sidecar(&text)?;
+            let prefix =
+                verify_integrity_prefix(&payloads, &chain, verifier.as_ref(), verifier.is_some())?;
+            if prefix.uncovered > 0 {
+                warnings.push(format!(
+                    "{} chunk(s) after the chain's last checkpoint are not covered by it",
+                    prefix.uncovered
+                ));
+            }
+            let signatures = match &verifier {
+                Some(verifier) => verifier.algorithm().to_string(),
+                None => "unchecked".to_owned(),
+            };
+            ("verified", signatures)
+        }
+        (None, Some(_)) => return Err(CliError::TakrecChainRequired),
+        (None, None) => ("absent", "unchecked".to_owned()),
+    };
+    println!(
+        "validate_takrec chunks={} truncated_tail={} chain={chain} signatures={signatures}",
+        payloads.len(),
+        report.truncated_tail
+    );
+    if report.truncated_tail {
+        warnings.push("takrec ends in a partial chunk that was ignored".to_owned());
+    }
+    report_warnings("validate", &warnings, args.fail_on)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bytes::Bytes;
+    use clap::Parser;
+    use rustak_record::{
+        chain_sidecar_path, DetachedSigner, IntegrityError, RecordEnvelope, RetentionPolicy,
+        RotationPolicy, TakrecHeader, TakrecWriter,
+    };
+
+    use super::{ValidateArgs, ValidationFormat};
+    use crate::commands::health::HealthArgs;
+    use crate::commands::record::TakrecRecorder;
+    use crate::tests::replay_event;
+    use crate::{
+        execute_command, validate_wire_payload, Cli, CliError, Command, ExitStatus, FailOn,
+    };
+
+    #[test]
+    fn validate_config_requires_input_path() {
+        let error = execute_command(Command::Validate(ValidateArgs {
+            format: ValidationFormat::Config,
+            input: None,
+            config: None,
+            fail_on: FailOn::Errors,
+            chain: None,
+            verify_key: None,
+        }))
+        .expect_err("config validation should require input path");
+
+        assert!(matches!(error, CliError::ConfigFormatRequiresInputPath));
+        assert_eq!(error.exit_code(), 2);
+    }
+
+    #[test]
+    fn fail_on_warnings_turns_validate_warnings_into_partial_success() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_fail_on_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let input = dir.join("event.xml");
+        std::fs::write(&input, b"<event uid=\"a\" type=\"a-f-G\"/>").expect("write input");
+
+        let validate = |fail_on| {
+            execute_command(Command::Validate(ValidateArgs {
+                format: ValidationFormat::Xml,
+                input: Some(input.clone()),
+                config: None,
+                fail_on,
+                chain: None,
+                verify_key: None,
+            }))
+        };
+        validate(FailOn::Errors).expect("warnings alone do not fail");
+        let error = validate(FailOn::Warnings).expect_err("warnings fail the threshold");
+        assert!(matches!(
+            error,
+            CliError::WarningThreshold {
+                command: "validate",
+                warnings: 1
+            }
+        ));
+        assert_eq!(error.exit_status(), ExitStatus::PartialSuccess);
+
+        let cli = Cli::try_parse_from(["rustak", "health", "--fail-on", "warnings"])
+            .expect("health accepts --fail-on");
+        assert!(matches!(
+            cli.command,
+            Command::Health(HealthArgs {
+                fail_on: FailOn::Warnings,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn xml_validation_routes_through_wire_payload_path() {
+        let payload = b"<event uid=\"unit-test\"/>".to_vec();
+        validate_wire_payload(&payload, rustak_wire::WireFormat::Xml)
+            .expect("xml payload should be wire-roundtrippable");
+    }
+
+    #[test]
+    fn signed_recordings_validate_end_to_end() {
+        let dir = std::env::temp_dir().join(format!("rustak_cli_signed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let key = rcgen::KeyPair::generate_for(&rcgen::PKCS_ED25519).expect("key");
+        let private_key = dir.join("recorder.key.pem");
+        let public_key = dir.join("recorder.pub.pem");
+        std::fs::write(&private_key, key.serialize_pem()).expect("write key");
+        std::fs::write(&public_key, key.public_key_pem()).expect("write public key");
+        let recording = dir.join("session.takrec");
+        let write_capture = |uids: &[&str]| {
+            let mut writer =
+                TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
+            for uid in uids {
+                writer
+                    .append_chunk(&replay_event(uid, "2024-01-01T00:00:00.000Z"))
+                    .expect("append");
+            }
+            std::fs::write(&recording, writer.into_inner().expect("finish"))
+                .expect("write capture");
+        };
+
+        let args = Cli::try_parse_from(["rustak", "record", "--sign", "recorder.key.pem"])
+            .expect("sign parses");
+        let Command::Record(args) = args.command else {
+            panic!("expected record");
+        };
+        assert_eq!(args.sign.as_deref(), Some(Path::new("recorder.key.pem")));
+        let signer = DetachedSigner::from_pem(&std::fs::read_to_string(&private_key).expect("key"))
+            .expect("signer");
+        let mut recorder = TakrecRecorder::create(
+            recording.clone(),
+            TakrecHeader::default(),
+            RotationPolicy::default(),
+            RetentionPolicy::default(),
+        )
+        .expect("recorder")
+        .with_signer(signer)
+        .expect("sidecar");
+        for uid in ["a", "b"] {
+            recorder
+                .record(&RecordEnvelope::new(Bytes::from(replay_event(
+                    uid,
+                    "2024-01-01T00:00:00.000Z",
+                ))))
+                .expect("record");
+        }
+        recorder.finish().expect("finish");
+
+        let validate_with = |verify_key: Option<&Path>, fail_on: FailOn| {
+            execute_command(Command::Validate(ValidateArgs {
+                format: ValidationFormat::Takrec,
+                input: Some(recording.clone()),
+                config: None,
+                fail_on,
+                chain: None,
+                verify_key: verify_key.map(Path::to_path_buf),
+            }))
+        };
+        let validate = |verify_key: Option<&Path>| validate_with(verify_key, FailOn::Errors);
+        validate(Some(&public_key)).expect("signed chain verifies");
+        validate(None).expect("hashes verify without a key");
+
+        // Chunks recorded after the last checkpoint, as a crash leaves them.
+        write_capture(&["a", "b", "c"]);
+        validate(Some(&public_key)).expect("the checkpointed prefix verifies");
+        let error = validate_with(Some(&public_key), FailOn::Warnings).expect_err("uncovered");
+        assert!(matches!(
+            error,
+            CliError::WarningThreshold { warnings: 1, .. }
+        ));
+
+        let other = rcgen::KeyPair::generate_for(&rcgen::PKCS_ECDSA_P256_SHA256).expect("key");
+        let other_key = dir.join("other.pub.pem");
+        std::fs::write(&other_key, other.public_key_pem()).expect("write public key");
+        let error = validate(Some(&other_key)).expect_err("foreign key");
+        assert!(matches!(
+            error,
+            CliError::Integrity(IntegrityError::InvalidSignature { sequence: 1 })
+        ));
+        assert_eq!(error.exit_status(), ExitStatus::Validation);
+
+        // Rewritten with valid chunk checksums, so only the chain notices.
+        write_capture(&["a", "c"]);
+        let error = validate(Some(&public_key)).expect_err("tampered capture");
+        assert!(matches!(
+            error,
+            CliError::Integrity(IntegrityError::PayloadHashMismatch { sequence: 1 })
+        ));
+
+        std::fs::remove_file(chain_sidecar_path(&recording)).expect("remove sidecar");
+        let error = validate(Some(&public_key)).expect_err("no sidecar");
+        assert!(matches!(error, CliError::TakrecChainRequired));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,14 +1,80 @@
 use std::fs;
 use std::io::{self, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use rustak::RustakError;
+use rustak_commo::ContactDirectoryError;
+use rustak_core::{CoreError, CotEvent, CotXmlError};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use rustak_record::{IntegrityError, PcapImportError, RotateError, ScrubError, StatsError};
 use rustak_sapient::SapientCodecError;
-use rustak_server::ServerConfigError;
-use rustak_wire::{WireFormat, WirePayloadError};
+use rustak_server::{ServerConfigError, StreamingError};
+use rustak_transport::{
+    ConnectionManager, ConnectionManagerError, Protocol, SendQueueError, TransportComposeError,
+    TransportConfig, UdpTarget, UdpTransportError, MAX_UDP_DATAGRAM_BYTES,
+};
+use rustak_wire::{MeshFrameCodec, TakProtocolVersion, WireFormat, WirePayloadError};
 use thiserror::Error;
 
+use crate::commands::bridge::run_bridge;
+use crate::commands::certs::{
+    run_certs_convert, run_certs_enroll, run_certs_inspect, run_certs_pin, run_certs_verify,
+};
+use crate::commands::config::{run_config_budget, run_config_explain};
+use crate::commands::connect::run_connect;
+use crate::commands::contacts::{run_contacts_export, run_contacts_import};
+use crate::commands::convert::run_convert;
+use crate::commands::doctor::run_doctor;
+use crate::commands::health::run_health;
+use crate::commands::listen::run_listen;
+use crate::commands::record::{run_record, run_record_import, run_record_scrub, run_record_stats};
+use crate::commands::replay::run_replay;
+use crate::commands::send::run_send;
+use crate::commands::sim::run_sim;
+use crate::commands::stress::run_stress;
+use crate::commands::validate::run_validate;
+
+mod commands;
+
+pub use commands::bridge::{bridge_sapient, BridgeArgs};
+pub use commands::certs::{
+    CertsAction, CertsArgs, CertsConvertArgs, CertsEnrollArgs, CertsIdentityArgs, CertsPinArgs,
+};
+pub use commands::config::{
+    ConfigAction, ConfigArgs, ConfigBudgetArgs, ConfigDocsArgs, ConfigExplainArgs,
+};
+pub use commands::connect::{connect_session, ConnectArgs};
+pub use commands::contacts::{
+    ContactsAction, ContactsArgs, ContactsExportArgs, ContactsImportArgs,
+};
+pub use commands::convert::ConvertArgs;
+pub use commands::doctor::{doctor_checks, CheckStatus, DoctorArgs, DoctorCheck, DoctorOptions};
+pub use commands::health::{
+    health_probe, HealthArgs, HealthReport, HealthStage, HealthStageResult,
+};
+pub use commands::listen::{
+    listen_event_line, listen_idle_line, listen_pretty_line, listen_tcp, listen_udp, ListenArgs,
+    ListenEndpoint, ListenOptions, ListenPrinter, ListenStats, LISTEN_IDLE_HINT_SECS,
+    LISTEN_STATS_TOP_TALKERS,
+};
+pub use commands::record::{
+    record_stream, record_udp, RecordAction, RecordArgs, RecordImportArgs, RecordSource,
+    RecordStatsArgs, RecordSummary, ScrubArgs, TakrecRecorder,
+};
+pub use commands::replay::{replay_timeline, ReplayArgs, ReplaySink, ReplayTimeline};
+pub use commands::send::{send_payload, SendArgs, SendEvent, DEFAULT_SEND_COT_TYPE};
+pub use commands::sim::{
+    sim_run, SimArgs, SimEntity, SimOrigin, SimRoute, SimRouteMode, SimRun, SimScenario,
+    SimSummary, SimWaypoint, DEFAULT_SIM_STALE_SECS, DEFAULT_SIM_STEP_MILLIS,
+};
+pub use commands::stress::{
+    stress_run, StressArgs, StressFrame, StressPlan, StressProfile, StressRamp, StressReport,
+    DEFAULT_STRESS_DURATION, DEFAULT_STRESS_RAMP_STEPS,
+};
+pub use commands::validate::{ValidateArgs, ValidationFormat};
+
 #[derive(Debug, Parser)]
 #[command(
     name = "rustak",
@@ -16,10 +82,24 @@ use thiserror::Error;
     about = "Command-line diagnostics and utilities for RusTAK"
 )]
 pub struct Cli {
+    /// How a failing command reports its error on stderr.
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    pub error_format: ErrorFormat,
     #[command(subcommand)]
     pub command: Command,
 }
 
+/// Rendering of the final error; both forms carry its stable
+/// `RTK-<DOMAIN>-<NNNN>` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ErrorFormat {
+    /// `RTK-CLI-0006: input payload must not be empty`.
+    #[default]
+    Text,
+    /// `{"error":{"code":...,"message":...,"exit_code":...}}` on one line.
+    Json,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     Listen(ListenArgs),
@@ -36,88 +116,21 @@ pub enum Command {
     Health(HealthArgs),
     Sapient(SapientArgs),
     Bridge(BridgeArgs),
+    Config(ConfigArgs),
+    Contacts(ContactsArgs),
+    /// Check config, certificates, endpoints and disk before going live.
+    Doctor(DoctorArgs),
 }
 
-#[derive(Debug, Args)]
-pub struct ListenArgs {
-    #[arg(long, help = "UDP endpoint to listen on (for example 239.2.3.1:6969)")]
-    pub udp: Option<String>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct SendArgs {
-    #[arg(long = "type", help = "CoT type string to emit")]
-    pub cot_type: Option<String>,
-    #[arg(long)]
-    pub lat: Option<f64>,
-    #[arg(long)]
-    pub lon: Option<f64>,
-    #[arg(long)]
-    pub alt: Option<f64>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct ConnectArgs {
-    #[arg(long)]
-    pub host: Option<String>,
-    #[arg(long)]
-    pub port: Option<u16>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct SimArgs {
-    #[arg(long)]
-    pub scenario: Option<PathBuf>,
-    #[arg(long)]
-    pub target: Option<String>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct ReplayArgs {
-    #[arg(long)]
-    pub input: Option<PathBuf>,
-    #[arg(long)]
-    pub target: Option<String>,
-    #[arg(long)]
-    pub speed: Option<f32>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct RecordArgs {
-    #[arg(long)]
-    pub source: Option<String>,
-    #[arg(long)]
-    pub output: Option<PathBuf>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
-pub enum ValidationFormat {
-    Xml,
-    TakV1,
-    Sapient,
-    Config,
-}
-
-#[derive(Debug, Args)]
-pub struct ValidateArgs {
-    #[arg(long, value_enum)]
-    pub format: ValidationFormat,
-    #[arg(long, help = "Input file path; defaults to stdin when omitted")]
-    pub input: Option<PathBuf>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
+/// Outcome that makes `validate`, `convert`, `health` and `doctor` exit
+/// non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum FailOn {
+    /// Only errors fail the command; warnings are reported on stderr.
+    #[default]
+    Errors,
+    /// Any warning fails the command with [`ExitStatus::PartialSuccess`].
+    Warnings,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -126,52 +139,12 @@ pub enum ConvertFormat {
     TakV1,
 }
 
-#[derive(Debug, Args)]
-pub struct ConvertArgs {
-    #[arg(long, value_enum)]
-    pub from: ConvertFormat,
-    #[arg(long, value_enum)]
-    pub to: ConvertFormat,
-    #[arg(long, help = "Input file path; defaults to stdin when omitted")]
-    pub input: Option<PathBuf>,
-    #[arg(long, help = "Output file path; defaults to stdout when omitted")]
-    pub output: Option<PathBuf>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct CertsArgs {
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
 #[derive(Debug, Args)]
 pub struct ScenarioArgs {
     #[arg(long, help = "Optional path to rustak YAML config")]
     pub config: Option<PathBuf>,
 }
 
-#[derive(Debug, Args)]
-pub struct StressArgs {
-    #[arg(long)]
-    pub count: Option<u32>,
-    #[arg(long)]
-    pub duration: Option<String>,
-    #[arg(long)]
-    pub target: Option<String>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
-#[derive(Debug, Args)]
-pub struct HealthArgs {
-    #[arg(long)]
-    pub target: Option<String>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
 #[derive(Debug, Args)]
 pub struct SapientArgs {
     #[arg(long)]
@@ -180,128 +153,309 @@ pub struct SapientArgs {
     pub config: Option<PathBuf>,
 }
 
-#[derive(Debug, Args)]
-pub struct BridgeArgs {
-    #[arg(long)]
-    pub sapient: Option<String>,
-    #[arg(long)]
-    pub tak: Option<String>,
-    #[arg(long, help = "Optional path to rustak YAML config")]
-    pub config: Option<PathBuf>,
-}
-
+/// Installs logging from the command's config, then runs the command.
 pub fn run(cli: Cli) -> Result<(), CliError> {
+    install_logging(cli.command.config())?;
     execute_command(cli.command)
 }
 
+impl Command {
+    /// The `--config` the command was given, if it takes one.
+    #[must_use]
+    pub fn config(&self) -> Option<&Path> {
+        match self {
+            Self::Listen(args) => args.config.as_deref(),
+            Self::Send(args) => args.config.as_deref(),
+            Self::Connect(args) => args.config.as_deref(),
+            Self::Sim(args) => args.config.as_deref(),
+            Self::Replay(args) => args.config.as_deref(),
+            Self::Record(args) => args.config.as_deref(),
+            Self::Validate(args) => args.config.as_deref(),
+            Self::Convert(args) => args.config.as_deref(),
+            Self::Certs(CertsArgs {
+                action: CertsAction::Inspect(identity) | CertsAction::Verify(identity),
+            }) => identity.config.as_deref(),
+            Self::Scenario(args) => args.config.as_deref(),
+            Self::Stress(args) => args.config.as_deref(),
+            Self::Health(args) => args.config.as_deref(),
+            Self::Sapient(args) => args.config.as_deref(),
+            Self::Bridge(args) => args.config.as_deref(),
+            Self::Config(ConfigArgs {
+                action: ConfigAction::Budget(budget),
+            }) => budget.config.as_deref(),
+            Self::Doctor(args) => args.config.as_deref(),
+            Self::Certs(_) | Self::Config(_) | Self::Contacts(_) => None,
+        }
+    }
+}
+
+/// Installs the `tracing` subscriber for the config's `logging` section,
+/// or the built-in defaults. Logs go to stderr. A config that does not load
+/// is left for the command itself to report.
+fn install_logging(config: Option<&Path>) -> Result<(), CliError> {
+    let logging = config
+        .and_then(|path| rustak_config::RustakConfig::load(path).ok())
+        .and_then(|config| config.logging)
+        .unwrap_or_default();
+    rustak_telemetry::install(&logging)?;
+    Ok(())
+}
+
 fn execute_command(command: Command) -> Result<(), CliError> {
     match command {
-        Command::Listen(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_transport_defaults()?;
-            scaffolded("listen")
-        }
-        Command::Send(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_transport_defaults()?;
-            scaffolded("send")
-        }
-        Command::Connect(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_server_defaults()?;
-            scaffolded("connect")
-        }
-        Command::Sim(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            scaffolded("sim")
-        }
-        Command::Replay(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            scaffolded("replay")
-        }
-        Command::Record(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            scaffolded("record")
-        }
+        Command::Listen(args) => run_listen(args),
+        Command::Send(args) => run_send(args),
+        Command::Connect(args) => run_connect(args),
+        Command::Sim(args) => run_sim(args),
+        Command::Replay(args) => run_replay(args),
+        Command::Record(mut args) => match args.action.take() {
+            Some(RecordAction::Scrub(scrub)) => {
+                validate_optional_config(args.config.as_deref())?;
+                run_record_scrub(scrub)
+            }
+            Some(RecordAction::Stats(stats)) => {
+                validate_optional_config(args.config.as_deref())?;
+                run_record_stats(stats)
+            }
+            Some(RecordAction::Import(import)) => {
+                validate_optional_config(args.config.as_deref())?;
+                run_record_import(import)
+            }
+            None => run_record(args),
+        },
         Command::Validate(args) => run_validate(args),
         Command::Convert(args) => run_convert(args),
-        Command::Certs(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            scaffolded("certs")
-        }
+        Command::Certs(args) => match args.action {
+            CertsAction::Inspect(identity) => run_certs_inspect(&identity),
+            CertsAction::Verify(identity) => run_certs_verify(&identity),
+            CertsAction::Pin(pin) => run_certs_pin(&pin),
+            CertsAction::Convert(convert) => run_certs_convert(&convert),
+            CertsAction::Enroll(enroll) => run_certs_enroll(&enroll),
+        },
         Command::Scenario(args) => {
             validate_optional_config(args.config.as_deref())?;
             scaffolded("scenario")
         }
-        Command::Stress(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_transport_defaults()?;
-            scaffolded("stress")
-        }
-        Command::Health(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_server_defaults()?;
-            scaffolded("health")
-        }
+        Command::Stress(args) => run_stress(args),
+        Command::Health(args) => run_health(args),
         Command::Sapient(args) => {
             validate_optional_config(args.config.as_deref())?;
             validate_sapient_defaults()?;
             scaffolded("sapient")
         }
-        Command::Bridge(args) => {
-            validate_optional_config(args.config.as_deref())?;
-            validate_sapient_defaults()?;
-            validate_transport_defaults()?;
-            scaffolded("bridge")
-        }
+        Command::Bridge(args) => run_bridge(args),
+        Command::Config(args) => match args.action {
+            ConfigAction::Budget(budget) => run_config_budget(budget),
+            ConfigAction::Docs(docs) => write_output_bytes(
+                rustak_config::render_config_reference().as_bytes(),
+                docs.output.as_deref(),
+            ),
+            ConfigAction::Explain(explain) => run_config_explain(&explain),
+        },
+        Command::Contacts(args) => match args.action {
+            ContactsAction::Export(export) => run_contacts_export(export),
+            ContactsAction::Import(import) => run_contacts_import(import),
+        },
+        Command::Doctor(args) => run_doctor(args),
     }
 }
 
-fn run_validate(args: ValidateArgs) -> Result<(), CliError> {
-    validate_optional_config(args.config.as_deref())?;
+fn read_key_pem(path: &Path) -> Result<String, CliError> {
+    fs::read_to_string(path).map_err(|source| CliError::InputRead {
+        path: path.display().to_string(),
+        source,
+    })
+}
 
-    match args.format {
-        ValidationFormat::Config => {
-            let Some(path) = args.input.as_deref() else {
-                return Err(CliError::ConfigFormatRequiresInputPath);
-            };
-            validate_optional_config(Some(path))
-        }
-        ValidationFormat::Xml => {
-            validate_wire_defaults()?;
-            let payload = read_input_bytes(args.input.as_deref())?;
-            validate_wire_payload(&payload, WireFormat::Xml)
-        }
-        ValidationFormat::TakV1 => {
-            validate_wire_defaults()?;
-            let payload = read_input_bytes(args.input.as_deref())?;
-            validate_wire_payload(&payload, WireFormat::TakProtocolV1)
+/// Non-fatal findings in a CoT event: fields TAK clients expect but that
+/// the wire codecs do not require.
+fn cot_warnings(cot_xml: &[u8], limits: &Limits) -> Vec<String> {
+    let Ok(cot_xml) = std::str::from_utf8(cot_xml) else {
+        return vec!["kind=non_utf8_payload".to_owned()];
+    };
+    match CotEvent::from_xml(cot_xml, limits) {
+        Ok(event) if event.how.is_none() => {
+            vec!["kind=missing_attribute attribute=how".to_owned()]
         }
-        ValidationFormat::Sapient => {
-            validate_sapient_defaults()?;
-            let payload = read_input_bytes(args.input.as_deref())?;
-            ensure_non_empty(&payload)?;
-            rustak_sapient::SapientConfig::default()
-                .codec()
-                .validate_payload(&payload)
-                .map_err(CliError::SapientCodec)
+        Ok(_) => Vec::new(),
+        Err(CotXmlError::MissingAttribute {
+            element: "event",
+            name,
+        }) => vec![format!("kind=missing_attribute attribute={name}")],
+        Err(error) => vec![format!("kind=invalid_event code={}", error.code())],
+    }
+}
+
+/// Logs warnings as `<command>_warning` events and applies the `--fail-on`
+/// threshold.
+fn report_warnings(
+    command: &'static str,
+    warnings: &[String],
+    fail_on: FailOn,
+) -> Result<(), CliError> {
+    for warning in warnings {
+        tracing::warn!(command, warning = %warning, "{command}_warning");
+    }
+    if fail_on == FailOn::Warnings && !warnings.is_empty() {
+        return Err(CliError::WarningThreshold {
+            command,
+            warnings: warnings.len(),
+        });
+    }
+    Ok(())
+}
+
+fn udp_target(addr: SocketAddr) -> UdpTarget {
+    match addr {
+        SocketAddr::V4(v4) if v4.ip().is_multicast() => UdpTarget::Multicast {
+            group: *v4.ip(),
+            port: v4.port(),
+        },
+        other => UdpTarget::Unicast(other),
+    }
+}
+
+fn parse_endpoint(value: &str) -> Result<SocketAddr, CliError> {
+    value.parse().map_err(|source| CliError::InvalidEndpoint {
+        value: value.to_owned(),
+        source,
+    })
+}
+
+impl From<ConvertFormat> for WireFormat {
+    fn from(value: ConvertFormat) -> Self {
+        match value {
+            ConvertFormat::Xml => Self::Xml,
+            ConvertFormat::TakV1 => Self::TakProtocolV1,
         }
     }
 }
 
-fn run_convert(args: ConvertArgs) -> Result<(), CliError> {
-    validate_optional_config(args.config.as_deref())?;
-    validate_wire_defaults()?;
-    let payload = read_input_bytes(args.input.as_deref())?;
-    let converted = convert_payload(&payload, args.from, args.to)?;
-    write_output_bytes(&converted, args.output.as_deref())
+/// The CoT event in `cot_xml`, parsed under `limits`.
+fn parse_event(cot_xml: &[u8], limits: &Limits) -> Option<CotEvent> {
+    let cot_xml = std::str::from_utf8(cot_xml).ok()?;
+    CotEvent::from_xml(cot_xml, limits).ok()
+}
+
+/// The `transport.limits` of the loaded config, or the defaults without one.
+fn config_limits(config: Option<&rustak_config::RustakConfig>) -> Limits {
+    config.map_or_else(Limits::default, |config| config.transport.limits.clone())
+}
+
+/// Codec for TAK protocol v1 mesh (UDP) datagrams, whose protobuf body
+/// follows the `0xBF 0x01 0xBF` header. Stream frames carry no such header.
+const TAK_MESH: MeshFrameCodec =
+    MeshFrameCodec::new(TakProtocolVersion::V1, MAX_UDP_DATAGRAM_BYTES);
+
+/// The protobuf body of `frame`, with its mesh header removed if it has one.
+fn mesh_body(frame: &[u8]) -> &[u8] {
+    TAK_MESH.decode(frame).unwrap_or(frame)
+}
+
+#[cfg(feature = "tls")]
+fn with_tls_connector(
+    manager: ConnectionManager,
+    config: Option<&rustak_config::RustakConfig>,
+) -> Result<ConnectionManager, CliError> {
+    let crypto = config
+        .and_then(tls_crypto_config)
+        .ok_or(CliError::TlsCryptoRequired)?;
+    let identity = crypto
+        .load_identity()
+        .map_err(rustak_transport::TlsError::from)?;
+    let connector = rustak_transport::TlsConnector::new(
+        &identity,
+        &rustak_transport::TlsClientConfig::from_crypto_config(&crypto)?,
+    )?;
+    Ok(manager.with_tls(connector))
+}
+
+#[cfg(not(feature = "tls"))]
+fn with_tls_connector(
+    _manager: ConnectionManager,
+    _config: Option<&rustak_config::RustakConfig>,
+) -> Result<ConnectionManager, CliError> {
+    Err(CliError::UnsupportedSendProtocol { protocol: "tls" })
+}
+
+/// Client TLS settings from the config's `crypto` section plus the PEM
+/// paths in `certificates`; `None` unless both are present.
+fn tls_crypto_config(config: &rustak_config::RustakConfig) -> Option<rustak_crypto::CryptoConfig> {
+    let crypto = config.crypto.as_ref()?;
+    let certificates = config.certificates.as_ref()?;
+    Some(rustak_crypto::CryptoConfig {
+        provider: match crypto.provider {
+            rustak_config::CryptoProvider::Ring => rustak_crypto::CryptoProviderMode::Ring,
+            rustak_config::CryptoProvider::AwsLcRs => rustak_crypto::CryptoProviderMode::AwsLcRs,
+            rustak_config::CryptoProvider::AwsLcRsFips => {
+                rustak_crypto::CryptoProviderMode::AwsLcRsFips
+            }
+        },
+        revocation: match crypto.revocation {
+            rustak_config::RevocationPolicy::Off => rustak_crypto::RevocationPolicy::Off,
+            rustak_config::RevocationPolicy::Prefer => rustak_crypto::RevocationPolicy::Prefer,
+            rustak_config::RevocationPolicy::Require => rustak_crypto::RevocationPolicy::Require,
+        },
+        identity: rustak_crypto::IdentitySource::PemFiles {
+            ca_cert_path: PathBuf::from(&certificates.ca_cert),
+            client_cert_path: PathBuf::from(&certificates.client_cert),
+            client_key_path: PathBuf::from(&certificates.client_key),
+        },
+        server_spki_pin: crypto.server_spki_pin.clone(),
+    })
+}
+
+/// Resolves `target` as a TCP stream, or TLS when the config's protocol
+/// is TLS, falling back to the config's TCP or TLS protocol. `required` is
+/// returned when there is neither.
+fn stream_transport(
+    target: Option<&str>,
+    config: Option<&rustak_config::RustakConfig>,
+    required: CliError,
+) -> Result<TransportConfig, CliError> {
+    let base = config
+        .map(|config| config.transport.clone())
+        .unwrap_or_default();
+    let configured = config.map(|_| &base.protocol);
+    let protocol = match (target, configured) {
+        (Some(target), configured) => {
+            let addr = target
+                .to_socket_addrs()
+                .ok()
+                .and_then(|mut addrs| addrs.next())
+                .ok_or_else(|| CliError::ResolveHost {
+                    host: target.to_owned(),
+                    source: io::Error::from(io::ErrorKind::NotFound),
+                })?;
+            match configured {
+                Some(Protocol::Tls { server_name, .. }) => Protocol::Tls {
+                    addr,
+                    server_name: server_name.clone(),
+                },
+                _ => Protocol::Tcp { addr },
+            }
+        }
+        (None, Some(protocol @ (Protocol::Tcp { .. } | Protocol::Tls { .. }))) => protocol.clone(),
+        (None, Some(Protocol::Udp { .. })) => {
+            return Err(CliError::UnsupportedSendProtocol { protocol: "udp" })
+        }
+        (None, Some(Protocol::WebSocket { .. })) => {
+            return Err(CliError::UnsupportedSendProtocol {
+                protocol: "websocket",
+            })
+        }
+        (None, None) => return Err(required),
+    };
+    Ok(TransportConfig { protocol, ..base })
 }
 
-fn convert_payload(
+/// Converts `payload` and lists the [`cot_warnings`] of the event it holds.
+fn convert_with_warnings(
     payload: &[u8],
     from: ConvertFormat,
     to: ConvertFormat,
-) -> Result<Vec<u8>, CliError> {
+    limits: &Limits,
+) -> Result<(Vec<u8>, Vec<String>), CliError> {
     ensure_non_empty(payload)?;
 
     let cot_xml = match from {
@@ -313,14 +467,15 @@ fn convert_payload(
             rustak_wire::decode_payload_for_format(payload, WireFormat::TakProtocolV1)?
         }
     };
+    let warnings = cot_warnings(&cot_xml, limits);
 
-    match to {
-        ConvertFormat::Xml => Ok(cot_xml),
-        ConvertFormat::TakV1 => Ok(rustak_wire::encode_payload_for_format(
-            &cot_xml,
-            WireFormat::TakProtocolV1,
-        )?),
-    }
+    let converted = match to {
+        ConvertFormat::Xml => cot_xml,
+        ConvertFormat::TakV1 => {
+            rustak_wire::encode_payload_for_format(&cot_xml, WireFormat::TakProtocolV1)?
+        }
+    };
+    Ok((converted, warnings))
 }
 
 fn validate_wire_payload(payload: &[u8], format: WireFormat) -> Result<(), CliError> {
@@ -335,12 +490,35 @@ fn validate_wire_payload(payload: &[u8], format: WireFormat) -> Result<(), CliEr
 }
 
 fn validate_optional_config(path: Option<&Path>) -> Result<(), CliError> {
-    if let Some(path) = path {
-        let config = rustak_config::RustakConfig::load(path)
-            .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
-        config
-            .validate_startup()
-            .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    load_optional_config(path).map(|_| ())
+}
+
+fn load_optional_config(
+    path: Option<&Path>,
+) -> Result<Option<rustak_config::RustakConfig>, CliError> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let config = rustak_config::RustakConfig::load(path)
+        .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    config
+        .validate_startup()
+        .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    log_config_diff(&config)?;
+    Ok(Some(config))
+}
+
+fn log_config_diff(config: &rustak_config::RustakConfig) -> Result<(), CliError> {
+    let diff = config
+        .diff_from_defaults()
+        .map_err(|source| CliError::Facade(RustakError::Config(source)))?;
+    for change in &diff {
+        tracing::info!(
+            path = %change.path,
+            default = change.default.as_deref().unwrap_or("<unset>"),
+            effective = change.effective.as_deref().unwrap_or("<unset>"),
+            "config_diff"
+        );
     }
     Ok(())
 }
@@ -363,12 +541,6 @@ fn validate_sapient_defaults() -> Result<(), CliError> {
         .map_err(|source| CliError::Facade(RustakError::Sapient(source)))
 }
 
-fn validate_server_defaults() -> Result<(), CliError> {
-    rustak_server::ServerClientConfig::default()
-        .validate()
-        .map_err(CliError::ServerConfig)
-}
-
 fn ensure_non_empty(payload: &[u8]) -> Result<(), CliError> {
     if payload.is_empty() {
         return Err(CliError::EmptyInput);
@@ -426,6 +598,21 @@ pub enum CliError {
     #[error(transparent)]
     ServerConfig(ServerConfigError),
 
+    #[error(transparent)]
+    Scrub(#[from] ScrubError),
+
+    #[error(transparent)]
+    Stats(#[from] StatsError),
+
+    #[error(transparent)]
+    Rotate(#[from] RotateError),
+
+    #[error(transparent)]
+    PcapImport(#[from] PcapImportError),
+
+    #[error(transparent)]
+    Contacts(#[from] ContactDirectoryError),
+
     #[error("`validate --format config` requires `--input <path-to-rustak.yaml>`")]
     ConfigFormatRequiresInputPath,
 
@@ -446,23 +633,472 @@ pub enum CliError {
 
     #[error("failed to write stdout: {source}")]
     StdoutWrite { source: io::Error },
+
+    #[error("`listen` needs `--udp`, `--tcp` or a config with a udp protocol")]
+    ListenEndpointRequired,
+
+    #[error("invalid endpoint `{value}`: {source}")]
+    InvalidEndpoint {
+        value: String,
+        source: std::net::AddrParseError,
+    },
+
+    #[error("failed to bind `{addr}`: {source}")]
+    Bind { addr: SocketAddr, source: io::Error },
+
+    #[error(transparent)]
+    Udp(#[from] UdpTransportError),
+
+    #[error(transparent)]
+    Transport(#[from] TransportComposeError),
+
+    #[error("`send` needs `--lat` and `--lon`")]
+    SendPositionRequired,
+
+    #[error("invalid position: {0}")]
+    InvalidPosition(CoreError),
+
+    #[error("`send` needs `--udp`, `--tcp` or a config with a transport protocol")]
+    SendEndpointRequired,
+
+    #[error("`send` does not support the {protocol} protocol")]
+    UnsupportedSendProtocol { protocol: &'static str },
+
+    #[error("event of {payload_bytes} bytes exceeds the UDP MTU policy")]
+    SendOversize { payload_bytes: usize },
+
+    #[error("tls protocol needs `crypto` and `certificates` sections in the config")]
+    TlsCryptoRequired,
+
+    #[error(transparent)]
+    Connect(#[from] ConnectionManagerError),
+
+    #[error("`connect` needs `--host` or a config with a tcp or tls protocol")]
+    ConnectEndpointRequired,
+
+    #[error("failed to resolve `{host}`: {source}")]
+    ResolveHost { host: String, source: io::Error },
+
+    #[error(transparent)]
+    Streaming(#[from] StreamingError),
+
+    #[error("`bridge` needs `--sapient <listen-addr>`")]
+    BridgeSapientRequired,
+
+    #[error("`bridge` needs `--tak` or a config with a tcp or tls protocol")]
+    BridgeTakRequired,
+
+    #[error("`record` needs `--source`, `--tcp` or a config with a udp, tcp or tls protocol")]
+    RecordSourceRequired,
+
+    #[error("`record` needs `--output <path.takrec>`")]
+    RecordOutputRequired,
+
+    #[error("`replay` needs `--input <path.takrec>`")]
+    ReplayInputRequired,
+
+    #[error("`replay` needs `--target`, `--tcp` or a config with a transport protocol")]
+    ReplayTargetRequired,
+
+    #[error("`--speed` must be 0 or a positive multiplier, got {speed}")]
+    ReplaySpeedInvalid { speed: f64 },
+
+    #[error("`{path}` is not a config field; `rustak config docs` lists them all")]
+    UnknownConfigField { path: String },
+
+    #[error("`doctor` found {failures} failing check(s)")]
+    DoctorFailed { failures: usize },
+
+    #[cfg(feature = "tls")]
+    #[error(transparent)]
+    Tls(#[from] rustak_transport::TlsError),
+
+    #[error("async runtime error: {source}")]
+    Runtime { source: io::Error },
+
+    #[error("`{command}` reported {warnings} warning(s) with `--fail-on warnings`")]
+    WarningThreshold {
+        command: &'static str,
+        warnings: usize,
+    },
+
+    #[error("`convert --input-dir` needs `--output-dir`")]
+    ConvertOutputDirRequired,
+
+    #[error("`--output-dir` `{path}` must differ from `--input-dir`")]
+    ConvertOutputDirOverlapsInput { path: String },
+
+    #[error("failed to read input directory `{path}`: {source}")]
+    InputDirRead { path: String, source: io::Error },
+
+    #[error("`convert` failed for {failed} of {total} file(s)")]
+    ConvertBatchFailed { failed: usize, total: usize },
+
+    #[error(transparent)]
+    Crypto(#[from] rustak_crypto::CryptoError),
+
+    #[error("`certs` needs `--p12`, `--cert`, `--key`, `--ca` or a config with a `certificates` section")]
+    CertsIdentityRequired,
+
+    #[error("`--password-env` names `{name}`, which is not set")]
+    PasswordEnvUnset { name: String },
+
+    #[error("the private key does not belong to the client certificate")]
+    CertsKeyMismatch,
+
+    #[error(transparent)]
+    Enrollment(#[from] rustak_server::EnrollmentError),
+
+    #[error("`--server {server}` must be HOST or HOST:PORT")]
+    InvalidEnrollmentServer { server: String },
+
+    #[error("`sim` needs `--scenario <path.yaml>`")]
+    SimScenarioRequired,
+
+    #[error("`sim` needs `--target`, `--tcp` or a config with a transport protocol")]
+    SimTargetRequired,
+
+    #[error("failed to parse scenario `{path}`: {message}")]
+    SimScenarioParse { path: String, message: String },
+
+    #[error("scenario has no entities to simulate")]
+    SimNoEntities,
+
+    #[error(transparent)]
+    Scenario(#[from] rustak_sim::ScenarioError),
+
+    #[error(transparent)]
+    TruthEngine(#[from] rustak_sim::TruthEngineError),
+
+    #[error(transparent)]
+    TrackEmitter(#[from] rustak_sim::TrackEmitterError),
+
+    #[error(transparent)]
+    Route(#[from] rustak_sim::RouteError),
+
+    #[error("`stress` needs `--count` or a profile with `count`")]
+    StressCountRequired,
+
+    #[error("`{value}` is not a duration; use e.g. 300s, 5m or 1h")]
+    StressDurationInvalid { value: String },
+
+    #[error("`stress` needs `--target` or a config with a tcp or tls protocol")]
+    StressTargetRequired,
+
+    #[error("failed to parse stress profile `{path}`: {message}")]
+    StressProfileParse { path: String, message: String },
+
+    #[error("stress `{field}` must be > 0")]
+    StressProfileInvalid { field: &'static str },
+
+    #[error(transparent)]
+    SendQueue(#[from] SendQueueError),
+
+    #[error("`health` needs `--target` or a config with a tcp or tls protocol")]
+    HealthTargetRequired,
+
+    #[error("`health` failed at the {stage} stage")]
+    HealthCheckFailed { stage: &'static str },
+
+    #[error(transparent)]
+    Telemetry(#[from] rustak_telemetry::TelemetryError),
+
+    #[error("`{flag}` must be an RFC 3339 time such as 2024-01-01T12:00:00Z, got `{value}`")]
+    ReplayTimeInvalid { flag: &'static str, value: String },
+
+    #[error("`--start {start}` must be before `--end {end}`")]
+    ReplayWindowEmpty { start: String, end: String },
+
+    #[error("replay digest of `{input}` does not match `{compare}`")]
+    ReplayDigestMismatch { input: String, compare: String },
+
+    #[error(transparent)]
+    Integrity(#[from] IntegrityError),
+
+    #[error(
+        "`--verify-key` needs the takrec's chain; pass `--chain` or keep `<input>.chain` beside it"
+    )]
+    TakrecChainRequired,
+}
+
+impl CodedError for CliError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotImplemented { .. } => ErrorCode::new("CLI", 1),
+            Self::Facade(error) => error.code(),
+            Self::WirePayload(error) => error.code(),
+            Self::SapientCodec(error) => error.code(),
+            Self::ServerConfig(error) => error.code(),
+            Self::Scrub(error) => error.code(),
+            Self::Stats(error) => error.code(),
+            Self::Rotate(error) => error.code(),
+            Self::PcapImport(error) => error.code(),
+            Self::Contacts(error) => error.code(),
+            Self::ConfigFormatRequiresInputPath => ErrorCode::new("CLI", 2),
+            Self::WireRoundTripMismatch { .. } => ErrorCode::new("CLI", 3),
+            Self::InputRead { .. } => ErrorCode::new("CLI", 4),
+            Self::StdinRead { .. } => ErrorCode::new("CLI", 5),
+            Self::EmptyInput => ErrorCode::new("CLI", 6),
+            Self::OutputWrite { .. } => ErrorCode::new("CLI", 7),
+            Self::StdoutWrite { .. } => ErrorCode::new("CLI", 8),
+            Self::ListenEndpointRequired => ErrorCode::new("CLI", 9),
+            Self::InvalidEndpoint { .. } => ErrorCode::new("CLI", 10),
+            Self::Bind { .. } => ErrorCode::new("CLI", 11),
+            Self::Udp(error) => error.code(),
+            Self::Transport(error) => error.code(),
+            Self::SendPositionRequired => ErrorCode::new("CLI", 12),
+            Self::InvalidPosition(_) => ErrorCode::new("CLI", 13),
+            Self::SendEndpointRequired => ErrorCode::new("CLI", 14),
+            Self::UnsupportedSendProtocol { .. } => ErrorCode::new("CLI", 15),
+            Self::SendOversize { .. } => ErrorCode::new("CLI", 16),
+            Self::TlsCryptoRequired => ErrorCode::new("CLI", 17),
+            Self::Connect(error) => error.code(),
+            Self::ConnectEndpointRequired => ErrorCode::new("CLI", 18),
+            Self::ResolveHost { .. } => ErrorCode::new("CLI", 19),
+            Self::Streaming(error) => error.code(),
+            Self::BridgeSapientRequired => ErrorCode::new("CLI", 20),
+            Self::BridgeTakRequired => ErrorCode::new("CLI", 21),
+            Self::RecordSourceRequired => ErrorCode::new("CLI", 22),
+            Self::RecordOutputRequired => ErrorCode::new("CLI", 23),
+            Self::ReplayInputRequired => ErrorCode::new("CLI", 24),
+            Self::ReplayTargetRequired => ErrorCode::new("CLI", 25),
+            Self::ReplaySpeedInvalid { .. } => ErrorCode::new("CLI", 26),
+            Self::UnknownConfigField { .. } => ErrorCode::new("CLI", 27),
+            Self::DoctorFailed { .. } => ErrorCode::new("CLI", 28),
+            #[cfg(feature = "tls")]
+            Self::Tls(error) => error.code(),
+            Self::Runtime { .. } => ErrorCode::new("CLI", 29),
+            Self::WarningThreshold { .. } => ErrorCode::new("CLI", 30),
+            Self::ConvertOutputDirRequired => ErrorCode::new("CLI", 31),
+            Self::ConvertOutputDirOverlapsInput { .. } => ErrorCode::new("CLI", 32),
+            Self::InputDirRead { .. } => ErrorCode::new("CLI", 33),
+            Self::ConvertBatchFailed { .. } => ErrorCode::new("CLI", 34),
+            Self::Crypto(error) => error.code(),
+            Self::CertsIdentityRequired => ErrorCode::new("CLI", 35),
+            Self::PasswordEnvUnset { .. } => ErrorCode::new("CLI", 36),
+            Self::CertsKeyMismatch => ErrorCode::new("CLI", 37),
+            Self::Enrollment(error) => error.code(),
+            Self::InvalidEnrollmentServer { .. } => ErrorCode::new("CLI", 38),
+            Self::SimScenarioRequired => ErrorCode::new("CLI", 39),
+            Self::SimTargetRequired => ErrorCode::new("CLI", 40),
+            Self::SimScenarioParse { .. } => ErrorCode::new("CLI", 41),
+            Self::SimNoEntities => ErrorCode::new("CLI", 42),
+            Self::Scenario(error) => error.code(),
+            Self::TruthEngine(error) => error.code(),
+            Self::TrackEmitter(error) => error.code(),
+            Self::Route(error) => error.code(),
+            Self::StressCountRequired => ErrorCode::new("CLI", 43),
+            Self::StressDurationInvalid { .. } => ErrorCode::new("CLI", 44),
+            Self::StressTargetRequired => ErrorCode::new("CLI", 45),
+            Self::StressProfileParse { .. } => ErrorCode::new("CLI", 46),
+            Self::StressProfileInvalid { .. } => ErrorCode::new("CLI", 47),
+            Self::SendQueue(error) => error.code(),
+            Self::HealthTargetRequired => ErrorCode::new("CLI", 48),
+            Self::HealthCheckFailed { .. } => ErrorCode::new("CLI", 49),
+            Self::Telemetry(error) => error.code(),
+            Self::ReplayTimeInvalid { .. } => ErrorCode::new("CLI", 50),
+            Self::ReplayWindowEmpty { .. } => ErrorCode::new("CLI", 51),
+            Self::ReplayDigestMismatch { .. } => ErrorCode::new("CLI", 52),
+            Self::Integrity(error) => error.code(),
+            Self::TakrecChainRequired => ErrorCode::new("CLI", 53),
+        }
+    }
+}
+
+/// Process exit codes. Scripts may rely on these values; they are only
+/// ever added to, never renumbered.
+///
+/// | code | meaning |
+/// |------|---------|
+/// | 0 | success |
+/// | 1 | unclassified failure, including local file and stdio errors |
+/// | 2 | usage error (clap also exits 2 for unparseable arguments) |
+/// | 3 | configuration failed to load or validate |
+/// | 4 | could not connect to, or lost, a peer |
+/// | 5 | input failed validation or decoding |
+/// | 6 | partial success: the command finished but `--fail-on` tripped |
+/// | 7 | command is not implemented yet |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExitStatus {
+    Success = 0,
+    Failure = 1,
+    Usage = 2,
+    Config = 3,
+    Connection = 4,
+    Validation = 5,
+    PartialSuccess = 6,
+    NotImplemented = 7,
+}
+
+impl ExitStatus {
+    #[must_use]
+    pub const fn code(self) -> u8 {
+        self as u8
+    }
 }
 
 impl CliError {
+    #[must_use]
+    pub const fn exit_status(&self) -> ExitStatus {
+        match self {
+            Self::NotImplemented { .. } => ExitStatus::NotImplemented,
+            Self::Facade(RustakError::Io(_)) => ExitStatus::Connection,
+            Self::Facade(
+                RustakError::Core(_)
+                | RustakError::Timestamp(_)
+                | RustakError::WireFrame(_)
+                | RustakError::Record(_)
+                | RustakError::Runtime(_),
+            ) => ExitStatus::Failure,
+            Self::Facade(_) | Self::ServerConfig(_) => ExitStatus::Config,
+            Self::WirePayload(_)
+            | Self::SapientCodec(_)
+            | Self::WireRoundTripMismatch { .. }
+            | Self::ReplayDigestMismatch { .. }
+            | Self::Integrity(_)
+            | Self::EmptyInput => ExitStatus::Validation,
+            Self::ConfigFormatRequiresInputPath
+            | Self::ListenEndpointRequired
+            | Self::InvalidEndpoint { .. }
+            | Self::SendPositionRequired
+            | Self::InvalidPosition(_)
+            | Self::SendEndpointRequired
+            | Self::ConnectEndpointRequired
+            | Self::BridgeSapientRequired
+            | Self::BridgeTakRequired
+            | Self::RecordSourceRequired
+            | Self::RecordOutputRequired
+            | Self::ReplayInputRequired
+            | Self::ReplayTargetRequired
+            | Self::ReplaySpeedInvalid { .. }
+            | Self::ReplayTimeInvalid { .. }
+            | Self::ReplayWindowEmpty { .. }
+            | Self::TakrecChainRequired
+            | Self::UnknownConfigField { .. }
+            | Self::ConvertOutputDirRequired
+            | Self::ConvertOutputDirOverlapsInput { .. }
+            | Self::CertsIdentityRequired
+            | Self::PasswordEnvUnset { .. }
+            | Self::InvalidEnrollmentServer { .. }
+            | Self::SimScenarioRequired
+            | Self::SimTargetRequired
+            | Self::StressCountRequired
+            | Self::StressDurationInvalid { .. }
+            | Self::StressTargetRequired
+            | Self::HealthTargetRequired
+            | Self::Enrollment(
+                rustak_server::EnrollmentError::EmptyField { .. }
+                | rustak_server::EnrollmentError::InvalidUsername
+                | rustak_server::EnrollmentError::ZeroTimeout,
+            ) => ExitStatus::Usage,
+            Self::UnsupportedSendProtocol { .. } | Self::TlsCryptoRequired | Self::SendQueue(_) => {
+                ExitStatus::Config
+            }
+            Self::Bind { .. }
+            | Self::Udp(_)
+            | Self::Transport(_)
+            | Self::Connect(_)
+            | Self::ResolveHost { .. }
+            | Self::Streaming(_)
+            | Self::HealthCheckFailed { .. }
+            | Self::Enrollment(_) => ExitStatus::Connection,
+            #[cfg(feature = "tls")]
+            Self::Tls(_) => ExitStatus::Connection,
+            Self::Crypto(rustak_crypto::CryptoError::ReadPath { .. }) => ExitStatus::Failure,
+            Self::SendOversize { .. }
+            | Self::ConvertBatchFailed { .. }
+            | Self::SimScenarioParse { .. }
+            | Self::SimNoEntities
+            | Self::Scenario(_)
+            | Self::TruthEngine(_)
+            | Self::TrackEmitter(_)
+            | Self::Route(_)
+            | Self::StressProfileParse { .. }
+            | Self::StressProfileInvalid { .. }
+            | Self::Crypto(_)
+            | Self::CertsKeyMismatch => ExitStatus::Validation,
+            Self::WarningThreshold { .. } => ExitStatus::PartialSuccess,
+            Self::Scrub(_)
+            | Self::Stats(_)
+            | Self::Rotate(_)
+            | Self::PcapImport(_)
+            | Self::Contacts(_)
+            | Self::DoctorFailed { .. }
+            | Self::InputRead { .. }
+            | Self::InputDirRead { .. }
+            | Self::StdinRead { .. }
+            | Self::OutputWrite { .. }
+            | Self::StdoutWrite { .. }
+            | Self::Telemetry(_)
+            | Self::Runtime { .. } => ExitStatus::Failure,
+        }
+    }
+
     #[must_use]
     pub const fn exit_code(&self) -> u8 {
-        1
+        self.exit_status().code()
+    }
+
+    /// The line `rustak` prints on stderr when a command fails.
+    #[must_use]
+    pub fn render(&self, format: ErrorFormat) -> String {
+        match format {
+            ErrorFormat::Text => self.coded_message(),
+            ErrorFormat::Json => serde_json::json!({
+                "error": {
+                    "code": self.code().to_string(),
+                    "message": self.to_string(),
+                    "exit_code": self.exit_code(),
+                }
+            })
+            .to_string(),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
     use clap::Parser;
+    use rustak_limits::CodedError;
 
-    use super::{
-        convert_payload, execute_command, validate_wire_payload, Cli, CliError, Command,
-        ConvertFormat, ListenArgs, ValidateArgs, ValidationFormat,
-    };
+    use rustak_config::{LogFormat, LogLevel, LoggingConfig};
+
+    use super::{log_config_diff, report_warnings, Cli, CliError, ErrorFormat, ExitStatus, FailOn};
+
+    #[derive(Debug, Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().expect("capture").extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// What `emit` logs through the subscriber `rustak` installs for
+    /// `logging`.
+    fn logged(logging: &LoggingConfig, emit: impl FnOnce()) -> String {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = rustak_telemetry::subscriber(logging, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, emit);
+        let bytes = capture.0.lock().expect("capture").clone();
+        String::from_utf8(bytes).expect("utf-8 logs")
+    }
+
+    /// A minimal CoT event stamped with `time`, shared by the command tests.
+    pub(crate) fn replay_event(uid: &str, time: &str) -> Vec<u8> {
+        format!("<event version=\"2.0\" uid=\"{uid}\" type=\"a-f-G\" how=\"m-g\" time=\"{time}\" start=\"{time}\" stale=\"{time}\"><point lat=\"1\" lon=\"2\" hae=\"0\" ce=\"5\" le=\"5\"/></event>")
+            .into_bytes()
+    }
 
     #[test]
     fn required_entrypoint_commands_parse() {
@@ -474,44 +1110,129 @@ mod tests {
     }
 
     #[test]
-    fn validate_config_requires_input_path() {
-        let error = execute_command(Command::Validate(ValidateArgs {
-            format: ValidationFormat::Config,
-            input: None,
-            config: None,
-        }))
-        .expect_err("config validation should require input path");
-
-        assert!(matches!(error, CliError::ConfigFormatRequiresInputPath));
+    fn commands_expose_the_config_logging_is_installed_from() {
+        let config = |argv: &[&str]| {
+            Cli::try_parse_from(std::iter::once(&"rustak").chain(argv))
+                .expect("parse")
+                .command
+                .config()
+                .map(std::path::Path::to_path_buf)
+        };
+        assert_eq!(
+            config(&["health", "--config", "ops.yaml"]),
+            Some("ops.yaml".into())
+        );
+        assert_eq!(
+            config(&["certs", "inspect", "--config", "ops.yaml"]),
+            Some("ops.yaml".into())
+        );
+        assert_eq!(
+            config(&["config", "budget", "--config", "ops.yaml"]),
+            Some("ops.yaml".into())
+        );
+        assert_eq!(config(&["listen"]), None);
+        assert_eq!(config(&["config", "explain", "transport.protocol"]), None);
     }
 
     #[test]
-    fn xml_validation_routes_through_wire_payload_path() {
-        let payload = b"<event uid=\"unit-test\"/>".to_vec();
-        validate_wire_payload(&payload, rustak_wire::WireFormat::Xml)
-            .expect("xml payload should be wire-roundtrippable");
+    fn exit_codes_follow_documented_taxonomy() {
+        let config_error = CliError::Facade(rustak::RustakError::Transport(
+            rustak_transport::TransportConfigError::ZeroUdpPayloadLimit,
+        ));
+        assert_eq!(config_error.exit_status(), ExitStatus::Config);
+        assert_eq!(config_error.exit_code(), 3);
+        let connection_error =
+            CliError::Facade(rustak::RustakError::Io(rustak_io::IoError::Closed));
+        assert_eq!(connection_error.exit_code(), 4);
+        assert_eq!(CliError::EmptyInput.exit_code(), 5);
+        assert_eq!(CliError::NotImplemented { command: "sim" }.exit_code(), 7);
     }
 
     #[test]
-    fn convert_round_trip_between_xml_and_tak_v1_is_lossless() {
-        let xml = b"<event uid=\"unit-test\"/>".to_vec();
-        let encoded = convert_payload(&xml, ConvertFormat::Xml, ConvertFormat::TakV1)
-            .expect("xml->tak conversion should succeed");
-        let decoded = convert_payload(&encoded, ConvertFormat::TakV1, ConvertFormat::Xml)
-            .expect("tak->xml conversion should succeed");
-        assert_eq!(decoded, xml);
+    fn errors_render_with_stable_codes() {
+        let error = CliError::Facade(rustak::RustakError::Transport(
+            rustak_transport::TransportConfigError::ZeroUdpPayloadLimit,
+        ));
+        assert_eq!(error.code().to_string(), "RTK-TRANSPORT-0005");
+        assert_eq!(
+            CliError::EmptyInput.render(ErrorFormat::Text),
+            "RTK-CLI-0006: input payload must not be empty"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&CliError::EmptyInput.render(ErrorFormat::Json))
+                .expect("json error");
+        assert_eq!(json["error"]["code"], "RTK-CLI-0006");
+        assert_eq!(json["error"]["exit_code"], 5);
+
+        let cli = Cli::try_parse_from(["rustak", "listen", "--error-format", "json"])
+            .expect("global flag after the subcommand");
+        assert_eq!(cli.error_format, ErrorFormat::Json);
     }
 
     #[test]
-    fn listen_is_explicitly_scaffolded() {
-        let error = execute_command(Command::Listen(ListenArgs {
-            udp: None,
-            config: None,
-        }))
-        .expect_err("listen should currently be scaffolded");
-        assert!(matches!(
-            error,
-            CliError::NotImplemented { command: "listen" }
-        ));
+    fn command_logs_follow_the_logging_config() {
+        let mut config = rustak_config::RustakConfig {
+            crypto: Some(rustak_config::CryptoConfig {
+                provider: rustak_config::CryptoProvider::Ring,
+                revocation: rustak_config::RevocationPolicy::Prefer,
+                server_spki_pin: Some("super-secret-pin".to_owned()),
+                signing: None,
+            }),
+            ..rustak_config::RustakConfig::default()
+        };
+        config.transport.limits.max_queue_messages = 256;
+        let emit = || {
+            log_config_diff(&config).expect("diff should render");
+            report_warnings(
+                "validate",
+                &["kind=missing_attribute".to_owned()],
+                FailOn::Errors,
+            )
+            .expect("warnings only");
+        };
+
+        let info = logged(
+            &LoggingConfig {
+                redact: vec!["report.warning".to_owned()],
+                ..LoggingConfig::default()
+            },
+            emit,
+        );
+        assert!(!info.contains("super-secret-pin"), "{info}");
+        let records: Vec<serde_json::Value> = info
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("json log line"))
+            .collect();
+        let queue = records
+            .iter()
+            .find(|record| record["fields"]["path"] == "transport.limits.max_queue_messages")
+            .expect("changed limit is logged");
+        assert_eq!(queue["level"], "INFO");
+        assert_eq!(queue["message"], "config_diff");
+        assert_eq!(queue["fields"]["default"], "1024");
+        assert_eq!(queue["fields"]["effective"], "256");
+        assert!(records.iter().any(|record| {
+            record["fields"]["path"] == "crypto.server_spki_pin"
+                && record["fields"]["effective"] == rustak_telemetry::REDACTED
+        }));
+        let warning = records.last().expect("warning is logged");
+        assert_eq!(warning["level"], "WARN");
+        assert_eq!(warning["message"], "validate_warning");
+        assert_eq!(warning["fields"]["command"], "validate");
+        assert_eq!(warning["fields"]["warning"], rustak_telemetry::REDACTED);
+
+        let warn = logged(
+            &LoggingConfig {
+                level: LogLevel::Warn,
+                format: LogFormat::Compact,
+                ..LoggingConfig::default()
+            },
+            emit,
+        );
+        assert_eq!(warn.lines().count(), 1, "{warn}");
+        assert!(
+            warn.contains(r#"WARN  rustak_cli: validate_warning command=validate warning="kind=missing_attribute""#),
+            "{warn}"
+        );
     }
 }
@@ -3,10 +3,11 @@ use std::process::ExitCode;
 
 fn main() -> ExitCode {
     let cli = rustak_cli::Cli::parse();
+    let error_format = cli.error_format;
     match rustak_cli::run(cli) {
         Ok(()) => ExitCode::SUCCESS,
         Err(error) => {
-            eprintln!("{error}");
+            eprintln!("{}", error.render(error_format));
             ExitCode::from(error.exit_code())
         }
     }
@@ -0,0 +1,589 @@
+use std::time::{Duration, Instant};
+
+use rustak_core::{CoreError, CotEvent, DetailNode, Position, TimestampUtc};
+use rustak_limits::{CodedError, ErrorCode};
+use thiserror::Error;
+
+/// Supplies the local node's position to a [`SelfReporter`].
+///
+/// `Ok(None)` means the source has no fix yet; the reporter skips that cycle.
+pub trait PositionSource {
+    fn current_position(&mut self) -> Result<Option<Position>, PositionSourceError>;
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PositionSourceError {
+    #[error("malformed NMEA sentence: {reason}")]
+    Nmea { reason: &'static str },
+
+    #[error("malformed gpsd report: {reason}")]
+    Gpsd { reason: String },
+
+    #[error(transparent)]
+    InvalidPosition(#[from] CoreError),
+}
+
+impl CodedError for PositionSourceError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Nmea { .. } => ErrorCode::new("COMMO", 201),
+            Self::Gpsd { .. } => ErrorCode::new("COMMO", 202),
+            Self::InvalidPosition(error) => error.code(),
+        }
+    }
+}
+
+/// Fixed position taken from configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticPositionSource {
+    position: Position,
+}
+
+impl StaticPositionSource {
+    #[must_use]
+    pub fn new(position: Position) -> Self {
+        Self { position }
+    }
+}
+
+impl PositionSource for StaticPositionSource {
+    fn current_position(&mut self) -> Result<Option<Position>, PositionSourceError> {
+        Ok(Some(self.position.clone()))
+    }
+}
+
+/// Latest fix from an NMEA 0183 stream (serial GPS, log file, TCP relay).
+///
+/// The caller owns the IO and feeds lines through [`Self::ingest_sentence`];
+/// `GGA` and `RMC` sentences from any talker are understood.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NmeaPositionSource {
+    latest: Option<Position>,
+}
+
+impl NmeaPositionSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `Ok(true)` when the sentence updated the current fix.
+    pub fn ingest_sentence(&mut self, line: &str) -> Result<bool, PositionSourceError> {
+        let body = nmea_body(line.trim())?;
+        let fields: Vec<&str> = body.split(',').collect();
+        let kind = fields
+            .first()
+            .filter(|tag| tag.len() == 5)
+            .map(|tag| &tag[2..])
+            .ok_or(PositionSourceError::Nmea {
+                reason: "missing sentence tag",
+            })?;
+
+        let position = match kind {
+            "GGA" => parse_gga(&fields)?,
+            "RMC" => parse_rmc(&fields)?,
+            _ => return Ok(false),
+        };
+
+        let Some(position) = position else {
+            return Ok(false);
+        };
+        self.latest = Some(position);
+        Ok(true)
+    }
+}
+
+impl PositionSource for NmeaPositionSource {
+    fn current_position(&mut self) -> Result<Option<Position>, PositionSourceError> {
+        Ok(self.latest.clone())
+    }
+}
+
+/// Latest fix from a gpsd JSON stream (`?WATCH={"enable":true,"json":true}`).
+///
+/// Only `TPV` reports with a 2D or 3D fix update the position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpsdPositionSource {
+    latest: Option<Position>,
+}
+
+impl GpsdPositionSource {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `Ok(true)` when the report updated the current fix.
+    pub fn ingest_line(&mut self, line: &str) -> Result<bool, PositionSourceError> {
+        let report: serde_json::Value =
+            serde_json::from_str(line).map_err(|error| PositionSourceError::Gpsd {
+                reason: error.to_string(),
+            })?;
+
+        if report.get("class").and_then(serde_json::Value::as_str) != Some("TPV") {
+            return Ok(false);
+        }
+        if report
+            .get("mode")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0)
+            < 2
+        {
+            return Ok(false);
+        }
+
+        let number = |key: &str| report.get(key).and_then(serde_json::Value::as_f64);
+        let (Some(latitude), Some(longitude)) = (number("lat"), number("lon")) else {
+            return Ok(false);
+        };
+
+        let mut position = Position::new(latitude, longitude)?;
+        if let Some(hae) = number("altHAE").or_else(|| number("alt")) {
+            position = position.with_hae(hae)?;
+        }
+        if let Some(horizontal) = number("eph") {
+            position = position.with_ce(horizontal)?;
+        }
+        if let Some(vertical) = number("epv") {
+            position = position.with_le(vertical)?;
+        }
+
+        self.latest = Some(position);
+        Ok(true)
+    }
+}
+
+impl PositionSource for GpsdPositionSource {
+    fn current_position(&mut self) -> Result<Option<Position>, PositionSourceError> {
+        Ok(self.latest.clone())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMembership {
+    pub name: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfReporterConfig {
+    pub uid: String,
+    pub callsign: String,
+    pub group: Option<GroupMembership>,
+    pub cot_type: String,
+    pub interval: Duration,
+    pub stale_after: Duration,
+    /// Fraction of `interval` (0.0..=1.0) by which each period is randomly
+    /// shortened or stretched, so co-started nodes do not beacon in lockstep.
+    pub jitter: f64,
+    pub seed: u64,
+}
+
+impl Default for SelfReporterConfig {
+    fn default() -> Self {
+        Self {
+            uid: String::new(),
+            callsign: String::new(),
+            group: Some(GroupMembership {
+                name: "Cyan".to_owned(),
+                role: "Team Member".to_owned(),
+            }),
+            cot_type: "a-f-G-U-C".to_owned(),
+            interval: Duration::from_secs(10),
+            stale_after: Duration::from_secs(60),
+            jitter: 0.1,
+            seed: 0x5E1F_BEAC,
+        }
+    }
+}
+
+impl SelfReporterConfig {
+    pub fn validate(&self) -> Result<(), SelfReporterError> {
+        if self.uid.trim().is_empty() {
+            return Err(SelfReporterError::EmptyField { field: "uid" });
+        }
+        if self.callsign.trim().is_empty() {
+            return Err(SelfReporterError::EmptyField { field: "callsign" });
+        }
+        if self.cot_type.trim().is_empty() {
+            return Err(SelfReporterError::EmptyField { field: "cot_type" });
+        }
+        if self.interval.is_zero() {
+            return Err(SelfReporterError::ZeroInterval);
+        }
+        if self.stale_after < self.interval {
+            return Err(SelfReporterError::StaleBeforeInterval {
+                stale_after: self.stale_after,
+                interval: self.interval,
+            });
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(SelfReporterError::JitterOutOfRange {
+                jitter: self.jitter,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SelfReporterError {
+    #[error("self reporter {field} must not be empty")]
+    EmptyField { field: &'static str },
+
+    #[error("self reporter interval must be greater than zero")]
+    ZeroInterval,
+
+    #[error("stale_after ({stale_after:?}) must be >= interval ({interval:?})")]
+    StaleBeforeInterval {
+        stale_after: Duration,
+        interval: Duration,
+    },
+
+    #[error("self reporter jitter must be within [0.0, 1.0], got {jitter}")]
+    JitterOutOfRange { jitter: f64 },
+
+    #[error(transparent)]
+    Source(#[from] PositionSourceError),
+}
+
+impl CodedError for SelfReporterError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyField { .. } => ErrorCode::new("COMMO", 301),
+            Self::ZeroInterval => ErrorCode::new("COMMO", 302),
+            Self::StaleBeforeInterval { .. } => ErrorCode::new("COMMO", 303),
+            Self::JitterOutOfRange { .. } => ErrorCode::new("COMMO", 304),
+            Self::Source(error) => error.code(),
+        }
+    }
+}
+
+/// Periodic PLI (position location information) beacon for the local node.
+///
+/// The reporter is clock-driven rather than owning a task: call
+/// [`Self::poll`] from whatever loop or timer the host uses, and send the
+/// returned CoT XML on the configured transport.
+#[derive(Debug)]
+pub struct SelfReporter<S: PositionSource> {
+    config: SelfReporterConfig,
+    source: S,
+    next_due: Option<Instant>,
+    rng_state: u64,
+}
+
+impl<S: PositionSource> SelfReporter<S> {
+    pub fn new(config: SelfReporterConfig, source: S) -> Result<Self, SelfReporterError> {
+        config.validate()?;
+        let rng_state = config.seed.max(1);
+        Ok(Self {
+            config,
+            source,
+            next_due: None,
+            rng_state,
+        })
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &SelfReporterConfig {
+        &self.config
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// `None` until the first poll, which always reports immediately.
+    #[must_use]
+    pub fn next_due(&self) -> Option<Instant> {
+        self.next_due
+    }
+
+    /// Emits a PLI event when the beacon is due and the source has a fix.
+    ///
+    /// A cycle without a fix is skipped rather than retried early, so a GPS
+    /// outage never turns into a burst of reports.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        wall: TimestampUtc,
+    ) -> Result<Option<String>, SelfReporterError> {
+        if self.next_due.is_some_and(|due| now < due) {
+            return Ok(None);
+        }
+
+        self.next_due = Some(now + self.next_period());
+        let Some(position) = self.source.current_position()? else {
+            return Ok(None);
+        };
+        Ok(Some(self.render_pli(&position, wall)))
+    }
+
+    #[must_use]
+    pub fn render_pli(&self, position: &Position, wall: TimestampUtc) -> String {
+        let stale_nanos = i128::try_from(self.config.stale_after.as_nanos()).unwrap_or(i128::MAX);
+        let stale = TimestampUtc::from_unix_nanos(wall.unix_nanos().saturating_add(stale_nanos));
+
+        let mut event = CotEvent::new(
+            self.config.uid.as_str(),
+            self.config.cot_type.as_str(),
+            wall,
+            stale,
+            position.clone(),
+        );
+        event.how = Some("m-g".to_owned());
+        event
+            .detail
+            .push(DetailNode::new("contact").with_attribute("callsign", &self.config.callsign));
+        if let Some(group) = &self.config.group {
+            event.detail.push(
+                DetailNode::new("__group")
+                    .with_attribute("name", &group.name)
+                    .with_attribute("role", &group.role),
+            );
+        }
+        event.to_xml()
+    }
+
+    fn next_period(&mut self) -> Duration {
+        if self.config.jitter == 0.0 {
+            return self.config.interval;
+        }
+        // Uniform in [-jitter, +jitter] of the base interval.
+        let unit = (next_rand(&mut self.rng_state) >> 11) as f64 / (1_u64 << 53) as f64;
+        let scale = 1.0 + self.config.jitter * (unit * 2.0 - 1.0);
+        self.config.interval.mul_f64(scale.max(0.0))
+    }
+}
+
+fn next_rand(state: &mut u64) -> u64 {
+    let mut value = *state;
+    value ^= value << 13;
+    value ^= value >> 7;
+    value ^= value << 17;
+    *state = value;
+    value
+}
+
+fn nmea_body(line: &str) -> Result<&str, PositionSourceError> {
+    let Some(rest) = line.strip_prefix('$') else {
+        return Err(PositionSourceError::Nmea {
+            reason: "sentence must start with '$'",
+        });
+    };
+
+    let Some((body, checksum)) = rest.split_once('*') else {
+        return Ok(rest);
+    };
+    let expected =
+        u8::from_str_radix(checksum.trim(), 16).map_err(|_| PositionSourceError::Nmea {
+            reason: "checksum is not hex",
+        })?;
+    let actual = body.bytes().fold(0_u8, |acc, byte| acc ^ byte);
+    if actual != expected {
+        return Err(PositionSourceError::Nmea {
+            reason: "checksum mismatch",
+        });
+    }
+    Ok(body)
+}
+
+fn parse_gga(fields: &[&str]) -> Result<Option<Position>, PositionSourceError> {
+    if fields.len() < 12 {
+        return Err(PositionSourceError::Nmea {
+            reason: "GGA sentence is truncated",
+        });
+    }
+    if fields[6].is_empty() || fields[6] == "0" {
+        return Ok(None);
+    }
+
+    let latitude = parse_nmea_coordinate(fields[2], fields[3], 2)?;
+    let longitude = parse_nmea_coordinate(fields[4], fields[5], 3)?;
+    let mut position = Position::new(latitude, longitude)?;
+
+    // GGA altitude is above mean sea level; adding the geoid separation
+    // yields height above the ellipsoid, which is what CoT expects.
+    if let Ok(msl) = fields[9].parse::<f64>() {
+        let separation = fields[11].parse::<f64>().unwrap_or(0.0);
+        position = position.with_hae(msl + separation)?;
+    }
+    Ok(Some(position))
+}
+
+fn parse_rmc(fields: &[&str]) -> Result<Option<Position>, PositionSourceError> {
+    if fields.len() < 7 {
+        return Err(PositionSourceError::Nmea {
+            reason: "RMC sentence is truncated",
+        });
+    }
+    if fields[2] != "A" {
+        return Ok(None);
+    }
+
+    let latitude = parse_nmea_coordinate(fields[3], fields[4], 2)?;
+    let longitude = parse_nmea_coordinate(fields[5], fields[6], 3)?;
+    Ok(Some(Position::new(latitude, longitude)?))
+}
+
+fn parse_nmea_coordinate(
+    value: &str,
+    hemisphere: &str,
+    degree_digits: usize,
+) -> Result<f64, PositionSourceError> {
+    let invalid = || PositionSourceError::Nmea {
+        reason: "invalid coordinate",
+    };
+    if value.len() <= degree_digits || !value.is_ascii() {
+        return Err(invalid());
+    }
+    let degrees: f64 = value[..degree_digits].parse().map_err(|_| invalid())?;
+    let minutes: f64 = value[degree_digits..].parse().map_err(|_| invalid())?;
+    let magnitude = degrees + minutes / 60.0;
+
+    match hemisphere {
+        "N" | "E" => Ok(magnitude),
+        "S" | "W" => Ok(-magnitude),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rustak_core::{Position, TimestampUtc};
+
+    use super::{
+        GpsdPositionSource, NmeaPositionSource, PositionSource, PositionSourceError, SelfReporter,
+        SelfReporterConfig, SelfReporterError, StaticPositionSource,
+    };
+
+    fn config() -> SelfReporterConfig {
+        SelfReporterConfig {
+            uid: "ANDROID-node-1".to_owned(),
+            callsign: "Viper <1>".to_owned(),
+            jitter: 0.0,
+            ..SelfReporterConfig::default()
+        }
+    }
+
+    #[test]
+    fn config_rejects_stale_shorter_than_interval() {
+        let error = SelfReporterConfig {
+            stale_after: Duration::from_secs(5),
+            ..config()
+        }
+        .validate()
+        .expect_err("stale window must cover the interval");
+        assert!(matches!(
+            error,
+            SelfReporterError::StaleBeforeInterval { .. }
+        ));
+    }
+
+    #[test]
+    fn reporter_emits_on_first_poll_then_waits_for_interval() {
+        let position = Position::new(51.5, -0.12).expect("position");
+        let mut reporter = SelfReporter::new(config(), StaticPositionSource::new(position))
+            .expect("reporter should construct");
+        let start = Instant::now();
+        let wall = TimestampUtc::from_unix_seconds(1_700_000_000);
+
+        let pli = reporter
+            .poll(start, wall)
+            .expect("poll")
+            .expect("first poll reports");
+        assert!(pli.contains("uid=\"ANDROID-node-1\""));
+        assert!(pli.contains("type=\"a-f-G-U-C\""));
+        assert!(pli.contains("time=\"2023-11-14T22:13:20.000Z\""));
+        assert!(pli.contains("stale=\"2023-11-14T22:14:20.000Z\""));
+        assert!(pli.contains("lat=\"51.5\" lon=\"-0.12\""));
+        assert!(pli.contains("<contact callsign=\"Viper &lt;1&gt;\"/>"));
+        assert!(pli.contains("<__group name=\"Cyan\" role=\"Team Member\"/>"));
+
+        assert_eq!(
+            reporter.poll(start + Duration::from_secs(9), wall),
+            Ok(None)
+        );
+        assert!(reporter
+            .poll(start + Duration::from_secs(10), wall)
+            .expect("poll")
+            .is_some());
+    }
+
+    #[test]
+    fn jitter_keeps_period_within_bounds() {
+        let mut reporter = SelfReporter::new(
+            SelfReporterConfig {
+                jitter: 0.2,
+                ..config()
+            },
+            StaticPositionSource::new(Position::new(0.0, 0.0).expect("position")),
+        )
+        .expect("reporter should construct");
+        let start = Instant::now();
+        let wall = TimestampUtc::UNIX_EPOCH;
+
+        let mut now = start;
+        for _ in 0..32 {
+            reporter.poll(now, wall).expect("poll");
+            let due = reporter.next_due().expect("scheduled");
+            let period = due - now;
+            assert!(period >= Duration::from_secs(8) && period <= Duration::from_secs(12));
+            now = due;
+        }
+    }
+
+    #[test]
+    fn nmea_gga_and_rmc_update_fix() {
+        let mut source = NmeaPositionSource::new();
+        assert_eq!(source.current_position(), Ok(None));
+
+        let gga = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47";
+        assert_eq!(source.ingest_sentence(gga), Ok(true));
+        let fix = source.current_position().expect("fix").expect("some");
+        assert!((fix.latitude() - 48.1173).abs() < 1e-4);
+        assert!((fix.longitude() - 11.516_666).abs() < 1e-4);
+        assert!((fix.hae().expect("hae") - 592.3).abs() < 1e-9);
+
+        let rmc = "$GNRMC,123519,A,3345.000,S,15112.000,W,022.4,084.4,230394,003.1,W";
+        assert_eq!(source.ingest_sentence(rmc), Ok(true));
+        let fix = source.current_position().expect("fix").expect("some");
+        assert!((fix.latitude() + 33.75).abs() < 1e-9);
+        assert!((fix.longitude() + 151.2).abs() < 1e-9);
+
+        assert_eq!(
+            source.ingest_sentence("$GPGSV,3,1,11,03,03,111,00"),
+            Ok(false)
+        );
+        assert_eq!(
+            source.ingest_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08*00"),
+            Err(PositionSourceError::Nmea {
+                reason: "checksum mismatch"
+            })
+        );
+    }
+
+    #[test]
+    fn gpsd_tpv_reports_update_fix() {
+        let mut source = GpsdPositionSource::new();
+        assert_eq!(
+            source.ingest_line(r#"{"class":"SKY","satellites":[]}"#),
+            Ok(false)
+        );
+        assert_eq!(source.ingest_line(r#"{"class":"TPV","mode":1}"#), Ok(false));
+        assert_eq!(
+            source.ingest_line(
+                r#"{"class":"TPV","mode":3,"lat":38.9,"lon":-77.03,"altHAE":12.5,"eph":4.0,"epv":6.0}"#
+            ),
+            Ok(true)
+        );
+
+        let fix = source.current_position().expect("fix").expect("some");
+        assert_eq!(fix.latitude(), 38.9);
+        assert_eq!(fix.hae(), Some(12.5));
+        assert_eq!(fix.ce(), Some(4.0));
+        assert_eq!(fix.le(), Some(6.0));
+    }
+}
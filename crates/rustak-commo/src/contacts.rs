@@ -0,0 +1,370 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustak_core::{Contact, CotEvent, Group};
+use rustak_limits::{CodedError, ErrorCode};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use crate::beacon::GroupMembership;
+
+/// Version written to and accepted from exported contact directories.
+pub const CONTACT_DIRECTORY_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactEntry {
+    pub uid: String,
+    pub callsign: Option<String>,
+    /// `host:port:protocol` from the `<contact>` detail.
+    pub endpoint: Option<String>,
+    pub group: Option<GroupMembership>,
+    pub last_seen_unix_ms: u64,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ContactDirectoryError {
+    #[error("contact tracker max_contacts must be greater than zero")]
+    ZeroCapacity,
+
+    #[error("contact directory is not valid JSON: {reason}")]
+    InvalidJson { reason: String },
+
+    #[error("unsupported contact directory version {found}; expected {CONTACT_DIRECTORY_VERSION}")]
+    UnsupportedVersion { found: u64 },
+
+    #[error("contact directory entry {index} is invalid: {reason}")]
+    InvalidEntry { index: usize, reason: &'static str },
+}
+
+impl CodedError for ContactDirectoryError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroCapacity => ErrorCode::new("COMMO", 401),
+            Self::InvalidJson { .. } => ErrorCode::new("COMMO", 402),
+            Self::UnsupportedVersion { .. } => ErrorCode::new("COMMO", 403),
+            Self::InvalidEntry { .. } => ErrorCode::new("COMMO", 404),
+        }
+    }
+}
+
+/// Bounded UID to callsign/team directory learned from observed CoT.
+///
+/// When full, the least recently seen contact is evicted.
+#[derive(Debug, Clone)]
+pub struct ContactTracker {
+    max_contacts: usize,
+    contacts: HashMap<String, ContactEntry>,
+    /// `(last_seen_unix_ms, uid)` of every contact, stalest first.
+    by_last_seen: BTreeSet<(u64, String)>,
+}
+
+impl ContactTracker {
+    pub fn new(max_contacts: usize) -> Result<Self, ContactDirectoryError> {
+        if max_contacts == 0 {
+            return Err(ContactDirectoryError::ZeroCapacity);
+        }
+        Ok(Self {
+            max_contacts,
+            contacts: HashMap::new(),
+            by_last_seen: BTreeSet::new(),
+        })
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.contacts.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.contacts.is_empty()
+    }
+
+    #[must_use]
+    pub fn get(&self, uid: &str) -> Option<&ContactEntry> {
+        self.contacts.get(uid)
+    }
+
+    #[must_use]
+    pub fn callsign(&self, uid: &str) -> Option<&str> {
+        self.get(uid)?.callsign.as_deref()
+    }
+
+    /// Entries sorted by uid.
+    #[must_use]
+    pub fn entries(&self) -> Vec<&ContactEntry> {
+        let mut entries = self.contacts.values().collect::<Vec<_>>();
+        entries.sort_by(|left, right| left.uid.cmp(&right.uid));
+        entries
+    }
+
+    /// Records the [`Contact`] and [`Group`] details of a CoT event.
+    /// Returns `false` when the event carries neither in a valid form.
+    pub fn observe_event(&mut self, event: &CotEvent, seen_at: SystemTime) -> bool {
+        let contact = event.extension::<Contact>().and_then(Result::ok);
+        let group = event.extension::<Group>().and_then(Result::ok);
+        if contact.is_none() && group.is_none() {
+            return false;
+        }
+
+        let (callsign, endpoint) = contact.map_or((None, None), |contact| {
+            (Some(contact.callsign), contact.endpoint)
+        });
+        self.upsert(ContactEntry {
+            uid: event.uid.clone(),
+            callsign,
+            endpoint,
+            group: group.map(|group| GroupMembership {
+                name: group.name,
+                role: group.role,
+            }),
+            last_seen_unix_ms: unix_millis(seen_at),
+        });
+        true
+    }
+
+    /// Merges `entry`, keeping fields the newer entry does not carry. An
+    /// older entry never overwrites fields of a newer one.
+    pub fn upsert(&mut self, entry: ContactEntry) {
+        if let Some(existing) = self.contacts.get_mut(&entry.uid) {
+            let (newer, older) = if entry.last_seen_unix_ms >= existing.last_seen_unix_ms {
+                (entry, existing.clone())
+            } else {
+                (existing.clone(), entry)
+            };
+            self.by_last_seen
+                .remove(&(existing.last_seen_unix_ms, existing.uid.clone()));
+            *existing = ContactEntry {
+                callsign: newer.callsign.or(older.callsign),
+                endpoint: newer.endpoint.or(older.endpoint),
+                group: newer.group.or(older.group),
+                ..newer
+            };
+            self.by_last_seen
+                .insert((existing.last_seen_unix_ms, existing.uid.clone()));
+            return;
+        }
+
+        if self.contacts.len() >= self.max_contacts {
+            if let Some((_, stalest)) = self.by_last_seen.pop_first() {
+                self.contacts.remove(&stalest);
+            }
+        }
+        self.by_last_seen
+            .insert((entry.last_seen_unix_ms, entry.uid.clone()));
+        self.contacts.insert(entry.uid.clone(), entry);
+    }
+
+    /// Pretty-printed `{"version":1,"contacts":[...]}` document.
+    #[must_use]
+    pub fn export_json(&self) -> String {
+        let contacts = self
+            .entries()
+            .into_iter()
+            .map(|entry| {
+                json!({
+                    "uid": entry.uid,
+                    "callsign": entry.callsign,
+                    "endpoint": entry.endpoint,
+                    "team": entry.group.as_ref().map(|group| group.name.as_str()),
+                    "role": entry.group.as_ref().map(|group| group.role.as_str()),
+                    "last_seen_unix_ms": entry.last_seen_unix_ms,
+                })
+            })
+            .collect::<Vec<_>>();
+        let document = json!({
+            "version": CONTACT_DIRECTORY_VERSION,
+            "contacts": contacts,
+        });
+        serde_json::to_string_pretty(&document).unwrap_or_else(|_| document.to_string())
+    }
+
+    /// Merges an exported directory into the tracker, returning how many
+    /// entries it contained.
+    pub fn import_json(&mut self, json: &str) -> Result<usize, ContactDirectoryError> {
+        let document: Value =
+            serde_json::from_str(json).map_err(|error| ContactDirectoryError::InvalidJson {
+                reason: error.to_string(),
+            })?;
+        let version = document.get("version").and_then(Value::as_u64).ok_or(
+            ContactDirectoryError::InvalidJson {
+                reason: "missing numeric `version`".to_owned(),
+            },
+        )?;
+        if version != CONTACT_DIRECTORY_VERSION {
+            return Err(ContactDirectoryError::UnsupportedVersion { found: version });
+        }
+        let contacts = document.get("contacts").and_then(Value::as_array).ok_or(
+            ContactDirectoryError::InvalidJson {
+                reason: "missing `contacts` array".to_owned(),
+            },
+        )?;
+
+        let entries = contacts
+            .iter()
+            .enumerate()
+            .map(|(index, contact)| parse_entry(index, contact))
+            .collect::<Result<Vec<_>, _>>()?;
+        let imported = entries.len();
+        for entry in entries {
+            self.upsert(entry);
+        }
+        Ok(imported)
+    }
+}
+
+fn parse_entry(index: usize, contact: &Value) -> Result<ContactEntry, ContactDirectoryError> {
+    let invalid = |reason| ContactDirectoryError::InvalidEntry { index, reason };
+    let optional_string = |key: &str| match contact.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(invalid("string fields must be strings or null")),
+    };
+
+    let uid = optional_string("uid")?
+        .filter(|uid| !uid.trim().is_empty())
+        .ok_or_else(|| invalid("uid must be a non-empty string"))?;
+    let callsign = optional_string("callsign")?;
+    let endpoint = optional_string("endpoint")?;
+    let team = optional_string("team")?;
+    let role = optional_string("role")?;
+    let last_seen_unix_ms = match contact.get("last_seen_unix_ms") {
+        None | Some(Value::Null) => 0,
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| invalid("last_seen_unix_ms must be an unsigned integer"))?,
+    };
+
+    Ok(ContactEntry {
+        uid,
+        callsign,
+        endpoint,
+        group: team.map(|name| GroupMembership {
+            name,
+            role: role.unwrap_or_default(),
+        }),
+        last_seen_unix_ms,
+    })
+}
+
+fn unix_millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map_or(0, |elapsed| {
+        u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use rustak_core::CotEvent;
+    use rustak_limits::Limits;
+
+    use super::{ContactDirectoryError, ContactEntry, ContactTracker};
+
+    const PLI_DETAIL: &str = "<contact endpoint=\"*:-1:stcp\" callsign=\"Red Fox &amp; Co\"/><__group name=\"Cyan\" role=\"Team Lead\"/>";
+
+    fn event(uid: &str, detail: &str) -> CotEvent {
+        let xml = format!("<event version=\"2.0\" uid=\"{uid}\" type=\"a-f-G-U-C\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:05:00Z\"><point lat=\"0\" lon=\"0\"/><detail>{detail}</detail></event>");
+        CotEvent::from_xml(&xml, &Limits::default()).expect("event")
+    }
+
+    #[test]
+    fn observed_contacts_round_trip_through_json() {
+        let mut tracker = ContactTracker::new(8).expect("capacity");
+        assert!(tracker.observe_event(
+            &event("ANDROID-1", PLI_DETAIL),
+            UNIX_EPOCH + Duration::from_millis(5_000)
+        ));
+        assert!(!tracker.observe_event(&event("x", "<remarks>hi</remarks>"), UNIX_EPOCH));
+        assert!(
+            !tracker.observe_event(&event("y", "<contact endpoint=\"*:-1:stcp\"/>"), UNIX_EPOCH)
+        );
+        assert_eq!(tracker.callsign("ANDROID-1"), Some("Red Fox & Co"));
+        assert_eq!(
+            tracker
+                .get("ANDROID-1")
+                .and_then(|entry| entry.endpoint.as_deref()),
+            Some("*:-1:stcp")
+        );
+
+        let exported = tracker.export_json();
+        let mut restored = ContactTracker::new(8).expect("capacity");
+        assert_eq!(restored.import_json(&exported), Ok(1));
+        assert_eq!(restored.entries(), tracker.entries());
+        let group = restored
+            .get("ANDROID-1")
+            .and_then(|entry| entry.group.clone())
+            .expect("group");
+        assert_eq!(
+            (group.name.as_str(), group.role.as_str()),
+            ("Cyan", "Team Lead")
+        );
+    }
+
+    #[test]
+    fn older_import_does_not_overwrite_newer_callsign() {
+        let mut tracker = ContactTracker::new(8).expect("capacity");
+        tracker.observe_event(
+            &event("ANDROID-1", PLI_DETAIL),
+            UNIX_EPOCH + Duration::from_millis(5_000),
+        );
+
+        let stale = r#"{"version":1,"contacts":[{"uid":"ANDROID-1","callsign":"Old Name","last_seen_unix_ms":10},{"uid":"ANDROID-2","callsign":"Blue"}]}"#;
+        assert_eq!(tracker.import_json(stale), Ok(2));
+        assert_eq!(tracker.callsign("ANDROID-1"), Some("Red Fox & Co"));
+        assert_eq!(tracker.callsign("ANDROID-2"), Some("Blue"));
+    }
+
+    #[test]
+    fn capacity_evicts_least_recently_seen() {
+        let mut tracker = ContactTracker::new(2).expect("capacity");
+        for (uid, millis) in [("a", 30), ("b", 10), ("c", 20)] {
+            tracker.observe_event(
+                &event(uid, &format!("<contact callsign=\"{uid}\"/>")),
+                UNIX_EPOCH + Duration::from_millis(millis),
+            );
+        }
+        assert!(tracker.get("b").is_none());
+        assert_eq!(tracker.len(), 2);
+
+        // Seeing "c" again makes "a" the stalest; an older sighting of "a"
+        // does not refresh it.
+        tracker.upsert(ContactEntry {
+            uid: "c".to_owned(),
+            callsign: None,
+            endpoint: None,
+            group: None,
+            last_seen_unix_ms: 40,
+        });
+        tracker.upsert(ContactEntry {
+            uid: "a".to_owned(),
+            callsign: Some("stale".to_owned()),
+            endpoint: None,
+            group: None,
+            last_seen_unix_ms: 5,
+        });
+        assert_eq!(tracker.callsign("a"), Some("a"));
+        tracker.observe_event(
+            &event("d", "<contact callsign=\"d\"/>"),
+            UNIX_EPOCH + Duration::from_millis(50),
+        );
+        assert!(tracker.get("a").is_none());
+        assert_eq!(tracker.callsign("c"), Some("c"));
+        assert_eq!(tracker.len(), 2);
+    }
+
+    #[test]
+    fn import_rejects_unknown_versions_and_bad_entries() {
+        let mut tracker = ContactTracker::new(4).expect("capacity");
+        assert_eq!(
+            tracker.import_json(r#"{"version":2,"contacts":[]}"#),
+            Err(ContactDirectoryError::UnsupportedVersion { found: 2 })
+        );
+        assert!(matches!(
+            tracker.import_json(r#"{"version":1,"contacts":[{"callsign":"x"}]}"#),
+            Err(ContactDirectoryError::InvalidEntry { index: 0, .. })
+        ));
+        assert!(tracker.is_empty());
+    }
+}
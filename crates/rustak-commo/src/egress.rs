@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use rustak_core::{CotEvent, DetailNode, Position};
+use rustak_io::layers::FanOutLayer;
+use rustak_io::{CotMessage, MessageSink};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use thiserror::Error;
+
+/// One coarsening step applied to CoT XML leaving for a destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EgressRule {
+    /// Drops the whole `<detail>` tree.
+    StripDetail,
+    /// Rounds every coordinate to `decimals` places: the `<point>`, and in
+    /// the detail any `lat`/`lon`/`latitude`/`longitude` attribute and the
+    /// `lat,lon` pair of a `point` attribute such as `<link point=...>`.
+    RoundCoordinates { decimals: u8 },
+    /// Replaces the event type with `to` when it equals `from_prefix` or
+    /// extends it (`a-f-G` matches `a-f-G-U-C` but not `a-f-GX`).
+    RemapType { from_prefix: String, to: String },
+    /// Keeps only the first `max_atoms` dash-separated atoms of the type,
+    /// e.g. `a-f-G-U-C-I` becomes `a-f-G` with 3.
+    GeneralizeType { max_atoms: usize },
+}
+
+/// Coordinates cannot usefully be rounded past ~1 cm.
+pub const MAX_COORDINATE_DECIMALS: u8 = 7;
+
+/// Detail attributes that hold a latitude or longitude on their own.
+const COORDINATE_ATTRIBUTES: [&str; 4] = ["lat", "lon", "latitude", "longitude"];
+
+/// Ordered rules applied to every message sent to one destination.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EgressTransform {
+    pub rules: Vec<EgressRule>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EgressError {
+    #[error("egress destination name must not be empty")]
+    EmptyDestinationName,
+
+    #[error("egress destination `{destination}`: {reason}")]
+    InvalidRule {
+        destination: String,
+        reason: &'static str,
+    },
+
+    #[error("egress rules configured for `{destination}` but no sink was provided")]
+    MissingDestinationSink { destination: String },
+
+    #[error("egress destination `{destination}` was given more than one sink")]
+    DuplicateDestination { destination: String },
+}
+
+impl CodedError for EgressError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyDestinationName => ErrorCode::new("COMMO", 501),
+            Self::InvalidRule { .. } => ErrorCode::new("COMMO", 502),
+            Self::MissingDestinationSink { .. } => ErrorCode::new("COMMO", 503),
+            Self::DuplicateDestination { .. } => ErrorCode::new("COMMO", 504),
+        }
+    }
+}
+
+impl EgressTransform {
+    fn validate(&self, destination: &str) -> Result<(), EgressError> {
+        let invalid = |reason| EgressError::InvalidRule {
+            destination: destination.to_owned(),
+            reason,
+        };
+        for rule in &self.rules {
+            match rule {
+                EgressRule::RoundCoordinates { decimals }
+                    if *decimals > MAX_COORDINATE_DECIMALS =>
+                {
+                    return Err(invalid("round_coordinates decimals must be <= 7"));
+                }
+                EgressRule::RemapType { from_prefix, to }
+                    if from_prefix.trim().is_empty() || to.trim().is_empty() =>
+                {
+                    return Err(invalid("remap_type from_prefix and to must not be empty"));
+                }
+                EgressRule::GeneralizeType { max_atoms: 0 } => {
+                    return Err(invalid("generalize_type max_atoms must be > 0"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies the rules to one CoT XML payload and re-serializes the
+    /// event. Returns `None` when the payload is not a CoT event the rules
+    /// can be applied to, so nothing uncoarsened leaks to the destination.
+    #[must_use]
+    pub fn apply(&self, payload: &[u8]) -> Option<Bytes> {
+        if self.rules.is_empty() {
+            return Some(Bytes::copy_from_slice(payload));
+        }
+        let xml = std::str::from_utf8(payload).ok()?;
+        let mut event = CotEvent::from_xml(xml, &Limits::conservative_defaults()).ok()?;
+
+        for rule in &self.rules {
+            match rule {
+                EgressRule::StripDetail => event.detail.clear(),
+                EgressRule::RoundCoordinates { decimals } => {
+                    event.point = round_position(&event.point, *decimals)?;
+                    for node in &mut event.detail {
+                        round_detail_coordinates(node, *decimals);
+                    }
+                }
+                EgressRule::RemapType { from_prefix, to } => {
+                    let matches = event
+                        .cot_type
+                        .strip_prefix(from_prefix.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'));
+                    if matches {
+                        event.cot_type.clone_from(to);
+                    }
+                }
+                EgressRule::GeneralizeType { max_atoms } => {
+                    event.cot_type = event
+                        .cot_type
+                        .split('-')
+                        .take(*max_atoms)
+                        .collect::<Vec<_>>()
+                        .join("-");
+                }
+            }
+        }
+        Some(Bytes::from(event.to_xml()))
+    }
+}
+
+/// Egress rules keyed by destination name. Destinations without an entry
+/// receive the original stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EgressConfig {
+    pub destinations: BTreeMap<String, EgressTransform>,
+}
+
+impl EgressConfig {
+    pub fn validate(&self) -> Result<(), EgressError> {
+        for (destination, transform) in &self.destinations {
+            if destination.trim().is_empty() {
+                return Err(EgressError::EmptyDestinationName);
+            }
+            transform.validate(destination)?;
+        }
+        Ok(())
+    }
+
+    /// Builds a fan-out over `sinks`, attaching each destination's rules.
+    /// Every configured destination must have a sink so a typo cannot route
+    /// a partner feed around its rules.
+    pub fn fan_out(
+        &self,
+        sinks: impl IntoIterator<Item = (String, Box<dyn MessageSink<CotMessage>>)>,
+    ) -> Result<FanOutLayer<CotMessage>, EgressError> {
+        self.validate()?;
+        let sinks = sinks.into_iter().collect::<Vec<_>>();
+        if let Some(destination) = self
+            .destinations
+            .keys()
+            .find(|destination| !sinks.iter().any(|(name, _)| name == *destination))
+        {
+            return Err(EgressError::MissingDestinationSink {
+                destination: destination.clone(),
+            });
+        }
+
+        let mut layer = FanOutLayer::new();
+        for (name, sink) in sinks {
+            if name.trim().is_empty() {
+                return Err(EgressError::EmptyDestinationName);
+            }
+            // Names are non-empty here, so the fan-out can only reject a
+            // repeated one.
+            let duplicate = EgressError::DuplicateDestination {
+                destination: name.clone(),
+            };
+            layer = match self.destinations.get(&name) {
+                Some(transform) if !transform.rules.is_empty() => {
+                    let transform = transform.clone();
+                    layer.with_transformed_destination(name, sink, move |envelope| {
+                        let message = transform.apply(&envelope.message)?;
+                        let mut coarsened = envelope.clone().map_message(|_| message);
+                        // The raw frame still holds the original bytes.
+                        coarsened.raw_frame = None;
+                        Some(coarsened)
+                    })
+                }
+                _ => layer.with_destination(name, sink),
+            }
+            .map_err(|_| duplicate)?;
+        }
+        Ok(layer)
+    }
+}
+
+fn round_coordinate(value: f64, decimals: u8) -> f64 {
+    format!("{value:.*}", usize::from(decimals))
+        .parse()
+        .unwrap_or(value)
+}
+
+fn round_text(value: &str, decimals: u8) -> Option<String> {
+    let value = value.trim().parse::<f64>().ok()?;
+    Some(format!("{value:.*}", usize::from(decimals)))
+}
+
+fn round_position(point: &Position, decimals: u8) -> Option<Position> {
+    let mut rounded = Position::new(
+        round_coordinate(point.latitude(), decimals),
+        round_coordinate(point.longitude(), decimals),
+    )
+    .ok()?;
+    if let Some(hae) = point.hae() {
+        rounded = rounded.with_hae(hae).ok()?;
+    }
+    if let Some(ce) = point.ce() {
+        rounded = rounded.with_ce(ce).ok()?;
+    }
+    if let Some(le) = point.le() {
+        rounded = rounded.with_le(le).ok()?;
+    }
+    Some(rounded)
+}
+
+fn round_detail_coordinates(node: &mut DetailNode, decimals: u8) {
+    for (name, value) in &mut node.attributes {
+        if COORDINATE_ATTRIBUTES.contains(&name.as_str()) {
+            if let Some(rounded) = round_text(value, decimals) {
+                *value = rounded;
+            }
+        } else if name == "point" {
+            // `lat,lon[,hae]`; the altitude is left alone.
+            *value = value
+                .split(',')
+                .enumerate()
+                .map(|(index, part)| match round_text(part, decimals) {
+                    Some(rounded) if index < 2 => rounded,
+                    _ => part.to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+        }
+    }
+    for child in &mut node.children {
+        round_detail_coordinates(child, decimals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use futures::executor::block_on;
+    use futures::future::BoxFuture;
+    use rustak_core::CotEvent;
+    use rustak_io::{CotEnvelope, CotMessage, IoError, MessageEnvelope, MessageSink};
+    use rustak_limits::Limits;
+
+    use super::{EgressConfig, EgressError, EgressRule, EgressTransform};
+
+    const EVENT: &str = "<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G-U-C-I\" how=\"m-g\" time=\"2024-05-01T10:00:00.000Z\" start=\"2024-05-01T10:00:00.000Z\" stale=\"2024-05-01T10:05:00.000Z\"><point lat=\"34.123456\" lon=\"-117.987654\" hae=\"10\" ce=\"5\" le=\"9999999.0\"/><detail><contact callsign=\"ALPHA\"/><remarks>grid 11SMT</remarks></detail></event>";
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<Bytes>>>, Arc<Mutex<Vec<Option<Bytes>>>>);
+
+    impl MessageSink<CotMessage> for Collect {
+        fn send(&self, msg: CotMessage) -> BoxFuture<'_, Result<(), IoError>> {
+            self.send_envelope(MessageEnvelope::new(msg))
+        }
+
+        fn send_envelope(&self, env: CotEnvelope) -> BoxFuture<'_, Result<(), IoError>> {
+            self.1.lock().expect("collect mutex").push(env.raw_frame);
+            self.0.lock().expect("collect mutex").push(env.message);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn partner_rules() -> EgressTransform {
+        EgressTransform {
+            rules: vec![
+                EgressRule::StripDetail,
+                EgressRule::RoundCoordinates { decimals: 2 },
+                EgressRule::GeneralizeType { max_atoms: 3 },
+            ],
+        }
+    }
+
+    #[test]
+    fn rules_coarsen_detail_position_and_type() {
+        let coarse = partner_rules().apply(EVENT.as_bytes()).expect("cot xml");
+        assert_eq!(
+            std::str::from_utf8(&coarse).expect("utf8"),
+            "<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G\" how=\"m-g\" time=\"2024-05-01T10:00:00.000Z\" start=\"2024-05-01T10:00:00.000Z\" stale=\"2024-05-01T10:05:00.000Z\"><point lat=\"34.12\" lon=\"-117.99\" hae=\"10\" ce=\"5\" le=\"9999999.0\"/></event>"
+        );
+
+        let remap = EgressTransform {
+            rules: vec![EgressRule::RemapType {
+                from_prefix: "a-f-G-U".to_owned(),
+                to: "a-u-G".to_owned(),
+            }],
+        };
+        let remapped = remap.apply(EVENT.as_bytes()).expect("cot xml");
+        assert!(std::str::from_utf8(&remapped)
+            .expect("utf8")
+            .contains("type=\"a-u-G\""));
+        assert!(partner_rules().apply(b"\xbf\x01\xbf binary").is_none());
+
+        let escaped = EgressTransform {
+            rules: vec![EgressRule::RemapType {
+                from_prefix: "a-f".to_owned(),
+                to: "a-u-G\"/><detail><x".to_owned(),
+            }],
+        }
+        .apply(EVENT.as_bytes())
+        .expect("cot xml");
+        let event = CotEvent::from_xml(
+            std::str::from_utf8(&escaped).expect("utf8"),
+            &Limits::conservative_defaults(),
+        )
+        .expect("remapped event stays well formed");
+        assert_eq!(event.cot_type, "a-u-G\"/><detail><x");
+        assert_eq!(event.detail.len(), 2);
+    }
+
+    #[test]
+    fn round_coordinates_reaches_detail_coordinates() {
+        let drawing = "<event version=\"2.0\" uid=\"route-1\" type=\"b-m-r\" how=\"h-e\" time=\"2024-05-01T10:00:00.000Z\" start=\"2024-05-01T10:00:00.000Z\" stale=\"2024-05-01T11:00:00.000Z\"><point lat=\"34.123456\" lon=\"-117.987654\" hae=\"0\" ce=\"9999999\" le=\"9999999\"/><detail><link uid=\"wp-1\" type=\"b-m-p-w\" point=\"34.111111,-117.222222,12.5\"/><shape><polyline closed=\"true\"><vertex lat=\"34.333333\" lon=\"-117.444444\"/></polyline></shape><target latitude=\"34.555555\" longitude=\"-117.666666\"/><remarks>hold</remarks></detail></event>";
+        let coarse = EgressTransform {
+            rules: vec![EgressRule::RoundCoordinates { decimals: 2 }],
+        }
+        .apply(drawing.as_bytes())
+        .expect("cot xml");
+        let coarse = std::str::from_utf8(&coarse).expect("utf8");
+
+        for precise in [
+            "123456", "987654", "111111", "222222", "333333", "444444", "555555", "666666",
+        ] {
+            assert!(!coarse.contains(precise), "{precise} leaked: {coarse}");
+        }
+        let event = CotEvent::from_xml(coarse, &Limits::conservative_defaults()).expect("event");
+        assert_eq!(event.point.latitude(), 34.12);
+        assert_eq!(event.point.longitude(), -117.99);
+        let link = event.detail_element("link").expect("link");
+        assert_eq!(link.attribute("point"), Some("34.11,-117.22,12.5"));
+        assert_eq!(link.attribute("uid"), Some("wp-1"));
+        let vertex = event.detail_element("shape").expect("shape").children[0]
+            .child("vertex")
+            .expect("vertex");
+        assert_eq!(vertex.attribute("lat"), Some("34.33"));
+        assert_eq!(vertex.attribute("lon"), Some("-117.44"));
+        let target = event.detail_element("target").expect("target");
+        assert_eq!(target.attribute("latitude"), Some("34.56"));
+        assert_eq!(target.attribute("longitude"), Some("-117.67"));
+        assert_eq!(
+            event.detail_element("remarks").expect("remarks").text,
+            "hold"
+        );
+    }
+
+    #[test]
+    fn fan_out_leaves_unruled_destinations_untouched() {
+        let internal = Collect::default();
+        let partner = Collect::default();
+        let config = EgressConfig {
+            destinations: [("partner".to_owned(), partner_rules())].into(),
+        };
+        let fan_out = config
+            .fan_out([
+                (
+                    "internal".to_owned(),
+                    Box::new(internal.clone()) as Box<dyn MessageSink<CotMessage>>,
+                ),
+                ("partner".to_owned(), Box::new(partner.clone())),
+            ])
+            .expect("fan out");
+
+        let original = Bytes::from_static(EVENT.as_bytes());
+        let envelope = MessageEnvelope::new(original.clone()).with_raw_frame(original.clone());
+        block_on(fan_out.send_envelope(envelope)).expect("send");
+
+        assert_eq!(
+            internal.0.lock().expect("collect mutex")[..],
+            [EVENT.as_bytes()]
+        );
+        assert_eq!(original, EVENT.as_bytes());
+        let partner_sent = partner.0.lock().expect("collect mutex");
+        assert_eq!(partner_sent.len(), 1);
+        assert!(!partner_sent[0].windows(8).any(|w| w == b"callsign"));
+        assert_eq!(*partner.1.lock().expect("collect mutex"), [None]);
+        assert_eq!(*internal.1.lock().expect("collect mutex"), [Some(original)]);
+    }
+
+    #[test]
+    fn config_rejects_bad_rules_and_unrouted_destinations() {
+        let config = EgressConfig {
+            destinations: [(
+                "partner".to_owned(),
+                EgressTransform {
+                    rules: vec![EgressRule::GeneralizeType { max_atoms: 0 }],
+                },
+            )]
+            .into(),
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(EgressError::InvalidRule { .. })
+        ));
+
+        let config = EgressConfig {
+            destinations: [("partnr".to_owned(), partner_rules())].into(),
+        };
+        assert!(matches!(
+            config.fan_out([(
+                "partner".to_owned(),
+                Box::new(Collect::default()) as Box<dyn MessageSink<CotMessage>>,
+            )]),
+            Err(EgressError::MissingDestinationSink { destination }) if destination == "partnr"
+        ));
+    }
+}
@@ -1,8 +1,22 @@
 use std::time::Duration;
 
+use rustak_limits::{CodedError, ErrorCode};
 use rustak_wire::TakProtocolVersion;
 use thiserror::Error;
 
+pub mod beacon;
+pub mod contacts;
+pub mod egress;
+
+pub use beacon::{
+    GpsdPositionSource, GroupMembership, NmeaPositionSource, PositionSource, PositionSourceError,
+    SelfReporter, SelfReporterConfig, SelfReporterError, StaticPositionSource,
+};
+pub use contacts::{
+    ContactDirectoryError, ContactEntry, ContactTracker, CONTACT_DIRECTORY_VERSION,
+};
+pub use egress::{EgressConfig, EgressError, EgressRule, EgressTransform, MAX_COORDINATE_DECIMALS};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CommoConfig {
     pub takcontrol_interval: Duration,
@@ -56,6 +70,16 @@ pub enum CommoConfigError {
     ZeroMessageBudget,
 }
 
+impl CodedError for CommoConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroTakControlInterval => ErrorCode::new("COMMO", 1),
+            Self::StaleBeforeCadence { .. } => ErrorCode::new("COMMO", 2),
+            Self::ZeroMessageBudget => ErrorCode::new("COMMO", 3),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ContactCapabilities {
     pub uid: String,
@@ -97,6 +121,15 @@ pub enum ContactError {
     NoVersions,
 }
 
+impl CodedError for ContactError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyUid => ErrorCode::new("COMMO", 101),
+            Self::NoVersions => ErrorCode::new("COMMO", 102),
+        }
+    }
+}
+
 #[must_use]
 pub fn select_mesh_version(contacts: &[ContactCapabilities]) -> Option<TakProtocolVersion> {
     if contacts.is_empty() {
@@ -0,0 +1,237 @@
+use std::mem::size_of;
+use std::time::SystemTime;
+
+use rustak_limits::Limits;
+
+use crate::{ConfigError, RustakConfig};
+
+/// Per-message bookkeeping assumed for queued envelopes (metadata, deque
+/// slot, priority/coalesce key) on top of the payload bytes themselves.
+pub const QUEUE_SLOT_OVERHEAD_BYTES: usize = 128;
+
+/// Upper bound assumed for a dedup key; bridge keys are short uid strings.
+pub const DEDUP_KEY_ESTIMATE_BYTES: usize = 128;
+
+/// The key and timestamp are held twice: once in the map, once in the
+/// eviction order.
+const DEDUP_ENTRY_BYTES: usize = 2 * (size_of::<(String, SystemTime)>() + DEDUP_KEY_ESTIMATE_BYTES);
+
+/// Correlator keys are `node=...;object=...` strings and UIDs a prefix plus
+/// 32 hex digits.
+const CORRELATOR_KEY_ESTIMATE_BYTES: usize = 128;
+const CORRELATOR_UID_ESTIMATE_BYTES: usize = 64;
+
+/// The key is held three times (uid map, reverse map, recency order) and the
+/// UID twice.
+const CORRELATOR_ENTRY_BYTES: usize = 5 * size_of::<String>()
+    + 2 * size_of::<(SystemTime, u64)>()
+    + 3 * CORRELATOR_KEY_ESTIMATE_BYTES
+    + 2 * CORRELATOR_UID_ESTIMATE_BYTES;
+
+/// Takrec chunk framing as recorders write it: magic, codec, capture time,
+/// sequence, length, crc32, stored length, commit marker.
+const TAKREC_CHUNK_FRAMING_BYTES: usize = 4 + 1 + 8 + 8 + 4 + 4 + 4 + 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BudgetEntry {
+    pub component: &'static str,
+    /// `None` when the component has no configured bound.
+    pub worst_case_bytes: Option<usize>,
+    pub basis: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MemoryBudget {
+    pub entries: Vec<BudgetEntry>,
+}
+
+impl MemoryBudget {
+    /// Sum of every bounded entry.
+    #[must_use]
+    pub fn bounded_total_bytes(&self) -> usize {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.worst_case_bytes)
+            .fold(0, usize::saturating_add)
+    }
+
+    #[must_use]
+    pub fn unbounded_components(&self) -> Vec<&'static str> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.worst_case_bytes.is_none())
+            .map(|entry| entry.component)
+            .collect()
+    }
+
+    /// One `component=... worst_case_bytes=... basis=...` line per entry.
+    #[must_use]
+    pub fn report_lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                let bytes = entry
+                    .worst_case_bytes
+                    .map_or_else(|| "unbounded".to_owned(), |bytes| bytes.to_string());
+                format!(
+                    "memory_budget component={} worst_case_bytes={bytes} basis={}",
+                    entry.component, entry.basis
+                )
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn memory_budget(config: &RustakConfig) -> Result<MemoryBudget, ConfigError> {
+    let mut budget = MemoryBudget::default();
+    let transport = &config.transport;
+
+    budget.entries.push(decode_buffer_entry(
+        "transport.decode_buffer",
+        &transport.limits,
+    ));
+
+    let queue = &transport.send_queue;
+    let queued_payload = queue.max_bytes.min(
+        queue
+            .max_messages
+            .saturating_mul(transport.limits.max_frame_bytes),
+    );
+    budget.entries.push(BudgetEntry {
+        component: "transport.send_queue",
+        worst_case_bytes: Some(
+            queued_payload
+                .saturating_add(queue.max_messages.saturating_mul(QUEUE_SLOT_OVERHEAD_BYTES)),
+        ),
+        basis: format!(
+            "min(max_bytes={}, max_messages={} * max_frame_bytes={}) + max_messages * {QUEUE_SLOT_OVERHEAD_BYTES}",
+            queue.max_bytes, queue.max_messages, transport.limits.max_frame_bytes
+        ),
+    });
+
+    if let Some(sapient) = config.resolve_sapient()? {
+        budget.entries.push(decode_buffer_entry(
+            "sapient.decode_buffer",
+            &sapient.limits,
+        ));
+    }
+
+    if let Some(bridge) = &config.bridge {
+        budget.entries.push(BudgetEntry {
+            component: "bridge.dedup",
+            worst_case_bytes: Some(bridge.dedup.max_keys.saturating_mul(DEDUP_ENTRY_BYTES)),
+            basis: format!(
+                "max_keys={} * {DEDUP_ENTRY_BYTES} (keys <= {DEDUP_KEY_ESTIMATE_BYTES} bytes)",
+                bridge.dedup.max_keys
+            ),
+        });
+        budget.entries.push(BudgetEntry {
+            component: "bridge.emitter",
+            worst_case_bytes: Some(
+                bridge
+                    .emitter
+                    .max_pending_events
+                    .saturating_mul(bridge.limits.max_frame_bytes),
+            ),
+            basis: format!(
+                "max_pending_events={} * max_frame_bytes={}",
+                bridge.emitter.max_pending_events, bridge.limits.max_frame_bytes
+            ),
+        });
+        budget.entries.push(BudgetEntry {
+            component: "bridge.correlator",
+            worst_case_bytes: Some(
+                bridge
+                    .correlator
+                    .max_entries
+                    .saturating_mul(CORRELATOR_ENTRY_BYTES),
+            ),
+            basis: format!(
+                "max_entries={} * {CORRELATOR_ENTRY_BYTES} (keys <= {CORRELATOR_KEY_ESTIMATE_BYTES} bytes)",
+                bridge.correlator.max_entries
+            ),
+        });
+    }
+
+    budget.entries.push(BudgetEntry {
+        component: "record.chunk_buffer",
+        worst_case_bytes: Some(
+            transport
+                .limits
+                .max_frame_bytes
+                .saturating_add(TAKREC_CHUNK_FRAMING_BYTES),
+        ),
+        basis: format!(
+            "max_frame_bytes={} + {TAKREC_CHUNK_FRAMING_BYTES} framing (when recording)",
+            transport.limits.max_frame_bytes
+        ),
+    });
+
+    Ok(budget)
+}
+
+fn decode_buffer_entry(component: &'static str, limits: &Limits) -> BudgetEntry {
+    let scan = limits.max_xml_scan_bytes.max(limits.max_protobuf_bytes);
+    BudgetEntry {
+        component,
+        worst_case_bytes: Some(limits.max_frame_bytes.saturating_add(scan)),
+        basis: format!(
+            "max_frame_bytes={} + max(max_xml_scan_bytes={}, max_protobuf_bytes={})",
+            limits.max_frame_bytes, limits.max_xml_scan_bytes, limits.max_protobuf_bytes
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_bridge::BridgeConfig;
+
+    use super::{CORRELATOR_ENTRY_BYTES, QUEUE_SLOT_OVERHEAD_BYTES};
+    use crate::RustakConfig;
+
+    #[test]
+    fn default_budget_is_fully_bounded() {
+        let config = RustakConfig::default();
+        let budget = config.memory_budget().expect("budget");
+
+        assert!(budget.unbounded_components().is_empty());
+        let queue = budget
+            .entries
+            .iter()
+            .find(|entry| entry.component == "transport.send_queue")
+            .expect("send queue entry");
+        let send_queue = &config.transport.send_queue;
+        let expected_payload = send_queue
+            .max_bytes
+            .min(send_queue.max_messages * config.transport.limits.max_frame_bytes);
+        assert_eq!(
+            queue.worst_case_bytes,
+            Some(expected_payload + send_queue.max_messages * QUEUE_SLOT_OVERHEAD_BYTES)
+        );
+        assert!(budget.bounded_total_bytes() > expected_payload);
+    }
+
+    #[test]
+    fn bridge_budget_bounds_correlator_by_max_entries() {
+        let config = RustakConfig {
+            bridge: Some(BridgeConfig::default()),
+            ..RustakConfig::default()
+        };
+        let budget = config.memory_budget().expect("budget");
+
+        assert!(budget.unbounded_components().is_empty());
+        let correlator = budget
+            .entries
+            .iter()
+            .find(|entry| entry.component == "bridge.correlator")
+            .expect("correlator entry");
+        assert_eq!(
+            correlator.worst_case_bytes,
+            Some(BridgeConfig::default().correlator.max_entries * CORRELATOR_ENTRY_BYTES)
+        );
+        assert!(budget
+            .report_lines()
+            .iter()
+            .any(|line| line.starts_with("memory_budget component=bridge.dedup ")));
+    }
+}
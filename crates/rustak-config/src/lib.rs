@@ -1,15 +1,33 @@
-use std::{io::Read, path::Path, time::Duration};
+use std::{collections::BTreeMap, io::Read, path::Path, time::Duration};
 
+use rustak_admin::{WebhookConfig, WebhookConfigError};
 use rustak_bridge::{BridgeConfig, BridgeConfigError};
-use rustak_limits::{Limits, LimitsError};
+use rustak_commo::{EgressConfig, EgressError};
+use rustak_limits::{CodedError, ErrorCode, Limits, LimitsError};
 use rustak_sapient::{SapientConfig, SapientConfigError};
 use rustak_transport::{TransportConfig, TransportConfigError};
 use thiserror::Error;
 
+mod budget;
+mod overlay;
 mod redact;
+mod reference;
+mod reload;
 mod schema;
 mod validate;
 
+pub use budget::{BudgetEntry, MemoryBudget, DEDUP_KEY_ESTIMATE_BYTES, QUEUE_SLOT_OVERHEAD_BYTES};
+pub use overlay::{
+    ConfigFormat, ConfigLoader, ConfigProvenance, ConfigSource, MergeMode, ENV_PREFIX, INCLUDE_KEY,
+    MAX_INCLUDE_DEPTH,
+};
+pub use redact::ConfigFieldChange;
+pub use reference::{
+    config_reference, explain_config_field, render_config_reference, ConfigFieldReference,
+};
+pub use reload::{
+    ConfigReloader, ReloadChange, ReloadImpact, ReloadOutcome, ReloadPlan, HOT_RELOAD_PATHS,
+};
 pub use schema::json_schema;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -17,9 +35,11 @@ pub struct RustakConfig {
     pub transport: TransportConfig,
     pub sapient: Option<SapientConfigSpec>,
     pub bridge: Option<BridgeConfig>,
+    pub egress: Option<EgressConfig>,
     pub crypto: Option<CryptoConfig>,
     pub certificates: Option<CertificatesConfig>,
     pub logging: Option<LoggingConfig>,
+    pub webhooks: Option<WebhookConfig>,
 }
 
 impl Default for RustakConfig {
@@ -28,31 +48,96 @@ impl Default for RustakConfig {
             transport: TransportConfig::default(),
             sapient: None,
             bridge: None,
+            egress: None,
             crypto: None,
             certificates: None,
             logging: Some(LoggingConfig::default()),
+            webhooks: None,
         }
     }
 }
 
 impl RustakConfig {
+    /// Loads `path`, merging any files it names under `include:` beneath it
+    /// with [`MergeMode::Override`]. Each file's [`ConfigFormat`] comes from
+    /// its extension, or failing that its contents.
     pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
-        let path_ref = path.as_ref();
-        let file = std::fs::File::open(path_ref).map_err(|source| ConfigError::ReadConfig {
-            path: path_ref.display().to_string(),
-            source,
-        })?;
-        Self::from_reader(file)
+        Self::load_layered([path], MergeMode::Override)
     }
 
-    pub fn from_reader(reader: impl Read) -> Result<Self, ConfigError> {
+    /// Like [`Self::load`], reading `path` itself as `format` whatever its
+    /// extension.
+    pub fn load_with_format(
+        path: impl AsRef<Path>,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigError> {
+        Self::from_yaml_value(overlay::load_file(
+            path.as_ref(),
+            MergeMode::Override,
+            Some(format),
+        )?)
+    }
+
+    /// Loads a base file followed by overlays; each file's includes are
+    /// resolved first and later files take precedence. The merged result is
+    /// parsed and validated as a single document.
+    pub fn load_layered<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+        mode: MergeMode,
+    ) -> Result<Self, ConfigError> {
+        let mut merged = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        for path in paths {
+            let layer = overlay::load_file(path.as_ref(), mode, None)?;
+            overlay::merge(&mut merged, layer, mode, "")?;
+        }
+        Self::from_yaml_value(merged)
+    }
+
+    /// Merges in-memory YAML layers, later layers taking precedence.
+    /// `include:` is not resolved here since there is no file to be relative
+    /// to.
+    pub fn from_yaml_layers<'a>(
+        layers: impl IntoIterator<Item = &'a str>,
+        mode: MergeMode,
+    ) -> Result<Self, ConfigError> {
+        let mut merged = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+        for layer in layers {
+            overlay::merge(
+                &mut merged,
+                overlay::parse_layer(layer, ConfigFormat::Yaml)?,
+                mode,
+                "",
+            )?;
+        }
+        Self::from_yaml_value(merged)
+    }
+
+    fn from_yaml_value(value: serde_yaml::Value) -> Result<Self, ConfigError> {
         let document: schema::RustakConfigDocument =
-            serde_yaml::from_reader(reader).map_err(ConfigError::DeserializeConfig)?;
+            serde_yaml::from_value(value).map_err(ConfigError::DeserializeConfig)?;
         let config = Self::try_from(document)?;
         config.validate()?;
         Ok(config)
     }
 
+    /// Reads one document, detecting its [`ConfigFormat`] from the contents.
+    pub fn from_reader(reader: impl Read) -> Result<Self, ConfigError> {
+        let text = read_document(reader)?;
+        Self::from_str_with_format(&text, ConfigFormat::detect(&text))
+    }
+
+    pub fn from_reader_with_format(
+        reader: impl Read,
+        format: ConfigFormat,
+    ) -> Result<Self, ConfigError> {
+        Self::from_str_with_format(&read_document(reader)?, format)
+    }
+
+    /// Parses one `format` document; `include:` is not resolved.
+    pub fn from_str_with_format(text: &str, format: ConfigFormat) -> Result<Self, ConfigError> {
+        Self::from_yaml_value(overlay::parse_layer(text, format)?)
+    }
+
     pub fn from_yaml_str(yaml: &str) -> Result<Self, ConfigError> {
         let document: schema::RustakConfigDocument =
             serde_yaml::from_str(yaml).map_err(ConfigError::DeserializeConfig)?;
@@ -69,6 +154,12 @@ impl RustakConfig {
         if let Some(bridge) = &self.bridge {
             bridge.validate()?;
         }
+        if let Some(egress) = &self.egress {
+            egress.validate()?;
+        }
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.validate()?;
+        }
 
         if let Some(crypto) = &self.crypto {
             if let Some(pin) = &crypto.server_spki_pin {
@@ -78,6 +169,9 @@ impl RustakConfig {
                     });
                 }
             }
+            if let Some(signing) = &crypto.signing {
+                signing.validate()?;
+            }
         }
 
         if let Some(certificates) = &self.certificates {
@@ -126,12 +220,35 @@ impl RustakConfig {
         redact::to_redacted_yaml(self)
     }
 
+    /// Lists every field that differs from `RustakConfig::default()`, with
+    /// the same redaction applied as `to_redacted_yaml`.
+    pub fn diff_from_defaults(&self) -> Result<Vec<ConfigFieldChange>, ConfigError> {
+        redact::diff_from_defaults(self)
+    }
+
+    /// Worst-case resident memory per subsystem derived from configured
+    /// limits, for sizing deployments on constrained hardware.
+    pub fn memory_budget(&self) -> Result<MemoryBudget, ConfigError> {
+        budget::memory_budget(self)
+    }
+
     #[must_use]
     pub fn json_schema() -> serde_json::Value {
         schema::json_schema()
     }
 }
 
+fn read_document(mut reader: impl Read) -> Result<String, ConfigError> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|source| ConfigError::ReadConfig {
+            path: "<reader>".to_owned(),
+            source,
+        })?;
+    Ok(text)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SapientConfigSpec {
     pub version: String,
@@ -139,6 +256,7 @@ pub struct SapientConfigSpec {
     pub read_timeout: Duration,
     pub write_timeout: Duration,
     pub tcp_nodelay: bool,
+    pub version_pins: BTreeMap<String, String>,
 }
 
 impl SapientConfigSpec {
@@ -153,6 +271,7 @@ impl SapientConfigSpec {
             read_timeout: self.read_timeout,
             write_timeout: self.write_timeout,
             tcp_nodelay: self.tcp_nodelay,
+            version_pins: self.version_pins.clone(),
         };
         config.validate()?;
         Ok(config)
@@ -199,6 +318,89 @@ pub struct CryptoConfig {
     pub provider: CryptoProvider,
     pub revocation: RevocationPolicy,
     pub server_spki_pin: Option<String>,
+    pub signing: Option<SigningConfig>,
+}
+
+/// Application-layer CoT signing: our own key (if we sign outbound events)
+/// and the peer keys trusted when verifying inbound ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningConfig {
+    pub verification: SignatureVerification,
+    pub key_id: Option<String>,
+    /// Base64-encoded 32-byte Ed25519 seed.
+    pub private_key: Option<String>,
+    pub trusted_keys: Vec<TrustedKey>,
+}
+
+impl SigningConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        match (&self.key_id, &self.private_key) {
+            (Some(key_id), Some(private_key)) => {
+                if key_id.trim().is_empty() {
+                    return Err(ConfigError::EmptyField {
+                        field: "crypto.signing.key_id",
+                    });
+                }
+                if private_key.trim().is_empty() {
+                    return Err(ConfigError::EmptySensitiveField {
+                        field: "crypto.signing.private_key",
+                    });
+                }
+            }
+            (Some(_), None) => {
+                return Err(ConfigError::MissingField {
+                    field: "crypto.signing.private_key",
+                })
+            }
+            (None, Some(_)) => {
+                return Err(ConfigError::MissingField {
+                    field: "crypto.signing.key_id",
+                })
+            }
+            (None, None) => {}
+        }
+
+        let mut seen = std::collections::BTreeSet::new();
+        for trusted in &self.trusted_keys {
+            if trusted.key_id.trim().is_empty() {
+                return Err(ConfigError::EmptyField {
+                    field: "crypto.signing.trusted_keys[].key_id",
+                });
+            }
+            if trusted.public_key.trim().is_empty() {
+                return Err(ConfigError::EmptyField {
+                    field: "crypto.signing.trusted_keys[].public_key",
+                });
+            }
+            if !seen.insert(trusted.key_id.as_str()) {
+                return Err(ConfigError::DuplicateSigningKeyId {
+                    key_id: trusted.key_id.clone(),
+                });
+            }
+        }
+
+        if self.verification == SignatureVerification::Require && self.trusted_keys.is_empty() {
+            return Err(ConfigError::MissingField {
+                field: "crypto.signing.trusted_keys",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureVerification {
+    Ignore,
+    Warn,
+    Require,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKey {
+    pub key_id: String,
+    /// Base64-encoded Ed25519 public key.
+    pub public_key: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -238,6 +440,7 @@ impl Default for LoggingConfig {
                 "certificates.client_key".to_owned(),
                 "certificates.client_cert".to_owned(),
                 "crypto.server_spki_pin".to_owned(),
+                "crypto.signing.private_key".to_owned(),
             ],
         }
     }
@@ -304,6 +507,12 @@ pub enum ConfigError {
     #[error(transparent)]
     InvalidBridge(#[from] BridgeConfigError),
 
+    #[error(transparent)]
+    InvalidEgress(#[from] EgressError),
+
+    #[error(transparent)]
+    InvalidWebhooks(#[from] WebhookConfigError),
+
     #[error(transparent)]
     InvalidLimits(#[from] LimitsError),
 
@@ -323,6 +532,27 @@ pub enum ConfigError {
     #[error("failed to parse config yaml: {0}")]
     DeserializeConfig(#[source] serde_yaml::Error),
 
+    #[error("failed to parse config json: {0}")]
+    DeserializeJson(#[source] serde_json::Error),
+
+    #[error("failed to parse config toml: {0}")]
+    DeserializeToml(#[source] toml::de::Error),
+
+    #[error("config layer must be a mapping")]
+    NonMappingLayer,
+
+    #[error("config field {path} is set by more than one layer")]
+    MergeConflict { path: String },
+
+    #[error("config file {path} includes itself")]
+    IncludeCycle { path: String },
+
+    #[error("config file {path} exceeds the include depth of {max_depth}")]
+    IncludeTooDeep { path: String, max_depth: usize },
+
+    #[error("config file {path} has an invalid include; expected a path or list of paths")]
+    InvalidInclude { path: String },
+
     #[error("failed to render config yaml: {0}")]
     SerializeConfig(#[source] serde_yaml::Error),
 
@@ -344,6 +574,9 @@ pub enum ConfigError {
     #[error("missing required config field: {field}")]
     MissingField { field: &'static str },
 
+    #[error("duplicate signing key id in crypto.signing.trusted_keys: {key_id}")]
+    DuplicateSigningKeyId { key_id: String },
+
     #[error("conflicting config fields: {left} and {right}")]
     ConflictingFields {
         left: &'static str,
@@ -365,20 +598,71 @@ pub enum ConfigError {
         bridge_pending_events: usize,
         transport_max_queue_messages: usize,
     },
+
+    #[error("config override path '{path}' has an empty segment")]
+    InvalidOverridePath { path: String },
+
+    #[error("{layer}: {error}")]
+    InLayer {
+        layer: ConfigSource,
+        #[source]
+        error: Box<ConfigError>,
+    },
+}
+
+impl CodedError for ConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidTransport(error) => error.code(),
+            Self::InvalidSapient(error) => error.code(),
+            Self::InvalidBridge(error) => error.code(),
+            Self::InvalidEgress(error) => error.code(),
+            Self::InvalidWebhooks(error) => error.code(),
+            Self::InvalidLimits(error) => error.code(),
+            Self::EmptyLimitsReferencePath => ErrorCode::new("CONFIG", 1),
+            Self::UnknownLimitsReference { .. } => ErrorCode::new("CONFIG", 2),
+            Self::ReadConfig { .. } => ErrorCode::new("CONFIG", 3),
+            Self::DeserializeConfig(_) => ErrorCode::new("CONFIG", 4),
+            Self::NonMappingLayer => ErrorCode::new("CONFIG", 5),
+            Self::MergeConflict { .. } => ErrorCode::new("CONFIG", 6),
+            Self::IncludeCycle { .. } => ErrorCode::new("CONFIG", 7),
+            Self::IncludeTooDeep { .. } => ErrorCode::new("CONFIG", 8),
+            Self::InvalidInclude { .. } => ErrorCode::new("CONFIG", 9),
+            Self::SerializeConfig(_) => ErrorCode::new("CONFIG", 10),
+            Self::EmptyField { .. } => ErrorCode::new("CONFIG", 11),
+            Self::EmptySensitiveField { .. } => ErrorCode::new("CONFIG", 12),
+            Self::InvalidAddress { .. } => ErrorCode::new("CONFIG", 13),
+            Self::InvalidIpv4Address { .. } => ErrorCode::new("CONFIG", 14),
+            Self::InvalidDuration { .. } => ErrorCode::new("CONFIG", 15),
+            Self::MissingField { .. } => ErrorCode::new("CONFIG", 16),
+            Self::DuplicateSigningKeyId { .. } => ErrorCode::new("CONFIG", 17),
+            Self::ConflictingFields { .. } => ErrorCode::new("CONFIG", 18),
+            Self::StrictStartupBridgeFrameLimitExceedsTransport { .. } => {
+                ErrorCode::new("CONFIG", 19)
+            }
+            Self::StrictStartupBridgePendingEventsExceedTransport { .. } => {
+                ErrorCode::new("CONFIG", 20)
+            }
+            Self::InvalidOverridePath { .. } => ErrorCode::new("CONFIG", 21),
+            Self::DeserializeJson(_) => ErrorCode::new("CONFIG", 22),
+            Self::DeserializeToml(_) => ErrorCode::new("CONFIG", 23),
+            Self::InLayer { error, .. } => error.code(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, time::Duration};
+    use std::{collections::BTreeMap, path::PathBuf, time::Duration};
 
     use rustak_bridge::BridgeConfig;
     use rustak_limits::Limits;
     use rustak_transport::TransportConfig;
 
     use crate::{
-        ConfigError, CryptoConfig, CryptoProvider, LegacyTransportSizeKnobs, LimitsBinding,
-        LimitsRef, LogFormat, LogLevel, LoggingConfig, RevocationPolicy, RustakConfig,
-        SapientConfigSpec,
+        ConfigError, ConfigFormat, CryptoConfig, CryptoProvider, LegacyTransportSizeKnobs,
+        LimitsBinding, LimitsRef, LogFormat, LogLevel, LoggingConfig, MergeMode, RevocationPolicy,
+        RustakConfig, SapientConfigSpec, SignatureVerification, SigningConfig, TrustedKey,
     };
 
     #[test]
@@ -393,6 +677,7 @@ mod tests {
                 read_timeout: Duration::from_secs(15),
                 write_timeout: Duration::from_secs(15),
                 tcp_nodelay: true,
+                version_pins: BTreeMap::new(),
             }),
             transport: transport.clone(),
             ..RustakConfig::default()
@@ -416,6 +701,7 @@ mod tests {
                 read_timeout: Duration::from_secs(15),
                 write_timeout: Duration::from_secs(15),
                 tcp_nodelay: true,
+                version_pins: BTreeMap::new(),
             }),
             transport: TransportConfig::default(),
             ..RustakConfig::default()
@@ -486,11 +772,232 @@ sapient:
   read_timeout: 15s
   write_timeout: 15s
   tcp_nodelay: true
+  version_pins:
+    radar-1: BSI Flex 335 v2.0
 "#;
 
         let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
         assert_eq!(config.transport.limits.max_frame_bytes, 1_048_576);
-        assert!(config.resolve_sapient().is_ok());
+        let sapient = config
+            .resolve_sapient()
+            .expect("sapient config resolves")
+            .expect("sapient config should exist");
+        assert_eq!(
+            sapient.registry().pinned_version("radar-1"),
+            Some("BSI Flex 335 v2.0")
+        );
+    }
+
+    #[test]
+    fn parses_mtu_oversize_policy() {
+        let yaml = r#"
+transport:
+  protocol:
+    type: udp_unicast
+    bind_addr: 0.0.0.0:6969
+    target_addr: 127.0.0.1:6969
+  mtu_safety:
+    max_udp_payload_bytes: 1200
+    oversize: truncate_detail
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        assert_eq!(
+            config.transport.mtu_safety.map(|mtu| mtu.oversize),
+            Some(rustak_transport::OversizePolicy::TruncateDetail)
+        );
+    }
+
+    #[test]
+    fn parses_send_queue_traffic_shaping() {
+        let yaml = r#"
+transport:
+  protocol:
+    type: tcp
+    addr: 127.0.0.1:8089
+  send_queue:
+    mode: priority
+    max_messages: 256
+    max_bytes: 1048576
+    shaping:
+      pli:
+        bytes_per_second: 2048
+        burst_bytes: 4096
+      large_detail:
+        bytes_per_second: 512
+        burst_bytes: 65536
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let shaping = config.transport.send_queue.shaping.expect("shaping");
+        assert_eq!(shaping.budgets.len(), 2);
+        assert_eq!(
+            shaping.budgets[&rustak_transport::MessageClass::LargeDetail],
+            rustak_transport::ClassBudget {
+                bytes_per_second: 512,
+                burst_bytes: 65_536,
+            }
+        );
+
+        let zero_rate = yaml.replace("bytes_per_second: 512", "bytes_per_second: 0");
+        assert!(RustakConfig::from_yaml_str(&zero_rate).is_err());
+    }
+
+    #[test]
+    fn parses_transport_byte_quotas() {
+        let yaml = r#"
+transport:
+  protocol:
+    type: tcp
+    addr: 127.0.0.1:8089
+  quota:
+    day:
+      send_bytes: 5000000
+      receive_bytes: 20000000
+    action:
+      type: throttle
+      bytes_per_second: 256
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let quota = config.transport.quota.expect("quota");
+        assert_eq!(
+            quota.action,
+            rustak_transport::QuotaAction::Throttle {
+                bytes_per_second: 256
+            }
+        );
+        assert_eq!(
+            quota.limits[&rustak_transport::QuotaWindow::Day].send_bytes,
+            Some(5_000_000)
+        );
+        assert!(!quota
+            .limits
+            .contains_key(&rustak_transport::QuotaWindow::Hour));
+
+        let empty_window = yaml.replace(
+            "      send_bytes: 5000000\n      receive_bytes: 20000000\n",
+            "      {}\n",
+        );
+        assert!(RustakConfig::from_yaml_str(&empty_window).is_err());
+    }
+
+    #[test]
+    fn parses_bridge_sensor_coverage_with_style_defaults() {
+        let yaml = r#"
+bridge:
+  sensor_coverage:
+    emit_field_of_view: true
+    arc_segments: 24
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let coverage = config.bridge.expect("bridge").sensor_coverage;
+        assert!(coverage.emit_field_of_view);
+        assert!(!coverage.emit_sensor_location);
+        assert_eq!(coverage.arc_segments, 24);
+        assert_eq!(coverage.style, rustak_bridge::CoverageStyle::default());
+    }
+
+    #[test]
+    fn parses_bridge_normalization_per_sensor() {
+        let yaml = r#"
+bridge:
+  normalization:
+    required_sensors: [radar-1]
+    sensors:
+      radar-1:
+        range_unit: nautical_miles
+        bearing_unit: mils
+        bearing_reference:
+          type: magnetic
+          declination_degrees: -2.5
+        datum_offset:
+          altitude_meters: -47.0
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let normalization = config.bridge.expect("bridge").normalization;
+        let radar = normalization.sensors["radar-1"];
+        assert_eq!(radar.range_unit, rustak_bridge::RangeUnit::NauticalMiles);
+        assert_eq!(radar.bearing_unit, rustak_bridge::AngleUnit::Mils);
+        assert_eq!(
+            radar.bearing_reference,
+            rustak_bridge::BearingReference::Magnetic {
+                declination_degrees: -2.5
+            }
+        );
+        assert_eq!(radar.datum_offset.altitude_meters, -47.0);
+        assert!(normalization.fallback.is_some());
+
+        let missing = "bridge:\n  normalization:\n    required_sensors: [eo-2]\n";
+        assert!(RustakConfig::from_yaml_str(missing).is_err());
+    }
+
+    #[test]
+    fn parses_webhooks_and_redacts_the_auth_header() {
+        let yaml = r#"
+webhooks:
+  urls:
+    - https://alerts.example:8443/hooks/rustak
+  auth_header: "Authorization: Bearer s3cret"
+  timeout: 2s
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let webhooks = config.webhooks.as_ref().expect("webhooks");
+        assert_eq!(webhooks.urls, ["https://alerts.example:8443/hooks/rustak"]);
+        assert_eq!(webhooks.timeout, Duration::from_secs(2));
+        let rendered = config.to_redacted_yaml().expect("render");
+        assert!(!rendered.contains("s3cret"), "{rendered}");
+
+        let defaults = RustakConfig::from_yaml_str("webhooks:\n  urls: []\n").expect("defaults");
+        assert_eq!(
+            defaults.webhooks,
+            Some(rustak_admin::WebhookConfig::default())
+        );
+
+        let invalid = "webhooks:\n  urls: [ftp://alerts.example/hook]\n";
+        assert!(matches!(
+            RustakConfig::from_yaml_str(invalid),
+            Err(ConfigError::InvalidWebhooks(_))
+        ));
+    }
+
+    #[test]
+    fn parses_egress_rules_per_destination() {
+        let yaml = r#"
+egress:
+  destinations:
+    coalition:
+      rules:
+        - type: strip_detail
+        - type: round_coordinates
+          decimals: 3
+        - type: remap_type
+          from_prefix: a-f-G-U-C
+          to: a-f-G
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let egress = config.egress.expect("egress");
+        assert_eq!(
+            egress.destinations["coalition"].rules,
+            [
+                rustak_commo::EgressRule::StripDetail,
+                rustak_commo::EgressRule::RoundCoordinates { decimals: 3 },
+                rustak_commo::EgressRule::RemapType {
+                    from_prefix: "a-f-G-U-C".to_owned(),
+                    to: "a-f-G".to_owned(),
+                },
+            ]
+        );
+
+        let invalid = "egress:\n  destinations:\n    p:\n      rules:\n        - type: generalize_type\n          max_atoms: 0\n";
+        assert!(matches!(
+            RustakConfig::from_yaml_str(invalid),
+            Err(ConfigError::InvalidEgress(_))
+        ));
     }
 
     #[test]
@@ -500,6 +1007,7 @@ sapient:
                 provider: CryptoProvider::Ring,
                 revocation: RevocationPolicy::Prefer,
                 server_spki_pin: Some("super-secret-pin".to_owned()),
+                signing: None,
             }),
             certificates: Some(crate::CertificatesConfig {
                 ca_cert: "/etc/rustak/ca.pem".to_owned(),
@@ -522,6 +1030,126 @@ sapient:
         assert!(!rendered.contains("/etc/rustak/client-key.pem"));
     }
 
+    #[test]
+    fn parses_signing_keys_and_redacts_private_key() {
+        let yaml = r#"
+crypto:
+  provider: ring
+  revocation: prefer
+  signing:
+    verification: require
+    key_id: gw-1
+    private_key: c2VjcmV0LXNlZWQ=
+    trusted_keys:
+      - key_id: gw-2
+        public_key: cHVibGljLWtleQ==
+"#;
+
+        let config = RustakConfig::from_yaml_str(yaml).expect("yaml should parse");
+        let signing = config
+            .crypto
+            .as_ref()
+            .and_then(|crypto| crypto.signing.as_ref())
+            .expect("signing block should be present");
+        assert_eq!(signing.verification, SignatureVerification::Require);
+        assert_eq!(signing.trusted_keys[0].key_id, "gw-2");
+
+        let rendered = config
+            .to_redacted_yaml()
+            .expect("redacted render should work");
+        assert!(!rendered.contains("c2VjcmV0LXNlZWQ="));
+        assert!(rendered.contains("cHVibGljLWtleQ=="));
+    }
+
+    #[test]
+    fn signing_validation_rejects_incomplete_key_setup() {
+        let mut signing = SigningConfig {
+            verification: SignatureVerification::Require,
+            key_id: None,
+            private_key: None,
+            trusted_keys: Vec::new(),
+        };
+        assert!(matches!(
+            signing.validate(),
+            Err(ConfigError::MissingField {
+                field: "crypto.signing.trusted_keys"
+            })
+        ));
+
+        signing.verification = SignatureVerification::Warn;
+        signing.key_id = Some("gw-1".to_owned());
+        assert!(matches!(
+            signing.validate(),
+            Err(ConfigError::MissingField {
+                field: "crypto.signing.private_key"
+            })
+        ));
+
+        signing.key_id = None;
+        signing.trusted_keys = vec![
+            TrustedKey {
+                key_id: "gw-2".to_owned(),
+                public_key: "a2V5".to_owned(),
+            },
+            TrustedKey {
+                key_id: "gw-2".to_owned(),
+                public_key: "b3RoZXI=".to_owned(),
+            },
+        ];
+        assert!(matches!(
+            signing.validate(),
+            Err(ConfigError::DuplicateSigningKeyId { .. })
+        ));
+    }
+
+    #[test]
+    fn default_config_has_empty_diff() {
+        let diff = RustakConfig::default()
+            .diff_from_defaults()
+            .expect("diff should render");
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn diff_reports_only_changed_fields_with_redaction() {
+        let mut config = RustakConfig {
+            crypto: Some(CryptoConfig {
+                provider: CryptoProvider::Ring,
+                revocation: RevocationPolicy::Prefer,
+                server_spki_pin: Some("super-secret-pin".to_owned()),
+                signing: None,
+            }),
+            ..RustakConfig::default()
+        };
+        config.transport.limits.max_queue_messages = 256;
+
+        let diff = config.diff_from_defaults().expect("diff should render");
+        let queue = diff
+            .iter()
+            .find(|change| change.path == "transport.limits.max_queue_messages")
+            .expect("changed limit should be listed");
+        assert_eq!(queue.default.as_deref(), Some("1024"));
+        assert_eq!(queue.effective.as_deref(), Some("256"));
+        assert_eq!(
+            queue.to_string(),
+            "transport.limits.max_queue_messages: 1024 -> 256"
+        );
+
+        let pin = diff
+            .iter()
+            .find(|change| change.path == "crypto.server_spki_pin")
+            .expect("new crypto section should be listed");
+        assert_eq!(pin.default, None);
+        assert_eq!(pin.effective.as_deref(), Some("[REDACTED]"));
+
+        assert!(!diff
+            .iter()
+            .any(|change| change.path == "transport.limits.max_frame_bytes"));
+        assert!(!diff
+            .iter()
+            .any(|change| change.to_string().contains("super-secret-pin")));
+    }
+
     #[test]
     fn schema_contains_top_level_transport() {
         let schema = RustakConfig::json_schema();
@@ -581,6 +1209,128 @@ transport:
         ));
     }
 
+    #[test]
+    fn layered_yaml_overrides_scalars_and_keeps_unset_fields() {
+        let base = r#"
+transport:
+  protocol:
+    type: tcp
+    addr: 127.0.0.1:8089
+  read_timeout: 15s
+  write_timeout: 15s
+"#;
+        let site = r#"
+transport:
+  protocol:
+    addr: 10.0.0.5:8089
+  read_timeout: 30s
+"#;
+
+        let config = RustakConfig::from_yaml_layers([base, site], MergeMode::Override)
+            .expect("layers should merge");
+        assert!(matches!(
+            config.transport.protocol,
+            rustak_transport::Protocol::Tcp { addr } if addr.to_string() == "10.0.0.5:8089"
+        ));
+        assert_eq!(config.transport.read_timeout, Duration::from_secs(30));
+        assert_eq!(config.transport.write_timeout, Duration::from_secs(15));
+
+        let error = RustakConfig::from_yaml_layers([base, site], MergeMode::Strict)
+            .expect_err("strict mode should reject redefined scalars");
+        assert!(matches!(
+            error,
+            ConfigError::MergeConflict { path } if path == "transport.protocol.addr"
+        ));
+    }
+
+    #[test]
+    fn load_resolves_includes_relative_to_the_including_file() {
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("target/test-rustak-config-include");
+        std::fs::create_dir_all(dir.join("sites")).expect("must create fixture dir");
+        std::fs::write(
+            dir.join("base.yaml"),
+            "transport:\n  protocol:\n    type: tcp\n    addr: 127.0.0.1:8089\n  read_timeout: 15s\n",
+        )
+        .expect("must write base");
+        std::fs::write(
+            dir.join("sites/alpha.yaml"),
+            "include: ../base.yaml\ntransport:\n  read_timeout: 45s\n",
+        )
+        .expect("must write overlay");
+
+        let config = RustakConfig::load(dir.join("sites/alpha.yaml")).expect("include loads");
+        assert!(matches!(
+            config.transport.protocol,
+            rustak_transport::Protocol::Tcp { .. }
+        ));
+        assert_eq!(config.transport.read_timeout, Duration::from_secs(45));
+
+        std::fs::write(dir.join("loop_a.yaml"), "include: [loop_b.yaml]\n")
+            .expect("must write loop a");
+        std::fs::write(dir.join("loop_b.yaml"), "include: loop_a.yaml\n")
+            .expect("must write loop b");
+        let error = RustakConfig::load(dir.join("loop_a.yaml")).expect_err("cycle");
+        assert!(matches!(error, ConfigError::IncludeCycle { .. }));
+    }
+
+    #[test]
+    fn json_and_toml_documents_parse_into_the_same_config() {
+        let yaml = "transport:\n  protocol:\n    type: tcp\n    addr: 127.0.0.1:8089\n  read_timeout: 45s\nsapient:\n  version: bsi_flex_335_v2_0\n  version_pins:\n    radar-1: BSI Flex 335 v2.0\n";
+        let json = r#"{"transport": {"protocol": {"type": "tcp", "addr": "127.0.0.1:8089"}, "read_timeout": "45s"},
+  "sapient": {"version": "bsi_flex_335_v2_0", "version_pins": {"radar-1": "BSI Flex 335 v2.0"}}}"#;
+        let toml = "# site config\n[transport]\nread_timeout = \"45s\"\nprotocol = { type = \"tcp\", addr = \"127.0.0.1:8089\" }\n\n[sapient]\nversion = \"bsi_flex_335_v2_0\"\nversion_pins = { radar-1 = \"BSI Flex 335 v2.0\" }\n";
+
+        assert_eq!(ConfigFormat::detect(yaml), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::detect(json), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::detect(toml), ConfigFormat::Toml);
+        assert_eq!(
+            ConfigFormat::detect("limits_ref: a=b\n"),
+            ConfigFormat::Yaml
+        );
+
+        let expected = RustakConfig::from_yaml_str(yaml).expect("yaml");
+        assert_eq!(
+            RustakConfig::from_reader(json.as_bytes()).expect("json"),
+            expected
+        );
+        assert_eq!(
+            RustakConfig::from_reader(toml.as_bytes()).expect("toml"),
+            expected
+        );
+        assert!(matches!(
+            RustakConfig::from_str_with_format(json, ConfigFormat::Toml),
+            Err(ConfigError::DeserializeToml(_))
+        ));
+
+        let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        dir.push("target/test-rustak-config-formats");
+        std::fs::create_dir_all(&dir).expect("must create fixture dir");
+        std::fs::write(dir.join("base.toml"), toml).expect("must write base");
+        std::fs::write(
+            dir.join("site.json"),
+            r#"{"include": "base.toml", "transport": {"read_timeout": "15s"}}"#,
+        )
+        .expect("must write overlay");
+        std::fs::write(dir.join("site.conf"), json).expect("must write conf");
+
+        let config = RustakConfig::load(dir.join("site.json")).expect("mixed includes load");
+        assert_eq!(config.transport.read_timeout, Duration::from_secs(15));
+        assert_eq!(config.sapient, expected.sapient);
+        assert_eq!(
+            RustakConfig::load(dir.join("site.conf")).expect("detected json"),
+            expected
+        );
+        assert!(matches!(
+            RustakConfig::load_with_format(dir.join("site.conf"), ConfigFormat::Yaml),
+            Ok(config) if config == expected
+        ));
+        assert!(matches!(
+            RustakConfig::load_with_format(dir.join("base.toml"), ConfigFormat::Json),
+            Err(ConfigError::DeserializeJson(_))
+        ));
+    }
+
     #[test]
     fn strict_startup_rejects_bridge_limits_above_transport_limits() {
         let mut config = RustakConfig::default();
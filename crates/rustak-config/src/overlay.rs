@@ -0,0 +1,614 @@
+//! Layered config documents: a base file plus site overlays, merged into
+//! one effective document before it is parsed and validated.
+//!
+//! Later layers take precedence. Mappings merge key by key; scalars and
+//! sequences from a later layer replace earlier ones wholesale. A file's
+//! top-level `include:` (a path or list of paths, relative to that file) is
+//! merged first, in order, and the including file is layered on top.
+//!
+//! [`ConfigLoader`] stacks files, `RUSTAK__`-prefixed environment variables
+//! and programmatic overrides on top of the defaults and records which layer
+//! supplied each field. When the merged document fails to parse or validate,
+//! the error names the layer that introduced the failure.
+//!
+//! Files may be YAML, JSON or TOML (see [`ConfigFormat`]); each is read into
+//! the same document before merging, so layers of different formats mix.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{ConfigError, RustakConfig};
+
+/// Top-level key naming files to merge underneath the current one.
+pub const INCLUDE_KEY: &str = "include";
+/// Deepest chain of nested includes accepted.
+pub const MAX_INCLUDE_DEPTH: usize = 8;
+/// Prefix of environment variables read by [`ConfigLoader::with_env`];
+/// `__` separates path segments, so `RUSTAK__TRANSPORT__READ_TIMEOUT` sets
+/// `transport.read_timeout`.
+pub const ENV_PREFIX: &str = "RUSTAK__";
+
+/// Syntax of a config document. Every format is read into the same schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConfigFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Format named by the extension of `path`, if it is a known one.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the first line that is neither blank nor a
+    /// `#` comment: `{` opens JSON, while a `[table]` header or a
+    /// `key = value` pair means TOML. Anything else is read as YAML.
+    #[must_use]
+    pub fn detect(text: &str) -> Self {
+        let Some(line) = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+        else {
+            return Self::Yaml;
+        };
+        if line.starts_with('{') {
+            return Self::Json;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            return Self::Toml;
+        }
+        match line.split_once('=') {
+            Some((key, _)) if !key.contains(':') && !key.trim().is_empty() => Self::Toml,
+            _ => Self::Yaml,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+}
+
+/// How conflicting values from two layers are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeMode {
+    /// The later layer wins.
+    #[default]
+    Override,
+    /// A scalar or sequence set by more than one layer is an error; only
+    /// mappings may be extended.
+    Strict,
+}
+
+/// Where a config value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Defaults,
+    /// A config file, together with everything it includes.
+    File(PathBuf),
+    /// An environment variable, by name.
+    Env(String),
+    /// A programmatic override, by dotted path.
+    Override(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Defaults => f.write_str("defaults"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Env(name) => write!(f, "env {name}"),
+            Self::Override(path) => write!(f, "override {path}"),
+        }
+    }
+}
+
+static DEFAULTS: ConfigSource = ConfigSource::Defaults;
+
+/// The layer that last set each field of a loaded config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigProvenance {
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl ConfigProvenance {
+    /// Source of the field at dotted `path`, or of the nearest enclosing
+    /// list or scalar; fields no layer set come from the defaults.
+    #[must_use]
+    pub fn source_of(&self, path: &str) -> &ConfigSource {
+        let mut candidate = path;
+        loop {
+            if let Some(source) = self.sources.get(candidate) {
+                return source;
+            }
+            match candidate.rsplit_once('.') {
+                Some((parent, _)) => candidate = parent,
+                None => return &DEFAULTS,
+            }
+        }
+    }
+
+    /// Every field set by a layer, ordered by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConfigSource)> {
+        self.sources
+            .iter()
+            .map(|(path, source)| (path.as_str(), source))
+    }
+
+    fn record(&mut self, value: &Value, path: String, source: &ConfigSource) {
+        match value {
+            Value::Mapping(mapping) => {
+                for (key, child) in mapping {
+                    self.record(child, child_path(&path, key), source);
+                }
+            }
+            _ => {
+                let nested = format!("{path}.");
+                self.sources
+                    .retain(|existing, _| !existing.starts_with(&nested));
+                self.sources.insert(path, source.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PendingLayer {
+    File(PathBuf),
+    Field {
+        source: ConfigSource,
+        path: String,
+        value: String,
+    },
+}
+
+/// Builds a [`RustakConfig`] from defaults, files, environment variables and
+/// programmatic overrides, applied in the order they are added.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    mode: MergeMode,
+    layers: Vec<PendingLayer>,
+}
+
+impl ConfigLoader {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_merge_mode(mut self, mode: MergeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Adds a file, resolving its includes as [`RustakConfig::load`] does.
+    #[must_use]
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.layers.push(PendingLayer::File(path.into()));
+        self
+    }
+
+    /// Adds every process environment variable starting with
+    /// [`ENV_PREFIX`].
+    #[must_use]
+    pub fn with_env(self) -> Self {
+        self.with_env_vars(std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        }))
+    }
+
+    /// Adds the variables among `vars` that start with [`ENV_PREFIX`],
+    /// ordered by name so a parent is applied before its children. Values
+    /// are read as YAML, so `30s`, `true` and `[a, b]` keep their types.
+    #[must_use]
+    pub fn with_env_vars(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut vars = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let path = name
+                    .strip_prefix(ENV_PREFIX)?
+                    .split("__")
+                    .map(str::to_ascii_lowercase)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                Some((name, path, value))
+            })
+            .collect::<Vec<_>>();
+        vars.sort();
+        self.layers.extend(
+            vars.into_iter()
+                .map(|(name, path, value)| PendingLayer::Field {
+                    source: ConfigSource::Env(name),
+                    path,
+                    value,
+                }),
+        );
+        self
+    }
+
+    /// Sets the field at dotted `path` to `value`, read as YAML.
+    #[must_use]
+    pub fn with_override(mut self, path: impl Into<String>, value: impl Into<String>) -> Self {
+        let path = path.into();
+        self.layers.push(PendingLayer::Field {
+            source: ConfigSource::Override(path.clone()),
+            path,
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn load(&self) -> Result<RustakConfig, ConfigError> {
+        self.load_with_provenance().map(|(config, _)| config)
+    }
+
+    /// Merges and validates every layer. A failure is wrapped in
+    /// [`ConfigError::InLayer`] naming the layer that introduced it: the
+    /// latest one without which the same error does not occur.
+    pub fn load_with_provenance(&self) -> Result<(RustakConfig, ConfigProvenance), ConfigError> {
+        let mut merged = Value::Mapping(Mapping::new());
+        let mut provenance = ConfigProvenance::default();
+        let mut applied = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let (source, value) = self.resolve(layer)?;
+            provenance.record(&value, String::new(), &source);
+            merge(&mut merged, value, self.mode, "").map_err(|error| ConfigError::InLayer {
+                layer: source.clone(),
+                error: Box::new(error),
+            })?;
+            applied.push((source, merged.clone()));
+        }
+
+        match RustakConfig::from_yaml_value(merged) {
+            Ok(config) => Ok((config, provenance)),
+            Err(error) => Err(blame(applied, error)),
+        }
+    }
+
+    fn resolve(&self, layer: &PendingLayer) -> Result<(ConfigSource, Value), ConfigError> {
+        match layer {
+            PendingLayer::File(path) => {
+                let source = ConfigSource::File(path.clone());
+                match load_file(path, self.mode, None) {
+                    Ok(value) => Ok((source, value)),
+                    Err(error) => Err(ConfigError::InLayer {
+                        layer: source,
+                        error: Box::new(error),
+                    }),
+                }
+            }
+            PendingLayer::Field {
+                source,
+                path,
+                value,
+            } => match field_layer(path, value) {
+                Ok(value) => Ok((source.clone(), value)),
+                Err(error) => Err(ConfigError::InLayer {
+                    layer: source.clone(),
+                    error: Box::new(error),
+                }),
+            },
+        }
+    }
+}
+
+/// Nests `value` under the segments of dotted `path`.
+fn field_layer(path: &str, value: &str) -> Result<Value, ConfigError> {
+    let segments = path.split('.').collect::<Vec<_>>();
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(ConfigError::InvalidOverridePath {
+            path: path.to_owned(),
+        });
+    }
+    let value = serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.to_owned()));
+    Ok(segments.into_iter().rev().fold(value, |value, segment| {
+        let mut mapping = Mapping::new();
+        mapping.insert(Value::String(segment.to_owned()), value);
+        Value::Mapping(mapping)
+    }))
+}
+
+/// Attributes `error` from the fully merged document to the latest layer
+/// whose predecessors merge into a document that does not fail the same way.
+fn blame(applied: Vec<(ConfigSource, Value)>, error: ConfigError) -> ConfigError {
+    let message = error.to_string();
+    let mut culprit = ConfigSource::Defaults;
+    let mut applied = applied.into_iter().rev().peekable();
+    while let Some((source, _)) = applied.next() {
+        let before = applied.peek().map_or_else(
+            || Value::Mapping(Mapping::new()),
+            |(_, value)| value.clone(),
+        );
+        let fails_the_same = RustakConfig::from_yaml_value(before)
+            .is_err_and(|earlier| earlier.to_string() == message);
+        if !fails_the_same {
+            culprit = source;
+            break;
+        }
+    }
+    ConfigError::InLayer {
+        layer: culprit,
+        error: Box::new(error),
+    }
+}
+
+pub(crate) fn parse_layer(text: &str, format: ConfigFormat) -> Result<Value, ConfigError> {
+    let value: Value = match format {
+        ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(ConfigError::DeserializeConfig)?,
+        ConfigFormat::Json => serde_json::from_str(text).map_err(ConfigError::DeserializeJson)?,
+        ConfigFormat::Toml => toml::from_str(text).map_err(ConfigError::DeserializeToml)?,
+    };
+    match value {
+        Value::Null => Ok(Value::Mapping(Mapping::new())),
+        Value::Mapping(_) => Ok(value),
+        _ => Err(ConfigError::NonMappingLayer),
+    }
+}
+
+/// Merges `overlay` into `base`. `path` is the dotted location of `base`,
+/// used in conflict errors.
+pub(crate) fn merge(
+    base: &mut Value,
+    overlay: Value,
+    mode: MergeMode,
+    path: &str,
+) -> Result<(), ConfigError> {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                let child = child_path(path, &key);
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value, mode, &child)?,
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+            Ok(())
+        }
+        (base, overlay) => {
+            if mode == MergeMode::Strict {
+                return Err(ConfigError::MergeConflict {
+                    path: path.to_owned(),
+                });
+            }
+            *base = overlay;
+            Ok(())
+        }
+    }
+}
+
+/// Reads `path` and everything it includes into one merged document.
+/// `format` applies to `path` alone; the format of each file without one is
+/// taken from its extension, or failing that its contents.
+pub(crate) fn load_file(
+    path: &Path,
+    mode: MergeMode,
+    format: Option<ConfigFormat>,
+) -> Result<Value, ConfigError> {
+    let mut chain = Vec::new();
+    load_file_inner(path, mode, format, &mut chain)
+}
+
+fn load_file_inner(
+    path: &Path,
+    mode: MergeMode,
+    format: Option<ConfigFormat>,
+    chain: &mut Vec<PathBuf>,
+) -> Result<Value, ConfigError> {
+    let display = path.display().to_string();
+    let canonical = path
+        .canonicalize()
+        .map_err(|source| ConfigError::ReadConfig {
+            path: display.clone(),
+            source,
+        })?;
+    if chain.contains(&canonical) {
+        return Err(ConfigError::IncludeCycle { path: display });
+    }
+    if chain.len() >= MAX_INCLUDE_DEPTH {
+        return Err(ConfigError::IncludeTooDeep {
+            path: display,
+            max_depth: MAX_INCLUDE_DEPTH,
+        });
+    }
+
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigError::ReadConfig {
+        path: display.clone(),
+        source,
+    })?;
+    let format = format
+        .or_else(|| ConfigFormat::from_path(path))
+        .unwrap_or_else(|| ConfigFormat::detect(&text));
+    let mut layer = parse_layer(&text, format)?;
+    let includes = take_includes(&mut layer, &display)?;
+    if includes.is_empty() {
+        return Ok(layer);
+    }
+
+    chain.push(canonical);
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut merged = Value::Mapping(Mapping::new());
+    for include in includes {
+        let included = load_file_inner(&base_dir.join(include), mode, None, chain)?;
+        merge(&mut merged, included, mode, "")?;
+    }
+    chain.pop();
+
+    merge(&mut merged, layer, mode, "")?;
+    Ok(merged)
+}
+
+fn take_includes(layer: &mut Value, path: &str) -> Result<Vec<PathBuf>, ConfigError> {
+    let Value::Mapping(mapping) = layer else {
+        return Ok(Vec::new());
+    };
+    let invalid = || ConfigError::InvalidInclude {
+        path: path.to_owned(),
+    };
+    match mapping.remove(INCLUDE_KEY) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(include)) => Ok(vec![PathBuf::from(include)]),
+        Some(Value::Sequence(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(PathBuf::from(include)),
+                _ => Err(invalid()),
+            })
+            .collect(),
+        Some(_) => Err(invalid()),
+    }
+}
+
+fn child_path(parent: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        other => serde_yaml::to_string(other)
+            .map(|rendered| rendered.trim_end().to_owned())
+            .unwrap_or_default(),
+    };
+    if parent.is_empty() {
+        key
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_yaml::Value;
+
+    use super::{merge, parse_layer, ConfigFormat, ConfigLoader, ConfigSource, MergeMode};
+    use crate::ConfigError;
+
+    fn merged(base: &str, overlay: &str, mode: MergeMode) -> Result<Value, ConfigError> {
+        let mut base = parse_layer(base, ConfigFormat::Yaml).expect("base");
+        merge(
+            &mut base,
+            parse_layer(overlay, ConfigFormat::Yaml).expect("overlay"),
+            mode,
+            "",
+        )?;
+        Ok(base)
+    }
+
+    #[test]
+    fn override_merges_mappings_and_replaces_scalars_and_sequences() {
+        let value = merged(
+            "a:\n  b: 1\n  c: [1, 2]\n  d: keep\n",
+            "a:\n  b: 2\n  c: [3]\n  e: new\n",
+            MergeMode::Override,
+        )
+        .expect("merge");
+
+        let expected = parse_layer(
+            "a:\n  b: 2\n  c: [3]\n  d: keep\n  e: new\n",
+            ConfigFormat::Yaml,
+        )
+        .expect("yaml");
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn strict_rejects_scalars_defined_twice() {
+        assert!(merged("a:\n  b: 1\n", "a:\n  c: 2\n", MergeMode::Strict).is_ok());
+
+        let error =
+            merged("a:\n  b: 1\n", "a:\n  b: 2\n", MergeMode::Strict).expect_err("conflict");
+        assert!(matches!(error, ConfigError::MergeConflict { path } if path == "a.b"));
+    }
+
+    #[test]
+    fn loader_layers_env_and_overrides_and_blames_the_bad_layer() {
+        let dir = std::env::temp_dir().join(format!("rustak_config_loader_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = dir.join("rustak.yaml");
+        std::fs::write(&path, "transport:\n  protocol:\n    type: tcp\n    addr: 127.0.0.1:8089\n  read_timeout: 20s\n  write_timeout: 20s\n")
+            .expect("write config");
+        let env = |value: &str| {
+            vec![
+                ("HOME".to_owned(), "/root".to_owned()),
+                (
+                    "RUSTAK__TRANSPORT__READ_TIMEOUT".to_owned(),
+                    value.to_owned(),
+                ),
+            ]
+        };
+
+        let (config, provenance) = ConfigLoader::new()
+            .with_file(&path)
+            .with_env_vars(env("30s"))
+            .with_override("transport.write_timeout", "45s")
+            .load_with_provenance()
+            .expect("layers load");
+        assert_eq!(config.transport.read_timeout, Duration::from_secs(30));
+        assert_eq!(config.transport.write_timeout, Duration::from_secs(45));
+        assert_eq!(
+            provenance.source_of("transport.read_timeout"),
+            &ConfigSource::Env("RUSTAK__TRANSPORT__READ_TIMEOUT".to_owned())
+        );
+        assert_eq!(
+            provenance.source_of("transport.write_timeout"),
+            &ConfigSource::Override("transport.write_timeout".to_owned())
+        );
+        assert_eq!(
+            provenance.source_of("transport.limits.max_frame_bytes"),
+            &ConfigSource::Defaults
+        );
+
+        let error = ConfigLoader::new()
+            .with_file(&path)
+            .with_env_vars(env("0s"))
+            .with_override("transport.write_timeout", "45s")
+            .load()
+            .expect_err("zero read timeout is invalid");
+        let ConfigError::InLayer { layer, .. } = &error else {
+            panic!("error names its layer: {error:?}");
+        };
+        assert_eq!(
+            layer,
+            &ConfigSource::Env("RUSTAK__TRANSPORT__READ_TIMEOUT".to_owned())
+        );
+        assert!(error
+            .to_string()
+            .starts_with("env RUSTAK__TRANSPORT__READ_TIMEOUT: "));
+
+        let error = ConfigLoader::new()
+            .with_file(&path)
+            .with_override("transport.no_such_field", "1")
+            .load()
+            .expect_err("unknown fields are rejected");
+        assert!(matches!(
+            error,
+            ConfigError::InLayer { layer: ConfigSource::Override(path), .. }
+                if path == "transport.no_such_field"
+        ));
+        assert!(matches!(
+            ConfigLoader::new().with_override("transport..read_timeout", "1s").load(),
+            Err(ConfigError::InLayer { error, .. })
+                if matches!(*error, ConfigError::InvalidOverridePath { .. })
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,18 +1,92 @@
+use std::{collections::BTreeMap, fmt};
+
 use serde_yaml::Value;
 
 use crate::{schema::RustakConfigDocument, ConfigError, RustakConfig};
 
 const REDACTED: &str = "[REDACTED]";
-const DEFAULT_REDACT_PATHS: [&str; 3] = [
+const DEFAULT_REDACT_PATHS: [&str; 5] = [
     "certificates.client_key",
     "certificates.client_cert",
     "crypto.server_spki_pin",
+    "crypto.signing.private_key",
+    "webhooks.auth_header",
 ];
 
+/// A single field whose effective value differs from the built-in default.
+///
+/// Values are rendered after redaction, so sensitive fields only ever show
+/// `[REDACTED]`. `None` means the field is absent on that side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFieldChange {
+    pub path: String,
+    pub default: Option<String>,
+    pub effective: Option<String>,
+}
+
+impl fmt::Display for ConfigFieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            self.default.as_deref().unwrap_or("<unset>"),
+            self.effective.as_deref().unwrap_or("<unset>")
+        )
+    }
+}
+
 pub(crate) fn to_redacted_yaml(config: &RustakConfig) -> Result<String, ConfigError> {
-    let document = RustakConfigDocument::from(config);
-    let mut value = serde_yaml::to_value(document).map_err(ConfigError::SerializeConfig)?;
+    let value = redacted_value(config, &redact_paths(config))?;
+    serde_yaml::to_string(&value).map_err(ConfigError::SerializeConfig)
+}
+
+pub(crate) fn diff_from_defaults(
+    config: &RustakConfig,
+) -> Result<Vec<ConfigFieldChange>, ConfigError> {
+    diff_configs(&RustakConfig::default(), config)
+}
+
+/// Fields that differ between `base` and `config`; `default` holds the
+/// `base` side.
+pub(crate) fn diff_configs(
+    base: &RustakConfig,
+    config: &RustakConfig,
+) -> Result<Vec<ConfigFieldChange>, ConfigError> {
+    // Redact both sides with the paths of both configs so a secret that
+    // only exists on one side never leaks through the other column.
+    let mut paths = redact_paths(base);
+    paths.extend(redact_paths(config));
+    paths.sort();
+    paths.dedup();
+    let defaults = redacted_value(base, &paths)?;
+    let effective = redacted_value(config, &paths)?;
+
+    let mut default_leaves = Vec::new();
+    flatten_leaves(&defaults, String::new(), &mut default_leaves);
+    let mut effective_leaves = Vec::new();
+    flatten_leaves(&effective, String::new(), &mut effective_leaves);
+
+    let mut merged = BTreeMap::<String, (Option<String>, Option<String>)>::new();
+    for (path, value) in default_leaves {
+        merged.entry(path).or_default().0 = Some(value);
+    }
+    for (path, value) in effective_leaves {
+        merged.entry(path).or_default().1 = Some(value);
+    }
+
+    Ok(merged
+        .into_iter()
+        .filter(|(_, (default, effective))| default != effective)
+        .map(|(path, (default, effective))| ConfigFieldChange {
+            path,
+            default,
+            effective,
+        })
+        .collect())
+}
 
+fn redact_paths(config: &RustakConfig) -> Vec<String> {
     let mut redact_paths: Vec<String> = DEFAULT_REDACT_PATHS
         .iter()
         .map(|path| (*path).to_owned())
@@ -24,12 +98,47 @@ pub(crate) fn to_redacted_yaml(config: &RustakConfig) -> Result<String, ConfigEr
 
     redact_paths.sort();
     redact_paths.dedup();
+    redact_paths
+}
+
+fn redacted_value(config: &RustakConfig, redact_paths: &[String]) -> Result<Value, ConfigError> {
+    let document = RustakConfigDocument::from(config);
+    let mut value = serde_yaml::to_value(document).map_err(ConfigError::SerializeConfig)?;
 
     for path in redact_paths {
-        redact_path(&mut value, &path);
+        redact_path(&mut value, path);
     }
 
-    serde_yaml::to_string(&value).map_err(ConfigError::SerializeConfig)
+    Ok(value)
+}
+
+fn flatten_leaves(value: &Value, prefix: String, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::Mapping(mapping) => {
+            for (key, child) in mapping {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    other => render_scalar(other),
+                };
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_leaves(child, path, out);
+            }
+        }
+        Value::Tagged(tagged) => flatten_leaves(&tagged.value, prefix, out),
+        other => out.push((prefix, render_scalar(other))),
+    }
+}
+
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => serde_json::to_string(other).unwrap_or_else(|_| format!("{other:?}")),
+    }
 }
 
 fn redact_path(root: &mut Value, path: &str) {
@@ -0,0 +1,645 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value as JsonValue};
+
+use crate::schema::json_schema;
+
+/// Cross-field and semantic rules enforced by the `validate` methods behind
+/// `RustakConfig::validate`, which the JSON schema cannot express. Keyed by
+/// config path; a test keeps every key pointing at a field in the schema.
+const VALIDATOR_CONSTRAINTS: &[(&str, &str)] = &[
+    ("transport.read_timeout", "must be greater than zero"),
+    ("transport.write_timeout", "must be greater than zero"),
+    ("transport.keepalive.interval", "must be greater than zero"),
+    ("transport.keepalive.timeout", "must be greater than zero"),
+    (
+        "transport.keepalive.timeout",
+        "must not exceed transport.keepalive.interval",
+    ),
+    (
+        "transport.reconnect.initial_delay",
+        "must be greater than zero when reconnect is enabled",
+    ),
+    (
+        "transport.reconnect.max_delay",
+        "must be greater than zero when reconnect is enabled",
+    ),
+    ("transport.reconnect.backoff_factor", "must be >= 1.0"),
+    ("transport.reconnect.jitter", "must be within [0.0, 1.0]"),
+    ("transport.mtu_safety.max_udp_payload_bytes", "must be > 0"),
+    (
+        "transport.mtu_safety.max_udp_payload_bytes",
+        "must not exceed transport.limits.max_frame_bytes",
+    ),
+    ("transport.send_queue.max_messages", "must be > 0"),
+    (
+        "transport.send_queue.max_messages",
+        "must not exceed transport.limits.max_queue_messages",
+    ),
+    ("transport.send_queue.max_bytes", "must be > 0"),
+    (
+        "transport.send_queue.max_bytes",
+        "must not exceed transport.limits.max_queue_bytes",
+    ),
+    (
+        "transport.send_queue.shaping.pli.bytes_per_second",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.pli.burst_bytes",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.chat.bytes_per_second",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.chat.burst_bytes",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.track.bytes_per_second",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.track.burst_bytes",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.large_detail.bytes_per_second",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.large_detail.burst_bytes",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.other.bytes_per_second",
+        "must be > 0",
+    ),
+    (
+        "transport.send_queue.shaping.other.burst_bytes",
+        "must be > 0",
+    ),
+    (
+        "transport.quota",
+        "must set `hour` or `day`; each set window needs `send_bytes` or `receive_bytes`",
+    ),
+    ("transport.quota.hour.send_bytes", "must be > 0"),
+    ("transport.quota.hour.receive_bytes", "must be > 0"),
+    ("transport.quota.day.send_bytes", "must be > 0"),
+    ("transport.quota.day.receive_bytes", "must be > 0"),
+    ("transport.quota.action.bytes_per_second", "must be > 0"),
+    ("transport.limits.max_frame_bytes", "must be > 0"),
+    (
+        "transport.limits.max_xml_scan_bytes",
+        "must be > 0 and not exceed max_frame_bytes",
+    ),
+    (
+        "transport.limits.max_protobuf_bytes",
+        "must be > 0 and not exceed max_frame_bytes",
+    ),
+    (
+        "transport.limits.max_queue_bytes",
+        "must be > 0 and at least max_frame_bytes",
+    ),
+    (
+        "transport.limits.max_queue_messages",
+        "must be > 0 and not exceed max_queue_bytes",
+    ),
+    ("transport.limits.max_detail_elements", "must be > 0"),
+    ("crypto.server_spki_pin", "must not be blank"),
+    (
+        "crypto.signing.key_id",
+        "must not be blank; set together with crypto.signing.private_key",
+    ),
+    (
+        "crypto.signing.private_key",
+        "must not be blank; set together with crypto.signing.key_id",
+    ),
+    (
+        "crypto.signing.trusted_keys",
+        "must not be empty when verification is `require`",
+    ),
+    (
+        "crypto.signing.trusted_keys[].key_id",
+        "must not be blank or repeated",
+    ),
+    (
+        "crypto.signing.trusted_keys[].public_key",
+        "must not be blank",
+    ),
+    ("bridge.correlator.uid_prefix", "must not be blank"),
+    ("bridge.correlator.max_entries", "must be > 0"),
+    (
+        "bridge.correlator.max_idle",
+        "must be greater than zero when set",
+    ),
+    (
+        "bridge.mappings.classes",
+        "needs `validation.classification_mapping_entries` entries with non-blank CoT types when `rustak bridge` starts under strict startup",
+    ),
+    (
+        "bridge.mappings.behaviours",
+        "needs `validation.behaviour_mapping_entries` entries with non-blank detail keys when `rustak bridge` starts under strict startup",
+    ),
+    (
+        "bridge.track_quality.half_life",
+        "must be greater than zero",
+    ),
+    (
+        "bridge.track_quality.ce_growth_meters_per_second",
+        "must be finite and >= 0",
+    ),
+    (
+        "bridge.track_quality.le_growth_meters_per_second",
+        "must be finite and >= 0",
+    ),
+    (
+        "bridge.cardinality.active_window",
+        "must be greater than zero",
+    ),
+    (
+        "bridge.cardinality.coalesce_interval",
+        "must be greater than zero",
+    ),
+    (
+        "bridge.cardinality.sample_one_in",
+        "must be > 0",
+    ),
+    (
+        "bridge.cardinality.sample_above",
+        "must lie between `coalesce_above` and `max_active_tracks`",
+    ),
+    (
+        "bridge.cardinality.high_priority_type_prefixes",
+        "entries must not be blank",
+    ),
+    ("certificates.ca_cert", "must not be blank"),
+    ("certificates.client_cert", "must not be blank"),
+    ("certificates.client_key", "must not be blank"),
+    ("logging.redact", "entries must not be blank"),
+    (
+        "webhooks.urls",
+        "entries must be http:// or https:// URLs without credentials",
+    ),
+    (
+        "webhooks.auth_header",
+        "must be a single `Name: value` line",
+    ),
+    ("webhooks.timeout", "must be greater than zero"),
+];
+
+/// One field of the config schema with what the docs need to say about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFieldReference {
+    /// Dotted YAML path; `[]` marks array items and `<name>` map keys.
+    pub path: String,
+    /// Schema type, e.g. `integer (uint32)`, `object` or `array of string`.
+    pub kind: String,
+    /// Compact JSON of the value used when the field is omitted.
+    pub default: Option<String>,
+    pub required: bool,
+    pub description: Option<String>,
+    /// Schema bounds and enum values followed by validator rules.
+    pub constraints: Vec<String>,
+}
+
+/// Every field reachable from the config root in path order, generated
+/// from [`json_schema`] and the validator rules.
+#[must_use]
+pub fn config_reference() -> Vec<ConfigFieldReference> {
+    let schema = json_schema();
+    let mut walker = SchemaWalker {
+        definitions: schema.get("definitions").and_then(JsonValue::as_object),
+        fields: BTreeMap::new(),
+    };
+    walker.visit_properties(&schema, "", None, None);
+
+    let mut fields = walker.fields;
+    for (path, rule) in VALIDATOR_CONSTRAINTS {
+        if let Some(field) = fields.get_mut(*path) {
+            merge_constraint(&mut field.constraints, (*rule).to_owned());
+        }
+    }
+    fields.into_values().collect()
+}
+
+/// Looks up one field, e.g. `transport.reconnect.jitter`.
+#[must_use]
+pub fn explain_config_field(path: &str) -> Option<ConfigFieldReference> {
+    config_reference()
+        .into_iter()
+        .find(|field| field.path == path)
+}
+
+/// Renders [`config_reference`] as the Markdown checked in at
+/// `docs/config_reference.md`.
+#[must_use]
+pub fn render_config_reference() -> String {
+    let mut out = String::from(
+        "# RusTAK config reference\n\n\
+         <!-- Generated by `rustak config docs --output docs/config_reference.md`; do not edit. -->\n\n\
+         Every field accepted in a rustak config, whether written as YAML, JSON or\n\
+         TOML. Defaults apply when a field is omitted; constraints combine the JSON\n\
+         schema with the checks `rustak validate` runs on load.\n",
+    );
+    let mut section = String::new();
+    for field in config_reference() {
+        let top = field.path.split(['.', '[']).next().unwrap_or_default();
+        if top != section {
+            section = top.to_owned();
+            out.push_str(&format!(
+                "\n## `{top}`\n\n| Field | Type | Default | Constraints | Description |\n|---|---|---|---|---|\n"
+            ));
+        }
+        out.push_str(&format!(
+            "| `{}` | {} | {} | {} | {} |\n",
+            field.path,
+            field.kind,
+            field.default.as_deref().map_or_else(
+                || if field.required {
+                    "required".to_owned()
+                } else {
+                    String::new()
+                },
+                |default| format!("`{default}`")
+            ),
+            markdown_cell(&field.constraints.join("; ")),
+            markdown_cell(field.description.as_deref().unwrap_or_default()),
+        ));
+    }
+    out
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+struct SchemaWalker<'a> {
+    definitions: Option<&'a Map<String, JsonValue>>,
+    fields: BTreeMap<String, ConfigFieldReference>,
+}
+
+impl<'a> SchemaWalker<'a> {
+    /// Follows `$ref`, single-entry `allOf` and `anyOf [T, null]` wrappers
+    /// down to the schema that carries the type.
+    fn resolve(&self, mut schema: &'a JsonValue) -> &'a JsonValue {
+        loop {
+            if let Some(name) = schema
+                .get("$ref")
+                .and_then(JsonValue::as_str)
+                .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            {
+                match self
+                    .definitions
+                    .and_then(|definitions| definitions.get(name))
+                {
+                    Some(definition) => schema = definition,
+                    None => return schema,
+                }
+            } else if let Some([inner]) = schema
+                .get("allOf")
+                .and_then(JsonValue::as_array)
+                .map(Vec::as_slice)
+            {
+                schema = inner;
+            } else if let Some(inner) = schema
+                .get("anyOf")
+                .and_then(JsonValue::as_array)
+                .and_then(|variants| non_null_variant(variants))
+            {
+                schema = inner;
+            } else {
+                return schema;
+            }
+        }
+    }
+
+    /// Visits every property of an object schema. Each variant of a tagged
+    /// enum is visited with the `type is value` condition its fields
+    /// depend on.
+    fn visit_properties(
+        &mut self,
+        schema: &'a JsonValue,
+        prefix: &str,
+        defaults: Option<&JsonValue>,
+        condition: Option<&str>,
+    ) {
+        let schema = self.resolve(schema);
+        for variant in schema
+            .get("oneOf")
+            .and_then(JsonValue::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let tag = self.variant_tag(variant);
+            let condition = tag.map(|(name, value)| format!("`{name}` is `{value}`"));
+            self.visit_properties(variant, prefix, defaults, condition.as_deref());
+        }
+        let required = schema
+            .get("required")
+            .and_then(JsonValue::as_array)
+            .map(|required| {
+                required
+                    .iter()
+                    .filter_map(JsonValue::as_str)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let tag = self.variant_tag(schema).map(|(name, _)| name);
+        for (name, property) in schema
+            .get("properties")
+            .and_then(JsonValue::as_object)
+            .into_iter()
+            .flatten()
+        {
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{prefix}.{name}")
+            };
+            let default = property
+                .get("default")
+                .or_else(|| defaults.and_then(|defaults| defaults.get(name)));
+            let condition = condition.filter(|_| tag != Some(name.as_str()));
+            self.visit_field(
+                &path,
+                property,
+                default,
+                required.contains(&name.as_str()),
+                condition,
+            );
+        }
+    }
+
+    fn visit_field(
+        &mut self,
+        path: &str,
+        property: &'a JsonValue,
+        default: Option<&JsonValue>,
+        required: bool,
+        condition: Option<&str>,
+    ) {
+        let schema = self.resolve(property);
+        let mut kind = self.kind(schema);
+        if is_nullable(property) {
+            kind.push_str(", optional");
+        }
+        let description = property
+            .get("description")
+            .or_else(|| schema.get("description"))
+            .and_then(JsonValue::as_str)
+            .map(str::to_owned);
+        let mut constraints = schema_constraints(schema);
+        if let Some(condition) = condition {
+            constraints.insert(
+                0,
+                if required {
+                    format!("required when {condition}")
+                } else {
+                    format!("only when {condition}")
+                },
+            );
+        }
+
+        let entry = self
+            .fields
+            .entry(path.to_owned())
+            .or_insert_with(|| ConfigFieldReference {
+                path: path.to_owned(),
+                kind: kind.clone(),
+                default: None,
+                required: false,
+                description: None,
+                constraints: Vec::new(),
+            });
+        // Tagged enum variants contribute the same field more than once.
+        if entry.kind != kind && !entry.kind.split(" or ").any(|known| known == kind) {
+            entry.kind = format!("{} or {kind}", entry.kind);
+        }
+        if entry.default.is_none() && !is_object(schema) {
+            entry.default = default
+                .filter(|default| !default.is_null())
+                .map(JsonValue::to_string);
+        }
+        entry.required |= required && condition.is_none() && default.is_none();
+        if entry.description.is_none() {
+            entry.description = description;
+        }
+        for constraint in constraints {
+            merge_constraint(&mut entry.constraints, constraint);
+        }
+
+        if is_object(schema) {
+            self.visit_properties(schema, path, default, None);
+        } else if let Some(values) = map_values(schema) {
+            self.visit_field(&format!("{path}.<name>"), values, None, false, None);
+        } else if let Some(items) = schema.get("items") {
+            if is_object(self.resolve(items)) {
+                self.visit_properties(items, &format!("{path}[]"), None, None);
+            }
+        }
+    }
+
+    /// The discriminator of a tagged enum variant: the required property
+    /// that only admits one value.
+    fn variant_tag(&self, variant: &'a JsonValue) -> Option<(&'a str, &'a str)> {
+        let required = variant.get("required")?.as_array()?;
+        variant
+            .get("properties")?
+            .as_object()?
+            .iter()
+            .find_map(|(name, property)| {
+                let values = self.resolve(property).get("enum")?.as_array()?;
+                match values.as_slice() {
+                    [value] if required.iter().any(|required| required == name) => {
+                        Some((name.as_str(), value.as_str()?))
+                    }
+                    _ => None,
+                }
+            })
+    }
+
+    fn kind(&self, schema: &JsonValue) -> String {
+        if is_object(schema) {
+            return "object".to_owned();
+        }
+        let base = match schema.get("type") {
+            Some(JsonValue::String(kind)) => kind.clone(),
+            Some(JsonValue::Array(kinds)) => kinds
+                .iter()
+                .filter_map(JsonValue::as_str)
+                .filter(|kind| *kind != "null")
+                .collect::<Vec<_>>()
+                .join(" or "),
+            _ => "any".to_owned(),
+        };
+        if let Some(values) = map_values(schema) {
+            return format!("map of {}", self.kind(self.resolve(values)));
+        }
+        if base == "array" {
+            return match schema.get("items") {
+                Some(items) => format!("array of {}", self.kind(self.resolve(items))),
+                None => base,
+            };
+        }
+        match schema.get("format").and_then(JsonValue::as_str) {
+            Some(format) => format!("{base} ({format})"),
+            None => base,
+        }
+    }
+}
+
+fn is_object(schema: &JsonValue) -> bool {
+    schema.get("properties").is_some()
+        || schema
+            .get("oneOf")
+            .and_then(JsonValue::as_array)
+            .is_some_and(|variants| {
+                variants
+                    .iter()
+                    .any(|variant| variant.get("properties").is_some())
+            })
+}
+
+fn map_values(schema: &JsonValue) -> Option<&JsonValue> {
+    schema
+        .get("additionalProperties")
+        .filter(|values| values.is_object())
+}
+
+/// Adds `constraint` unless already listed. Value lists such as `one of
+/// `a`` or `required when `type` is `a`` are extended instead of repeated.
+fn merge_constraint(constraints: &mut Vec<String>, constraint: String) {
+    if constraints.contains(&constraint) {
+        return;
+    }
+    let split = if constraint.starts_with("one of ") {
+        Some("one of ".len())
+    } else {
+        constraint.find(" is `").map(|at| at + " is ".len())
+    };
+    if let Some((prefix, values)) = split.map(|at| constraint.split_at(at)) {
+        if let Some(existing) = constraints
+            .iter_mut()
+            .find(|existing| existing.starts_with(prefix))
+        {
+            existing.push_str(", ");
+            existing.push_str(values);
+            return;
+        }
+    }
+    constraints.push(constraint);
+}
+
+fn is_nullable(schema: &JsonValue) -> bool {
+    let null_type = |schema: &JsonValue| match schema.get("type") {
+        Some(JsonValue::String(kind)) => kind == "null",
+        Some(JsonValue::Array(kinds)) => kinds.iter().any(|kind| kind == "null"),
+        _ => false,
+    };
+    null_type(schema)
+        || schema
+            .get("anyOf")
+            .and_then(JsonValue::as_array)
+            .is_some_and(|variants| variants.iter().any(null_type))
+}
+
+fn non_null_variant(variants: &[JsonValue]) -> Option<&JsonValue> {
+    match variants {
+        [inner, null] | [null, inner] if null.get("type").is_some_and(|kind| kind == "null") => {
+            Some(inner)
+        }
+        _ => None,
+    }
+}
+
+fn schema_constraints(schema: &JsonValue) -> Vec<String> {
+    let mut constraints = Vec::new();
+    // Unit enums with documented variants become `oneOf` single-value enums.
+    let values = match schema.get("oneOf").and_then(JsonValue::as_array) {
+        Some(variants) if !is_object(schema) => Some(
+            variants
+                .iter()
+                .filter_map(|variant| variant.get("enum").and_then(JsonValue::as_array))
+                .flatten()
+                .collect::<Vec<_>>(),
+        ),
+        _ => schema
+            .get("enum")
+            .and_then(JsonValue::as_array)
+            .map(|values| values.iter().collect()),
+    };
+    if let Some(values) = values {
+        let values = values
+            .into_iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), |value| format!("`{value}`"))
+            })
+            .collect::<Vec<_>>();
+        constraints.push(format!("one of {}", values.join(", ")));
+    }
+    let unsigned = schema
+        .get("format")
+        .and_then(JsonValue::as_str)
+        .is_some_and(|format| format.starts_with("uint"));
+    if let Some(minimum) = schema.get("minimum").and_then(JsonValue::as_f64) {
+        if !(unsigned && minimum == 0.0) {
+            constraints.push(format!(">= {minimum}"));
+        }
+    }
+    if let Some(maximum) = schema.get("maximum").and_then(JsonValue::as_f64) {
+        constraints.push(format!("<= {maximum}"));
+    }
+    constraints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        config_reference, explain_config_field, render_config_reference, VALIDATOR_CONSTRAINTS,
+    };
+
+    #[test]
+    fn validator_constraints_point_at_schema_fields() {
+        let reference = config_reference();
+        for (path, _) in VALIDATOR_CONSTRAINTS {
+            assert!(
+                reference.iter().any(|field| field.path == *path),
+                "{path} is not a config field"
+            );
+        }
+    }
+
+    #[test]
+    fn explain_combines_schema_defaults_and_validator_rules() {
+        let jitter = explain_config_field("transport.reconnect.jitter").expect("jitter");
+        assert_eq!(jitter.kind, "number (double)");
+        assert_eq!(jitter.default.as_deref(), Some("0.2"));
+        assert_eq!(jitter.constraints, ["must be within [0.0, 1.0]"]);
+        assert!(jitter.description.is_some());
+
+        let group = explain_config_field("transport.protocol.group").expect("group");
+        assert!(!group.required);
+        assert_eq!(
+            group.constraints,
+            ["required when `type` is `udp_multicast`"]
+        );
+
+        let window = explain_config_field("bridge.dedup.window").expect("window");
+        assert_eq!(window.default.as_deref(), Some("\"500ms\""));
+        assert!(explain_config_field("transport.nope").is_none());
+    }
+
+    #[test]
+    fn checked_in_reference_matches_schema() {
+        assert_eq!(
+            include_str!("../../../docs/config_reference.md"),
+            render_config_reference(),
+            "regenerate with `rustak config docs --output docs/config_reference.md`"
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Re-reading configuration while a service runs.
+//!
+//! A [`ConfigReloader`] remembers the files the running config came from.
+//! [`ConfigReloader::reload`] loads them again, diffs the result against the
+//! running config and classifies each changed field: limits, queue sizes,
+//! timeouts and logging can be applied in place, while anything else, such
+//! as the protocol, wire format or TLS material, needs a restart. A reload
+//! is all or nothing: the new config is published to subscribers only when
+//! every change is hot-applicable.
+//!
+//! Services typically call [`ConfigReloader::reload`] from
+//! `AdminState::request_reload` and react to updates on
+//! [`ConfigReloader::subscribe`].
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::{redact, ConfigError, MergeMode, RustakConfig};
+
+/// Field paths, and everything below them, that take effect without a
+/// restart. Other changes require one.
+pub const HOT_RELOAD_PATHS: [&str; 11] = [
+    "logging",
+    "transport.limits",
+    "transport.send_queue",
+    "transport.read_timeout",
+    "transport.write_timeout",
+    "transport.reconnect",
+    "transport.quota",
+    "sapient.limits",
+    "bridge.limits",
+    "bridge.emitter",
+    "bridge.cardinality",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReloadImpact {
+    Hot,
+    RestartRequired,
+}
+
+impl ReloadImpact {
+    /// Impact of changing the field at dotted `path`.
+    #[must_use]
+    pub fn of(path: &str) -> Self {
+        let hot = HOT_RELOAD_PATHS.iter().any(|prefix| {
+            path.strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        });
+        if hot {
+            Self::Hot
+        } else {
+            Self::RestartRequired
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hot => "hot",
+            Self::RestartRequired => "restart_required",
+        }
+    }
+}
+
+/// One changed field, rendered with the same redaction as
+/// [`RustakConfig::to_redacted_yaml`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReloadChange {
+    pub path: String,
+    pub running: Option<String>,
+    pub reloaded: Option<String>,
+    pub impact: ReloadImpact,
+}
+
+impl fmt::Display for ReloadChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "config_reload path={} impact={} running={} reloaded={}",
+            self.path,
+            self.impact.as_str(),
+            self.running.as_deref().unwrap_or("<unset>"),
+            self.reloaded.as_deref().unwrap_or("<unset>")
+        )
+    }
+}
+
+/// Every field that differs between the running and a candidate config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReloadPlan {
+    pub changes: Vec<ReloadChange>,
+}
+
+impl ReloadPlan {
+    pub fn between(running: &RustakConfig, candidate: &RustakConfig) -> Result<Self, ConfigError> {
+        let changes = redact::diff_configs(running, candidate)?
+            .into_iter()
+            .map(|change| ReloadChange {
+                impact: ReloadImpact::of(&change.path),
+                path: change.path,
+                running: change.default,
+                reloaded: change.effective,
+            })
+            .collect();
+        Ok(Self { changes })
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    #[must_use]
+    pub fn requires_restart(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.impact == ReloadImpact::RestartRequired)
+    }
+
+    pub fn restart_required(&self) -> impl Iterator<Item = &ReloadChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.impact == ReloadImpact::RestartRequired)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReloadOutcome {
+    /// The files match the running config.
+    Unchanged,
+    /// Every change was hot-applicable and subscribers were notified.
+    Applied(ReloadPlan),
+    /// At least one change needs a restart; the running config is kept.
+    RestartRequired(ReloadPlan),
+}
+
+impl ReloadOutcome {
+    #[must_use]
+    pub fn plan(&self) -> Option<&ReloadPlan> {
+        match self {
+            Self::Unchanged => None,
+            Self::Applied(plan) | Self::RestartRequired(plan) => Some(plan),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigReloader {
+    paths: Vec<PathBuf>,
+    mode: MergeMode,
+    current: watch::Sender<Arc<RustakConfig>>,
+}
+
+impl ConfigReloader {
+    /// Loads `paths` as [`RustakConfig::load_layered`] does and remembers
+    /// them for later reloads.
+    pub fn load<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+        mode: MergeMode,
+    ) -> Result<Self, ConfigError> {
+        let paths = paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect::<Vec<_>>();
+        let config = RustakConfig::load_layered(&paths, mode)?;
+        Ok(Self::new(paths, mode, config))
+    }
+
+    /// Starts from an already loaded `config` that came from `paths`.
+    #[must_use]
+    pub fn new(paths: Vec<PathBuf>, mode: MergeMode, config: RustakConfig) -> Self {
+        Self {
+            paths,
+            mode,
+            current: watch::Sender::new(Arc::new(config)),
+        }
+    }
+
+    #[must_use]
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// The running config.
+    #[must_use]
+    pub fn current(&self) -> Arc<RustakConfig> {
+        Arc::clone(&self.current.borrow())
+    }
+
+    /// Receives each config applied by [`Self::reload`].
+    #[must_use]
+    pub fn subscribe(&self) -> watch::Receiver<Arc<RustakConfig>> {
+        self.current.subscribe()
+    }
+
+    /// Re-reads and validates the files and applies the result when every
+    /// change is hot-applicable. A file that fails to load or validate
+    /// leaves the running config in place.
+    pub fn reload(&self) -> Result<ReloadOutcome, ConfigError> {
+        let candidate = RustakConfig::load_layered(&self.paths, self.mode)?;
+        self.apply(candidate)
+    }
+
+    /// Like [`Self::reload`] with a config obtained elsewhere, which must
+    /// already be validated.
+    pub fn apply(&self, candidate: RustakConfig) -> Result<ReloadOutcome, ConfigError> {
+        let plan = ReloadPlan::between(&self.current(), &candidate)?;
+        if plan.is_empty() {
+            return Ok(ReloadOutcome::Unchanged);
+        }
+        if plan.requires_restart() {
+            return Ok(ReloadOutcome::RestartRequired(plan));
+        }
+        self.current.send_replace(Arc::new(candidate));
+        Ok(ReloadOutcome::Applied(plan))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ConfigReloader, ReloadImpact, ReloadOutcome};
+    use crate::MergeMode;
+
+    const BASE: &str = "transport:
+  protocol:
+    type: tcp
+    addr: 127.0.0.1:8089
+  limits:
+    max_frame_bytes: 1048576
+    max_xml_scan_bytes: 1048576
+    max_protobuf_bytes: 1048576
+    max_queue_messages: 1024
+    max_queue_bytes: 8388608
+    max_detail_elements: 512
+";
+
+    fn write_config(dir: &std::path::Path, yaml: &str) -> PathBuf {
+        let path = dir.join("rustak.yaml");
+        std::fs::write(&path, yaml).expect("write config");
+        path
+    }
+
+    #[test]
+    fn classifies_paths_by_prefix() {
+        assert_eq!(
+            ReloadImpact::of("transport.limits.max_frame_bytes"),
+            ReloadImpact::Hot
+        );
+        assert_eq!(ReloadImpact::of("logging.level"), ReloadImpact::Hot);
+        assert_eq!(
+            ReloadImpact::of("transport.protocol.addr"),
+            ReloadImpact::RestartRequired
+        );
+        assert_eq!(
+            ReloadImpact::of("transport.limits_extra"),
+            ReloadImpact::RestartRequired
+        );
+        assert_eq!(
+            ReloadImpact::of("certificates.client_key"),
+            ReloadImpact::RestartRequired
+        );
+    }
+
+    #[test]
+    fn reload_applies_hot_changes_and_holds_restart_changes() {
+        let dir = std::env::temp_dir().join(format!("rustak_config_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let path = write_config(&dir, BASE);
+        let reloader = ConfigReloader::load([&path], MergeMode::Override).expect("load");
+        let mut updates = reloader.subscribe();
+
+        assert_eq!(reloader.reload().expect("reload"), ReloadOutcome::Unchanged);
+        assert!(!updates.has_changed().expect("sender alive"));
+
+        write_config(&dir, &BASE.replace("512", "256"));
+        let outcome = reloader.reload().expect("reload");
+        let ReloadOutcome::Applied(plan) = outcome else {
+            panic!("limits are hot-applicable: {outcome:?}");
+        };
+        assert_eq!(
+            plan.changes[0].to_string(),
+            "config_reload path=transport.limits.max_detail_elements impact=hot running=512 reloaded=256"
+        );
+        assert!(updates.has_changed().expect("sender alive"));
+        assert_eq!(
+            updates
+                .borrow_and_update()
+                .transport
+                .limits
+                .max_detail_elements,
+            256
+        );
+
+        write_config(&dir, &BASE.replace("8089", "8090"));
+        let outcome = reloader.reload().expect("reload");
+        let ReloadOutcome::RestartRequired(plan) = &outcome else {
+            panic!("protocol changes need a restart: {outcome:?}");
+        };
+        let restart = plan.restart_required().collect::<Vec<_>>();
+        assert_eq!(restart.len(), 1);
+        assert_eq!(restart[0].path, "transport.protocol.addr");
+        assert!(!updates.has_changed().expect("sender alive"));
+        assert_eq!(reloader.current().transport.limits.max_detail_elements, 256);
+
+        write_config(&dir, "transport: [");
+        assert!(reloader.reload().is_err());
+        assert_eq!(reloader.current().transport.limits.max_detail_elements, 256);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,43 +1,67 @@
 use std::{
+    collections::BTreeMap,
     net::{Ipv4Addr, SocketAddr},
     str::FromStr,
     time::Duration,
 };
 
-use schemars::{schema_for, JsonSchema};
+use schemars::{
+    gen::SchemaGenerator,
+    schema::{InstanceType, Metadata, Schema, SchemaObject},
+    schema_for, JsonSchema,
+};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value as JsonValue;
 
 use crate::{
     CertificatesConfig, ConfigError, CryptoConfig, CryptoProvider, LimitsBinding, LimitsRef,
     LogFormat, LogLevel, LoggingConfig, RevocationPolicy, RustakConfig, SapientConfigSpec,
+    SignatureVerification, SigningConfig, TrustedKey,
 };
+use rustak_admin::WebhookConfig;
 use rustak_bridge::{
-    BridgeConfig, BridgeValidationConfig, DedupConfig, EmitterConfig, TimePolicyMode,
+    AngleUnit, BearingReference, BehaviourMapping, BridgeConfig, BridgeValidationConfig,
+    CorrelatorConfig, CoverageStyle, DatumOffset, DedupConfig, EmitterConfig, MappingSeverity,
+    MappingTables, NormalizationConfig, RangeUnit, SensorCoverageConfig, SensorNormalization,
+    TimePolicyMode, TrackCardinalityConfig, TrackQualityConfig, UidPolicy,
 };
+use rustak_commo::{EgressConfig, EgressRule, EgressTransform};
 use rustak_limits::Limits;
 use rustak_sapient::SapientConfig;
 use rustak_transport::{
-    Keepalive, MtuSafety, Protocol, ReconnectPolicy, SendQueueConfig, SendQueueMode,
-    TransportConfig, UdpTarget,
+    ClassBudget, Keepalive, MessageClass, MtuSafety, OversizePolicy, Protocol, QuotaAction,
+    QuotaLimit, QuotaWindow, ReconnectPolicy, SendQueueConfig, SendQueueMode, TrafficQuotaConfig,
+    TrafficShapingConfig, TransportConfig, UdpTarget,
 };
 use rustak_wire::WireFormat;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct RustakConfigDocument {
+    /// Socket, framing and queue settings for the TAK connection.
     #[serde(default = "default_transport_document")]
     pub transport: TransportConfigDocument,
+    /// SAPIENT peer settings; omit when not bridging SAPIENT.
     #[serde(default)]
     pub sapient: Option<SapientConfigSpecDocument>,
+    /// SAPIENT-to-CoT bridge behaviour.
     #[serde(default)]
     pub bridge: Option<BridgeConfigDocument>,
+    /// Per-destination rewrite rules applied before sending.
+    #[serde(default)]
+    pub egress: Option<EgressConfigDocument>,
+    /// TLS crypto provider, revocation and CoT signing.
     #[serde(default)]
     pub crypto: Option<CryptoConfigDocument>,
+    /// PEM files for the TLS client identity and trust anchor.
     #[serde(default)]
     pub certificates: Option<CertificatesConfigDocument>,
+    /// Log level, format and redacted config paths.
     #[serde(default)]
     pub logging: Option<LoggingConfigDocument>,
+    /// HTTP(S) endpoints notified of connection and health events.
+    #[serde(default)]
+    pub webhooks: Option<WebhookConfigDocument>,
 }
 
 impl From<&RustakConfig> for RustakConfigDocument {
@@ -46,12 +70,14 @@ impl From<&RustakConfig> for RustakConfigDocument {
             transport: TransportConfigDocument::from(&value.transport),
             sapient: value.sapient.as_ref().map(SapientConfigSpecDocument::from),
             bridge: value.bridge.as_ref().map(BridgeConfigDocument::from),
+            egress: value.egress.as_ref().map(EgressConfigDocument::from),
             crypto: value.crypto.as_ref().map(CryptoConfigDocument::from),
             certificates: value
                 .certificates
                 .as_ref()
                 .map(CertificatesConfigDocument::from),
             logging: value.logging.as_ref().map(LoggingConfigDocument::from),
+            webhooks: value.webhooks.as_ref().map(WebhookConfigDocument::from),
         }
     }
 }
@@ -64,9 +90,11 @@ impl TryFrom<RustakConfigDocument> for RustakConfig {
             transport: value.transport.try_into()?,
             sapient: value.sapient.map(TryInto::try_into).transpose()?,
             bridge: value.bridge.map(Into::into),
+            egress: value.egress.map(Into::into),
             crypto: value.crypto.map(Into::into),
             certificates: value.certificates.map(Into::into),
             logging: value.logging.map(Into::into),
+            webhooks: value.webhooks.map(Into::into),
         })
     }
 }
@@ -74,23 +102,35 @@ impl TryFrom<RustakConfigDocument> for RustakConfig {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct TransportConfigDocument {
+    /// Where to connect or bind; `type` selects the variant.
     pub protocol: ProtocolDocument,
+    /// Payload encoding: CoT XML or TAK protocol v1 protobuf.
     #[serde(default = "default_wire_format_document")]
     pub wire_format: WireFormatDocument,
+    /// Frame, parse and queue budgets shared by every codec.
     #[serde(default = "default_limits_document")]
     pub limits: LimitsDocument,
+    /// Timeout for each read on stream transports.
     #[serde(default = "default_read_timeout_document")]
     pub read_timeout: DurationDocument,
+    /// Timeout for each write on stream transports.
     #[serde(default = "default_write_timeout_document")]
     pub write_timeout: DurationDocument,
+    /// Stream keepalive probes; omit to disable.
     #[serde(default)]
     pub keepalive: Option<KeepaliveDocument>,
+    /// Backoff for re-dialling dropped streams.
     #[serde(rename = "reconnect", default = "default_reconnect_policy_document")]
     pub reconnect_policy: ReconnectPolicyDocument,
+    /// UDP payload cap and what to do with larger messages.
     #[serde(default)]
     pub mtu_safety: Option<MtuSafetyDocument>,
+    /// Bounded outbound queue between callers and the socket.
     #[serde(default = "default_send_queue_document")]
     pub send_queue: SendQueueConfigDocument,
+    /// Byte allowances for metered links; omit to leave traffic unmetered.
+    #[serde(default)]
+    pub quota: Option<TrafficQuotaDocument>,
 }
 
 impl From<&TransportConfig> for TransportConfigDocument {
@@ -105,6 +145,7 @@ impl From<&TransportConfig> for TransportConfigDocument {
             reconnect_policy: ReconnectPolicyDocument::from(&value.reconnect_policy),
             mtu_safety: value.mtu_safety.as_ref().map(MtuSafetyDocument::from),
             send_queue: SendQueueConfigDocument::from(&value.send_queue),
+            quota: value.quota.as_ref().map(TrafficQuotaDocument::from),
         }
     }
 }
@@ -123,6 +164,7 @@ impl TryFrom<TransportConfigDocument> for TransportConfig {
             reconnect_policy: value.reconnect_policy.into(),
             mtu_safety: value.mtu_safety.map(Into::into),
             send_queue: value.send_queue.into(),
+            quota: value.quota.map(Into::into),
         })
     }
 }
@@ -131,26 +173,37 @@ impl TryFrom<TransportConfigDocument> for TransportConfig {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub(crate) enum ProtocolDocument {
     Tcp {
+        /// Stream address as `host:port`.
         addr: String,
     },
     Tls {
+        /// Stream address as `host:port`.
         addr: String,
+        /// TLS server name checked against the certificate.
         server_name: String,
     },
     UdpUnicast {
+        /// Local `ip:port` to bind.
         bind_addr: String,
+        /// Unicast destination `ip:port`.
         target_addr: String,
     },
     UdpMulticast {
+        /// Local `ip:port` to bind.
         bind_addr: String,
+        /// IPv4 multicast group.
         group: String,
+        /// Destination port.
         port: u16,
     },
     UdpBroadcast {
+        /// Local `ip:port` to bind.
         bind_addr: String,
+        /// Destination port.
         port: u16,
     },
     WebSocket {
+        /// WebSocket URL.
         url: String,
     },
 }
@@ -256,11 +309,17 @@ impl From<WireFormatDocument> for WireFormat {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct LimitsDocument {
+    /// Largest frame accepted or sent.
     pub max_frame_bytes: usize,
+    /// Bytes of XML scanned per event.
     pub max_xml_scan_bytes: usize,
+    /// Largest TAK protocol v1 payload decoded.
     pub max_protobuf_bytes: usize,
+    /// Messages held by any bounded queue.
     pub max_queue_messages: usize,
+    /// Bytes held by any bounded queue.
     pub max_queue_bytes: usize,
+    /// `<detail>` children parsed per event.
     pub max_detail_elements: usize,
 }
 
@@ -290,10 +349,31 @@ impl From<LimitsDocument> for Limits {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
-#[schemars(with = "String")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct DurationDocument(Duration);
 
+impl JsonSchema for DurationDocument {
+    fn schema_name() -> String {
+        "DurationDocument".to_owned()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        SchemaObject {
+            instance_type: Some(vec![InstanceType::String, InstanceType::Integer].into()),
+            format: Some("duration".to_owned()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "Duration such as `500ms`, `30s`, `5m` or `1h`; a bare integer is milliseconds."
+                        .to_owned(),
+                ),
+                ..Metadata::default()
+            })),
+            ..SchemaObject::default()
+        }
+        .into()
+    }
+}
+
 impl DurationDocument {
     pub(crate) const fn from_duration(value: Duration) -> Self {
         Self(value)
@@ -338,7 +418,9 @@ impl<'de> Deserialize<'de> for DurationDocument {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct KeepaliveDocument {
+    /// Time between probes.
     pub interval: DurationDocument,
+    /// How long to wait for a probe reply.
     pub timeout: DurationDocument,
 }
 
@@ -363,11 +445,17 @@ impl From<KeepaliveDocument> for Keepalive {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct ReconnectPolicyDocument {
+    /// Re-dial after a stream drops.
     pub enabled: bool,
+    /// Delay before the first retry.
     pub initial_delay: DurationDocument,
+    /// Cap on the backoff delay.
     pub max_delay: DurationDocument,
+    /// Multiplier applied to the delay after each failure.
     pub backoff_factor: f64,
+    /// Random fraction added to each delay.
     pub jitter: f64,
+    /// Give up after this many attempts; unset retries forever.
     #[serde(default)]
     pub max_retries: Option<u32>,
 }
@@ -401,15 +489,18 @@ impl From<ReconnectPolicyDocument> for ReconnectPolicy {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MtuSafetyDocument {
+    /// Largest UDP payload sent in one datagram.
     pub max_udp_payload_bytes: usize,
-    pub drop_oversize: bool,
+    /// Handling for payloads over the cap.
+    #[serde(default = "default_oversize_policy_document")]
+    pub oversize: OversizePolicyDocument,
 }
 
 impl From<&MtuSafety> for MtuSafetyDocument {
     fn from(value: &MtuSafety) -> Self {
         Self {
             max_udp_payload_bytes: value.max_udp_payload_bytes,
-            drop_oversize: value.drop_oversize,
+            oversize: OversizePolicyDocument::from(value.oversize),
         }
     }
 }
@@ -418,7 +509,41 @@ impl From<MtuSafetyDocument> for MtuSafety {
     fn from(value: MtuSafetyDocument) -> Self {
         Self {
             max_udp_payload_bytes: value.max_udp_payload_bytes,
-            drop_oversize: value.drop_oversize,
+            oversize: value.oversize.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OversizePolicyDocument {
+    Drop,
+    Split,
+    TruncateDetail,
+    StreamFallback,
+    Chunk,
+}
+
+impl From<OversizePolicy> for OversizePolicyDocument {
+    fn from(value: OversizePolicy) -> Self {
+        match value {
+            OversizePolicy::Drop => Self::Drop,
+            OversizePolicy::Split => Self::Split,
+            OversizePolicy::TruncateDetail => Self::TruncateDetail,
+            OversizePolicy::StreamFallback => Self::StreamFallback,
+            OversizePolicy::Chunk => Self::Chunk,
+        }
+    }
+}
+
+impl From<OversizePolicyDocument> for OversizePolicy {
+    fn from(value: OversizePolicyDocument) -> Self {
+        match value {
+            OversizePolicyDocument::Drop => Self::Drop,
+            OversizePolicyDocument::Split => Self::Split,
+            OversizePolicyDocument::TruncateDetail => Self::TruncateDetail,
+            OversizePolicyDocument::StreamFallback => Self::StreamFallback,
+            OversizePolicyDocument::Chunk => Self::Chunk,
         }
     }
 }
@@ -426,9 +551,17 @@ impl From<MtuSafetyDocument> for MtuSafety {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SendQueueConfigDocument {
+    /// Queue capacity in messages.
     pub max_messages: usize,
+    /// Queue capacity in bytes.
     pub max_bytes: usize,
+    /// Plain FIFO, priority order, or only the newest event per uid (still
+    /// drained in priority order).
     pub mode: SendQueueModeDocument,
+    /// Per-class bandwidth budgets enforced when the queue drains; omit to
+    /// send as fast as the link allows.
+    #[serde(default)]
+    pub shaping: Option<TrafficShapingDocument>,
 }
 
 impl From<&SendQueueConfig> for SendQueueConfigDocument {
@@ -437,6 +570,7 @@ impl From<&SendQueueConfig> for SendQueueConfigDocument {
             max_messages: value.max_messages,
             max_bytes: value.max_bytes,
             mode: SendQueueModeDocument::from(value.mode.clone()),
+            shaping: value.shaping.as_ref().map(TrafficShapingDocument::from),
         }
     }
 }
@@ -447,6 +581,194 @@ impl From<SendQueueConfigDocument> for SendQueueConfig {
             max_messages: value.max_messages,
             max_bytes: value.max_bytes,
             mode: value.mode.into(),
+            shaping: value.shaping.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TrafficShapingDocument {
+    /// Budget for own-position reports.
+    #[serde(default)]
+    pub pli: Option<ClassBudgetDocument>,
+    /// Budget for chat messages.
+    #[serde(default)]
+    pub chat: Option<ClassBudgetDocument>,
+    /// Budget for relayed tracks.
+    #[serde(default)]
+    pub track: Option<ClassBudgetDocument>,
+    /// Budget for events with imagery-like or otherwise large details.
+    #[serde(default)]
+    pub large_detail: Option<ClassBudgetDocument>,
+    /// Budget for everything else.
+    #[serde(default)]
+    pub other: Option<ClassBudgetDocument>,
+}
+
+impl TrafficShapingDocument {
+    fn slot(&mut self, class: MessageClass) -> &mut Option<ClassBudgetDocument> {
+        match class {
+            MessageClass::Pli => &mut self.pli,
+            MessageClass::Chat => &mut self.chat,
+            MessageClass::Track => &mut self.track,
+            MessageClass::LargeDetail => &mut self.large_detail,
+            MessageClass::Other => &mut self.other,
+        }
+    }
+}
+
+impl From<&TrafficShapingConfig> for TrafficShapingDocument {
+    fn from(value: &TrafficShapingConfig) -> Self {
+        let mut document = Self::default();
+        for (class, budget) in &value.budgets {
+            *document.slot(*class) = Some(ClassBudgetDocument::from(budget));
+        }
+        document
+    }
+}
+
+impl From<TrafficShapingDocument> for TrafficShapingConfig {
+    fn from(mut value: TrafficShapingDocument) -> Self {
+        let budgets = MessageClass::ALL
+            .into_iter()
+            .filter_map(|class| Some((class, value.slot(class).take()?.into())))
+            .collect();
+        Self { budgets }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ClassBudgetDocument {
+    /// Sustained rate for the class.
+    pub bytes_per_second: u64,
+    /// Bytes the class may send at once after being idle.
+    pub burst_bytes: usize,
+}
+
+impl From<&ClassBudget> for ClassBudgetDocument {
+    fn from(value: &ClassBudget) -> Self {
+        Self {
+            bytes_per_second: value.bytes_per_second,
+            burst_bytes: value.burst_bytes,
+        }
+    }
+}
+
+impl From<ClassBudgetDocument> for ClassBudget {
+    fn from(value: ClassBudgetDocument) -> Self {
+        Self {
+            bytes_per_second: value.bytes_per_second,
+            burst_bytes: value.burst_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TrafficQuotaDocument {
+    /// Allowance per UTC hour.
+    #[serde(default)]
+    pub hour: Option<QuotaLimitDocument>,
+    /// Allowance per UTC day.
+    #[serde(default)]
+    pub day: Option<QuotaLimitDocument>,
+    /// What happens once an allowance is used up, until its window rolls
+    /// over.
+    #[serde(default = "default_quota_action_document")]
+    pub action: QuotaActionDocument,
+}
+
+impl From<&TrafficQuotaConfig> for TrafficQuotaDocument {
+    fn from(value: &TrafficQuotaConfig) -> Self {
+        let limit = |window| value.limits.get(&window).map(QuotaLimitDocument::from);
+        Self {
+            hour: limit(QuotaWindow::Hour),
+            day: limit(QuotaWindow::Day),
+            action: QuotaActionDocument::from(value.action),
+        }
+    }
+}
+
+impl From<TrafficQuotaDocument> for TrafficQuotaConfig {
+    fn from(value: TrafficQuotaDocument) -> Self {
+        let limits = [
+            (QuotaWindow::Hour, value.hour),
+            (QuotaWindow::Day, value.day),
+        ]
+        .into_iter()
+        .filter_map(|(window, limit)| Some((window, limit?.into())))
+        .collect();
+        Self {
+            limits,
+            action: value.action.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct QuotaLimitDocument {
+    /// Bytes that may be sent per window; omit to leave sends unmetered.
+    #[serde(default)]
+    pub send_bytes: Option<u64>,
+    /// Bytes that may be received per window; omit to leave receives
+    /// unmetered.
+    #[serde(default)]
+    pub receive_bytes: Option<u64>,
+}
+
+impl From<&QuotaLimit> for QuotaLimitDocument {
+    fn from(value: &QuotaLimit) -> Self {
+        Self {
+            send_bytes: value.send_bytes,
+            receive_bytes: value.receive_bytes,
+        }
+    }
+}
+
+impl From<QuotaLimitDocument> for QuotaLimit {
+    fn from(value: QuotaLimitDocument) -> Self {
+        Self {
+            send_bytes: value.send_bytes,
+            receive_bytes: value.receive_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum QuotaActionDocument {
+    /// Count the overrun and keep the traffic flowing.
+    Warn,
+    /// Pace traffic in the exhausted direction.
+    Throttle {
+        /// Rate allowed once the quota is used up.
+        bytes_per_second: u64,
+    },
+    /// Fail sends and receives and refuse reconnects.
+    Disconnect,
+}
+
+impl From<QuotaAction> for QuotaActionDocument {
+    fn from(value: QuotaAction) -> Self {
+        match value {
+            QuotaAction::Warn => Self::Warn,
+            QuotaAction::Throttle { bytes_per_second } => Self::Throttle { bytes_per_second },
+            QuotaAction::Disconnect => Self::Disconnect,
+        }
+    }
+}
+
+impl From<QuotaActionDocument> for QuotaAction {
+    fn from(value: QuotaActionDocument) -> Self {
+        match value {
+            QuotaActionDocument::Warn => Self::Warn,
+            QuotaActionDocument::Throttle { bytes_per_second } => {
+                Self::Throttle { bytes_per_second }
+            }
+            QuotaActionDocument::Disconnect => Self::Disconnect,
         }
     }
 }
@@ -482,17 +804,25 @@ impl From<SendQueueModeDocument> for SendQueueMode {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SapientConfigSpecDocument {
+    /// SAPIENT interface version spoken by the peer.
     pub version: String,
+    /// Inline limits for SAPIENT frames; defaults to the transport limits.
     #[serde(default)]
     pub limits: Option<LimitsDocument>,
+    /// Named limits profile used instead of inline `limits`.
     #[serde(default)]
     pub limits_ref: Option<String>,
     #[serde(default = "default_read_timeout_document")]
     pub read_timeout: DurationDocument,
     #[serde(default = "default_write_timeout_document")]
     pub write_timeout: DurationDocument,
+    /// Disable Nagle on the SAPIENT socket.
     #[serde(default = "default_tcp_nodelay")]
     pub tcp_nodelay: bool,
+    /// ICD version each listed sensor node must declare when it registers,
+    /// keyed by node ID; mismatched registrations are rejected.
+    #[serde(default)]
+    pub version_pins: BTreeMap<String, String>,
 }
 
 impl From<&SapientConfigSpec> for SapientConfigSpecDocument {
@@ -509,6 +839,7 @@ impl From<&SapientConfigSpec> for SapientConfigSpecDocument {
             read_timeout: DurationDocument::from_duration(value.read_timeout),
             write_timeout: DurationDocument::from_duration(value.write_timeout),
             tcp_nodelay: value.tcp_nodelay,
+            version_pins: value.version_pins.clone(),
         }
     }
 }
@@ -535,27 +866,50 @@ impl TryFrom<SapientConfigSpecDocument> for SapientConfigSpec {
             read_timeout: value.read_timeout.into_duration(),
             write_timeout: value.write_timeout.into_duration(),
             tcp_nodelay: value.tcp_nodelay,
+            version_pins: value.version_pins,
         })
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct BridgeConfigDocument {
     #[serde(default = "default_limits_document")]
     pub limits: LimitsDocument,
+    /// Stale time of emitted CoT events.
     #[serde(default = "default_bridge_cot_stale_seconds")]
     pub cot_stale_seconds: u32,
+    /// Largest sensor clock offset accepted before clamping.
     #[serde(default = "default_bridge_max_clock_skew_seconds")]
     pub max_clock_skew_seconds: u32,
+    /// Which timestamp emitted events carry.
     #[serde(default = "default_bridge_time_policy_document")]
     pub time_policy: TimePolicyModeDocument,
+    /// Suppresses repeated detections within a window.
     #[serde(default = "default_bridge_dedup_document")]
     pub dedup: BridgeDedupDocument,
+    /// Maps sensor object/detection IDs to CoT UIDs and bounds how many are
+    /// remembered.
+    #[serde(default = "default_bridge_correlator_document")]
+    pub correlator: BridgeCorrelatorDocument,
+    /// Rate limits on emitted CoT.
     #[serde(default = "default_bridge_emitter_document")]
     pub emitter: BridgeEmitterDocument,
     #[serde(default = "default_bridge_validation_document")]
     pub validation: BridgeValidationDocument,
+    #[serde(default = "default_bridge_sensor_coverage_document")]
+    pub sensor_coverage: BridgeSensorCoverageDocument,
+    #[serde(default = "default_bridge_normalization_document")]
+    pub normalization: BridgeNormalizationDocument,
+    /// Confidence decay and error growth between detections.
+    #[serde(default = "default_bridge_track_quality_document")]
+    pub track_quality: BridgeTrackQualityDocument,
+    /// Caps on distinct active tracks during sensor storms.
+    #[serde(default = "default_bridge_cardinality_document")]
+    pub cardinality: BridgeCardinalityDocument,
+    /// SAPIENT classification and behaviour tables for emitted tracks.
+    #[serde(default)]
+    pub mappings: BridgeMappingsDocument,
 }
 
 impl From<&BridgeConfig> for BridgeConfigDocument {
@@ -566,8 +920,14 @@ impl From<&BridgeConfig> for BridgeConfigDocument {
             max_clock_skew_seconds: value.max_clock_skew_seconds,
             time_policy: TimePolicyModeDocument::from(value.time_policy),
             dedup: BridgeDedupDocument::from(&value.dedup),
+            correlator: BridgeCorrelatorDocument::from(&value.correlator),
             emitter: BridgeEmitterDocument::from(&value.emitter),
             validation: BridgeValidationDocument::from(&value.validation),
+            sensor_coverage: BridgeSensorCoverageDocument::from(&value.sensor_coverage),
+            normalization: BridgeNormalizationDocument::from(&value.normalization),
+            track_quality: BridgeTrackQualityDocument::from(&value.track_quality),
+            cardinality: BridgeCardinalityDocument::from(&value.cardinality),
+            mappings: BridgeMappingsDocument::from(&value.mappings),
         }
     }
 }
@@ -580,8 +940,14 @@ impl From<BridgeConfigDocument> for BridgeConfig {
             max_clock_skew_seconds: value.max_clock_skew_seconds,
             time_policy: value.time_policy.into(),
             dedup: value.dedup.into(),
+            correlator: value.correlator.into(),
             emitter: value.emitter.into(),
             validation: value.validation.into(),
+            sensor_coverage: value.sensor_coverage.into(),
+            normalization: value.normalization.into(),
+            track_quality: value.track_quality.into(),
+            cardinality: value.cardinality.into(),
+            mappings: value.mappings.into(),
         }
     }
 }
@@ -639,6 +1005,69 @@ impl From<BridgeDedupDocument> for DedupConfig {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UidPolicyDocument {
+    StablePerObject,
+    StablePerDetection,
+}
+
+impl From<UidPolicy> for UidPolicyDocument {
+    fn from(value: UidPolicy) -> Self {
+        match value {
+            UidPolicy::StablePerObject => Self::StablePerObject,
+            UidPolicy::StablePerDetection => Self::StablePerDetection,
+        }
+    }
+}
+
+impl From<UidPolicyDocument> for UidPolicy {
+    fn from(value: UidPolicyDocument) -> Self {
+        match value {
+            UidPolicyDocument::StablePerObject => Self::StablePerObject,
+            UidPolicyDocument::StablePerDetection => Self::StablePerDetection,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeCorrelatorDocument {
+    #[serde(default = "default_bridge_correlator_uid_policy")]
+    pub uid_policy: UidPolicyDocument,
+    #[serde(default = "default_bridge_correlator_uid_prefix")]
+    pub uid_prefix: String,
+    /// Correlations kept at most; the least recently seen is evicted first.
+    #[serde(default = "default_bridge_correlator_max_entries")]
+    pub max_entries: usize,
+    /// Evicts correlations not seen for this long; `null` keeps them until
+    /// `max_entries` forces them out.
+    #[serde(default = "default_bridge_correlator_max_idle")]
+    pub max_idle: Option<DurationDocument>,
+}
+
+impl From<&CorrelatorConfig> for BridgeCorrelatorDocument {
+    fn from(value: &CorrelatorConfig) -> Self {
+        Self {
+            uid_policy: value.uid_policy.into(),
+            uid_prefix: value.uid_prefix.clone(),
+            max_entries: value.max_entries,
+            max_idle: value.max_idle.map(DurationDocument::from_duration),
+        }
+    }
+}
+
+impl From<BridgeCorrelatorDocument> for CorrelatorConfig {
+    fn from(value: BridgeCorrelatorDocument) -> Self {
+        Self {
+            uid_policy: value.uid_policy.into(),
+            uid_prefix: value.uid_prefix,
+            max_entries: value.max_entries,
+            max_idle: value.max_idle.map(DurationDocument::into_duration),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct BridgeEmitterDocument {
@@ -702,13 +1131,596 @@ impl From<BridgeValidationDocument> for BridgeValidationConfig {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeSensorCoverageDocument {
+    #[serde(default)]
+    pub emit_sensor_location: bool,
+    #[serde(default)]
+    pub emit_field_of_view: bool,
+    #[serde(default = "default_sensor_cot_type")]
+    pub sensor_cot_type: String,
+    #[serde(default = "default_sensor_coverage_uid_prefix")]
+    pub uid_prefix: String,
+    #[serde(default = "default_sensor_coverage_arc_segments")]
+    pub arc_segments: u16,
+    #[serde(default = "default_coverage_style_document")]
+    pub style: CoverageStyleDocument,
+}
+
+impl From<&SensorCoverageConfig> for BridgeSensorCoverageDocument {
+    fn from(value: &SensorCoverageConfig) -> Self {
+        Self {
+            emit_sensor_location: value.emit_sensor_location,
+            emit_field_of_view: value.emit_field_of_view,
+            sensor_cot_type: value.sensor_cot_type.clone(),
+            uid_prefix: value.uid_prefix.clone(),
+            arc_segments: value.arc_segments,
+            style: CoverageStyleDocument::from(&value.style),
+        }
+    }
+}
+
+impl From<BridgeSensorCoverageDocument> for SensorCoverageConfig {
+    fn from(value: BridgeSensorCoverageDocument) -> Self {
+        Self {
+            emit_sensor_location: value.emit_sensor_location,
+            emit_field_of_view: value.emit_field_of_view,
+            sensor_cot_type: value.sensor_cot_type,
+            uid_prefix: value.uid_prefix,
+            arc_segments: value.arc_segments,
+            style: value.style.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeTrackQualityDocument {
+    /// Time for a track's confidence to halve without a new detection.
+    #[serde(default = "default_track_quality_half_life")]
+    pub half_life: DurationDocument,
+    /// Metres added to `ce` per second since the last detection.
+    #[serde(default = "default_track_quality_ce_growth")]
+    pub ce_growth_meters_per_second: f64,
+    /// Metres added to `le` per second since the last detection.
+    #[serde(default = "default_track_quality_le_growth")]
+    pub le_growth_meters_per_second: f64,
+    /// Adds a `<track_quality score confidence age>` detail to emitted tracks.
+    #[serde(default = "default_true")]
+    pub emit_detail: bool,
+}
+
+impl From<&TrackQualityConfig> for BridgeTrackQualityDocument {
+    fn from(value: &TrackQualityConfig) -> Self {
+        Self {
+            half_life: DurationDocument::from_duration(value.half_life),
+            ce_growth_meters_per_second: value.ce_growth_meters_per_second,
+            le_growth_meters_per_second: value.le_growth_meters_per_second,
+            emit_detail: value.emit_detail,
+        }
+    }
+}
+
+impl From<BridgeTrackQualityDocument> for TrackQualityConfig {
+    fn from(value: BridgeTrackQualityDocument) -> Self {
+        Self {
+            half_life: value.half_life.into_duration(),
+            ce_growth_meters_per_second: value.ce_growth_meters_per_second,
+            le_growth_meters_per_second: value.le_growth_meters_per_second,
+            emit_detail: value.emit_detail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeCardinalityDocument {
+    /// A track counts as active until this long after it was last forwarded.
+    #[serde(default = "default_cardinality_active_window")]
+    pub active_window: DurationDocument,
+    /// Active tracks beyond which low-priority updates are coalesced.
+    #[serde(default = "default_cardinality_coalesce_above")]
+    pub coalesce_above: usize,
+    /// Least time between forwarded updates of a coalesced track.
+    #[serde(default = "default_cardinality_coalesce_interval")]
+    pub coalesce_interval: DurationDocument,
+    /// Active tracks beyond which new low-priority tracks are sampled.
+    #[serde(default = "default_cardinality_sample_above")]
+    pub sample_above: usize,
+    /// One new low-priority track is admitted per this many while sampling.
+    #[serde(default = "default_cardinality_sample_one_in")]
+    pub sample_one_in: u32,
+    /// Active tracks beyond which new low-priority tracks are dropped.
+    #[serde(default = "default_cardinality_max_active_tracks")]
+    pub max_active_tracks: usize,
+    /// CoT type prefixes that are never coalesced, sampled or dropped.
+    #[serde(default = "default_cardinality_high_priority_type_prefixes")]
+    pub high_priority_type_prefixes: Vec<String>,
+}
+
+impl From<&TrackCardinalityConfig> for BridgeCardinalityDocument {
+    fn from(value: &TrackCardinalityConfig) -> Self {
+        Self {
+            active_window: DurationDocument::from_duration(value.active_window),
+            coalesce_above: value.coalesce_above,
+            coalesce_interval: DurationDocument::from_duration(value.coalesce_interval),
+            sample_above: value.sample_above,
+            sample_one_in: value.sample_one_in,
+            max_active_tracks: value.max_active_tracks,
+            high_priority_type_prefixes: value.high_priority_type_prefixes.clone(),
+        }
+    }
+}
+
+impl From<BridgeCardinalityDocument> for TrackCardinalityConfig {
+    fn from(value: BridgeCardinalityDocument) -> Self {
+        Self {
+            active_window: value.active_window.into_duration(),
+            coalesce_above: value.coalesce_above,
+            coalesce_interval: value.coalesce_interval.into_duration(),
+            sample_above: value.sample_above,
+            sample_one_in: value.sample_one_in,
+            max_active_tracks: value.max_active_tracks,
+            high_priority_type_prefixes: value.high_priority_type_prefixes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeMappingsDocument {
+    /// SAPIENT classification type to CoT type; unmapped classes use
+    /// `validation.unknown_class_fallback`.
+    #[serde(default)]
+    pub classes: BTreeMap<String, String>,
+    /// SAPIENT behaviour type to the detail element added to the track.
+    #[serde(default)]
+    pub behaviours: BTreeMap<String, BehaviourMappingDocument>,
+}
+
+impl From<&MappingTables> for BridgeMappingsDocument {
+    fn from(value: &MappingTables) -> Self {
+        Self {
+            classes: value.class_to_cot.clone(),
+            behaviours: value
+                .behaviour_to_detail
+                .iter()
+                .map(|(behaviour, mapping)| {
+                    (behaviour.clone(), BehaviourMappingDocument::from(mapping))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<BridgeMappingsDocument> for MappingTables {
+    fn from(value: BridgeMappingsDocument) -> Self {
+        Self {
+            class_to_cot: value.classes,
+            behaviour_to_detail: value
+                .behaviours
+                .into_iter()
+                .map(|(behaviour, mapping)| (behaviour, mapping.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BehaviourMappingDocument {
+    pub detail_key: String,
+    #[serde(default)]
+    pub severity: MappingSeverityDocument,
+}
+
+impl From<&BehaviourMapping> for BehaviourMappingDocument {
+    fn from(value: &BehaviourMapping) -> Self {
+        Self {
+            detail_key: value.detail_key.clone(),
+            severity: value.severity.into(),
+        }
+    }
+}
+
+impl From<BehaviourMappingDocument> for BehaviourMapping {
+    fn from(value: BehaviourMappingDocument) -> Self {
+        Self {
+            detail_key: value.detail_key,
+            severity: value.severity.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MappingSeverityDocument {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl From<MappingSeverity> for MappingSeverityDocument {
+    fn from(value: MappingSeverity) -> Self {
+        match value {
+            MappingSeverity::Info => Self::Info,
+            MappingSeverity::Warning => Self::Warning,
+            MappingSeverity::Critical => Self::Critical,
+        }
+    }
+}
+
+impl From<MappingSeverityDocument> for MappingSeverity {
+    fn from(value: MappingSeverityDocument) -> Self {
+        match value {
+            MappingSeverityDocument::Info => Self::Info,
+            MappingSeverityDocument::Warning => Self::Warning,
+            MappingSeverityDocument::Critical => Self::Critical,
+        }
+    }
+}
+
+/// Colours are ARGB integers, e.g. `0x40FFA500` for translucent orange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CoverageStyleDocument {
+    pub stroke_color: u32,
+    pub fill_color: u32,
+    pub stroke_weight: u8,
+}
+
+impl From<&CoverageStyle> for CoverageStyleDocument {
+    fn from(value: &CoverageStyle) -> Self {
+        Self {
+            stroke_color: value.stroke_color,
+            fill_color: value.fill_color,
+            stroke_weight: value.stroke_weight,
+        }
+    }
+}
+
+impl From<CoverageStyleDocument> for CoverageStyle {
+    fn from(value: CoverageStyleDocument) -> Self {
+        Self {
+            stroke_color: value.stroke_color,
+            fill_color: value.fill_color,
+            stroke_weight: value.stroke_weight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BridgeNormalizationDocument {
+    #[serde(default)]
+    pub sensors: BTreeMap<String, SensorNormalizationDocument>,
+    #[serde(default)]
+    pub required_sensors: Vec<String>,
+    /// `null` rejects readings from sensors without an entry.
+    #[serde(default = "default_normalization_fallback_document")]
+    pub fallback: Option<SensorNormalizationDocument>,
+}
+
+impl From<&NormalizationConfig> for BridgeNormalizationDocument {
+    fn from(value: &NormalizationConfig) -> Self {
+        Self {
+            sensors: value
+                .sensors
+                .iter()
+                .map(|(sensor, normalization)| {
+                    (
+                        sensor.clone(),
+                        SensorNormalizationDocument::from(normalization),
+                    )
+                })
+                .collect(),
+            required_sensors: value.required_sensors.clone(),
+            fallback: value
+                .fallback
+                .as_ref()
+                .map(SensorNormalizationDocument::from),
+        }
+    }
+}
+
+impl From<BridgeNormalizationDocument> for NormalizationConfig {
+    fn from(value: BridgeNormalizationDocument) -> Self {
+        Self {
+            sensors: value
+                .sensors
+                .into_iter()
+                .map(|(sensor, normalization)| (sensor, normalization.into()))
+                .collect(),
+            required_sensors: value.required_sensors,
+            fallback: value.fallback.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SensorNormalizationDocument {
+    #[serde(default)]
+    pub range_unit: RangeUnitDocument,
+    #[serde(default)]
+    pub bearing_unit: AngleUnitDocument,
+    #[serde(default)]
+    pub bearing_reference: BearingReferenceDocument,
+    #[serde(default)]
+    pub datum_offset: DatumOffsetDocument,
+}
+
+impl From<&SensorNormalization> for SensorNormalizationDocument {
+    fn from(value: &SensorNormalization) -> Self {
+        Self {
+            range_unit: value.range_unit.into(),
+            bearing_unit: value.bearing_unit.into(),
+            bearing_reference: value.bearing_reference.into(),
+            datum_offset: DatumOffsetDocument::from(&value.datum_offset),
+        }
+    }
+}
+
+impl From<SensorNormalizationDocument> for SensorNormalization {
+    fn from(value: SensorNormalizationDocument) -> Self {
+        Self {
+            range_unit: value.range_unit.into(),
+            bearing_unit: value.bearing_unit.into(),
+            bearing_reference: value.bearing_reference.into(),
+            datum_offset: value.datum_offset.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RangeUnitDocument {
+    #[default]
+    Meters,
+    Kilometers,
+    Feet,
+    Yards,
+    StatuteMiles,
+    NauticalMiles,
+}
+
+impl From<RangeUnit> for RangeUnitDocument {
+    fn from(value: RangeUnit) -> Self {
+        match value {
+            RangeUnit::Meters => Self::Meters,
+            RangeUnit::Kilometers => Self::Kilometers,
+            RangeUnit::Feet => Self::Feet,
+            RangeUnit::Yards => Self::Yards,
+            RangeUnit::StatuteMiles => Self::StatuteMiles,
+            RangeUnit::NauticalMiles => Self::NauticalMiles,
+        }
+    }
+}
+
+impl From<RangeUnitDocument> for RangeUnit {
+    fn from(value: RangeUnitDocument) -> Self {
+        match value {
+            RangeUnitDocument::Meters => Self::Meters,
+            RangeUnitDocument::Kilometers => Self::Kilometers,
+            RangeUnitDocument::Feet => Self::Feet,
+            RangeUnitDocument::Yards => Self::Yards,
+            RangeUnitDocument::StatuteMiles => Self::StatuteMiles,
+            RangeUnitDocument::NauticalMiles => Self::NauticalMiles,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AngleUnitDocument {
+    #[default]
+    Degrees,
+    Radians,
+    Mils,
+}
+
+impl From<AngleUnit> for AngleUnitDocument {
+    fn from(value: AngleUnit) -> Self {
+        match value {
+            AngleUnit::Degrees => Self::Degrees,
+            AngleUnit::Radians => Self::Radians,
+            AngleUnit::Mils => Self::Mils,
+        }
+    }
+}
+
+impl From<AngleUnitDocument> for AngleUnit {
+    fn from(value: AngleUnitDocument) -> Self {
+        match value {
+            AngleUnitDocument::Degrees => Self::Degrees,
+            AngleUnitDocument::Radians => Self::Radians,
+            AngleUnitDocument::Mils => Self::Mils,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum BearingReferenceDocument {
+    #[default]
+    True,
+    /// Declination is east-positive.
+    Magnetic { declination_degrees: f64 },
+}
+
+impl From<BearingReference> for BearingReferenceDocument {
+    fn from(value: BearingReference) -> Self {
+        match value {
+            BearingReference::True => Self::True,
+            BearingReference::Magnetic {
+                declination_degrees,
+            } => Self::Magnetic {
+                declination_degrees,
+            },
+        }
+    }
+}
+
+impl From<BearingReferenceDocument> for BearingReference {
+    fn from(value: BearingReferenceDocument) -> Self {
+        match value {
+            BearingReferenceDocument::True => Self::True,
+            BearingReferenceDocument::Magnetic {
+                declination_degrees,
+            } => Self::Magnetic {
+                declination_degrees,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DatumOffsetDocument {
+    #[serde(default)]
+    pub latitude_degrees: f64,
+    #[serde(default)]
+    pub longitude_degrees: f64,
+    #[serde(default)]
+    pub altitude_meters: f64,
+}
+
+impl From<&DatumOffset> for DatumOffsetDocument {
+    fn from(value: &DatumOffset) -> Self {
+        Self {
+            latitude_degrees: value.latitude_degrees,
+            longitude_degrees: value.longitude_degrees,
+            altitude_meters: value.altitude_meters,
+        }
+    }
+}
+
+impl From<DatumOffsetDocument> for DatumOffset {
+    fn from(value: DatumOffsetDocument) -> Self {
+        Self {
+            latitude_degrees: value.latitude_degrees,
+            longitude_degrees: value.longitude_degrees,
+            altitude_meters: value.altitude_meters,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct EgressConfigDocument {
+    #[serde(default)]
+    pub destinations: BTreeMap<String, EgressDestinationDocument>,
+}
+
+impl From<&EgressConfig> for EgressConfigDocument {
+    fn from(value: &EgressConfig) -> Self {
+        Self {
+            destinations: value
+                .destinations
+                .iter()
+                .map(|(destination, transform)| {
+                    (
+                        destination.clone(),
+                        EgressDestinationDocument::from(transform),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<EgressConfigDocument> for EgressConfig {
+    fn from(value: EgressConfigDocument) -> Self {
+        Self {
+            destinations: value
+                .destinations
+                .into_iter()
+                .map(|(destination, transform)| (destination, transform.into()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct EgressDestinationDocument {
+    /// Applied in order to every message sent to the destination.
+    #[serde(default)]
+    pub rules: Vec<EgressRuleDocument>,
+}
+
+impl From<&EgressTransform> for EgressDestinationDocument {
+    fn from(value: &EgressTransform) -> Self {
+        Self {
+            rules: value.rules.iter().map(EgressRuleDocument::from).collect(),
+        }
+    }
+}
+
+impl From<EgressDestinationDocument> for EgressTransform {
+    fn from(value: EgressDestinationDocument) -> Self {
+        Self {
+            rules: value.rules.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum EgressRuleDocument {
+    StripDetail,
+    RoundCoordinates { decimals: u8 },
+    RemapType { from_prefix: String, to: String },
+    GeneralizeType { max_atoms: usize },
+}
+
+impl From<&EgressRule> for EgressRuleDocument {
+    fn from(value: &EgressRule) -> Self {
+        match value {
+            EgressRule::StripDetail => Self::StripDetail,
+            EgressRule::RoundCoordinates { decimals } => Self::RoundCoordinates {
+                decimals: *decimals,
+            },
+            EgressRule::RemapType { from_prefix, to } => Self::RemapType {
+                from_prefix: from_prefix.clone(),
+                to: to.clone(),
+            },
+            EgressRule::GeneralizeType { max_atoms } => Self::GeneralizeType {
+                max_atoms: *max_atoms,
+            },
+        }
+    }
+}
+
+impl From<EgressRuleDocument> for EgressRule {
+    fn from(value: EgressRuleDocument) -> Self {
+        match value {
+            EgressRuleDocument::StripDetail => Self::StripDetail,
+            EgressRuleDocument::RoundCoordinates { decimals } => {
+                Self::RoundCoordinates { decimals }
+            }
+            EgressRuleDocument::RemapType { from_prefix, to } => {
+                Self::RemapType { from_prefix, to }
+            }
+            EgressRuleDocument::GeneralizeType { max_atoms } => Self::GeneralizeType { max_atoms },
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct CryptoConfigDocument {
+    /// rustls crypto backend.
     pub provider: CryptoProviderDocument,
+    /// Certificate revocation checking.
     pub revocation: RevocationPolicyDocument,
+    /// SPKI pin the server certificate must match.
     #[serde(default)]
     pub server_spki_pin: Option<String>,
+    #[serde(default)]
+    pub signing: Option<SigningConfigDocument>,
 }
 
 impl From<&CryptoConfig> for CryptoConfigDocument {
@@ -717,6 +1729,7 @@ impl From<&CryptoConfig> for CryptoConfigDocument {
             provider: CryptoProviderDocument::from(value.provider),
             revocation: RevocationPolicyDocument::from(value.revocation),
             server_spki_pin: value.server_spki_pin.clone(),
+            signing: value.signing.as_ref().map(SigningConfigDocument::from),
         }
     }
 }
@@ -727,6 +1740,103 @@ impl From<CryptoConfigDocument> for CryptoConfig {
             provider: value.provider.into(),
             revocation: value.revocation.into(),
             server_spki_pin: value.server_spki_pin,
+            signing: value.signing.map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SigningConfigDocument {
+    /// How inbound events with missing or bad signatures are treated.
+    #[serde(default = "default_signature_verification_document")]
+    pub verification: SignatureVerificationDocument,
+    /// Identifier published with our signatures.
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// Base64-encoded 32-byte Ed25519 seed used to sign outbound events.
+    #[serde(default)]
+    pub private_key: Option<String>,
+    /// Peer keys trusted when verifying.
+    #[serde(default)]
+    pub trusted_keys: Vec<TrustedKeyDocument>,
+}
+
+impl From<&SigningConfig> for SigningConfigDocument {
+    fn from(value: &SigningConfig) -> Self {
+        Self {
+            verification: SignatureVerificationDocument::from(value.verification),
+            key_id: value.key_id.clone(),
+            private_key: value.private_key.clone(),
+            trusted_keys: value
+                .trusted_keys
+                .iter()
+                .map(TrustedKeyDocument::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<SigningConfigDocument> for SigningConfig {
+    fn from(value: SigningConfigDocument) -> Self {
+        Self {
+            verification: value.verification.into(),
+            key_id: value.key_id,
+            private_key: value.private_key,
+            trusted_keys: value.trusted_keys.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SignatureVerificationDocument {
+    Ignore,
+    Warn,
+    Require,
+}
+
+impl From<SignatureVerification> for SignatureVerificationDocument {
+    fn from(value: SignatureVerification) -> Self {
+        match value {
+            SignatureVerification::Ignore => Self::Ignore,
+            SignatureVerification::Warn => Self::Warn,
+            SignatureVerification::Require => Self::Require,
+        }
+    }
+}
+
+impl From<SignatureVerificationDocument> for SignatureVerification {
+    fn from(value: SignatureVerificationDocument) -> Self {
+        match value {
+            SignatureVerificationDocument::Ignore => Self::Ignore,
+            SignatureVerificationDocument::Warn => Self::Warn,
+            SignatureVerificationDocument::Require => Self::Require,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TrustedKeyDocument {
+    pub key_id: String,
+    pub public_key: String,
+}
+
+impl From<&TrustedKey> for TrustedKeyDocument {
+    fn from(value: &TrustedKey) -> Self {
+        Self {
+            key_id: value.key_id.clone(),
+            public_key: value.public_key.clone(),
+        }
+    }
+}
+
+impl From<TrustedKeyDocument> for TrustedKey {
+    fn from(value: TrustedKeyDocument) -> Self {
+        Self {
+            key_id: value.key_id,
+            public_key: value.public_key,
         }
     }
 }
@@ -790,8 +1900,11 @@ impl From<RevocationPolicyDocument> for RevocationPolicy {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct CertificatesConfigDocument {
+    /// PEM trust anchor for the server certificate.
     pub ca_cert: String,
+    /// PEM client certificate chain.
     pub client_cert: String,
+    /// PEM client private key.
     pub client_key: String,
 }
 
@@ -818,8 +1931,11 @@ impl From<CertificatesConfigDocument> for CertificatesConfig {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct LoggingConfigDocument {
+    /// Minimum level logged.
     pub level: LogLevelDocument,
+    /// Log line format.
     pub format: LogFormatDocument,
+    /// Config paths masked when configs are logged or dumped.
     #[serde(default)]
     pub redact: Vec<String>,
 }
@@ -844,6 +1960,39 @@ impl From<LoggingConfigDocument> for LoggingConfig {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WebhookConfigDocument {
+    /// `http://` or `https://` endpoints; every event is posted to each.
+    pub urls: Vec<String>,
+    /// Header sent with every post, e.g. `Authorization: Bearer ...`.
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    /// Bound on each delivery, TLS handshake included.
+    #[serde(default = "default_webhook_timeout_document")]
+    pub timeout: DurationDocument,
+}
+
+impl From<&WebhookConfig> for WebhookConfigDocument {
+    fn from(value: &WebhookConfig) -> Self {
+        Self {
+            urls: value.urls.clone(),
+            auth_header: value.auth_header.clone(),
+            timeout: DurationDocument::from_duration(value.timeout),
+        }
+    }
+}
+
+impl From<WebhookConfigDocument> for WebhookConfig {
+    fn from(value: WebhookConfigDocument) -> Self {
+        Self {
+            urls: value.urls,
+            auth_header: value.auth_header,
+            timeout: value.timeout.into_duration(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub(crate) enum LogLevelDocument {
@@ -934,10 +2083,26 @@ fn default_reconnect_policy_document() -> ReconnectPolicyDocument {
     ReconnectPolicyDocument::from(&ReconnectPolicy::default())
 }
 
+fn default_oversize_policy_document() -> OversizePolicyDocument {
+    OversizePolicyDocument::Drop
+}
+
+fn default_signature_verification_document() -> SignatureVerificationDocument {
+    SignatureVerificationDocument::Warn
+}
+
 fn default_send_queue_document() -> SendQueueConfigDocument {
     SendQueueConfigDocument::from(&TransportConfig::default().send_queue)
 }
 
+fn default_webhook_timeout_document() -> DurationDocument {
+    DurationDocument::from_duration(WebhookConfig::default().timeout)
+}
+
+fn default_quota_action_document() -> QuotaActionDocument {
+    QuotaActionDocument::Warn
+}
+
 fn default_bridge_cot_stale_seconds() -> u32 {
     BridgeConfig::default().cot_stale_seconds
 }
@@ -954,6 +2119,26 @@ fn default_bridge_dedup_document() -> BridgeDedupDocument {
     BridgeDedupDocument::from(&BridgeConfig::default().dedup)
 }
 
+fn default_bridge_correlator_document() -> BridgeCorrelatorDocument {
+    BridgeCorrelatorDocument::from(&BridgeConfig::default().correlator)
+}
+
+fn default_bridge_correlator_uid_policy() -> UidPolicyDocument {
+    default_bridge_correlator_document().uid_policy
+}
+
+fn default_bridge_correlator_uid_prefix() -> String {
+    default_bridge_correlator_document().uid_prefix
+}
+
+fn default_bridge_correlator_max_entries() -> usize {
+    default_bridge_correlator_document().max_entries
+}
+
+fn default_bridge_correlator_max_idle() -> Option<DurationDocument> {
+    default_bridge_correlator_document().max_idle
+}
+
 fn default_bridge_emitter_document() -> BridgeEmitterDocument {
     BridgeEmitterDocument::from(&BridgeConfig::default().emitter)
 }
@@ -962,6 +2147,85 @@ fn default_bridge_validation_document() -> BridgeValidationDocument {
     BridgeValidationDocument::from(&BridgeConfig::default().validation)
 }
 
+fn default_bridge_sensor_coverage_document() -> BridgeSensorCoverageDocument {
+    BridgeSensorCoverageDocument::from(&SensorCoverageConfig::default())
+}
+
+fn default_sensor_cot_type() -> String {
+    SensorCoverageConfig::default().sensor_cot_type
+}
+
+fn default_bridge_normalization_document() -> BridgeNormalizationDocument {
+    BridgeNormalizationDocument::from(&NormalizationConfig::default())
+}
+
+fn default_normalization_fallback_document() -> Option<SensorNormalizationDocument> {
+    NormalizationConfig::default()
+        .fallback
+        .as_ref()
+        .map(SensorNormalizationDocument::from)
+}
+
+fn default_bridge_track_quality_document() -> BridgeTrackQualityDocument {
+    BridgeTrackQualityDocument::from(&TrackQualityConfig::default())
+}
+
+fn default_track_quality_half_life() -> DurationDocument {
+    default_bridge_track_quality_document().half_life
+}
+
+fn default_track_quality_ce_growth() -> f64 {
+    TrackQualityConfig::default().ce_growth_meters_per_second
+}
+
+fn default_track_quality_le_growth() -> f64 {
+    TrackQualityConfig::default().le_growth_meters_per_second
+}
+
+fn default_bridge_cardinality_document() -> BridgeCardinalityDocument {
+    BridgeCardinalityDocument::from(&TrackCardinalityConfig::default())
+}
+
+fn default_cardinality_active_window() -> DurationDocument {
+    default_bridge_cardinality_document().active_window
+}
+
+fn default_cardinality_coalesce_above() -> usize {
+    TrackCardinalityConfig::default().coalesce_above
+}
+
+fn default_cardinality_coalesce_interval() -> DurationDocument {
+    default_bridge_cardinality_document().coalesce_interval
+}
+
+fn default_cardinality_sample_above() -> usize {
+    TrackCardinalityConfig::default().sample_above
+}
+
+fn default_cardinality_sample_one_in() -> u32 {
+    TrackCardinalityConfig::default().sample_one_in
+}
+
+fn default_cardinality_max_active_tracks() -> usize {
+    TrackCardinalityConfig::default().max_active_tracks
+}
+
+fn default_cardinality_high_priority_type_prefixes() -> Vec<String> {
+    TrackCardinalityConfig::default().high_priority_type_prefixes
+}
+
+fn default_sensor_coverage_uid_prefix() -> String {
+    SensorCoverageConfig::default().uid_prefix
+}
+
+fn default_sensor_coverage_arc_segments() -> u16 {
+    SensorCoverageConfig::default().arc_segments
+}
+
+fn default_coverage_style_document() -> CoverageStyleDocument {
+    CoverageStyleDocument::from(&CoverageStyle::default())
+}
+
 fn default_true() -> bool {
     true
 }
@@ -0,0 +1,125 @@
+//! Config documents written against earlier releases must keep loading with
+//! the same meaning. Fixtures under `tests/fixtures/compat` are frozen: add a
+//! new file when the format grows instead of editing an existing one.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rustak_bridge::TimePolicyMode;
+use rustak_config::{
+    CryptoProvider, LimitsBinding, LogFormat, LogLevel, RevocationPolicy, RustakConfig,
+};
+use rustak_transport::{OversizePolicy, Protocol, SendQueueMode, UdpTarget};
+use rustak_wire::WireFormat;
+
+fn load_fixture(name: &str) -> RustakConfig {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("..")
+        .join("tests")
+        .join("fixtures")
+        .join("compat")
+        .join(name);
+    RustakConfig::load(&path).unwrap_or_else(|error| panic!("load {}: {error}", path.display()))
+}
+
+#[test]
+fn v0_1_tak_client_config_still_loads() {
+    let config = load_fixture("config_v0_1_tak_client.yaml");
+    let transport = &config.transport;
+
+    assert_eq!(
+        transport.protocol,
+        Protocol::Tls {
+            addr: SocketAddr::from(([10, 0, 0, 5], 8089)),
+            server_name: "tak.example.mil".to_owned(),
+        }
+    );
+    assert_eq!(transport.wire_format, WireFormat::TakProtocolV1);
+    assert_eq!(transport.limits.max_frame_bytes, 1_048_576);
+    assert_eq!(transport.limits.max_detail_elements, 256);
+    assert_eq!(transport.read_timeout, Duration::from_secs(30));
+    assert_eq!(transport.write_timeout, Duration::from_millis(15_000));
+    let keepalive = transport.keepalive.as_ref().expect("keepalive");
+    assert_eq!(keepalive.interval, Duration::from_secs(10));
+    assert_eq!(
+        transport.reconnect_policy.initial_delay,
+        Duration::from_millis(500)
+    );
+    assert_eq!(
+        transport.reconnect_policy.max_delay,
+        Duration::from_secs(120)
+    );
+    assert_eq!(transport.reconnect_policy.max_retries, Some(10));
+    assert_eq!(
+        transport.send_queue.mode,
+        SendQueueMode::CoalesceLatestByUid
+    );
+
+    let crypto = config.crypto.as_ref().expect("crypto");
+    assert_eq!(crypto.provider, CryptoProvider::Ring);
+    assert_eq!(crypto.revocation, RevocationPolicy::Prefer);
+    assert!(crypto.server_spki_pin.is_some());
+    assert!(crypto.signing.is_none());
+    let certificates = config.certificates.as_ref().expect("certificates");
+    assert_eq!(certificates.client_key, "/etc/rustak/client-key.pem");
+    let logging = config.logging.as_ref().expect("logging");
+    assert_eq!(
+        (logging.level, logging.format),
+        (LogLevel::Info, LogFormat::Json)
+    );
+    assert_eq!(logging.redact, ["crypto.server_spki_pin"]);
+}
+
+#[test]
+fn v0_1_sapient_bridge_config_still_loads() {
+    let config = load_fixture("config_v0_1_sapient_bridge.yaml");
+
+    assert_eq!(
+        config.transport.protocol,
+        Protocol::Udp {
+            bind_addr: SocketAddr::from(([0, 0, 0, 0], 6969)),
+            target: UdpTarget::Multicast {
+                group: Ipv4Addr::new(239, 2, 3, 1),
+                port: 6969,
+            },
+        }
+    );
+    let mtu = config.transport.mtu_safety.as_ref().expect("mtu safety");
+    assert_eq!(mtu.max_udp_payload_bytes, 1200);
+    assert_eq!(mtu.oversize, OversizePolicy::Chunk);
+
+    let sapient = config.sapient.as_ref().expect("sapient");
+    let LimitsBinding::Reference(reference) = &sapient.limits else {
+        panic!("sapient limits should stay a reference");
+    };
+    assert_eq!(reference.path(), "transport.limits");
+    let resolved = config.resolve_sapient().expect("resolve").expect("sapient");
+    assert_eq!(resolved.limits, config.transport.limits);
+    assert_eq!(resolved.read_timeout, Duration::from_secs(20));
+
+    let bridge = config.bridge.as_ref().expect("bridge");
+    assert_eq!(bridge.time_policy, TimePolicyMode::ObservedWithSkewClamp);
+    assert_eq!(bridge.dedup.window, Duration::from_millis(750));
+    assert_eq!(bridge.emitter.max_updates_per_second, 10);
+    assert!(bridge.sensor_coverage.emit_sensor_location);
+    // Sections added after 0.1 fall back to their defaults.
+    assert!(bridge.normalization.sensors.is_empty());
+    assert!(bridge.normalization.fallback.is_some());
+}
+
+#[test]
+fn compat_fixtures_survive_redacted_round_trip() {
+    for name in [
+        "config_v0_1_tak_client.yaml",
+        "config_v0_1_sapient_bridge.yaml",
+    ] {
+        let config = load_fixture(name);
+        let yaml = config.to_redacted_yaml().expect("redacted yaml");
+        let reparsed = RustakConfig::from_yaml_str(&yaml)
+            .unwrap_or_else(|error| panic!("{name} re-parse: {error}"));
+        assert_eq!(reparsed.transport, config.transport, "{name}");
+        assert_eq!(reparsed.bridge, config.bridge, "{name}");
+    }
+}
@@ -0,0 +1,339 @@
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
+
+use crate::event::{CotEvent, DetailNode};
+use crate::model::Position;
+use crate::time::TimestampUtc;
+
+/// CoT type of a GeoChat message. Delivery and read receipts extend it
+/// (`b-t-f-d`, `b-t-f-r`), so it doubles as the chat type prefix.
+pub const GEOCHAT_COT_TYPE: &str = "b-t-f";
+
+/// `how` ATAK stamps on chat events.
+pub const GEOCHAT_HOW: &str = "h-g-i-g-o";
+
+/// Room id and name of the broadcast room every client joins.
+pub const ALL_CHAT_ROOMS: &str = "All Chat Rooms";
+
+/// `parent` of `All Chat Rooms` and direct messages.
+const ROOT_CONTACT_GROUP: &str = "RootContactGroup";
+/// `parent` of named group rooms.
+const USER_GROUPS: &str = "UserGroups";
+
+/// Whether `cot_type` is a GeoChat message or receipt.
+#[must_use]
+pub fn is_chat_type(cot_type: &str) -> bool {
+    cot_type.starts_with(GEOCHAT_COT_TYPE)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatDestination {
+    AllChatRooms,
+    /// One contact, addressed by uid and shown by callsign.
+    Direct {
+        uid: String,
+        callsign: String,
+    },
+    /// A named group room.
+    Room {
+        id: String,
+        name: String,
+    },
+}
+
+impl ChatDestination {
+    /// The `id` ATAK keys the conversation by.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        match self {
+            Self::AllChatRooms => ALL_CHAT_ROOMS,
+            Self::Direct { uid, .. } => uid,
+            Self::Room { id, .. } => id,
+        }
+    }
+
+    /// The `chatroom` name shown to the user.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::AllChatRooms => ALL_CHAT_ROOMS,
+            Self::Direct { callsign, .. } => callsign,
+            Self::Room { name, .. } => name,
+        }
+    }
+}
+
+/// A GeoChat message, carried in CoT as a `b-t-f` event whose uid is
+/// `GeoChat.<sender uid>.<room id>.<message id>` and whose detail holds
+/// `<__chat>`, a `<link>` to the sender and the text in `<remarks>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    pub message_id: String,
+    pub sender_uid: String,
+    pub sender_callsign: String,
+    pub destination: ChatDestination,
+    pub text: String,
+    pub time: TimestampUtc,
+}
+
+impl ChatMessage {
+    #[must_use]
+    pub fn event_uid(&self) -> String {
+        format!(
+            "GeoChat.{}.{}.{}",
+            self.sender_uid,
+            self.destination.id(),
+            self.message_id
+        )
+    }
+
+    /// The chat event, placed at the sender's `point` and stale at `stale`.
+    /// Direct messages also carry `<marti><dest callsign=.../></marti>` so
+    /// TAK Server delivers them only to the recipient.
+    #[must_use]
+    pub fn to_event(&self, point: Position, stale: TimestampUtc) -> CotEvent {
+        let room = self.destination.id();
+        let parent = match self.destination {
+            ChatDestination::Room { .. } => USER_GROUPS,
+            ChatDestination::AllChatRooms | ChatDestination::Direct { .. } => ROOT_CONTACT_GROUP,
+        };
+        let chat = DetailNode::new("__chat")
+            .with_attribute("parent", parent)
+            .with_attribute("groupOwner", "false")
+            .with_attribute("messageId", &self.message_id)
+            .with_attribute("chatroom", self.destination.name())
+            .with_attribute("id", room)
+            .with_attribute("senderCallsign", &self.sender_callsign)
+            .with_child(
+                DetailNode::new("chatgrp")
+                    .with_attribute("uid0", &self.sender_uid)
+                    .with_attribute("uid1", room)
+                    .with_attribute("id", room),
+            );
+        let link = DetailNode::new("link")
+            .with_attribute("uid", &self.sender_uid)
+            .with_attribute("type", "a-f-G-U-C")
+            .with_attribute("relation", "p-p");
+        let remarks = DetailNode::new("remarks")
+            .with_attribute("source", format!("BAO.F.ATAK.{}", self.sender_uid))
+            .with_attribute("to", room)
+            .with_attribute("time", self.time.to_rfc3339_millis())
+            .with_text(&self.text);
+
+        let mut event = CotEvent::new(self.event_uid(), GEOCHAT_COT_TYPE, self.time, stale, point);
+        event.how = Some(GEOCHAT_HOW.to_owned());
+        event.detail = vec![chat, link, remarks];
+        if let ChatDestination::Direct { callsign, .. } = &self.destination {
+            event.detail.push(
+                DetailNode::new("marti")
+                    .with_child(DetailNode::new("dest").with_attribute("callsign", callsign)),
+            );
+        }
+        event
+    }
+
+    /// Reads a `b-t-f` message event. Missing `messageId` and sender uid
+    /// fall back to the `GeoChat.` uid convention; the time is the event's.
+    pub fn from_event(event: &CotEvent) -> Result<Self, ChatError> {
+        if event.cot_type != GEOCHAT_COT_TYPE {
+            return Err(ChatError::NotChat {
+                cot_type: event.cot_type.clone(),
+            });
+        }
+        let chat = event
+            .detail_element("__chat")
+            .ok_or(ChatError::MissingElement { name: "__chat" })?;
+        let required = |name: &'static str| {
+            chat.attribute(name)
+                .map(str::to_owned)
+                .ok_or(ChatError::MissingAttribute {
+                    element: "__chat",
+                    name,
+                })
+        };
+        let id = required("id")?;
+        let room_name = chat.attribute("chatroom").unwrap_or(&id).to_owned();
+
+        // GeoChat.<sender>.<room>.<message>; the room may contain dots, the
+        // sender uid and message id in practice do not.
+        let uid_parts = event
+            .uid
+            .strip_prefix("GeoChat.")
+            .and_then(|rest| rest.split_once('.'))
+            .and_then(|(sender, rest)| Some((sender, rest.rsplit_once('.')?.1)));
+        let sender_uid = chat
+            .child("chatgrp")
+            .and_then(|group| group.attribute("uid0"))
+            .or_else(|| event.detail_element("link")?.attribute("uid"))
+            .or(uid_parts.map(|(sender, _)| sender))
+            .ok_or(ChatError::MissingAttribute {
+                element: "chatgrp",
+                name: "uid0",
+            })?
+            .to_owned();
+        let message_id = chat
+            .attribute("messageId")
+            .or(uid_parts.map(|(_, message)| message))
+            .ok_or(ChatError::MissingAttribute {
+                element: "__chat",
+                name: "messageId",
+            })?
+            .to_owned();
+
+        let destination = if id == ALL_CHAT_ROOMS {
+            ChatDestination::AllChatRooms
+        } else if chat.attribute("parent") == Some(ROOT_CONTACT_GROUP) {
+            ChatDestination::Direct {
+                uid: id,
+                callsign: room_name,
+            }
+        } else {
+            ChatDestination::Room {
+                id,
+                name: room_name,
+            }
+        };
+
+        Ok(Self {
+            message_id,
+            sender_uid,
+            sender_callsign: chat
+                .attribute("senderCallsign")
+                .unwrap_or_default()
+                .to_owned(),
+            destination,
+            text: event
+                .detail_element("remarks")
+                .map(|remarks| remarks.text.clone())
+                .unwrap_or_default(),
+            time: event.time,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatError {
+    NotChat {
+        cot_type: String,
+    },
+    MissingElement {
+        name: &'static str,
+    },
+    MissingAttribute {
+        element: &'static str,
+        name: &'static str,
+    },
+}
+
+impl CodedError for ChatError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotChat { .. } => ErrorCode::new("CORE", 301),
+            Self::MissingElement { .. } => ErrorCode::new("CORE", 302),
+            Self::MissingAttribute { .. } => ErrorCode::new("CORE", 303),
+        }
+    }
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotChat { cot_type } => {
+                write!(f, "CoT type `{cot_type}` is not a GeoChat message")
+            }
+            Self::MissingElement { name } => write!(f, "GeoChat event has no <{name}> detail"),
+            Self::MissingAttribute { element, name } => {
+                write!(f, "GeoChat <{element}> is missing the `{name}` attribute")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+#[cfg(test)]
+mod tests {
+    use rustak_limits::Limits;
+
+    use super::{ChatDestination, ChatError, ChatMessage, ALL_CHAT_ROOMS};
+    use crate::{CotEvent, Position, TimestampUtc};
+
+    fn message(destination: ChatDestination) -> ChatMessage {
+        ChatMessage {
+            message_id: "5b9f2e1c".to_owned(),
+            sender_uid: "ANDROID-1".to_owned(),
+            sender_callsign: "ALPHA".to_owned(),
+            destination,
+            text: "RV at <checkpoint> 2 & hold".to_owned(),
+            time: TimestampUtc::from_unix_seconds_nanos(1_700_000_000, 0).expect("time"),
+        }
+    }
+
+    fn round_trip(message: &ChatMessage) -> (CotEvent, ChatMessage) {
+        let stale = TimestampUtc::from_unix_seconds_nanos(1_700_086_400, 0).expect("stale");
+        let point = Position::new(51.5, -0.12).expect("point");
+        let xml = message.to_event(point, stale).to_xml();
+        let event = CotEvent::from_xml(&xml, &Limits::conservative_defaults()).expect("parse");
+        let decoded = ChatMessage::from_event(&event).expect("chat");
+        (event, decoded)
+    }
+
+    #[test]
+    fn all_rooms_direct_and_group_messages_round_trip() {
+        let broadcast = message(ChatDestination::AllChatRooms);
+        let (event, decoded) = round_trip(&broadcast);
+        assert_eq!(event.uid, "GeoChat.ANDROID-1.All Chat Rooms.5b9f2e1c");
+        assert_eq!(event.cot_type, "b-t-f");
+        assert_eq!(
+            event
+                .detail_element("remarks")
+                .map(|remarks| remarks.text.as_str()),
+            Some("RV at <checkpoint> 2 & hold")
+        );
+        assert!(event.detail_element("marti").is_none());
+        assert_eq!(decoded, broadcast);
+
+        let direct = message(ChatDestination::Direct {
+            uid: "ANDROID-2".to_owned(),
+            callsign: "BRAVO".to_owned(),
+        });
+        let (event, decoded) = round_trip(&direct);
+        let dest = event
+            .detail_element("marti")
+            .and_then(|marti| marti.child("dest"))
+            .and_then(|dest| dest.attribute("callsign"));
+        assert_eq!(dest, Some("BRAVO"));
+        assert_eq!(decoded, direct);
+
+        let room = message(ChatDestination::Room {
+            id: "Cyan".to_owned(),
+            name: "Cyan Team".to_owned(),
+        });
+        assert_eq!(round_trip(&room).1, room);
+    }
+
+    #[test]
+    fn sparse_atak_events_fall_back_to_the_uid_convention() {
+        let xml = r#"<event version="2.0" uid="GeoChat.ANDROID-9.All Chat Rooms.m-1" type="b-t-f" how="h-g-i-g-o" time="2024-05-01T10:00:00.000Z" start="2024-05-01T10:00:00.000Z" stale="2024-05-02T10:00:00.000Z"><point lat="0" lon="0" hae="0" ce="9999999" le="9999999"/><detail><__chat chatroom="All Chat Rooms" id="All Chat Rooms" senderCallsign="NINER"/><remarks>hi</remarks></detail></event>"#;
+        let event = CotEvent::from_xml(xml, &Limits::conservative_defaults()).expect("parse");
+        let decoded = ChatMessage::from_event(&event).expect("chat");
+        assert_eq!(decoded.sender_uid, "ANDROID-9");
+        assert_eq!(decoded.message_id, "m-1");
+        assert_eq!(decoded.destination.id(), ALL_CHAT_ROOMS);
+        assert_eq!(decoded.text, "hi");
+
+        let mut receipt = event.clone();
+        receipt.cot_type = "b-t-f-r".to_owned();
+        assert!(matches!(
+            ChatMessage::from_event(&receipt),
+            Err(ChatError::NotChat { .. })
+        ));
+        let mut bare = event;
+        bare.detail.clear();
+        assert_eq!(
+            ChatMessage::from_event(&bare),
+            Err(ChatError::MissingElement { name: "__chat" })
+        );
+    }
+}
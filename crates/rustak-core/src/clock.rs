@@ -0,0 +1,373 @@
+//! Wall-clock providers and local clock-confidence reporting.
+//!
+//! Every place that stamps wall time (envelopes, CoT `time`/`start`/`stale`,
+//! recording headers) can take a [`TimeSource`] instead of reading the system
+//! clock directly. Besides the time itself a source reports a [`ClockStatus`]:
+//! how far off the local clock may be, as estimated by chrony or ntpd. Events
+//! stamped while that confidence is poor can carry a [`clock_annotation`] so
+//! consumers know not to trust their timestamps too far.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::time::TimestampUtc;
+
+/// Supplies wall time and how much it can be trusted.
+pub trait TimeSource: fmt::Debug + Send + Sync {
+    fn now_system(&self) -> SystemTime;
+
+    fn now(&self) -> TimestampUtc {
+        TimestampUtc::from_system_time(self.now_system())
+    }
+
+    fn status(&self) -> ClockStatus {
+        ClockStatus::unknown("system")
+    }
+}
+
+/// The system clock, with no synchronisation information.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now_system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClockConfidence {
+    /// Synchronised with an error bound within [`ClockThresholds::degraded`].
+    Synchronized,
+    /// Synchronised, but the error bound exceeds [`ClockThresholds::degraded`].
+    Degraded,
+    /// Not synchronised, or the error bound exceeds
+    /// [`ClockThresholds::unsynchronized`].
+    Unsynchronized,
+    /// No synchronisation information is available.
+    Unknown,
+}
+
+impl ClockConfidence {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Synchronized => "synchronized",
+            Self::Degraded => "degraded",
+            Self::Unsynchronized => "unsynchronized",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Error bounds separating [`ClockConfidence`] levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockThresholds {
+    pub degraded: Duration,
+    pub unsynchronized: Duration,
+}
+
+impl Default for ClockThresholds {
+    fn default() -> Self {
+        Self {
+            degraded: Duration::from_millis(100),
+            unsynchronized: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ClockThresholds {
+    #[must_use]
+    pub fn classify(&self, estimated_error: Duration) -> ClockConfidence {
+        if estimated_error > self.unsynchronized {
+            ClockConfidence::Unsynchronized
+        } else if estimated_error > self.degraded {
+            ClockConfidence::Degraded
+        } else {
+            ClockConfidence::Synchronized
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClockStatus {
+    pub confidence: ClockConfidence,
+    /// Upper bound on the local clock's offset from true time, if known.
+    pub estimated_error: Option<Duration>,
+    /// Where the estimate came from: `system`, `chrony` or `ntp`.
+    pub source: &'static str,
+}
+
+impl ClockStatus {
+    #[must_use]
+    pub const fn unknown(source: &'static str) -> Self {
+        Self {
+            confidence: ClockConfidence::Unknown,
+            estimated_error: None,
+            source,
+        }
+    }
+
+    /// `true` when timestamps should be flagged to consumers. Missing
+    /// information alone does not count as poor.
+    #[must_use]
+    pub const fn is_poor(&self) -> bool {
+        matches!(
+            self.confidence,
+            ClockConfidence::Degraded | ClockConfidence::Unsynchronized
+        )
+    }
+
+    fn from_error(source: &'static str, error: Duration, thresholds: &ClockThresholds) -> Self {
+        Self {
+            confidence: thresholds.classify(error),
+            estimated_error: Some(error),
+            source,
+        }
+    }
+
+    fn unsynchronized(source: &'static str) -> Self {
+        Self {
+            confidence: ClockConfidence::Unsynchronized,
+            estimated_error: None,
+            source,
+        }
+    }
+}
+
+/// `<__clock>` detail element for events stamped while `status` is poor,
+/// `None` otherwise.
+#[must_use]
+pub fn clock_annotation(status: &ClockStatus) -> Option<String> {
+    if !status.is_poor() {
+        return None;
+    }
+    let error = status
+        .estimated_error
+        .map(|error| format!(" error_ms=\"{:.1}\"", error.as_secs_f64() * 1_000.0))
+        .unwrap_or_default();
+    Some(format!(
+        "<__clock confidence=\"{}\"{error} source=\"{}\"/>",
+        status.confidence.as_str(),
+        status.source
+    ))
+}
+
+/// Parses `chronyc tracking` output. The error bound is chrony's own:
+/// `|system time offset| + root dispersion + root delay / 2`.
+#[must_use]
+pub fn parse_chrony_tracking(text: &str, thresholds: &ClockThresholds) -> Option<ClockStatus> {
+    let mut offset = None;
+    let mut root_delay = None;
+    let mut root_dispersion = None;
+    let mut leap_status = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "System time" => offset = leading_seconds(value),
+            "Root delay" => root_delay = leading_seconds(value),
+            "Root dispersion" => root_dispersion = leading_seconds(value),
+            "Leap status" => leap_status = Some(value),
+            _ => {}
+        }
+    }
+
+    if leap_status? == "Not synchronised" {
+        return Some(ClockStatus::unsynchronized("chrony"));
+    }
+    let error = offset?.abs() + root_dispersion? + root_delay? / 2.0;
+    Some(ClockStatus::from_error(
+        "chrony",
+        Duration::try_from_secs_f64(error).ok()?,
+        thresholds,
+    ))
+}
+
+/// Parses `ntpq -c rv` output (`key=value` pairs, times in milliseconds).
+/// Uses the same bound as chrony: `|offset| + rootdisp + rootdelay / 2`.
+#[must_use]
+pub fn parse_ntpq_readvar(text: &str, thresholds: &ClockThresholds) -> Option<ClockStatus> {
+    let mut offset = None;
+    let mut root_delay = None;
+    let mut root_dispersion = None;
+    let mut leap = None;
+    for pair in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let Some((key, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let value = value.trim_matches('"');
+        match key {
+            "offset" => offset = value.parse::<f64>().ok(),
+            "rootdelay" => root_delay = value.parse::<f64>().ok(),
+            "rootdisp" => root_dispersion = value.parse::<f64>().ok(),
+            "leap" => leap = Some(value),
+            _ => {}
+        }
+    }
+
+    if matches!(leap?, "11" | "3") {
+        return Some(ClockStatus::unsynchronized("ntp"));
+    }
+    let error_ms = offset?.abs() + root_dispersion? + root_delay? / 2.0;
+    Some(ClockStatus::from_error(
+        "ntp",
+        Duration::try_from_secs_f64(error_ms / 1_000.0).ok()?,
+        thresholds,
+    ))
+}
+
+/// Parses either `chronyc tracking` or `ntpq -c rv` output.
+#[must_use]
+pub fn parse_clock_status(text: &str, thresholds: &ClockThresholds) -> Option<ClockStatus> {
+    parse_chrony_tracking(text, thresholds).or_else(|| parse_ntpq_readvar(text, thresholds))
+}
+
+fn leading_seconds(value: &str) -> Option<f64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// System clock whose [`ClockStatus`] comes from a periodically refreshed
+/// status file, for example `chronyc tracking > /run/rustak/clock` from a
+/// timer. An unreadable or unparseable file reports
+/// [`ClockConfidence::Unknown`].
+#[derive(Debug)]
+pub struct StatusFileClock {
+    path: PathBuf,
+    thresholds: ClockThresholds,
+    refresh_interval: Duration,
+    cached: Mutex<Option<(Instant, ClockStatus)>>,
+}
+
+impl StatusFileClock {
+    /// Re-reads the file at most once per second by default.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, thresholds: ClockThresholds) -> Self {
+        Self {
+            path: path.into(),
+            thresholds,
+            refresh_interval: Duration::from_secs(1),
+            cached: Mutex::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    fn read_status(&self) -> ClockStatus {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| parse_clock_status(&text, &self.thresholds))
+            .unwrap_or(ClockStatus::unknown("system"))
+    }
+}
+
+impl TimeSource for StatusFileClock {
+    fn now_system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn status(&self) -> ClockStatus {
+        let mut cached = self
+            .cached
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some((read_at, status)) = cached.as_ref() {
+            if read_at.elapsed() < self.refresh_interval {
+                return status.clone();
+            }
+        }
+        let status = self.read_status();
+        *cached = Some((Instant::now(), status.clone()));
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        clock_annotation, parse_chrony_tracking, parse_clock_status, parse_ntpq_readvar,
+        ClockConfidence, ClockThresholds, StatusFileClock, TimeSource,
+    };
+
+    const CHRONY_TRACKING: &str = "\
+Reference ID    : A9FEA97B (169.254.169.123)
+Stratum         : 4
+Ref time (UTC)  : Mon Jan 01 00:00:00 2024
+System time     : 0.150000000 seconds fast of NTP time
+Last offset     : -0.000001234 seconds
+RMS offset      : 0.000023456 seconds
+Frequency       : 1.234 ppm slow
+Root delay      : 0.020000000 seconds
+Root dispersion : 0.040000000 seconds
+Update interval : 16.0 seconds
+Leap status     : Normal
+";
+
+    #[test]
+    fn chrony_tracking_bounds_error_and_classifies() {
+        let status =
+            parse_chrony_tracking(CHRONY_TRACKING, &ClockThresholds::default()).expect("parse");
+        assert_eq!(status.source, "chrony");
+        assert_eq!(status.confidence, ClockConfidence::Degraded);
+        let error = status.estimated_error.expect("error bound");
+        assert!((error.as_secs_f64() - 0.2).abs() < 1e-9, "{error:?}");
+        assert_eq!(
+            clock_annotation(&status).as_deref(),
+            Some("<__clock confidence=\"degraded\" error_ms=\"200.0\" source=\"chrony\"/>")
+        );
+
+        let unsynced = CHRONY_TRACKING.replace(
+            "Leap status     : Normal",
+            "Leap status     : Not synchronised",
+        );
+        let status = parse_clock_status(&unsynced, &ClockThresholds::default()).expect("parse");
+        assert_eq!(status.confidence, ClockConfidence::Unsynchronized);
+        assert_eq!(status.estimated_error, None);
+    }
+
+    #[test]
+    fn ntpq_readvar_reports_milliseconds() {
+        let text = "associd=0 status=0615 leap_none, sync_ntp, 1 event, clock_sync,\n\
+version=\"ntpd 4.2.8p15\", leap=00, stratum=2, rootdelay=2.000, rootdisp=3.000,\n\
+offset=-1.000, frequency=-5.3, sys_jitter=0.2";
+        let status = parse_ntpq_readvar(text, &ClockThresholds::default()).expect("parse");
+        assert_eq!(status.source, "ntp");
+        assert_eq!(status.confidence, ClockConfidence::Synchronized);
+        assert_eq!(status.estimated_error, Some(Duration::from_millis(5)));
+        assert_eq!(clock_annotation(&status), None);
+
+        let alarm = parse_clock_status(
+            &text.replace("leap=00", "leap=11"),
+            &ClockThresholds::default(),
+        )
+        .expect("parse");
+        assert_eq!(alarm.confidence, ClockConfidence::Unsynchronized);
+        assert!(parse_clock_status("garbage", &ClockThresholds::default()).is_none());
+    }
+
+    #[test]
+    fn status_file_clock_falls_back_to_unknown() {
+        let dir = std::env::temp_dir().join(format!("rustak-clock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        let path = dir.join("tracking");
+
+        let clock = StatusFileClock::new(&path, ClockThresholds::default())
+            .with_refresh_interval(Duration::ZERO);
+        assert_eq!(clock.status().confidence, ClockConfidence::Unknown);
+
+        std::fs::write(&path, CHRONY_TRACKING).expect("write");
+        assert_eq!(clock.status().confidence, ClockConfidence::Degraded);
+        assert!(clock.now().unix_seconds() > 1_700_000_000);
+    }
+}
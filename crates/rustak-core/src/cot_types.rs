@@ -0,0 +1,601 @@
+//! CoT type string helpers.
+//!
+//! Atom types (`a-<affiliation>-<dimension>-<function...>`) follow the
+//! MIL-STD-2525 hierarchy. [`CotType`] parses them into their parts and
+//! converts to and from 15-character 2525B symbol identification codes
+//! (SIDCs); [`describe_cot_type`] turns them into operator-readable labels
+//! such as `Hostile Ground Unit — Armor`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rustak_limits::{CodedError, ErrorCode};
+
+/// CoT type prefix of emergency beacons (`b-a-o-tbl` 911 alert, `b-a-o-pan`
+/// ring the bell, `b-a-o-opn` troops in contact, `b-a-o-can` cancel).
+pub const EMERGENCY_COT_TYPE_PREFIX: &str = "b-a-o-";
+
+/// Length of a MIL-STD-2525B SIDC.
+pub const SIDC_LEN: usize = 15;
+
+/// Function codes a SIDC has room for.
+const SIDC_FUNCTION_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Affiliation {
+    Pending,
+    Unknown,
+    AssumedFriend,
+    Friend,
+    Neutral,
+    Suspect,
+    Hostile,
+    /// Exercise friend playing a suspect.
+    Joker,
+    /// Exercise friend playing a hostile.
+    Faker,
+    None,
+}
+
+impl Affiliation {
+    /// The CoT code; the SIDC uses the same letter in upper case.
+    #[must_use]
+    pub const fn code(self) -> char {
+        match self {
+            Self::Pending => 'p',
+            Self::Unknown => 'u',
+            Self::AssumedFriend => 'a',
+            Self::Friend => 'f',
+            Self::Neutral => 'n',
+            Self::Suspect => 's',
+            Self::Hostile => 'h',
+            Self::Joker => 'j',
+            Self::Faker => 'k',
+            Self::None => 'o',
+        }
+    }
+
+    /// Reads a CoT or SIDC affiliation code, in either case.
+    #[must_use]
+    pub fn from_code(code: char) -> Option<Self> {
+        Some(match code.to_ascii_lowercase() {
+            'p' => Self::Pending,
+            'u' => Self::Unknown,
+            'a' => Self::AssumedFriend,
+            'f' => Self::Friend,
+            'n' => Self::Neutral,
+            's' => Self::Suspect,
+            'h' => Self::Hostile,
+            'j' => Self::Joker,
+            'k' => Self::Faker,
+            'o' => Self::None,
+            _ => return None,
+        })
+    }
+
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Pending => "Pending",
+            Self::Unknown => "Unknown",
+            Self::AssumedFriend => "Assumed Friend",
+            Self::Friend => "Friendly",
+            Self::Neutral => "Neutral",
+            Self::Suspect => "Suspect",
+            Self::Hostile => "Hostile",
+            Self::Joker => "Joker",
+            Self::Faker => "Faker",
+            Self::None => "None",
+        }
+    }
+
+    #[must_use]
+    pub const fn is_friendly(self) -> bool {
+        matches!(self, Self::Friend | Self::AssumedFriend)
+    }
+
+    /// Hostile and suspect, including the exercise joker and faker, which
+    /// are drawn with hostile frames.
+    #[must_use]
+    pub const fn is_hostile(self) -> bool {
+        matches!(
+            self,
+            Self::Hostile | Self::Suspect | Self::Joker | Self::Faker
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum BattleDimension {
+    Space,
+    Air,
+    Ground,
+    SeaSurface,
+    Subsurface,
+    Sof,
+    Other,
+}
+
+impl BattleDimension {
+    /// The code CoT and the SIDC share.
+    #[must_use]
+    pub const fn code(self) -> char {
+        match self {
+            Self::Space => 'P',
+            Self::Air => 'A',
+            Self::Ground => 'G',
+            Self::SeaSurface => 'S',
+            Self::Subsurface => 'U',
+            Self::Sof => 'F',
+            Self::Other => 'X',
+        }
+    }
+
+    #[must_use]
+    pub fn from_code(code: char) -> Option<Self> {
+        Some(match code.to_ascii_uppercase() {
+            'P' => Self::Space,
+            'A' => Self::Air,
+            'G' => Self::Ground,
+            'S' => Self::SeaSurface,
+            'U' => Self::Subsurface,
+            'F' => Self::Sof,
+            'X' => Self::Other,
+            _ => return None,
+        })
+    }
+
+    #[must_use]
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Space => "Space",
+            Self::Air => "Air",
+            Self::Ground => "Ground",
+            Self::SeaSurface => "Sea Surface",
+            Self::Subsurface => "Subsurface",
+            Self::Sof => "SOF",
+            Self::Other => "Other",
+        }
+    }
+}
+
+/// A parsed atom CoT type, e.g. `a-h-G-U-C-A` (hostile ground armor).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CotType {
+    pub affiliation: Affiliation,
+    pub dimension: BattleDimension,
+    /// Function codes after the dimension, most general first: `["U", "C",
+    /// "A"]` for armor.
+    pub function: Vec<String>,
+}
+
+impl CotType {
+    #[must_use]
+    pub fn new(affiliation: Affiliation, dimension: BattleDimension) -> Self {
+        Self {
+            affiliation,
+            dimension,
+            function: Vec::new(),
+        }
+    }
+
+    /// Parses an atom type. Non-atom types such as `b-t-f` are
+    /// [`CotTypeError::NotAtom`].
+    pub fn parse(cot_type: &str) -> Result<Self, CotTypeError> {
+        let not_atom = || CotTypeError::NotAtom {
+            cot_type: cot_type.to_owned(),
+        };
+        let mut parts = cot_type.split('-');
+        if parts.next() != Some("a") {
+            return Err(not_atom());
+        }
+        let affiliation = parts.next().ok_or_else(not_atom)?;
+        let dimension = parts.next().ok_or_else(not_atom)?;
+        let affiliation = single_char(affiliation)
+            .and_then(Affiliation::from_code)
+            .ok_or_else(|| CotTypeError::UnknownAffiliation {
+                code: affiliation.to_owned(),
+            })?;
+        let dimension = single_char(dimension)
+            .and_then(BattleDimension::from_code)
+            .ok_or_else(|| CotTypeError::UnknownDimension {
+                code: dimension.to_owned(),
+            })?;
+        let function = parts
+            .map(|code| {
+                if code.is_empty() {
+                    Err(CotTypeError::EmptyFunctionCode {
+                        cot_type: cot_type.to_owned(),
+                    })
+                } else {
+                    Ok(code.to_owned())
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            affiliation,
+            dimension,
+            function,
+        })
+    }
+
+    /// Reads a 2525B warfighting SIDC (`S` coding scheme). Status, modifiers
+    /// and country are dropped; the function ends at the first `-` or `*`.
+    pub fn from_sidc(sidc: &str) -> Result<Self, CotTypeError> {
+        let invalid = |reason: &'static str| CotTypeError::InvalidSidc {
+            sidc: sidc.to_owned(),
+            reason,
+        };
+        let chars: Vec<char> = sidc.chars().collect();
+        if chars.len() != SIDC_LEN {
+            return Err(invalid("expected 15 characters"));
+        }
+        if !chars[0].eq_ignore_ascii_case(&'S') {
+            return Err(invalid(
+                "only the warfighting coding scheme `S` maps to CoT",
+            ));
+        }
+        let affiliation =
+            Affiliation::from_code(chars[1]).ok_or_else(|| invalid("unknown affiliation"))?;
+        let dimension =
+            BattleDimension::from_code(chars[2]).ok_or_else(|| invalid("unknown dimension"))?;
+        let function = chars[4..4 + SIDC_FUNCTION_LEN]
+            .iter()
+            .take_while(|code| !matches!(code, '-' | '*'))
+            .map(|code| {
+                if code.is_ascii_alphanumeric() {
+                    Ok(code.to_ascii_uppercase().to_string())
+                } else {
+                    Err(invalid("function codes must be letters or digits"))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            affiliation,
+            dimension,
+            function,
+        })
+    }
+
+    /// The 2525B SIDC with present status and no modifiers, e.g.
+    /// `SHGPUCA--------`. Fails when a function code is not one character or
+    /// there are more than six.
+    pub fn to_sidc(&self) -> Result<String, CotTypeError> {
+        let representable = self.function.len() <= SIDC_FUNCTION_LEN
+            && self
+                .function
+                .iter()
+                .all(|code| single_char(code).is_some_and(|code| code.is_ascii_alphanumeric()));
+        if !representable {
+            return Err(CotTypeError::NotSidcRepresentable {
+                cot_type: self.to_string(),
+            });
+        }
+        let mut sidc = String::with_capacity(SIDC_LEN);
+        sidc.push('S');
+        sidc.push(self.affiliation.code().to_ascii_uppercase());
+        sidc.push(self.dimension.code());
+        sidc.push('P');
+        for code in &self.function {
+            sidc.push_str(&code.to_ascii_uppercase());
+        }
+        while sidc.len() < SIDC_LEN {
+            sidc.push('-');
+        }
+        Ok(sidc)
+    }
+
+    #[must_use]
+    pub fn is_friendly(&self) -> bool {
+        self.affiliation.is_friendly()
+    }
+
+    #[must_use]
+    pub fn is_hostile(&self) -> bool {
+        self.affiliation.is_hostile()
+    }
+
+    #[must_use]
+    pub fn is_air(&self) -> bool {
+        self.dimension == BattleDimension::Air
+    }
+
+    #[must_use]
+    pub fn is_ground(&self) -> bool {
+        self.dimension == BattleDimension::Ground
+    }
+
+    /// Sea surface or subsurface.
+    #[must_use]
+    pub fn is_maritime(&self) -> bool {
+        matches!(
+            self.dimension,
+            BattleDimension::SeaSurface | BattleDimension::Subsurface
+        )
+    }
+
+    /// Whether the function codes start with `prefix`, so `U-C` matches
+    /// every combat unit.
+    #[must_use]
+    pub fn has_function_prefix(&self, prefix: &[&str]) -> bool {
+        self.function.len() >= prefix.len()
+            && self
+                .function
+                .iter()
+                .zip(prefix)
+                .all(|(code, want)| code == want)
+    }
+}
+
+impl fmt::Display for CotType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a-{}-{}", self.affiliation.code(), self.dimension.code())?;
+        for code in &self.function {
+            write!(f, "-{code}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CotType {
+    type Err = CotTypeError;
+
+    fn from_str(cot_type: &str) -> Result<Self, Self::Err> {
+        Self::parse(cot_type)
+    }
+}
+
+/// Whether `cot_type` is an atom with a friend or assumed-friend affiliation.
+#[must_use]
+pub fn is_friendly(cot_type: &str) -> bool {
+    CotType::parse(cot_type).is_ok_and(|parsed| parsed.is_friendly())
+}
+
+/// Whether `cot_type` is an atom with a hostile-framed affiliation.
+#[must_use]
+pub fn is_hostile(cot_type: &str) -> bool {
+    CotType::parse(cot_type).is_ok_and(|parsed| parsed.is_hostile())
+}
+
+/// Whether `cot_type` is an air atom.
+#[must_use]
+pub fn is_air(cot_type: &str) -> bool {
+    CotType::parse(cot_type).is_ok_and(|parsed| parsed.is_air())
+}
+
+/// Whether `cot_type` is an emergency beacon or its cancellation.
+#[must_use]
+pub fn is_emergency(cot_type: &str) -> bool {
+    cot_type.starts_with(EMERGENCY_COT_TYPE_PREFIX)
+}
+
+/// Returns a human-readable classification for an atom CoT type, or `None`
+/// when the type is not an atom or uses an unknown affiliation/dimension.
+#[must_use]
+pub fn describe_cot_type(cot_type: &str) -> Option<String> {
+    let parsed = CotType::parse(cot_type).ok()?;
+    let dimension_code = parsed.dimension.code().to_string();
+    let function: Vec<&str> = parsed.function.iter().map(String::as_str).collect();
+
+    let mut label = format!(
+        "{} {}",
+        parsed.affiliation.label(),
+        parsed.dimension.label()
+    );
+    let Some(category) = function.first() else {
+        return Some(label);
+    };
+    if let Some(category) = category_label(&dimension_code, category) {
+        label.push(' ');
+        label.push_str(category);
+    }
+    if let Some(detail) = function_label(&dimension_code, &function) {
+        label.push_str(" — ");
+        label.push_str(detail);
+    }
+    Some(label)
+}
+
+fn single_char(code: &str) -> Option<char> {
+    let mut chars = code.chars();
+    let first = chars.next()?;
+    chars.next().is_none().then_some(first)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CotTypeError {
+    NotAtom { cot_type: String },
+    UnknownAffiliation { code: String },
+    UnknownDimension { code: String },
+    EmptyFunctionCode { cot_type: String },
+    InvalidSidc { sidc: String, reason: &'static str },
+    NotSidcRepresentable { cot_type: String },
+}
+
+impl CodedError for CotTypeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotAtom { .. } => ErrorCode::new("CORE", 401),
+            Self::UnknownAffiliation { .. } => ErrorCode::new("CORE", 402),
+            Self::UnknownDimension { .. } => ErrorCode::new("CORE", 403),
+            Self::EmptyFunctionCode { .. } => ErrorCode::new("CORE", 404),
+            Self::InvalidSidc { .. } => ErrorCode::new("CORE", 405),
+            Self::NotSidcRepresentable { .. } => ErrorCode::new("CORE", 406),
+        }
+    }
+}
+
+impl fmt::Display for CotTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAtom { cot_type } => write!(
+                f,
+                "CoT type `{cot_type}` is not an atom (`a-<affiliation>-<dimension>...`)"
+            ),
+            Self::UnknownAffiliation { code } => write!(f, "unknown CoT affiliation `{code}`"),
+            Self::UnknownDimension { code } => write!(f, "unknown CoT battle dimension `{code}`"),
+            Self::EmptyFunctionCode { cot_type } => {
+                write!(f, "CoT type `{cot_type}` has an empty function code")
+            }
+            Self::InvalidSidc { sidc, reason } => write!(f, "invalid SIDC `{sidc}`: {reason}"),
+            Self::NotSidcRepresentable { cot_type } => write!(
+                f,
+                "CoT type `{cot_type}` has function codes a SIDC cannot carry"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CotTypeError {}
+
+fn category_label(dimension: &str, category: &str) -> Option<&'static str> {
+    Some(match (dimension, category) {
+        ("G", "U") => "Unit",
+        ("G", "E") => "Equipment",
+        ("G", "I") => "Installation",
+        ("A", "M") => "Military",
+        ("A", "C") => "Civilian",
+        ("A", "W") => "Weapon",
+        ("S", "C") => "Combatant",
+        ("S", "N") => "Non-combatant",
+        ("S", "X") => "Non-military",
+        ("U", "S") => "Submarine",
+        ("U", "W") => "Underwater Weapon",
+        _ => return None,
+    })
+}
+
+/// Most specific known label for the function codes after the category.
+fn function_label(dimension: &str, function: &[&str]) -> Option<&'static str> {
+    (2..=function.len())
+        .rev()
+        .find_map(|len| function_entry(dimension, &function[..len].join("-")))
+}
+
+fn function_entry(dimension: &str, function: &str) -> Option<&'static str> {
+    Some(match (dimension, function) {
+        ("G", "U-C") => "Combat",
+        ("G", "U-C-A") => "Armor",
+        ("G", "U-C-I") => "Infantry",
+        ("G", "U-C-R") => "Reconnaissance",
+        ("G", "U-C-E") => "Engineer",
+        ("G", "U-C-F") => "Field Artillery",
+        ("G", "U-C-D") => "Air Defense",
+        ("G", "U-C-V") => "Aviation",
+        ("G", "U-S") => "Combat Service Support",
+        ("G", "U-S-M") => "Medical",
+        ("G", "U-U") => "Combat Support",
+        ("G", "E-V") => "Ground Vehicle",
+        ("G", "E-V-A-T") => "Tank",
+        ("G", "E-W") => "Weapon",
+        ("G", "I-M") => "Military Base",
+        ("A", "M-F") => "Fixed Wing",
+        ("A", "M-F-Q") => "Drone",
+        ("A", "M-H") => "Rotary Wing",
+        ("A", "C-F") => "Fixed Wing",
+        ("A", "C-H") => "Rotary Wing",
+        ("S", "C-L") => "Line",
+        ("S", "C-S") => "Surface Warfare",
+        ("S", "X-F") => "Fishing",
+        ("S", "X-M") => "Merchant",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        describe_cot_type, is_air, is_emergency, is_friendly, is_hostile, Affiliation,
+        BattleDimension, CotType, CotTypeError,
+    };
+
+    #[test]
+    fn describes_atom_types_at_known_depth() {
+        assert_eq!(
+            describe_cot_type("a-h-G-U-C-A").as_deref(),
+            Some("Hostile Ground Unit — Armor")
+        );
+        assert_eq!(
+            describe_cot_type("a-f-A-M-H").as_deref(),
+            Some("Friendly Air Military — Rotary Wing")
+        );
+        // Unknown trailing codes fall back to the deepest known function.
+        assert_eq!(
+            describe_cot_type("a-n-G-U-C-I-Z").as_deref(),
+            Some("Neutral Ground Unit — Infantry")
+        );
+        assert_eq!(
+            describe_cot_type("a-u-S").as_deref(),
+            Some("Unknown Sea Surface")
+        );
+    }
+
+    #[test]
+    fn rejects_non_atom_and_malformed_types() {
+        assert_eq!(describe_cot_type("b-t-f"), None);
+        assert_eq!(describe_cot_type("a-z-G"), None);
+        assert_eq!(describe_cot_type("a-f"), None);
+        assert_eq!(describe_cot_type(""), None);
+    }
+
+    #[test]
+    fn parses_atoms_and_round_trips_sidcs() {
+        let armor = CotType::parse("a-h-G-U-C-A").expect("atom");
+        assert_eq!(armor.affiliation, Affiliation::Hostile);
+        assert_eq!(armor.dimension, BattleDimension::Ground);
+        assert_eq!(armor.function, ["U", "C", "A"]);
+        assert!(armor.has_function_prefix(&["U", "C"]));
+        assert_eq!(armor.to_string(), "a-h-G-U-C-A");
+
+        let sidc = armor.to_sidc().expect("sidc");
+        assert_eq!(sidc, "SHGPUCA--------");
+        assert_eq!(CotType::from_sidc(&sidc), Ok(armor));
+        assert_eq!(
+            CotType::from_sidc("sfapmfq---*****").map(|parsed| parsed.to_string()),
+            Ok("a-f-A-M-F-Q".to_owned())
+        );
+        assert_eq!(
+            CotType::parse("a-u-S").expect("atom").to_sidc().as_deref(),
+            Ok("SUSP-----------")
+        );
+
+        assert!(matches!(
+            CotType::from_sidc("GFGPUCA--------"),
+            Err(CotTypeError::InvalidSidc { .. })
+        ));
+        assert!(matches!(
+            CotType::from_sidc("SFGPUCA"),
+            Err(CotTypeError::InvalidSidc { .. })
+        ));
+        assert!(matches!(
+            CotType::parse("a-f-G-U-C-I-d-X-Y-Z").and_then(|parsed| parsed.to_sidc()),
+            Err(CotTypeError::NotSidcRepresentable { .. })
+        ));
+        assert_eq!(
+            CotType::parse("a-x-G"),
+            Err(CotTypeError::UnknownAffiliation {
+                code: "x".to_owned()
+            })
+        );
+        assert!(matches!(
+            CotType::parse("a-f-G--C"),
+            Err(CotTypeError::EmptyFunctionCode { .. })
+        ));
+    }
+
+    #[test]
+    fn predicates_classify_by_affiliation_dimension_and_type() {
+        assert!(is_friendly("a-f-G-U-C"));
+        assert!(is_friendly("a-a-A"));
+        assert!(!is_friendly("a-h-G"));
+        assert!(!is_friendly("b-t-f"));
+        assert!(is_hostile("a-s-S"));
+        assert!(is_hostile("a-k-G"));
+        assert!(!is_hostile("a-n-G"));
+        assert!(is_air("a-u-A-M-F-Q"));
+        assert!(!is_air("a-u-G"));
+        assert!(is_emergency("b-a-o-tbl"));
+        assert!(is_emergency("b-a-o-can"));
+        assert!(!is_emergency("a-f-G"));
+    }
+}
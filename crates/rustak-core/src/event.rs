@@ -0,0 +1,754 @@
+use std::fmt;
+
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rustak_limits::{CodedError, ErrorCode, Limits};
+
+use crate::model::{CoreError, Position};
+use crate::time::TimestampUtc;
+
+/// CoT placeholder for an unknown `hae`, `ce` or `le`.
+const UNKNOWN_POINT_VALUE: f64 = 9_999_999.0;
+
+/// A CoT event with the envelope attributes, point and `<detail>` tree.
+///
+/// [`CotEvent::to_xml`] is stable under round trips: serializing a parsed
+/// serialization yields the same bytes. Timestamps are written with
+/// millisecond precision and unknown point values as `9999999.0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CotEvent {
+    pub version: String,
+    pub uid: String,
+    pub cot_type: String,
+    pub how: Option<String>,
+    pub time: TimestampUtc,
+    pub start: TimestampUtc,
+    pub stale: TimestampUtc,
+    /// Remaining `<event>` attributes (`access`, `qos`, ...) in document order.
+    pub other_attributes: Vec<(String, String)>,
+    pub point: Position,
+    /// Children of `<detail>`; empty when the event has none.
+    pub detail: Vec<DetailNode>,
+}
+
+/// One element of the `<detail>` tree. Text is kept ahead of children, which
+/// is how every common CoT detail (`<remarks>`, `<contact>`, ...) is shaped.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DetailNode {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<DetailNode>,
+}
+
+impl DetailNode {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((name.into(), value.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_child(mut self, child: DetailNode) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    #[must_use]
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        attribute(&self.attributes, name)
+    }
+
+    #[must_use]
+    pub fn child(&self, name: &str) -> Option<&DetailNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Parses a sequence of detail elements such as the `xmlDetail` field
+    /// of a TAK protocol v1 event, bounded like [`CotEvent::from_xml`].
+    pub fn parse_fragment(xml: &str, limits: &Limits) -> Result<Vec<Self>, CotXmlError> {
+        if xml.len() > limits.max_xml_scan_bytes {
+            return Err(CotXmlError::TooLarge {
+                len: xml.len(),
+                max: limits.max_xml_scan_bytes,
+            });
+        }
+
+        let mut reader = Reader::from_str(xml);
+        let mut detail = DetailBuilder::new(limits);
+        loop {
+            match reader.read_event().map_err(syntax)? {
+                Event::Start(element) => detail.start(&element, false)?,
+                Event::Empty(element) => detail.start(&element, true)?,
+                Event::End(element) => {
+                    if !detail.end() {
+                        return Err(CotXmlError::UnexpectedElement {
+                            name: String::from_utf8_lossy(element.name().as_ref()).into_owned(),
+                        });
+                    }
+                }
+                Event::Text(text) => detail.text(&text.unescape().map_err(syntax)?),
+                Event::CData(text) => detail.text(&String::from_utf8_lossy(&text.into_inner())),
+                Event::Eof => break,
+                Event::Decl(_) | Event::PI(_) | Event::Comment(_) | Event::DocType(_) => {}
+            }
+        }
+        if !detail.open.is_empty() {
+            return Err(CotXmlError::Syntax(
+                "detail fragment ended inside an element".to_owned(),
+            ));
+        }
+        Ok(detail.finish())
+    }
+
+    /// Serializes the element and its subtree the way [`CotEvent::to_xml`]
+    /// writes it inside `<detail>`.
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut out = String::new();
+        self.write_xml(&mut out);
+        out
+    }
+
+    fn write_xml(&self, out: &mut String) {
+        out.push('<');
+        out.push_str(&self.name);
+        write_attributes(out, &self.attributes);
+        if self.text.is_empty() && self.children.is_empty() {
+            out.push_str("/>");
+            return;
+        }
+        out.push('>');
+        out.push_str(&escape(self.text.as_str()));
+        for child in &self.children {
+            child.write_xml(out);
+        }
+        out.push_str("</");
+        out.push_str(&self.name);
+        out.push('>');
+    }
+}
+
+impl CotEvent {
+    /// An event that starts at `time`, with no `how` and an empty detail.
+    #[must_use]
+    pub fn new(
+        uid: impl Into<String>,
+        cot_type: impl Into<String>,
+        time: TimestampUtc,
+        stale: TimestampUtc,
+        point: Position,
+    ) -> Self {
+        Self {
+            version: "2.0".to_owned(),
+            uid: uid.into(),
+            cot_type: cot_type.into(),
+            how: None,
+            time,
+            start: time,
+            stale,
+            other_attributes: Vec::new(),
+            point,
+            detail: Vec::new(),
+        }
+    }
+
+    /// First top-level `<detail>` child called `name`.
+    #[must_use]
+    pub fn detail_element(&self, name: &str) -> Option<&DetailNode> {
+        self.detail.iter().find(|node| node.name == name)
+    }
+
+    /// Parses one CoT event, refusing input longer than
+    /// `limits.max_xml_scan_bytes` or a detail tree with more than
+    /// `limits.max_detail_elements` elements.
+    pub fn from_xml(xml: &str, limits: &Limits) -> Result<Self, CotXmlError> {
+        if xml.len() > limits.max_xml_scan_bytes {
+            return Err(CotXmlError::TooLarge {
+                len: xml.len(),
+                max: limits.max_xml_scan_bytes,
+            });
+        }
+
+        let mut reader = Reader::from_str(xml);
+        let mut scope = Scope::Document;
+        let mut envelope = None;
+        let mut point = None;
+        let mut detail = None::<DetailBuilder>;
+
+        loop {
+            let (element, empty) = match reader.read_event().map_err(syntax)? {
+                Event::Start(element) => (element, false),
+                Event::Empty(element) => (element, true),
+                Event::End(_) => {
+                    let closed_detail_child =
+                        scope == Scope::Detail && detail.as_mut().is_some_and(DetailBuilder::end);
+                    scope = match scope {
+                        Scope::Detail if closed_detail_child => Scope::Detail,
+                        Scope::Detail | Scope::Point => Scope::Event,
+                        Scope::Event | Scope::Document | Scope::Closed => Scope::Closed,
+                    };
+                    continue;
+                }
+                Event::Text(text) => {
+                    if let (Scope::Detail, Some(detail)) = (scope, detail.as_mut()) {
+                        detail.text(&text.unescape().map_err(syntax)?);
+                    }
+                    continue;
+                }
+                Event::CData(text) => {
+                    if let (Scope::Detail, Some(detail)) = (scope, detail.as_mut()) {
+                        detail.text(&String::from_utf8_lossy(&text.into_inner()));
+                    }
+                    continue;
+                }
+                Event::Eof => break,
+                Event::Decl(_) | Event::PI(_) | Event::Comment(_) | Event::DocType(_) => continue,
+            };
+
+            match (scope, detail.as_mut()) {
+                (Scope::Detail, Some(detail)) => detail.start(&element, empty)?,
+                (Scope::Document, _) if element.name().as_ref() == b"event" => {
+                    envelope = Some(attributes(&element)?);
+                    scope = if empty { Scope::Closed } else { Scope::Event };
+                }
+                (Scope::Event, _) if element.name().as_ref() == b"point" && point.is_none() => {
+                    point = Some(parse_point(&attributes(&element)?)?);
+                    if !empty {
+                        scope = Scope::Point;
+                    }
+                }
+                (Scope::Event, None) if element.name().as_ref() == b"detail" => {
+                    detail = Some(DetailBuilder::new(limits));
+                    if !empty {
+                        scope = Scope::Detail;
+                    }
+                }
+                _ => {
+                    return Err(CotXmlError::UnexpectedElement {
+                        name: element_name(&element)?,
+                    })
+                }
+            }
+        }
+
+        if scope != Scope::Closed {
+            return Err(CotXmlError::Syntax(
+                "document ended before </event>".to_owned(),
+            ));
+        }
+        let envelope = envelope.ok_or(CotXmlError::MissingElement { name: "event" })?;
+        let point = point.ok_or(CotXmlError::MissingElement { name: "point" })?;
+        build_event(
+            envelope,
+            point,
+            detail.map(DetailBuilder::finish).unwrap_or_default(),
+        )
+    }
+
+    /// Serializes the event as a single-line `<event>` document.
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut out = String::with_capacity(256);
+        out.push_str("<event");
+        let mut envelope = vec![
+            ("version", self.version.as_str()),
+            ("uid", self.uid.as_str()),
+            ("type", self.cot_type.as_str()),
+        ];
+        if let Some(how) = &self.how {
+            envelope.push(("how", how.as_str()));
+        }
+        let times = [
+            ("time", self.time.to_rfc3339_millis()),
+            ("start", self.start.to_rfc3339_millis()),
+            ("stale", self.stale.to_rfc3339_millis()),
+        ];
+        envelope.extend(times.iter().map(|(name, value)| (*name, value.as_str())));
+        write_attributes(&mut out, &envelope);
+        write_attributes(&mut out, &self.other_attributes);
+
+        out.push_str("><point");
+        let point = [
+            ("lat", self.point.latitude().to_string()),
+            ("lon", self.point.longitude().to_string()),
+            ("hae", point_value(self.point.hae())),
+            ("ce", point_value(self.point.ce())),
+            ("le", point_value(self.point.le())),
+        ];
+        write_attributes(&mut out, &point);
+        out.push_str("/>");
+
+        if !self.detail.is_empty() {
+            out.push_str("<detail>");
+            for node in &self.detail {
+                node.write_xml(&mut out);
+            }
+            out.push_str("</detail>");
+        }
+        out.push_str("</event>");
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CotXmlError {
+    TooLarge {
+        len: usize,
+        max: usize,
+    },
+    TooManyDetailElements {
+        max: usize,
+    },
+    Syntax(String),
+    MissingElement {
+        name: &'static str,
+    },
+    UnexpectedElement {
+        name: String,
+    },
+    MissingAttribute {
+        element: &'static str,
+        name: &'static str,
+    },
+    InvalidAttribute {
+        element: &'static str,
+        name: &'static str,
+        value: String,
+    },
+    Point(CoreError),
+}
+
+impl CodedError for CotXmlError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::TooLarge { .. } => ErrorCode::new("CORE", 101),
+            Self::TooManyDetailElements { .. } => ErrorCode::new("CORE", 102),
+            Self::Syntax(_) => ErrorCode::new("CORE", 103),
+            Self::MissingElement { .. } => ErrorCode::new("CORE", 104),
+            Self::UnexpectedElement { .. } => ErrorCode::new("CORE", 105),
+            Self::MissingAttribute { .. } => ErrorCode::new("CORE", 106),
+            Self::InvalidAttribute { .. } => ErrorCode::new("CORE", 107),
+            Self::Point(_) => ErrorCode::new("CORE", 108),
+        }
+    }
+}
+
+impl fmt::Display for CotXmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { len, max } => {
+                write!(f, "CoT XML is {len} bytes; max_xml_scan_bytes is {max}")
+            }
+            Self::TooManyDetailElements { max } => {
+                write!(f, "detail exceeds max_detail_elements ({max})")
+            }
+            Self::Syntax(message) => write!(f, "malformed CoT XML: {message}"),
+            Self::MissingElement { name } => write!(f, "CoT XML has no <{name}> element"),
+            Self::UnexpectedElement { name } => {
+                write!(f, "unexpected <{name}> element in CoT XML")
+            }
+            Self::MissingAttribute { element, name } => {
+                write!(f, "<{element}> is missing the `{name}` attribute")
+            }
+            Self::InvalidAttribute {
+                element,
+                name,
+                value,
+            } => write!(f, "<{element}> attribute `{name}` is invalid: {value:?}"),
+            Self::Point(error) => write!(f, "invalid <point>: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CotXmlError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Document,
+    Event,
+    Point,
+    Detail,
+    Closed,
+}
+
+fn syntax(error: impl fmt::Display) -> CotXmlError {
+    CotXmlError::Syntax(error.to_string())
+}
+
+fn element_name(element: &BytesStart<'_>) -> Result<String, CotXmlError> {
+    std::str::from_utf8(element.name().as_ref())
+        .map(str::to_owned)
+        .map_err(syntax)
+}
+
+fn attributes(element: &BytesStart<'_>) -> Result<Vec<(String, String)>, CotXmlError> {
+    element
+        .attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(syntax)?;
+            let name = std::str::from_utf8(attribute.key.as_ref()).map_err(syntax)?;
+            let value = attribute.unescape_value().map_err(syntax)?;
+            Ok((name.to_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Assembles `<detail>` children, counting elements against
+/// `max_detail_elements`.
+struct DetailBuilder {
+    nodes: Vec<DetailNode>,
+    open: Vec<DetailNode>,
+    elements: usize,
+    max_elements: usize,
+}
+
+impl DetailBuilder {
+    fn new(limits: &Limits) -> Self {
+        Self {
+            nodes: Vec::new(),
+            open: Vec::new(),
+            elements: 0,
+            max_elements: limits.max_detail_elements,
+        }
+    }
+
+    fn start(&mut self, element: &BytesStart<'_>, empty: bool) -> Result<(), CotXmlError> {
+        self.elements += 1;
+        if self.elements > self.max_elements {
+            return Err(CotXmlError::TooManyDetailElements {
+                max: self.max_elements,
+            });
+        }
+        let node = DetailNode {
+            name: element_name(element)?,
+            attributes: attributes(element)?,
+            ..DetailNode::default()
+        };
+        if empty {
+            self.attach(node);
+        } else {
+            self.open.push(node);
+        }
+        Ok(())
+    }
+
+    /// Closes the innermost open element; `false` when none is open, i.e.
+    /// the end tag belongs to the enclosing `<detail>`.
+    fn end(&mut self) -> bool {
+        match self.open.pop() {
+            Some(node) => {
+                self.attach(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(node) = self.open.last_mut() {
+            if !text.trim().is_empty() {
+                node.text.push_str(text);
+            }
+        }
+    }
+
+    fn attach(&mut self, node: DetailNode) {
+        match self.open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.nodes.push(node),
+        }
+    }
+
+    fn finish(self) -> Vec<DetailNode> {
+        self.nodes
+    }
+}
+
+fn write_attributes<'a, N, V>(out: &mut String, attributes: impl IntoIterator<Item = &'a (N, V)>)
+where
+    N: AsRef<str> + 'a,
+    V: AsRef<str> + 'a,
+{
+    for (name, value) in attributes {
+        let (name, value) = (name.as_ref(), value.as_ref());
+        out.push(' ');
+        out.push_str(name);
+        out.push_str("=\"");
+        out.push_str(&escape(value));
+        out.push('"');
+    }
+}
+
+fn point_value(value: Option<f64>) -> String {
+    value.map_or_else(
+        || format!("{UNKNOWN_POINT_VALUE:.1}"),
+        |value| value.to_string(),
+    )
+}
+
+fn parse_point(attributes: &[(String, String)]) -> Result<Position, CotXmlError> {
+    let number = |name: &'static str| -> Result<Option<f64>, CotXmlError> {
+        attribute(attributes, name)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| CotXmlError::InvalidAttribute {
+                        element: "point",
+                        name,
+                        value: value.to_owned(),
+                    })
+            })
+            .transpose()
+    };
+    let required = |name: &'static str| {
+        number(name)?.ok_or(CotXmlError::MissingAttribute {
+            element: "point",
+            name,
+        })
+    };
+    let known = |value: Option<f64>| value.filter(|value| *value != UNKNOWN_POINT_VALUE);
+
+    let mut position =
+        Position::new(required("lat")?, required("lon")?).map_err(CotXmlError::Point)?;
+    if let Some(hae) = known(number("hae")?) {
+        position = position.with_hae(hae).map_err(CotXmlError::Point)?;
+    }
+    if let Some(ce) = known(number("ce")?) {
+        position = position.with_ce(ce).map_err(CotXmlError::Point)?;
+    }
+    if let Some(le) = known(number("le")?) {
+        position = position.with_le(le).map_err(CotXmlError::Point)?;
+    }
+    Ok(position)
+}
+
+fn build_event(
+    envelope: Vec<(String, String)>,
+    point: Position,
+    detail: Vec<DetailNode>,
+) -> Result<CotEvent, CotXmlError> {
+    let mut version = None;
+    let mut uid = None;
+    let mut cot_type = None;
+    let mut how = None;
+    let mut times = [None, None, None];
+    let mut other_attributes = Vec::new();
+    for (name, value) in envelope {
+        match name.as_str() {
+            "version" => version = Some(value),
+            "uid" => uid = Some(value),
+            "type" => cot_type = Some(value),
+            "how" => how = Some(value),
+            "time" => times[0] = Some(value),
+            "start" => times[1] = Some(value),
+            "stale" => times[2] = Some(value),
+            _ => other_attributes.push((name, value)),
+        }
+    }
+
+    let required = |value: Option<String>, name: &'static str| {
+        value.ok_or(CotXmlError::MissingAttribute {
+            element: "event",
+            name,
+        })
+    };
+    let [time, start, stale] = [("time", 0), ("start", 1), ("stale", 2)].map(|(name, index)| {
+        let value = required(times[index].take(), name)?;
+        TimestampUtc::parse_rfc3339(&value).map_err(|_| CotXmlError::InvalidAttribute {
+            element: "event",
+            name,
+            value,
+        })
+    });
+
+    Ok(CotEvent {
+        version: version.unwrap_or_else(|| "2.0".to_owned()),
+        uid: required(uid, "uid")?,
+        cot_type: required(cot_type, "type")?,
+        how,
+        time: time?,
+        start: start?,
+        stale: stale?,
+        other_attributes,
+        point,
+        detail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_limits::Limits;
+
+    use super::{CotEvent, CotXmlError, DetailNode};
+    use crate::model::Position;
+    use crate::time::TimestampUtc;
+
+    const EVENT: &str = concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n",
+        "<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G-U-C\" how=\"m-g\" ",
+        "time=\"2024-05-01T12:00:00.250Z\" start=\"2024-05-01T12:00:00.250Z\" ",
+        "stale=\"2024-05-01T12:05:00Z\" access=\"Unclassified\">\n",
+        "  <point lat=\"38.5\" lon=\"-77.25\" hae=\"12.5\" ce=\"9999999.0\" le=\"9999999\"/>\n",
+        "  <detail>\n",
+        "    <contact callsign=\"ALPHA &amp; CO\" endpoint=\"*:-1:stcp\"/>\n",
+        "    <remarks source=\"user\">Hold &lt;here&gt;</remarks>\n",
+        "    <link uid=\"parent\"><extra kind=\"x\"/></link>\n",
+        "  </detail>\n",
+        "</event>\n",
+    );
+
+    fn limits() -> Limits {
+        Limits::conservative_defaults()
+    }
+
+    #[test]
+    fn parses_envelope_point_and_detail_tree() {
+        let event = CotEvent::from_xml(EVENT, &limits()).expect("parse");
+
+        assert_eq!(event.uid, "ANDROID-1");
+        assert_eq!(event.cot_type, "a-f-G-U-C");
+        assert_eq!(event.how.as_deref(), Some("m-g"));
+        assert_eq!(event.time.to_rfc3339_millis(), "2024-05-01T12:00:00.250Z");
+        assert_eq!(
+            event.other_attributes,
+            [("access".to_owned(), "Unclassified".to_owned())]
+        );
+        assert_eq!(event.point.latitude(), 38.5);
+        assert_eq!(event.point.hae(), Some(12.5));
+        assert_eq!(event.point.ce(), None);
+        assert_eq!(event.point.le(), None);
+
+        let contact = event.detail_element("contact").expect("contact");
+        assert_eq!(contact.attribute("callsign"), Some("ALPHA & CO"));
+        let remarks = event.detail_element("remarks").expect("remarks");
+        assert_eq!(remarks.text, "Hold <here>");
+        let link = event.detail_element("link").expect("link");
+        assert_eq!(
+            link.child("extra")
+                .and_then(|extra| extra.attribute("kind")),
+            Some("x")
+        );
+    }
+
+    #[test]
+    fn serialization_round_trips() {
+        let event = CotEvent::from_xml(EVENT, &limits()).expect("parse");
+        let xml = event.to_xml();
+        let reparsed = CotEvent::from_xml(&xml, &limits()).expect("reparse");
+        assert_eq!(reparsed, event);
+        assert_eq!(reparsed.to_xml(), xml);
+
+        let fragment: String = event.detail.iter().map(DetailNode::to_xml).collect();
+        assert_eq!(
+            DetailNode::parse_fragment(&fragment, &limits()).expect("fragment"),
+            event.detail
+        );
+        assert!(DetailNode::parse_fragment("<link>", &limits()).is_err());
+
+        let built = CotEvent::new(
+            "uas-1",
+            "a-f-A",
+            TimestampUtc::from_unix_seconds(1_700_000_000),
+            TimestampUtc::from_unix_seconds(1_700_000_060),
+            Position::new(1.0, 2.0).expect("position"),
+        );
+        let mut built = built;
+        built
+            .detail
+            .push(DetailNode::new("remarks").with_text("a \"quoted\" note"));
+        assert_eq!(
+            built.to_xml(),
+            concat!(
+                "<event version=\"2.0\" uid=\"uas-1\" type=\"a-f-A\" ",
+                "time=\"2023-11-14T22:13:20.000Z\" start=\"2023-11-14T22:13:20.000Z\" ",
+                "stale=\"2023-11-14T22:14:20.000Z\"><point lat=\"1\" lon=\"2\" ",
+                "hae=\"9999999.0\" ce=\"9999999.0\" le=\"9999999.0\"/>",
+                "<detail><remarks>a &quot;quoted&quot; note</remarks></detail></event>",
+            )
+        );
+        assert_eq!(
+            CotEvent::from_xml(&built.to_xml(), &limits()).expect("reparse"),
+            built
+        );
+    }
+
+    #[test]
+    fn parsing_is_bounded_by_limits() {
+        let tight = Limits {
+            max_xml_scan_bytes: 64,
+            ..limits()
+        };
+        assert_eq!(
+            CotEvent::from_xml(EVENT, &tight),
+            Err(CotXmlError::TooLarge {
+                len: EVENT.len(),
+                max: 64
+            })
+        );
+
+        let few = Limits {
+            max_detail_elements: 3,
+            ..limits()
+        };
+        assert_eq!(
+            CotEvent::from_xml(EVENT, &few),
+            Err(CotXmlError::TooManyDetailElements { max: 3 })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_events() {
+        let parse = |xml: &str| CotEvent::from_xml(xml, &limits());
+
+        assert!(matches!(
+            parse("<event uid=\"a\"><point lat=\"1\" lon=\"2\"/>"),
+            Err(CotXmlError::Syntax(_))
+        ));
+        assert_eq!(
+            parse("<message/>"),
+            Err(CotXmlError::UnexpectedElement {
+                name: "message".to_owned()
+            })
+        );
+        assert_eq!(
+            parse("<event uid=\"a\" type=\"t\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:00:00Z\"/>"),
+            Err(CotXmlError::MissingElement { name: "point" })
+        );
+        assert_eq!(
+            parse("<event uid=\"a\" type=\"t\" time=\"soon\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:00:00Z\"><point lat=\"1\" lon=\"2\"/></event>"),
+            Err(CotXmlError::InvalidAttribute {
+                element: "event",
+                name: "time",
+                value: "soon".to_owned()
+            })
+        );
+        assert!(matches!(
+            parse("<event uid=\"a\" type=\"t\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:00:00Z\"><point lat=\"91\" lon=\"2\"/></event>"),
+            Err(CotXmlError::Point(_))
+        ));
+    }
+}
@@ -0,0 +1,477 @@
+use rustak_limits::Limits;
+
+use crate::detail::ExtensionRegistry;
+use crate::event::{CotEvent, CotXmlError, DetailNode};
+use crate::model::{CoreError, DetailElement, Kinematics, Track};
+
+/// A `<detail>` child with a typed form. `decode` reads the element named
+/// [`Self::ELEMENT`]; `encode` writes it back.
+pub trait DetailExtension: Sized {
+    const ELEMENT: &'static str;
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError>;
+
+    fn encode(&self) -> DetailNode;
+}
+
+/// `<takv>`: the sending client's software and device.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Takv {
+    pub device: Option<String>,
+    pub platform: Option<String>,
+    pub os: Option<String>,
+    pub version: Option<String>,
+}
+
+/// `<contact>`: callsign and how to reach the sender.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Contact {
+    pub callsign: String,
+    /// `host:port:protocol` for direct TCP/UDP delivery, e.g.
+    /// `192.168.1.10:4242:tcp`.
+    pub endpoint: Option<String>,
+    pub phone: Option<String>,
+}
+
+/// `<__group>`: team colour and role.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Group {
+    /// Team colour, e.g. `Cyan`.
+    pub name: String,
+    /// e.g. `Team Member`, `Team Lead`, `HQ`.
+    pub role: String,
+}
+
+/// `<precisionlocation>`: where the point's position and altitude came
+/// from (`GPS`, `USER`, `DTED0`, ...).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct PrecisionLocation {
+    pub geopointsrc: Option<String>,
+    pub altsrc: Option<String>,
+}
+
+/// `<status>`: device state.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Status {
+    /// Battery charge in percent.
+    pub battery: Option<u8>,
+}
+
+/// `<emergency>` on `b-a-o-*` beacons.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Emergency {
+    /// e.g. `911 Alert`, `Ring The Bell`, `In Contact`.
+    pub kind: Option<String>,
+    pub cancel: bool,
+    /// Callsign of the sender, as ATAK writes it.
+    pub text: String,
+}
+
+impl DetailExtension for Takv {
+    const ELEMENT: &'static str = "takv";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        Ok(Self {
+            device: optional(node, "device"),
+            platform: optional(node, "platform"),
+            os: optional(node, "os"),
+            version: optional(node, "version"),
+        })
+    }
+
+    fn encode(&self) -> DetailNode {
+        with_optional(
+            DetailNode::new(Self::ELEMENT),
+            &[
+                ("device", &self.device),
+                ("platform", &self.platform),
+                ("os", &self.os),
+                ("version", &self.version),
+            ],
+        )
+    }
+}
+
+impl DetailExtension for Contact {
+    const ELEMENT: &'static str = "contact";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        Ok(Self {
+            callsign: required(node, Self::ELEMENT, "callsign")?,
+            endpoint: optional(node, "endpoint"),
+            phone: optional(node, "phone"),
+        })
+    }
+
+    fn encode(&self) -> DetailNode {
+        with_optional(
+            DetailNode::new(Self::ELEMENT).with_attribute("callsign", &self.callsign),
+            &[("endpoint", &self.endpoint), ("phone", &self.phone)],
+        )
+    }
+}
+
+impl DetailExtension for Group {
+    const ELEMENT: &'static str = "__group";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        Ok(Self {
+            name: required(node, Self::ELEMENT, "name")?,
+            role: required(node, Self::ELEMENT, "role")?,
+        })
+    }
+
+    fn encode(&self) -> DetailNode {
+        DetailNode::new(Self::ELEMENT)
+            .with_attribute("name", &self.name)
+            .with_attribute("role", &self.role)
+    }
+}
+
+impl DetailExtension for PrecisionLocation {
+    const ELEMENT: &'static str = "precisionlocation";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        Ok(Self {
+            geopointsrc: optional(node, "geopointsrc"),
+            altsrc: optional(node, "altsrc"),
+        })
+    }
+
+    fn encode(&self) -> DetailNode {
+        with_optional(
+            DetailNode::new(Self::ELEMENT),
+            &[("geopointsrc", &self.geopointsrc), ("altsrc", &self.altsrc)],
+        )
+    }
+}
+
+impl DetailExtension for Status {
+    const ELEMENT: &'static str = "status";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        let battery = node
+            .attribute("battery")
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|percent| *percent <= 100)
+                    .ok_or_else(|| invalid(Self::ELEMENT, "battery", value))
+            })
+            .transpose()?;
+        Ok(Self { battery })
+    }
+
+    fn encode(&self) -> DetailNode {
+        let node = DetailNode::new(Self::ELEMENT);
+        match self.battery {
+            Some(battery) => node.with_attribute("battery", battery.to_string()),
+            None => node,
+        }
+    }
+}
+
+impl DetailExtension for Emergency {
+    const ELEMENT: &'static str = "emergency";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        Ok(Self {
+            kind: optional(node, "type"),
+            cancel: node
+                .attribute("cancel")
+                .is_some_and(|cancel| cancel.eq_ignore_ascii_case("true")),
+            text: node.text.clone(),
+        })
+    }
+
+    fn encode(&self) -> DetailNode {
+        let mut node = with_optional(DetailNode::new(Self::ELEMENT), &[("type", &self.kind)]);
+        if self.cancel {
+            node = node.with_attribute("cancel", "true");
+        }
+        node.with_text(&self.text)
+    }
+}
+
+/// `<track course="..." speed="..."/>`, course in degrees and speed in m/s.
+/// CoT has no vertical rate attribute, so it is not written.
+impl DetailExtension for Track {
+    const ELEMENT: &'static str = "track";
+
+    fn decode(node: &DetailNode) -> Result<Self, CotXmlError> {
+        let number = |name: &'static str| {
+            node.attribute(name)
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(|_| invalid(Self::ELEMENT, name, value))
+                })
+                .transpose()
+        };
+        let (speed, course) = (number("speed")?, number("course")?);
+        Kinematics::new(speed, course, None)
+            .and_then(Track::new)
+            .map_err(|error| match error {
+                CoreError::NonFiniteValue { field, .. }
+                | CoreError::OutOfRange { field, .. }
+                | CoreError::NegativeValue { field, .. } => CotXmlError::InvalidAttribute {
+                    element: Self::ELEMENT,
+                    name: field,
+                    value: error.to_string(),
+                },
+                CoreError::EmptyTrack | CoreError::DuplicateTrackElements { .. } => {
+                    CotXmlError::MissingAttribute {
+                        element: Self::ELEMENT,
+                        name: "speed",
+                    }
+                }
+            })
+    }
+
+    fn encode(&self) -> DetailNode {
+        let kinematics = self.kinematics();
+        let mut node = DetailNode::new(Self::ELEMENT);
+        if let Some(course) = kinematics.course() {
+            node = node.with_attribute("course", course.to_string());
+        }
+        if let Some(speed) = kinematics.speed() {
+            node = node.with_attribute("speed", speed.to_string());
+        }
+        node
+    }
+}
+
+impl CotEvent {
+    /// The typed `T` detail, or `None` when the event has no such element.
+    #[must_use]
+    pub fn extension<T: DetailExtension>(&self) -> Option<Result<T, CotXmlError>> {
+        self.detail_element(T::ELEMENT).map(T::decode)
+    }
+
+    /// Replaces the first `T` detail element, or appends one.
+    pub fn set_extension<T: DetailExtension>(&mut self, value: &T) {
+        let node = value.encode();
+        match self
+            .detail
+            .iter_mut()
+            .find(|child| child.name == T::ELEMENT)
+        {
+            Some(existing) => *existing = node,
+            None => self.detail.push(node),
+        }
+    }
+}
+
+/// [`ExtensionRegistry`] for the common TAK detail elements. Keys are
+/// element names and payloads the element's XML, as produced by
+/// [`DetailNode::to_xml`].
+#[derive(Debug, Clone, Default)]
+pub struct TakDetailRegistry {
+    limits: Limits,
+}
+
+impl TakDetailRegistry {
+    /// Registry whose payload parsing is bounded by `limits`.
+    #[must_use]
+    pub fn new(limits: Limits) -> Self {
+        Self { limits }
+    }
+
+    fn node(&self, key: &str, bytes: &[u8]) -> Option<DetailNode> {
+        let xml = std::str::from_utf8(bytes).ok()?;
+        let mut nodes = DetailNode::parse_fragment(xml, &self.limits).ok()?;
+        (nodes.len() == 1 && nodes[0].name == key).then(|| nodes.remove(0))
+    }
+}
+
+impl ExtensionRegistry for TakDetailRegistry {
+    fn decode(&self, key: &str, bytes: &[u8]) -> Option<DetailElement> {
+        let node = self.node(key, bytes)?;
+        match key {
+            Takv::ELEMENT => Takv::decode(&node).ok().map(DetailElement::Takv),
+            Contact::ELEMENT => Contact::decode(&node).ok().map(DetailElement::Contact),
+            Group::ELEMENT => Group::decode(&node).ok().map(DetailElement::Group),
+            PrecisionLocation::ELEMENT => PrecisionLocation::decode(&node)
+                .ok()
+                .map(DetailElement::PrecisionLocation),
+            Status::ELEMENT => Status::decode(&node).ok().map(DetailElement::Status),
+            Emergency::ELEMENT => Emergency::decode(&node).ok().map(DetailElement::Emergency),
+            Track::ELEMENT => Track::decode(&node).ok().map(DetailElement::Track),
+            _ => None,
+        }
+    }
+
+    fn encode(&self, element: &DetailElement) -> Option<(String, Vec<u8>)> {
+        let node = match element {
+            DetailElement::Takv(takv) => takv.encode(),
+            DetailElement::Contact(contact) => contact.encode(),
+            DetailElement::Group(group) => group.encode(),
+            DetailElement::PrecisionLocation(location) => location.encode(),
+            DetailElement::Status(status) => status.encode(),
+            DetailElement::Emergency(emergency) => emergency.encode(),
+            DetailElement::Track(track) => track.encode(),
+            DetailElement::Unknown(_) | DetailElement::Extension(_) => return None,
+        };
+        Some((node.name.clone(), node.to_xml().into_bytes()))
+    }
+}
+
+fn optional(node: &DetailNode, name: &str) -> Option<String> {
+    node.attribute(name).map(str::to_owned)
+}
+
+fn required(
+    node: &DetailNode,
+    element: &'static str,
+    name: &'static str,
+) -> Result<String, CotXmlError> {
+    optional(node, name).ok_or(CotXmlError::MissingAttribute { element, name })
+}
+
+fn invalid(element: &'static str, name: &'static str, value: &str) -> CotXmlError {
+    CotXmlError::InvalidAttribute {
+        element,
+        name,
+        value: value.to_owned(),
+    }
+}
+
+fn with_optional(mut node: DetailNode, attributes: &[(&str, &Option<String>)]) -> DetailNode {
+    for (name, value) in attributes {
+        if let Some(value) = value {
+            node = node.with_attribute(*name, value);
+        }
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_limits::Limits;
+
+    use super::{
+        Contact, DetailExtension, Emergency, Group, PrecisionLocation, Status, TakDetailRegistry,
+        Takv,
+    };
+    use crate::detail::{decode_extension_element, encode_extension_element};
+    use crate::event::{CotEvent, CotXmlError, DetailNode};
+    use crate::model::{DetailElement, Kinematics, Track};
+
+    const SA: &str = r#"<event version="2.0" uid="ANDROID-1" type="a-f-G-U-C" how="m-g" time="2024-05-01T10:00:00.000Z" start="2024-05-01T10:00:00.000Z" stale="2024-05-01T10:06:00.000Z"><point lat="51.5" lon="-0.12" hae="12" ce="5" le="9999999"/><detail><takv os="34" version="4.10.0" device="PIXEL 7" platform="ATAK-CIV"/><contact endpoint="*:-1:stcp" callsign="ALPHA"/><uid Droid="ALPHA"/><precisionlocation altsrc="GPS" geopointsrc="GPS"/><__group role="Team Lead" name="Cyan"/><status battery="87"/><track course="271.5" speed="1.25"/></detail></event>"#;
+
+    #[test]
+    fn position_reports_expose_callsign_team_and_battery() {
+        let event = CotEvent::from_xml(SA, &Limits::conservative_defaults()).expect("parse");
+
+        let contact = event
+            .extension::<Contact>()
+            .expect("contact")
+            .expect("valid");
+        assert_eq!(contact.callsign, "ALPHA");
+        assert_eq!(contact.endpoint.as_deref(), Some("*:-1:stcp"));
+        let group = event.extension::<Group>().expect("group").expect("valid");
+        assert_eq!(
+            (group.name.as_str(), group.role.as_str()),
+            ("Cyan", "Team Lead")
+        );
+        let status = event.extension::<Status>().expect("status").expect("valid");
+        assert_eq!(status.battery, Some(87));
+        let takv = event.extension::<Takv>().expect("takv").expect("valid");
+        assert_eq!(takv.platform.as_deref(), Some("ATAK-CIV"));
+        let location = event
+            .extension::<PrecisionLocation>()
+            .expect("precisionlocation")
+            .expect("valid");
+        assert_eq!(location.altsrc.as_deref(), Some("GPS"));
+        let track = event.extension::<Track>().expect("track").expect("valid");
+        assert_eq!(track.kinematics().course(), Some(271.5));
+        assert!(event.extension::<Emergency>().is_none());
+
+        let mut updated = event.clone();
+        updated.set_extension(&Status { battery: Some(12) });
+        updated.set_extension(&Emergency {
+            kind: Some("911 Alert".to_owned()),
+            cancel: false,
+            text: "ALPHA".to_owned(),
+        });
+        assert_eq!(updated.detail.len(), event.detail.len() + 1);
+        let reparsed =
+            CotEvent::from_xml(&updated.to_xml(), &Limits::conservative_defaults()).expect("parse");
+        assert_eq!(
+            reparsed.extension::<Status>().expect("status"),
+            Ok(Status { battery: Some(12) })
+        );
+        assert_eq!(
+            reparsed
+                .extension::<Emergency>()
+                .expect("emergency")
+                .map(|emergency| emergency.kind),
+            Ok(Some("911 Alert".to_owned()))
+        );
+    }
+
+    #[test]
+    fn malformed_attributes_are_typed_errors() {
+        let overcharged = DetailNode::new("status").with_attribute("battery", "140");
+        assert!(matches!(
+            Status::decode(&overcharged),
+            Err(CotXmlError::InvalidAttribute {
+                name: "battery",
+                ..
+            })
+        ));
+        assert_eq!(
+            Contact::decode(&DetailNode::new("contact")),
+            Err(CotXmlError::MissingAttribute {
+                element: "contact",
+                name: "callsign"
+            })
+        );
+        let still = DetailNode::new("track").with_attribute("speed", "fast");
+        assert!(Track::decode(&still).is_err());
+    }
+
+    #[test]
+    fn registry_round_trips_every_typed_element() {
+        let registry = TakDetailRegistry::default();
+        let track =
+            Track::new(Kinematics::new(Some(3.5), Some(90.0), None).expect("kin")).expect("track");
+        let elements = [
+            DetailElement::Takv(Takv {
+                platform: Some("rustak".to_owned()),
+                ..Takv::default()
+            }),
+            DetailElement::Contact(Contact {
+                callsign: "ALPHA".to_owned(),
+                endpoint: None,
+                phone: Some("555-0100".to_owned()),
+            }),
+            DetailElement::Group(Group {
+                name: "Cyan".to_owned(),
+                role: "Team Member".to_owned(),
+            }),
+            DetailElement::PrecisionLocation(PrecisionLocation {
+                geopointsrc: Some("USER".to_owned()),
+                altsrc: Some("DTED0".to_owned()),
+            }),
+            DetailElement::Status(Status { battery: Some(100) }),
+            DetailElement::Emergency(Emergency {
+                kind: None,
+                cancel: true,
+                text: "ALPHA".to_owned(),
+            }),
+            DetailElement::Track(track),
+        ];
+        for element in elements {
+            let (key, bytes) = encode_extension_element(&registry, &element).expect("encode");
+            assert_eq!(decode_extension_element(&registry, &key, &bytes), element);
+        }
+
+        // Payloads that do not parse as the keyed element stay opaque.
+        let opaque = decode_extension_element(&registry, "contact", b"<status battery=\"1\"/>");
+        assert!(matches!(opaque, DetailElement::Extension(_)));
+    }
+}
@@ -1,8 +1,23 @@
+pub mod chat;
+pub mod clock;
+pub mod cot_types;
 pub mod detail;
+pub mod event;
+pub mod extensions;
 pub mod model;
 pub mod time;
 
+pub use chat::{is_chat_type, ChatDestination, ChatError, ChatMessage};
+pub use clock::{
+    clock_annotation, parse_clock_status, ClockConfidence, ClockStatus, ClockThresholds,
+    StatusFileClock, SystemClock, TimeSource,
+};
+pub use cot_types::{describe_cot_type, Affiliation, BattleDimension, CotType, CotTypeError};
 pub use detail::{decode_extension_element, encode_extension_element, ExtensionRegistry};
+pub use event::{CotEvent, CotXmlError, DetailNode};
+pub use extensions::{
+    Contact, DetailExtension, Emergency, Group, PrecisionLocation, Status, TakDetailRegistry, Takv,
+};
 pub use model::{
     CoreError, CotDetail, DetailElement, ExtensionBlob, Kinematics, Position, Track, XmlElement,
 };
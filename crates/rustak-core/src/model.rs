@@ -1,6 +1,10 @@
 use std::cmp::Ordering;
 use std::fmt;
 
+use rustak_limits::{CodedError, ErrorCode};
+
+use crate::extensions::{Contact, Emergency, Group, PrecisionLocation, Status, Takv};
+
 /// WGS84 position with optional altitude and accuracy fields.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Position {
@@ -167,6 +171,12 @@ impl ExtensionBlob {
 #[derive(Debug, Clone, PartialEq)]
 pub enum DetailElement {
     Track(Track),
+    Takv(Takv),
+    Contact(Contact),
+    Group(Group),
+    PrecisionLocation(PrecisionLocation),
+    Status(Status),
+    Emergency(Emergency),
     Unknown(XmlElement),
     Extension(ExtensionBlob),
 }
@@ -217,6 +227,18 @@ pub enum CoreError {
     },
 }
 
+impl CodedError for CoreError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NonFiniteValue { .. } => ErrorCode::new("CORE", 1),
+            Self::OutOfRange { .. } => ErrorCode::new("CORE", 2),
+            Self::NegativeValue { .. } => ErrorCode::new("CORE", 3),
+            Self::EmptyTrack => ErrorCode::new("CORE", 4),
+            Self::DuplicateTrackElements { .. } => ErrorCode::new("CORE", 5),
+        }
+    }
+}
+
 impl fmt::Display for CoreError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -269,6 +291,14 @@ fn detail_element_cmp(left: &DetailElement, right: &DetailElement) -> Ordering {
         (DetailElement::Track(left_track), DetailElement::Track(right_track)) => {
             kinematics_cmp(left_track.kinematics(), right_track.kinematics())
         }
+        (DetailElement::Takv(left), DetailElement::Takv(right)) => left.cmp(right),
+        (DetailElement::Contact(left), DetailElement::Contact(right)) => left.cmp(right),
+        (DetailElement::Group(left), DetailElement::Group(right)) => left.cmp(right),
+        (DetailElement::PrecisionLocation(left), DetailElement::PrecisionLocation(right)) => {
+            left.cmp(right)
+        }
+        (DetailElement::Status(left), DetailElement::Status(right)) => left.cmp(right),
+        (DetailElement::Emergency(left), DetailElement::Emergency(right)) => left.cmp(right),
         (DetailElement::Unknown(left_xml), DetailElement::Unknown(right_xml)) => left_xml
             .name
             .cmp(&right_xml.name)
@@ -284,8 +314,14 @@ fn detail_element_cmp(left: &DetailElement, right: &DetailElement) -> Ordering {
 fn detail_element_rank(element: &DetailElement) -> u8 {
     match element {
         DetailElement::Track(_) => 0,
-        DetailElement::Unknown(_) => 1,
-        DetailElement::Extension(_) => 2,
+        DetailElement::Takv(_) => 1,
+        DetailElement::Contact(_) => 2,
+        DetailElement::Group(_) => 3,
+        DetailElement::PrecisionLocation(_) => 4,
+        DetailElement::Status(_) => 5,
+        DetailElement::Emergency(_) => 6,
+        DetailElement::Unknown(_) => 7,
+        DetailElement::Extension(_) => 8,
     }
 }
 
@@ -355,7 +391,8 @@ fn canonicalize_zero(value: f64) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::{
-        CoreError, CotDetail, DetailElement, ExtensionBlob, Kinematics, Position, Track, XmlElement,
+        CoreError, CotDetail, DetailElement, ExtensionBlob, Kinematics, Position, Status, Track,
+        XmlElement,
     };
 
     #[test]
@@ -457,6 +494,7 @@ mod tests {
             DetailElement::Unknown(XmlElement::new("contact", "<contact />")),
             DetailElement::Track(track),
             DetailElement::Extension(ExtensionBlob::new("alpha", vec![1])),
+            DetailElement::Status(Status { battery: Some(50) }),
         ])
         .expect("detail should canonicalize");
 
@@ -468,12 +506,19 @@ mod tests {
                 DetailElement::Unknown(_) => "unknown",
                 DetailElement::Extension(blob) if blob.key == "alpha" => "extension-alpha",
                 DetailElement::Extension(_) => "extension-other",
+                _ => "typed",
             })
             .collect();
 
         assert_eq!(
             ordered_kinds,
-            vec!["track", "unknown", "extension-alpha", "extension-other"]
+            vec![
+                "track",
+                "typed",
+                "unknown",
+                "extension-alpha",
+                "extension-other"
+            ]
         );
     }
 }
@@ -1,6 +1,8 @@
 use std::fmt;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use rustak_limits::{CodedError, ErrorCode};
+
 const NANOS_PER_SECOND: i128 = 1_000_000_000;
 
 /// UTC timestamp represented as nanoseconds since Unix epoch.
@@ -74,6 +76,32 @@ impl TimestampUtc {
         self.unix_nanos.rem_euclid(NANOS_PER_SECOND) as u32
     }
 
+    /// Formats as CoT-style RFC 3339 UTC with millisecond precision,
+    /// e.g. `2023-11-14T22:13:20.123Z`.
+    #[must_use]
+    pub fn to_rfc3339_millis(self) -> String {
+        let seconds = self.unix_seconds();
+        let days = seconds.div_euclid(86_400);
+        let second_of_day = seconds.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+            second_of_day / 3_600,
+            (second_of_day % 3_600) / 60,
+            second_of_day % 60,
+            self.subsec_nanos() / 1_000_000
+        )
+    }
+
+    /// Parses an RFC 3339 timestamp as found in CoT `time`/`start`/`stale`
+    /// attributes, e.g. `2023-11-14T22:13:20.123Z` or
+    /// `2023-11-14T23:13:20+01:00`. Fractions beyond nanoseconds are truncated.
+    pub fn parse_rfc3339(value: &str) -> Result<Self, TimestampError> {
+        parse_rfc3339(value).ok_or_else(|| TimestampError::InvalidRfc3339 {
+            value: value.to_owned(),
+        })
+    }
+
     /// Converts this timestamp to `SystemTime`.
     pub fn to_system_time(self) -> Result<SystemTime, TimestampError> {
         if self.unix_nanos >= 0 {
@@ -136,6 +164,18 @@ pub enum TimestampError {
     InvalidNanoseconds { nanoseconds: u32 },
     OutOfRangeForSystemTime { unix_nanos: i128 },
     OutOfRangeForChrono { unix_nanos: i128 },
+    InvalidRfc3339 { value: String },
+}
+
+impl CodedError for TimestampError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidNanoseconds { .. } => ErrorCode::new("CORE", 201),
+            Self::OutOfRangeForSystemTime { .. } => ErrorCode::new("CORE", 202),
+            Self::OutOfRangeForChrono { .. } => ErrorCode::new("CORE", 203),
+            Self::InvalidRfc3339 { .. } => ErrorCode::new("CORE", 204),
+        }
+    }
 }
 
 impl fmt::Display for TimestampError {
@@ -153,12 +193,119 @@ impl fmt::Display for TimestampError {
                     "timestamp {unix_nanos}ns is out of range for chrono::DateTime<Utc>"
                 )
             }
+            Self::InvalidRfc3339 { value } => {
+                write!(f, "`{value}` is not an RFC 3339 timestamp")
+            }
         }
     }
 }
 
 impl std::error::Error for TimestampError {}
 
+/// Days since the Unix epoch to proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i128) -> (i128, u32, u32) {
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i128::from(month <= 2);
+    (year, month, day)
+}
+
+/// Proleptic Gregorian (year, month, day) to days since the Unix epoch.
+fn days_from_civil(year: i128, month: u32, day: u32) -> i128 {
+    let year = year - i128::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i128::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i128::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn parse_rfc3339(value: &str) -> Option<TimestampUtc> {
+    fn digits(value: &str, range: std::ops::Range<usize>) -> Option<u32> {
+        let field = value.get(range)?;
+        if !field.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        field.parse().ok()
+    }
+
+    let bytes = value.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let year = digits(value, 0..4)?;
+    let month = digits(value, 5..7)?;
+    let day = digits(value, 8..10)?;
+    let hour = digits(value, 11..13)?;
+    let minute = digits(value, 14..16)?;
+    let second = digits(value, 17..19)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+    // Leap seconds (`:60`) are folded into the following second.
+    if second > 60 {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    let mut nanos = 0_u32;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        for (index, byte) in fraction.bytes().take(len.min(9)).enumerate() {
+            nanos += u32::from(byte - b'0') * 10_u32.pow(8 - index as u32);
+        }
+        rest = &fraction[len..];
+    }
+    let offset_seconds = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            if rest.len() != 6 || rest.as_bytes()[3] != b':' {
+                return None;
+            }
+            let offset_hours = digits(rest, 1..3)?;
+            let offset_minutes = digits(rest, 4..6)?;
+            if offset_hours > 23 || offset_minutes > 59 {
+                return None;
+            }
+            sign * i128::from(offset_hours * 3_600 + offset_minutes * 60)
+        }
+    };
+
+    let seconds = days_from_civil(i128::from(year), month, day) * 86_400
+        + i128::from(hour * 3_600 + minute * 60 + second)
+        - offset_seconds;
+    Some(TimestampUtc::from_unix_nanos(
+        seconds * NANOS_PER_SECOND + i128::from(nanos),
+    ))
+}
+
 fn duration_to_nanos(delta: Duration) -> i128 {
     (delta.as_secs() as i128) * NANOS_PER_SECOND + (delta.subsec_nanos() as i128)
 }
@@ -216,6 +363,46 @@ mod tests {
         assert_eq!(roundtrip, system_time);
     }
 
+    #[test]
+    fn parses_rfc3339_with_fraction_and_offset() {
+        let parsed =
+            TimestampUtc::parse_rfc3339("2023-11-14T22:13:20.123456789Z").expect("valid timestamp");
+        assert_eq!(parsed.unix_nanos(), 1_700_000_000_123_456_789);
+        assert_eq!(
+            TimestampUtc::parse_rfc3339("2023-11-14T23:13:20+01:00"),
+            Ok(TimestampUtc::from_unix_seconds(1_700_000_000))
+        );
+        assert_eq!(
+            TimestampUtc::parse_rfc3339("1969-12-31T23:59:59.5Z"),
+            Ok(TimestampUtc::from_unix_nanos(-500_000_000))
+        );
+        for invalid in ["2023-11-14", "2023-13-01T00:00:00Z", "2023-11-14T22:13:20"] {
+            assert!(matches!(
+                TimestampUtc::parse_rfc3339(invalid),
+                Err(TimestampError::InvalidRfc3339 { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn formats_rfc3339_with_millis() {
+        let timestamp = TimestampUtc::from_unix_seconds_nanos(1_700_000_000, 123_456_789)
+            .expect("valid timestamp");
+        assert_eq!(timestamp.to_rfc3339_millis(), "2023-11-14T22:13:20.123Z");
+        assert_eq!(
+            TimestampUtc::UNIX_EPOCH.to_rfc3339_millis(),
+            "1970-01-01T00:00:00.000Z"
+        );
+        assert_eq!(
+            TimestampUtc::from_unix_seconds(951_782_400).to_rfc3339_millis(),
+            "2000-02-29T00:00:00.000Z"
+        );
+        assert_eq!(
+            TimestampUtc::from_unix_nanos(-1).to_rfc3339_millis(),
+            "1969-12-31T23:59:59.999Z"
+        );
+    }
+
     #[cfg(feature = "chrono")]
     #[test]
     fn chrono_roundtrip_preserves_value() {
@@ -0,0 +1,546 @@
+//! X.509 inspection and chain checks for TLS identities.
+//!
+//! [`CertificateInfo`] summarises a certificate the way operators ask about
+//! it: who it names, when it expires, what key it carries and the SPKI pin
+//! `server_spki_pin` compares against. [`PemIdentity::inspect`] applies that
+//! to a whole identity and [`verify_client_chain`] checks a client chain
+//! against a CA bundle the way a TAK server does during mutual TLS.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use const_oid::db::{rfc5912, rfc8410};
+use der::asn1::{AnyRef, ObjectIdentifier, UintRef};
+use der::{Decode, Encode, Sequence};
+use ring::rand::SystemRandom;
+use ring::signature::{self, KeyPair};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use sha2::{Digest, Sha256};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::{BasicConstraints, SubjectAltName as SubjectAltNameExtension};
+use x509_cert::spki::{AlgorithmIdentifierRef, SubjectPublicKeyInfoOwned};
+use x509_cert::Certificate;
+
+use crate::{CryptoError, PemIdentity, Result};
+
+/// Public key algorithm of a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyType {
+    Rsa { bits: usize },
+    EcdsaP256,
+    EcdsaP384,
+    EcdsaP521,
+    Ed25519,
+    Other { oid: String },
+}
+
+impl KeyType {
+    fn from_spki(spki: &SubjectPublicKeyInfoOwned) -> Self {
+        let algorithm = spki.algorithm.oid;
+        if algorithm == rfc5912::RSA_ENCRYPTION {
+            let bits = spki
+                .subject_public_key
+                .as_bytes()
+                .and_then(|key| RsaPublicKey::from_der(key).ok())
+                .map_or(0, |key| uint_bits(key.modulus.as_bytes()));
+            return Self::Rsa { bits };
+        }
+        if algorithm == rfc5912::ID_EC_PUBLIC_KEY {
+            let curve = spki
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+            return match curve {
+                Some(curve) if curve == rfc5912::SECP_256_R_1 => Self::EcdsaP256,
+                Some(curve) if curve == rfc5912::SECP_384_R_1 => Self::EcdsaP384,
+                Some(curve) if curve == rfc5912::SECP_521_R_1 => Self::EcdsaP521,
+                Some(curve) => Self::Other {
+                    oid: curve.to_string(),
+                },
+                None => Self::Other {
+                    oid: algorithm.to_string(),
+                },
+            };
+        }
+        if algorithm == rfc8410::ID_ED_25519 {
+            return Self::Ed25519;
+        }
+        Self::Other {
+            oid: algorithm.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rsa { bits } => write!(f, "rsa-{bits}"),
+            Self::EcdsaP256 => f.write_str("ecdsa-p256"),
+            Self::EcdsaP384 => f.write_str("ecdsa-p384"),
+            Self::EcdsaP521 => f.write_str("ecdsa-p521"),
+            Self::Ed25519 => f.write_str("ed25519"),
+            Self::Other { oid } => write!(f, "oid-{oid}"),
+        }
+    }
+}
+
+/// One subjectAltName entry, displayed with OpenSSL's prefixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+    Email(String),
+    Uri(String),
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(name) => write!(f, "DNS:{name}"),
+            Self::Ip(address) => write!(f, "IP:{address}"),
+            Self::Email(address) => write!(f, "email:{address}"),
+            Self::Uri(uri) => write!(f, "URI:{uri}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    /// RFC 4514 rendering of the subject name.
+    pub subject: String,
+    pub issuer: String,
+    /// Serial number as upper-case hex.
+    pub serial: String,
+    pub not_before: SystemTime,
+    pub not_after: SystemTime,
+    pub subject_alt_names: Vec<SubjectAltName>,
+    pub key_type: KeyType,
+    /// Whether basicConstraints marks this as a CA certificate.
+    pub is_ca: bool,
+    /// SHA-256 of the DER SubjectPublicKeyInfo.
+    pub spki_sha256: [u8; 32],
+}
+
+impl CertificateInfo {
+    pub fn from_der(certificate_der: &[u8]) -> Result<Self> {
+        let certificate = Certificate::from_der(certificate_der).map_err(invalid_certificate)?;
+        let tbs = &certificate.tbs_certificate;
+
+        let mut subject_alt_names = Vec::new();
+        if let Some((_, names)) = tbs
+            .get::<SubjectAltNameExtension>()
+            .map_err(invalid_certificate)?
+        {
+            for name in names.0 {
+                match name {
+                    GeneralName::DnsName(name) => {
+                        subject_alt_names.push(SubjectAltName::Dns(name.to_string()));
+                    }
+                    GeneralName::Rfc822Name(address) => {
+                        subject_alt_names.push(SubjectAltName::Email(address.to_string()));
+                    }
+                    GeneralName::UniformResourceIdentifier(uri) => {
+                        subject_alt_names.push(SubjectAltName::Uri(uri.to_string()));
+                    }
+                    GeneralName::IpAddress(octets) => {
+                        let octets = octets.as_bytes();
+                        let address = match octets.len() {
+                            4 => <[u8; 4]>::try_from(octets).ok().map(IpAddr::from),
+                            16 => <[u8; 16]>::try_from(octets).ok().map(IpAddr::from),
+                            _ => None,
+                        };
+                        if let Some(address) = address {
+                            subject_alt_names.push(SubjectAltName::Ip(address));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let is_ca = tbs
+            .get::<BasicConstraints>()
+            .map_err(invalid_certificate)?
+            .is_some_and(|(_, constraints)| constraints.ca);
+
+        let spki_der = tbs
+            .subject_public_key_info
+            .to_der()
+            .map_err(invalid_certificate)?;
+        Ok(Self {
+            subject: tbs.subject.to_string(),
+            issuer: tbs.issuer.to_string(),
+            serial: tbs
+                .serial_number
+                .as_bytes()
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect(),
+            not_before: tbs.validity.not_before.to_system_time(),
+            not_after: tbs.validity.not_after.to_system_time(),
+            subject_alt_names,
+            key_type: KeyType::from_spki(&tbs.subject_public_key_info),
+            is_ca,
+            spki_sha256: Sha256::digest(&spki_der).into(),
+        })
+    }
+
+    /// The SPKI digest as base64, the form `crypto.server_spki_pin` takes.
+    #[must_use]
+    pub fn spki_pin(&self) -> String {
+        BASE64.encode(self.spki_sha256)
+    }
+
+    #[must_use]
+    pub fn is_valid_at(&self, now: SystemTime) -> bool {
+        self.not_before <= now && now <= self.not_after
+    }
+}
+
+/// What [`PemIdentity::inspect`] found. Empty PEM fields leave their part of
+/// the report empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityReport {
+    /// The client certificate followed by any intermediates, in file order.
+    pub client_chain: Vec<CertificateInfo>,
+    pub ca_certificates: Vec<CertificateInfo>,
+    /// Whether the private key belongs to the client certificate; `None`
+    /// when either is missing or the key type cannot be checked.
+    pub key_matches_certificate: Option<bool>,
+}
+
+impl PemIdentity {
+    /// Parses every certificate in the identity and checks the private key
+    /// against the client certificate.
+    pub fn inspect(&self) -> Result<IdentityReport> {
+        let client_chain = optional_pem_certificates("client_cert_pem", &self.client_cert_pem)?;
+        let ca_certificates = optional_pem_certificates("ca_cert_pem", &self.ca_cert_pem)?;
+        let key_matches_certificate = match (client_chain.first(), self.client_key_pem.trim()) {
+            (Some(leaf), key) if !key.is_empty() => {
+                let key = pem_private_key("client_key_pem", key)?;
+                key_matches_certificate(&key, leaf)?
+            }
+            _ => None,
+        };
+        Ok(IdentityReport {
+            client_chain: client_chain
+                .iter()
+                .map(|der| CertificateInfo::from_der(der))
+                .collect::<Result<_>>()?,
+            ca_certificates: ca_certificates
+                .iter()
+                .map(|der| CertificateInfo::from_der(der))
+                .collect::<Result<_>>()?,
+            key_matches_certificate,
+        })
+    }
+}
+
+/// Checks that the first certificate in `chain_pem`, helped by any
+/// intermediates after it, chains to a certificate in `ca_pem` and is valid
+/// for TLS client authentication at `now`.
+pub fn verify_client_chain(ca_pem: &str, chain_pem: &str, now: SystemTime) -> Result<()> {
+    let anchors_der = pem_certificates("ca_cert_pem", ca_pem)?;
+    let chain_der = pem_certificates("client_cert_pem", chain_pem)?;
+    let anchors = anchors_der
+        .iter()
+        .map(|der| webpki::anchor_from_trusted_cert(der).map_err(chain_rejected))
+        .collect::<Result<Vec<_>>>()?;
+    let (leaf, intermediates) = chain_der.split_first().ok_or(CryptoError::InvalidPem {
+        field: "client_cert_pem",
+        reason: "no CERTIFICATE blocks".to_owned(),
+    })?;
+    let leaf = webpki::EndEntityCert::try_from(leaf).map_err(chain_rejected)?;
+    let now = UnixTime::since_unix_epoch(now.duration_since(UNIX_EPOCH).unwrap_or_default());
+    leaf.verify_for_usage(
+        webpki::ALL_VERIFICATION_ALGS,
+        &anchors,
+        intermediates,
+        now,
+        webpki::KeyUsage::client_auth(),
+        None,
+        None,
+    )
+    .map_err(chain_rejected)?;
+    Ok(())
+}
+
+/// DER bodies of every `CERTIFICATE` block in `pem`; at least one is
+/// required.
+pub fn pem_certificates(field: &'static str, pem: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certificates = optional_pem_certificates(field, pem)?;
+    if certificates.is_empty() {
+        return Err(CryptoError::InvalidPem {
+            field,
+            reason: "no CERTIFICATE blocks".to_owned(),
+        });
+    }
+    Ok(certificates)
+}
+
+fn optional_pem_certificates(
+    field: &'static str,
+    pem: &str,
+) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_slice_iter(pem.as_bytes())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|error| CryptoError::InvalidPem {
+            field,
+            reason: error.to_string(),
+        })
+}
+
+/// The private key in `pem` as PKCS#8 DER. PKCS#1 (`RSA PRIVATE KEY`) and
+/// SEC1 (`EC PRIVATE KEY`) keys are rewrapped.
+pub fn pem_private_key(field: &'static str, pem: &str) -> Result<Vec<u8>> {
+    let key =
+        PrivateKeyDer::from_pem_slice(pem.as_bytes()).map_err(|error| CryptoError::InvalidPem {
+            field,
+            reason: error.to_string(),
+        })?;
+    match key {
+        PrivateKeyDer::Pkcs8(key) => Ok(key.secret_pkcs8_der().to_vec()),
+        PrivateKeyDer::Pkcs1(key) => wrap_pkcs8(
+            AlgorithmIdentifierRef {
+                oid: rfc5912::RSA_ENCRYPTION,
+                parameters: Some(AnyRef::NULL),
+            },
+            key.secret_pkcs1_der(),
+        ),
+        PrivateKeyDer::Sec1(key) => {
+            let parsed =
+                sec1::EcPrivateKey::from_der(key.secret_sec1_der()).map_err(invalid_private_key)?;
+            let curve = parsed
+                .parameters
+                .and_then(|parameters| parameters.named_curve())
+                .ok_or_else(|| CryptoError::InvalidPrivateKey {
+                    reason: "EC key does not name its curve".to_owned(),
+                })?;
+            let curve = curve.to_der().map_err(invalid_private_key)?;
+            wrap_pkcs8(
+                AlgorithmIdentifierRef {
+                    oid: rfc5912::ID_EC_PUBLIC_KEY,
+                    parameters: Some(AnyRef::from_der(&curve).map_err(invalid_private_key)?),
+                },
+                key.secret_sec1_der(),
+            )
+        }
+        _ => Err(CryptoError::InvalidPrivateKey {
+            reason: "unsupported private key encoding".to_owned(),
+        }),
+    }
+}
+
+fn wrap_pkcs8(algorithm: AlgorithmIdentifierRef<'_>, private_key: &[u8]) -> Result<Vec<u8>> {
+    pkcs8::PrivateKeyInfo::new(algorithm, private_key)
+        .to_der()
+        .map_err(invalid_private_key)
+}
+
+/// Compares the public half of a PKCS#8 key with the certificate's SPKI.
+/// `None` when the key is of a type *ring* cannot load (RSA under 2048
+/// bits, P-521).
+pub(crate) fn key_matches_certificate(
+    pkcs8_der: &[u8],
+    certificate_der: &[u8],
+) -> Result<Option<bool>> {
+    let certificate = Certificate::from_der(certificate_der).map_err(invalid_certificate)?;
+    let spki = &certificate.tbs_certificate.subject_public_key_info;
+    let Some(certificate_key) = spki.subject_public_key.as_bytes() else {
+        return Ok(Some(false));
+    };
+    let info = pkcs8::PrivateKeyInfo::try_from(pkcs8_der).map_err(invalid_private_key)?;
+    if info.algorithm.oid != spki.algorithm.oid {
+        return Ok(Some(false));
+    }
+
+    let public_key = match KeyType::from_spki(spki) {
+        KeyType::Rsa { .. } => signature::RsaKeyPair::from_pkcs8(pkcs8_der)
+            .ok()
+            .map(|key| key.public_key().as_ref().to_vec()),
+        KeyType::EcdsaP256 => {
+            ecdsa_public_key(&signature::ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8_der)
+        }
+        KeyType::EcdsaP384 => {
+            ecdsa_public_key(&signature::ECDSA_P384_SHA384_ASN1_SIGNING, pkcs8_der)
+        }
+        KeyType::Ed25519 => signature::Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8_der)
+            .ok()
+            .map(|key| key.public_key().as_ref().to_vec()),
+        KeyType::EcdsaP521 | KeyType::Other { .. } => None,
+    };
+    Ok(public_key.map(|public_key| public_key == certificate_key))
+}
+
+fn ecdsa_public_key(
+    algorithm: &'static signature::EcdsaSigningAlgorithm,
+    pkcs8_der: &[u8],
+) -> Option<Vec<u8>> {
+    signature::EcdsaKeyPair::from_pkcs8(algorithm, pkcs8_der, &SystemRandom::new())
+        .ok()
+        .map(|key| key.public_key().as_ref().to_vec())
+}
+
+/// RFC 8017 `RSAPublicKey`, the subjectPublicKey of an RSA certificate.
+#[derive(Sequence)]
+struct RsaPublicKey<'a> {
+    modulus: UintRef<'a>,
+    #[allow(dead_code)]
+    public_exponent: UintRef<'a>,
+}
+
+fn uint_bits(bytes: &[u8]) -> usize {
+    match bytes.first() {
+        Some(first) => bytes.len() * 8 - first.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn invalid_certificate(error: der::Error) -> CryptoError {
+    CryptoError::InvalidCertificate {
+        reason: error.to_string(),
+    }
+}
+
+fn invalid_private_key(error: impl fmt::Display) -> CryptoError {
+    CryptoError::InvalidPrivateKey {
+        reason: error.to_string(),
+    }
+}
+
+fn chain_rejected(error: webpki::Error) -> CryptoError {
+    CryptoError::ChainRejected {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use base64::Engine;
+    use rcgen::{date_time_ymd, BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+    use sha2::{Digest, Sha256};
+
+    use super::{verify_client_chain, CertificateInfo, KeyType, SubjectAltName};
+    use crate::{CryptoError, PemIdentity};
+
+    fn ca() -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().expect("ca key");
+        let mut params = CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        (params.self_signed(&key).expect("ca cert"), key)
+    }
+
+    fn client(params: CertificateParams, ca: &(Certificate, KeyPair)) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().expect("client key");
+        let cert = params.signed_by(&key, &ca.0, &ca.1).expect("client cert");
+        (cert, key)
+    }
+
+    fn client_params() -> CertificateParams {
+        CertificateParams::new(vec!["rustak-client".to_owned(), "10.0.0.5".to_owned()])
+            .expect("client params")
+    }
+
+    #[test]
+    fn inspect_reports_names_key_type_and_spki_pin() {
+        let ca = ca();
+        let (cert, key) = client(client_params(), &ca);
+        let identity = PemIdentity {
+            ca_cert_pem: ca.0.pem(),
+            client_cert_pem: cert.pem(),
+            client_key_pem: key.serialize_pem(),
+        };
+
+        let report = identity.inspect().expect("inspect");
+        let leaf = &report.client_chain[0];
+        assert_eq!(
+            leaf.subject_alt_names,
+            [
+                SubjectAltName::Dns("rustak-client".to_owned()),
+                SubjectAltName::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5))),
+            ]
+        );
+        assert_eq!(leaf.key_type, KeyType::EcdsaP256);
+        assert_eq!(leaf.key_type.to_string(), "ecdsa-p256");
+        assert!(!leaf.is_ca);
+        assert!(leaf.is_valid_at(SystemTime::now()));
+        assert_eq!(
+            leaf.spki_pin(),
+            base64::engine::general_purpose::STANDARD.encode(Sha256::digest(key.public_key_der()))
+        );
+        assert!(report.ca_certificates[0].is_ca);
+        assert_eq!(leaf.issuer, report.ca_certificates[0].subject);
+        assert_eq!(report.key_matches_certificate, Some(true));
+
+        let other_key = KeyPair::generate().expect("other key");
+        let mismatched = PemIdentity {
+            client_key_pem: other_key.serialize_pem(),
+            ..identity
+        };
+        assert_eq!(
+            mismatched
+                .inspect()
+                .expect("inspect")
+                .key_matches_certificate,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn validity_reads_utc_and_generalized_times() {
+        let key = KeyPair::generate().expect("key");
+        let mut params = CertificateParams::new(vec!["tak.example".to_owned()]).expect("params");
+        params.not_before = date_time_ymd(2024, 1, 2);
+        params.not_after = date_time_ymd(2051, 6, 30);
+        let cert = params.self_signed(&key).expect("cert");
+
+        let info = CertificateInfo::from_der(cert.der()).expect("info");
+        assert_eq!(
+            info.not_before,
+            UNIX_EPOCH + Duration::from_secs(1_704_153_600)
+        );
+        assert_eq!(
+            info.not_after,
+            UNIX_EPOCH + Duration::from_secs(2_571_696_000)
+        );
+        assert!(CertificateInfo::from_der(&[0x30, 0x05, 0x02]).is_err());
+    }
+
+    #[test]
+    fn client_chains_must_lead_to_the_ca_and_be_current() {
+        let ca = ca();
+        let (cert, _) = client(client_params(), &ca);
+        verify_client_chain(&ca.0.pem(), &cert.pem(), SystemTime::now()).expect("issued by ca");
+
+        let (foreign, _) = client(client_params(), &self::ca());
+        let error = verify_client_chain(&ca.0.pem(), &foreign.pem(), SystemTime::now())
+            .expect_err("issued by another ca");
+        assert!(matches!(error, CryptoError::ChainRejected { .. }));
+
+        let mut expired = client_params();
+        expired.not_before = date_time_ymd(2000, 1, 1);
+        expired.not_after = date_time_ymd(2001, 1, 1);
+        let (expired, _) = client(expired, &ca);
+        let error = verify_client_chain(&ca.0.pem(), &expired.pem(), SystemTime::now())
+            .expect_err("expired");
+        assert!(
+            matches!(&error, CryptoError::ChainRejected { reason } if reason.starts_with("CertExpired")),
+            "{error}"
+        );
+
+        assert!(matches!(
+            verify_client_chain(&ca.0.pem(), "no pem here", SystemTime::now()),
+            Err(CryptoError::InvalidPem {
+                field: "client_cert_pem",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,211 @@
+//! Key and PKCS#10 request generation for certificate enrollment.
+//!
+//! [`CertificateRequest::generate`] creates an ECDSA P-256 key in memory and a
+//! CSR signed with it. The CSR goes to the signing service; the key stays here
+//! until [`CertificateRequest::into_identity`] pairs it with the issued
+//! certificate.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use const_oid::db::{rfc4519, rfc5912};
+use der::asn1::{Any, BitString, ObjectIdentifier, PrintableStringRef, SetOfVec, Utf8StringRef};
+use der::Encode;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use x509_cert::attr::AttributeTypeAndValue;
+use x509_cert::name::{Name, RdnSequence, RelativeDistinguishedName};
+use x509_cert::request::{CertReq, CertReqInfo, Version};
+use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+
+use crate::{CryptoError, PemIdentity, Result};
+
+/// A fresh key and the CSR that asks for it to be certified.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CertificateRequest {
+    pub csr_der: Vec<u8>,
+    /// PKCS#8 `PRIVATE KEY` PEM.
+    pub client_key_pem: String,
+}
+
+impl std::fmt::Debug for CertificateRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertificateRequest")
+            .field("csr_der", &self.csr_der.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertificateRequest {
+    /// Generates a key and a CSR whose subject is `subject`, given as
+    /// `(attribute, value)` pairs in DN order, e.g. `[("O", "TAK"),
+    /// ("CN", "alpha")]`. Attributes are `C`, `ST`, `L`, `O`, `OU` or `CN`.
+    pub fn generate(subject: &[(&str, &str)]) -> Result<Self> {
+        let subject = subject_name(subject)?;
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+            .map_err(|_| csr_failed("key generation failed"))?;
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .map_err(|_| csr_failed("generated key was rejected"))?;
+
+        let info = CertReqInfo {
+            version: Version::V1,
+            subject,
+            public_key: SubjectPublicKeyInfoOwned {
+                algorithm: AlgorithmIdentifierOwned {
+                    oid: rfc5912::ID_EC_PUBLIC_KEY,
+                    parameters: Some(Any::encode_from(&rfc5912::SECP_256_R_1).map_err(encoding)?),
+                },
+                subject_public_key: BitString::from_bytes(key_pair.public_key().as_ref())
+                    .map_err(encoding)?,
+            },
+            attributes: SetOfVec::new(),
+        };
+        let signature = key_pair
+            .sign(&rng, &info.to_der().map_err(encoding)?)
+            .map_err(|_| csr_failed("signing the request failed"))?;
+        let request = CertReq {
+            info,
+            algorithm: AlgorithmIdentifierOwned {
+                oid: rfc5912::ECDSA_WITH_SHA_256,
+                parameters: None,
+            },
+            signature: BitString::from_bytes(signature.as_ref()).map_err(encoding)?,
+        };
+
+        Ok(Self {
+            csr_der: request.to_der().map_err(encoding)?,
+            client_key_pem: der::pem::encode_string(
+                "PRIVATE KEY",
+                der::pem::LineEnding::LF,
+                pkcs8.as_ref(),
+            )
+            .map_err(|error| csr_failed(&error.to_string()))?,
+        })
+    }
+
+    /// The CSR as unwrapped base64, the body TAK Server's `signClient`
+    /// endpoint expects.
+    #[must_use]
+    pub fn csr_base64(&self) -> String {
+        BASE64.encode(&self.csr_der)
+    }
+
+    /// Pairs the key with the certificate issued for this request.
+    #[must_use]
+    pub fn into_identity(self, client_cert_pem: String, ca_cert_pem: String) -> PemIdentity {
+        PemIdentity {
+            ca_cert_pem,
+            client_cert_pem,
+            client_key_pem: self.client_key_pem,
+        }
+    }
+}
+
+fn subject_name(subject: &[(&str, &str)]) -> Result<Name> {
+    if !subject.iter().any(|(attribute, _)| *attribute == "CN") {
+        return Err(invalid_subject("CN", "a common name is required"));
+    }
+    let rdns = subject
+        .iter()
+        .map(|(attribute, value)| {
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(invalid_subject(attribute, "value must not be empty"));
+            }
+            let (oid, value) = match *attribute {
+                "C" => (
+                    rfc4519::C,
+                    PrintableStringRef::new(value)
+                        .map_err(|_| invalid_subject(attribute, "not a printable string"))
+                        .and_then(|value| Any::encode_from(&value).map_err(encoding))?,
+                ),
+                "ST" | "L" | "O" | "OU" | "CN" => (
+                    attribute_oid(attribute),
+                    Utf8StringRef::new(value)
+                        .and_then(|value| Any::encode_from(&value))
+                        .map_err(encoding)?,
+                ),
+                _ => return Err(invalid_subject(attribute, "unsupported attribute")),
+            };
+            SetOfVec::try_from(vec![AttributeTypeAndValue { oid, value }])
+                .map(RelativeDistinguishedName)
+                .map_err(encoding)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RdnSequence(rdns))
+}
+
+fn attribute_oid(attribute: &str) -> ObjectIdentifier {
+    match attribute {
+        "ST" => rfc4519::ST,
+        "L" => rfc4519::L,
+        "O" => rfc4519::O,
+        "OU" => rfc4519::OU,
+        _ => rfc4519::CN,
+    }
+}
+
+fn invalid_subject(attribute: &str, reason: &'static str) -> CryptoError {
+    CryptoError::InvalidCsrSubject {
+        attribute: attribute.to_owned(),
+        reason,
+    }
+}
+
+fn csr_failed(reason: &str) -> CryptoError {
+    CryptoError::CsrGeneration {
+        reason: reason.to_owned(),
+    }
+}
+
+fn encoding(error: der::Error) -> CryptoError {
+    CryptoError::CsrGeneration {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use der::{Decode, Encode};
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+    use x509_cert::request::CertReq;
+
+    use super::CertificateRequest;
+    use crate::CryptoError;
+
+    #[test]
+    fn requests_are_self_signed_and_name_the_subject() {
+        let request = CertificateRequest::generate(&[("O", "TAK"), ("OU", "TAK"), ("CN", "alpha")])
+            .expect("request");
+        let parsed = CertReq::from_der(&request.csr_der).expect("csr parses");
+        // RFC 4514 renders the most specific RDN first.
+        assert_eq!(parsed.info.subject.to_string(), "CN=alpha,OU=TAK,O=TAK");
+
+        let public_key = parsed
+            .info
+            .public_key
+            .subject_public_key
+            .as_bytes()
+            .expect("public key");
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, public_key)
+            .verify(
+                &parsed.info.to_der().expect("info"),
+                parsed.signature.as_bytes().expect("signature"),
+            )
+            .expect("csr signature verifies");
+        assert!(crate::certs::pem_private_key("client_key_pem", &request.client_key_pem).is_ok());
+    }
+
+    #[test]
+    fn subjects_need_a_common_name_and_known_attributes() {
+        assert!(matches!(
+            CertificateRequest::generate(&[("O", "TAK")]),
+            Err(CryptoError::InvalidCsrSubject { ref attribute, .. }) if attribute == "CN"
+        ));
+        assert!(matches!(
+            CertificateRequest::generate(&[("DC", "tak"), ("CN", "alpha")]),
+            Err(CryptoError::InvalidCsrSubject { ref attribute, .. }) if attribute == "DC"
+        ));
+    }
+}
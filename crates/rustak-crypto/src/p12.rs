@@ -0,0 +1,507 @@
+//! PKCS#12 (`.p12`/`.pfx`) archives.
+//!
+//! [`decode_pkcs12`] unpacks the certificates and private key from archives
+//! written by OpenSSL, Java `keytool` or TAK Server's enrollment tooling:
+//! PBES2 with AES-CBC and the legacy PKCS#12 3DES and RC2 schemes are all
+//! read. The archive MAC is checked before anything is decrypted, so a wrong
+//! password is reported as [`CryptoError::Pkcs12WrongPassword`] rather than
+//! as corruption. [`encode_pkcs12`] writes the profile OpenSSL 3 defaults
+//! to: PBES2 with AES-256-CBC and PBKDF2-HMAC-SHA256, plus an HMAC-SHA256
+//! MAC.
+
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, InnerIvInit, KeyIvInit};
+use cms::content_info::{CmsVersion, ContentInfo};
+use cms::encrypted_data::EncryptedData;
+use cms::enveloped_data::EncryptedContentInfo;
+use const_oid::db::{rfc5911, rfc5912};
+use der::asn1::{ContextSpecific, ObjectIdentifier, OctetString, SetOfVec};
+use der::{Any, Decode, Encode};
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::{Digest, FixedOutputReset};
+use hmac::{Mac, SimpleHmac};
+use pkcs12::authenticated_safe::AuthenticatedSafe;
+use pkcs12::cert_type::CertBag;
+use pkcs12::digest_info::DigestInfo;
+use pkcs12::kdf::{derive_key_utf8, Pkcs12KeyType};
+use pkcs12::mac_data::MacData;
+use pkcs12::pbe_params::{EncryptedPrivateKeyInfo, Pkcs12PbeParams};
+use pkcs12::pfx::{Pfx, Version};
+use pkcs12::safe_bag::{SafeBag, SafeContents};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use x509_cert::attr::{Attribute, Attributes};
+use x509_cert::spki::AlgorithmIdentifierOwned;
+use x509_cert::Certificate;
+
+use crate::certs::{key_matches_certificate, pem_certificates, pem_private_key};
+use crate::{CryptoError, PemIdentity, Result};
+
+/// PBKDF2 and MAC iteration count for archives we write, OpenSSL's default.
+pub const PKCS12_ITERATIONS: u32 = 2048;
+
+/// PKCS#9 `localKeyId`, which pairs a key bag with its certificate.
+const LOCAL_KEY_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.21");
+
+const SALT_LEN: usize = 16;
+
+/// Unpacks a PKCS#12 archive. The certificate paired with the private key
+/// and any intermediates become `client_cert_pem`; self-issued
+/// certificates become `ca_cert_pem`, which is empty when the archive
+/// carries no CA.
+pub fn decode_pkcs12(archive: &[u8], password: &str) -> Result<PemIdentity> {
+    let pfx = Pfx::from_der(archive).map_err(corrupt)?;
+    if pfx.auth_safe.content_type != rfc5911::ID_DATA {
+        return Err(unsupported(pfx.auth_safe.content_type));
+    }
+    let auth_safe = pfx
+        .auth_safe
+        .content
+        .decode_as::<OctetString>()
+        .map_err(corrupt)?;
+    let mac_verified = match &pfx.mac_data {
+        Some(mac_data) => {
+            verify_mac(mac_data, password, auth_safe.as_bytes())?;
+            true
+        }
+        None => false,
+    };
+    let decrypt = |algorithm: &AlgorithmIdentifierOwned, ciphertext: &[u8]| {
+        decrypt(algorithm, password, ciphertext).map_err(|error| match error {
+            // Without a MAC, bad padding is the only sign of a wrong password.
+            CryptoError::Pkcs12WrongPassword if mac_verified => CryptoError::CorruptPkcs12 {
+                reason: "encrypted content does not decrypt".to_owned(),
+            },
+            other => other,
+        })
+    };
+
+    let mut certificates = Vec::new();
+    let mut keys = Vec::new();
+    for content in AuthenticatedSafe::from_der(auth_safe.as_bytes()).map_err(corrupt)? {
+        let safe_contents = if content.content_type == rfc5911::ID_DATA {
+            content
+                .content
+                .decode_as::<OctetString>()
+                .map_err(corrupt)?
+                .into_bytes()
+        } else if content.content_type == rfc5911::ID_ENCRYPTED_DATA {
+            let encrypted = content
+                .content
+                .decode_as::<EncryptedData>()
+                .map_err(corrupt)?;
+            let ciphertext = encrypted
+                .enc_content_info
+                .encrypted_content
+                .ok_or_else(|| CryptoError::CorruptPkcs12 {
+                    reason: "encrypted content is missing".to_owned(),
+                })?;
+            decrypt(
+                &encrypted.enc_content_info.content_enc_alg,
+                ciphertext.as_bytes(),
+            )?
+        } else {
+            return Err(unsupported(content.content_type));
+        };
+
+        for bag in SafeContents::from_der(&safe_contents).map_err(corrupt)? {
+            let key_id = local_key_id(&bag);
+            if bag.bag_id == pkcs12::PKCS_12_CERT_BAG_OID {
+                let cert_bag = ContextSpecific::<CertBag>::from_der(&bag.bag_value)
+                    .map_err(corrupt)?
+                    .value;
+                if cert_bag.cert_id == pkcs12::PKCS_12_X509_CERT_OID {
+                    certificates.push((cert_bag.cert_value.into_bytes(), key_id));
+                }
+            } else if bag.bag_id == pkcs12::PKCS_12_PKCS8_KEY_BAG_OID {
+                let encrypted =
+                    ContextSpecific::<EncryptedPrivateKeyInfo>::from_der(&bag.bag_value)
+                        .map_err(corrupt)?
+                        .value;
+                let key = decrypt(
+                    &encrypted.encryption_algorithm,
+                    encrypted.encrypted_data.as_bytes(),
+                )?;
+                keys.push((key, key_id));
+            } else if bag.bag_id == pkcs12::PKCS_12_KEY_BAG_OID {
+                let key = ContextSpecific::<Any>::from_der(&bag.bag_value)
+                    .map_err(corrupt)?
+                    .value
+                    .to_der()
+                    .map_err(corrupt)?;
+                keys.push((key, key_id));
+            }
+        }
+    }
+
+    let Some((key, key_id)) = keys.into_iter().next() else {
+        return Err(CryptoError::CorruptPkcs12 {
+            reason: "archive holds no private key".to_owned(),
+        });
+    };
+    if certificates.is_empty() {
+        return Err(CryptoError::CorruptPkcs12 {
+            reason: "archive holds no certificate".to_owned(),
+        });
+    }
+    let leaf = certificates
+        .iter()
+        .position(|(_, id)| id.is_some() && *id == key_id)
+        .or_else(|| {
+            certificates.iter().position(|(certificate, _)| {
+                matches!(key_matches_certificate(&key, certificate), Ok(Some(true)))
+            })
+        })
+        .unwrap_or(0);
+
+    let mut client_cert_pem = pem_encode("CERTIFICATE", &certificates[leaf].0)?;
+    let mut ca_cert_pem = String::new();
+    for (index, (certificate, _)) in certificates.iter().enumerate() {
+        if index == leaf {
+            continue;
+        }
+        let parsed = Certificate::from_der(certificate).map_err(corrupt)?;
+        let block = pem_encode("CERTIFICATE", certificate)?;
+        if parsed.tbs_certificate.subject == parsed.tbs_certificate.issuer {
+            ca_cert_pem.push_str(&block);
+        } else {
+            client_cert_pem.push_str(&block);
+        }
+    }
+    Ok(PemIdentity {
+        ca_cert_pem,
+        client_cert_pem,
+        client_key_pem: pem_encode("PRIVATE KEY", &key)?,
+    })
+}
+
+/// Packs a PEM identity into a PKCS#12 archive protected by `password`.
+/// The client chain and CA bundle share one encrypted certificate bag; the
+/// key is stored PKCS#8-encrypted and paired with the first client
+/// certificate through `localKeyId`.
+pub fn encode_pkcs12(identity: &PemIdentity, password: &str) -> Result<Vec<u8>> {
+    let chain = pem_certificates("client_cert_pem", &identity.client_cert_pem)?;
+    let ca = if identity.ca_cert_pem.trim().is_empty() {
+        Vec::new()
+    } else {
+        pem_certificates("ca_cert_pem", &identity.ca_cert_pem)?
+    };
+    let key = pem_private_key("client_key_pem", &identity.client_key_pem)?;
+    let key_id = Sha1::digest(&chain[0]).to_vec();
+    let rng = SystemRandom::new();
+
+    let (algorithm, encrypted_key) = encrypt(password, &key, &rng)?;
+    let key_bag = SafeBag {
+        bag_id: pkcs12::PKCS_12_PKCS8_KEY_BAG_OID,
+        bag_value: EncryptedPrivateKeyInfo {
+            encryption_algorithm: algorithm,
+            encrypted_data: OctetString::new(encrypted_key).map_err(corrupt)?,
+        }
+        .to_der()
+        .map_err(corrupt)?,
+        bag_attributes: Some(local_key_id_attributes(&key_id)?),
+    };
+
+    let mut cert_bags = Vec::with_capacity(chain.len() + ca.len());
+    for (index, certificate) in chain.iter().chain(&ca).enumerate() {
+        cert_bags.push(SafeBag {
+            bag_id: pkcs12::PKCS_12_CERT_BAG_OID,
+            bag_value: CertBag {
+                cert_id: pkcs12::PKCS_12_X509_CERT_OID,
+                cert_value: OctetString::new(certificate.to_vec()).map_err(corrupt)?,
+            }
+            .to_der()
+            .map_err(corrupt)?,
+            bag_attributes: if index == 0 {
+                Some(local_key_id_attributes(&key_id)?)
+            } else {
+                None
+            },
+        });
+    }
+    let (algorithm, encrypted_certs) =
+        encrypt(password, &cert_bags.to_der().map_err(corrupt)?, &rng)?;
+    let certs_content = ContentInfo {
+        content_type: rfc5911::ID_ENCRYPTED_DATA,
+        content: Any::encode_from(&EncryptedData {
+            version: CmsVersion::V0,
+            enc_content_info: EncryptedContentInfo {
+                content_type: rfc5911::ID_DATA,
+                content_enc_alg: algorithm,
+                encrypted_content: Some(OctetString::new(encrypted_certs).map_err(corrupt)?),
+            },
+            unprotected_attrs: None,
+        })
+        .map_err(corrupt)?,
+    };
+    let key_content = ContentInfo {
+        content_type: rfc5911::ID_DATA,
+        content: Any::encode_from(
+            &OctetString::new(vec![key_bag].to_der().map_err(corrupt)?).map_err(corrupt)?,
+        )
+        .map_err(corrupt)?,
+    };
+    let auth_safe = vec![certs_content, key_content].to_der().map_err(corrupt)?;
+
+    let salt = random_bytes::<SALT_LEN>(&rng)?;
+    let digest = pkcs12_hmac::<Sha256>(password, &salt, PKCS12_ITERATIONS as i32, &auth_safe)?
+        .finalize()
+        .into_bytes();
+    Pfx {
+        version: Version::V3,
+        auth_safe: ContentInfo {
+            content_type: rfc5911::ID_DATA,
+            content: Any::encode_from(&OctetString::new(auth_safe).map_err(corrupt)?)
+                .map_err(corrupt)?,
+        },
+        mac_data: Some(MacData {
+            mac: DigestInfo {
+                algorithm: AlgorithmIdentifierOwned {
+                    oid: rfc5912::ID_SHA_256,
+                    parameters: Some(Any::null()),
+                },
+                digest: OctetString::new(digest.to_vec()).map_err(corrupt)?,
+            },
+            mac_salt: OctetString::new(salt.to_vec()).map_err(corrupt)?,
+            iterations: PKCS12_ITERATIONS as i32,
+        }),
+    }
+    .to_der()
+    .map_err(corrupt)
+}
+
+fn verify_mac(mac_data: &MacData, password: &str, auth_safe: &[u8]) -> Result<()> {
+    let salt = mac_data.mac_salt.as_bytes();
+    let iterations = mac_data.iterations;
+    let expected = mac_data.mac.digest.as_bytes();
+    let oid = mac_data.mac.algorithm.oid;
+    let verified = if oid == rfc5912::ID_SHA_1 {
+        pkcs12_hmac::<Sha1>(password, salt, iterations, auth_safe)?.verify_slice(expected)
+    } else if oid == rfc5912::ID_SHA_256 {
+        pkcs12_hmac::<Sha256>(password, salt, iterations, auth_safe)?.verify_slice(expected)
+    } else if oid == rfc5912::ID_SHA_384 {
+        pkcs12_hmac::<Sha384>(password, salt, iterations, auth_safe)?.verify_slice(expected)
+    } else if oid == rfc5912::ID_SHA_512 {
+        pkcs12_hmac::<Sha512>(password, salt, iterations, auth_safe)?.verify_slice(expected)
+    } else {
+        return Err(unsupported(oid));
+    };
+    verified.map_err(|_| CryptoError::Pkcs12WrongPassword)
+}
+
+/// HMAC over `data` keyed with the RFC 7292 appendix B MAC key.
+fn pkcs12_hmac<D>(
+    password: &str,
+    salt: &[u8],
+    iterations: i32,
+    data: &[u8],
+) -> Result<SimpleHmac<D>>
+where
+    D: Digest + FixedOutputReset + BlockSizeUser,
+{
+    let key = derive_key_utf8::<D>(
+        password,
+        salt,
+        Pkcs12KeyType::Mac,
+        iterations,
+        <D as Digest>::output_size(),
+    )
+    .map_err(corrupt)?;
+    let mut mac =
+        <SimpleHmac<D> as Mac>::new_from_slice(&key).map_err(|_| CryptoError::CorruptPkcs12 {
+            reason: "MAC key has an invalid length".to_owned(),
+        })?;
+    mac.update(data);
+    Ok(mac)
+}
+
+fn decrypt(
+    algorithm: &AlgorithmIdentifierOwned,
+    password: &str,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    let parameters = algorithm
+        .parameters
+        .as_ref()
+        .ok_or_else(|| CryptoError::CorruptPkcs12 {
+            reason: format!("algorithm {} has no parameters", algorithm.oid),
+        })?
+        .to_der()
+        .map_err(corrupt)?;
+
+    if algorithm.oid == pkcs5::pbes2::PBES2_OID {
+        let scheme = pkcs5::pbes2::Parameters::from_der(&parameters).map_err(corrupt)?;
+        return scheme
+            .decrypt(password, ciphertext)
+            .map_err(|error| match error {
+                // pkcs5 0.7 reports bad padding as `EncryptFailed`.
+                pkcs5::Error::DecryptFailed | pkcs5::Error::EncryptFailed => {
+                    CryptoError::Pkcs12WrongPassword
+                }
+                pkcs5::Error::UnsupportedAlgorithm { oid } => unsupported(oid),
+                other => CryptoError::CorruptPkcs12 {
+                    reason: other.to_string(),
+                },
+            });
+    }
+
+    let pbe = Pkcs12PbeParams::from_der(&parameters).map_err(corrupt)?;
+    let derive = |kind, len| {
+        derive_key_utf8::<Sha1>(password, pbe.salt.as_bytes(), kind, pbe.iterations, len)
+            .map_err(corrupt)
+    };
+    let plaintext = if algorithm.oid == pkcs12::PKCS_12_PBE_WITH_SHAAND3_KEY_TRIPLE_DES_CBC {
+        let key = derive(Pkcs12KeyType::EncryptionKey, 24)?;
+        let iv = derive(Pkcs12KeyType::Iv, 8)?;
+        cbc::Decryptor::<des::TdesEde3>::new_from_slices(&key, &iv)
+            .map_err(|_| invalid_cipher_input())?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+    } else if algorithm.oid == pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC
+        || algorithm.oid == pkcs12::PKCS_12_PBE_WITH_SHAAND128_BIT_RC2_CBC
+    {
+        let key_len = if algorithm.oid == pkcs12::PKCS_12_PBEWITH_SHAAND40_BIT_RC2_CBC {
+            5
+        } else {
+            16
+        };
+        let key = derive(Pkcs12KeyType::EncryptionKey, key_len)?;
+        let iv = derive(Pkcs12KeyType::Iv, 8)?;
+        let cipher = rc2::Rc2::new_with_eff_key_len(&key, key_len * 8);
+        cbc::Decryptor::<rc2::Rc2>::inner_iv_slice_init(cipher, &iv)
+            .map_err(|_| invalid_cipher_input())?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+    } else {
+        return Err(unsupported(algorithm.oid));
+    };
+    plaintext.map_err(|_| CryptoError::Pkcs12WrongPassword)
+}
+
+fn encrypt(
+    password: &str,
+    plaintext: &[u8],
+    rng: &SystemRandom,
+) -> Result<(AlgorithmIdentifierOwned, Vec<u8>)> {
+    let salt = random_bytes::<SALT_LEN>(rng)?;
+    let iv = random_bytes::<16>(rng)?;
+    let scheme = pkcs5::pbes2::Parameters::pbkdf2_sha256_aes256cbc(PKCS12_ITERATIONS, &salt, &iv)
+        .map_err(corrupt)?;
+    let ciphertext = scheme.encrypt(password, plaintext).map_err(corrupt)?;
+    let algorithm = AlgorithmIdentifierOwned {
+        oid: pkcs5::pbes2::PBES2_OID,
+        parameters: Some(Any::encode_from(&scheme).map_err(corrupt)?),
+    };
+    Ok((algorithm, ciphertext))
+}
+
+fn local_key_id(bag: &SafeBag) -> Option<Vec<u8>> {
+    bag.bag_attributes
+        .as_ref()?
+        .iter()
+        .find(|attribute| attribute.oid == LOCAL_KEY_ID)?
+        .values
+        .iter()
+        .next()?
+        .decode_as::<OctetString>()
+        .ok()
+        .map(OctetString::into_bytes)
+}
+
+fn local_key_id_attributes(key_id: &[u8]) -> Result<Attributes> {
+    let value = Any::encode_from(&OctetString::new(key_id).map_err(corrupt)?).map_err(corrupt)?;
+    let attribute = Attribute {
+        oid: LOCAL_KEY_ID,
+        values: SetOfVec::try_from(vec![value]).map_err(corrupt)?,
+    };
+    SetOfVec::try_from(vec![attribute]).map_err(corrupt)
+}
+
+fn pem_encode(label: &'static str, der: &[u8]) -> Result<String> {
+    der::pem::encode_string(label, der::pem::LineEnding::LF, der).map_err(corrupt)
+}
+
+fn random_bytes<const N: usize>(rng: &SystemRandom) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    rng.fill(&mut bytes)
+        .map_err(|_| CryptoError::CorruptPkcs12 {
+            reason: "system random source failed".to_owned(),
+        })?;
+    Ok(bytes)
+}
+
+fn invalid_cipher_input() -> CryptoError {
+    CryptoError::CorruptPkcs12 {
+        reason: "derived key or IV has the wrong length".to_owned(),
+    }
+}
+
+fn unsupported(oid: ObjectIdentifier) -> CryptoError {
+    CryptoError::UnsupportedPkcs12Algorithm {
+        oid: oid.to_string(),
+    }
+}
+
+fn corrupt(error: impl std::fmt::Display) -> CryptoError {
+    CryptoError::CorruptPkcs12 {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+
+    use super::{decode_pkcs12, encode_pkcs12};
+    use crate::certs::pem_private_key;
+    use crate::{CryptoError, PemIdentity};
+
+    fn identity() -> PemIdentity {
+        let ca_key = KeyPair::generate().expect("ca key");
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).expect("ca cert");
+        let key = KeyPair::generate().expect("client key");
+        let cert = CertificateParams::new(vec!["rustak-client".to_owned()])
+            .expect("client params")
+            .signed_by(&key, &ca, &ca_key)
+            .expect("client cert");
+        PemIdentity {
+            ca_cert_pem: ca.pem(),
+            client_cert_pem: cert.pem(),
+            client_key_pem: key.serialize_pem(),
+        }
+    }
+
+    #[test]
+    fn identities_round_trip_through_pkcs12() {
+        let identity = identity();
+        let archive = encode_pkcs12(&identity, "correct horse").expect("encode");
+
+        let decoded = decode_pkcs12(&archive, "correct horse").expect("decode");
+        assert_eq!(
+            decoded.ca_cert_pem.replace("\r\n", "\n"),
+            identity.ca_cert_pem.replace("\r\n", "\n")
+        );
+        assert_eq!(
+            decoded.client_cert_pem.replace("\r\n", "\n"),
+            identity.client_cert_pem.replace("\r\n", "\n")
+        );
+        assert_eq!(
+            pem_private_key("decoded", &decoded.client_key_pem).expect("decoded key"),
+            pem_private_key("original", &identity.client_key_pem).expect("original key")
+        );
+    }
+
+    #[test]
+    fn wrong_passwords_and_damage_are_told_apart() {
+        let archive = encode_pkcs12(&identity(), "correct horse").expect("encode");
+        assert!(matches!(
+            decode_pkcs12(&archive, "battery staple"),
+            Err(CryptoError::Pkcs12WrongPassword)
+        ));
+        assert!(matches!(
+            decode_pkcs12(&archive[..archive.len() / 2], "correct horse"),
+            Err(CryptoError::CorruptPkcs12 { .. })
+        ));
+    }
+}
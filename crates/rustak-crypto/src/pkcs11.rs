@@ -0,0 +1,808 @@
+//! Identities whose private key lives on a PKCS#11 token (HSM or smartcard).
+//!
+//! [`load_identity`] loads the vendor module, opens a session on one slot,
+//! logs in with the user PIN and reads the token's certificates. The key
+//! never leaves the token: [`Pkcs11Identity::signing_key`] wraps it as a
+//! rustls `SigningKey` that calls `C_Sign` for each handshake. RSA (PKCS#1
+//! v1.5 and PSS) and ECDSA P-256/P-384 keys are supported.
+//!
+//! The module is initialised with OS locking; a module that another
+//! component already initialised is reused and left initialised on drop.
+
+use std::ffi::{c_ulong, c_void};
+use std::fmt;
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use der::asn1::UintRef;
+use der::{Decode, Encode, Sequence};
+use libloading::Library;
+use rustls::sign::{CertifiedKey, Signer, SigningKey};
+use rustls::{SignatureAlgorithm, SignatureScheme};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use x509_cert::Certificate;
+
+use crate::{CertificateInfo, CryptoError, KeyType, Result};
+
+type CkUlong = c_ulong;
+type CkRv = CkUlong;
+type CkSessionHandle = CkUlong;
+type CkObjectHandle = CkUlong;
+
+const CKR_OK: CkRv = 0x000;
+const CKR_SLOT_ID_INVALID: CkRv = 0x003;
+const CKR_GENERAL_ERROR: CkRv = 0x005;
+const CKR_FUNCTION_NOT_SUPPORTED: CkRv = 0x054;
+const CKR_PIN_INCORRECT: CkRv = 0x0A0;
+const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+
+const CKF_OS_LOCKING_OK: CkUlong = 0x2;
+const CKF_SERIAL_SESSION: CkUlong = 0x4;
+const CKU_USER: CkUlong = 1;
+const CK_UNAVAILABLE_INFORMATION: CkUlong = !0;
+
+const CKA_CLASS: CkUlong = 0x000;
+const CKA_VALUE: CkUlong = 0x011;
+const CKA_ID: CkUlong = 0x102;
+const CKO_CERTIFICATE: CkUlong = 1;
+const CKO_PRIVATE_KEY: CkUlong = 3;
+
+const CKM_RSA_PKCS: CkUlong = 0x0001;
+const CKM_RSA_PKCS_PSS: CkUlong = 0x000D;
+const CKM_ECDSA: CkUlong = 0x1041;
+const CKM_SHA256: CkUlong = 0x0250;
+const CKM_SHA384: CkUlong = 0x0260;
+const CKM_SHA512: CkUlong = 0x0270;
+const CKG_MGF1_SHA256: CkUlong = 2;
+const CKG_MGF1_SHA384: CkUlong = 3;
+const CKG_MGF1_SHA512: CkUlong = 4;
+
+/// DER `DigestInfo` headers that precede the hash in a PKCS#1 v1.5
+/// signature (RFC 8017 section 9.2, note 1).
+const SHA256_DIGEST_INFO: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+const SHA384_DIGEST_INFO: &[u8] = &[
+    0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05,
+    0x00, 0x04, 0x30,
+];
+const SHA512_DIGEST_INFO: &[u8] = &[
+    0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05,
+    0x00, 0x04, 0x40,
+];
+
+/// Schemes offered for RSA keys, strongest first.
+const RSA_SCHEMES: &[SignatureScheme] = &[
+    SignatureScheme::RSA_PSS_SHA512,
+    SignatureScheme::RSA_PSS_SHA384,
+    SignatureScheme::RSA_PSS_SHA256,
+    SignatureScheme::RSA_PKCS1_SHA512,
+    SignatureScheme::RSA_PKCS1_SHA384,
+    SignatureScheme::RSA_PKCS1_SHA256,
+];
+
+#[repr(C)]
+struct CkVersion {
+    major: u8,
+    minor: u8,
+}
+
+// Cryptoki structures are packed to one byte on Windows.
+#[cfg_attr(windows, repr(C, packed))]
+#[cfg_attr(not(windows), repr(C))]
+struct CkAttribute {
+    kind: CkUlong,
+    value: *mut c_void,
+    value_len: CkUlong,
+}
+
+#[cfg_attr(windows, repr(C, packed))]
+#[cfg_attr(not(windows), repr(C))]
+struct CkMechanism {
+    mechanism: CkUlong,
+    parameter: *mut c_void,
+    parameter_len: CkUlong,
+}
+
+#[repr(C)]
+struct CkRsaPkcsPssParams {
+    hash: CkUlong,
+    mgf: CkUlong,
+    salt_len: CkUlong,
+}
+
+#[cfg_attr(windows, repr(C, packed))]
+#[cfg_attr(not(windows), repr(C))]
+struct CkInitializeArgs {
+    create_mutex: *mut c_void,
+    destroy_mutex: *mut c_void,
+    lock_mutex: *mut c_void,
+    unlock_mutex: *mut c_void,
+    flags: CkUlong,
+    reserved: *mut c_void,
+}
+
+type Unused = Option<unsafe extern "C" fn()>;
+
+/// `CK_FUNCTION_LIST` up to `C_Sign`; the entries after it are never read.
+#[repr(C)]
+struct CkFunctionList {
+    version: CkVersion,
+    initialize: Option<unsafe extern "C" fn(*mut c_void) -> CkRv>,
+    finalize: Option<unsafe extern "C" fn(*mut c_void) -> CkRv>,
+    get_info: Unused,
+    get_function_list: Unused,
+    get_slot_list: Unused,
+    get_slot_info: Unused,
+    get_token_info: Unused,
+    get_mechanism_list: Unused,
+    get_mechanism_info: Unused,
+    init_token: Unused,
+    init_pin: Unused,
+    set_pin: Unused,
+    open_session: Option<
+        unsafe extern "C" fn(CkUlong, CkUlong, *mut c_void, Unused, *mut CkSessionHandle) -> CkRv,
+    >,
+    close_session: Option<unsafe extern "C" fn(CkSessionHandle) -> CkRv>,
+    close_all_sessions: Unused,
+    get_session_info: Unused,
+    get_operation_state: Unused,
+    set_operation_state: Unused,
+    login: Option<unsafe extern "C" fn(CkSessionHandle, CkUlong, *const u8, CkUlong) -> CkRv>,
+    logout: Option<unsafe extern "C" fn(CkSessionHandle) -> CkRv>,
+    create_object: Unused,
+    copy_object: Unused,
+    destroy_object: Unused,
+    get_object_size: Unused,
+    get_attribute_value: Option<
+        unsafe extern "C" fn(CkSessionHandle, CkObjectHandle, *mut CkAttribute, CkUlong) -> CkRv,
+    >,
+    set_attribute_value: Unused,
+    find_objects_init:
+        Option<unsafe extern "C" fn(CkSessionHandle, *mut CkAttribute, CkUlong) -> CkRv>,
+    find_objects: Option<
+        unsafe extern "C" fn(CkSessionHandle, *mut CkObjectHandle, CkUlong, *mut CkUlong) -> CkRv,
+    >,
+    find_objects_final: Option<unsafe extern "C" fn(CkSessionHandle) -> CkRv>,
+    encrypt_init: Unused,
+    encrypt: Unused,
+    encrypt_update: Unused,
+    encrypt_final: Unused,
+    decrypt_init: Unused,
+    decrypt: Unused,
+    decrypt_update: Unused,
+    decrypt_final: Unused,
+    digest_init: Unused,
+    digest: Unused,
+    digest_update: Unused,
+    digest_key: Unused,
+    digest_final: Unused,
+    sign_init:
+        Option<unsafe extern "C" fn(CkSessionHandle, *mut CkMechanism, CkObjectHandle) -> CkRv>,
+    sign: Option<
+        unsafe extern "C" fn(CkSessionHandle, *const u8, CkUlong, *mut u8, *mut CkUlong) -> CkRv,
+    >,
+}
+
+type GetFunctionList = unsafe extern "C" fn(*mut *const CkFunctionList) -> CkRv;
+
+/// A certificate chain read from a token plus a handle on its private key.
+#[derive(Clone)]
+pub struct Pkcs11Identity {
+    /// Self-issued certificates found on the token; empty when it holds none.
+    pub ca_cert_pem: String,
+    /// The certificate paired with the private key, then any intermediates.
+    pub client_cert_pem: String,
+    key: Arc<TokenKey>,
+}
+
+impl fmt::Debug for Pkcs11Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkcs11Identity")
+            .field("slot", &self.key.slot)
+            .field("algorithm", &self.key.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for Pkcs11Identity {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.key, &other.key)
+            && self.ca_cert_pem == other.ca_cert_pem
+            && self.client_cert_pem == other.client_cert_pem
+    }
+}
+
+impl Eq for Pkcs11Identity {}
+
+impl Pkcs11Identity {
+    /// The token key as a rustls signing key.
+    #[must_use]
+    pub fn signing_key(&self) -> Arc<dyn SigningKey> {
+        Arc::new(TokenSigningKey(Arc::clone(&self.key)))
+    }
+
+    /// The client chain paired with [`Self::signing_key`], ready for a
+    /// rustls client certificate resolver.
+    pub fn certified_key(&self) -> Result<CertifiedKey> {
+        let chain = crate::certs::pem_certificates("client_cert_pem", &self.client_cert_pem)?;
+        Ok(CertifiedKey::new(chain, self.signing_key()))
+    }
+}
+
+/// Loads `module_path`, logs in to `slot` with `pin` and pairs the first
+/// private key with the certificate sharing its `CKA_ID`.
+pub fn load_identity(module_path: &Path, slot: u64, pin: &str) -> Result<Pkcs11Identity> {
+    let token = Token::open(module_path, slot, pin)?;
+
+    let keys = token.find(CKO_PRIVATE_KEY)?;
+    let Some(&key) = keys.first() else {
+        return Err(CryptoError::Pkcs11ObjectMissing {
+            slot,
+            object: "private key",
+        });
+    };
+    let key_id = token.attribute(key, CKA_ID)?;
+    let mut certificates = token
+        .find(CKO_CERTIFICATE)?
+        .into_iter()
+        .map(|certificate| {
+            Ok((
+                token.attribute(certificate, CKA_VALUE)?,
+                token.attribute(certificate, CKA_ID)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let leaf = match certificates
+        .iter()
+        .position(|(_, id)| !key_id.is_empty() && *id == key_id)
+    {
+        Some(leaf) => leaf,
+        None if certificates.len() == 1 => 0,
+        None => {
+            return Err(CryptoError::Pkcs11ObjectMissing {
+                slot,
+                object: "certificate matching the private key",
+            })
+        }
+    };
+    let (leaf_der, _) = certificates.remove(leaf);
+    let algorithm = match CertificateInfo::from_der(&leaf_der)?.key_type {
+        KeyType::Rsa { .. } => TokenAlgorithm::Rsa,
+        KeyType::EcdsaP256 => TokenAlgorithm::EcdsaP256,
+        KeyType::EcdsaP384 => TokenAlgorithm::EcdsaP384,
+        other => {
+            return Err(CryptoError::Pkcs11UnsupportedKey {
+                key_type: other.to_string(),
+            })
+        }
+    };
+
+    let mut client_cert_pem = pem_certificate(&leaf_der)?;
+    let mut ca_cert_pem = String::new();
+    for (certificate, _) in &certificates {
+        let parsed = Certificate::from_der(certificate).map_err(invalid_certificate)?;
+        let block = pem_certificate(certificate)?;
+        if parsed.tbs_certificate.subject == parsed.tbs_certificate.issuer {
+            ca_cert_pem.push_str(&block);
+        } else {
+            client_cert_pem.push_str(&block);
+        }
+    }
+    Ok(Pkcs11Identity {
+        ca_cert_pem,
+        client_cert_pem,
+        key: Arc::new(TokenKey {
+            token: Mutex::new(token),
+            handle: key,
+            slot,
+            algorithm,
+        }),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenAlgorithm {
+    Rsa,
+    EcdsaP256,
+    EcdsaP384,
+}
+
+struct TokenKey {
+    /// One session serves every signature, so calls are serialised.
+    token: Mutex<Token>,
+    handle: CkObjectHandle,
+    slot: u64,
+    algorithm: TokenAlgorithm,
+}
+
+impl fmt::Debug for TokenKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenKey")
+            .field("slot", &self.slot)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct TokenSigningKey(Arc<TokenKey>);
+
+impl SigningKey for TokenSigningKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        let scheme = match self.0.algorithm {
+            TokenAlgorithm::Rsa => RSA_SCHEMES
+                .iter()
+                .copied()
+                .find(|scheme| offered.contains(scheme))?,
+            TokenAlgorithm::EcdsaP256 => SignatureScheme::ECDSA_NISTP256_SHA256,
+            TokenAlgorithm::EcdsaP384 => SignatureScheme::ECDSA_NISTP384_SHA384,
+        };
+        if !offered.contains(&scheme) {
+            return None;
+        }
+        Some(Box::new(TokenSigner {
+            key: Arc::clone(&self.0),
+            scheme,
+        }))
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        match self.0.algorithm {
+            TokenAlgorithm::Rsa => SignatureAlgorithm::RSA,
+            TokenAlgorithm::EcdsaP256 | TokenAlgorithm::EcdsaP384 => SignatureAlgorithm::ECDSA,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenSigner {
+    key: Arc<TokenKey>,
+    scheme: SignatureScheme,
+}
+
+impl Signer for TokenSigner {
+    fn sign(&self, message: &[u8]) -> std::result::Result<Vec<u8>, rustls::Error> {
+        let token = self
+            .key
+            .token
+            .lock()
+            .map_err(|_| rustls::Error::General("pkcs#11 session lock poisoned".to_owned()))?;
+        sign_with(&token, self.key.handle, self.scheme, message)
+            .map_err(|error| rustls::Error::General(error.to_string()))
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
+
+fn sign_with(
+    token: &Token,
+    key: CkObjectHandle,
+    scheme: SignatureScheme,
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    match scheme {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => {
+            let raw = token.sign(key, CKM_ECDSA, None, &Sha256::digest(message))?;
+            ecdsa_der(&raw)
+        }
+        SignatureScheme::ECDSA_NISTP384_SHA384 => {
+            let raw = token.sign(key, CKM_ECDSA, None, &Sha384::digest(message))?;
+            ecdsa_der(&raw)
+        }
+        SignatureScheme::RSA_PKCS1_SHA256 => token.sign(
+            key,
+            CKM_RSA_PKCS,
+            None,
+            &digest_info(SHA256_DIGEST_INFO, &Sha256::digest(message)),
+        ),
+        SignatureScheme::RSA_PKCS1_SHA384 => token.sign(
+            key,
+            CKM_RSA_PKCS,
+            None,
+            &digest_info(SHA384_DIGEST_INFO, &Sha384::digest(message)),
+        ),
+        SignatureScheme::RSA_PKCS1_SHA512 => token.sign(
+            key,
+            CKM_RSA_PKCS,
+            None,
+            &digest_info(SHA512_DIGEST_INFO, &Sha512::digest(message)),
+        ),
+        SignatureScheme::RSA_PSS_SHA256 => {
+            let mut params = pss_params(CKM_SHA256, CKG_MGF1_SHA256, 32);
+            token.sign(
+                key,
+                CKM_RSA_PKCS_PSS,
+                Some(&mut params),
+                &Sha256::digest(message),
+            )
+        }
+        SignatureScheme::RSA_PSS_SHA384 => {
+            let mut params = pss_params(CKM_SHA384, CKG_MGF1_SHA384, 48);
+            token.sign(
+                key,
+                CKM_RSA_PKCS_PSS,
+                Some(&mut params),
+                &Sha384::digest(message),
+            )
+        }
+        SignatureScheme::RSA_PSS_SHA512 => {
+            let mut params = pss_params(CKM_SHA512, CKG_MGF1_SHA512, 64);
+            token.sign(
+                key,
+                CKM_RSA_PKCS_PSS,
+                Some(&mut params),
+                &Sha512::digest(message),
+            )
+        }
+        _ => Err(CryptoError::Pkcs11UnsupportedKey {
+            key_type: format!("{scheme:?}"),
+        }),
+    }
+}
+
+fn pss_params(hash: CkUlong, mgf: CkUlong, salt_len: CkUlong) -> CkRsaPkcsPssParams {
+    CkRsaPkcsPssParams {
+        hash,
+        mgf,
+        salt_len,
+    }
+}
+
+fn digest_info(header: &[u8], digest: &[u8]) -> Vec<u8> {
+    [header, digest].concat()
+}
+
+#[derive(Sequence)]
+struct EcdsaSignature<'a> {
+    r: UintRef<'a>,
+    s: UintRef<'a>,
+}
+
+/// PKCS#11 returns ECDSA signatures as `r || s`; TLS wants the DER
+/// `ECDSA-Sig-Value`.
+fn ecdsa_der(raw: &[u8]) -> Result<Vec<u8>> {
+    let malformed = |_| call_failed("C_Sign", CKR_GENERAL_ERROR);
+    if raw.is_empty() || raw.len() % 2 != 0 {
+        return Err(malformed(()));
+    }
+    let (r, s) = raw.split_at(raw.len() / 2);
+    let signature = EcdsaSignature {
+        r: UintRef::new(r).map_err(|_| malformed(()))?,
+        s: UintRef::new(s).map_err(|_| malformed(()))?,
+    };
+    signature.to_der().map_err(|_| malformed(()))
+}
+
+/// A logged-in session on one slot.
+struct Token {
+    functions: *const CkFunctionList,
+    session: CkSessionHandle,
+    finalize: bool,
+    // Dropped last so the function table outlives the session.
+    _library: Library,
+}
+
+// The function table is immutable and the module was initialised with OS
+// locking; `TokenKey` serialises use of the session.
+unsafe impl Send for Token {}
+unsafe impl Sync for Token {}
+
+impl Token {
+    fn open(module_path: &Path, slot: u64, pin: &str) -> Result<Self> {
+        let module_error = |reason: String| CryptoError::Pkcs11Module {
+            path: module_path.display().to_string(),
+            reason,
+        };
+        // SAFETY: loading a PKCS#11 module runs its initialisers; the path
+        // is operator configuration naming a vendor library.
+        let library = unsafe { Library::new(module_path) }
+            .map_err(|error| module_error(error.to_string()))?;
+        let mut functions: *const CkFunctionList = ptr::null();
+        // SAFETY: `C_GetFunctionList` has this signature in every Cryptoki
+        // version and writes a pointer to a static table.
+        let rv = unsafe {
+            let get_function_list = library
+                .get::<GetFunctionList>(b"C_GetFunctionList\0")
+                .map_err(|error| module_error(error.to_string()))?;
+            get_function_list(&mut functions)
+        };
+        check("C_GetFunctionList", rv)?;
+        if functions.is_null() {
+            return Err(module_error(
+                "C_GetFunctionList returned no table".to_owned(),
+            ));
+        }
+        // SAFETY: checked non-null above; the table lives as long as `library`.
+        let table = unsafe { &*functions };
+
+        let mut args = CkInitializeArgs {
+            create_mutex: ptr::null_mut(),
+            destroy_mutex: ptr::null_mut(),
+            lock_mutex: ptr::null_mut(),
+            unlock_mutex: ptr::null_mut(),
+            flags: CKF_OS_LOCKING_OK,
+            reserved: ptr::null_mut(),
+        };
+        let initialize = entry("C_Initialize", table.initialize)?;
+        // SAFETY: `args` is a valid CK_C_INITIALIZE_ARGS for the call.
+        let finalize = match unsafe { initialize(ptr::addr_of_mut!(args).cast()) } {
+            CKR_OK => true,
+            CKR_CRYPTOKI_ALREADY_INITIALIZED => false,
+            rv => return Err(call_failed("C_Initialize", rv)),
+        };
+
+        let slot_id = CkUlong::try_from(slot)
+            .map_err(|_| call_failed("C_OpenSession", CKR_SLOT_ID_INVALID))?;
+        let open_session = entry("C_OpenSession", table.open_session)?;
+        let mut session: CkSessionHandle = 0;
+        // SAFETY: no application callback is registered; `session` is a
+        // valid out-pointer.
+        let rv = unsafe {
+            open_session(
+                slot_id,
+                CKF_SERIAL_SESSION,
+                ptr::null_mut(),
+                None,
+                &mut session,
+            )
+        };
+        if rv != CKR_OK {
+            if finalize {
+                if let Some(finalize) = table.finalize {
+                    // SAFETY: we initialised the module and hold no sessions.
+                    unsafe { finalize(ptr::null_mut()) };
+                }
+            }
+            return Err(call_failed("C_OpenSession", rv));
+        }
+        let token = Self {
+            functions,
+            session,
+            finalize,
+            _library: library,
+        };
+
+        let login = entry("C_Login", token.table().login)?;
+        let pin_len = CkUlong::try_from(pin.len()).unwrap_or(CkUlong::MAX);
+        // SAFETY: `pin` outlives the call and `pin_len` is its length.
+        match unsafe { login(token.session, CKU_USER, pin.as_ptr(), pin_len) } {
+            CKR_OK | CKR_USER_ALREADY_LOGGED_IN => Ok(token),
+            CKR_PIN_INCORRECT => Err(CryptoError::Pkcs11PinIncorrect { slot }),
+            rv => Err(call_failed("C_Login", rv)),
+        }
+    }
+
+    fn table(&self) -> &CkFunctionList {
+        // SAFETY: non-null since `open`, valid while `_library` is loaded.
+        unsafe { &*self.functions }
+    }
+
+    /// Handles of every object of `class` visible to the session.
+    fn find(&self, class: CkUlong) -> Result<Vec<CkObjectHandle>> {
+        let table = self.table();
+        let init = entry("C_FindObjectsInit", table.find_objects_init)?;
+        let find = entry("C_FindObjects", table.find_objects)?;
+        let finish = entry("C_FindObjectsFinal", table.find_objects_final)?;
+
+        let mut class = class;
+        let mut template = CkAttribute {
+            kind: CKA_CLASS,
+            value: ptr::addr_of_mut!(class).cast(),
+            value_len: std::mem::size_of::<CkUlong>() as CkUlong,
+        };
+        // SAFETY: the template and the value it points at outlive the call.
+        check("C_FindObjectsInit", unsafe {
+            init(self.session, &mut template, 1)
+        })?;
+        let mut handles = Vec::new();
+        let result = loop {
+            let mut batch = [0 as CkObjectHandle; 16];
+            let mut count: CkUlong = 0;
+            // SAFETY: `batch` holds the 16 handles the call may write.
+            let rv = unsafe {
+                find(
+                    self.session,
+                    batch.as_mut_ptr(),
+                    batch.len() as CkUlong,
+                    &mut count,
+                )
+            };
+            if rv != CKR_OK {
+                break Err(call_failed("C_FindObjects", rv));
+            }
+            let count = usize::try_from(count).unwrap_or(0).min(batch.len());
+            if count == 0 {
+                break Ok(());
+            }
+            handles.extend_from_slice(&batch[..count]);
+        };
+        // SAFETY: a search is active on the session.
+        let rv = unsafe { finish(self.session) };
+        result?;
+        check("C_FindObjectsFinal", rv)?;
+        Ok(handles)
+    }
+
+    /// One attribute of `object`; empty when the token does not expose it.
+    fn attribute(&self, object: CkObjectHandle, kind: CkUlong) -> Result<Vec<u8>> {
+        let get = entry("C_GetAttributeValue", self.table().get_attribute_value)?;
+        let mut template = CkAttribute {
+            kind,
+            value: ptr::null_mut(),
+            value_len: 0,
+        };
+        // SAFETY: a null value asks only for the length.
+        check("C_GetAttributeValue", unsafe {
+            get(self.session, object, &mut template, 1)
+        })?;
+        let len = template.value_len;
+        if len == CK_UNAVAILABLE_INFORMATION || len == 0 {
+            return Ok(Vec::new());
+        }
+        let mut value = vec![0_u8; usize::try_from(len).unwrap_or(0)];
+        template.value = value.as_mut_ptr().cast();
+        // SAFETY: `value` has the length the token reported.
+        check("C_GetAttributeValue", unsafe {
+            get(self.session, object, &mut template, 1)
+        })?;
+        let written = usize::try_from(template.value_len).unwrap_or(0);
+        value.truncate(written);
+        Ok(value)
+    }
+
+    fn sign(
+        &self,
+        key: CkObjectHandle,
+        mechanism: CkUlong,
+        params: Option<&mut CkRsaPkcsPssParams>,
+        data: &[u8],
+    ) -> Result<Vec<u8>> {
+        let table = self.table();
+        let sign_init = entry("C_SignInit", table.sign_init)?;
+        let sign = entry("C_Sign", table.sign)?;
+        let (parameter, parameter_len) = match params {
+            Some(params) => (
+                ptr::from_mut(params).cast(),
+                std::mem::size_of::<CkRsaPkcsPssParams>() as CkUlong,
+            ),
+            None => (ptr::null_mut(), 0),
+        };
+        let mut mechanism = CkMechanism {
+            mechanism,
+            parameter,
+            parameter_len,
+        };
+        let data_len = data.len() as CkUlong;
+        // SAFETY: the mechanism and its parameters outlive the call.
+        check("C_SignInit", unsafe {
+            sign_init(self.session, &mut mechanism, key)
+        })?;
+        let mut len: CkUlong = 0;
+        // SAFETY: a null output buffer asks for the signature length without
+        // ending the operation.
+        check("C_Sign", unsafe {
+            sign(
+                self.session,
+                data.as_ptr(),
+                data_len,
+                ptr::null_mut(),
+                &mut len,
+            )
+        })?;
+        let mut signature = vec![0_u8; usize::try_from(len).unwrap_or(0)];
+        // SAFETY: `signature` has the length the token asked for.
+        check("C_Sign", unsafe {
+            sign(
+                self.session,
+                data.as_ptr(),
+                data_len,
+                signature.as_mut_ptr(),
+                &mut len,
+            )
+        })?;
+        signature.truncate(usize::try_from(len).unwrap_or(0));
+        Ok(signature)
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        let table = self.table();
+        // SAFETY: the session is open; errors on teardown are ignored.
+        unsafe {
+            if let Some(logout) = table.logout {
+                logout(self.session);
+            }
+            if let Some(close_session) = table.close_session {
+                close_session(self.session);
+            }
+            if self.finalize {
+                if let Some(finalize) = table.finalize {
+                    finalize(ptr::null_mut());
+                }
+            }
+        }
+    }
+}
+
+/// A function-table entry the module left null.
+fn entry<F>(function: &'static str, entry: Option<F>) -> Result<F> {
+    entry.ok_or_else(|| call_failed(function, CKR_FUNCTION_NOT_SUPPORTED))
+}
+
+fn check(function: &'static str, rv: CkRv) -> Result<()> {
+    if rv == CKR_OK {
+        Ok(())
+    } else {
+        Err(call_failed(function, rv))
+    }
+}
+
+// CK_ULONG is only 32 bits on Windows.
+#[allow(clippy::useless_conversion)]
+fn call_failed(function: &'static str, rv: CkRv) -> CryptoError {
+    CryptoError::Pkcs11Call {
+        function,
+        rv: u64::from(rv),
+    }
+}
+
+fn pem_certificate(certificate: &[u8]) -> Result<String> {
+    der::pem::encode_string("CERTIFICATE", der::pem::LineEnding::LF, certificate).map_err(|error| {
+        CryptoError::InvalidCertificate {
+            reason: error.to_string(),
+        }
+    })
+}
+
+fn invalid_certificate(error: der::Error) -> CryptoError {
+    CryptoError::InvalidCertificate {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use der::asn1::UintRef;
+    use der::Decode;
+
+    use super::{digest_info, ecdsa_der, load_identity, EcdsaSignature, SHA256_DIGEST_INFO};
+    use crate::CryptoError;
+
+    #[test]
+    fn ecdsa_signatures_are_rewrapped_as_der() {
+        let mut raw = vec![0_u8; 64];
+        raw[0] = 0x80;
+        raw[63] = 0x01;
+        let der = ecdsa_der(&raw).expect("der signature");
+        let parsed = EcdsaSignature::from_der(&der).expect("parses");
+        assert_eq!(parsed.r, UintRef::new(&raw[..32]).expect("r"));
+        assert_eq!(parsed.s.as_bytes(), [0x01]);
+        // A high bit in r needs a leading zero to stay positive.
+        assert_eq!(&der[2..5], [0x02, 0x21, 0x00]);
+        assert!(ecdsa_der(&raw[..63]).is_err());
+    }
+
+    #[test]
+    fn pkcs1_digest_info_prefixes_the_hash() {
+        let encoded = digest_info(SHA256_DIGEST_INFO, &[0xAB; 32]);
+        assert_eq!(encoded.len(), 51);
+        assert_eq!(encoded[1] as usize, encoded.len() - 2);
+        assert_eq!(&encoded[19..], [0xAB; 32]);
+    }
+
+    #[test]
+    fn missing_modules_fail_to_load() {
+        let error = load_identity(Path::new("/nonexistent/libpkcs11-missing.so"), 0, "1234")
+            .expect_err("module does not exist");
+        assert!(matches!(error, CryptoError::Pkcs11Module { .. }));
+    }
+}
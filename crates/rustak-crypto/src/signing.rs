@@ -0,0 +1,843 @@
+//! Application-layer Ed25519 signatures for CoT events.
+//!
+//! The signature covers a canonical form of the event XML and travels inside
+//! `<detail>` as a `<_rustak_sig>` element, so it survives gateways that
+//! terminate transport security. The event is parsed to find that element;
+//! an event carrying more than one is refused. Canonicalisation is textual:
+//! once the single signature element is cut out, the XML declaration and
+//! inter-element whitespace are removed and an empty `<detail>` collapses to
+//! `<detail/>`. Attributes are not reordered, so relays may re-indent events
+//! but must not rewrite elements.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use const_oid::db::{rfc5912, rfc8410};
+use der::asn1::ObjectIdentifier;
+use der::Decode;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use ring::rand::SystemRandom;
+use ring::signature::{self as ring_signature, EcdsaKeyPair, KeyPair};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::SubjectPublicKeyInfoDer;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+
+use crate::certs::{pem_certificates, pem_private_key};
+use crate::{CryptoError, Result};
+
+pub const SIGNATURE_ELEMENT: &str = "_rustak_sig";
+pub const SIGNATURE_ALGORITHM: &str = "ed25519";
+
+/// How inbound events are treated with respect to their signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignaturePolicy {
+    /// Signatures are not checked at all.
+    #[default]
+    Ignore,
+    /// Signatures are checked and the outcome reported, but nothing is rejected.
+    Warn,
+    /// Only events carrying a valid signature from a trusted key are accepted.
+    Require,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Verified { key_id: String },
+    NotChecked,
+    Unsigned,
+    Malformed,
+    UnknownKey { key_id: String },
+    Invalid { key_id: String },
+}
+
+impl SignatureStatus {
+    #[must_use]
+    pub fn is_verified(&self) -> bool {
+        matches!(self, Self::Verified { .. })
+    }
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verified { key_id } => write!(f, "verified by `{key_id}`"),
+            Self::NotChecked => f.write_str("not checked"),
+            Self::Unsigned => f.write_str("unsigned"),
+            Self::Malformed => f.write_str("malformed signature element"),
+            Self::UnknownKey { key_id } => write!(f, "signed by unknown key `{key_id}`"),
+            Self::Invalid { key_id } => write!(f, "invalid signature for key `{key_id}`"),
+        }
+    }
+}
+
+pub struct CotSigner {
+    key_id: String,
+    key: SigningKey,
+}
+
+impl fmt::Debug for CotSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CotSigner")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CotSigner {
+    pub fn from_seed(key_id: impl Into<String>, seed: [u8; 32]) -> Result<Self> {
+        let key_id = key_id.into();
+        validate_key_id(&key_id)?;
+        Ok(Self {
+            key_id,
+            key: SigningKey::from_bytes(&seed),
+        })
+    }
+
+    /// Builds a signer from a base64-encoded 32-byte Ed25519 seed.
+    pub fn from_base64_seed(key_id: impl Into<String>, seed: &str) -> Result<Self> {
+        let key_id = key_id.into();
+        let seed = decode_key_bytes(&key_id, seed)?;
+        Self::from_seed(key_id, seed)
+    }
+
+    #[must_use]
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Base64 public key to distribute to verifying peers.
+    #[must_use]
+    pub fn public_key_base64(&self) -> String {
+        BASE64.encode(self.key.verifying_key().as_bytes())
+    }
+
+    /// Returns `xml` with any existing signature replaced by a fresh one.
+    pub fn sign(&self, xml: &str) -> Result<String> {
+        let unsigned = match find_signature_element(xml) {
+            Ok(Some(found)) => format!("{}{}", &xml[..found.start], &xml[found.end..]),
+            Ok(None) => xml.to_owned(),
+            Err(reason) => return Err(CryptoError::InvalidCotXml { reason }),
+        };
+        let (unsigned, at) = open_detail(&unsigned)?;
+        let signature = self.key.sign(canonicalize_cot(&unsigned).as_bytes());
+        let element = format!(
+            "<{SIGNATURE_ELEMENT} alg=\"{SIGNATURE_ALGORITHM}\" kid=\"{}\" sig=\"{}\"/>",
+            self.key_id,
+            BASE64.encode(signature.to_bytes())
+        );
+        Ok(format!("{}{element}{}", &unsigned[..at], &unsigned[at..]))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CotVerifier {
+    policy: SignaturePolicy,
+    trusted: BTreeMap<String, VerifyingKey>,
+}
+
+impl CotVerifier {
+    #[must_use]
+    pub fn new(policy: SignaturePolicy) -> Self {
+        Self {
+            policy,
+            trusted: BTreeMap::new(),
+        }
+    }
+
+    /// Trusts a base64-encoded Ed25519 public key under `key_id`.
+    pub fn with_trusted_key(mut self, key_id: impl Into<String>, public_key: &str) -> Result<Self> {
+        let key_id = key_id.into();
+        validate_key_id(&key_id)?;
+        let bytes = decode_key_bytes(&key_id, public_key)?;
+        let key = VerifyingKey::from_bytes(&bytes).map_err(|_| CryptoError::InvalidSigningKey {
+            key_id: key_id.clone(),
+            reason: "not a valid ed25519 public key",
+        })?;
+        self.trusted.insert(key_id, key);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn policy(&self) -> SignaturePolicy {
+        self.policy
+    }
+
+    #[must_use]
+    pub fn trusted_key_count(&self) -> usize {
+        self.trusted.len()
+    }
+
+    /// Checks `xml` under the configured policy.
+    ///
+    /// Under `Require` anything other than a verified signature is an error;
+    /// under `Warn` the status is returned for the caller to report.
+    pub fn verify(&self, xml: &str) -> Result<SignatureStatus> {
+        if self.policy == SignaturePolicy::Ignore {
+            return Ok(SignatureStatus::NotChecked);
+        }
+
+        let status = self.check(xml);
+        if self.policy == SignaturePolicy::Require && !status.is_verified() {
+            return Err(CryptoError::SignatureRejected { status });
+        }
+        Ok(status)
+    }
+
+    fn check(&self, xml: &str) -> SignatureStatus {
+        let found = match find_signature_element(xml) {
+            Ok(Some(found)) => found,
+            Ok(None) => return SignatureStatus::Unsigned,
+            Err(_) => return SignatureStatus::Malformed,
+        };
+        let (Some(alg), Some(key_id), Some(encoded)) = (found.alg, found.kid, found.sig) else {
+            return SignatureStatus::Malformed;
+        };
+        if alg != SIGNATURE_ALGORITHM {
+            return SignatureStatus::Malformed;
+        }
+        let Some(key) = self.trusted.get(&key_id) else {
+            return SignatureStatus::UnknownKey { key_id };
+        };
+        let Some(signature) = BASE64
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        else {
+            return SignatureStatus::Malformed;
+        };
+
+        let unsigned = format!("{}{}", &xml[..found.start], &xml[found.end..]);
+        match key.verify(canonicalize_cot(&unsigned).as_bytes(), &signature) {
+            Ok(()) => SignatureStatus::Verified { key_id },
+            Err(_) => SignatureStatus::Invalid { key_id },
+        }
+    }
+}
+
+/// Key algorithm of a [`DetachedSigner`] or [`DetachedVerifier`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetachedAlgorithm {
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256, signatures in the fixed 64-byte form.
+    EcdsaP256,
+}
+
+impl DetachedAlgorithm {
+    fn from_oids(algorithm: ObjectIdentifier, curve: Option<ObjectIdentifier>) -> Option<Self> {
+        if algorithm == rfc8410::ID_ED_25519 {
+            return Some(Self::Ed25519);
+        }
+        (algorithm == rfc5912::ID_EC_PUBLIC_KEY && curve == Some(rfc5912::SECP_256_R_1))
+            .then_some(Self::EcdsaP256)
+    }
+}
+
+impl fmt::Display for DetachedAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ed25519 => f.write_str("ed25519"),
+            Self::EcdsaP256 => f.write_str("ecdsa-p256"),
+        }
+    }
+}
+
+/// Signs arbitrary bytes, such as recording integrity chains, with an
+/// Ed25519 or ECDSA P-256 private key.
+pub struct DetachedSigner {
+    key: DetachedKey,
+    rng: SystemRandom,
+}
+
+enum DetachedKey {
+    Ed25519(SigningKey),
+    EcdsaP256(EcdsaKeyPair),
+}
+
+impl fmt::Debug for DetachedSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetachedSigner")
+            .field("algorithm", &self.algorithm())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DetachedSigner {
+    /// Loads a PKCS#8 (`PRIVATE KEY`) or SEC1 (`EC PRIVATE KEY`) PEM key.
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_pkcs8(&pem_private_key("signing_key", pem)?)
+    }
+
+    pub fn from_pkcs8(pkcs8_der: &[u8]) -> Result<Self> {
+        let info = pkcs8::PrivateKeyInfo::try_from(pkcs8_der).map_err(invalid_private_key)?;
+        let curve = info
+            .algorithm
+            .parameters
+            .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+        let rng = SystemRandom::new();
+        let key = match DetachedAlgorithm::from_oids(info.algorithm.oid, curve) {
+            Some(DetachedAlgorithm::Ed25519) => DetachedKey::Ed25519(
+                SigningKey::from_pkcs8_der(pkcs8_der).map_err(invalid_private_key)?,
+            ),
+            Some(DetachedAlgorithm::EcdsaP256) => DetachedKey::EcdsaP256(
+                EcdsaKeyPair::from_pkcs8(
+                    &ring_signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+                    pkcs8_der,
+                    &rng,
+                )
+                .map_err(invalid_private_key)?,
+            ),
+            None => {
+                return Err(CryptoError::UnsupportedSigningKey {
+                    algorithm: curve.unwrap_or(info.algorithm.oid).to_string(),
+                })
+            }
+        };
+        Ok(Self { key, rng })
+    }
+
+    #[must_use]
+    pub fn algorithm(&self) -> DetachedAlgorithm {
+        match self.key {
+            DetachedKey::Ed25519(_) => DetachedAlgorithm::Ed25519,
+            DetachedKey::EcdsaP256(_) => DetachedAlgorithm::EcdsaP256,
+        }
+    }
+
+    /// Raw public key: 32 bytes for Ed25519, an uncompressed point for P-256.
+    #[must_use]
+    pub fn public_key(&self) -> &[u8] {
+        match &self.key {
+            DetachedKey::Ed25519(key) => AsRef::<VerifyingKey>::as_ref(key).as_bytes(),
+            DetachedKey::EcdsaP256(key) => key.public_key().as_ref(),
+        }
+    }
+
+    #[must_use]
+    pub fn verifier(&self) -> DetachedVerifier {
+        DetachedVerifier::new(self.algorithm(), self.public_key().to_vec())
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        match &self.key {
+            DetachedKey::Ed25519(key) => Ok(key.sign(message).to_vec()),
+            DetachedKey::EcdsaP256(key) => key
+                .sign(&self.rng, message)
+                .map(|signature| signature.as_ref().to_vec())
+                .map_err(|_| CryptoError::SigningFailed),
+        }
+    }
+}
+
+/// Checks [`DetachedSigner`] signatures against a trusted public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedVerifier {
+    algorithm: DetachedAlgorithm,
+    public_key: Vec<u8>,
+}
+
+impl DetachedVerifier {
+    #[must_use]
+    pub fn new(algorithm: DetachedAlgorithm, public_key: Vec<u8>) -> Self {
+        Self {
+            algorithm,
+            public_key,
+        }
+    }
+
+    /// Loads a `PUBLIC KEY` PEM, or the key of the first `CERTIFICATE` in
+    /// `pem` so a signer's certificate can be trusted directly.
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let spki = match SubjectPublicKeyInfoDer::from_pem_slice(pem.as_bytes()) {
+            Ok(spki) => SubjectPublicKeyInfoOwned::from_der(spki.as_ref()),
+            Err(_) => {
+                let certificates = pem_certificates("verify_key", pem)?;
+                Certificate::from_der(&certificates[0])
+                    .map(|certificate| certificate.tbs_certificate.subject_public_key_info)
+            }
+        }
+        .map_err(|error| CryptoError::InvalidPem {
+            field: "verify_key",
+            reason: error.to_string(),
+        })?;
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+        let algorithm =
+            DetachedAlgorithm::from_oids(spki.algorithm.oid, curve).ok_or_else(|| {
+                CryptoError::UnsupportedSigningKey {
+                    algorithm: curve.unwrap_or(spki.algorithm.oid).to_string(),
+                }
+            })?;
+        let public_key =
+            spki.subject_public_key
+                .as_bytes()
+                .ok_or_else(|| CryptoError::InvalidPem {
+                    field: "verify_key",
+                    reason: "public key has unused bits".to_owned(),
+                })?;
+        Ok(Self::new(algorithm, public_key.to_vec()))
+    }
+
+    #[must_use]
+    pub fn algorithm(&self) -> DetachedAlgorithm {
+        self.algorithm
+    }
+
+    #[must_use]
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    #[must_use]
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self.algorithm {
+            DetachedAlgorithm::Ed25519 => {
+                let (Ok(key), Ok(signature)) = (
+                    VerifyingKey::try_from(self.public_key.as_slice()),
+                    Signature::from_slice(signature),
+                ) else {
+                    return false;
+                };
+                key.verify(message, &signature).is_ok()
+            }
+            DetachedAlgorithm::EcdsaP256 => ring_signature::UnparsedPublicKey::new(
+                &ring_signature::ECDSA_P256_SHA256_FIXED,
+                &self.public_key,
+            )
+            .verify(message, signature)
+            .is_ok(),
+        }
+    }
+}
+
+/// Canonical byte form that signatures are computed over, for an event whose
+/// signature element has already been removed.
+#[must_use]
+pub fn canonicalize_cot(xml: &str) -> String {
+    let mut body = xml.trim();
+    if body.starts_with("<?xml") {
+        if let Some(end) = body.find("?>") {
+            body = body[end + 2..].trim_start();
+        }
+    }
+
+    let mut canonical = String::with_capacity(body.len());
+    let mut pending_whitespace = String::new();
+    for ch in body.chars() {
+        if ch.is_whitespace() {
+            pending_whitespace.push(ch);
+            continue;
+        }
+        let between_tags = ch == '<' && canonical.ends_with('>');
+        if !between_tags && !canonical.is_empty() {
+            canonical.push_str(&pending_whitespace);
+        }
+        pending_whitespace.clear();
+        canonical.push(ch);
+    }
+
+    canonical.replace("<detail></detail>", "<detail/>")
+}
+
+fn invalid_private_key(error: impl fmt::Display) -> CryptoError {
+    CryptoError::InvalidPrivateKey {
+        reason: error.to_string(),
+    }
+}
+
+fn validate_key_id(key_id: &str) -> Result<()> {
+    let valid = !key_id.is_empty()
+        && key_id
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.' | ':'));
+    if valid {
+        Ok(())
+    } else {
+        Err(CryptoError::InvalidSigningKey {
+            key_id: key_id.to_owned(),
+            reason: "key id must be non-empty and use [A-Za-z0-9._:-]",
+        })
+    }
+}
+
+fn decode_key_bytes(key_id: &str, encoded: &str) -> Result<[u8; 32]> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|_| CryptoError::InvalidSigningKey {
+            key_id: key_id.to_owned(),
+            reason: "key material is not valid base64",
+        })?;
+    bytes
+        .try_into()
+        .map_err(|_| CryptoError::InvalidSigningKey {
+            key_id: key_id.to_owned(),
+            reason: "key material must decode to 32 bytes",
+        })
+}
+
+/// The signature element of a parsed event: its byte span and attributes.
+struct SignatureElement {
+    start: usize,
+    end: usize,
+    alg: Option<String>,
+    kid: Option<String>,
+    sig: Option<String>,
+}
+
+/// Finds the one empty `<_rustak_sig/>` directly under `<event><detail>`.
+/// Events that do not parse, carry more than one signature element or carry
+/// it anywhere else are refused with the reason.
+fn find_signature_element(xml: &str) -> std::result::Result<Option<SignatureElement>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut path = Vec::new();
+    let mut found = None;
+    loop {
+        let start = to_offset(reader.buffer_position());
+        let event = reader.read_event().map_err(|error| error.to_string())?;
+        match event {
+            Event::Start(element) => {
+                if element.local_name().as_ref() == SIGNATURE_ELEMENT.as_bytes() {
+                    return Err(format!("<{SIGNATURE_ELEMENT}> must be an empty element"));
+                }
+                path.push(element.name().as_ref().to_vec());
+            }
+            Event::End(_) => {
+                path.pop();
+            }
+            Event::Empty(element)
+                if element.local_name().as_ref() == SIGNATURE_ELEMENT.as_bytes() =>
+            {
+                if found.is_some() {
+                    return Err(format!("more than one <{SIGNATURE_ELEMENT}> element"));
+                }
+                if path != [b"event".as_slice(), b"detail"] {
+                    return Err(format!("<{SIGNATURE_ELEMENT}> must sit in <event><detail>"));
+                }
+                found = Some(signature_attributes(
+                    &element,
+                    start,
+                    to_offset(reader.buffer_position()),
+                )?);
+            }
+            Event::Eof => return Ok(found),
+            _ => {}
+        }
+    }
+}
+
+fn signature_attributes(
+    element: &BytesStart<'_>,
+    start: usize,
+    end: usize,
+) -> std::result::Result<SignatureElement, String> {
+    let mut found = SignatureElement {
+        start,
+        end,
+        alg: None,
+        kid: None,
+        sig: None,
+    };
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|error| error.to_string())?;
+        let slot = match attribute.key.as_ref() {
+            b"alg" => &mut found.alg,
+            b"kid" => &mut found.kid,
+            b"sig" => &mut found.sig,
+            _ => continue,
+        };
+        let value = attribute
+            .unescape_value()
+            .map_err(|error| error.to_string())?;
+        *slot = Some(value.into_owned());
+    }
+    Ok(found)
+}
+
+fn to_offset(position: u64) -> usize {
+    usize::try_from(position).unwrap_or(usize::MAX)
+}
+
+/// `xml` with an open `<detail>` under its `<event>` root, and the offset of
+/// that detail's closing tag, where the signature element goes. An empty
+/// `<detail/>` or `<event/>` is expanded and a missing detail is added
+/// before `</event>`; the element is located by parsing, so attributes and
+/// whitespace inside either tag do not matter.
+fn open_detail(xml: &str) -> Result<(String, usize)> {
+    let mut reader = Reader::from_str(xml);
+    let mut depth = 0_usize;
+    let mut in_detail = false;
+    loop {
+        let start = to_offset(reader.buffer_position());
+        let event = reader
+            .read_event()
+            .map_err(|error| CryptoError::InvalidCotXml {
+                reason: error.to_string(),
+            })?;
+        let end = to_offset(reader.buffer_position());
+        match event {
+            Event::Start(element) => {
+                if depth == 0 && element.name().as_ref() != b"event" {
+                    return Err(CryptoError::UnsignableEvent);
+                }
+                in_detail |= depth == 1 && element.name().as_ref() == b"detail";
+                depth += 1;
+            }
+            Event::Empty(element) => match (depth, element.name().as_ref()) {
+                (0, b"event") => {
+                    return Ok(expand_empty(xml, end, "<detail>", "</detail></event>"))
+                }
+                (0, _) => return Err(CryptoError::UnsignableEvent),
+                (1, b"detail") => return Ok(expand_empty(xml, end, "", "</detail>")),
+                _ => {}
+            },
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                match depth {
+                    1 if in_detail => return Ok((xml.to_owned(), start)),
+                    0 => {
+                        let unsigned =
+                            format!("{}<detail></detail>{}", &xml[..start], &xml[start..]);
+                        return Ok((unsigned, start + "<detail>".len()));
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => return Err(CryptoError::UnsignableEvent),
+            _ => {}
+        }
+    }
+}
+
+/// Rewrites the empty element ending at `end` as `<name ...>{before}{after}`
+/// and returns the offset between `before` and `after`.
+fn expand_empty(xml: &str, end: usize, before: &str, after: &str) -> (String, usize) {
+    let head = &xml[..end - "/>".len()];
+    let unsigned = format!("{head}>{before}{after}{}", &xml[end..]);
+    let at = head.len() + ">".len() + before.len();
+    (unsigned, at)
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{
+        CertificateParams, KeyPair, PKCS_ECDSA_P256_SHA256, PKCS_ECDSA_P384_SHA384, PKCS_ED25519,
+    };
+
+    use super::{
+        canonicalize_cot, CotSigner, CotVerifier, DetachedAlgorithm, DetachedSigner,
+        DetachedVerifier, SignaturePolicy, SignatureStatus,
+    };
+    use crate::CryptoError;
+
+    const EVENT: &str = "<?xml version=\"1.0\"?>\n<event version=\"2.0\" uid=\"u-1\" type=\"a-f-G\">\n  <point lat=\"1.0\" lon=\"2.0\"/>\n  <detail>\n    <contact callsign=\"ALPHA\"/>\n  </detail>\n</event>";
+
+    fn signer() -> CotSigner {
+        CotSigner::from_seed("gw-1", [7; 32]).expect("signer")
+    }
+
+    fn verifier(policy: SignaturePolicy) -> CotVerifier {
+        CotVerifier::new(policy)
+            .with_trusted_key("gw-1", &signer().public_key_base64())
+            .expect("trusted key")
+    }
+
+    #[test]
+    fn canonical_form_ignores_declaration_and_layout_whitespace() {
+        assert_eq!(
+            canonicalize_cot(EVENT),
+            "<event version=\"2.0\" uid=\"u-1\" type=\"a-f-G\"><point lat=\"1.0\" lon=\"2.0\"/><detail><contact callsign=\"ALPHA\"/></detail></event>"
+        );
+    }
+
+    #[test]
+    fn signed_event_verifies_after_whitespace_reformatting() {
+        let signed = signer().sign(EVENT).expect("sign");
+        assert!(signed.contains("<_rustak_sig alg=\"ed25519\" kid=\"gw-1\" sig=\""));
+
+        let reformatted = signed.replace("><", ">\n<");
+        let status = verifier(SignaturePolicy::Require)
+            .verify(&reformatted)
+            .expect("valid signature");
+        assert_eq!(
+            status,
+            SignatureStatus::Verified {
+                key_id: "gw-1".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn signing_adds_detail_when_event_has_none() {
+        let bare = "<event uid=\"u-2\"><point lat=\"0\" lon=\"0\"/></event>";
+        let signed = signer().sign(bare).expect("sign");
+        assert!(signed
+            .starts_with("<event uid=\"u-2\"><point lat=\"0\" lon=\"0\"/><detail><_rustak_sig"));
+        assert!(verifier(SignaturePolicy::Require)
+            .verify(&signed)
+            .expect("valid")
+            .is_verified());
+    }
+
+    #[test]
+    fn signing_fills_a_spaced_empty_detail() {
+        let spaced = "<event uid=\"u-3\"><point lat=\"0\" lon=\"0\"/><detail /></event>";
+        let signed = signer().sign(spaced).expect("sign");
+        assert!(signed.contains("<detail ><_rustak_sig alg=\"ed25519\""));
+        assert!(signed.ends_with("/></detail></event>"));
+        assert!(verifier(SignaturePolicy::Require)
+            .verify(&signed)
+            .expect("valid")
+            .is_verified());
+    }
+
+    #[test]
+    fn signing_expands_a_self_closing_event() {
+        let signed = signer().sign("<event uid=\"u-4\" />").expect("sign");
+        assert!(signed.starts_with("<event uid=\"u-4\" ><detail><_rustak_sig"));
+        assert!(verifier(SignaturePolicy::Require)
+            .verify(&signed)
+            .expect("valid")
+            .is_verified());
+    }
+
+    #[test]
+    fn signing_refuses_xml_without_an_event_root() {
+        for xml in [
+            "<point lat=\"0\" lon=\"0\"/>",
+            "<message><detail/></message>",
+            "",
+        ] {
+            let error = signer().sign(xml).expect_err("nothing to sign");
+            assert!(matches!(error, CryptoError::UnsignableEvent), "{xml}");
+        }
+    }
+
+    #[test]
+    fn tampered_event_is_rejected_when_required() {
+        let signed = signer().sign(EVENT).expect("sign");
+        let tampered = signed.replace("ALPHA", "BRAVO");
+
+        let error = verifier(SignaturePolicy::Require)
+            .verify(&tampered)
+            .expect_err("tampered payload must fail");
+        assert!(matches!(
+            error,
+            CryptoError::SignatureRejected {
+                status: SignatureStatus::Invalid { .. }
+            }
+        ));
+
+        let status = verifier(SignaturePolicy::Warn)
+            .verify(&tampered)
+            .expect("warn mode reports instead of failing");
+        assert!(matches!(status, SignatureStatus::Invalid { .. }));
+    }
+
+    #[test]
+    fn extra_signature_elements_fail_verification() {
+        let signed = signer().sign(EVENT).expect("sign");
+        let forged = "<_rustak_sig alg=\"ed25519\" kid=\"gw-1\" sig=\"AAAA\"/>";
+        let appended = signed.replace("</detail>", &format!("{forged}</detail>"));
+        let trailing = format!("{signed}{forged}");
+        let misplaced = signed.replace("<point", &format!("{forged}<point"));
+
+        for tampered in [appended, trailing, misplaced] {
+            let error = verifier(SignaturePolicy::Require)
+                .verify(&tampered)
+                .expect_err("a second signature element must fail");
+            assert!(matches!(
+                error,
+                CryptoError::SignatureRejected {
+                    status: SignatureStatus::Malformed
+                }
+            ));
+            let error = signer().sign(&tampered).expect_err("ambiguous signature");
+            assert!(matches!(error, CryptoError::InvalidCotXml { .. }));
+        }
+    }
+
+    #[test]
+    fn policy_controls_unsigned_and_unknown_key_handling() {
+        assert_eq!(
+            verifier(SignaturePolicy::Ignore)
+                .verify(EVENT)
+                .expect("ignored"),
+            SignatureStatus::NotChecked
+        );
+        assert_eq!(
+            verifier(SignaturePolicy::Warn).verify(EVENT).expect("warn"),
+            SignatureStatus::Unsigned
+        );
+
+        let foreign = CotSigner::from_seed("other", [9; 32])
+            .expect("signer")
+            .sign(EVENT)
+            .expect("sign");
+        let error = verifier(SignaturePolicy::Require)
+            .verify(&foreign)
+            .expect_err("unknown key must fail");
+        assert!(matches!(
+            error,
+            CryptoError::SignatureRejected {
+                status: SignatureStatus::UnknownKey { .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_key_material() {
+        let error = CotVerifier::new(SignaturePolicy::Require)
+            .with_trusted_key("gw-1", "c2hvcnQ=")
+            .expect_err("short key");
+        assert!(matches!(error, CryptoError::InvalidSigningKey { .. }));
+
+        let error = CotSigner::from_seed("bad id", [1; 32]).expect_err("space in id");
+        assert!(matches!(error, CryptoError::InvalidSigningKey { .. }));
+    }
+
+    #[test]
+    fn detached_signatures_verify_with_public_key_or_certificate() {
+        for (algorithm, expected) in [
+            (&PKCS_ED25519, DetachedAlgorithm::Ed25519),
+            (&PKCS_ECDSA_P256_SHA256, DetachedAlgorithm::EcdsaP256),
+        ] {
+            let key = KeyPair::generate_for(algorithm).expect("key");
+            let signer = DetachedSigner::from_pem(&key.serialize_pem()).expect("signer");
+            assert_eq!(signer.algorithm(), expected);
+            let signature = signer.sign(b"chain head").expect("sign");
+
+            let verifier = DetachedVerifier::from_pem(&key.public_key_pem()).expect("verifier");
+            assert_eq!(verifier, signer.verifier());
+            assert!(verifier.verify(b"chain head", &signature));
+            assert!(!verifier.verify(b"chain tail", &signature));
+
+            let certificate = CertificateParams::new(vec!["recorder".to_owned()])
+                .expect("params")
+                .self_signed(&key)
+                .expect("certificate");
+            let from_certificate =
+                DetachedVerifier::from_pem(&certificate.pem()).expect("certificate verifier");
+            assert!(from_certificate.verify(b"chain head", &signature));
+        }
+    }
+
+    #[test]
+    fn detached_signer_rejects_keys_it_cannot_sign_with() {
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).expect("key");
+        let error = DetachedSigner::from_pem(&key.serialize_pem()).expect_err("p-384");
+        assert!(matches!(
+            error,
+            CryptoError::UnsupportedSigningKey { ref algorithm } if algorithm == "1.3.132.0.34"
+        ));
+        let error = DetachedVerifier::from_pem(&key.public_key_pem()).expect_err("p-384");
+        assert!(matches!(error, CryptoError::UnsupportedSigningKey { .. }));
+    }
+}
@@ -0,0 +1,377 @@
+//! rustls client configuration enforcing a [`CryptoConfig`].
+//!
+//! [`client_config`] turns a PEM or PKCS#12 identity into a rustls
+//! `ClientConfig` for the selected provider. The server's certificate is
+//! validated against the identity's CA bundle under the configured
+//! [`RevocationPolicy`]; when `server_spki_pin` is set, [`SpkiPinnedVerifier`]
+//! also requires the server key to match the pin. The pin is checked after
+//! chain validation, never instead of it.
+
+use std::sync::Arc;
+
+use der::{Decode, Encode};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WantsClientCert;
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{
+    CertificateDer, CertificateRevocationListDer, PrivateKeyDer, ServerName, UnixTime,
+};
+use rustls::{
+    CertificateError, ClientConfig, ConfigBuilder, DigitallySignedStruct, RootCertStore,
+    SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use x509_cert::Certificate;
+
+use crate::certs::{pem_certificates, pem_private_key};
+use crate::{
+    CryptoConfig, CryptoError, CryptoProviderMode, LoadedIdentity, PemIdentity, Result,
+    RevocationPolicy,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsClientConfig {
+    pub provider: CryptoProviderMode,
+    pub revocation: RevocationPolicy,
+    /// PEM-encoded CRLs consulted unless `revocation` is `Off`.
+    pub crls_pem: Vec<String>,
+    /// SHA-256 of the server certificate's SubjectPublicKeyInfo. Checked in
+    /// addition to normal chain validation, never instead of it.
+    pub server_spki_pin: Option<[u8; 32]>,
+}
+
+impl TlsClientConfig {
+    pub fn from_crypto_config(config: &CryptoConfig) -> Result<Self> {
+        Ok(Self {
+            provider: config.provider,
+            revocation: config.revocation,
+            crls_pem: Vec::new(),
+            server_spki_pin: config.server_spki_pin_sha256()?,
+        })
+    }
+}
+
+/// Mutual-TLS client configuration presenting `identity` and trusting the
+/// CA bundle that came with it. Token-held keys sign through PKCS#11.
+pub fn client_config(identity: &LoadedIdentity, config: &TlsClientConfig) -> Result<ClientConfig> {
+    #[cfg(feature = "pkcs11")]
+    if let LoadedIdentity::Pkcs11(token) = identity {
+        let certified_key = Arc::new(token.certified_key()?);
+        return Ok(builder(&token.ca_cert_pem, config)?
+            .with_client_cert_resolver(Arc::new(SingleClientCert(certified_key))));
+    }
+    let pem = identity.to_pem()?;
+    let (client_chain, client_key) = client_auth(&pem)?;
+    builder(&pem.ca_cert_pem, config)?
+        .with_client_auth_cert(client_chain, client_key)
+        .map_err(tls_config)
+}
+
+/// Client configuration that authenticates the server against
+/// `ca_cert_pem` but presents no certificate of its own.
+pub fn client_config_without_client_auth(
+    ca_cert_pem: &str,
+    config: &TlsClientConfig,
+) -> Result<ClientConfig> {
+    Ok(builder(ca_cert_pem, config)?.with_no_client_auth())
+}
+
+fn builder(
+    ca_cert_pem: &str,
+    config: &TlsClientConfig,
+) -> Result<ConfigBuilder<ClientConfig, WantsClientCert>> {
+    let provider = Arc::new(crypto_provider(config.provider)?);
+    let verifier = server_verifier(&provider, ca_cert_pem, config)?;
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(tls_config)?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier))
+}
+
+/// Presents the same certificate and key to every server.
+#[cfg(feature = "pkcs11")]
+#[derive(Debug)]
+struct SingleClientCert(Arc<rustls::sign::CertifiedKey>);
+
+#[cfg(feature = "pkcs11")]
+impl rustls::client::ResolvesClientCert for SingleClientCert {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        Some(Arc::clone(&self.0))
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+/// The certificate chain and key rustls presents for `identity`. Keys may be
+/// PKCS#8, PKCS#1 or SEC1.
+pub fn client_auth(
+    identity: &PemIdentity,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let chain = pem_certificates("client_cert_pem", &identity.client_cert_pem)?;
+    let key = pem_private_key("client_key_pem", &identity.client_key_pem)?;
+    Ok((chain, PrivateKeyDer::Pkcs8(key.into())))
+}
+
+/// WebPKI validation against `ca_cert_pem` with the revocation policy and
+/// optional SPKI pin from `config`.
+pub fn server_verifier(
+    provider: &Arc<CryptoProvider>,
+    ca_cert_pem: &str,
+    config: &TlsClientConfig,
+) -> Result<Arc<dyn ServerCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for ca in pem_certificates("ca_cert_pem", ca_cert_pem)? {
+        roots.add(ca).map_err(tls_config)?;
+    }
+    let crls = config
+        .crls_pem
+        .iter()
+        .map(|crl| {
+            CertificateRevocationListDer::from_pem_slice(crl.as_bytes()).map_err(|error| {
+                CryptoError::InvalidPem {
+                    field: "crls_pem",
+                    reason: error.to_string(),
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut verifier =
+        WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(provider));
+    match config.revocation {
+        RevocationPolicy::Off => {}
+        RevocationPolicy::Prefer => {
+            verifier = verifier.with_crls(crls).allow_unknown_revocation_status();
+        }
+        RevocationPolicy::Require => {
+            if crls.is_empty() {
+                return Err(CryptoError::RevocationUnavailable);
+            }
+            verifier = verifier.with_crls(crls);
+        }
+    }
+    let verifier = verifier.build().map_err(tls_config)?;
+    Ok(match config.server_spki_pin {
+        Some(pin) => Arc::new(SpkiPinnedVerifier::new(verifier, pin)),
+        None => verifier,
+    })
+}
+
+pub fn crypto_provider(mode: CryptoProviderMode) -> Result<CryptoProvider> {
+    match mode {
+        CryptoProviderMode::Ring => Ok(rustls::crypto::ring::default_provider()),
+        CryptoProviderMode::AwsLcRs | CryptoProviderMode::AwsLcRsFips => {
+            Err(CryptoError::TlsProviderUnavailable { provider: mode })
+        }
+    }
+}
+
+/// Whether `mode` can be used for TLS in this build.
+#[must_use]
+pub fn provider_available(mode: CryptoProviderMode) -> bool {
+    crypto_provider(mode).is_ok()
+}
+
+/// Chain validation followed by an SPKI digest comparison.
+#[derive(Debug)]
+pub struct SpkiPinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pin: [u8; 32],
+}
+
+impl SpkiPinnedVerifier {
+    #[must_use]
+    pub fn new(inner: Arc<WebPkiServerVerifier>, pin: [u8; 32]) -> Self {
+        Self { inner, pin }
+    }
+}
+
+impl ServerCertVerifier for SpkiPinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        match spki_sha256(end_entity) {
+            Some(digest) if digest == self.pin => Ok(verified),
+            Some(_) => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+            None => Err(rustls::Error::InvalidCertificate(
+                CertificateError::BadEncoding,
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+fn spki_sha256(certificate_der: &[u8]) -> Option<[u8; 32]> {
+    let certificate = Certificate::from_der(certificate_der).ok()?;
+    let spki_der = certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .ok()?;
+    Some(Sha256::digest(&spki_der).into())
+}
+
+fn tls_config(error: impl std::fmt::Display) -> CryptoError {
+    CryptoError::TlsConfig {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::pki_types::{ServerName, UnixTime};
+    use rustls::{CertificateError, RootCertStore};
+
+    use super::{client_config, crypto_provider, SpkiPinnedVerifier, TlsClientConfig};
+    use crate::{
+        decode_pkcs12, encode_pkcs12, CertificateInfo, CryptoError, CryptoProviderMode,
+        LoadedIdentity, PemIdentity, RevocationPolicy,
+    };
+
+    fn settings() -> TlsClientConfig {
+        TlsClientConfig {
+            provider: CryptoProviderMode::Ring,
+            revocation: RevocationPolicy::Off,
+            crls_pem: Vec::new(),
+            server_spki_pin: None,
+        }
+    }
+
+    #[test]
+    fn pinned_verifier_requires_a_valid_chain_and_the_pinned_key() {
+        let ca_key = KeyPair::generate().expect("ca key");
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).expect("ca cert");
+        let server_key = KeyPair::generate().expect("server key");
+        let server = CertificateParams::new(vec!["tak.example.com".to_owned()])
+            .expect("server params")
+            .signed_by(&server_key, &ca, &ca_key)
+            .expect("server cert");
+
+        let provider = Arc::new(crypto_provider(CryptoProviderMode::Ring).expect("ring"));
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).expect("root");
+        let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .expect("webpki verifier");
+        let verify = |pin: [u8; 32], name: &'static str| {
+            SpkiPinnedVerifier::new(Arc::clone(&inner), pin).verify_server_cert(
+                server.der(),
+                &[],
+                &ServerName::try_from(name).expect("name"),
+                &[],
+                UnixTime::now(),
+            )
+        };
+        use rustls::client::danger::ServerCertVerifier;
+
+        let pin = CertificateInfo::from_der(server.der())
+            .expect("server certificate")
+            .spki_sha256;
+        assert!(verify(pin, "tak.example.com").is_ok());
+        assert!(matches!(
+            verify([7; 32], "tak.example.com"),
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            ))
+        ));
+        // A matching pin does not rescue a certificate for another host.
+        assert!(matches!(
+            verify(pin, "other.example.com"),
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn client_configs_accept_pkcs12_and_enforce_provider_and_revocation() {
+        let ca_key = KeyPair::generate().expect("ca key");
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).expect("ca cert");
+        let client_key = KeyPair::generate().expect("client key");
+        let client = CertificateParams::new(vec!["rustak-client".to_owned()])
+            .expect("client params")
+            .signed_by(&client_key, &ca, &ca_key)
+            .expect("client cert");
+        let pem = PemIdentity {
+            ca_cert_pem: ca.pem(),
+            client_cert_pem: client.pem(),
+            client_key_pem: client_key.serialize_pem(),
+        };
+        let archive = encode_pkcs12(&pem, "atakatak").expect("archive");
+        let pkcs12 = LoadedIdentity::Pem(decode_pkcs12(&archive, "atakatak").expect("unpack"));
+
+        let config = client_config(&pkcs12, &settings()).expect("pkcs12 identity");
+        assert!(config.client_auth_cert_resolver.has_certs());
+        assert!(matches!(
+            client_config(
+                &LoadedIdentity::Pem(pem.clone()),
+                &TlsClientConfig {
+                    provider: CryptoProviderMode::AwsLcRsFips,
+                    ..settings()
+                }
+            ),
+            Err(CryptoError::TlsProviderUnavailable { .. })
+        ));
+        assert!(matches!(
+            client_config(
+                &LoadedIdentity::Pem(pem),
+                &TlsClientConfig {
+                    revocation: RevocationPolicy::Require,
+                    ..settings()
+                }
+            ),
+            Err(CryptoError::RevocationUnavailable)
+        ));
+    }
+}
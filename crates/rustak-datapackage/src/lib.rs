@@ -0,0 +1,584 @@
+//! TAK data packages: zip archives whose `MANIFEST/manifest.xml` lists the
+//! CoT events and files they carry.
+//!
+//! [`DataPackageBuilder`] assembles a package from events and attachments;
+//! [`DataPackage::parse`] reads one back under [`PackageLimits`], refusing
+//! oversized or unsafe archives before inflating them in full.
+
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Write};
+
+use rustak_core::{CotEvent, CotXmlError};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use thiserror::Error;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub mod manifest;
+
+pub use manifest::{Manifest, ManifestContent, MANIFEST_PATH, MANIFEST_VERSION};
+
+/// `contentType` the builder records for CoT entries.
+pub const COT_CONTENT_TYPE: &str = "CoT Event";
+
+/// Bounds applied while reading an untrusted package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageLimits {
+    pub max_archive_bytes: usize,
+    pub max_entries: usize,
+    /// Largest inflated size of any one file.
+    pub max_entry_bytes: usize,
+    /// Largest inflated size of all files together.
+    pub max_total_bytes: usize,
+    /// Bounds the manifest (`max_xml_scan_bytes`) and every `.cot` entry.
+    pub xml: Limits,
+}
+
+impl PackageLimits {
+    pub const DEFAULT_MAX_ARCHIVE_BYTES: usize = 64 * 1024 * 1024;
+    pub const DEFAULT_MAX_ENTRIES: usize = 1_024;
+    pub const DEFAULT_MAX_ENTRY_BYTES: usize = 32 * 1024 * 1024;
+    pub const DEFAULT_MAX_TOTAL_BYTES: usize = 128 * 1024 * 1024;
+
+    /// Default archive bounds with XML bounded by `xml`.
+    #[must_use]
+    pub fn from_limits(xml: Limits) -> Self {
+        Self {
+            max_archive_bytes: Self::DEFAULT_MAX_ARCHIVE_BYTES,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            max_entry_bytes: Self::DEFAULT_MAX_ENTRY_BYTES,
+            max_total_bytes: Self::DEFAULT_MAX_TOTAL_BYTES,
+            xml,
+        }
+    }
+}
+
+impl Default for PackageLimits {
+    fn default() -> Self {
+        Self::from_limits(Limits::conservative_defaults())
+    }
+}
+
+/// A file stored in the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageFile {
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+/// A manifest and the files it describes. Archive files the manifest does
+/// not list are kept, as ATAK does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataPackage {
+    pub manifest: Manifest,
+    pub files: Vec<PackageFile>,
+}
+
+impl DataPackage {
+    /// Reads and validates a package. Entry sizes are checked against the
+    /// central directory first and enforced again while inflating.
+    pub fn parse(archive: &[u8], limits: &PackageLimits) -> Result<Self, DataPackageError> {
+        if archive.len() > limits.max_archive_bytes {
+            return Err(DataPackageError::ArchiveTooLarge {
+                size: archive.len(),
+                max: limits.max_archive_bytes,
+            });
+        }
+        let mut zip = ZipArchive::new(Cursor::new(archive)).map_err(zip_error)?;
+        if zip.len() > limits.max_entries {
+            return Err(DataPackageError::TooManyEntries {
+                count: zip.len(),
+                max: limits.max_entries,
+            });
+        }
+
+        let mut manifest = None;
+        let mut files = Vec::new();
+        let mut total = 0_usize;
+        for index in 0..zip.len() {
+            let mut entry = zip.by_index(index).map_err(zip_error)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let path = entry.name().to_owned();
+            let is_manifest = path == MANIFEST_PATH;
+            let max = if is_manifest {
+                limits.xml.max_xml_scan_bytes
+            } else {
+                limits.max_entry_bytes
+            };
+            let too_large = |size| DataPackageError::EntryTooLarge {
+                path: path.clone(),
+                size,
+                max,
+            };
+            let declared = usize::try_from(entry.size()).unwrap_or(usize::MAX);
+            if declared > max {
+                return Err(too_large(declared));
+            }
+            let mut bytes = Vec::with_capacity(declared);
+            (&mut entry)
+                .take(max as u64 + 1)
+                .read_to_end(&mut bytes)
+                .map_err(|error| DataPackageError::Zip {
+                    reason: format!("{path}: {error}"),
+                })?;
+            if bytes.len() > max {
+                return Err(too_large(bytes.len()));
+            }
+            total += bytes.len();
+            if total > limits.max_total_bytes {
+                return Err(DataPackageError::TotalTooLarge {
+                    max: limits.max_total_bytes,
+                });
+            }
+
+            if is_manifest {
+                let xml = std::str::from_utf8(&bytes).map_err(|_| DataPackageError::Manifest {
+                    reason: "manifest is not UTF-8".to_owned(),
+                })?;
+                manifest = Some(Manifest::from_xml(xml)?);
+            } else {
+                files.push(PackageFile { path, bytes });
+            }
+        }
+
+        let package = Self {
+            manifest: manifest.ok_or(DataPackageError::MissingManifest)?,
+            files,
+        };
+        package.validate()?;
+        Ok(package)
+    }
+
+    /// Checks the package uid, that every content path is a relative,
+    /// unique archive path present in the package, and that content uids
+    /// are not blank.
+    pub fn validate(&self) -> Result<(), DataPackageError> {
+        if self.manifest.uid.trim().is_empty() {
+            return Err(DataPackageError::EmptyPackageUid);
+        }
+        let mut paths = HashSet::new();
+        for file in &self.files {
+            validate_path(&file.path)?;
+            if !paths.insert(file.path.as_str()) {
+                return Err(DataPackageError::DuplicateEntry {
+                    path: file.path.clone(),
+                });
+            }
+        }
+        let mut listed = HashSet::new();
+        for content in &self.manifest.contents {
+            let path = content.zip_entry.as_str();
+            validate_path(path)?;
+            if !listed.insert(path) {
+                return Err(DataPackageError::DuplicateEntry {
+                    path: path.to_owned(),
+                });
+            }
+            if !paths.contains(path) {
+                return Err(DataPackageError::MissingEntry {
+                    path: path.to_owned(),
+                });
+            }
+            if content
+                .uid
+                .as_deref()
+                .is_some_and(|uid| uid.trim().is_empty())
+            {
+                return Err(DataPackageError::EmptyContentUid {
+                    path: path.to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.files
+            .iter()
+            .find(|file| file.path == path)
+            .map(|file| file.bytes.as_slice())
+    }
+
+    /// Events of the non-ignored `.cot` contents, in manifest order. An
+    /// entry whose manifest uid differs from its event's uid is an error.
+    pub fn cot_events(&self, limits: &Limits) -> Result<Vec<CotEvent>, DataPackageError> {
+        self.manifest
+            .contents
+            .iter()
+            .filter(|content| !content.ignore && content.zip_entry.ends_with(".cot"))
+            .map(|content| {
+                let path = content.zip_entry.as_str();
+                let bytes = self
+                    .file(path)
+                    .ok_or_else(|| DataPackageError::MissingEntry {
+                        path: path.to_owned(),
+                    })?;
+                let cot = |source| DataPackageError::Cot {
+                    path: path.to_owned(),
+                    source,
+                };
+                let xml = std::str::from_utf8(bytes)
+                    .map_err(|_| cot(CotXmlError::Syntax("event is not UTF-8".to_owned())))?;
+                let event = CotEvent::from_xml(xml, limits).map_err(cot)?;
+                if let Some(uid) = content.uid.as_deref().filter(|uid| *uid != event.uid) {
+                    return Err(DataPackageError::CotUidMismatch {
+                        path: path.to_owned(),
+                        manifest_uid: uid.to_owned(),
+                        event_uid: event.uid,
+                    });
+                }
+                Ok(event)
+            })
+            .collect()
+    }
+
+    /// Writes the package as a deflated zip, manifest first.
+    pub fn to_zip(&self) -> Result<Vec<u8>, DataPackageError> {
+        self.validate()?;
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let manifest = self.manifest.to_xml();
+        let entries = std::iter::once((MANIFEST_PATH, manifest.as_bytes())).chain(
+            self.files
+                .iter()
+                .map(|file| (file.path.as_str(), file.bytes.as_slice())),
+        );
+        for (path, bytes) in entries {
+            zip.start_file(path, options).map_err(zip_error)?;
+            zip.write_all(bytes)
+                .map_err(|error| DataPackageError::Zip {
+                    reason: format!("{path}: {error}"),
+                })?;
+        }
+        Ok(zip.finish().map_err(zip_error)?.into_inner())
+    }
+}
+
+/// Builds a package the way ATAK exports one: each event at
+/// `<uid>/<uid>.cot` and its attachments beside it.
+#[derive(Debug, Clone)]
+pub struct DataPackageBuilder {
+    package: DataPackage,
+}
+
+impl DataPackageBuilder {
+    #[must_use]
+    pub fn new(uid: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            package: DataPackage {
+                manifest: Manifest {
+                    uid: uid.into(),
+                    name: name.into(),
+                    on_receive_delete: false,
+                    contents: Vec::new(),
+                },
+                files: Vec::new(),
+            },
+        }
+    }
+
+    #[must_use]
+    pub fn on_receive_delete(mut self, on_receive_delete: bool) -> Self {
+        self.package.manifest.on_receive_delete = on_receive_delete;
+        self
+    }
+
+    #[must_use]
+    pub fn cot_event(self, event: &CotEvent) -> Self {
+        let path = format!("{0}/{0}.cot", event.uid);
+        self.push(
+            path,
+            event.to_xml().into_bytes(),
+            Some(event.uid.clone()),
+            Some(COT_CONTENT_TYPE.to_owned()),
+        )
+    }
+
+    /// A file attached to the event with uid `cot_uid`.
+    #[must_use]
+    pub fn attachment(self, cot_uid: &str, file_name: &str, bytes: Vec<u8>) -> Self {
+        self.push(
+            format!("{cot_uid}/{file_name}"),
+            bytes,
+            Some(cot_uid.to_owned()),
+            None,
+        )
+    }
+
+    /// A standalone file at `path`.
+    #[must_use]
+    pub fn file(self, path: impl Into<String>, bytes: Vec<u8>) -> Self {
+        self.push(path.into(), bytes, None, None)
+    }
+
+    /// The validated package; see [`DataPackage::validate`].
+    pub fn build(self) -> Result<DataPackage, DataPackageError> {
+        self.package.validate()?;
+        Ok(self.package)
+    }
+
+    fn push(
+        mut self,
+        path: String,
+        bytes: Vec<u8>,
+        uid: Option<String>,
+        content_type: Option<String>,
+    ) -> Self {
+        self.package.manifest.contents.push(ManifestContent {
+            uid,
+            content_type,
+            ..ManifestContent::new(path.clone())
+        });
+        self.package.files.push(PackageFile { path, bytes });
+        self
+    }
+}
+
+/// Archive paths must be relative, `/`-separated and free of `.` and `..`
+/// components so extraction cannot escape its directory.
+fn validate_path(path: &str) -> Result<(), DataPackageError> {
+    let unsafe_path = path.is_empty()
+        || path.starts_with('/')
+        || path.contains('\\')
+        || path.contains('\0')
+        || path.contains(':')
+        || path
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..");
+    if unsafe_path {
+        return Err(DataPackageError::UnsafeEntryPath {
+            path: path.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn zip_error(error: zip::result::ZipError) -> DataPackageError {
+    DataPackageError::Zip {
+        reason: error.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum DataPackageError {
+    #[error("invalid zip archive: {reason}")]
+    Zip { reason: String },
+
+    #[error("package of {size} bytes exceeds the {max}-byte limit")]
+    ArchiveTooLarge { size: usize, max: usize },
+
+    #[error("package has {count} entries, more than the limit of {max}")]
+    TooManyEntries { count: usize, max: usize },
+
+    #[error("entry `{path}` inflates to {size} bytes, more than the limit of {max}")]
+    EntryTooLarge {
+        path: String,
+        size: usize,
+        max: usize,
+    },
+
+    #[error("package contents exceed the {max}-byte total limit")]
+    TotalTooLarge { max: usize },
+
+    #[error("package has no {MANIFEST_PATH}")]
+    MissingManifest,
+
+    #[error("invalid manifest: {reason}")]
+    Manifest { reason: String },
+
+    #[error("manifest uid must not be empty")]
+    EmptyPackageUid,
+
+    #[error("entry path `{path}` is not a safe relative path")]
+    UnsafeEntryPath { path: String },
+
+    #[error("entry `{path}` appears more than once")]
+    DuplicateEntry { path: String },
+
+    #[error("manifest lists `{path}`, which the archive does not contain")]
+    MissingEntry { path: String },
+
+    #[error("manifest content `{path}` has an empty uid")]
+    EmptyContentUid { path: String },
+
+    #[error("`{path}` is listed as uid `{manifest_uid}` but holds event `{event_uid}`")]
+    CotUidMismatch {
+        path: String,
+        manifest_uid: String,
+        event_uid: String,
+    },
+
+    #[error("`{path}` is not a valid CoT event: {source}")]
+    Cot { path: String, source: CotXmlError },
+}
+
+impl CodedError for DataPackageError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Zip { .. } => ErrorCode::new("DATAPACKAGE", 1),
+            Self::ArchiveTooLarge { .. } => ErrorCode::new("DATAPACKAGE", 2),
+            Self::TooManyEntries { .. } => ErrorCode::new("DATAPACKAGE", 3),
+            Self::EntryTooLarge { .. } => ErrorCode::new("DATAPACKAGE", 4),
+            Self::TotalTooLarge { .. } => ErrorCode::new("DATAPACKAGE", 5),
+            Self::MissingManifest => ErrorCode::new("DATAPACKAGE", 6),
+            Self::Manifest { .. } => ErrorCode::new("DATAPACKAGE", 7),
+            Self::EmptyPackageUid => ErrorCode::new("DATAPACKAGE", 8),
+            Self::UnsafeEntryPath { .. } => ErrorCode::new("DATAPACKAGE", 9),
+            Self::DuplicateEntry { .. } => ErrorCode::new("DATAPACKAGE", 10),
+            Self::MissingEntry { .. } => ErrorCode::new("DATAPACKAGE", 11),
+            Self::EmptyContentUid { .. } => ErrorCode::new("DATAPACKAGE", 12),
+            Self::CotUidMismatch { .. } => ErrorCode::new("DATAPACKAGE", 13),
+            Self::Cot { .. } => ErrorCode::new("DATAPACKAGE", 14),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use rustak_core::{CotEvent, Position, TimestampUtc};
+    use rustak_limits::{CodedError, Limits};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::{DataPackage, DataPackageBuilder, DataPackageError, PackageLimits, MANIFEST_PATH};
+
+    fn event(uid: &str) -> CotEvent {
+        let time = TimestampUtc::from_unix_seconds_nanos(1_700_000_000, 0).expect("time");
+        let stale = TimestampUtc::from_unix_seconds_nanos(1_700_000_300, 0).expect("stale");
+        CotEvent::new(
+            uid,
+            "a-f-G-U-C",
+            time,
+            stale,
+            Position::new(51.5, -0.12).expect("position"),
+        )
+    }
+
+    fn raw_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, bytes) in entries {
+            zip.start_file(*path, SimpleFileOptions::default())
+                .expect("start entry");
+            zip.write_all(bytes).expect("write entry");
+        }
+        zip.finish().expect("finish").into_inner()
+    }
+
+    #[test]
+    fn built_packages_parse_back_with_events_and_attachments() {
+        let package = DataPackageBuilder::new("pkg-1", "Patrol")
+            .on_receive_delete(true)
+            .cot_event(&event("marker-1"))
+            .attachment("marker-1", "photo.jpg", vec![0xFF, 0xD8, 0xFF])
+            .file("notes.txt", b"north gate".to_vec())
+            .build()
+            .expect("package");
+        let archive = package.to_zip().expect("zip");
+
+        let parsed = DataPackage::parse(&archive, &PackageLimits::default()).expect("parse");
+        assert_eq!(parsed, package);
+        assert_eq!(
+            parsed.file("marker-1/photo.jpg"),
+            Some(&[0xFF, 0xD8, 0xFF][..])
+        );
+        let events = parsed
+            .cot_events(&Limits::conservative_defaults())
+            .expect("events");
+        assert_eq!(events, [event("marker-1")]);
+    }
+
+    #[test]
+    fn limits_are_enforced_before_inflating() {
+        let archive = DataPackageBuilder::new("pkg-1", "Large")
+            .file("blob.bin", vec![0; 4096])
+            .build()
+            .and_then(|package| package.to_zip())
+            .expect("zip");
+
+        let limits = PackageLimits {
+            max_entry_bytes: 1024,
+            ..PackageLimits::default()
+        };
+        let error = DataPackage::parse(&archive, &limits).expect_err("entry limit");
+        assert!(matches!(
+            error,
+            DataPackageError::EntryTooLarge {
+                size: 4096,
+                max: 1024,
+                ..
+            }
+        ));
+        assert_eq!(error.code().to_string(), "RTK-DATAPACKAGE-0004");
+
+        let limits = PackageLimits {
+            max_entries: 1,
+            ..PackageLimits::default()
+        };
+        assert!(matches!(
+            DataPackage::parse(&archive, &limits),
+            Err(DataPackageError::TooManyEntries { count: 2, max: 1 })
+        ));
+        let limits = PackageLimits {
+            max_archive_bytes: 16,
+            ..PackageLimits::default()
+        };
+        assert!(matches!(
+            DataPackage::parse(&archive, &limits),
+            Err(DataPackageError::ArchiveTooLarge { max: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn manifests_are_validated_against_the_archive() {
+        let manifest = |contents: &str| {
+            format!(
+                r#"<MissionPackageManifest version="2"><Configuration><Parameter name="uid" value="pkg"/><Parameter name="name" value="n"/></Configuration><Contents>{contents}</Contents></MissionPackageManifest>"#
+            )
+        };
+        let limits = PackageLimits::default();
+        let cot = event("b2").to_xml();
+
+        let missing = manifest(r#"<Content ignore="false" zipEntry="a/a.cot"/>"#);
+        assert_eq!(
+            DataPackage::parse(&raw_zip(&[(MANIFEST_PATH, missing.as_bytes())]), &limits),
+            Err(DataPackageError::MissingEntry {
+                path: "a/a.cot".to_owned()
+            })
+        );
+
+        let escaping = manifest(r#"<Content ignore="false" zipEntry="../evil.cot"/>"#);
+        let archive = raw_zip(&[
+            (MANIFEST_PATH, escaping.as_bytes()),
+            ("../evil.cot", cot.as_bytes()),
+        ]);
+        assert!(matches!(
+            DataPackage::parse(&archive, &limits),
+            Err(DataPackageError::UnsafeEntryPath { .. })
+        ));
+
+        let mismatched = manifest(
+            r#"<Content ignore="false" zipEntry="a/a.cot"><Parameter name="uid" value="a"/></Content>"#,
+        );
+        let archive = raw_zip(&[
+            (MANIFEST_PATH, mismatched.as_bytes()),
+            ("a/a.cot", cot.as_bytes()),
+        ]);
+        let package = DataPackage::parse(&archive, &limits).expect("structurally valid");
+        assert!(matches!(
+            package.cot_events(&limits.xml),
+            Err(DataPackageError::CotUidMismatch { event_uid, .. }) if event_uid == "b2"
+        ));
+
+        let archive = raw_zip(&[("a/a.cot", cot.as_bytes())]);
+        assert_eq!(
+            DataPackage::parse(&archive, &limits),
+            Err(DataPackageError::MissingManifest)
+        );
+        assert!(DataPackageBuilder::new(" ", "blank").build().is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::DataPackageError;
+
+/// Archive path ATAK reads the manifest from.
+pub const MANIFEST_PATH: &str = "MANIFEST/manifest.xml";
+
+/// `MissionPackageManifest` version this crate writes.
+pub const MANIFEST_VERSION: &str = "2";
+
+/// The `MissionPackageManifest` document of a data package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub uid: String,
+    pub name: String,
+    /// Asks the receiver to delete the package once imported.
+    pub on_receive_delete: bool,
+    pub contents: Vec<ManifestContent>,
+}
+
+/// One `<Content>` entry, pointing at a file in the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestContent {
+    pub zip_entry: String,
+    /// Receivers skip ignored entries on import.
+    pub ignore: bool,
+    /// For `.cot` entries the event uid; for attachments the uid of the
+    /// event they belong to.
+    pub uid: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl ManifestContent {
+    #[must_use]
+    pub fn new(zip_entry: impl Into<String>) -> Self {
+        Self {
+            zip_entry: zip_entry.into(),
+            ignore: false,
+            uid: None,
+            content_type: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Document,
+    Manifest,
+    Configuration,
+    Contents,
+    Content,
+}
+
+impl Manifest {
+    #[must_use]
+    pub fn to_xml(&self) -> String {
+        let mut out = format!("<MissionPackageManifest version=\"{MANIFEST_VERSION}\">");
+        out.push_str("<Configuration>");
+        write_parameter(&mut out, "uid", &self.uid);
+        write_parameter(&mut out, "name", &self.name);
+        if self.on_receive_delete {
+            write_parameter(&mut out, "onReceiveDelete", "true");
+        }
+        out.push_str("</Configuration><Contents>");
+        for content in &self.contents {
+            out.push_str(&format!(
+                "<Content ignore=\"{}\" zipEntry=\"{}\"",
+                content.ignore,
+                escape(content.zip_entry.as_str())
+            ));
+            if content.uid.is_none() && content.content_type.is_none() {
+                out.push_str("/>");
+                continue;
+            }
+            out.push('>');
+            if let Some(uid) = &content.uid {
+                write_parameter(&mut out, "uid", uid);
+            }
+            if let Some(content_type) = &content.content_type {
+                write_parameter(&mut out, "contentType", content_type);
+            }
+            out.push_str("</Content>");
+        }
+        out.push_str("</Contents></MissionPackageManifest>");
+        out
+    }
+
+    /// Parses a manifest. Unknown parameters and elements are skipped;
+    /// structure and uids are checked by [`crate::DataPackage::validate`].
+    pub fn from_xml(xml: &str) -> Result<Self, DataPackageError> {
+        let mut reader = Reader::from_str(xml);
+        let mut scope = Scope::Document;
+        let mut depth_in_unknown = 0_usize;
+        let mut manifest = Self {
+            uid: String::new(),
+            name: String::new(),
+            on_receive_delete: false,
+            contents: Vec::new(),
+        };
+        let mut seen_root = false;
+
+        loop {
+            let (element, empty) = match reader.read_event().map_err(syntax)? {
+                Event::Start(element) => (element, false),
+                Event::Empty(element) => (element, true),
+                Event::End(_) => {
+                    if depth_in_unknown > 0 {
+                        depth_in_unknown -= 1;
+                        continue;
+                    }
+                    scope = match scope {
+                        Scope::Content => Scope::Contents,
+                        Scope::Configuration | Scope::Contents => Scope::Manifest,
+                        Scope::Manifest | Scope::Document => Scope::Document,
+                    };
+                    continue;
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+            if depth_in_unknown > 0 {
+                depth_in_unknown += usize::from(!empty);
+                continue;
+            }
+
+            let next = match (scope, element.name().as_ref()) {
+                (Scope::Document, b"MissionPackageManifest") if !seen_root => {
+                    seen_root = true;
+                    Some(Scope::Manifest)
+                }
+                (Scope::Manifest, b"Configuration") => Some(Scope::Configuration),
+                (Scope::Manifest, b"Contents") => Some(Scope::Contents),
+                (Scope::Configuration, b"Parameter") => {
+                    let (name, value) = parameter(&element)?;
+                    match name.as_str() {
+                        "uid" => manifest.uid = value,
+                        "name" => manifest.name = value,
+                        "onReceiveDelete" => {
+                            manifest.on_receive_delete = value.eq_ignore_ascii_case("true");
+                        }
+                        _ => {}
+                    }
+                    None
+                }
+                (Scope::Contents, b"Content") => {
+                    let zip_entry = attribute(&element, "zipEntry")?.ok_or_else(|| {
+                        DataPackageError::Manifest {
+                            reason: "<Content> without a zipEntry".to_owned(),
+                        }
+                    })?;
+                    let mut content = ManifestContent::new(zip_entry);
+                    content.ignore = attribute(&element, "ignore")?
+                        .is_some_and(|ignore| ignore.eq_ignore_ascii_case("true"));
+                    manifest.contents.push(content);
+                    Some(Scope::Content)
+                }
+                (Scope::Content, b"Parameter") => {
+                    let (name, value) = parameter(&element)?;
+                    if let Some(content) = manifest.contents.last_mut() {
+                        match name.as_str() {
+                            "uid" => content.uid = Some(value),
+                            "contentType" => content.content_type = Some(value),
+                            _ => {}
+                        }
+                    }
+                    None
+                }
+                (Scope::Document, _) => {
+                    return Err(DataPackageError::Manifest {
+                        reason: "root element is not <MissionPackageManifest>".to_owned(),
+                    })
+                }
+                _ => None,
+            };
+            match (next, empty) {
+                (_, true) => {}
+                (Some(next), false) => scope = next,
+                // Skip to the matching end tag of parameters and unknowns.
+                (None, false) => depth_in_unknown = 1,
+            }
+        }
+
+        if !seen_root || scope != Scope::Document || depth_in_unknown > 0 {
+            return Err(DataPackageError::Manifest {
+                reason: "missing or unterminated <MissionPackageManifest>".to_owned(),
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+fn write_parameter(out: &mut String, name: &str, value: &str) {
+    out.push_str(&format!(
+        "<Parameter name=\"{}\" value=\"{}\"/>",
+        escape(name),
+        escape(value)
+    ));
+}
+
+fn parameter(element: &BytesStart<'_>) -> Result<(String, String), DataPackageError> {
+    let name = attribute(element, "name")?;
+    let value = attribute(element, "value")?;
+    name.zip(value).ok_or_else(|| DataPackageError::Manifest {
+        reason: "<Parameter> needs name and value".to_owned(),
+    })
+}
+
+fn attribute(element: &BytesStart<'_>, name: &str) -> Result<Option<String>, DataPackageError> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(syntax)?;
+        if attribute.key.as_ref() == name.as_bytes() {
+            return Ok(Some(
+                attribute.unescape_value().map_err(syntax)?.into_owned(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+fn syntax(error: impl std::fmt::Display) -> DataPackageError {
+    DataPackageError::Manifest {
+        reason: error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, ManifestContent};
+
+    #[test]
+    fn manifests_round_trip_and_skip_unknown_elements() {
+        let manifest = Manifest {
+            uid: "pkg-1".to_owned(),
+            name: "Route & markers".to_owned(),
+            on_receive_delete: true,
+            contents: vec![
+                ManifestContent {
+                    uid: Some("marker-1".to_owned()),
+                    ..ManifestContent::new("marker-1/marker-1.cot")
+                },
+                ManifestContent::new("notes.txt"),
+            ],
+        };
+        assert_eq!(Manifest::from_xml(&manifest.to_xml()), Ok(manifest));
+
+        let atak = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MissionPackageManifest version="2">
+  <Configuration>
+    <Parameter name="uid" value="a1"/>
+    <Parameter name="name" value="ATAK export"/>
+    <Parameter name="remarks" value=""/>
+  </Configuration>
+  <Contents>
+    <Content ignore="false" zipEntry="b2/b2.cot"><Parameter name="uid" value="b2"/><Extra><Nested/></Extra></Content>
+  </Contents>
+</MissionPackageManifest>"#;
+        let parsed = Manifest::from_xml(atak).expect("ATAK manifest");
+        assert_eq!(parsed.uid, "a1");
+        assert!(!parsed.on_receive_delete);
+        assert_eq!(parsed.contents[0].uid.as_deref(), Some("b2"));
+
+        assert!(Manifest::from_xml("<Manifest/>").is_err());
+        assert!(Manifest::from_xml("").is_err());
+    }
+}
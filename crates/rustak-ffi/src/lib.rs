@@ -1,8 +1,11 @@
-use std::ffi::c_char;
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
 use std::ptr;
 
+use rustak_limits::{CodedError, ErrorCode};
+
 pub const RUSTAK_FFI_ABI_MAJOR: u16 = 1;
-pub const RUSTAK_FFI_ABI_MINOR: u16 = 0;
+pub const RUSTAK_FFI_ABI_MINOR: u16 = 1;
 pub const RUSTAK_FFI_ABI_PATCH: u16 = 0;
 
 #[repr(C)]
@@ -44,6 +47,12 @@ pub enum RustakFfiStatus {
 }
 
 impl RustakFfiStatus {
+    /// Stable code for failures raised by the FFI layer itself rather than
+    /// the codec underneath.
+    const fn error_code(self) -> ErrorCode {
+        ErrorCode::new("FFI", self as u16)
+    }
+
     const fn from_code(code: i32) -> Self {
         match code {
             0 => Self::Ok,
@@ -58,6 +67,59 @@ impl RustakFfiStatus {
     }
 }
 
+struct LastError {
+    code: CString,
+    message: CString,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(code: ErrorCode, message: &str) {
+    let last = LastError {
+        code: CString::new(code.to_string()).unwrap_or_default(),
+        message: CString::new(message.replace('\0', " ")).unwrap_or_default(),
+    };
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = Some(last));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|slot| *slot.borrow_mut() = None);
+}
+
+fn fail(status: RustakFfiStatus) -> RustakFfiStatus {
+    let message = status_message_bytes(status);
+    let message = String::from_utf8_lossy(&message[..message.len() - 1]);
+    set_last_error(status.error_code(), &message);
+    status
+}
+
+fn fail_with(status: RustakFfiStatus, error: &impl CodedError) -> RustakFfiStatus {
+    set_last_error(error.code(), &error.to_string());
+    status
+}
+
+fn last_error_field(field: fn(&LastError) -> *const c_char) -> *const c_char {
+    LAST_ERROR.with(|slot| slot.borrow().as_ref().map_or(ptr::null(), field))
+}
+
+/// Stable error code (for example `RTK-PROTO-0003`) of the last failed call
+/// on this thread, or null if the last call succeeded.
+///
+/// The string stays valid until the next RusTAK FFI call on the same thread.
+#[no_mangle]
+pub extern "C" fn rustak_ffi_last_error_code() -> *const c_char {
+    last_error_field(|last| last.code.as_ptr())
+}
+
+/// Human-readable message for [`rustak_ffi_last_error_code`], or null if the
+/// last call succeeded. Same lifetime rules apply.
+#[no_mangle]
+pub extern "C" fn rustak_ffi_last_error_message() -> *const c_char {
+    last_error_field(|last| last.message.as_ptr())
+}
+
 #[no_mangle]
 pub extern "C" fn rustak_ffi_current_abi_version() -> RustakFfiVersion {
     RustakFfiVersion {
@@ -92,6 +154,13 @@ pub unsafe extern "C" fn rustak_ffi_negotiate_abi_version(
 
 #[no_mangle]
 pub extern "C" fn rustak_ffi_status_message(status_code: i32) -> *const c_char {
+    status_message_bytes(RustakFfiStatus::from_code(status_code))
+        .as_ptr()
+        .cast::<c_char>()
+}
+
+/// NUL-terminated message for `status`.
+fn status_message_bytes(status: RustakFfiStatus) -> &'static [u8] {
     const OK: &[u8] = b"ok\0";
     const NULL_POINTER: &[u8] = b"null pointer\0";
     const INVALID_LENGTH: &[u8] = b"invalid length\0";
@@ -100,7 +169,7 @@ pub extern "C" fn rustak_ffi_status_message(status_code: i32) -> *const c_char {
     const ENCODE_ERROR: &[u8] = b"encode error\0";
     const DECODE_ERROR: &[u8] = b"decode error\0";
 
-    let bytes = match RustakFfiStatus::from_code(status_code) {
+    match status {
         RustakFfiStatus::Ok => OK,
         RustakFfiStatus::NullPointer => NULL_POINTER,
         RustakFfiStatus::InvalidLength => INVALID_LENGTH,
@@ -108,8 +177,7 @@ pub extern "C" fn rustak_ffi_status_message(status_code: i32) -> *const c_char {
         RustakFfiStatus::UnsupportedVersion => UNSUPPORTED_VERSION,
         RustakFfiStatus::EncodeError => ENCODE_ERROR,
         RustakFfiStatus::DecodeError => DECODE_ERROR,
-    };
-    bytes.as_ptr().cast::<c_char>()
+    }
 }
 
 #[no_mangle]
@@ -122,14 +190,15 @@ pub unsafe extern "C" fn rustak_ffi_encode_tak_v1(
     input_len: usize,
     out_buffer: *mut RustakFfiBuffer,
 ) -> RustakFfiStatus {
+    clear_last_error();
     let input = match unsafe { copy_input(input_ptr, input_len) } {
         Ok(input) => input,
-        Err(status) => return status,
+        Err(status) => return fail(status),
     };
 
     let encoded = match rustak_proto::encode_v1_payload(&input) {
         Ok(encoded) => encoded,
-        Err(_) => return RustakFfiStatus::EncodeError,
+        Err(error) => return fail_with(RustakFfiStatus::EncodeError, &error),
     };
 
     unsafe { write_vec_to_out_buffer(encoded, out_buffer) }
@@ -145,14 +214,15 @@ pub unsafe extern "C" fn rustak_ffi_decode_tak_v1(
     input_len: usize,
     out_buffer: *mut RustakFfiBuffer,
 ) -> RustakFfiStatus {
+    clear_last_error();
     let input = match unsafe { copy_input(input_ptr, input_len) } {
         Ok(input) => input,
-        Err(status) => return status,
+        Err(status) => return fail(status),
     };
 
     let decoded = match rustak_proto::decode_v1_payload(&input) {
         Ok(decoded) => decoded,
-        Err(_) => return RustakFfiStatus::DecodeError,
+        Err(error) => return fail_with(RustakFfiStatus::DecodeError, &error),
     };
 
     unsafe { write_vec_to_out_buffer(decoded, out_buffer) }
@@ -211,7 +281,7 @@ unsafe fn write_vec_to_out_buffer(
     out_buffer: *mut RustakFfiBuffer,
 ) -> RustakFfiStatus {
     if out_buffer.is_null() {
-        return RustakFfiStatus::NullPointer;
+        return fail(RustakFfiStatus::NullPointer);
     }
 
     let output = RustakFfiBuffer {
@@ -227,10 +297,13 @@ unsafe fn write_vec_to_out_buffer(
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::CStr;
+
     use super::{
         rustak_ffi_buffer_free, rustak_ffi_current_abi_version, rustak_ffi_decode_tak_v1,
-        rustak_ffi_encode_tak_v1, rustak_ffi_negotiate_abi_version, RustakFfiBuffer,
-        RustakFfiStatus, RustakFfiVersion, RUSTAK_FFI_ABI_MAJOR,
+        rustak_ffi_encode_tak_v1, rustak_ffi_last_error_code, rustak_ffi_last_error_message,
+        rustak_ffi_negotiate_abi_version, RustakFfiBuffer, RustakFfiStatus, RustakFfiVersion,
+        RUSTAK_FFI_ABI_MAJOR,
     };
 
     #[test]
@@ -259,9 +332,19 @@ mod tests {
         assert_eq!(negotiated, rustak_ffi_current_abi_version());
     }
 
+    fn cot_event(uid: &str) -> Vec<u8> {
+        format!(
+            "<event version=\"2.0\" uid=\"{uid}\" type=\"a-f-G\" \
+             time=\"2024-01-01T00:00:00.000Z\" start=\"2024-01-01T00:00:00.000Z\" \
+             stale=\"2024-01-01T00:01:00.000Z\"><point lat=\"1\" lon=\"2\" \
+             hae=\"9999999.0\" ce=\"9999999.0\" le=\"9999999.0\"/></event>"
+        )
+        .into_bytes()
+    }
+
     #[test]
     fn encode_decode_round_trip_respects_buffer_ownership_contract() {
-        let source = b"<event uid=\"ffi-roundtrip\"/>".to_vec();
+        let source = cot_event("ffi-roundtrip");
         let mut encoded = RustakFfiBuffer::default();
         let mut decoded = RustakFfiBuffer::default();
 
@@ -290,10 +373,55 @@ mod tests {
 
     #[test]
     fn null_output_buffer_is_rejected() {
-        let source = b"<event uid=\"ffi-null\"/>".to_vec();
+        let source = cot_event("ffi-null");
         let status = unsafe {
             rustak_ffi_encode_tak_v1(source.as_ptr(), source.len(), std::ptr::null_mut())
         };
         assert_eq!(status, RustakFfiStatus::NullPointer);
     }
+
+    fn last_error() -> Option<(String, String)> {
+        let code = rustak_ffi_last_error_code();
+        if code.is_null() {
+            return None;
+        }
+        let read = |text| {
+            unsafe { CStr::from_ptr(text) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        Some((read(code), read(rustak_ffi_last_error_message())))
+    }
+
+    #[test]
+    fn failures_expose_stable_last_error_codes() {
+        let garbage = [0xff_u8; 4];
+        let mut decoded = RustakFfiBuffer::default();
+        let status =
+            unsafe { rustak_ffi_decode_tak_v1(garbage.as_ptr(), garbage.len(), &mut decoded) };
+        assert_eq!(status, RustakFfiStatus::DecodeError);
+        let (code, message) = last_error().expect("decode failure is recorded");
+        assert!(code.starts_with("RTK-PROTO-"), "{code}");
+        assert!(!message.is_empty());
+
+        let source = cot_event("ffi-last-error");
+        let status = unsafe {
+            rustak_ffi_encode_tak_v1(source.as_ptr(), source.len(), std::ptr::null_mut())
+        };
+        assert_eq!(status, RustakFfiStatus::NullPointer);
+        assert_eq!(
+            last_error(),
+            Some(("RTK-FFI-0001".to_owned(), "null pointer".to_owned()))
+        );
+
+        let mut encoded = RustakFfiBuffer::default();
+        let status =
+            unsafe { rustak_ffi_encode_tak_v1(source.as_ptr(), source.len(), &mut encoded) };
+        assert_eq!(status, RustakFfiStatus::Ok);
+        assert_eq!(last_error(), None, "success clears the last error");
+        assert_eq!(
+            unsafe { rustak_ffi_buffer_free(&mut encoded) },
+            RustakFfiStatus::Ok
+        );
+    }
 }
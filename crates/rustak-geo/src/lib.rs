@@ -1,4 +1,5 @@
 use rustak_core::Position;
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 pub const WGS84_AUTHALIC_RADIUS_METERS: f64 = 6_371_008.8;
@@ -14,6 +15,15 @@ pub enum GeoError {
     Core(#[from] rustak_core::CoreError),
 }
 
+impl CodedError for GeoError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidFraction { .. } => ErrorCode::new("GEO", 1),
+            Self::Core(error) => error.code(),
+        }
+    }
+}
+
 pub fn haversine_distance_meters(from: &Position, to: &Position) -> f64 {
     let lat1 = degrees_to_radians(from.latitude());
     let lon1 = degrees_to_radians(from.longitude());
@@ -44,6 +54,32 @@ pub fn initial_bearing_degrees(from: &Position, to: &Position) -> f64 {
     normalize_bearing_degrees(bearing)
 }
 
+/// Point reached by travelling `distance_meters` from `from` along the great
+/// circle with initial bearing `bearing_degrees`. Altitude and accuracy are
+/// not carried over.
+pub fn destination_point(
+    from: &Position,
+    bearing_degrees: f64,
+    distance_meters: f64,
+) -> Result<Position, GeoError> {
+    let lat1 = degrees_to_radians(from.latitude());
+    let lon1 = degrees_to_radians(from.longitude());
+    let bearing = degrees_to_radians(bearing_degrees);
+    let angular = distance_meters / WGS84_AUTHALIC_RADIUS_METERS;
+
+    let lat2 = (lat1.sin() * angular.cos() + lat1.cos() * angular.sin() * bearing.cos())
+        .clamp(-1.0, 1.0)
+        .asin();
+    let lon2 = lon1
+        + (bearing.sin() * angular.sin() * lat1.cos())
+            .atan2(angular.cos() - lat1.sin() * lat2.sin());
+
+    Ok(Position::new(
+        lat2.to_degrees(),
+        normalize_longitude_degrees(lon2.to_degrees()),
+    )?)
+}
+
 pub fn interpolate_great_circle(
     from: &Position,
     to: &Position,
@@ -172,7 +208,8 @@ mod tests {
     use rustak_core::Position;
 
     use crate::{
-        haversine_distance_meters, initial_bearing_degrees, interpolate_great_circle, GeoError,
+        destination_point, haversine_distance_meters, initial_bearing_degrees,
+        interpolate_great_circle, GeoError,
     };
 
     fn approx_equal(left: f64, right: f64, tolerance: f64) {
@@ -209,6 +246,19 @@ mod tests {
         approx_equal(bearing, 136.5, 1.0);
     }
 
+    #[test]
+    fn destination_point_round_trips_distance_and_bearing() {
+        let origin = Position::new(51.5, -0.1).expect("origin");
+        let destination = destination_point(&origin, 45.0, 10_000.0).expect("destination");
+
+        approx_equal(
+            haversine_distance_meters(&origin, &destination),
+            10_000.0,
+            1e-6,
+        );
+        approx_equal(initial_bearing_degrees(&origin, &destination), 45.0, 1e-6);
+    }
+
     #[test]
     fn interpolation_returns_expected_equatorial_midpoint() {
         let left = Position::new(0.0, 0.0).expect("point should validate");
@@ -1,5 +1,6 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::hash::Hash;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
@@ -487,6 +488,16 @@ impl<S> MetricsLayer<S> {
         }
     }
 
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
     pub fn record_drop(&self) {
         self.dropped.fetch_add(1, Ordering::Relaxed);
     }
@@ -541,6 +552,438 @@ where
     }
 }
 
+type FanOutTransform<T> =
+    Box<dyn Fn(&MessageEnvelope<T>) -> Option<MessageEnvelope<T>> + Send + Sync>;
+
+struct FanOutDestination<T> {
+    name: String,
+    sink: Box<dyn MessageSink<T>>,
+    transform: Option<FanOutTransform<T>>,
+}
+
+/// Delivers each envelope to every named destination.
+///
+/// Destinations registered with a transform receive its output, or nothing
+/// when it returns `None`; the rest get an unmodified clone. Transforms only
+/// see a shared reference, so no destination can alter what the others
+/// receive. Every destination is attempted even after a failure, and the
+/// first error is returned.
+pub struct FanOutLayer<T> {
+    destinations: Vec<FanOutDestination<T>>,
+}
+
+impl<T> Default for FanOutLayer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FanOutLayer<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            destinations: Vec::new(),
+        }
+    }
+
+    pub fn with_destination(
+        self,
+        name: impl Into<String>,
+        sink: Box<dyn MessageSink<T>>,
+    ) -> Result<Self, IoError> {
+        self.push_destination(name.into(), sink, None)
+    }
+
+    pub fn with_transformed_destination<F>(
+        self,
+        name: impl Into<String>,
+        sink: Box<dyn MessageSink<T>>,
+        transform: F,
+    ) -> Result<Self, IoError>
+    where
+        F: Fn(&MessageEnvelope<T>) -> Option<MessageEnvelope<T>> + Send + Sync + 'static,
+    {
+        self.push_destination(name.into(), sink, Some(Box::new(transform)))
+    }
+
+    /// Destination names in registration (and delivery) order.
+    pub fn destination_names(&self) -> impl Iterator<Item = &str> {
+        self.destinations
+            .iter()
+            .map(|destination| destination.name.as_str())
+    }
+
+    fn push_destination(
+        mut self,
+        name: String,
+        sink: Box<dyn MessageSink<T>>,
+        transform: Option<FanOutTransform<T>>,
+    ) -> Result<Self, IoError> {
+        if name.trim().is_empty() {
+            return Err(IoError::Other(
+                "fan-out destination name must not be empty".to_string(),
+            ));
+        }
+        if self.destination_names().any(|existing| existing == name) {
+            return Err(IoError::Other(format!(
+                "duplicate fan-out destination `{name}`"
+            )));
+        }
+        self.destinations.push(FanOutDestination {
+            name,
+            sink,
+            transform,
+        });
+        Ok(self)
+    }
+}
+
+impl<T> MessageSink<T> for FanOutLayer<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn send(&self, msg: T) -> BoxFuture<'_, Result<(), IoError>> {
+        self.send_envelope(MessageEnvelope::new(msg))
+    }
+
+    fn send_envelope(&self, env: MessageEnvelope<T>) -> BoxFuture<'_, Result<(), IoError>> {
+        Box::pin(async move {
+            let mut first_error = None;
+            for destination in &self.destinations {
+                let outbound = match &destination.transform {
+                    Some(transform) => match transform(&env) {
+                        Some(transformed) => transformed,
+                        None => continue,
+                    },
+                    None => env.clone(),
+                };
+                if let Err(error) = destination.sink.send_envelope(outbound).await {
+                    first_error.get_or_insert(error);
+                }
+            }
+            first_error.map_or(Ok(()), Err)
+        })
+    }
+}
+
+/// What a [`PeerRateLimiter`] does when a new peer arrives and the table is
+/// already at `max_peers` after idle peers have been forgotten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PeerEviction {
+    /// Forget the peer heard from least recently to admit the new one. A
+    /// flood of spoofed source addresses can churn out real peers.
+    #[default]
+    LeastRecentlySeen,
+    /// Keep the known peers and drop messages from unknown ones.
+    RejectNewPeers,
+}
+
+/// What a [`PeerRateLimiter`] counts as one peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PeerRateKey {
+    /// The source IP, so a sender cannot dodge its bucket by rotating
+    /// source ports. IPv4-mapped IPv6 addresses count as the IPv4 address.
+    #[default]
+    Ip,
+    /// The source IP and port, for peers that share an address, such as
+    /// several clients behind one NAT.
+    IpAndPort,
+}
+
+/// Token bucket per source: each peer may burst `rate.max_events`
+/// messages, refilled at `rate.max_events` per `rate.per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerRateLimitConfig {
+    pub rate: RateLimitConfig,
+    pub max_peers: usize,
+    /// Peers silent this long are forgotten when the table fills.
+    pub idle_timeout: Duration,
+    pub eviction: PeerEviction,
+    pub key: PeerRateKey,
+}
+
+impl PeerRateLimitConfig {
+    pub fn validate(&self) -> Result<(), IoError> {
+        self.rate.validate()?;
+        if self.max_peers == 0 {
+            return Err(IoError::Other(
+                "peer rate-limit max_peers must be greater than zero".to_string(),
+            ));
+        }
+        if self.idle_timeout.is_zero() {
+            return Err(IoError::Other(
+                "peer rate-limit idle_timeout must be greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerRateDecision {
+    Allow,
+    /// The peer's bucket is empty.
+    DropRateExceeded,
+    /// The peer is unknown and [`PeerEviction::RejectNewPeers`] keeps the
+    /// full table as it is.
+    DropTableFull,
+}
+
+impl PeerRateDecision {
+    #[must_use]
+    pub fn is_allowed(self) -> bool {
+        self == Self::Allow
+    }
+}
+
+/// Counters for a [`PeerRateLimiter`]. Envelopes without a peer address are
+/// always allowed and counted as `unkeyed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PeerRateLimitStats {
+    pub allowed: u64,
+    pub dropped_rate_exceeded: u64,
+    pub dropped_table_full: u64,
+    pub unkeyed: u64,
+    pub evicted_idle: u64,
+    pub evicted_for_new_peer: u64,
+    pub tracked_peers: usize,
+}
+
+/// Counters for one tracked peer, reset when it is evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PeerRateCounters {
+    pub allowed: u64,
+    pub dropped: u64,
+}
+
+struct PeerBucket {
+    tokens: f64,
+    refilled_at: Instant,
+    counters: PeerRateCounters,
+}
+
+struct PeerRateState {
+    peers: HashMap<SocketAddr, PeerBucket>,
+    /// Every tracked peer by when it was last heard from, oldest first.
+    recency: BTreeSet<(Instant, SocketAddr)>,
+    stats: PeerRateLimitStats,
+}
+
+/// Inbound flood protection keyed by source, as [`PeerRateKey`] says, for
+/// listeners that serve many peers such as mesh UDP sockets.
+///
+/// [`Self::check`] charges one message to a peer and says whether to keep
+/// it; [`PeerRateLimitLayer`] applies the same decision in front of a sink.
+/// Peers are also ordered by when they were last heard from, so forgetting
+/// idle or least recent ones costs `O(log n)` each.
+pub struct PeerRateLimiter<C = SystemClock> {
+    config: PeerRateLimitConfig,
+    clock: C,
+    state: Mutex<PeerRateState>,
+}
+
+impl PeerRateLimiter<SystemClock> {
+    pub fn new(config: PeerRateLimitConfig) -> Result<Self, IoError> {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C> PeerRateLimiter<C>
+where
+    C: Clock,
+{
+    pub fn with_clock(config: PeerRateLimitConfig, clock: C) -> Result<Self, IoError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            clock,
+            state: Mutex::new(PeerRateState {
+                peers: HashMap::new(),
+                recency: BTreeSet::new(),
+                stats: PeerRateLimitStats::default(),
+            }),
+        })
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &PeerRateLimitConfig {
+        &self.config
+    }
+
+    /// Charges one message from `peer` against its bucket.
+    pub fn check(&self, peer: SocketAddr) -> PeerRateDecision {
+        let peer = self.key(peer);
+        let now = self.clock.now();
+        let mut state = self.state.lock().expect("peer rate-limit mutex poisoned");
+        if !state.peers.contains_key(&peer) && !self.admit(&mut state, now) {
+            state.stats.dropped_table_full += 1;
+            return PeerRateDecision::DropTableFull;
+        }
+
+        let capacity = self.config.rate.max_events as f64;
+        let refill_per_sec = capacity / self.config.rate.per.as_secs_f64();
+        let PeerRateState { peers, recency, .. } = &mut *state;
+        let bucket = peers.entry(peer).or_insert(PeerBucket {
+            tokens: capacity,
+            refilled_at: now,
+            counters: PeerRateCounters::default(),
+        });
+        recency.remove(&(bucket.refilled_at, peer));
+        recency.insert((now, peer));
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * refill_per_sec).min(capacity);
+        bucket.refilled_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+            bucket.counters.allowed += 1;
+        } else {
+            bucket.counters.dropped += 1;
+        }
+        state.stats.tracked_peers = state.peers.len();
+        if allowed {
+            state.stats.allowed += 1;
+            PeerRateDecision::Allow
+        } else {
+            state.stats.dropped_rate_exceeded += 1;
+            PeerRateDecision::DropRateExceeded
+        }
+    }
+
+    /// Like [`Self::check`], keyed by the envelope's peer; envelopes without
+    /// one are allowed.
+    pub fn check_envelope<T>(&self, envelope: &MessageEnvelope<T>) -> PeerRateDecision {
+        match envelope.peer {
+            Some(peer) => self.check(peer),
+            None => {
+                self.state
+                    .lock()
+                    .expect("peer rate-limit mutex poisoned")
+                    .stats
+                    .unkeyed += 1;
+                PeerRateDecision::Allow
+            }
+        }
+    }
+
+    /// Makes room for a new peer, returning `false` when the eviction
+    /// policy keeps the table full.
+    fn admit(&self, state: &mut PeerRateState, now: Instant) -> bool {
+        if state.peers.len() < self.config.max_peers {
+            return true;
+        }
+
+        while let Some(&(seen, peer)) = state.recency.first() {
+            if now.saturating_duration_since(seen) < self.config.idle_timeout {
+                break;
+            }
+            state.recency.pop_first();
+            state.peers.remove(&peer);
+            state.stats.evicted_idle += 1;
+        }
+        if state.peers.len() < self.config.max_peers {
+            return true;
+        }
+
+        match self.config.eviction {
+            PeerEviction::RejectNewPeers => false,
+            PeerEviction::LeastRecentlySeen => {
+                if let Some((_, oldest)) = state.recency.pop_first() {
+                    state.peers.remove(&oldest);
+                    state.stats.evicted_for_new_peer += 1;
+                }
+                true
+            }
+        }
+    }
+
+    /// The table key for `peer` under [`PeerRateLimitConfig::key`].
+    fn key(&self, peer: SocketAddr) -> SocketAddr {
+        match self.config.key {
+            PeerRateKey::Ip => SocketAddr::new(peer.ip().to_canonical(), 0),
+            PeerRateKey::IpAndPort => peer,
+        }
+    }
+
+    #[must_use]
+    pub fn peer_counters(&self, peer: SocketAddr) -> Option<PeerRateCounters> {
+        let peer = self.key(peer);
+        let state = self.state.lock().expect("peer rate-limit mutex poisoned");
+        state.peers.get(&peer).map(|bucket| bucket.counters)
+    }
+
+    /// Tracked peers with drops, most dropped first. Keyed by
+    /// [`PeerRateKey::Ip`], each address has port 0.
+    #[must_use]
+    pub fn top_offenders(&self, limit: usize) -> Vec<(SocketAddr, PeerRateCounters)> {
+        let state = self.state.lock().expect("peer rate-limit mutex poisoned");
+        let mut offenders: Vec<_> = state
+            .peers
+            .iter()
+            .filter(|(_, bucket)| bucket.counters.dropped > 0)
+            .map(|(peer, bucket)| (*peer, bucket.counters))
+            .collect();
+        offenders.sort_by(|(left_peer, left), (right_peer, right)| {
+            right
+                .dropped
+                .cmp(&left.dropped)
+                .then_with(|| left_peer.cmp(right_peer))
+        });
+        offenders.truncate(limit);
+        offenders
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> PeerRateLimitStats {
+        self.state
+            .lock()
+            .expect("peer rate-limit mutex poisoned")
+            .stats
+    }
+}
+
+/// Forwards envelopes whose peer is within its rate, dropping the rest as
+/// success like [`DedupLayer`]. Bare `send` calls carry no peer and pass.
+pub struct PeerRateLimitLayer<S, C = SystemClock> {
+    inner: S,
+    limiter: PeerRateLimiter<C>,
+}
+
+impl<S, C> PeerRateLimitLayer<S, C> {
+    #[must_use]
+    pub fn new(inner: S, limiter: PeerRateLimiter<C>) -> Self {
+        Self { inner, limiter }
+    }
+
+    #[must_use]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    #[must_use]
+    pub fn limiter(&self) -> &PeerRateLimiter<C> {
+        &self.limiter
+    }
+}
+
+impl<S, C, T> MessageSink<T> for PeerRateLimitLayer<S, C>
+where
+    S: MessageSink<T>,
+    C: Clock,
+{
+    fn send(&self, msg: T) -> BoxFuture<'_, Result<(), IoError>> {
+        self.inner.send(msg)
+    }
+
+    fn send_envelope(&self, env: MessageEnvelope<T>) -> BoxFuture<'_, Result<(), IoError>> {
+        if !self.limiter.check_envelope(&env).is_allowed() {
+            return Box::pin(async { Ok(()) });
+        }
+        self.inner.send_envelope(env)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
@@ -550,8 +993,9 @@ mod tests {
 
     use super::{
         Clock, CoalesceAction, CoalesceConfig, CoalesceLatestLayer, DedupConfig, DedupLayer,
-        ImpairmentConfig, ImpairmentLayer, ImpairmentOutcome, MetricsLayer, RateLimitConfig,
-        RateLimitLayer, TapLayer,
+        FanOutLayer, ImpairmentConfig, ImpairmentLayer, ImpairmentOutcome, MetricsLayer,
+        PeerEviction, PeerRateCounters, PeerRateDecision, PeerRateKey, PeerRateLimitConfig,
+        PeerRateLimitLayer, PeerRateLimiter, RateLimitConfig, RateLimitLayer, TapLayer,
     };
     use crate::{IoError, MessageEnvelope, MessageSink, ObservedTime};
 
@@ -784,4 +1228,235 @@ mod tests {
             } => (2, *delay, *reordered),
         }
     }
+
+    struct SharedSink<T>(Arc<CollectSink<T>>);
+
+    impl<T: Send + 'static> MessageSink<T> for SharedSink<T> {
+        fn send(&self, msg: T) -> futures::future::BoxFuture<'_, Result<(), IoError>> {
+            self.0.send(msg)
+        }
+
+        fn send_envelope(
+            &self,
+            env: MessageEnvelope<T>,
+        ) -> futures::future::BoxFuture<'_, Result<(), IoError>> {
+            self.0.send_envelope(env)
+        }
+    }
+
+    #[test]
+    fn fan_out_layer_transforms_per_destination_without_touching_others() {
+        let internal = Arc::new(CollectSink::<String>::default());
+        let partner = Arc::new(CollectSink::<String>::default());
+        let failing = Arc::new(CollectSink::<String>::failing());
+        let layer = FanOutLayer::new()
+            .with_destination("failing", Box::new(SharedSink(Arc::clone(&failing))))
+            .expect("destination")
+            .with_destination("internal", Box::new(SharedSink(Arc::clone(&internal))))
+            .expect("destination")
+            .with_transformed_destination(
+                "partner",
+                Box::new(SharedSink(Arc::clone(&partner))),
+                |env| {
+                    (env.message != "secret").then(|| env.clone().map_message(|m| m.to_uppercase()))
+                },
+            )
+            .expect("destination");
+        assert!(FanOutLayer::<String>::new()
+            .with_destination("a", Box::new(CollectSink::default()))
+            .and_then(|layer| layer.with_destination("a", Box::new(CollectSink::default())))
+            .is_err());
+
+        for message in ["alpha", "secret"] {
+            let error = block_on(layer.send(message.to_owned()))
+                .expect_err("failing destination error should surface");
+            assert!(matches!(error, IoError::Other(_)));
+        }
+
+        let messages = |sink: &CollectSink<String>| {
+            sink.sent
+                .lock()
+                .expect("collect mutex poisoned")
+                .iter()
+                .map(|env| env.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(messages(&internal), ["alpha", "secret"]);
+        assert_eq!(messages(&partner), ["ALPHA"]);
+        assert_eq!(
+            layer.destination_names().collect::<Vec<_>>(),
+            ["failing", "internal", "partner"]
+        );
+    }
+
+    fn peer_config(max_peers: usize, eviction: PeerEviction) -> PeerRateLimitConfig {
+        PeerRateLimitConfig {
+            rate: RateLimitConfig {
+                max_events: 2,
+                per: Duration::from_secs(1),
+            },
+            max_peers,
+            idle_timeout: Duration::from_secs(30),
+            eviction,
+            key: PeerRateKey::Ip,
+        }
+    }
+
+    fn addr(host: u8) -> std::net::SocketAddr {
+        std::net::SocketAddr::from(([192, 0, 2, host], 4242))
+    }
+
+    #[test]
+    fn peer_rate_limiter_buckets_each_peer_and_refills_over_time() {
+        let clock = TestClock::new(Instant::now());
+        let limiter = PeerRateLimiter::with_clock(
+            peer_config(8, PeerEviction::LeastRecentlySeen),
+            clock.clone(),
+        )
+        .expect("peer rate-limit config should be valid");
+
+        let flooder = addr(1);
+        let decisions: Vec<_> = (0..4).map(|_| limiter.check(flooder)).collect();
+        assert_eq!(
+            decisions,
+            [
+                PeerRateDecision::Allow,
+                PeerRateDecision::Allow,
+                PeerRateDecision::DropRateExceeded,
+                PeerRateDecision::DropRateExceeded,
+            ]
+        );
+        assert!(limiter.check(addr(2)).is_allowed(), "peers are independent");
+
+        clock.advance(Duration::from_millis(500));
+        assert!(
+            limiter.check(flooder).is_allowed(),
+            "half a period refills one"
+        );
+        assert!(!limiter.check(flooder).is_allowed());
+
+        assert_eq!(
+            limiter.peer_counters(flooder),
+            Some(PeerRateCounters {
+                allowed: 3,
+                dropped: 3,
+            })
+        );
+        assert_eq!(
+            limiter.top_offenders(4),
+            [(
+                std::net::SocketAddr::from(([192, 0, 2, 1], 0)),
+                PeerRateCounters {
+                    allowed: 3,
+                    dropped: 3,
+                }
+            )]
+        );
+        let stats = limiter.stats();
+        assert_eq!(stats.allowed, 4);
+        assert_eq!(stats.dropped_rate_exceeded, 3);
+        assert_eq!(stats.tracked_peers, 2);
+    }
+
+    #[test]
+    fn peer_rate_limiter_eviction_policies_bound_the_peer_table() {
+        let clock = TestClock::new(Instant::now());
+        let lru = PeerRateLimiter::with_clock(
+            peer_config(2, PeerEviction::LeastRecentlySeen),
+            clock.clone(),
+        )
+        .expect("config");
+        let reject = PeerRateLimiter::with_clock(
+            peer_config(2, PeerEviction::RejectNewPeers),
+            clock.clone(),
+        )
+        .expect("config");
+        for limiter in [&lru, &reject] {
+            limiter.check(addr(1));
+            clock.advance(Duration::from_secs(1));
+            limiter.check(addr(2));
+        }
+
+        assert!(lru.check(addr(3)).is_allowed());
+        assert_eq!(
+            lru.peer_counters(addr(1)),
+            None,
+            "least recent peer evicted"
+        );
+        assert_eq!(lru.stats().evicted_for_new_peer, 1);
+
+        assert_eq!(reject.check(addr(3)), PeerRateDecision::DropTableFull);
+        assert!(reject.peer_counters(addr(1)).is_some());
+        assert_eq!(reject.stats().dropped_table_full, 1);
+
+        // Once the known peers go quiet they age out and newcomers get in.
+        clock.advance(Duration::from_secs(30));
+        assert!(reject.check(addr(3)).is_allowed());
+        let stats = reject.stats();
+        assert_eq!(stats.evicted_idle, 2);
+        assert_eq!(stats.tracked_peers, 1);
+    }
+
+    #[test]
+    fn peer_rate_limiter_keys_by_ip_unless_configured_per_port() {
+        let clock = TestClock::new(Instant::now());
+        let by_ip = PeerRateLimiter::with_clock(
+            peer_config(8, PeerEviction::LeastRecentlySeen),
+            clock.clone(),
+        )
+        .expect("config");
+        let by_port = PeerRateLimiter::with_clock(
+            PeerRateLimitConfig {
+                key: PeerRateKey::IpAndPort,
+                ..peer_config(8, PeerEviction::LeastRecentlySeen)
+            },
+            clock,
+        )
+        .expect("config");
+        let rotating = |port| std::net::SocketAddr::from(([198, 51, 100, 7], port));
+
+        let decisions: Vec<_> = (1..=3).map(|port| by_ip.check(rotating(port))).collect();
+        assert_eq!(
+            decisions,
+            [
+                PeerRateDecision::Allow,
+                PeerRateDecision::Allow,
+                PeerRateDecision::DropRateExceeded,
+            ],
+            "rotating source ports share one bucket"
+        );
+        let mapped: std::net::SocketAddr = "[::ffff:198.51.100.7]:9".parse().expect("addr");
+        assert!(!by_ip.check(mapped).is_allowed());
+        assert_eq!(by_ip.stats().tracked_peers, 1);
+
+        assert!((1..=3).all(|port| by_port.check(rotating(port)).is_allowed()));
+        assert_eq!(by_port.stats().tracked_peers, 3);
+    }
+
+    #[test]
+    fn peer_rate_limit_layer_drops_flooding_envelopes_as_success() {
+        let clock = TestClock::new(Instant::now());
+        let limiter =
+            PeerRateLimiter::with_clock(peer_config(8, PeerEviction::LeastRecentlySeen), clock)
+                .expect("config");
+        let layer = PeerRateLimitLayer::new(CollectSink::<String>::default(), limiter);
+
+        for index in 0..3 {
+            let env = MessageEnvelope::new(format!("flood-{index}")).with_peer(addr(1));
+            block_on(layer.send_envelope(env)).expect("drops are not errors");
+        }
+        block_on(layer.send_envelope(MessageEnvelope::new("local".to_string())))
+            .expect("unkeyed envelopes pass");
+
+        let sent = layer.inner().sent.lock().expect("collect mutex poisoned");
+        let messages: Vec<&str> = sent.iter().map(|env| env.message.as_str()).collect();
+        assert_eq!(messages, ["flood-0", "flood-1", "local"]);
+        assert_eq!(layer.limiter().stats().unkeyed, 1);
+        assert!(PeerRateLimitConfig {
+            max_peers: 0,
+            ..peer_config(1, PeerEviction::RejectNewPeers)
+        }
+        .validate()
+        .is_err());
+    }
 }
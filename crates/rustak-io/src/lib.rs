@@ -5,9 +5,13 @@ use std::time::{Duration, Instant, SystemTime};
 use bytes::Bytes;
 use futures::future::BoxFuture;
 use futures::Stream;
+use rustak_core::TimeSource;
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 pub mod layers;
+#[cfg(feature = "tower")]
+pub mod tower;
 
 #[derive(Debug, Error)]
 pub enum IoError {
@@ -27,6 +31,18 @@ pub enum IoError {
     Other(String),
 }
 
+impl CodedError for IoError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Closed => ErrorCode::new("IO", 1),
+            Self::Timeout(_) => ErrorCode::new("IO", 2),
+            Self::Overloaded => ErrorCode::new("IO", 3),
+            Self::Io(_) => ErrorCode::new("IO", 4),
+            Self::Other(_) => ErrorCode::new("IO", 5),
+        }
+    }
+}
+
 /// Wall and monotonic timestamps for replay fidelity and audit correlation.
 #[derive(Debug, Clone)]
 pub struct ObservedTime {
@@ -47,6 +63,15 @@ impl ObservedTime {
     pub fn new(wall: SystemTime, monotonic: Instant) -> Self {
         Self { wall, monotonic }
     }
+
+    /// Stamps the wall time from `source` instead of the system clock.
+    #[must_use]
+    pub fn from_time_source(source: &dyn TimeSource) -> Self {
+        Self {
+            wall: source.now_system(),
+            monotonic: Instant::now(),
+        }
+    }
 }
 
 impl Default for ObservedTime {
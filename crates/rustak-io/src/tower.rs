@@ -0,0 +1,295 @@
+//! Adapters between [`MessageSink`] and `tower` services.
+//!
+//! [`SinkService`] exposes any sink (including a stack of rustak layers) as a
+//! `Service<MessageEnvelope<T>>`, [`ServiceSink`] goes the other way, and
+//! [`SinkLayer`] lets rustak layers sit inside a tower `ServiceBuilder` stack.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::{poll_fn, BoxFuture};
+use futures::lock::Mutex;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{IoError, MessageEnvelope, MessageSink};
+
+/// Error type tower middleware conventionally converts into.
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Serves envelopes by forwarding them to a shared [`MessageSink`].
+pub struct SinkService<S> {
+    sink: Arc<S>,
+}
+
+impl<S> SinkService<S> {
+    #[must_use]
+    pub fn new(sink: S) -> Self {
+        Self::from_arc(Arc::new(sink))
+    }
+
+    #[must_use]
+    pub fn from_arc(sink: Arc<S>) -> Self {
+        Self { sink }
+    }
+
+    #[must_use]
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+}
+
+impl<S> Clone for SinkService<S> {
+    fn clone(&self) -> Self {
+        Self {
+            sink: Arc::clone(&self.sink),
+        }
+    }
+}
+
+impl<S> fmt::Debug for SinkService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SinkService").finish_non_exhaustive()
+    }
+}
+
+impl<S, T> Service<MessageEnvelope<T>> for SinkService<S>
+where
+    S: MessageSink<T> + 'static,
+    T: Send + 'static,
+{
+    type Response = ();
+    type Error = IoError;
+    type Future = BoxFuture<'static, Result<(), IoError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Sinks apply their own backpressure inside `send_envelope`.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, envelope: MessageEnvelope<T>) -> Self::Future {
+        let sink = Arc::clone(&self.sink);
+        Box::pin(async move { sink.send_envelope(envelope).await })
+    }
+}
+
+/// Drives a tower service as a [`MessageSink`].
+///
+/// Calls are serialised: each send waits for `poll_ready` before calling the
+/// service, and the service response is discarded. Service errors that are
+/// not already [`IoError`]s surface as [`IoError::Other`].
+pub struct ServiceSink<Svc> {
+    service: Mutex<Svc>,
+}
+
+impl<Svc> ServiceSink<Svc> {
+    #[must_use]
+    pub fn new(service: Svc) -> Self {
+        Self {
+            service: Mutex::new(service),
+        }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> Svc {
+        self.service.into_inner()
+    }
+}
+
+impl<Svc> fmt::Debug for ServiceSink<Svc> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceSink").finish_non_exhaustive()
+    }
+}
+
+impl<Svc, T> MessageSink<T> for ServiceSink<Svc>
+where
+    Svc: Service<MessageEnvelope<T>> + Send,
+    Svc::Future: Send,
+    Svc::Error: Into<BoxError>,
+    T: Send + 'static,
+{
+    fn send(&self, msg: T) -> BoxFuture<'_, Result<(), IoError>> {
+        self.send_envelope(MessageEnvelope::new(msg))
+    }
+
+    fn send_envelope(&self, env: MessageEnvelope<T>) -> BoxFuture<'_, Result<(), IoError>> {
+        Box::pin(async move {
+            let response = {
+                let mut service = self.service.lock().await;
+                poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .map_err(into_io_error)?;
+                service.call(env)
+            };
+            response.await.map(drop).map_err(into_io_error)
+        })
+    }
+}
+
+/// Tower layer that wraps the inner service in rustak sink layers.
+///
+/// `wrap` receives the inner service as a [`ServiceSink`] and returns the
+/// layered sink, which is then served as a [`SinkService`].
+#[derive(Clone)]
+pub struct SinkLayer<F> {
+    wrap: F,
+}
+
+impl<F> SinkLayer<F> {
+    #[must_use]
+    pub fn new(wrap: F) -> Self {
+        Self { wrap }
+    }
+}
+
+impl<F> fmt::Debug for SinkLayer<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SinkLayer").finish_non_exhaustive()
+    }
+}
+
+impl<Svc, F, L> Layer<Svc> for SinkLayer<F>
+where
+    F: Fn(ServiceSink<Svc>) -> L,
+{
+    type Service = SinkService<L>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        SinkService::new((self.wrap)(ServiceSink::new(inner)))
+    }
+}
+
+fn into_io_error(error: impl Into<BoxError>) -> IoError {
+    match error.into().downcast::<IoError>() {
+        Ok(error) => *error,
+        Err(error) => IoError::Other(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+    use futures::future::{self, BoxFuture, Ready};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use super::{ServiceSink, SinkLayer, SinkService};
+    use crate::layers::{DedupConfig, DedupLayer, RateLimitConfig, RateLimitLayer};
+    use crate::{IoError, MessageEnvelope, MessageSink};
+
+    #[derive(Clone, Default)]
+    struct CollectSink {
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MessageSink<String> for CollectSink {
+        fn send(&self, msg: String) -> BoxFuture<'_, Result<(), IoError>> {
+            self.sent.lock().expect("collect mutex").push(msg);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingService {
+        seen: Arc<Mutex<Vec<String>>>,
+        reject: Option<&'static str>,
+    }
+
+    impl Service<MessageEnvelope<String>> for RecordingService {
+        type Response = usize;
+        type Error = std::io::Error;
+        type Future = Ready<Result<usize, std::io::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, envelope: MessageEnvelope<String>) -> Self::Future {
+            if self.reject == Some(envelope.message.as_str()) {
+                return future::ready(Err(std::io::Error::other("rejected")));
+            }
+            let mut seen = self.seen.lock().expect("recording mutex");
+            seen.push(envelope.message);
+            future::ready(Ok(seen.len()))
+        }
+    }
+
+    #[test]
+    fn sink_service_forwards_through_rustak_layers() {
+        let sink = CollectSink::default();
+        let layered = RateLimitLayer::new(
+            sink.clone(),
+            RateLimitConfig {
+                max_events: 2,
+                per: Duration::from_secs(60),
+            },
+        )
+        .expect("rate limit config");
+        let mut service = SinkService::new(layered);
+
+        for message in ["a", "b"] {
+            block_on(service.call(MessageEnvelope::new(message.to_owned()))).expect("forwarded");
+        }
+        let error = block_on(service.call(MessageEnvelope::new("c".to_owned())))
+            .expect_err("rate limit should reject the third envelope");
+
+        assert!(matches!(error, IoError::Overloaded));
+        assert_eq!(*sink.sent.lock().expect("collect mutex"), ["a", "b"]);
+    }
+
+    #[test]
+    fn sink_layer_composes_rustak_layers_over_tower_service() {
+        let inner = RecordingService {
+            reject: Some("bad"),
+            ..RecordingService::default()
+        };
+        let layer = SinkLayer::new(|service| {
+            DedupLayer::new(
+                service,
+                DedupConfig { max_keys: 8 },
+                |env: &MessageEnvelope<String>| env.message.clone(),
+            )
+            .expect("dedup config")
+        });
+        let mut service = layer.layer(inner.clone());
+
+        for message in ["a", "a", "b"] {
+            block_on(service.call(MessageEnvelope::new(message.to_owned()))).expect("forwarded");
+        }
+        let error = block_on(service.call(MessageEnvelope::new("bad".to_owned())))
+            .expect_err("inner service rejection should surface");
+
+        assert!(matches!(error, IoError::Other(message) if message == "rejected"));
+        assert_eq!(*inner.seen.lock().expect("recording mutex"), ["a", "b"]);
+    }
+
+    #[test]
+    fn service_sink_preserves_io_errors() {
+        struct Closed;
+
+        impl Service<MessageEnvelope<String>> for Closed {
+            type Response = ();
+            type Error = IoError;
+            type Future = Ready<Result<(), IoError>>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+                Poll::Ready(Err(IoError::Closed))
+            }
+
+            fn call(&mut self, _envelope: MessageEnvelope<String>) -> Self::Future {
+                future::ready(Ok(()))
+            }
+        }
+
+        let sink = ServiceSink::new(Closed);
+        let error = block_on(sink.send("a".to_owned())).expect_err("closed service");
+        assert!(matches!(error, IoError::Closed));
+    }
+}
@@ -0,0 +1,76 @@
+//! Stable, machine-readable error codes.
+//!
+//! Every public error enum in the workspace implements [`CodedError`]. Codes
+//! read `RTK-<DOMAIN>-<NNNN>`: the domain names the crate (`TRANSPORT`,
+//! `WIRE`, ...) and each enum owns a block of 100 numbers in it, with its
+//! variants numbered in declaration order. A number is never reassigned, so
+//! support can quote a code across releases. New variants take the next
+//! free number in their block. Transparent wrappers around another coded
+//! error report the inner error's code rather than one of their own.
+//!
+//! `docs/error_codes.md` lists the domains and blocks.
+
+use std::fmt;
+
+/// Prefix shared by every rendered code.
+pub const ERROR_CODE_PREFIX: &str = "RTK";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorCode {
+    domain: &'static str,
+    number: u16,
+}
+
+impl ErrorCode {
+    #[must_use]
+    pub const fn new(domain: &'static str, number: u16) -> Self {
+        Self { domain, number }
+    }
+
+    #[must_use]
+    pub const fn domain(self) -> &'static str {
+        self.domain
+    }
+
+    #[must_use]
+    pub const fn number(self) -> u16 {
+        self.number
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{ERROR_CODE_PREFIX}-{}-{:04}", self.domain, self.number)
+    }
+}
+
+/// An error with a stable [`ErrorCode`].
+pub trait CodedError: std::error::Error {
+    fn code(&self) -> ErrorCode;
+
+    /// `RTK-...: message`, the form used in logs and diagnostics.
+    fn coded_message(&self) -> String {
+        format!("{}: {self}", self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodedError, ErrorCode};
+    use crate::LimitsError;
+
+    #[test]
+    fn renders_zero_padded_codes() {
+        assert_eq!(
+            ErrorCode::new("TRANSPORT", 42).to_string(),
+            "RTK-TRANSPORT-0042"
+        );
+        let error = LimitsError::Zero {
+            field: "max_frame_bytes",
+        };
+        assert_eq!(
+            error.coded_message(),
+            "RTK-LIMITS-0001: max_frame_bytes must be greater than zero"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{CodedError, ErrorCode};
+
 /// Validation failures for the shared `Limits` contract.
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum LimitsError {
@@ -37,3 +39,15 @@ pub enum LimitsError {
         max_queue_bytes: usize,
     },
 }
+
+impl CodedError for LimitsError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Zero { .. } => ErrorCode::new("LIMITS", 1),
+            Self::XmlScanExceedsFrame { .. } => ErrorCode::new("LIMITS", 2),
+            Self::ProtobufExceedsFrame { .. } => ErrorCode::new("LIMITS", 3),
+            Self::QueueBytesBelowFrame { .. } => ErrorCode::new("LIMITS", 4),
+            Self::QueueMessagesExceedQueueBytes { .. } => ErrorCode::new("LIMITS", 5),
+        }
+    }
+}
@@ -1,5 +1,7 @@
+mod code;
 mod error;
 
+pub use code::{CodedError, ErrorCode, ERROR_CODE_PREFIX};
 pub use error::LimitsError;
 
 /// Shared, validated resource budgets for TAK/SAPIENT boundaries.
@@ -1,3 +1,4 @@
+use bytes::BytesMut;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::DelimiterFrameError;
@@ -8,6 +9,58 @@ pub async fn read_delimited_frame<R>(
     max_frame_bytes: usize,
     include_delimiter: bool,
 ) -> Result<Vec<u8>, DelimiterFrameError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut frame = BytesMut::new();
+    read_delimited_frame_into(
+        reader,
+        delimiter,
+        max_frame_bytes,
+        include_delimiter,
+        &mut frame,
+    )
+    .await?;
+    Ok(frame.into())
+}
+
+/// Like [`read_delimited_frame`], but appends the frame to `buf` and returns
+/// its length; see [`crate::read_length_prefixed_frame_into`]. On error
+/// `buf` is left as it was.
+pub async fn read_delimited_frame_into<R>(
+    reader: &mut R,
+    delimiter: &[u8],
+    max_frame_bytes: usize,
+    include_delimiter: bool,
+    buf: &mut BytesMut,
+) -> Result<usize, DelimiterFrameError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_scan_limited_frame_into(
+        reader,
+        delimiter,
+        max_frame_bytes,
+        max_frame_bytes,
+        include_delimiter,
+        buf,
+    )
+    .await
+}
+
+/// Like [`read_delimited_frame_into`], but gives up with
+/// [`DelimiterFrameError::XmlScanLimitExceeded`] as soon as
+/// `max_scan_bytes` have arrived without a delimiter, so a peer streaming
+/// unterminated XML costs at most that much buffering. A `max_scan_bytes`
+/// at or above `max_frame_bytes` leaves only the frame bound in force.
+pub async fn read_scan_limited_frame_into<R>(
+    reader: &mut R,
+    delimiter: &[u8],
+    max_frame_bytes: usize,
+    max_scan_bytes: usize,
+    include_delimiter: bool,
+    buf: &mut BytesMut,
+) -> Result<usize, DelimiterFrameError>
 where
     R: AsyncRead + Unpin,
 {
@@ -15,34 +68,69 @@ where
         return Err(DelimiterFrameError::EmptyDelimiter);
     }
 
-    let mut frame = Vec::new();
-    let mut byte = [0_u8; 1];
+    let start = buf.len();
+    let bounds = ScanBounds {
+        max_frame_bytes,
+        max_scan_bytes,
+    };
+    let result = scan_delimited(reader, delimiter, bounds, buf, start).await;
+    match result {
+        Ok(()) => {
+            if !include_delimiter {
+                buf.truncate(buf.len() - delimiter.len());
+            }
+            Ok(buf.len() - start)
+        }
+        Err(error) => {
+            buf.truncate(start);
+            Err(error)
+        }
+    }
+}
 
+#[derive(Clone, Copy)]
+struct ScanBounds {
+    max_frame_bytes: usize,
+    max_scan_bytes: usize,
+}
+
+/// Reads one byte at a time, so nothing past the delimiter is consumed.
+async fn scan_delimited<R>(
+    reader: &mut R,
+    delimiter: &[u8],
+    bounds: ScanBounds,
+    buf: &mut BytesMut,
+    start: usize,
+) -> Result<(), DelimiterFrameError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut byte = [0_u8; 1];
     loop {
         let read = reader
             .read(&mut byte)
             .await
             .map_err(DelimiterFrameError::Io)?;
+        let scanned = buf.len() - start;
         if read == 0 {
-            return Err(DelimiterFrameError::UnexpectedEof {
-                scanned: frame.len(),
-            });
+            return Err(DelimiterFrameError::UnexpectedEof { scanned });
         }
 
-        frame.push(byte[0]);
-        if frame.len() > max_frame_bytes {
+        buf.extend_from_slice(&byte);
+        if scanned + 1 > bounds.max_frame_bytes {
             return Err(DelimiterFrameError::FrameTooLarge {
-                max_frame_bytes,
-                scanned: frame.len(),
+                max_frame_bytes: bounds.max_frame_bytes,
+                scanned: scanned + 1,
             });
         }
-
-        if frame.ends_with(delimiter) {
-            if !include_delimiter {
-                let len_without_delimiter = frame.len() - delimiter.len();
-                frame.truncate(len_without_delimiter);
-            }
-            return Ok(frame);
+        if scanned + 1 > bounds.max_scan_bytes {
+            return Err(DelimiterFrameError::XmlScanLimitExceeded {
+                max_xml_scan_bytes: bounds.max_scan_bytes,
+                scanned: scanned + 1,
+            });
+        }
+        if buf[start..].ends_with(delimiter) {
+            return Ok(());
         }
     }
 }
@@ -90,7 +178,9 @@ where
 mod tests {
     use tokio::io::{duplex, AsyncWriteExt};
 
-    use super::{read_delimited_frame, write_delimited_frame};
+    use bytes::BytesMut;
+
+    use super::{read_delimited_frame, read_scan_limited_frame_into, write_delimited_frame};
     use crate::DelimiterFrameError;
 
     #[tokio::test]
@@ -150,6 +240,37 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn scan_limit_aborts_unterminated_stream_before_frame_limit() {
+        let (mut writer, mut reader) = duplex(64);
+        writer
+            .write_all(b"<ok/>\n<event uid=\"never-ends")
+            .await
+            .expect("write should work");
+
+        let mut buf = BytesMut::new();
+        let len = read_scan_limited_frame_into(&mut reader, b"\n", 64, 8, false, &mut buf)
+            .await
+            .expect("a frame within the scan bound decodes");
+        assert_eq!(&buf[..len], b"<ok/>");
+
+        // The writer stays open: the read must give up on the bound alone.
+        let error = read_scan_limited_frame_into(&mut reader, b"\n", 64, 8, false, &mut buf)
+            .await
+            .expect_err("scan should exceed the XML scan limit");
+        match error {
+            DelimiterFrameError::XmlScanLimitExceeded {
+                max_xml_scan_bytes,
+                scanned,
+            } => {
+                assert_eq!(max_xml_scan_bytes, 8);
+                assert_eq!(scanned, 9);
+            }
+            _ => panic!("unexpected error variant"),
+        }
+        assert_eq!(buf.len(), len, "a failed scan leaves the buffer as it was");
+    }
+
     #[tokio::test]
     async fn write_rejects_frames_that_exceed_limit() {
         let (mut writer, _reader) = duplex(64);
@@ -1,5 +1,6 @@
 use std::io;
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,6 +15,16 @@ pub enum BoundedReadError {
     Io(#[source] io::Error),
 }
 
+impl CodedError for BoundedReadError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::LimitExceeded { .. } => ErrorCode::new("NET", 1),
+            Self::IntegerOverflow => ErrorCode::new("NET", 2),
+            Self::Io(_) => ErrorCode::new("NET", 3),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum LengthPrefixedError {
     #[error("frame length {frame_len} exceeds max frame bound {max_frame_bytes}")]
@@ -38,6 +49,18 @@ pub enum LengthPrefixedError {
     Io(#[source] io::Error),
 }
 
+impl CodedError for LengthPrefixedError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::FrameTooLarge { .. } => ErrorCode::new("NET", 101),
+            Self::PrefixOverflow { .. } => ErrorCode::new("NET", 102),
+            Self::VarintTooLong => ErrorCode::new("NET", 103),
+            Self::VarintOverflow => ErrorCode::new("NET", 104),
+            Self::Io(_) => ErrorCode::new("NET", 105),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum DelimiterFrameError {
     #[error("delimiter cannot be empty")]
@@ -54,4 +77,22 @@ pub enum DelimiterFrameError {
 
     #[error("I/O error: {0}")]
     Io(#[source] io::Error),
+
+    #[error("no delimiter within max XML scan bound {max_xml_scan_bytes} after {scanned} bytes")]
+    XmlScanLimitExceeded {
+        max_xml_scan_bytes: usize,
+        scanned: usize,
+    },
+}
+
+impl CodedError for DelimiterFrameError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyDelimiter => ErrorCode::new("NET", 201),
+            Self::FrameTooLarge { .. } => ErrorCode::new("NET", 202),
+            Self::UnexpectedEof { .. } => ErrorCode::new("NET", 203),
+            Self::Io(_) => ErrorCode::new("NET", 204),
+            Self::XmlScanLimitExceeded { .. } => ErrorCode::new("NET", 205),
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use bytes::BytesMut;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::LengthPrefixedError;
@@ -16,6 +17,24 @@ pub async fn read_length_prefixed_frame<R>(
     prefix: LengthPrefixKind,
     max_frame_bytes: usize,
 ) -> Result<Vec<u8>, LengthPrefixedError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut payload = BytesMut::new();
+    read_length_prefixed_frame_into(reader, prefix, max_frame_bytes, &mut payload).await?;
+    Ok(payload.into())
+}
+
+/// Like [`read_length_prefixed_frame`], but appends the payload to `buf` and
+/// returns its length. `buf.split_to(len).freeze()` hands the frame on as
+/// `Bytes` without copying, and `buf` keeps any spare capacity for the next
+/// frame. On error `buf` is left as it was.
+pub async fn read_length_prefixed_frame_into<R>(
+    reader: &mut R,
+    prefix: LengthPrefixKind,
+    max_frame_bytes: usize,
+    buf: &mut BytesMut,
+) -> Result<usize, LengthPrefixedError>
 where
     R: AsyncRead + Unpin,
 {
@@ -33,12 +52,13 @@ where
         });
     }
 
-    let mut payload = vec![0_u8; frame_len];
-    reader
-        .read_exact(&mut payload)
-        .await
-        .map_err(LengthPrefixedError::Io)?;
-    Ok(payload)
+    let start = buf.len();
+    buf.resize(start + frame_len, 0);
+    if let Err(error) = reader.read_exact(&mut buf[start..]).await {
+        buf.truncate(start);
+        return Err(LengthPrefixedError::Io(error));
+    }
+    Ok(frame_len)
 }
 
 pub async fn write_length_prefixed_frame<W>(
@@ -173,7 +193,12 @@ fn encode_varint_u64(mut value: u64) -> Vec<u8> {
 mod tests {
     use tokio::io::{duplex, AsyncWriteExt};
 
-    use super::{read_length_prefixed_frame, write_length_prefixed_frame, LengthPrefixKind};
+    use bytes::BytesMut;
+
+    use super::{
+        read_length_prefixed_frame, read_length_prefixed_frame_into, write_length_prefixed_frame,
+        LengthPrefixKind,
+    };
     use crate::LengthPrefixedError;
 
     #[tokio::test]
@@ -196,6 +221,33 @@ mod tests {
         assert_eq!(frame, b"hello");
     }
 
+    #[tokio::test]
+    async fn reads_frames_into_a_reused_buffer() {
+        let (mut writer, mut reader) = duplex(64);
+        writer
+            .write_all(b"\x00\x03abc\x00\x02de\x00\x09short")
+            .await
+            .expect("write should work");
+        drop(writer);
+
+        let mut buf = BytesMut::with_capacity(16);
+        let first =
+            read_length_prefixed_frame_into(&mut reader, LengthPrefixKind::U16Be, 16, &mut buf)
+                .await
+                .expect("first frame");
+        let first = buf.split_to(first).freeze();
+        let second =
+            read_length_prefixed_frame_into(&mut reader, LengthPrefixKind::U16Be, 16, &mut buf)
+                .await
+                .expect("second frame");
+        assert_eq!((&first[..], &buf[..second]), (&b"abc"[..], &b"de"[..]));
+
+        read_length_prefixed_frame_into(&mut reader, LengthPrefixKind::U16Be, 16, &mut buf)
+            .await
+            .expect_err("truncated frame");
+        assert_eq!(&buf[..], b"de", "a failed read leaves the buffer as it was");
+    }
+
     #[tokio::test]
     async fn rejects_frame_larger_than_bound() {
         let (mut writer, mut reader) = duplex(64);
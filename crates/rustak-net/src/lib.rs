@@ -4,8 +4,12 @@ mod error;
 mod length_prefixed;
 
 pub use bounded::BoundedReader;
-pub use delimiter::{read_delimited_frame, write_delimited_frame};
+pub use delimiter::{
+    read_delimited_frame, read_delimited_frame_into, read_scan_limited_frame_into,
+    write_delimited_frame,
+};
 pub use error::{BoundedReadError, DelimiterFrameError, LengthPrefixedError};
 pub use length_prefixed::{
-    read_length_prefixed_frame, write_length_prefixed_frame, LengthPrefixKind,
+    read_length_prefixed_frame, read_length_prefixed_frame_into, write_length_prefixed_frame,
+    LengthPrefixKind,
 };
@@ -0,0 +1,352 @@
+//! Conversion between the protobuf [`takmessage::CotEvent`] and the
+//! `rustak-core` [`CotEvent`].
+//!
+//! Detail elements that match a typed sub-schema exactly (known attributes
+//! only, no text or children) travel in the typed field; everything else is
+//! serialized into `xml_detail`. Decoding emits typed elements first, in
+//! schema order, followed by the `xml_detail` elements.
+
+use rustak_core::{CotEvent, DetailNode, Position, TimestampUtc};
+use rustak_limits::Limits;
+
+use crate::takmessage::{self, Contact, Detail, Group, PrecisionLocation, Status, Takv, Track};
+use crate::ProtoError;
+
+/// TAK's placeholder for an unknown `hae`, `ce` or `le`.
+const UNKNOWN_POINT_VALUE: f64 = 9_999_999.0;
+
+const NANOS_PER_MILLI: i128 = 1_000_000;
+
+/// `<event>` attributes with a dedicated protobuf field, in field order.
+const EVENT_STRING_ATTRIBUTES: [&str; 5] = ["access", "qos", "opex", "caveat", "releaseableTo"];
+
+/// Converts to the protobuf event. Times are truncated to milliseconds and
+/// `<event>` attributes without a protobuf field are dropped.
+#[must_use]
+pub fn cot_event_to_proto(event: &CotEvent) -> takmessage::CotEvent {
+    let attribute = |name: &str| {
+        event
+            .other_attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    };
+
+    takmessage::CotEvent {
+        r#type: event.cot_type.clone(),
+        access: attribute("access"),
+        qos: attribute("qos"),
+        opex: attribute("opex"),
+        caveat: attribute("caveat"),
+        releaseable_to: attribute("releaseableTo"),
+        uid: event.uid.clone(),
+        send_time: to_millis(event.time),
+        start_time: to_millis(event.start),
+        stale_time: to_millis(event.stale),
+        how: event.how.clone().unwrap_or_default(),
+        lat: event.point.latitude(),
+        lon: event.point.longitude(),
+        hae: event.point.hae().unwrap_or(UNKNOWN_POINT_VALUE),
+        ce: event.point.ce().unwrap_or(UNKNOWN_POINT_VALUE),
+        le: event.point.le().unwrap_or(UNKNOWN_POINT_VALUE),
+        detail: (!event.detail.is_empty()).then(|| detail_to_proto(&event.detail)),
+    }
+}
+
+/// Converts from the protobuf event, parsing `xml_detail` within `limits`.
+pub fn cot_event_from_proto(
+    event: takmessage::CotEvent,
+    limits: &Limits,
+) -> Result<CotEvent, ProtoError> {
+    let known = |value: f64| (value != UNKNOWN_POINT_VALUE).then_some(value);
+    let mut point = Position::new(event.lat, event.lon)?;
+    if let Some(hae) = known(event.hae) {
+        point = point.with_hae(hae)?;
+    }
+    if let Some(ce) = known(event.ce) {
+        point = point.with_ce(ce)?;
+    }
+    if let Some(le) = known(event.le) {
+        point = point.with_le(le)?;
+    }
+
+    let mut cot = CotEvent::new(
+        event.uid,
+        event.r#type,
+        from_millis(event.send_time),
+        from_millis(event.stale_time),
+        point,
+    );
+    cot.start = from_millis(event.start_time);
+    cot.how = Some(event.how).filter(|how| !how.is_empty());
+    cot.other_attributes = EVENT_STRING_ATTRIBUTES
+        .into_iter()
+        .zip([
+            event.access,
+            event.qos,
+            event.opex,
+            event.caveat,
+            event.releaseable_to,
+        ])
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+    if let Some(detail) = event.detail {
+        cot.detail = detail_from_proto(detail, limits)?;
+    }
+    Ok(cot)
+}
+
+fn to_millis(timestamp: TimestampUtc) -> u64 {
+    u64::try_from(timestamp.unix_nanos().div_euclid(NANOS_PER_MILLI)).unwrap_or(0)
+}
+
+fn from_millis(millis: u64) -> TimestampUtc {
+    TimestampUtc::from_unix_nanos(i128::from(millis) * NANOS_PER_MILLI)
+}
+
+fn detail_to_proto(nodes: &[DetailNode]) -> Detail {
+    let mut detail = Detail::default();
+    for node in nodes {
+        if !set_typed_detail(&mut detail, node) {
+            detail.xml_detail.push_str(&node.to_xml());
+        }
+    }
+    detail
+}
+
+/// Moves `node` into its typed field when that loses nothing; `false` leaves
+/// it for `xml_detail`.
+fn set_typed_detail(detail: &mut Detail, node: &DetailNode) -> bool {
+    if !node.text.is_empty() || !node.children.is_empty() {
+        return false;
+    }
+    match node.name.as_str() {
+        "contact" if detail.contact.is_none() => {
+            detail.contact = strings(node, ["endpoint", "callsign"])
+                .map(|[endpoint, callsign]| Contact { endpoint, callsign });
+            detail.contact.is_some()
+        }
+        "__group" if detail.group.is_none() => {
+            detail.group = strings(node, ["name", "role"]).map(|[name, role]| Group { name, role });
+            detail.group.is_some()
+        }
+        "precisionlocation" if detail.precision_location.is_none() => {
+            detail.precision_location =
+                strings(node, ["geopointsrc", "altsrc"]).map(|[geopointsrc, altsrc]| {
+                    PrecisionLocation {
+                        geopointsrc,
+                        altsrc,
+                    }
+                });
+            detail.precision_location.is_some()
+        }
+        "status" if detail.status.is_none() => {
+            detail.status = match node.attributes.as_slice() {
+                [(name, battery)] if name == "battery" => battery
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|parsed| parsed.to_string() == *battery)
+                    .map(|battery| Status { battery }),
+                _ => None,
+            };
+            detail.status.is_some()
+        }
+        "takv" if detail.takv.is_none() => {
+            detail.takv = strings(node, ["device", "platform", "os", "version"]).map(
+                |[device, platform, os, version]| Takv {
+                    device,
+                    platform,
+                    os,
+                    version,
+                },
+            );
+            detail.takv.is_some()
+        }
+        "track" if detail.track.is_none() => {
+            detail.track = match (node.attribute("speed"), node.attribute("course")) {
+                (Some(speed), Some(course)) if node.attributes.len() == 2 => {
+                    match (speed.parse::<f64>(), course.parse::<f64>()) {
+                        (Ok(speed), Ok(course)) => Some(Track { speed, course }),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            detail.track.is_some()
+        }
+        _ => false,
+    }
+}
+
+/// The values of `names`, when every attribute of `node` is one of them,
+/// none repeats and none is empty (empty protobuf strings mean absent).
+fn strings<const N: usize>(node: &DetailNode, names: [&str; N]) -> Option<[String; N]> {
+    let mut values: [String; N] = std::array::from_fn(|_| String::new());
+    for (name, value) in &node.attributes {
+        let index = names.iter().position(|known| known == name)?;
+        if value.is_empty() || !values[index].is_empty() {
+            return None;
+        }
+        values[index].clone_from(value);
+    }
+    Some(values)
+}
+
+fn detail_from_proto(detail: Detail, limits: &Limits) -> Result<Vec<DetailNode>, ProtoError> {
+    let mut nodes = Vec::new();
+    if let Some(contact) = detail.contact {
+        nodes.push(string_node(
+            "contact",
+            [
+                ("endpoint", contact.endpoint),
+                ("callsign", contact.callsign),
+            ],
+        ));
+    }
+    if let Some(group) = detail.group {
+        nodes.push(string_node(
+            "__group",
+            [("name", group.name), ("role", group.role)],
+        ));
+    }
+    if let Some(location) = detail.precision_location {
+        nodes.push(string_node(
+            "precisionlocation",
+            [
+                ("geopointsrc", location.geopointsrc),
+                ("altsrc", location.altsrc),
+            ],
+        ));
+    }
+    if let Some(status) = detail.status {
+        nodes.push(DetailNode::new("status").with_attribute("battery", status.battery.to_string()));
+    }
+    if let Some(takv) = detail.takv {
+        nodes.push(string_node(
+            "takv",
+            [
+                ("device", takv.device),
+                ("platform", takv.platform),
+                ("os", takv.os),
+                ("version", takv.version),
+            ],
+        ));
+    }
+    if let Some(track) = detail.track {
+        nodes.push(
+            DetailNode::new("track")
+                .with_attribute("speed", track.speed.to_string())
+                .with_attribute("course", track.course.to_string()),
+        );
+    }
+    if !detail.xml_detail.is_empty() {
+        nodes.extend(DetailNode::parse_fragment(&detail.xml_detail, limits)?);
+    }
+    Ok(nodes)
+}
+
+fn string_node<const N: usize>(name: &str, attributes: [(&str, String); N]) -> DetailNode {
+    attributes
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .fold(DetailNode::new(name), |node, (name, value)| {
+            node.with_attribute(name, value)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_core::{CotEvent, DetailNode};
+    use rustak_limits::Limits;
+
+    use super::{cot_event_from_proto, cot_event_to_proto};
+
+    const EVENT: &str = concat!(
+        "<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G-U-C\" how=\"h-e\" ",
+        "time=\"2024-05-01T12:00:00.250Z\" start=\"2024-05-01T12:00:00.250Z\" ",
+        "stale=\"2024-05-01T12:05:00.000Z\" access=\"Unclassified\" custom=\"dropped\">",
+        "<point lat=\"38.5\" lon=\"-77.25\" hae=\"12.5\" ce=\"9999999.0\" le=\"3\"/>",
+        "<detail>",
+        "<remarks>Hold here</remarks>",
+        "<track course=\"90.5\" speed=\"4\"/>",
+        "<contact callsign=\"ALPHA\" endpoint=\"*:-1:stcp\"/>",
+        "<__group name=\"Cyan\" role=\"Team Member\"/>",
+        "<status battery=\"87\" readiness=\"true\"/>",
+        "<takv device=\"Pixel\" platform=\"ATAK-CIV\" os=\"34\" version=\"5.2\"/>",
+        "</detail></event>",
+    );
+
+    #[test]
+    fn typed_details_use_schema_fields_and_the_rest_stays_xml() {
+        let limits = Limits::conservative_defaults();
+        let event = CotEvent::from_xml(EVENT, &limits).expect("parse");
+        let proto = cot_event_to_proto(&event);
+
+        assert_eq!(proto.r#type, "a-f-G-U-C");
+        assert_eq!(proto.send_time, 1_714_564_800_250);
+        assert_eq!(proto.access, "Unclassified");
+        assert_eq!(proto.ce, 9_999_999.0);
+        let detail = proto.detail.as_ref().expect("detail");
+        assert_eq!(detail.contact.as_ref().expect("contact").callsign, "ALPHA");
+        assert_eq!(detail.group.as_ref().expect("group").role, "Team Member");
+        assert_eq!(detail.track.as_ref().expect("track").course, 90.5);
+        assert_eq!(detail.takv.as_ref().expect("takv").platform, "ATAK-CIV");
+        assert!(detail.status.is_none());
+        assert_eq!(
+            detail.xml_detail,
+            "<remarks>Hold here</remarks><status battery=\"87\" readiness=\"true\"/>"
+        );
+
+        let decoded = cot_event_from_proto(proto, &limits).expect("decode");
+        assert_eq!(decoded.uid, event.uid);
+        assert_eq!(decoded.time, event.time);
+        assert_eq!(decoded.point, event.point);
+        assert_eq!(
+            decoded.other_attributes,
+            [("access".to_owned(), "Unclassified".to_owned())]
+        );
+        let names: Vec<&str> = decoded
+            .detail
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["contact", "__group", "takv", "track", "remarks", "status"]
+        );
+        assert_eq!(
+            decoded.detail_element("contact"),
+            Some(
+                &DetailNode::new("contact")
+                    .with_attribute("endpoint", "*:-1:stcp")
+                    .with_attribute("callsign", "ALPHA")
+            )
+        );
+
+        // The decoded form is canonical: converting it again is lossless.
+        let canonical = decoded.to_xml();
+        let again = cot_event_from_proto(cot_event_to_proto(&decoded), &limits).expect("again");
+        assert_eq!(again.to_xml(), canonical);
+    }
+
+    #[test]
+    fn partial_typed_elements_fall_back_to_xml_detail() {
+        let limits = Limits::conservative_defaults();
+        let event = CotEvent::from_xml(
+            &EVENT
+                .replace(" course=\"90.5\"", "")
+                .replace("callsign=\"ALPHA\"", "callsign=\"\""),
+            &limits,
+        )
+        .expect("parse");
+        let detail = cot_event_to_proto(&event).detail.expect("detail");
+        assert!(detail.track.is_none());
+        assert!(detail.contact.is_none());
+        assert!(detail.xml_detail.contains("<track speed=\"4\"/>"));
+        assert!(detail
+            .xml_detail
+            .contains("<contact callsign=\"\" endpoint=\"*:-1:stcp\"/>"));
+    }
+}
@@ -1,31 +1,48 @@
+pub mod convert;
+pub mod takmessage;
+
 use prost::Message;
+use rustak_core::{CoreError, CotEvent, CotXmlError};
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use thiserror::Error;
 
-#[derive(Clone, PartialEq, Message)]
-struct TakV1Payload {
-    #[prost(bytes = "vec", tag = "1")]
-    cot_message: Vec<u8>,
-}
+pub use convert::{cot_event_from_proto, cot_event_to_proto};
+pub use takmessage::TakMessage;
 
+/// Decodes a TAK protocol v1 payload into the canonical CoT XML for its event.
 pub fn decode_v1_payload(bytes: &[u8]) -> Result<Vec<u8>, ProtoError> {
-    let payload = TakV1Payload::decode(bytes)?;
-    if payload.cot_message.is_empty() {
-        return Err(ProtoError::EmptyCotMessage);
-    }
-
-    Ok(payload.cot_message)
+    let event = decode_v1_event(bytes, &Limits::conservative_defaults())?;
+    Ok(event.to_xml().into_bytes())
 }
 
+/// Encodes CoT XML as a binary TAK protocol v1 payload.
 pub fn encode_v1_payload(message: &[u8]) -> Result<Vec<u8>, ProtoError> {
     if message.is_empty() {
         return Err(ProtoError::EmptyCotMessage);
     }
 
-    let payload = TakV1Payload {
-        cot_message: message.to_vec(),
+    let limits = Limits::conservative_defaults();
+    let xml =
+        std::str::from_utf8(message).map_err(|_| CotXmlError::Syntax("invalid UTF-8".into()))?;
+    encode_v1_event(&CotEvent::from_xml(xml, &limits)?)
+}
+
+/// Decodes a TAK protocol v1 payload carrying a CoT event; `limits` bounds the
+/// `xml_detail` parse.
+pub fn decode_v1_event(bytes: &[u8], limits: &Limits) -> Result<CotEvent, ProtoError> {
+    let message = TakMessage::decode(bytes)?;
+    let event = message.cot_event.ok_or(ProtoError::EmptyCotMessage)?;
+    cot_event_from_proto(event, limits)
+}
+
+/// Encodes `event` as a TAK protocol v1 payload.
+pub fn encode_v1_event(event: &CotEvent) -> Result<Vec<u8>, ProtoError> {
+    let message = TakMessage {
+        cot_event: Some(cot_event_to_proto(event)),
+        ..TakMessage::default()
     };
-    let mut encoded = Vec::with_capacity(payload.encoded_len());
-    payload.encode(&mut encoded)?;
+    let mut encoded = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut encoded)?;
     Ok(encoded)
 }
 
@@ -37,4 +54,20 @@ pub enum ProtoError {
     Encode(#[from] prost::EncodeError),
     #[error("payload must contain a non-empty CoT message")]
     EmptyCotMessage,
+    #[error("invalid CoT XML: {0}")]
+    Xml(#[from] CotXmlError),
+    #[error("invalid CoT event: {0}")]
+    InvalidEvent(#[from] CoreError),
+}
+
+impl CodedError for ProtoError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Decode(_) => ErrorCode::new("PROTO", 1),
+            Self::Encode(_) => ErrorCode::new("PROTO", 2),
+            Self::EmptyCotMessage => ErrorCode::new("PROTO", 3),
+            Self::Xml(_) => ErrorCode::new("PROTO", 4),
+            Self::InvalidEvent(_) => ErrorCode::new("PROTO", 5),
+        }
+    }
 }
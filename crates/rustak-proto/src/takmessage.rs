@@ -0,0 +1,149 @@
+//! TAK Protocol Version 1 message schema (`takmessage.proto` and the files it
+//! imports), hand-written with `prost` derives so no `protoc` is needed at
+//! build time. Field names follow the `.proto` files in snake case.
+
+use prost::Message;
+
+/// Top-level TAK protocol v1 payload.
+#[derive(Clone, PartialEq, Message)]
+pub struct TakMessage {
+    #[prost(message, optional, tag = "1")]
+    pub tak_control: Option<TakControl>,
+    #[prost(message, optional, tag = "2")]
+    pub cot_event: Option<CotEvent>,
+    /// Milliseconds since the Unix epoch when a server accepted the message.
+    #[prost(uint64, tag = "3")]
+    pub submission_time: u64,
+    /// Milliseconds since the Unix epoch when the message was created.
+    #[prost(uint64, tag = "4")]
+    pub creation_time: u64,
+}
+
+/// Protocol version range advertised by the sender.
+#[derive(Clone, PartialEq, Message)]
+pub struct TakControl {
+    #[prost(uint32, tag = "1")]
+    pub min_proto_version: u32,
+    #[prost(uint32, tag = "2")]
+    pub max_proto_version: u32,
+    #[prost(string, tag = "3")]
+    pub contact_uid: String,
+}
+
+/// A CoT event. Times are milliseconds since the Unix epoch; empty strings
+/// stand for absent attributes.
+#[derive(Clone, PartialEq, Message)]
+pub struct CotEvent {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(string, tag = "2")]
+    pub access: String,
+    #[prost(string, tag = "3")]
+    pub qos: String,
+    #[prost(string, tag = "4")]
+    pub opex: String,
+    #[prost(string, tag = "16")]
+    pub caveat: String,
+    #[prost(string, tag = "17")]
+    pub releaseable_to: String,
+    #[prost(string, tag = "5")]
+    pub uid: String,
+    #[prost(uint64, tag = "6")]
+    pub send_time: u64,
+    #[prost(uint64, tag = "7")]
+    pub start_time: u64,
+    #[prost(uint64, tag = "8")]
+    pub stale_time: u64,
+    #[prost(string, tag = "9")]
+    pub how: String,
+    #[prost(double, tag = "10")]
+    pub lat: f64,
+    #[prost(double, tag = "11")]
+    pub lon: f64,
+    #[prost(double, tag = "12")]
+    pub hae: f64,
+    #[prost(double, tag = "13")]
+    pub ce: f64,
+    #[prost(double, tag = "14")]
+    pub le: f64,
+    #[prost(message, optional, tag = "15")]
+    pub detail: Option<Detail>,
+}
+
+/// `<detail>` split into the typed sub-schemas and the remaining XML.
+///
+/// An element carried in a typed field must not also appear in
+/// `xml_detail`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Detail {
+    #[prost(string, tag = "1")]
+    pub xml_detail: String,
+    #[prost(message, optional, tag = "2")]
+    pub contact: Option<Contact>,
+    #[prost(message, optional, tag = "3")]
+    pub group: Option<Group>,
+    #[prost(message, optional, tag = "4")]
+    pub precision_location: Option<PrecisionLocation>,
+    #[prost(message, optional, tag = "5")]
+    pub status: Option<Status>,
+    #[prost(message, optional, tag = "6")]
+    pub takv: Option<Takv>,
+    #[prost(message, optional, tag = "7")]
+    pub track: Option<Track>,
+}
+
+/// `<contact endpoint=".." callsign=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct Contact {
+    #[prost(string, tag = "1")]
+    pub endpoint: String,
+    #[prost(string, tag = "2")]
+    pub callsign: String,
+}
+
+/// `<__group name=".." role=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct Group {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub role: String,
+}
+
+/// `<precisionlocation geopointsrc=".." altsrc=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct PrecisionLocation {
+    #[prost(string, tag = "1")]
+    pub geopointsrc: String,
+    #[prost(string, tag = "2")]
+    pub altsrc: String,
+}
+
+/// `<status battery=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct Status {
+    #[prost(uint32, tag = "1")]
+    pub battery: u32,
+}
+
+/// `<takv device=".." platform=".." os=".." version=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct Takv {
+    #[prost(string, tag = "1")]
+    pub device: String,
+    #[prost(string, tag = "2")]
+    pub platform: String,
+    #[prost(string, tag = "3")]
+    pub os: String,
+    #[prost(string, tag = "4")]
+    pub version: String,
+}
+
+/// `<track speed=".." course=".."/>`
+#[derive(Clone, PartialEq, Message)]
+pub struct Track {
+    #[prost(double, tag = "1")]
+    pub speed: f64,
+    #[prost(double, tag = "2")]
+    pub course: f64,
+}
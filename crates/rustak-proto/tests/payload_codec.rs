@@ -1,7 +1,8 @@
 use std::fs;
 use std::path::PathBuf;
 
-use rustak_proto::{decode_v1_payload, encode_v1_payload, ProtoError};
+use prost::Message;
+use rustak_proto::{decode_v1_payload, encode_v1_payload, ProtoError, TakMessage};
 
 fn fixture_path(file_name: &str) -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -38,3 +39,26 @@ fn empty_payloads_are_rejected() {
     let decode_error = decode_v1_payload(&[0x0A, 0x00]).expect_err("empty encoded payload fails");
     assert!(matches!(decode_error, ProtoError::EmptyCotMessage));
 }
+
+#[test]
+fn v1_payload_carries_a_binary_cot_event() {
+    let cot_message =
+        fs::read(fixture_path("proto_v1_cot_message.xml")).expect("fixture should be readable");
+    let encoded = encode_v1_payload(&cot_message).expect("encoding should succeed");
+    assert!(encoded.len() < cot_message.len());
+    assert!(!encoded.windows(6).any(|window| window == b"<event"));
+
+    let message = TakMessage::decode(encoded.as_slice()).expect("TakMessage");
+    let event = message.cot_event.expect("cot event");
+    assert_eq!(event.uid, "fixture-01");
+    assert_eq!(event.stale_time - event.send_time, 15_000);
+    let detail = event.detail.expect("detail");
+    assert_eq!(detail.contact.expect("contact").callsign, "FIXTURE");
+    assert_eq!(detail.xml_detail, "<remarks>fixture</remarks>");
+}
+
+#[test]
+fn non_event_xml_is_rejected() {
+    let error = encode_v1_payload(b"<event uid=\"x\"/>").expect_err("point is required");
+    assert!(matches!(error, ProtoError::Xml(_)));
+}
@@ -0,0 +1,203 @@
+//! Deterministic digests proving two captures carry the same traffic.
+//!
+//! Chunks are canonicalised before hashing so that captures of the same
+//! events compare equal however they were framed on the wire: a chunk that
+//! decodes as CoT XML, or as TAK protocol v1 with or without the
+//! `0xBF <version> 0xBF` mesh header, is hashed as its [`CotEvent::to_xml`]
+//! serialization, which carries the event `time`, `start` and `stale` at
+//! millisecond precision. Anything else is hashed as its raw bytes. Chunks
+//! are hashed in sequence order, so a replay that reorders frames digests
+//! differently.
+
+use std::fmt;
+use std::io::Read;
+
+use rustak_core::CotEvent;
+use rustak_limits::Limits;
+use sha2::{Digest, Sha256};
+
+use crate::{for_each_chunk, RecordWriteError};
+
+/// Tag ahead of a chunk hashed as canonical CoT XML.
+const DECODED_TAG: u8 = b'E';
+/// Tag ahead of a chunk hashed as raw bytes.
+const RAW_TAG: u8 = b'R';
+
+const MESH_MAGIC: u8 = 0xBF;
+
+/// Digest of a capture and how its chunks were canonicalised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayDigest {
+    pub sha256: [u8; 32],
+    pub chunks: u64,
+    /// Chunks hashed as canonical CoT XML.
+    pub decoded: u64,
+    /// Chunks hashed as raw bytes because they did not decode.
+    pub undecodable: u64,
+    pub truncated_tail: bool,
+}
+
+impl ReplayDigest {
+    /// Lower-case hex of [`ReplayDigest::sha256`].
+    #[must_use]
+    pub fn hex(&self) -> String {
+        self.sha256
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
+
+impl fmt::Display for ReplayDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hex())
+    }
+}
+
+/// Accumulates a [`ReplayDigest`] one chunk payload at a time, for callers
+/// that see frames outside a takrec file.
+pub struct ReplayDigester {
+    hasher: Sha256,
+    limits: Limits,
+    chunks: u64,
+    decoded: u64,
+}
+
+impl ReplayDigester {
+    #[must_use]
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            hasher: Sha256::new(),
+            limits,
+            chunks: 0,
+            decoded: 0,
+        }
+    }
+
+    pub fn observe(&mut self, payload: &[u8]) {
+        self.chunks += 1;
+        match canonical_event(payload, &self.limits) {
+            Some(xml) => {
+                self.decoded += 1;
+                self.update(DECODED_TAG, xml.as_bytes());
+            }
+            None => self.update(RAW_TAG, payload),
+        }
+    }
+
+    #[must_use]
+    pub fn finish(self, truncated_tail: bool) -> ReplayDigest {
+        ReplayDigest {
+            sha256: self.hasher.finalize().into(),
+            chunks: self.chunks,
+            decoded: self.decoded,
+            undecodable: self.chunks - self.decoded,
+            truncated_tail,
+        }
+    }
+
+    fn update(&mut self, tag: u8, bytes: &[u8]) {
+        self.hasher.update([tag]);
+        self.hasher.update((bytes.len() as u64).to_be_bytes());
+        self.hasher.update(bytes);
+    }
+}
+
+/// Streams a capture through [`ReplayDigester`] in constant memory.
+pub fn replay_digest<R: Read>(source: R, limits: Limits) -> Result<ReplayDigest, RecordWriteError> {
+    let mut digester = ReplayDigester::new(limits);
+    let scan = for_each_chunk(source, |_, payload| digester.observe(payload))?;
+    Ok(digester.finish(scan.truncated_tail))
+}
+
+fn canonical_event(payload: &[u8], limits: &Limits) -> Option<String> {
+    let trimmed = payload.trim_ascii();
+    let event = if trimmed.starts_with(b"<") {
+        CotEvent::from_xml(std::str::from_utf8(trimmed).ok()?, limits).ok()?
+    } else {
+        let proto = match payload {
+            [MESH_MAGIC, _, MESH_MAGIC, rest @ ..] => rest,
+            _ => payload,
+        };
+        rustak_proto::decode_v1_event(proto, limits).ok()?
+    };
+    Some(event.to_xml())
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_limits::Limits;
+
+    use super::{replay_digest, ReplayDigester};
+    use crate::{TakrecHeader, TakrecWriter};
+
+    fn event(uid: &str, time: &str) -> String {
+        format!(
+            "<event version=\"2.0\" uid=\"{uid}\" type=\"a-f-G\" how=\"m-g\" time=\"{time}\" start=\"{time}\" stale=\"{time}\"><point lat=\"1\" lon=\"2\" hae=\"0\" ce=\"5\" le=\"5\"/></event>"
+        )
+    }
+
+    fn capture(payloads: &[Vec<u8>]) -> Vec<u8> {
+        let mut writer =
+            TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer should start");
+        for payload in payloads {
+            writer.append_chunk(payload).expect("chunk should append");
+        }
+        writer.into_inner().expect("writer should finish")
+    }
+
+    #[test]
+    fn xml_and_tak_protocol_captures_of_the_same_events_digest_equal() {
+        let alpha = event("alpha", "2024-01-01T00:00:00.000Z");
+        let beta = event("beta", "2024-01-01T00:00:01.000Z");
+        let xml = capture(&[
+            format!("<?xml version=\"1.0\"?>\n{alpha}\n").into_bytes(),
+            beta.clone().into_bytes(),
+            b"opaque".to_vec(),
+        ]);
+        let mut mesh = vec![0xBF, 0x01, 0xBF];
+        mesh.extend(rustak_proto::encode_v1_payload(alpha.as_bytes()).expect("encode"));
+        let proto = capture(&[
+            mesh,
+            rustak_proto::encode_v1_payload(beta.as_bytes()).expect("encode"),
+            b"opaque".to_vec(),
+        ]);
+
+        let xml_digest = replay_digest(xml.as_slice(), Limits::default()).expect("digest");
+        let proto_digest = replay_digest(proto.as_slice(), Limits::default()).expect("digest");
+        assert_eq!(xml_digest, proto_digest);
+        assert_eq!(
+            (
+                xml_digest.chunks,
+                xml_digest.decoded,
+                xml_digest.undecodable
+            ),
+            (3, 2, 1)
+        );
+        assert_eq!(xml_digest.hex().len(), 64);
+        assert!(!xml_digest.truncated_tail);
+    }
+
+    #[test]
+    fn timestamps_order_and_raw_bytes_change_the_digest() {
+        let digest = |payloads: &[&str]| {
+            let mut digester = ReplayDigester::new(Limits::default());
+            for payload in payloads {
+                digester.observe(payload.as_bytes());
+            }
+            digester.finish(false).hex()
+        };
+        let alpha = event("alpha", "2024-01-01T00:00:00.000Z");
+        let beta = event("beta", "2024-01-01T00:00:00.000Z");
+        let base = digest(&[&alpha, &beta]);
+
+        assert_eq!(base, digest(&[&alpha, &beta]));
+        assert_ne!(base, digest(&[&beta, &alpha]));
+        assert_ne!(
+            base,
+            digest(&[&alpha, &event("beta", "2024-01-01T00:00:00.001Z")])
+        );
+        assert_ne!(digest(&["raw-a"]), digest(&["raw-b"]));
+        assert_ne!(digest(&["ab", "c"]), digest(&["a", "bc"]));
+    }
+}
@@ -1,10 +1,9 @@
-use std::io::Read;
+use std::io::{self, Read};
+use std::time::SystemTime;
 
-use crate::{recover_chunk_index, ChunkCommit, RecordWriteError, RecoveryReport, TakrecHeader};
-
-const FILE_HEADER_FIXED_BYTES: u64 = 8 + 2 + 8;
-const LEN_PREFIX_BYTES: u64 = 2;
-const CHUNK_FIXED_BYTES: u64 = 4 + 8 + 4 + 4 + 4;
+use crate::reader::WallTimes;
+use crate::writer::{read_header, read_next_chunk, NextChunk, DEFAULT_MAX_CHUNK_BYTES};
+use crate::{RecordWriteError, TakrecHeader};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkIndexEntry {
@@ -12,6 +11,10 @@ pub struct ChunkIndexEntry {
     pub offset: u64,
     pub payload_len: u32,
     pub checksum: u32,
+    /// Wall time as [`crate::TakrecReader`] reports it: the recorded capture
+    /// time, or for files without one the CoT event `time`, or the previous
+    /// chunk's when the payload has none.
+    pub observed_wall: SystemTime,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -33,38 +36,53 @@ impl ChunkIndex {
     pub fn find_by_sequence(&self, sequence: u64) -> Option<&ChunkIndexEntry> {
         self.entries.iter().find(|entry| entry.sequence == sequence)
     }
-}
-
-pub fn rebuild_index<R: Read>(source: R) -> Result<ChunkIndex, RecordWriteError> {
-    let report = recover_chunk_index(source)?;
-    Ok(rebuild_index_from_recovery(report))
-}
 
-#[must_use]
-pub fn rebuild_index_from_recovery(report: RecoveryReport) -> ChunkIndex {
-    let header_bytes = header_size_bytes(&report.header);
-    let mut running_offset = header_bytes;
-    let mut entries = Vec::with_capacity(report.chunks.len());
-
-    for chunk in &report.chunks {
-        entries.push(ChunkIndexEntry {
-            sequence: chunk.sequence,
-            offset: running_offset,
-            payload_len: chunk.payload_len,
-            checksum: chunk.checksum,
-        });
-        running_offset = running_offset.saturating_add(chunk_size_bytes(chunk));
+    /// First entry, in file order, observed at or after `target`.
+    #[must_use]
+    pub fn find_by_time(&self, target: SystemTime) -> Option<&ChunkIndexEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.observed_wall >= target)
     }
+}
 
-    ChunkIndex {
-        header: report.header,
+/// Indexes every committed chunk, reading one payload at a time so chunks
+/// without a capture time can have their wall time restored. Offsets are where each chunk starts in the file, as
+/// stored, so they hold for compressed chunks too.
+pub fn rebuild_index<R: Read>(source: R) -> Result<ChunkIndex, RecordWriteError> {
+    let mut source = CountingReader {
+        inner: source,
+        position: 0,
+    };
+    let header = read_header(&mut source)?;
+    let mut walls = WallTimes::new(&header);
+    let mut entries = Vec::new();
+    let mut offset = source.position;
+    let truncated_tail = loop {
+        match read_next_chunk(&mut source, DEFAULT_MAX_CHUNK_BYTES)? {
+            NextChunk::Committed(commit, payload, captured, _) => {
+                entries.push(ChunkIndexEntry {
+                    sequence: commit.sequence,
+                    offset,
+                    payload_len: commit.payload_len,
+                    checksum: commit.checksum,
+                    observed_wall: walls.observe(&payload, captured),
+                });
+                offset = source.position;
+            }
+            NextChunk::End { truncated_tail } => break truncated_tail,
+        }
+    };
+
+    Ok(ChunkIndex {
+        header,
         diagnostics: RebuildDiagnostics {
-            recovered_chunks: report.chunks.len(),
-            truncated_tail: report.truncated_tail,
-            indexed_bytes: running_offset,
+            recovered_chunks: entries.len(),
+            truncated_tail,
+            indexed_bytes: offset,
         },
         entries,
-    }
+    })
 }
 
 #[must_use]
@@ -78,27 +96,28 @@ pub fn format_rebuild_diagnostics(index: &ChunkIndex) -> String {
     )
 }
 
-fn chunk_size_bytes(chunk: &ChunkCommit) -> u64 {
-    CHUNK_FIXED_BYTES.saturating_add(u64::from(chunk.payload_len))
-}
-
-fn header_size_bytes(header: &TakrecHeader) -> u64 {
-    FILE_HEADER_FIXED_BYTES
-        .saturating_add(len_prefixed_bytes(&header.tool_name))
-        .saturating_add(len_prefixed_bytes(&header.tool_version))
-        .saturating_add(len_prefixed_bytes(&header.protocol_hint))
-        .saturating_add(len_prefixed_bytes(&header.limits_profile))
+struct CountingReader<R> {
+    inner: R,
+    position: u64,
 }
 
-fn len_prefixed_bytes(value: &str) -> u64 {
-    LEN_PREFIX_BYTES.saturating_add(value.len() as u64)
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
 
-    use crate::{format_rebuild_diagnostics, rebuild_index, TakrecHeader, TakrecWriter};
+    use crate::tests::dated_event;
+    use crate::{
+        format_rebuild_diagnostics, rebuild_index, ChunkCompression, TakrecHeader, TakrecWriter,
+    };
 
     #[test]
     fn rebuild_index_computes_deterministic_offsets() {
@@ -118,6 +137,48 @@ mod tests {
         assert_eq!(index.diagnostics.recovered_chunks, 2);
     }
 
+    #[test]
+    fn rebuild_index_records_wall_times_and_stored_offsets() {
+        let header = TakrecHeader {
+            created_unix_nanos: 1_000_000_000,
+            ..TakrecHeader::default()
+        };
+        let mut writer =
+            TakrecWriter::with_compression(Vec::new(), header, ChunkCompression::Zstd { level: 3 })
+                .expect("writer");
+        let event = dated_event("2024-01-01T00:00:00Z", &"x".repeat(256));
+        writer.append_chunk(b"opaque").expect("chunk");
+        writer.append_chunk(&event).expect("chunk");
+        writer.append_chunk(b"undated").expect("chunk");
+        let data = writer.into_inner().expect("inner");
+
+        let index = rebuild_index(Cursor::new(data.as_slice())).expect("rebuild");
+        let walls: Vec<_> = index
+            .entries
+            .iter()
+            .map(|entry| entry.observed_wall)
+            .collect();
+        let created = UNIX_EPOCH + Duration::from_secs(1);
+        let event_time = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        assert_eq!(walls, [created, event_time, event_time]);
+        assert_eq!(index.diagnostics.indexed_bytes, data.len() as u64);
+        let last = &index.entries[2];
+        assert_eq!(&data[last.offset as usize..][..4], b"CHNK");
+
+        assert_eq!(
+            index.find_by_time(created).map(|entry| entry.sequence),
+            Some(0)
+        );
+        let later = created + Duration::from_nanos(1);
+        assert_eq!(
+            index.find_by_time(later).map(|entry| entry.sequence),
+            Some(1)
+        );
+        assert!(index
+            .find_by_time(event_time + Duration::from_secs(1))
+            .is_none());
+    }
+
     #[test]
     fn rebuild_index_marks_truncated_tail_without_panicking() {
         let mut writer = TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
@@ -1,6 +1,17 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use rustak_limits::{CodedError, ErrorCode};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+/// First line of a chain sidecar written by [`IntegrityChain::to_sidecar`].
+const SIDECAR_MAGIC: &str = "takrec-chain 1";
+
+/// Links between [`IntegrityChainBuilder`] checkpoints unless configured.
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 64;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IntegrityLink {
     pub sequence: u64,
@@ -15,6 +26,182 @@ pub struct IntegrityChain {
     pub links: Vec<IntegrityLink>,
 }
 
+impl IntegrityChain {
+    /// Text form stored beside a capture: a `takrec-chain 1` line, then one
+    /// `<sequence> <payload hash> <chain hash> <signature>` line per link
+    /// in hex, with `-` for an unsigned link. Previous chain hashes are
+    /// implied by line order.
+    #[must_use]
+    pub fn to_sidecar(&self) -> String {
+        let mut out = format!("{SIDECAR_MAGIC}\n");
+        for link in &self.links {
+            write_sidecar_line(&mut out, link);
+        }
+        out
+    }
+
+    /// Parses [`IntegrityChain::to_sidecar`] output. Hashes are only
+    /// parsed here; [`verify_integrity_chain`] checks them.
+    pub fn from_sidecar(text: &str) -> Result<Self, IntegrityError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line.trim()) != Some(SIDECAR_MAGIC) {
+            return Err(IntegrityError::MalformedSidecar { line: 1 });
+        }
+        let mut links = Vec::new();
+        let mut previous_chain_hash = None;
+        for (index, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let malformed = IntegrityError::MalformedSidecar { line: index + 1 };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [sequence, payload_hash, chain_hash, signature] = fields[..] else {
+                return Err(malformed);
+            };
+            let chain_hash = hash_from_hex(chain_hash).ok_or(malformed.clone())?;
+            links.push(IntegrityLink {
+                sequence: sequence.parse().map_err(|_| malformed.clone())?,
+                payload_hash: hash_from_hex(payload_hash).ok_or(malformed.clone())?,
+                previous_chain_hash,
+                chain_hash,
+                signature: match signature {
+                    "-" => None,
+                    signature => Some(bytes_from_hex(signature).ok_or(malformed)?),
+                },
+            });
+            previous_chain_hash = Some(chain_hash);
+        }
+        Ok(Self { links })
+    }
+}
+
+/// Builds a chain one chunk at a time as a capture is written, appending
+/// links to a sidecar in [`IntegrityChain::to_sidecar`] form.
+///
+/// Links are held back until a checkpoint, every `checkpoint_interval`
+/// chunks and on [`IntegrityChainBuilder::finish`]; a checkpoint signs its
+/// last link, writes the held links and flushes the sidecar. The hash chain
+/// makes that one signature vouch for every earlier link, so a recording
+/// that crashes keeps a sidecar verifiable through its last checkpoint (see
+/// [`verify_integrity_prefix`]) without re-reading the capture.
+#[derive(Debug)]
+pub struct IntegrityChainBuilder<W: Write, S: SignatureProvider = NoopSigner> {
+    sidecar: W,
+    signer: Option<S>,
+    checkpoint_interval: usize,
+    next_sequence: u64,
+    previous_chain_hash: [u8; 32],
+    pending: Vec<IntegrityLink>,
+}
+
+impl<W: Write> IntegrityChainBuilder<W> {
+    /// An unsigned chain; its sidecar header is written immediately.
+    pub fn new(sidecar: W) -> io::Result<Self> {
+        Self::start(sidecar, None)
+    }
+}
+
+impl<W: Write, S: SignatureProvider> IntegrityChainBuilder<W, S> {
+    /// A chain whose checkpoint links `signer` signs.
+    pub fn with_signer(sidecar: W, signer: S) -> io::Result<Self> {
+        Self::start(sidecar, Some(signer))
+    }
+
+    fn start(mut sidecar: W, signer: Option<S>) -> io::Result<Self> {
+        writeln!(sidecar, "{SIDECAR_MAGIC}")?;
+        sidecar.flush()?;
+        Ok(Self {
+            sidecar,
+            signer,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            next_sequence: 0,
+            previous_chain_hash: [0; 32],
+            pending: Vec::new(),
+        })
+    }
+
+    /// Links between checkpoints; at least one.
+    #[must_use]
+    pub fn with_checkpoint_interval(mut self, links: usize) -> Self {
+        self.checkpoint_interval = links.max(1);
+        self
+    }
+
+    /// Links the next chunk payload, checkpointing when one is due.
+    pub fn push(&mut self, payload: &[u8]) -> io::Result<()> {
+        let link = next_link(self.next_sequence, payload, self.previous_chain_hash);
+        self.next_sequence += 1;
+        self.previous_chain_hash = link.chain_hash;
+        self.pending.push(link);
+        if self.pending.len() >= self.checkpoint_interval {
+            self.checkpoint()?;
+        }
+        Ok(())
+    }
+
+    /// Signs the newest link and writes every held link to the sidecar.
+    pub fn checkpoint(&mut self) -> io::Result<()> {
+        let Some(last) = self.pending.last_mut() else {
+            return Ok(());
+        };
+        last.signature = self
+            .signer
+            .as_ref()
+            .and_then(|signer| signer.sign(last.sequence, &last.chain_hash));
+        let mut lines = String::new();
+        for link in self.pending.drain(..) {
+            write_sidecar_line(&mut lines, &link);
+        }
+        self.sidecar.write_all(lines.as_bytes())?;
+        self.sidecar.flush()
+    }
+
+    /// Checkpoints, then starts a new chain in `sidecar` for the next
+    /// file of a rotating capture. Returns the finished sidecar.
+    pub fn restart(&mut self, sidecar: W) -> io::Result<W> {
+        self.checkpoint()?;
+        let mut sidecar = sidecar;
+        writeln!(sidecar, "{SIDECAR_MAGIC}")?;
+        sidecar.flush()?;
+        self.next_sequence = 0;
+        self.previous_chain_hash = [0; 32];
+        Ok(std::mem::replace(&mut self.sidecar, sidecar))
+    }
+
+    /// Chunks linked so far, including those awaiting a checkpoint.
+    #[must_use]
+    pub fn links(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// Checkpoints and returns the sidecar.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.checkpoint()?;
+        Ok(self.sidecar)
+    }
+}
+
+/// How much of a capture [`verify_integrity_prefix`] vouched for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifiedPrefix {
+    /// Leading payloads covered by the chain.
+    pub links: usize,
+    /// Payloads after the chain's last link, written after its last
+    /// checkpoint.
+    pub uncovered: usize,
+    /// Sequence of the last link whose signature was verified.
+    pub signed_through: Option<u64>,
+}
+
+/// Where the chain sidecar of `recording` lives: `session.takrec` keeps its
+/// chain in `session.takrec.chain`.
+#[must_use]
+pub fn chain_sidecar_path(recording: &Path) -> PathBuf {
+    let mut path = recording.as_os_str().to_owned();
+    path.push(".chain");
+    PathBuf::from(path)
+}
+
 pub trait SignatureProvider {
     fn sign(&self, sequence: u64, chain_hash: &[u8; 32]) -> Option<Vec<u8>>;
 }
@@ -23,7 +210,7 @@ pub trait SignatureVerifier {
     fn verify(&self, sequence: u64, chain_hash: &[u8; 32], signature: &[u8]) -> bool;
 }
 
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
 pub enum IntegrityError {
     #[error("payload count {payload_count} does not match chain length {chain_len}")]
     PayloadCountMismatch {
@@ -52,6 +239,24 @@ pub enum IntegrityError {
 
     #[error("invalid signature at sequence {sequence}")]
     InvalidSignature { sequence: u64 },
+
+    #[error("malformed chain sidecar at line {line}")]
+    MalformedSidecar { line: usize },
+}
+
+impl CodedError for IntegrityError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::PayloadCountMismatch { .. } => ErrorCode::new("RECORD", 101),
+            Self::SequenceMismatch { .. } => ErrorCode::new("RECORD", 102),
+            Self::PayloadHashMismatch { .. } => ErrorCode::new("RECORD", 103),
+            Self::ChainHashMismatch { .. } => ErrorCode::new("RECORD", 104),
+            Self::MissingSignature { .. } => ErrorCode::new("RECORD", 105),
+            Self::MissingVerifier => ErrorCode::new("RECORD", 106),
+            Self::InvalidSignature { .. } => ErrorCode::new("RECORD", 107),
+            Self::MalformedSidecar { .. } => ErrorCode::new("RECORD", 108),
+        }
+    }
 }
 
 #[must_use]
@@ -82,39 +287,7 @@ pub fn verify_integrity_chain<V: SignatureVerifier>(
 
     let mut previous_chain_hash = [0u8; 32];
     for (index, (payload, link)) in payloads.iter().zip(chain.links.iter()).enumerate() {
-        let expected_sequence = index as u64;
-        if link.sequence != expected_sequence {
-            return Err(IntegrityError::SequenceMismatch {
-                index,
-                expected: expected_sequence,
-                found: link.sequence,
-            });
-        }
-
-        let payload_hash = digest_bytes(payload);
-        if link.payload_hash != payload_hash {
-            return Err(IntegrityError::PayloadHashMismatch {
-                sequence: link.sequence,
-            });
-        }
-
-        let expected_previous = if index == 0 {
-            None
-        } else {
-            Some(previous_chain_hash)
-        };
-        if link.previous_chain_hash != expected_previous {
-            return Err(IntegrityError::ChainHashMismatch {
-                sequence: link.sequence,
-            });
-        }
-
-        let expected_chain_hash = chain_hash(expected_sequence, payload_hash, previous_chain_hash);
-        if link.chain_hash != expected_chain_hash {
-            return Err(IntegrityError::ChainHashMismatch {
-                sequence: link.sequence,
-            });
-        }
+        check_link(index, payload, link, previous_chain_hash)?;
 
         if require_signatures {
             let signature = link
@@ -137,6 +310,100 @@ pub fn verify_integrity_chain<V: SignatureVerifier>(
     Ok(())
 }
 
+/// Verifies a checkpointed chain, such as one [`IntegrityChainBuilder`]
+/// left behind, against the leading payloads it covers. Payloads past the
+/// chain's end are reported as uncovered rather than rejected. Every
+/// signature present is checked when a verifier is given; with
+/// `require_signatures` the chain's last link must also be signed, which
+/// through the hash chain vouches for all earlier links.
+pub fn verify_integrity_prefix<V: SignatureVerifier>(
+    payloads: &[Vec<u8>],
+    chain: &IntegrityChain,
+    verifier: Option<&V>,
+    require_signatures: bool,
+) -> Result<VerifiedPrefix, IntegrityError> {
+    if payloads.len() < chain.links.len() {
+        return Err(IntegrityError::PayloadCountMismatch {
+            payload_count: payloads.len(),
+            chain_len: chain.links.len(),
+        });
+    }
+    if require_signatures && verifier.is_none() {
+        return Err(IntegrityError::MissingVerifier);
+    }
+
+    let mut previous_chain_hash = [0u8; 32];
+    let mut signed_through = None;
+    for (index, (payload, link)) in payloads.iter().zip(chain.links.iter()).enumerate() {
+        check_link(index, payload, link, previous_chain_hash)?;
+        if let (Some(signature), Some(verifier)) = (&link.signature, verifier) {
+            if !verifier.verify(link.sequence, &link.chain_hash, signature) {
+                return Err(IntegrityError::InvalidSignature {
+                    sequence: link.sequence,
+                });
+            }
+            signed_through = Some(link.sequence);
+        }
+        previous_chain_hash = link.chain_hash;
+    }
+
+    if require_signatures {
+        let last = chain.links.len().saturating_sub(1) as u64;
+        if signed_through != Some(last) {
+            return Err(IntegrityError::MissingSignature { sequence: last });
+        }
+    }
+    Ok(VerifiedPrefix {
+        links: chain.links.len(),
+        uncovered: payloads.len() - chain.links.len(),
+        signed_through,
+    })
+}
+
+/// Checks one link's sequence and hashes against its payload and the
+/// previous link.
+fn check_link(
+    index: usize,
+    payload: &[u8],
+    link: &IntegrityLink,
+    previous_chain_hash: [u8; 32],
+) -> Result<(), IntegrityError> {
+    let expected_sequence = index as u64;
+    if link.sequence != expected_sequence {
+        return Err(IntegrityError::SequenceMismatch {
+            index,
+            expected: expected_sequence,
+            found: link.sequence,
+        });
+    }
+
+    let payload_hash = digest_bytes(payload);
+    if link.payload_hash != payload_hash {
+        return Err(IntegrityError::PayloadHashMismatch {
+            sequence: link.sequence,
+        });
+    }
+
+    let expected_previous = if index == 0 {
+        None
+    } else {
+        Some(previous_chain_hash)
+    };
+    if link.previous_chain_hash != expected_previous {
+        return Err(IntegrityError::ChainHashMismatch {
+            sequence: link.sequence,
+        });
+    }
+
+    let expected_chain_hash = chain_hash(expected_sequence, payload_hash, previous_chain_hash);
+    if link.chain_hash != expected_chain_hash {
+        return Err(IntegrityError::ChainHashMismatch {
+            sequence: link.sequence,
+        });
+    }
+    Ok(())
+}
+
 fn build_integrity_chain_internal<S: SignatureProvider>(
     payloads: &[Vec<u8>],
     signer: Option<&S>,
@@ -145,29 +412,42 @@ fn build_integrity_chain_internal<S: SignatureProvider>(
     let mut previous_chain_hash = [0u8; 32];
 
     for (index, payload) in payloads.iter().enumerate() {
-        let sequence = index as u64;
-        let payload_hash = digest_bytes(payload);
-        let chain_hash = chain_hash(sequence, payload_hash, previous_chain_hash);
-        let previous_link_hash = if index == 0 {
-            None
-        } else {
-            Some(previous_chain_hash)
-        };
-        let signature = signer.and_then(|provider| provider.sign(sequence, &chain_hash));
-
-        links.push(IntegrityLink {
-            sequence,
-            payload_hash,
-            previous_chain_hash: previous_link_hash,
-            chain_hash,
-            signature,
-        });
-        previous_chain_hash = chain_hash;
+        let mut link = next_link(index as u64, payload, previous_chain_hash);
+        link.signature = signer.and_then(|provider| provider.sign(link.sequence, &link.chain_hash));
+        previous_chain_hash = link.chain_hash;
+        links.push(link);
     }
 
     IntegrityChain { links }
 }
 
+/// The unsigned link for `payload` after a link with `previous_chain_hash`
+/// (all zeroes before the first link).
+fn next_link(sequence: u64, payload: &[u8], previous_chain_hash: [u8; 32]) -> IntegrityLink {
+    let payload_hash = digest_bytes(payload);
+    IntegrityLink {
+        sequence,
+        payload_hash,
+        previous_chain_hash: (sequence != 0).then_some(previous_chain_hash),
+        chain_hash: chain_hash(sequence, payload_hash, previous_chain_hash),
+        signature: None,
+    }
+}
+
+fn write_sidecar_line(out: &mut String, link: &IntegrityLink) {
+    let signature = link
+        .signature
+        .as_deref()
+        .map_or_else(|| "-".to_owned(), hex);
+    let _ = writeln!(
+        out,
+        "{} {} {} {signature}",
+        link.sequence,
+        hex(&link.payload_hash),
+        hex(&link.chain_hash)
+    );
+}
+
 fn digest_bytes(bytes: &[u8]) -> [u8; 32] {
     let mut hasher = Sha256::new();
     hasher.update(bytes);
@@ -182,8 +462,27 @@ fn chain_hash(sequence: u64, payload_hash: [u8; 32], previous_chain_hash: [u8; 3
     hasher.finalize().into()
 }
 
-#[derive(Debug)]
-struct NoopSigner;
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn bytes_from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(text.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+fn hash_from_hex(text: &str) -> Option<[u8; 32]> {
+    bytes_from_hex(text)?.try_into().ok()
+}
+
+/// Signs nothing; the signer of unsigned chains.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSigner;
 
 impl SignatureProvider for NoopSigner {
     fn sign(&self, _sequence: u64, _chain_hash: &[u8; 32]) -> Option<Vec<u8>> {
@@ -193,9 +492,12 @@ impl SignatureProvider for NoopSigner {
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use crate::integrity::{
-        build_integrity_chain, build_integrity_chain_with_signer, verify_integrity_chain,
-        IntegrityError, SignatureProvider, SignatureVerifier,
+        build_integrity_chain, build_integrity_chain_with_signer, chain_sidecar_path,
+        verify_integrity_chain, verify_integrity_prefix, IntegrityChain, IntegrityChainBuilder,
+        IntegrityError, SignatureProvider, SignatureVerifier, VerifiedPrefix,
     };
 
     #[derive(Debug)]
@@ -271,4 +573,115 @@ mod tests {
             IntegrityError::MissingSignature { sequence: 0 }
         ));
     }
+
+    #[test]
+    fn sidecar_round_trips_signed_and_unsigned_links() {
+        let payloads = sample_payloads();
+        let mut chain = build_integrity_chain_with_signer(&payloads, &PrefixSigner);
+        chain.links[2].signature = None;
+
+        let sidecar = chain.to_sidecar();
+        assert!(sidecar.starts_with("takrec-chain 1\n0 "));
+        assert!(sidecar.lines().nth(3).expect("third link").ends_with(" -"));
+        assert_eq!(IntegrityChain::from_sidecar(&sidecar), Ok(chain));
+        assert_eq!(
+            chain_sidecar_path(Path::new("captures/session.takrec")),
+            Path::new("captures/session.takrec.chain")
+        );
+    }
+
+    #[test]
+    fn malformed_sidecar_reports_the_line() {
+        assert_eq!(
+            IntegrityChain::from_sidecar("0 aa bb -"),
+            Err(IntegrityError::MalformedSidecar { line: 1 })
+        );
+        let sidecar = build_integrity_chain(&sample_payloads()).to_sidecar();
+        let short_hash = sidecar.replacen(" -", "ff -", 1);
+        assert_eq!(
+            IntegrityChain::from_sidecar(&short_hash),
+            Err(IntegrityError::MalformedSidecar { line: 2 })
+        );
+        let extra_field = format!("{}1 2 3 4 5\n", sidecar);
+        assert_eq!(
+            IntegrityChain::from_sidecar(&extra_field),
+            Err(IntegrityError::MalformedSidecar { line: 5 })
+        );
+    }
+
+    #[test]
+    fn builder_matches_the_batch_chain_and_leaves_a_verifiable_prefix() {
+        let payloads = sample_payloads();
+        let mut builder = IntegrityChainBuilder::new(Vec::new())
+            .expect("builder should start")
+            .with_checkpoint_interval(2);
+        for payload in &payloads {
+            builder.push(payload).expect("link should be written");
+        }
+        assert_eq!(builder.links(), 3);
+        let crashed = String::from_utf8(builder.sidecar.clone()).expect("utf-8 sidecar");
+        let finished = String::from_utf8(builder.finish().expect("finish")).expect("utf-8 sidecar");
+        assert_eq!(finished, build_integrity_chain(&payloads).to_sidecar());
+
+        let prefix = IntegrityChain::from_sidecar(&crashed).expect("checkpointed prefix");
+        assert_eq!(prefix.links.len(), 2);
+        assert_eq!(
+            verify_integrity_prefix::<PrefixVerifier>(&payloads, &prefix, None, false),
+            Ok(VerifiedPrefix {
+                links: 2,
+                uncovered: 1,
+                signed_through: None,
+            })
+        );
+        assert_eq!(
+            verify_integrity_prefix::<PrefixVerifier>(&payloads[..1], &prefix, None, false),
+            Err(IntegrityError::PayloadCountMismatch {
+                payload_count: 1,
+                chain_len: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn builder_signs_checkpoints_and_restarts_per_file() {
+        let payloads = sample_payloads();
+        let mut builder = IntegrityChainBuilder::with_signer(Vec::new(), PrefixSigner)
+            .expect("builder should start")
+            .with_checkpoint_interval(2);
+        for payload in &payloads {
+            builder.push(payload).expect("link should be written");
+        }
+        let first = builder.restart(Vec::new()).expect("restart");
+        builder.push(&payloads[0]).expect("link should be written");
+        let second = builder.finish().expect("finish");
+
+        let first = IntegrityChain::from_sidecar(&String::from_utf8(first).expect("utf-8"))
+            .expect("first sidecar");
+        let signed: Vec<bool> = first.links.iter().map(|l| l.signature.is_some()).collect();
+        assert_eq!(signed, [false, true, true]);
+        assert_eq!(
+            verify_integrity_prefix(&payloads, &first, Some(&PrefixVerifier), true),
+            Ok(VerifiedPrefix {
+                links: 3,
+                uncovered: 0,
+                signed_through: Some(2),
+            })
+        );
+        assert_eq!(
+            verify_integrity_chain(&payloads, &first, Some(&PrefixVerifier), true),
+            Err(IntegrityError::MissingSignature { sequence: 0 })
+        );
+
+        let second = IntegrityChain::from_sidecar(&String::from_utf8(second).expect("utf-8"))
+            .expect("second sidecar");
+        assert_eq!(second.links.len(), 1);
+        assert_eq!(second.links[0].sequence, 0);
+
+        let mut unsigned_tail = first.clone();
+        unsigned_tail.links[2].signature = None;
+        assert_eq!(
+            verify_integrity_prefix(&payloads, &unsigned_tail, Some(&PrefixVerifier), true),
+            Err(IntegrityError::MissingSignature { sequence: 2 })
+        );
+    }
 }
@@ -1,8 +1,11 @@
 use std::io::{self, Read, Write};
 
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
 const PCAP_MAGIC_LE: u32 = 0xA1B2_C3D4;
+/// Magic of captures whose packet timestamps carry nanoseconds.
+const PCAP_MAGIC_NANOS_LE: u32 = 0xA1B2_3C4D;
 const PCAP_VERSION_MAJOR: u16 = 2;
 const PCAP_VERSION_MINOR: u16 = 4;
 const PCAP_THISZONE: i32 = 0;
@@ -90,6 +93,23 @@ pub enum InteropError {
     FieldTooLarge { field: &'static str, len: usize },
 }
 
+impl CodedError for InteropError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::new("RECORD", 201),
+            Self::TruncatedGlobalHeader => ErrorCode::new("RECORD", 202),
+            Self::InvalidMagic { .. } => ErrorCode::new("RECORD", 203),
+            Self::UnsupportedPcapVersion { .. } => ErrorCode::new("RECORD", 204),
+            Self::TruncatedPacketHeader => ErrorCode::new("RECORD", 205),
+            Self::TruncatedPacketPayload { .. } => ErrorCode::new("RECORD", 206),
+            Self::UnsupportedAnnotationVersion { .. } => ErrorCode::new("RECORD", 207),
+            Self::InvalidDirection { .. } => ErrorCode::new("RECORD", 208),
+            Self::InvalidDecodeStatus { .. } => ErrorCode::new("RECORD", 209),
+            Self::FieldTooLarge { .. } => ErrorCode::new("RECORD", 210),
+        }
+    }
+}
+
 pub fn export_annotations_to_pcap<W: Write>(
     mut sink: W,
     annotations: &[PcapAnnotation],
@@ -128,65 +148,148 @@ pub fn export_annotations_to_pcap<W: Write>(
 }
 
 pub fn import_annotations_from_pcap<R: Read>(
-    mut source: R,
+    source: R,
 ) -> Result<Vec<PcapAnnotation>, InteropError> {
-    let mut header = [0u8; 24];
-    read_exact_or_truncated(
-        &mut source,
-        &mut header,
-        InteropError::TruncatedGlobalHeader,
-    )?;
-
-    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
-    if magic != PCAP_MAGIC_LE {
-        return Err(InteropError::InvalidMagic { found: magic });
+    let mut packets = PcapReader::new(source)?;
+    let mut annotations = Vec::new();
+    while let Some(packet) = packets.next_packet()? {
+        annotations.push(decode_annotation_frame(
+            packet.timestamp_micros,
+            &packet.data,
+        )?);
+    }
+    Ok(annotations)
+}
+
+/// One packet record from a classic pcap file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PcapPacket {
+    pub(crate) timestamp_micros: u64,
+    pub(crate) data: Vec<u8>,
+    /// The snapshot length cut the packet short of its original length.
+    pub(crate) truncated: bool,
+}
+
+/// Reads the packet records of a classic libpcap file (not pcapng) in
+/// either byte order, with microsecond or nanosecond timestamps. The link
+/// type is reported, not checked.
+#[derive(Debug)]
+pub(crate) struct PcapReader<R> {
+    source: R,
+    big_endian: bool,
+    nanos: bool,
+    linktype: u32,
+    max_packet_bytes: Option<usize>,
+}
+
+impl<R: Read> PcapReader<R> {
+    pub(crate) fn new(mut source: R) -> Result<Self, InteropError> {
+        let mut header = [0u8; 24];
+        read_exact_or_truncated(
+            &mut source,
+            &mut header,
+            InteropError::TruncatedGlobalHeader,
+        )?;
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let (big_endian, nanos) = match (magic, magic.swap_bytes()) {
+            (PCAP_MAGIC_LE, _) => (false, false),
+            (PCAP_MAGIC_NANOS_LE, _) => (false, true),
+            (_, PCAP_MAGIC_LE) => (true, false),
+            (_, PCAP_MAGIC_NANOS_LE) => (true, true),
+            _ => return Err(InteropError::InvalidMagic { found: magic }),
+        };
+        let mut reader = Self {
+            source,
+            big_endian,
+            nanos,
+            linktype: 0,
+            max_packet_bytes: None,
+        };
+        let major = reader.u16_at(&header, 4);
+        let minor = reader.u16_at(&header, 6);
+        if major != PCAP_VERSION_MAJOR {
+            return Err(InteropError::UnsupportedPcapVersion { major, minor });
+        }
+        // The top bits carry FCS information in newer files.
+        reader.linktype = reader.u32_at(&header, 20) & 0x0FFF_FFFF;
+        Ok(reader)
     }
 
-    let major = u16::from_le_bytes([header[4], header[5]]);
-    let minor = u16::from_le_bytes([header[6], header[7]]);
-    if major != PCAP_VERSION_MAJOR || minor != PCAP_VERSION_MINOR {
-        return Err(InteropError::UnsupportedPcapVersion { major, minor });
+    /// Refuses packet records longer than `max_packet_bytes` with
+    /// [`InteropError::FieldTooLarge`] instead of reading them.
+    pub(crate) fn with_max_packet_bytes(mut self, max_packet_bytes: usize) -> Self {
+        self.max_packet_bytes = Some(max_packet_bytes);
+        self
     }
 
-    let mut annotations = Vec::new();
-    while let Some(packet_header) = read_packet_header(&mut source)? {
-        let ts_sec = u32::from_le_bytes([
-            packet_header[0],
-            packet_header[1],
-            packet_header[2],
-            packet_header[3],
-        ]);
-        let ts_usec = u32::from_le_bytes([
-            packet_header[4],
-            packet_header[5],
-            packet_header[6],
-            packet_header[7],
-        ]);
-        let incl_len = u32::from_le_bytes([
-            packet_header[8],
-            packet_header[9],
-            packet_header[10],
-            packet_header[11],
-        ]);
-        let incl_len_usize = incl_len as usize;
-
-        let mut payload = vec![0u8; incl_len_usize];
-        let read = source.read(&mut payload)?;
-        if read < incl_len_usize {
+    pub(crate) fn linktype(&self) -> u32 {
+        self.linktype
+    }
+
+    pub(crate) fn next_packet(&mut self) -> Result<Option<PcapPacket>, InteropError> {
+        let mut header = [0u8; PACKET_HEADER_LEN];
+        if self.source.read(&mut header[..1])? == 0 {
+            return Ok(None);
+        }
+        read_exact_or_truncated(
+            &mut self.source,
+            &mut header[1..],
+            InteropError::TruncatedPacketHeader,
+        )?;
+        let seconds = u64::from(self.u32_at(&header, 0));
+        let fraction = u64::from(self.u32_at(&header, 4));
+        let included = self.u32_at(&header, 8) as usize;
+        let original = self.u32_at(&header, 12) as usize;
+        if self.max_packet_bytes.is_some_and(|max| included > max) {
+            return Err(InteropError::FieldTooLarge {
+                field: "incl_len",
+                len: included,
+            });
+        }
+
+        // Grown as bytes arrive, so a lying length cannot force a large
+        // allocation up front.
+        let mut data = Vec::new();
+        let read = (&mut self.source)
+            .take(included as u64)
+            .read_to_end(&mut data)?;
+        if read < included {
             return Err(InteropError::TruncatedPacketPayload {
-                expected: incl_len_usize,
+                expected: included,
                 actual: read,
             });
         }
+        let fraction_micros = if self.nanos {
+            fraction / 1_000
+        } else {
+            fraction
+        };
+        Ok(Some(PcapPacket {
+            timestamp_micros: seconds
+                .saturating_mul(1_000_000)
+                .saturating_add(fraction_micros),
+            data,
+            truncated: included < original,
+        }))
+    }
 
-        let timestamp_micros = u64::from(ts_sec)
-            .saturating_mul(1_000_000)
-            .saturating_add(u64::from(ts_usec));
-        let annotation = decode_annotation_frame(timestamp_micros, &payload)?;
-        annotations.push(annotation);
+    fn u16_at(&self, bytes: &[u8], at: usize) -> u16 {
+        let raw = [bytes[at], bytes[at + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(raw)
+        } else {
+            u16::from_le_bytes(raw)
+        }
     }
 
-    Ok(annotations)
+    fn u32_at(&self, bytes: &[u8], at: usize) -> u32 {
+        let raw = [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+        if self.big_endian {
+            u32::from_be_bytes(raw)
+        } else {
+            u32::from_le_bytes(raw)
+        }
+    }
 }
 
 fn encode_annotation_frame(annotation: &PcapAnnotation) -> Result<Vec<u8>, InteropError> {
@@ -273,25 +376,6 @@ fn decode_annotation_frame(
     })
 }
 
-fn read_packet_header<R: Read>(
-    source: &mut R,
-) -> Result<Option<[u8; PACKET_HEADER_LEN]>, InteropError> {
-    let mut header = [0u8; PACKET_HEADER_LEN];
-    let first = source.read(&mut header[..1])?;
-    if first == 0 {
-        return Ok(None);
-    }
-
-    source
-        .read_exact(&mut header[1..])
-        .map_err(|error| match error.kind() {
-            io::ErrorKind::UnexpectedEof => InteropError::TruncatedPacketHeader,
-            _ => InteropError::Io(error),
-        })?;
-
-    Ok(Some(header))
-}
-
 fn read_exact_or_truncated<R: Read>(
     source: &mut R,
     bytes: &mut [u8],
@@ -421,6 +505,10 @@ mod tests {
         bytes.truncate(bytes.len() - 3);
 
         let error = import_annotations_from_pcap(bytes.as_slice()).expect_err("must fail");
-        assert!(matches!(error, InteropError::TruncatedPacketPayload { .. }));
+        assert!(matches!(
+            error,
+            InteropError::TruncatedPacketPayload { expected, actual }
+                if actual == expected - 3
+        ));
     }
 }
@@ -1,26 +1,56 @@
+pub mod digest;
 pub mod index;
 pub mod integrity;
 pub mod interop;
+pub mod pcap;
+pub mod reader;
+pub mod rotate;
+pub mod scrub;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod stats;
 pub mod writer;
 
 use std::io::Write;
+use std::sync::{Mutex, PoisonError};
 
 use bytes::Bytes;
-use rustak_io::{MessageEnvelope, MessageSink, MessageSource};
+use futures::future::BoxFuture;
+use rustak_io::{IoError, MessageEnvelope, MessageSink, MessageSource};
 
+pub use digest::{replay_digest, ReplayDigest, ReplayDigester};
 pub use index::{
     format_rebuild_diagnostics, rebuild_index, ChunkIndex, ChunkIndexEntry, RebuildDiagnostics,
 };
 pub use integrity::{
-    build_integrity_chain, build_integrity_chain_with_signer, verify_integrity_chain,
-    IntegrityChain, IntegrityError, IntegrityLink, SignatureProvider, SignatureVerifier,
+    build_integrity_chain, build_integrity_chain_with_signer, chain_sidecar_path,
+    verify_integrity_chain, verify_integrity_prefix, IntegrityChain, IntegrityChainBuilder,
+    IntegrityError, IntegrityLink, NoopSigner, SignatureProvider, SignatureVerifier,
+    VerifiedPrefix, DEFAULT_CHECKPOINT_INTERVAL,
 };
 pub use interop::{
     export_annotations_to_pcap, import_annotations_from_pcap, DecodeStatus, InteropError,
     PcapAnnotation, TrafficDirection,
 };
+pub use pcap::{
+    import_pcap, ImportTransport, ImportedFrame, ImportedFraming, PcapImportConfig,
+    PcapImportError, PcapImportReport, DEFAULT_TAK_PORTS,
+};
+pub use reader::TakrecReader;
+pub use rotate::{
+    RetentionPolicy, RotateError, RotatedFile, RotatingTakrecConfig, RotatingTakrecWriter,
+    RotationPolicy, RotationSummary,
+};
+pub use scrub::{scrub_recording, CoordinateOffset, ScrubConfig, ScrubError, ScrubReport};
+#[cfg(feature = "signing")]
+pub use signing::{DetachedAlgorithm, DetachedSigner, DetachedVerifier};
+pub use stats::{
+    recording_stats, Gap, GapSummary, KeyCount, KeySummary, RateBin, RateHistogram, RecordingStats,
+    StatsConfig, StatsError, StatsReport,
+};
 pub use writer::{
-    recover_chunk_index, ChunkCommit, RecordWriteError, RecoveryReport, TakrecHeader, TakrecWriter,
+    for_each_chunk, recover_chunk_index, recover_chunk_payloads, ChunkCommit, ChunkCompression,
+    ChunkScan, RecordWriteError, RecoveryReport, TakrecHeader, TakrecWriter,
     DEFAULT_MAX_CHUNK_BYTES,
 };
 
@@ -33,15 +63,63 @@ pub fn append_envelope_chunk<W: Write>(
     envelope: &RecordEnvelope<Bytes>,
 ) -> Result<ChunkCommit, RecordWriteError> {
     let payload = envelope.raw_frame.as_deref().unwrap_or(&envelope.message);
-    writer.append_chunk(payload)
+    writer.append_chunk_at(payload, envelope.observed.wall)
+}
+
+/// Adapts a [`TakrecWriter`] to [`MessageSink`], e.g. as the target of a
+/// transport frame tap. Each envelope becomes one chunk, as with
+/// [`append_envelope_chunk`]. Appends block the calling task.
+#[derive(Debug)]
+pub struct TakrecSink<W: Write> {
+    writer: Mutex<TakrecWriter<W>>,
+}
+
+impl<W: Write> TakrecSink<W> {
+    #[must_use]
+    pub fn new(writer: TakrecWriter<W>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> TakrecWriter<W> {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Write + Send> MessageSink<Bytes> for TakrecSink<W> {
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+        self.send_envelope(MessageEnvelope::new(msg))
+    }
+
+    fn send_envelope(&self, env: MessageEnvelope<Bytes>) -> BoxFuture<'_, Result<(), IoError>> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = match append_envelope_chunk(&mut writer, &env) {
+            Ok(_) => Ok(()),
+            Err(RecordWriteError::Io(error)) => Err(IoError::Io(error)),
+            Err(error) => Err(IoError::Other(error.to_string())),
+        };
+        Box::pin(async move { result })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use bytes::Bytes;
 
-    use crate::{append_envelope_chunk, TakrecHeader, TakrecWriter};
-    use rustak_io::MessageEnvelope;
+    use crate::{
+        append_envelope_chunk, recover_chunk_payloads, TakrecHeader, TakrecSink, TakrecWriter,
+    };
+    use rustak_io::{MessageEnvelope, MessageSink};
+
+    /// A minimal CoT event stamped with `time`, shared by the module tests.
+    pub(crate) fn dated_event(time: &str, remarks: &str) -> Vec<u8> {
+        format!("<event version=\"2.0\" uid=\"a\" type=\"a-f-G\" time=\"{time}\" start=\"{time}\" stale=\"{time}\"><point lat=\"1\" lon=\"2\"/><detail><remarks>{remarks}</remarks></detail></event>")
+            .into_bytes()
+    }
 
     #[test]
     fn append_envelope_chunk_prefers_raw_frame() {
@@ -64,4 +142,35 @@ mod tests {
         let commit = append_envelope_chunk(&mut writer, &envelope).expect("append should succeed");
         assert_eq!(commit.payload_len, 13);
     }
+
+    #[test]
+    fn takrec_sink_appends_each_envelope_as_a_chunk() {
+        let writer = TakrecWriter::new(Vec::new(), TakrecHeader::default())
+            .expect("writer should initialize");
+        let sink = TakrecSink::new(writer.with_max_chunk_bytes(16));
+
+        futures::executor::block_on(async {
+            sink.send(Bytes::from_static(b"<event/>"))
+                .await
+                .expect("send should append");
+            sink.send_envelope(
+                MessageEnvelope::new(Bytes::from_static(b"decoded"))
+                    .with_raw_frame(Bytes::from_static(b"raw-frame")),
+            )
+            .await
+            .expect("send should append");
+            assert!(sink
+                .send(Bytes::from_static(b"over the chunk limit"))
+                .await
+                .is_err());
+        });
+
+        let bytes = sink
+            .into_inner()
+            .into_inner()
+            .expect("writer should finish");
+        let (report, payloads) = recover_chunk_payloads(bytes.as_slice()).expect("recover");
+        assert!(!report.truncated_tail);
+        assert_eq!(payloads, [b"<event/>".to_vec(), b"raw-frame".to_vec()]);
+    }
 }
@@ -0,0 +1,1132 @@
+//! Importing raw packet captures of TAK traffic into takrec.
+//!
+//! [`import_pcap`] reads a classic libpcap file (not pcapng), keeps the UDP
+//! datagrams and TCP segments with a configured TAK port on either end, and
+//! splits them into frames the way a live receiver would:
+//!
+//! - a UDP datagram is one frame: CoT XML, or TAK protocol v1 behind the
+//!   `0xBF <version> 0xBF` mesh header, recorded whole as `rustak record`
+//!   records datagrams;
+//! - TCP segments are reassembled per direction, and each frame's framing is
+//!   detected from its first byte, so a stream that upgrades from XML to TAK
+//!   protocol part way through splits correctly: `<` is XML up to the next
+//!   `</event>`, `0xBF` is the `0xBF <varint length>` streaming header, and
+//!   anything else is tried as a big-endian `u32` length prefix. Streamed
+//!   frames are recorded without their header.
+//!
+//! Every frame is reported with a [`DecodeStatus`]: `Decoded` when the
+//! payload parses as CoT, `Malformed` when the framing was recognised but
+//! the payload does not parse or the stream ends mid-frame, and `Opaque`
+//! when the bytes are not TAK framing at all. Complete frames are written to
+//! the takrec whether they decode or not; cut-off frames and opaque bytes
+//! are only reported. A TCP direction whose bytes go missing, or whose
+//! framing cannot be followed, is abandoned until its next SYN.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, UNIX_EPOCH};
+
+use rustak_core::CotEvent;
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use thiserror::Error;
+
+use crate::interop::{DecodeStatus, InteropError, PcapPacket, PcapReader};
+use crate::{ChunkCompression, RecordWriteError, TakrecHeader, TakrecWriter};
+
+/// Ports TAK clients use for SA and chat without TLS.
+pub const DEFAULT_TAK_PORTS: &[u16] = &[4242, 6969, 8087, 17012];
+
+/// Largest snapshot length libpcap writes.
+const MAX_PACKET_BYTES: usize = 262_144;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+
+const STREAM_MAGIC: u8 = 0xBF;
+const XML_EVENT_END: &[u8] = b"</event>";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapImportConfig {
+    /// UDP and TCP ports whose traffic is imported, matched on either end.
+    pub ports: Vec<u16>,
+    /// Frame size bounds (`max_xml_scan_bytes`, `max_protobuf_bytes`) and
+    /// the limits CoT is decoded under.
+    pub limits: Limits,
+    /// Out-of-order TCP bytes held per direction while waiting for a
+    /// retransmission to fill the gap. Past this the stream is abandoned.
+    pub max_reorder_bytes: usize,
+}
+
+impl Default for PcapImportConfig {
+    fn default() -> Self {
+        Self {
+            ports: DEFAULT_TAK_PORTS.to_vec(),
+            limits: Limits::conservative_defaults(),
+            max_reorder_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+impl PcapImportConfig {
+    pub fn validate(&self) -> Result<(), PcapImportError> {
+        if self.ports.is_empty() {
+            return Err(PcapImportError::InvalidConfig { field: "ports" });
+        }
+        if self.max_reorder_bytes == 0 {
+            return Err(PcapImportError::InvalidConfig {
+                field: "max_reorder_bytes",
+            });
+        }
+        self.limits
+            .validate()
+            .map_err(|_| PcapImportError::InvalidConfig { field: "limits" })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PcapImportError {
+    #[error(transparent)]
+    Pcap(#[from] InteropError),
+
+    #[error(transparent)]
+    Record(#[from] RecordWriteError),
+
+    #[error("pcap link type {linktype} is not supported")]
+    UnsupportedLinkType { linktype: u32 },
+
+    #[error("pcap import config field `{field}` is invalid")]
+    InvalidConfig { field: &'static str },
+}
+
+impl CodedError for PcapImportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Pcap(error) => error.code(),
+            Self::Record(error) => error.code(),
+            Self::UnsupportedLinkType { .. } => ErrorCode::new("RECORD", 601),
+            Self::InvalidConfig { .. } => ErrorCode::new("RECORD", 602),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportTransport {
+    Udp,
+    Tcp,
+}
+
+/// How a frame was delimited on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportedFraming {
+    /// CoT XML: a whole datagram, or up to `</event>` on a stream.
+    Xml,
+    /// `0xBF <version> 0xBF` ahead of a TAK protocol datagram.
+    MeshHeader,
+    /// `0xBF <varint length>` ahead of a streamed TAK protocol payload.
+    StreamHeader,
+    /// Big-endian `u32` length ahead of a streamed TAK protocol payload.
+    U32LengthPrefixed,
+    /// Not TAK framing; the bytes are reported but not recorded.
+    Unknown,
+}
+
+/// One frame found in the capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFrame {
+    /// Capture time of the packet that completed the frame.
+    pub timestamp_micros: u64,
+    pub transport: ImportTransport,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub framing: ImportedFraming,
+    pub decode_status: DecodeStatus,
+    /// Bytes recorded, or for opaque data the bytes skipped.
+    pub len: usize,
+    /// Chunk sequence in the takrec, for recorded frames.
+    pub chunk: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PcapImportReport {
+    pub packets: u64,
+    /// Packets that are not UDP or TCP on a TAK port, are IP fragments or
+    /// were cut short by the snapshot length.
+    pub skipped_packets: u64,
+    pub tcp_streams: usize,
+    /// TCP directions abandoned because bytes went missing or the framing
+    /// could not be followed.
+    pub abandoned_streams: usize,
+    pub frames: Vec<ImportedFrame>,
+}
+
+impl PcapImportReport {
+    #[must_use]
+    pub fn count(&self, status: DecodeStatus) -> usize {
+        self.frames
+            .iter()
+            .filter(|frame| frame.decode_status == status)
+            .count()
+    }
+
+    /// Frames written to the takrec.
+    #[must_use]
+    pub fn chunks(&self) -> usize {
+        self.frames
+            .iter()
+            .filter(|frame| frame.chunk.is_some())
+            .count()
+    }
+}
+
+/// Reads a pcap from `source` and writes its TAK frames to `sink` as a
+/// takrec with `header`, whose creation time is replaced by the first
+/// packet's capture time.
+pub fn import_pcap<R: Read, W: Write>(
+    source: R,
+    sink: W,
+    header: TakrecHeader,
+    config: &PcapImportConfig,
+) -> Result<PcapImportReport, PcapImportError> {
+    config.validate()?;
+    let mut packets = open_capture(source)?;
+    let first = packets.next_packet()?;
+    let created = first.as_ref().map_or(header.created_unix_nanos, |packet| {
+        packet.timestamp_micros.saturating_mul(1_000)
+    });
+    let writer = TakrecWriter::with_capture_times(
+        sink,
+        TakrecHeader {
+            created_unix_nanos: created,
+            ..header
+        },
+        ChunkCompression::None,
+    )?;
+    let mut import = Importer {
+        config,
+        writer,
+        streams: HashMap::new(),
+        report: PcapImportReport::default(),
+    };
+
+    let mut next = first;
+    while let Some(packet) = next {
+        import.packet(packets.linktype(), &packet)?;
+        next = packets.next_packet()?;
+    }
+    import.finish()
+}
+
+/// Opens `source` as a pcap whose link type the importer can unwrap.
+fn open_capture<R: Read>(source: R) -> Result<PcapReader<R>, PcapImportError> {
+    let packets = PcapReader::new(source)?.with_max_packet_bytes(MAX_PACKET_BYTES);
+    let linktype = packets.linktype();
+    if !matches!(
+        linktype,
+        LINKTYPE_NULL
+            | LINKTYPE_ETHERNET
+            | LINKTYPE_RAW
+            | LINKTYPE_LINUX_SLL
+            | LINKTYPE_IPV4
+            | LINKTYPE_IPV6
+            | LINKTYPE_LINUX_SLL2
+    ) {
+        return Err(PcapImportError::UnsupportedLinkType { linktype });
+    }
+    Ok(packets)
+}
+
+/// One direction of a TCP connection.
+type FlowKey = (SocketAddr, SocketAddr);
+
+struct Segment<'a> {
+    transport: ImportTransport,
+    source: SocketAddr,
+    destination: SocketAddr,
+    sequence: u32,
+    flags: u8,
+    payload: &'a [u8],
+}
+
+#[derive(Default)]
+struct TcpStream {
+    /// Sequence number of stream offset 0.
+    base: Option<u32>,
+    /// Next stream offset expected.
+    next: u64,
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_bytes: usize,
+    buffer: Vec<u8>,
+    last_micros: u64,
+    abandoned: bool,
+}
+
+struct Importer<'a, W: Write> {
+    config: &'a PcapImportConfig,
+    writer: TakrecWriter<W>,
+    streams: HashMap<FlowKey, TcpStream>,
+    report: PcapImportReport,
+}
+
+impl<W: Write> Importer<'_, W> {
+    fn packet(&mut self, linktype: u32, packet: &PcapPacket) -> Result<(), PcapImportError> {
+        self.report.packets += 1;
+        let segment = if packet.truncated {
+            None
+        } else {
+            link_payload(linktype, &packet.data)
+                .and_then(ip_payload)
+                .filter(|segment| {
+                    self.config.ports.contains(&segment.source.port())
+                        || self.config.ports.contains(&segment.destination.port())
+                })
+        };
+        let Some(segment) = segment else {
+            self.report.skipped_packets += 1;
+            return Ok(());
+        };
+        match segment.transport {
+            ImportTransport::Udp => self.datagram(packet.timestamp_micros, &segment),
+            ImportTransport::Tcp => self.segment(packet.timestamp_micros, &segment),
+        }
+    }
+
+    fn datagram(&mut self, micros: u64, segment: &Segment<'_>) -> Result<(), PcapImportError> {
+        let payload = segment.payload;
+        let frame = Frame {
+            micros,
+            transport: ImportTransport::Udp,
+            source: segment.source,
+            destination: segment.destination,
+        };
+        let limits = &self.config.limits;
+        if payload.len() > 3 && payload[0] == STREAM_MAGIC && payload[2] == STREAM_MAGIC {
+            let status = decode_protobuf(&payload[3..], limits);
+            self.record(&frame, ImportedFraming::MeshHeader, status, payload)
+        } else if payload.trim_ascii_start().starts_with(b"<") {
+            let status = decode_xml(payload, limits);
+            self.record(&frame, ImportedFraming::Xml, status, payload)
+        } else if !payload.is_empty() {
+            self.opaque(&frame, payload.len());
+            Ok(())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn segment(&mut self, micros: u64, segment: &Segment<'_>) -> Result<(), PcapImportError> {
+        let key = (segment.source, segment.destination);
+        let syn = segment.flags & TCP_SYN != 0;
+        let max_reorder = self.config.max_reorder_bytes;
+        let stream = match self.streams.get_mut(&key) {
+            // A SYN after an abandoned stream starts a new connection.
+            Some(stream) if stream.abandoned && syn => {
+                *stream = TcpStream::default();
+                self.report.tcp_streams += 1;
+                stream
+            }
+            Some(stream) => stream,
+            None => {
+                self.report.tcp_streams += 1;
+                self.streams.entry(key).or_default()
+            }
+        };
+        if stream.abandoned {
+            return Ok(());
+        }
+        stream.last_micros = micros;
+        let sequence = segment.sequence.wrapping_add(u32::from(syn));
+        let base = *stream.base.get_or_insert(sequence);
+        let offset = u64::from(sequence.wrapping_sub(base));
+
+        let mut overflowed = false;
+        if !segment.payload.is_empty() {
+            if offset <= stream.next {
+                stream.append(offset, segment.payload);
+                stream.drain_pending();
+            } else if stream.pending_bytes + segment.payload.len() <= max_reorder {
+                stream.pending_bytes += segment.payload.len();
+                stream.pending.insert(offset, segment.payload.to_vec());
+            } else {
+                overflowed = true;
+            }
+        }
+
+        self.split_stream(key)?;
+        if overflowed {
+            self.abandon(key);
+        } else if segment.flags & (TCP_FIN | TCP_RST) != 0 {
+            self.close_stream(key);
+            self.streams.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Records every complete frame buffered for `key`.
+    fn split_stream(&mut self, key: FlowKey) -> Result<(), PcapImportError> {
+        loop {
+            let Some(stream) = self.streams.get_mut(&key) else {
+                return Ok(());
+            };
+            if stream.abandoned {
+                return Ok(());
+            }
+            let frame = Frame::tcp(key, stream.last_micros);
+            let start = stream.buffer.len() - stream.buffer.trim_ascii_start().len();
+            stream.buffer.drain(..start);
+            match next_stream_frame(&stream.buffer, &self.config.limits) {
+                StreamFrame::Incomplete => return Ok(()),
+                StreamFrame::Complete {
+                    framing,
+                    header,
+                    len,
+                } => {
+                    let bytes: Vec<u8> = stream.buffer.drain(..header + len).skip(header).collect();
+                    let limits = &self.config.limits;
+                    let status = match framing {
+                        ImportedFraming::Xml => decode_xml(&bytes, limits),
+                        _ => decode_protobuf(&bytes, limits),
+                    };
+                    self.record(&frame, framing, status, &bytes)?;
+                }
+                StreamFrame::Unfollowable {
+                    framing: ImportedFraming::Unknown,
+                } => match find_xml_start(&stream.buffer) {
+                    // Resynchronise on the next event, as a capture that
+                    // starts mid-connection needs to.
+                    Some(skip) => {
+                        stream.buffer.drain(..skip);
+                        self.opaque(&frame, skip);
+                    }
+                    None => {
+                        self.abandon(key);
+                        return Ok(());
+                    }
+                },
+                StreamFrame::Unfollowable { .. } => {
+                    self.abandon(key);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Stops following `key`, reporting what was buffered. Later segments
+    /// are ignored until a new SYN.
+    fn abandon(&mut self, key: FlowKey) {
+        self.report_leftover(key);
+        if let Some(stream) = self.streams.get_mut(&key) {
+            stream.abandoned = true;
+            stream.pending.clear();
+            stream.pending_bytes = 0;
+            self.report.abandoned_streams += 1;
+        }
+    }
+
+    /// Ends a stream that closed or outlived the capture.
+    fn close_stream(&mut self, key: FlowKey) {
+        match self.streams.get(&key) {
+            Some(stream) if stream.abandoned => {}
+            Some(stream) if !stream.pending.is_empty() => self.abandon(key),
+            Some(_) => self.report_leftover(key),
+            None => {}
+        }
+    }
+
+    /// Reports the incomplete frame left in `key`'s buffer, if any.
+    fn report_leftover(&mut self, key: FlowKey) {
+        let Some(stream) = self.streams.get_mut(&key) else {
+            return;
+        };
+        let frame = Frame::tcp(key, stream.last_micros);
+        let leftover = std::mem::take(&mut stream.buffer);
+        let leftover = leftover.trim_ascii();
+        let framing = match leftover.first() {
+            None => return,
+            Some(b'<') => ImportedFraming::Xml,
+            Some(&STREAM_MAGIC) => ImportedFraming::StreamHeader,
+            Some(_) => return self.opaque(&frame, leftover.len()),
+        };
+        self.report.frames.push(frame.report(
+            framing,
+            DecodeStatus::Malformed,
+            leftover.len(),
+            None,
+        ));
+    }
+
+    fn record(
+        &mut self,
+        frame: &Frame,
+        framing: ImportedFraming,
+        status: DecodeStatus,
+        payload: &[u8],
+    ) -> Result<(), PcapImportError> {
+        let captured = UNIX_EPOCH + Duration::from_micros(frame.micros);
+        let commit = self.writer.append_chunk_at(payload, captured)?;
+        self.report.frames.push(frame.report(
+            framing,
+            status,
+            payload.len(),
+            Some(commit.sequence),
+        ));
+        Ok(())
+    }
+
+    fn opaque(&mut self, frame: &Frame, len: usize) {
+        self.report.frames.push(frame.report(
+            ImportedFraming::Unknown,
+            DecodeStatus::Opaque,
+            len,
+            None,
+        ));
+    }
+
+    fn finish(mut self) -> Result<PcapImportReport, PcapImportError> {
+        let mut open: Vec<FlowKey> = self.streams.keys().copied().collect();
+        open.sort_by_key(|key| (self.streams[key].last_micros, *key));
+        for key in open {
+            self.close_stream(key);
+        }
+        self.writer.into_inner()?;
+        Ok(self.report)
+    }
+}
+
+impl TcpStream {
+    /// Appends the part of the segment at `offset` past what is buffered.
+    fn append(&mut self, offset: u64, bytes: &[u8]) {
+        let end = offset + bytes.len() as u64;
+        if end > self.next {
+            let skip = usize::try_from(self.next - offset).unwrap_or(usize::MAX);
+            self.buffer.extend_from_slice(&bytes[skip..]);
+            self.next = end;
+        }
+    }
+
+    fn drain_pending(&mut self) {
+        while let Some(entry) = self.pending.first_entry() {
+            let offset = *entry.key();
+            if offset > self.next {
+                return;
+            }
+            let bytes = entry.remove();
+            self.pending_bytes -= bytes.len();
+            self.append(offset, &bytes);
+        }
+    }
+}
+
+struct Frame {
+    micros: u64,
+    transport: ImportTransport,
+    source: SocketAddr,
+    destination: SocketAddr,
+}
+
+impl Frame {
+    fn tcp(key: FlowKey, micros: u64) -> Self {
+        Self {
+            micros,
+            transport: ImportTransport::Tcp,
+            source: key.0,
+            destination: key.1,
+        }
+    }
+
+    fn report(
+        &self,
+        framing: ImportedFraming,
+        decode_status: DecodeStatus,
+        len: usize,
+        chunk: Option<u64>,
+    ) -> ImportedFrame {
+        ImportedFrame {
+            timestamp_micros: self.micros,
+            transport: self.transport,
+            source: self.source,
+            destination: self.destination,
+            framing,
+            decode_status,
+            len,
+            chunk,
+        }
+    }
+}
+
+enum StreamFrame {
+    Incomplete,
+    /// `header` bytes of framing, then `len` bytes of frame.
+    Complete {
+        framing: ImportedFraming,
+        header: usize,
+        len: usize,
+    },
+    /// Over the size limit, or not framing we know.
+    Unfollowable {
+        framing: ImportedFraming,
+    },
+}
+
+/// Finds the frame at the start of `buffer`, which has no leading
+/// whitespace.
+fn next_stream_frame(buffer: &[u8], limits: &Limits) -> StreamFrame {
+    let Some(&first) = buffer.first() else {
+        return StreamFrame::Incomplete;
+    };
+    match first {
+        b'<' => {
+            let scan = &buffer[..buffer.len().min(limits.max_xml_scan_bytes)];
+            match scan
+                .windows(XML_EVENT_END.len())
+                .position(|window| window == XML_EVENT_END)
+            {
+                Some(at) => StreamFrame::Complete {
+                    framing: ImportedFraming::Xml,
+                    header: 0,
+                    len: at + XML_EVENT_END.len(),
+                },
+                None if buffer.len() >= limits.max_xml_scan_bytes => StreamFrame::Unfollowable {
+                    framing: ImportedFraming::Xml,
+                },
+                None => StreamFrame::Incomplete,
+            }
+        }
+        STREAM_MAGIC => match read_varint(&buffer[1..]) {
+            None => StreamFrame::Incomplete,
+            Some(Err(())) => StreamFrame::Unfollowable {
+                framing: ImportedFraming::StreamHeader,
+            },
+            Some(Ok((len, used))) => {
+                length_prefixed(buffer, ImportedFraming::StreamHeader, 1 + used, len, limits)
+            }
+        },
+        _ => match buffer.get(..4) {
+            None => StreamFrame::Incomplete,
+            Some(prefix) => {
+                let len = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]);
+                if len == 0 {
+                    StreamFrame::Unfollowable {
+                        framing: ImportedFraming::Unknown,
+                    }
+                } else {
+                    match length_prefixed(
+                        buffer,
+                        ImportedFraming::U32LengthPrefixed,
+                        4,
+                        u64::from(len),
+                        limits,
+                    ) {
+                        // A length this large is more likely not a prefix.
+                        StreamFrame::Unfollowable { .. } => StreamFrame::Unfollowable {
+                            framing: ImportedFraming::Unknown,
+                        },
+                        found => found,
+                    }
+                }
+            }
+        },
+    }
+}
+
+fn length_prefixed(
+    buffer: &[u8],
+    framing: ImportedFraming,
+    header: usize,
+    len: u64,
+    limits: &Limits,
+) -> StreamFrame {
+    match usize::try_from(len) {
+        Ok(len) if len <= limits.max_protobuf_bytes => {
+            if buffer.len() >= header + len {
+                StreamFrame::Complete {
+                    framing,
+                    header,
+                    len,
+                }
+            } else {
+                StreamFrame::Incomplete
+            }
+        }
+        _ => StreamFrame::Unfollowable { framing },
+    }
+}
+
+/// Protobuf base-128 varint: `None` when more bytes are needed, `Err` when
+/// it runs past ten bytes.
+fn read_varint(bytes: &[u8]) -> Option<Result<(u64, usize), ()>> {
+    let mut value = 0u64;
+    for (index, byte) in bytes.iter().enumerate() {
+        if index == 10 {
+            return Some(Err(()));
+        }
+        value |= u64::from(byte & 0x7F) << (7 * index);
+        if byte & 0x80 == 0 {
+            return Some(Ok((value, index + 1)));
+        }
+    }
+    if bytes.len() >= 10 {
+        Some(Err(()))
+    } else {
+        None
+    }
+}
+
+/// Where the next `<event` or `<?xml` starts.
+fn find_xml_start(buffer: &[u8]) -> Option<usize> {
+    [&b"<?xml"[..], b"<event"]
+        .iter()
+        .filter_map(|marker| {
+            buffer
+                .windows(marker.len())
+                .position(|window| window == *marker)
+        })
+        .min()
+}
+
+fn decode_xml(payload: &[u8], limits: &Limits) -> DecodeStatus {
+    let decoded = std::str::from_utf8(payload)
+        .ok()
+        .is_some_and(|xml| CotEvent::from_xml(xml.trim(), limits).is_ok());
+    if decoded {
+        DecodeStatus::Decoded
+    } else {
+        DecodeStatus::Malformed
+    }
+}
+
+fn decode_protobuf(payload: &[u8], limits: &Limits) -> DecodeStatus {
+    if rustak_proto::decode_v1_event(payload, limits).is_ok() {
+        DecodeStatus::Decoded
+    } else {
+        DecodeStatus::Malformed
+    }
+}
+
+/// The IP packet inside a link-layer frame.
+fn link_payload(linktype: u32, data: &[u8]) -> Option<&[u8]> {
+    match linktype {
+        LINKTYPE_NULL => data.get(4..),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(data),
+        LINKTYPE_LINUX_SLL => ethertype_payload(u16_be(data, 14)?, data.get(16..)?),
+        LINKTYPE_LINUX_SLL2 => ethertype_payload(u16_be(data, 0)?, data.get(20..)?),
+        LINKTYPE_ETHERNET => {
+            let mut at = 12;
+            let mut ethertype = u16_be(data, at)?;
+            while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ) {
+                at += 4;
+                ethertype = u16_be(data, at)?;
+            }
+            ethertype_payload(ethertype, data.get(at + 2..)?)
+        }
+        _ => None,
+    }
+}
+
+fn ethertype_payload(ethertype: u16, payload: &[u8]) -> Option<&[u8]> {
+    matches!(ethertype, ETHERTYPE_IPV4 | ETHERTYPE_IPV6).then_some(payload)
+}
+
+/// The UDP or TCP segment in an IP packet. Fragments are skipped.
+fn ip_payload(packet: &[u8]) -> Option<Segment<'_>> {
+    let version = packet.first()? >> 4;
+    let (source, destination, mut protocol, mut body) = match version {
+        4 => {
+            let header_len = usize::from(packet[0] & 0x0F) * 4;
+            let total_len = usize::from(u16_be(packet, 2)?);
+            let fragment = u16_be(packet, 6)?;
+            // More-fragments flag or a non-zero offset.
+            if fragment & 0x3FFF != 0 || header_len < 20 {
+                return None;
+            }
+            let source = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).ok()?);
+            let destination = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).ok()?);
+            // Ethernet pads short packets past the IP total length.
+            let body = packet.get(header_len..total_len.min(packet.len()))?;
+            (IpAddr::V4(source), IpAddr::V4(destination), packet[9], body)
+        }
+        6 => {
+            let payload_len = usize::from(u16_be(packet, 4)?);
+            let source = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(8..24)?).ok()?);
+            let destination = Ipv6Addr::from(<[u8; 16]>::try_from(packet.get(24..40)?).ok()?);
+            let body = packet.get(40..(40 + payload_len).min(packet.len()))?;
+            (IpAddr::V6(source), IpAddr::V6(destination), packet[6], body)
+        }
+        _ => return None,
+    };
+    // IPv6 hop-by-hop, routing and destination options headers; a fragment
+    // header (44) falls through to the protocol check and is skipped.
+    while version == 6 && matches!(protocol, 0 | 43 | 60) {
+        let len = (usize::from(*body.get(1)?) + 1) * 8;
+        protocol = *body.first()?;
+        body = body.get(len..)?;
+    }
+    let source_port = u16_be(body, 0)?;
+    let destination_port = u16_be(body, 2)?;
+    let (transport, sequence, flags, payload) = match protocol {
+        IP_PROTO_UDP => {
+            let len = usize::from(u16_be(body, 4)?);
+            let payload = body.get(8..len.clamp(8, body.len()))?;
+            (ImportTransport::Udp, 0, 0, payload)
+        }
+        IP_PROTO_TCP => {
+            let sequence = u32::from_be_bytes(<[u8; 4]>::try_from(body.get(4..8)?).ok()?);
+            let offset = usize::from(body.get(12)? >> 4) * 4;
+            let flags = *body.get(13)?;
+            (ImportTransport::Tcp, sequence, flags, body.get(offset..)?)
+        }
+        _ => return None,
+    };
+    Some(Segment {
+        transport,
+        source: SocketAddr::new(source, source_port),
+        destination: SocketAddr::new(destination, destination_port),
+        sequence,
+        flags,
+        payload,
+    })
+}
+
+fn u16_be(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*bytes.get(at)?, *bytes.get(at + 1)?]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::{
+        import_pcap, ImportTransport, ImportedFraming, PcapImportConfig, PcapImportError, TCP_FIN,
+        TCP_SYN,
+    };
+    use crate::interop::{DecodeStatus, InteropError};
+    use crate::{recover_chunk_payloads, TakrecHeader};
+
+    const CLIENT: &str = "10.0.0.2:50000";
+    const SERVER: &str = "10.0.0.1:8087";
+
+    fn event(uid: &str) -> Vec<u8> {
+        format!(
+            "<event version=\"2.0\" uid=\"{uid}\" type=\"a-f-G\" how=\"m-g\" \
+             time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" \
+             stale=\"2024-01-01T00:01:00Z\"><point lat=\"1\" lon=\"2\" hae=\"0\" \
+             ce=\"5\" le=\"5\"/></event>"
+        )
+        .into_bytes()
+    }
+
+    /// Ethernet, IPv4 and UDP or TCP headers around `payload`.
+    fn packet(udp: bool, from: &str, to: &str, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let from: SocketAddr = from.parse().expect("addr");
+        let to: SocketAddr = to.parse().expect("addr");
+        let ip = |addr: SocketAddr| match addr.ip() {
+            std::net::IpAddr::V4(ip) => ip.octets(),
+            std::net::IpAddr::V6(_) => unreachable!(),
+        };
+        let mut transport = Vec::new();
+        transport.extend_from_slice(&from.port().to_be_bytes());
+        transport.extend_from_slice(&to.port().to_be_bytes());
+        if udp {
+            transport.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+            transport.extend_from_slice(&[0, 0]);
+        } else {
+            transport.extend_from_slice(&seq.to_be_bytes());
+            transport.extend_from_slice(&[0; 4]);
+            transport.extend_from_slice(&[5 << 4, flags, 0xFF, 0xFF, 0, 0, 0, 0]);
+        }
+        transport.extend_from_slice(payload);
+
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0]);
+        frame.extend_from_slice(&(20 + transport.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 0x40, 0, 64, if udp { 17 } else { 6 }, 0, 0]);
+        frame.extend_from_slice(&ip(from));
+        frame.extend_from_slice(&ip(to));
+        frame.extend_from_slice(&transport);
+        frame
+    }
+
+    /// A little-endian microsecond pcap with Ethernet framing.
+    fn pcap(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&65_535u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        for (index, packet) in packets.iter().enumerate() {
+            bytes.extend_from_slice(&1_704_067_200u32.to_le_bytes());
+            bytes.extend_from_slice(&(index as u32).to_le_bytes());
+            bytes.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(packet);
+        }
+        bytes
+    }
+
+    #[test]
+    fn imports_udp_datagrams_by_framing() {
+        let protobuf = rustak_proto::encode_v1_payload(&event("mesh")).expect("encode");
+        let mut mesh = vec![0xBF, 0x01, 0xBF];
+        mesh.extend_from_slice(&protobuf);
+        let capture = pcap(&[
+            packet(
+                true,
+                "10.0.0.2:40000",
+                "239.2.3.1:6969",
+                0,
+                0,
+                &event("xml"),
+            ),
+            packet(true, "10.0.0.2:40000", "239.2.3.1:6969", 0, 0, &mesh),
+            packet(true, "10.0.0.2:40000", "239.2.3.1:6969", 0, 0, b"<event"),
+            packet(true, "10.0.0.2:40000", "239.2.3.1:6969", 0, 0, b"\x00\x01"),
+            packet(true, "10.0.0.2:40000", "10.0.0.3:53", 0, 0, &event("dns")),
+        ]);
+
+        let mut takrec = Vec::new();
+        let report = import_pcap(
+            capture.as_slice(),
+            &mut takrec,
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect("import");
+        assert_eq!((report.packets, report.skipped_packets), (5, 1));
+        let statuses: Vec<_> = report
+            .frames
+            .iter()
+            .map(|frame| (frame.framing, frame.decode_status, frame.chunk))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (ImportedFraming::Xml, DecodeStatus::Decoded, Some(0)),
+                (ImportedFraming::MeshHeader, DecodeStatus::Decoded, Some(1)),
+                (ImportedFraming::Xml, DecodeStatus::Malformed, Some(2)),
+                (ImportedFraming::Unknown, DecodeStatus::Opaque, None),
+            ]
+        );
+        assert_eq!(report.frames[1].transport, ImportTransport::Udp);
+        assert_eq!(report.frames[1].timestamp_micros, 1_704_067_200_000_001);
+
+        let (recovery, payloads) = recover_chunk_payloads(takrec.as_slice()).expect("recover");
+        assert_eq!(
+            recovery.header.created_unix_nanos,
+            1_704_067_200_000_000_000
+        );
+        assert_eq!(payloads, [event("xml"), mesh, b"<event".to_vec()]);
+    }
+
+    #[test]
+    fn reassembles_tcp_streams_across_reordering_and_an_upgrade() {
+        let first = event("first");
+        let (head, tail) = first.split_at(40);
+        let protobuf = rustak_proto::encode_v1_payload(&event("upgraded")).expect("encode");
+        let mut upgraded = vec![0xBF, protobuf.len() as u8];
+        upgraded.extend_from_slice(&protobuf);
+
+        let isn = u32::MAX - 10;
+        let at = |offset: usize| isn.wrapping_add(1).wrapping_add(offset as u32);
+        let after_first = first.len() + 1;
+        let capture = pcap(&[
+            packet(false, CLIENT, SERVER, isn, TCP_SYN, b""),
+            // The tail arrives before the head, then the head twice.
+            packet(false, CLIENT, SERVER, at(40), 0, tail),
+            packet(false, CLIENT, SERVER, at(0), 0, head),
+            packet(false, CLIENT, SERVER, at(0), 0, head),
+            packet(false, CLIENT, SERVER, at(first.len()), 0, b"\n"),
+            packet(false, CLIENT, SERVER, at(after_first), 0, &upgraded),
+            packet(
+                false,
+                CLIENT,
+                SERVER,
+                at(after_first + upgraded.len()),
+                TCP_FIN,
+                b"<event uid=\"cut",
+            ),
+        ]);
+
+        let mut takrec = Vec::new();
+        let report = import_pcap(
+            capture.as_slice(),
+            &mut takrec,
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect("import");
+        assert_eq!(report.tcp_streams, 1);
+        assert_eq!(report.abandoned_streams, 0);
+        let statuses: Vec<_> = report
+            .frames
+            .iter()
+            .map(|frame| (frame.framing, frame.decode_status, frame.chunk))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (ImportedFraming::Xml, DecodeStatus::Decoded, Some(0)),
+                (
+                    ImportedFraming::StreamHeader,
+                    DecodeStatus::Decoded,
+                    Some(1)
+                ),
+                (ImportedFraming::Xml, DecodeStatus::Malformed, None),
+            ]
+        );
+        assert_eq!(report.frames[0].source, CLIENT.parse().expect("addr"));
+
+        let (_, payloads) = recover_chunk_payloads(takrec.as_slice()).expect("recover");
+        assert_eq!(payloads, [first, protobuf]);
+    }
+
+    #[test]
+    fn streams_with_missing_bytes_are_abandoned_and_resynchronised_by_syn() {
+        let config = PcapImportConfig {
+            max_reorder_bytes: 8,
+            ..PcapImportConfig::default()
+        };
+        let capture = pcap(&[
+            packet(false, CLIENT, SERVER, 100, TCP_SYN, b""),
+            packet(false, CLIENT, SERVER, 101, 0, b"<event uid=\"a\""),
+            packet(false, CLIENT, SERVER, 500, 0, b"far ahead of the gap"),
+            packet(false, CLIENT, SERVER, 520, 0, b"ignored"),
+            packet(false, CLIENT, SERVER, 900, TCP_SYN, b""),
+            packet(false, CLIENT, SERVER, 901, 0, &event("again")),
+        ]);
+        let mut takrec = Vec::new();
+        let report = import_pcap(
+            capture.as_slice(),
+            &mut takrec,
+            TakrecHeader::default(),
+            &config,
+        )
+        .expect("import");
+        assert_eq!((report.tcp_streams, report.abandoned_streams), (2, 1));
+        let statuses: Vec<_> = report
+            .frames
+            .iter()
+            .map(|frame| (frame.decode_status, frame.chunk))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (DecodeStatus::Malformed, None),
+                (DecodeStatus::Decoded, Some(0))
+            ]
+        );
+    }
+
+    /// Rewrites a capture from [`pcap`] as big-endian with nanosecond
+    /// timestamps.
+    fn big_endian_nanos(capture: &[u8]) -> Vec<u8> {
+        let word = |at: usize| u32::from_le_bytes(capture[at..at + 4].try_into().expect("word"));
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0xA1B2_3C4Du32.to_be_bytes());
+        bytes.extend_from_slice(&2u16.to_be_bytes());
+        bytes.extend_from_slice(&4u16.to_be_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&word(16).to_be_bytes());
+        bytes.extend_from_slice(&word(20).to_be_bytes());
+        let mut at = 24;
+        while at < capture.len() {
+            let len = word(at + 8) as usize;
+            bytes.extend_from_slice(&word(at).to_be_bytes());
+            bytes.extend_from_slice(&(word(at + 4) * 1_000 + 999).to_be_bytes());
+            bytes.extend_from_slice(&word(at + 8).to_be_bytes());
+            bytes.extend_from_slice(&word(at + 12).to_be_bytes());
+            bytes.extend_from_slice(&capture[at + 16..at + 16 + len]);
+            at += 16 + len;
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_big_endian_nanosecond_captures() {
+        let datagram = |uid| packet(true, "10.0.0.2:40000", "239.2.3.1:6969", 0, 0, &event(uid));
+        let capture = big_endian_nanos(&pcap(&[datagram("a"), datagram("b")]));
+
+        let report = import_pcap(
+            capture.as_slice(),
+            Vec::new(),
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect("import");
+        let times: Vec<_> = report
+            .frames
+            .iter()
+            .map(|frame| (frame.decode_status, frame.timestamp_micros))
+            .collect();
+        assert_eq!(
+            times,
+            [
+                (DecodeStatus::Decoded, 1_704_067_200_000_000),
+                (DecodeStatus::Decoded, 1_704_067_200_000_001),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_packets_report_the_bytes_read() {
+        let mut capture = pcap(&[packet(
+            true,
+            "10.0.0.2:40000",
+            "239.2.3.1:6969",
+            0,
+            0,
+            &event("cut"),
+        )]);
+        let expected = capture.len() - 40;
+        capture.truncate(capture.len() - 10);
+
+        let error = import_pcap(
+            capture.as_slice(),
+            Vec::new(),
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect_err("payload is cut short");
+        assert!(matches!(
+            error,
+            PcapImportError::Pcap(InteropError::TruncatedPacketPayload { expected: e, actual })
+                if e == expected && actual == expected - 10
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_link_types() {
+        let mut capture = pcap(&[]);
+        capture[20..24].copy_from_slice(&105u32.to_le_bytes());
+        let error = import_pcap(
+            capture.as_slice(),
+            Vec::new(),
+            TakrecHeader::default(),
+            &PcapImportConfig::default(),
+        )
+        .expect_err("802.11 is not supported");
+        assert!(matches!(
+            error,
+            PcapImportError::UnsupportedLinkType { linktype: 105 }
+        ));
+    }
+}
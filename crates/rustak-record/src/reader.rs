@@ -0,0 +1,533 @@
+//! Lazy, chunk-at-a-time reading of `.takrec` captures as envelopes.
+//!
+//! [`TakrecReader`] reads the header up front and then one chunk per
+//! [`Iterator::next`], verifying each checksum as it goes, so memory stays
+//! bounded by the largest chunk and a corrupt chunk only surfaces when the
+//! reader reaches it.
+//!
+//! Each envelope's [`ObservedTime`] is the chunk's capture time when the
+//! file stores one (see [`crate::TakrecWriter::with_capture_times`]). Older
+//! files carry none, so it is restored from the CoT event `time` when the
+//! payload is XML that has one, otherwise from the previous chunk, starting
+//! from the header's `created_unix_nanos`. The monotonic instant keeps the
+//! same spacing from when the reader was opened, so replay pacing can use
+//! either clock. [`TakrecReader::seek_to_time`] uses the same times to start
+//! part way through a capture, through a [`ChunkIndex`] when one is given.
+
+use std::io::{Read, Seek, SeekFrom};
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::Stream;
+use rustak_core::CotEvent;
+use rustak_io::{IoError, MessageEnvelope, MessageSource, ObservedTime};
+use rustak_limits::Limits;
+
+use crate::writer::{
+    read_header, read_next_chunk, read_versioned_header, NextChunk, DEFAULT_MAX_CHUNK_BYTES,
+};
+use crate::{ChunkCommit, ChunkIndex, RecordEnvelope, RecordWriteError, TakrecHeader};
+
+/// Iterates a capture as [`RecordEnvelope<Bytes>`] whose message and
+/// `raw_frame` are the recorded chunk payload.
+///
+/// Iteration ends after the last committed chunk, or after the first error;
+/// [`Self::truncated_tail`] then says whether the file ends in a partially
+/// written chunk.
+#[derive(Debug)]
+pub struct TakrecReader<R> {
+    source: R,
+    header: TakrecHeader,
+    opened_at: Instant,
+    first_wall: Option<SystemTime>,
+    walls: WallTimes,
+    capture_times: bool,
+    captured: Option<SystemTime>,
+    compressed: bool,
+    index: Option<ChunkIndex>,
+    max_chunk_bytes: usize,
+    chunks_read: u64,
+    truncated_tail: bool,
+    finished: bool,
+}
+
+impl<R: Read> TakrecReader<R> {
+    /// Reads and validates the header; chunks are read on demand.
+    pub fn new(mut source: R) -> Result<Self, RecordWriteError> {
+        let (header, capture_times) = read_versioned_header(&mut source)?;
+        Ok(Self {
+            source,
+            walls: WallTimes::new(&header),
+            header,
+            opened_at: Instant::now(),
+            first_wall: None,
+            capture_times,
+            captured: None,
+            compressed: false,
+            index: None,
+            max_chunk_bytes: DEFAULT_MAX_CHUNK_BYTES,
+            chunks_read: 0,
+            truncated_tail: false,
+            finished: false,
+        })
+    }
+
+    /// Trusts chunk length fields up to `max_chunk_bytes` instead of
+    /// [`DEFAULT_MAX_CHUNK_BYTES`]; for captures written with a larger
+    /// writer limit.
+    #[must_use]
+    pub fn with_max_chunk_bytes(mut self, max_chunk_bytes: usize) -> Self {
+        self.max_chunk_bytes = max_chunk_bytes;
+        self
+    }
+
+    /// Lets [`Self::seek_to_time`] jump straight to the chunk `index` names
+    /// instead of scanning from the start. `index` must describe this file,
+    /// as [`crate::rebuild_index`] built it.
+    #[must_use]
+    pub fn with_index(mut self, index: ChunkIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Next committed chunk and its payload, without building an envelope.
+    pub fn next_chunk(&mut self) -> Option<Result<(ChunkCommit, Bytes), RecordWriteError>> {
+        if self.finished {
+            return None;
+        }
+        match read_next_chunk(&mut self.source, self.max_chunk_bytes) {
+            Ok(NextChunk::Committed(commit, payload, captured, compressed)) => {
+                self.captured = captured;
+                self.compressed = compressed;
+                self.chunks_read += 1;
+                Some(Ok((commit, Bytes::from(payload))))
+            }
+            Ok(NextChunk::End { truncated_tail }) => {
+                self.truncated_tail = truncated_tail;
+                self.finished = true;
+                None
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+
+    fn envelope(&mut self, payload: Bytes) -> RecordEnvelope<Bytes> {
+        let wall = self.walls.observe(&payload, self.captured);
+        let first_wall = *self.first_wall.get_or_insert(wall);
+        let offset = wall.duration_since(first_wall).unwrap_or_default();
+        let monotonic = self.opened_at.checked_add(offset).unwrap_or(self.opened_at);
+        MessageEnvelope::new(payload.clone())
+            .with_raw_frame(payload)
+            .with_observed(ObservedTime::new(wall, monotonic))
+    }
+}
+
+impl<R: Read + Seek> TakrecReader<R> {
+    /// Repositions the reader so the next chunk is the first, in file order,
+    /// whose wall time is at or after `target`, and returns its sequence.
+    /// Returns `None`, with iteration ended, when no chunk is that late.
+    /// With [`Self::with_index`] this seeks straight to the chunk; otherwise
+    /// earlier chunks are read and checksum-verified on the way. The
+    /// monotonic clock of the envelopes that follow starts again from the
+    /// chunk sought to.
+    pub fn seek_to_time(&mut self, target: SystemTime) -> Result<Option<u64>, RecordWriteError> {
+        self.first_wall = None;
+        self.truncated_tail = false;
+        self.finished = false;
+        if let Some(index) = &self.index {
+            let Some(entry) = index.find_by_time(target) else {
+                self.chunks_read = index.entries.len() as u64;
+                self.truncated_tail = index.diagnostics.truncated_tail;
+                self.finished = true;
+                return Ok(None);
+            };
+            let position = index
+                .entries
+                .iter()
+                .position(|candidate| std::ptr::eq(candidate, entry))
+                .unwrap_or_default();
+            self.walls = match position.checked_sub(1) {
+                Some(previous) => WallTimes::resumed(index.entries[previous].observed_wall),
+                None => WallTimes::new(&self.header),
+            };
+            self.chunks_read = position as u64;
+            self.source.seek(SeekFrom::Start(entry.offset))?;
+            return Ok(Some(entry.sequence));
+        }
+
+        self.source.seek(SeekFrom::Start(0))?;
+        read_header(&mut self.source)?;
+        self.walls = WallTimes::new(&self.header);
+        self.chunks_read = 0;
+        loop {
+            let offset = self.source.stream_position()?;
+            let before = self.walls;
+            match self.next_chunk() {
+                Some(Ok((commit, payload))) => {
+                    if self.walls.observe(&payload, self.captured) >= target {
+                        self.source.seek(SeekFrom::Start(offset))?;
+                        self.walls = before;
+                        self.chunks_read -= 1;
+                        return Ok(Some(commit.sequence));
+                    }
+                }
+                Some(Err(error)) => return Err(error),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<R> TakrecReader<R> {
+    #[must_use]
+    pub fn header(&self) -> &TakrecHeader {
+        &self.header
+    }
+
+    /// Whether the file stores a capture time with every chunk; see
+    /// [`crate::TakrecWriter::with_capture_times`].
+    #[must_use]
+    pub fn capture_times(&self) -> bool {
+        self.capture_times
+    }
+
+    /// Capture time stored with the chunk [`Self::next_chunk`] last
+    /// returned; `None` for files without capture times.
+    #[must_use]
+    pub fn chunk_captured(&self) -> Option<SystemTime> {
+        self.captured
+    }
+
+    /// Whether the chunk [`Self::next_chunk`] last returned was stored
+    /// compressed.
+    #[must_use]
+    pub fn chunk_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Committed chunks read so far.
+    #[must_use]
+    pub fn chunks_read(&self) -> u64 {
+        self.chunks_read
+    }
+
+    /// Whether the file ended in a partially written chunk. Only meaningful
+    /// once iteration has ended.
+    #[must_use]
+    pub fn truncated_tail(&self) -> bool {
+        self.truncated_tail
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+}
+
+impl<R: Read> Iterator for TakrecReader<R> {
+    type Item = Result<RecordEnvelope<Bytes>, RecordWriteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(
+            self.next_chunk()?
+                .map(|(_, payload)| self.envelope(payload)),
+        )
+    }
+}
+
+/// Reads block the calling task, which suits local files; wrap slow sources
+/// in `spawn_blocking`. The end of the capture is [`IoError::Closed`].
+impl<R> MessageSource<Bytes> for TakrecReader<R>
+where
+    R: Read + Send + Sync + 'static,
+{
+    fn recv(&mut self) -> BoxFuture<'_, Result<MessageEnvelope<Bytes>, IoError>> {
+        let next = self.next();
+        Box::pin(async move {
+            match next {
+                Some(Ok(envelope)) => Ok(envelope),
+                Some(Err(RecordWriteError::Io(error))) => Err(IoError::Io(error)),
+                Some(Err(error)) => Err(IoError::Other(error.to_string())),
+                None => Err(IoError::Closed),
+            }
+        })
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+    ) -> Pin<Box<dyn Stream<Item = Result<MessageEnvelope<Bytes>, IoError>> + Send>> {
+        Box::pin(futures::stream::unfold(*self, |mut reader| async move {
+            let next = reader.recv().await;
+            match next {
+                Err(IoError::Closed) => None,
+                item => Some((item, reader)),
+            }
+        }))
+    }
+}
+
+/// Chunk wall times: the recorded capture time when the chunk has one,
+/// else the CoT event `time`, else the previous chunk's, starting from the
+/// header's creation time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WallTimes {
+    last: SystemTime,
+}
+
+impl WallTimes {
+    pub(crate) fn new(header: &TakrecHeader) -> Self {
+        Self::resumed(UNIX_EPOCH + Duration::from_nanos(header.created_unix_nanos))
+    }
+
+    /// Carries on after a chunk observed at `last`.
+    pub(crate) fn resumed(last: SystemTime) -> Self {
+        Self { last }
+    }
+
+    pub(crate) fn observe(&mut self, payload: &[u8], captured: Option<SystemTime>) -> SystemTime {
+        self.last = captured
+            .or_else(|| event_time(payload))
+            .unwrap_or(self.last);
+        self.last
+    }
+}
+
+fn event_time(payload: &[u8]) -> Option<SystemTime> {
+    let xml = std::str::from_utf8(payload).ok()?;
+    CotEvent::from_xml(xml, &Limits::default())
+        .ok()?
+        .time
+        .to_system_time()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use rustak_io::{IoError, MessageSource};
+
+    use super::TakrecReader;
+    use crate::tests::dated_event;
+    use crate::{rebuild_index, ChunkCompression, RecordWriteError, TakrecHeader, TakrecWriter};
+
+    const CREATED_UNIX_NANOS: u64 = 1_700_000_000_000_000_000;
+
+    fn capture(payloads: &[&[u8]]) -> Vec<u8> {
+        let header = TakrecHeader {
+            created_unix_nanos: CREATED_UNIX_NANOS,
+            ..TakrecHeader::default()
+        };
+        let mut writer = TakrecWriter::new(Vec::new(), header).expect("writer");
+        for payload in payloads {
+            writer.append_chunk(payload).expect("append");
+        }
+        writer.into_inner().expect("finish")
+    }
+
+    #[test]
+    fn iterates_envelopes_with_restored_observed_time() {
+        let recording = capture(&[
+            b"opaque",
+            &dated_event("2024-01-01T00:00:00Z", ""),
+            b"\xbf\x01\xbf",
+            &dated_event("2024-01-01T00:00:02.500Z", ""),
+        ]);
+        let mut reader = TakrecReader::new(recording.as_slice()).expect("reader");
+        let envelopes = reader
+            .by_ref()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("chunks");
+        assert_eq!(reader.chunks_read(), 4);
+        assert!(!reader.truncated_tail());
+
+        let created = UNIX_EPOCH + Duration::from_nanos(CREATED_UNIX_NANOS);
+        let event = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        let walls: Vec<_> = envelopes.iter().map(|env| env.observed.wall).collect();
+        assert_eq!(
+            walls,
+            [created, event, event, event + Duration::from_millis(2_500)]
+        );
+        assert_eq!(
+            envelopes[3].observed.monotonic - envelopes[1].observed.monotonic,
+            Duration::from_millis(2_500)
+        );
+        assert_eq!(&envelopes[2].message[..], b"\xbf\x01\xbf");
+        assert_eq!(
+            envelopes[2].raw_frame.as_deref(),
+            Some(&b"\xbf\x01\xbf"[..])
+        );
+    }
+
+    #[test]
+    fn seek_to_time_starts_at_the_first_chunk_that_late() {
+        let recording = capture(&[
+            &dated_event("2024-01-01T00:00:00Z", ""),
+            b"undated",
+            &dated_event("2024-01-01T00:00:05Z", ""),
+            &dated_event("2024-01-01T00:00:09Z", ""),
+        ]);
+        let event = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        let mut reader = TakrecReader::new(Cursor::new(recording)).expect("reader");
+        reader.next().expect("first").expect("chunk");
+
+        assert_eq!(
+            reader
+                .seek_to_time(event + Duration::from_secs(1))
+                .expect("seek"),
+            Some(2)
+        );
+        assert_eq!(reader.chunks_read(), 2);
+        let walls: Vec<_> = reader
+            .by_ref()
+            .map(|envelope| envelope.expect("chunk").observed.wall)
+            .collect();
+        assert_eq!(
+            walls,
+            [
+                event + Duration::from_secs(5),
+                event + Duration::from_secs(9)
+            ]
+        );
+
+        assert_eq!(reader.seek_to_time(event).expect("seek"), Some(0));
+        let undated = reader.nth(1).expect("second").expect("chunk");
+        assert_eq!(undated.observed.wall, event, "carried from the first chunk");
+
+        assert_eq!(
+            reader
+                .seek_to_time(event + Duration::from_secs(10))
+                .expect("seek"),
+            None
+        );
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn indexed_seek_jumps_by_recorded_capture_time() {
+        let captured = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let header = TakrecHeader {
+            created_unix_nanos: CREATED_UNIX_NANOS,
+            ..TakrecHeader::default()
+        };
+        let mut writer = TakrecWriter::with_capture_times(
+            Vec::new(),
+            header,
+            ChunkCompression::Zstd { level: 3 },
+        )
+        .expect("writer");
+        let chunks: [&[u8]; 4] = [
+            b"first",
+            &dated_event("2024-01-01T00:00:00Z", ""),
+            &[b'x'; 256],
+            b"last",
+        ];
+        for (at, payload) in chunks.iter().enumerate() {
+            writer
+                .append_chunk_at(payload, captured + Duration::from_secs(at as u64 * 10))
+                .expect("append");
+        }
+        let mut recording = writer.into_inner().expect("finish");
+        let index = rebuild_index(recording.as_slice()).expect("index");
+        let walls: Vec<_> = index
+            .entries
+            .iter()
+            .map(|entry| entry.observed_wall)
+            .collect();
+        assert_eq!(
+            walls,
+            (0..4)
+                .map(|at| captured + Duration::from_secs(at * 10))
+                .collect::<Vec<_>>(),
+            "the capture time wins over the CoT time"
+        );
+
+        // A scan from the start would now fail on the first chunk.
+        let pos = recording
+            .windows(5)
+            .position(|window| window == b"first")
+            .expect("payload");
+        recording[pos] ^= 0xff;
+        let mut reader = TakrecReader::new(Cursor::new(recording))
+            .expect("reader")
+            .with_index(index);
+        assert_eq!(
+            reader
+                .seek_to_time(captured + Duration::from_secs(15))
+                .expect("seek"),
+            Some(2)
+        );
+        assert_eq!(reader.chunks_read(), 2);
+        let sought: Vec<_> = reader
+            .by_ref()
+            .map(|envelope| envelope.expect("chunk").observed.wall)
+            .collect();
+        assert_eq!(
+            sought,
+            [
+                captured + Duration::from_secs(20),
+                captured + Duration::from_secs(30)
+            ]
+        );
+
+        assert_eq!(
+            reader
+                .seek_to_time(captured + Duration::from_secs(31))
+                .expect("seek"),
+            None
+        );
+        assert_eq!(reader.chunks_read(), 4);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn checksums_are_verified_when_the_chunk_is_reached() {
+        let mut recording = capture(&[b"first", b"second", b"third"]);
+        let pos = recording
+            .windows(6)
+            .position(|window| window == b"second")
+            .expect("payload");
+        recording[pos] ^= 0xff;
+
+        let mut reader = TakrecReader::new(recording.as_slice()).expect("reader");
+        let first = reader.next().expect("first").expect("intact chunk");
+        assert_eq!(&first.message[..], b"first");
+        assert!(matches!(
+            reader.next(),
+            Some(Err(RecordWriteError::ChecksumMismatch { sequence: 1, .. }))
+        ));
+        assert!(
+            reader.next().is_none(),
+            "iteration stops at the first error"
+        );
+    }
+
+    #[test]
+    fn message_source_ends_with_closed_at_the_torn_tail() {
+        let mut recording = capture(&[b"one", b"two"]);
+        recording.truncate(recording.len() - 2);
+
+        let mut reader = TakrecReader::new(Cursor::new(recording.clone())).expect("reader");
+        let first = block_on(reader.recv()).expect("first chunk");
+        assert_eq!(&first.message[..], b"one");
+        assert!(matches!(block_on(reader.recv()), Err(IoError::Closed)));
+        assert!(reader.truncated_tail());
+
+        let source = Box::new(TakrecReader::new(Cursor::new(recording)).expect("reader"));
+        let messages: Vec<_> = block_on(
+            source
+                .into_stream()
+                .map(|item| item.expect("chunk").message)
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(messages, [&b"one"[..]]);
+    }
+}
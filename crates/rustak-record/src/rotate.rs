@@ -0,0 +1,634 @@
+//! Rolling `.takrec` files for long-running recorders.
+//!
+//! [`RotatingTakrecWriter`] writes `<prefix>-<UTC timestamp>-<seq>.takrec`
+//! files into one directory, starting a new file once the current one
+//! reaches [`RotationPolicy::max_file_bytes`] or has been open for
+//! [`RotationPolicy::max_file_age`]. Finished files are synced to disk
+//! before the next one starts, and [`RetentionPolicy`] then deletes the
+//! oldest files this naming scheme produced for the same prefix, including
+//! ones left by earlier runs, so the directory stays bounded. Anything else
+//! in the directory, such as another prefix that merely starts the same way,
+//! is left alone. The file being written is never deleted. Every file
+//! stores chunk capture times, as [`TakrecWriter::with_capture_times`]
+//! writes them.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use rustak_core::{SystemClock, TimeSource, TimestampUtc};
+use rustak_limits::{CodedError, ErrorCode};
+use thiserror::Error;
+
+use crate::{
+    ChunkCommit, ChunkCompression, RecordEnvelope, RecordWriteError, TakrecHeader, TakrecWriter,
+};
+
+const EXTENSION: &str = "takrec";
+
+/// When to start a new file. Rotation is checked before each append, so a
+/// quiet source keeps its file open past `max_file_age` until the next
+/// chunk arrives. A file always takes at least one chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RotationPolicy {
+    pub max_file_bytes: Option<u64>,
+    pub max_file_age: Option<Duration>,
+}
+
+/// How many finished files to keep. Enforced after every rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetentionPolicy {
+    pub max_files: Option<usize>,
+    /// Bound on all kept files together, the one being written included.
+    pub max_total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotatingTakrecConfig {
+    pub directory: PathBuf,
+    /// File name prefix; retention only considers
+    /// `<prefix>-<stamp>-<seq>.takrec` files carrying exactly this prefix.
+    pub prefix: String,
+    pub rotation: RotationPolicy,
+    pub retention: RetentionPolicy,
+    pub compression: ChunkCompression,
+}
+
+impl RotatingTakrecConfig {
+    #[must_use]
+    pub fn new(directory: impl Into<PathBuf>, prefix: impl Into<String>) -> Self {
+        Self {
+            directory: directory.into(),
+            prefix: prefix.into(),
+            rotation: RotationPolicy::default(),
+            retention: RetentionPolicy::default(),
+            compression: ChunkCompression::None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), RotateError> {
+        let invalid_prefix = self.prefix.is_empty()
+            || self
+                .prefix
+                .contains(|c: char| std::path::is_separator(c) || c.is_control());
+        if invalid_prefix {
+            return Err(RotateError::InvalidConfig { field: "prefix" });
+        }
+        let zero_fields = [
+            (
+                "rotation.max_file_bytes",
+                self.rotation.max_file_bytes == Some(0),
+            ),
+            (
+                "rotation.max_file_age",
+                self.rotation.max_file_age == Some(Duration::ZERO),
+            ),
+            ("retention.max_files", self.retention.max_files == Some(0)),
+            (
+                "retention.max_total_bytes",
+                self.retention.max_total_bytes == Some(0),
+            ),
+        ];
+        if let Some((field, _)) = zero_fields.into_iter().find(|(_, zero)| *zero) {
+            return Err(RotateError::InvalidConfig { field });
+        }
+        self.compression.validate()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RotateError {
+    #[error(transparent)]
+    Record(#[from] RecordWriteError),
+
+    #[error("rotating takrec config field `{field}` is invalid")]
+    InvalidConfig { field: &'static str },
+
+    #[error("takrec file `{path}`: {source}")]
+    File { path: String, source: io::Error },
+}
+
+impl CodedError for RotateError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Record(error) => error.code(),
+            Self::InvalidConfig { .. } => ErrorCode::new("RECORD", 501),
+            Self::File { .. } => ErrorCode::new("RECORD", 502),
+        }
+    }
+}
+
+/// A finished or current file and its size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotatedFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// What a [`RotatingTakrecWriter`] wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RotationSummary {
+    pub chunks: u64,
+    /// Bytes written across every file, deleted ones included.
+    pub bytes: u64,
+    /// Files this writer created, oldest first, deleted ones included.
+    pub files: Vec<PathBuf>,
+    /// Files removed by the retention policy, from this run or earlier ones.
+    pub deleted: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct RotatingTakrecWriter<C: TimeSource = SystemClock> {
+    config: RotatingTakrecConfig,
+    header: TakrecHeader,
+    clock: C,
+    writer: TakrecWriter<fs::File>,
+    path: PathBuf,
+    opened_at: SystemTime,
+    chunks_in_file: u64,
+    /// Finished files with this prefix still on disk, oldest first.
+    retained: Vec<RotatedFile>,
+    summary: RotationSummary,
+    closed_bytes: u64,
+}
+
+impl RotatingTakrecWriter<SystemClock> {
+    pub fn create(config: RotatingTakrecConfig, header: TakrecHeader) -> Result<Self, RotateError> {
+        Self::with_clock(config, header, SystemClock)
+    }
+}
+
+impl<C: TimeSource> RotatingTakrecWriter<C> {
+    /// Like [`Self::create`], naming and ageing files by `clock`. Every file
+    /// gets `header` re-stamped with its own creation time.
+    pub fn with_clock(
+        config: RotatingTakrecConfig,
+        header: TakrecHeader,
+        clock: C,
+    ) -> Result<Self, RotateError> {
+        config.validate()?;
+        fs::create_dir_all(&config.directory)
+            .map_err(|source| file_error(&config.directory, source))?;
+        let retained = existing_files(&config.directory, &config.prefix)?;
+        let opened_at = clock.now_system();
+        let (path, writer) = open_file(&config, &header, opened_at, 0)?;
+        let mut rotating = Self {
+            config,
+            header,
+            clock,
+            writer,
+            path: path.clone(),
+            opened_at,
+            chunks_in_file: 0,
+            retained,
+            summary: RotationSummary::default(),
+            closed_bytes: 0,
+        };
+        rotating.summary.files.push(path);
+        rotating.enforce_retention()?;
+        Ok(rotating)
+    }
+
+    /// The file currently being written.
+    #[must_use]
+    pub fn current_path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &RotatingTakrecConfig {
+        &self.config
+    }
+
+    #[must_use]
+    pub fn chunks(&self) -> u64 {
+        self.summary.chunks
+    }
+
+    /// Appends `payload` captured now by the writer's clock, first rotating
+    /// if the current file is due.
+    pub fn append_chunk(&mut self, payload: &[u8]) -> Result<ChunkCommit, RotateError> {
+        let captured = self.clock.now_system();
+        self.append_chunk_at(payload, captured)
+    }
+
+    /// Appends `payload` captured at `captured`, first rotating if the
+    /// current file is due.
+    pub fn append_chunk_at(
+        &mut self,
+        payload: &[u8],
+        captured: SystemTime,
+    ) -> Result<ChunkCommit, RotateError> {
+        if self.rotation_due() {
+            self.rotate()?;
+        }
+        let commit = self.writer.append_chunk_at(payload, captured)?;
+        self.chunks_in_file += 1;
+        self.summary.chunks += 1;
+        Ok(commit)
+    }
+
+    /// Appends the envelope's raw frame, or its message when it has none,
+    /// captured at its observed wall time.
+    pub fn append_envelope(
+        &mut self,
+        envelope: &RecordEnvelope<Bytes>,
+    ) -> Result<ChunkCommit, RotateError> {
+        let payload = envelope.raw_frame.as_deref().unwrap_or(&envelope.message);
+        self.append_chunk_at(payload, envelope.observed.wall)
+    }
+
+    fn rotation_due(&self) -> bool {
+        if self.chunks_in_file == 0 {
+            return false;
+        }
+        let policy = self.config.rotation;
+        let too_big = policy
+            .max_file_bytes
+            .is_some_and(|limit| self.writer.bytes_written() >= limit);
+        let too_old = policy.max_file_age.is_some_and(|limit| {
+            self.clock
+                .now_system()
+                .duration_since(self.opened_at)
+                .is_ok_and(|age| age >= limit)
+        });
+        too_big || too_old
+    }
+
+    /// Finishes the current file, syncing it to disk, and starts the next.
+    pub fn rotate(&mut self) -> Result<(), RotateError> {
+        let opened_at = self.clock.now_system();
+        let sequence = self.summary.files.len();
+        let (path, writer) = open_file(&self.config, &self.header, opened_at, sequence)?;
+        let previous = std::mem::replace(&mut self.writer, writer);
+        let previous_path = std::mem::replace(&mut self.path, path.clone());
+        let bytes = sync_file(previous, &previous_path)?;
+        self.closed_bytes += bytes;
+        self.retained.push(RotatedFile {
+            path: previous_path,
+            bytes,
+        });
+        self.summary.files.push(path);
+        self.opened_at = opened_at;
+        self.chunks_in_file = 0;
+        self.enforce_retention()
+    }
+
+    fn enforce_retention(&mut self) -> Result<(), RotateError> {
+        let policy = self.config.retention;
+        let current = self.writer.bytes_written();
+        loop {
+            let files = self.retained.len() + 1;
+            let total = current + self.retained.iter().map(|file| file.bytes).sum::<u64>();
+            let over = policy.max_files.is_some_and(|limit| files > limit)
+                || policy.max_total_bytes.is_some_and(|limit| total > limit);
+            if !over || self.retained.is_empty() {
+                return Ok(());
+            }
+            let oldest = self.retained.remove(0);
+            match fs::remove_file(&oldest.path) {
+                Ok(()) => {}
+                // Someone else already cleaned it up.
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(source) => return Err(file_error(&oldest.path, source)),
+            }
+            self.summary.deleted.push(oldest.path);
+        }
+    }
+
+    /// Syncs the current file and reports everything written.
+    pub fn finish(mut self) -> Result<RotationSummary, RotateError> {
+        let bytes = sync_file(self.writer, &self.path)?;
+        self.summary.bytes = self.closed_bytes + bytes;
+        Ok(self.summary)
+    }
+}
+
+fn file_error(path: &Path, source: io::Error) -> RotateError {
+    RotateError::File {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// `<prefix>-20240102T030405.678Z-0003.takrec`: names sort by creation time
+/// and then by rotation count.
+fn file_name(prefix: &str, opened_at: SystemTime, sequence: usize) -> String {
+    let stamp: String = TimestampUtc::from_system_time(opened_at)
+        .to_rfc3339_millis()
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    format!("{prefix}-{stamp}-{sequence:04}.{EXTENSION}")
+}
+
+/// Creates the next file, skipping names an earlier run already used.
+fn open_file(
+    config: &RotatingTakrecConfig,
+    header: &TakrecHeader,
+    opened_at: SystemTime,
+    mut sequence: usize,
+) -> Result<(PathBuf, TakrecWriter<fs::File>), RotateError> {
+    let (path, file) = loop {
+        let path = config
+            .directory
+            .join(file_name(&config.prefix, opened_at, sequence));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => break (path, file),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => sequence += 1,
+            Err(source) => return Err(file_error(&path, source)),
+        }
+    };
+    let header = TakrecHeader {
+        created_unix_nanos: opened_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_nanos()).unwrap_or(u64::MAX)
+            }),
+        ..header.clone()
+    };
+    let writer = TakrecWriter::with_capture_times(file, header, config.compression)?;
+    Ok((path, writer))
+}
+
+fn sync_file(writer: TakrecWriter<fs::File>, path: &Path) -> Result<u64, RotateError> {
+    let bytes = writer.bytes_written();
+    let file = writer.into_inner()?;
+    file.sync_all().map_err(|source| file_error(path, source))?;
+    Ok(bytes)
+}
+
+/// Stamp and sequence of a name [`file_name`] produced for `prefix`.
+fn parse_file_name<'a>(name: &'a str, prefix: &str) -> Option<(&'a str, u64)> {
+    let rest = name
+        .strip_prefix(prefix)?
+        .strip_prefix('-')?
+        .strip_suffix(EXTENSION)?
+        .strip_suffix('.')?;
+    let (stamp, sequence) = rest.rsplit_once('-')?;
+    if sequence.len() < 4 || !sequence.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // `20240102T030405.678Z`
+    let shape = stamp.bytes().enumerate().all(|(at, b)| match at {
+        8 => b == b'T',
+        15 => b == b'.',
+        19 => b == b'Z',
+        _ => b.is_ascii_digit(),
+    });
+    if stamp.len() != 20 || !shape {
+        return None;
+    }
+    Some((stamp, sequence.parse().ok()?))
+}
+
+/// Files this writer's naming scheme produced for `prefix` already in
+/// `directory`, oldest first.
+fn existing_files(directory: &Path, prefix: &str) -> Result<Vec<RotatedFile>, RotateError> {
+    let entries = fs::read_dir(directory).map_err(|source| file_error(directory, source))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|source| file_error(directory, source))?;
+        let path = entry.path();
+        let Some(key) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| parse_file_name(name, prefix))
+            .map(|(stamp, sequence)| (stamp.to_owned(), sequence))
+        else {
+            continue;
+        };
+        let metadata = entry
+            .metadata()
+            .map_err(|source| file_error(&path, source))?;
+        if metadata.is_file() {
+            files.push((
+                key,
+                RotatedFile {
+                    path,
+                    bytes: metadata.len(),
+                },
+            ));
+        }
+    }
+    files.sort_by(|left, right| left.0.cmp(&right.0));
+    Ok(files.into_iter().map(|(_, file)| file).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use rustak_core::TimeSource;
+
+    use super::{
+        RetentionPolicy, RotateError, RotatingTakrecConfig, RotatingTakrecWriter, RotationPolicy,
+    };
+    use crate::{recover_chunk_payloads, TakrecHeader};
+
+    #[derive(Debug)]
+    struct ManualClock(Mutex<SystemTime>);
+
+    impl ManualClock {
+        fn at(seconds: u64) -> Self {
+            Self(Mutex::new(UNIX_EPOCH + Duration::from_secs(seconds)))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().expect("clock") += by;
+        }
+    }
+
+    impl TimeSource for &ManualClock {
+        fn now_system(&self) -> SystemTime {
+            *self.0.lock().expect("clock")
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustak_record_{name}_{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        dir
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .expect("name")
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    fn payloads(path: &Path) -> Vec<Vec<u8>> {
+        let (report, payloads) =
+            recover_chunk_payloads(fs::File::open(path).expect("open")).expect("recover");
+        assert!(!report.truncated_tail);
+        payloads
+    }
+
+    #[test]
+    fn rolls_by_size_and_age_with_timestamped_names() {
+        let dir = temp_dir("rotate_roll");
+        let clock = ManualClock::at(1_704_067_200);
+        let mut config = RotatingTakrecConfig::new(&dir, "session");
+        config.rotation = RotationPolicy {
+            max_file_bytes: Some(150),
+            max_file_age: Some(Duration::from_secs(60)),
+        };
+        let mut writer = RotatingTakrecWriter::with_clock(config, TakrecHeader::default(), &clock)
+            .expect("writer");
+        writer.append_chunk(&[b'a'; 40]).expect("first");
+        writer
+            .append_chunk(&[b'b'; 40])
+            .expect("over the size limit");
+        writer.append_chunk(b"c").expect("rolled by size");
+        clock.advance(Duration::from_secs(61));
+        writer.append_chunk(b"d").expect("rolled by age");
+        let summary = writer.finish().expect("finish");
+
+        assert_eq!(
+            names(&summary.files),
+            [
+                "session-20240101T000000.000Z-0000.takrec",
+                "session-20240101T000000.000Z-0001.takrec",
+                "session-20240101T000101.000Z-0002.takrec",
+            ]
+        );
+        assert_eq!(summary.chunks, 4);
+        assert!(summary.deleted.is_empty());
+        let on_disk: u64 = summary
+            .files
+            .iter()
+            .map(|path| fs::metadata(path).expect("metadata").len())
+            .sum();
+        assert_eq!(summary.bytes, on_disk);
+        assert_eq!(payloads(&summary.files[0]).len(), 2);
+        assert_eq!(payloads(&summary.files[1]), [b"c".to_vec()]);
+        assert_eq!(payloads(&summary.files[2]), [b"d".to_vec()]);
+
+        let (report, _) = recover_chunk_payloads(fs::File::open(&summary.files[2]).expect("open"))
+            .expect("recover");
+        assert_eq!(report.header.created_unix_nanos, 1_704_067_261_000_000_000);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn retention_deletes_the_oldest_files_including_earlier_runs() {
+        let dir = temp_dir("rotate_retain");
+        fs::create_dir_all(&dir).expect("dir");
+        let stale = dir.join("session-20000101T000000.000Z-0000.takrec");
+        fs::write(&stale, b"old").expect("stale");
+        let unrelated = dir.join("other-20000101T000000.000Z-0000.takrec");
+        fs::write(&unrelated, b"keep").expect("unrelated");
+
+        let clock = ManualClock::at(1_704_067_200);
+        let mut config = RotatingTakrecConfig::new(&dir, "session");
+        config.rotation.max_file_age = Some(Duration::from_secs(1));
+        config.retention = RetentionPolicy {
+            max_files: Some(2),
+            max_total_bytes: None,
+        };
+        let mut writer = RotatingTakrecWriter::with_clock(config, TakrecHeader::default(), &clock)
+            .expect("writer");
+        for payload in [b"one", b"two", b"six"] {
+            writer.append_chunk(payload).expect("append");
+            clock.advance(Duration::from_secs(1));
+        }
+        let summary = writer.finish().expect("finish");
+
+        assert_eq!(summary.files.len(), 3);
+        assert_eq!(summary.deleted, [stale, summary.files[0].clone()]);
+        assert!(!summary.files[0].exists());
+        assert_eq!(payloads(&summary.files[2]), [b"six".to_vec()]);
+        assert!(unrelated.exists(), "other prefixes are left alone");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn retention_leaves_foreign_files_with_a_similar_prefix() {
+        let dir = temp_dir("rotate_foreign");
+        fs::create_dir_all(&dir).expect("dir");
+        let stale = dir.join("gw-20000101T000000.000Z-0000.takrec");
+        fs::write(&stale, b"old").expect("stale");
+        let foreign = [
+            "gw-east-20000101T000000.000Z-0000.takrec",
+            "gw-notes.takrec",
+            "gw-20000101T000000.000Z.takrec",
+            "gw-20000101T000000.000Z-00x0.takrec",
+            "gw-20000101T000000.000Z-0000.takrec.bak",
+        ]
+        .map(|name| {
+            let path = dir.join(name);
+            fs::write(&path, b"keep").expect("foreign");
+            path
+        });
+
+        let clock = ManualClock::at(1_704_067_200);
+        let mut config = RotatingTakrecConfig::new(&dir, "gw");
+        config.rotation.max_file_age = Some(Duration::from_secs(1));
+        config.retention.max_files = Some(1);
+        let mut writer = RotatingTakrecWriter::with_clock(config, TakrecHeader::default(), &clock)
+            .expect("writer");
+        for payload in [b"one", b"two"] {
+            writer.append_chunk(payload).expect("append");
+            clock.advance(Duration::from_secs(1));
+        }
+        let summary = writer.finish().expect("finish");
+
+        assert_eq!(summary.deleted, [stale, summary.files[0].clone()]);
+        for path in &foreign {
+            assert!(path.exists(), "{} must survive pruning", path.display());
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn total_byte_budget_never_deletes_the_current_file() {
+        let dir = temp_dir("rotate_budget");
+        let clock = ManualClock::at(1_704_067_200);
+        let mut config = RotatingTakrecConfig::new(&dir, "session");
+        config.rotation.max_file_bytes = Some(1);
+        config.retention.max_total_bytes = Some(1);
+        let mut writer = RotatingTakrecWriter::with_clock(config, TakrecHeader::default(), &clock)
+            .expect("writer");
+        writer.append_chunk(b"first").expect("first");
+        writer.append_chunk(b"second").expect("second");
+        let current = writer.current_path().to_path_buf();
+        let summary = writer.finish().expect("finish");
+
+        assert_eq!(summary.deleted, [summary.files[0].clone()]);
+        assert_eq!(payloads(&current), [b"second".to_vec()]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_zero_limits_and_path_like_prefixes() {
+        let mut config = RotatingTakrecConfig::new(std::env::temp_dir(), "a/b");
+        assert!(matches!(
+            config.validate(),
+            Err(RotateError::InvalidConfig { field: "prefix" })
+        ));
+        config.prefix = "session".to_string();
+        config.retention.max_files = Some(0);
+        assert!(matches!(
+            config.validate(),
+            Err(RotateError::InvalidConfig {
+                field: "retention.max_files"
+            })
+        ));
+    }
+}
@@ -0,0 +1,716 @@
+//! Anonymisation of `.takrec` captures so they can be shared outside the unit.
+//!
+//! Chunks are rewritten in their original order under the original header, so replay
+//! timing is unchanged. Each chunk is parsed as a [`CotEvent`] and, when a
+//! transformation changed it, re-serialized with [`CotEvent::to_xml`]; any
+//! chunk that is not a CoT XML event is treated as opaque.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use rustak_core::{CotEvent, DetailNode, Position};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use thiserror::Error;
+
+use crate::{
+    build_integrity_chain, ChunkCompression, IntegrityChain, RecordWriteError, TakrecReader,
+    TakrecWriter,
+};
+
+const UID_ATTRIBUTES: [&str; 2] = ["uid", "senderUid"];
+const CALLSIGN_ATTRIBUTES: [&str; 3] = ["callsign", "senderCallsign", "parent_callsign"];
+/// Detail attributes that hold a latitude or longitude on their own, as on
+/// `<vertex lat lon>` and `<target latitude longitude>`.
+const LATITUDE_ATTRIBUTES: [&str; 2] = ["lat", "latitude"];
+const LONGITUDE_ATTRIBUTES: [&str; 2] = ["lon", "longitude"];
+const CHAT_TYPE_PREFIX: &str = "b-t-f";
+/// Prefix of GeoChat event UIDs, `GeoChat.<sender uid>.<chat room>.<message id>`.
+const GEOCHAT_UID_PREFIX: &str = "GeoChat";
+/// The room every client shares; any other room is named after a UID or group.
+const PUBLIC_CHAT_ROOM: &str = "All Chat Rooms";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateOffset {
+    pub lat_deg: f64,
+    pub lon_deg: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScrubConfig {
+    /// Added to the `<point>` and to every detail coordinate: `lat`, `lon`,
+    /// `latitude` and `longitude` attributes and the `lat,lon` pair of a
+    /// `point` attribute such as `<link point=...>`. Latitude is clamped and
+    /// longitude wrapped.
+    pub coordinate_offset: Option<CoordinateOffset>,
+    /// Replaces UIDs with stable pseudonyms (`uid-1`, `uid-2`, ...),
+    /// including the sender and room embedded in GeoChat event UIDs and the
+    /// chat room ids of `<__chat>` and `<chatgrp>`.
+    pub rename_uids: bool,
+    /// Replaces callsigns with stable pseudonyms (`CALLSIGN-1`, ...).
+    pub rename_callsigns: bool,
+    /// Empties `<remarks>` on GeoChat (`b-t-f*`) events.
+    pub drop_chat_bodies: bool,
+    /// Drops chunks that do not parse as CoT XML instead of copying them.
+    pub drop_opaque_chunks: bool,
+    /// Bounds each chunk is parsed under; larger events are opaque.
+    pub limits: Limits,
+}
+
+impl ScrubConfig {
+    pub fn validate(&self) -> Result<(), ScrubError> {
+        if let Some(offset) = self.coordinate_offset {
+            if !offset.lat_deg.is_finite() || !(-90.0..=90.0).contains(&offset.lat_deg) {
+                return Err(ScrubError::InvalidOffset { field: "lat_deg" });
+            }
+            if !offset.lon_deg.is_finite() || !(-360.0..=360.0).contains(&offset.lon_deg) {
+                return Err(ScrubError::InvalidOffset { field: "lon_deg" });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScrubReport {
+    pub chunks_read: usize,
+    pub chunks_written: usize,
+    pub chunks_rewritten: usize,
+    pub opaque_chunks: usize,
+    pub opaque_chunks_dropped: usize,
+    pub uids_renamed: usize,
+    pub callsigns_renamed: usize,
+    pub truncated_tail: bool,
+    /// Integrity chain regenerated over the scrubbed payloads.
+    pub integrity: IntegrityChain,
+}
+
+#[derive(Debug, Error)]
+pub enum ScrubError {
+    #[error(transparent)]
+    Record(#[from] RecordWriteError),
+
+    #[error("coordinate offset `{field}` is out of range")]
+    InvalidOffset { field: &'static str },
+}
+
+impl CodedError for ScrubError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Record(error) => error.code(),
+            Self::InvalidOffset { .. } => ErrorCode::new("RECORD", 301),
+        }
+    }
+}
+
+/// Reads a takrec from `source`, applies `config` to every chunk and writes the
+/// result to `sink` with the original header. The output keeps the source's
+/// chunk capture times and, when any chunk was stored compressed, compresses
+/// its chunks at the zstd default level.
+pub fn scrub_recording<R: Read, W: Write>(
+    source: R,
+    sink: W,
+    config: &ScrubConfig,
+) -> Result<ScrubReport, ScrubError> {
+    config.validate()?;
+    let mut reader = TakrecReader::new(source)?;
+    let mut chunks = Vec::new();
+    let mut compressed = false;
+    while let Some(chunk) = reader.next_chunk() {
+        let (_, payload) = chunk?;
+        compressed |= reader.chunk_compressed();
+        chunks.push((payload, reader.chunk_captured()));
+    }
+
+    let compression = if compressed {
+        ChunkCompression::Zstd {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    } else {
+        ChunkCompression::None
+    };
+    let header = reader.header().clone();
+    let mut writer = if reader.capture_times() {
+        TakrecWriter::with_capture_times(sink, header, compression)?
+    } else {
+        TakrecWriter::with_compression(sink, header, compression)?
+    };
+    let mut scrubber = Scrubber::new(config);
+    let mut written = Vec::with_capacity(chunks.len());
+    let mut chunks_rewritten = 0;
+    let mut opaque_chunks = 0;
+    let mut opaque_chunks_dropped = 0;
+
+    for (payload, captured) in &chunks {
+        let scrubbed = match scrubber.scrub_payload(payload) {
+            Some(scrubbed) => {
+                if scrubbed != *payload {
+                    chunks_rewritten += 1;
+                }
+                scrubbed
+            }
+            None => {
+                opaque_chunks += 1;
+                if config.drop_opaque_chunks {
+                    opaque_chunks_dropped += 1;
+                    continue;
+                }
+                payload.to_vec()
+            }
+        };
+        match captured {
+            Some(captured) => writer.append_chunk_at(&scrubbed, *captured)?,
+            None => writer.append_chunk(&scrubbed)?,
+        };
+        written.push(scrubbed);
+    }
+    writer.into_inner()?;
+
+    Ok(ScrubReport {
+        chunks_read: chunks.len(),
+        chunks_written: written.len(),
+        chunks_rewritten,
+        opaque_chunks,
+        opaque_chunks_dropped,
+        uids_renamed: scrubber.uids.len(),
+        callsigns_renamed: scrubber.callsigns.len(),
+        truncated_tail: reader.truncated_tail(),
+        integrity: build_integrity_chain(&written),
+    })
+}
+
+struct Scrubber<'a> {
+    config: &'a ScrubConfig,
+    uids: HashMap<String, String>,
+    callsigns: HashMap<String, String>,
+}
+
+impl<'a> Scrubber<'a> {
+    fn new(config: &'a ScrubConfig) -> Self {
+        Self {
+            config,
+            uids: HashMap::new(),
+            callsigns: HashMap::new(),
+        }
+    }
+
+    /// The scrubbed payload: the original bytes when nothing needed
+    /// changing, else the re-serialized event. `None` for a chunk that is
+    /// not a CoT XML event.
+    fn scrub_payload(&mut self, payload: &[u8]) -> Option<Vec<u8>> {
+        let xml = std::str::from_utf8(payload).ok()?;
+        let original = CotEvent::from_xml(xml, &self.config.limits).ok()?;
+        let mut event = original.clone();
+
+        if self.config.rename_uids {
+            event.uid = self.event_uid(&event.uid);
+        }
+        if let Some(offset) = self.config.coordinate_offset {
+            event.point = shift_position(&event.point, offset)?;
+        }
+        let is_chat = event.cot_type.starts_with(CHAT_TYPE_PREFIX);
+        for node in &mut event.detail {
+            self.scrub_detail(node, is_chat);
+        }
+
+        if event == original {
+            return Some(payload.to_vec());
+        }
+        Some(event.to_xml().into_bytes())
+    }
+
+    fn scrub_detail(&mut self, node: &mut DetailNode, is_chat: bool) {
+        for (name, value) in &mut node.attributes {
+            if let Some(replacement) = self.replacement(&node.name, name, value) {
+                *value = replacement;
+            }
+        }
+        if is_chat && self.config.drop_chat_bodies && node.name == "remarks" {
+            node.text.clear();
+            node.children.clear();
+        }
+        for child in &mut node.children {
+            self.scrub_detail(child, is_chat);
+        }
+    }
+
+    /// GeoChat UIDs keep their shape with the sender and room renamed, so
+    /// replies still line up; any other UID is renamed whole.
+    fn event_uid(&mut self, uid: &str) -> String {
+        let parts = uid.splitn(4, '.').collect::<Vec<_>>();
+        match parts.as_slice() {
+            [GEOCHAT_UID_PREFIX, sender, room, message] => format!(
+                "{GEOCHAT_UID_PREFIX}.{}.{}.{message}",
+                pseudonym(&mut self.uids, sender, "uid-"),
+                self.chat_room(room)
+            ),
+            _ => pseudonym(&mut self.uids, uid, "uid-"),
+        }
+    }
+
+    fn chat_room(&mut self, room: &str) -> String {
+        if room == PUBLIC_CHAT_ROOM {
+            room.to_owned()
+        } else {
+            pseudonym(&mut self.uids, room, "uid-")
+        }
+    }
+
+    fn replacement(&mut self, element: &str, name: &str, value: &str) -> Option<String> {
+        if self.config.rename_uids {
+            if UID_ATTRIBUTES.contains(&name) {
+                return Some(pseudonym(&mut self.uids, value, "uid-"));
+            }
+            // `<chatgrp uid0 uid1 ...>` lists the members, which for the
+            // public room includes the room itself.
+            let room = match element {
+                "__chat" => matches!(name, "id" | "chatroom"),
+                "chatgrp" => name == "id" || name.starts_with("uid"),
+                _ => false,
+            };
+            if room {
+                return Some(self.chat_room(value));
+            }
+        }
+        if self.config.rename_callsigns && CALLSIGN_ATTRIBUTES.contains(&name) {
+            return Some(pseudonym(&mut self.callsigns, value, "CALLSIGN-"));
+        }
+        let offset = self.config.coordinate_offset?;
+        if name == "point" {
+            // `lat,lon[,hae]`; the altitude is left alone.
+            let shifted = value
+                .split(',')
+                .enumerate()
+                .map(|(index, part)| {
+                    let shifted = part
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|parsed| match index {
+                            0 => Some(shift_latitude(parsed, offset)),
+                            1 => Some(shift_longitude(parsed, offset)),
+                            _ => None,
+                        });
+                    shifted.map_or_else(|| part.to_owned(), |shifted| shifted.to_string())
+                })
+                .collect::<Vec<_>>();
+            return Some(shifted.join(","));
+        }
+        let parsed = value.trim().parse::<f64>().ok()?;
+        if LATITUDE_ATTRIBUTES.contains(&name) {
+            Some(shift_latitude(parsed, offset).to_string())
+        } else if LONGITUDE_ATTRIBUTES.contains(&name) {
+            Some(shift_longitude(parsed, offset).to_string())
+        } else {
+            None
+        }
+    }
+}
+
+fn pseudonym(table: &mut HashMap<String, String>, value: &str, prefix: &str) -> String {
+    let next = table.len() + 1;
+    table
+        .entry(value.to_owned())
+        .or_insert_with(|| format!("{prefix}{next}"))
+        .clone()
+}
+
+fn shift_latitude(lat: f64, offset: CoordinateOffset) -> f64 {
+    (lat + offset.lat_deg).clamp(-90.0, 90.0)
+}
+
+fn shift_longitude(lon: f64, offset: CoordinateOffset) -> f64 {
+    wrap_longitude(lon + offset.lon_deg)
+}
+
+fn shift_position(point: &Position, offset: CoordinateOffset) -> Option<Position> {
+    let mut shifted = Position::new(
+        shift_latitude(point.latitude(), offset),
+        shift_longitude(point.longitude(), offset),
+    )
+    .ok()?;
+    if let Some(hae) = point.hae() {
+        shifted = shifted.with_hae(hae).ok()?;
+    }
+    if let Some(ce) = point.ce() {
+        shifted = shifted.with_ce(ce).ok()?;
+    }
+    if let Some(le) = point.le() {
+        shifted = shifted.with_le(le).ok()?;
+    }
+    Some(shifted)
+}
+
+fn wrap_longitude(lon: f64) -> f64 {
+    let wrapped = (lon + 180.0).rem_euclid(360.0) - 180.0;
+    if wrapped == -180.0 && lon > 0.0 {
+        180.0
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use rustak_core::CotEvent;
+    use rustak_limits::Limits;
+
+    use super::{scrub_recording, CoordinateOffset, ScrubConfig, ScrubError};
+    use crate::{recover_chunk_payloads, verify_integrity_chain, SignatureVerifier};
+    use crate::{ChunkCompression, TakrecHeader, TakrecReader, TakrecWriter};
+
+    struct NoVerifier;
+
+    impl SignatureVerifier for NoVerifier {
+        fn verify(&self, _sequence: u64, _chain_hash: &[u8; 32], _signature: &[u8]) -> bool {
+            false
+        }
+    }
+
+    fn recording(chunks: &[&[u8]]) -> (TakrecHeader, Vec<u8>) {
+        let header = TakrecHeader::new("unit-test", "0.1.0", "tak", "conservative");
+        let mut writer = TakrecWriter::new(Vec::new(), header.clone()).expect("writer");
+        for chunk in chunks {
+            writer.append_chunk(chunk).expect("chunk");
+        }
+        (header, writer.into_inner().expect("inner"))
+    }
+
+    fn scrub(chunks: &[&[u8]], config: &ScrubConfig) -> (TakrecHeader, Vec<String>) {
+        let (_, input) = recording(chunks);
+        let mut output = Vec::new();
+        scrub_recording(Cursor::new(input), &mut output, config).expect("scrub");
+        let (report, payloads) = recover_chunk_payloads(Cursor::new(output)).expect("recover");
+        (
+            report.header,
+            payloads
+                .into_iter()
+                .map(|payload| String::from_utf8(payload).expect("utf8"))
+                .collect(),
+        )
+    }
+
+    /// A complete CoT event with `detail` as the body of its `<detail>`.
+    fn event(uid: &str, cot_type: &str, point: &str, detail: &str) -> Vec<u8> {
+        format!("<event version=\"2.0\" uid=\"{uid}\" type=\"{cot_type}\" how=\"h-e\" time=\"2024-01-01T00:00:00Z\" start=\"2024-01-01T00:00:00Z\" stale=\"2024-01-01T00:05:00Z\">{point}<detail>{detail}</detail></event>")
+            .into_bytes()
+    }
+
+    fn parsed(xml: &str) -> CotEvent {
+        CotEvent::from_xml(xml, &Limits::default()).expect("scrubbed event parses")
+    }
+
+    const POINT: &str = "<point lat=\"1\" lon=\"2\"/>";
+
+    #[test]
+    fn renames_uids_and_callsigns_consistently_across_chunks() {
+        let config = ScrubConfig {
+            rename_uids: true,
+            rename_callsigns: true,
+            ..ScrubConfig::default()
+        };
+        let first = event(
+            "ANDROID-abc",
+            "a-f-G",
+            POINT,
+            "<contact callsign=\"VIPER\"/>",
+        );
+        let (_, scrubbed) = scrub(
+            &[
+                &first,
+                &event(
+                    "ANDROID-def",
+                    "a-f-G",
+                    POINT,
+                    "<link uid='ANDROID-abc' relation=\"p-p\"/>",
+                ),
+                &first,
+            ],
+            &config,
+        );
+
+        let renamed = parsed(&scrubbed[0]);
+        assert_eq!(renamed.uid, "uid-1");
+        assert_eq!(
+            renamed
+                .detail_element("contact")
+                .expect("contact")
+                .attribute("callsign"),
+            Some("CALLSIGN-1")
+        );
+        let linked = parsed(&scrubbed[1]);
+        assert_eq!(linked.uid, "uid-2");
+        assert_eq!(
+            linked
+                .detail_element("link")
+                .expect("link")
+                .attribute("uid"),
+            Some("uid-1")
+        );
+        assert_eq!(scrubbed[2], scrubbed[0]);
+    }
+
+    #[test]
+    fn shifts_points_and_wraps_longitude() {
+        let config = ScrubConfig {
+            coordinate_offset: Some(CoordinateOffset {
+                lat_deg: 1.5,
+                lon_deg: 10.0,
+            }),
+            ..ScrubConfig::default()
+        };
+        let (_, scrubbed) = scrub(
+            &[&event(
+                "a",
+                "a-f-G",
+                "<point lat=\"89.5\" lon=\"175\" hae=\"10\" ce=\"5\" le=\"9999999\"/>",
+                "",
+            )],
+            &config,
+        );
+
+        let shifted = parsed(&scrubbed[0]);
+        assert_eq!(shifted.point.latitude(), 90.0);
+        assert_eq!(shifted.point.longitude(), -175.0);
+        assert_eq!(shifted.point.hae(), Some(10.0));
+        assert_eq!(shifted.point.ce(), Some(5.0));
+    }
+
+    #[test]
+    fn shifts_detail_coordinates() {
+        let config = ScrubConfig {
+            coordinate_offset: Some(CoordinateOffset {
+                lat_deg: 1.0,
+                lon_deg: -2.0,
+            }),
+            ..ScrubConfig::default()
+        };
+        let (_, scrubbed) = scrub(
+            &[&event(
+                "route-1",
+                "b-m-r",
+                POINT,
+                "<link uid=\"wp-1\" type=\"b-m-p-w\" point=\"34.5,-117.25,12.5\"/>\
+                 <shape><polyline closed=\"true\"><vertex lat=\"34.25\" lon=\"-117.5\"/></polyline></shape>\
+                 <target latitude=\"10\" longitude=\"179\"/>",
+            )],
+            &config,
+        );
+
+        let shifted = parsed(&scrubbed[0]);
+        let link = shifted.detail_element("link").expect("link");
+        assert_eq!(link.attribute("point"), Some("35.5,-119.25,12.5"));
+        assert_eq!(link.attribute("uid"), Some("wp-1"));
+        let vertex = shifted.detail_element("shape").expect("shape").children[0]
+            .child("vertex")
+            .expect("vertex");
+        assert_eq!(vertex.attribute("lat"), Some("35.25"));
+        assert_eq!(vertex.attribute("lon"), Some("-119.5"));
+        let target = shifted.detail_element("target").expect("target");
+        assert_eq!(target.attribute("latitude"), Some("11"));
+        assert_eq!(target.attribute("longitude"), Some("177"));
+    }
+
+    #[test]
+    fn renames_uids_embedded_in_geochat_and_links() {
+        let config = ScrubConfig {
+            rename_uids: true,
+            rename_callsigns: true,
+            ..ScrubConfig::default()
+        };
+        let (_, scrubbed) = scrub(
+            &[
+                &event(
+                    "GeoChat.ANDROID-abc.All Chat Rooms.msg-1",
+                    "b-t-f",
+                    POINT,
+                    "<__chat id=\"All Chat Rooms\" chatroom=\"All Chat Rooms\" senderCallsign=\"VIPER\">\
+                     <chatgrp uid0=\"ANDROID-abc\" uid1=\"All Chat Rooms\" id=\"All Chat Rooms\"/></__chat>\
+                     <link uid=\"ANDROID-abc\" type=\"a-f-G\" relation=\"p-p\"/>",
+                ),
+                &event(
+                    "GeoChat.ANDROID-abc.ANDROID-def.msg-2",
+                    "b-t-f",
+                    POINT,
+                    "<__chat id=\"ANDROID-def\" chatroom=\"ANDROID-def\" senderCallsign=\"VIPER\">\
+                     <chatgrp uid0=\"ANDROID-abc\" uid1=\"ANDROID-def\" id=\"ANDROID-def\"/></__chat>",
+                ),
+            ],
+            &config,
+        );
+        for scrubbed in &scrubbed {
+            assert!(!scrubbed.contains("ANDROID-"), "{scrubbed}");
+            assert!(!scrubbed.contains("VIPER"), "{scrubbed}");
+        }
+
+        let public = parsed(&scrubbed[0]);
+        assert_eq!(public.uid, "GeoChat.uid-1.All Chat Rooms.msg-1");
+        let chat = public.detail_element("__chat").expect("__chat");
+        assert_eq!(chat.attribute("id"), Some("All Chat Rooms"));
+        assert_eq!(chat.attribute("chatroom"), Some("All Chat Rooms"));
+        assert_eq!(chat.attribute("senderCallsign"), Some("CALLSIGN-1"));
+        let group = chat.child("chatgrp").expect("chatgrp");
+        assert_eq!(group.attribute("uid0"), Some("uid-1"));
+        assert_eq!(group.attribute("uid1"), Some("All Chat Rooms"));
+        assert_eq!(
+            public
+                .detail_element("link")
+                .expect("link")
+                .attribute("uid"),
+            Some("uid-1")
+        );
+
+        let direct = parsed(&scrubbed[1]);
+        assert_eq!(direct.uid, "GeoChat.uid-1.uid-2.msg-2");
+        let chat = direct.detail_element("__chat").expect("__chat");
+        assert_eq!(chat.attribute("id"), Some("uid-2"));
+        assert_eq!(chat.attribute("chatroom"), Some("uid-2"));
+        let group = chat.child("chatgrp").expect("chatgrp");
+        assert_eq!(group.attribute("uid1"), Some("uid-2"));
+        assert_eq!(group.attribute("id"), Some("uid-2"));
+    }
+
+    #[test]
+    fn drops_chat_bodies_only_on_geochat_events() {
+        let config = ScrubConfig {
+            drop_chat_bodies: true,
+            ..ScrubConfig::default()
+        };
+        let (_, scrubbed) = scrub(
+            &[
+                &event(
+                    "chat",
+                    "b-t-f",
+                    POINT,
+                    "<remarks source=\"x\"><![CDATA[secret <plan>]]></remarks>",
+                ),
+                &event("pli", "a-f-G", POINT, "<remarks>keep me</remarks>"),
+            ],
+            &config,
+        );
+
+        let chat = parsed(&scrubbed[0]);
+        let remarks = chat.detail_element("remarks").expect("remarks");
+        assert_eq!(remarks.attribute("source"), Some("x"));
+        assert!(remarks.text.is_empty());
+        assert!(!scrubbed[0].contains("secret"));
+        assert!(scrubbed[1].contains("keep me"));
+    }
+
+    #[test]
+    fn untouched_events_keep_their_bytes() {
+        let original = event("a", "a-f-G", POINT, "<contact callsign='VIPER'/>");
+        let config = ScrubConfig {
+            coordinate_offset: Some(CoordinateOffset {
+                lat_deg: 0.0,
+                lon_deg: 0.0,
+            }),
+            drop_chat_bodies: true,
+            ..ScrubConfig::default()
+        };
+        let (_, scrubbed) = scrub(&[&original], &config);
+        assert_eq!(scrubbed[0].as_bytes(), original.as_slice());
+    }
+
+    #[test]
+    fn preserves_header_and_regenerates_integrity_chain() {
+        let (header, input) = recording(&[
+            &event("a", "a-f-G", POINT, ""),
+            b"\x00\x01binary",
+            b"<event uid=\"partial\"/>",
+        ]);
+        let config = ScrubConfig {
+            rename_uids: true,
+            drop_opaque_chunks: true,
+            ..ScrubConfig::default()
+        };
+        let mut output = Vec::new();
+        let report = scrub_recording(Cursor::new(input), &mut output, &config).expect("scrub");
+
+        assert_eq!(report.chunks_read, 3);
+        assert_eq!(report.chunks_written, 1);
+        assert_eq!(report.chunks_rewritten, 1);
+        assert_eq!(report.opaque_chunks, 2);
+        assert_eq!(report.opaque_chunks_dropped, 2);
+        assert_eq!(report.uids_renamed, 1);
+
+        let (recovered, payloads) = recover_chunk_payloads(Cursor::new(output)).expect("recover");
+        assert_eq!(recovered.header, header);
+        verify_integrity_chain(&payloads, &report.integrity, None::<&NoVerifier>, false)
+            .expect("regenerated chain matches scrubbed payloads");
+    }
+
+    #[test]
+    fn keeps_capture_times_and_compression() {
+        let captured = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let mut writer = TakrecWriter::with_capture_times(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::Zstd { level: 3 },
+        )
+        .expect("writer");
+        let long = event("a", "a-f-G", POINT, &"<remarks>x</remarks>".repeat(32));
+        for (at, payload) in [long.as_slice(), b"opaque"].into_iter().enumerate() {
+            writer
+                .append_chunk_at(payload, captured + Duration::from_secs(at as u64 * 10))
+                .expect("append");
+        }
+        let input = writer.into_inner().expect("finish");
+
+        let mut output = Vec::new();
+        let config = ScrubConfig {
+            rename_uids: true,
+            ..ScrubConfig::default()
+        };
+        scrub_recording(Cursor::new(input), &mut output, &config).expect("scrub");
+
+        let mut reader = TakrecReader::new(output.as_slice()).expect("reader");
+        assert!(reader.capture_times());
+        let (_, scrubbed) = reader.next_chunk().expect("first").expect("chunk");
+        assert!(reader.chunk_compressed());
+        assert_eq!(reader.chunk_captured(), Some(captured));
+        assert_eq!(
+            parsed(std::str::from_utf8(&scrubbed).expect("utf8")).uid,
+            "uid-1"
+        );
+        reader.next_chunk().expect("second").expect("chunk");
+        assert!(!reader.chunk_compressed(), "too small to shrink");
+        assert_eq!(
+            reader.chunk_captured(),
+            Some(captured + Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn plain_recordings_stay_plain() {
+        let (_, input) = recording(&[&event("a", "a-f-G", POINT, "")]);
+        let mut output = Vec::new();
+        scrub_recording(
+            Cursor::new(input.clone()),
+            &mut output,
+            &ScrubConfig::default(),
+        )
+        .expect("scrub");
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn rejects_out_of_range_offsets() {
+        let config = ScrubConfig {
+            coordinate_offset: Some(CoordinateOffset {
+                lat_deg: 120.0,
+                lon_deg: 0.0,
+            }),
+            ..ScrubConfig::default()
+        };
+        let (_, input) = recording(&[b"<event/>"]);
+        let error = scrub_recording(Cursor::new(input), Vec::new(), &config)
+            .expect_err("offset out of range");
+        assert!(matches!(
+            error,
+            ScrubError::InvalidOffset { field: "lat_deg" }
+        ));
+    }
+}
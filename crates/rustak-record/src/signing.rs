@@ -0,0 +1,84 @@
+//! Integrity chain signatures backed by `rustak-crypto` keys.
+//!
+//! [`DetachedSigner`] signs each link and [`DetachedVerifier`] checks it, so
+//! an Ed25519 or ECDSA P-256 key loaded from PEM plugs straight into
+//! [`build_integrity_chain_with_signer`](crate::build_integrity_chain_with_signer)
+//! and [`verify_integrity_chain`](crate::verify_integrity_chain). The signed
+//! message is a domain tag, the link sequence and the chain hash, so a
+//! signature cannot be replayed onto another link or reused outside takrec.
+
+pub use rustak_crypto::{DetachedAlgorithm, DetachedSigner, DetachedVerifier};
+
+use crate::{SignatureProvider, SignatureVerifier};
+
+const LINK_SIGNATURE_DOMAIN: &[u8] = b"rustak-takrec-chain-link\0";
+
+impl SignatureProvider for DetachedSigner {
+    fn sign(&self, sequence: u64, chain_hash: &[u8; 32]) -> Option<Vec<u8>> {
+        DetachedSigner::sign(self, &link_message(sequence, chain_hash)).ok()
+    }
+}
+
+impl SignatureVerifier for DetachedVerifier {
+    fn verify(&self, sequence: u64, chain_hash: &[u8; 32], signature: &[u8]) -> bool {
+        DetachedVerifier::verify(self, &link_message(sequence, chain_hash), signature)
+    }
+}
+
+fn link_message(sequence: u64, chain_hash: &[u8; 32]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(LINK_SIGNATURE_DOMAIN.len() + 8 + chain_hash.len());
+    message.extend_from_slice(LINK_SIGNATURE_DOMAIN);
+    message.extend_from_slice(&sequence.to_be_bytes());
+    message.extend_from_slice(chain_hash);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{KeyPair, PKCS_ECDSA_P256_SHA256, PKCS_ED25519};
+
+    use super::{DetachedSigner, DetachedVerifier};
+    use crate::{
+        build_integrity_chain_with_signer, verify_integrity_chain, IntegrityChain, IntegrityError,
+        SignatureProvider,
+    };
+
+    fn payloads() -> Vec<Vec<u8>> {
+        vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+    }
+
+    #[test]
+    fn ed25519_and_p256_chains_verify_after_a_sidecar_round_trip() {
+        for algorithm in [&PKCS_ED25519, &PKCS_ECDSA_P256_SHA256] {
+            let key = KeyPair::generate_for(algorithm).expect("key");
+            let signer = DetachedSigner::from_pem(&key.serialize_pem()).expect("signer");
+            let verifier = DetachedVerifier::from_pem(&key.public_key_pem()).expect("verifier");
+
+            let chain = build_integrity_chain_with_signer(&payloads(), &signer);
+            let chain = IntegrityChain::from_sidecar(&chain.to_sidecar()).expect("sidecar");
+            verify_integrity_chain(&payloads(), &chain, Some(&verifier), true)
+                .expect("signed chain verifies");
+        }
+    }
+
+    #[test]
+    fn signatures_do_not_move_between_links_or_keys() {
+        let key = KeyPair::generate_for(&PKCS_ED25519).expect("key");
+        let signer = DetachedSigner::from_pem(&key.serialize_pem()).expect("signer");
+        let mut chain = build_integrity_chain_with_signer(&payloads(), &signer);
+        chain.links[1].signature = chain.links[0].signature.clone();
+        assert_eq!(
+            verify_integrity_chain(&payloads(), &chain, Some(&signer.verifier()), true),
+            Err(IntegrityError::InvalidSignature { sequence: 1 })
+        );
+
+        let other = KeyPair::generate_for(&PKCS_ED25519).expect("key");
+        let other = DetachedSigner::from_pem(&other.serialize_pem()).expect("signer");
+        let chain = build_integrity_chain_with_signer(&payloads(), &signer);
+        assert_eq!(
+            verify_integrity_chain(&payloads(), &chain, Some(&other.verifier()), true),
+            Err(IntegrityError::InvalidSignature { sequence: 0 })
+        );
+        assert!(SignatureProvider::sign(&signer, 0, &[0; 32]).is_some());
+    }
+}
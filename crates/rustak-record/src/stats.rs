@@ -0,0 +1,572 @@
+//! Constant-memory statistics over `.takrec` captures of any length.
+//!
+//! Chunks are streamed through a [`TakrecReader`], so nothing grows with the
+//! recording. Per-type and per-UID counts use a Space-Saving heavy-hitter
+//! sketch bounded by [`StatsConfig::max_tracked_keys`]; counts are exact
+//! until the first eviction and upper bounds afterwards. Rates and gaps come
+//! from each chunk's recorded capture time, or from the CoT event `time` in
+//! files that store none.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+
+use rustak_core::CotEvent;
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use thiserror::Error;
+
+use crate::{ChunkCommit, RecordWriteError, TakrecReader};
+
+/// Lower bounds of the messages-per-window histogram bins; the last bin is
+/// open-ended.
+pub const RATE_BIN_LOWER_BOUNDS: [u64; 11] = [0, 1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsConfig {
+    /// Distinct types and distinct UIDs each tracked at most.
+    pub max_tracked_keys: usize,
+    /// Width of the windows whose message counts feed the rate histogram.
+    pub rate_window: Duration,
+    /// Silences longer than this between consecutive events count as gaps.
+    pub gap_threshold: Duration,
+    /// Longest gaps kept for the report.
+    pub max_reported_gaps: usize,
+    /// Bounds each decoded event is parsed under.
+    pub limits: Limits,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            max_tracked_keys: 1_024,
+            rate_window: Duration::from_secs(1),
+            gap_threshold: Duration::from_secs(30),
+            max_reported_gaps: 10,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl StatsConfig {
+    pub fn validate(&self) -> Result<(), StatsError> {
+        if self.max_tracked_keys == 0 {
+            return Err(StatsError::InvalidConfig {
+                field: "max_tracked_keys",
+            });
+        }
+        if self.rate_window.is_zero() {
+            return Err(StatsError::InvalidConfig {
+                field: "rate_window",
+            });
+        }
+        if self.gap_threshold.is_zero() {
+            return Err(StatsError::InvalidConfig {
+                field: "gap_threshold",
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StatsError {
+    #[error("stats config field `{field}` must be greater than zero")]
+    InvalidConfig { field: &'static str },
+
+    #[error(transparent)]
+    Record(#[from] RecordWriteError),
+}
+
+impl CodedError for StatsError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidConfig { .. } => ErrorCode::new("RECORD", 401),
+            Self::Record(error) => error.code(),
+        }
+    }
+}
+
+/// A tracked key and how often it was seen. After evictions `count` is an
+/// upper bound that overstates the true count by at most `overcount`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCount {
+    pub key: String,
+    pub count: u64,
+    pub overcount: u64,
+}
+
+/// Space-Saving heavy hitters: at most `capacity` keys, with a new key
+/// replacing the least frequent one once full.
+#[derive(Debug, Clone)]
+struct HeavyHitters {
+    capacity: usize,
+    counts: HashMap<String, (u64, u64)>,
+    evictions: u64,
+}
+
+impl HeavyHitters {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::with_capacity(capacity),
+            evictions: 0,
+        }
+    }
+
+    fn observe(&mut self, key: &str) {
+        if let Some((count, _)) = self.counts.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        if self.counts.len() < self.capacity {
+            self.counts.insert(key.to_owned(), (1, 0));
+            return;
+        }
+        let Some((evicted, minimum)) = self
+            .counts
+            .iter()
+            .min_by(|(a_key, (a, _)), (b_key, (b, _))| a.cmp(b).then_with(|| a_key.cmp(b_key)))
+            .map(|(key, (count, _))| (key.clone(), *count))
+        else {
+            return;
+        };
+        self.counts.remove(&evicted);
+        self.counts.insert(key.to_owned(), (minimum + 1, minimum));
+        self.evictions += 1;
+    }
+
+    fn summary(&self, top: usize) -> KeySummary {
+        let mut keys = self
+            .counts
+            .iter()
+            .map(|(key, (count, overcount))| KeyCount {
+                key: key.clone(),
+                count: *count,
+                overcount: *overcount,
+            })
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+        keys.truncate(top);
+        KeySummary {
+            top: keys,
+            tracked: self.counts.len(),
+            exact: self.evictions == 0,
+        }
+    }
+}
+
+/// The most frequent keys of one kind (types or UIDs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySummary {
+    pub top: Vec<KeyCount>,
+    pub tracked: usize,
+    /// `false` once any key was evicted; counts are then upper bounds and
+    /// `tracked` is no longer the number of distinct keys.
+    pub exact: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateBin {
+    pub min_messages: u64,
+    /// `None` for the open-ended last bin.
+    pub max_messages: Option<u64>,
+    pub windows: u64,
+}
+
+/// How many `window`-wide slices of the capture saw each message rate.
+/// Empty slices inside the capture land in the zero bin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateHistogram {
+    pub window: Duration,
+    pub bins: Vec<RateBin>,
+    pub peak_messages_per_window: u64,
+}
+
+/// A silence between two consecutive events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// Sequence of the chunk that ended the silence.
+    pub sequence: u64,
+    pub start: SystemTime,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GapSummary {
+    pub threshold: Duration,
+    pub count: u64,
+    pub total: Duration,
+    /// Longest first.
+    pub longest: Vec<Gap>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsReport {
+    pub frames: u64,
+    pub bytes: u64,
+    /// Chunks the decoder could not turn into a CoT event.
+    pub undecodable: u64,
+    /// Events without a capture time whose `time` is outside the
+    /// `SystemTime` range; counted but left out of rates and gaps.
+    pub undated: u64,
+    pub first_time: Option<SystemTime>,
+    pub last_time: Option<SystemTime>,
+    pub types: KeySummary,
+    pub uids: KeySummary,
+    pub rates: RateHistogram,
+    pub gaps: GapSummary,
+    pub truncated_tail: bool,
+}
+
+impl StatsReport {
+    /// Time between the earliest and latest event.
+    #[must_use]
+    pub fn span(&self) -> Duration {
+        match (self.first_time, self.last_time) {
+            (Some(first), Some(last)) => last.duration_since(first).unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Incremental accumulator behind [`recording_stats`].
+#[derive(Debug, Clone)]
+pub struct RecordingStats {
+    config: StatsConfig,
+    frames: u64,
+    bytes: u64,
+    undecodable: u64,
+    undated: u64,
+    first_time: Option<SystemTime>,
+    last_time: Option<SystemTime>,
+    types: HeavyHitters,
+    uids: HeavyHitters,
+    window_start: Option<SystemTime>,
+    window_messages: u64,
+    rate_bins: [u64; RATE_BIN_LOWER_BOUNDS.len()],
+    peak_messages: u64,
+    gap_count: u64,
+    gap_total: Duration,
+    longest_gaps: Vec<Gap>,
+}
+
+impl RecordingStats {
+    pub fn new(config: StatsConfig) -> Result<Self, StatsError> {
+        config.validate()?;
+        Ok(Self {
+            types: HeavyHitters::new(config.max_tracked_keys),
+            uids: HeavyHitters::new(config.max_tracked_keys),
+            config,
+            frames: 0,
+            bytes: 0,
+            undecodable: 0,
+            undated: 0,
+            first_time: None,
+            last_time: None,
+            window_start: None,
+            window_messages: 0,
+            rate_bins: [0; RATE_BIN_LOWER_BOUNDS.len()],
+            peak_messages: 0,
+            gap_count: 0,
+            gap_total: Duration::ZERO,
+            longest_gaps: Vec::new(),
+        })
+    }
+
+    /// Accounts one chunk; `captured` is its recorded capture time and
+    /// `cot_xml` its decoded event, if any. Without a capture time the
+    /// event `time` stands in.
+    pub fn observe(
+        &mut self,
+        chunk: &ChunkCommit,
+        captured: Option<SystemTime>,
+        cot_xml: Option<&str>,
+    ) {
+        self.frames += 1;
+        self.bytes += u64::from(chunk.payload_len);
+        let Some(event) = cot_xml.and_then(|xml| CotEvent::from_xml(xml, &self.config.limits).ok())
+        else {
+            self.undecodable += 1;
+            return;
+        };
+        self.types.observe(&event.cot_type);
+        self.uids.observe(&event.uid);
+
+        match captured.or_else(|| event.time.to_system_time().ok()) {
+            Some(time) => self.observe_time(chunk.sequence, time),
+            None => self.undated += 1,
+        }
+    }
+
+    fn observe_time(&mut self, sequence: u64, time: SystemTime) {
+        if let Some(latest) = self.last_time {
+            if let Ok(silence) = time.duration_since(latest) {
+                if silence > self.config.gap_threshold {
+                    self.record_gap(Gap {
+                        sequence,
+                        start: latest,
+                        duration: silence,
+                    });
+                }
+            }
+        }
+        self.first_time = Some(self.first_time.map_or(time, |first| first.min(time)));
+        self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+
+        let window = self.config.rate_window;
+        match self.window_start {
+            None => {
+                self.window_start = Some(time);
+                self.window_messages = 1;
+            }
+            Some(start) => match time.duration_since(start) {
+                // Events stamped before the window opened are late
+                // arrivals and count towards it.
+                Ok(elapsed) if elapsed >= window => {
+                    let windows = elapsed.as_nanos() / window.as_nanos();
+                    self.close_window();
+                    self.rate_bins[0] += u64::try_from(windows - 1).unwrap_or(u64::MAX);
+                    let advance = u32::try_from(windows)
+                        .ok()
+                        .and_then(|windows| window.checked_mul(windows))
+                        .unwrap_or(elapsed);
+                    self.window_start = Some(start + advance);
+                    self.window_messages = 1;
+                }
+                _ => self.window_messages += 1,
+            },
+        }
+    }
+
+    fn close_window(&mut self) {
+        let bin = RATE_BIN_LOWER_BOUNDS
+            .iter()
+            .rposition(|lower| self.window_messages >= *lower)
+            .unwrap_or(0);
+        self.rate_bins[bin] += 1;
+        self.peak_messages = self.peak_messages.max(self.window_messages);
+    }
+
+    fn record_gap(&mut self, gap: Gap) {
+        self.gap_count += 1;
+        self.gap_total += gap.duration;
+        if self.config.max_reported_gaps == 0 {
+            return;
+        }
+        let at = self
+            .longest_gaps
+            .partition_point(|kept| kept.duration >= gap.duration);
+        if at < self.config.max_reported_gaps {
+            self.longest_gaps.insert(at, gap);
+            self.longest_gaps.truncate(self.config.max_reported_gaps);
+        }
+    }
+
+    /// Closes the open rate window and reports the `top` most frequent types
+    /// and UIDs.
+    #[must_use]
+    pub fn finish(mut self, top: usize, truncated_tail: bool) -> StatsReport {
+        if self.window_start.is_some() {
+            self.close_window();
+        }
+        let bins = RATE_BIN_LOWER_BOUNDS
+            .iter()
+            .zip(self.rate_bins)
+            .enumerate()
+            .map(|(index, (lower, windows))| RateBin {
+                min_messages: *lower,
+                max_messages: RATE_BIN_LOWER_BOUNDS.get(index + 1).map(|next| next - 1),
+                windows,
+            })
+            .collect();
+        StatsReport {
+            frames: self.frames,
+            bytes: self.bytes,
+            undecodable: self.undecodable,
+            undated: self.undated,
+            first_time: self.first_time,
+            last_time: self.last_time,
+            types: self.types.summary(top),
+            uids: self.uids.summary(top),
+            rates: RateHistogram {
+                window: self.config.rate_window,
+                bins,
+                peak_messages_per_window: self.peak_messages,
+            },
+            gaps: GapSummary {
+                threshold: self.config.gap_threshold,
+                count: self.gap_count,
+                total: self.gap_total,
+                longest: self.longest_gaps,
+            },
+            truncated_tail,
+        }
+    }
+}
+
+/// Streams a capture through [`RecordingStats`]. `decode` turns a chunk
+/// payload into CoT XML, returning `None` for anything else.
+pub fn recording_stats<R, F>(
+    source: R,
+    config: StatsConfig,
+    top: usize,
+    mut decode: F,
+) -> Result<StatsReport, StatsError>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> Option<String>,
+{
+    let mut stats = RecordingStats::new(config)?;
+    let mut reader = TakrecReader::new(source)?;
+    while let Some(chunk) = reader.next_chunk() {
+        let (chunk, payload) = chunk?;
+        stats.observe(&chunk, reader.chunk_captured(), decode(&payload).as_deref());
+    }
+    Ok(stats.finish(top, reader.truncated_tail()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{recording_stats, StatsConfig, StatsError};
+    use crate::{ChunkCompression, TakrecHeader, TakrecWriter};
+
+    fn event(uid: &str, cot_type: &str, time: &str) -> Vec<u8> {
+        format!("<event version=\"2.0\" uid=\"{uid}\" type=\"{cot_type}\" how=\"m-g\" time=\"{time}\" start=\"{time}\" stale=\"{time}\"><point lat=\"1\" lon=\"2\"/></event>")
+            .into_bytes()
+    }
+
+    fn capture(payloads: &[Vec<u8>]) -> Vec<u8> {
+        let mut writer = TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
+        for payload in payloads {
+            writer.append_chunk(payload).expect("append");
+        }
+        writer.into_inner().expect("finish")
+    }
+
+    fn utf8(payload: &[u8]) -> Option<String> {
+        String::from_utf8(payload.to_vec()).ok()
+    }
+
+    #[test]
+    fn stats_count_types_uids_rates_and_gaps() {
+        let recording = capture(&[
+            event("a", "a-f-G", "2024-01-01T00:00:00.000Z"),
+            event("b", "a-h-G", "2024-01-01T00:00:00.400Z"),
+            event("a", "a-f-G", "2024-01-01T00:00:00.800Z"),
+            b"opaque".to_vec(),
+            event("a", "a-f-G", "2024-01-01T00:00:03.500Z"),
+            event("c", "a-f-G", "2024-01-01T00:01:00.000Z"),
+        ]);
+        let config = StatsConfig {
+            gap_threshold: Duration::from_secs(10),
+            ..StatsConfig::default()
+        };
+        let report = recording_stats(recording.as_slice(), config, 2, utf8).expect("stats");
+
+        assert_eq!(report.frames, 6);
+        assert_eq!(report.undecodable, 1);
+        assert_eq!(report.undated, 0);
+        assert_eq!(report.span(), Duration::from_secs(60));
+        assert_eq!(
+            report
+                .types
+                .top
+                .iter()
+                .map(|key| (key.key.as_str(), key.count))
+                .collect::<Vec<_>>(),
+            [("a-f-G", 4), ("a-h-G", 1)]
+        );
+        assert!(report.uids.exact);
+        assert_eq!(report.uids.tracked, 3);
+
+        // Windows: [0s,1s) holds 3 events, 1s and 2s are empty, [3s,4s) holds
+        // one, 4s..59s are empty and the final event opens the last window.
+        let windows = |min: u64| {
+            report
+                .rates
+                .bins
+                .iter()
+                .find(|bin| bin.min_messages == min)
+                .expect("bin")
+                .windows
+        };
+        assert_eq!(windows(0), 2 + 56);
+        assert_eq!(windows(1), 2);
+        assert_eq!(windows(2), 1);
+        assert_eq!(report.rates.peak_messages_per_window, 3);
+
+        assert_eq!(report.gaps.count, 1);
+        assert_eq!(report.gaps.longest[0].sequence, 5);
+        assert_eq!(
+            report.gaps.longest[0].duration,
+            Duration::from_millis(56_500)
+        );
+    }
+
+    #[test]
+    fn stats_time_chunks_by_their_capture_time() {
+        let mut writer = TakrecWriter::with_capture_times(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::None,
+        )
+        .expect("writer");
+        let captured = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        for (offset, uid) in [(0, "a"), (2, "b"), (45, "c")] {
+            writer
+                .append_chunk_at(
+                    &event(uid, "a-f-G", "2000-01-01T00:00:00Z"),
+                    captured + Duration::from_secs(offset),
+                )
+                .expect("append");
+        }
+        let recording = writer.into_inner().expect("finish");
+        let report =
+            recording_stats(recording.as_slice(), StatsConfig::default(), 1, utf8).expect("stats");
+
+        assert_eq!(report.first_time, Some(captured));
+        assert_eq!(report.span(), Duration::from_secs(45));
+        assert_eq!(report.gaps.count, 1);
+        assert_eq!(report.gaps.longest[0].sequence, 2);
+        assert_eq!(report.gaps.longest[0].duration, Duration::from_secs(43));
+    }
+
+    #[test]
+    fn heavy_hitters_stay_bounded_and_flag_approximate_counts() {
+        let mut payloads = vec![event("hot", "a-f-G", "2024-01-01T00:00:00Z"); 5];
+        payloads.extend(
+            (0..6).map(|index| event(&format!("cold-{index}"), "a-f-G", "2024-01-01T00:00:00Z")),
+        );
+        let config = StatsConfig {
+            max_tracked_keys: 4,
+            ..StatsConfig::default()
+        };
+        let report =
+            recording_stats(capture(&payloads).as_slice(), config, 1, utf8).expect("stats");
+
+        assert_eq!(report.uids.tracked, 4);
+        assert!(!report.uids.exact);
+        assert_eq!(report.uids.top[0].key, "hot");
+        assert_eq!(report.uids.top[0].count, 5);
+        assert!(report.types.exact);
+
+        let error = recording_stats(
+            capture(&payloads).as_slice(),
+            StatsConfig {
+                rate_window: Duration::ZERO,
+                ..StatsConfig::default()
+            },
+            1,
+            utf8,
+        )
+        .expect_err("zero window");
+        assert!(matches!(
+            error,
+            StatsError::InvalidConfig {
+                field: "rate_window"
+            }
+        ));
+    }
+}
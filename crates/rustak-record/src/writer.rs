@@ -1,13 +1,33 @@
 use std::io::{self, BufWriter, Read, Write};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use rustak_core::TimeSource;
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
+/// Largest chunk payload a writer accepts, and the largest stored or
+/// inflated chunk length a reader trusts, unless configured otherwise.
 pub const DEFAULT_MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
 
 const FILE_MAGIC: [u8; 8] = *b"TAKREC01";
 const FILE_VERSION: u16 = 1;
+/// Files that may hold compressed chunks; v1 readers reject them up front.
+const FILE_VERSION_COMPRESSED: u16 = 2;
+/// Files whose chunks carry their capture time; v1 and v2 readers reject
+/// them up front.
+const FILE_VERSION_TIMED: u16 = 3;
 const CHUNK_MAGIC: [u8; 4] = *b"CHNK";
+/// A chunk whose header carries a codec byte and the stored length.
+const CHUNK_MAGIC_COMPRESSED: [u8; 4] = *b"CHNC";
+/// A chunk whose header carries a codec byte, the capture time and, unless
+/// the codec is [`CODEC_NONE`], the stored length.
+const CHUNK_MAGIC_TIMED: [u8; 4] = *b"CHNT";
+const CODEC_NONE: u8 = 0;
+const CODEC_ZSTD: u8 = 1;
+/// Magic, sequence, length, checksum and commit marker around a plain
+/// chunk; compressed chunks add the codec byte and stored length, timed
+/// chunks the codec byte and capture time.
+const CHUNK_FRAMING_BYTES: u64 = 24;
 const CHUNK_COMMIT_MARKER: u32 = 0xC0DE_CAFE;
 const MAX_HEADER_FIELD_LEN: usize = 4 * 1024;
 
@@ -36,6 +56,17 @@ impl TakrecHeader {
             created_unix_nanos: now_unix_nanos(),
         }
     }
+
+    /// Re-stamps `created_unix_nanos` from `source` rather than the system
+    /// clock.
+    #[must_use]
+    pub fn stamped_by(mut self, source: &dyn TimeSource) -> Self {
+        self.created_unix_nanos = source
+            .now_system()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, duration_to_nanos);
+        self
+    }
 }
 
 impl Default for TakrecHeader {
@@ -50,6 +81,34 @@ impl Default for TakrecHeader {
     }
 }
 
+/// Per-chunk compression for [`TakrecWriter::with_compression`]. Chunks
+/// that would not shrink are stored as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkCompression {
+    #[default]
+    None,
+    /// `level` as zstd takes it; higher is smaller and slower.
+    Zstd { level: i32 },
+}
+
+impl ChunkCompression {
+    pub fn validate(&self) -> Result<(), RecordWriteError> {
+        if let Self::Zstd { level } = *self {
+            let range = zstd::compression_level_range();
+            if !range.contains(&level) {
+                return Err(RecordWriteError::InvalidCompressionLevel {
+                    level,
+                    min: *range.start(),
+                    max: *range.end(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `payload_len` and `checksum` describe the payload as appended, before
+/// any compression.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChunkCommit {
     pub sequence: u64,
@@ -64,21 +123,68 @@ pub struct RecoveryReport {
     pub truncated_tail: bool,
 }
 
+/// What [`for_each_chunk`] read: the header, how many chunks were committed
+/// and whether the file ends in a partially written chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkScan {
+    pub header: TakrecHeader,
+    pub chunks: u64,
+    pub truncated_tail: bool,
+}
+
 #[derive(Debug)]
 pub struct TakrecWriter<W: Write> {
     sink: BufWriter<W>,
     header: TakrecHeader,
     max_chunk_bytes: usize,
     next_sequence: u64,
+    compression: ChunkCompression,
+    capture_times: bool,
+    bytes_written: u64,
 }
 
 impl<W: Write> TakrecWriter<W> {
     pub fn new(sink: W, header: TakrecHeader) -> Result<Self, RecordWriteError> {
+        Self::with_compression(sink, header, ChunkCompression::None)
+    }
+
+    /// Like [`Self::new`], compressing each chunk. Anything but
+    /// [`ChunkCompression::None`] writes a version 2 file, which older
+    /// readers refuse; uncompressed output stays byte-for-byte version 1.
+    pub fn with_compression(
+        sink: W,
+        header: TakrecHeader,
+        compression: ChunkCompression,
+    ) -> Result<Self, RecordWriteError> {
+        Self::open(sink, header, compression, false)
+    }
+
+    /// Like [`Self::with_compression`], storing a capture time with every
+    /// chunk so readers and the chunk index need not infer one from the
+    /// payload. Writes a version 3 file, which older readers refuse.
+    pub fn with_capture_times(
+        sink: W,
+        header: TakrecHeader,
+        compression: ChunkCompression,
+    ) -> Result<Self, RecordWriteError> {
+        Self::open(sink, header, compression, true)
+    }
+
+    fn open(
+        sink: W,
+        header: TakrecHeader,
+        compression: ChunkCompression,
+        capture_times: bool,
+    ) -> Result<Self, RecordWriteError> {
+        compression.validate()?;
         let mut writer = Self {
             sink: BufWriter::new(sink),
             header,
             max_chunk_bytes: DEFAULT_MAX_CHUNK_BYTES,
             next_sequence: 0,
+            compression,
+            capture_times,
+            bytes_written: 0,
         };
 
         writer.write_header()?;
@@ -106,7 +212,36 @@ impl<W: Write> TakrecWriter<W> {
         self.next_sequence
     }
 
+    #[must_use]
+    pub const fn compression(&self) -> ChunkCompression {
+        self.compression
+    }
+
+    /// Whether chunks carry a capture time; see [`Self::with_capture_times`].
+    #[must_use]
+    pub const fn capture_times(&self) -> bool {
+        self.capture_times
+    }
+
+    /// File size so far: the header plus every appended chunk as stored.
+    #[must_use]
+    pub const fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Appends `payload`, stamped with the current time when the writer
+    /// stores capture times.
     pub fn append_chunk(&mut self, payload: &[u8]) -> Result<ChunkCommit, RecordWriteError> {
+        self.append_chunk_at(payload, SystemTime::now())
+    }
+
+    /// Appends `payload` captured at `captured`. Writers without
+    /// [`Self::capture_times`] have nowhere to store the time and drop it.
+    pub fn append_chunk_at(
+        &mut self,
+        payload: &[u8],
+        captured: SystemTime,
+    ) -> Result<ChunkCommit, RecordWriteError> {
         if payload.len() > self.max_chunk_bytes {
             return Err(RecordWriteError::ChunkTooLarge {
                 payload_len: payload.len(),
@@ -132,13 +267,51 @@ impl<W: Write> TakrecWriter<W> {
             checksum,
         };
 
-        self.sink.write_all(&CHUNK_MAGIC)?;
+        let compressed = match self.compression {
+            ChunkCompression::None => None,
+            ChunkCompression::Zstd { level } => Some(zstd::bulk::compress(payload, level)?)
+                .filter(|compressed| compressed.len() < payload.len()),
+        };
+        let mut framing = CHUNK_FRAMING_BYTES;
+        if self.capture_times {
+            let codec = if compressed.is_some() {
+                CODEC_ZSTD
+            } else {
+                CODEC_NONE
+            };
+            self.sink.write_all(&CHUNK_MAGIC_TIMED)?;
+            self.sink.write_all(&[codec])?;
+            let captured = captured
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, duration_to_nanos);
+            write_u64_le(&mut self.sink, captured)?;
+            framing += 9;
+        } else {
+            match &compressed {
+                None => self.sink.write_all(&CHUNK_MAGIC)?,
+                Some(_) => {
+                    self.sink.write_all(&CHUNK_MAGIC_COMPRESSED)?;
+                    self.sink.write_all(&[CODEC_ZSTD])?;
+                    framing += 1;
+                }
+            }
+        }
         write_u64_le(&mut self.sink, commit.sequence)?;
         write_u32_le(&mut self.sink, commit.payload_len)?;
         write_u32_le(&mut self.sink, commit.checksum)?;
-        self.sink.write_all(payload)?;
+        match &compressed {
+            None => self.sink.write_all(payload)?,
+            Some(stored) => {
+                // Shorter than the payload, so it fits the u32 as well.
+                write_u32_le(&mut self.sink, stored.len() as u32)?;
+                self.sink.write_all(stored)?;
+                framing += 4;
+            }
+        }
         write_u32_le(&mut self.sink, CHUNK_COMMIT_MARKER)?;
         self.flush_boundary()?;
+        let stored_len = compressed.as_ref().map_or(payload.len(), Vec::len);
+        self.bytes_written += framing + stored_len as u64;
         Ok(commit)
     }
 
@@ -154,8 +327,13 @@ impl<W: Write> TakrecWriter<W> {
     }
 
     fn write_header(&mut self) -> Result<(), RecordWriteError> {
+        let version = match self.compression {
+            _ if self.capture_times => FILE_VERSION_TIMED,
+            ChunkCompression::None => FILE_VERSION,
+            ChunkCompression::Zstd { .. } => FILE_VERSION_COMPRESSED,
+        };
         self.sink.write_all(&FILE_MAGIC)?;
-        write_u16_le(&mut self.sink, FILE_VERSION)?;
+        write_u16_le(&mut self.sink, version)?;
         write_u64_le(&mut self.sink, self.header.created_unix_nanos)?;
         write_len_prefixed_string(&mut self.sink, "tool_name", &self.header.tool_name)?;
         write_len_prefixed_string(&mut self.sink, "tool_version", &self.header.tool_version)?;
@@ -165,104 +343,235 @@ impl<W: Write> TakrecWriter<W> {
             "limits_profile",
             &self.header.limits_profile,
         )?;
+        let fields = [
+            &self.header.tool_name,
+            &self.header.tool_version,
+            &self.header.protocol_hint,
+            &self.header.limits_profile,
+        ];
+        // Magic, version and creation time, then length-prefixed fields.
+        self.bytes_written = 18
+            + fields
+                .iter()
+                .map(|field| 2 + field.len() as u64)
+                .sum::<u64>();
         Ok(())
     }
 }
 
-pub fn recover_chunk_index<R: Read>(mut source: R) -> Result<RecoveryReport, RecordWriteError> {
-    let header = read_header(&mut source)?;
-    let mut chunks = Vec::new();
-    let mut truncated_tail = false;
+pub fn recover_chunk_index<R: Read>(source: R) -> Result<RecoveryReport, RecordWriteError> {
+    recover_chunks(source, |_| {})
+}
 
-    loop {
-        let magic = match read_array_status::<4, _>(&mut source)? {
-            ReadStatus::Complete(magic) => magic,
-            ReadStatus::Eof => break,
-            ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+/// Streams every committed chunk to `on_chunk` in sequence order without
+/// keeping payloads or the chunk index, so memory stays bounded by the
+/// largest chunk however long the recording is.
+pub fn for_each_chunk<R: Read, F: FnMut(&ChunkCommit, &[u8])>(
+    source: R,
+    mut on_chunk: F,
+) -> Result<ChunkScan, RecordWriteError> {
+    let mut chunks = 0;
+    let (header, truncated_tail) = scan_chunks(source, |commit, payload| {
+        chunks += 1;
+        on_chunk(&commit, &payload);
+    })?;
+    Ok(ChunkScan {
+        header,
+        chunks,
+        truncated_tail,
+    })
+}
 
-        if magic != CHUNK_MAGIC {
-            return Err(RecordWriteError::CorruptChunkMagic { found: magic });
-        }
+/// Like [`recover_chunk_index`], but also returns every committed payload in
+/// sequence order.
+pub fn recover_chunk_payloads<R: Read>(
+    source: R,
+) -> Result<(RecoveryReport, Vec<Vec<u8>>), RecordWriteError> {
+    let mut payloads = Vec::new();
+    let report = recover_chunks(source, |payload| payloads.push(payload))?;
+    Ok((report, payloads))
+}
 
-        let sequence = match read_u64_status(&mut source)? {
-            ReadStatus::Complete(value) => value,
-            ReadStatus::Eof | ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+fn recover_chunks<R: Read, F: FnMut(Vec<u8>)>(
+    source: R,
+    mut on_payload: F,
+) -> Result<RecoveryReport, RecordWriteError> {
+    let mut chunks = Vec::new();
+    let (header, truncated_tail) = scan_chunks(source, |commit, payload| {
+        chunks.push(commit);
+        on_payload(payload);
+    })?;
+    Ok(RecoveryReport {
+        header,
+        chunks,
+        truncated_tail,
+    })
+}
 
-        let payload_len = match read_u32_status(&mut source)? {
-            ReadStatus::Complete(value) => value,
-            ReadStatus::Eof | ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+/// Reads the header and hands each committed chunk to `on_chunk`. Returns
+/// the header and whether the file ends in a truncated chunk.
+fn scan_chunks<R: Read, F: FnMut(ChunkCommit, Vec<u8>)>(
+    mut source: R,
+    mut on_chunk: F,
+) -> Result<(TakrecHeader, bool), RecordWriteError> {
+    let header = read_header(&mut source)?;
+    loop {
+        match read_next_chunk(&mut source, DEFAULT_MAX_CHUNK_BYTES)? {
+            NextChunk::Committed(commit, payload, ..) => on_chunk(commit, payload),
+            NextChunk::End { truncated_tail } => return Ok((header, truncated_tail)),
+        }
+    }
+}
 
-        let expected_checksum = match read_u32_status(&mut source)? {
-            ReadStatus::Complete(value) => value,
-            ReadStatus::Eof | ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+pub(crate) enum NextChunk {
+    /// The chunk, its payload, its capture time for timed chunks and
+    /// whether it was stored compressed.
+    Committed(ChunkCommit, Vec<u8>, Option<SystemTime>, bool),
+    /// No further committed chunk; `truncated_tail` when the file ends
+    /// partway through one.
+    End { truncated_tail: bool },
+}
 
-        let payload_len_usize =
-            usize::try_from(payload_len).map_err(|_| RecordWriteError::ChunkTooLarge {
-                payload_len: usize::MAX,
-                max_chunk_bytes: DEFAULT_MAX_CHUNK_BYTES,
-            })?;
+/// Reads one chunk after the header, verifying its commit marker and
+/// checksum. Length fields above `max_chunk_bytes` are rejected before
+/// anything is allocated for them.
+pub(crate) fn read_next_chunk<R: Read>(
+    source: &mut R,
+    max_chunk_bytes: usize,
+) -> Result<NextChunk, RecordWriteError> {
+    const TRUNCATED: NextChunk = NextChunk::End {
+        truncated_tail: true,
+    };
 
-        let payload = match read_vec_status(&mut source, payload_len_usize)? {
-            ReadStatus::Complete(value) => value,
-            ReadStatus::Eof | ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+    let magic = match read_array_status::<4, _>(source)? {
+        ReadStatus::Complete(magic) => magic,
+        ReadStatus::Eof => {
+            return Ok(NextChunk::End {
+                truncated_tail: false,
+            })
+        }
+        ReadStatus::Truncated => return Ok(TRUNCATED),
+    };
 
-        let commit_marker = match read_u32_status(&mut source)? {
-            ReadStatus::Complete(value) => value,
-            ReadStatus::Eof | ReadStatus::Truncated => {
-                truncated_tail = true;
-                break;
-            }
-        };
+    let (codec, captured) = match magic {
+        CHUNK_MAGIC => (None, None),
+        CHUNK_MAGIC_COMPRESSED => {
+            let ReadStatus::Complete([codec]) = read_array_status::<1, _>(source)? else {
+                return Ok(TRUNCATED);
+            };
+            (Some(codec), None)
+        }
+        CHUNK_MAGIC_TIMED => {
+            let ReadStatus::Complete([codec]) = read_array_status::<1, _>(source)? else {
+                return Ok(TRUNCATED);
+            };
+            let ReadStatus::Complete(captured) = read_u64_status(source)? else {
+                return Ok(TRUNCATED);
+            };
+            let captured = UNIX_EPOCH + Duration::from_nanos(captured);
+            (
+                Some(codec).filter(|codec| *codec != CODEC_NONE),
+                Some(captured),
+            )
+        }
+        found => return Err(RecordWriteError::CorruptChunkMagic { found }),
+    };
 
-        if commit_marker != CHUNK_COMMIT_MARKER {
-            return Err(RecordWriteError::CommitMarkerMismatch {
-                sequence,
-                expected: CHUNK_COMMIT_MARKER,
-                actual: commit_marker,
-            });
+    let ReadStatus::Complete(sequence) = read_u64_status(source)? else {
+        return Ok(TRUNCATED);
+    };
+    let ReadStatus::Complete(payload_len) = read_u32_status(source)? else {
+        return Ok(TRUNCATED);
+    };
+    let ReadStatus::Complete(expected_checksum) = read_u32_status(source)? else {
+        return Ok(TRUNCATED);
+    };
+    let stored_len = match codec {
+        None => payload_len,
+        Some(_) => {
+            let ReadStatus::Complete(stored_len) = read_u32_status(source)? else {
+                return Ok(TRUNCATED);
+            };
+            stored_len
         }
+    };
 
-        let actual_checksum = crc32fast::hash(&payload);
-        if actual_checksum != expected_checksum {
-            return Err(RecordWriteError::ChecksumMismatch {
+    for len in [payload_len, stored_len] {
+        if usize::try_from(len).map_or(true, |len| len > max_chunk_bytes) {
+            return Err(RecordWriteError::CorruptChunkLength {
                 sequence,
-                expected: expected_checksum,
-                actual: actual_checksum,
+                len,
+                max_chunk_bytes,
             });
         }
+    }
+
+    let ReadStatus::Complete(stored) = read_vec_status(source, stored_len as usize)? else {
+        return Ok(TRUNCATED);
+    };
+    let ReadStatus::Complete(commit_marker) = read_u32_status(source)? else {
+        return Ok(TRUNCATED);
+    };
+
+    if commit_marker != CHUNK_COMMIT_MARKER {
+        return Err(RecordWriteError::CommitMarkerMismatch {
+            sequence,
+            expected: CHUNK_COMMIT_MARKER,
+            actual: commit_marker,
+        });
+    }
 
-        chunks.push(ChunkCommit {
+    let compressed = codec.is_some();
+    let payload = match codec {
+        None => stored,
+        Some(codec) => decompress_chunk(sequence, codec, &stored, payload_len)?,
+    };
+    let actual_checksum = crc32fast::hash(&payload);
+    if actual_checksum != expected_checksum {
+        return Err(RecordWriteError::ChecksumMismatch {
+            sequence,
+            expected: expected_checksum,
+            actual: actual_checksum,
+        });
+    }
+
+    Ok(NextChunk::Committed(
+        ChunkCommit {
             sequence,
             payload_len,
             checksum: expected_checksum,
+        },
+        payload,
+        captured,
+        compressed,
+    ))
+}
+
+/// Inflates a stored chunk into exactly `payload_len` bytes, refusing to
+/// allocate past that however the stored bytes claim to expand. The caller
+/// has already bounded `payload_len`.
+fn decompress_chunk(
+    sequence: u64,
+    codec: u8,
+    stored: &[u8],
+    payload_len: u32,
+) -> Result<Vec<u8>, RecordWriteError> {
+    if codec != CODEC_ZSTD {
+        return Err(RecordWriteError::UnknownCompression { sequence, codec });
+    }
+    let expected = payload_len as usize;
+    let payload =
+        zstd::bulk::decompress(stored, expected).map_err(|error| RecordWriteError::Decompress {
+            sequence,
+            reason: error.to_string(),
+        })?;
+    if payload.len() != expected {
+        return Err(RecordWriteError::Decompress {
+            sequence,
+            reason: format!("inflated to {} bytes, expected {expected}", payload.len()),
         });
     }
-
-    Ok(RecoveryReport {
-        header,
-        chunks,
-        truncated_tail,
-    })
+    Ok(payload)
 }
 
 #[derive(Debug, Error)]
@@ -318,6 +627,44 @@ pub enum RecordWriteError {
 
     #[error("truncated takrec header")]
     TruncatedHeader,
+
+    #[error("zstd compression level {level} outside {min}..={max}")]
+    InvalidCompressionLevel { level: i32, min: i32, max: i32 },
+
+    #[error("chunk {sequence} uses unknown compression codec {codec}")]
+    UnknownCompression { sequence: u64, codec: u8 },
+
+    #[error("chunk {sequence} failed to decompress: {reason}")]
+    Decompress { sequence: u64, reason: String },
+
+    #[error("chunk {sequence} claims length {len}, above the reader limit {max_chunk_bytes}")]
+    CorruptChunkLength {
+        sequence: u64,
+        len: u32,
+        max_chunk_bytes: usize,
+    },
+}
+
+impl CodedError for RecordWriteError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::new("RECORD", 1),
+            Self::HeaderFieldTooLong { .. } => ErrorCode::new("RECORD", 2),
+            Self::HeaderFieldTooLargeOnRead { .. } => ErrorCode::new("RECORD", 3),
+            Self::InvalidFileMagic { .. } => ErrorCode::new("RECORD", 4),
+            Self::UnsupportedVersion { .. } => ErrorCode::new("RECORD", 5),
+            Self::ChunkTooLarge { .. } => ErrorCode::new("RECORD", 6),
+            Self::SequenceOverflow => ErrorCode::new("RECORD", 7),
+            Self::CorruptChunkMagic { .. } => ErrorCode::new("RECORD", 8),
+            Self::ChecksumMismatch { .. } => ErrorCode::new("RECORD", 9),
+            Self::CommitMarkerMismatch { .. } => ErrorCode::new("RECORD", 10),
+            Self::TruncatedHeader => ErrorCode::new("RECORD", 11),
+            Self::InvalidCompressionLevel { .. } => ErrorCode::new("RECORD", 12),
+            Self::UnknownCompression { .. } => ErrorCode::new("RECORD", 13),
+            Self::Decompress { .. } => ErrorCode::new("RECORD", 14),
+            Self::CorruptChunkLength { .. } => ErrorCode::new("RECORD", 15),
+        }
+    }
 }
 
 fn now_unix_nanos() -> u64 {
@@ -360,16 +707,27 @@ fn write_len_prefixed_string<W: Write>(
     Ok(())
 }
 
-fn read_header<R: Read>(source: &mut R) -> Result<TakrecHeader, RecordWriteError> {
+pub(crate) fn read_header<R: Read>(source: &mut R) -> Result<TakrecHeader, RecordWriteError> {
+    read_versioned_header(source).map(|(header, _)| header)
+}
+
+/// Like [`read_header`], also returning whether the file's chunks carry
+/// capture times.
+pub(crate) fn read_versioned_header<R: Read>(
+    source: &mut R,
+) -> Result<(TakrecHeader, bool), RecordWriteError> {
     let magic = read_array_required::<8, _>(source, RecordWriteError::TruncatedHeader)?;
     if magic != FILE_MAGIC {
         return Err(RecordWriteError::InvalidFileMagic { found: magic });
     }
 
     let version = read_u16_required(source, RecordWriteError::TruncatedHeader)?;
-    if version != FILE_VERSION {
+    if !matches!(
+        version,
+        FILE_VERSION | FILE_VERSION_COMPRESSED | FILE_VERSION_TIMED
+    ) {
         return Err(RecordWriteError::UnsupportedVersion {
-            expected: FILE_VERSION,
+            expected: FILE_VERSION_TIMED,
             found: version,
         });
     }
@@ -380,13 +738,14 @@ fn read_header<R: Read>(source: &mut R) -> Result<TakrecHeader, RecordWriteError
     let protocol_hint = read_len_prefixed_string(source, "protocol_hint", MAX_HEADER_FIELD_LEN)?;
     let limits_profile = read_len_prefixed_string(source, "limits_profile", MAX_HEADER_FIELD_LEN)?;
 
-    Ok(TakrecHeader {
+    let header = TakrecHeader {
         tool_name,
         tool_version,
         protocol_hint,
         limits_profile,
         created_unix_nanos,
-    })
+    };
+    Ok((header, version == FILE_VERSION_TIMED))
 }
 
 fn read_len_prefixed_string<R: Read>(
@@ -515,7 +874,9 @@ mod tests {
     use std::io::Cursor;
 
     use super::{
-        recover_chunk_index, RecordWriteError, TakrecHeader, TakrecWriter, CHUNK_COMMIT_MARKER,
+        recover_chunk_index, recover_chunk_payloads, ChunkCompression, RecordWriteError,
+        TakrecHeader, TakrecWriter, CHUNK_COMMIT_MARKER, CHUNK_MAGIC, CHUNK_MAGIC_COMPRESSED,
+        CODEC_ZSTD, DEFAULT_MAX_CHUNK_BYTES,
     };
 
     #[test]
@@ -532,6 +893,28 @@ mod tests {
         assert!(!report.truncated_tail);
     }
 
+    #[derive(Debug)]
+    struct FixedClock(std::time::SystemTime);
+
+    impl rustak_core::TimeSource for FixedClock {
+        fn now_system(&self) -> std::time::SystemTime {
+            self.0
+        }
+    }
+
+    #[test]
+    fn header_can_be_stamped_by_time_source() {
+        let clock = FixedClock(std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_500));
+        let header = TakrecHeader::default().stamped_by(&clock);
+        assert_eq!(header.created_unix_nanos, 1_500_000_000);
+
+        let mut writer = TakrecWriter::new(Vec::new(), header.clone()).expect("writer");
+        writer.append_chunk(b"alpha").expect("chunk");
+        let data = writer.into_inner().expect("inner");
+        let report = recover_chunk_index(Cursor::new(data)).expect("recover");
+        assert_eq!(report.header.created_unix_nanos, 1_500_000_000);
+    }
+
     #[test]
     fn recovery_drops_truncated_tail_chunk() {
         let mut writer = TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
@@ -617,4 +1000,126 @@ mod tests {
             _ => panic!("unexpected error variant"),
         }
     }
+
+    #[test]
+    fn oversized_length_fields_are_rejected_before_allocating() {
+        let writer = TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
+        let header = writer.into_inner().expect("inner");
+
+        let mut plain = header.clone();
+        plain.extend_from_slice(&CHUNK_MAGIC);
+        plain.extend_from_slice(&0_u64.to_le_bytes());
+        plain.extend_from_slice(&u32::MAX.to_le_bytes());
+        plain.extend_from_slice(&0_u32.to_le_bytes());
+
+        let mut compressed = header;
+        compressed.extend_from_slice(&CHUNK_MAGIC_COMPRESSED);
+        compressed.push(CODEC_ZSTD);
+        compressed.extend_from_slice(&7_u64.to_le_bytes());
+        compressed.extend_from_slice(&16_u32.to_le_bytes());
+        compressed.extend_from_slice(&0_u32.to_le_bytes());
+        compressed.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        for (data, expected_sequence) in [(plain, 0), (compressed, 7)] {
+            match recover_chunk_index(Cursor::new(data)).expect_err("oversized length") {
+                RecordWriteError::CorruptChunkLength {
+                    sequence,
+                    len,
+                    max_chunk_bytes,
+                } => {
+                    assert_eq!(sequence, expected_sequence);
+                    assert_eq!(len, u32::MAX);
+                    assert_eq!(max_chunk_bytes, DEFAULT_MAX_CHUNK_BYTES);
+                }
+                other => panic!("unexpected error: {other}"),
+            }
+        }
+    }
+
+    fn count(data: &[u8], magic: [u8; 4]) -> usize {
+        data.windows(4).filter(|window| *window == magic).count()
+    }
+
+    #[test]
+    fn compressed_chunks_round_trip_and_incompressible_ones_stay_plain() {
+        let repetitive = b"<event uid=\"alpha\" type=\"a-f-G\"/>".repeat(32);
+        let tiny = b"x".to_vec();
+        let mut writer = TakrecWriter::with_compression(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::Zstd { level: 3 },
+        )
+        .expect("writer");
+        let commit = writer.append_chunk(&repetitive).expect("chunk");
+        assert_eq!(commit.payload_len as usize, repetitive.len());
+        assert_eq!(commit.checksum, crc32fast::hash(&repetitive));
+        writer.append_chunk(&tiny).expect("chunk");
+        let data = writer.into_inner().expect("inner");
+
+        assert_eq!(u16::from_le_bytes([data[8], data[9]]), 2);
+        assert_eq!(count(&data, CHUNK_MAGIC_COMPRESSED), 1);
+        assert_eq!(count(&data, CHUNK_MAGIC), 1);
+        assert!(data.len() < repetitive.len());
+
+        let (report, payloads) = recover_chunk_payloads(data.as_slice()).expect("recover");
+        assert_eq!(payloads, [repetitive, tiny]);
+        assert_eq!(report.chunks[0], commit);
+        assert!(!report.truncated_tail);
+    }
+
+    #[test]
+    fn compressed_chunk_rejects_unknown_codec_and_bad_levels() {
+        let mut writer = TakrecWriter::with_compression(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::Zstd { level: 1 },
+        )
+        .expect("writer");
+        writer.append_chunk(&[b'a'; 256]).expect("chunk");
+        let mut data = writer.into_inner().expect("inner");
+
+        let codec_pos = data
+            .windows(4)
+            .position(|window| window == CHUNK_MAGIC_COMPRESSED)
+            .expect("compressed chunk")
+            + 4;
+        data[codec_pos] = 9;
+        assert!(matches!(
+            recover_chunk_index(data.as_slice()),
+            Err(RecordWriteError::UnknownCompression {
+                sequence: 0,
+                codec: 9
+            })
+        ));
+
+        let error = TakrecWriter::with_compression(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::Zstd { level: 99 },
+        )
+        .expect_err("level out of range");
+        assert!(matches!(
+            error,
+            RecordWriteError::InvalidCompressionLevel { level: 99, .. }
+        ));
+    }
+
+    #[test]
+    fn bytes_written_tracks_the_file_size() {
+        let mut writer = TakrecWriter::new(Vec::new(), TakrecHeader::default()).expect("writer");
+        writer.append_chunk(b"alpha").expect("chunk");
+        let mut compressed = TakrecWriter::with_compression(
+            Vec::new(),
+            TakrecHeader::default(),
+            ChunkCompression::Zstd { level: 3 },
+        )
+        .expect("writer");
+        compressed.append_chunk(&[b'z'; 512]).expect("chunk");
+        compressed.append_chunk(b"beta").expect("chunk");
+
+        for writer in [writer, compressed] {
+            let expected = writer.bytes_written();
+            assert_eq!(writer.into_inner().expect("inner").len() as u64, expected);
+        }
+    }
 }
@@ -0,0 +1,88 @@
+//! Committed takrec v1 artifacts must keep reading, and the writer must keep
+//! producing byte-identical output for the same header and payloads.
+
+use std::fs;
+use std::path::PathBuf;
+
+use rustak_record::{recover_chunk_payloads, ChunkCommit, TakrecHeader, TakrecWriter};
+
+const CREATED_UNIX_NANOS: u64 = 1_700_000_000_123_456_789;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("..")
+        .join("tests")
+        .join("fixtures")
+        .join("compat")
+        .join(name);
+    fs::read(&path).unwrap_or_else(|error| panic!("read {}: {error}", path.display()))
+}
+
+fn fixture_header() -> TakrecHeader {
+    TakrecHeader {
+        created_unix_nanos: CREATED_UNIX_NANOS,
+        ..TakrecHeader::new("rustak", "0.1.0", "mixed", "conservative")
+    }
+}
+
+fn fixture_payloads() -> Vec<Vec<u8>> {
+    vec![
+        concat!(
+            r#"<event version="2.0" uid="ANDROID-1" type="a-f-G-U-C" how="m-g" "#,
+            r#"time="2023-11-14T22:13:20Z" start="2023-11-14T22:13:20Z" "#,
+            r#"stale="2023-11-14T22:18:20Z"><point lat="34.0" lon="-117.0" "#,
+            r#"hae="100.0" ce="9.9" le="9.9"/><detail><contact callsign="ALPHA"/>"#,
+            r#"</detail></event>"#
+        )
+        .as_bytes()
+        .to_vec(),
+        vec![0xbf, 0x01, 0xbf, 0x0a, 0x03, b'a', b'b', b'c'],
+        br#"<event version="2.0" uid="ANDROID-2" type="b-t-f" how="h-g-i-g-o"/>"#.to_vec(),
+    ]
+}
+
+#[test]
+fn v1_fixture_recovers_header_chunks_and_payloads() {
+    let bytes = fixture("takrec_v1_three_chunks.takrec");
+    let (report, payloads) = recover_chunk_payloads(bytes.as_slice()).expect("v1 fixture");
+
+    assert_eq!(report.header, fixture_header());
+    assert!(!report.truncated_tail);
+    assert_eq!(payloads, fixture_payloads());
+    let expected_chunks = payloads
+        .iter()
+        .zip(0_u64..)
+        .map(|(payload, sequence)| ChunkCommit {
+            sequence,
+            payload_len: u32::try_from(payload.len()).expect("fixture payload fits u32"),
+            checksum: crc32fast::hash(payload),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(report.chunks, expected_chunks);
+}
+
+#[test]
+fn v1_fixture_with_torn_tail_keeps_committed_chunks() {
+    let bytes = fixture("takrec_v1_truncated_tail.takrec");
+    let (report, payloads) = recover_chunk_payloads(bytes.as_slice()).expect("v1 fixture");
+
+    assert!(report.truncated_tail);
+    assert_eq!(report.chunks.len(), 2);
+    assert_eq!(payloads, fixture_payloads()[..2]);
+}
+
+#[test]
+fn writer_output_matches_v1_fixture_bytes() {
+    let mut writer = TakrecWriter::new(Vec::new(), fixture_header()).expect("writer");
+    for payload in fixture_payloads() {
+        writer.append_chunk(&payload).expect("append");
+    }
+    let written = writer.into_inner().expect("finish");
+
+    assert_eq!(
+        written,
+        fixture("takrec_v1_three_chunks.takrec"),
+        "takrec v1 encoding changed; existing recordings would no longer match"
+    );
+}
@@ -1,4 +1,4 @@
-use rustak_limits::Limits;
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncWrite};
 
@@ -72,6 +72,19 @@ pub enum SapientCodecError {
 
     #[error(transparent)]
     Frame(#[from] SapientFrameError),
+
+    #[error("invalid SAPIENT message: {0}")]
+    Decode(#[from] prost::DecodeError),
+}
+
+impl CodedError for SapientCodecError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::FrameTooLarge { .. } => ErrorCode::new("SAPIENT", 201),
+            Self::Frame(error) => error.code(),
+            Self::Decode(_) => ErrorCode::new("SAPIENT", 202),
+        }
+    }
 }
 
 #[cfg(test)]
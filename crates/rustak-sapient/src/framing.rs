@@ -1,4 +1,4 @@
-use rustak_limits::Limits;
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use rustak_net::{
     read_length_prefixed_frame, write_length_prefixed_frame, LengthPrefixKind, LengthPrefixedError,
 };
@@ -60,6 +60,14 @@ pub enum SapientFrameError {
     LengthPrefixed(#[from] LengthPrefixedError),
 }
 
+impl CodedError for SapientFrameError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::LengthPrefixed(error) => error.code(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tokio::io::{duplex, AsyncWriteExt};
@@ -1,14 +1,19 @@
+use std::collections::BTreeMap;
 use std::time::Duration;
 
-use rustak_limits::{Limits, LimitsError};
+use rustak_limits::{CodedError, ErrorCode, Limits, LimitsError};
 use thiserror::Error;
 
 pub mod codec;
 pub mod framing;
+pub mod message;
+pub mod registration;
 pub mod session;
 
 pub use codec::{SapientCodec, SapientCodecError};
 pub use framing::{SapientFrameCodec, SapientFrameError};
+pub use message::{DetectionReport, Registration, SapientMessage};
+pub use registration::{NodeVersion, SapientRegistrationError, SapientRegistry};
 pub use session::{SapientSessionBuffers, SapientSessionError, SessionDirection};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,6 +22,9 @@ pub struct SapientConfig {
     pub read_timeout: Duration,
     pub write_timeout: Duration,
     pub tcp_nodelay: bool,
+    /// ICD version each listed node must declare when it registers, keyed
+    /// by node ID. Unlisted nodes may declare any version.
+    pub version_pins: BTreeMap<String, String>,
 }
 
 impl Default for SapientConfig {
@@ -26,6 +34,7 @@ impl Default for SapientConfig {
             read_timeout: Duration::from_secs(15),
             write_timeout: Duration::from_secs(15),
             tcp_nodelay: true,
+            version_pins: BTreeMap::new(),
         }
     }
 }
@@ -35,6 +44,13 @@ impl SapientConfig {
         self.limits.validate()?;
         ensure_non_zero_duration("read_timeout", self.read_timeout)?;
         ensure_non_zero_duration("write_timeout", self.write_timeout)?;
+        for (node_id, version) in &self.version_pins {
+            if node_id.trim().is_empty() || version.trim().is_empty() {
+                return Err(SapientConfigError::EmptyVersionPin {
+                    node_id: node_id.clone(),
+                });
+            }
+        }
         Ok(())
     }
 
@@ -52,6 +68,11 @@ impl SapientConfig {
     pub fn session_buffers(&self) -> SapientSessionBuffers {
         SapientSessionBuffers::from_limits(&self.limits)
     }
+
+    #[must_use]
+    pub fn registry(&self) -> SapientRegistry {
+        SapientRegistry::new(self.version_pins.clone())
+    }
 }
 
 #[derive(Debug, Error, PartialEq, Eq)]
@@ -61,6 +82,19 @@ pub enum SapientConfigError {
 
     #[error("{field} must be greater than zero")]
     ZeroDuration { field: &'static str },
+
+    #[error("version_pins entry for node '{node_id}' needs a node ID and a version")]
+    EmptyVersionPin { node_id: String },
+}
+
+impl CodedError for SapientConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidLimits(error) => error.code(),
+            Self::ZeroDuration { .. } => ErrorCode::new("SAPIENT", 1),
+            Self::EmptyVersionPin { .. } => ErrorCode::new("SAPIENT", 2),
+        }
+    }
 }
 
 fn ensure_non_zero_duration(
@@ -138,6 +172,19 @@ mod tests {
         assert!(matches!(error, SapientConfigError::InvalidLimits(_)));
     }
 
+    #[test]
+    fn rejects_blank_version_pins() {
+        let mut cfg = SapientConfig::default();
+        cfg.version_pins
+            .insert("radar-1".to_owned(), " ".to_owned());
+        assert_eq!(
+            cfg.validate(),
+            Err(SapientConfigError::EmptyVersionPin {
+                node_id: "radar-1".to_owned()
+            })
+        );
+    }
+
     #[test]
     fn fuzz_hook_handles_arbitrary_bytes_without_panicking() {
         let corpus = [
@@ -0,0 +1,173 @@
+//! The subset of the BSI Flex 335 v2.0 `SapientMessage` schema the bridge
+//! consumes, hand-written with `prost` derives so no `protoc` is needed at
+//! build time. Members of the `content` and `location_oneof` oneofs are
+//! modelled as optional fields; fields not listed here are skipped when
+//! decoding.
+
+use prost::Message;
+
+use crate::SapientCodecError;
+
+/// Top-level SAPIENT message.
+#[derive(Clone, PartialEq, Message)]
+pub struct SapientMessage {
+    #[prost(message, optional, tag = "1")]
+    pub timestamp: Option<Timestamp>,
+    #[prost(string, optional, tag = "2")]
+    pub node_id: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub destination_id: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    pub registration: Option<Registration>,
+    #[prost(message, optional, tag = "7")]
+    pub detection_report: Option<DetectionReport>,
+}
+
+impl SapientMessage {
+    /// Decodes one framed SAPIENT payload.
+    pub fn decode_payload(payload: &[u8]) -> Result<Self, SapientCodecError> {
+        Ok(Self::decode(payload)?)
+    }
+
+    #[must_use]
+    pub fn encode_payload(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+}
+
+/// `google.protobuf.Timestamp`.
+#[derive(Clone, Copy, PartialEq, Eq, Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+/// Sent by a sensor when it connects; only the identifying fields are kept.
+#[derive(Clone, PartialEq, Eq, Message)]
+pub struct Registration {
+    /// ICD version the sensor speaks, e.g. `BSI Flex 335 v2.0`.
+    #[prost(string, optional, tag = "2")]
+    pub icd_version: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DetectionReport {
+    #[prost(string, optional, tag = "1")]
+    pub report_id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub object_id: Option<String>,
+    #[prost(message, optional, tag = "5")]
+    pub location: Option<Location>,
+    #[prost(message, optional, tag = "6")]
+    pub range_bearing: Option<RangeBearing>,
+    #[prost(float, optional, tag = "7")]
+    pub detection_confidence: Option<f32>,
+    #[prost(message, repeated, tag = "11")]
+    pub classification: Vec<DetectionReportClassification>,
+    #[prost(message, repeated, tag = "12")]
+    pub behaviour: Vec<Behaviour>,
+}
+
+impl DetectionReport {
+    /// Classification with the highest confidence; the first one wins ties.
+    #[must_use]
+    pub fn primary_classification(&self) -> Option<&DetectionReportClassification> {
+        self.classification.iter().reduce(|best, candidate| {
+            if candidate.confidence.unwrap_or(0.0) > best.confidence.unwrap_or(0.0) {
+                candidate
+            } else {
+                best
+            }
+        })
+    }
+}
+
+/// Location in the sensor's coordinate system; for lat/lng systems `x` is
+/// longitude and `y` latitude.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct Location {
+    #[prost(double, optional, tag = "1")]
+    pub x: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub y: Option<f64>,
+    #[prost(double, optional, tag = "3")]
+    pub z: Option<f64>,
+}
+
+/// Range and bearing from the sensor, in its configured units.
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct RangeBearing {
+    #[prost(double, optional, tag = "1")]
+    pub elevation: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub azimuth: Option<f64>,
+    #[prost(double, optional, tag = "3")]
+    pub range: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DetectionReportClassification {
+    #[prost(string, optional, tag = "1")]
+    pub r#type: Option<String>,
+    #[prost(float, optional, tag = "2")]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Behaviour {
+    #[prost(string, optional, tag = "1")]
+    pub r#type: Option<String>,
+    #[prost(float, optional, tag = "2")]
+    pub confidence: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DetectionReport, DetectionReportClassification, Location, SapientMessage};
+
+    fn classification(name: &str, confidence: f32) -> DetectionReportClassification {
+        DetectionReportClassification {
+            r#type: Some(name.to_owned()),
+            confidence: Some(confidence),
+        }
+    }
+
+    #[test]
+    fn detection_report_round_trips_and_picks_most_confident_class() {
+        let message = SapientMessage {
+            node_id: Some("radar-1".to_owned()),
+            detection_report: Some(DetectionReport {
+                report_id: Some("r-1".to_owned()),
+                object_id: Some("o-1".to_owned()),
+                location: Some(Location {
+                    x: Some(-0.1),
+                    y: Some(51.5),
+                    z: None,
+                }),
+                classification: vec![classification("Human", 0.2), classification("UAV", 0.7)],
+                ..DetectionReport::default()
+            }),
+            ..SapientMessage::default()
+        };
+
+        let decoded =
+            SapientMessage::decode_payload(&message.encode_payload()).expect("payload decodes");
+        assert_eq!(decoded, message);
+        let report = decoded.detection_report.expect("detection report");
+        assert_eq!(
+            report
+                .primary_classification()
+                .and_then(|class| class.r#type.as_deref()),
+            Some("UAV")
+        );
+    }
+
+    #[test]
+    fn rejects_garbage_payloads() {
+        assert!(SapientMessage::decode_payload(&[0x0a, 0xff]).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Per-sensor SAPIENT version pinning.
+//!
+//! Sensors declare the ICD version they speak in their registration.
+//! [`SapientRegistry`] checks each declaration against the version pinned for
+//! that node in [`SapientConfig::version_pins`](crate::SapientConfig), refuses
+//! mismatches and remembers what every node declared, so estates that mix
+//! sensor generations can be audited.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
+use thiserror::Error;
+
+use crate::message::Registration;
+
+/// What one node pinned and declared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeVersion<'a> {
+    pub node_id: &'a str,
+    pub pinned: Option<&'a str>,
+    pub declared: Option<&'a str>,
+}
+
+impl fmt::Display for NodeVersion<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sapient_version node_id={} pinned={:?} declared={:?}",
+            self.node_id,
+            self.pinned.unwrap_or("<unpinned>"),
+            self.declared.unwrap_or("<unregistered>")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SapientRegistry {
+    pins: BTreeMap<String, String>,
+    declared: BTreeMap<String, String>,
+}
+
+impl SapientRegistry {
+    /// Registry enforcing `pins`, keyed by node ID.
+    #[must_use]
+    pub fn new(pins: BTreeMap<String, String>) -> Self {
+        Self {
+            pins,
+            declared: BTreeMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn pinned_version(&self, node_id: &str) -> Option<&str> {
+        self.pins.get(node_id).map(String::as_str)
+    }
+
+    /// Version `node_id` declared in its last accepted registration.
+    #[must_use]
+    pub fn declared_version(&self, node_id: &str) -> Option<&str> {
+        self.declared.get(node_id).map(String::as_str)
+    }
+
+    /// Accepts `registration` from `node_id` unless it contradicts the pin
+    /// for that node. A rejected registration leaves the previous
+    /// declaration in place.
+    pub fn register(
+        &mut self,
+        node_id: &str,
+        registration: &Registration,
+    ) -> Result<(), SapientRegistrationError> {
+        let node_id = node_id.trim();
+        if node_id.is_empty() {
+            return Err(SapientRegistrationError::MissingNodeId);
+        }
+        let declared = registration
+            .icd_version
+            .as_deref()
+            .map(str::trim)
+            .filter(|version| !version.is_empty());
+
+        if let Some(expected) = self.pins.get(node_id) {
+            match declared {
+                None => {
+                    return Err(SapientRegistrationError::MissingVersion {
+                        node_id: node_id.to_owned(),
+                        expected: expected.clone(),
+                    })
+                }
+                Some(declared) if declared != expected => {
+                    return Err(SapientRegistrationError::VersionMismatch {
+                        node_id: node_id.to_owned(),
+                        expected: expected.clone(),
+                        declared: declared.to_owned(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        match declared {
+            Some(version) => {
+                self.declared.insert(node_id.to_owned(), version.to_owned());
+            }
+            None => {
+                self.declared.remove(node_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every pinned or registered node, ordered by node ID.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeVersion<'_>> {
+        let mut node_ids = self
+            .pins
+            .keys()
+            .chain(self.declared.keys())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        node_ids.sort_unstable();
+        node_ids.dedup();
+        node_ids.into_iter().map(|node_id| NodeVersion {
+            node_id,
+            pinned: self.pinned_version(node_id),
+            declared: self.declared_version(node_id),
+        })
+    }
+
+    /// One `sapient_version` line per node for diagnostics.
+    #[must_use]
+    pub fn diagnostic_lines(&self) -> Vec<String> {
+        self.nodes().map(|node| node.to_string()).collect()
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SapientRegistrationError {
+    #[error("registration has no node_id")]
+    MissingNodeId,
+
+    #[error("node '{node_id}' is pinned to SAPIENT version '{expected}' but declared none")]
+    MissingVersion { node_id: String, expected: String },
+
+    #[error(
+        "node '{node_id}' is pinned to SAPIENT version '{expected}' but declared '{declared}'"
+    )]
+    VersionMismatch {
+        node_id: String,
+        expected: String,
+        declared: String,
+    },
+}
+
+impl CodedError for SapientRegistrationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::MissingNodeId => ErrorCode::new("SAPIENT", 401),
+            Self::MissingVersion { .. } => ErrorCode::new("SAPIENT", 402),
+            Self::VersionMismatch { .. } => ErrorCode::new("SAPIENT", 403),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SapientRegistrationError, SapientRegistry};
+    use crate::message::Registration;
+
+    fn registration(version: Option<&str>) -> Registration {
+        Registration {
+            icd_version: version.map(str::to_owned),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn pinned_nodes_reject_other_versions_and_keep_their_declaration() {
+        let mut registry = SapientRegistry::new(
+            [("radar-1".to_owned(), "BSI Flex 335 v2.0".to_owned())]
+                .into_iter()
+                .collect(),
+        );
+
+        registry
+            .register("radar-1", &registration(Some(" BSI Flex 335 v2.0 ")))
+            .expect("pinned version is accepted");
+        assert_eq!(
+            registry.register("radar-1", &registration(Some("BSI Flex 335 v1.0"))),
+            Err(SapientRegistrationError::VersionMismatch {
+                node_id: "radar-1".to_owned(),
+                expected: "BSI Flex 335 v2.0".to_owned(),
+                declared: "BSI Flex 335 v1.0".to_owned(),
+            })
+        );
+        assert!(matches!(
+            registry.register("radar-1", &registration(None)),
+            Err(SapientRegistrationError::MissingVersion { .. })
+        ));
+        assert_eq!(
+            registry.declared_version("radar-1"),
+            Some("BSI Flex 335 v2.0")
+        );
+
+        registry
+            .register("eo-2", &registration(Some("BSI Flex 335 v1.0")))
+            .expect("unpinned nodes may declare any version");
+        assert_eq!(
+            registry.diagnostic_lines(),
+            [
+                "sapient_version node_id=eo-2 pinned=\"<unpinned>\" declared=\"BSI Flex 335 v1.0\"",
+                "sapient_version node_id=radar-1 pinned=\"BSI Flex 335 v2.0\" declared=\"BSI Flex 335 v2.0\"",
+            ]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::VecDeque;
 
-use rustak_limits::Limits;
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -173,6 +173,16 @@ pub enum SapientSessionError {
     },
 }
 
+impl CodedError for SapientSessionError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::FrameTooLarge { .. } => ErrorCode::new("SAPIENT", 301),
+            Self::QueueMessagesExceeded { .. } => ErrorCode::new("SAPIENT", 302),
+            Self::QueueBytesExceeded { .. } => ErrorCode::new("SAPIENT", 303),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rustak_limits::Limits;
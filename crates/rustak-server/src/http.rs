@@ -0,0 +1,329 @@
+//! HTTP/1.1 exchanges for the Marti API on hyper's client connection.
+//!
+//! A [`KeepAlive`] holds at most one idle connection and sends the next
+//! request over it while the server keeps it open, dialing a fresh one
+//! otherwise. Redirects to a path on the same server are followed; hyper
+//! decodes chunked and close-delimited bodies, which callers bound.
+
+use std::future::Future;
+use std::io;
+use std::sync::Mutex;
+
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::Bytes;
+use hyper::client::conn::http1::{self, SendRequest};
+use hyper::header::{CONTENT_TYPE, HOST, LOCATION, USER_AGENT};
+use hyper::{Method, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Redirect hops followed before the last response is returned as is.
+const MAX_REDIRECTS: usize = 5;
+
+pub(crate) struct Request<'a> {
+    pub(crate) method: &'a str,
+    pub(crate) target: &'a str,
+    /// `host:port`, sent as the `Host` header.
+    pub(crate) authority: &'a str,
+    pub(crate) headers: &'a [(&'a str, &'a str)],
+    pub(crate) body: Option<Body<'a>>,
+}
+
+pub(crate) struct Body<'a> {
+    pub(crate) content_type: &'a str,
+    pub(crate) bytes: &'a [u8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) body: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Failure<E> {
+    Dial(E),
+    Io(io::Error),
+    Malformed(String),
+}
+
+impl<E> From<hyper::Error> for Failure<E> {
+    fn from(error: hyper::Error) -> Self {
+        if error.is_parse() || error.is_parse_status() {
+            Self::Malformed(error.to_string())
+        } else {
+            Self::Io(io::Error::other(error))
+        }
+    }
+}
+
+/// One reusable connection to a single server.
+#[derive(Debug, Default)]
+pub(crate) struct KeepAlive {
+    idle: Mutex<Option<SendRequest<Full<Bytes>>>>,
+}
+
+impl KeepAlive {
+    /// Sends `request` and reads the whole response, failing once the body
+    /// grows past `max_response_bytes`. Connections come from `dial` when
+    /// there is no open idle one.
+    pub(crate) async fn exchange<F, Fut, IO, E>(
+        &self,
+        dial: &mut F,
+        request: &Request<'_>,
+        max_response_bytes: usize,
+    ) -> Result<Response, Failure<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<IO, E>>,
+        IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let mut method = Method::from_bytes(request.method.as_bytes())
+            .map_err(|error| Failure::Malformed(error.to_string()))?;
+        let mut target = request.target.to_owned();
+        let mut body = request.body.as_ref();
+        let mut redirects = 0;
+        loop {
+            let message = build(request, &method, &target, body)?;
+            let (sender, response) = self.send(dial, message).await?;
+            let status = response.status();
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| same_server_target(location, request.authority));
+            let bytes = match Limited::new(response.into_body(), max_response_bytes)
+                .collect()
+                .await
+            {
+                Ok(collected) => collected.to_bytes(),
+                Err(error) if error.is::<LengthLimitError>() => {
+                    return Err(Failure::Malformed(format!(
+                        "response exceeds {max_response_bytes} bytes"
+                    )))
+                }
+                Err(error) => match error.downcast::<hyper::Error>() {
+                    Ok(error) => return Err((*error).into()),
+                    Err(error) => return Err(Failure::Io(io::Error::other(error))),
+                },
+            };
+            if !sender.is_closed() {
+                *self.idle.lock().expect("idle connection") = Some(sender);
+            }
+
+            match location {
+                Some(location) if status.is_redirection() && redirects < MAX_REDIRECTS => {
+                    redirects += 1;
+                    target = location;
+                    if status == StatusCode::SEE_OTHER
+                        || (matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND)
+                            && method != Method::GET
+                            && method != Method::HEAD)
+                    {
+                        method = Method::GET;
+                        body = None;
+                    }
+                }
+                _ => {
+                    return Ok(Response {
+                        status: status.as_u16(),
+                        body: bytes.to_vec(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Sends over the idle connection when it is still open, otherwise
+    /// over a freshly dialed one.
+    async fn send<F, Fut, IO, E>(
+        &self,
+        dial: &mut F,
+        message: hyper::Request<Full<Bytes>>,
+    ) -> Result<
+        (
+            SendRequest<Full<Bytes>>,
+            hyper::Response<hyper::body::Incoming>,
+        ),
+        Failure<E>,
+    >
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<IO, E>>,
+        IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let idle = self.idle.lock().expect("idle connection").take();
+        let mut message = Some(message);
+        if let Some(mut sender) = idle {
+            if sender.ready().await.is_ok() {
+                let pending = message.take().expect("unsent request");
+                match sender.try_send_request(pending).await {
+                    Ok(response) => return Ok((sender, response)),
+                    // Closed before the request went out: safe to resend.
+                    Err(mut error) => match error.take_message() {
+                        Some(unsent) => message = Some(unsent),
+                        None => return Err(error.into_error().into()),
+                    },
+                }
+            }
+        }
+
+        let io = dial().await.map_err(Failure::Dial)?;
+        let (mut sender, connection) = http1::Builder::new()
+            .title_case_headers(true)
+            .handshake(TokioIo::new(io))
+            .await?;
+        tokio::spawn(async move {
+            // Failures surface through the request that was in flight.
+            let _ = connection.await;
+        });
+        let response = sender
+            .send_request(message.expect("unsent request"))
+            .await?;
+        Ok((sender, response))
+    }
+}
+
+fn build<E>(
+    request: &Request<'_>,
+    method: &Method,
+    target: &str,
+    body: Option<&Body<'_>>,
+) -> Result<hyper::Request<Full<Bytes>>, Failure<E>> {
+    let mut builder = hyper::Request::builder()
+        .method(method.clone())
+        .uri(target)
+        .header(HOST, request.authority)
+        .header(USER_AGENT, concat!("rustak/", env!("CARGO_PKG_VERSION")));
+    for (name, value) in request.headers {
+        builder = builder.header(*name, *value);
+    }
+    let payload = match body {
+        Some(body) => {
+            builder = builder.header(CONTENT_TYPE, body.content_type);
+            Bytes::copy_from_slice(body.bytes)
+        }
+        None => Bytes::new(),
+    };
+    builder
+        .body(Full::new(payload))
+        .map_err(|error| Failure::Malformed(error.to_string()))
+}
+
+/// Request target for a `Location` that stays on `authority`, which is the
+/// only server the dialer reaches.
+fn same_server_target(location: &str, authority: &str) -> Option<String> {
+    let uri = location.parse::<Uri>().ok()?;
+    if let Some(redirected) = uri.authority() {
+        if !redirected.as_str().eq_ignore_ascii_case(authority) {
+            return None;
+        }
+    }
+    let target = uri.path_and_query()?.as_str();
+    target.starts_with('/').then(|| target.to_owned())
+}
+
+/// Percent-encodes everything outside RFC 3986's unreserved set.
+pub(crate) fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::{percent_encode, same_server_target, KeepAlive, Request};
+
+    async fn read_head(stream: &mut DuplexStream) -> String {
+        let mut head = Vec::new();
+        let mut byte = [0_u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            if stream.read(&mut byte).await.expect("read request") == 0 {
+                return String::new();
+            }
+            head.push(byte[0]);
+        }
+        String::from_utf8(head).expect("utf-8 head")
+    }
+
+    #[tokio::test]
+    async fn redirects_and_chunked_bodies_reuse_one_connection() {
+        let (client, mut server) = duplex(4096);
+        let server = tokio::spawn(async move {
+            let head = read_head(&mut server).await;
+            assert!(head.starts_with("GET /old HTTP/1.1\r\n"));
+            assert!(head.contains("\r\nHost: tak.example:8443\r\n"));
+            server
+                .write_all(b"HTTP/1.1 302 Found\r\nLocation: https://tak.example:8443/new?a=1\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .expect("redirect");
+            let head = read_head(&mut server).await;
+            assert!(head.starts_with("GET /new?a=1 HTTP/1.1\r\n"));
+            server
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n")
+                .await
+                .expect("chunked");
+            let head = read_head(&mut server).await;
+            assert!(head.starts_with("GET /big HTTP/1.1\r\n"));
+            server
+                .write_all(
+                    format!("HTTP/1.1 200 OK\r\nContent-Length: 64\r\n\r\n{:64}", "").as_bytes(),
+                )
+                .await
+                .expect("oversized");
+        });
+
+        let dials = AtomicUsize::new(0);
+        let mut client = Some(client);
+        let mut dial = || {
+            dials.fetch_add(1, Ordering::Relaxed);
+            std::future::ready(client.take().ok_or(()))
+        };
+        let keep_alive = KeepAlive::default();
+        let request = |target| Request {
+            method: "GET",
+            target,
+            authority: "tak.example:8443",
+            headers: &[],
+            body: None,
+        };
+        let response = keep_alive
+            .exchange(&mut dial, &request("/old"), 1024)
+            .await
+            .expect("followed redirect");
+        assert_eq!((response.status, &response.body[..]), (200, &b"abcde"[..]));
+        assert!(matches!(
+            keep_alive.exchange(&mut dial, &request("/big"), 16).await,
+            Err(super::Failure::Malformed(_))
+        ));
+        assert_eq!(dials.load(Ordering::Relaxed), 1);
+        server.await.expect("server");
+    }
+
+    #[test]
+    fn redirects_only_stay_on_the_dialed_server() {
+        assert_eq!(
+            same_server_target("/Marti/api/version", "tak.example:8443").as_deref(),
+            Some("/Marti/api/version")
+        );
+        assert_eq!(
+            same_server_target("https://TAK.example:8443/x?y=1", "tak.example:8443").as_deref(),
+            Some("/x?y=1")
+        );
+        assert_eq!(
+            same_server_target("https://elsewhere:8443/x", "tak.example:8443"),
+            None
+        );
+        assert_eq!(percent_encode("ANDROID 1/a"), "ANDROID%201%2Fa");
+    }
+}
@@ -1,10 +1,36 @@
 use std::collections::HashSet;
 
 use rustak_crypto::{CryptoConfig, CryptoError, ProviderSupport};
-use rustak_transport::{TransportConfig, TransportConfigError, TransportFraming};
-use rustak_wire::TakProtocolVersion;
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_transport::{
+    ConnectionManager, ConnectionManagerError, ManagedStream, NegotiationOutcome, Protocol,
+    TransportComposeError, TransportConfig, TransportConfigError, TransportConnection,
+    TransportFraming,
+};
+use rustak_wire::{
+    NegotiationConfig, NegotiationReason, TakProtocolVersion, WireConfig, WireConfigError,
+    WireFormat,
+};
 use thiserror::Error;
 
+#[cfg(feature = "marti")]
+pub mod enrollment;
+#[cfg(feature = "marti")]
+mod http;
+#[cfg(feature = "marti")]
+pub mod marti;
+#[cfg(feature = "marti")]
+pub mod mission;
+
+#[cfg(feature = "marti")]
+pub use enrollment::{EnrollmentClient, EnrollmentConfig, EnrollmentError};
+#[cfg(feature = "marti")]
+pub use marti::{MartiClient, MartiError, MartiGroup, ServerVersion};
+#[cfg(feature = "marti")]
+pub use mission::{
+    Mission, MissionChange, MissionChangeKind, MissionClient, MissionContent, MissionSubscription,
+};
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ServerClientConfig {
     pub endpoint: String,
@@ -14,6 +40,9 @@ pub struct ServerClientConfig {
     pub protocol_version: TakProtocolVersion,
     pub crypto: Option<CryptoConfig>,
     pub provider_support: ProviderSupport,
+    /// Upgrade timeout and downgrade policy for streams whose
+    /// `transport.wire_format` is TAK protocol.
+    pub negotiation: NegotiationConfig,
 }
 
 impl Default for ServerClientConfig {
@@ -26,6 +55,7 @@ impl Default for ServerClientConfig {
             protocol_version: TakProtocolVersion::V1,
             crypto: None,
             provider_support: ProviderSupport::default(),
+            negotiation: NegotiationConfig::default(),
         }
     }
 }
@@ -36,6 +66,11 @@ impl ServerClientConfig {
         validate_channel_path(&self.channel_path)?;
         validate_capabilities(&self.required_capabilities)?;
         self.transport.validate()?;
+        WireConfig {
+            negotiation: self.negotiation.clone(),
+            ..WireConfig::default()
+        }
+        .validate()?;
 
         if self.requires_tls() && self.crypto.is_none() {
             return Err(ServerConfigError::TlsEndpointRequiresCryptoConfig);
@@ -82,6 +117,15 @@ pub struct StreamingSession {
     pub negotiated_capabilities: Vec<String>,
 }
 
+/// Live stream opened by [`StreamingClient::open`].
+#[derive(Debug)]
+pub struct StreamingConnection {
+    pub session: StreamingSession,
+    pub connection: TransportConnection<ManagedStream>,
+    /// `None` when the stream stayed XML and no upgrade was attempted.
+    pub negotiation: Option<NegotiationOutcome>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct StreamingClient {
     config: ServerClientConfig,
@@ -98,6 +142,78 @@ impl StreamingClient {
         &self.config
     }
 
+    /// Dials `transport.protocol` (TCP or TLS) and, when the configured
+    /// wire format is TAK protocol, negotiates the upgrade from legacy XML.
+    /// XML events received during negotiation are in
+    /// [`NegotiationOutcome::early_events`].
+    pub async fn open(&self) -> Result<StreamingConnection, StreamingError> {
+        let mut manager = ConnectionManager::new(
+            self.config.transport.clone(),
+            self.config.negotiation.downgrade_policy,
+        )?;
+        if matches!(self.config.transport.protocol, Protocol::Tls { .. }) {
+            manager = self.with_tls(manager)?;
+        }
+        let mut connection = manager.connect().await?;
+
+        let negotiation = match self.config.transport.wire_format {
+            WireFormat::Xml => None,
+            WireFormat::TakProtocolV1 => Some(
+                connection
+                    .negotiate_with_config(self.config.protocol_version, &self.config.negotiation)
+                    .await
+                    .map_err(|error| match error {
+                        TransportComposeError::NegotiationTerminated { reason } => {
+                            StreamingError::NegotiationTerminated { reason }
+                        }
+                        other => StreamingError::Compose(other),
+                    })?,
+            ),
+        };
+
+        Ok(StreamingConnection {
+            session: StreamingSession {
+                endpoint: self.config.endpoint.clone(),
+                channel_path: self.config.channel_path.clone(),
+                framing: connection.framing(),
+                protocol_version: self.config.protocol_version,
+                negotiated_capabilities: self.config.required_capabilities.clone(),
+            },
+            connection,
+            negotiation,
+        })
+    }
+
+    #[cfg(feature = "tls")]
+    fn with_tls(&self, manager: ConnectionManager) -> Result<ConnectionManager, StreamingError> {
+        let crypto = self
+            .config
+            .crypto
+            .as_ref()
+            .ok_or(StreamingError::MissingCrypto)?;
+        let identity = crypto
+            .load_identity()
+            .map_err(rustak_transport::TlsError::from)?;
+        let connector = rustak_transport::TlsConnector::new(
+            &identity,
+            &rustak_transport::TlsClientConfig::from_crypto_config(crypto)
+                .map_err(rustak_transport::TlsError::from)?,
+        )?;
+        Ok(manager.with_tls(connector))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    fn with_tls(&self, _manager: ConnectionManager) -> Result<ConnectionManager, StreamingError> {
+        Err(StreamingError::TlsUnavailable)
+    }
+
+    /// Queries the server's Marti API for the contract
+    /// [`Self::connect_contract`] checks, instead of a synthetic one.
+    #[cfg(feature = "marti")]
+    pub async fn discover_contract(&self) -> Result<ConnectionContract, MartiError> {
+        MartiClient::new(&self.config)?.contract().await
+    }
+
     pub fn connect_contract(
         &self,
         contract: &ConnectionContract,
@@ -175,6 +291,63 @@ pub enum ServerConfigError {
 
     #[error(transparent)]
     InvalidCrypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    InvalidNegotiation(#[from] WireConfigError),
+}
+
+impl CodedError for ServerConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyEndpoint => ErrorCode::new("SERVER", 1),
+            Self::EndpointMustBeHttpOrHttps => ErrorCode::new("SERVER", 2),
+            Self::EmptyChannelPath => ErrorCode::new("SERVER", 3),
+            Self::ChannelPathMustStartWithSlash => ErrorCode::new("SERVER", 4),
+            Self::RootChannelPathNotAllowed => ErrorCode::new("SERVER", 5),
+            Self::EmptyCapability => ErrorCode::new("SERVER", 6),
+            Self::DuplicateCapability { .. } => ErrorCode::new("SERVER", 7),
+            Self::TlsEndpointRequiresCryptoConfig => ErrorCode::new("SERVER", 8),
+            Self::InvalidTransport(error) => error.code(),
+            Self::InvalidCrypto(error) => error.code(),
+            Self::InvalidNegotiation(error) => error.code(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum StreamingError {
+    #[error(transparent)]
+    Connect(#[from] ConnectionManagerError),
+
+    #[error(transparent)]
+    Compose(#[from] TransportComposeError),
+
+    #[error("tls transport requires crypto config")]
+    MissingCrypto,
+
+    #[cfg(feature = "tls")]
+    #[error(transparent)]
+    Tls(#[from] rustak_transport::TlsError),
+
+    #[error("tls transport requires the `tls` feature")]
+    TlsUnavailable,
+
+    #[error("protocol negotiation terminated: {reason:?}")]
+    NegotiationTerminated { reason: NegotiationReason },
+}
+
+impl CodedError for StreamingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Connect(error) => error.code(),
+            Self::Compose(error) => error.code(),
+            Self::MissingCrypto => ErrorCode::new("SERVER", 101),
+            #[cfg(feature = "tls")]
+            Self::Tls(error) => error.code(),
+            Self::TlsUnavailable => ErrorCode::new("SERVER", 102),
+            Self::NegotiationTerminated { .. } => ErrorCode::new("SERVER", 103),
+        }
+    }
 }
 
 #[derive(Debug, Error, PartialEq, Eq)]
@@ -192,6 +365,17 @@ pub enum ServerClientError {
     MissingCapability { capability: String },
 }
 
+impl CodedError for ServerClientError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ServerUnreachable { .. } => ErrorCode::new("SERVER", 201),
+            Self::TlsRequired => ErrorCode::new("SERVER", 202),
+            Self::MissingChannel { .. } => ErrorCode::new("SERVER", 203),
+            Self::MissingCapability { .. } => ErrorCode::new("SERVER", 204),
+        }
+    }
+}
+
 fn validate_endpoint(endpoint: &str) -> Result<(), ServerConfigError> {
     if endpoint.trim().is_empty() {
         return Err(ServerConfigError::EmptyEndpoint);
@@ -236,11 +420,14 @@ fn validate_capabilities(capabilities: &[String]) -> Result<(), ServerConfigErro
 
 #[cfg(test)]
 mod tests {
-    use super::{ServerClientConfig, ServerConfigError, StreamingClient};
+    use super::{
+        Protocol, ServerClientConfig, ServerConfigError, StreamingClient, TakProtocolVersion,
+        TransportConfig, TransportFraming, WireFormat,
+    };
     use rustak_crypto::{
         CryptoConfig, CryptoProviderMode, IdentitySource, ProviderSupport, RevocationPolicy,
     };
-    use rustak_wire::TakProtocolVersion;
+    use rustak_wire::NegotiationState;
     use std::path::PathBuf;
 
     #[test]
@@ -284,6 +471,7 @@ mod tests {
                     archive_path: PathBuf::from("tests/fixtures/certs/dev_identity.p12"),
                     password: Some("dev-pass".to_owned()),
                 },
+                server_spki_pin: None,
             }),
             protocol_version: TakProtocolVersion::V1,
             ..ServerClientConfig::default()
@@ -303,6 +491,7 @@ mod tests {
                     archive_path: PathBuf::from("tests/fixtures/certs/dev_identity.p12"),
                     password: Some("dev-pass".to_owned()),
                 },
+                server_spki_pin: None,
             }),
             ..ServerClientConfig::default()
         };
@@ -323,6 +512,7 @@ mod tests {
                     archive_path: PathBuf::from("tests/fixtures/certs/dev_identity.p12"),
                     password: Some("dev-pass".to_owned()),
                 },
+                server_spki_pin: None,
             }),
             provider_support: ProviderSupport::with_fips_enabled(true),
             ..ServerClientConfig::default()
@@ -330,4 +520,71 @@ mod tests {
 
         assert!(StreamingClient::new(config).is_ok());
     }
+
+    #[tokio::test]
+    async fn open_negotiates_tak_protocol_with_streaming_server() {
+        use rustak_wire::negotiation::events::TakControlMessage;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let control = |message: TakControlMessage| {
+            let mut line = message.encode("server", rustak_core::TimestampUtc::now());
+            line.push(b'\n');
+            line
+        };
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.expect("accept");
+            let mut announcement = b"<event uid=\"early\"/>\n".to_vec();
+            announcement.extend(control(TakControlMessage::ProtocolSupport {
+                version: Some(TakProtocolVersion::V1),
+            }));
+            stream.write_all(&announcement).await.expect("announce");
+            let mut request = Vec::new();
+            while request.last() != Some(&b'\n') {
+                request.push(stream.read_u8().await.expect("request"));
+            }
+            stream
+                .write_all(&control(TakControlMessage::Response { accepted: true }))
+                .await
+                .expect("respond");
+            request
+        });
+
+        let client = StreamingClient::new(ServerClientConfig {
+            transport: TransportConfig {
+                protocol: Protocol::Tcp { addr },
+                wire_format: WireFormat::TakProtocolV1,
+                ..TransportConfig::default()
+            },
+            ..ServerClientConfig::default()
+        })
+        .expect("config");
+        let stream = client.open().await.expect("open");
+        let request = server.await.expect("server");
+        assert_eq!(
+            TakControlMessage::parse(
+                request.trim_ascii_end(),
+                &rustak_transport::TransportConfig::default().limits
+            ),
+            Ok(Some(TakControlMessage::Request {
+                version: TakProtocolVersion::V1
+            }))
+        );
+        assert_eq!(
+            stream.session.framing,
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+        let negotiation = stream.negotiation.expect("negotiated");
+        assert_eq!(
+            negotiation.state,
+            NegotiationState::Upgraded(TakProtocolVersion::V1)
+        );
+        assert_eq!(
+            negotiation.early_events,
+            vec![b"<event uid=\"early\"/>".to_vec()]
+        );
+    }
 }
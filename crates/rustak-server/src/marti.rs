@@ -0,0 +1,605 @@
+//! Marti REST queries behind [`StreamingClient::discover_contract`].
+//!
+//! [`MartiClient`] talks to the API port named by
+//! `ServerClientConfig::endpoint` (8443 for `https://`, 8080 for `http://`),
+//! presenting the configured client identity on HTTPS. Two calls feed the
+//! [`ConnectionContract`]:
+//!
+//! - `GET /Marti/api/version/config` proves the server is reachable and
+//!   reports its release and API version;
+//! - `GET /Marti/api/groups/all` lists the groups (ATAK's "channels") the
+//!   caller belongs to.
+//!
+//! Every TAK Server streams CoT to its members, so the contract always
+//! advertises `/Marti/api/channels/streaming` with the `cot-stream` and
+//! `streaming` capabilities, plus one channel per active group and a
+//! `marti-api-v<N>` capability for the reported API version.
+//!
+//! [`StreamingClient::discover_contract`]: crate::StreamingClient::discover_contract
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_transport::ManagedStream;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use crate::http::{self, percent_encode, KeepAlive};
+use crate::{ConnectionContract, ServerClientConfig, ServerConfigError};
+
+pub const VERSION_CONFIG_PATH: &str = "/Marti/api/version/config";
+pub const GROUPS_PATH: &str = "/Marti/api/groups/all";
+/// Prefix of the channel paths a discovered contract advertises.
+pub const CHANNELS_PATH: &str = "/Marti/api/channels";
+pub const STREAMING_CHANNEL: &str = "streaming";
+
+pub const DEFAULT_HTTPS_PORT: u16 = 8443;
+pub const DEFAULT_HTTP_PORT: u16 = 8080;
+
+/// Version and group listings are small; this only stops a runaway peer.
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens the connections Marti requests run over; one is kept open
+/// between requests while the server allows it.
+pub trait MartiDial: Send + Sync {
+    type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    fn dial(&self) -> impl Future<Output = Result<Self::Io, MartiError>> + Send;
+}
+
+impl<F, Fut, IO> MartiDial for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<IO, MartiError>> + Send,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Io = IO;
+
+    fn dial(&self) -> impl Future<Output = Result<IO, MartiError>> + Send {
+        self()
+    }
+}
+
+/// Dials the configured endpoint over TCP, completing a TLS handshake for
+/// `https://` endpoints.
+#[derive(Debug, Clone)]
+pub struct EndpointDialer {
+    host: String,
+    port: u16,
+    #[cfg(feature = "tls")]
+    tls: Option<rustak_transport::TlsConnector>,
+}
+
+impl MartiDial for EndpointDialer {
+    type Io = ManagedStream;
+
+    async fn dial(&self) -> Result<ManagedStream, MartiError> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        tcp.set_nodelay(true)?;
+        #[cfg(feature = "tls")]
+        if let Some(connector) = &self.tls {
+            let stream = connector.connect(&self.host, tcp).await?;
+            return Ok(ManagedStream::Tls(Box::new(stream)));
+        }
+        Ok(ManagedStream::Tcp(tcp))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerVersion {
+    /// Release string, e.g. `5.2-RELEASE-16-HEAD`.
+    pub version: String,
+    /// Marti API version.
+    pub api: String,
+    pub hostname: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MartiGroup {
+    pub name: String,
+    /// `IN`, `OUT` or absent when the server does not split directions.
+    pub direction: Option<String>,
+    pub active: bool,
+}
+
+/// Clones share the kept-alive connection.
+#[derive(Debug, Clone)]
+pub struct MartiClient<D = EndpointDialer> {
+    authority: String,
+    supports_tls: bool,
+    dialer: D,
+    connection: Arc<KeepAlive>,
+    timeout: Duration,
+}
+
+impl MartiClient<EndpointDialer> {
+    /// Client for `config.endpoint`. HTTPS endpoints present the identity
+    /// from `config.crypto` and need the `tls` feature.
+    pub fn new(config: &ServerClientConfig) -> Result<Self, MartiError> {
+        config.validate()?;
+        let (tls, host, port) = parse_endpoint(&config.endpoint)?;
+        let dialer = EndpointDialer {
+            #[cfg(feature = "tls")]
+            tls: if tls {
+                Some(tls_connector(config)?)
+            } else {
+                None
+            },
+            host,
+            port,
+        };
+        #[cfg(not(feature = "tls"))]
+        if tls {
+            return Err(MartiError::TlsUnavailable);
+        }
+        Self::with_dialer(config, dialer)
+    }
+}
+
+impl<D: MartiDial> MartiClient<D> {
+    /// Client for `config.endpoint` whose connections come from `dialer`.
+    pub fn with_dialer(config: &ServerClientConfig, dialer: D) -> Result<Self, MartiError> {
+        config.validate()?;
+        let (tls, host, port) = parse_endpoint(&config.endpoint)?;
+        let authority = if host.contains(':') {
+            format!("[{host}]:{port}")
+        } else {
+            format!("{host}:{port}")
+        };
+        Ok(Self {
+            authority,
+            supports_tls: tls,
+            dialer,
+            connection: Arc::default(),
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Bound on each request, connection set-up included.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn version(&self) -> Result<ServerVersion, MartiError> {
+        let data = self.get_data(VERSION_CONFIG_PATH).await?;
+        let field = |key: &str| data.get(key).and_then(Value::as_str).map(str::to_owned);
+        Ok(ServerVersion {
+            version: field("version").ok_or_else(|| MartiError::MalformedResponse {
+                endpoint: VERSION_CONFIG_PATH,
+                reason: "missing `data.version`".to_owned(),
+            })?,
+            api: field("api").unwrap_or_default(),
+            hostname: field("hostname"),
+        })
+    }
+
+    pub async fn groups(&self) -> Result<Vec<MartiGroup>, MartiError> {
+        let data = self.get_data(GROUPS_PATH).await?;
+        let entries = data
+            .as_array()
+            .ok_or_else(|| MartiError::MalformedResponse {
+                endpoint: GROUPS_PATH,
+                reason: "`data` is not an array".to_owned(),
+            })?;
+        entries
+            .iter()
+            .map(|entry| {
+                let name = entry.get("name").and_then(Value::as_str).ok_or_else(|| {
+                    MartiError::MalformedResponse {
+                        endpoint: GROUPS_PATH,
+                        reason: "group without a `name`".to_owned(),
+                    }
+                })?;
+                Ok(MartiGroup {
+                    name: name.to_owned(),
+                    direction: entry
+                        .get("direction")
+                        .and_then(Value::as_str)
+                        .map(str::to_owned),
+                    active: entry.get("active").and_then(Value::as_bool).unwrap_or(true),
+                })
+            })
+            .collect()
+    }
+
+    /// The contract the live server offers. Connection failures and
+    /// timeouts yield `server_reachable: false`; other errors are returned.
+    pub async fn contract(&self) -> Result<ConnectionContract, MartiError> {
+        let version = match self.version().await {
+            Ok(version) => version,
+            Err(MartiError::Io(_) | MartiError::TimedOut { .. }) => {
+                return Ok(ConnectionContract {
+                    server_reachable: false,
+                    supports_tls: self.supports_tls,
+                    advertised_channels: Vec::new(),
+                    advertised_capabilities: Vec::new(),
+                })
+            }
+            Err(error) => return Err(error),
+        };
+        let groups = self.groups().await?;
+
+        let mut advertised_channels = vec![format!("{CHANNELS_PATH}/{STREAMING_CHANNEL}")];
+        for group in groups.iter().filter(|group| group.active) {
+            let channel = format!("{CHANNELS_PATH}/{}", percent_encode(&group.name));
+            if !advertised_channels.contains(&channel) {
+                advertised_channels.push(channel);
+            }
+        }
+        let mut advertised_capabilities = vec!["cot-stream".to_owned(), "streaming".to_owned()];
+        if !version.api.is_empty() {
+            advertised_capabilities.push(format!("marti-api-v{}", version.api));
+        }
+        Ok(ConnectionContract {
+            server_reachable: true,
+            supports_tls: self.supports_tls,
+            advertised_channels,
+            advertised_capabilities,
+        })
+    }
+
+    async fn get_data(&self, endpoint: &'static str) -> Result<Value, MartiError> {
+        let body = self.request("GET", endpoint, endpoint, None).await?;
+        envelope_data(endpoint, &body)
+    }
+
+    /// One JSON request; non-2xx statuses are errors.
+    pub(crate) async fn request(
+        &self,
+        method: &str,
+        endpoint: &'static str,
+        target: &str,
+        body: Option<http::Body<'_>>,
+    ) -> Result<Vec<u8>, MartiError> {
+        let request = http::Request {
+            method,
+            target,
+            authority: &self.authority,
+            headers: &[("Accept", "application/json")],
+            body,
+        };
+        self.send(endpoint, &request, MAX_RESPONSE_BYTES).await
+    }
+
+    /// `GET` of an opaque payload of at most `max_bytes`.
+    pub(crate) async fn download(
+        &self,
+        endpoint: &'static str,
+        target: &str,
+        max_bytes: usize,
+    ) -> Result<Vec<u8>, MartiError> {
+        let request = http::Request {
+            method: "GET",
+            target,
+            authority: &self.authority,
+            headers: &[("Accept", "application/octet-stream")],
+            body: None,
+        };
+        self.send(endpoint, &request, max_bytes).await
+    }
+
+    async fn send(
+        &self,
+        endpoint: &'static str,
+        request: &http::Request<'_>,
+        max_response_bytes: usize,
+    ) -> Result<Vec<u8>, MartiError> {
+        let timeout = self.timeout;
+        let exchange = async {
+            let mut dial = || self.dialer.dial();
+            self.connection
+                .exchange(&mut dial, request, max_response_bytes)
+                .await
+                .map_err(|failure| match failure {
+                    http::Failure::Dial(error) => error,
+                    http::Failure::Io(error) => MartiError::Io(error),
+                    http::Failure::Malformed(reason) => {
+                        MartiError::MalformedResponse { endpoint, reason }
+                    }
+                })
+        };
+        let response = tokio::time::timeout(timeout, exchange)
+            .await
+            .map_err(|_| MartiError::TimedOut { timeout })??;
+        match response.status {
+            200..=299 => Ok(response.body),
+            401 | 403 => Err(MartiError::Rejected {
+                endpoint,
+                status: response.status,
+            }),
+            status => Err(MartiError::Status { endpoint, status }),
+        }
+    }
+}
+
+/// `data` member of the JSON envelope TAK Server wraps API answers in.
+pub(crate) fn envelope_data(endpoint: &'static str, body: &[u8]) -> Result<Value, MartiError> {
+    let mut document = json(endpoint, body)?;
+    document
+        .get_mut("data")
+        .map(Value::take)
+        .ok_or_else(|| MartiError::MalformedResponse {
+            endpoint,
+            reason: "missing `data`".to_owned(),
+        })
+}
+
+pub(crate) fn json(endpoint: &'static str, body: &[u8]) -> Result<Value, MartiError> {
+    serde_json::from_slice(body).map_err(|error| MartiError::MalformedResponse {
+        endpoint,
+        reason: error.to_string(),
+    })
+}
+
+#[cfg(feature = "tls")]
+fn tls_connector(
+    config: &ServerClientConfig,
+) -> Result<rustak_transport::TlsConnector, MartiError> {
+    let crypto = config.crypto.as_ref().ok_or(MartiError::Config(
+        ServerConfigError::TlsEndpointRequiresCryptoConfig,
+    ))?;
+    let identity = crypto.load_identity()?;
+    Ok(rustak_transport::TlsConnector::new(
+        &identity,
+        &rustak_transport::TlsClientConfig::from_crypto_config(crypto)?,
+    )?)
+}
+
+/// `(tls, host, port)` of an `http://` or `https://` endpoint; any path is
+/// ignored because Marti paths are absolute.
+fn parse_endpoint(endpoint: &str) -> Result<(bool, String, u16), MartiError> {
+    let invalid = || MartiError::InvalidEndpoint {
+        endpoint: endpoint.to_owned(),
+    };
+    let (tls, rest) = if let Some(rest) = endpoint.strip_prefix("https://") {
+        (true, rest)
+    } else if let Some(rest) = endpoint.strip_prefix("http://") {
+        (false, rest)
+    } else {
+        return Err(invalid());
+    };
+    let default_port = if tls {
+        DEFAULT_HTTPS_PORT
+    } else {
+        DEFAULT_HTTP_PORT
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| invalid())?,
+        None => default_port,
+    };
+    Ok((tls, host.to_owned(), port))
+}
+
+#[derive(Debug, Error)]
+pub enum MartiError {
+    #[error(transparent)]
+    Config(#[from] ServerConfigError),
+
+    #[error("endpoint `{endpoint}` has no usable host and port")]
+    InvalidEndpoint { endpoint: String },
+
+    #[error("marti connection failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("marti request did not finish within {timeout:?}")]
+    TimedOut { timeout: Duration },
+
+    #[error("{endpoint} rejected the client (HTTP {status})")]
+    Rejected { endpoint: &'static str, status: u16 },
+
+    #[error("{endpoint} responded with status {status}")]
+    Status { endpoint: &'static str, status: u16 },
+
+    #[error("{endpoint} returned an unusable response: {reason}")]
+    MalformedResponse {
+        endpoint: &'static str,
+        reason: String,
+    },
+
+    #[error("https endpoints require the `tls` feature")]
+    TlsUnavailable,
+
+    #[error("mission client uid must not be empty")]
+    EmptyClientUid,
+
+    #[error("mission name must not be empty")]
+    EmptyMissionName,
+
+    #[error("package of {size} bytes exceeds the {limit}-byte limit")]
+    PackageTooLarge { size: usize, limit: usize },
+
+    #[error(transparent)]
+    Crypto(#[from] rustak_crypto::CryptoError),
+
+    #[cfg(feature = "tls")]
+    #[error(transparent)]
+    Tls(#[from] rustak_transport::TlsError),
+}
+
+impl CodedError for MartiError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Config(error) => error.code(),
+            Self::InvalidEndpoint { .. } => ErrorCode::new("SERVER", 401),
+            Self::Io(_) => ErrorCode::new("SERVER", 402),
+            Self::TimedOut { .. } => ErrorCode::new("SERVER", 403),
+            Self::Rejected { .. } => ErrorCode::new("SERVER", 404),
+            Self::Status { .. } => ErrorCode::new("SERVER", 405),
+            Self::MalformedResponse { .. } => ErrorCode::new("SERVER", 406),
+            Self::TlsUnavailable => ErrorCode::new("SERVER", 407),
+            Self::EmptyClientUid => ErrorCode::new("SERVER", 408),
+            Self::EmptyMissionName => ErrorCode::new("SERVER", 409),
+            Self::PackageTooLarge { .. } => ErrorCode::new("SERVER", 410),
+            Self::Crypto(error) => error.code(),
+            #[cfg(feature = "tls")]
+            Self::Tls(error) => error.code(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::{parse_endpoint, MartiClient, MartiError, GROUPS_PATH, VERSION_CONFIG_PATH};
+    use crate::{ServerClientConfig, StreamingClient};
+
+    const VERSION: &str = r#"{"version":"3","type":"ServerConfig","data":{"version":"5.2-RELEASE-16-HEAD","api":"3","hostname":"0.0.0.0"},"nodeId":"a1"}"#;
+    const GROUPS: &str = r#"{"version":"3","type":"com.bbn.marti.remote.groups.Group","data":[{"name":"__ANON__","direction":"IN","active":true},{"name":"__ANON__","direction":"OUT","active":true},{"name":"Blue Team","direction":"IN","active":true},{"name":"Retired","direction":"IN","active":false}]}"#;
+
+    /// Answers requests from a fixed path-to-response table, keeping each
+    /// dialed connection open.
+    fn fake_server(
+        routes: &'static [(&'static str, u16, &'static str)],
+    ) -> impl Fn() -> std::future::Ready<Result<DuplexStream, MartiError>> + Send + Sync {
+        move || {
+            let (client, mut server) = duplex(16 * 1024);
+            tokio::spawn(async move {
+                loop {
+                    let mut request = Vec::new();
+                    let mut buffer = [0_u8; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        let read = server.read(&mut buffer).await.expect("read request");
+                        if read == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                    let head = String::from_utf8_lossy(&request).into_owned();
+                    assert!(head.contains("\r\nHost: tak.example:8443\r\n"));
+                    let path = head.split(' ').nth(1).unwrap_or_default().to_owned();
+                    let (status, body) = routes
+                        .iter()
+                        .find(|(route, _, _)| *route == path)
+                        .map_or((404, ""), |(_, status, body)| (*status, *body));
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                    server
+                        .write_all(response.as_bytes())
+                        .await
+                        .expect("write response");
+                }
+            });
+            std::future::ready(Ok(client))
+        }
+    }
+
+    fn config() -> ServerClientConfig {
+        ServerClientConfig {
+            endpoint: "https://tak.example".to_owned(),
+            crypto: Some(rustak_crypto::CryptoConfig {
+                provider: rustak_crypto::CryptoProviderMode::Ring,
+                revocation: rustak_crypto::RevocationPolicy::Off,
+                identity: rustak_crypto::IdentitySource::Pkcs12File {
+                    archive_path: "tests/fixtures/certs/dev_identity.p12".into(),
+                    password: None,
+                },
+                server_spki_pin: None,
+            }),
+            required_capabilities: vec!["cot-stream".to_owned(), "marti-api-v3".to_owned()],
+            ..ServerClientConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn live_contract_reflects_version_and_active_groups() {
+        static ROUTES: &[(&str, u16, &str)] = &[
+            (VERSION_CONFIG_PATH, 200, VERSION),
+            (GROUPS_PATH, 200, GROUPS),
+        ];
+        let client = MartiClient::with_dialer(&config(), fake_server(ROUTES)).expect("client");
+
+        let version = client.version().await.expect("version");
+        assert_eq!(version.version, "5.2-RELEASE-16-HEAD");
+        assert_eq!(version.api, "3");
+
+        let contract = client.contract().await.expect("contract");
+        assert!(contract.server_reachable && contract.supports_tls);
+        assert_eq!(
+            contract.advertised_channels,
+            [
+                "/Marti/api/channels/streaming",
+                "/Marti/api/channels/__ANON__",
+                "/Marti/api/channels/Blue%20Team",
+            ]
+        );
+        let session = StreamingClient::new(config())
+            .expect("streaming client")
+            .connect_contract(&contract)
+            .expect("live contract satisfies the config");
+        assert_eq!(
+            session.negotiated_capabilities,
+            ["cot-stream", "marti-api-v3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_servers_and_rejections_are_distinguished() {
+        let refused = || {
+            std::future::ready(Err::<DuplexStream, _>(MartiError::Io(
+                std::io::ErrorKind::ConnectionRefused.into(),
+            )))
+        };
+        let contract = MartiClient::with_dialer(&config(), refused)
+            .expect("client")
+            .contract()
+            .await
+            .expect("unreachable is a contract, not an error");
+        assert!(!contract.server_reachable);
+
+        static FORBIDDEN: &[(&str, u16, &str)] = &[(VERSION_CONFIG_PATH, 403, "")];
+        let error = MartiClient::with_dialer(&config(), fake_server(FORBIDDEN))
+            .expect("client")
+            .with_timeout(Duration::from_secs(5))
+            .contract()
+            .await
+            .expect_err("403");
+        assert!(matches!(
+            error,
+            MartiError::Rejected {
+                endpoint: VERSION_CONFIG_PATH,
+                status: 403
+            }
+        ));
+    }
+
+    #[test]
+    fn endpoints_default_to_the_marti_api_ports() {
+        assert_eq!(
+            parse_endpoint("https://tak.example").expect("https"),
+            (true, "tak.example".to_owned(), 8443)
+        );
+        assert_eq!(
+            parse_endpoint("http://[::1]:8081/Marti").expect("ipv6"),
+            (false, "::1".to_owned(), 8081)
+        );
+        assert!(matches!(
+            parse_endpoint("https://:8443"),
+            Err(MartiError::InvalidEndpoint { .. })
+        ));
+    }
+}
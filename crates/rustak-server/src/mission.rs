@@ -0,0 +1,591 @@
+//! TAK Mission API: the data-sync side of a TAK Server.
+//!
+//! [`MissionClient`] reuses [`MartiClient`]'s endpoint, identity and
+//! timeout handling. Missions are listed from `GET /Marti/api/missions`,
+//! subscriptions are `PUT`/`DELETE` on `.../subscription?uid=`, and change
+//! logs come from `GET .../changes`. Packages travel through the
+//! enterprise sync store: [`MissionClient::upload_package`] posts the bytes
+//! to `/Marti/sync/upload` and attaches the returned hash to the mission;
+//! [`MissionClient::download_package`] fetches them back by hash.
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::http::{self, percent_encode};
+use crate::marti::{self, EndpointDialer, MartiClient, MartiDial, MartiError};
+use crate::ServerClientConfig;
+
+pub const MISSIONS_PATH: &str = "/Marti/api/missions";
+pub const SYNC_UPLOAD_PATH: &str = "/Marti/sync/upload";
+pub const SYNC_CONTENT_PATH: &str = "/Marti/sync/content";
+
+/// Upper bound on package uploads and downloads unless overridden with
+/// [`MissionClient::with_max_package_bytes`].
+pub const DEFAULT_MAX_PACKAGE_BYTES: usize = 64 * 1024 * 1024;
+
+const SUBSCRIPTION_ENDPOINT: &str = "/Marti/api/missions/{name}/subscription";
+const CHANGES_ENDPOINT: &str = "/Marti/api/missions/{name}/changes";
+const CONTENTS_ENDPOINT: &str = "/Marti/api/missions/{name}/contents";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mission {
+    pub name: String,
+    pub description: Option<String>,
+    pub creator_uid: Option<String>,
+    /// ISO 8601, as the server reports it.
+    pub create_time: Option<String>,
+    pub keywords: Vec<String>,
+    pub groups: Vec<String>,
+    pub password_protected: bool,
+    pub contents: Vec<MissionContent>,
+    /// CoT uids attached to the mission.
+    pub uids: Vec<String>,
+}
+
+/// A file in the sync store, identified by its SHA-256 `hash`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissionContent {
+    pub hash: String,
+    pub name: String,
+    pub mime_type: Option<String>,
+    pub size: Option<u64>,
+    pub submitter: Option<String>,
+    pub submission_time: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissionSubscription {
+    pub mission_name: String,
+    pub client_uid: String,
+    /// Bearer token the server issues for the subscription, when it does.
+    pub token: Option<String>,
+    /// Mission state at subscription time, when the server includes it.
+    pub mission: Option<Mission>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MissionChangeKind {
+    CreateMission,
+    DeleteMission,
+    AddContent,
+    RemoveContent,
+    /// Change types this client does not model, verbatim.
+    Other(String),
+}
+
+impl MissionChangeKind {
+    fn parse(value: &str) -> Self {
+        match value {
+            "CREATE_MISSION" => Self::CreateMission,
+            "DELETE_MISSION" => Self::DeleteMission,
+            "ADD_CONTENT" => Self::AddContent,
+            "REMOVE_CONTENT" => Self::RemoveContent,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissionChange {
+    pub kind: MissionChangeKind,
+    pub mission_name: String,
+    pub timestamp: Option<String>,
+    pub creator_uid: Option<String>,
+    /// File added or removed by `AddContent`/`RemoveContent`.
+    pub content: Option<MissionContent>,
+    /// CoT uid added or removed, for uid changes.
+    pub content_uid: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MissionClient<D = EndpointDialer> {
+    marti: MartiClient<D>,
+    client_uid: String,
+    max_package_bytes: usize,
+}
+
+impl MissionClient<EndpointDialer> {
+    /// Mission client for the server `config` points at, acting as
+    /// `client_uid` (the device uid ATAK would subscribe with).
+    pub fn new(
+        config: &ServerClientConfig,
+        client_uid: impl Into<String>,
+    ) -> Result<Self, MartiError> {
+        Self::with_marti(MartiClient::new(config)?, client_uid)
+    }
+}
+
+impl<D: MartiDial> MissionClient<D> {
+    pub fn with_marti(
+        marti: MartiClient<D>,
+        client_uid: impl Into<String>,
+    ) -> Result<Self, MartiError> {
+        let client_uid = client_uid.into();
+        if client_uid.trim().is_empty() {
+            return Err(MartiError::EmptyClientUid);
+        }
+        Ok(Self {
+            marti,
+            client_uid,
+            max_package_bytes: DEFAULT_MAX_PACKAGE_BYTES,
+        })
+    }
+
+    #[must_use]
+    pub fn with_max_package_bytes(mut self, max_package_bytes: usize) -> Self {
+        self.max_package_bytes = max_package_bytes;
+        self
+    }
+
+    #[must_use]
+    pub fn client_uid(&self) -> &str {
+        &self.client_uid
+    }
+
+    /// Missions visible to the client's groups.
+    pub async fn missions(&self) -> Result<Vec<Mission>, MartiError> {
+        let body = self
+            .marti
+            .request("GET", MISSIONS_PATH, MISSIONS_PATH, None)
+            .await?;
+        array(MISSIONS_PATH, marti::envelope_data(MISSIONS_PATH, &body)?)?
+            .iter()
+            .map(|entry| parse_mission(MISSIONS_PATH, entry))
+            .collect()
+    }
+
+    pub async fn subscribe(&self, mission_name: &str) -> Result<MissionSubscription, MartiError> {
+        let target = format!(
+            "{}?uid={}",
+            self.mission_path(mission_name, "subscription")?,
+            percent_encode(&self.client_uid)
+        );
+        let body = self
+            .marti
+            .request("PUT", SUBSCRIPTION_ENDPOINT, &target, None)
+            .await?;
+        // Older servers answer with an empty body.
+        let data = if body.is_empty() {
+            Value::Null
+        } else {
+            marti::envelope_data(SUBSCRIPTION_ENDPOINT, &body)?
+        };
+        Ok(MissionSubscription {
+            mission_name: mission_name.to_owned(),
+            client_uid: self.client_uid.clone(),
+            token: text(&data, "token"),
+            mission: data
+                .get("mission")
+                .map(|mission| parse_mission(SUBSCRIPTION_ENDPOINT, mission))
+                .transpose()?,
+        })
+    }
+
+    pub async fn unsubscribe(&self, mission_name: &str) -> Result<(), MartiError> {
+        let target = format!(
+            "{}?uid={}",
+            self.mission_path(mission_name, "subscription")?,
+            percent_encode(&self.client_uid)
+        );
+        self.marti
+            .request("DELETE", SUBSCRIPTION_ENDPOINT, &target, None)
+            .await?;
+        Ok(())
+    }
+
+    /// Change log of `mission_name`, limited to the last `since` when given.
+    pub async fn changes(
+        &self,
+        mission_name: &str,
+        since: Option<Duration>,
+    ) -> Result<Vec<MissionChange>, MartiError> {
+        let mut target = self.mission_path(mission_name, "changes")?;
+        if let Some(since) = since {
+            target.push_str(&format!("?secago={}", since.as_secs()));
+        }
+        let body = self
+            .marti
+            .request("GET", CHANGES_ENDPOINT, &target, None)
+            .await?;
+        array(
+            CHANGES_ENDPOINT,
+            marti::envelope_data(CHANGES_ENDPOINT, &body)?,
+        )?
+        .iter()
+        .map(|entry| {
+            let kind = text(entry, "type").ok_or_else(|| malformed(CHANGES_ENDPOINT, "type"))?;
+            Ok(MissionChange {
+                kind: MissionChangeKind::parse(&kind),
+                mission_name: text(entry, "missionName").unwrap_or_else(|| mission_name.to_owned()),
+                timestamp: text(entry, "timestamp"),
+                creator_uid: text(entry, "creatorUid"),
+                content: entry
+                    .get("contentResource")
+                    .map(|resource| parse_content(CHANGES_ENDPOINT, resource))
+                    .transpose()?,
+                content_uid: text(entry, "contentUid"),
+            })
+        })
+        .collect()
+    }
+
+    /// Stores `bytes` in the sync store as `filename` and attaches the
+    /// result to `mission_name`.
+    pub async fn upload_package(
+        &self,
+        mission_name: &str,
+        filename: &str,
+        bytes: &[u8],
+    ) -> Result<MissionContent, MartiError> {
+        if bytes.len() > self.max_package_bytes {
+            return Err(MartiError::PackageTooLarge {
+                size: bytes.len(),
+                limit: self.max_package_bytes,
+            });
+        }
+        let contents_target = format!(
+            "{}?creatorUid={}",
+            self.mission_path(mission_name, "contents")?,
+            percent_encode(&self.client_uid)
+        );
+
+        let upload_target = format!(
+            "{SYNC_UPLOAD_PATH}?name={}&creatorUid={}",
+            percent_encode(filename),
+            percent_encode(&self.client_uid)
+        );
+        let body = self
+            .marti
+            .request(
+                "POST",
+                SYNC_UPLOAD_PATH,
+                &upload_target,
+                Some(http::Body {
+                    content_type: "application/octet-stream",
+                    bytes,
+                }),
+            )
+            .await?;
+        let mut content = parse_content(SYNC_UPLOAD_PATH, &marti::json(SYNC_UPLOAD_PATH, &body)?)?;
+        content.size.get_or_insert(bytes.len() as u64);
+
+        let hashes = json!({ "hashes": [content.hash] }).to_string();
+        self.marti
+            .request(
+                "PUT",
+                CONTENTS_ENDPOINT,
+                &contents_target,
+                Some(http::Body {
+                    content_type: "application/json",
+                    bytes: hashes.as_bytes(),
+                }),
+            )
+            .await?;
+        Ok(content)
+    }
+
+    /// Sync-store content with SHA-256 `hash`, bounded by the package limit.
+    pub async fn download_package(&self, hash: &str) -> Result<Vec<u8>, MartiError> {
+        let target = format!("{SYNC_CONTENT_PATH}?hash={}", percent_encode(hash));
+        self.marti
+            .download(SYNC_CONTENT_PATH, &target, self.max_package_bytes)
+            .await
+    }
+
+    fn mission_path(&self, mission_name: &str, action: &str) -> Result<String, MartiError> {
+        if mission_name.trim().is_empty() {
+            return Err(MartiError::EmptyMissionName);
+        }
+        Ok(format!(
+            "{MISSIONS_PATH}/{}/{action}",
+            percent_encode(mission_name)
+        ))
+    }
+}
+
+fn parse_mission(endpoint: &'static str, value: &Value) -> Result<Mission, MartiError> {
+    Ok(Mission {
+        name: text(value, "name").ok_or_else(|| malformed(endpoint, "name"))?,
+        description: text(value, "description").filter(|description| !description.is_empty()),
+        creator_uid: text(value, "creatorUid"),
+        create_time: text(value, "createTime"),
+        keywords: texts(value, "keywords"),
+        groups: texts(value, "groups"),
+        password_protected: value
+            .get("passwordProtected")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        contents: entries(value, "contents")
+            .filter_map(|entry| entry.get("data"))
+            .map(|resource| parse_content(endpoint, resource))
+            .collect::<Result<_, _>>()?,
+        uids: entries(value, "uids")
+            .filter_map(|entry| text(entry, "data"))
+            .collect(),
+    })
+}
+
+/// Sync-store resource. Mission listings use camelCase keys, while
+/// `/Marti/sync/upload` answers with the enterprise sync metadata names.
+fn parse_content(endpoint: &'static str, value: &Value) -> Result<MissionContent, MartiError> {
+    let field = |keys: &[&str]| keys.iter().find_map(|key| text(value, key));
+    Ok(MissionContent {
+        hash: field(&["hash", "Hash"]).ok_or_else(|| malformed(endpoint, "hash"))?,
+        name: field(&["name", "filename", "Name"]).unwrap_or_default(),
+        mime_type: field(&["mimeType", "MIMEType"]),
+        size: value.get("size").and_then(Value::as_u64),
+        submitter: field(&["submitter", "SubmissionUser"]),
+        submission_time: field(&["submissionTime", "SubmissionDateTime"]),
+        keywords: ["keywords", "Keywords"]
+            .iter()
+            .map(|key| texts(value, key))
+            .find(|keywords| !keywords.is_empty())
+            .unwrap_or_default(),
+    })
+}
+
+fn array(endpoint: &'static str, data: Value) -> Result<Vec<Value>, MartiError> {
+    match data {
+        Value::Array(entries) => Ok(entries),
+        _ => Err(MartiError::MalformedResponse {
+            endpoint,
+            reason: "`data` is not an array".to_owned(),
+        }),
+    }
+}
+
+fn entries<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+fn text(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_owned)
+}
+
+fn texts(value: &Value, key: &str) -> Vec<String> {
+    entries(value, key)
+        .filter_map(Value::as_str)
+        .map(str::to_owned)
+        .collect()
+}
+
+fn malformed(endpoint: &'static str, field: &str) -> MartiError {
+    MartiError::MalformedResponse {
+        endpoint,
+        reason: format!("missing `{field}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::{MissionChangeKind, MissionClient};
+    use crate::marti::{MartiClient, MartiError};
+    use crate::ServerClientConfig;
+
+    const MISSIONS: &str = r#"{"version":"3","type":"Mission","data":[{"name":"op north","description":"","chatRoom":"","tool":"public","keywords":["recon"],"creatorUid":"ANDROID-1","createTime":"2024-05-01T10:00:00.000Z","groups":["__ANON__"],"passwordProtected":false,"contents":[{"data":{"filename":"route.zip","keywords":[],"mimeType":"application/zip","name":"route.zip","submissionTime":"2024-05-01T10:05:00.000Z","submitter":"alice","uid":"c1","hash":"ab12","size":2048},"timestamp":"2024-05-01T10:05:00.000Z","creatorUid":"ANDROID-1"}],"uids":[{"data":"ANDROID-2","timestamp":"2024-05-01T10:06:00.000Z","creatorUid":"ANDROID-1"}]}]}"#;
+    const SUBSCRIPTION: &str = r#"{"version":"3","type":"com.bbn.marti.sync.model.MissionSubscription","data":{"token":"eyJ0b2tlbiJ9","clientUid":"rustak-1"}}"#;
+    const CHANGES: &str = r#"{"version":"3","type":"MissionChange","data":[{"type":"CREATE_MISSION","missionName":"op north","timestamp":"2024-05-01T10:00:00.000Z","creatorUid":"ANDROID-1"},{"type":"ADD_CONTENT","missionName":"op north","timestamp":"2024-05-01T10:05:00.000Z","creatorUid":"ANDROID-1","contentResource":{"name":"route.zip","hash":"ab12","size":2048}},{"type":"ADD_UID","missionName":"op north","contentUid":"ANDROID-2"}]}"#;
+    const UPLOADED: &str = r#"{"UID":"c2","Name":"package.zip","MIMEType":"application/zip","SubmissionUser":"rustak","Hash":"cd34","Keywords":["missionpackage"]}"#;
+
+    /// Answers requests from a `METHOD target` table over kept-alive
+    /// connections; uploads must carry `package` as their body.
+    fn fake_server(
+        routes: &'static [(&'static str, u16, &'static str)],
+        package: &'static [u8],
+    ) -> impl Fn() -> std::future::Ready<Result<DuplexStream, MartiError>> + Send + Sync {
+        move || {
+            let (client, mut server) = duplex(64 * 1024);
+            tokio::spawn(async move {
+                loop {
+                    let mut request = Vec::new();
+                    let mut buffer = [0_u8; 1024];
+                    let head_end = loop {
+                        if let Some(end) =
+                            request.windows(4).position(|window| window == b"\r\n\r\n")
+                        {
+                            break end + 4;
+                        }
+                        let read = server.read(&mut buffer).await.expect("read request");
+                        if read == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buffer[..read]);
+                    };
+                    let head = String::from_utf8_lossy(&request[..head_end]).into_owned();
+                    let content_length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .map_or(0, |length| length.trim().parse::<usize>().expect("length"));
+                    while request.len() < head_end + content_length {
+                        let read = server.read(&mut buffer).await.expect("read body");
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                    let mut words = head.split(' ');
+                    let route = format!(
+                        "{} {}",
+                        words.next().unwrap_or_default(),
+                        words.next().unwrap_or_default()
+                    );
+                    if route.starts_with("POST /Marti/sync/upload") {
+                        assert_eq!(&request[head_end..], package);
+                    }
+                    let (status, body) = routes
+                        .iter()
+                        .find(|(candidate, _, _)| *candidate == route)
+                        .map_or((404, ""), |(_, status, body)| (*status, *body));
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\nContent-Length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                    server
+                        .write_all(response.as_bytes())
+                        .await
+                        .expect("write response");
+                }
+            });
+            std::future::ready(Ok(client))
+        }
+    }
+
+    fn client(
+        routes: &'static [(&'static str, u16, &'static str)],
+        package: &'static [u8],
+    ) -> MissionClient<impl crate::marti::MartiDial> {
+        let config = ServerClientConfig {
+            endpoint: "http://tak.example".to_owned(),
+            ..ServerClientConfig::default()
+        };
+        let marti =
+            MartiClient::with_dialer(&config, fake_server(routes, package)).expect("marti client");
+        MissionClient::with_marti(marti, "rustak-1").expect("mission client")
+    }
+
+    #[tokio::test]
+    async fn missions_subscriptions_and_changes_are_typed() {
+        static ROUTES: &[(&str, u16, &str)] = &[
+            ("GET /Marti/api/missions", 200, MISSIONS),
+            (
+                "PUT /Marti/api/missions/op%20north/subscription?uid=rustak-1",
+                201,
+                SUBSCRIPTION,
+            ),
+            (
+                "DELETE /Marti/api/missions/op%20north/subscription?uid=rustak-1",
+                200,
+                "",
+            ),
+            (
+                "GET /Marti/api/missions/op%20north/changes?secago=3600",
+                200,
+                CHANGES,
+            ),
+        ];
+        let client = client(ROUTES, b"");
+
+        let missions = client.missions().await.expect("missions");
+        assert_eq!(missions.len(), 1);
+        let mission = &missions[0];
+        assert_eq!(mission.name, "op north");
+        assert_eq!(mission.description, None);
+        assert_eq!(mission.keywords, ["recon"]);
+        assert_eq!(mission.uids, ["ANDROID-2"]);
+        assert_eq!(mission.contents[0].hash, "ab12");
+        assert_eq!(mission.contents[0].size, Some(2048));
+
+        let subscription = client.subscribe("op north").await.expect("subscribe");
+        assert_eq!(subscription.token.as_deref(), Some("eyJ0b2tlbiJ9"));
+        assert_eq!(subscription.client_uid, "rustak-1");
+        client.unsubscribe("op north").await.expect("unsubscribe");
+
+        let changes = client
+            .changes("op north", Some(Duration::from_secs(3600)))
+            .await
+            .expect("changes");
+        let kinds: Vec<_> = changes.iter().map(|change| change.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                MissionChangeKind::CreateMission,
+                MissionChangeKind::AddContent,
+                MissionChangeKind::Other("ADD_UID".to_owned()),
+            ]
+        );
+        assert_eq!(
+            changes[1]
+                .content
+                .as_ref()
+                .map(|content| content.name.as_str()),
+            Some("route.zip")
+        );
+        assert_eq!(changes[2].content_uid.as_deref(), Some("ANDROID-2"));
+    }
+
+    #[tokio::test]
+    async fn packages_round_trip_through_the_sync_store() {
+        static PACKAGE: &[u8] = b"PK\x03\x04mission package";
+        static ROUTES: &[(&str, u16, &str)] = &[
+            (
+                "POST /Marti/sync/upload?name=package.zip&creatorUid=rustak-1",
+                200,
+                UPLOADED,
+            ),
+            (
+                "PUT /Marti/api/missions/op%20north/contents?creatorUid=rustak-1",
+                200,
+                MISSIONS,
+            ),
+            (
+                "GET /Marti/sync/content?hash=cd34",
+                200,
+                "PK\x03\x04mission package",
+            ),
+        ];
+        let client = client(ROUTES, PACKAGE);
+
+        let content = client
+            .upload_package("op north", "package.zip", PACKAGE)
+            .await
+            .expect("upload");
+        assert_eq!(content.hash, "cd34");
+        assert_eq!(content.mime_type.as_deref(), Some("application/zip"));
+        assert_eq!(content.size, Some(PACKAGE.len() as u64));
+        assert_eq!(content.keywords, ["missionpackage"]);
+
+        let downloaded = client.download_package("cd34").await.expect("download");
+        assert_eq!(downloaded, PACKAGE);
+
+        let client = client.with_max_package_bytes(4);
+        assert!(matches!(
+            client
+                .upload_package("op north", "package.zip", PACKAGE)
+                .await,
+            Err(MartiError::PackageTooLarge { limit: 4, .. })
+        ));
+        assert!(matches!(
+            client.download_package("cd34").await,
+            Err(MartiError::MalformedResponse { .. })
+        ));
+        assert!(matches!(
+            client.subscribe(" ").await,
+            Err(MartiError::EmptyMissionName)
+        ));
+        assert!(matches!(
+            MissionClient::with_marti(
+                MartiClient::with_dialer(&ServerClientConfig::default(), fake_server(ROUTES, b""))
+                    .expect("marti client"),
+                ""
+            ),
+            Err(MartiError::EmptyClientUid)
+        ));
+    }
+}
@@ -17,6 +17,7 @@ fn secure_config() -> ServerClientConfig {
                 archive_path: PathBuf::from("tests/fixtures/certs/dev_identity.p12"),
                 password: Some("dev-pass".to_owned()),
             },
+            server_spki_pin: None,
         }),
         ..ServerClientConfig::default()
     }
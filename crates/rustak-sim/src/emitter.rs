@@ -0,0 +1,254 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::route::RouteSample;
+use crate::truth::{TruthSnapshot, TruthState};
+use rustak_core::{
+    Contact, CoreError, CotEvent, CotType, CotTypeError, Kinematics, Position, TimestampUtc, Track,
+};
+use rustak_geo::{destination_point, GeoError};
+use rustak_limits::{CodedError, ErrorCode};
+
+/// `how` stamped on simulated tracks: machine generated, simulated.
+pub const SIMULATED_HOW: &str = "m-s";
+
+/// Identity and presentation of one simulated track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackEmitterConfig {
+    pub uid: String,
+    /// Atom CoT type, e.g. `a-h-A-M-F-Q` for a hostile drone.
+    pub cot_type: String,
+    pub callsign: Option<String>,
+    /// Where truth `x_mm`/`y_mm` (east/north) are measured from. Its `hae`
+    /// is carried onto every event.
+    pub origin: Position,
+    /// How long after each update the track goes stale.
+    pub stale: Duration,
+}
+
+/// Renders [`TruthSnapshot`]s of one entity as CoT position reports with
+/// `<track>` and, when configured, `<contact>` details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackCotEmitter {
+    config: TrackEmitterConfig,
+}
+
+impl TrackCotEmitter {
+    pub fn new(config: TrackEmitterConfig) -> Result<Self, TrackEmitterError> {
+        if config.uid.trim().is_empty() {
+            return Err(TrackEmitterError::EmptyUid);
+        }
+        CotType::parse(&config.cot_type).map_err(TrackEmitterError::CotType)?;
+        if config.stale.is_zero() {
+            return Err(TrackEmitterError::ZeroStale);
+        }
+        Ok(Self { config })
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &TrackEmitterConfig {
+        &self.config
+    }
+
+    /// Position of `state`, offset from the origin along the great circle.
+    pub fn position(&self, state: &TruthState) -> Result<Position, TrackEmitterError> {
+        let east_m = state.x_mm as f64 / 1_000.0;
+        let north_m = state.y_mm as f64 / 1_000.0;
+        let mut position = destination_point(
+            &self.config.origin,
+            east_m.atan2(north_m).to_degrees(),
+            east_m.hypot(north_m),
+        )
+        .map_err(TrackEmitterError::Geo)?;
+        if let Some(hae) = self.config.origin.hae() {
+            position = position.with_hae(hae).map_err(TrackEmitterError::Core)?;
+        }
+        Ok(position)
+    }
+
+    /// The event for `snapshot`, timed `start` plus the snapshot's elapsed
+    /// simulation time.
+    pub fn emit(
+        &self,
+        snapshot: &TruthSnapshot,
+        start: TimestampUtc,
+    ) -> Result<CotEvent, TrackEmitterError> {
+        let east = f64::from(snapshot.state.vx_mm_per_s) / 1_000.0;
+        let north = f64::from(snapshot.state.vy_mm_per_s) / 1_000.0;
+        let course = east.atan2(north).to_degrees().rem_euclid(360.0);
+        self.event(
+            self.position(&snapshot.state)?,
+            Kinematics::new(Some(east.hypot(north)), Some(course), None)
+                .map_err(TrackEmitterError::Core)?,
+            snapshot.elapsed_millis,
+            start,
+        )
+    }
+
+    /// The event for an entity following a route, which places it instead
+    /// of the snapshot's `x_mm`/`y_mm`.
+    pub fn emit_route_sample(
+        &self,
+        sample: &RouteSample,
+        snapshot: &TruthSnapshot,
+        start: TimestampUtc,
+    ) -> Result<CotEvent, TrackEmitterError> {
+        let mut position = sample.position.clone();
+        if let (None, Some(hae)) = (position.hae(), self.config.origin.hae()) {
+            position = position.with_hae(hae).map_err(TrackEmitterError::Core)?;
+        }
+        self.event(
+            position,
+            Kinematics::new(Some(sample.speed_mps), sample.course_degrees, None)
+                .map_err(TrackEmitterError::Core)?,
+            snapshot.elapsed_millis,
+            start,
+        )
+    }
+
+    fn event(
+        &self,
+        point: Position,
+        kinematics: Kinematics,
+        elapsed_millis: u64,
+        start: TimestampUtc,
+    ) -> Result<CotEvent, TrackEmitterError> {
+        let time = TimestampUtc::from_unix_nanos(
+            start
+                .unix_nanos()
+                .saturating_add(i128::from(elapsed_millis) * 1_000_000),
+        );
+        let stale = TimestampUtc::from_unix_nanos(
+            time.unix_nanos()
+                .saturating_add(self.config.stale.as_nanos() as i128),
+        );
+        let mut event = CotEvent::new(&self.config.uid, &self.config.cot_type, time, stale, point);
+        event.how = Some(SIMULATED_HOW.to_owned());
+        event.set_extension(&Track::new(kinematics).map_err(TrackEmitterError::Core)?);
+        if let Some(callsign) = &self.config.callsign {
+            event.set_extension(&Contact {
+                callsign: callsign.clone(),
+                endpoint: None,
+                phone: None,
+            });
+        }
+        Ok(event)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TrackEmitterError {
+    EmptyUid,
+    CotType(CotTypeError),
+    ZeroStale,
+    Geo(GeoError),
+    Core(CoreError),
+}
+
+impl fmt::Display for TrackEmitterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyUid => f.write_str("track emitter uid must not be empty"),
+            Self::CotType(error) => write!(f, "track emitter cot_type is invalid: {error}"),
+            Self::ZeroStale => f.write_str("track emitter stale must be > 0"),
+            Self::Geo(error) => write!(f, "track position is out of range: {error}"),
+            Self::Core(error) => write!(f, "track kinematics are invalid: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TrackEmitterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CotType(error) => Some(error),
+            Self::Geo(error) => Some(error),
+            Self::Core(error) => Some(error),
+            Self::EmptyUid | Self::ZeroStale => None,
+        }
+    }
+}
+
+impl CodedError for TrackEmitterError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyUid => ErrorCode::new("SIM", 401),
+            Self::CotType(_) => ErrorCode::new("SIM", 402),
+            Self::ZeroStale => ErrorCode::new("SIM", 403),
+            Self::Geo(_) => ErrorCode::new("SIM", 404),
+            Self::Core(_) => ErrorCode::new("SIM", 405),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::{Contact, Position, TimestampUtc, Track};
+
+    use crate::emitter::{TrackCotEmitter, TrackEmitterConfig, TrackEmitterError};
+    use crate::truth::{TruthSnapshot, TruthState};
+
+    fn config() -> TrackEmitterConfig {
+        TrackEmitterConfig {
+            uid: "sim-1".to_owned(),
+            cot_type: "a-h-A-M-F-Q".to_owned(),
+            callsign: Some("DRONE-1".to_owned()),
+            origin: Position::new(0.0, 0.0)
+                .and_then(|origin| origin.with_hae(120.0))
+                .expect("origin"),
+            stale: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn snapshots_become_timed_position_reports() {
+        let emitter = TrackCotEmitter::new(config()).expect("emitter");
+        let snapshot = TruthSnapshot {
+            tick: 10,
+            elapsed_millis: 10_000,
+            state: TruthState {
+                x_mm: 111_195_080,
+                y_mm: 0,
+                vx_mm_per_s: 0,
+                vy_mm_per_s: -5_000,
+            },
+        };
+        let start = TimestampUtc::from_unix_seconds(1_700_000_000);
+        let event = emitter.emit(&snapshot, start).expect("event");
+
+        assert_eq!(event.uid, "sim-1");
+        assert_eq!(event.how.as_deref(), Some("m-s"));
+        assert_eq!(event.time, TimestampUtc::from_unix_seconds(1_700_000_010));
+        assert_eq!(event.stale, TimestampUtc::from_unix_seconds(1_700_000_040));
+        // 111.195 km east of the origin is one degree of longitude.
+        assert!(event.point.latitude().abs() < 1e-9);
+        assert!((event.point.longitude() - 1.0).abs() < 1e-4);
+        assert_eq!(event.point.hae(), Some(120.0));
+
+        let track = event.extension::<Track>().expect("track").expect("valid");
+        assert_eq!(track.kinematics().speed(), Some(5.0));
+        assert_eq!(track.kinematics().course(), Some(180.0));
+        let contact = event
+            .extension::<Contact>()
+            .expect("contact")
+            .expect("valid");
+        assert_eq!(contact.callsign, "DRONE-1");
+    }
+
+    #[test]
+    fn emitter_config_is_validated() {
+        let mut invalid = config();
+        invalid.cot_type = "b-t-f".to_owned();
+        assert!(matches!(
+            TrackCotEmitter::new(invalid),
+            Err(TrackEmitterError::CotType(_))
+        ));
+        let mut invalid = config();
+        invalid.stale = Duration::ZERO;
+        assert_eq!(
+            TrackCotEmitter::new(invalid),
+            Err(TrackEmitterError::ZeroStale)
+        );
+    }
+}
@@ -1,6 +1,9 @@
+use std::fmt;
+
 use crate::truth::TruthSnapshot;
 use rustak_core::Position;
 use rustak_geo::{interpolate_great_circle, GeoError};
+use rustak_limits::{CodedError, ErrorCode};
 
 #[derive(Debug, PartialEq)]
 pub enum GeoInterpolationError {
@@ -9,6 +12,38 @@ pub enum GeoInterpolationError {
     Geo(GeoError),
 }
 
+impl fmt::Display for GeoInterpolationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroDurationTicks => f.write_str("interpolation duration_ticks must be > 0"),
+            Self::TickOutOfRange {
+                tick,
+                duration_ticks,
+            } => write!(f, "tick {tick} is past duration_ticks {duration_ticks}"),
+            Self::Geo(error) => write!(f, "great-circle interpolation failed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for GeoInterpolationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Geo(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl CodedError for GeoInterpolationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroDurationTicks => ErrorCode::new("SIM", 301),
+            Self::TickOutOfRange { .. } => ErrorCode::new("SIM", 302),
+            Self::Geo(_) => ErrorCode::new("SIM", 303),
+        }
+    }
+}
+
 pub fn interpolate_route_position(
     start: &Position,
     end: &Position,
@@ -1,12 +1,15 @@
 #[cfg(feature = "geo")]
+pub mod emitter;
+#[cfg(feature = "geo")]
 pub mod geo;
+#[cfg(feature = "geo")]
+pub mod route;
 pub mod scenario;
 pub mod sensor;
 pub mod sweep;
 pub mod truth;
 
 use rustak_io::{MessageEnvelope, MessageSink, MessageSource};
-use sensor::SensorModel;
 #[cfg(feature = "geo")]
 use {geo::interpolate_snapshot_route_position, rustak_core::Position};
 
@@ -15,11 +18,20 @@ pub use rustak_io::{
     MessageSink as IoMessageSink, MessageSource as IoMessageSource, ObservedTime,
 };
 pub use scenario::{Scenario, ScenarioComposition, ScenarioError, ScenarioOverlay};
-pub use sensor::{DeterministicSensorModel, SensorObservation};
+pub use sensor::{
+    ConfiguredSensorModel, DetectionSensorModel, DeterministicSensorModel, FieldOfView,
+    FieldOfViewSensorModel, GaussianSensorModel, KinematicObservation, SensorConfig,
+    SensorConfigError, SensorModel, SensorObservation,
+};
 pub use sweep::{SweepAxis, SweepCase, SweepReport, SweepRunOptions, SweepRunner};
 pub use truth::{TruthEngine, TruthEngineConfig, TruthEngineError, TruthSnapshot, TruthState};
 #[cfg(feature = "geo")]
-pub use {geo::interpolate_route_position, geo::GeoInterpolationError};
+pub use {
+    emitter::{TrackCotEmitter, TrackEmitterConfig, TrackEmitterError},
+    geo::interpolate_route_position,
+    geo::GeoInterpolationError,
+    route::{Route, RouteError, RouteFollower, RouteMode, RouteSample, Waypoint},
+};
 
 pub type SimEnvelope<T> = MessageEnvelope<T>;
 pub type SimSink<T> = dyn MessageSink<T>;
@@ -62,13 +74,30 @@ pub fn simulate_step_with_geo<M: SensorModel>(
     Ok((envelope, position))
 }
 
+/// Advances `engine` and places the entity on `route` at the new elapsed
+/// time.
+#[cfg(feature = "geo")]
+pub fn simulate_step_on_route<M: SensorModel>(
+    engine: &mut TruthEngine,
+    model: &M,
+    route: &RouteFollower,
+) -> Result<(SimEnvelope<M::Observation>, RouteSample), RouteError> {
+    let snapshot = engine.advance();
+    let envelope = observe_with_model(&snapshot, model);
+    Ok((envelope, route.follow(&snapshot)?))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::sensor::SensorModel;
     use crate::truth::{TruthEngine, TruthEngineConfig, TruthState};
     use crate::{scenario_envelope, simulate_step, DeterministicSensorModel};
     #[cfg(feature = "geo")]
-    use {crate::simulate_step_with_geo, rustak_core::Position};
+    use {
+        crate::{simulate_step_on_route, simulate_step_with_geo},
+        crate::{Route, RouteFollower, RouteMode, Waypoint},
+        rustak_core::Position,
+    };
 
     #[test]
     fn scenario_envelope_wraps_message() {
@@ -130,4 +159,39 @@ mod tests {
             "expected midpoint longitude"
         );
     }
+
+    #[cfg(feature = "geo")]
+    #[test]
+    fn simulate_step_on_route_follows_the_engine_clock() {
+        let mut engine = TruthEngine::new(
+            5,
+            TruthState {
+                x_mm: 0,
+                y_mm: 0,
+                vx_mm_per_s: 0,
+                vy_mm_per_s: 0,
+            },
+            TruthEngineConfig {
+                step_millis: 30_000,
+                ..TruthEngineConfig::default()
+            },
+        )
+        .expect("engine");
+        let route = RouteFollower::new(Route::new(
+            vec![
+                Waypoint::new(Position::new(0.0, 0.0).expect("start"), 100.0),
+                Waypoint::new(Position::new(0.1, 0.0).expect("end"), 100.0),
+            ],
+            RouteMode::PingPong,
+        ))
+        .expect("route");
+
+        let (envelope, sample) =
+            simulate_step_on_route(&mut engine, &DeterministicSensorModel::default(), &route)
+                .expect("step");
+        assert_eq!(envelope.message.tick, 1);
+        // 30 s at 100 m/s due north.
+        assert!((sample.position.latitude() - 3_000.0 / 111_195.08).abs() < 1e-6);
+        assert_eq!(sample.course_degrees.map(f64::round), Some(0.0));
+    }
 }
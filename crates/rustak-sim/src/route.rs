@@ -0,0 +1,388 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::truth::TruthSnapshot;
+use rustak_core::Position;
+use rustak_geo::{
+    haversine_distance_meters, initial_bearing_degrees, interpolate_great_circle, GeoError,
+};
+use rustak_limits::{CodedError, ErrorCode};
+
+/// One point of a [`Route`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    pub position: Position,
+    /// Ground speed on the leg that leaves this waypoint.
+    pub speed_mps: f64,
+    /// How long the entity holds here on arrival before moving on.
+    pub dwell: Duration,
+}
+
+impl Waypoint {
+    #[must_use]
+    pub fn new(position: Position, speed_mps: f64) -> Self {
+        Self {
+            position,
+            speed_mps,
+            dwell: Duration::ZERO,
+        }
+    }
+
+    #[must_use]
+    pub fn with_dwell(mut self, dwell: Duration) -> Self {
+        self.dwell = dwell;
+        self
+    }
+}
+
+/// What happens after the last waypoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RouteMode {
+    /// Hold at the last waypoint.
+    #[default]
+    Once,
+    /// Fly back to the first waypoint and start over.
+    Loop,
+    /// Retrace the route backwards, then forwards again.
+    PingPong,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub waypoints: Vec<Waypoint>,
+    pub mode: RouteMode,
+}
+
+impl Route {
+    #[must_use]
+    pub fn new(waypoints: Vec<Waypoint>, mode: RouteMode) -> Self {
+        Self { waypoints, mode }
+    }
+}
+
+/// Where a route puts its entity at some elapsed time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSample {
+    pub position: Position,
+    /// 0 while dwelling.
+    pub speed_mps: f64,
+    /// Great-circle bearing towards the next waypoint; `None` while
+    /// dwelling.
+    pub course_degrees: Option<f64>,
+    /// Index of the waypoint last reached.
+    pub waypoint: usize,
+    /// A [`RouteMode::Once`] route has reached its last waypoint and
+    /// finished dwelling there.
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Dwell {
+        at: usize,
+        duration: Duration,
+    },
+    Leg {
+        from: usize,
+        to: usize,
+        duration: Duration,
+    },
+}
+
+impl Segment {
+    fn duration(&self) -> Duration {
+        match self {
+            Self::Dwell { duration, .. } | Self::Leg { duration, .. } => *duration,
+        }
+    }
+}
+
+/// Follows a [`Route`] along great circles, sampled by elapsed time so a
+/// [`crate::TruthEngine`] clock can drive it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteFollower {
+    route: Route,
+    /// One cycle of the route; `Once` routes run it a single time.
+    segments: Vec<Segment>,
+    cycle: Duration,
+}
+
+impl RouteFollower {
+    pub fn new(route: Route) -> Result<Self, RouteError> {
+        let count = route.waypoints.len();
+        if count < 2 {
+            return Err(RouteError::TooFewWaypoints { count });
+        }
+        if let Some((index, waypoint)) = route
+            .waypoints
+            .iter()
+            .enumerate()
+            .find(|(_, waypoint)| !(waypoint.speed_mps.is_finite() && waypoint.speed_mps > 0.0))
+        {
+            return Err(RouteError::InvalidSpeed {
+                index,
+                speed_mps: waypoint.speed_mps,
+            });
+        }
+
+        let order: Vec<usize> = match route.mode {
+            RouteMode::Once => (0..count).collect(),
+            RouteMode::Loop => (0..count).chain([0]).collect(),
+            RouteMode::PingPong => (0..count).chain((0..count - 1).rev()).collect(),
+        };
+        let mut segments = Vec::with_capacity(order.len() * 2);
+        for pair in order.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            segments.push(Segment::Dwell {
+                at: from,
+                duration: route.waypoints[from].dwell,
+            });
+            let distance = haversine_distance_meters(
+                &route.waypoints[from].position,
+                &route.waypoints[to].position,
+            );
+            let speed_mps = route.waypoints[from].speed_mps;
+            let duration = Duration::try_from_secs_f64(distance / speed_mps).map_err(|_| {
+                RouteError::InvalidSpeed {
+                    index: from,
+                    speed_mps,
+                }
+            })?;
+            segments.push(Segment::Leg { from, to, duration });
+        }
+        if route.mode == RouteMode::Once {
+            segments.push(Segment::Dwell {
+                at: count - 1,
+                duration: route.waypoints[count - 1].dwell,
+            });
+        }
+        segments.retain(|segment| !segment.duration().is_zero());
+        let cycle = segments.iter().map(Segment::duration).sum::<Duration>();
+        if cycle.is_zero() {
+            return Err(RouteError::ZeroLengthRoute);
+        }
+
+        Ok(Self {
+            route,
+            segments,
+            cycle,
+        })
+    }
+
+    #[must_use]
+    pub fn route(&self) -> &Route {
+        &self.route
+    }
+
+    /// Time to run the route once; for `Loop` and `PingPong` the period.
+    #[must_use]
+    pub fn cycle(&self) -> Duration {
+        self.cycle
+    }
+
+    pub fn sample(&self, elapsed: Duration) -> Result<RouteSample, RouteError> {
+        let waypoints = &self.route.waypoints;
+        let last = waypoints.len() - 1;
+        if self.route.mode == RouteMode::Once && elapsed >= self.cycle {
+            return Ok(RouteSample {
+                position: waypoints[last].position.clone(),
+                speed_mps: 0.0,
+                course_degrees: None,
+                waypoint: last,
+                finished: true,
+            });
+        }
+
+        let cycle_nanos = self.cycle.as_nanos();
+        let mut remaining = Duration::from_nanos((elapsed.as_nanos() % cycle_nanos) as u64);
+        for segment in &self.segments {
+            let duration = segment.duration();
+            if remaining >= duration {
+                remaining -= duration;
+                continue;
+            }
+            return match *segment {
+                Segment::Dwell { at, .. } => Ok(RouteSample {
+                    position: waypoints[at].position.clone(),
+                    speed_mps: 0.0,
+                    course_degrees: None,
+                    waypoint: at,
+                    finished: false,
+                }),
+                Segment::Leg { from, to, .. } => {
+                    let fraction = remaining.as_secs_f64() / duration.as_secs_f64();
+                    let position = interpolate_great_circle(
+                        &waypoints[from].position,
+                        &waypoints[to].position,
+                        fraction,
+                    )
+                    .map_err(RouteError::Geo)?;
+                    let course = initial_bearing_degrees(&position, &waypoints[to].position);
+                    Ok(RouteSample {
+                        position,
+                        speed_mps: waypoints[from].speed_mps,
+                        course_degrees: Some(course),
+                        waypoint: from,
+                        finished: false,
+                    })
+                }
+            };
+        }
+        // Unreachable: `remaining` is below the cycle, the segments' sum.
+        Err(RouteError::ZeroLengthRoute)
+    }
+
+    /// The sample at `snapshot`'s elapsed simulation time.
+    pub fn follow(&self, snapshot: &TruthSnapshot) -> Result<RouteSample, RouteError> {
+        self.sample(Duration::from_millis(snapshot.elapsed_millis))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RouteError {
+    TooFewWaypoints { count: usize },
+    InvalidSpeed { index: usize, speed_mps: f64 },
+    ZeroLengthRoute,
+    Geo(GeoError),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooFewWaypoints { count } => {
+                write!(f, "route needs at least 2 waypoints, got {count}")
+            }
+            Self::InvalidSpeed { index, speed_mps } => write!(
+                f,
+                "waypoint {index} speed_mps must be finite and > 0, got {speed_mps}"
+            ),
+            Self::ZeroLengthRoute => {
+                f.write_str("route has no distance to travel and no dwell time")
+            }
+            Self::Geo(error) => write!(f, "great-circle interpolation failed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Geo(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl CodedError for RouteError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::TooFewWaypoints { .. } => ErrorCode::new("SIM", 501),
+            Self::InvalidSpeed { .. } => ErrorCode::new("SIM", 502),
+            Self::ZeroLengthRoute => ErrorCode::new("SIM", 503),
+            Self::Geo(_) => ErrorCode::new("SIM", 504),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::Position;
+
+    use crate::route::{Route, RouteError, RouteFollower, RouteMode, Waypoint};
+
+    /// Three waypoints on the equator one degree apart (~111.2 km), flown
+    /// at 1111.95 m/s so each leg takes 100 s.
+    fn route(mode: RouteMode) -> Route {
+        let waypoint =
+            |lon: f64| Waypoint::new(Position::new(0.0, lon).expect("waypoint"), 1_111.950_8);
+        Route::new(
+            vec![
+                waypoint(0.0),
+                waypoint(1.0).with_dwell(Duration::from_secs(20)),
+                waypoint(2.0),
+            ],
+            mode,
+        )
+    }
+
+    fn longitude(follower: &RouteFollower, secs: u64) -> f64 {
+        follower
+            .sample(Duration::from_secs(secs))
+            .expect("sample")
+            .position
+            .longitude()
+    }
+
+    #[test]
+    fn once_routes_dwell_then_hold_at_the_end() {
+        let follower = RouteFollower::new(route(RouteMode::Once)).expect("route");
+        assert!((follower.cycle().as_secs_f64() - 220.0).abs() < 1e-3);
+
+        let midway = follower.sample(Duration::from_secs(50)).expect("sample");
+        assert!((midway.position.longitude() - 0.5).abs() < 1e-3);
+        assert!((midway.speed_mps - 1_111.950_8).abs() < 1e-9);
+        assert!((midway.course_degrees.expect("course") - 90.0).abs() < 1e-6);
+
+        let dwelling = follower.sample(Duration::from_secs(110)).expect("sample");
+        assert_eq!(dwelling.waypoint, 1);
+        assert_eq!(dwelling.speed_mps, 0.0);
+        assert!((dwelling.position.longitude() - 1.0).abs() < 1e-9);
+
+        assert!((longitude(&follower, 170) - 1.5).abs() < 1e-3);
+        let done = follower.sample(Duration::from_secs(1_000)).expect("sample");
+        assert!(done.finished);
+        assert!((done.position.longitude() - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn loop_and_ping_pong_routes_repeat() {
+        let looping = RouteFollower::new(route(RouteMode::Loop)).expect("route");
+        // 0 -> 1 (100 s), dwell 20 s, 1 -> 2 (100 s), 2 -> 0 (200 s).
+        assert!((looping.cycle().as_secs_f64() - 420.0).abs() < 1e-3);
+        assert!((longitude(&looping, 320) - 1.0).abs() < 1e-3);
+        assert!((longitude(&looping, 420 + 50) - 0.5).abs() < 1e-3);
+        let returning = looping.sample(Duration::from_secs(320)).expect("sample");
+        assert!((returning.course_degrees.expect("course") - 270.0).abs() < 1e-6);
+
+        let ping_pong = RouteFollower::new(route(RouteMode::PingPong)).expect("route");
+        // Out as above, back 2 -> 1, dwell 20 s, 1 -> 0.
+        assert!((ping_pong.cycle().as_secs_f64() - 440.0).abs() < 1e-3);
+        assert!((longitude(&ping_pong, 270) - 1.5).abs() < 1e-3);
+        assert_eq!(
+            ping_pong
+                .sample(Duration::from_secs(330))
+                .expect("sample")
+                .waypoint,
+            1
+        );
+        assert!((longitude(&ping_pong, 440 + 170) - 1.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn invalid_routes_are_rejected() {
+        let mut short = route(RouteMode::Once);
+        short.waypoints.truncate(1);
+        assert_eq!(
+            RouteFollower::new(short),
+            Err(RouteError::TooFewWaypoints { count: 1 })
+        );
+
+        let mut stopped = route(RouteMode::Loop);
+        stopped.waypoints[2].speed_mps = 0.0;
+        assert_eq!(
+            RouteFollower::new(stopped),
+            Err(RouteError::InvalidSpeed {
+                index: 2,
+                speed_mps: 0.0
+            })
+        );
+
+        let here = Waypoint::new(Position::new(1.0, 1.0).expect("waypoint"), 5.0);
+        assert_eq!(
+            RouteFollower::new(Route::new(vec![here.clone(), here], RouteMode::Loop)),
+            Err(RouteError::ZeroLengthRoute)
+        );
+    }
+}
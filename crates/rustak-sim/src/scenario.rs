@@ -1,4 +1,7 @@
 use std::collections::BTreeMap;
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Scenario {
@@ -54,6 +57,28 @@ pub enum ScenarioError {
     ZeroDurationTicks,
 }
 
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoIncludes => f.write_str("scenario composition has no includes"),
+            Self::EmptyName => f.write_str("scenario name must not be empty"),
+            Self::ZeroDurationTicks => f.write_str("scenario duration_ticks must be > 0"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl CodedError for ScenarioError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NoIncludes => ErrorCode::new("SIM", 1),
+            Self::EmptyName => ErrorCode::new("SIM", 2),
+            Self::ZeroDurationTicks => ErrorCode::new("SIM", 3),
+        }
+    }
+}
+
 impl ScenarioComposition {
     pub fn compose(&self) -> Result<Scenario, ScenarioError> {
         let mut scenarios = self.includes.iter();
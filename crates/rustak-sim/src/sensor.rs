@@ -1,4 +1,27 @@
-use crate::truth::TruthSnapshot;
+use std::f64::consts::TAU;
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
+
+use crate::sweep::SweepCase;
+use crate::truth::{TruthSnapshot, TruthState};
+
+/// Sweep parameters [`SensorConfig::with_sweep_case`] reads; other axes of
+/// a case are left to the caller.
+pub const SWEEP_POSITION_SIGMA_MM: &str = "position_sigma_mm";
+pub const SWEEP_VELOCITY_SIGMA_MM_PER_S: &str = "velocity_sigma_mm_per_s";
+pub const SWEEP_DETECTION_PPM: &str = "detection_ppm";
+pub const SWEEP_FOV_WIDTH_DEGREES: &str = "fov_width_degrees";
+pub const SWEEP_FOV_MAX_RANGE_MM: &str = "fov_max_range_mm";
+
+/// Detection probability of one, in parts per million.
+pub const CERTAIN_DETECTION_PPM: u32 = 1_000_000;
+
+const NOISE_X_STREAM: u64 = 0x93A5_17F2;
+const NOISE_Y_STREAM: u64 = 0x5E1B_93D7;
+const NOISE_VX_STREAM: u64 = 0x2F8C_6E05;
+const NOISE_VY_STREAM: u64 = 0xB43A_0D69;
+const DETECTION_STREAM: u64 = 0x71E6_C2B8;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SensorObservation {
@@ -59,6 +82,318 @@ impl SensorModel for DeterministicSensorModel {
     }
 }
 
+/// Position and velocity as a noisy sensor reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KinematicObservation {
+    pub tick: u64,
+    pub observed_x_mm: i64,
+    pub observed_y_mm: i64,
+    pub observed_vx_mm_per_s: i32,
+    pub observed_vy_mm_per_s: i32,
+}
+
+/// Adds zero-mean Gaussian noise with the given standard deviations to
+/// position and velocity. Noise is drawn from `seed` and the tick, so a
+/// snapshot always observes the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GaussianSensorModel {
+    pub seed: u64,
+    pub position_sigma_mm: u32,
+    pub velocity_sigma_mm_per_s: u32,
+}
+
+impl SensorModel for GaussianSensorModel {
+    type Observation = KinematicObservation;
+
+    fn observe(&self, truth: &TruthSnapshot) -> Self::Observation {
+        let noise = |stream, sigma| gaussian_noise(self.seed ^ stream, truth.tick, sigma);
+        let velocity = |value: i32, stream| {
+            let noisy = i64::from(value) + noise(stream, self.velocity_sigma_mm_per_s);
+            noisy.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+        };
+        KinematicObservation {
+            tick: truth.tick,
+            observed_x_mm: truth
+                .state
+                .x_mm
+                .saturating_add(noise(NOISE_X_STREAM, self.position_sigma_mm)),
+            observed_y_mm: truth
+                .state
+                .y_mm
+                .saturating_add(noise(NOISE_Y_STREAM, self.position_sigma_mm)),
+            observed_vx_mm_per_s: velocity(truth.state.vx_mm_per_s, NOISE_VX_STREAM),
+            observed_vy_mm_per_s: velocity(truth.state.vy_mm_per_s, NOISE_VY_STREAM),
+        }
+    }
+}
+
+/// Passes a tick to `inner` with probability `detection_ppm` per million;
+/// missed ticks observe `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectionSensorModel<M> {
+    pub inner: M,
+    pub seed: u64,
+    pub detection_ppm: u32,
+}
+
+impl<M> DetectionSensorModel<M> {
+    #[must_use]
+    pub fn detects(&self, tick: u64) -> bool {
+        detects(self.seed, tick, self.detection_ppm)
+    }
+}
+
+impl<M: SensorModel> SensorModel for DetectionSensorModel<M> {
+    type Observation = Option<M::Observation>;
+
+    fn observe(&self, truth: &TruthSnapshot) -> Self::Observation {
+        self.detects(truth.tick).then(|| self.inner.observe(truth))
+    }
+}
+
+/// A sensor's coverage: a sector `width_degrees` wide centred on
+/// `boresight_degrees` (clockwise from north, i.e. +y), out to
+/// `max_range_mm` from the sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldOfView {
+    pub sensor_x_mm: i64,
+    pub sensor_y_mm: i64,
+    pub boresight_degrees: f64,
+    pub width_degrees: f64,
+    pub max_range_mm: i64,
+}
+
+impl Default for FieldOfView {
+    /// All round, unlimited range.
+    fn default() -> Self {
+        Self {
+            sensor_x_mm: 0,
+            sensor_y_mm: 0,
+            boresight_degrees: 0.0,
+            width_degrees: 360.0,
+            max_range_mm: i64::MAX,
+        }
+    }
+}
+
+impl FieldOfView {
+    #[must_use]
+    pub fn contains(&self, state: &TruthState) -> bool {
+        let dx = state.x_mm.saturating_sub(self.sensor_x_mm) as f64;
+        let dy = state.y_mm.saturating_sub(self.sensor_y_mm) as f64;
+        if dx.hypot(dy) > self.max_range_mm as f64 {
+            return false;
+        }
+        if self.width_degrees >= 360.0 || (dx == 0.0 && dy == 0.0) {
+            return true;
+        }
+        let bearing = dx.atan2(dy).to_degrees();
+        let off_axis = (bearing - self.boresight_degrees + 180.0).rem_euclid(360.0) - 180.0;
+        off_axis.abs() <= self.width_degrees / 2.0
+    }
+}
+
+/// Passes truth inside `field_of_view` to `inner`; anything outside
+/// observes `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldOfViewSensorModel<M> {
+    pub inner: M,
+    pub field_of_view: FieldOfView,
+}
+
+impl<M: SensorModel> SensorModel for FieldOfViewSensorModel<M> {
+    type Observation = Option<M::Observation>;
+
+    fn observe(&self, truth: &TruthSnapshot) -> Self::Observation {
+        self.field_of_view
+            .contains(&truth.state)
+            .then(|| self.inner.observe(truth))
+    }
+}
+
+/// Noise, detection and coverage parameters of a simulated sensor. The
+/// default is a perfect, all-seeing sensor; sweeps vary it per case with
+/// [`SensorConfig::with_sweep_case`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorConfig {
+    pub seed: u64,
+    pub position_sigma_mm: u32,
+    pub velocity_sigma_mm_per_s: u32,
+    pub detection_ppm: u32,
+    pub field_of_view: FieldOfView,
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            position_sigma_mm: 0,
+            velocity_sigma_mm_per_s: 0,
+            detection_ppm: CERTAIN_DETECTION_PPM,
+            field_of_view: FieldOfView::default(),
+        }
+    }
+}
+
+impl SensorConfig {
+    pub fn validate(&self) -> Result<(), SensorConfigError> {
+        if self.detection_ppm > CERTAIN_DETECTION_PPM {
+            return Err(SensorConfigError::DetectionPpmOutOfRange {
+                detection_ppm: self.detection_ppm,
+            });
+        }
+        let fov = &self.field_of_view;
+        if !(fov.width_degrees > 0.0 && fov.width_degrees <= 360.0) {
+            return Err(SensorConfigError::InvalidFieldOfViewWidth {
+                width_degrees: fov.width_degrees,
+            });
+        }
+        if !fov.boresight_degrees.is_finite() {
+            return Err(SensorConfigError::InvalidBoresight {
+                boresight_degrees: fov.boresight_degrees,
+            });
+        }
+        if fov.max_range_mm <= 0 {
+            return Err(SensorConfigError::NonPositiveMaxRange {
+                max_range_mm: fov.max_range_mm,
+            });
+        }
+        Ok(())
+    }
+
+    /// This config with the `SWEEP_*` parameters of `case` applied, and the
+    /// case id mixed into the seed so every case draws its own noise.
+    pub fn with_sweep_case(mut self, case: &SweepCase) -> Result<Self, SensorConfigError> {
+        let parameter = |name: &'static str| case.parameters.get(name).copied();
+        let unsigned = |name: &'static str, value: i64| {
+            u32::try_from(value).map_err(|_| SensorConfigError::ParameterOutOfRange { name, value })
+        };
+        if let Some(value) = parameter(SWEEP_POSITION_SIGMA_MM) {
+            self.position_sigma_mm = unsigned(SWEEP_POSITION_SIGMA_MM, value)?;
+        }
+        if let Some(value) = parameter(SWEEP_VELOCITY_SIGMA_MM_PER_S) {
+            self.velocity_sigma_mm_per_s = unsigned(SWEEP_VELOCITY_SIGMA_MM_PER_S, value)?;
+        }
+        if let Some(value) = parameter(SWEEP_DETECTION_PPM) {
+            self.detection_ppm = unsigned(SWEEP_DETECTION_PPM, value)?;
+        }
+        if let Some(value) = parameter(SWEEP_FOV_WIDTH_DEGREES) {
+            self.field_of_view.width_degrees = value as f64;
+        }
+        if let Some(value) = parameter(SWEEP_FOV_MAX_RANGE_MM) {
+            self.field_of_view.max_range_mm = value;
+        }
+        self.seed ^= case.case_id;
+        self.validate()?;
+        Ok(self)
+    }
+
+    pub fn build(&self) -> Result<ConfiguredSensorModel, SensorConfigError> {
+        self.validate()?;
+        Ok(ConfiguredSensorModel { config: *self })
+    }
+}
+
+/// The sensor a [`SensorConfig`] describes: truth outside the field of view
+/// or on a missed tick observes `None`, anything else a Gaussian-noised
+/// [`KinematicObservation`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfiguredSensorModel {
+    config: SensorConfig,
+}
+
+impl ConfiguredSensorModel {
+    #[must_use]
+    pub fn config(&self) -> &SensorConfig {
+        &self.config
+    }
+}
+
+impl SensorModel for ConfiguredSensorModel {
+    type Observation = Option<KinematicObservation>;
+
+    fn observe(&self, truth: &TruthSnapshot) -> Self::Observation {
+        let config = &self.config;
+        let noise = GaussianSensorModel {
+            seed: config.seed,
+            position_sigma_mm: config.position_sigma_mm,
+            velocity_sigma_mm_per_s: config.velocity_sigma_mm_per_s,
+        };
+        (config.field_of_view.contains(&truth.state)
+            && detects(config.seed, truth.tick, config.detection_ppm))
+        .then(|| noise.observe(truth))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorConfigError {
+    DetectionPpmOutOfRange { detection_ppm: u32 },
+    InvalidFieldOfViewWidth { width_degrees: f64 },
+    InvalidBoresight { boresight_degrees: f64 },
+    NonPositiveMaxRange { max_range_mm: i64 },
+    ParameterOutOfRange { name: &'static str, value: i64 },
+}
+
+impl fmt::Display for SensorConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DetectionPpmOutOfRange { detection_ppm } => write!(
+                f,
+                "sensor detection_ppm must be <= {CERTAIN_DETECTION_PPM}, got {detection_ppm}"
+            ),
+            Self::InvalidFieldOfViewWidth { width_degrees } => write!(
+                f,
+                "sensor field of view width must be in (0, 360] degrees, got {width_degrees}"
+            ),
+            Self::InvalidBoresight { boresight_degrees } => write!(
+                f,
+                "sensor boresight must be finite, got {boresight_degrees}"
+            ),
+            Self::NonPositiveMaxRange { max_range_mm } => {
+                write!(f, "sensor max_range_mm must be > 0, got {max_range_mm}")
+            }
+            Self::ParameterOutOfRange { name, value } => {
+                write!(f, "sweep parameter `{name}` must fit in a u32, got {value}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SensorConfigError {}
+
+impl CodedError for SensorConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::DetectionPpmOutOfRange { .. } => ErrorCode::new("SIM", 601),
+            Self::InvalidFieldOfViewWidth { .. } => ErrorCode::new("SIM", 602),
+            Self::InvalidBoresight { .. } => ErrorCode::new("SIM", 603),
+            Self::NonPositiveMaxRange { .. } => ErrorCode::new("SIM", 604),
+            Self::ParameterOutOfRange { .. } => ErrorCode::new("SIM", 605),
+        }
+    }
+}
+
+fn detects(seed: u64, tick: u64, detection_ppm: u32) -> bool {
+    let draw = splitmix64(seed ^ DETECTION_STREAM ^ tick.rotate_left(17));
+    draw % u64::from(CERTAIN_DETECTION_PPM) < u64::from(detection_ppm)
+}
+
+/// A uniform draw in `[0, 1)`.
+fn unit_draw(seed: u64, tick: u64) -> f64 {
+    (splitmix64(seed ^ tick.rotate_left(17)) >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// A normal draw with standard deviation `sigma`, by Box-Muller.
+fn gaussian_noise(seed: u64, tick: u64, sigma: u32) -> i64 {
+    if sigma == 0 {
+        return 0;
+    }
+
+    let radius = (-2.0 * (1.0 - unit_draw(seed, tick)).ln()).sqrt();
+    let angle = TAU * unit_draw(splitmix64(seed), tick);
+    (radius * angle.cos() * f64::from(sigma)).round() as i64
+}
+
 fn signed_noise(seed: u64, tick: u64, amplitude: i64) -> i64 {
     if amplitude <= 0 {
         return 0;
@@ -88,10 +423,30 @@ fn quantize(value: i64, quantum: i64) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::sensor::{DeterministicSensorModel, SensorModel};
+    use std::collections::BTreeMap;
+
+    use crate::sensor::{
+        DetectionSensorModel, DeterministicSensorModel, FieldOfView, FieldOfViewSensorModel,
+        GaussianSensorModel, SensorConfig, SensorConfigError, SensorModel, SWEEP_DETECTION_PPM,
+        SWEEP_FOV_WIDTH_DEGREES, SWEEP_POSITION_SIGMA_MM,
+    };
+    use crate::sweep::{SweepAxis, SweepCase, SweepRunner};
     use crate::truth::TruthSnapshot;
     use crate::truth::TruthState;
 
+    fn at(tick: u64, x_mm: i64, y_mm: i64) -> TruthSnapshot {
+        TruthSnapshot {
+            tick,
+            elapsed_millis: tick * 100,
+            state: TruthState {
+                x_mm,
+                y_mm,
+                vx_mm_per_s: 1_000,
+                vy_mm_per_s: 0,
+            },
+        }
+    }
+
     #[test]
     fn deterministic_sensor_is_stable_for_same_tick() {
         let model = DeterministicSensorModel::default();
@@ -140,4 +495,132 @@ mod tests {
             (second.observed_x_mm, second.observed_y_mm)
         );
     }
+
+    #[test]
+    fn gaussian_noise_is_seeded_and_has_the_configured_spread() {
+        let model = GaussianSensorModel {
+            seed: 9,
+            position_sigma_mm: 500,
+            velocity_sigma_mm_per_s: 20,
+        };
+        let samples: Vec<_> = (1..=4_000)
+            .map(|tick| model.observe(&at(tick, 0, 0)))
+            .collect();
+        assert_eq!(samples[17], model.observe(&at(18, 0, 0)));
+
+        let moments = |values: Vec<f64>| {
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            let variance =
+                values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+            (mean, variance.sqrt())
+        };
+        let (mean, sigma) = moments(samples.iter().map(|o| o.observed_x_mm as f64).collect());
+        assert!(mean.abs() < 30.0, "mean {mean}");
+        assert!((sigma - 500.0).abs() < 25.0, "sigma {sigma}");
+        let (mean, sigma) = moments(
+            samples
+                .iter()
+                .map(|o| f64::from(o.observed_vx_mm_per_s))
+                .collect(),
+        );
+        assert!((mean - 1_000.0).abs() < 2.0, "mean {mean}");
+        assert!((sigma - 20.0).abs() < 2.0, "sigma {sigma}");
+        assert_ne!(samples[0].observed_x_mm, samples[0].observed_y_mm);
+    }
+
+    #[test]
+    fn detection_probability_drops_the_expected_share_of_ticks() {
+        let model = DetectionSensorModel {
+            inner: DeterministicSensorModel::default(),
+            seed: 4,
+            detection_ppm: 250_000,
+        };
+        let detected = (1..=10_000)
+            .filter(|tick| model.observe(&at(*tick, 0, 0)).is_some())
+            .count();
+        assert!((2_300..=2_700).contains(&detected), "{detected}");
+        assert_eq!(
+            model.observe(&at(33, 0, 0)).is_some(),
+            model.detects(33),
+            "observations follow detects()"
+        );
+    }
+
+    #[test]
+    fn field_of_view_limits_bearing_and_range() {
+        let model = FieldOfViewSensorModel {
+            inner: DeterministicSensorModel::default(),
+            field_of_view: FieldOfView {
+                sensor_x_mm: 0,
+                sensor_y_mm: 0,
+                boresight_degrees: 90.0,
+                width_degrees: 60.0,
+                max_range_mm: 10_000,
+            },
+        };
+        assert!(model.observe(&at(1, 5_000, 0)).is_some());
+        assert!(model.observe(&at(1, 5_000, 2_500)).is_some());
+        assert!(
+            model.observe(&at(1, 5_000, 5_000)).is_none(),
+            "45° off axis"
+        );
+        assert!(model.observe(&at(1, -5_000, 0)).is_none(), "behind");
+        assert!(model.observe(&at(1, 20_000, 0)).is_none(), "out of range");
+        assert!(FieldOfView::default().contains(&at(1, -5_000, 0).state));
+    }
+
+    #[test]
+    fn sensor_config_builds_from_sweep_cases() {
+        let runner = SweepRunner::new(
+            vec![
+                SweepAxis::new(SWEEP_POSITION_SIGMA_MM, vec![0, 1_000]).expect("axis"),
+                SweepAxis::new(SWEEP_DETECTION_PPM, vec![0, 1_000_000]).expect("axis"),
+            ],
+            5,
+        )
+        .expect("runner");
+        let configs: Vec<_> = runner
+            .enumerate_cases()
+            .iter()
+            .map(|case| SensorConfig::default().with_sweep_case(case))
+            .collect::<Result<_, _>>()
+            .expect("configs");
+        assert_eq!(configs.len(), 4);
+        assert_eq!(configs[3].position_sigma_mm, 1_000);
+        assert_ne!(configs[0].seed, configs[1].seed);
+
+        let truth = at(7, 1_000, 1_000);
+        let blind = configs[0].build().expect("sensor");
+        assert_eq!(blind.observe(&truth), None);
+        let exact = configs[1].build().expect("sensor").observe(&truth);
+        assert_eq!(
+            exact.map(|o| (o.observed_x_mm, o.observed_y_mm)),
+            Some((1_000, 1_000))
+        );
+        let noisy = configs[3].build().expect("sensor");
+        assert_eq!(noisy.observe(&truth), noisy.observe(&truth));
+
+        let case = |name: &str, value| SweepCase {
+            index: 0,
+            case_id: 1,
+            parameters: BTreeMap::from([(name.to_owned(), value)]),
+        };
+        assert_eq!(
+            SensorConfig::default().with_sweep_case(&case(SWEEP_POSITION_SIGMA_MM, -1)),
+            Err(SensorConfigError::ParameterOutOfRange {
+                name: SWEEP_POSITION_SIGMA_MM,
+                value: -1
+            })
+        );
+        assert_eq!(
+            SensorConfig::default().with_sweep_case(&case(SWEEP_FOV_WIDTH_DEGREES, 0)),
+            Err(SensorConfigError::InvalidFieldOfViewWidth { width_degrees: 0.0 })
+        );
+        assert_eq!(
+            SensorConfig::default().with_sweep_case(&case(SWEEP_DETECTION_PPM, 1_000_001)),
+            Err(SensorConfigError::DetectionPpmOutOfRange {
+                detection_ppm: 1_000_001
+            })
+        );
+    }
 }
@@ -1,4 +1,7 @@
 use std::collections::BTreeMap;
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SweepAxis {
@@ -53,6 +56,28 @@ pub enum SweepError {
     DuplicateAxisName,
 }
 
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyAxisName => f.write_str("sweep axis name must not be empty"),
+            Self::EmptyAxisValues { axis } => write!(f, "sweep axis `{axis}` has no values"),
+            Self::DuplicateAxisName => f.write_str("sweep axis names must be unique"),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+impl CodedError for SweepError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyAxisName => ErrorCode::new("SIM", 101),
+            Self::EmptyAxisValues { .. } => ErrorCode::new("SIM", 102),
+            Self::DuplicateAxisName => ErrorCode::new("SIM", 103),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SweepRunner {
     axes: Vec<SweepAxis>,
@@ -1,3 +1,7 @@
+use std::fmt;
+
+use rustak_limits::{CodedError, ErrorCode};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TruthState {
     pub x_mm: i64,
@@ -37,6 +41,32 @@ pub enum TruthEngineError {
     NegativeVelocityJitter,
 }
 
+impl fmt::Display for TruthEngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroStepMillis => f.write_str("truth engine step_millis must be > 0"),
+            Self::NonPositiveVelocityLimit => {
+                f.write_str("truth engine velocity_limit_mm_per_s must be > 0")
+            }
+            Self::NegativeVelocityJitter => {
+                f.write_str("truth engine velocity_jitter_mm_per_s must be >= 0")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TruthEngineError {}
+
+impl CodedError for TruthEngineError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroStepMillis => ErrorCode::new("SIM", 201),
+            Self::NonPositiveVelocityLimit => ErrorCode::new("SIM", 202),
+            Self::NegativeVelocityJitter => ErrorCode::new("SIM", 203),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TruthEngine {
     config: TruthEngineConfig,
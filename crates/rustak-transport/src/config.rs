@@ -1,3 +1,7 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
 use rustak_limits::Limits;
 
 use crate::TransportConfigError;
@@ -7,6 +11,9 @@ pub struct SendQueueConfig {
     pub max_messages: usize,
     pub max_bytes: usize,
     pub mode: SendQueueMode,
+    /// Per-class bandwidth budgets applied when the queue is drained;
+    /// `None` drains at link speed.
+    pub shaping: Option<TrafficShapingConfig>,
 }
 
 impl SendQueueConfig {
@@ -29,7 +36,200 @@ impl SendQueueConfig {
                 limits_max_bytes: limits.max_queue_bytes,
             });
         }
+        if let Some(shaping) = &self.shaping {
+            shaping.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Traffic class a [`crate::SendQueueClassifier`] assigns to each message
+/// so bandwidth can be budgeted per class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessageClass {
+    /// Own-position reports.
+    Pli,
+    Chat,
+    /// Tracks relayed for other entities, e.g. bridged sensor tracks.
+    Track,
+    /// Events with imagery-like or otherwise large details.
+    LargeDetail,
+    Other,
+}
+
+impl MessageClass {
+    pub const ALL: [Self; 5] = [
+        Self::Pli,
+        Self::Chat,
+        Self::Track,
+        Self::LargeDetail,
+        Self::Other,
+    ];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pli => "pli",
+            Self::Chat => "chat",
+            Self::Track => "track",
+            Self::LargeDetail => "large_detail",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for MessageClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Token bucket for one class: `bytes_per_second` sustained, up to
+/// `burst_bytes` after an idle period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClassBudget {
+    pub bytes_per_second: u64,
+    pub burst_bytes: usize,
+}
+
+/// Bandwidth budgets by class. Classes without a budget are not shaped.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrafficShapingConfig {
+    pub budgets: BTreeMap<MessageClass, ClassBudget>,
+}
+
+impl TrafficShapingConfig {
+    #[must_use]
+    pub fn with_budget(mut self, class: MessageClass, budget: ClassBudget) -> Self {
+        self.budgets.insert(class, budget);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), TransportConfigError> {
+        for (class, budget) in &self.budgets {
+            if budget.bytes_per_second == 0 {
+                return Err(TransportConfigError::ZeroShapingRate {
+                    class: class.as_str(),
+                });
+            }
+            if budget.burst_bytes == 0 {
+                return Err(TransportConfigError::ZeroShapingBurst {
+                    class: class.as_str(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fixed accounting period for a byte quota, aligned to UTC hour or day
+/// boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QuotaWindow {
+    Hour,
+    Day,
+}
+
+impl QuotaWindow {
+    pub const ALL: [Self; 2] = [Self::Hour, Self::Day];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    #[must_use]
+    pub const fn length(self) -> Duration {
+        match self {
+            Self::Hour => Duration::from_secs(60 * 60),
+            Self::Day => Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl fmt::Display for QuotaWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Bytes allowed per window in each direction; `None` leaves a direction
+/// unmetered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QuotaLimit {
+    pub send_bytes: Option<u64>,
+    pub receive_bytes: Option<u64>,
+}
+
+/// What happens to a connection once a quota is used up, until the window
+/// rolls over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaAction {
+    /// Count the overrun and keep the traffic flowing.
+    Warn,
+    /// Pace frames in the exhausted direction to `bytes_per_second`.
+    Throttle { bytes_per_second: u64 },
+    /// Fail sends and receives, and refuse reconnects.
+    Disconnect,
+}
+
+/// Per-connection byte quotas for metered links. Usage is kept by a
+/// [`crate::QuotaMeter`], which outlives reconnects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficQuotaConfig {
+    pub limits: BTreeMap<QuotaWindow, QuotaLimit>,
+    pub action: QuotaAction,
+}
+
+impl TrafficQuotaConfig {
+    #[must_use]
+    pub fn new(action: QuotaAction) -> Self {
+        Self {
+            limits: BTreeMap::new(),
+            action,
+        }
+    }
+
+    #[must_use]
+    pub fn with_limit(mut self, window: QuotaWindow, limit: QuotaLimit) -> Self {
+        self.limits.insert(window, limit);
+        self
+    }
 
+    pub fn validate(&self) -> Result<(), TransportConfigError> {
+        if self.limits.is_empty() {
+            return Err(TransportConfigError::EmptyQuota);
+        }
+        for (window, limit) in &self.limits {
+            let window = window.as_str();
+            match (limit.send_bytes, limit.receive_bytes) {
+                (None, None) => return Err(TransportConfigError::EmptyQuotaWindow { window }),
+                (Some(0), _) => {
+                    return Err(TransportConfigError::ZeroQuotaBytes {
+                        window,
+                        field: "send_bytes",
+                    })
+                }
+                (_, Some(0)) => {
+                    return Err(TransportConfigError::ZeroQuotaBytes {
+                        window,
+                        field: "receive_bytes",
+                    })
+                }
+                _ => {}
+            }
+        }
+        if self.action
+            == (QuotaAction::Throttle {
+                bytes_per_second: 0,
+            })
+        {
+            return Err(TransportConfigError::ZeroQuotaThrottleRate);
+        }
         Ok(())
     }
 }
@@ -37,6 +237,34 @@ impl SendQueueConfig {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SendQueueMode {
     Fifo,
+    /// Drains [`crate::QueuePriority::High`] before `Normal` before `Low`.
     Priority,
+    /// Keeps only the latest message per coalesce key. Messages without a
+    /// key are never coalesced; drain order follows priority as in
+    /// [`SendQueueMode::Priority`].
     CoalesceLatestByUid,
 }
+
+/// Micro-batching for `TransportSender`: small frames are grouped into one
+/// write, flushed once `max_batch_frames` are pending or the oldest pending
+/// frame has waited `flush_interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteBatchConfig {
+    /// Upper bound on the latency batching adds to any frame.
+    pub flush_interval: Duration,
+    pub max_batch_frames: usize,
+}
+
+impl WriteBatchConfig {
+    pub fn validate(&self) -> Result<(), TransportConfigError> {
+        if self.flush_interval.is_zero() {
+            return Err(TransportConfigError::ZeroDuration {
+                field: "write_batch.flush_interval",
+            });
+        }
+        if self.max_batch_frames == 0 {
+            return Err(TransportConfigError::ZeroWriteBatchFrames);
+        }
+        Ok(())
+    }
+}
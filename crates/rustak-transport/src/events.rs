@@ -0,0 +1,196 @@
+//! Typed link-health events broadcast to any number of observers.
+//!
+//! [`ConnectionManager`](crate::ConnectionManager), the connections it dials
+//! and any [`OutboundSendQueue`](crate::OutboundSendQueue) given a
+//! [`TransportEvents`] handle publish to the same channel. Publishing never
+//! blocks; a subscriber that falls more than the channel capacity behind
+//! skips the oldest events.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::Stream;
+use rustak_wire::{NegotiationReason, TakProtocolVersion};
+use tokio::sync::broadcast;
+
+use crate::MessageClass;
+
+/// Events buffered per subscriber before the oldest are skipped.
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportEvent {
+    Connected {
+        peer: SocketAddr,
+    },
+    Disconnected {
+        reason: String,
+    },
+    /// `attempt` is the dial that failed; the next one starts after `delay`.
+    ReconnectScheduled {
+        attempt: u32,
+        delay: Duration,
+    },
+    NegotiationUpgraded {
+        version: TakProtocolVersion,
+    },
+    /// A `FailOpen` downgrade policy dropped the stream back to legacy XML.
+    NegotiationFellBack {
+        reason: NegotiationReason,
+    },
+    /// A `FailClosed` downgrade policy gave up on the stream; every later
+    /// frame fails with
+    /// [`TransportComposeError::NegotiationTerminated`](crate::TransportComposeError::NegotiationTerminated).
+    NegotiationTerminated {
+        reason: NegotiationReason,
+    },
+    /// The send queue went over `max_messages` or `max_bytes` and is about
+    /// to drop messages. Sizes are taken before any drop.
+    QueueSaturated {
+        queued_messages: usize,
+        queued_bytes: usize,
+    },
+    /// One message dropped from the send queue under pressure.
+    FrameDropped {
+        class: MessageClass,
+        bytes: usize,
+    },
+}
+
+impl fmt::Display for TransportEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected { peer } => write!(f, "connected peer={peer}"),
+            Self::Disconnected { reason } => write!(f, "disconnected reason={reason:?}"),
+            Self::ReconnectScheduled { attempt, delay } => write!(
+                f,
+                "reconnect_scheduled attempt={attempt} delay_ms={}",
+                delay.as_millis()
+            ),
+            Self::NegotiationUpgraded { version } => {
+                write!(f, "negotiation_upgraded version={}", version.wire_byte())
+            }
+            Self::NegotiationFellBack { reason } => {
+                write!(f, "negotiation_fell_back reason={reason:?}")
+            }
+            Self::NegotiationTerminated { reason } => {
+                write!(f, "negotiation_terminated reason={reason:?}")
+            }
+            Self::QueueSaturated {
+                queued_messages,
+                queued_bytes,
+            } => write!(
+                f,
+                "queue_saturated queued_messages={queued_messages} queued_bytes={queued_bytes}"
+            ),
+            Self::FrameDropped { class, bytes } => {
+                write!(f, "frame_dropped class={class} bytes={bytes}")
+            }
+        }
+    }
+}
+
+/// Sending half of the event channel. Clones publish to the same
+/// subscribers.
+#[derive(Debug, Clone)]
+pub struct TransportEvents {
+    sender: broadcast::Sender<TransportEvent>,
+}
+
+impl TransportEvents {
+    /// `capacity` is clamped to at least 1.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    /// Receives every event published after this call.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<TransportEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Like [`Self::subscribe`], as a stream that skips past lagged events
+    /// and ends once every [`TransportEvents`] clone is dropped.
+    pub fn stream(&self) -> impl Stream<Item = TransportEvent> + Send + 'static {
+        futures::stream::unfold(self.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Sends `event` to current subscribers; a no-op without any.
+    pub fn publish(&self, event: TransportEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    #[must_use]
+    pub fn receiver_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for TransportEvents {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use super::{TransportEvent, TransportEvents};
+    use crate::MessageClass;
+
+    #[tokio::test]
+    async fn stream_skips_lagged_events_and_ends_when_senders_drop() {
+        let events = TransportEvents::new(2);
+        let mut stream = Box::pin(events.stream());
+        for attempt in 1..=3 {
+            events.publish(TransportEvent::ReconnectScheduled {
+                attempt,
+                delay: Duration::from_millis(100),
+            });
+        }
+        events.publish(TransportEvent::FrameDropped {
+            class: MessageClass::Chat,
+            bytes: 12,
+        });
+        drop(events);
+
+        let received: Vec<String> = stream
+            .by_ref()
+            .map(|event| event.to_string())
+            .collect()
+            .await;
+        assert_eq!(
+            received,
+            [
+                "reconnect_scheduled attempt=3 delay_ms=100",
+                "frame_dropped class=chat bytes=12",
+            ]
+        );
+    }
+
+    #[test]
+    fn publishing_without_subscribers_is_a_no_op() {
+        let events = TransportEvents::default();
+        assert_eq!(events.receiver_count(), 0);
+        events.publish(TransportEvent::Disconnected {
+            reason: "eof".to_owned(),
+        });
+        let receiver = events.subscribe();
+        assert_eq!(events.receiver_count(), 1);
+        assert!(receiver.is_empty());
+    }
+}
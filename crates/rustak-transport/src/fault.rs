@@ -0,0 +1,272 @@
+//! Scripted fault injection for chaos tests (feature `fault-injection`).
+//!
+//! A [`FaultController`] is shared between the test (or an admin endpoint)
+//! and a [`FaultInjectingIo`] wrapped around the connection's socket. Armed
+//! faults are consumed as the wrapped IO hits them.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use crate::TransportConnection;
+use rustak_wire::NegotiationEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FaultSnapshot {
+    pub pending_write_drops: u32,
+    pub read_delay: Option<Duration>,
+    pub negotiation_timeout_armed: bool,
+    pub flush_error_armed: bool,
+    pub writes_dropped: u64,
+    pub reads_delayed: u64,
+    pub flush_errors: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FaultController {
+    state: Arc<Mutex<FaultSnapshot>>,
+}
+
+impl FaultController {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Silently discards the next `count` writes while reporting success.
+    pub fn drop_next_writes(&self, count: u32) {
+        self.lock().pending_write_drops = count;
+    }
+
+    /// Holds every read for `delay` before touching the inner reader.
+    pub fn delay_reads(&self, delay: Duration) {
+        self.lock().read_delay = (!delay.is_zero()).then_some(delay);
+    }
+
+    /// Makes the next [`TransportConnection::apply_injected_faults`] report a
+    /// negotiation timeout.
+    pub fn force_negotiation_timeout(&self) {
+        self.lock().negotiation_timeout_armed = true;
+    }
+
+    /// Fails the next flush with an I/O error.
+    pub fn fail_next_flush(&self) {
+        self.lock().flush_error_armed = true;
+    }
+
+    /// Disarms every pending fault; counters are kept.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.pending_write_drops = 0;
+        state.read_delay = None;
+        state.negotiation_timeout_armed = false;
+        state.flush_error_armed = false;
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> FaultSnapshot {
+        *self.lock()
+    }
+
+    fn take_write_drop(&self) -> bool {
+        let mut state = self.lock();
+        if state.pending_write_drops == 0 {
+            return false;
+        }
+        state.pending_write_drops -= 1;
+        state.writes_dropped = state.writes_dropped.saturating_add(1);
+        true
+    }
+
+    fn take_negotiation_timeout(&self) -> bool {
+        std::mem::take(&mut self.lock().negotiation_timeout_armed)
+    }
+
+    fn take_flush_error(&self) -> bool {
+        let mut state = self.lock();
+        if !std::mem::take(&mut state.flush_error_armed) {
+            return false;
+        }
+        state.flush_errors = state.flush_errors.saturating_add(1);
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FaultSnapshot> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Async IO wrapper that applies the faults armed on its controller.
+#[derive(Debug)]
+pub struct FaultInjectingIo<IO> {
+    inner: IO,
+    controller: FaultController,
+    read_delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<IO> FaultInjectingIo<IO> {
+    #[must_use]
+    pub fn new(inner: IO, controller: FaultController) -> Self {
+        Self {
+            inner,
+            controller,
+            read_delay: None,
+        }
+    }
+
+    #[must_use]
+    pub fn controller(&self) -> &FaultController {
+        &self.controller
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> IO {
+        self.inner
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for FaultInjectingIo<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if this.read_delay.is_none() {
+            if let Some(delay) = this.controller.snapshot().read_delay {
+                this.read_delay = Some(Box::pin(tokio::time::sleep(delay)));
+                let mut state = this.controller.lock();
+                state.reads_delayed = state.reads_delayed.saturating_add(1);
+            }
+        }
+        if let Some(delay) = this.read_delay.as_mut() {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if result.is_ready() {
+            this.read_delay = None;
+        }
+        result
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for FaultInjectingIo<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.controller.take_write_drop() {
+            return Poll::Ready(Ok(buf.len()));
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.controller.take_flush_error() {
+            return Poll::Ready(Err(io::Error::other("injected flush failure")));
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+impl<IO> TransportConnection<FaultInjectingIo<IO>> {
+    /// Feeds a forced negotiation timeout, if one is armed, into the
+    /// connection's negotiator.
+    pub fn apply_injected_faults(&mut self) -> Option<NegotiationEvent> {
+        if self.io.controller.take_negotiation_timeout() {
+            return Some(self.observe_timeout());
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_wire::{DowngradePolicy, NegotiationState};
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::{FaultController, FaultInjectingIo};
+    use crate::{TransportConfig, TransportConnection};
+
+    #[tokio::test]
+    async fn drops_next_writes_then_resumes() {
+        let (client, mut server) = duplex(64);
+        let controller = FaultController::new();
+        let mut io = FaultInjectingIo::new(client, controller.clone());
+
+        controller.drop_next_writes(1);
+        io.write_all(b"lost")
+            .await
+            .expect("dropped write reports ok");
+        io.write_all(b"kept").await.expect("write");
+        drop(io);
+
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).await.expect("read");
+        assert_eq!(received, b"kept");
+        assert_eq!(controller.snapshot().writes_dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn flush_error_is_one_shot() {
+        let (client, _server) = duplex(64);
+        let controller = FaultController::new();
+        let mut io = FaultInjectingIo::new(client, controller.clone());
+
+        controller.fail_next_flush();
+        assert!(io.flush().await.is_err());
+        assert!(io.flush().await.is_ok());
+        assert_eq!(controller.snapshot().flush_errors, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delays_reads_by_configured_duration() {
+        let (client, mut server) = duplex(64);
+        let controller = FaultController::new();
+        let mut io = FaultInjectingIo::new(client, controller.clone());
+        controller.delay_reads(Duration::from_millis(250));
+        server.write_all(b"x").await.expect("write");
+
+        let started = tokio::time::Instant::now();
+        let mut byte = [0_u8; 1];
+        io.read_exact(&mut byte).await.expect("read");
+        assert!(started.elapsed() >= Duration::from_millis(250));
+        assert_eq!(controller.snapshot().reads_delayed, 1);
+    }
+
+    #[tokio::test]
+    async fn forced_negotiation_timeout_feeds_negotiator() {
+        let (client, _server) = duplex(64);
+        let controller = FaultController::new();
+        let mut connection = TransportConnection::new(
+            FaultInjectingIo::new(client, controller.clone()),
+            &TransportConfig::default(),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("connection");
+
+        assert!(connection.apply_injected_faults().is_none());
+        connection.begin_upgrade_attempt();
+        controller.force_negotiation_timeout();
+        assert!(connection.apply_injected_faults().is_some());
+        assert_eq!(connection.negotiation_state(), NegotiationState::LegacyXml);
+    }
+}
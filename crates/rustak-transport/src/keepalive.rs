@@ -0,0 +1,231 @@
+//! Application-level keepalive for TAK streaming connections.
+//!
+//! TCP keepalive (see [`crate::apply_tcp_keepalive`]) only notices a dead
+//! peer after the kernel gives up; TAK servers also expect periodic `t-x-c-t`
+//! ping events. [`KeepaliveDriver`] tracks inbound traffic and decides when to
+//! ping and when the peer has gone quiet for too long;
+//! [`crate::TransportConnection::recv_frame_with_keepalive`] drives it.
+
+use std::time::Duration;
+
+use rustak_core::time::TimestampUtc;
+use rustak_core::{CotEvent, Position};
+use tokio::time::Instant;
+
+use crate::{Keepalive, TransportComposeError, TransportFraming};
+
+/// CoT type of TAK ping events (and the `t-x-c-t-r` replies).
+pub const PING_COT_TYPE: &str = "t-x-c-t";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepaliveAction {
+    /// Nothing is due before [`KeepaliveDriver::next_deadline`].
+    Wait,
+    /// `interval` passed without inbound traffic; send a ping.
+    SendPing,
+    /// A ping went unanswered for `timeout`.
+    TimedOut,
+}
+
+/// Ping/timeout bookkeeping for one connection. Any inbound frame counts as
+/// traffic, not only ping replies.
+#[derive(Debug, Clone)]
+pub struct KeepaliveDriver {
+    keepalive: Keepalive,
+    uid: String,
+    last_traffic: Instant,
+    ping_sent_at: Option<Instant>,
+    pings_sent: u64,
+}
+
+impl KeepaliveDriver {
+    /// `uid` identifies this node; pings are sent as `{uid}-ping`.
+    #[must_use]
+    pub fn new(keepalive: Keepalive, uid: impl Into<String>) -> Self {
+        Self {
+            keepalive,
+            uid: uid.into(),
+            last_traffic: Instant::now(),
+            ping_sent_at: None,
+            pings_sent: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn keepalive(&self) -> &Keepalive {
+        &self.keepalive
+    }
+
+    #[must_use]
+    pub fn pings_sent(&self) -> u64 {
+        self.pings_sent
+    }
+
+    pub fn observe_traffic(&mut self, at: Instant) {
+        self.last_traffic = self.last_traffic.max(at);
+        self.ping_sent_at = None;
+    }
+
+    /// When the next ping is due, or when an outstanding ping times out.
+    #[must_use]
+    pub fn next_deadline(&self) -> Instant {
+        match self.ping_sent_at {
+            Some(sent_at) => sent_at + self.keepalive.timeout,
+            None => self.last_traffic + self.keepalive.interval,
+        }
+    }
+
+    #[must_use]
+    pub fn action_at(&self, now: Instant) -> KeepaliveAction {
+        if now < self.next_deadline() {
+            KeepaliveAction::Wait
+        } else if self.ping_sent_at.is_some() {
+            KeepaliveAction::TimedOut
+        } else {
+            KeepaliveAction::SendPing
+        }
+    }
+
+    /// Records a ping sent at `at` and returns the framed-ready payload: CoT
+    /// XML for XML streams, a TAK protocol v1 payload otherwise.
+    pub fn ping_payload(
+        &mut self,
+        framing: TransportFraming,
+        at: Instant,
+    ) -> Result<Vec<u8>, TransportComposeError> {
+        let xml = render_ping(&self.uid, TimestampUtc::now(), self.keepalive.interval);
+        let payload = match framing {
+            TransportFraming::XmlNewlineDelimited => xml.into_bytes(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+            | TransportFraming::TakProtocolMeshHeader => {
+                rustak_proto::encode_v1_payload(xml.as_bytes())?
+            }
+        };
+        self.ping_sent_at = Some(at);
+        self.pings_sent += 1;
+        Ok(payload)
+    }
+}
+
+/// Renders a TAK ping event for `uid`, stale `stale_after` from `now`.
+#[must_use]
+pub fn render_ping(uid: &str, now: TimestampUtc, stale_after: Duration) -> String {
+    let stale_nanos = i128::try_from(stale_after.as_nanos()).unwrap_or(i128::MAX);
+    let stale = TimestampUtc::from_unix_nanos(now.unix_nanos().saturating_add(stale_nanos));
+    let point = Position::new(0.0, 0.0)
+        .and_then(|point| point.with_hae(0.0))
+        .expect("the origin is a valid position");
+    let mut event = CotEvent::new(format!("{uid}-ping"), PING_COT_TYPE, now, stale, point);
+    event.how = Some("h-g-i-g-o".to_owned());
+    event.to_xml()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_core::time::TimestampUtc;
+    use rustak_wire::DowngradePolicy;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+    use tokio::time::Instant;
+
+    use super::{render_ping, KeepaliveAction, KeepaliveDriver};
+    use crate::{Keepalive, TransportComposeError, TransportConfig, TransportConnection};
+
+    const KEEPALIVE: Keepalive = Keepalive {
+        interval: Duration::from_secs(10),
+        timeout: Duration::from_secs(3),
+    };
+
+    #[test]
+    fn renders_tak_ping_event() {
+        let ping = render_ping(
+            "ANDROID-1",
+            TimestampUtc::from_unix_seconds(1_700_000_000),
+            Duration::from_secs(10),
+        );
+        assert!(ping.starts_with(
+            "<event version=\"2.0\" uid=\"ANDROID-1-ping\" type=\"t-x-c-t\" how=\"h-g-i-g-o\" time=\"2023-11-14T22:13:20.000Z\""
+        ));
+        assert!(ping.contains("stale=\"2023-11-14T22:13:30.000Z\""));
+
+        let hostile = render_ping(
+            "A\"&<B",
+            TimestampUtc::from_unix_seconds(1_700_000_000),
+            Duration::from_secs(10),
+        );
+        let event = rustak_core::CotEvent::from_xml(
+            &hostile,
+            &rustak_limits::Limits::conservative_defaults(),
+        )
+        .expect("ping stays well formed");
+        assert_eq!(event.uid, "A\"&<B-ping");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn driver_pings_after_idle_interval_then_times_out() {
+        let start = Instant::now();
+        let mut driver = KeepaliveDriver::new(KEEPALIVE, "node");
+        assert_eq!(
+            driver.action_at(start + Duration::from_secs(9)),
+            KeepaliveAction::Wait
+        );
+        assert_eq!(
+            driver.action_at(start + Duration::from_secs(10)),
+            KeepaliveAction::SendPing
+        );
+
+        driver
+            .ping_payload(
+                crate::TransportFraming::XmlNewlineDelimited,
+                start + Duration::from_secs(10),
+            )
+            .expect("ping");
+        assert_eq!(driver.next_deadline(), start + Duration::from_secs(13));
+        assert_eq!(
+            driver.action_at(start + Duration::from_secs(13)),
+            KeepaliveAction::TimedOut
+        );
+
+        driver.observe_traffic(start + Duration::from_secs(12));
+        assert_eq!(driver.next_deadline(), start + Duration::from_secs(22));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connection_pings_idle_peer_and_reports_timeout() {
+        let (client, mut server) = duplex(4096);
+        let config = TransportConfig {
+            keepalive: Some(KEEPALIVE),
+            ..TransportConfig::default()
+        };
+        let mut connection =
+            TransportConnection::new(client, &config, DowngradePolicy::FailOpen).expect("compose");
+        let mut driver = KeepaliveDriver::new(KEEPALIVE, "node");
+
+        server
+            .write_all(b"<event uid=\"a\"/>\n")
+            .await
+            .expect("write");
+        let frame = connection
+            .recv_frame_with_keepalive(&mut driver)
+            .await
+            .expect("frame");
+        assert_eq!(frame, b"<event uid=\"a\"/>");
+
+        let error = connection
+            .recv_frame_with_keepalive(&mut driver)
+            .await
+            .expect_err("silent peer");
+        assert!(matches!(
+            error,
+            TransportComposeError::KeepaliveTimeout { timeout } if timeout == KEEPALIVE.timeout
+        ));
+        assert_eq!(driver.pings_sent(), 1);
+
+        let mut sent = vec![0_u8; 512];
+        let read = server.read(&mut sent).await.expect("read ping");
+        let ping = std::str::from_utf8(&sent[..read]).expect("utf8");
+        assert!(ping.contains("uid=\"node-ping\" type=\"t-x-c-t\""));
+        assert!(ping.ends_with("</event>\n"));
+    }
+}
@@ -1,28 +1,79 @@
+use std::io::IoSlice;
 use std::net::{Ipv4Addr, SocketAddr};
-use std::time::Duration;
-
-use bytes::Bytes;
-use rustak_io::{MessageEnvelope, MessageSink, MessageSource};
-use rustak_limits::{Limits, LimitsError};
+use std::pin::Pin;
+use std::time::{Duration, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::Stream;
+use rustak_core::TimestampUtc;
+use rustak_io::{IoError, MessageEnvelope, MessageSink, MessageSource};
+use rustak_limits::{CodedError, ErrorCode, Limits, LimitsError};
 use rustak_net::{
-    read_delimited_frame, read_length_prefixed_frame, write_delimited_frame,
+    read_length_prefixed_frame_into, read_scan_limited_frame_into, write_delimited_frame,
     write_length_prefixed_frame, DelimiterFrameError, LengthPrefixKind, LengthPrefixedError,
 };
+use rustak_wire::negotiation::events::TakControlMessage;
 use rustak_wire::{
-    DowngradePolicy, NegotiationEvent, NegotiationState, Negotiator, TakProtocolVersion, WireFormat,
+    DowngradePolicy, MeshFrameCodec, MeshFrameError, NegotiationConfig, NegotiationEvent,
+    NegotiationEventKind, NegotiationReason, NegotiationState, NegotiationStream, Negotiator,
+    StreamFrame, TakProtocolVersion, WireFormat, TAK_MESH_HEADER_LEN,
 };
 use thiserror::Error;
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{timeout_at, Instant};
 
 pub mod config;
+pub mod events;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod keepalive;
+pub mod manager;
 pub mod queue;
+pub mod quota;
+pub mod recv;
+pub mod socket;
+pub mod split;
+pub mod tap;
+#[cfg(feature = "tls")]
+pub mod tls;
 pub mod udp;
 
-pub use config::{SendQueueConfig, SendQueueMode};
+pub use config::{
+    ClassBudget, MessageClass, QuotaAction, QuotaLimit, QuotaWindow, SendQueueConfig,
+    SendQueueMode, TrafficQuotaConfig, TrafficShapingConfig, WriteBatchConfig,
+};
+pub use events::{TransportEvent, TransportEvents, DEFAULT_EVENT_CAPACITY};
+#[cfg(feature = "fault-injection")]
+pub use fault::{FaultController, FaultInjectingIo, FaultSnapshot};
+pub use keepalive::{render_ping, KeepaliveAction, KeepaliveDriver, PING_COT_TYPE};
+pub use manager::{
+    ConnectionEvent, ConnectionManager, ConnectionManagerError, ConnectionState, ManagedStream,
+    ReconnectBackoff,
+};
+#[cfg(feature = "tower")]
+pub use queue::SendQueueService;
 pub use queue::{
-    OutboundSendQueue, QueueEnqueueReport, QueuePriority, SendQueueClassifier, SendQueueError,
+    ClassTrafficMetrics, CotPriorityClassifier, DrainStats, OutboundSendQueue, QueueClassification,
+    QueueDriver, QueueEnqueueReport, QueueHandle, QueuePriority, SendQueueClassifier,
+    SendQueueError, CHAT_COT_TYPE_PREFIX, EMERGENCY_COT_TYPE_PREFIX, LARGE_DETAIL_BYTES,
+};
+pub use quota::{QuotaDirection, QuotaMeter, QuotaUsage};
+pub use recv::{RecvOverflow, RecvPipeline, RecvStats};
+pub use socket::{
+    apply_tcp_keepalive, bind_udp_socket, bind_udp_socket_with_joins, effective_bind_addr,
+    tcp_link_stats, MulticastJoin, MulticastMembership, TcpLinkSampler, TcpLinkStats,
+    UdpSocketOptions, TCP_KEEPALIVE_RETRIES,
+};
+pub use split::{TransportConnectionReader, TransportConnectionWriter};
+pub use tap::{FrameTap, TapDirection};
+#[cfg(feature = "tls")]
+pub use tls::{provider_available, TlsClientConfig, TlsConnector, TlsError};
+pub use udp::{
+    apply_mtu_policy, UdpBatchConfig, UdpChunkReassembler, UdpPolicyError, UdpSendDecision,
+    UdpTransport, UdpTransportError, CHUNK_HEADER_BYTES, MAX_UDP_DATAGRAM_BYTES,
+    TRUNCATED_DETAIL_MARKER, UDP_CHUNK_IDLE_TIMEOUT, UDP_TRANSPORT_PENDING_CHUNKS,
 };
-pub use udp::{apply_mtu_policy, UdpPolicyError, UdpSendDecision};
 
 pub type TransportEnvelope<T> = MessageEnvelope<T>;
 pub type TransportSink<T> = dyn MessageSink<T>;
@@ -39,6 +90,20 @@ const XML_FRAME_DELIMITER: &[u8] = b"\n";
 pub enum TransportFraming {
     XmlNewlineDelimited,
     TakProtocolU32LengthPrefixed,
+    /// `0xBF <version> 0xBF` ahead of each payload, one frame per datagram.
+    TakProtocolMeshHeader,
+}
+
+impl TransportFraming {
+    /// Framing for `config`: TAK protocol v1 over [`Protocol::Udp`] uses the
+    /// mesh header, streams use [`TransportFraming::from`] the wire format.
+    #[must_use]
+    pub fn for_config(config: &TransportConfig) -> Self {
+        match (&config.protocol, config.wire_format) {
+            (Protocol::Udp { .. }, WireFormat::TakProtocolV1) => Self::TakProtocolMeshHeader,
+            (_, wire_format) => Self::from(wire_format),
+        }
+    }
 }
 
 impl From<WireFormat> for TransportFraming {
@@ -61,6 +126,8 @@ pub struct TransportConfig {
     pub reconnect_policy: ReconnectPolicy,
     pub mtu_safety: Option<MtuSafety>,
     pub send_queue: SendQueueConfig,
+    /// Byte quotas for metered links; `None` leaves traffic unmetered.
+    pub quota: Option<TrafficQuotaConfig>,
 }
 
 impl Default for TransportConfig {
@@ -80,13 +147,15 @@ impl Default for TransportConfig {
             reconnect_policy: ReconnectPolicy::default(),
             mtu_safety: Some(MtuSafety {
                 max_udp_payload_bytes: 1_200,
-                drop_oversize: true,
+                oversize: OversizePolicy::Drop,
             }),
             send_queue: SendQueueConfig {
                 max_messages: limits.max_queue_messages,
                 max_bytes: limits.max_queue_bytes,
                 mode: SendQueueMode::CoalesceLatestByUid,
+                shaping: None,
             },
+            quota: None,
             limits,
         }
     }
@@ -112,6 +181,9 @@ impl TransportConfig {
 
         self.reconnect_policy.validate()?;
         self.send_queue.validate(&self.limits)?;
+        if let Some(quota) = &self.quota {
+            quota.validate()?;
+        }
 
         if let Some(mtu_safety) = &self.mtu_safety {
             if mtu_safety.max_udp_payload_bytes == 0 {
@@ -210,7 +282,27 @@ impl ReconnectPolicy {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MtuSafety {
     pub max_udp_payload_bytes: usize,
-    pub drop_oversize: bool,
+    pub oversize: OversizePolicy,
+}
+
+/// What to do with an outbound UDP payload larger than
+/// `MtuSafety::max_udp_payload_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizePolicy {
+    /// Drop the message and report it.
+    Drop,
+    /// Best-effort raw split into MTU-sized datagrams with no framing;
+    /// receivers cannot reassemble, so this only suits lossy diagnostics.
+    Split,
+    /// Replace the CoT `<detail>` body with a truncation marker. Payloads that
+    /// are not CoT XML, or still do not fit, are dropped.
+    TruncateDetail,
+    /// Hand the message back so the caller can send it over its TCP/TLS
+    /// stream; callers without a stream path should treat it as a drop.
+    StreamFallback,
+    /// Application-level chunking with a reassembly header for recognized
+    /// payload types (CoT XML). Anything else is dropped.
+    Chunk,
 }
 
 #[derive(Debug, Error, PartialEq)]
@@ -245,12 +337,36 @@ pub enum TransportConfigError {
         max_frame_bytes: usize,
     },
 
+    #[error("write_batch.max_batch_frames must be > 0")]
+    ZeroWriteBatchFrames,
+
     #[error("send_queue.max_messages must be > 0")]
     ZeroSendQueueMessages,
 
     #[error("send_queue.max_bytes must be > 0")]
     ZeroSendQueueBytes,
 
+    #[error("send_queue.shaping.{class}.bytes_per_second must be > 0")]
+    ZeroShapingRate { class: &'static str },
+
+    #[error("send_queue.shaping.{class}.burst_bytes must be > 0")]
+    ZeroShapingBurst { class: &'static str },
+
+    #[error("quota must limit at least one window")]
+    EmptyQuota,
+
+    #[error("quota.{window} must set send_bytes or receive_bytes")]
+    EmptyQuotaWindow { window: &'static str },
+
+    #[error("quota.{window}.{field} must be > 0")]
+    ZeroQuotaBytes {
+        window: &'static str,
+        field: &'static str,
+    },
+
+    #[error("quota.action.bytes_per_second must be > 0")]
+    ZeroQuotaThrottleRate,
+
     #[error(
         "send_queue.max_messages ({max_messages}) cannot exceed limits.max_queue_messages ({limits_max_messages})"
     )]
@@ -268,6 +384,31 @@ pub enum TransportConfigError {
     },
 }
 
+impl CodedError for TransportConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidLimits(error) => error.code(),
+            Self::ZeroDuration { .. } => ErrorCode::new("TRANSPORT", 1),
+            Self::KeepaliveTimeoutExceedsInterval { .. } => ErrorCode::new("TRANSPORT", 2),
+            Self::BackoffFactorTooSmall { .. } => ErrorCode::new("TRANSPORT", 3),
+            Self::JitterOutOfRange { .. } => ErrorCode::new("TRANSPORT", 4),
+            Self::ZeroUdpPayloadLimit => ErrorCode::new("TRANSPORT", 5),
+            Self::MtuPayloadExceedsFrame { .. } => ErrorCode::new("TRANSPORT", 6),
+            Self::ZeroWriteBatchFrames => ErrorCode::new("TRANSPORT", 7),
+            Self::ZeroSendQueueMessages => ErrorCode::new("TRANSPORT", 8),
+            Self::ZeroSendQueueBytes => ErrorCode::new("TRANSPORT", 9),
+            Self::ZeroShapingRate { .. } => ErrorCode::new("TRANSPORT", 10),
+            Self::ZeroShapingBurst { .. } => ErrorCode::new("TRANSPORT", 11),
+            Self::EmptyQuota => ErrorCode::new("TRANSPORT", 12),
+            Self::EmptyQuotaWindow { .. } => ErrorCode::new("TRANSPORT", 13),
+            Self::ZeroQuotaBytes { .. } => ErrorCode::new("TRANSPORT", 14),
+            Self::ZeroQuotaThrottleRate => ErrorCode::new("TRANSPORT", 15),
+            Self::SendQueueMessagesExceedLimits { .. } => ErrorCode::new("TRANSPORT", 16),
+            Self::SendQueueBytesExceedLimits { .. } => ErrorCode::new("TRANSPORT", 17),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum TransportComposeError {
     #[error(transparent)]
@@ -278,6 +419,48 @@ pub enum TransportComposeError {
 
     #[error(transparent)]
     Delimited(#[from] DelimiterFrameError),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Proto(#[from] rustak_proto::ProtoError),
+
+    #[error(transparent)]
+    Mesh(#[from] MeshFrameError),
+
+    #[error("peer sent no traffic within {timeout:?} of a keepalive ping")]
+    KeepaliveTimeout { timeout: Duration },
+
+    #[error("{direction} quota for this {window} is used up; resets in {resets_in:?}")]
+    QuotaExceeded {
+        window: QuotaWindow,
+        direction: QuotaDirection,
+        resets_in: Duration,
+    },
+
+    #[error("protocol negotiation terminated: {reason:?}")]
+    NegotiationTerminated { reason: NegotiationReason },
+
+    #[error("receive pipeline stopped: {reason}")]
+    ReaderTaskFailed { reason: String },
+}
+
+impl CodedError for TransportComposeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidConfig(error) => error.code(),
+            Self::LengthPrefixed(error) => error.code(),
+            Self::Delimited(error) => error.code(),
+            Self::Io(_) => ErrorCode::new("TRANSPORT", 101),
+            Self::Proto(error) => error.code(),
+            Self::Mesh(error) => error.code(),
+            Self::KeepaliveTimeout { .. } => ErrorCode::new("TRANSPORT", 102),
+            Self::QuotaExceeded { .. } => ErrorCode::new("TRANSPORT", 103),
+            Self::NegotiationTerminated { .. } => ErrorCode::new("TRANSPORT", 104),
+            Self::ReaderTaskFailed { .. } => ErrorCode::new("TRANSPORT", 105),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -285,6 +468,15 @@ pub struct TransportSender<W> {
     writer: W,
     framing: TransportFraming,
     max_frame_bytes: usize,
+    batch: Option<WriteBatch>,
+}
+
+#[derive(Debug)]
+struct WriteBatch {
+    config: WriteBatchConfig,
+    buffer: Vec<u8>,
+    frames: usize,
+    oldest_pending: Option<Instant>,
 }
 
 impl<W> TransportSender<W> {
@@ -294,14 +486,45 @@ impl<W> TransportSender<W> {
             writer,
             framing,
             max_frame_bytes,
+            batch: None,
         })
     }
 
+    /// Groups frames into fewer, larger writes. Batched frames only reach
+    /// the writer on flush, so callers must keep driving
+    /// [`TransportSender::flush_when_due`] to honour the latency bound.
+    pub fn with_write_batching(
+        mut self,
+        config: WriteBatchConfig,
+    ) -> Result<Self, TransportComposeError> {
+        config.validate()?;
+        self.batch = Some(WriteBatch {
+            config,
+            buffer: Vec::new(),
+            frames: 0,
+            oldest_pending: None,
+        });
+        Ok(self)
+    }
+
     #[must_use]
     pub fn framing(&self) -> TransportFraming {
         self.framing
     }
 
+    #[must_use]
+    pub fn pending_frames(&self) -> usize {
+        self.batch.as_ref().map_or(0, |batch| batch.frames)
+    }
+
+    /// When the oldest batched frame reaches its latency bound.
+    #[must_use]
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        let batch = self.batch.as_ref()?;
+        Some(batch.oldest_pending? + batch.config.flush_interval)
+    }
+
+    /// Discards any batched frames that were never flushed.
     #[must_use]
     pub fn into_inner(self) -> W {
         self.writer
@@ -313,20 +536,104 @@ where
     W: AsyncWrite + Unpin,
 {
     pub async fn send_frame(&mut self, payload: &[u8]) -> Result<(), TransportComposeError> {
+        let Some(batch) = self.batch.as_mut() else {
+            return send_frame_with_framing(
+                &mut self.writer,
+                self.framing,
+                payload,
+                self.max_frame_bytes,
+            )
+            .await;
+        };
+
         send_frame_with_framing(
-            &mut self.writer,
+            &mut batch.buffer,
             self.framing,
             payload,
             self.max_frame_bytes,
         )
-        .await
+        .await?;
+        batch.frames += 1;
+        let now = Instant::now();
+        let oldest = *batch.oldest_pending.get_or_insert(now);
+        if batch.frames >= batch.config.max_batch_frames
+            || now.duration_since(oldest) >= batch.config.flush_interval
+        {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Sends each payload as its own frame in as few writes as the writer
+    /// allows: one vectored write of every prefix and payload, or a single
+    /// contiguous write when the writer has no vectored support. Nothing is
+    /// written if any payload is over the frame limit. Under write batching
+    /// the frames join the batch instead, and mesh frames, which are encoded
+    /// whole, go out one write each.
+    pub async fn send_frames<P>(&mut self, payloads: &[P]) -> Result<(), TransportComposeError>
+    where
+        P: AsRef<[u8]>,
+    {
+        if self.batch.is_some() || self.framing == TransportFraming::TakProtocolMeshHeader {
+            for payload in payloads {
+                self.send_frame(payload.as_ref()).await?;
+            }
+            return Ok(());
+        }
+
+        let prefixes = payloads
+            .iter()
+            .map(|payload| {
+                stream_frame_prefix(self.framing, payload.as_ref(), self.max_frame_bytes)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut slices = Vec::with_capacity(payloads.len() * 2);
+        for (prefix, payload) in prefixes.iter().zip(payloads) {
+            if let Some(prefix) = prefix {
+                slices.push(IoSlice::new(prefix));
+            }
+            slices.push(IoSlice::new(payload.as_ref()));
+            if self.framing == TransportFraming::XmlNewlineDelimited {
+                slices.push(IoSlice::new(XML_FRAME_DELIMITER));
+            }
+        }
+        write_all_vectored(&mut self.writer, &mut slices).await?;
+        Ok(())
     }
 
-    pub async fn send_envelope(
+    pub async fn send_envelope<M>(
         &mut self,
-        envelope: TransportEnvelope<Vec<u8>>,
-    ) -> Result<(), TransportComposeError> {
-        self.send_frame(&envelope.message).await
+        envelope: TransportEnvelope<M>,
+    ) -> Result<(), TransportComposeError>
+    where
+        M: AsRef<[u8]>,
+    {
+        self.send_frame(envelope.message.as_ref()).await
+    }
+
+    /// Writes any batched frames in a single write and flushes the writer.
+    pub async fn flush(&mut self) -> Result<(), TransportComposeError> {
+        if let Some(batch) = self.batch.as_mut() {
+            if !batch.buffer.is_empty() {
+                self.writer.write_all(&batch.buffer).await?;
+                batch.buffer.clear();
+            }
+            batch.frames = 0;
+            batch.oldest_pending = None;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Waits for [`TransportSender::flush_deadline`] and flushes. Never
+    /// completes while nothing is pending, so it can sit in a `select!`
+    /// next to the send path.
+    pub async fn flush_when_due(&mut self) -> Result<(), TransportComposeError> {
+        let Some(deadline) = self.flush_deadline() else {
+            return std::future::pending().await;
+        };
+        tokio::time::sleep_until(deadline).await;
+        self.flush().await
     }
 }
 
@@ -335,6 +642,8 @@ pub struct TransportReceiver<R> {
     reader: R,
     framing: TransportFraming,
     max_frame_bytes: usize,
+    max_xml_scan_bytes: usize,
+    tap: Option<FrameTap>,
 }
 
 impl<R> TransportReceiver<R> {
@@ -344,9 +653,18 @@ impl<R> TransportReceiver<R> {
             reader,
             framing,
             max_frame_bytes,
+            max_xml_scan_bytes: config.limits.max_xml_scan_bytes,
+            tap: None,
         })
     }
 
+    /// Copies every received frame into `tap` before it is decoded.
+    #[must_use]
+    pub fn with_frame_tap(mut self, tap: FrameTap) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
     #[must_use]
     pub fn framing(&self) -> TransportFraming {
         self.framing
@@ -363,15 +681,67 @@ where
     R: AsyncRead + Unpin,
 {
     pub async fn recv_frame(&mut self) -> Result<Vec<u8>, TransportComposeError> {
-        recv_frame_with_framing(&mut self.reader, self.framing, self.max_frame_bytes).await
+        let frame = recv_frame_with_framing(
+            &mut self.reader,
+            self.framing,
+            self.max_frame_bytes,
+            self.max_xml_scan_bytes,
+        )
+        .await?;
+        tap::capture(self.tap.as_ref(), TapDirection::Inbound, &frame).await;
+        Ok(frame)
+    }
+
+    /// Appends the next frame to `buf` and returns its length, so a relay
+    /// can reuse one buffer and hand frames on with
+    /// `buf.split_to(len).freeze()`. On error `buf` is left as it was.
+    pub async fn recv_frame_into(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<usize, TransportComposeError> {
+        let len = recv_frame_into_with_framing(
+            &mut self.reader,
+            self.framing,
+            self.max_frame_bytes,
+            self.max_xml_scan_bytes,
+            buf,
+        )
+        .await?;
+        tap::capture(self.tap.as_ref(), TapDirection::Inbound, tail(buf, len)).await;
+        Ok(len)
     }
 
+    /// The message and raw frame share one buffer.
     pub async fn recv_envelope(
         &mut self,
-    ) -> Result<TransportEnvelope<Vec<u8>>, TransportComposeError> {
-        let frame = self.recv_frame().await?;
-        let raw_frame = Bytes::copy_from_slice(&frame);
-        Ok(TransportEnvelope::new(frame).with_raw_frame(raw_frame))
+    ) -> Result<TransportEnvelope<Bytes>, TransportComposeError> {
+        Ok(frame_envelope(self.recv_frame().await?))
+    }
+}
+
+/// Result of [`TransportConnection::negotiate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiationOutcome {
+    /// The event that ended the attempt: `UpgradeAccepted`,
+    /// `FallbackToLegacy` or `Terminated`.
+    pub event: NegotiationEvent,
+    pub state: NegotiationState,
+    /// Legacy XML events the server sent before answering, in order.
+    pub early_events: Vec<Vec<u8>>,
+}
+
+impl NegotiationOutcome {
+    /// The wire format the connection now frames with; `None` once the
+    /// negotiation terminated.
+    #[must_use]
+    pub fn wire_format(&self) -> Option<WireFormat> {
+        match self.state {
+            NegotiationState::Upgraded(_) => Some(WireFormat::TakProtocolV1),
+            NegotiationState::LegacyXml | NegotiationState::AwaitingResponse => {
+                Some(WireFormat::Xml)
+            }
+            NegotiationState::Terminated { .. } => None,
+        }
     }
 }
 
@@ -380,9 +750,16 @@ pub struct TransportConnection<IO> {
     io: IO,
     framing: TransportFraming,
     max_frame_bytes: usize,
+    limits: Limits,
     negotiator: Negotiator,
+    quota: Option<QuotaMeter>,
+    events: Option<TransportEvents>,
+    tap: Option<FrameTap>,
 }
 
+/// Sender uid on the `TakRequest` sent by [`TransportConnection::negotiate`].
+const NEGOTIATION_UID: &str = "rustak-negotiation";
+
 impl<IO> TransportConnection<IO> {
     pub fn new(
         io: IO,
@@ -394,10 +771,48 @@ impl<IO> TransportConnection<IO> {
             io,
             framing,
             max_frame_bytes,
+            limits: config.limits.clone(),
             negotiator: Negotiator::new(downgrade_policy),
+            quota: config.quota.clone().map(QuotaMeter::new),
+            events: None,
+            tap: None,
         })
     }
 
+    /// Publishes [`TransportEvent::NegotiationUpgraded`] and the downgrade
+    /// policy's decode-failure decisions on `events`.
+    #[must_use]
+    pub fn with_events(mut self, events: TransportEvents) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Counts traffic on `meter` instead of a fresh one, so usage carries
+    /// over from earlier connections.
+    #[must_use]
+    pub fn with_quota_meter(mut self, meter: QuotaMeter) -> Self {
+        self.quota = Some(meter);
+        self
+    }
+
+    #[must_use]
+    pub fn quota_meter(&self) -> Option<&QuotaMeter> {
+        self.quota.as_ref()
+    }
+
+    /// Copies every frame read or written, negotiation and keepalive
+    /// frames included, into `tap`'s sinks (see [`tap`]).
+    #[must_use]
+    pub fn with_frame_tap(mut self, tap: FrameTap) -> Self {
+        self.tap = Some(tap);
+        self
+    }
+
+    #[must_use]
+    pub fn frame_tap(&self) -> Option<&FrameTap> {
+        self.tap.as_ref()
+    }
+
     #[must_use]
     pub fn framing(&self) -> TransportFraming {
         self.framing
@@ -432,10 +847,166 @@ impl<IO> TransportConnection<IO> {
         self.negotiator.observe_policy_denied()
     }
 
+    /// Decodes a received frame under the current framing. On TAK protocol
+    /// streams every result feeds the negotiator's decode-failure streak;
+    /// once the downgrade policy falls back to legacy XML, later frames are
+    /// read newline-delimited, and once it terminates the stream every frame
+    /// fails with [`TransportComposeError::NegotiationTerminated`]. Either
+    /// decision is published on the connection's events.
+    pub fn decode_frame_payload(&mut self, frame: &[u8]) -> Result<Vec<u8>, TransportComposeError> {
+        decode_tracked_payload(
+            &mut self.framing,
+            &mut self.negotiator,
+            self.events.as_ref(),
+            frame,
+        )
+    }
+
+    /// Like [`Self::decode_frame_payload`], but a legacy XML frame is
+    /// returned as a shared handle instead of a copy.
+    pub fn decode_frame_bytes(&mut self, frame: &Bytes) -> Result<Bytes, TransportComposeError> {
+        decode_tracked_bytes(
+            &mut self.framing,
+            &mut self.negotiator,
+            self.events.as_ref(),
+            frame,
+        )
+    }
+
+    pub fn observe_decode_failure(&mut self) -> NegotiationEvent {
+        observe_tracked_decode_failure(
+            &mut self.framing,
+            &mut self.negotiator,
+            self.events.as_ref(),
+        )
+    }
+
+    #[must_use]
+    pub fn get_ref(&self) -> &IO {
+        &self.io
+    }
+
     #[must_use]
     pub fn into_inner(self) -> IO {
         self.io
     }
+
+    fn check_quota(&self, direction: QuotaDirection) -> Result<(), TransportComposeError> {
+        check_quota(self.quota.as_ref(), direction)
+    }
+
+    async fn charge_quota(&self, direction: QuotaDirection, payload_len: usize) {
+        charge_quota(self.quota.as_ref(), self.framing, direction, payload_len).await;
+    }
+}
+
+/// Decodes `frame` under `framing`, feeding the result to `negotiator`'s
+/// decode-failure streak and falling back to legacy XML framing when the
+/// downgrade policy says so. Once the policy has terminated the stream,
+/// every frame fails with [`TransportComposeError::NegotiationTerminated`].
+fn decode_tracked_payload(
+    framing: &mut TransportFraming,
+    negotiator: &mut Negotiator,
+    events: Option<&TransportEvents>,
+    frame: &[u8],
+) -> Result<Vec<u8>, TransportComposeError> {
+    ensure_not_terminated(negotiator)?;
+    if *framing == TransportFraming::XmlNewlineDelimited {
+        return Ok(frame.to_vec());
+    }
+    match rustak_proto::decode_v1_payload(frame) {
+        Ok(payload) => {
+            negotiator.observe_decode_success();
+            Ok(payload)
+        }
+        Err(error) => {
+            observe_tracked_decode_failure(framing, negotiator, events);
+            ensure_not_terminated(negotiator)?;
+            Err(error.into())
+        }
+    }
+}
+
+/// Like [`decode_tracked_payload`], but shares `frame` instead of copying
+/// it when the stream is legacy XML.
+fn decode_tracked_bytes(
+    framing: &mut TransportFraming,
+    negotiator: &mut Negotiator,
+    events: Option<&TransportEvents>,
+    frame: &Bytes,
+) -> Result<Bytes, TransportComposeError> {
+    ensure_not_terminated(negotiator)?;
+    if *framing == TransportFraming::XmlNewlineDelimited {
+        return Ok(frame.clone());
+    }
+    decode_tracked_payload(framing, negotiator, events, frame).map(Bytes::from)
+}
+
+/// Counts a decode failure, switching to legacy XML framing on fallback
+/// and publishing the downgrade policy's decision on `events`.
+fn observe_tracked_decode_failure(
+    framing: &mut TransportFraming,
+    negotiator: &mut Negotiator,
+    events: Option<&TransportEvents>,
+) -> NegotiationEvent {
+    let event = negotiator.observe_decode_failure();
+    let published = match (event.kind, event.reason) {
+        (NegotiationEventKind::FallbackToLegacy, Some(reason)) => {
+            *framing = TransportFraming::XmlNewlineDelimited;
+            Some(TransportEvent::NegotiationFellBack { reason })
+        }
+        (NegotiationEventKind::Terminated, Some(reason)) => {
+            Some(TransportEvent::NegotiationTerminated { reason })
+        }
+        _ => None,
+    };
+    if let (Some(published), Some(events)) = (published, events) {
+        events.publish(published);
+    }
+    event
+}
+
+fn ensure_not_terminated(negotiator: &Negotiator) -> Result<(), TransportComposeError> {
+    match negotiator.state() {
+        NegotiationState::Terminated { reason } => {
+            Err(TransportComposeError::NegotiationTerminated { reason })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fails once `direction` is used up under [`QuotaAction::Disconnect`].
+fn check_quota(
+    quota: Option<&QuotaMeter>,
+    direction: QuotaDirection,
+) -> Result<(), TransportComposeError> {
+    let Some(usage) = quota.and_then(|meter| meter.blocking_at(direction, SystemTime::now()))
+    else {
+        return Ok(());
+    };
+    Err(TransportComposeError::QuotaExceeded {
+        window: usage.window,
+        direction,
+        resets_in: usage.resets_in,
+    })
+}
+
+/// Counts a frame of `payload_len` bytes plus framing overhead, pausing if
+/// the quota asks for throttling.
+async fn charge_quota(
+    quota: Option<&QuotaMeter>,
+    framing: TransportFraming,
+    direction: QuotaDirection,
+    payload_len: usize,
+) {
+    let Some(meter) = quota else {
+        return;
+    };
+    let bytes = u64::try_from(framed_len(framing, payload_len)).unwrap_or(u64::MAX);
+    let pause = meter.record(direction, bytes);
+    if !pause.is_zero() {
+        tokio::time::sleep(pause).await;
+    }
 }
 
 impl<IO> TransportConnection<IO>
@@ -443,35 +1014,407 @@ where
     IO: AsyncRead + AsyncWrite + Unpin,
 {
     pub async fn send_frame(&mut self, payload: &[u8]) -> Result<(), TransportComposeError> {
-        send_frame_with_framing(&mut self.io, self.framing, payload, self.max_frame_bytes).await
+        self.check_quota(QuotaDirection::Send)?;
+        send_frame_with_framing(&mut self.io, self.framing, payload, self.max_frame_bytes).await?;
+        self.charge_quota(QuotaDirection::Send, payload.len()).await;
+        tap::capture(self.tap.as_ref(), TapDirection::Outbound, payload).await;
+        Ok(())
     }
 
-    pub async fn send_envelope(
+    pub async fn send_envelope<M>(
         &mut self,
-        envelope: TransportEnvelope<Vec<u8>>,
-    ) -> Result<(), TransportComposeError> {
-        self.send_frame(&envelope.message).await
+        envelope: TransportEnvelope<M>,
+    ) -> Result<(), TransportComposeError>
+    where
+        M: AsRef<[u8]>,
+    {
+        self.send_frame(envelope.message.as_ref()).await
     }
 
     pub async fn recv_frame(&mut self) -> Result<Vec<u8>, TransportComposeError> {
-        recv_frame_with_framing(&mut self.io, self.framing, self.max_frame_bytes).await
+        self.check_quota(QuotaDirection::Receive)?;
+        let frame = recv_frame_with_framing(
+            &mut self.io,
+            self.framing,
+            self.max_frame_bytes,
+            self.limits.max_xml_scan_bytes,
+        )
+        .await?;
+        self.charge_quota(QuotaDirection::Receive, frame.len())
+            .await;
+        tap::capture(self.tap.as_ref(), TapDirection::Inbound, &frame).await;
+        Ok(frame)
     }
 
+    /// Like [`TransportReceiver::recv_frame_into`].
+    pub async fn recv_frame_into(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<usize, TransportComposeError> {
+        self.check_quota(QuotaDirection::Receive)?;
+        let len = recv_frame_into_with_framing(
+            &mut self.io,
+            self.framing,
+            self.max_frame_bytes,
+            self.limits.max_xml_scan_bytes,
+            buf,
+        )
+        .await?;
+        self.charge_quota(QuotaDirection::Receive, len).await;
+        tap::capture(self.tap.as_ref(), TapDirection::Inbound, tail(buf, len)).await;
+        Ok(len)
+    }
+
+    /// Runs the stream-mode TAK protocol upgrade. The stream starts in
+    /// legacy XML and frames are read until the server's `TakProtocolSupport`
+    /// (`t-x-takp-v`) offers `version`; a `TakRequest` (`t-x-takp-q`) for it
+    /// is sent back, and an accepting `TakResponse` (`t-x-takp-r`) switches
+    /// the connection to TAK protocol framing. A timeout, refusal or
+    /// malformed control message is resolved by the downgrade policy.
+    ///
+    /// Only the wait for a frame's first byte is raced against the deadline;
+    /// a frame that starts but does not finish in time fails with
+    /// [`std::io::ErrorKind::TimedOut`].
+    pub async fn negotiate(
+        &mut self,
+        version: TakProtocolVersion,
+        timeout: Duration,
+    ) -> Result<NegotiationOutcome, TransportComposeError> {
+        let deadline = Instant::now() + timeout;
+        self.framing = TransportFraming::XmlNewlineDelimited;
+
+        let mut requested = false;
+        let mut negotiation = NegotiationStream::from_negotiator(self.negotiator);
+        let mut event = negotiation.begin_upgrade_attempt();
+        let mut early_events = Vec::new();
+        while negotiation.state() == NegotiationState::AwaitingResponse {
+            let mut first = [0_u8; 1];
+            let read = match timeout_at(deadline, self.io.read(&mut first)).await {
+                Ok(read) => read?,
+                Err(_elapsed) => {
+                    event = negotiation.negotiator_mut().observe_timeout();
+                    break;
+                }
+            };
+            if read == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            let mut reader = (&first[..]).chain(&mut self.io);
+            let frame = timeout_at(
+                deadline,
+                recv_frame_with_framing(
+                    &mut reader,
+                    self.framing,
+                    self.max_frame_bytes,
+                    self.limits.max_xml_scan_bytes,
+                ),
+            )
+            .await
+            .map_err(|_elapsed| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+            self.charge_quota(QuotaDirection::Receive, frame.len())
+                .await;
+            tap::capture(self.tap.as_ref(), TapDirection::Inbound, &frame).await;
+            let data = match negotiation.observe_frame(&frame) {
+                StreamFrame::Data(data) => data,
+                StreamFrame::Control(control) => {
+                    event = control;
+                    continue;
+                }
+                StreamFrame::Ignored(_) => continue,
+            };
+            let message = match TakControlMessage::parse(data, &self.limits) {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    early_events.push(data.to_vec());
+                    continue;
+                }
+                Err(_) => {
+                    event = negotiation.negotiator_mut().observe_malformed_control();
+                    continue;
+                }
+            };
+            event = negotiation
+                .negotiator_mut()
+                .observe_tak_control(message, version);
+            if !requested
+                && negotiation.state() == NegotiationState::AwaitingResponse
+                && matches!(message, TakControlMessage::ProtocolSupport { .. })
+            {
+                let request = TakControlMessage::Request { version }
+                    .encode(NEGOTIATION_UID, TimestampUtc::now());
+                self.send_frame(&request).await?;
+                self.io.flush().await?;
+                requested = true;
+            }
+        }
+
+        self.negotiator = *negotiation.negotiator_mut();
+        let state = self.negotiator.state();
+        if let NegotiationState::Upgraded(version) = state {
+            self.framing = TransportFraming::TakProtocolU32LengthPrefixed;
+            if let Some(events) = &self.events {
+                events.publish(TransportEvent::NegotiationUpgraded { version });
+            }
+        }
+        Ok(NegotiationOutcome {
+            event,
+            state,
+            early_events,
+        })
+    }
+
+    /// Runs [`Self::negotiate`] under `config`: the negotiator is reset to
+    /// its downgrade policy and decode-failure limit, and the attempt is
+    /// timed out after `streaming_timeout` on the tokio clock. Resolves once
+    /// the connection frames with [`NegotiationOutcome::wire_format`], TAK
+    /// protocol after an upgrade and legacy XML after a fallback; a
+    /// terminated negotiation is
+    /// [`TransportComposeError::NegotiationTerminated`].
+    pub async fn negotiate_with_config(
+        &mut self,
+        version: TakProtocolVersion,
+        config: &NegotiationConfig,
+    ) -> Result<NegotiationOutcome, TransportComposeError> {
+        self.negotiator = config.negotiator();
+        let outcome = self.negotiate(version, config.streaming_timeout).await?;
+        if let NegotiationState::Terminated { reason } = outcome.state {
+            return Err(TransportComposeError::NegotiationTerminated { reason });
+        }
+        Ok(outcome)
+    }
+
+    /// Receives the next frame while `driver` pings an idle peer. Returns
+    /// [`TransportComposeError::KeepaliveTimeout`] once a ping goes
+    /// unanswered; the connection should then be dropped and redialed (see
+    /// [`ConnectionManager::reconnect`]).
+    ///
+    /// Only the wait for a frame's first byte is raced against the keepalive
+    /// deadline, because the frame readers are not cancel-safe. A frame that
+    /// starts but stalls for longer than `interval + timeout` is also treated
+    /// as a keepalive timeout.
+    pub async fn recv_frame_with_keepalive(
+        &mut self,
+        driver: &mut KeepaliveDriver,
+    ) -> Result<Vec<u8>, TransportComposeError> {
+        self.check_quota(QuotaDirection::Receive)?;
+        let mut first = [0_u8; 1];
+        loop {
+            match driver.action_at(Instant::now()) {
+                KeepaliveAction::TimedOut => {
+                    return Err(TransportComposeError::KeepaliveTimeout {
+                        timeout: driver.keepalive().timeout,
+                    });
+                }
+                KeepaliveAction::SendPing => {
+                    let ping = driver.ping_payload(self.framing, Instant::now())?;
+                    self.send_frame(&ping).await?;
+                    continue;
+                }
+                KeepaliveAction::Wait => {}
+            }
+
+            let read = match timeout_at(driver.next_deadline(), self.io.read(&mut first)).await {
+                Ok(read) => read?,
+                Err(_elapsed) => continue,
+            };
+            if read == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            driver.observe_traffic(Instant::now());
+
+            let keepalive = driver.keepalive().clone();
+            let frame_deadline = Instant::now() + keepalive.interval + keepalive.timeout;
+            let mut reader = (&first[..]).chain(&mut self.io);
+            let frame = timeout_at(
+                frame_deadline,
+                recv_frame_with_framing(
+                    &mut reader,
+                    self.framing,
+                    self.max_frame_bytes,
+                    self.limits.max_xml_scan_bytes,
+                ),
+            )
+            .await
+            .map_err(|_elapsed| TransportComposeError::KeepaliveTimeout {
+                timeout: keepalive.timeout,
+            })??;
+            driver.observe_traffic(Instant::now());
+            self.charge_quota(QuotaDirection::Receive, frame.len())
+                .await;
+            tap::capture(self.tap.as_ref(), TapDirection::Inbound, &frame).await;
+            return Ok(frame);
+        }
+    }
+
+    /// The message and raw frame share one buffer.
     pub async fn recv_envelope(
         &mut self,
-    ) -> Result<TransportEnvelope<Vec<u8>>, TransportComposeError> {
-        let frame = self.recv_frame().await?;
-        let raw_frame = Bytes::copy_from_slice(&frame);
-        Ok(TransportEnvelope::new(frame).with_raw_frame(raw_frame))
+    ) -> Result<TransportEnvelope<Bytes>, TransportComposeError> {
+        Ok(frame_envelope(self.recv_frame().await?))
+    }
+}
+
+/// Adapts a [`TransportSender`] or [`TransportConnection`] to
+/// [`MessageSink`], whose `send` takes `&self`, by serialising sends behind
+/// an async mutex. Each message is written as one frame and flushed, except
+/// that a batching sender keeps frames until its batch fills; drive
+/// [`TransportSender::flush_when_due`] through [`Self::lock`] for those.
+///
+/// Composes with the `rustak-io` layers, e.g.
+/// `MetricsLayer::new(LockedSink::new(sender))`.
+#[derive(Debug)]
+pub struct LockedSink<T> {
+    inner: tokio::sync::Mutex<T>,
+}
+
+impl<T> LockedSink<T> {
+    #[must_use]
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: tokio::sync::Mutex::new(inner),
+        }
+    }
+
+    /// Waits for in-flight sends and returns exclusive access.
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, T> {
+        self.inner.lock().await
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<W> MessageSink<Bytes> for LockedSink<TransportSender<W>>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+        Box::pin(async move {
+            let mut sender = self.inner.lock().await;
+            sender.send_frame(&msg).await?;
+            if sender.batch.is_none() {
+                sender.flush().await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<IO> MessageSink<Bytes> for LockedSink<TransportConnection<IO>>
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + Sync,
+{
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+        Box::pin(async move {
+            let mut connection = self.inner.lock().await;
+            connection.send_frame(&msg).await?;
+            connection.io.flush().await?;
+            Ok(())
+        })
+    }
+}
+
+impl<R> MessageSource<Bytes> for TransportReceiver<R>
+where
+    R: AsyncRead + Unpin + Send + Sync + 'static,
+{
+    fn recv(&mut self) -> BoxFuture<'_, Result<MessageEnvelope<Bytes>, IoError>> {
+        Box::pin(async move { Ok(frame_envelope(self.recv_frame().await?)) })
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+    ) -> Pin<Box<dyn Stream<Item = Result<MessageEnvelope<Bytes>, IoError>> + Send>> {
+        envelope_stream(*self)
     }
 }
 
+/// Receives through [`TransportConnection::recv_frame`], so quotas apply.
+/// Frames are returned as framed; decode TAK payloads with
+/// [`TransportConnection::decode_frame_payload`] before consuming the
+/// source if the negotiator should see decode failures.
+impl<IO> MessageSource<Bytes> for TransportConnection<IO>
+where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    fn recv(&mut self) -> BoxFuture<'_, Result<MessageEnvelope<Bytes>, IoError>> {
+        Box::pin(async move { Ok(frame_envelope(self.recv_frame().await?)) })
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+    ) -> Pin<Box<dyn Stream<Item = Result<MessageEnvelope<Bytes>, IoError>> + Send>> {
+        envelope_stream(*self)
+    }
+}
+
+/// The last `len` bytes of `buf`, the frame a `recv_frame_into` appended.
+fn tail(buf: &BytesMut, len: usize) -> &[u8] {
+    &buf[buf.len() - len..]
+}
+
+fn frame_envelope(frame: Vec<u8>) -> MessageEnvelope<Bytes> {
+    let frame = Bytes::from(frame);
+    MessageEnvelope::new(frame.clone()).with_raw_frame(frame)
+}
+
+/// Yields envelopes until the peer closes the stream, or until the first
+/// other error, which is yielded before the stream ends.
+fn envelope_stream<S>(
+    source: S,
+) -> Pin<Box<dyn Stream<Item = Result<MessageEnvelope<Bytes>, IoError>> + Send>>
+where
+    S: MessageSource<Bytes> + 'static,
+{
+    Box::pin(futures::stream::unfold(Some(source), |source| async move {
+        let mut source = source?;
+        match source.recv().await {
+            Ok(envelope) => Some((Ok(envelope), Some(source))),
+            Err(IoError::Closed) => None,
+            Err(error) => Some((Err(error), None)),
+        }
+    }))
+}
+
+/// The peer closing the stream becomes [`IoError::Closed`] and keepalive
+/// expiry [`IoError::Timeout`]; other failures keep their message.
+impl From<TransportComposeError> for IoError {
+    fn from(error: TransportComposeError) -> Self {
+        match error {
+            TransportComposeError::Io(error)
+            | TransportComposeError::LengthPrefixed(LengthPrefixedError::Io(error))
+                if error.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Self::Closed
+            }
+            TransportComposeError::Delimited(DelimiterFrameError::UnexpectedEof { scanned: 0 }) => {
+                Self::Closed
+            }
+            TransportComposeError::Io(error) => Self::Io(error),
+            TransportComposeError::KeepaliveTimeout { timeout } => Self::Timeout(timeout),
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+/// Bytes `framing` puts on the wire for a `payload_len`-byte payload.
+fn framed_len(framing: TransportFraming, payload_len: usize) -> usize {
+    let overhead = match framing {
+        TransportFraming::XmlNewlineDelimited => XML_FRAME_DELIMITER.len(),
+        TransportFraming::TakProtocolU32LengthPrefixed => std::mem::size_of::<u32>(),
+        TransportFraming::TakProtocolMeshHeader => TAK_MESH_HEADER_LEN,
+    };
+    payload_len.saturating_add(overhead)
+}
+
 fn framing_settings(
     config: &TransportConfig,
 ) -> Result<(TransportFraming, usize), TransportComposeError> {
     config.validate()?;
     Ok((
-        TransportFraming::from(config.wire_format),
+        TransportFraming::for_config(config),
         config.limits.max_frame_bytes,
     ))
 }
@@ -493,29 +1436,140 @@ where
             write_length_prefixed_frame(writer, LengthPrefixKind::U32Be, payload, max_frame_bytes)
                 .await?;
         }
+        TransportFraming::TakProtocolMeshHeader => {
+            let frame =
+                MeshFrameCodec::new(TakProtocolVersion::V1, max_frame_bytes).encode(payload)?;
+            writer.write_all(&frame).await?;
+        }
     }
     Ok(())
 }
 
-async fn recv_frame_with_framing<R>(
-    reader: &mut R,
+/// Checks `payload` against the limit the way [`send_frame_with_framing`]
+/// would, and returns its length prefix, if the framing has one. Mesh
+/// frames are encoded whole and never come through here.
+fn stream_frame_prefix(
     framing: TransportFraming,
+    payload: &[u8],
     max_frame_bytes: usize,
-) -> Result<Vec<u8>, TransportComposeError>
-where
-    R: AsyncRead + Unpin,
+) -> Result<Option<[u8; 4]>, TransportComposeError> {
+    if framing == TransportFraming::XmlNewlineDelimited {
+        let scanned = payload.len().saturating_add(XML_FRAME_DELIMITER.len());
+        if scanned > max_frame_bytes {
+            return Err(DelimiterFrameError::FrameTooLarge {
+                max_frame_bytes,
+                scanned,
+            }
+            .into());
+        }
+        return Ok(None);
+    }
+    let frame_len = payload.len();
+    if frame_len > max_frame_bytes {
+        return Err(LengthPrefixedError::FrameTooLarge {
+            frame_len,
+            max_frame_bytes,
+        }
+        .into());
+    }
+    let prefix = u32::try_from(frame_len).map_err(|_| LengthPrefixedError::PrefixOverflow {
+        prefix: "u32_be",
+        frame_len,
+    })?;
+    Ok(Some(prefix.to_be_bytes()))
+}
+
+/// Writes every slice in order. Writers without vectored support get one
+/// contiguous copy instead of a write per slice.
+async fn write_all_vectored<W>(
+    writer: &mut W,
+    mut slices: &mut [IoSlice<'_>],
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
 {
-    match framing {
-        TransportFraming::XmlNewlineDelimited => {
-            read_delimited_frame(reader, XML_FRAME_DELIMITER, max_frame_bytes, false)
-                .await
-                .map_err(TransportComposeError::from)
+    if !writer.is_write_vectored() {
+        let joined = slices
+            .iter()
+            .flat_map(|slice| slice.iter().copied())
+            .collect::<Vec<_>>();
+        return writer.write_all(&joined).await;
+    }
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        let written = writer.write_vectored(slices).await?;
+        if written == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
         }
+        IoSlice::advance_slices(&mut slices, written);
+    }
+    Ok(())
+}
+
+async fn recv_frame_with_framing<R>(
+    reader: &mut R,
+    framing: TransportFraming,
+    max_frame_bytes: usize,
+    max_xml_scan_bytes: usize,
+) -> Result<Vec<u8>, TransportComposeError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut frame = BytesMut::new();
+    recv_frame_into_with_framing(
+        reader,
+        framing,
+        max_frame_bytes,
+        max_xml_scan_bytes,
+        &mut frame,
+    )
+    .await?;
+    Ok(frame.into())
+}
+
+/// Appends the next frame's payload to `buf` and returns its length.
+/// `max_xml_scan_bytes` bounds how far legacy XML framing reads looking
+/// for a delimiter.
+async fn recv_frame_into_with_framing<R>(
+    reader: &mut R,
+    framing: TransportFraming,
+    max_frame_bytes: usize,
+    max_xml_scan_bytes: usize,
+    buf: &mut BytesMut,
+) -> Result<usize, TransportComposeError>
+where
+    R: AsyncRead + Unpin,
+{
+    match framing {
+        TransportFraming::XmlNewlineDelimited => read_scan_limited_frame_into(
+            reader,
+            XML_FRAME_DELIMITER,
+            max_frame_bytes,
+            max_xml_scan_bytes,
+            false,
+            buf,
+        )
+        .await
+        .map_err(TransportComposeError::from),
         TransportFraming::TakProtocolU32LengthPrefixed => {
-            read_length_prefixed_frame(reader, LengthPrefixKind::U32Be, max_frame_bytes)
+            read_length_prefixed_frame_into(reader, LengthPrefixKind::U32Be, max_frame_bytes, buf)
                 .await
                 .map_err(TransportComposeError::from)
         }
+        TransportFraming::TakProtocolMeshHeader => {
+            // Mesh frames carry no length, so the reader holds exactly one
+            // datagram; one byte past the limit is enough to reject it.
+            let limit = TAK_MESH_HEADER_LEN + max_frame_bytes + 1;
+            let mut frame = Vec::new();
+            reader
+                .take(u64::try_from(limit).unwrap_or(u64::MAX))
+                .read_to_end(&mut frame)
+                .await?;
+            let payload =
+                MeshFrameCodec::new(TakProtocolVersion::V1, max_frame_bytes).decode(&frame)?;
+            buf.extend_from_slice(payload);
+            Ok(payload.len())
+        }
     }
 }
 
@@ -553,7 +1607,13 @@ pub fn fuzz_hook_validate_transport_config(data: &[u8]) -> Result<(), TransportC
 
     if let Some(mtu_safety) = config.mtu_safety.as_mut() {
         mtu_safety.max_udp_payload_bytes = word_at(data, 24);
-        mtu_safety.drop_oversize = byte_at(data, 26) % 2 == 0;
+        mtu_safety.oversize = match byte_at(data, 26) % 5 {
+            0 => OversizePolicy::Drop,
+            1 => OversizePolicy::Split,
+            2 => OversizePolicy::TruncateDetail,
+            3 => OversizePolicy::StreamFallback,
+            _ => OversizePolicy::Chunk,
+        };
     }
 
     config.reconnect_policy.backoff_factor = (byte_at(data, 27) as f64) / 64.0;
@@ -576,16 +1636,22 @@ fn byte_at(data: &[u8], offset: usize) -> u8 {
 
 #[cfg(test)]
 mod tests {
-    use bytes::Bytes;
+    use bytes::{Bytes, BytesMut};
+    use std::io::IoSlice;
     use std::time::Duration;
 
+    use rustak_core::TimestampUtc;
     use rustak_limits::Limits;
-    use rustak_wire::{DowngradePolicy, NegotiationEventKind, NegotiationReason, WireFormat};
+    use rustak_wire::negotiation::events::TakControlMessage;
+    use rustak_wire::{
+        DowngradePolicy, NegotiationEventKind, NegotiationReason, TakProtocolVersion, WireFormat,
+    };
     use tokio::io::duplex;
 
     use crate::{
-        envelope, TransportConfig, TransportConfigError, TransportConnection, TransportFraming,
-        TransportReceiver, TransportSender,
+        envelope, LockedSink, TransportComposeError, TransportConfig, TransportConfigError,
+        TransportConnection, TransportEvent, TransportEvents, TransportFraming, TransportReceiver,
+        TransportSender, WriteBatchConfig,
     };
 
     #[test]
@@ -692,6 +1758,111 @@ mod tests {
         assert_eq!(frame, b"<event uid=\"transport\"/>");
     }
 
+    #[tokio::test]
+    async fn xml_receiver_stops_at_the_scan_limit_on_unterminated_input() {
+        use tokio::io::AsyncWriteExt;
+
+        let (mut client, server) = duplex(128);
+        let limits = Limits {
+            max_frame_bytes: 64,
+            max_xml_scan_bytes: 16,
+            max_protobuf_bytes: 64,
+            ..Limits::default()
+        };
+        let cfg = TransportConfig {
+            wire_format: WireFormat::Xml,
+            limits,
+            mtu_safety: None,
+            ..TransportConfig::default()
+        };
+        let mut receiver = TransportReceiver::new(server, &cfg).expect("config should be valid");
+
+        // The peer keeps the stream open and never sends a newline.
+        client
+            .write_all(b"<event uid=\"unterminated\">")
+            .await
+            .expect("write");
+        let error = receiver
+            .recv_frame()
+            .await
+            .expect_err("unterminated XML should hit the scan limit");
+        assert!(matches!(
+            error,
+            TransportComposeError::Delimited(
+                rustak_net::DelimiterFrameError::XmlScanLimitExceeded {
+                    max_xml_scan_bytes: 16,
+                    scanned: 17,
+                }
+            )
+        ));
+    }
+
+    #[tokio::test]
+    async fn transport_halves_compose_with_io_layers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use futures::StreamExt;
+        use rustak_io::layers::{DedupConfig, DedupLayer, MetricsLayer, TapLayer};
+        use rustak_io::{IoError, MessageEnvelope, MessageSink, MessageSource};
+
+        let (client, server) = duplex(1024);
+        let cfg = TransportConfig::default();
+        let tapped = Arc::new(AtomicUsize::new(0));
+        let tap_count = Arc::clone(&tapped);
+        let sink = MetricsLayer::new(
+            DedupLayer::new(
+                TapLayer::new(
+                    LockedSink::new(
+                        TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+                            .expect("connection"),
+                    ),
+                    move |_: &MessageEnvelope<Bytes>| {
+                        tap_count.fetch_add(1, Ordering::Relaxed);
+                    },
+                ),
+                DedupConfig { max_keys: 8 },
+                |envelope: &MessageEnvelope<Bytes>| envelope.message.clone(),
+            )
+            .expect("dedup"),
+        );
+        for uid in ["a", "b", "a"] {
+            let event = Bytes::from(format!("<event uid=\"{uid}\"/>"));
+            sink.send_envelope(MessageEnvelope::new(event))
+                .await
+                .expect("send");
+        }
+        assert_eq!(sink.snapshot().sent, 3);
+        assert_eq!(tapped.load(Ordering::Relaxed), 2, "duplicate is not sent");
+        drop(sink);
+
+        let mut receiver = TransportReceiver::new(server, &cfg).expect("receiver");
+        let first = receiver.recv().await.expect("first frame");
+        assert_eq!(first.message, &b"<event uid=\"a\"/>"[..]);
+        assert_eq!(first.raw_frame.as_ref(), Some(&first.message));
+        let rest = Box::new(receiver).into_stream().collect::<Vec<_>>().await;
+        assert_eq!(rest.len(), 1, "stream ends at EOF: {rest:?}");
+        assert_eq!(
+            rest[0].as_ref().expect("second frame").message,
+            &b"<event uid=\"b\"/>"[..]
+        );
+
+        let (client, server) = duplex(1024);
+        let sender = LockedSink::new(TransportSender::new(client, &cfg).expect("sender"));
+        let mut connection =
+            TransportConnection::new(server, &cfg, DowngradePolicy::FailOpen).expect("connection");
+        sender
+            .send(Bytes::from_static(b"<event uid=\"c\"/>"))
+            .await
+            .expect("send");
+        assert_eq!(
+            connection.recv().await.expect("frame").message,
+            &b"<event uid=\"c\"/>"[..]
+        );
+        drop(sender);
+        assert!(matches!(connection.recv().await, Err(IoError::Closed)));
+    }
+
     #[tokio::test]
     async fn sender_receiver_round_trip_tak_length_prefixed_framing() {
         let (client, server) = duplex(128);
@@ -728,6 +1899,29 @@ mod tests {
         assert_eq!(frame, &[0xDE, 0xAD, 0xBE, 0xEF]);
     }
 
+    #[tokio::test]
+    async fn udp_tak_protocol_selects_mesh_header_framing() {
+        let (client, server) = duplex(128);
+        let cfg = TransportConfig {
+            protocol: crate::Protocol::Udp {
+                bind_addr: "127.0.0.1:0".parse().expect("bind addr"),
+                target: crate::UdpTarget::Unicast("127.0.0.1:4242".parse().expect("target")),
+            },
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        assert_eq!(
+            TransportFraming::for_config(&cfg),
+            TransportFraming::TakProtocolMeshHeader
+        );
+
+        let mut sender = TransportSender::new(client, &cfg).expect("config should be valid");
+        let mut receiver = TransportReceiver::new(server, &cfg).expect("config should be valid");
+        sender.send_frame(&[0x0A, 0x00]).await.expect("send");
+        drop(sender);
+        assert_eq!(receiver.recv_frame().await.expect("receive"), &[0x0A, 0x00]);
+    }
+
     #[tokio::test]
     async fn connection_wraps_frames_and_reuses_wire_negotiator() {
         let (client, server) = duplex(128);
@@ -762,10 +1956,557 @@ mod tests {
             .await
             .expect("receive envelope should succeed");
 
-        assert_eq!(envelope.message, b"<tak-proto/>");
+        assert_eq!(envelope.message, &b"<tak-proto/>"[..]);
         assert_eq!(
             envelope.raw_frame,
             Some(Bytes::from_static(b"<tak-proto/>"))
         );
+        assert_eq!(
+            envelope.raw_frame.map(|frame| frame.as_ptr()),
+            Some(envelope.message.as_ptr()),
+            "message and raw frame share one buffer"
+        );
+    }
+
+    #[tokio::test]
+    async fn recv_frame_into_reuses_one_buffer_and_decodes_without_copying_xml() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = duplex(256);
+        let cfg = TransportConfig::default();
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+            .expect("connection should build");
+        server
+            .write_all(b"<event uid=\"a\"/>\n<event uid=\"b\"/>\n")
+            .await
+            .expect("write");
+
+        let mut buf = BytesMut::with_capacity(64);
+        let len = connection.recv_frame_into(&mut buf).await.expect("first");
+        let first = buf.split_to(len).freeze();
+        let len = connection.recv_frame_into(&mut buf).await.expect("second");
+        assert_eq!(&first[..], b"<event uid=\"a\"/>");
+        assert_eq!(&buf[..len], b"<event uid=\"b\"/>");
+
+        let payload = connection.decode_frame_bytes(&first).expect("decode");
+        assert_eq!(payload.as_ptr(), first.as_ptr());
+    }
+
+    #[tokio::test]
+    async fn persistent_decode_failures_downgrade_connection_to_xml() {
+        let (client, mut server) = duplex(256);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            mtu_safety: None,
+            ..TransportConfig::default()
+        };
+        let events = TransportEvents::default();
+        let mut published = events.subscribe();
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+            .expect("connection should build")
+            .with_events(events);
+        connection.begin_upgrade_attempt();
+        connection.observe_supported_version(rustak_wire::TakProtocolVersion::V1);
+
+        let ping = crate::keepalive::render_ping(
+            "peer",
+            rustak_core::TimestampUtc::UNIX_EPOCH,
+            Duration::from_secs(10),
+        );
+        let valid = rustak_proto::encode_v1_payload(ping.as_bytes()).expect("encode");
+        let decoded = connection.decode_frame_payload(&valid).expect("decode");
+        assert!(decoded.starts_with(b"<event version=\"2.0\" uid=\"peer-ping\""));
+        for _ in 1..rustak_wire::DEFAULT_DECODE_FAILURE_LIMIT {
+            assert!(connection.decode_frame_payload(&[0xff]).is_err());
+            assert_eq!(
+                connection.framing(),
+                TransportFraming::TakProtocolU32LengthPrefixed
+            );
+        }
+        assert!(connection.decode_frame_payload(&[0xff]).is_err());
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+        assert_eq!(
+            connection.negotiation_state(),
+            rustak_wire::NegotiationState::LegacyXml
+        );
+        assert_eq!(
+            published.try_recv().expect("fallback event"),
+            TransportEvent::NegotiationFellBack {
+                reason: NegotiationReason::PersistentDecodeFailure
+            }
+        );
+        assert!(published.try_recv().is_err());
+
+        tokio::io::AsyncWriteExt::write_all(&mut server, b"<event uid=\"x\"/>\n")
+            .await
+            .expect("write");
+        let frame = connection.recv_frame().await.expect("xml frame");
+        assert_eq!(
+            connection.decode_frame_payload(&frame).expect("xml"),
+            b"<event uid=\"x\"/>"
+        );
+    }
+
+    #[tokio::test]
+    async fn persistent_decode_failures_terminate_a_fail_closed_connection() {
+        let (client, _server) = duplex(256);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            mtu_safety: None,
+            ..TransportConfig::default()
+        };
+        let events = TransportEvents::default();
+        let mut published = events.subscribe();
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailClosed)
+            .expect("connection should build")
+            .with_events(events);
+        connection.begin_upgrade_attempt();
+        connection.observe_supported_version(TakProtocolVersion::V1);
+
+        for _ in 1..rustak_wire::DEFAULT_DECODE_FAILURE_LIMIT {
+            assert!(matches!(
+                connection.decode_frame_payload(&[0xff]),
+                Err(TransportComposeError::Proto(_))
+            ));
+        }
+        let terminated = |error: TransportComposeError| {
+            matches!(
+                error,
+                TransportComposeError::NegotiationTerminated {
+                    reason: NegotiationReason::PersistentDecodeFailure
+                }
+            )
+        };
+        let error = connection
+            .decode_frame_payload(&[0xff])
+            .expect_err("limit reached");
+        assert!(terminated(error));
+        assert_eq!(
+            connection.framing(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+
+        // Even a well-formed payload is refused once the stream is terminated.
+        let ping = crate::render_ping(
+            "peer",
+            rustak_core::TimestampUtc::UNIX_EPOCH,
+            Duration::from_secs(10),
+        );
+        let valid = rustak_proto::encode_v1_payload(ping.as_bytes()).expect("encode");
+        let error = connection
+            .decode_frame_bytes(&Bytes::from(valid))
+            .expect_err("terminated");
+        assert!(terminated(error));
+        assert_eq!(
+            published.try_recv().expect("termination event"),
+            TransportEvent::NegotiationTerminated {
+                reason: NegotiationReason::PersistentDecodeFailure
+            }
+        );
+        assert!(published.try_recv().is_err());
+    }
+
+    fn control_line(message: TakControlMessage) -> Vec<u8> {
+        let mut line = message.encode("server", TimestampUtc::now());
+        line.push(b'\n');
+        line
+    }
+
+    async fn read_line<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Vec<u8> {
+        use tokio::io::AsyncReadExt;
+
+        let mut line = Vec::new();
+        loop {
+            let byte = reader.read_u8().await.expect("line byte");
+            if byte == b'\n' {
+                return line;
+            }
+            line.push(byte);
+        }
+    }
+
+    #[tokio::test]
+    async fn negotiate_upgrades_framing_and_keeps_early_xml_events() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = duplex(256);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let events = TransportEvents::default();
+        let mut published = events.subscribe();
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailClosed)
+            .expect("connection should build")
+            .with_events(events);
+        let server = async move {
+            let mut announcement = b"<event uid=\"early\"/>\n\r\n".to_vec();
+            announcement.extend(control_line(TakControlMessage::ProtocolSupport {
+                version: Some(TakProtocolVersion::V1),
+            }));
+            server.write_all(&announcement).await.expect("announce");
+            let request = read_line(&mut server).await;
+            server
+                .write_all(&control_line(TakControlMessage::Response {
+                    accepted: true,
+                }))
+                .await
+                .expect("respond");
+            (server, request)
+        };
+
+        let (outcome, (_server, request)) = tokio::join!(
+            connection.negotiate(TakProtocolVersion::V1, Duration::from_secs(5)),
+            server
+        );
+        let outcome = outcome.expect("negotiate");
+        assert_eq!(outcome.event.kind, NegotiationEventKind::UpgradeAccepted);
+        assert_eq!(
+            outcome.early_events,
+            vec![b"<event uid=\"early\"/>".to_vec()]
+        );
+        assert_eq!(
+            connection.framing(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+        assert_eq!(
+            published.try_recv(),
+            Ok(TransportEvent::NegotiationUpgraded {
+                version: TakProtocolVersion::V1
+            })
+        );
+        assert_eq!(
+            TakControlMessage::parse(&request, &Limits::conservative_defaults()),
+            Ok(Some(TakControlMessage::Request {
+                version: TakProtocolVersion::V1
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn negotiate_refused_response_applies_downgrade_policy() {
+        use tokio::io::AsyncWriteExt;
+
+        let (client, mut server) = duplex(1024);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+            .expect("connection should build");
+        let server = async move {
+            server
+                .write_all(&control_line(TakControlMessage::ProtocolSupport {
+                    version: Some(TakProtocolVersion::V1),
+                }))
+                .await
+                .expect("announce");
+            read_line(&mut server).await;
+            server
+                .write_all(&control_line(TakControlMessage::Response {
+                    accepted: false,
+                }))
+                .await
+                .expect("respond");
+            server
+        };
+
+        let (outcome, _server) = tokio::join!(
+            connection.negotiate(TakProtocolVersion::V1, Duration::from_secs(5)),
+            server
+        );
+        let outcome = outcome.expect("negotiate");
+        assert_eq!(outcome.event.kind, NegotiationEventKind::FallbackToLegacy);
+        assert_eq!(
+            outcome.event.reason,
+            Some(NegotiationReason::UnsupportedVersion)
+        );
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn negotiate_timeout_applies_downgrade_policy() {
+        let (client, _server) = duplex(256);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+            .expect("connection should build");
+        let outcome = connection
+            .negotiate(rustak_wire::TakProtocolVersion::V1, Duration::from_secs(5))
+            .await
+            .expect("negotiate");
+        assert_eq!(outcome.event.kind, NegotiationEventKind::FallbackToLegacy);
+        assert_eq!(outcome.event.reason, Some(NegotiationReason::Timeout));
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn negotiate_with_config_resolves_the_wire_format() {
+        use tokio::io::AsyncWriteExt;
+
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let fail_open = rustak_wire::NegotiationConfig {
+            streaming_timeout: Duration::from_secs(3),
+            downgrade_policy: DowngradePolicy::FailOpen,
+            decode_failure_limit: 1,
+            ..rustak_wire::NegotiationConfig::default()
+        };
+
+        let (client, _silent) = duplex(256);
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailClosed)
+            .expect("connection should build");
+        let started = tokio::time::Instant::now();
+        let outcome = connection
+            .negotiate_with_config(TakProtocolVersion::V1, &fail_open)
+            .await
+            .expect("fail-open timeout falls back");
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+        assert_eq!(outcome.wire_format(), Some(WireFormat::Xml));
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+
+        let (client, _silent) = duplex(256);
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen)
+            .expect("connection should build");
+        let fail_closed = rustak_wire::NegotiationConfig {
+            downgrade_policy: DowngradePolicy::FailClosed,
+            ..fail_open.clone()
+        };
+        let error = connection
+            .negotiate_with_config(TakProtocolVersion::V1, &fail_closed)
+            .await
+            .expect_err("fail-closed timeout terminates");
+        assert!(matches!(
+            error,
+            TransportComposeError::NegotiationTerminated {
+                reason: NegotiationReason::Timeout
+            }
+        ));
+        assert_eq!(
+            rustak_limits::CodedError::code(&error).to_string(),
+            "RTK-TRANSPORT-0104"
+        );
+
+        let (client, mut server) = duplex(256);
+        let mut connection = TransportConnection::new(client, &cfg, DowngradePolicy::FailClosed)
+            .expect("connection should build");
+        let server = async move {
+            server
+                .write_all(&control_line(TakControlMessage::ProtocolSupport {
+                    version: Some(TakProtocolVersion::V1),
+                }))
+                .await
+                .expect("announce");
+            read_line(&mut server).await;
+            server
+                .write_all(&control_line(TakControlMessage::Response {
+                    accepted: true,
+                }))
+                .await
+                .expect("respond");
+            server
+        };
+        let (outcome, _server) = tokio::join!(
+            connection.negotiate_with_config(TakProtocolVersion::V1, &fail_open),
+            server
+        );
+        assert_eq!(
+            outcome.expect("upgrade").wire_format(),
+            Some(WireFormat::TakProtocolV1)
+        );
+        assert_eq!(
+            connection.framing(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+        // The configured decode-failure limit of one replaced the default.
+        assert!(connection.decode_frame_payload(&[0xff]).is_err());
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+    }
+
+    #[derive(Default)]
+    struct CountingWriter {
+        bytes: Vec<u8>,
+        writes: usize,
+        vectored: bool,
+        /// Most bytes one write call takes, to exercise short writes.
+        write_limit: Option<usize>,
+    }
+
+    impl tokio::io::AsyncWrite for CountingWriter {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let taken = buf.len().min(self.write_limit.unwrap_or(usize::MAX));
+            self.writes += 1;
+            self.bytes.extend_from_slice(&buf[..taken]);
+            std::task::Poll::Ready(Ok(taken))
+        }
+
+        fn poll_write_vectored(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            let mut budget = self.write_limit.unwrap_or(usize::MAX);
+            let mut taken = 0;
+            for buf in bufs {
+                let part = buf.len().min(budget);
+                self.bytes.extend_from_slice(&buf[..part]);
+                budget -= part;
+                taken += part;
+            }
+            self.writes += 1;
+            std::task::Poll::Ready(Ok(taken))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.vectored
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    fn batching_sender(max_batch_frames: usize) -> TransportSender<CountingWriter> {
+        TransportSender::new(CountingWriter::default(), &TransportConfig::default())
+            .expect("sender")
+            .with_write_batching(WriteBatchConfig {
+                flush_interval: Duration::from_millis(5),
+                max_batch_frames,
+            })
+            .expect("batching config")
+    }
+
+    #[tokio::test]
+    async fn write_batching_groups_frames_into_one_write() {
+        let mut sender = batching_sender(3);
+        for frame in [&b"<a/>"[..], b"<b/>"] {
+            sender.send_frame(frame).await.expect("batched send");
+        }
+        assert_eq!(sender.pending_frames(), 2);
+        assert!(sender.flush_deadline().is_some());
+
+        sender.send_frame(b"<c/>").await.expect("batched send");
+        assert_eq!(sender.pending_frames(), 0);
+        assert_eq!(sender.flush_deadline(), None);
+
+        let writer = sender.into_inner();
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.bytes, b"<a/>\n<b/>\n<c/>\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn write_batching_flushes_within_the_latency_bound() {
+        let mut sender = batching_sender(64);
+        let sent_at = tokio::time::Instant::now();
+        sender.send_frame(b"<a/>").await.expect("batched send");
+
+        sender.flush_when_due().await.expect("timed flush");
+        assert_eq!(sent_at.elapsed(), Duration::from_millis(5));
+        assert_eq!(sender.pending_frames(), 0);
+
+        sender.send_frame(b"<b/>").await.expect("batched send");
+        tokio::time::advance(Duration::from_millis(6)).await;
+        sender.send_frame(b"<c/>").await.expect("late send flushes");
+        assert_eq!(sender.pending_frames(), 0);
+        assert_eq!(sender.into_inner().writes, 2);
+    }
+
+    #[tokio::test]
+    async fn send_frames_coalesces_frames_into_one_vectored_write() {
+        let tak = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let writer = CountingWriter {
+            vectored: true,
+            ..CountingWriter::default()
+        };
+        let mut sender = TransportSender::new(writer, &tak).expect("sender");
+        sender
+            .send_frames(&[Bytes::from_static(b"ab"), Bytes::from_static(b"cde")])
+            .await
+            .expect("send frames");
+        let writer = sender.into_inner();
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.bytes, b"\0\0\0\x02ab\0\0\0\x03cde");
+
+        let writer = CountingWriter {
+            vectored: true,
+            write_limit: Some(3),
+            ..CountingWriter::default()
+        };
+        let mut sender = TransportSender::new(writer, &TransportConfig::default()).expect("sender");
+        sender
+            .send_frames(&[&b"<a/>"[..], b"<bb/>"])
+            .await
+            .expect("short writes resume");
+        let writer = sender.into_inner();
+        assert_eq!(writer.bytes, b"<a/>\n<bb/>\n");
+        assert_eq!(writer.writes, 4);
+    }
+
+    #[tokio::test]
+    async fn send_frames_joins_frames_for_plain_writers_and_checks_every_limit_first() {
+        let mut sender =
+            TransportSender::new(CountingWriter::default(), &TransportConfig::default())
+                .expect("sender");
+        sender
+            .send_frames(&[&b"<a/>"[..], b"<b/>", b"<c/>"])
+            .await
+            .expect("send frames");
+
+        let oversize = vec![b'x'; TransportConfig::default().limits.max_frame_bytes];
+        let error = sender
+            .send_frames(&[&b"<d/>"[..], &oversize])
+            .await
+            .expect_err("delimiter pushes the frame over the limit");
+        assert!(matches!(
+            error,
+            TransportComposeError::Delimited(rustak_net::DelimiterFrameError::FrameTooLarge { .. })
+        ));
+
+        let writer = sender.into_inner();
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.bytes, b"<a/>\n<b/>\n<c/>\n");
+    }
+
+    #[test]
+    fn write_batching_rejects_zero_knobs() {
+        let config = WriteBatchConfig {
+            flush_interval: Duration::ZERO,
+            max_batch_frames: 4,
+        };
+        assert_eq!(
+            config.validate(),
+            Err(TransportConfigError::ZeroDuration {
+                field: "write_batch.flush_interval",
+            })
+        );
+        assert_eq!(
+            WriteBatchConfig {
+                flush_interval: Duration::from_millis(1),
+                max_batch_frames: 0,
+            }
+            .validate(),
+            Err(TransportConfigError::ZeroWriteBatchFrames)
+        );
     }
 }
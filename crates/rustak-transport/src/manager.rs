@@ -0,0 +1,839 @@
+//! Dialing and reconnecting TAK streaming connections.
+//!
+//! [`ConnectionManager`] owns the `Protocol::Tcp`/`Protocol::Tls` dial,
+//! retries failed attempts under the configured [`ReconnectPolicy`] and
+//! records every state change as a [`ConnectionEvent`] for callers to drain.
+//! Link-health changes are also broadcast as [`TransportEvent`]s to any
+//! subscriber of [`ConnectionManager::transport_events`].
+//! Reads and writes stay with the returned [`TransportConnection`]; when one
+//! fails, report it through [`ConnectionManager::disconnected`] and call
+//! [`ConnectionManager::connect`] again.
+//!
+//! All waiting goes through the tokio clock, so tests can pause it and
+//! drive [`ConnectionManager::connect_with`] over in-memory streams to check
+//! exact backoff and keepalive timing without sleeping.
+//!
+//! With `quota` configured, the manager keeps one [`QuotaMeter`] for every
+//! connection it dials, so byte usage survives reconnects.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_wire::DowngradePolicy;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnector;
+use crate::{
+    apply_tcp_keepalive, Keepalive, KeepaliveDriver, Protocol, QuotaDirection, QuotaMeter,
+    QuotaWindow, ReconnectPolicy, TransportComposeError, TransportConfig, TransportConfigError,
+    TransportConnection, TransportEvent, TransportEvents,
+};
+
+const DEFAULT_JITTER_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Delays between reconnect attempts: exponential from `initial_delay` by
+/// `backoff_factor`, capped at `max_delay`, then scaled by a uniform factor
+/// in `[1 - jitter, 1 + jitter]` (still capped).
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    policy: ReconnectPolicy,
+    retries: u32,
+    rng_state: u64,
+}
+
+impl ReconnectBackoff {
+    #[must_use]
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self::with_seed(policy, DEFAULT_JITTER_SEED)
+    }
+
+    /// Seeds the jitter generator so retry schedules are reproducible.
+    #[must_use]
+    pub fn with_seed(policy: ReconnectPolicy, seed: u64) -> Self {
+        Self {
+            policy,
+            retries: 0,
+            rng_state: seed.max(1),
+        }
+    }
+
+    /// Retries handed out since the last [`ReconnectBackoff::reset`].
+    #[must_use]
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Delay before the next retry, or `None` when reconnect is disabled or
+    /// `max_retries` is used up.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if !self.policy.enabled
+            || self
+                .policy
+                .max_retries
+                .is_some_and(|max_retries| self.retries >= max_retries)
+        {
+            return None;
+        }
+
+        let exponent = i32::try_from(self.retries).unwrap_or(i32::MAX);
+        let base =
+            self.policy.initial_delay.as_secs_f64() * self.policy.backoff_factor.powi(exponent);
+        let max_delay = self.policy.max_delay.as_secs_f64();
+        let mut delay = base.min(max_delay);
+        if self.policy.jitter > 0.0 {
+            let unit = (xorshift64(&mut self.rng_state) >> 11) as f64 / (1_u64 << 53) as f64;
+            delay = (delay * (1.0 + self.policy.jitter * (unit * 2.0 - 1.0))).min(max_delay);
+        }
+
+        self.retries += 1;
+        Some(Duration::from_secs_f64(delay.max(0.0)))
+    }
+
+    /// Called after a successful connect so the next outage starts again at
+    /// `initial_delay`.
+    pub fn reset(&mut self) {
+        self.retries = 0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Idle,
+    Connecting { attempt: u32 },
+    BackingOff { attempt: u32, delay: Duration },
+    Connected { peer: SocketAddr },
+    Disconnected,
+    GaveUp { attempts: u32 },
+}
+
+/// State changes recorded by [`ConnectionManager`]. `attempt` counts dials
+/// since the last successful connect, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connecting { attempt: u32 },
+    Connected { attempt: u32, peer: SocketAddr },
+    AttemptFailed { attempt: u32, error: String },
+    BackingOff { attempt: u32, delay: Duration },
+    GaveUp { attempts: u32 },
+    Disconnected { reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum ConnectionManagerError {
+    #[error(transparent)]
+    InvalidConfig(#[from] TransportConfigError),
+
+    #[error("connection manager only dials tcp and tls protocols")]
+    UnsupportedProtocol,
+
+    #[error("tls protocol configured but no TLS connector was provided")]
+    MissingTlsConnector,
+
+    #[error(transparent)]
+    Compose(#[from] TransportComposeError),
+
+    #[error("gave up after {attempts} connection attempts: {last_error}")]
+    RetriesExhausted { attempts: u32, last_error: String },
+
+    #[error("{direction} quota for this {window} is used up; resets in {resets_in:?}")]
+    QuotaExhausted {
+        window: QuotaWindow,
+        direction: QuotaDirection,
+        resets_in: Duration,
+    },
+}
+
+impl CodedError for ConnectionManagerError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidConfig(error) => error.code(),
+            Self::UnsupportedProtocol => ErrorCode::new("TRANSPORT", 501),
+            Self::MissingTlsConnector => ErrorCode::new("TRANSPORT", 502),
+            Self::Compose(error) => error.code(),
+            Self::RetriesExhausted { .. } => ErrorCode::new("TRANSPORT", 503),
+            Self::QuotaExhausted { .. } => ErrorCode::new("TRANSPORT", 504),
+        }
+    }
+}
+
+/// Stream produced by [`ConnectionManager`], plain TCP or TLS depending on
+/// the configured protocol.
+#[derive(Debug)]
+pub enum ManagedStream {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl ManagedStream {
+    /// Underlying TCP socket, e.g. for [`crate::TcpLinkSampler`].
+    #[must_use]
+    pub fn tcp_stream(&self) -> &TcpStream {
+        match self {
+            Self::Tcp(stream) => stream,
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => stream.get_ref().0,
+        }
+    }
+}
+
+impl AsyncRead for ManagedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ManagedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Dials the configured TCP/TLS endpoint and retries with backoff.
+///
+/// Each dial, including the TLS handshake, is bounded by the transport
+/// `write_timeout`.
+#[derive(Debug)]
+pub struct ConnectionManager {
+    config: TransportConfig,
+    downgrade_policy: DowngradePolicy,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+    backoff: ReconnectBackoff,
+    state: ConnectionState,
+    events: Vec<ConnectionEvent>,
+    transport_events: TransportEvents,
+    quota: Option<QuotaMeter>,
+}
+
+impl ConnectionManager {
+    pub fn new(
+        config: TransportConfig,
+        downgrade_policy: DowngradePolicy,
+    ) -> Result<Self, ConnectionManagerError> {
+        config.validate()?;
+        if !matches!(config.protocol, Protocol::Tcp { .. } | Protocol::Tls { .. }) {
+            return Err(ConnectionManagerError::UnsupportedProtocol);
+        }
+        Ok(Self {
+            backoff: ReconnectBackoff::new(config.reconnect_policy.clone()),
+            quota: config.quota.clone().map(QuotaMeter::new),
+            config,
+            downgrade_policy,
+            #[cfg(feature = "tls")]
+            tls: None,
+            state: ConnectionState::Idle,
+            events: Vec::new(),
+            transport_events: TransportEvents::default(),
+        })
+    }
+
+    /// Connector used for `Protocol::Tls` endpoints.
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub fn with_tls(mut self, connector: TlsConnector) -> Self {
+        self.tls = Some(connector);
+        self
+    }
+
+    #[must_use]
+    pub fn with_jitter_seed(mut self, seed: u64) -> Self {
+        self.backoff = ReconnectBackoff::with_seed(self.config.reconnect_policy.clone(), seed);
+        self
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &TransportConfig {
+        &self.config
+    }
+
+    #[must_use]
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Byte usage shared by every connection this manager dials.
+    #[must_use]
+    pub fn quota_meter(&self) -> Option<&QuotaMeter> {
+        self.quota.as_ref()
+    }
+
+    #[must_use]
+    pub fn events(&self) -> &[ConnectionEvent] {
+        &self.events
+    }
+
+    pub fn drain_events(&mut self) -> Vec<ConnectionEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Broadcast channel for this manager and the connections it dials.
+    /// Hand a clone to [`crate::OutboundSendQueue::with_events`] to see
+    /// queue pressure on the same stream.
+    #[must_use]
+    pub fn transport_events(&self) -> &TransportEvents {
+        &self.transport_events
+    }
+
+    /// Records that the caller's connection dropped.
+    pub fn disconnected(&mut self, reason: impl Into<String>) {
+        self.transition(
+            ConnectionState::Disconnected,
+            ConnectionEvent::Disconnected {
+                reason: reason.into(),
+            },
+        );
+    }
+
+    /// Keepalive driver for the configured `keepalive`, or `None` when
+    /// application pings are disabled.
+    #[must_use]
+    pub fn keepalive_driver(&self, uid: impl Into<String>) -> Option<KeepaliveDriver> {
+        self.config
+            .keepalive
+            .clone()
+            .map(|keepalive| KeepaliveDriver::new(keepalive, uid))
+    }
+
+    /// Records the drop (for example a
+    /// [`TransportComposeError::KeepaliveTimeout`]) and redials under the
+    /// reconnect policy.
+    pub async fn reconnect(
+        &mut self,
+        reason: impl Into<String>,
+    ) -> Result<TransportConnection<ManagedStream>, ConnectionManagerError> {
+        self.disconnected(reason);
+        self.connect().await
+    }
+
+    /// Dials until a connection is established or the reconnect policy
+    /// gives up. Refuses to dial while a quota under
+    /// [`crate::QuotaAction::Disconnect`] is used up.
+    pub async fn connect(
+        &mut self,
+    ) -> Result<TransportConnection<ManagedStream>, ConnectionManagerError> {
+        if matches!(self.config.protocol, Protocol::Tls { .. }) && !self.has_tls_connector() {
+            return Err(ConnectionManagerError::MissingTlsConnector);
+        }
+        let endpoint = Endpoint {
+            protocol: self.config.protocol.clone(),
+            keepalive: self.config.keepalive.clone(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.clone(),
+        };
+        self.connect_with(|| {
+            let endpoint = endpoint.clone();
+            async move { endpoint.dial().await.map_err(io::Error::other) }
+        })
+        .await
+    }
+
+    /// Like [`Self::connect`], but each attempt calls `dial` instead of
+    /// dialing the configured endpoint. Backoff, the `write_timeout` bound
+    /// on each attempt, quota checks and events behave the same, which lets
+    /// tests and simulations run the reconnect loop over in-memory streams.
+    pub async fn connect_with<IO, D, F>(
+        &mut self,
+        mut dial: D,
+    ) -> Result<TransportConnection<IO>, ConnectionManagerError>
+    where
+        IO: AsyncRead + AsyncWrite,
+        D: FnMut() -> F,
+        F: Future<Output = io::Result<(IO, SocketAddr)>>,
+    {
+        if let Some(usage) = self
+            .quota
+            .as_ref()
+            .and_then(|meter| meter.blocking_any_at(SystemTime::now()))
+        {
+            return Err(ConnectionManagerError::QuotaExhausted {
+                window: usage.window,
+                direction: usage.direction,
+                resets_in: usage.resets_in,
+            });
+        }
+
+        let mut attempt = 1;
+        loop {
+            self.transition(
+                ConnectionState::Connecting { attempt },
+                ConnectionEvent::Connecting { attempt },
+            );
+            let error = match tokio::time::timeout(self.config.write_timeout, dial()).await {
+                Ok(Ok((stream, peer))) => {
+                    self.backoff.reset();
+                    self.transition(
+                        ConnectionState::Connected { peer },
+                        ConnectionEvent::Connected { attempt, peer },
+                    );
+                    let connection =
+                        TransportConnection::new(stream, &self.config, self.downgrade_policy)?
+                            .with_events(self.transport_events.clone());
+                    return Ok(match &self.quota {
+                        Some(meter) => connection.with_quota_meter(meter.clone()),
+                        None => connection,
+                    });
+                }
+                Ok(Err(error)) => error.to_string(),
+                Err(_) => format!("dial timed out after {:?}", self.config.write_timeout),
+            };
+            self.events.push(ConnectionEvent::AttemptFailed {
+                attempt,
+                error: error.clone(),
+            });
+
+            let Some(delay) = self.backoff.next_delay() else {
+                self.backoff.reset();
+                self.transition(
+                    ConnectionState::GaveUp { attempts: attempt },
+                    ConnectionEvent::GaveUp { attempts: attempt },
+                );
+                return Err(ConnectionManagerError::RetriesExhausted {
+                    attempts: attempt,
+                    last_error: error,
+                });
+            };
+            self.transition(
+                ConnectionState::BackingOff { attempt, delay },
+                ConnectionEvent::BackingOff { attempt, delay },
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    fn has_tls_connector(&self) -> bool {
+        #[cfg(feature = "tls")]
+        {
+            self.tls.is_some()
+        }
+        #[cfg(not(feature = "tls"))]
+        {
+            false
+        }
+    }
+
+    fn transition(&mut self, state: ConnectionState, event: ConnectionEvent) {
+        self.state = state;
+        let published = match &event {
+            ConnectionEvent::Connected { peer, .. } => {
+                Some(TransportEvent::Connected { peer: *peer })
+            }
+            ConnectionEvent::Disconnected { reason } => Some(TransportEvent::Disconnected {
+                reason: reason.clone(),
+            }),
+            ConnectionEvent::BackingOff { attempt, delay } => {
+                Some(TransportEvent::ReconnectScheduled {
+                    attempt: *attempt,
+                    delay: *delay,
+                })
+            }
+            _ => None,
+        };
+        if let Some(published) = published {
+            self.transport_events.publish(published);
+        }
+        self.events.push(event);
+    }
+}
+
+/// What [`ConnectionManager::connect`] dials, detached from the manager so
+/// each attempt can own it.
+#[derive(Clone)]
+struct Endpoint {
+    protocol: Protocol,
+    keepalive: Option<Keepalive>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl Endpoint {
+    async fn dial(self) -> Result<(ManagedStream, SocketAddr), String> {
+        let addr = match &self.protocol {
+            Protocol::Tcp { addr } | Protocol::Tls { addr, .. } => *addr,
+            _ => unreachable!("protocol checked in ConnectionManager::new"),
+        };
+        let tcp = TcpStream::connect(addr)
+            .await
+            .map_err(|error| error.to_string())?;
+        let peer = tcp.peer_addr().map_err(|error| error.to_string())?;
+        tcp.set_nodelay(true).map_err(|error| error.to_string())?;
+        if let Some(keepalive) = &self.keepalive {
+            apply_tcp_keepalive(&tcp, keepalive).map_err(|error| error.to_string())?;
+        }
+
+        #[cfg(feature = "tls")]
+        if let (Protocol::Tls { server_name, .. }, Some(connector)) = (&self.protocol, &self.tls) {
+            let stream = connector
+                .connect(server_name, tcp)
+                .await
+                .map_err(|error| error.to_string())?;
+            return Ok((ManagedStream::Tls(Box::new(stream)), peer));
+        }
+
+        Ok((ManagedStream::Tcp(tcp), peer))
+    }
+}
+
+fn xorshift64(state: &mut u64) -> u64 {
+    let mut value = *state;
+    value ^= value << 13;
+    value ^= value >> 7;
+    value ^= value << 17;
+    *state = value;
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use rustak_wire::DowngradePolicy;
+    use tokio::io::{duplex, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::{
+        ConnectionEvent, ConnectionManager, ConnectionManagerError, ConnectionState,
+        ReconnectBackoff,
+    };
+    use crate::{
+        Keepalive, Protocol, QuotaAction, QuotaDirection, QuotaLimit, QuotaWindow, ReconnectPolicy,
+        TrafficQuotaConfig, TransportComposeError, TransportConfig, TransportEvent,
+    };
+
+    fn policy(jitter: f64, max_retries: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            enabled: true,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            backoff_factor: 2.0,
+            jitter,
+            max_retries,
+        }
+    }
+
+    fn tcp_config(addr: SocketAddr, reconnect_policy: ReconnectPolicy) -> TransportConfig {
+        TransportConfig {
+            protocol: Protocol::Tcp { addr },
+            keepalive: None,
+            reconnect_policy,
+            ..TransportConfig::default()
+        }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_caps_and_exhausts() {
+        let mut backoff = ReconnectBackoff::new(policy(0.0, Some(5)));
+        let delays = std::iter::from_fn(|| backoff.next_delay())
+            .map(|delay| delay.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+
+        let disabled = ReconnectPolicy {
+            enabled: false,
+            ..policy(0.0, None)
+        };
+        assert_eq!(ReconnectBackoff::new(disabled).next_delay(), None);
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds_and_is_seeded() {
+        let mut first = ReconnectBackoff::with_seed(policy(0.5, None), 7);
+        let mut second = ReconnectBackoff::with_seed(policy(0.5, None), 7);
+        for retry in 0..8 {
+            let delay = first.next_delay().expect("unbounded retries");
+            assert_eq!(Some(delay), second.next_delay());
+            let base = (100.0 * 2_f64.powi(retry)).min(500.0);
+            let millis = delay.as_secs_f64() * 1_000.0;
+            assert!(millis >= base * 0.5 - 1e-6, "retry {retry}: {millis}ms");
+            assert!(
+                delay <= Duration::from_millis(500),
+                "retry {retry}: {delay:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn connects_and_reports_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            socket.write_all(b"<event/>\n").await.expect("write");
+        });
+
+        let mut manager = ConnectionManager::new(
+            tcp_config(addr, policy(0.0, Some(1))),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("manager");
+        let mut connection = manager.connect().await.expect("connect");
+        assert_eq!(connection.recv_frame().await.expect("frame"), b"<event/>");
+        server.await.expect("server");
+        assert_eq!(
+            connection
+                .get_ref()
+                .tcp_stream()
+                .peer_addr()
+                .expect("peer addr"),
+            addr
+        );
+
+        assert_eq!(manager.state(), ConnectionState::Connected { peer: addr });
+        manager.disconnected("peer closed");
+        assert_eq!(
+            manager.drain_events(),
+            [
+                ConnectionEvent::Connecting { attempt: 1 },
+                ConnectionEvent::Connected {
+                    attempt: 1,
+                    peer: addr
+                },
+                ConnectionEvent::Disconnected {
+                    reason: "peer closed".to_owned()
+                },
+            ]
+        );
+        assert_eq!(manager.state(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn broadcasts_link_health_to_subscribers() {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 8089));
+        let mut manager = ConnectionManager::new(
+            tcp_config(addr, policy(0.0, Some(1))),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("manager");
+        let mut receiver = manager.transport_events().subscribe();
+
+        let mut dials = 0;
+        manager
+            .connect_with(|| {
+                dials += 1;
+                std::future::ready(if dials == 1 {
+                    Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                } else {
+                    Ok((duplex(64).0, addr))
+                })
+            })
+            .await
+            .expect("second dial connects");
+        manager.disconnected("peer closed");
+
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            [
+                TransportEvent::ReconnectScheduled {
+                    attempt: 1,
+                    delay: Duration::from_millis(100),
+                },
+                TransportEvent::Connected { peer: addr },
+                TransportEvent::Disconnected {
+                    reason: "peer closed".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+            listener.local_addr().expect("addr")
+        };
+        let reconnect_policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+            ..policy(0.0, Some(2))
+        };
+        let mut manager = ConnectionManager::new(
+            tcp_config(addr, reconnect_policy),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("manager");
+
+        let error = manager.connect().await.expect_err("nothing is listening");
+        assert!(matches!(
+            error,
+            ConnectionManagerError::RetriesExhausted { attempts: 3, .. }
+        ));
+        assert_eq!(manager.state(), ConnectionState::GaveUp { attempts: 3 });
+
+        let events = manager.drain_events();
+        let count =
+            |wanted: fn(&ConnectionEvent) -> bool| events.iter().filter(|e| wanted(e)).count();
+        assert_eq!(
+            count(|e| matches!(e, ConnectionEvent::AttemptFailed { .. })),
+            3
+        );
+        assert_eq!(
+            count(|e| matches!(e, ConnectionEvent::BackingOff { .. })),
+            2
+        );
+        assert_eq!(
+            events.last(),
+            Some(&ConnectionEvent::GaveUp { attempts: 3 })
+        );
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_keepalive_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            let (silent, _) = listener.accept().await.expect("accept silent");
+            let (mut socket, _) = listener.accept().await.expect("accept");
+            socket.write_all(b"<event/>\n").await.expect("write");
+            drop(silent);
+        });
+
+        let config = TransportConfig {
+            keepalive: Some(Keepalive {
+                interval: Duration::from_millis(20),
+                timeout: Duration::from_millis(20),
+            }),
+            ..tcp_config(addr, policy(0.0, Some(1)))
+        };
+        let mut manager =
+            ConnectionManager::new(config, DowngradePolicy::FailOpen).expect("manager");
+        let mut driver = manager.keepalive_driver("node").expect("keepalive");
+        let mut connection = manager.connect().await.expect("connect");
+
+        let error = connection
+            .recv_frame_with_keepalive(&mut driver)
+            .await
+            .expect_err("silent peer");
+        assert!(matches!(
+            error,
+            TransportComposeError::KeepaliveTimeout { .. }
+        ));
+
+        let mut connection = manager
+            .reconnect(error.to_string())
+            .await
+            .expect("reconnect");
+        let mut driver = manager.keepalive_driver("node").expect("keepalive");
+        assert_eq!(
+            connection
+                .recv_frame_with_keepalive(&mut driver)
+                .await
+                .expect("frame"),
+            b"<event/>"
+        );
+        server.await.expect("server");
+        assert!(manager
+            .events()
+            .iter()
+            .any(|event| matches!(event, ConnectionEvent::Disconnected { .. })));
+    }
+
+    #[tokio::test]
+    async fn quota_usage_carries_across_reconnects_and_blocks_redial() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("addr");
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().await.expect("accept");
+                socket.write_all(b"<event/>\n").await.expect("write");
+            }
+        });
+
+        let quota = TrafficQuotaConfig::new(QuotaAction::Disconnect).with_limit(
+            QuotaWindow::Day,
+            QuotaLimit {
+                send_bytes: None,
+                receive_bytes: Some(16),
+            },
+        );
+        let config = TransportConfig {
+            quota: Some(quota),
+            ..tcp_config(addr, policy(0.0, Some(1)))
+        };
+        let mut manager =
+            ConnectionManager::new(config, DowngradePolicy::FailOpen).expect("manager");
+
+        let mut connection = manager.connect().await.expect("connect");
+        connection.recv_frame().await.expect("first frame");
+        let mut connection = manager.reconnect("test").await.expect("reconnect");
+        connection.recv_frame().await.expect("second frame");
+        server.await.expect("server");
+
+        let usage = manager.quota_meter().expect("meter").usage();
+        assert_eq!(usage[0].used_bytes, 18, "both connections are counted");
+        assert!(matches!(
+            connection.recv_frame().await,
+            Err(TransportComposeError::QuotaExceeded {
+                window: QuotaWindow::Day,
+                direction: QuotaDirection::Receive,
+                ..
+            })
+        ));
+        assert!(matches!(
+            manager.reconnect("quota").await,
+            Err(ConnectionManagerError::QuotaExhausted { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_non_stream_protocols() {
+        let config = TransportConfig {
+            protocol: Protocol::WebSocket {
+                url: "ws://127.0.0.1:8080".to_owned(),
+            },
+            ..TransportConfig::default()
+        };
+        assert!(matches!(
+            ConnectionManager::new(config, DowngradePolicy::FailOpen),
+            Err(ConnectionManagerError::UnsupportedProtocol)
+        ));
+    }
+}
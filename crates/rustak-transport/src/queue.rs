@@ -1,8 +1,19 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "tower")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use rustak_core::{CotEvent, CotType};
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use thiserror::Error;
+use tokio::io::AsyncWrite;
+use tokio::sync::Notify;
 
-use crate::{SendQueueConfig, SendQueueMode};
+use crate::{
+    ClassBudget, MessageClass, SendQueueConfig, SendQueueMode, TransportComposeError,
+    TransportEvent, TransportEvents, TransportSender,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum QueuePriority {
@@ -11,6 +22,11 @@ pub enum QueuePriority {
     Low,
 }
 
+impl QueuePriority {
+    /// Drain order, highest first.
+    pub const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+}
+
 pub trait SendQueueClassifier<T> {
     fn byte_size(&self, item: &T) -> usize;
 
@@ -21,6 +37,159 @@ pub trait SendQueueClassifier<T> {
     fn coalesce_key(&self, _item: &T) -> Option<String> {
         None
     }
+
+    /// Class whose bandwidth budget the item is drained against.
+    fn message_class(&self, _item: &T) -> MessageClass {
+        MessageClass::Other
+    }
+
+    /// Everything the queue keeps about `item`. The queue calls this once
+    /// per enqueue and never asks again; the default combines the methods
+    /// above, so a classifier that has to parse the item should override it
+    /// and parse once.
+    fn classify(&self, item: &T) -> QueueClassification {
+        QueueClassification {
+            byte_size: self.byte_size(item),
+            priority: self.priority(item),
+            coalesce_key: self.coalesce_key(item),
+            class: self.message_class(item),
+        }
+    }
+}
+
+/// What a [`SendQueueClassifier`] decided about one queued item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueClassification {
+    pub byte_size: usize,
+    pub priority: QueuePriority,
+    pub coalesce_key: Option<String>,
+    pub class: MessageClass,
+}
+
+/// CoT type prefix of emergency beacons (`b-a-o-tbl` 911 alert, `b-a-o-pan`
+/// ring the bell, `b-a-o-opn` troops in contact, `b-a-o-can` cancel).
+pub const EMERGENCY_COT_TYPE_PREFIX: &str = rustak_core::cot_types::EMERGENCY_COT_TYPE_PREFIX;
+
+/// CoT type prefix of GeoChat messages and their receipts.
+pub const CHAT_COT_TYPE_PREFIX: &str = rustak_core::chat::GEOCHAT_COT_TYPE;
+
+/// Payload size from which [`CotPriorityClassifier`] budgets an event as
+/// [`MessageClass::LargeDetail`].
+pub const LARGE_DETAIL_BYTES: usize = 4096;
+
+/// `<detail>` children that carry imagery or attachments.
+const LARGE_DETAIL_ELEMENTS: [&str; 3] = ["image", "attachment_list", "fileshare"];
+
+/// [`SendQueueClassifier`] for encoded CoT payloads, CoT XML or TAK Protocol
+/// v1, that reads the event's `type`, `uid` and detail.
+///
+/// Emergency beacons and GeoChat are [`QueuePriority::High`] and never
+/// coalesced, so they overtake routine position updates and none is lost
+/// to a newer message from the same sender. Everything else is `Normal` and
+/// coalesces by `uid`. Each event is budgeted against the class
+/// [`Self::message_class_for_event`] picks. Payloads that are not CoT are
+/// `Normal`, [`MessageClass::Other`] and left alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CotPriorityClassifier;
+
+impl CotPriorityClassifier {
+    #[must_use]
+    pub fn priority_for_type(cot_type: &str) -> QueuePriority {
+        if rustak_core::cot_types::is_emergency(cot_type) || rustak_core::is_chat_type(cot_type) {
+            QueuePriority::High
+        } else {
+            QueuePriority::Normal
+        }
+    }
+
+    /// Priority of a typed event before it is encoded, e.g. one built with
+    /// [`rustak_core::ChatMessage::to_event`].
+    #[must_use]
+    pub fn priority_for_event(event: &CotEvent) -> QueuePriority {
+        Self::priority_for_type(&event.cot_type)
+    }
+
+    /// GeoChat is [`MessageClass::Chat`]. Events of [`LARGE_DETAIL_BYTES`]
+    /// or more, or with an `<image>`, `<attachment_list>` or `<fileshare>`
+    /// detail, are `LargeDetail`. Friendly atoms carrying the `<takv>` or
+    /// `<__group>` a TAK client attaches to its own position are `Pli`;
+    /// other atoms that [`CotType::parse`] accepts are `Track`s. Everything
+    /// else is `Other`.
+    #[must_use]
+    pub fn message_class_for_event(event: &CotEvent, payload_bytes: usize) -> MessageClass {
+        if rustak_core::is_chat_type(&event.cot_type) {
+            MessageClass::Chat
+        } else if payload_bytes >= LARGE_DETAIL_BYTES
+            || LARGE_DETAIL_ELEMENTS
+                .iter()
+                .any(|name| event.detail_element(name).is_some())
+        {
+            MessageClass::LargeDetail
+        } else if let Ok(cot_type) = CotType::parse(&event.cot_type) {
+            let client_detail =
+                event.detail_element("takv").is_some() || event.detail_element("__group").is_some();
+            if cot_type.is_friendly() && client_detail {
+                MessageClass::Pli
+            } else {
+                MessageClass::Track
+            }
+        } else {
+            MessageClass::Other
+        }
+    }
+}
+
+impl<T> SendQueueClassifier<T> for CotPriorityClassifier
+where
+    T: AsRef<[u8]>,
+{
+    fn byte_size(&self, item: &T) -> usize {
+        item.as_ref().len()
+    }
+
+    fn priority(&self, item: &T) -> QueuePriority {
+        self.classify(item).priority
+    }
+
+    fn coalesce_key(&self, item: &T) -> Option<String> {
+        self.classify(item).coalesce_key
+    }
+
+    fn message_class(&self, item: &T) -> MessageClass {
+        self.classify(item).class
+    }
+
+    fn classify(&self, item: &T) -> QueueClassification {
+        let byte_size = item.as_ref().len();
+        let Some(event) = parse_event(item.as_ref()) else {
+            return QueueClassification {
+                byte_size,
+                priority: QueuePriority::Normal,
+                coalesce_key: None,
+                class: MessageClass::Other,
+            };
+        };
+        let priority = Self::priority_for_event(&event);
+        let class = Self::message_class_for_event(&event, byte_size);
+        QueueClassification {
+            byte_size,
+            priority,
+            coalesce_key: (priority != QueuePriority::High && !event.uid.is_empty())
+                .then_some(event.uid),
+            class,
+        }
+    }
+}
+
+/// The event in a CoT XML or TAK Protocol v1 payload, if it parses.
+fn parse_event(payload: &[u8]) -> Option<CotEvent> {
+    let limits = Limits::conservative_defaults();
+    let trimmed = payload.trim_ascii_start();
+    if trimmed.first() == Some(&b'<') {
+        let xml = std::str::from_utf8(trimmed).ok()?;
+        return CotEvent::from_xml(xml, &limits).ok();
+    }
+    rustak_proto::decode_v1_event(payload, &limits).ok()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -36,6 +205,29 @@ pub enum SendQueueError {
     ZeroMaxMessages,
     #[error("send queue max_bytes must be > 0")]
     ZeroMaxBytes,
+    #[error("send queue shaping budget for {class} must have a non-zero rate and burst")]
+    ZeroShapingBudget { class: MessageClass },
+}
+
+impl CodedError for SendQueueError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroMaxMessages => ErrorCode::new("TRANSPORT", 201),
+            Self::ZeroMaxBytes => ErrorCode::new("TRANSPORT", 202),
+            Self::ZeroShapingBudget { .. } => ErrorCode::new("TRANSPORT", 203),
+        }
+    }
+}
+
+/// Per-class drain counters. `delayed` counts drains that held back at
+/// least one message of the class because its budget was spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClassTrafficMetrics {
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub delayed: u64,
+    pub dropped_messages: u64,
+    pub dropped_bytes: u64,
 }
 
 pub struct OutboundSendQueue<T, C> {
@@ -43,6 +235,8 @@ pub struct OutboundSendQueue<T, C> {
     classifier: C,
     current_bytes: usize,
     storage: QueueStorage<T>,
+    shaper: TrafficShaper,
+    events: Option<TransportEvents>,
 }
 
 impl<T, C> OutboundSendQueue<T, C>
@@ -56,6 +250,12 @@ where
         if config.max_bytes == 0 {
             return Err(SendQueueError::ZeroMaxBytes);
         }
+        let shaper = TrafficShaper::new(
+            config
+                .shaping
+                .iter()
+                .flat_map(|shaping| shaping.budgets.iter()),
+        )?;
 
         let storage = match config.mode {
             SendQueueMode::Fifo => QueueStorage::Fifo(VecDeque::new()),
@@ -68,9 +268,19 @@ where
             classifier,
             current_bytes: 0,
             storage,
+            shaper,
+            events: None,
         })
     }
 
+    /// Publishes [`TransportEvent::QueueSaturated`] and one
+    /// [`TransportEvent::FrameDropped`] per message dropped for pressure.
+    #[must_use]
+    pub fn with_events(mut self, events: TransportEvents) -> Self {
+        self.events = Some(events);
+        self
+    }
+
     #[must_use]
     pub fn mode(&self) -> SendQueueMode {
         self.config.mode.clone()
@@ -97,54 +307,56 @@ where
 
     pub fn enqueue(&mut self, item: T) -> QueueEnqueueReport {
         let mut report = QueueEnqueueReport::default();
-        let item_size = self.classifier.byte_size(&item);
+        let classification = self.classifier.classify(&item);
+        let entry = QueueEntry {
+            item,
+            bytes: classification.byte_size,
+            priority: classification.priority,
+            class: classification.class,
+            key: classification.coalesce_key,
+        };
+        self.current_bytes += entry.bytes;
 
         match &mut self.storage {
-            QueueStorage::Fifo(queue) => {
-                queue.push_back(item);
-                self.current_bytes += item_size;
-            }
-            QueueStorage::Priority(buckets) => {
-                let bucket = match self.classifier.priority(&item) {
-                    QueuePriority::High => &mut buckets.high,
-                    QueuePriority::Normal => &mut buckets.normal,
-                    QueuePriority::Low => &mut buckets.low,
-                };
-                bucket.push_back(item);
-                self.current_bytes += item_size;
-            }
+            QueueStorage::Fifo(queue) => queue.push_back(entry),
+            QueueStorage::Priority(buckets) => buckets.bucket(entry.priority).push_back(entry),
             QueueStorage::Coalesce(entries) => {
-                if let Some(key) = self.classifier.coalesce_key(&item) {
-                    if let Some(existing) = entries
+                let existing = entry.key.as_deref().and_then(|key| {
+                    entries
                         .iter_mut()
-                        .find(|entry| entry.key.as_deref() == Some(key.as_str()))
-                    {
-                        let replaced_size = self.classifier.byte_size(&existing.item);
-                        self.current_bytes = self.current_bytes.saturating_sub(replaced_size);
-                        existing.item = item;
-                        self.current_bytes += item_size;
-                        report.replaced_existing = true;
-                    } else {
-                        entries.push_back(CoalescedEntry {
-                            key: Some(key),
-                            item,
-                        });
-                        self.current_bytes += item_size;
-                    }
+                        .find(|queued| queued.key.as_deref() == Some(key))
+                });
+                if let Some(existing) = existing {
+                    self.current_bytes = self.current_bytes.saturating_sub(existing.bytes);
+                    *existing = entry;
+                    report.replaced_existing = true;
                 } else {
-                    entries.push_back(CoalescedEntry { key: None, item });
-                    self.current_bytes += item_size;
+                    entries.push_back(entry);
                 }
             }
         }
 
+        let saturated = self.len_messages() > self.config.max_messages
+            || self.current_bytes > self.config.max_bytes;
+        if let (true, Some(events)) = (saturated, &self.events) {
+            events.publish(TransportEvent::QueueSaturated {
+                queued_messages: self.len_messages(),
+                queued_bytes: self.current_bytes,
+            });
+        }
         while self.len_messages() > self.config.max_messages
             || self.current_bytes > self.config.max_bytes
         {
-            let dropped_size = match self.drop_for_pressure() {
-                Some(size) => size,
+            let (class, dropped_size) = match self.drop_for_pressure() {
+                Some(dropped) => dropped,
                 None => break,
             };
+            if let Some(events) = &self.events {
+                events.publish(TransportEvent::FrameDropped {
+                    class,
+                    bytes: dropped_size,
+                });
+            }
             report.dropped_messages += 1;
             report.dropped_bytes += dropped_size;
         }
@@ -152,52 +364,549 @@ where
         report
     }
 
+    /// Takes the next message whose class budget allows it now.
     pub fn dequeue(&mut self) -> Option<T> {
-        let maybe_item = match &mut self.storage {
-            QueueStorage::Fifo(queue) => queue.pop_front(),
-            QueueStorage::Priority(buckets) => buckets.pop_front(),
-            QueueStorage::Coalesce(entries) => entries.pop_front().map(|entry| entry.item),
+        self.dequeue_at(Instant::now())
+    }
+
+    /// Takes the next message, in queue order, whose class budget allows it
+    /// at `now`. Messages of spent classes stay queued, so one class cannot
+    /// monopolize the link; `None` with a non-empty queue means every
+    /// remaining class is waiting for [`Self::next_shaped_send`].
+    pub fn dequeue_at(&mut self, now: Instant) -> Option<T> {
+        self.dequeue_entry_at(now).map(|entry| entry.item)
+    }
+
+    fn dequeue_entry_at(&mut self, now: Instant) -> Option<QueueEntry<T>> {
+        let Self {
+            storage, shaper, ..
+        } = self;
+        shaper.begin_drain(now);
+        let mut admit = |entry: &QueueEntry<T>| shaper.admits(entry.class, entry.bytes);
+        let maybe_entry = match storage {
+            QueueStorage::Fifo(queue) => take_first(queue, &mut admit),
+            QueueStorage::Priority(buckets) => buckets.take_first(&mut admit),
+            QueueStorage::Coalesce(entries) => {
+                QueuePriority::ALL.into_iter().find_map(|priority| {
+                    let index = entries
+                        .iter()
+                        .position(|entry| entry.priority == priority && admit(entry))?;
+                    entries.remove(index)
+                })
+            }
         };
+        shaper.end_drain();
 
-        if let Some(item) = maybe_item {
-            let bytes = self.classifier.byte_size(&item);
-            self.current_bytes = self.current_bytes.saturating_sub(bytes);
-            Some(item)
-        } else {
-            None
-        }
+        let entry = maybe_entry?;
+        self.current_bytes = self.current_bytes.saturating_sub(entry.bytes);
+        self.shaper.consume(entry.class, entry.bytes);
+        Some(entry)
+    }
+
+    /// When a message held back by the last drain can next be sent, or
+    /// `None` if nothing was held back.
+    #[must_use]
+    pub fn next_shaped_send(&self) -> Option<Instant> {
+        self.shaper.next_send()
+    }
+
+    #[must_use]
+    pub fn class_metrics(&self, class: MessageClass) -> ClassTrafficMetrics {
+        self.shaper.classes[class_index(class)].metrics
     }
 
-    fn drop_for_pressure(&mut self) -> Option<usize> {
-        let maybe_item = match &mut self.storage {
+    /// Metrics for every class, including classes without a budget.
+    #[must_use]
+    pub fn traffic_metrics(&self) -> BTreeMap<MessageClass, ClassTrafficMetrics> {
+        MessageClass::ALL
+            .into_iter()
+            .map(|class| (class, self.class_metrics(class)))
+            .collect()
+    }
+
+    fn drop_for_pressure(&mut self) -> Option<(MessageClass, usize)> {
+        let maybe_entry = match &mut self.storage {
             QueueStorage::Fifo(queue) => queue.pop_front(),
             QueueStorage::Priority(buckets) => buckets.pop_for_pressure(),
-            QueueStorage::Coalesce(entries) => entries.pop_front().map(|entry| entry.item),
+            QueueStorage::Coalesce(entries) => {
+                QueuePriority::ALL.into_iter().rev().find_map(|priority| {
+                    let index = entries
+                        .iter()
+                        .position(|entry| entry.priority == priority)?;
+                    entries.remove(index)
+                })
+            }
         };
 
-        maybe_item.map(|item| {
-            let bytes = self.classifier.byte_size(&item);
-            self.current_bytes = self.current_bytes.saturating_sub(bytes);
-            bytes
+        maybe_entry.map(|entry| {
+            self.current_bytes = self.current_bytes.saturating_sub(entry.bytes);
+            let metrics = &mut self.shaper.classes[class_index(entry.class)].metrics;
+            metrics.dropped_messages += 1;
+            metrics.dropped_bytes += entry.bytes as u64;
+            (entry.class, entry.bytes)
+        })
+    }
+}
+
+fn take_first<T>(queue: &mut VecDeque<T>, admit: &mut impl FnMut(&T) -> bool) -> Option<T> {
+    let index = queue.iter().position(admit)?;
+    queue.remove(index)
+}
+
+const fn class_index(class: MessageClass) -> usize {
+    match class {
+        MessageClass::Pli => 0,
+        MessageClass::Chat => 1,
+        MessageClass::Track => 2,
+        MessageClass::LargeDetail => 3,
+        MessageClass::Other => 4,
+    }
+}
+
+/// Token buckets and counters for every [`MessageClass`].
+struct TrafficShaper {
+    classes: [ClassState; MessageClass::ALL.len()],
+    drained_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct ClassState {
+    bucket: Option<TokenBucket>,
+    metrics: ClassTrafficMetrics,
+    /// Smallest message held back in the current or last drain.
+    blocked_bytes: Option<usize>,
+}
+
+struct TokenBucket {
+    bytes_per_second: f64,
+    burst_bytes: f64,
+    tokens: f64,
+}
+
+impl TrafficShaper {
+    fn new<'a>(
+        budgets: impl Iterator<Item = (&'a MessageClass, &'a ClassBudget)>,
+    ) -> Result<Self, SendQueueError> {
+        let mut classes: [ClassState; MessageClass::ALL.len()] = Default::default();
+        for (class, budget) in budgets {
+            if budget.bytes_per_second == 0 || budget.burst_bytes == 0 {
+                return Err(SendQueueError::ZeroShapingBudget { class: *class });
+            }
+            classes[class_index(*class)].bucket = Some(TokenBucket {
+                bytes_per_second: budget.bytes_per_second as f64,
+                burst_bytes: budget.burst_bytes as f64,
+                tokens: budget.burst_bytes as f64,
+            });
+        }
+        Ok(Self {
+            classes,
+            drained_at: None,
         })
     }
+
+    fn begin_drain(&mut self, now: Instant) {
+        let elapsed = self
+            .drained_at
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.drained_at = Some(self.drained_at.map_or(now, |last| last.max(now)));
+        for class in &mut self.classes {
+            class.blocked_bytes = None;
+            if let Some(bucket) = &mut class.bucket {
+                bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * bucket.bytes_per_second)
+                    .min(bucket.burst_bytes);
+            }
+        }
+    }
+
+    /// A message larger than the burst goes out once the bucket is full, and
+    /// leaves the bucket in debt.
+    fn admits(&mut self, class: MessageClass, bytes: usize) -> bool {
+        let state = &mut self.classes[class_index(class)];
+        let Some(bucket) = &state.bucket else {
+            return true;
+        };
+        if bucket.tokens >= (bytes as f64).min(bucket.burst_bytes) {
+            return true;
+        }
+        state.blocked_bytes = Some(state.blocked_bytes.map_or(bytes, |held| held.min(bytes)));
+        false
+    }
+
+    fn end_drain(&mut self) {
+        for class in &mut self.classes {
+            if class.blocked_bytes.is_some() {
+                class.metrics.delayed += 1;
+            }
+        }
+    }
+
+    fn consume(&mut self, class: MessageClass, bytes: usize) {
+        let state = &mut self.classes[class_index(class)];
+        if let Some(bucket) = &mut state.bucket {
+            bucket.tokens -= bytes as f64;
+        }
+        state.metrics.sent_messages += 1;
+        state.metrics.sent_bytes += bytes as u64;
+    }
+
+    fn next_send(&self) -> Option<Instant> {
+        let drained_at = self.drained_at?;
+        self.classes
+            .iter()
+            .filter_map(|class| {
+                let bucket = class.bucket.as_ref()?;
+                let needed = (class.blocked_bytes? as f64).min(bucket.burst_bytes) - bucket.tokens;
+                Some(
+                    drained_at + Duration::from_secs_f64(needed.max(0.0) / bucket.bytes_per_second),
+                )
+            })
+            .min()
+    }
+}
+
+/// Tower `Service` front for a shared [`OutboundSendQueue`].
+///
+/// Each call enqueues one item and resolves immediately with the
+/// [`QueueEnqueueReport`]; the writer drains the same queue through
+/// [`SendQueueService::queue`].
+#[cfg(feature = "tower")]
+pub struct SendQueueService<T, C> {
+    queue: Arc<Mutex<OutboundSendQueue<T, C>>>,
+}
+
+#[cfg(feature = "tower")]
+impl<T, C> SendQueueService<T, C> {
+    #[must_use]
+    pub fn new(queue: OutboundSendQueue<T, C>) -> Self {
+        Self {
+            queue: Arc::new(Mutex::new(queue)),
+        }
+    }
+
+    #[must_use]
+    pub fn queue(&self) -> Arc<Mutex<OutboundSendQueue<T, C>>> {
+        Arc::clone(&self.queue)
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<T, C> Clone for SendQueueService<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<T, C> tower_service::Service<T> for SendQueueService<T, C>
+where
+    C: SendQueueClassifier<T>,
+{
+    type Response = QueueEnqueueReport;
+    type Error = std::convert::Infallible;
+    type Future = std::future::Ready<Result<QueueEnqueueReport, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // The queue sheds load by dropping under pressure rather than by
+        // refusing new items, so it is always ready.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, item: T) -> Self::Future {
+        let report = self
+            .queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .enqueue(item);
+        std::future::ready(Ok(report))
+    }
+}
+
+/// Most messages [`QueueDriver`] hands to one vectored write.
+const DRAIN_BATCH_FRAMES: usize = 64;
+
+/// Enqueue and drain counters for a [`QueueDriver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrainStats {
+    pub enqueued: u64,
+    /// Enqueues that replaced a queued message with the same coalesce key.
+    pub coalesced: u64,
+    /// Messages shed under queue pressure.
+    pub dropped_messages: u64,
+    pub dropped_bytes: u64,
+    pub sent_messages: u64,
+    pub sent_bytes: u64,
+    pub sent_high: u64,
+    pub sent_normal: u64,
+    pub sent_low: u64,
+    /// Times the driver waited for a class budget with messages queued.
+    pub shaped_waits: u64,
+    /// Deepest the queue has been, in messages, right after an enqueue.
+    pub peak_depth: usize,
+}
+
+impl DrainStats {
+    fn record_sent(&mut self, priority: QueuePriority, bytes: usize) {
+        self.sent_messages += 1;
+        self.sent_bytes += bytes as u64;
+        match priority {
+            QueuePriority::High => self.sent_high += 1,
+            QueuePriority::Normal => self.sent_normal += 1,
+            QueuePriority::Low => self.sent_low += 1,
+        }
+    }
+}
+
+struct DriverState<T, C> {
+    queue: OutboundSendQueue<T, C>,
+    stats: DrainStats,
+    closed: bool,
+}
+
+struct DriverShared<T, C> {
+    state: Mutex<DriverState<T, C>>,
+    wake: Notify,
+}
+
+impl<T, C> DriverShared<T, C> {
+    fn lock(&self) -> MutexGuard<'_, DriverState<T, C>> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Producer side of a [`QueueDriver`]; clones feed the same queue.
+pub struct QueueHandle<T, C> {
+    shared: Arc<DriverShared<T, C>>,
+}
+
+impl<T, C> Clone for QueueHandle<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T, C> QueueHandle<T, C>
+where
+    C: SendQueueClassifier<T>,
+{
+    /// Queues `item` and wakes the driver. The queue sheds load itself, so
+    /// this never waits on the link.
+    pub fn enqueue(&self, item: T) -> QueueEnqueueReport {
+        let report = {
+            let mut state = self.shared.lock();
+            let report = state.queue.enqueue(item);
+            let depth = state.queue.len_messages();
+            let stats = &mut state.stats;
+            stats.enqueued += 1;
+            stats.coalesced += u64::from(report.replaced_existing);
+            stats.dropped_messages += report.dropped_messages as u64;
+            stats.dropped_bytes += report.dropped_bytes as u64;
+            stats.peak_depth = stats.peak_depth.max(depth);
+            report
+        };
+        self.shared.wake.notify_one();
+        report
+    }
+
+    #[must_use]
+    pub fn len_messages(&self) -> usize {
+        self.shared.lock().queue.len_messages()
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> DrainStats {
+        self.shared.lock().stats
+    }
+
+    #[must_use]
+    pub fn traffic_metrics(&self) -> BTreeMap<MessageClass, ClassTrafficMetrics> {
+        self.shared.lock().queue.traffic_metrics()
+    }
+
+    /// Lets [`QueueDriver::run`] return once what is already queued has
+    /// been sent.
+    pub fn close(&self) {
+        self.shared.lock().closed = true;
+        self.shared.wake.notify_one();
+    }
+}
+
+/// Drains an [`OutboundSendQueue`] into a [`TransportSender`].
+///
+/// Producers enqueue through [`QueueHandle`]s while [`QueueDriver::run`]
+/// sends in the order the queue's [`SendQueueMode`] gives, waits out class
+/// budgets, and honours the sender's write batching. Backpressure comes from
+/// the sender: while a write is pending the queue keeps absorbing, and
+/// sheds, new messages.
+pub struct QueueDriver<T, C> {
+    shared: Arc<DriverShared<T, C>>,
+    on_sent: Option<SentHook<T>>,
+}
+
+type SentHook<T> = Box<dyn FnMut(&T) + Send>;
+
+impl<T, C> QueueDriver<T, C>
+where
+    T: AsRef<[u8]>,
+    C: SendQueueClassifier<T>,
+{
+    #[must_use]
+    pub fn new(queue: OutboundSendQueue<T, C>) -> Self {
+        Self {
+            shared: Arc::new(DriverShared {
+                state: Mutex::new(DriverState {
+                    queue,
+                    stats: DrainStats::default(),
+                    closed: false,
+                }),
+                wake: Notify::new(),
+            }),
+            on_sent: None,
+        }
+    }
+
+    /// Calls `hook` with each message once the sender has taken its frame,
+    /// e.g. to measure how long messages wait in the queue.
+    #[must_use]
+    pub fn on_sent(mut self, hook: impl FnMut(&T) + Send + 'static) -> Self {
+        self.on_sent = Some(Box::new(hook));
+        self
+    }
+
+    #[must_use]
+    pub fn handle(&self) -> QueueHandle<T, C> {
+        QueueHandle {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> DrainStats {
+        self.shared.lock().stats
+    }
+
+    /// Sends every message the queue releases now, up to 64 per
+    /// [`TransportSender::send_frames`] call, then flushes the sender unless
+    /// it is holding a write batch. Returns how many were sent.
+    pub async fn drain_ready<W>(
+        &mut self,
+        sender: &mut TransportSender<W>,
+    ) -> Result<usize, TransportComposeError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut sent = 0;
+        loop {
+            let batch = std::iter::from_fn(|| self.next_item())
+                .take(DRAIN_BATCH_FRAMES)
+                .collect::<Vec<_>>();
+            if batch.is_empty() {
+                break;
+            }
+            sent += batch.len();
+            self.send(sender, batch).await?;
+        }
+        if sent > 0 && sender.flush_deadline().is_none() {
+            sender.flush().await?;
+        }
+        Ok(sent)
+    }
+
+    /// Drains until [`QueueHandle::close`] is called and the queue is
+    /// empty, then flushes and returns the final statistics. A send error
+    /// stops the driver; the batch being written is lost.
+    pub async fn run<W>(
+        &mut self,
+        sender: &mut TransportSender<W>,
+    ) -> Result<DrainStats, TransportComposeError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        loop {
+            self.drain_ready(sender).await?;
+            let (closed, empty, shaped) = {
+                let state = self.shared.lock();
+                (
+                    state.closed,
+                    state.queue.is_empty(),
+                    state.queue.next_shaped_send(),
+                )
+            };
+            if closed && empty {
+                sender.flush().await?;
+                return Ok(self.stats());
+            }
+            if let Some(resume) = shaped {
+                self.shared.lock().stats.shaped_waits += 1;
+                tokio::select! {
+                    () = tokio::time::sleep_until(resume.into()) => {}
+                    () = self.shared.wake.notified() => {}
+                    flushed = sender.flush_when_due() => flushed?,
+                }
+            } else {
+                tokio::select! {
+                    () = self.shared.wake.notified() => {}
+                    flushed = sender.flush_when_due() => flushed?,
+                }
+            }
+        }
+    }
+
+    fn next_item(&self) -> Option<(T, QueuePriority)> {
+        let entry = self.shared.lock().queue.dequeue_entry_at(Instant::now())?;
+        Some((entry.item, entry.priority))
+    }
+
+    async fn send<W>(
+        &mut self,
+        sender: &mut TransportSender<W>,
+        batch: Vec<(T, QueuePriority)>,
+    ) -> Result<(), TransportComposeError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let payloads = batch
+            .iter()
+            .map(|(item, _)| item.as_ref())
+            .collect::<Vec<_>>();
+        sender.send_frames(&payloads).await?;
+        for (item, priority) in &batch {
+            self.shared
+                .lock()
+                .stats
+                .record_sent(*priority, item.as_ref().len());
+            if let Some(hook) = &mut self.on_sent {
+                hook(item);
+            }
+        }
+        Ok(())
+    }
 }
 
 enum QueueStorage<T> {
-    Fifo(VecDeque<T>),
+    Fifo(VecDeque<QueueEntry<T>>),
     Priority(PriorityBuckets<T>),
-    Coalesce(VecDeque<CoalescedEntry<T>>),
+    Coalesce(VecDeque<QueueEntry<T>>),
 }
 
-struct CoalescedEntry<T> {
-    key: Option<String>,
+/// A queued item with its [`QueueClassification`].
+struct QueueEntry<T> {
     item: T,
+    bytes: usize,
+    priority: QueuePriority,
+    class: MessageClass,
+    key: Option<String>,
 }
 
 struct PriorityBuckets<T> {
-    high: VecDeque<T>,
-    normal: VecDeque<T>,
-    low: VecDeque<T>,
+    high: VecDeque<QueueEntry<T>>,
+    normal: VecDeque<QueueEntry<T>>,
+    low: VecDeque<QueueEntry<T>>,
 }
 
 impl<T> PriorityBuckets<T> {
@@ -205,14 +914,24 @@ impl<T> PriorityBuckets<T> {
         self.high.len() + self.normal.len() + self.low.len()
     }
 
-    fn pop_front(&mut self) -> Option<T> {
-        self.high
-            .pop_front()
-            .or_else(|| self.normal.pop_front())
-            .or_else(|| self.low.pop_front())
+    fn bucket(&mut self, priority: QueuePriority) -> &mut VecDeque<QueueEntry<T>> {
+        match priority {
+            QueuePriority::High => &mut self.high,
+            QueuePriority::Normal => &mut self.normal,
+            QueuePriority::Low => &mut self.low,
+        }
+    }
+
+    fn take_first(
+        &mut self,
+        admit: &mut impl FnMut(&QueueEntry<T>) -> bool,
+    ) -> Option<QueueEntry<T>> {
+        take_first(&mut self.high, admit)
+            .or_else(|| take_first(&mut self.normal, admit))
+            .or_else(|| take_first(&mut self.low, admit))
     }
 
-    fn pop_for_pressure(&mut self) -> Option<T> {
+    fn pop_for_pressure(&mut self) -> Option<QueueEntry<T>> {
         self.low
             .pop_front()
             .or_else(|| self.normal.pop_front())
@@ -232,8 +951,19 @@ impl<T> Default for PriorityBuckets<T> {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use tokio::io::AsyncReadExt;
+
     use super::{
-        OutboundSendQueue, QueuePriority, SendQueueClassifier, SendQueueConfig, SendQueueMode,
+        ClassTrafficMetrics, CotPriorityClassifier, DrainStats, OutboundSendQueue, QueueDriver,
+        QueuePriority, SendQueueClassifier, SendQueueConfig, SendQueueError, SendQueueMode,
+    };
+    use crate::{
+        ClassBudget, MessageClass, TrafficShapingConfig, TransportConfig, TransportEvent,
+        TransportEvents, TransportSender,
     };
 
     #[derive(Debug, Clone, PartialEq, Eq)]
@@ -259,6 +989,16 @@ mod tests {
         fn coalesce_key(&self, item: &TestItem) -> Option<String> {
             item.coalesce_key.map(str::to_string)
         }
+
+        fn message_class(&self, item: &TestItem) -> MessageClass {
+            if item.id.starts_with("pli") {
+                MessageClass::Pli
+            } else if item.id.starts_with("chat") {
+                MessageClass::Chat
+            } else {
+                MessageClass::Other
+            }
+        }
     }
 
     fn config(max_messages: usize, max_bytes: usize, mode: SendQueueMode) -> SendQueueConfig {
@@ -266,6 +1006,7 @@ mod tests {
             max_messages,
             max_bytes,
             mode,
+            shaping: None,
         }
     }
 
@@ -303,6 +1044,42 @@ mod tests {
         assert!(queue.is_empty());
     }
 
+    #[test]
+    fn pressure_drops_are_published_as_transport_events() {
+        let events = TransportEvents::default();
+        let mut receiver = events.subscribe();
+        let mut queue = OutboundSendQueue::new(config(4, 25, SendQueueMode::Fifo), TestClassifier)
+            .expect("config should be valid")
+            .with_events(events);
+
+        queue.enqueue(test_item("chat-1", 10, QueuePriority::Normal, None));
+        queue.enqueue(test_item("pli-1", 10, QueuePriority::Normal, None));
+        assert!(receiver.is_empty(), "no events below the limits");
+        queue.enqueue(test_item("other", 20, QueuePriority::Normal, None));
+
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(
+            received,
+            [
+                TransportEvent::QueueSaturated {
+                    queued_messages: 3,
+                    queued_bytes: 40,
+                },
+                TransportEvent::FrameDropped {
+                    class: MessageClass::Chat,
+                    bytes: 10,
+                },
+                TransportEvent::FrameDropped {
+                    class: MessageClass::Pli,
+                    bytes: 10,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn priority_mode_prefers_high_priority_and_drops_lowest_first() {
         let mut queue =
@@ -364,4 +1141,479 @@ mod tests {
         let item = queue.dequeue().expect("remaining item");
         assert_eq!(item.id, "b");
     }
+
+    #[test]
+    fn shaping_holds_back_spent_class_without_blocking_others() {
+        let mut config = config(8, 256, SendQueueMode::Fifo);
+        config.shaping = Some(TrafficShapingConfig::default().with_budget(
+            MessageClass::Pli,
+            ClassBudget {
+                bytes_per_second: 100,
+                burst_bytes: 20,
+            },
+        ));
+        let mut queue = OutboundSendQueue::new(config, TestClassifier).expect("valid config");
+        queue.enqueue(test_item("pli-1", 20, QueuePriority::Normal, None));
+        queue.enqueue(test_item("pli-2", 20, QueuePriority::Normal, None));
+        queue.enqueue(test_item("chat-1", 10, QueuePriority::Normal, None));
+
+        let start = Instant::now();
+        assert_eq!(queue.dequeue_at(start).expect("pli-1").id, "pli-1");
+        assert_eq!(queue.dequeue_at(start).expect("chat-1").id, "chat-1");
+        assert!(queue.dequeue_at(start).is_none());
+        assert_eq!(queue.len_messages(), 1);
+
+        let resume = queue.next_shaped_send().expect("pli is waiting");
+        assert_eq!(resume, start + Duration::from_millis(200));
+        assert!(queue
+            .dequeue_at(resume - Duration::from_millis(50))
+            .is_none());
+        assert_eq!(queue.dequeue_at(resume).expect("pli-2").id, "pli-2");
+        assert!(queue.next_shaped_send().is_none());
+
+        assert_eq!(
+            queue.class_metrics(MessageClass::Pli),
+            ClassTrafficMetrics {
+                sent_messages: 2,
+                sent_bytes: 40,
+                delayed: 3,
+                dropped_messages: 0,
+                dropped_bytes: 0,
+            }
+        );
+        assert_eq!(queue.class_metrics(MessageClass::Chat).sent_messages, 1);
+        assert_eq!(queue.class_metrics(MessageClass::Chat).delayed, 0);
+    }
+
+    /// [`TestClassifier`] that counts how often an item is classified.
+    #[derive(Debug, Clone, Default)]
+    struct CountingClassifier(Arc<AtomicUsize>);
+
+    impl SendQueueClassifier<TestItem> for CountingClassifier {
+        fn byte_size(&self, item: &TestItem) -> usize {
+            TestClassifier.byte_size(item)
+        }
+
+        fn priority(&self, item: &TestItem) -> QueuePriority {
+            TestClassifier.priority(item)
+        }
+
+        fn coalesce_key(&self, item: &TestItem) -> Option<String> {
+            TestClassifier.coalesce_key(item)
+        }
+
+        fn message_class(&self, item: &TestItem) -> MessageClass {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            TestClassifier.message_class(item)
+        }
+    }
+
+    #[test]
+    fn items_are_classified_once_at_enqueue() {
+        for mode in [
+            SendQueueMode::Fifo,
+            SendQueueMode::Priority,
+            SendQueueMode::CoalesceLatestByUid,
+        ] {
+            let mut config = config(8, 256, mode);
+            config.shaping = Some(TrafficShapingConfig::default().with_budget(
+                MessageClass::Pli,
+                ClassBudget {
+                    bytes_per_second: 100,
+                    burst_bytes: 20,
+                },
+            ));
+            let classifier = CountingClassifier::default();
+            let calls = Arc::clone(&classifier.0);
+            let mut queue = OutboundSendQueue::new(config, classifier).expect("valid config");
+            queue.enqueue(test_item("pli-1", 20, QueuePriority::Normal, Some("a")));
+            queue.enqueue(test_item("pli-2", 20, QueuePriority::Normal, Some("b")));
+            queue.enqueue(test_item("chat-1", 10, QueuePriority::High, None));
+
+            let start = Instant::now();
+            while queue.dequeue_at(start).is_some() {}
+            assert!(queue.dequeue_at(start).is_none());
+            let resume = queue.next_shaped_send().expect("a pli is waiting");
+            assert!(queue.dequeue_at(resume).is_some());
+            assert!(queue.is_empty());
+            assert_eq!(calls.load(Ordering::Relaxed), 3);
+        }
+    }
+
+    #[test]
+    fn pressure_drops_are_counted_per_class() {
+        let mut queue = OutboundSendQueue::new(config(2, 128, SendQueueMode::Fifo), TestClassifier)
+            .expect("config should be valid");
+
+        queue.enqueue(test_item("chat-1", 7, QueuePriority::Normal, None));
+        queue.enqueue(test_item("pli-1", 5, QueuePriority::Normal, None));
+        queue.enqueue(test_item("pli-2", 5, QueuePriority::Normal, None));
+        queue.enqueue(test_item("pli-3", 5, QueuePriority::Normal, None));
+
+        let metrics = queue.traffic_metrics();
+        assert_eq!(metrics.len(), MessageClass::ALL.len());
+        assert_eq!(metrics[&MessageClass::Chat].dropped_messages, 1);
+        assert_eq!(metrics[&MessageClass::Chat].dropped_bytes, 7);
+        assert_eq!(metrics[&MessageClass::Pli].dropped_messages, 1);
+        assert_eq!(metrics[&MessageClass::Pli].dropped_bytes, 5);
+    }
+
+    #[test]
+    fn shaping_rejects_zero_budget() {
+        let mut config = config(8, 256, SendQueueMode::Fifo);
+        config.shaping = Some(TrafficShapingConfig::default().with_budget(
+            MessageClass::Track,
+            ClassBudget {
+                bytes_per_second: 0,
+                burst_bytes: 64,
+            },
+        ));
+
+        assert!(matches!(
+            OutboundSendQueue::new(config, TestClassifier),
+            Err(SendQueueError::ZeroShapingBudget {
+                class: MessageClass::Track
+            })
+        ));
+    }
+
+    #[test]
+    fn coalesce_mode_drains_by_priority_and_sheds_low_priority_first() {
+        let mut queue = OutboundSendQueue::new(
+            config(3, 256, SendQueueMode::CoalesceLatestByUid),
+            TestClassifier,
+        )
+        .expect("config should be valid");
+
+        queue.enqueue(test_item("pli-a", 8, QueuePriority::Low, Some("uid-a")));
+        queue.enqueue(test_item(
+            "track-b",
+            8,
+            QueuePriority::Normal,
+            Some("uid-b"),
+        ));
+        queue.enqueue(test_item("chat-1", 8, QueuePriority::High, None));
+        let report = queue.enqueue(test_item("chat-2", 8, QueuePriority::High, None));
+
+        assert_eq!(report.dropped_messages, 1);
+        let drained: Vec<_> = std::iter::from_fn(|| queue.dequeue())
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(drained, ["chat-1", "chat-2", "track-b"]);
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    struct PayloadClassifier;
+
+    impl SendQueueClassifier<Vec<u8>> for PayloadClassifier {
+        fn byte_size(&self, item: &Vec<u8>) -> usize {
+            item.len()
+        }
+
+        fn priority(&self, item: &Vec<u8>) -> QueuePriority {
+            if item.starts_with(b"chat") {
+                QueuePriority::High
+            } else {
+                QueuePriority::Normal
+            }
+        }
+
+        fn coalesce_key(&self, item: &Vec<u8>) -> Option<String> {
+            let text = std::str::from_utf8(item).ok()?;
+            text.strip_prefix("pli:")
+                .and_then(|rest| rest.split('#').next())
+                .map(str::to_owned)
+        }
+    }
+
+    fn driver(max_messages: usize) -> QueueDriver<Vec<u8>, PayloadClassifier> {
+        let queue = OutboundSendQueue::new(
+            config(max_messages, 1024, SendQueueMode::CoalesceLatestByUid),
+            PayloadClassifier,
+        )
+        .expect("config should be valid");
+        QueueDriver::new(queue)
+    }
+
+    fn lines(written: &[u8]) -> Vec<&str> {
+        std::str::from_utf8(written)
+            .expect("utf-8 frames")
+            .lines()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn driver_sends_chat_first_and_coalesces_positions() {
+        let driver = driver(8);
+        let handle = driver.handle();
+        handle.enqueue(b"pli:alpha#1".to_vec());
+        handle.enqueue(b"pli:bravo#1".to_vec());
+        handle.enqueue(b"pli:alpha#2".to_vec());
+        handle.enqueue(b"chat hello".to_vec());
+        handle.close();
+
+        let mut sender = TransportSender::new(Vec::new(), &TransportConfig::default())
+            .expect("config should be valid");
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let observed = Arc::clone(&sent);
+        let mut driver = driver.on_sent(move |item: &Vec<u8>| {
+            observed.lock().expect("hook").push(item.clone());
+        });
+        let stats = driver.run(&mut sender).await.expect("drain");
+
+        assert_eq!(
+            lines(&sender.into_inner()),
+            ["chat hello", "pli:alpha#2", "pli:bravo#1"]
+        );
+        assert_eq!(sent.lock().expect("hook").len(), 3);
+        assert_eq!(
+            stats,
+            DrainStats {
+                enqueued: 4,
+                coalesced: 1,
+                sent_messages: 3,
+                sent_bytes: 32,
+                sent_high: 1,
+                sent_normal: 2,
+                peak_depth: 3,
+                ..DrainStats::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn driver_wakes_for_new_messages_until_closed() {
+        let mut driver = driver(2);
+        let handle = driver.handle();
+        let producer = handle.clone();
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut sender = TransportSender::new(client, &TransportConfig::default())
+            .expect("config should be valid");
+        let task = tokio::spawn(async move {
+            let stats = driver.run(&mut sender).await;
+            (stats, sender)
+        });
+
+        producer.enqueue(b"pli:alpha#1".to_vec());
+        let mut first = vec![0; b"pli:alpha#1\n".len()];
+        server.read_exact(&mut first).await.expect("first frame");
+        assert_eq!(first, b"pli:alpha#1\n");
+
+        producer.enqueue(b"chat one".to_vec());
+        producer.enqueue(b"chat two".to_vec());
+        producer.enqueue(b"chat three".to_vec());
+        handle.close();
+        let (stats, sender) = task.await.expect("driver task");
+        let stats = stats.expect("drain");
+        drop(sender);
+
+        let mut rest = Vec::new();
+        server
+            .read_to_end(&mut rest)
+            .await
+            .expect("remaining frames");
+        assert_eq!(lines(&rest), ["chat two", "chat three"]);
+        assert_eq!(stats.sent_messages, 3);
+        assert_eq!(stats.dropped_messages, 1);
+        assert_eq!(handle.stats(), stats);
+    }
+
+    fn cot(uid: &str, cot_type: &str) -> Vec<u8> {
+        cot_with_detail(uid, cot_type, "")
+    }
+
+    fn cot_with_detail(uid: &str, cot_type: &str, detail: &str) -> Vec<u8> {
+        format!(
+            "<?xml version=\"1.0\"?><event version=\"2.0\" uid=\"{uid}\" type=\"{cot_type}\" how=\"h-e\" time=\"2024-03-01T12:00:00Z\" start=\"2024-03-01T12:00:00Z\" stale=\"2024-03-01T12:05:00Z\"><point lat=\"51.5\" lon=\"-0.1\" hae=\"0\" ce=\"10\" le=\"10\"/><detail>{detail}</detail></event>"
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn cot_classifier_assigns_bandwidth_classes() {
+        let classifier = CotPriorityClassifier;
+        let class = |payload: &Vec<u8>| classifier.message_class(payload);
+
+        let self_pli = cot_with_detail(
+            "ANDROID-1",
+            "a-f-G-U-C",
+            "<takv platform=\"ATAK-CIV\"/><contact callsign=\"ALPHA\"/><__group name=\"Cyan\"/>",
+        );
+        assert_eq!(class(&self_pli), MessageClass::Pli);
+        let encoded = rustak_proto::encode_v1_payload(&self_pli).expect("encode pli");
+        assert_eq!(class(&encoded), MessageClass::Pli);
+
+        assert_eq!(class(&cot("sensor-7", "a-h-G")), MessageClass::Track);
+        assert_eq!(
+            class(&cot_with_detail(
+                "hostile-1",
+                "a-h-G-U-C",
+                "<__group name=\"Red\" role=\"Team Member\"/>"
+            )),
+            MessageClass::Track
+        );
+        assert_eq!(class(&cot("odd-1", "a-q-G")), MessageClass::Other);
+        assert_eq!(class(&cot("odd-2", "a-f")), MessageClass::Other);
+        assert_eq!(
+            class(&cot_with_detail(
+                "marker-1",
+                "a-u-G",
+                "<contact callsign=\"TGT\"/>"
+            )),
+            MessageClass::Track
+        );
+        assert_eq!(
+            class(&cot("GeoChat.ANDROID-1.All.1", "b-t-f")),
+            MessageClass::Chat
+        );
+        assert_eq!(
+            class(&cot_with_detail(
+                "photo-1",
+                "b-i-x-i",
+                "<image mime=\"image/jpeg\">AAAA</image>"
+            )),
+            MessageClass::LargeDetail
+        );
+        let remarks = format!(
+            "<remarks>{}</remarks>",
+            "x".repeat(super::LARGE_DETAIL_BYTES)
+        );
+        assert_eq!(
+            class(&cot_with_detail("ANDROID-1", "a-f-G-U-C", &remarks)),
+            MessageClass::LargeDetail
+        );
+        assert_eq!(
+            class(&cot("ANDROID-1-9-1-1", "b-a-o-tbl")),
+            MessageClass::Other
+        );
+        assert_eq!(class(&b"not cot".to_vec()), MessageClass::Other);
+    }
+
+    #[test]
+    fn cot_classifier_reads_xml_and_tak_protocol_payloads() {
+        let classifier = CotPriorityClassifier;
+        let emergency = cot("ANDROID-1-9-1-1", "b-a-o-tbl");
+        let chat = rustak_proto::encode_v1_payload(&cot("GeoChat.ANDROID-1.All", "b-t-f"))
+            .expect("encode chat");
+        let position = rustak_proto::encode_v1_payload(&cot("ANDROID-1", "a-f-G-U-C"))
+            .expect("encode position");
+
+        assert_eq!(classifier.priority(&emergency), QueuePriority::High);
+        assert_eq!(classifier.coalesce_key(&emergency), None);
+        assert_eq!(classifier.priority(&chat), QueuePriority::High);
+        assert_eq!(classifier.coalesce_key(&chat), None);
+        assert_eq!(classifier.message_class(&chat), MessageClass::Chat);
+        assert_eq!(classifier.priority(&position), QueuePriority::Normal);
+        assert_eq!(
+            classifier.coalesce_key(&position).as_deref(),
+            Some("ANDROID-1")
+        );
+        assert_eq!(
+            classifier
+                .coalesce_key(&cot("ANDROID-2", "a-f-G-U-C"))
+                .as_deref(),
+            Some("ANDROID-2")
+        );
+
+        let garbage = b"not cot".to_vec();
+        assert_eq!(classifier.priority(&garbage), QueuePriority::Normal);
+        assert_eq!(classifier.coalesce_key(&garbage), None);
+
+        let time =
+            rustak_core::TimestampUtc::from_unix_seconds_nanos(1_700_000_000, 0).expect("time");
+        let geochat = rustak_core::ChatMessage {
+            message_id: "m-1".to_owned(),
+            sender_uid: "ANDROID-1".to_owned(),
+            sender_callsign: "ALPHA".to_owned(),
+            destination: rustak_core::ChatDestination::AllChatRooms,
+            text: "moving".to_owned(),
+            time,
+        }
+        .to_event(rustak_core::Position::new(51.5, -0.1).expect("point"), time);
+        assert_eq!(
+            CotPriorityClassifier::priority_for_event(&geochat),
+            QueuePriority::High
+        );
+        assert_eq!(
+            classifier.priority(&geochat.to_xml().into_bytes()),
+            QueuePriority::High
+        );
+    }
+
+    #[test]
+    fn cot_classifier_sends_emergencies_ahead_without_coalescing() {
+        let mut queue = OutboundSendQueue::new(
+            config(8, 4096, SendQueueMode::CoalesceLatestByUid),
+            CotPriorityClassifier,
+        )
+        .expect("config should be valid");
+
+        queue.enqueue(cot("ANDROID-1", "a-f-G-U-C"));
+        queue.enqueue(cot("ANDROID-1-9-1-1", "b-a-o-tbl"));
+        let report = queue.enqueue(cot("ANDROID-1", "a-f-G-U-C"));
+        assert!(report.replaced_existing);
+        let report = queue.enqueue(cot("ANDROID-1-9-1-1", "b-a-o-can"));
+        assert!(!report.replaced_existing);
+
+        let types: Vec<_> = std::iter::from_fn(|| queue.dequeue())
+            .map(|payload| {
+                let xml = String::from_utf8(payload).expect("utf-8");
+                let start = xml.find("type=\"").expect("type") + "type=\"".len();
+                xml[start..start + xml[start..].find('"').expect("quote")].to_owned()
+            })
+            .collect();
+        assert_eq!(types, ["b-a-o-tbl", "b-a-o-can", "a-f-G-U-C"]);
+    }
+
+    #[cfg(feature = "tower")]
+    #[test]
+    fn queue_service_enqueues_and_reports_pressure() {
+        use tower_service::Service;
+
+        use super::SendQueueService;
+
+        let queue = OutboundSendQueue::new(config(1, 128, SendQueueMode::Fifo), TestClassifier)
+            .expect("config should be valid");
+        let mut service = SendQueueService::new(queue);
+
+        let first = service
+            .call(test_item("a", 10, QueuePriority::Normal, None))
+            .into_inner()
+            .expect("enqueue");
+        let second = service
+            .call(test_item("b", 10, QueuePriority::Normal, None))
+            .into_inner()
+            .expect("enqueue");
+
+        assert_eq!(first.dropped_messages, 0);
+        assert_eq!(second.dropped_messages, 1);
+        let shared = service.queue();
+        let mut queue = shared.lock().expect("queue mutex");
+        assert_eq!(queue.dequeue().expect("remaining item").id, "b");
+    }
+
+    #[cfg(feature = "tower")]
+    #[test]
+    fn queue_service_keeps_enqueueing_after_a_holder_panics() {
+        use tower_service::Service;
+
+        use super::SendQueueService;
+
+        let queue = OutboundSendQueue::new(config(4, 128, SendQueueMode::Fifo), TestClassifier)
+            .expect("config should be valid");
+        let mut service = SendQueueService::new(queue);
+        let shared = service.queue();
+        let _ = std::thread::spawn(move || {
+            let _guard = shared.lock();
+            panic!("writer panicked while holding the queue");
+        })
+        .join();
+        assert!(service.queue().is_poisoned());
+
+        let report = service
+            .call(test_item("a", 10, QueuePriority::Normal, None))
+            .into_inner()
+            .expect("enqueue");
+        assert_eq!(report.dropped_messages, 0);
+    }
 }
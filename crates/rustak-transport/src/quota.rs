@@ -0,0 +1,366 @@
+//! Byte quotas for metered links such as SATCOM.
+//!
+//! A [`QuotaMeter`] counts framed bytes in each direction against the
+//! hourly and daily limits of a [`TrafficQuotaConfig`]. It is a shared
+//! handle: [`crate::ConnectionManager`] keeps one and attaches it to every
+//! connection it dials, so usage carries across reconnects. Windows are
+//! aligned to UTC hour and day boundaries and reset when they roll over.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{QuotaAction, QuotaWindow, TrafficQuotaConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QuotaDirection {
+    Send,
+    Receive,
+}
+
+impl QuotaDirection {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Send => "send",
+            Self::Receive => "receive",
+        }
+    }
+}
+
+impl fmt::Display for QuotaDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Usage of one limited direction in the current window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub window: QuotaWindow,
+    pub direction: QuotaDirection,
+    pub used_bytes: u64,
+    pub limit_bytes: u64,
+    /// Time until the window rolls over.
+    pub resets_in: Duration,
+}
+
+impl QuotaUsage {
+    #[must_use]
+    pub fn exhausted(&self) -> bool {
+        self.used_bytes >= self.limit_bytes
+    }
+}
+
+impl fmt::Display for QuotaUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "quota window={} direction={} used_bytes={} limit_bytes={} resets_in_secs={} exhausted={}",
+            self.window,
+            self.direction,
+            self.used_bytes,
+            self.limit_bytes,
+            self.resets_in.as_secs(),
+            self.exhausted()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct WindowUsage {
+    /// Whole windows since the Unix epoch.
+    index: u64,
+    sent: u64,
+    received: u64,
+    send_overrun: bool,
+    receive_overrun: bool,
+}
+
+impl WindowUsage {
+    fn used(&self, direction: QuotaDirection) -> u64 {
+        match direction {
+            QuotaDirection::Send => self.sent,
+            QuotaDirection::Receive => self.received,
+        }
+    }
+
+    fn overrun(&mut self, direction: QuotaDirection) -> &mut bool {
+        match direction {
+            QuotaDirection::Send => &mut self.send_overrun,
+            QuotaDirection::Receive => &mut self.receive_overrun,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MeterState {
+    windows: BTreeMap<QuotaWindow, WindowUsage>,
+    overruns: u64,
+}
+
+/// Shared byte counter for one logical connection; clones observe the same
+/// usage.
+#[derive(Debug, Clone)]
+pub struct QuotaMeter {
+    config: Arc<TrafficQuotaConfig>,
+    state: Arc<Mutex<MeterState>>,
+}
+
+impl QuotaMeter {
+    #[must_use]
+    pub fn new(config: TrafficQuotaConfig) -> Self {
+        let windows = config
+            .limits
+            .keys()
+            .map(|window| (*window, WindowUsage::default()))
+            .collect();
+        Self {
+            config: Arc::new(config),
+            state: Arc::new(Mutex::new(MeterState {
+                windows,
+                overruns: 0,
+            })),
+        }
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &TrafficQuotaConfig {
+        &self.config
+    }
+
+    /// Times a limit has been crossed, at most once per window and
+    /// direction.
+    #[must_use]
+    pub fn overruns(&self) -> u64 {
+        self.lock().overruns
+    }
+
+    /// Usage of every limited direction at `now`, hourly first.
+    #[must_use]
+    pub fn usage_at(&self, now: SystemTime) -> Vec<QuotaUsage> {
+        let mut state = self.lock();
+        let mut usage = Vec::new();
+        for (window, limit) in &self.config.limits {
+            let current = current_window(&mut state, *window, now);
+            for (direction, limit_bytes) in [
+                (QuotaDirection::Send, limit.send_bytes),
+                (QuotaDirection::Receive, limit.receive_bytes),
+            ] {
+                if let Some(limit_bytes) = limit_bytes {
+                    usage.push(QuotaUsage {
+                        window: *window,
+                        direction,
+                        used_bytes: current.used(direction),
+                        limit_bytes,
+                        resets_in: resets_in(*window, now),
+                    });
+                }
+            }
+        }
+        usage
+    }
+
+    #[must_use]
+    pub fn usage(&self) -> Vec<QuotaUsage> {
+        self.usage_at(SystemTime::now())
+    }
+
+    /// One [`QuotaUsage`] line per limited direction, for diagnostics.
+    #[must_use]
+    pub fn diagnostic_lines(&self) -> Vec<String> {
+        self.usage().iter().map(ToString::to_string).collect()
+    }
+
+    /// The exhausted limit that blocks `direction` at `now`, if any. Only
+    /// [`QuotaAction::Disconnect`] blocks traffic; the other actions always
+    /// return `None`.
+    #[must_use]
+    pub fn blocking_at(&self, direction: QuotaDirection, now: SystemTime) -> Option<QuotaUsage> {
+        if self.config.action != QuotaAction::Disconnect {
+            return None;
+        }
+        self.usage_at(now)
+            .into_iter()
+            .find(|usage| usage.direction == direction && usage.exhausted())
+    }
+
+    /// The first exhausted limit in either direction under
+    /// [`QuotaAction::Disconnect`].
+    #[must_use]
+    pub fn blocking_any_at(&self, now: SystemTime) -> Option<QuotaUsage> {
+        self.blocking_at(QuotaDirection::Send, now)
+            .or_else(|| self.blocking_at(QuotaDirection::Receive, now))
+    }
+
+    /// Adds `bytes` to every window and returns how long the caller should
+    /// pause before more traffic in `direction` under
+    /// [`QuotaAction::Throttle`]. The pause is zero while within quota.
+    pub fn record_at(&self, direction: QuotaDirection, bytes: u64, now: SystemTime) -> Duration {
+        let mut state = self.lock();
+        let mut exhausted = false;
+        let mut overruns = 0;
+        for (window, limit) in &self.config.limits {
+            let limit_bytes = match direction {
+                QuotaDirection::Send => limit.send_bytes,
+                QuotaDirection::Receive => limit.receive_bytes,
+            };
+            let current = current_window(&mut state, *window, now);
+            let used = match direction {
+                QuotaDirection::Send => &mut current.sent,
+                QuotaDirection::Receive => &mut current.received,
+            };
+            *used = used.saturating_add(bytes);
+            let used = *used;
+            let Some(limit_bytes) = limit_bytes else {
+                continue;
+            };
+            if used >= limit_bytes {
+                exhausted = true;
+                let overrun = current.overrun(direction);
+                if !*overrun {
+                    *overrun = true;
+                    overruns += 1;
+                }
+            }
+        }
+        state.overruns = state.overruns.saturating_add(overruns);
+
+        match self.config.action {
+            QuotaAction::Throttle { bytes_per_second } if exhausted && bytes_per_second > 0 => {
+                Duration::from_secs_f64(bytes as f64 / bytes_per_second as f64)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn record(&self, direction: QuotaDirection, bytes: u64) -> Duration {
+        self.record_at(direction, bytes, SystemTime::now())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MeterState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn window_index(window: QuotaWindow, now: SystemTime) -> u64 {
+    let elapsed = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    elapsed.as_secs() / window.length().as_secs()
+}
+
+fn resets_in(window: QuotaWindow, now: SystemTime) -> Duration {
+    let length = window.length().as_secs();
+    let next = (window_index(window, now) + 1).saturating_mul(length);
+    let elapsed = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_secs(next).saturating_sub(elapsed)
+}
+
+fn current_window(
+    state: &mut MeterState,
+    window: QuotaWindow,
+    now: SystemTime,
+) -> &mut WindowUsage {
+    let index = window_index(window, now);
+    let usage = state.windows.entry(window).or_default();
+    if usage.index != index {
+        *usage = WindowUsage {
+            index,
+            ..WindowUsage::default()
+        };
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{QuotaDirection, QuotaMeter};
+    use crate::{QuotaAction, QuotaLimit, QuotaWindow, TrafficQuotaConfig};
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn meter(action: QuotaAction) -> QuotaMeter {
+        QuotaMeter::new(
+            TrafficQuotaConfig::new(action)
+                .with_limit(
+                    QuotaWindow::Hour,
+                    QuotaLimit {
+                        send_bytes: Some(1_000),
+                        receive_bytes: None,
+                    },
+                )
+                .with_limit(
+                    QuotaWindow::Day,
+                    QuotaLimit {
+                        send_bytes: Some(5_000),
+                        receive_bytes: Some(10_000),
+                    },
+                ),
+        )
+    }
+
+    #[test]
+    fn counts_per_window_and_resets_on_rollover() {
+        let meter = meter(QuotaAction::Warn);
+        let start = 10 * 24 * 3_600 + 1_800;
+        assert_eq!(
+            meter.record_at(QuotaDirection::Send, 1_200, at(start)),
+            Duration::ZERO
+        );
+        meter.record_at(QuotaDirection::Receive, 300, at(start));
+
+        let usage = meter.usage_at(at(start));
+        assert_eq!(usage.len(), 3);
+        assert_eq!(usage[0].window, QuotaWindow::Hour);
+        assert!(usage[0].exhausted());
+        assert_eq!(usage[0].resets_in, Duration::from_secs(1_800));
+        assert_eq!(usage[2].used_bytes, 300);
+        assert_eq!(meter.overruns(), 1);
+        assert!(
+            meter.blocking_any_at(at(start)).is_none(),
+            "warn never blocks"
+        );
+
+        let next_hour = meter.usage_at(at(start + 1_800));
+        assert_eq!(next_hour[0].used_bytes, 0);
+        assert_eq!(next_hour[1].used_bytes, 1_200, "daily usage carries over");
+        assert_eq!(
+            next_hour[0].to_string(),
+            "quota window=hour direction=send used_bytes=0 limit_bytes=1000 \
+             resets_in_secs=3600 exhausted=false"
+        );
+    }
+
+    #[test]
+    fn throttle_paces_and_disconnect_blocks_once_exhausted() {
+        let throttled = meter(QuotaAction::Throttle {
+            bytes_per_second: 500,
+        });
+        assert_eq!(
+            throttled.record_at(QuotaDirection::Send, 999, at(0)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            throttled.record_at(QuotaDirection::Send, 250, at(0)),
+            Duration::from_millis(500)
+        );
+
+        let disconnecting = meter(QuotaAction::Disconnect);
+        let shared = disconnecting.clone();
+        shared.record_at(QuotaDirection::Send, 1_000, at(0));
+        let blocking = disconnecting
+            .blocking_at(QuotaDirection::Send, at(0))
+            .expect("hourly send quota is used up");
+        assert_eq!(blocking.window, QuotaWindow::Hour);
+        assert!(disconnecting
+            .blocking_at(QuotaDirection::Receive, at(0))
+            .is_none());
+        assert!(disconnecting.blocking_any_at(at(3_600)).is_none());
+    }
+}
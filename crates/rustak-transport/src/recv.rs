@@ -0,0 +1,512 @@
+//! Background receive pipeline with a bounded hand-off queue.
+//!
+//! [`RecvPipeline::spawn`] moves a [`TransportConnectionReader`] onto its
+//! own task, which reads and decodes frames ahead of the consumer. Decoded
+//! envelopes wait in a queue bounded by the reader's
+//! `Limits::max_queue_messages` and `Limits::max_queue_bytes`; what happens
+//! when a slow consumer lets it fill is set by [`RecvOverflow`].
+//!
+//! The reader task is watched: if it panics or is aborted, the pipeline
+//! finishes with [`TransportComposeError::ReaderTaskFailed`] instead of
+//! leaving [`RecvPipeline::recv`] waiting forever.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::AsyncRead;
+use tokio::sync::Notify;
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::{TransportComposeError, TransportConnectionReader, TransportEnvelope};
+
+/// What the reader task does with a decoded frame that does not fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecvOverflow {
+    /// Stop reading until the consumer makes room, pushing back on the
+    /// peer through TCP flow control.
+    #[default]
+    Block,
+    /// Discard the oldest queued envelopes to make room.
+    DropOldest,
+    /// Discard the new envelope.
+    DropNewest,
+}
+
+/// Counters for a [`RecvPipeline`]. Bytes are decoded payload bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RecvStats {
+    pub received_messages: u64,
+    pub received_bytes: u64,
+    pub delivered_messages: u64,
+    pub dropped_oldest: u64,
+    pub dropped_newest: u64,
+    pub dropped_bytes: u64,
+    /// Frames skipped because their payload failed to decode. They still
+    /// count toward the negotiator's fallback to legacy XML.
+    pub decode_failures: u64,
+    /// Envelopes that waited for room under [`RecvOverflow::Block`].
+    pub blocked_waits: u64,
+    /// Deepest the queue has been, in messages.
+    pub peak_depth: usize,
+}
+
+type Envelope = TransportEnvelope<Bytes>;
+
+struct RecvState {
+    queue: VecDeque<Envelope>,
+    bytes: usize,
+    stats: RecvStats,
+    /// Set once the reader stops: `Some(error)` until the consumer takes it.
+    finished: Option<Option<TransportComposeError>>,
+}
+
+struct RecvShared {
+    state: Mutex<RecvState>,
+    max_messages: usize,
+    max_bytes: usize,
+    overflow: RecvOverflow,
+    readable: Notify,
+    writable: Notify,
+}
+
+impl RecvShared {
+    fn lock(&self) -> MutexGuard<'_, RecvState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn fits(&self, state: &RecvState, bytes: usize) -> bool {
+        // A payload over the byte bound on its own still goes into an
+        // empty queue rather than stalling the link for good.
+        state.queue.is_empty()
+            || (state.queue.len() < self.max_messages && state.bytes + bytes <= self.max_bytes)
+    }
+
+    /// Queues `envelope` under the overflow policy, waiting for room under
+    /// [`RecvOverflow::Block`].
+    async fn push(&self, mut envelope: Envelope) {
+        let mut waited = false;
+        while let Some(blocked) = self.try_push(envelope, waited) {
+            envelope = blocked;
+            waited = true;
+            self.writable.notified().await;
+        }
+    }
+
+    /// Hands `envelope` back when it must wait for room.
+    fn try_push(&self, envelope: Envelope, waited: bool) -> Option<Envelope> {
+        let bytes = envelope.message.len();
+        let mut state = self.lock();
+        if !self.fits(&state, bytes) {
+            match self.overflow {
+                RecvOverflow::Block => {
+                    state.stats.blocked_waits += u64::from(!waited);
+                    return Some(envelope);
+                }
+                RecvOverflow::DropNewest => {
+                    state.stats.dropped_newest += 1;
+                    state.stats.dropped_bytes += bytes as u64;
+                    return None;
+                }
+                RecvOverflow::DropOldest => {
+                    while !self.fits(&state, bytes) {
+                        let Some(oldest) = state.queue.pop_front() else {
+                            break;
+                        };
+                        state.bytes -= oldest.message.len();
+                        state.stats.dropped_oldest += 1;
+                        state.stats.dropped_bytes += oldest.message.len() as u64;
+                    }
+                }
+            }
+        }
+        state.bytes += bytes;
+        state.queue.push_back(envelope);
+        state.stats.peak_depth = state.stats.peak_depth.max(state.queue.len());
+        drop(state);
+        self.readable.notify_one();
+        None
+    }
+
+    fn finish(&self, error: TransportComposeError) {
+        self.lock().finished = Some(Some(error));
+        self.readable.notify_one();
+    }
+}
+
+/// Reads and decodes frames on a spawned task ahead of the consumer.
+///
+/// The task stops at the first read error, which [`Self::recv`] returns
+/// after every envelope queued before it; a peer closing the stream
+/// surfaces as that error, as with
+/// [`TransportConnectionReader::recv_frame`]. A decoder returning
+/// [`TransportComposeError::NegotiationTerminated`] stops it the same way.
+/// Dropping the pipeline aborts the task.
+pub struct RecvPipeline {
+    shared: Arc<RecvShared>,
+    reader: AbortHandle,
+    watcher: JoinHandle<()>,
+}
+
+impl RecvPipeline {
+    /// Must be called within a tokio runtime.
+    pub fn spawn<IO>(reader: TransportConnectionReader<IO>, overflow: RecvOverflow) -> Self
+    where
+        IO: AsyncRead + Send + 'static,
+    {
+        Self::spawn_with_decoder(
+            reader,
+            overflow,
+            TransportConnectionReader::decode_frame_bytes,
+        )
+    }
+
+    /// Like [`Self::spawn`], decoding each frame with `decoder` instead of
+    /// [`TransportConnectionReader::decode_frame_bytes`]. A decoder error
+    /// skips the frame and counts as a decode failure, except
+    /// [`TransportComposeError::NegotiationTerminated`], which stops the
+    /// pipeline.
+    pub fn spawn_with_decoder<IO, D>(
+        reader: TransportConnectionReader<IO>,
+        overflow: RecvOverflow,
+        decoder: D,
+    ) -> Self
+    where
+        IO: AsyncRead + Send + 'static,
+        D: FnMut(
+                &mut TransportConnectionReader<IO>,
+                &Bytes,
+            ) -> Result<Bytes, TransportComposeError>
+            + Send
+            + 'static,
+    {
+        let limits = reader.limits();
+        let shared = Arc::new(RecvShared {
+            state: Mutex::new(RecvState {
+                queue: VecDeque::new(),
+                bytes: 0,
+                stats: RecvStats::default(),
+                finished: None,
+            }),
+            max_messages: limits.max_queue_messages,
+            max_bytes: limits.max_queue_bytes,
+            overflow,
+            readable: Notify::new(),
+            writable: Notify::new(),
+        });
+        let task = tokio::spawn(read_loop(reader, decoder, Arc::clone(&shared)));
+        let reader = task.abort_handle();
+        let watched = Arc::clone(&shared);
+        let watcher = tokio::spawn(async move {
+            if let Err(error) = task.await {
+                let reason = if error.is_panic() {
+                    panic_message(error.into_panic().as_ref())
+                } else {
+                    "reader task was aborted".to_owned()
+                };
+                watched.finish(TransportComposeError::ReaderTaskFailed { reason });
+            }
+        });
+        Self {
+            shared,
+            reader,
+            watcher,
+        }
+    }
+
+    /// Next decoded envelope, or the error that stopped the reader once the
+    /// queue is empty. `None` after that error has been returned.
+    pub async fn recv(&mut self) -> Option<Result<Envelope, TransportComposeError>> {
+        loop {
+            {
+                let mut state = self.shared.lock();
+                if let Some(envelope) = state.queue.pop_front() {
+                    state.bytes -= envelope.message.len();
+                    state.stats.delivered_messages += 1;
+                    drop(state);
+                    self.shared.writable.notify_one();
+                    return Some(Ok(envelope));
+                }
+                if let Some(finished) = &mut state.finished {
+                    return finished.take().map(Err);
+                }
+            }
+            self.shared.readable.notified().await;
+        }
+    }
+
+    /// Whether the reader task has stopped. Envelopes queued before it
+    /// stopped may still be waiting.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock().finished.is_some()
+    }
+
+    #[must_use]
+    pub fn overflow(&self) -> RecvOverflow {
+        self.shared.overflow
+    }
+
+    #[must_use]
+    pub fn len_messages(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    #[must_use]
+    pub fn len_bytes(&self) -> usize {
+        self.shared.lock().bytes
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> RecvStats {
+        self.shared.lock().stats
+    }
+}
+
+impl Drop for RecvPipeline {
+    fn drop(&mut self) {
+        self.watcher.abort();
+        self.reader.abort();
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        format!("reader task panicked: {message}")
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        format!("reader task panicked: {message}")
+    } else {
+        "reader task panicked".to_owned()
+    }
+}
+
+async fn read_loop<IO, D>(
+    mut reader: TransportConnectionReader<IO>,
+    mut decoder: D,
+    shared: Arc<RecvShared>,
+) where
+    IO: AsyncRead,
+    D: FnMut(&mut TransportConnectionReader<IO>, &Bytes) -> Result<Bytes, TransportComposeError>,
+{
+    // Frames are split off one shared buffer, which reclaims its capacity
+    // once the consumer has dropped them.
+    let mut buf = BytesMut::new();
+    loop {
+        let frame = match reader.recv_frame_into(&mut buf).await {
+            Ok(len) => buf.split_to(len).freeze(),
+            Err(error) => {
+                shared.finish(error);
+                return;
+            }
+        };
+        let payload = match decoder(&mut reader, &frame) {
+            Ok(payload) => payload,
+            Err(error @ TransportComposeError::NegotiationTerminated { .. }) => {
+                shared.finish(error);
+                return;
+            }
+            Err(_) => {
+                shared.lock().stats.decode_failures += 1;
+                continue;
+            }
+        };
+        {
+            let mut state = shared.lock();
+            state.stats.received_messages += 1;
+            state.stats.received_bytes += payload.len() as u64;
+        }
+        shared
+            .push(TransportEnvelope::new(payload).with_raw_frame(frame))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rustak_wire::{DowngradePolicy, NegotiationReason, TakProtocolVersion, WireFormat};
+    use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+    use super::{RecvOverflow, RecvPipeline, RecvStats};
+    use crate::{
+        TransportComposeError, TransportConfig, TransportConnection, TransportConnectionReader,
+    };
+
+    /// Pipeline over a legacy XML stream whose queue holds two messages.
+    fn pipeline(overflow: RecvOverflow) -> (RecvPipeline, DuplexStream) {
+        let (reader, server) = reader();
+        (RecvPipeline::spawn(reader, overflow), server)
+    }
+
+    fn reader() -> (TransportConnectionReader<DuplexStream>, DuplexStream) {
+        let (client, server) = duplex(1024);
+        let mut config = TransportConfig::default();
+        config.limits.max_queue_messages = 2;
+        config.send_queue.max_messages = 2;
+        let connection = TransportConnection::new(client, &config, DowngradePolicy::FailOpen)
+            .expect("connection");
+        let (reader, _writer) = connection.split();
+        (reader, server)
+    }
+
+    async fn send_events(server: &mut DuplexStream, count: usize) {
+        for index in 0..count {
+            let line = format!("<event uid=\"{index}\"/>\n");
+            server.write_all(line.as_bytes()).await.expect("write");
+        }
+        server.shutdown().await.expect("shutdown");
+    }
+
+    async fn wait_until(pipeline: &RecvPipeline, done: impl Fn(&RecvPipeline) -> bool) {
+        while !done(pipeline) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    async fn drain(pipeline: &mut RecvPipeline) -> (Vec<String>, TransportComposeError) {
+        let mut received = Vec::new();
+        loop {
+            match pipeline.recv().await.expect("error before the end") {
+                Ok(envelope) => {
+                    received.push(String::from_utf8(envelope.message.to_vec()).expect("utf8"));
+                }
+                Err(error) => return (received, error),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest_messages() {
+        let (mut pipeline, mut server) = pipeline(RecvOverflow::DropOldest);
+        send_events(&mut server, 4).await;
+        wait_until(&pipeline, RecvPipeline::is_finished).await;
+
+        let (received, _closed) = drain(&mut pipeline).await;
+        assert_eq!(received, ["<event uid=\"2\"/>", "<event uid=\"3\"/>"]);
+        assert!(pipeline.recv().await.is_none());
+        let stats = pipeline.stats();
+        assert_eq!(
+            stats,
+            RecvStats {
+                received_messages: 4,
+                received_bytes: 64,
+                delivered_messages: 2,
+                dropped_oldest: 2,
+                dropped_bytes: 32,
+                peak_depth: 2,
+                ..RecvStats::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn drop_newest_keeps_the_first_messages() {
+        let (mut pipeline, mut server) = pipeline(RecvOverflow::DropNewest);
+        send_events(&mut server, 4).await;
+        wait_until(&pipeline, RecvPipeline::is_finished).await;
+
+        let (received, _closed) = drain(&mut pipeline).await;
+        assert_eq!(received, ["<event uid=\"0\"/>", "<event uid=\"1\"/>"]);
+        assert_eq!(pipeline.stats().dropped_newest, 2);
+        assert_eq!(pipeline.len_messages(), 0);
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_the_consumer_and_loses_nothing() {
+        let (mut pipeline, mut server) = pipeline(RecvOverflow::Block);
+        send_events(&mut server, 5).await;
+        wait_until(&pipeline, |pipeline| pipeline.stats().blocked_waits > 0).await;
+        assert!(!pipeline.is_finished());
+        assert_eq!(pipeline.len_messages(), 2);
+        assert_eq!(pipeline.len_bytes(), 32);
+
+        let (received, closed) = drain(&mut pipeline).await;
+        assert_eq!(received.len(), 5);
+        assert_eq!(received[4], "<event uid=\"4\"/>");
+        assert!(matches!(closed, TransportComposeError::Delimited(_)));
+        let stats = pipeline.stats();
+        assert_eq!(stats.delivered_messages, 5);
+        assert_eq!(stats.dropped_oldest + stats.dropped_newest, 0);
+        assert_eq!(stats.peak_depth, 2);
+    }
+
+    #[tokio::test]
+    async fn a_panicking_decoder_fails_the_pipeline_instead_of_hanging() {
+        let (reader, mut server) = reader();
+        let mut decoded = 0;
+        let mut pipeline =
+            RecvPipeline::spawn_with_decoder(reader, RecvOverflow::Block, move |_, frame| {
+                decoded += 1;
+                assert!(decoded < 2, "decoder exploded");
+                Ok(frame.clone())
+            });
+        send_events(&mut server, 3).await;
+
+        let (received, error) = tokio::time::timeout(Duration::from_secs(5), drain(&mut pipeline))
+            .await
+            .expect("recv must not hang after the reader task panics");
+        assert_eq!(received, ["<event uid=\"0\"/>"]);
+        assert!(
+            matches!(
+                &error,
+                TransportComposeError::ReaderTaskFailed { reason }
+                    if reason.contains("decoder exploded")
+            ),
+            "{error}"
+        );
+        assert!(pipeline.is_finished());
+        assert!(pipeline.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn a_fail_closed_termination_stops_the_pipeline() {
+        let (client, mut server) = duplex(1024);
+        let config = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            mtu_safety: None,
+            ..TransportConfig::default()
+        };
+        let mut connection = TransportConnection::new(client, &config, DowngradePolicy::FailClosed)
+            .expect("connection");
+        connection.begin_upgrade_attempt();
+        connection.observe_supported_version(TakProtocolVersion::V1);
+        let (reader, _writer) = connection.split();
+        let mut pipeline = RecvPipeline::spawn(reader, RecvOverflow::Block);
+
+        // Garbage payloads, then a valid one the pipeline must never reach.
+        for _ in 0..rustak_wire::DEFAULT_DECODE_FAILURE_LIMIT {
+            server.write_all(&[0, 0, 0, 1, 0xff]).await.expect("write");
+        }
+        let ping = crate::render_ping(
+            "peer",
+            rustak_core::TimestampUtc::UNIX_EPOCH,
+            Duration::from_secs(10),
+        );
+        let valid = rustak_proto::encode_v1_payload(ping.as_bytes()).expect("encode");
+        let len = u32::try_from(valid.len()).expect("len");
+        server.write_all(&len.to_be_bytes()).await.expect("write");
+        server.write_all(&valid).await.expect("write");
+
+        let (received, error) = tokio::time::timeout(Duration::from_secs(5), drain(&mut pipeline))
+            .await
+            .expect("the pipeline must stop instead of reading on");
+        assert!(received.is_empty());
+        assert!(
+            matches!(
+                error,
+                TransportComposeError::NegotiationTerminated {
+                    reason: NegotiationReason::PersistentDecodeFailure
+                }
+            ),
+            "{error}"
+        );
+        assert_eq!(
+            pipeline.stats().decode_failures,
+            u64::from(rustak_wire::DEFAULT_DECODE_FAILURE_LIMIT) - 1
+        );
+    }
+}
@@ -0,0 +1,555 @@
+//! Platform-specific socket option handling.
+//!
+//! UDP multicast/broadcast and TCP keepalive behave differently on Linux,
+//! macOS and Windows; this module maps one transport config onto whatever
+//! each platform needs so gateways and operator laptops share config files.
+//!
+//! Notable differences handled here:
+//! - Windows cannot bind a socket to a multicast group address; the bind is
+//!   widened to `0.0.0.0` and group filtering relies on the membership.
+//! - macOS/BSD need `SO_REUSEPORT` for several listeners on one multicast
+//!   port; Linux and Windows only need `SO_REUSEADDR`.
+//! - With no interface configured, Linux also joins multicast groups on every
+//!   up, multicast-capable interface so multi-homed hosts hear all of them;
+//!   other platforms rely on the kernel's choice of interface.
+//! - TCP keepalive probe counts are not configurable on Windows.
+//! - `TCP_INFO` link statistics are only sampled on Linux.
+
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol as SocketProtocol, SockRef, Socket, TcpKeepalive, Type};
+use tokio::time::Instant;
+
+use crate::{Keepalive, UdpTarget};
+
+/// Keepalive probes sent before the peer is declared dead, where the
+/// platform lets us choose.
+pub const TCP_KEEPALIVE_RETRIES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastMembership {
+    pub group: Ipv4Addr,
+    pub interface: Ipv4Addr,
+    pub ttl: u32,
+    pub loopback: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpSocketOptions {
+    pub bind_addr: SocketAddr,
+    pub reuse_address: bool,
+    pub broadcast: bool,
+    pub multicast: Option<MulticastMembership>,
+}
+
+impl UdpSocketOptions {
+    /// Derives socket options for a configured UDP bind address and target.
+    #[must_use]
+    pub fn for_target(bind_addr: SocketAddr, target: &UdpTarget) -> Self {
+        match target {
+            UdpTarget::Unicast(_) => Self {
+                bind_addr,
+                reuse_address: false,
+                broadcast: false,
+                multicast: None,
+            },
+            UdpTarget::Multicast { group, .. } => Self {
+                bind_addr,
+                reuse_address: true,
+                broadcast: false,
+                multicast: Some(MulticastMembership {
+                    group: *group,
+                    interface: Ipv4Addr::UNSPECIFIED,
+                    ttl: 1,
+                    loopback: true,
+                }),
+            },
+            UdpTarget::Broadcast { .. } => Self {
+                bind_addr,
+                reuse_address: true,
+                broadcast: true,
+                multicast: None,
+            },
+        }
+    }
+}
+
+/// Outcome of one `IP_ADD_MEMBERSHIP` on a bound socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MulticastJoin {
+    pub group: Ipv4Addr,
+    /// Interface name, or `default` when the kernel picks the interface.
+    pub interface: String,
+    pub address: Ipv4Addr,
+    /// Why the join failed; `None` once the socket is a member.
+    pub error: Option<String>,
+}
+
+impl MulticastJoin {
+    #[must_use]
+    pub fn joined(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for MulticastJoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "multicast_join group={} interface={} address={}",
+            self.group, self.interface, self.address
+        )?;
+        match &self.error {
+            None => f.write_str(" status=joined"),
+            Some(error) => write!(f, " status=failed error=\"{error}\""),
+        }
+    }
+}
+
+/// Address actually passed to `bind` on this platform.
+#[must_use]
+pub fn effective_bind_addr(bind_addr: SocketAddr) -> SocketAddr {
+    match bind_addr {
+        SocketAddr::V4(addr) if cfg!(windows) && addr.ip().is_multicast() => {
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, addr.port()))
+        }
+        other => other,
+    }
+}
+
+/// Creates, configures and binds a UDP socket.
+pub fn bind_udp_socket(options: &UdpSocketOptions) -> io::Result<UdpSocket> {
+    bind_udp_socket_with_joins(options).map(|(socket, _)| socket)
+}
+
+/// Like [`bind_udp_socket`], and also reports each multicast join. The join
+/// on the configured (or default) interface must succeed; joins on the
+/// remaining interfaces are best effort and only reported.
+pub fn bind_udp_socket_with_joins(
+    options: &UdpSocketOptions,
+) -> io::Result<(UdpSocket, Vec<MulticastJoin>)> {
+    let bind_addr = effective_bind_addr(options.bind_addr);
+    let socket = Socket::new(
+        Domain::for_address(bind_addr),
+        Type::DGRAM,
+        Some(SocketProtocol::UDP),
+    )?;
+
+    if options.reuse_address {
+        socket.set_reuse_address(true)?;
+        set_reuse_port(&socket)?;
+    }
+    if options.broadcast {
+        socket.set_broadcast(true)?;
+    }
+    socket.bind(&bind_addr.into())?;
+
+    let mut joins = Vec::new();
+    if let Some(membership) = &options.multicast {
+        socket.join_multicast_v4(&membership.group, &membership.interface)?;
+        joins.push(MulticastJoin {
+            group: membership.group,
+            interface: if membership.interface.is_unspecified() {
+                "default".to_owned()
+            } else {
+                membership.interface.to_string()
+            },
+            address: membership.interface,
+            error: None,
+        });
+        if membership.interface.is_unspecified() {
+            for (name, address) in multicast_interfaces().unwrap_or_default() {
+                let error = match socket.join_multicast_v4(&membership.group, &address) {
+                    // The default join already covers this interface.
+                    Err(error) if error.kind() == io::ErrorKind::AddrInUse => None,
+                    Err(error) => Some(error.to_string()),
+                    Ok(()) => None,
+                };
+                joins.push(MulticastJoin {
+                    group: membership.group,
+                    interface: name,
+                    address,
+                    error,
+                });
+            }
+        }
+        socket.set_multicast_ttl_v4(membership.ttl)?;
+        // Unix applies this to outbound copies, Windows to inbound delivery;
+        // setting it on the shared socket gives the same observable result.
+        socket.set_multicast_loop_v4(membership.loopback)?;
+        if !membership.interface.is_unspecified() {
+            socket.set_multicast_if_v4(&membership.interface)?;
+        }
+    }
+
+    Ok((socket.into(), joins))
+}
+
+/// Name and IPv4 address of every up, multicast-capable, non-loopback
+/// interface.
+#[cfg(target_os = "linux")]
+fn multicast_interfaces() -> io::Result<Vec<(String, Ipv4Addr)>> {
+    use std::ffi::CStr;
+
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: `head` is valid for writes; on success the list is released
+    // with `freeifaddrs` below.
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let wanted = (libc::IFF_UP | libc::IFF_MULTICAST) as libc::c_uint;
+    let mut interfaces = Vec::new();
+    let mut cursor = head;
+    while !cursor.is_null() {
+        // SAFETY: every node of the list stays valid until `freeifaddrs`.
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null()
+            || entry.ifa_flags & wanted != wanted
+            || entry.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint != 0
+        {
+            continue;
+        }
+        // SAFETY: `ifa_addr` is non-null and starts with the family field;
+        // `AF_INET` entries are `sockaddr_in`.
+        let address = unsafe {
+            if i32::from((*entry.ifa_addr).sa_family) != libc::AF_INET {
+                continue;
+            }
+            let address = &*entry.ifa_addr.cast::<libc::sockaddr_in>();
+            Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr))
+        };
+        // SAFETY: `ifa_name` is a NUL-terminated string owned by the list.
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+        interfaces.push((name, address));
+    }
+    // SAFETY: `head` came from a successful `getifaddrs`.
+    unsafe { libc::freeifaddrs(head) };
+    Ok(interfaces)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn multicast_interfaces() -> io::Result<Vec<(String, Ipv4Addr)>> {
+    Ok(Vec::new())
+}
+
+/// Enables TCP keepalive on a connected stream.
+///
+/// `keepalive.interval` is the idle time before the first probe and
+/// `keepalive.timeout` the spacing between probes.
+pub fn apply_tcp_keepalive<'s, S>(stream: &'s S, keepalive: &Keepalive) -> io::Result<()>
+where
+    SockRef<'s>: From<&'s S>,
+{
+    let socket = SockRef::from(stream);
+    socket.set_tcp_keepalive(&tcp_keepalive_params(keepalive))
+}
+
+fn tcp_keepalive_params(keepalive: &Keepalive) -> TcpKeepalive {
+    let params = TcpKeepalive::new()
+        .with_time(clamp_keepalive(keepalive.interval))
+        .with_interval(clamp_keepalive(keepalive.timeout));
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_vendor = "apple"))]
+    let params = params.with_retries(TCP_KEEPALIVE_RETRIES);
+
+    params
+}
+
+/// Platforms take keepalive timings in whole seconds; round sub-second values
+/// up rather than disabling the probe.
+fn clamp_keepalive(duration: Duration) -> Duration {
+    Duration::from_secs(duration.as_secs().max(1))
+}
+
+/// Kernel view of a TCP connection's link quality, from `TCP_INFO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TcpLinkStats {
+    /// Smoothed round-trip time.
+    pub rtt: Duration,
+    pub rtt_variance: Duration,
+    /// Congestion window, in segments.
+    pub congestion_window: u32,
+    /// Segments sent but not yet acknowledged.
+    pub unacked_segments: u32,
+    pub lost_segments: u32,
+    /// Consecutive retransmits of the current segment.
+    pub retransmits: u8,
+    /// Retransmits over the connection's lifetime.
+    pub total_retransmits: u32,
+}
+
+impl TcpLinkStats {
+    /// Renders the sample as Prometheus gauges for the admin `/metrics`
+    /// endpoint. Peers are not used as labels to keep cardinality bounded.
+    #[must_use]
+    pub fn to_metrics_text(&self) -> String {
+        format!(
+            "rustak_transport_tcp_rtt_seconds {}\n\
+             rustak_transport_tcp_rtt_variance_seconds {}\n\
+             rustak_transport_tcp_congestion_window_segments {}\n\
+             rustak_transport_tcp_unacked_segments {}\n\
+             rustak_transport_tcp_lost_segments {}\n\
+             rustak_transport_tcp_retransmits {}\n\
+             rustak_transport_tcp_retransmits_total {}\n",
+            self.rtt.as_secs_f64(),
+            self.rtt_variance.as_secs_f64(),
+            self.congestion_window,
+            self.unacked_segments,
+            self.lost_segments,
+            self.retransmits,
+            self.total_retransmits,
+        )
+    }
+}
+
+/// Reads `TCP_INFO` for a connected stream. Returns
+/// [`io::ErrorKind::Unsupported`] on platforms other than Linux.
+pub fn tcp_link_stats<'s, S>(stream: &'s S) -> io::Result<TcpLinkStats>
+where
+    SockRef<'s>: From<&'s S>,
+{
+    read_tcp_info(&SockRef::from(stream))
+}
+
+#[cfg(target_os = "linux")]
+fn read_tcp_info(socket: &Socket) -> io::Result<TcpLinkStats> {
+    use std::mem;
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: `tcp_info` is plain old data, so all-zero is a valid value.
+    let mut info: libc::tcp_info = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+    // SAFETY: `info` and `len` are valid for writes and `len` holds the
+    // buffer size; the kernel writes at most `len` bytes.
+    let result = unsafe {
+        libc::getsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            std::ptr::addr_of_mut!(info).cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(TcpLinkStats {
+        rtt: Duration::from_micros(u64::from(info.tcpi_rtt)),
+        rtt_variance: Duration::from_micros(u64::from(info.tcpi_rttvar)),
+        congestion_window: info.tcpi_snd_cwnd,
+        unacked_segments: info.tcpi_unacked,
+        lost_segments: info.tcpi_lost,
+        retransmits: info.tcpi_retransmits,
+        total_retransmits: info.tcpi_total_retrans,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_tcp_info(_socket: &Socket) -> io::Result<TcpLinkStats> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP_INFO sampling is only available on Linux",
+    ))
+}
+
+/// Rate-limits `TCP_INFO` sampling for an active connection and keeps the
+/// latest sample for diagnostics.
+#[derive(Debug, Clone)]
+pub struct TcpLinkSampler {
+    interval: Duration,
+    next_due: Option<Instant>,
+    latest: Option<TcpLinkStats>,
+    samples: u64,
+}
+
+impl TcpLinkSampler {
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_due: None,
+            latest: None,
+            samples: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<TcpLinkStats> {
+        self.latest
+    }
+
+    #[must_use]
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Samples `stream` if `interval` has passed since the last sample and
+    /// returns the newest stats, if any. Unsupported platforms yield `None`
+    /// without erroring.
+    pub fn sample_if_due<'s, S>(
+        &mut self,
+        stream: &'s S,
+        now: Instant,
+    ) -> io::Result<Option<TcpLinkStats>>
+    where
+        SockRef<'s>: From<&'s S>,
+    {
+        if self.next_due.is_some_and(|due| now < due) {
+            return Ok(self.latest);
+        }
+        self.next_due = Some(now + self.interval);
+        match tcp_link_stats(stream) {
+            Ok(stats) => {
+                self.latest = Some(stats);
+                self.samples += 1;
+                Ok(Some(stats))
+            }
+            Err(error) if error.kind() == io::ErrorKind::Unsupported => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn set_reuse_port(socket: &Socket) -> io::Result<()> {
+    if cfg!(target_os = "linux") || cfg!(target_os = "android") {
+        // Linux load-balances unicast across SO_REUSEPORT sockets, which
+        // would split a feed between listeners; SO_REUSEADDR already
+        // permits shared multicast binds there.
+        return Ok(());
+    }
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn set_reuse_port(_socket: &Socket) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use super::{
+        bind_udp_socket, clamp_keepalive, effective_bind_addr, MulticastJoin, UdpSocketOptions,
+    };
+    use crate::UdpTarget;
+
+    #[test]
+    fn multicast_join_lines_name_the_interface_and_outcome() {
+        let mut join = MulticastJoin {
+            group: Ipv4Addr::new(239, 2, 3, 1),
+            interface: "wlan0".to_owned(),
+            address: Ipv4Addr::new(10, 0, 0, 7),
+            error: None,
+        };
+        assert_eq!(
+            join.to_string(),
+            "multicast_join group=239.2.3.1 interface=wlan0 address=10.0.0.7 status=joined"
+        );
+        join.error = Some("No such device".to_owned());
+        assert!(!join.joined());
+        assert!(join
+            .to_string()
+            .ends_with("status=failed error=\"No such device\""));
+    }
+
+    #[test]
+    fn multicast_target_requests_membership_and_reuse() {
+        let options = UdpSocketOptions::for_target(
+            SocketAddr::from(([0, 0, 0, 0], 6969)),
+            &UdpTarget::Multicast {
+                group: Ipv4Addr::new(239, 2, 3, 1),
+                port: 6969,
+            },
+        );
+        assert!(options.reuse_address);
+        assert_eq!(
+            options.multicast.map(|membership| membership.group),
+            Some(Ipv4Addr::new(239, 2, 3, 1))
+        );
+    }
+
+    #[test]
+    fn bind_addr_is_only_widened_on_windows() {
+        let group_bind = SocketAddr::from(([239, 2, 3, 1], 6969));
+        let expected = if cfg!(windows) {
+            SocketAddr::from(([0, 0, 0, 0], 6969))
+        } else {
+            group_bind
+        };
+        assert_eq!(effective_bind_addr(group_bind), expected);
+
+        let unicast = SocketAddr::from(([127, 0, 0, 1], 4242));
+        assert_eq!(effective_bind_addr(unicast), unicast);
+    }
+
+    #[test]
+    fn sub_second_keepalive_rounds_up() {
+        assert_eq!(
+            clamp_keepalive(Duration::from_millis(200)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            clamp_keepalive(Duration::from_secs(10)),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn samples_tcp_info_on_loopback_connection() {
+        use std::net::{TcpListener, TcpStream};
+
+        use super::{TcpLinkSampler, TcpLinkStats};
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let stream = TcpStream::connect(listener.local_addr().expect("addr")).expect("connect");
+        let _accepted = listener.accept().expect("accept");
+
+        let mut sampler = TcpLinkSampler::new(Duration::from_secs(5));
+        let now = tokio::time::Instant::now();
+        let stats = sampler
+            .sample_if_due(&stream, now)
+            .expect("tcp_info")
+            .expect("linux supports tcp_info");
+        assert!(stats.congestion_window > 0);
+        assert_eq!(stats.total_retransmits, 0);
+
+        sampler
+            .sample_if_due(&stream, now + Duration::from_secs(1))
+            .expect("cached");
+        assert_eq!(sampler.samples(), 1);
+        sampler
+            .sample_if_due(&stream, now + Duration::from_secs(5))
+            .expect("tcp_info");
+        assert_eq!(sampler.samples(), 2);
+
+        let text = TcpLinkStats {
+            rtt: Duration::from_micros(1_500),
+            ..stats
+        }
+        .to_metrics_text();
+        assert!(text.starts_with("rustak_transport_tcp_rtt_seconds 0.0015\n"));
+        assert!(text.contains("rustak_transport_tcp_retransmits_total 0\n"));
+    }
+
+    #[test]
+    fn binds_unicast_socket_on_loopback() {
+        let options = UdpSocketOptions::for_target(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            &UdpTarget::Unicast(SocketAddr::from(([127, 0, 0, 1], 6969))),
+        );
+        let socket = bind_udp_socket(&options).expect("loopback bind");
+        assert!(socket.local_addr().expect("local addr").port() > 0);
+    }
+}
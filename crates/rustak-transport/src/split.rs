@@ -0,0 +1,389 @@
+//! Owned read and write halves of a [`TransportConnection`].
+//!
+//! [`TransportConnection::split`] hands the stream to [`tokio::io::split`]
+//! so a read task and a write task can run side by side. Both halves carry
+//! the connection's limits and quota meter, and share its framing and
+//! negotiator: when decode failures on the reader fall back to legacy XML,
+//! the writer switches framing with it.
+//!
+//! A frame tap goes with the halves, the reader copying inbound frames and
+//! the writer outbound ones.
+//!
+//! The halves do not drive keepalives; pings need the writer while the
+//! deadline lives with the reader, so keep [`TransportConnection`] whole for
+//! [`TransportConnection::recv_frame_with_keepalive`].
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::Stream;
+use rustak_io::{IoError, MessageEnvelope, MessageSink, MessageSource};
+use rustak_limits::Limits;
+use rustak_wire::{NegotiationEvent, NegotiationState, Negotiator};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+
+use crate::{
+    charge_quota, check_quota, decode_tracked_bytes, decode_tracked_payload, envelope_stream,
+    frame_envelope, observe_tracked_decode_failure, recv_frame_into_with_framing,
+    recv_frame_with_framing, send_frame_with_framing, tail, tap, FrameTap, LockedSink,
+    QuotaDirection, QuotaMeter, TapDirection, TransportComposeError, TransportConnection,
+    TransportEnvelope, TransportEvents, TransportFraming,
+};
+
+#[derive(Debug)]
+struct SharedState {
+    framing: TransportFraming,
+    negotiator: Negotiator,
+}
+
+#[derive(Debug, Clone)]
+struct Halves {
+    state: Arc<Mutex<SharedState>>,
+    max_frame_bytes: usize,
+    limits: Limits,
+    quota: Option<QuotaMeter>,
+    events: Option<TransportEvents>,
+    tap: Option<FrameTap>,
+}
+
+impl Halves {
+    fn state(&self) -> MutexGuard<'_, SharedState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn framing(&self) -> TransportFraming {
+        self.state().framing
+    }
+}
+
+/// Receiving half from [`TransportConnection::split`].
+#[derive(Debug)]
+pub struct TransportConnectionReader<IO> {
+    reader: ReadHalf<IO>,
+    halves: Halves,
+}
+
+/// Sending half from [`TransportConnection::split`].
+#[derive(Debug)]
+pub struct TransportConnectionWriter<IO> {
+    writer: WriteHalf<IO>,
+    halves: Halves,
+}
+
+impl<IO> TransportConnection<IO>
+where
+    IO: AsyncRead + AsyncWrite,
+{
+    /// Splits the connection for a read task and a write task. Negotiate
+    /// first: the halves keep whatever framing is current.
+    pub fn split(self) -> (TransportConnectionReader<IO>, TransportConnectionWriter<IO>) {
+        let (reader, writer) = tokio::io::split(self.io);
+        let halves = Halves {
+            state: Arc::new(Mutex::new(SharedState {
+                framing: self.framing,
+                negotiator: self.negotiator,
+            })),
+            max_frame_bytes: self.max_frame_bytes,
+            limits: self.limits,
+            quota: self.quota,
+            events: self.events,
+            tap: self.tap,
+        };
+        (
+            TransportConnectionReader {
+                reader,
+                halves: halves.clone(),
+            },
+            TransportConnectionWriter { writer, halves },
+        )
+    }
+}
+
+impl<IO> TransportConnectionReader<IO> {
+    /// Framing shared with the writer.
+    #[must_use]
+    pub fn framing(&self) -> TransportFraming {
+        self.halves.framing()
+    }
+
+    #[must_use]
+    pub fn negotiation_state(&self) -> NegotiationState {
+        self.halves.state().negotiator.state()
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> &Limits {
+        &self.halves.limits
+    }
+
+    #[must_use]
+    pub fn quota_meter(&self) -> Option<&QuotaMeter> {
+        self.halves.quota.as_ref()
+    }
+
+    /// Like [`TransportConnection::decode_frame_payload`]; a fallback to
+    /// legacy XML also switches the writer.
+    pub fn decode_frame_payload(&mut self, frame: &[u8]) -> Result<Vec<u8>, TransportComposeError> {
+        let mut state = self.halves.state();
+        let SharedState {
+            framing,
+            negotiator,
+        } = &mut *state;
+        decode_tracked_payload(framing, negotiator, self.halves.events.as_ref(), frame)
+    }
+
+    /// Like [`TransportConnection::decode_frame_bytes`].
+    pub fn decode_frame_bytes(&mut self, frame: &Bytes) -> Result<Bytes, TransportComposeError> {
+        let mut state = self.halves.state();
+        let SharedState {
+            framing,
+            negotiator,
+        } = &mut *state;
+        decode_tracked_bytes(framing, negotiator, self.halves.events.as_ref(), frame)
+    }
+
+    pub fn observe_decode_failure(&mut self) -> NegotiationEvent {
+        let mut state = self.halves.state();
+        let SharedState {
+            framing,
+            negotiator,
+        } = &mut *state;
+        observe_tracked_decode_failure(framing, negotiator, self.halves.events.as_ref())
+    }
+
+    /// Rejoins the halves. Panics if `writer` came from another split, like
+    /// [`ReadHalf::unsplit`].
+    pub fn unsplit(self, writer: TransportConnectionWriter<IO>) -> TransportConnection<IO>
+    where
+        IO: Unpin,
+    {
+        let state = self.halves.state();
+        TransportConnection {
+            io: self.reader.unsplit(writer.writer),
+            framing: state.framing,
+            max_frame_bytes: self.halves.max_frame_bytes,
+            limits: self.halves.limits.clone(),
+            negotiator: state.negotiator,
+            quota: self.halves.quota.clone(),
+            events: self.halves.events.clone(),
+            tap: self.halves.tap.clone(),
+        }
+    }
+}
+
+impl<IO> TransportConnectionReader<IO>
+where
+    IO: AsyncRead,
+{
+    pub async fn recv_frame(&mut self) -> Result<Vec<u8>, TransportComposeError> {
+        let quota = self.halves.quota.as_ref();
+        check_quota(quota, QuotaDirection::Receive)?;
+        let framing = self.halves.framing();
+        let frame = recv_frame_with_framing(
+            &mut self.reader,
+            framing,
+            self.halves.max_frame_bytes,
+            self.halves.limits.max_xml_scan_bytes,
+        )
+        .await?;
+        charge_quota(quota, framing, QuotaDirection::Receive, frame.len()).await;
+        tap::capture(self.halves.tap.as_ref(), TapDirection::Inbound, &frame).await;
+        Ok(frame)
+    }
+
+    /// Like [`TransportConnection::recv_frame_into`].
+    pub async fn recv_frame_into(
+        &mut self,
+        buf: &mut BytesMut,
+    ) -> Result<usize, TransportComposeError> {
+        let quota = self.halves.quota.as_ref();
+        check_quota(quota, QuotaDirection::Receive)?;
+        let framing = self.halves.framing();
+        let len = recv_frame_into_with_framing(
+            &mut self.reader,
+            framing,
+            self.halves.max_frame_bytes,
+            self.halves.limits.max_xml_scan_bytes,
+            buf,
+        )
+        .await?;
+        charge_quota(quota, framing, QuotaDirection::Receive, len).await;
+        tap::capture(
+            self.halves.tap.as_ref(),
+            TapDirection::Inbound,
+            tail(buf, len),
+        )
+        .await;
+        Ok(len)
+    }
+
+    /// The message and raw frame share one buffer.
+    pub async fn recv_envelope(
+        &mut self,
+    ) -> Result<TransportEnvelope<Bytes>, TransportComposeError> {
+        Ok(frame_envelope(self.recv_frame().await?))
+    }
+}
+
+impl<IO> TransportConnectionWriter<IO> {
+    /// Framing shared with the reader.
+    #[must_use]
+    pub fn framing(&self) -> TransportFraming {
+        self.halves.framing()
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> &Limits {
+        &self.halves.limits
+    }
+}
+
+impl<IO> TransportConnectionWriter<IO>
+where
+    IO: AsyncWrite,
+{
+    pub async fn send_frame(&mut self, payload: &[u8]) -> Result<(), TransportComposeError> {
+        let quota = self.halves.quota.as_ref();
+        check_quota(quota, QuotaDirection::Send)?;
+        let framing = self.halves.framing();
+        send_frame_with_framing(
+            &mut self.writer,
+            framing,
+            payload,
+            self.halves.max_frame_bytes,
+        )
+        .await?;
+        charge_quota(quota, framing, QuotaDirection::Send, payload.len()).await;
+        tap::capture(self.halves.tap.as_ref(), TapDirection::Outbound, payload).await;
+        Ok(())
+    }
+
+    pub async fn send_envelope<M>(
+        &mut self,
+        envelope: TransportEnvelope<M>,
+    ) -> Result<(), TransportComposeError>
+    where
+        M: AsRef<[u8]>,
+    {
+        self.send_frame(envelope.message.as_ref()).await
+    }
+
+    pub async fn flush(&mut self) -> Result<(), TransportComposeError> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Shuts down the write side; the reader keeps receiving.
+    pub async fn shutdown(&mut self) -> Result<(), TransportComposeError> {
+        self.writer.shutdown().await?;
+        Ok(())
+    }
+}
+
+impl<IO> MessageSink<Bytes> for LockedSink<TransportConnectionWriter<IO>>
+where
+    IO: AsyncWrite + Send,
+{
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+        Box::pin(async move {
+            let mut writer = self.lock().await;
+            writer.send_frame(&msg).await?;
+            writer.flush().await?;
+            Ok(())
+        })
+    }
+}
+
+impl<IO> MessageSource<Bytes> for TransportConnectionReader<IO>
+where
+    IO: AsyncRead + Send + Sync + 'static,
+{
+    fn recv(&mut self) -> BoxFuture<'_, Result<MessageEnvelope<Bytes>, IoError>> {
+        Box::pin(async move { Ok(frame_envelope(self.recv_frame().await?)) })
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+    ) -> Pin<Box<dyn Stream<Item = Result<MessageEnvelope<Bytes>, IoError>> + Send>> {
+        envelope_stream(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustak_wire::{DowngradePolicy, NegotiationState, TakProtocolVersion, WireFormat};
+    use tokio::io::duplex;
+
+    use crate::{TransportConfig, TransportConnection, TransportFraming};
+
+    #[tokio::test]
+    async fn halves_read_and_write_from_separate_tasks() {
+        let (client, server) = duplex(64);
+        let cfg = TransportConfig::default();
+        let connection =
+            TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen).expect("connection");
+        let (mut reader, mut writer) = connection.split();
+        let peer = TransportConnection::new(server, &cfg, DowngradePolicy::FailOpen)
+            .expect("peer connection");
+        let (mut peer_reader, mut peer_writer) = peer.split();
+
+        // Both directions at once, with a buffer too small for either side
+        // to finish writing before the other starts reading.
+        let events = (0..32)
+            .map(|index| format!("<event uid=\"evt-{index}\"/>").into_bytes())
+            .collect::<Vec<_>>();
+        let outbound = events.clone();
+        let write_task = tokio::spawn(async move {
+            for event in &outbound {
+                writer.send_frame(event).await.expect("send");
+            }
+            writer
+        });
+        let echo_task = tokio::spawn(async move {
+            for _ in 0..32 {
+                let frame = peer_reader.recv_frame().await.expect("peer recv");
+                peer_writer.send_frame(&frame).await.expect("echo");
+            }
+        });
+        let mut echoed = Vec::new();
+        for _ in 0..32 {
+            echoed.push(reader.recv_frame().await.expect("recv"));
+        }
+        echo_task.await.expect("echo task");
+        let writer = write_task.await.expect("write task");
+        assert_eq!(echoed, events);
+
+        let connection = reader.unsplit(writer);
+        assert_eq!(connection.framing(), TransportFraming::XmlNewlineDelimited);
+    }
+
+    #[tokio::test]
+    async fn decode_fallback_on_the_reader_switches_the_writer() {
+        let (client, _server) = duplex(256);
+        let cfg = TransportConfig {
+            wire_format: WireFormat::TakProtocolV1,
+            mtu_safety: None,
+            ..TransportConfig::default()
+        };
+        let mut connection =
+            TransportConnection::new(client, &cfg, DowngradePolicy::FailOpen).expect("connection");
+        connection.begin_upgrade_attempt();
+        connection.observe_supported_version(TakProtocolVersion::V1);
+        let (mut reader, writer) = connection.split();
+        assert_eq!(
+            writer.framing(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+
+        for _ in 0..rustak_wire::DEFAULT_DECODE_FAILURE_LIMIT {
+            assert!(reader.decode_frame_payload(&[0xff]).is_err());
+        }
+        assert_eq!(reader.negotiation_state(), NegotiationState::LegacyXml);
+        assert_eq!(writer.framing(), TransportFraming::XmlNewlineDelimited);
+        assert_eq!(writer.limits().max_frame_bytes, cfg.limits.max_frame_bytes);
+    }
+}
@@ -0,0 +1,261 @@
+//! Lossless copies of the frames a connection reads and writes.
+//!
+//! A [`FrameTap`] attached with [`TransportConnection::with_frame_tap`] or
+//! [`TransportReceiver::with_frame_tap`] hands every frame to a
+//! [`MessageSink`] before it is decoded, or after it is encoded, as the
+//! same bytes [`TransportConnection::recv_frame`] returns or
+//! [`TransportConnection::send_frame`] is given: the frame body without
+//! its newline delimiter or length prefix. Handshake, keepalive and
+//! application frames are all copied, so application receive loops stay as
+//! they are. Each direction has its own sink, since an envelope does not
+//! say which way it went; pointing both at one sink interleaves them.
+//!
+//! A failing sink never fails the connection: the frame still goes through
+//! and [`FrameTap::failures`] counts the miss.
+//!
+//! [`TransportConnection::with_frame_tap`]: crate::TransportConnection::with_frame_tap
+//! [`TransportReceiver::with_frame_tap`]: crate::TransportReceiver::with_frame_tap
+//! [`TransportConnection::recv_frame`]: crate::TransportConnection::recv_frame
+//! [`TransportConnection::send_frame`]: crate::TransportConnection::send_frame
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use rustak_io::{MessageEnvelope, MessageSink};
+
+/// Which way a tapped frame travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapDirection {
+    Inbound,
+    Outbound,
+}
+
+/// Copies frames into per-direction sinks. Clones share sinks and the
+/// failure count.
+#[derive(Clone, Default)]
+pub struct FrameTap {
+    inbound: Option<Arc<dyn MessageSink<Bytes>>>,
+    outbound: Option<Arc<dyn MessageSink<Bytes>>>,
+    peer: Option<SocketAddr>,
+    failures: Arc<AtomicU64>,
+}
+
+impl FrameTap {
+    /// A tap with no sinks; add them with [`Self::with_inbound`] and
+    /// [`Self::with_outbound`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies received frames into `sink`.
+    #[must_use]
+    pub fn with_inbound(mut self, sink: Arc<dyn MessageSink<Bytes>>) -> Self {
+        self.inbound = Some(sink);
+        self
+    }
+
+    /// Copies sent frames into `sink`.
+    #[must_use]
+    pub fn with_outbound(mut self, sink: Arc<dyn MessageSink<Bytes>>) -> Self {
+        self.outbound = Some(sink);
+        self
+    }
+
+    /// Stamps tapped envelopes with the remote address.
+    #[must_use]
+    pub fn with_peer(mut self, peer: SocketAddr) -> Self {
+        self.peer = Some(peer);
+        self
+    }
+
+    /// Frames a sink rejected.
+    #[must_use]
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Whether frames travelling `direction` are copied.
+    #[must_use]
+    pub fn taps(&self, direction: TapDirection) -> bool {
+        self.sink(direction).is_some()
+    }
+
+    pub(crate) async fn capture(&self, direction: TapDirection, frame: &[u8]) {
+        let Some(sink) = self.sink(direction) else {
+            return;
+        };
+        let frame = Bytes::copy_from_slice(frame);
+        let mut envelope = MessageEnvelope::new(frame.clone()).with_raw_frame(frame);
+        envelope.peer = self.peer;
+        if sink.send_envelope(envelope).await.is_err() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn sink(&self, direction: TapDirection) -> Option<&Arc<dyn MessageSink<Bytes>>> {
+        match direction {
+            TapDirection::Inbound => self.inbound.as_ref(),
+            TapDirection::Outbound => self.outbound.as_ref(),
+        }
+    }
+}
+
+impl fmt::Debug for FrameTap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameTap")
+            .field("inbound", &self.inbound.is_some())
+            .field("outbound", &self.outbound.is_some())
+            .field("peer", &self.peer)
+            .field("failures", &self.failures())
+            .finish()
+    }
+}
+
+/// Taps `frame` when there is a tap.
+pub(crate) async fn capture(tap: Option<&FrameTap>, direction: TapDirection, frame: &[u8]) {
+    if let Some(tap) = tap {
+        tap.capture(direction, frame).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bytes::{Bytes, BytesMut};
+    use futures::future::BoxFuture;
+    use rustak_io::{IoError, MessageEnvelope, MessageSink};
+    use rustak_wire::DowngradePolicy;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::{FrameTap, TapDirection};
+    use crate::{TransportConfig, TransportConnection};
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<MessageEnvelope<Bytes>>>);
+
+    impl Collect {
+        fn frames(&self) -> Vec<Bytes> {
+            let envelopes = self.0.lock().expect("lock");
+            envelopes.iter().map(|env| env.message.clone()).collect()
+        }
+    }
+
+    impl MessageSink<Bytes> for Collect {
+        fn send(&self, msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+            self.send_envelope(MessageEnvelope::new(msg))
+        }
+
+        fn send_envelope(&self, env: MessageEnvelope<Bytes>) -> BoxFuture<'_, Result<(), IoError>> {
+            self.0.lock().expect("lock").push(env);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    struct Refuse;
+
+    impl MessageSink<Bytes> for Refuse {
+        fn send(&self, _msg: Bytes) -> BoxFuture<'_, Result<(), IoError>> {
+            Box::pin(async { Err(IoError::Overloaded) })
+        }
+    }
+
+    #[tokio::test]
+    async fn tap_copies_frames_in_both_directions_and_through_split() {
+        let (client, mut server) = duplex(1024);
+        let inbound = Arc::new(Collect::default());
+        let outbound = Arc::new(Collect::default());
+        let peer = "10.0.0.9:8087".parse().expect("peer");
+        let tap = FrameTap::new()
+            .with_inbound(inbound.clone())
+            .with_outbound(outbound.clone())
+            .with_peer(peer);
+        assert!(tap.taps(TapDirection::Inbound) && tap.taps(TapDirection::Outbound));
+        let mut connection = TransportConnection::new(
+            client,
+            &TransportConfig::default(),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("connection")
+        .with_frame_tap(tap);
+
+        connection
+            .send_frame(b"<event uid=\"out\"/>")
+            .await
+            .expect("send");
+        server
+            .write_all(b"<event uid=\"in-1\"/>\n<event uid=\"in-2\"/>\n<event uid=\"in-3\"/>\n")
+            .await
+            .expect("peer write");
+        assert_eq!(
+            connection.recv_frame().await.expect("recv"),
+            b"<event uid=\"in-1\"/>"
+        );
+        let mut buf = BytesMut::from(&b"kept"[..]);
+        let len = connection
+            .recv_frame_into(&mut buf)
+            .await
+            .expect("recv into");
+        assert_eq!(&buf[4..4 + len], b"<event uid=\"in-2\"/>");
+
+        let (mut reader, mut writer) = connection.split();
+        writer
+            .send_frame(b"<event uid=\"split\"/>")
+            .await
+            .expect("send");
+        reader.recv_frame().await.expect("recv");
+
+        assert_eq!(
+            inbound.frames(),
+            [
+                &b"<event uid=\"in-1\"/>"[..],
+                b"<event uid=\"in-2\"/>",
+                b"<event uid=\"in-3\"/>"
+            ]
+        );
+        assert_eq!(
+            outbound.frames(),
+            [&b"<event uid=\"out\"/>"[..], b"<event uid=\"split\"/>"]
+        );
+        let first = inbound.0.lock().expect("lock")[0].clone();
+        assert_eq!(first.peer, Some(peer));
+        assert_eq!(first.raw_frame.as_deref(), Some(&first.message[..]));
+
+        let mut written = vec![0_u8; 64];
+        let read = server.read(&mut written).await.expect("peer read");
+        assert_eq!(
+            &written[..read],
+            b"<event uid=\"out\"/>\n<event uid=\"split\"/>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_tap_sink_never_fails_the_connection() {
+        let (client, mut server) = duplex(256);
+        let tap = FrameTap::new().with_inbound(Arc::new(Refuse));
+        assert!(!tap.taps(TapDirection::Outbound));
+        let mut connection = TransportConnection::new(
+            client,
+            &TransportConfig::default(),
+            DowngradePolicy::FailOpen,
+        )
+        .expect("connection")
+        .with_frame_tap(tap);
+
+        server
+            .write_all(b"<event/>\n<event/>\n")
+            .await
+            .expect("peer write");
+        connection.recv_frame().await.expect("first frame");
+        connection.recv_frame().await.expect("second frame");
+        connection
+            .send_frame(b"<event/>")
+            .await
+            .expect("untapped send");
+        assert_eq!(connection.frame_tap().expect("tap").failures(), 2);
+    }
+}
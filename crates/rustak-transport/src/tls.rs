@@ -0,0 +1,341 @@
+//! TLS client connections over rustls.
+//!
+//! [`TlsConnector`] is built from a [`LoadedIdentity`] (CA bundle plus the
+//! client certificate TAK servers expect for mutual TLS) and a
+//! [`TlsClientConfig`] carrying the provider mode, revocation policy and an
+//! optional server SPKI pin. The resulting stream is an ordinary
+//! `AsyncRead + AsyncWrite`, so [`TransportConnection`] frames it unchanged.
+
+use std::fmt;
+use std::sync::Arc;
+
+use rustak_crypto::verifier;
+use rustak_crypto::{CryptoError, CryptoProviderMode, LoadedIdentity};
+use rustak_limits::{CodedError, ErrorCode};
+use rustak_wire::DowngradePolicy;
+use rustls::pki_types::ServerName;
+use rustls::ClientConfig;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::{
+    apply_tcp_keepalive, Protocol, TransportComposeError, TransportConfig, TransportConnection,
+};
+
+pub use rustak_crypto::TlsClientConfig;
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+
+    #[error("invalid TLS server name `{server_name}`")]
+    InvalidServerName { server_name: String },
+
+    #[error("transport protocol must be tls to open a TLS connection")]
+    NotTlsProtocol,
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Compose(#[from] TransportComposeError),
+}
+
+impl CodedError for TlsError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Crypto(error) => error.code(),
+            Self::Rustls(_) => ErrorCode::new("TRANSPORT", 606),
+            Self::InvalidServerName { .. } => ErrorCode::new("TRANSPORT", 607),
+            Self::NotTlsProtocol => ErrorCode::new("TRANSPORT", 608),
+            Self::Io(_) => ErrorCode::new("TRANSPORT", 609),
+            Self::Compose(error) => error.code(),
+        }
+    }
+}
+
+/// Client-side TLS handshakes for TAK streaming connections.
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+}
+
+impl fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConnector").finish_non_exhaustive()
+    }
+}
+
+impl TlsConnector {
+    /// Mutual-TLS connector presenting `identity`, which may be PEM or
+    /// PKCS#12.
+    pub fn new(identity: &LoadedIdentity, config: &TlsClientConfig) -> Result<Self, TlsError> {
+        Ok(Self {
+            config: Arc::new(verifier::client_config(identity, config)?),
+        })
+    }
+
+    /// Connector that authenticates the server against `ca_cert_pem` but
+    /// presents no client certificate, for endpoints such as certificate
+    /// enrollment that run before the client has one.
+    pub fn without_client_auth(
+        ca_cert_pem: &str,
+        config: &TlsClientConfig,
+    ) -> Result<Self, TlsError> {
+        Ok(Self {
+            config: Arc::new(verifier::client_config_without_client_auth(
+                ca_cert_pem,
+                config,
+            )?),
+        })
+    }
+
+    #[must_use]
+    pub fn client_config(&self) -> Arc<ClientConfig> {
+        Arc::clone(&self.config)
+    }
+
+    /// Runs the client handshake over an already-connected stream.
+    pub async fn connect<IO>(&self, server_name: &str, io: IO) -> Result<TlsStream<IO>, TlsError>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let name = ServerName::try_from(server_name.to_owned()).map_err(|_| {
+            TlsError::InvalidServerName {
+                server_name: server_name.to_owned(),
+            }
+        })?;
+        let stream = tokio_rustls::TlsConnector::from(Arc::clone(&self.config))
+            .connect(name, io)
+            .await?;
+        Ok(stream)
+    }
+
+    /// Dials the `Protocol::Tls` endpoint in `config`, applies TCP keepalive,
+    /// completes the handshake and frames the stream.
+    pub async fn connect_transport(
+        &self,
+        config: &TransportConfig,
+        downgrade_policy: DowngradePolicy,
+    ) -> Result<TransportConnection<TlsStream<TcpStream>>, TlsError> {
+        let Protocol::Tls { addr, server_name } = &config.protocol else {
+            return Err(TlsError::NotTlsProtocol);
+        };
+        config
+            .validate()
+            .map_err(TransportComposeError::InvalidConfig)?;
+
+        let tcp = TcpStream::connect(addr).await?;
+        tcp.set_nodelay(true)?;
+        if let Some(keepalive) = &config.keepalive {
+            apply_tcp_keepalive(&tcp, keepalive)?;
+        }
+        let stream = self.connect(server_name, tcp).await?;
+        Ok(TransportConnection::new(stream, config, downgrade_policy)?)
+    }
+}
+
+/// Whether `mode` can be used for TLS in this build.
+#[must_use]
+pub fn provider_available(mode: CryptoProviderMode) -> bool {
+    verifier::provider_available(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedKey, IsCa, KeyPair};
+    use rustak_crypto::{
+        CertificateInfo, CryptoError, CryptoProviderMode, LoadedIdentity, PemIdentity,
+        RevocationPolicy,
+    };
+    use rustak_wire::DowngradePolicy;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::PrivateKeyDer;
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::{RootCertStore, ServerConfig};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    use super::{provider_available, TlsClientConfig, TlsConnector, TlsError};
+    use crate::{Protocol, TransportConfig, TransportConnection};
+
+    struct Pki {
+        ca: CertifiedKey,
+        server: CertifiedKey,
+        client: CertifiedKey,
+    }
+
+    fn pki() -> Pki {
+        let ca_key = KeyPair::generate().expect("ca key");
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).expect("ca params");
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_cert = ca_params.self_signed(&ca_key).expect("ca cert");
+
+        let leaf = |name: &str| {
+            let key = KeyPair::generate().expect("leaf key");
+            let cert = CertificateParams::new(vec![name.to_owned()])
+                .expect("leaf params")
+                .signed_by(&key, &ca_cert, &ca_key)
+                .expect("leaf cert");
+            CertifiedKey {
+                cert,
+                key_pair: key,
+            }
+        };
+        let server = leaf("localhost");
+        let client = leaf("rustak-client");
+        Pki {
+            ca: CertifiedKey {
+                cert: ca_cert,
+                key_pair: ca_key,
+            },
+            server,
+            client,
+        }
+    }
+
+    fn identity(pki: &Pki) -> LoadedIdentity {
+        LoadedIdentity::Pem(PemIdentity {
+            ca_cert_pem: pki.ca.cert.pem(),
+            client_cert_pem: pki.client.cert.pem(),
+            client_key_pem: pki.client.key_pair.serialize_pem(),
+        })
+    }
+
+    fn client_config(pin: Option<[u8; 32]>) -> TlsClientConfig {
+        TlsClientConfig {
+            provider: CryptoProviderMode::Ring,
+            revocation: RevocationPolicy::Off,
+            crls_pem: Vec::new(),
+            server_spki_pin: pin,
+        }
+    }
+
+    fn acceptor(pki: &Pki) -> TlsAcceptor {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut roots = RootCertStore::empty();
+        roots.add(pki.ca.cert.der().clone()).expect("ca root");
+        let client_verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), Arc::clone(&provider))
+                .build()
+                .expect("client verifier");
+        let key = PrivateKeyDer::from_pem_slice(pki.server.key_pair.serialize_pem().as_bytes())
+            .expect("server key");
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .expect("protocol versions")
+            .with_client_cert_verifier(client_verifier)
+            .with_single_cert(vec![pki.server.cert.der().clone()], key)
+            .expect("server config");
+        TlsAcceptor::from(Arc::new(config))
+    }
+
+    async fn serve_one(pki: &Pki) -> (std::net::SocketAddr, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let acceptor = acceptor(pki);
+        let handle = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.expect("accept");
+            let Ok(stream) = acceptor.accept(tcp).await else {
+                return Vec::new();
+            };
+            let mut connection = TransportConnection::new(
+                stream,
+                &TransportConfig::default(),
+                DowngradePolicy::FailOpen,
+            )
+            .expect("server connection");
+            connection.recv_frame().await.unwrap_or_default()
+        });
+        (addr, handle)
+    }
+
+    fn tls_transport(addr: std::net::SocketAddr) -> TransportConfig {
+        TransportConfig {
+            protocol: Protocol::Tls {
+                addr,
+                server_name: "localhost".to_owned(),
+            },
+            ..TransportConfig::default()
+        }
+    }
+
+    #[test]
+    fn provider_availability_follows_the_build() {
+        assert!(provider_available(CryptoProviderMode::Ring));
+        assert!(!provider_available(CryptoProviderMode::AwsLcRsFips));
+    }
+
+    #[tokio::test]
+    async fn mutual_tls_transport_delivers_frames_with_matching_pin() {
+        let pki = pki();
+        let pin = CertificateInfo::from_der(pki.server.cert.der())
+            .ok()
+            .map(|info| info.spki_sha256);
+        let connector = TlsConnector::new(&identity(&pki), &client_config(pin)).expect("connector");
+        let (addr, server) = serve_one(&pki).await;
+
+        let mut connection = connector
+            .connect_transport(&tls_transport(addr), DowngradePolicy::FailOpen)
+            .await
+            .expect("tls connect");
+        connection
+            .send_frame(b"<event uid=\"tls\"/>")
+            .await
+            .expect("send over tls");
+
+        assert_eq!(server.await.expect("server task"), b"<event uid=\"tls\"/>");
+    }
+
+    #[tokio::test]
+    async fn mismatched_spki_pin_fails_the_handshake() {
+        let pki = pki();
+        let connector =
+            TlsConnector::new(&identity(&pki), &client_config(Some([7; 32]))).expect("connector");
+        let (addr, server) = serve_one(&pki).await;
+
+        let error = connector
+            .connect_transport(&tls_transport(addr), DowngradePolicy::FailOpen)
+            .await
+            .expect_err("pin mismatch must fail");
+        assert!(matches!(error, TlsError::Io(_)));
+        assert!(server.await.expect("server task").is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_unusable_identity_provider_and_revocation_combinations() {
+        let pki = pki();
+        let aws = TlsClientConfig {
+            provider: CryptoProviderMode::AwsLcRs,
+            ..client_config(None)
+        };
+        assert!(matches!(
+            TlsConnector::new(&identity(&pki), &aws),
+            Err(TlsError::Crypto(CryptoError::TlsProviderUnavailable { .. }))
+        ));
+
+        let require = TlsClientConfig {
+            revocation: RevocationPolicy::Require,
+            ..client_config(None)
+        };
+        assert!(matches!(
+            TlsConnector::new(&identity(&pki), &require),
+            Err(TlsError::Crypto(CryptoError::RevocationUnavailable))
+        ));
+        assert!(matches!(
+            TlsConnector::new(&identity(&pki), &client_config(None))
+                .expect("connector")
+                .connect_transport(&TransportConfig::default(), DowngradePolicy::FailOpen)
+                .await,
+            Err(TlsError::NotTlsProtocol)
+        ));
+    }
+}
@@ -1,10 +1,34 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use rustak_core::{CotEvent, DetailNode};
+use rustak_io::{MessageEnvelope, ObservedTime};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use rustak_wire::{MeshFrameCodec, MeshFrameError, TakProtocolVersion};
 use thiserror::Error;
 
-use crate::MtuSafety;
+use crate::socket::{bind_udp_socket_with_joins, MulticastJoin, UdpSocketOptions};
+use crate::{
+    MtuSafety, OversizePolicy, Protocol, TransportConfig, TransportConfigError, TransportFraming,
+    UdpTarget,
+};
+
+/// Largest payload a single IPv4 UDP datagram can carry.
+pub const MAX_UDP_DATAGRAM_BYTES: usize = 65_507;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum UdpSendDecision {
     SendDatagrams(Vec<Vec<u8>>),
+    SendTruncated {
+        datagram: Vec<u8>,
+        original_bytes: usize,
+    },
+    RerouteToStream {
+        payload_bytes: usize,
+    },
     DropOversize {
         payload_bytes: usize,
         max_udp_payload_bytes: usize,
@@ -15,48 +39,842 @@ pub enum UdpSendDecision {
 pub enum UdpPolicyError {
     #[error("max_udp_payload_bytes must be greater than zero")]
     ZeroMaxPayload,
+
+    #[error("udp batch max_datagrams must be greater than zero")]
+    ZeroBatchSize,
+
+    #[error("udp batch max_datagram_bytes must be greater than zero")]
+    ZeroDatagramBytes,
+
+    #[error("malformed udp chunk header")]
+    MalformedChunk,
+
+    #[error("udp chunk reassembler max_pending_messages must be greater than zero")]
+    ZeroPendingChunks,
+
+    #[error("chunked udp message of {bytes} bytes exceeds max_frame_bytes {max_bytes}")]
+    ChunkedMessageTooLarge { bytes: usize, max_bytes: usize },
 }
 
+impl CodedError for UdpPolicyError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ZeroMaxPayload => ErrorCode::new("TRANSPORT", 301),
+            Self::ZeroBatchSize => ErrorCode::new("TRANSPORT", 302),
+            Self::ZeroDatagramBytes => ErrorCode::new("TRANSPORT", 303),
+            Self::MalformedChunk => ErrorCode::new("TRANSPORT", 304),
+            Self::ZeroPendingChunks => ErrorCode::new("TRANSPORT", 305),
+            Self::ChunkedMessageTooLarge { .. } => ErrorCode::new("TRANSPORT", 306),
+        }
+    }
+}
+
+/// Element written in place of a truncated CoT `<detail>` body.
+pub const TRUNCATED_DETAIL_MARKER: &str = "_rustak_truncated";
+
+const CHUNK_MAGIC: [u8; 4] = *b"RTKC";
+
+/// `RTKC` magic, u32 message id, u16 index and u16 count, all big-endian.
+pub const CHUNK_HEADER_BYTES: usize = 12;
+
+/// Decides how `payload` goes out under `mtu_safety`. `limits` bounds the
+/// CoT parse behind [`OversizePolicy::TruncateDetail`].
 pub fn apply_mtu_policy(
     payload: &[u8],
     mtu_safety: &MtuSafety,
+    limits: &Limits,
 ) -> Result<UdpSendDecision, UdpPolicyError> {
-    if mtu_safety.max_udp_payload_bytes == 0 {
+    let max = mtu_safety.max_udp_payload_bytes;
+    if max == 0 {
         return Err(UdpPolicyError::ZeroMaxPayload);
     }
 
-    if payload.len() <= mtu_safety.max_udp_payload_bytes {
+    if payload.len() <= max {
         return Ok(UdpSendDecision::SendDatagrams(vec![payload.to_vec()]));
     }
 
-    if mtu_safety.drop_oversize {
-        return Ok(UdpSendDecision::DropOversize {
+    let drop = UdpSendDecision::DropOversize {
+        payload_bytes: payload.len(),
+        max_udp_payload_bytes: max,
+    };
+
+    let decision = match mtu_safety.oversize {
+        OversizePolicy::Drop => drop,
+        OversizePolicy::Split => {
+            UdpSendDecision::SendDatagrams(payload.chunks(max).map(<[u8]>::to_vec).collect())
+        }
+        OversizePolicy::TruncateDetail => match truncate_detail(payload, limits) {
+            Some(datagram) if datagram.len() <= max => UdpSendDecision::SendTruncated {
+                datagram,
+                original_bytes: payload.len(),
+            },
+            _ => drop,
+        },
+        OversizePolicy::StreamFallback => UdpSendDecision::RerouteToStream {
             payload_bytes: payload.len(),
-            max_udp_payload_bytes: mtu_safety.max_udp_payload_bytes,
+        },
+        OversizePolicy::Chunk => {
+            if !is_cot_xml(payload) {
+                drop
+            } else {
+                chunk_payload(payload, max).map_or(drop, UdpSendDecision::SendDatagrams)
+            }
+        }
+    };
+
+    Ok(decision)
+}
+
+fn is_cot_xml(payload: &[u8]) -> bool {
+    let start = payload
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(payload.len());
+    let trimmed = &payload[start..];
+    trimmed.starts_with(b"<?xml") || trimmed.starts_with(b"<event")
+}
+
+/// Re-serializes a CoT event with its detail replaced by the truncation
+/// marker; `None` when the payload is not a CoT event or has no detail.
+fn truncate_detail(payload: &[u8], limits: &Limits) -> Option<Vec<u8>> {
+    let xml = std::str::from_utf8(payload).ok()?;
+    let mut event = CotEvent::from_xml(xml, limits).ok()?;
+    if event.detail.is_empty() {
+        return None;
+    }
+    event.detail = vec![DetailNode::new(TRUNCATED_DETAIL_MARKER)
+        .with_attribute("original_bytes", payload.len().to_string())];
+    Some(event.to_xml().into_bytes())
+}
+
+fn chunk_payload(payload: &[u8], max_udp_payload_bytes: usize) -> Option<Vec<Vec<u8>>> {
+    let body_bytes = max_udp_payload_bytes.checked_sub(CHUNK_HEADER_BYTES)?;
+    if body_bytes == 0 {
+        return None;
+    }
+    let count = u16::try_from(payload.len().div_ceil(body_bytes)).ok()?;
+    let message_id = fnv1a32(payload);
+
+    Some(
+        payload
+            .chunks(body_bytes)
+            .enumerate()
+            .map(|(index, body)| {
+                let mut datagram = Vec::with_capacity(CHUNK_HEADER_BYTES + body.len());
+                datagram.extend_from_slice(&CHUNK_MAGIC);
+                datagram.extend_from_slice(&message_id.to_be_bytes());
+                datagram.extend_from_slice(&(index as u16).to_be_bytes());
+                datagram.extend_from_slice(&count.to_be_bytes());
+                datagram.extend_from_slice(body);
+                datagram
+            })
+            .collect(),
+    )
+}
+
+fn fnv1a32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// How long a partial chunked message is held after its latest part.
+pub const UDP_CHUNK_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Partial messages are keyed by sender as well as message id, so one peer
+/// cannot fill in or collide with another's.
+type ChunkKey = (SocketAddr, u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingChunks {
+    parts: Vec<Option<Vec<u8>>>,
+    received: usize,
+    bytes: usize,
+    last_seen: Instant,
+}
+
+/// Receive-side counterpart of [`OversizePolicy::Chunk`].
+///
+/// Datagrams without the chunk header pass straight through. Partial
+/// messages are bounded three ways: at most `max_pending_messages` of them,
+/// none larger than `Limits::max_frame_bytes` (judged up front from the
+/// declared part count), and no more than `Limits::max_queue_bytes` held
+/// across all of them. The oldest message is evicted to stay within the
+/// count and byte bounds, and any message with no new part for the idle
+/// timeout is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdpChunkReassembler {
+    max_pending_messages: usize,
+    max_message_bytes: usize,
+    max_pending_bytes: usize,
+    idle_timeout: Duration,
+    pending: BTreeMap<ChunkKey, PendingChunks>,
+    arrival: VecDeque<ChunkKey>,
+    pending_bytes: usize,
+}
+
+impl UdpChunkReassembler {
+    pub fn new(max_pending_messages: usize, limits: &Limits) -> Result<Self, UdpPolicyError> {
+        if max_pending_messages == 0 {
+            return Err(UdpPolicyError::ZeroPendingChunks);
+        }
+        Ok(Self {
+            max_pending_messages,
+            max_message_bytes: limits.max_frame_bytes,
+            max_pending_bytes: limits.max_queue_bytes.max(limits.max_frame_bytes),
+            idle_timeout: UDP_CHUNK_IDLE_TIMEOUT,
+            pending: BTreeMap::new(),
+            arrival: VecDeque::new(),
+            pending_bytes: 0,
+        })
+    }
+
+    /// Drops partial messages after `idle_timeout` instead of
+    /// [`UDP_CHUNK_IDLE_TIMEOUT`].
+    #[must_use]
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn pending_messages(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Part bytes held across all partial messages.
+    #[must_use]
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    /// Like [`Self::accept`], handing a datagram without the chunk header
+    /// back as it is rather than copying it.
+    pub fn accept_bytes(
+        &mut self,
+        peer: SocketAddr,
+        datagram: Bytes,
+        now: Instant,
+    ) -> Result<Option<Bytes>, UdpPolicyError> {
+        if !datagram.starts_with(&CHUNK_MAGIC) {
+            return Ok(Some(datagram));
+        }
+        Ok(self.accept(peer, &datagram, now)?.map(Bytes::from))
+    }
+
+    /// Adds a datagram from `peer` received at `now`, returning the whole
+    /// message once its last part is in.
+    pub fn accept(
+        &mut self,
+        peer: SocketAddr,
+        datagram: &[u8],
+        now: Instant,
+    ) -> Result<Option<Vec<u8>>, UdpPolicyError> {
+        if !datagram.starts_with(&CHUNK_MAGIC) {
+            return Ok(Some(datagram.to_vec()));
+        }
+        if datagram.len() < CHUNK_HEADER_BYTES {
+            return Err(UdpPolicyError::MalformedChunk);
+        }
+
+        let message_id = u32::from_be_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]);
+        let index = usize::from(u16::from_be_bytes([datagram[8], datagram[9]]));
+        let count = usize::from(u16::from_be_bytes([datagram[10], datagram[11]]));
+        if count == 0 || index >= count {
+            return Err(UdpPolicyError::MalformedChunk);
+        }
+        let body = &datagram[CHUNK_HEADER_BYTES..];
+        let key = (peer, message_id);
+        self.evict_idle(now);
+
+        // Every part but the last is full-size, so one of them gives the
+        // size of the whole message before any of it is held.
+        let declared_bytes = count.saturating_mul(body.len());
+        if index + 1 < count && declared_bytes > self.max_message_bytes {
+            self.remove(key);
+            return Err(UdpPolicyError::ChunkedMessageTooLarge {
+                bytes: declared_bytes,
+                max_bytes: self.max_message_bytes,
+            });
+        }
+
+        let held_bytes = match self.pending.get(&key) {
+            Some(entry) if entry.parts.len() != count => {
+                return Err(UdpPolicyError::MalformedChunk);
+            }
+            Some(entry) if entry.parts[index].is_some() => return Ok(None),
+            Some(entry) => entry.bytes,
+            None => {
+                while self.pending.len() >= self.max_pending_messages {
+                    if !self.evict_oldest_except(key) {
+                        break;
+                    }
+                }
+                self.arrival.push_back(key);
+                self.pending.insert(
+                    key,
+                    PendingChunks {
+                        parts: vec![None; count],
+                        received: 0,
+                        bytes: 0,
+                        last_seen: now,
+                    },
+                );
+                0
+            }
+        };
+        if held_bytes + body.len() > self.max_message_bytes {
+            self.remove(key);
+            return Err(UdpPolicyError::ChunkedMessageTooLarge {
+                bytes: held_bytes + body.len(),
+                max_bytes: self.max_message_bytes,
+            });
+        }
+        while self.pending_bytes + body.len() > self.max_pending_bytes {
+            if !self.evict_oldest_except(key) {
+                break;
+            }
+        }
+
+        let Some(entry) = self.pending.get_mut(&key) else {
+            return Ok(None);
+        };
+        entry.parts[index] = Some(body.to_vec());
+        entry.received += 1;
+        entry.bytes += body.len();
+        entry.last_seen = now;
+        self.pending_bytes += body.len();
+        if entry.received < count {
+            return Ok(None);
+        }
+
+        let Some(complete) = self.remove(key) else {
+            return Ok(None);
+        };
+        Ok(Some(
+            complete.parts.into_iter().flatten().flatten().collect(),
+        ))
+    }
+
+    fn evict_idle(&mut self, now: Instant) {
+        let idle: Vec<ChunkKey> = self
+            .pending
+            .iter()
+            .filter(|(_, entry)| {
+                now.saturating_duration_since(entry.last_seen) >= self.idle_timeout
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in idle {
+            self.remove(key);
+        }
+    }
+
+    /// Evicts the oldest partial message other than `keep`; `false` when
+    /// there is none.
+    fn evict_oldest_except(&mut self, keep: ChunkKey) -> bool {
+        let Some(oldest) = self.arrival.iter().copied().find(|key| *key != keep) else {
+            return false;
+        };
+        self.remove(oldest);
+        true
+    }
+
+    fn remove(&mut self, key: ChunkKey) -> Option<PendingChunks> {
+        let removed = self.pending.remove(&key)?;
+        self.pending_bytes -= removed.bytes;
+        self.arrival.retain(|pending| *pending != key);
+        Some(removed)
+    }
+}
+
+/// How many datagrams [`UdpTransport`] drains per receive syscall, and the
+/// largest it accepts whole; longer ones are counted as
+/// [`UdpTransport::truncated_datagrams`] and dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpBatchConfig {
+    pub max_datagrams: usize,
+    pub max_datagram_bytes: usize,
+}
+
+impl Default for UdpBatchConfig {
+    fn default() -> Self {
+        Self {
+            max_datagrams: 32,
+            max_datagram_bytes: MAX_UDP_DATAGRAM_BYTES,
+        }
+    }
+}
+
+impl UdpBatchConfig {
+    pub fn validate(&self) -> Result<(), UdpPolicyError> {
+        if self.max_datagrams == 0 {
+            return Err(UdpPolicyError::ZeroBatchSize);
+        }
+        if self.max_datagram_bytes == 0 {
+            return Err(UdpPolicyError::ZeroDatagramBytes);
+        }
+        Ok(())
+    }
+}
+
+/// Partially received chunked messages a [`UdpTransport`] holds at once.
+pub const UDP_TRANSPORT_PENDING_CHUNKS: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum UdpTransportError {
+    #[error(transparent)]
+    InvalidConfig(#[from] TransportConfigError),
+
+    #[error("udp transport requires a udp protocol")]
+    NotUdp,
+
+    #[error(transparent)]
+    Policy(#[from] UdpPolicyError),
+
+    #[error(transparent)]
+    Mesh(#[from] MeshFrameError),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl CodedError for UdpTransportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidConfig(error) => error.code(),
+            Self::NotUdp => ErrorCode::new("TRANSPORT", 401),
+            Self::Policy(error) => error.code(),
+            Self::Mesh(error) => error.code(),
+            Self::Io(_) => ErrorCode::new("TRANSPORT", 402),
+        }
+    }
+}
+
+/// Unicast, multicast or broadcast datagram transport for
+/// [`Protocol::Udp`].
+///
+/// Binding applies the platform socket options from
+/// [`UdpSocketOptions::for_target`] (group membership, `SO_BROADCAST`,
+/// address reuse). Outbound payloads go through [`apply_mtu_policy`]; inbound
+/// datagrams are reassembled when they carry the chunk header and are
+/// otherwise delivered as-is.
+///
+/// [`Self::send`] and [`Self::recv`] move raw datagrams;
+/// [`Self::send_frame`] and [`Self::recv_frame`] also apply the mesh header
+/// when [`TransportFraming::for_config`] selects it.
+///
+/// Receiving drains up to [`UdpBatchConfig::max_datagrams`] queued
+/// datagrams per wakeup, with one `recvmmsg(2)` on Linux and one
+/// `recv_from` elsewhere, and stamps them all with one [`ObservedTime`].
+/// Datagrams are read into reusable buffers and handed out without a copy.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: tokio::net::UdpSocket,
+    destination: SocketAddr,
+    mtu_safety: MtuSafety,
+    limits: Limits,
+    reassembler: UdpChunkReassembler,
+    batch: UdpBatchConfig,
+    buffers: Vec<BytesMut>,
+    received: VecDeque<(Bytes, SocketAddr)>,
+    observed: ObservedTime,
+    malformed_datagrams: u64,
+    truncated_datagrams: u64,
+    datagrams_received: u64,
+    multicast_joins: Vec<MulticastJoin>,
+    framing: TransportFraming,
+    mesh: MeshFrameCodec,
+}
+
+impl UdpTransport {
+    /// Binds the socket described by `config`. Must be called inside a
+    /// Tokio runtime.
+    pub fn bind(config: &TransportConfig) -> Result<Self, UdpTransportError> {
+        config.validate()?;
+        let Protocol::Udp { bind_addr, target } = &config.protocol else {
+            return Err(UdpTransportError::NotUdp);
+        };
+
+        let (socket, multicast_joins) =
+            bind_udp_socket_with_joins(&UdpSocketOptions::for_target(*bind_addr, target))?;
+        socket.set_nonblocking(true)?;
+        let mtu_safety = config.mtu_safety.clone().unwrap_or(MtuSafety {
+            max_udp_payload_bytes: MAX_UDP_DATAGRAM_BYTES,
+            oversize: OversizePolicy::Drop,
         });
+        Ok(Self {
+            socket: tokio::net::UdpSocket::from_std(socket)?,
+            destination: udp_destination(target),
+            mtu_safety,
+            limits: config.limits.clone(),
+            reassembler: UdpChunkReassembler::new(UDP_TRANSPORT_PENDING_CHUNKS, &config.limits)?,
+            batch: UdpBatchConfig::default(),
+            buffers: batch_buffers(UdpBatchConfig::default()),
+            received: VecDeque::new(),
+            observed: ObservedTime::now(),
+            malformed_datagrams: 0,
+            truncated_datagrams: 0,
+            datagrams_received: 0,
+            multicast_joins,
+            framing: TransportFraming::for_config(config),
+            mesh: MeshFrameCodec::from_limits(TakProtocolVersion::V1, &config.limits),
+        })
+    }
+
+    /// Receives in batches sized by `batch` instead of
+    /// [`UdpBatchConfig::default`].
+    pub fn with_batch_config(mut self, batch: UdpBatchConfig) -> Result<Self, UdpPolicyError> {
+        batch.validate()?;
+        self.batch = batch;
+        self.buffers = batch_buffers(batch);
+        Ok(self)
+    }
+
+    #[must_use]
+    pub fn batch_config(&self) -> UdpBatchConfig {
+        self.batch
+    }
+
+    #[must_use]
+    pub fn framing(&self) -> TransportFraming {
+        self.framing
+    }
+
+    #[must_use]
+    pub fn socket(&self) -> &tokio::net::UdpSocket {
+        &self.socket
+    }
+
+    /// Where [`Self::send`] delivers: the unicast peer, the multicast group
+    /// or the IPv4 limited broadcast address.
+    #[must_use]
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+
+    /// Datagrams discarded because their chunk header, or under mesh framing
+    /// their mesh header, was malformed.
+    #[must_use]
+    pub fn malformed_datagrams(&self) -> u64 {
+        self.malformed_datagrams
+    }
+
+    /// Datagrams longer than [`UdpBatchConfig::max_datagram_bytes`], dropped
+    /// because only their head was read.
+    #[must_use]
+    pub fn truncated_datagrams(&self) -> u64 {
+        self.truncated_datagrams
+    }
+
+    /// Datagrams read from the socket, before reassembly and framing.
+    /// Comparing this with decoded frames tells "nothing arrives" apart from
+    /// "nothing decodes".
+    #[must_use]
+    pub fn datagrams_received(&self) -> u64 {
+        self.datagrams_received
+    }
+
+    /// Group joins made at bind, one per interface; empty for unicast and
+    /// broadcast targets.
+    #[must_use]
+    pub fn multicast_joins(&self) -> &[MulticastJoin] {
+        &self.multicast_joins
     }
 
-    let datagrams = payload
-        .chunks(mtu_safety.max_udp_payload_bytes)
-        .map(|chunk| chunk.to_vec())
+    /// Applies the MTU policy and sends the resulting datagrams. Returns the
+    /// decision so callers can report drops or reroute to a stream; only
+    /// `SendDatagrams` and `SendTruncated` put anything on the wire.
+    pub async fn send(&self, payload: &[u8]) -> Result<UdpSendDecision, UdpTransportError> {
+        let decision = apply_mtu_policy(payload, &self.mtu_safety, &self.limits)?;
+        match &decision {
+            UdpSendDecision::SendDatagrams(datagrams) => {
+                for datagram in datagrams {
+                    self.socket.send_to(datagram, self.destination).await?;
+                }
+            }
+            UdpSendDecision::SendTruncated { datagram, .. } => {
+                self.socket.send_to(datagram, self.destination).await?;
+            }
+            UdpSendDecision::RerouteToStream { .. } | UdpSendDecision::DropOversize { .. } => {}
+        }
+        Ok(decision)
+    }
+
+    /// Frames `payload` for [`Self::framing`] (prefixing the mesh header
+    /// when selected) and sends it like [`Self::send`].
+    pub async fn send_frame(&self, payload: &[u8]) -> Result<UdpSendDecision, UdpTransportError> {
+        if self.framing == TransportFraming::TakProtocolMeshHeader {
+            return self.send(&self.mesh.encode(payload)?).await;
+        }
+        self.send(payload).await
+    }
+
+    /// Like [`Self::recv`], but under mesh framing strips the header and
+    /// skips datagrams without a valid one. The raw frame keeps the header.
+    pub async fn recv_frame(&mut self) -> Result<MessageEnvelope<Bytes>, UdpTransportError> {
+        loop {
+            let envelope = self.recv().await?;
+            if self.framing != TransportFraming::TakProtocolMeshHeader {
+                return Ok(envelope);
+            }
+            match self.mesh.decode(&envelope.message) {
+                Ok(payload) => {
+                    let payload = envelope.message.slice_ref(payload);
+                    let raw_frame = envelope.message.clone();
+                    return Ok(MessageEnvelope {
+                        message: payload,
+                        ..envelope
+                    }
+                    .with_raw_frame(raw_frame));
+                }
+                Err(_) => self.malformed_datagrams += 1,
+            }
+        }
+    }
+
+    /// Waits for the next complete message, tagged with the sender address
+    /// and the time its batch was received.
+    pub async fn recv(&mut self) -> Result<MessageEnvelope<Bytes>, UdpTransportError> {
+        loop {
+            let Some((datagram, peer)) = self.received.pop_front() else {
+                self.recv_batch().await?;
+                continue;
+            };
+            match self
+                .reassembler
+                .accept_bytes(peer, datagram, self.observed.monotonic)
+            {
+                // Built directly so the batch timestamp is not re-sampled
+                // per envelope by `MessageEnvelope::new`.
+                Ok(Some(message)) => {
+                    return Ok(MessageEnvelope {
+                        observed: self.observed.clone(),
+                        peer: Some(peer),
+                        raw_frame: None,
+                        message,
+                    });
+                }
+                Ok(None) => {}
+                Err(_) => self.malformed_datagrams += 1,
+            }
+        }
+    }
+
+    /// Waits until the socket is readable, then queues every datagram one
+    /// syscall returns.
+    async fn recv_batch(&mut self) -> io::Result<()> {
+        for buffer in &mut self.buffers {
+            buffer.clear();
+            buffer.reserve(self.batch.max_datagram_bytes);
+        }
+        let datagrams = recv_datagrams(&self.socket, &mut self.buffers, self.batch).await?;
+        self.observed = ObservedTime::now();
+        for (buffer, datagram) in self.buffers.iter_mut().zip(datagrams) {
+            self.datagrams_received += 1;
+            if datagram.truncated {
+                self.truncated_datagrams += 1;
+                continue;
+            }
+            // Handed out without a copy; the next batch reuses the buffer's
+            // allocation once every message split from it has been dropped.
+            self.received
+                .push_back((buffer.split().freeze(), datagram.peer));
+        }
+        Ok(())
+    }
+}
+
+fn batch_buffers(batch: UdpBatchConfig) -> Vec<BytesMut> {
+    (0..batch.max_datagrams)
+        .map(|_| BytesMut::with_capacity(batch.max_datagram_bytes))
+        .collect()
+}
+
+fn udp_destination(target: &UdpTarget) -> SocketAddr {
+    match target {
+        UdpTarget::Unicast(addr) => *addr,
+        UdpTarget::Multicast { group, port } => SocketAddr::V4(SocketAddrV4::new(*group, *port)),
+        UdpTarget::Broadcast { port } => {
+            SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, *port))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ReceivedDatagram {
+    peer: SocketAddr,
+    truncated: bool,
+}
+
+/// Fills `buffers` from the front with the datagrams one `recvmmsg(2)`
+/// returns once the socket is readable, setting each filled buffer's length.
+/// Every buffer must have `batch.max_datagram_bytes` of spare capacity.
+#[cfg(target_os = "linux")]
+async fn recv_datagrams(
+    socket: &tokio::net::UdpSocket,
+    buffers: &mut [BytesMut],
+    batch: UdpBatchConfig,
+) -> io::Result<Vec<ReceivedDatagram>> {
+    socket
+        .async_io(tokio::io::Interest::READABLE, || {
+            recvmmsg(socket, buffers, batch.max_datagram_bytes)
+        })
+        .await
+}
+
+#[cfg(target_os = "linux")]
+fn recvmmsg(
+    socket: &tokio::net::UdpSocket,
+    buffers: &mut [BytesMut],
+    max_datagram_bytes: usize,
+) -> io::Result<Vec<ReceivedDatagram>> {
+    use std::mem;
+    use std::os::fd::AsRawFd;
+
+    let count = buffers.len();
+    let mut iovecs: Vec<libc::iovec> = buffers
+        .iter_mut()
+        .map(|buffer| {
+            debug_assert!(buffer.is_empty() && buffer.capacity() >= max_datagram_bytes);
+            libc::iovec {
+                iov_base: buffer.spare_capacity_mut().as_mut_ptr().cast(),
+                iov_len: max_datagram_bytes,
+            }
+        })
         .collect();
-    Ok(UdpSendDecision::SendDatagrams(datagrams))
+    // SAFETY: `sockaddr_storage` and `mmsghdr` are plain C structs for which
+    // all-zero bytes are a valid (empty) value.
+    let mut addresses: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; count];
+    let mut headers: Vec<libc::mmsghdr> = (0..count)
+        .map(|index| {
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_name = addresses[index..].as_mut_ptr().cast();
+            header.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as u32;
+            header.msg_hdr.msg_iov = iovecs[index..].as_mut_ptr();
+            header.msg_hdr.msg_iovlen = 1;
+            header
+        })
+        .collect();
+
+    // SAFETY: every header points at a live iovec/sockaddr_storage slot and
+    // each iovec points at the spare capacity of a distinct buffer, at least
+    // the advertised length; all of them outlive the call. The socket is
+    // non-blocking, so an empty queue is `EWOULDBLOCK`, which `async_io`
+    // waits out.
+    let received = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            headers.as_mut_ptr(),
+            count as libc::c_uint,
+            libc::MSG_DONTWAIT,
+            std::ptr::null_mut(),
+        )
+    };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let received = received as usize;
+    let mut datagrams = Vec::with_capacity(received);
+    for ((header, address), buffer) in headers.iter().zip(&addresses).zip(buffers).take(received) {
+        let len = (header.msg_len as usize).min(max_datagram_bytes);
+        // SAFETY: the kernel wrote `msg_len` bytes, capped by the iovec
+        // length, into this buffer's spare capacity.
+        unsafe { buffer.set_len(len) };
+        datagrams.push(ReceivedDatagram {
+            peer: socket_addr_from_storage(address)?,
+            truncated: header.msg_hdr.msg_flags & libc::MSG_TRUNC != 0,
+        });
+    }
+    Ok(datagrams)
+}
+
+#[cfg(target_os = "linux")]
+fn socket_addr_from_storage(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+
+    match i32::from(storage.ss_family) {
+        libc::AF_INET => {
+            // SAFETY: the kernel wrote a `sockaddr_in` for AF_INET peers.
+            let address =
+                unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            Ok(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)),
+                u16::from_be(address.sin_port),
+            )))
+        }
+        libc::AF_INET6 => {
+            // SAFETY: the kernel wrote a `sockaddr_in6` for AF_INET6 peers.
+            let address = unsafe {
+                &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(address.sin6_addr.s6_addr),
+                u16::from_be(address.sin6_port),
+                u32::from_be(address.sin6_flowinfo),
+                address.sin6_scope_id,
+            )))
+        }
+        family => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported peer address family {family}"),
+        )),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn recv_datagrams(
+    socket: &tokio::net::UdpSocket,
+    buffers: &mut [BytesMut],
+    batch: UdpBatchConfig,
+) -> io::Result<Vec<ReceivedDatagram>> {
+    let buffer = &mut buffers[0];
+    buffer.resize(batch.max_datagram_bytes, 0);
+    let (len, peer) = socket.recv_from(buffer).await?;
+    buffer.truncate(len);
+    Ok(vec![ReceivedDatagram {
+        peer,
+        // Without MSG_TRUNC reporting, a full buffer is the only hint.
+        truncated: len == batch.max_datagram_bytes && len < MAX_UDP_DATAGRAM_BYTES,
+    }])
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::MtuSafety;
+    use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+    use std::time::{Duration, Instant};
+
+    use rustak_core::CotEvent;
+    use rustak_limits::Limits;
+
+    use rustak_wire::WireFormat;
 
-    use super::{apply_mtu_policy, UdpPolicyError, UdpSendDecision};
+    use crate::{
+        MtuSafety, OversizePolicy, Protocol, TransportConfig, TransportFraming, UdpTarget,
+    };
+
+    #[cfg(target_os = "linux")]
+    use super::socket_addr_from_storage;
+    use super::{
+        apply_mtu_policy, udp_destination, UdpBatchConfig, UdpChunkReassembler, UdpPolicyError,
+        UdpSendDecision, UdpTransport, UdpTransportError, TRUNCATED_DETAIL_MARKER,
+    };
+
+    const PEER_A: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 4242);
+    const PEER_B: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 4242);
 
     #[test]
     fn sends_single_datagram_when_payload_fits_limit() {
         let mtu_safety = MtuSafety {
             max_udp_payload_bytes: 8,
-            drop_oversize: true,
+            oversize: OversizePolicy::Drop,
         };
 
-        let decision = apply_mtu_policy(b"tak", &mtu_safety).expect("policy should evaluate");
+        let decision = apply_mtu_policy(b"tak", &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
         assert_eq!(
             decision,
             UdpSendDecision::SendDatagrams(vec![b"tak".to_vec()])
@@ -67,10 +885,11 @@ mod tests {
     fn drops_oversize_payload_when_drop_policy_enabled() {
         let mtu_safety = MtuSafety {
             max_udp_payload_bytes: 4,
-            drop_oversize: true,
+            oversize: OversizePolicy::Drop,
         };
 
-        let decision = apply_mtu_policy(b"oversize", &mtu_safety).expect("policy should evaluate");
+        let decision = apply_mtu_policy(b"oversize", &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
         assert_eq!(
             decision,
             UdpSendDecision::DropOversize {
@@ -81,13 +900,14 @@ mod tests {
     }
 
     #[test]
-    fn fragments_oversize_payload_when_drop_policy_disabled() {
+    fn fragments_oversize_payload_with_split_policy() {
         let mtu_safety = MtuSafety {
             max_udp_payload_bytes: 3,
-            drop_oversize: false,
+            oversize: OversizePolicy::Split,
         };
 
-        let decision = apply_mtu_policy(b"abcdefg", &mtu_safety).expect("policy should evaluate");
+        let decision = apply_mtu_policy(b"abcdefg", &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
         assert_eq!(
             decision,
             UdpSendDecision::SendDatagrams(vec![b"abc".to_vec(), b"def".to_vec(), b"g".to_vec()])
@@ -98,10 +918,486 @@ mod tests {
     fn rejects_zero_udp_payload_limit() {
         let mtu_safety = MtuSafety {
             max_udp_payload_bytes: 0,
-            drop_oversize: true,
+            oversize: OversizePolicy::Drop,
         };
 
-        let error = apply_mtu_policy(b"tak", &mtu_safety).expect_err("zero max payload must fail");
+        let error = apply_mtu_policy(b"tak", &mtu_safety, &Limits::default())
+            .expect_err("zero max payload must fail");
         assert_eq!(error, UdpPolicyError::ZeroMaxPayload);
     }
+
+    const COT_WITH_DETAIL: &[u8] = b"<event uid=\"u1\" type=\"a-f-G\"><point lat=\"1\" lon=\"2\"/><detail><remarks>lots and lots of remarks text</remarks></detail></event>";
+
+    #[test]
+    fn truncate_detail_replaces_body_with_marker() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 320,
+            oversize: OversizePolicy::TruncateDetail,
+        };
+        // Single quotes and a detail child named like the element it sits in
+        // would trip a byte search; the parser takes them in its stride.
+        let payload = format!(
+            "<event version='2.0' uid='u1' type='a-f-G' how='m-g' \
+             time='2026-01-01T00:00:00Z' start='2026-01-01T00:00:00Z' \
+             stale='2026-01-01T00:05:00Z'><point lat='1' lon='2' hae='0' ce='5' le='5'/>\
+             <detail><detail-note/><remarks>{}</remarks></detail></event>",
+            "lots of remarks text ".repeat(20)
+        );
+
+        let decision = apply_mtu_policy(payload.as_bytes(), &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
+        let UdpSendDecision::SendTruncated {
+            datagram,
+            original_bytes,
+        } = decision
+        else {
+            panic!("expected truncated datagram, got {decision:?}");
+        };
+        assert_eq!(original_bytes, payload.len());
+        let text = String::from_utf8(datagram).expect("utf8");
+        let event = CotEvent::from_xml(&text, &Limits::default()).expect("still a CoT event");
+        assert_eq!(event.uid, "u1");
+        assert_eq!(event.detail.len(), 1);
+        let original_bytes = original_bytes.to_string();
+        assert_eq!(
+            event
+                .detail_element(TRUNCATED_DETAIL_MARKER)
+                .and_then(|marker| marker.attribute("original_bytes")),
+            Some(original_bytes.as_str())
+        );
+        assert!(!text.contains("remarks"));
+    }
+
+    #[test]
+    fn truncate_detail_drops_events_without_detail() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 16,
+            oversize: OversizePolicy::TruncateDetail,
+        };
+        let payload = b"<event version=\"2.0\" uid=\"u1\" type=\"a-f-G\" \
+            time=\"2026-01-01T00:00:00Z\" start=\"2026-01-01T00:00:00Z\" \
+            stale=\"2026-01-01T00:05:00Z\"><point lat=\"1\" lon=\"2\"/></event>";
+
+        let decision =
+            apply_mtu_policy(payload, &mtu_safety, &Limits::default()).expect("evaluate");
+        assert!(matches!(decision, UdpSendDecision::DropOversize { .. }));
+    }
+
+    #[test]
+    fn truncate_detail_drops_unrecognized_payload() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 4,
+            oversize: OversizePolicy::TruncateDetail,
+        };
+
+        let decision = apply_mtu_policy(b"\x00binary-blob", &mtu_safety, &Limits::default())
+            .expect("evaluate");
+        assert!(matches!(decision, UdpSendDecision::DropOversize { .. }));
+    }
+
+    #[test]
+    fn stream_fallback_hands_payload_back() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 16,
+            oversize: OversizePolicy::StreamFallback,
+        };
+
+        let decision = apply_mtu_policy(COT_WITH_DETAIL, &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
+        assert_eq!(
+            decision,
+            UdpSendDecision::RerouteToStream {
+                payload_bytes: COT_WITH_DETAIL.len()
+            }
+        );
+    }
+
+    #[test]
+    fn chunk_policy_round_trips_through_reassembler() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 40,
+            oversize: OversizePolicy::Chunk,
+        };
+
+        let decision = apply_mtu_policy(COT_WITH_DETAIL, &mtu_safety, &Limits::default())
+            .expect("policy should evaluate");
+        let UdpSendDecision::SendDatagrams(datagrams) = decision else {
+            panic!("chunk policy should produce datagrams");
+        };
+        assert!(datagrams.len() > 1);
+        assert!(datagrams.iter().all(|datagram| datagram.len() <= 40));
+
+        let mut reassembler = UdpChunkReassembler::new(4, &Limits::default()).expect("reassembler");
+        let now = Instant::now();
+        let mut complete = None;
+        for datagram in datagrams.iter().rev() {
+            assert!(complete.is_none());
+            complete = reassembler
+                .accept(PEER_A, datagram, now)
+                .expect("valid chunk");
+        }
+        assert_eq!(complete.as_deref(), Some(COT_WITH_DETAIL));
+        assert_eq!(reassembler.pending_messages(), 0);
+        assert_eq!(reassembler.pending_bytes(), 0);
+    }
+
+    #[test]
+    fn chunk_policy_drops_unrecognized_payload() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 16,
+            oversize: OversizePolicy::Chunk,
+        };
+
+        let decision =
+            apply_mtu_policy(&[0xCD; 64], &mtu_safety, &Limits::default()).expect("evaluate");
+        assert!(matches!(decision, UdpSendDecision::DropOversize { .. }));
+    }
+
+    #[test]
+    fn reassembler_evicts_oldest_partial_message() {
+        let mtu_safety = MtuSafety {
+            max_udp_payload_bytes: 40,
+            oversize: OversizePolicy::Chunk,
+        };
+        let second = b"<event uid=\"u2\"><detail>a different and long enough body</detail></event>";
+        let chunks_of =
+            |payload: &[u8]| match apply_mtu_policy(payload, &mtu_safety, &Limits::default()) {
+                Ok(UdpSendDecision::SendDatagrams(datagrams)) => datagrams,
+                other => panic!("unexpected decision {other:?}"),
+            };
+
+        let mut reassembler = UdpChunkReassembler::new(1, &Limits::default()).expect("reassembler");
+        let now = Instant::now();
+        let first_chunks = chunks_of(COT_WITH_DETAIL);
+        assert_eq!(reassembler.accept(PEER_A, &first_chunks[0], now), Ok(None));
+        let second_chunks = chunks_of(second);
+        assert_eq!(reassembler.accept(PEER_A, &second_chunks[0], now), Ok(None));
+        assert_eq!(reassembler.pending_messages(), 1);
+
+        for chunk in &first_chunks[1..] {
+            assert_eq!(reassembler.accept(PEER_A, chunk, now), Ok(None));
+        }
+        assert_eq!(
+            reassembler.accept(PEER_A, b"plain datagram", now),
+            Ok(Some(b"plain datagram".to_vec()))
+        );
+    }
+
+    /// A chunk datagram built by hand, as a hostile sender would.
+    fn chunk(message_id: u32, index: u16, count: u16, body: &[u8]) -> Vec<u8> {
+        let mut datagram = b"RTKC".to_vec();
+        datagram.extend_from_slice(&message_id.to_be_bytes());
+        datagram.extend_from_slice(&index.to_be_bytes());
+        datagram.extend_from_slice(&count.to_be_bytes());
+        datagram.extend_from_slice(body);
+        datagram
+    }
+
+    #[test]
+    fn reassembler_rejects_declared_counts_over_max_frame_bytes() {
+        let limits = Limits {
+            max_frame_bytes: 1_000,
+            max_xml_scan_bytes: 1_000,
+            max_protobuf_bytes: 1_000,
+            ..Limits::default()
+        };
+        let mut reassembler = UdpChunkReassembler::new(4, &limits).expect("reassembler");
+        let now = Instant::now();
+
+        let error = reassembler
+            .accept(PEER_A, &chunk(7, 0, u16::MAX, &[b'x'; 100]), now)
+            .expect_err("declared size is over the limit");
+        assert_eq!(
+            error,
+            UdpPolicyError::ChunkedMessageTooLarge {
+                bytes: usize::from(u16::MAX) * 100,
+                max_bytes: 1_000,
+            }
+        );
+        assert_eq!(reassembler.pending_messages(), 0);
+        assert_eq!(reassembler.pending_bytes(), 0);
+
+        // Ten 100-byte parts would fit exactly; an oversized last part does not.
+        for index in 0..9 {
+            let part = chunk(8, index, 10, &[b'y'; 100]);
+            assert_eq!(reassembler.accept(PEER_A, &part, now), Ok(None));
+        }
+        assert_eq!(reassembler.pending_bytes(), 900);
+        let error = reassembler
+            .accept(PEER_A, &chunk(8, 9, 10, &[b'y'; 200]), now)
+            .expect_err("held bytes go over the limit");
+        assert!(matches!(
+            error,
+            UdpPolicyError::ChunkedMessageTooLarge { bytes: 1_100, .. }
+        ));
+        assert_eq!(reassembler.pending_messages(), 0);
+        assert_eq!(reassembler.pending_bytes(), 0);
+    }
+
+    #[test]
+    fn reassembler_keeps_peers_sharing_a_message_id_apart() {
+        let mut reassembler = UdpChunkReassembler::new(4, &Limits::default()).expect("reassembler");
+        let now = Instant::now();
+
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(1, 0, 2, b"a0"), now),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.accept(PEER_B, &chunk(1, 0, 2, b"b0"), now),
+            Ok(None)
+        );
+        assert_eq!(reassembler.pending_messages(), 2);
+        assert_eq!(
+            reassembler.accept(PEER_B, &chunk(1, 1, 2, b"b1"), now),
+            Ok(Some(b"b0b1".to_vec()))
+        );
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(1, 1, 2, b"a1"), now),
+            Ok(Some(b"a0a1".to_vec()))
+        );
+    }
+
+    #[test]
+    fn reassembler_drops_idle_messages_and_stays_within_the_byte_budget() {
+        let limits = Limits {
+            max_frame_bytes: 100,
+            max_xml_scan_bytes: 100,
+            max_protobuf_bytes: 100,
+            max_queue_bytes: 150,
+            ..Limits::default()
+        };
+        let mut reassembler = UdpChunkReassembler::new(8, &limits)
+            .expect("reassembler")
+            .with_idle_timeout(Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(1, 0, 2, &[0; 50]), start),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(2, 0, 2, &[0; 50]), start),
+            Ok(None)
+        );
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(3, 0, 2, &[0; 50]), start),
+            Ok(None)
+        );
+        assert_eq!(reassembler.pending_bytes(), 150);
+        // A fourth message pushes the oldest out of the 150-byte budget.
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(4, 0, 2, &[0; 50]), start),
+            Ok(None)
+        );
+        assert_eq!(reassembler.pending_messages(), 3);
+        assert_eq!(reassembler.pending_bytes(), 150);
+        assert_eq!(
+            reassembler.accept(PEER_A, &chunk(1, 1, 2, b"late"), start),
+            Ok(None)
+        );
+
+        let later = start + Duration::from_secs(2);
+        assert_eq!(
+            reassembler.accept(PEER_B, &chunk(9, 0, 2, b"b0"), later),
+            Ok(None)
+        );
+        assert_eq!(reassembler.pending_messages(), 1);
+        assert_eq!(reassembler.pending_bytes(), 2);
+    }
+
+    #[test]
+    fn batch_config_rejects_zero_sizes() {
+        let error = UdpBatchConfig {
+            max_datagrams: 0,
+            ..UdpBatchConfig::default()
+        }
+        .validate()
+        .expect_err("zero batch must fail");
+        assert_eq!(error, UdpPolicyError::ZeroBatchSize);
+
+        let error = UdpBatchConfig {
+            max_datagram_bytes: 0,
+            ..UdpBatchConfig::default()
+        }
+        .validate()
+        .expect_err("zero datagram bytes must fail");
+        assert_eq!(error, UdpPolicyError::ZeroDatagramBytes);
+    }
+
+    #[tokio::test]
+    async fn udp_transport_drains_queued_datagrams_in_one_batch() {
+        let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+        let mut receiver = UdpTransport::bind(&TransportConfig {
+            protocol: Protocol::Udp {
+                bind_addr: loopback,
+                target: UdpTarget::Unicast(loopback),
+            },
+            ..TransportConfig::default()
+        })
+        .expect("receiver")
+        .with_batch_config(UdpBatchConfig {
+            max_datagrams: 8,
+            max_datagram_bytes: 64,
+        })
+        .expect("batch config");
+        let target = receiver.socket().local_addr().expect("receiver addr");
+        let sender = UdpSocket::bind("127.0.0.1:0").expect("bind sender");
+        let sender_addr = sender.local_addr().expect("sender addr");
+        for payload in [&b"alpha"[..], b"bravo", &[b'x'; 65], b"charlie"] {
+            sender.send_to(payload, target).expect("send datagram");
+        }
+
+        let mut envelopes = Vec::new();
+        for _ in 0..3 {
+            let envelope = tokio::time::timeout(Duration::from_secs(2), receiver.recv())
+                .await
+                .expect("datagram arrives")
+                .expect("recv");
+            assert_eq!(envelope.peer, Some(sender_addr));
+            envelopes.push(envelope);
+        }
+        let payloads: Vec<_> = envelopes.iter().map(|env| env.message.to_vec()).collect();
+        assert_eq!(
+            payloads,
+            vec![b"alpha".to_vec(), b"bravo".to_vec(), b"charlie".to_vec()]
+        );
+        assert_eq!(receiver.truncated_datagrams(), 1);
+        assert_eq!(receiver.datagrams_received(), 4);
+        #[cfg(target_os = "linux")]
+        assert!(
+            envelopes
+                .iter()
+                .all(|env| env.observed.monotonic == envelopes[0].observed.monotonic),
+            "one recvmmsg, one timestamp"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn ipv6_peer_flowinfo_is_read_in_host_byte_order() {
+        // SAFETY: all-zero bytes are a valid `sockaddr_storage`, and
+        // `sockaddr_in6` fits inside it.
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let address = unsafe {
+            &mut *(&mut storage as *mut libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+        };
+        address.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        address.sin6_port = 4242_u16.to_be();
+        address.sin6_flowinfo = 0x000a_bcde_u32.to_be();
+        address.sin6_addr.s6_addr = std::net::Ipv6Addr::LOCALHOST.octets();
+        address.sin6_scope_id = 3;
+
+        let SocketAddr::V6(peer) = socket_addr_from_storage(&storage).expect("peer") else {
+            panic!("expected an IPv6 peer");
+        };
+        assert_eq!(peer.port(), 4242);
+        assert_eq!(peer.flowinfo(), 0x000a_bcde);
+        assert_eq!(peer.scope_id(), 3);
+    }
+
+    #[tokio::test]
+    async fn udp_transport_chunks_and_reassembles_oversize_cot() {
+        let unicast = |bind: SocketAddr, peer: SocketAddr| TransportConfig {
+            protocol: Protocol::Udp {
+                bind_addr: bind,
+                target: UdpTarget::Unicast(peer),
+            },
+            mtu_safety: Some(MtuSafety {
+                max_udp_payload_bytes: 64,
+                oversize: OversizePolicy::Chunk,
+            }),
+            ..TransportConfig::default()
+        };
+        let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+        let mut receiver = UdpTransport::bind(&unicast(loopback, loopback)).expect("receiver");
+        let receiver_addr = receiver.socket().local_addr().expect("receiver addr");
+        let sender = UdpTransport::bind(&unicast(loopback, receiver_addr)).expect("sender");
+        let sender_addr = sender.socket().local_addr().expect("sender addr");
+
+        let event = format!(
+            "<event uid=\"u\" type=\"a-f-G\"><detail>{}</detail></event>",
+            "x".repeat(150)
+        );
+        let decision = sender.send(event.as_bytes()).await.expect("send");
+        assert!(
+            matches!(&decision, UdpSendDecision::SendDatagrams(datagrams) if datagrams.len() > 1)
+        );
+
+        let envelope = tokio::time::timeout(Duration::from_secs(2), receiver.recv())
+            .await
+            .expect("datagrams arrive")
+            .expect("recv");
+        assert_eq!(envelope.message, event.as_bytes());
+        assert_eq!(envelope.peer, Some(sender_addr));
+
+        let dropped = sender.send(&[0_u8; 65]).await.expect("policy");
+        assert!(matches!(dropped, UdpSendDecision::DropOversize { .. }));
+    }
+
+    #[tokio::test]
+    async fn udp_transport_applies_mesh_header_for_tak_protocol() {
+        let loopback = SocketAddr::from(([127, 0, 0, 1], 0));
+        let mesh = |bind: SocketAddr, peer: SocketAddr| TransportConfig {
+            protocol: Protocol::Udp {
+                bind_addr: bind,
+                target: UdpTarget::Unicast(peer),
+            },
+            wire_format: WireFormat::TakProtocolV1,
+            ..TransportConfig::default()
+        };
+        let mut receiver = UdpTransport::bind(&mesh(loopback, loopback)).expect("receiver");
+        assert_eq!(receiver.framing(), TransportFraming::TakProtocolMeshHeader);
+        let receiver_addr = receiver.socket().local_addr().expect("receiver addr");
+        let sender = UdpTransport::bind(&mesh(loopback, receiver_addr)).expect("sender");
+
+        sender
+            .send(b"\xbf\x09\xbf\x0a")
+            .await
+            .expect("send bad version");
+        sender.send_frame(b"\x12\x00").await.expect("send frame");
+
+        let envelope = tokio::time::timeout(Duration::from_secs(2), receiver.recv_frame())
+            .await
+            .expect("frame arrives")
+            .expect("recv");
+        assert_eq!(envelope.message, &b"\x12\x00"[..]);
+        assert_eq!(
+            envelope.raw_frame.as_deref(),
+            Some(&b"\xbf\x01\xbf\x12\x00"[..])
+        );
+        assert_eq!(receiver.malformed_datagrams(), 1);
+        assert_eq!(receiver.datagrams_received(), 2);
+        assert!(receiver.multicast_joins().is_empty());
+
+        let xml = UdpTransport::bind(&TransportConfig {
+            wire_format: WireFormat::Xml,
+            ..mesh(loopback, receiver_addr)
+        })
+        .expect("xml");
+        assert_eq!(xml.framing(), TransportFraming::XmlNewlineDelimited);
+    }
+
+    #[test]
+    fn udp_destination_follows_target_kind() {
+        assert_eq!(
+            udp_destination(&UdpTarget::Multicast {
+                group: Ipv4Addr::new(239, 2, 3, 1),
+                port: 6969,
+            }),
+            SocketAddr::from(([239, 2, 3, 1], 6969))
+        );
+        assert_eq!(
+            udp_destination(&UdpTarget::Broadcast { port: 4242 }),
+            SocketAddr::from(([255, 255, 255, 255], 4242))
+        );
+    }
+
+    #[test]
+    fn udp_transport_rejects_stream_protocols() {
+        assert!(matches!(
+            UdpTransport::bind(&TransportConfig::default()),
+            Err(UdpTransportError::NotUdp)
+        ));
+    }
 }
@@ -0,0 +1,276 @@
+//! Replays recorded server-side negotiation streams through
+//! `TransportConnection::negotiate` and checks the resulting state, the
+//! framing switch and what the client sent back. The captures live under
+//! `tests/protocol_conformance/fixtures/negotiation`; see the README there.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rustak_limits::Limits;
+use rustak_transport::{
+    NegotiationOutcome, TransportComposeError, TransportConfig, TransportConnection,
+    TransportFraming,
+};
+use rustak_wire::negotiation::events::TakControlMessage;
+use rustak_wire::{
+    DowngradePolicy, NegotiationEventKind, NegotiationReason, NegotiationState, TakProtocolVersion,
+    WireFormat,
+};
+use tokio::io::{duplex, split, AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf};
+
+/// Bytes per write when a capture is replayed in pieces.
+const CHUNK_BYTES: usize = 7;
+
+fn capture(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("..")
+        .join("tests")
+        .join("protocol_conformance")
+        .join("fixtures")
+        .join("negotiation")
+        .join(format!("{name}.bin"));
+    std::fs::read(&path).unwrap_or_else(|error| panic!("read {}: {error}", path.display()))
+}
+
+struct Replay {
+    connection: TransportConnection<DuplexStream>,
+    result: Result<NegotiationOutcome, TransportComposeError>,
+    server: ReadHalf<DuplexStream>,
+}
+
+impl Replay {
+    /// Drops the client and returns every frame it wrote during the replay.
+    async fn client_frames(self) -> Vec<Vec<u8>> {
+        let Self {
+            connection,
+            mut server,
+            ..
+        } = self;
+        drop(connection);
+        let mut written = Vec::new();
+        server
+            .read_to_end(&mut written)
+            .await
+            .expect("client output");
+        written
+            .split(|byte| *byte == b'\n')
+            .filter(|frame| !frame.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+}
+
+/// Feeds `name` to a fresh connection, whole or in [`CHUNK_BYTES`] pieces,
+/// and closes the server's write side once the capture is exhausted.
+async fn replay(name: &str, policy: DowngradePolicy, chunked: bool) -> Replay {
+    let bytes = capture(name);
+    let (client, server) = duplex(64 * 1024);
+    let (server, mut writer) = split(server);
+    let config = TransportConfig {
+        wire_format: WireFormat::TakProtocolV1,
+        ..TransportConfig::default()
+    };
+    let mut connection =
+        TransportConnection::new(client, &config, policy).expect("connection should build");
+
+    let feed = tokio::spawn(async move {
+        let chunk = if chunked { CHUNK_BYTES } else { bytes.len() };
+        for piece in bytes.chunks(chunk.max(1)) {
+            writer.write_all(piece).await.expect("replay capture");
+            tokio::task::yield_now().await;
+        }
+        writer.shutdown().await.expect("close capture");
+    });
+    let result = connection
+        .negotiate(TakProtocolVersion::V1, Duration::from_secs(5))
+        .await;
+    feed.await.expect("feed task");
+
+    Replay {
+        connection,
+        result,
+        server,
+    }
+}
+
+fn uid(event: &[u8]) -> String {
+    let event = std::str::from_utf8(event).expect("utf-8 event");
+    let start = event.find("uid=\"").expect("uid attribute") + "uid=\"".len();
+    let end = start + event[start..].find('"').expect("closing quote");
+    event[start..end].to_owned()
+}
+
+fn uids(events: &[Vec<u8>]) -> Vec<String> {
+    events.iter().map(|event| uid(event)).collect()
+}
+
+async fn next_uid(connection: &mut TransportConnection<DuplexStream>) -> String {
+    let frame = connection
+        .recv_frame()
+        .await
+        .expect("frame after negotiation");
+    uid(&connection.decode_frame_payload(&frame).expect("payload"))
+}
+
+fn assert_single_request(frames: &[Vec<u8>]) {
+    assert_eq!(frames.len(), 1, "client should send exactly one TakRequest");
+    assert_eq!(
+        TakControlMessage::parse(&frames[0], &Limits::conservative_defaults()),
+        Ok(Some(TakControlMessage::Request {
+            version: TakProtocolVersion::V1
+        }))
+    );
+}
+
+#[tokio::test]
+async fn accepted_upgrade_switches_to_length_prefixed_framing() {
+    for chunked in [false, true] {
+        let mut replay = replay("upgrade_accepted", DowngradePolicy::FailClosed, chunked).await;
+        let outcome = replay.result.as_ref().expect("negotiate");
+        assert_eq!(outcome.event.kind, NegotiationEventKind::UpgradeAccepted);
+        assert_eq!(
+            outcome.state,
+            NegotiationState::Upgraded(TakProtocolVersion::V1)
+        );
+        assert!(outcome.early_events.is_empty());
+        assert_eq!(
+            replay.connection.framing(),
+            TransportFraming::TakProtocolU32LengthPrefixed
+        );
+
+        assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-alpha");
+        assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-bravo");
+        assert_single_request(&replay.client_frames().await);
+    }
+}
+
+#[tokio::test]
+async fn events_around_the_offer_are_kept_in_order() {
+    for chunked in [false, true] {
+        let mut replay = replay(
+            "early_events_interleaved",
+            DowngradePolicy::FailClosed,
+            chunked,
+        )
+        .await;
+        let outcome = replay.result.as_ref().expect("negotiate");
+        assert_eq!(outcome.event.kind, NegotiationEventKind::UpgradeAccepted);
+        assert_eq!(
+            uids(&outcome.early_events),
+            ["ANDROID-alpha", "ANDROID-bravo"]
+        );
+
+        assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-charlie");
+        assert_single_request(&replay.client_frames().await);
+    }
+}
+
+#[tokio::test]
+async fn repeated_offer_is_answered_once() {
+    let mut replay = replay("duplicate_offer", DowngradePolicy::FailClosed, false).await;
+    let outcome = replay.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::UpgradeAccepted);
+    assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-alpha");
+    assert_single_request(&replay.client_frames().await);
+}
+
+#[tokio::test]
+async fn multi_version_offer_selects_the_version_we_speak() {
+    let mut replay = replay("multi_version_offer", DowngradePolicy::FailClosed, true).await;
+    let outcome = replay.result.as_ref().expect("negotiate");
+    assert_eq!(
+        outcome.state,
+        NegotiationState::Upgraded(TakProtocolVersion::V1)
+    );
+    assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-alpha");
+    assert_single_request(&replay.client_frames().await);
+}
+
+#[tokio::test]
+async fn offer_without_a_known_version_falls_back_without_a_request() {
+    let mut replay = replay("unsupported_offer", DowngradePolicy::FailOpen, false).await;
+    let outcome = replay.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::FallbackToLegacy);
+    assert_eq!(
+        outcome.event.reason,
+        Some(NegotiationReason::UnsupportedVersion)
+    );
+    assert_eq!(
+        replay.connection.framing(),
+        TransportFraming::XmlNewlineDelimited
+    );
+
+    assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-alpha");
+    assert!(replay.client_frames().await.is_empty());
+}
+
+#[tokio::test]
+async fn refused_response_follows_the_downgrade_policy() {
+    let mut open = replay("refused_response", DowngradePolicy::FailOpen, false).await;
+    let outcome = open.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::FallbackToLegacy);
+    assert_eq!(outcome.state, NegotiationState::LegacyXml);
+    assert_eq!(
+        open.connection.framing(),
+        TransportFraming::XmlNewlineDelimited
+    );
+    assert_eq!(next_uid(&mut open.connection).await, "ANDROID-alpha");
+    assert_single_request(&open.client_frames().await);
+
+    let closed = replay("refused_response", DowngradePolicy::FailClosed, true).await;
+    let outcome = closed.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::Terminated);
+    assert_eq!(
+        outcome.state,
+        NegotiationState::Terminated {
+            reason: NegotiationReason::UnsupportedVersion
+        }
+    );
+    assert_eq!(
+        closed.connection.framing(),
+        TransportFraming::XmlNewlineDelimited
+    );
+}
+
+#[tokio::test]
+async fn malformed_response_is_reported_as_malformed_control() {
+    let mut replay = replay("malformed_response", DowngradePolicy::FailOpen, true).await;
+    let outcome = replay.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::FallbackToLegacy);
+    assert_eq!(
+        outcome.event.reason,
+        Some(NegotiationReason::MalformedControl)
+    );
+    assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-alpha");
+    assert_single_request(&replay.client_frames().await);
+}
+
+#[tokio::test]
+async fn compact_announcement_upgrades_without_a_request() {
+    let mut replay = replay("compact_announcement", DowngradePolicy::FailClosed, false).await;
+    let outcome = replay.result.as_ref().expect("negotiate");
+    assert_eq!(outcome.event.kind, NegotiationEventKind::UpgradeAccepted);
+    assert_eq!(uids(&outcome.early_events), ["ANDROID-alpha"]);
+    assert_eq!(
+        replay.connection.framing(),
+        TransportFraming::TakProtocolU32LengthPrefixed
+    );
+
+    assert_eq!(next_uid(&mut replay.connection).await, "ANDROID-bravo");
+    assert!(replay.client_frames().await.is_empty());
+}
+
+#[tokio::test]
+async fn server_closing_before_its_response_is_an_error() {
+    let replay = replay("closed_after_offer", DowngradePolicy::FailOpen, false).await;
+    let error = replay
+        .result
+        .as_ref()
+        .expect_err("stream ended mid-negotiation");
+    assert!(matches!(
+        error,
+        TransportComposeError::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof
+    ));
+    assert_single_request(&replay.client_frames().await);
+}
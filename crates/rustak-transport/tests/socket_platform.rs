@@ -0,0 +1,92 @@
+//! Host-network socket tests. They touch real interfaces, so they are opt-in:
+//! `cargo test -p rustak-transport --test socket_platform -- --ignored`.
+
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use rustak_transport::{
+    apply_tcp_keepalive, bind_udp_socket, bind_udp_socket_with_joins, Keepalive, UdpSocketOptions,
+    UdpTarget,
+};
+
+const TEST_GROUP: Ipv4Addr = Ipv4Addr::new(239, 2, 3, 99);
+
+fn multicast_options(port: u16) -> UdpSocketOptions {
+    UdpSocketOptions::for_target(
+        SocketAddr::from((TEST_GROUP, port)),
+        &UdpTarget::Multicast {
+            group: TEST_GROUP,
+            port,
+        },
+    )
+}
+
+#[test]
+#[ignore = "joins a multicast group on the host network"]
+fn two_listeners_share_a_multicast_port() {
+    let first = bind_udp_socket(&multicast_options(0)).expect("first listener");
+    let port = first.local_addr().expect("local addr").port();
+    let second = bind_udp_socket(&multicast_options(port)).expect("second listener");
+
+    let sender = bind_udp_socket(&UdpSocketOptions::for_target(
+        SocketAddr::from(([0, 0, 0, 0], 0)),
+        &UdpTarget::Multicast {
+            group: TEST_GROUP,
+            port,
+        },
+    ))
+    .expect("sender");
+    sender
+        .send_to(b"<event/>", (TEST_GROUP, port))
+        .expect("send");
+
+    for listener in [&first, &second] {
+        listener
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .expect("timeout");
+        let mut buf = [0_u8; 64];
+        let (len, _) = listener.recv_from(&mut buf).expect("multicast datagram");
+        assert_eq!(&buf[..len], b"<event/>");
+    }
+}
+
+#[test]
+#[ignore = "joins a multicast group on the host network"]
+fn joins_are_reported_per_interface() {
+    let (_socket, joins) = bind_udp_socket_with_joins(&multicast_options(0)).expect("listener");
+    assert_eq!(joins[0].interface, "default");
+    assert!(joins[0].joined());
+    for join in &joins {
+        assert_eq!(join.group, TEST_GROUP, "{join}");
+    }
+}
+
+#[test]
+#[ignore = "sends a broadcast datagram on the host network"]
+fn broadcast_socket_can_send() {
+    let socket = bind_udp_socket(&UdpSocketOptions::for_target(
+        SocketAddr::from(([0, 0, 0, 0], 0)),
+        &UdpTarget::Broadcast { port: 4242 },
+    ))
+    .expect("broadcast socket");
+    assert!(socket.broadcast().expect("broadcast flag"));
+    socket
+        .send_to(b"<event/>", (Ipv4Addr::BROADCAST, 4242))
+        .expect("broadcast send");
+}
+
+#[test]
+#[ignore = "opens a loopback TCP connection"]
+fn keepalive_applies_to_connected_stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("listener");
+    let stream = TcpStream::connect(listener.local_addr().expect("addr")).expect("connect");
+
+    apply_tcp_keepalive(
+        &stream,
+        &Keepalive {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(5),
+        },
+    )
+    .expect("keepalive");
+}
@@ -0,0 +1,258 @@
+//! Reconnect and keepalive timing under a paused tokio clock.
+//!
+//! `ConnectionManager::connect_with` dials in-memory streams, so every test
+//! here runs in virtual time: the runtime jumps straight to the next timer
+//! and the assertions can check exact instants without sleeping.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rustak_transport::{
+    ConnectionEvent, ConnectionManager, ConnectionManagerError, Keepalive, Protocol,
+    ReconnectBackoff, ReconnectPolicy, TransportComposeError, TransportConfig,
+};
+use rustak_wire::DowngradePolicy;
+use tokio::io::{duplex, split, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::time::Instant;
+
+const KEEPALIVE: Keepalive = Keepalive {
+    interval: Duration::from_secs(10),
+    timeout: Duration::from_secs(3),
+};
+
+fn peer() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 8089))
+}
+
+fn policy(jitter: f64, max_retries: Option<u32>) -> ReconnectPolicy {
+    ReconnectPolicy {
+        enabled: true,
+        initial_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(500),
+        backoff_factor: 2.0,
+        jitter,
+        max_retries,
+    }
+}
+
+fn manager(reconnect_policy: ReconnectPolicy, keepalive: Option<Keepalive>) -> ConnectionManager {
+    let config = TransportConfig {
+        protocol: Protocol::Tcp { addr: peer() },
+        keepalive,
+        reconnect_policy,
+        write_timeout: Duration::from_secs(2),
+        ..TransportConfig::default()
+    };
+    ConnectionManager::new(config, DowngradePolicy::FailOpen).expect("manager")
+}
+
+fn refused() -> io::Result<(DuplexStream, SocketAddr)> {
+    Err(io::Error::from(io::ErrorKind::ConnectionRefused))
+}
+
+fn backoff_delays(events: &[ConnectionEvent]) -> Vec<Duration> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            ConnectionEvent::BackingOff { delay, .. } => Some(*delay),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+async fn retries_follow_the_exact_backoff_schedule() {
+    let mut manager = manager(policy(0.0, Some(4)), None);
+    let start = Instant::now();
+    let mut dialed_at = Vec::new();
+
+    let error = manager
+        .connect_with(|| {
+            dialed_at.push(start.elapsed());
+            std::future::ready(refused())
+        })
+        .await
+        .expect_err("every dial is refused");
+
+    assert!(matches!(
+        error,
+        ConnectionManagerError::RetriesExhausted { attempts: 5, .. }
+    ));
+    assert_eq!(
+        dialed_at,
+        [0, 100, 300, 700, 1_200].map(Duration::from_millis)
+    );
+    assert_eq!(
+        backoff_delays(manager.events()),
+        [100, 200, 400, 500].map(Duration::from_millis)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn jittered_retries_stay_within_bounds_and_match_the_seed() {
+    let reconnect_policy = policy(0.25, Some(6));
+    let mut manager = manager(reconnect_policy.clone(), None).with_jitter_seed(7);
+    let start = Instant::now();
+    let mut dialed_at = Vec::new();
+
+    manager
+        .connect_with(|| {
+            dialed_at.push(start.elapsed());
+            std::future::ready(refused())
+        })
+        .await
+        .expect_err("every dial is refused");
+
+    let delays = backoff_delays(manager.events());
+    let mut expected = ReconnectBackoff::with_seed(reconnect_policy, 7);
+    assert_eq!(
+        delays,
+        std::iter::from_fn(|| expected.next_delay()).collect::<Vec<_>>()
+    );
+    for (retry, (delay, gap)) in delays
+        .iter()
+        .zip(dialed_at.windows(2).map(|pair| pair[1] - pair[0]))
+        .enumerate()
+    {
+        let base = (100.0 * 2_f64.powi(retry as i32)).min(500.0);
+        let millis = delay.as_secs_f64() * 1_000.0;
+        assert!(millis >= base * 0.75 - 1e-6, "retry {retry}: {delay:?}");
+        assert!(
+            millis <= (base * 1.25).min(500.0) + 1e-6,
+            "retry {retry}: {delay:?}"
+        );
+        // Timers fire on whole milliseconds.
+        assert!(
+            gap >= *delay && gap < *delay + Duration::from_millis(1),
+            "retry {retry}: slept {gap:?} for {delay:?}"
+        );
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn stalled_dials_time_out_after_write_timeout() {
+    let mut manager = manager(policy(0.0, Some(1)), None);
+    let start = Instant::now();
+
+    let error = manager
+        .connect_with(std::future::pending::<io::Result<(DuplexStream, SocketAddr)>>)
+        .await
+        .expect_err("dials never finish");
+
+    assert!(matches!(
+        error,
+        ConnectionManagerError::RetriesExhausted { attempts: 2, ref last_error }
+            if last_error == "dial timed out after 2s"
+    ));
+    assert_eq!(start.elapsed(), Duration::from_millis(4_100));
+}
+
+#[tokio::test(start_paused = true)]
+async fn keepalive_pings_on_each_idle_interval_until_the_peer_goes_quiet() {
+    let mut manager = manager(policy(0.0, Some(0)), Some(KEEPALIVE));
+    let (client, server) = duplex(4096);
+    let mut client = Some(client);
+    let mut connection = manager
+        .connect_with(|| std::future::ready(Ok((client.take().expect("one dial"), peer()))))
+        .await
+        .expect("connect");
+    let mut driver = manager.keepalive_driver("node").expect("keepalive");
+    let start = Instant::now();
+
+    // Answers the first three pings a second late, then stops answering.
+    let peer_task = tokio::spawn(async move {
+        let (reader, mut writer) = split(server);
+        let mut lines = BufReader::new(reader).lines();
+        let mut pinged_at = Vec::new();
+        while let Some(line) = lines.next_line().await.expect("read ping") {
+            assert!(line.contains("uid=\"node-ping\" type=\"t-x-c-t\""));
+            pinged_at.push(start.elapsed());
+            if pinged_at.len() <= 3 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                writer
+                    .write_all(b"<event uid=\"pong\"/>\n")
+                    .await
+                    .expect("reply");
+            }
+            if pinged_at.len() == 4 {
+                return (pinged_at, writer);
+            }
+        }
+        panic!("connection closed before the fourth ping");
+    });
+
+    let mut replies = 0;
+    let error = loop {
+        match connection.recv_frame_with_keepalive(&mut driver).await {
+            Ok(frame) => {
+                assert_eq!(frame, b"<event uid=\"pong\"/>");
+                replies += 1;
+            }
+            Err(error) => break error,
+        }
+    };
+    assert!(matches!(
+        error,
+        TransportComposeError::KeepaliveTimeout { timeout } if timeout == KEEPALIVE.timeout
+    ));
+    assert_eq!(replies, 3);
+    assert_eq!(start.elapsed(), Duration::from_secs(46));
+    assert_eq!(driver.pings_sent(), 4);
+
+    let (pinged_at, _writer) = peer_task.await.expect("peer task");
+    assert_eq!(pinged_at, [10, 21, 32, 43].map(Duration::from_secs));
+}
+
+#[tokio::test(start_paused = true)]
+async fn keepalive_timeout_triggers_a_backed_off_reconnect() {
+    let mut manager = manager(policy(0.0, Some(3)), Some(KEEPALIVE));
+    let start = Instant::now();
+    let (silent, silent_peer) = duplex(4096);
+    let (live, mut live_peer) = duplex(4096);
+    live_peer
+        .write_all(b"<event uid=\"after-reconnect\"/>\n")
+        .await
+        .expect("queue frame");
+    let mut outcomes = VecDeque::from([Ok(silent), refused().map(|(stream, _)| stream), Ok(live)]);
+    let mut dialed_at = Vec::new();
+    let mut dial = || {
+        dialed_at.push(start.elapsed());
+        let outcome = outcomes.pop_front().expect("dial outcome");
+        std::future::ready(outcome.map(|stream| (stream, peer())))
+    };
+
+    let mut connection = manager.connect_with(&mut dial).await.expect("connect");
+    let mut driver = manager.keepalive_driver("node").expect("keepalive");
+    let error = connection
+        .recv_frame_with_keepalive(&mut driver)
+        .await
+        .expect_err("silent peer");
+    assert_eq!(start.elapsed(), Duration::from_secs(13));
+
+    manager.disconnected(error.to_string());
+    let mut connection = manager.connect_with(&mut dial).await.expect("reconnect");
+    let mut driver = manager.keepalive_driver("node").expect("keepalive");
+    assert_eq!(
+        connection
+            .recv_frame_with_keepalive(&mut driver)
+            .await
+            .expect("frame"),
+        b"<event uid=\"after-reconnect\"/>"
+    );
+    drop(silent_peer);
+
+    assert_eq!(dialed_at, [0, 13_000, 13_100].map(Duration::from_millis));
+    let events = manager.drain_events();
+    assert!(events.contains(&ConnectionEvent::Disconnected {
+        reason: error.to_string()
+    }));
+    assert_eq!(
+        events.last(),
+        Some(&ConnectionEvent::Connected {
+            attempt: 2,
+            peer: peer()
+        })
+    );
+}
@@ -1,14 +1,18 @@
-use rustak_transport::{apply_mtu_policy, MtuSafety, UdpPolicyError, UdpSendDecision};
+use rustak_limits::Limits;
+use rustak_transport::{
+    apply_mtu_policy, MtuSafety, OversizePolicy, UdpPolicyError, UdpSendDecision,
+};
 
 #[test]
 fn drop_policy_rejects_oversize_payload() {
     let mtu_safety = MtuSafety {
         max_udp_payload_bytes: 1200,
-        drop_oversize: true,
+        oversize: OversizePolicy::Drop,
     };
     let payload = vec![0xAB; 1400];
 
-    let decision = apply_mtu_policy(&payload, &mtu_safety).expect("policy should evaluate");
+    let decision = apply_mtu_policy(&payload, &mtu_safety, &Limits::default())
+        .expect("policy should evaluate");
     assert_eq!(
         decision,
         UdpSendDecision::DropOversize {
@@ -22,11 +26,12 @@ fn drop_policy_rejects_oversize_payload() {
 fn split_policy_fragments_oversize_payload_deterministically() {
     let mtu_safety = MtuSafety {
         max_udp_payload_bytes: 512,
-        drop_oversize: false,
+        oversize: OversizePolicy::Split,
     };
     let payload = vec![0xCD; 1300];
 
-    let decision = apply_mtu_policy(&payload, &mtu_safety).expect("policy should evaluate");
+    let decision = apply_mtu_policy(&payload, &mtu_safety, &Limits::default())
+        .expect("policy should evaluate");
     let UdpSendDecision::SendDatagrams(datagrams) = decision else {
         panic!("split policy should produce datagrams");
     };
@@ -42,11 +47,12 @@ fn split_policy_fragments_oversize_payload_deterministically() {
 fn payload_within_limit_is_single_datagram() {
     let mtu_safety = MtuSafety {
         max_udp_payload_bytes: 1200,
-        drop_oversize: false,
+        oversize: OversizePolicy::Split,
     };
     let payload = vec![0x7E; 1200];
 
-    let decision = apply_mtu_policy(&payload, &mtu_safety).expect("policy should evaluate");
+    let decision = apply_mtu_policy(&payload, &mtu_safety, &Limits::default())
+        .expect("policy should evaluate");
     assert_eq!(decision, UdpSendDecision::SendDatagrams(vec![payload]));
 }
 
@@ -54,10 +60,11 @@ fn payload_within_limit_is_single_datagram() {
 fn zero_mtu_limit_is_rejected() {
     let mtu_safety = MtuSafety {
         max_udp_payload_bytes: 0,
-        drop_oversize: true,
+        oversize: OversizePolicy::Drop,
     };
     let payload = vec![0x11; 64];
 
-    let error = apply_mtu_policy(&payload, &mtu_safety).expect_err("zero mtu should fail");
+    let error = apply_mtu_policy(&payload, &mtu_safety, &Limits::default())
+        .expect_err("zero mtu should fail");
     assert_eq!(error, UdpPolicyError::ZeroMaxPayload);
 }
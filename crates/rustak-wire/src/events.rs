@@ -1,3 +1,5 @@
+use rustak_core::{CotEvent, DetailNode, Position, TimestampUtc};
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use thiserror::Error;
 
 use super::{
@@ -7,6 +9,16 @@ use super::{
 pub const NEGOTIATION_TELEMETRY_CHANNEL: &str = "wire.negotiation.v1";
 pub const CONTROL_FRAME_VERSION_MARKER: u8 = b'V';
 
+/// CoT type of the server's `TakProtocolSupport` announcement.
+pub const TAK_PROTOCOL_SUPPORT_TYPE: &str = "t-x-takp-v";
+/// CoT type of the client's `TakRequest`.
+pub const TAK_REQUEST_TYPE: &str = "t-x-takp-q";
+/// CoT type of the server's `TakResponse`.
+pub const TAK_RESPONSE_TYPE: &str = "t-x-takp-r";
+
+const TAK_CONTROL_TYPE_PREFIX: &[u8] = b"t-x-takp-";
+const TAK_CONTROL_STALE_NANOS: i128 = 60 * 1_000_000_000;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NegotiationTelemetryEvent {
     pub session_id: u64,
@@ -163,6 +175,20 @@ pub enum TelemetryDecodeError {
     UnknownReason { code: String },
 }
 
+impl CodedError for TelemetryDecodeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidUtf8(_) => ErrorCode::new("WIRE", 501),
+            Self::MalformedField { .. } => ErrorCode::new("WIRE", 502),
+            Self::MissingField { .. } => ErrorCode::new("WIRE", 503),
+            Self::InvalidNumber { .. } => ErrorCode::new("WIRE", 504),
+            Self::UnknownState { .. } => ErrorCode::new("WIRE", 505),
+            Self::UnknownKind { .. } => ErrorCode::new("WIRE", 506),
+            Self::UnknownReason { .. } => ErrorCode::new("WIRE", 507),
+        }
+    }
+}
+
 #[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
 pub enum ControlFrameError {
     #[error("control frame is empty")]
@@ -176,6 +202,182 @@ pub enum ControlFrameError {
 
     #[error("unsupported TAK version `{version}`")]
     UnsupportedVersion { version: u8 },
+
+    #[error("TakControl message is malformed: {reason}")]
+    MalformedTakControl { reason: &'static str },
+}
+
+impl CodedError for ControlFrameError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::EmptyFrame => ErrorCode::new("WIRE", 401),
+            Self::InvalidMarker { .. } => ErrorCode::new("WIRE", 402),
+            Self::MissingVersion => ErrorCode::new("WIRE", 403),
+            Self::UnsupportedVersion { .. } => ErrorCode::new("WIRE", 404),
+            Self::MalformedTakControl { .. } => ErrorCode::new("WIRE", 405),
+        }
+    }
+}
+
+/// Stream-mode negotiation message, carried as a CoT event whose detail
+/// holds a `<TakControl>` element.
+///
+/// The server announces what it speaks on connect, the client asks for one
+/// version, and both sides switch to TAK protocol framing after the server
+/// accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TakControlMessage {
+    /// `t-x-takp-v`. Holds the newest announced version this crate speaks,
+    /// or `None` if the server only offered unknown ones.
+    ProtocolSupport { version: Option<TakProtocolVersion> },
+    /// `t-x-takp-q`.
+    Request { version: TakProtocolVersion },
+    /// `t-x-takp-r`.
+    Response { accepted: bool },
+}
+
+impl TakControlMessage {
+    #[must_use]
+    pub const fn cot_type(self) -> &'static str {
+        match self {
+            Self::ProtocolSupport { .. } => TAK_PROTOCOL_SUPPORT_TYPE,
+            Self::Request { .. } => TAK_REQUEST_TYPE,
+            Self::Response { .. } => TAK_RESPONSE_TYPE,
+        }
+    }
+
+    /// The message as an event from `uid` at `now`, stale a minute later.
+    #[must_use]
+    pub fn to_event(self, uid: &str, now: TimestampUtc) -> CotEvent {
+        let control = match self {
+            Self::ProtocolSupport { version } => {
+                version
+                    .into_iter()
+                    .fold(DetailNode::new("TakControl"), |control, version| {
+                        control.with_child(
+                            DetailNode::new("TakProtocolSupport")
+                                .with_attribute("version", version.wire_byte().to_string()),
+                        )
+                    })
+            }
+            Self::Request { version } => DetailNode::new("TakControl").with_child(
+                DetailNode::new("TakRequest")
+                    .with_attribute("version", version.wire_byte().to_string()),
+            ),
+            Self::Response { accepted } => DetailNode::new("TakControl").with_child(
+                DetailNode::new("TakResponse").with_attribute("status", accepted.to_string()),
+            ),
+        };
+        let stale = TimestampUtc::from_unix_nanos(now.unix_nanos() + TAK_CONTROL_STALE_NANOS);
+        let point = Position::new(0.0, 0.0).expect("the origin is a valid position");
+        let mut event = CotEvent::new(uid, self.cot_type(), now, stale, point);
+        event.how = Some("m-g".to_owned());
+        event.detail.push(control);
+        event
+    }
+
+    /// Newline-free CoT XML for the message, ready for a legacy XML stream.
+    #[must_use]
+    pub fn encode(self, uid: &str, now: TimestampUtc) -> Vec<u8> {
+        self.to_event(uid, now).to_xml().into_bytes()
+    }
+
+    /// Reads the message out of `event`; `Ok(None)` if the event is not a
+    /// TakControl type.
+    pub fn from_event(event: &CotEvent) -> Result<Option<Self>, ControlFrameError> {
+        let control = || {
+            event
+                .detail_element("TakControl")
+                .ok_or(ControlFrameError::MalformedTakControl {
+                    reason: "missing TakControl detail",
+                })
+        };
+        let message = match event.cot_type.as_str() {
+            TAK_PROTOCOL_SUPPORT_TYPE => Self::ProtocolSupport {
+                version: control()?
+                    .children
+                    .iter()
+                    .filter(|child| child.name == "TakProtocolSupport")
+                    .filter_map(|support| version_attribute(support).ok())
+                    .max_by_key(|version| version.wire_byte()),
+            },
+            TAK_REQUEST_TYPE => {
+                let request = control()?.child("TakRequest").ok_or(
+                    ControlFrameError::MalformedTakControl {
+                        reason: "missing TakRequest",
+                    },
+                )?;
+                Self::Request {
+                    version: version_attribute(request)?,
+                }
+            }
+            TAK_RESPONSE_TYPE => {
+                let response = control()?.child("TakResponse").ok_or(
+                    ControlFrameError::MalformedTakControl {
+                        reason: "missing TakResponse",
+                    },
+                )?;
+                let accepted = match response.attribute("status") {
+                    Some("true") => true,
+                    Some("false") => false,
+                    _ => {
+                        return Err(ControlFrameError::MalformedTakControl {
+                            reason: "TakResponse status must be true or false",
+                        })
+                    }
+                };
+                Self::Response { accepted }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(message))
+    }
+
+    /// Parses `frame` as a TakControl event. Frames that cannot be one are
+    /// rejected with `Ok(None)` before any XML parsing, so this is cheap to
+    /// call on every frame of a legacy stream.
+    pub fn parse(frame: &[u8], limits: &Limits) -> Result<Option<Self>, ControlFrameError> {
+        if !frame
+            .windows(TAK_CONTROL_TYPE_PREFIX.len())
+            .any(|window| window == TAK_CONTROL_TYPE_PREFIX)
+        {
+            return Ok(None);
+        }
+        let xml =
+            std::str::from_utf8(frame).map_err(|_| ControlFrameError::MalformedTakControl {
+                reason: "invalid UTF-8",
+            })?;
+        let event =
+            CotEvent::from_xml(xml.trim_start_matches('\u{feff}'), limits).map_err(|_| {
+                ControlFrameError::MalformedTakControl {
+                    reason: "invalid CoT XML",
+                }
+            })?;
+        Self::from_event(&event)
+    }
+}
+
+fn version_attribute(node: &DetailNode) -> Result<TakProtocolVersion, ControlFrameError> {
+    let raw = node
+        .attribute("version")
+        .ok_or(ControlFrameError::MalformedTakControl {
+            reason: "missing version attribute",
+        })?;
+    let version = raw
+        .parse::<u8>()
+        .map_err(|_| ControlFrameError::MalformedTakControl {
+            reason: "version is not a number",
+        })?;
+    TakProtocolVersion::from_wire_byte(version)
+        .ok_or(ControlFrameError::UnsupportedVersion { version })
+}
+
+/// Control frame announcing (or requesting) `version`.
+#[must_use]
+pub const fn encode_control_frame(version: TakProtocolVersion) -> [u8; 2] {
+    match version {
+        TakProtocolVersion::V1 => [CONTROL_FRAME_VERSION_MARKER, 1],
+    }
 }
 
 pub fn parse_control_frame(frame: &[u8]) -> Result<TakProtocolVersion, ControlFrameError> {
@@ -191,7 +393,10 @@ pub fn parse_control_frame(frame: &[u8]) -> Result<TakProtocolVersion, ControlFr
     }
 }
 
-fn state_code(state: NegotiationState) -> String {
+/// Stable code for `state`, as used in telemetry records
+/// (`legacy_xml`, `upgraded:v1`, `terminated:timeout`, ...).
+#[must_use]
+pub fn state_code(state: NegotiationState) -> String {
     match state {
         NegotiationState::LegacyXml => "legacy_xml".to_string(),
         NegotiationState::AwaitingResponse => "awaiting_response".to_string(),
@@ -248,6 +453,7 @@ fn reason_code(reason: NegotiationReason) -> String {
         NegotiationReason::MalformedControl => "malformed_control".to_string(),
         NegotiationReason::UnsupportedVersion => "unsupported_version".to_string(),
         NegotiationReason::PolicyDenied => "policy_denied".to_string(),
+        NegotiationReason::PersistentDecodeFailure => "persistent_decode_failure".to_string(),
     }
 }
 
@@ -264,6 +470,7 @@ fn parse_reason_code(value: &str) -> Result<NegotiationReason, TelemetryDecodeEr
         "malformed_control" => Ok(NegotiationReason::MalformedControl),
         "unsupported_version" => Ok(NegotiationReason::UnsupportedVersion),
         "policy_denied" => Ok(NegotiationReason::PolicyDenied),
+        "persistent_decode_failure" => Ok(NegotiationReason::PersistentDecodeFailure),
         _ => Err(TelemetryDecodeError::UnknownReason {
             code: value.to_string(),
         }),
@@ -272,8 +479,12 @@ fn parse_reason_code(value: &str) -> Result<NegotiationReason, TelemetryDecodeEr
 
 #[cfg(test)]
 mod tests {
+    use rustak_core::TimestampUtc;
+    use rustak_limits::Limits;
+
     use super::{
-        parse_control_frame, NegotiationTelemetry, NegotiationTelemetryEvent, TelemetryDecodeError,
+        encode_control_frame, parse_control_frame, ControlFrameError, NegotiationTelemetry,
+        NegotiationTelemetryEvent, TakControlMessage, TelemetryDecodeError,
         CONTROL_FRAME_VERSION_MARKER, NEGOTIATION_TELEMETRY_CHANNEL,
     };
     use crate::negotiation::{
@@ -287,12 +498,101 @@ mod tests {
             parse_control_frame(&[CONTROL_FRAME_VERSION_MARKER, 1]),
             Ok(TakProtocolVersion::V1)
         );
+        assert_eq!(
+            parse_control_frame(&encode_control_frame(TakProtocolVersion::V1)),
+            Ok(TakProtocolVersion::V1)
+        );
         assert!(parse_control_frame(&[]).is_err());
         assert!(parse_control_frame(&[CONTROL_FRAME_VERSION_MARKER]).is_err());
         assert!(parse_control_frame(&[CONTROL_FRAME_VERSION_MARKER, 9]).is_err());
         assert!(parse_control_frame(&[0x00, 1]).is_err());
     }
 
+    #[test]
+    fn tak_control_messages_round_trip_as_cot_xml() {
+        let limits = Limits::conservative_defaults();
+        let now = TimestampUtc::from_unix_seconds(1_700_000_000);
+        for message in [
+            TakControlMessage::ProtocolSupport {
+                version: Some(TakProtocolVersion::V1),
+            },
+            TakControlMessage::Request {
+                version: TakProtocolVersion::V1,
+            },
+            TakControlMessage::Response { accepted: true },
+            TakControlMessage::Response { accepted: false },
+        ] {
+            let frame = message.encode("ANDROID-1", now);
+            assert!(!frame.contains(&b'\n'));
+            assert_eq!(TakControlMessage::parse(&frame, &limits), Ok(Some(message)));
+        }
+
+        let request = String::from_utf8(
+            TakControlMessage::Request {
+                version: TakProtocolVersion::V1,
+            }
+            .encode("ANDROID-1", now),
+        )
+        .expect("utf8");
+        assert!(request.contains("type=\"t-x-takp-q\""));
+        assert!(request.contains("<TakControl><TakRequest version=\"1\"/></TakControl>"));
+    }
+
+    #[test]
+    fn tak_control_parse_picks_known_versions_and_rejects_bad_messages() {
+        let limits = Limits::conservative_defaults();
+        let event = |cot_type: &str, control: &str| {
+            format!(
+                "<event version=\"2.0\" uid=\"srv\" type=\"{cot_type}\" time=\"2023-11-14T22:13:20Z\" \
+start=\"2023-11-14T22:13:20Z\" stale=\"2023-11-14T22:14:20Z\"><point lat=\"0\" lon=\"0\" \
+hae=\"0\" ce=\"9999999\" le=\"9999999\"/><detail>{control}</detail></event>"
+            )
+        };
+
+        let offers = event(
+            "t-x-takp-v",
+            "<TakControl><TakProtocolSupport version=\"7\"/><TakProtocolSupport version=\"1\"/></TakControl>",
+        );
+        assert_eq!(
+            TakControlMessage::parse(offers.as_bytes(), &limits),
+            Ok(Some(TakControlMessage::ProtocolSupport {
+                version: Some(TakProtocolVersion::V1)
+            }))
+        );
+        let unknown_only = event(
+            "t-x-takp-v",
+            "<TakControl><TakProtocolSupport version=\"7\"/></TakControl>",
+        );
+        assert_eq!(
+            TakControlMessage::parse(unknown_only.as_bytes(), &limits),
+            Ok(Some(TakControlMessage::ProtocolSupport { version: None }))
+        );
+        let future_request = event(
+            "t-x-takp-q",
+            "<TakControl><TakRequest version=\"7\"/></TakControl>",
+        );
+        assert_eq!(
+            TakControlMessage::parse(future_request.as_bytes(), &limits),
+            Err(ControlFrameError::UnsupportedVersion { version: 7 })
+        );
+        let no_status = event("t-x-takp-r", "<TakControl><TakResponse/></TakControl>");
+        assert!(matches!(
+            TakControlMessage::parse(no_status.as_bytes(), &limits),
+            Err(ControlFrameError::MalformedTakControl { .. })
+        ));
+        assert!(matches!(
+            TakControlMessage::parse(b"<event type=\"t-x-takp-r\"", &limits),
+            Err(ControlFrameError::MalformedTakControl { .. })
+        ));
+
+        let pli = event("a-f-G-U-C", "<remarks>t-x-takp-v</remarks>");
+        assert_eq!(TakControlMessage::parse(pli.as_bytes(), &limits), Ok(None));
+        assert_eq!(
+            TakControlMessage::parse(b"<event uid=\"x\"/>", &limits),
+            Ok(None)
+        );
+    }
+
     #[test]
     fn telemetry_buffer_assigns_monotonic_sequence_numbers() {
         let mut telemetry = NegotiationTelemetry::default();
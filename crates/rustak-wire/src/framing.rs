@@ -1,4 +1,4 @@
-use rustak_limits::Limits;
+use rustak_limits::{CodedError, ErrorCode, Limits};
 use rustak_net::{
     read_delimited_frame, read_length_prefixed_frame, write_delimited_frame,
     write_length_prefixed_frame, DelimiterFrameError, LengthPrefixKind, LengthPrefixedError,
@@ -6,10 +6,16 @@ use rustak_net::{
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::WireFormat;
+use crate::{TakProtocolVersion, WireFormat};
 
 pub const LEGACY_XML_DELIMITER: &[u8] = b"\n";
 
+/// Magic byte on both sides of the version in a mesh frame header.
+pub const TAK_MESH_MAGIC: u8 = 0xbf;
+
+/// `0xBF <version> 0xBF`.
+pub const TAK_MESH_HEADER_LEN: usize = 3;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WireFrameCodec {
     format: WireFormat,
@@ -86,6 +92,114 @@ impl WireFrameCodec {
     }
 }
 
+/// TAK protocol mesh (UDP) framing: each datagram is one
+/// `0xBF <version> 0xBF` header followed by the protobuf payload, with no
+/// length prefix since the datagram boundary delimits the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshFrameCodec {
+    version: TakProtocolVersion,
+    max_protobuf_bytes: usize,
+}
+
+impl MeshFrameCodec {
+    #[must_use]
+    pub const fn new(version: TakProtocolVersion, max_protobuf_bytes: usize) -> Self {
+        Self {
+            version,
+            max_protobuf_bytes,
+        }
+    }
+
+    #[must_use]
+    pub const fn from_limits(version: TakProtocolVersion, limits: &Limits) -> Self {
+        Self::new(version, limits.max_protobuf_bytes)
+    }
+
+    #[must_use]
+    pub const fn version(&self) -> TakProtocolVersion {
+        self.version
+    }
+
+    #[must_use]
+    pub const fn header(&self) -> [u8; TAK_MESH_HEADER_LEN] {
+        [TAK_MESH_MAGIC, self.version.wire_byte(), TAK_MESH_MAGIC]
+    }
+
+    /// `true` when `datagram` starts with a mesh header of any version.
+    #[must_use]
+    pub fn is_mesh_frame(datagram: &[u8]) -> bool {
+        matches!(datagram, [TAK_MESH_MAGIC, _, TAK_MESH_MAGIC, ..])
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Result<Vec<u8>, MeshFrameError> {
+        self.check_payload(payload.len())?;
+        let mut datagram = Vec::with_capacity(TAK_MESH_HEADER_LEN + payload.len());
+        datagram.extend_from_slice(&self.header());
+        datagram.extend_from_slice(payload);
+        Ok(datagram)
+    }
+
+    /// Validates the header and returns the payload that follows it.
+    pub fn decode<'a>(&self, datagram: &'a [u8]) -> Result<&'a [u8], MeshFrameError> {
+        let [first, version, last, payload @ ..] = datagram else {
+            return Err(MeshFrameError::Truncated {
+                len: datagram.len(),
+            });
+        };
+        if *first != TAK_MESH_MAGIC || *last != TAK_MESH_MAGIC {
+            return Err(MeshFrameError::BadMagic);
+        }
+        if TakProtocolVersion::from_wire_byte(*version) != Some(self.version) {
+            return Err(MeshFrameError::UnsupportedVersion { version: *version });
+        }
+        self.check_payload(payload.len())?;
+        Ok(payload)
+    }
+
+    fn check_payload(&self, len: usize) -> Result<(), MeshFrameError> {
+        if len == 0 {
+            return Err(MeshFrameError::EmptyPayload);
+        }
+        if len > self.max_protobuf_bytes {
+            return Err(MeshFrameError::TooLarge {
+                len,
+                max: self.max_protobuf_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MeshFrameError {
+    #[error("mesh frame of {len} bytes is shorter than its header")]
+    Truncated { len: usize },
+
+    #[error("mesh frame header is missing the 0xBF magic bytes")]
+    BadMagic,
+
+    #[error("mesh frame carries unsupported TAK protocol version {version}")]
+    UnsupportedVersion { version: u8 },
+
+    #[error("mesh frame carries no payload")]
+    EmptyPayload,
+
+    #[error("mesh frame payload of {len} bytes exceeds {max}")]
+    TooLarge { len: usize, max: usize },
+}
+
+impl CodedError for MeshFrameError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Truncated { .. } => ErrorCode::new("WIRE", 301),
+            Self::BadMagic => ErrorCode::new("WIRE", 302),
+            Self::UnsupportedVersion { .. } => ErrorCode::new("WIRE", 303),
+            Self::EmptyPayload => ErrorCode::new("WIRE", 304),
+            Self::TooLarge { .. } => ErrorCode::new("WIRE", 305),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum WireFrameError {
     #[error(transparent)]
@@ -95,12 +209,21 @@ pub enum WireFrameError {
     LengthPrefixed(#[from] LengthPrefixedError),
 }
 
+impl CodedError for WireFrameError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Delimiter(error) => error.code(),
+            Self::LengthPrefixed(error) => error.code(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tokio::io::{duplex, AsyncWriteExt};
 
-    use super::{WireFrameCodec, WireFrameError};
-    use crate::WireFormat;
+    use super::{MeshFrameCodec, MeshFrameError, WireFrameCodec, WireFrameError};
+    use crate::{TakProtocolVersion, WireFormat};
     use rustak_net::{DelimiterFrameError, LengthPrefixedError};
 
     #[tokio::test]
@@ -182,4 +305,31 @@ mod tests {
             _ => panic!("unexpected error variant"),
         }
     }
+
+    #[test]
+    fn mesh_frames_carry_the_bf_version_header() {
+        let codec = MeshFrameCodec::new(TakProtocolVersion::V1, 8);
+        let datagram = codec.encode(b"\x0a\x02ab").expect("encode");
+        assert_eq!(datagram, b"\xbf\x01\xbf\x0a\x02ab");
+        assert!(MeshFrameCodec::is_mesh_frame(&datagram));
+        assert_eq!(codec.decode(&datagram).expect("decode"), b"\x0a\x02ab");
+
+        assert_eq!(
+            codec.decode(b"\xbf\x01"),
+            Err(MeshFrameError::Truncated { len: 2 })
+        );
+        assert_eq!(codec.decode(b"<event/>"), Err(MeshFrameError::BadMagic));
+        assert_eq!(
+            codec.decode(b"\xbf\x02\xbf\x0a"),
+            Err(MeshFrameError::UnsupportedVersion { version: 2 })
+        );
+        assert_eq!(
+            codec.decode(b"\xbf\x01\xbf"),
+            Err(MeshFrameError::EmptyPayload)
+        );
+        assert_eq!(
+            codec.encode(&[0; 9]),
+            Err(MeshFrameError::TooLarge { len: 9, max: 8 })
+        );
+    }
 }
@@ -1,16 +1,20 @@
 use std::time::Duration;
 
-use rustak_limits::{Limits, LimitsError};
+use rustak_limits::{CodedError, ErrorCode, Limits, LimitsError};
 use rustak_proto::ProtoError;
 use thiserror::Error;
 
 pub mod framing;
 pub mod negotiation;
 
-pub use framing::{WireFrameCodec, WireFrameError, LEGACY_XML_DELIMITER};
+pub use framing::{
+    MeshFrameCodec, MeshFrameError, WireFrameCodec, WireFrameError, LEGACY_XML_DELIMITER,
+    TAK_MESH_HEADER_LEN, TAK_MESH_MAGIC,
+};
 pub use negotiation::{
-    NegotiationEvent, NegotiationEventKind, NegotiationReason, NegotiationState, Negotiator,
-    TakProtocolVersion,
+    IgnoredFrame, NegotiationEvent, NegotiationEventKind, NegotiationReason, NegotiationState,
+    NegotiationStream, Negotiator, StreamFrame, StreamToleranceStats, TakProtocolVersion,
+    DEFAULT_DECODE_FAILURE_LIMIT,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -59,6 +63,9 @@ pub struct NegotiationConfig {
     pub mesh_takcontrol_interval: Duration,
     pub mesh_contact_stale_after: Duration,
     pub downgrade_policy: DowngradePolicy,
+    /// Consecutive undecodable payloads on an upgraded stream before
+    /// `downgrade_policy` is applied.
+    pub decode_failure_limit: u32,
 }
 
 impl Default for NegotiationConfig {
@@ -68,10 +75,18 @@ impl Default for NegotiationConfig {
             mesh_takcontrol_interval: Duration::from_secs(60),
             mesh_contact_stale_after: Duration::from_secs(120),
             downgrade_policy: DowngradePolicy::FailClosed,
+            decode_failure_limit: DEFAULT_DECODE_FAILURE_LIMIT,
         }
     }
 }
 
+impl NegotiationConfig {
+    #[must_use]
+    pub const fn negotiator(&self) -> Negotiator {
+        Negotiator::new(self.downgrade_policy).with_decode_failure_limit(self.decode_failure_limit)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct WireConfig {
     pub limits: Limits,
@@ -92,6 +107,10 @@ impl WireConfig {
             self.negotiation.mesh_contact_stale_after,
         )?;
 
+        if self.negotiation.decode_failure_limit == 0 {
+            return Err(WireConfigError::ZeroDecodeFailureLimit);
+        }
+
         if self.negotiation.mesh_contact_stale_after < self.negotiation.mesh_takcontrol_interval {
             return Err(WireConfigError::MeshStaleBeforeCadence {
                 mesh_contact_stale_after: self.negotiation.mesh_contact_stale_after,
@@ -116,6 +135,9 @@ pub enum WireConfigError {
     #[error("{field} must be greater than zero")]
     ZeroDuration { field: &'static str },
 
+    #[error("decode_failure_limit must be greater than zero")]
+    ZeroDecodeFailureLimit,
+
     #[error(
         "mesh_contact_stale_after ({mesh_contact_stale_after:?}) must be >= \
          mesh_takcontrol_interval ({mesh_takcontrol_interval:?})"
@@ -126,6 +148,17 @@ pub enum WireConfigError {
     },
 }
 
+impl CodedError for WireConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidLimits(error) => error.code(),
+            Self::ZeroDuration { .. } => ErrorCode::new("WIRE", 1),
+            Self::ZeroDecodeFailureLimit => ErrorCode::new("WIRE", 2),
+            Self::MeshStaleBeforeCadence { .. } => ErrorCode::new("WIRE", 3),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum WirePayloadError {
     #[error(transparent)]
@@ -135,6 +168,15 @@ pub enum WirePayloadError {
     EmptyPayload,
 }
 
+impl CodedError for WirePayloadError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Proto(error) => error.code(),
+            Self::EmptyPayload => ErrorCode::new("WIRE", 101),
+        }
+    }
+}
+
 fn ensure_non_zero_duration(field: &'static str, value: Duration) -> Result<(), WireConfigError> {
     if value.is_zero() {
         return Err(WireConfigError::ZeroDuration { field });
@@ -209,6 +251,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rejects_zero_decode_failure_limit() {
+        let mut cfg = WireConfig::default();
+        cfg.negotiation.decode_failure_limit = 0;
+
+        assert_eq!(cfg.validate(), Err(WireConfigError::ZeroDecodeFailureLimit));
+    }
+
     #[test]
     fn fuzz_hook_handles_arbitrary_bytes_without_panicking() {
         let corpus = [
@@ -234,7 +284,14 @@ mod tests {
 
     #[test]
     fn tak_v1_payload_routes_through_proto_codec() {
-        let payload = b"<event uid=\"tak-v1\"/>".to_vec();
+        let payload = concat!(
+            "<event version=\"2.0\" uid=\"tak-v1\" type=\"a-f-G\" ",
+            "time=\"2024-01-01T00:00:00.000Z\" start=\"2024-01-01T00:00:00.000Z\" ",
+            "stale=\"2024-01-01T00:01:00.000Z\"><point lat=\"1\" lon=\"2\" ",
+            "hae=\"9999999.0\" ce=\"9999999.0\" le=\"9999999.0\"/></event>",
+        )
+        .as_bytes()
+        .to_vec();
         let encoded =
             encode_payload_for_format(&payload, WireFormat::TakProtocolV1).expect("encode");
         let decoded =
@@ -3,13 +3,34 @@ use crate::DowngradePolicy;
 #[path = "events.rs"]
 pub mod events;
 
-use events::{ControlFrameError, NegotiationTelemetry, NegotiationTelemetryEvent};
+use events::{
+    ControlFrameError, NegotiationTelemetry, NegotiationTelemetryEvent, TakControlMessage,
+    CONTROL_FRAME_VERSION_MARKER,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TakProtocolVersion {
     V1,
 }
 
+impl TakProtocolVersion {
+    /// Version byte carried between the `0xBF` magic bytes of mesh frames.
+    #[must_use]
+    pub const fn wire_byte(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+        }
+    }
+
+    #[must_use]
+    pub const fn from_wire_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(Self::V1),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NegotiationState {
     LegacyXml,
@@ -32,6 +53,8 @@ pub enum NegotiationReason {
     MalformedControl,
     UnsupportedVersion,
     PolicyDenied,
+    /// Upgraded payloads kept failing to decode.
+    PersistentDecodeFailure,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,10 +73,16 @@ impl NegotiationEvent {
     }
 }
 
+/// Consecutive payload decode failures tolerated on an upgraded stream
+/// before the downgrade policy is applied.
+pub const DEFAULT_DECODE_FAILURE_LIMIT: u32 = 5;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Negotiator {
     policy: DowngradePolicy,
     state: NegotiationState,
+    decode_failure_limit: u32,
+    consecutive_decode_failures: u32,
 }
 
 impl Negotiator {
@@ -62,14 +91,29 @@ impl Negotiator {
         Self {
             policy,
             state: NegotiationState::LegacyXml,
+            decode_failure_limit: DEFAULT_DECODE_FAILURE_LIMIT,
+            consecutive_decode_failures: 0,
         }
     }
 
+    /// Overrides [`DEFAULT_DECODE_FAILURE_LIMIT`]; a limit of 0 is treated
+    /// as 1.
+    #[must_use]
+    pub const fn with_decode_failure_limit(mut self, limit: u32) -> Self {
+        self.decode_failure_limit = if limit == 0 { 1 } else { limit };
+        self
+    }
+
     #[must_use]
     pub const fn state(&self) -> NegotiationState {
         self.state
     }
 
+    #[must_use]
+    pub const fn consecutive_decode_failures(&self) -> u32 {
+        self.consecutive_decode_failures
+    }
+
     pub fn begin_upgrade_attempt(&mut self) -> NegotiationEvent {
         if self.state != NegotiationState::LegacyXml {
             return NegotiationEvent::no_change();
@@ -117,6 +161,30 @@ impl Negotiator {
         }
     }
 
+    /// Records a payload that decoded under the negotiated version,
+    /// clearing the failure streak.
+    pub fn observe_decode_success(&mut self) {
+        self.consecutive_decode_failures = 0;
+    }
+
+    /// Records a payload that failed to decode under the negotiated version.
+    /// Once [`DEFAULT_DECODE_FAILURE_LIMIT`] (or the configured limit)
+    /// failures arrive in a row, `FailOpen` falls back to legacy XML and
+    /// `FailClosed` terminates, both with
+    /// [`NegotiationReason::PersistentDecodeFailure`].
+    pub fn observe_decode_failure(&mut self) -> NegotiationEvent {
+        if !matches!(self.state, NegotiationState::Upgraded(_)) {
+            return NegotiationEvent::no_change();
+        }
+
+        self.consecutive_decode_failures = self.consecutive_decode_failures.saturating_add(1);
+        if self.consecutive_decode_failures < self.decode_failure_limit {
+            return NegotiationEvent::no_change();
+        }
+        self.consecutive_decode_failures = 0;
+        self.apply_downgrade_policy(NegotiationReason::PersistentDecodeFailure)
+    }
+
     pub fn observe_control_frame(&mut self, frame: &[u8]) -> NegotiationEvent {
         match events::parse_control_frame(frame) {
             Ok(version) => self.observe_supported_version(version),
@@ -125,6 +193,28 @@ impl Negotiator {
         }
     }
 
+    /// Applies a stream-mode control message received while asking for
+    /// `requested`. An announcement that offers `requested` changes nothing
+    /// (the caller answers it with a `TakRequest`); one that does not, or a
+    /// refusing `TakResponse`, takes the unsupported-version path.
+    pub fn observe_tak_control(
+        &mut self,
+        message: TakControlMessage,
+        requested: TakProtocolVersion,
+    ) -> NegotiationEvent {
+        match message {
+            TakControlMessage::ProtocolSupport { version } if version == Some(requested) => {
+                NegotiationEvent::no_change()
+            }
+            TakControlMessage::ProtocolSupport { .. }
+            | TakControlMessage::Response { accepted: false } => self.observe_unsupported_version(),
+            TakControlMessage::Response { accepted: true } => {
+                self.observe_supported_version(requested)
+            }
+            TakControlMessage::Request { .. } => NegotiationEvent::no_change(),
+        }
+    }
+
     pub fn begin_upgrade_attempt_with_telemetry(
         &mut self,
         session_id: u64,
@@ -180,6 +270,15 @@ impl Negotiator {
         self.emit_telemetry(session_id, event, telemetry)
     }
 
+    pub fn observe_decode_failure_with_telemetry(
+        &mut self,
+        session_id: u64,
+        telemetry: &mut NegotiationTelemetry,
+    ) -> NegotiationTelemetryEvent {
+        let event = self.observe_decode_failure();
+        self.emit_telemetry(session_id, event, telemetry)
+    }
+
     pub fn observe_control_frame_with_telemetry(
         &mut self,
         session_id: u64,
@@ -190,6 +289,17 @@ impl Negotiator {
         self.emit_telemetry(session_id, event, telemetry)
     }
 
+    pub fn observe_tak_control_with_telemetry(
+        &mut self,
+        session_id: u64,
+        message: TakControlMessage,
+        requested: TakProtocolVersion,
+        telemetry: &mut NegotiationTelemetry,
+    ) -> NegotiationTelemetryEvent {
+        let event = self.observe_tak_control(message, requested);
+        self.emit_telemetry(session_id, event, telemetry)
+    }
+
     fn emit_telemetry(
         &self,
         session_id: u64,
@@ -203,7 +313,10 @@ impl Negotiator {
         if self.state != NegotiationState::AwaitingResponse {
             return NegotiationEvent::no_change();
         }
+        self.apply_downgrade_policy(reason)
+    }
 
+    fn apply_downgrade_policy(&mut self, reason: NegotiationReason) -> NegotiationEvent {
         match self.policy {
             DowngradePolicy::FailOpen => {
                 self.state = NegotiationState::LegacyXml;
@@ -223,9 +336,181 @@ impl Negotiator {
     }
 }
 
+/// Why [`NegotiationStream::observe_frame`] swallowed a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgnoredFrame {
+    /// Empty or whitespace-only frame, e.g. a stray CRLF between events.
+    Blank,
+    /// Announcement that arrived before the upgrade attempt started; it is
+    /// replayed when the attempt begins.
+    EarlyAnnouncement,
+    /// Repeat of the announcement that already upgraded the stream.
+    DuplicateAnnouncement,
+    /// Control frame that disagrees with the already negotiated version.
+    ConflictingAnnouncement,
+    /// Non-XML frame on a legacy stream that is not a control frame either.
+    Unrecognized,
+    /// Negotiation terminated; nothing more is delivered.
+    AfterTermination,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFrame<'a> {
+    /// Event payload to hand to the decoder, with any leading whitespace
+    /// removed on XML streams.
+    Data(&'a [u8]),
+    /// Control frame that was fed to the negotiator.
+    Control(NegotiationEvent),
+    Ignored(IgnoredFrame),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamToleranceStats {
+    pub blank_frames: u64,
+    pub early_announcements: u64,
+    pub duplicate_announcements: u64,
+    pub conflicting_announcements: u64,
+    pub unrecognized_frames: u64,
+}
+
+/// Classifies frames read from a stream connection while negotiation may
+/// still be in flight.
+///
+/// Servers in the field prepend whitespace to frames, interleave legacy XML
+/// events with control frames, repeat their announcement, or announce before
+/// the client has asked. None of these should downgrade or terminate an
+/// otherwise healthy session, so they are absorbed here and counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiationStream {
+    negotiator: Negotiator,
+    early_announcement: Option<TakProtocolVersion>,
+    stats: StreamToleranceStats,
+}
+
+impl NegotiationStream {
+    #[must_use]
+    pub const fn new(policy: DowngradePolicy) -> Self {
+        Self {
+            negotiator: Negotiator::new(policy),
+            early_announcement: None,
+            stats: StreamToleranceStats {
+                blank_frames: 0,
+                early_announcements: 0,
+                duplicate_announcements: 0,
+                conflicting_announcements: 0,
+                unrecognized_frames: 0,
+            },
+        }
+    }
+
+    /// Wraps an existing negotiator, keeping its policy, state and
+    /// decode-failure limit.
+    #[must_use]
+    pub const fn from_negotiator(negotiator: Negotiator) -> Self {
+        let mut stream = Self::new(DowngradePolicy::FailOpen);
+        stream.negotiator = negotiator;
+        stream
+    }
+
+    #[must_use]
+    pub const fn state(&self) -> NegotiationState {
+        self.negotiator.state()
+    }
+
+    #[must_use]
+    pub const fn stats(&self) -> StreamToleranceStats {
+        self.stats
+    }
+
+    #[must_use]
+    pub fn negotiator_mut(&mut self) -> &mut Negotiator {
+        &mut self.negotiator
+    }
+
+    /// Starts the upgrade attempt, immediately applying an announcement the
+    /// server already sent.
+    pub fn begin_upgrade_attempt(&mut self) -> NegotiationEvent {
+        let event = self.negotiator.begin_upgrade_attempt();
+        match self.early_announcement.take() {
+            Some(version) if self.negotiator.state() == NegotiationState::AwaitingResponse => {
+                self.negotiator.observe_supported_version(version)
+            }
+            _ => event,
+        }
+    }
+
+    pub fn observe_frame<'a>(&mut self, frame: &'a [u8]) -> StreamFrame<'a> {
+        match self.negotiator.state() {
+            NegotiationState::Terminated { .. } => {
+                StreamFrame::Ignored(IgnoredFrame::AfterTermination)
+            }
+            NegotiationState::Upgraded(version) => self.observe_upgraded_frame(version, frame),
+            NegotiationState::LegacyXml | NegotiationState::AwaitingResponse => {
+                self.observe_xml_frame(frame)
+            }
+        }
+    }
+
+    fn observe_xml_frame<'a>(&mut self, frame: &'a [u8]) -> StreamFrame<'a> {
+        let trimmed = trim_leading_xml_whitespace(frame);
+        if trimmed.is_empty() {
+            self.stats.blank_frames += 1;
+            return StreamFrame::Ignored(IgnoredFrame::Blank);
+        }
+        if trimmed[0] == b'<' {
+            return StreamFrame::Data(trimmed);
+        }
+
+        if self.negotiator.state() == NegotiationState::AwaitingResponse {
+            return StreamFrame::Control(self.negotiator.observe_control_frame(trimmed));
+        }
+        match events::parse_control_frame(trimmed) {
+            Ok(version) => {
+                self.stats.early_announcements += 1;
+                self.early_announcement = Some(version);
+                StreamFrame::Ignored(IgnoredFrame::EarlyAnnouncement)
+            }
+            Err(_) => {
+                self.stats.unrecognized_frames += 1;
+                StreamFrame::Ignored(IgnoredFrame::Unrecognized)
+            }
+        }
+    }
+
+    fn observe_upgraded_frame<'a>(
+        &mut self,
+        negotiated: TakProtocolVersion,
+        frame: &'a [u8],
+    ) -> StreamFrame<'a> {
+        // Protobuf payloads are passed through untouched: a leading 0x0a is a
+        // field tag, not whitespace. The control marker byte would be an
+        // invalid protobuf wire type, so it cannot start a data frame.
+        if frame.first() != Some(&CONTROL_FRAME_VERSION_MARKER) {
+            return StreamFrame::Data(frame);
+        }
+        if events::parse_control_frame(frame) == Ok(negotiated) {
+            self.stats.duplicate_announcements += 1;
+            return StreamFrame::Ignored(IgnoredFrame::DuplicateAnnouncement);
+        }
+        self.stats.conflicting_announcements += 1;
+        StreamFrame::Ignored(IgnoredFrame::ConflictingAnnouncement)
+    }
+}
+
+fn trim_leading_xml_whitespace(frame: &[u8]) -> &[u8] {
+    let frame = frame.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(frame);
+    let start = frame
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(frame.len());
+    &frame[start..]
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::negotiation::events::{NegotiationTelemetry, NEGOTIATION_TELEMETRY_CHANNEL};
+    use crate::negotiation::events::{
+        NegotiationTelemetry, TakControlMessage, NEGOTIATION_TELEMETRY_CHANNEL,
+    };
     use crate::negotiation::{
         NegotiationEventKind, NegotiationReason, NegotiationState, Negotiator, TakProtocolVersion,
     };
@@ -349,6 +634,52 @@ mod tests {
         assert_eq!(negotiator.state(), NegotiationState::LegacyXml);
     }
 
+    #[test]
+    fn persistent_decode_failures_apply_downgrade_policy() {
+        let mut fail_open = Negotiator::new(DowngradePolicy::FailOpen).with_decode_failure_limit(3);
+        fail_open.begin_upgrade_attempt();
+        fail_open.observe_supported_version(TakProtocolVersion::V1);
+
+        for _ in 0..2 {
+            assert_eq!(
+                fail_open.observe_decode_failure().kind,
+                NegotiationEventKind::NoChange
+            );
+        }
+        fail_open.observe_decode_success();
+        assert_eq!(fail_open.consecutive_decode_failures(), 0);
+        for _ in 0..2 {
+            fail_open.observe_decode_failure();
+        }
+        let event = fail_open.observe_decode_failure();
+        assert_eq!(event.kind, NegotiationEventKind::FallbackToLegacy);
+        assert_eq!(
+            event.reason,
+            Some(NegotiationReason::PersistentDecodeFailure)
+        );
+        assert_eq!(fail_open.state(), NegotiationState::LegacyXml);
+        assert_eq!(
+            fail_open.observe_decode_failure().kind,
+            NegotiationEventKind::NoChange
+        );
+
+        let mut fail_closed =
+            Negotiator::new(DowngradePolicy::FailClosed).with_decode_failure_limit(1);
+        fail_closed.begin_upgrade_attempt();
+        fail_closed.observe_supported_version(TakProtocolVersion::V1);
+        let mut telemetry = NegotiationTelemetry::default();
+        let terminated = fail_closed.observe_decode_failure_with_telemetry(9, &mut telemetry);
+        assert_eq!(
+            terminated.state,
+            NegotiationState::Terminated {
+                reason: NegotiationReason::PersistentDecodeFailure
+            }
+        );
+        assert!(String::from_utf8(terminated.encode_record_payload())
+            .expect("utf8")
+            .ends_with("kind=terminated;reason=persistent_decode_failure"));
+    }
+
     #[test]
     fn control_frame_observation_classifies_supported_unsupported_and_malformed() {
         let mut supported = Negotiator::new(DowngradePolicy::FailClosed);
@@ -373,6 +704,52 @@ mod tests {
         assert_eq!(terminated.reason, Some(NegotiationReason::MalformedControl));
     }
 
+    #[test]
+    fn tak_control_messages_drive_stream_mode_upgrade() {
+        let support = TakControlMessage::ProtocolSupport {
+            version: Some(TakProtocolVersion::V1),
+        };
+        let mut negotiator = Negotiator::new(DowngradePolicy::FailClosed);
+        negotiator.begin_upgrade_attempt();
+        assert_eq!(
+            negotiator
+                .observe_tak_control(support, TakProtocolVersion::V1)
+                .kind,
+            NegotiationEventKind::NoChange
+        );
+        assert_eq!(negotiator.state(), NegotiationState::AwaitingResponse);
+        let accepted = negotiator.observe_tak_control(
+            TakControlMessage::Response { accepted: true },
+            TakProtocolVersion::V1,
+        );
+        assert_eq!(accepted.kind, NegotiationEventKind::UpgradeAccepted);
+        assert_eq!(
+            negotiator.state(),
+            NegotiationState::Upgraded(TakProtocolVersion::V1)
+        );
+
+        let mut refused = Negotiator::new(DowngradePolicy::FailOpen);
+        refused.begin_upgrade_attempt();
+        let fallback = refused.observe_tak_control(
+            TakControlMessage::Response { accepted: false },
+            TakProtocolVersion::V1,
+        );
+        assert_eq!(fallback.kind, NegotiationEventKind::FallbackToLegacy);
+        assert_eq!(fallback.reason, Some(NegotiationReason::UnsupportedVersion));
+
+        let mut no_offer = Negotiator::new(DowngradePolicy::FailClosed);
+        no_offer.begin_upgrade_attempt();
+        let terminated = no_offer.observe_tak_control(
+            TakControlMessage::ProtocolSupport { version: None },
+            TakProtocolVersion::V1,
+        );
+        assert_eq!(terminated.kind, NegotiationEventKind::Terminated);
+        assert_eq!(
+            terminated.reason,
+            Some(NegotiationReason::UnsupportedVersion)
+        );
+    }
+
     #[test]
     fn telemetry_emitters_link_events_to_session_and_state() {
         let mut negotiator = Negotiator::new(DowngradePolicy::FailOpen);
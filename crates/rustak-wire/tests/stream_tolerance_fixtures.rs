@@ -0,0 +1,152 @@
+//! Frame sequences reproducing stream-negotiation quirks seen from TAK
+//! servers: padded frames, repeated or early announcements, and legacy XML
+//! events interleaved with the control exchange.
+
+use rustak_wire::{
+    DowngradePolicy, IgnoredFrame, NegotiationEventKind, NegotiationState, NegotiationStream,
+    StreamFrame, TakProtocolVersion,
+};
+
+const PLI: &[u8] = b"<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G-U-C\"/>";
+
+fn data_frames(stream: &mut NegotiationStream, frames: &[&[u8]]) -> Vec<Vec<u8>> {
+    frames
+        .iter()
+        .filter_map(|frame| match stream.observe_frame(frame) {
+            StreamFrame::Data(payload) => Some(payload.to_vec()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn whitespace_prefixed_announcement_upgrades() {
+    for policy in [DowngradePolicy::FailOpen, DowngradePolicy::FailClosed] {
+        let mut stream = NegotiationStream::new(policy);
+        stream.begin_upgrade_attempt();
+
+        let frame = stream.observe_frame(b"\r\n  V\x01");
+        assert!(matches!(
+            frame,
+            StreamFrame::Control(event) if event.kind == NegotiationEventKind::UpgradeAccepted
+        ));
+        assert_eq!(
+            stream.state(),
+            NegotiationState::Upgraded(TakProtocolVersion::V1)
+        );
+    }
+}
+
+#[test]
+fn interleaved_xml_events_pass_through_while_awaiting_response() {
+    let mut stream = NegotiationStream::new(DowngradePolicy::FailClosed);
+    stream.begin_upgrade_attempt();
+
+    let mut padded = b"\n\t".to_vec();
+    padded.extend_from_slice(PLI);
+    let delivered = data_frames(&mut stream, &[PLI, b"", &padded, b"V\x01"]);
+
+    assert_eq!(delivered, vec![PLI.to_vec(), PLI.to_vec()]);
+    assert_eq!(
+        stream.state(),
+        NegotiationState::Upgraded(TakProtocolVersion::V1)
+    );
+    assert_eq!(stream.stats().blank_frames, 1);
+}
+
+#[test]
+fn duplicate_announcements_after_upgrade_are_absorbed() {
+    let mut stream = NegotiationStream::new(DowngradePolicy::FailClosed);
+    stream.begin_upgrade_attempt();
+    stream.observe_frame(b"V\x01");
+
+    let protobuf = [0x0a_u8, 0x03, b'a', b'b', b'c'];
+    assert_eq!(
+        stream.observe_frame(b"V\x01"),
+        StreamFrame::Ignored(IgnoredFrame::DuplicateAnnouncement)
+    );
+    assert_eq!(
+        stream.observe_frame(&protobuf),
+        StreamFrame::Data(&protobuf)
+    );
+    assert_eq!(
+        stream.observe_frame(b"V\x02"),
+        StreamFrame::Ignored(IgnoredFrame::ConflictingAnnouncement)
+    );
+
+    assert_eq!(
+        stream.state(),
+        NegotiationState::Upgraded(TakProtocolVersion::V1)
+    );
+    assert_eq!(stream.stats().duplicate_announcements, 1);
+    assert_eq!(stream.stats().conflicting_announcements, 1);
+}
+
+#[test]
+fn announcement_before_upgrade_attempt_is_replayed() {
+    let mut stream = NegotiationStream::new(DowngradePolicy::FailClosed);
+
+    assert_eq!(
+        stream.observe_frame(b" V\x01"),
+        StreamFrame::Ignored(IgnoredFrame::EarlyAnnouncement)
+    );
+    assert_eq!(stream.observe_frame(PLI), StreamFrame::Data(PLI));
+    assert_eq!(stream.state(), NegotiationState::LegacyXml);
+
+    let event = stream.begin_upgrade_attempt();
+    assert_eq!(event.kind, NegotiationEventKind::UpgradeAccepted);
+    assert_eq!(
+        stream.state(),
+        NegotiationState::Upgraded(TakProtocolVersion::V1)
+    );
+}
+
+#[test]
+fn repeated_early_announcements_upgrade_once() {
+    let mut stream = NegotiationStream::new(DowngradePolicy::FailOpen);
+    stream.observe_frame(b"V\x01");
+    stream.observe_frame(b"V\x01");
+    stream.begin_upgrade_attempt();
+
+    assert_eq!(
+        stream.observe_frame(b"V\x01"),
+        StreamFrame::Ignored(IgnoredFrame::DuplicateAnnouncement)
+    );
+    assert_eq!(stream.stats().early_announcements, 2);
+}
+
+#[test]
+fn legacy_stream_junk_is_ignored_without_state_change() {
+    let mut stream = NegotiationStream::new(DowngradePolicy::FailClosed);
+
+    assert_eq!(
+        stream.observe_frame(b"\xEF\xBB\xBF<event/>"),
+        StreamFrame::Data(b"<event/>")
+    );
+    assert_eq!(
+        stream.observe_frame(b"garbage"),
+        StreamFrame::Ignored(IgnoredFrame::Unrecognized)
+    );
+    assert_eq!(stream.state(), NegotiationState::LegacyXml);
+}
+
+#[test]
+fn malformed_control_while_awaiting_still_follows_policy() {
+    let mut fail_closed = NegotiationStream::new(DowngradePolicy::FailClosed);
+    fail_closed.begin_upgrade_attempt();
+    let frame = fail_closed.observe_frame(b"  X\x01");
+    assert!(matches!(
+        frame,
+        StreamFrame::Control(event) if event.kind == NegotiationEventKind::Terminated
+    ));
+    assert_eq!(
+        fail_closed.observe_frame(PLI),
+        StreamFrame::Ignored(IgnoredFrame::AfterTermination)
+    );
+
+    let mut fail_open = NegotiationStream::new(DowngradePolicy::FailOpen);
+    fail_open.begin_upgrade_attempt();
+    fail_open.observe_frame(b"V\x09");
+    assert_eq!(fail_open.state(), NegotiationState::LegacyXml);
+    assert_eq!(fail_open.observe_frame(PLI), StreamFrame::Data(PLI));
+}
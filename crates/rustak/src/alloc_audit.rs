@@ -0,0 +1,121 @@
+//! Allocation counting for hot-path audits (feature `alloc-audit`).
+//!
+//! Install [`CountingAllocator`] as the `#[global_allocator]` of a test or
+//! bench binary and wrap each pipeline stage in [`measure`]. Counts are kept
+//! per thread, so tests running in parallel do not pollute each other.
+//! `cargo run -p xtask -- alloc-audit` runs the stock framing, parse, map
+//! and serialize audit.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fmt;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static ALLOCATED_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// [`System`] allocator that counts allocations and reallocations made by
+/// the current thread.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingAllocator;
+
+// SAFETY: every call is forwarded unchanged to `System`; counting only
+// touches const-initialised thread locals, which never allocate.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        // SAFETY: the caller upholds `GlobalAlloc::alloc`'s contract.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        // SAFETY: the caller upholds `GlobalAlloc::alloc_zeroed`'s contract.
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        // SAFETY: the caller upholds `GlobalAlloc::realloc`'s contract.
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: the caller upholds `GlobalAlloc::dealloc`'s contract.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+fn record(bytes: usize) {
+    // `try_with` fails only while the thread's locals are being torn down.
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    let _ = ALLOCATED_BYTES.try_with(|total| {
+        total.set(total.get() + u64::try_from(bytes).unwrap_or(u64::MAX));
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllocationCounts {
+    /// Allocations plus reallocations.
+    pub allocations: u64,
+    /// Bytes requested, counting a reallocation's full new size.
+    pub bytes: u64,
+}
+
+/// Counts recorded on this thread so far; all zero unless
+/// [`CountingAllocator`] is the global allocator.
+#[must_use]
+pub fn thread_counts() -> AllocationCounts {
+    AllocationCounts {
+        allocations: ALLOCATIONS.with(Cell::get),
+        bytes: ALLOCATED_BYTES.with(Cell::get),
+    }
+}
+
+/// Runs `work` and returns what it allocated on this thread.
+pub fn measure<T>(work: impl FnOnce() -> T) -> (T, AllocationCounts) {
+    let before = thread_counts();
+    let value = work();
+    let after = thread_counts();
+    (
+        value,
+        AllocationCounts {
+            allocations: after.allocations - before.allocations,
+            bytes: after.bytes - before.bytes,
+        },
+    )
+}
+
+/// Allocation cost of one pipeline stage over `messages` messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StageReport {
+    pub stage: &'static str,
+    pub messages: u64,
+    pub counts: AllocationCounts,
+}
+
+impl StageReport {
+    #[must_use]
+    pub fn allocations_per_message(&self) -> f64 {
+        self.counts.allocations as f64 / self.messages.max(1) as f64
+    }
+
+    #[must_use]
+    pub fn bytes_per_message(&self) -> f64 {
+        self.counts.bytes as f64 / self.messages.max(1) as f64
+    }
+}
+
+impl fmt::Display for StageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "alloc_audit stage={} messages={} allocations_per_message={:.2} bytes_per_message={:.1}",
+            self.stage,
+            self.messages,
+            self.allocations_per_message(),
+            self.bytes_per_message()
+        )
+    }
+}
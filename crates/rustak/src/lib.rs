@@ -1,5 +1,19 @@
+use rustak_limits::{CodedError, ErrorCode};
 use thiserror::Error;
 
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
+pub mod runtime;
+pub mod supervisor;
+
+pub use runtime::{
+    BridgePipeline, InMemoryMetricsRegistry, MemoryTransportFactory, MemoryTransportHandle,
+    MetricsRegistry, NullRecorder, PassthroughPipeline, PayloadSigner, Recorder, RuntimeError,
+    RuntimeTransport, RustakRuntime, RustakRuntimeBuilder, RustakSession,
+    SignatureVerificationPipeline, TakrecRecorder, TransportFactory,
+};
+pub use supervisor::{RestartPolicy, Supervisor, TaskHealth};
+
 pub mod prelude {
     pub use rustak_core::{
         CoreError, CotDetail, DetailElement, ExtensionBlob, Kinematics, Position, TimestampUtc,
@@ -38,7 +52,32 @@ pub enum RustakError {
     #[error(transparent)]
     Config(#[from] rustak_config::ConfigError),
     #[error(transparent)]
+    Crypto(#[from] rustak_crypto::CryptoError),
+    #[error(transparent)]
     Admin(#[from] rustak_admin::AdminConfigError),
     #[error(transparent)]
     Record(#[from] rustak_record::RecordWriteError),
+    #[error(transparent)]
+    Runtime(#[from] runtime::RuntimeError),
+}
+
+impl CodedError for RustakError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Core(error) => error.code(),
+            Self::Timestamp(error) => error.code(),
+            Self::Io(error) => error.code(),
+            Self::Limits(error) => error.code(),
+            Self::WireConfig(error) => error.code(),
+            Self::WireFrame(error) => error.code(),
+            Self::Transport(error) => error.code(),
+            Self::Bridge(error) => error.code(),
+            Self::Sapient(error) => error.code(),
+            Self::Config(error) => error.code(),
+            Self::Crypto(error) => error.code(),
+            Self::Admin(error) => error.code(),
+            Self::Record(error) => error.code(),
+            Self::Runtime(error) => error.code(),
+        }
+    }
 }
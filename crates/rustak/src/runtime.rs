@@ -0,0 +1,593 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, StreamExt};
+use rustak_config::{RustakConfig, SignatureVerification, SigningConfig};
+use rustak_core::CotEvent;
+use rustak_crypto::{CotSigner, CotVerifier, SignaturePolicy};
+use rustak_io::layers::{Clock, SystemClock};
+use rustak_io::{CotEnvelope, IoError, MessageEnvelope, MessageSink, MessageSource};
+use rustak_limits::{CodedError, ErrorCode, Limits};
+use rustak_record::{append_envelope_chunk, RecordWriteError, TakrecWriter};
+use rustak_transport::TransportConfig;
+use rustak_wire::{decode_payload_for_format, encode_payload_for_format, WireFormat};
+use thiserror::Error;
+
+use crate::supervisor::Supervisor;
+use crate::Result;
+
+/// Opens the CoT sink/source pair a runtime session talks through.
+pub trait TransportFactory: Send + Sync {
+    fn open<'a>(
+        &'a self,
+        config: &'a TransportConfig,
+    ) -> BoxFuture<'a, std::result::Result<RuntimeTransport, IoError>>;
+}
+
+pub struct RuntimeTransport {
+    pub sink: Box<dyn MessageSink<Bytes>>,
+    pub source: Box<dyn MessageSource<Bytes>>,
+}
+
+impl fmt::Debug for RuntimeTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeTransport").finish_non_exhaustive()
+    }
+}
+
+/// Persists inbound envelopes (for example to a `.takrec` file).
+pub trait Recorder: Send + Sync {
+    fn record(&self, envelope: &CotEnvelope) -> std::result::Result<(), RecordWriteError>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullRecorder;
+
+impl Recorder for NullRecorder {
+    fn record(&self, _envelope: &CotEnvelope) -> std::result::Result<(), RecordWriteError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct TakrecRecorder<W: Write + Send> {
+    writer: Mutex<TakrecWriter<W>>,
+}
+
+impl<W: Write + Send> TakrecRecorder<W> {
+    #[must_use]
+    pub fn new(writer: TakrecWriter<W>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> TakrecWriter<W> {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<W: Write + Send> Recorder for TakrecRecorder<W> {
+    fn record(&self, envelope: &CotEnvelope) -> std::result::Result<(), RecordWriteError> {
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        append_envelope_chunk(&mut writer, envelope).map(|_| ())
+    }
+}
+
+/// Counter sink for runtime metrics.
+pub trait MetricsRegistry: Send + Sync {
+    fn increment(&self, name: &'static str, value: u64);
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryMetricsRegistry {
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl InMemoryMetricsRegistry {
+    #[must_use]
+    pub fn counter(&self, name: &str) -> u64 {
+        self.snapshot().get(name).copied().unwrap_or(0)
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> BTreeMap<&'static str, u64> {
+        self.counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl MetricsRegistry for InMemoryMetricsRegistry {
+    fn increment(&self, name: &'static str, value: u64) {
+        let mut counters = self
+            .counters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let counter = counters.entry(name).or_default();
+        *counter = counter.saturating_add(value);
+    }
+}
+
+pub const METRIC_MESSAGES_SENT: &str = "rustak_runtime_messages_sent";
+pub const METRIC_MESSAGES_RECEIVED: &str = "rustak_runtime_messages_received";
+pub const METRIC_MESSAGES_EMITTED: &str = "rustak_runtime_messages_emitted";
+
+/// Inbound processing stage between the transport and the application,
+/// e.g. SAPIENT/CoT bridging. May fan out, rewrite or swallow envelopes.
+pub trait BridgePipeline: Send + Sync {
+    fn process(&self, envelope: CotEnvelope) -> std::result::Result<Vec<CotEnvelope>, IoError>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassthroughPipeline;
+
+impl BridgePipeline for PassthroughPipeline {
+    fn process(&self, envelope: CotEnvelope) -> std::result::Result<Vec<CotEnvelope>, IoError> {
+        Ok(vec![envelope])
+    }
+}
+
+/// Signs outbound payloads with the `crypto.signing` key. The signature
+/// covers the decoded event rendered as CoT XML, so it verifies whichever
+/// wire format carried it.
+#[derive(Debug)]
+pub struct PayloadSigner {
+    signer: CotSigner,
+    wire_format: WireFormat,
+    limits: Limits,
+}
+
+impl PayloadSigner {
+    #[must_use]
+    pub fn new(signer: CotSigner, transport: &TransportConfig) -> Self {
+        Self {
+            signer,
+            wire_format: transport.wire_format,
+            limits: transport.limits.clone(),
+        }
+    }
+
+    /// `None` when the config carries no key of our own.
+    pub fn from_config(
+        config: &SigningConfig,
+        transport: &TransportConfig,
+    ) -> Result<Option<Self>> {
+        let (Some(key_id), Some(private_key)) = (&config.key_id, &config.private_key) else {
+            return Ok(None);
+        };
+        let signer = CotSigner::from_base64_seed(key_id.clone(), private_key)?;
+        Ok(Some(Self::new(signer, transport)))
+    }
+
+    /// Returns `payload` re-encoded in the transport's wire format with a
+    /// fresh signature.
+    pub fn sign(&self, payload: &[u8]) -> Result<Bytes> {
+        let unsignable = |reason: String| RuntimeError::UnsignablePayload { reason };
+        let xml = event_xml(payload, self.wire_format, &self.limits).map_err(unsignable)?;
+        let signed = self.signer.sign(&xml)?;
+        encode_payload_for_format(signed.as_bytes(), self.wire_format)
+            .map(Bytes::from)
+            .map_err(|error| unsignable(error.to_string()).into())
+    }
+}
+
+/// Checks CoT signatures on ingest against the decoded event, in whichever
+/// wire format it arrived. Under `require` rejected or undecodable events are
+/// dropped; under `warn` they pass through and are only counted.
+#[derive(Debug)]
+pub struct SignatureVerificationPipeline {
+    verifier: CotVerifier,
+    wire_format: WireFormat,
+    limits: Limits,
+    unverified: AtomicU64,
+}
+
+impl SignatureVerificationPipeline {
+    #[must_use]
+    pub fn new(verifier: CotVerifier) -> Self {
+        Self {
+            verifier,
+            wire_format: WireFormat::Xml,
+            limits: Limits::default(),
+            unverified: AtomicU64::new(0),
+        }
+    }
+
+    /// Decodes inbound payloads with the transport's wire format and limits.
+    #[must_use]
+    pub fn with_transport(mut self, transport: &TransportConfig) -> Self {
+        self.wire_format = transport.wire_format;
+        self.limits = transport.limits.clone();
+        self
+    }
+
+    pub fn from_config(config: &SigningConfig) -> Result<Self> {
+        let policy = match config.verification {
+            SignatureVerification::Ignore => SignaturePolicy::Ignore,
+            SignatureVerification::Warn => SignaturePolicy::Warn,
+            SignatureVerification::Require => SignaturePolicy::Require,
+        };
+        let verifier = config.trusted_keys.iter().try_fold(
+            CotVerifier::new(policy),
+            |verifier, trusted| {
+                verifier.with_trusted_key(trusted.key_id.clone(), &trusted.public_key)
+            },
+        )?;
+        Ok(Self::new(verifier))
+    }
+
+    /// Events seen without a valid trusted signature (dropped or not).
+    #[must_use]
+    pub fn unverified_count(&self) -> u64 {
+        self.unverified.load(Ordering::Relaxed)
+    }
+}
+
+impl BridgePipeline for SignatureVerificationPipeline {
+    fn process(&self, envelope: CotEnvelope) -> std::result::Result<Vec<CotEnvelope>, IoError> {
+        if self.verifier.policy() == SignaturePolicy::Ignore {
+            return Ok(vec![envelope]);
+        }
+        let verified = event_xml(&envelope.message, self.wire_format, &self.limits).is_ok_and(
+            |xml| matches!(self.verifier.verify(&xml), Ok(status) if status.is_verified()),
+        );
+        if verified {
+            return Ok(vec![envelope]);
+        }
+        self.unverified.fetch_add(1, Ordering::Relaxed);
+        if self.verifier.policy() == SignaturePolicy::Require {
+            Ok(Vec::new())
+        } else {
+            Ok(vec![envelope])
+        }
+    }
+}
+
+/// The event in `payload` rendered as CoT XML, the form signatures cover.
+fn event_xml(
+    payload: &[u8],
+    format: WireFormat,
+    limits: &Limits,
+) -> std::result::Result<String, String> {
+    let xml = decode_payload_for_format(payload, format).map_err(|error| error.to_string())?;
+    let xml = std::str::from_utf8(&xml).map_err(|error| error.to_string())?;
+    CotEvent::from_xml(xml, limits)
+        .map(|event| event.to_xml())
+        .map_err(|error| error.to_string())
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RuntimeError {
+    #[error("runtime builder requires a transport factory")]
+    MissingTransportFactory,
+    #[error("payload cannot be signed: {reason}")]
+    UnsignablePayload { reason: String },
+}
+
+impl CodedError for RuntimeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::MissingTransportFactory => ErrorCode::new("FACADE", 101),
+            Self::UnsignablePayload { .. } => ErrorCode::new("FACADE", 102),
+        }
+    }
+}
+
+/// Assembles a [`RustakRuntime`], letting every subsystem be swapped out.
+///
+/// Only the transport factory is mandatory; the clock defaults to the system
+/// clock, metrics to an in-memory registry, recording to a no-op and the
+/// bridge pipeline to a passthrough. `crypto.signing` in the config signs
+/// outbound payloads and verifies inbound ones ahead of the bridge pipeline.
+pub struct RustakRuntimeBuilder {
+    config: RustakConfig,
+    transport_factory: Option<Arc<dyn TransportFactory>>,
+    clock: Arc<dyn Clock>,
+    recorder: Arc<dyn Recorder>,
+    metrics: Arc<dyn MetricsRegistry>,
+    pipeline: Arc<dyn BridgePipeline>,
+}
+
+impl Default for RustakRuntimeBuilder {
+    fn default() -> Self {
+        Self::new(RustakConfig::default())
+    }
+}
+
+impl RustakRuntimeBuilder {
+    #[must_use]
+    pub fn new(config: RustakConfig) -> Self {
+        Self {
+            config,
+            transport_factory: None,
+            clock: Arc::new(SystemClock),
+            recorder: Arc::new(NullRecorder),
+            metrics: Arc::new(InMemoryMetricsRegistry::default()),
+            pipeline: Arc::new(PassthroughPipeline),
+        }
+    }
+
+    #[must_use]
+    pub fn with_transport_factory(mut self, factory: Arc<dyn TransportFactory>) -> Self {
+        self.transport_factory = Some(factory);
+        self
+    }
+
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    #[must_use]
+    pub fn with_recorder(mut self, recorder: Arc<dyn Recorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRegistry>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    #[must_use]
+    pub fn with_bridge_pipeline(mut self, pipeline: Arc<dyn BridgePipeline>) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    pub fn build(self) -> Result<RustakRuntime> {
+        self.config.validate_startup()?;
+        let transport_factory = self
+            .transport_factory
+            .ok_or(RuntimeError::MissingTransportFactory)?;
+        let signing = self
+            .config
+            .crypto
+            .as_ref()
+            .and_then(|crypto| crypto.signing.as_ref());
+        let transport = &self.config.transport;
+        let signer = match signing {
+            Some(signing) => PayloadSigner::from_config(signing, transport)?.map(Arc::new),
+            None => None,
+        };
+        let verification = match signing {
+            Some(signing) => Some(Arc::new(
+                SignatureVerificationPipeline::from_config(signing)?.with_transport(transport),
+            )),
+            None => None,
+        };
+
+        Ok(RustakRuntime {
+            signer,
+            verification,
+            config: self.config,
+            transport_factory,
+            clock: self.clock,
+            recorder: self.recorder,
+            supervisor: Supervisor::new().with_metrics(Arc::clone(&self.metrics)),
+            metrics: self.metrics,
+            pipeline: self.pipeline,
+        })
+    }
+}
+
+pub struct RustakRuntime {
+    config: RustakConfig,
+    signer: Option<Arc<PayloadSigner>>,
+    verification: Option<Arc<SignatureVerificationPipeline>>,
+    transport_factory: Arc<dyn TransportFactory>,
+    clock: Arc<dyn Clock>,
+    recorder: Arc<dyn Recorder>,
+    metrics: Arc<dyn MetricsRegistry>,
+    pipeline: Arc<dyn BridgePipeline>,
+    supervisor: Supervisor,
+}
+
+impl RustakRuntime {
+    #[must_use]
+    pub fn builder(config: RustakConfig) -> RustakRuntimeBuilder {
+        RustakRuntimeBuilder::new(config)
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &RustakConfig {
+        &self.config
+    }
+
+    /// Supervisor for the tasks an embedder spawns around its sessions;
+    /// panics and restarts are counted in the runtime metrics.
+    #[must_use]
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+
+    /// Inbound events seen without a valid trusted signature, when
+    /// `crypto.signing` asks for verification.
+    #[must_use]
+    pub fn unverified_count(&self) -> u64 {
+        self.verification
+            .as_ref()
+            .map_or(0, |verification| verification.unverified_count())
+    }
+
+    pub async fn connect(&self) -> Result<RustakSession> {
+        let transport = self.transport_factory.open(&self.config.transport).await?;
+        Ok(RustakSession {
+            signer: self.signer.clone(),
+            verification: self.verification.clone(),
+            sink: transport.sink,
+            source: transport.source,
+            clock: Arc::clone(&self.clock),
+            recorder: Arc::clone(&self.recorder),
+            metrics: Arc::clone(&self.metrics),
+            pipeline: Arc::clone(&self.pipeline),
+        })
+    }
+}
+
+/// One opened transport with the runtime's subsystems wired around it.
+pub struct RustakSession {
+    signer: Option<Arc<PayloadSigner>>,
+    verification: Option<Arc<SignatureVerificationPipeline>>,
+    sink: Box<dyn MessageSink<Bytes>>,
+    source: Box<dyn MessageSource<Bytes>>,
+    clock: Arc<dyn Clock>,
+    recorder: Arc<dyn Recorder>,
+    metrics: Arc<dyn MetricsRegistry>,
+    pipeline: Arc<dyn BridgePipeline>,
+}
+
+impl RustakSession {
+    /// Sends `payload`, signed first when the runtime has a signing key.
+    pub async fn send(&self, payload: Bytes) -> Result<()> {
+        let payload = match &self.signer {
+            Some(signer) => signer.sign(&payload)?,
+            None => payload,
+        };
+        self.sink.send(payload).await?;
+        self.metrics.increment(METRIC_MESSAGES_SENT, 1);
+        Ok(())
+    }
+
+    /// Receives one envelope, stamps its monotonic time from the runtime clock, records
+    /// it and returns whatever the bridge pipeline emits for it. Events failing
+    /// required signature verification are dropped before the pipeline.
+    pub async fn recv(&mut self) -> Result<Vec<CotEnvelope>> {
+        let mut envelope = self.source.recv().await?;
+        envelope.observed.monotonic = self.clock.now();
+        self.metrics.increment(METRIC_MESSAGES_RECEIVED, 1);
+
+        self.recorder.record(&envelope)?;
+        let mut emitted = Vec::new();
+        let accepted = match &self.verification {
+            Some(verification) => verification.process(envelope)?,
+            None => vec![envelope],
+        };
+        for envelope in accepted {
+            emitted.extend(self.pipeline.process(envelope)?);
+        }
+        self.metrics
+            .increment(METRIC_MESSAGES_EMITTED, emitted.len() as u64);
+        Ok(emitted)
+    }
+}
+
+/// In-process transport for tests and embedding: inbound envelopes are
+/// injected through [`MemoryTransportHandle`], outbound payloads collected.
+#[derive(Debug)]
+pub struct MemoryTransportFactory {
+    inbound: Mutex<Option<mpsc::UnboundedReceiver<CotEnvelope>>>,
+    outbound: Arc<Mutex<Vec<Bytes>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryTransportHandle {
+    inbound: mpsc::UnboundedSender<CotEnvelope>,
+    outbound: Arc<Mutex<Vec<Bytes>>>,
+}
+
+impl MemoryTransportFactory {
+    #[must_use]
+    pub fn new() -> (Self, MemoryTransportHandle) {
+        let (sender, receiver) = mpsc::unbounded();
+        let outbound = Arc::new(Mutex::new(Vec::new()));
+        (
+            Self {
+                inbound: Mutex::new(Some(receiver)),
+                outbound: Arc::clone(&outbound),
+            },
+            MemoryTransportHandle {
+                inbound: sender,
+                outbound,
+            },
+        )
+    }
+}
+
+impl MemoryTransportHandle {
+    pub fn inject(&self, envelope: CotEnvelope) -> std::result::Result<(), IoError> {
+        self.inbound
+            .unbounded_send(envelope)
+            .map_err(|_| IoError::Closed)
+    }
+
+    #[must_use]
+    pub fn sent(&self) -> Vec<Bytes> {
+        self.outbound
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl TransportFactory for MemoryTransportFactory {
+    fn open<'a>(
+        &'a self,
+        _config: &'a TransportConfig,
+    ) -> BoxFuture<'a, std::result::Result<RuntimeTransport, IoError>> {
+        let receiver = self
+            .inbound
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        let outbound = Arc::clone(&self.outbound);
+
+        async move {
+            let receiver = receiver.ok_or_else(|| {
+                IoError::Other("memory transport can only be opened once".to_owned())
+            })?;
+            Ok(RuntimeTransport {
+                sink: Box::new(MemorySink { outbound }),
+                source: Box::new(MemorySource { receiver }),
+            })
+        }
+        .boxed()
+    }
+}
+
+struct MemorySink {
+    outbound: Arc<Mutex<Vec<Bytes>>>,
+}
+
+impl MessageSink<Bytes> for MemorySink {
+    fn send(&self, msg: Bytes) -> BoxFuture<'_, std::result::Result<(), IoError>> {
+        self.outbound
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(msg);
+        async { Ok(()) }.boxed()
+    }
+}
+
+struct MemorySource {
+    receiver: mpsc::UnboundedReceiver<CotEnvelope>,
+}
+
+impl MessageSource<Bytes> for MemorySource {
+    fn recv(&mut self) -> BoxFuture<'_, std::result::Result<MessageEnvelope<Bytes>, IoError>> {
+        async move { self.receiver.next().await.ok_or(IoError::Closed) }.boxed()
+    }
+
+    fn into_stream(
+        self: Box<Self>,
+    ) -> Pin<Box<dyn Stream<Item = std::result::Result<MessageEnvelope<Bytes>, IoError>> + Send>>
+    {
+        Box::pin(self.receiver.map(Ok))
+    }
+}
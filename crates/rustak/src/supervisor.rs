@@ -0,0 +1,210 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rustak_io::IoError;
+
+use crate::runtime::MetricsRegistry;
+
+pub const METRIC_TASK_PANICS: &str = "rustak_runtime_task_panics";
+pub const METRIC_TASK_RESTARTS: &str = "rustak_runtime_task_restarts";
+
+/// When a supervised task is started again after it stops abnormally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// The first panic or error leaves the task `Failed`.
+    Never,
+    /// Panics restart the task, up to `max_restarts` times; errors fail it.
+    OnPanic { max_restarts: u32 },
+    /// Panics and errors both restart the task, up to `max_restarts` times.
+    OnFailure { max_restarts: u32 },
+}
+
+impl RestartPolicy {
+    fn allows(self, outcome: &TaskOutcome, restarts: u32) -> bool {
+        match self {
+            Self::Never => false,
+            Self::OnPanic { max_restarts } => {
+                matches!(outcome, TaskOutcome::Panicked(_)) && restarts < max_restarts
+            }
+            Self::OnFailure { max_restarts } => restarts < max_restarts,
+        }
+    }
+}
+
+/// Last known state of a supervised task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskHealth {
+    Running { restarts: u32 },
+    Completed { restarts: u32 },
+    Failed { reason: String, restarts: u32 },
+}
+
+impl TaskHealth {
+    #[must_use]
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed { .. })
+    }
+
+    #[must_use]
+    pub fn restarts(&self) -> u32 {
+        match self {
+            Self::Running { restarts }
+            | Self::Completed { restarts }
+            | Self::Failed { restarts, .. } => *restarts,
+        }
+    }
+}
+
+enum TaskOutcome {
+    Errored(IoError),
+    Panicked(String),
+}
+
+impl fmt::Display for TaskOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Errored(error) => write!(f, "error: {error}"),
+            Self::Panicked(message) => write!(f, "panicked: {message}"),
+        }
+    }
+}
+
+/// Runs long-lived subsystem tasks (read loops, queue drains, bridge stages)
+/// so a panic becomes a `Failed` health state, and a restart per
+/// [`RestartPolicy`], instead of silently ending the task.
+///
+/// The supervisor does not spawn anything itself: [`Supervisor::supervise`]
+/// returns a future for the caller's executor. Clones share health state.
+#[derive(Clone)]
+pub struct Supervisor {
+    tasks: Arc<Mutex<BTreeMap<String, TaskHealth>>>,
+    metrics: Option<Arc<dyn MetricsRegistry>>,
+}
+
+impl fmt::Debug for Supervisor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Supervisor")
+            .field("tasks", &self.health())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(BTreeMap::new())),
+            metrics: None,
+        }
+    }
+
+    /// Counts panics and restarts in `metrics`.
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: Arc<dyn MetricsRegistry>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Returns a future that runs `start()` until it completes or fails for
+    /// good, restarting it per `policy`. The future never panics and resolves
+    /// to the task's final health. Re-using `name` replaces the old entry.
+    pub fn supervise<F, Fut>(
+        &self,
+        name: impl Into<String>,
+        policy: RestartPolicy,
+        mut start: F,
+    ) -> BoxFuture<'static, TaskHealth>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), IoError>> + Send + 'static,
+    {
+        let name = name.into();
+        let supervisor = self.clone();
+        async move {
+            let mut restarts = 0;
+            loop {
+                supervisor.set(&name, TaskHealth::Running { restarts });
+                let outcome = match AssertUnwindSafe(async { start().await })
+                    .catch_unwind()
+                    .await
+                {
+                    Ok(Ok(())) => {
+                        let health = TaskHealth::Completed { restarts };
+                        supervisor.set(&name, health.clone());
+                        return health;
+                    }
+                    Ok(Err(error)) => TaskOutcome::Errored(error),
+                    Err(payload) => {
+                        supervisor.increment(METRIC_TASK_PANICS);
+                        TaskOutcome::Panicked(panic_message(payload.as_ref()))
+                    }
+                };
+
+                if !policy.allows(&outcome, restarts) {
+                    let health = TaskHealth::Failed {
+                        reason: outcome.to_string(),
+                        restarts,
+                    };
+                    supervisor.set(&name, health.clone());
+                    return health;
+                }
+                restarts += 1;
+                supervisor.increment(METRIC_TASK_RESTARTS);
+            }
+        }
+        .boxed()
+    }
+
+    #[must_use]
+    pub fn task_health(&self, name: &str) -> Option<TaskHealth> {
+        self.lock().get(name).cloned()
+    }
+
+    /// Health of every task supervised so far, by name.
+    #[must_use]
+    pub fn health(&self) -> BTreeMap<String, TaskHealth> {
+        self.lock().clone()
+    }
+
+    /// `true` while no supervised task has failed for good.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        !self.lock().values().any(TaskHealth::is_failed)
+    }
+
+    fn set(&self, name: &str, health: TaskHealth) {
+        self.lock().insert(name.to_owned(), health);
+    }
+
+    fn increment(&self, metric: &'static str) {
+        if let Some(metrics) = &self.metrics {
+            metrics.increment(metric, 1);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, TaskHealth>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_owned())
+}
@@ -0,0 +1,150 @@
+//! Allocations per message for each hot-path stage. Run with
+//! `cargo run -p xtask -- alloc-audit`; a stage that fails its assertion
+//! reports its `alloc_audit stage=...` line in the failure message.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use futures::executor::block_on;
+use rustak::alloc_audit::{measure, CountingAllocator, StageReport};
+use rustak_bridge::{BridgeConfig, DetectionPipeline, MappingTables};
+use rustak_core::CotEvent;
+use rustak_limits::Limits;
+use rustak_sapient::message::{DetectionReport, DetectionReportClassification, Location};
+use rustak_sapient::SapientMessage;
+use rustak_wire::{encode_payload_for_format, WireFormat, WireFrameCodec};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const MESSAGES: usize = 256;
+
+fn cot_stream(limits: &Limits) -> Vec<u8> {
+    let codec = WireFrameCodec::from_limits(WireFormat::Xml, limits);
+    let mut stream = Vec::new();
+    for index in 0..MESSAGES {
+        let event = format!(
+            "<event version=\"2.0\" uid=\"audit-{index}\" type=\"a-f-G-U-C\" how=\"m-g\" time=\"2024-03-01T12:00:00Z\" start=\"2024-03-01T12:00:00Z\" stale=\"2024-03-01T12:05:00Z\"><point lat=\"51.5\" lon=\"-0.12\" hae=\"11\" ce=\"9.9\" le=\"9.9\"/><detail><contact callsign=\"AUDIT-{index}\"/></detail></event>"
+        );
+        block_on(codec.write_frame(&mut stream, event.as_bytes())).expect("write frame");
+    }
+    stream
+}
+
+fn sapient_payloads() -> Vec<Vec<u8>> {
+    (0..MESSAGES)
+        .map(|index| {
+            SapientMessage {
+                node_id: Some("radar-1".to_owned()),
+                detection_report: Some(DetectionReport {
+                    report_id: Some(format!("r-{index}")),
+                    object_id: Some(format!("o-{}", index % 8)),
+                    location: Some(Location {
+                        x: Some(-0.12),
+                        y: Some(51.5),
+                        z: None,
+                    }),
+                    classification: vec![DetectionReportClassification {
+                        r#type: Some("UAV".to_owned()),
+                        confidence: Some(0.8),
+                    }],
+                    ..DetectionReport::default()
+                }),
+                ..SapientMessage::default()
+            }
+            .encode_payload()
+        })
+        .collect()
+}
+
+fn detection_pipeline() -> DetectionPipeline {
+    let mut config = BridgeConfig {
+        mappings: MappingTables {
+            class_to_cot: [("UAV".to_owned(), "a-h-A-M-F-Q".to_owned())]
+                .into_iter()
+                .collect(),
+            ..MappingTables::default()
+        },
+        ..BridgeConfig::default()
+    };
+    config.validation.strict_startup = false;
+    DetectionPipeline::new(config).expect("pipeline")
+}
+
+fn report(stage: &'static str, work: impl FnOnce()) -> StageReport {
+    let ((), counts) = measure(work);
+    StageReport {
+        stage,
+        messages: MESSAGES as u64,
+        counts,
+    }
+}
+
+#[test]
+fn reports_allocations_per_message_by_stage() {
+    let limits = Limits::default();
+    let xml_codec = WireFrameCodec::from_limits(WireFormat::Xml, &limits);
+    let tak_codec = WireFrameCodec::from_limits(WireFormat::TakProtocolV1, &limits);
+    let stream = cot_stream(&limits);
+    let payloads = sapient_payloads();
+    let mut pipeline = detection_pipeline();
+    let observed_at = UNIX_EPOCH + Duration::from_secs(1_709_294_400);
+
+    // Warm up lazily initialised state (executor thread locals and the
+    // like) so it is not charged to the first stage.
+    let mut warm = &stream[..];
+    block_on(xml_codec.read_frame(&mut warm)).expect("warm-up frame");
+
+    let mut frames = Vec::with_capacity(MESSAGES);
+    let frame = report("frame", || {
+        let mut reader = &stream[..];
+        for _ in 0..MESSAGES {
+            frames.push(block_on(xml_codec.read_frame(&mut reader)).expect("read frame"));
+        }
+    });
+
+    let mut parsed = 0;
+    let parse = report("parse", || {
+        for frame in &frames {
+            let xml = std::str::from_utf8(frame).expect("utf-8 frame");
+            CotEvent::from_xml(xml, &limits).expect("parse event");
+            parsed += 1;
+        }
+    });
+
+    let mut events = Vec::with_capacity(MESSAGES);
+    let map = report("map", || {
+        for payload in &payloads {
+            let message = SapientMessage::decode_payload(payload).expect("decode report");
+            let event = pipeline
+                .process(&message, observed_at)
+                .expect("map report")
+                .expect("new report");
+            events.push(event);
+        }
+    });
+
+    let mut output = Vec::with_capacity(MESSAGES * 256);
+    let serialize = report("serialize", || {
+        for event in &events {
+            let payload =
+                encode_payload_for_format(event.to_xml().as_bytes(), WireFormat::TakProtocolV1)
+                    .expect("encode event");
+            block_on(tak_codec.write_frame(&mut output, &payload)).expect("write frame");
+        }
+    });
+
+    assert_eq!(frames.len(), MESSAGES);
+    assert_eq!(parsed, MESSAGES);
+    assert_eq!(events.len(), MESSAGES);
+    assert!(!output.is_empty());
+    assert!(
+        frame.counts.allocations > 0,
+        "CountingAllocator should see each frame's buffer: {frame}"
+    );
+    for stage in [parse, map, serialize] {
+        assert!(
+            stage.counts.allocations > 0,
+            "stage allocated nothing: {stage}"
+        );
+    }
+}
@@ -1,7 +1,26 @@
-use std::time::Duration;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use rustak::prelude::{Limits, Position, TakProtocolVersion, WireConfig, WireFormat};
-use rustak::RustakError;
+use bytes::Bytes;
+use futures::executor::block_on;
+use rustak::prelude::{
+    CotEnvelope, IoError, Limits, MessageEnvelope, Position, TakProtocolVersion, WireConfig,
+    WireFormat,
+};
+use rustak::runtime::{METRIC_MESSAGES_EMITTED, METRIC_MESSAGES_RECEIVED, METRIC_MESSAGES_SENT};
+use rustak::supervisor::{METRIC_TASK_PANICS, METRIC_TASK_RESTARTS};
+use rustak::SignatureVerificationPipeline;
+use rustak::{
+    BridgePipeline, InMemoryMetricsRegistry, MemoryTransportFactory, Recorder, RestartPolicy,
+    RuntimeError, RustakError, RustakRuntime, Supervisor, TaskHealth,
+};
+use rustak_config::{RustakConfig, SignatureVerification, SigningConfig, TrustedKey};
+use rustak_core::CotEvent;
+use rustak_crypto::CotSigner;
+use rustak_io::layers::Clock;
+use rustak_record::RecordWriteError;
+use rustak_wire::{decode_payload_for_format, encode_payload_for_format};
 
 #[test]
 fn prelude_exposes_core_and_wire_types() {
@@ -37,3 +56,282 @@ fn unified_error_wraps_wire_config_errors() {
 
     assert!(matches!(facade_error, RustakError::WireConfig(_)));
 }
+
+struct FixedClock(Instant);
+
+impl Clock for FixedClock {
+    fn now(&self) -> Instant {
+        self.0
+    }
+}
+
+#[derive(Default)]
+struct CapturingRecorder {
+    frames: Mutex<Vec<Bytes>>,
+}
+
+impl Recorder for CapturingRecorder {
+    fn record(&self, envelope: &CotEnvelope) -> Result<(), RecordWriteError> {
+        self.frames
+            .lock()
+            .expect("recorder lock")
+            .push(envelope.message.clone());
+        Ok(())
+    }
+}
+
+struct DuplicatingPipeline;
+
+impl BridgePipeline for DuplicatingPipeline {
+    fn process(&self, envelope: CotEnvelope) -> Result<Vec<CotEnvelope>, IoError> {
+        Ok(vec![envelope.clone(), envelope])
+    }
+}
+
+#[test]
+fn runtime_builder_requires_transport_factory() {
+    let error = RustakRuntime::builder(RustakConfig::default())
+        .build()
+        .err()
+        .expect("builder without transport factory must fail");
+
+    assert!(matches!(
+        error,
+        RustakError::Runtime(RuntimeError::MissingTransportFactory)
+    ));
+}
+
+#[test]
+fn runtime_runs_full_stack_with_injected_doubles() {
+    let (factory, handle) = MemoryTransportFactory::new();
+    let pinned = Instant::now();
+    let recorder = Arc::new(CapturingRecorder::default());
+    let metrics = Arc::new(InMemoryMetricsRegistry::default());
+
+    let runtime = RustakRuntime::builder(RustakConfig::default())
+        .with_transport_factory(Arc::new(factory))
+        .with_clock(Arc::new(FixedClock(pinned)))
+        .with_recorder(recorder.clone())
+        .with_metrics(metrics.clone())
+        .with_bridge_pipeline(Arc::new(DuplicatingPipeline))
+        .build()
+        .expect("runtime should build");
+
+    block_on(async {
+        let mut session = runtime.connect().await.expect("memory transport opens");
+        session
+            .send(Bytes::from_static(b"<event/>"))
+            .await
+            .expect("send succeeds");
+
+        handle
+            .inject(MessageEnvelope::new(Bytes::from_static(
+                b"<event uid=\"a\"/>",
+            )))
+            .expect("inject succeeds");
+        let emitted = session.recv().await.expect("recv succeeds");
+
+        assert_eq!(emitted.len(), 2);
+        assert!(emitted
+            .iter()
+            .all(|envelope| envelope.observed.monotonic == pinned));
+    });
+
+    assert_eq!(handle.sent(), vec![Bytes::from_static(b"<event/>")]);
+    assert_eq!(
+        *recorder.frames.lock().expect("recorder lock"),
+        vec![Bytes::from_static(b"<event uid=\"a\"/>")]
+    );
+    assert_eq!(metrics.counter(METRIC_MESSAGES_SENT), 1);
+    assert_eq!(metrics.counter(METRIC_MESSAGES_RECEIVED), 1);
+    assert_eq!(metrics.counter(METRIC_MESSAGES_EMITTED), 2);
+}
+
+#[test]
+fn memory_transport_reports_closed_after_handle_drop() {
+    let (factory, handle) = MemoryTransportFactory::new();
+    let runtime = RustakRuntime::builder(RustakConfig::default())
+        .with_transport_factory(Arc::new(factory))
+        .build()
+        .expect("runtime should build");
+
+    block_on(async {
+        let mut session = runtime.connect().await.expect("memory transport opens");
+        drop(handle);
+        let error = session.recv().await.expect_err("closed source");
+        assert!(matches!(error, RustakError::Io(IoError::Closed)));
+
+        let reopen = runtime.connect().await.err().expect("single-use transport");
+        assert!(matches!(reopen, RustakError::Io(IoError::Other(_))));
+    });
+}
+
+const SIGNED_EVENT: &str = "<event version=\"2.0\" uid=\"ANDROID-1\" type=\"a-f-G-U-C\" how=\"h-e\" time=\"2024-03-01T12:00:00Z\" start=\"2024-03-01T12:00:00Z\" stale=\"2024-03-01T12:05:00Z\"><point lat=\"51.5\" lon=\"-0.1\" hae=\"0\" ce=\"10\" le=\"10\"/><detail><contact callsign=\"ALPHA\"/></detail></event>";
+
+#[test]
+fn signature_pipeline_drops_unsigned_events_when_required() {
+    let signer = CotSigner::from_seed("gw-1", [3; 32]).expect("signer");
+    let pipeline = SignatureVerificationPipeline::from_config(&SigningConfig {
+        verification: SignatureVerification::Require,
+        key_id: None,
+        private_key: None,
+        trusted_keys: vec![TrustedKey {
+            key_id: "gw-1".to_owned(),
+            public_key: signer.public_key_base64(),
+        }],
+    })
+    .expect("pipeline from config");
+
+    let event = CotEvent::from_xml(SIGNED_EVENT, &Limits::default())
+        .expect("event")
+        .to_xml();
+    let signed = signer.sign(&event).expect("sign");
+
+    let accepted = pipeline
+        .process(MessageEnvelope::new(Bytes::from(signed)))
+        .expect("pipeline never errors");
+    assert_eq!(accepted.len(), 1);
+
+    let dropped = pipeline
+        .process(MessageEnvelope::new(Bytes::from(event)))
+        .expect("pipeline never errors");
+    assert!(dropped.is_empty());
+    assert_eq!(pipeline.unverified_count(), 1);
+}
+
+#[test]
+fn runtime_signs_and_verifies_from_config_in_either_wire_format() {
+    let public_key = CotSigner::from_seed("gw-1", [3; 32])
+        .expect("signer")
+        .public_key_base64();
+    for (wire_format, format) in [
+        ("xml", WireFormat::Xml),
+        ("tak_v1", WireFormat::TakProtocolV1),
+    ] {
+        let config = RustakConfig::from_yaml_str(&format!(
+            r#"
+transport:
+  protocol:
+    type: tcp
+    addr: 127.0.0.1:8089
+  wire_format: {wire_format}
+crypto:
+  provider: ring
+  revocation: prefer
+  signing:
+    verification: require
+    key_id: gw-1
+    private_key: AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM=
+    trusted_keys:
+      - key_id: gw-1
+        public_key: {public_key}
+"#
+        ))
+        .expect("config");
+        let (factory, handle) = MemoryTransportFactory::new();
+        let runtime = RustakRuntime::builder(config)
+            .with_transport_factory(Arc::new(factory))
+            .build()
+            .expect("runtime builds from config");
+        let unsigned =
+            encode_payload_for_format(SIGNED_EVENT.as_bytes(), format).expect("encode event");
+
+        block_on(async {
+            let mut session = runtime.connect().await.expect("memory transport opens");
+            session
+                .send(Bytes::from(unsigned.clone()))
+                .await
+                .expect("send signs");
+            let signed = handle.sent().pop().expect("sent payload");
+            let xml = decode_payload_for_format(&signed, format).expect("decode sent");
+            assert!(
+                String::from_utf8_lossy(&xml).contains("<_rustak_sig alg=\"ed25519\" kid=\"gw-1\"")
+            );
+
+            let tampered = String::from_utf8(xml)
+                .expect("utf-8")
+                .replace("ALPHA", "BRAVO");
+            let tampered =
+                encode_payload_for_format(tampered.as_bytes(), format).expect("encode tampered");
+            for (payload, accepted) in [(signed.to_vec(), 1), (unsigned, 0), (tampered, 0)] {
+                handle
+                    .inject(MessageEnvelope::new(Bytes::from(payload)))
+                    .expect("inject");
+                assert_eq!(session.recv().await.expect("recv").len(), accepted);
+            }
+        });
+        assert_eq!(runtime.unverified_count(), 2, "{wire_format}");
+    }
+}
+
+#[test]
+fn supervisor_turns_panics_into_failed_health_after_restarts() {
+    let metrics = Arc::new(InMemoryMetricsRegistry::default());
+    let (factory, _handle) = MemoryTransportFactory::new();
+    let runtime = RustakRuntime::builder(RustakConfig::default())
+        .with_transport_factory(Arc::new(factory))
+        .with_metrics(metrics.clone())
+        .build()
+        .expect("runtime builds");
+    let supervisor = runtime.supervisor();
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = Arc::clone(&attempts);
+    let health = block_on(supervisor.supervise(
+        "read-loop",
+        RestartPolicy::OnPanic { max_restarts: 2 },
+        move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { panic!("decoder invariant broken") }
+        },
+    ));
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        health,
+        TaskHealth::Failed {
+            reason: "panicked: decoder invariant broken".to_owned(),
+            restarts: 2,
+        }
+    );
+    assert_eq!(supervisor.task_health("read-loop"), Some(health));
+    assert!(!supervisor.is_healthy());
+    assert_eq!(metrics.counter(METRIC_TASK_PANICS), 3);
+    assert_eq!(metrics.counter(METRIC_TASK_RESTARTS), 2);
+}
+
+#[test]
+fn supervisor_restarts_after_panic_and_records_completion() {
+    let supervisor = Supervisor::new();
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = Arc::clone(&attempts);
+    let health = block_on(supervisor.supervise(
+        "queue-drain",
+        RestartPolicy::OnPanic { max_restarts: 3 },
+        move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert!(attempt > 0, "first drain panics");
+                Ok(())
+            }
+        },
+    ));
+    assert_eq!(health, TaskHealth::Completed { restarts: 1 });
+    assert!(supervisor.is_healthy());
+
+    let failed = block_on(supervisor.supervise(
+        "bridge-stage",
+        RestartPolicy::OnPanic { max_restarts: 3 },
+        || async { Err(IoError::Closed) },
+    ));
+    assert!(failed.is_failed());
+    assert_eq!(failed.restarts(), 0);
+
+    let retried = block_on(supervisor.supervise(
+        "bridge-stage",
+        RestartPolicy::OnFailure { max_restarts: 1 },
+        || async { Err(IoError::Closed) },
+    ));
+    assert_eq!(retried.restarts(), 1);
+    assert_eq!(supervisor.health().len(), 2);
+}
@@ -1,8 +1,11 @@
 #![forbid(unsafe_code)]
 
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 
+use rustak_core::time::TimestampUtc;
+use rustak_record::{recover_chunk_payloads, RecordWriteError};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
@@ -22,6 +25,7 @@ pub struct ReplayObservation {
 pub enum HarnessError {
     Io(std::io::Error),
     Json(serde_json::Error),
+    Record(RecordWriteError),
 }
 
 impl std::fmt::Display for HarnessError {
@@ -29,6 +33,7 @@ impl std::fmt::Display for HarnessError {
         match self {
             Self::Io(error) => write!(f, "I/O error: {error}"),
             Self::Json(error) => write!(f, "JSON parse error: {error}"),
+            Self::Record(error) => write!(f, "takrec error: {error}"),
         }
     }
 }
@@ -47,11 +52,154 @@ impl From<serde_json::Error> for HarnessError {
     }
 }
 
+impl From<RecordWriteError> for HarnessError {
+    fn from(value: RecordWriteError) -> Self {
+        Self::Record(value)
+    }
+}
+
 pub fn load_replay_fixture(path: impl AsRef<Path>) -> Result<Vec<ReplayObservation>, HarnessError> {
     let bytes = fs::read(path)?;
     Ok(serde_json::from_slice::<Vec<ReplayObservation>>(&bytes)?)
 }
 
+/// Chunks of a capture that could not be turned into observations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TakrecConversionReport {
+    pub chunks_read: usize,
+    /// Chunk sequences whose payload was neither CoT XML nor a TAK protocol
+    /// v1 payload carrying CoT with `uid`, `type` and `time`.
+    pub undecodable_sequences: Vec<u64>,
+    pub truncated_tail: bool,
+}
+
+/// TAK mesh header (`0xbf 0x01 0xbf`) that UDP captures keep in front of
+/// each TAK protocol v1 payload.
+const TAK_MESH_HEADER: [u8; 3] = [0xbf, 0x01, 0xbf];
+
+/// Converts a `.takrec` capture into observations comparable with
+/// simulator golden runs. The stream id is the recording's channel tag
+/// (`protocol_hint` in the header; chunks carry no per-chunk tag), the
+/// sequence is the chunk sequence and the timestamp is the event `time`.
+/// Classification is the affiliation of the CoT type. CoT carries no
+/// behaviour or confidence, so those are left empty and `0.0`; convert golden
+/// runs the same way before comparing digests.
+pub fn observations_from_takrec<R: Read>(
+    source: R,
+) -> Result<(Vec<ReplayObservation>, TakrecConversionReport), HarnessError> {
+    let (recovery, payloads) = recover_chunk_payloads(source)?;
+    let stream_id = recovery.header.protocol_hint;
+    let mut report = TakrecConversionReport {
+        chunks_read: payloads.len(),
+        truncated_tail: recovery.truncated_tail,
+        ..TakrecConversionReport::default()
+    };
+
+    let mut observations = Vec::with_capacity(payloads.len());
+    for (chunk, payload) in recovery.chunks.iter().zip(&payloads) {
+        match observation_from_payload(&stream_id, chunk.sequence, payload) {
+            Some(observation) => observations.push(observation),
+            None => report.undecodable_sequences.push(chunk.sequence),
+        }
+    }
+    Ok((observations, report))
+}
+
+/// [`observations_from_takrec`] for a file on disk.
+pub fn load_takrec_observations(
+    path: impl AsRef<Path>,
+) -> Result<(Vec<ReplayObservation>, TakrecConversionReport), HarnessError> {
+    observations_from_takrec(std::io::BufReader::new(fs::File::open(path)?))
+}
+
+fn observation_from_payload(
+    stream_id: &str,
+    sequence: u64,
+    payload: &[u8],
+) -> Option<ReplayObservation> {
+    let decoded;
+    let xml_bytes = if payload.trim_ascii_start().starts_with(b"<") {
+        payload
+    } else {
+        let proto = payload.strip_prefix(&TAK_MESH_HEADER).unwrap_or(payload);
+        decoded = rustak_proto::decode_v1_payload(proto).ok()?;
+        &decoded
+    };
+    let xml = std::str::from_utf8(xml_bytes).ok()?;
+
+    let cot_type = element_attribute(xml, "event", "type")?;
+    let time = TimestampUtc::parse_rfc3339(&element_attribute(xml, "event", "time")?).ok()?;
+    Some(ReplayObservation {
+        stream_id: stream_id.to_owned(),
+        sequence,
+        timestamp_nanos: u64::try_from(time.unix_nanos()).ok()?,
+        uid: element_attribute(xml, "event", "uid")?,
+        classification: affiliation(&cot_type).to_owned(),
+        behavior: String::new(),
+        confidence: 0.0,
+        cot_type,
+    })
+}
+
+/// Affiliation name for the second atom of an atom-family CoT type
+/// (`a-h-...` is hostile); other families are `unknown`.
+fn affiliation(cot_type: &str) -> &'static str {
+    let mut atoms = cot_type.split('-');
+    if atoms.next() != Some("a") {
+        return "unknown";
+    }
+    match atoms.next() {
+        Some("f") => "friendly",
+        Some("a") => "assumed_friend",
+        Some("h") => "hostile",
+        Some("s") => "suspect",
+        Some("n") => "neutral",
+        Some("p") => "pending",
+        Some("j") => "joker",
+        Some("k") => "faker",
+        Some("o") => "none",
+        _ => "unknown",
+    }
+}
+
+/// Reads `attribute` from the first `<element ...>` start tag.
+fn element_attribute(xml: &str, element: &str, attribute: &str) -> Option<String> {
+    let open = format!("<{element}");
+    let mut search = 0;
+    let mut rest = loop {
+        let start = search + xml[search..].find(&open)? + open.len();
+        match xml[start..].chars().next() {
+            Some(next) if next.is_whitespace() || next == '/' || next == '>' => {
+                break &xml[start..]
+            }
+            _ => search = start,
+        }
+    };
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() || rest.starts_with('>') || rest.starts_with("/>") {
+            return None;
+        }
+        let equals = rest.find('=')?;
+        let name = rest[..equals].trim();
+        let after = rest[equals + 1..].trim_start();
+        let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value_end = after[1..].find(quote)? + 1;
+        if name == attribute {
+            return Some(
+                after[1..value_end]
+                    .replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&amp;", "&"),
+            );
+        }
+        rest = &after[value_end + 1..];
+    }
+}
+
 #[must_use]
 pub fn canonicalize(observations: &[ReplayObservation]) -> Vec<ReplayObservation> {
     let mut canonical = observations.to_vec();
@@ -109,6 +109,7 @@ fn run_bridge_projection_pipeline(observations: &[ReplayObservation]) -> Vec<Bri
     let mut correlator = Correlator::new(CorrelatorConfig {
         uid_policy: UidPolicy::StablePerObject,
         uid_prefix: "trk".to_owned(),
+        ..CorrelatorConfig::default()
     })
     .expect("correlator config should be valid");
     let mut deduplicator =
@@ -120,14 +121,18 @@ fn run_bridge_projection_pipeline(observations: &[ReplayObservation]) -> Vec<Bri
     for observation in observations {
         let observed_time = unix_nanos_to_system_time(observation.timestamp_nanos);
         let correlated_uid = correlator
-            .correlate(&CorrelationInput {
-                node_id: observation.stream_id.clone(),
-                object_id: Some(observation.uid.clone()),
-                detection_id: Some(format!(
-                    "{}:{}:{}",
-                    observation.uid, observation.stream_id, observation.sequence
-                )),
-            })
+            .correlate(
+                &CorrelationInput {
+                    node_id: observation.stream_id.clone(),
+                    object_id: Some(observation.uid.clone()),
+                    detection_id: Some(format!(
+                        "{}:{}:{}",
+                        observation.uid, observation.stream_id, observation.sequence
+                    )),
+                    sapient_version: None,
+                },
+                observed_time,
+            )
             .expect("correlator input must be complete");
 
         let dedup_key = format!("{correlated_uid}:{}", observation.sequence);
@@ -0,0 +1,117 @@
+use interop_harness_tests::{
+    deterministic_replay_digest, observations_from_takrec, ReplayObservation,
+};
+use rustak_limits::Limits;
+use rustak_record::{replay_digest, TakrecHeader, TakrecWriter};
+
+fn capture(payloads: &[Vec<u8>]) -> Vec<u8> {
+    let mut writer = TakrecWriter::new(
+        Vec::new(),
+        TakrecHeader::new("rustak", "0.1.0", "alpha", "default"),
+    )
+    .expect("writer");
+    for payload in payloads {
+        writer.append_chunk(payload).expect("append");
+    }
+    writer.into_inner().expect("finish")
+}
+
+fn event(uid: &str, cot_type: &str, time: &str) -> String {
+    format!(
+        "<event version=\"2.0\" uid=\"{uid}\" type=\"{cot_type}\" how=\"m-g\" time=\"{time}\" start=\"{time}\" stale=\"{time}\"><point lat=\"1\" lon=\"2\" hae=\"0\" ce=\"5\" le=\"5\"/></event>"
+    )
+}
+
+#[test]
+fn takrec_capture_converts_to_replay_observations() {
+    let mut mesh = vec![0xbf, 0x01, 0xbf];
+    mesh.extend(
+        rustak_proto::encode_v1_payload(
+            event("trk-211", "a-h-A-M-F-Q", "2023-11-14T22:13:20.500Z").as_bytes(),
+        )
+        .expect("encode"),
+    );
+    let recording = capture(&[
+        event("uas-001", "a-f-G-U-C", "2023-11-14T22:13:20.000Z").into_bytes(),
+        b"not a cot event".to_vec(),
+        mesh,
+    ]);
+
+    let (observations, report) = observations_from_takrec(recording.as_slice()).expect("convert");
+    assert_eq!(report.chunks_read, 3);
+    assert_eq!(report.undecodable_sequences, vec![1]);
+    assert!(!report.truncated_tail);
+    assert_eq!(
+        observations,
+        vec![
+            ReplayObservation {
+                stream_id: "alpha".to_owned(),
+                sequence: 0,
+                timestamp_nanos: 1_700_000_000_000_000_000,
+                uid: "uas-001".to_owned(),
+                cot_type: "a-f-G-U-C".to_owned(),
+                classification: "friendly".to_owned(),
+                behavior: String::new(),
+                confidence: 0.0,
+            },
+            ReplayObservation {
+                stream_id: "alpha".to_owned(),
+                sequence: 2,
+                timestamp_nanos: 1_700_000_000_500_000_000,
+                uid: "trk-211".to_owned(),
+                cot_type: "a-h-A-M-F-Q".to_owned(),
+                classification: "hostile".to_owned(),
+                behavior: String::new(),
+                confidence: 0.0,
+            },
+        ]
+    );
+}
+
+#[test]
+fn takrec_observation_digest_matches_golden_run_in_any_order() {
+    let golden = [
+        event("uas-001", "a-f-G-U-C", "2023-11-14T22:13:20.000Z").into_bytes(),
+        event("uas-002", "a-u-G", "2023-11-14T22:13:21.000Z").into_bytes(),
+    ];
+    let (expected, _) = observations_from_takrec(capture(&golden).as_slice()).expect("golden");
+    let (mut field, _) = observations_from_takrec(capture(&golden).as_slice()).expect("field");
+    field.reverse();
+    assert_eq!(
+        deterministic_replay_digest(&field),
+        deterministic_replay_digest(&expected)
+    );
+
+    let drifted = [
+        golden[0].clone(),
+        event("uas-002", "a-h-G", "2023-11-14T22:13:21.000Z").into_bytes(),
+    ];
+    let (drifted, _) = observations_from_takrec(capture(&drifted).as_slice()).expect("drifted");
+    assert_ne!(
+        deterministic_replay_digest(&drifted),
+        deterministic_replay_digest(&expected)
+    );
+}
+
+#[test]
+fn record_replay_pair_digests_equal_in_rustak_record() {
+    let recorded = [
+        event("uas-001", "a-f-G-U-C", "2023-11-14T22:13:20.000Z").into_bytes(),
+        event("uas-002", "a-u-G", "2023-11-14T22:13:21.000Z").into_bytes(),
+    ];
+    let replayed: Vec<Vec<u8>> = recorded
+        .iter()
+        .map(|xml| {
+            let mut mesh = vec![0xbf, 0x01, 0xbf];
+            mesh.extend(rustak_proto::encode_v1_payload(xml).expect("encode"));
+            mesh
+        })
+        .collect();
+
+    let recorded =
+        replay_digest(capture(&recorded).as_slice(), Limits::default()).expect("recorded");
+    let replayed =
+        replay_digest(capture(&replayed).as_slice(), Limits::default()).expect("replayed");
+    assert_eq!(recorded.decoded, 2);
+    assert_eq!(recorded.hex(), replayed.hex());
+}
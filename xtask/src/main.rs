@@ -1,7 +1,8 @@
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitCode};
+use std::time::SystemTime;
 
 #[derive(Debug)]
 struct AppError {
@@ -49,6 +50,13 @@ fn run() -> Result<(), AppError> {
         return Err(AppError::usage(usage()));
     };
 
+    if subcommand == "perf-gate" {
+        return run_perf_gate(PerfGateOptions::parse(args)?);
+    }
+    if subcommand == "api-check" {
+        return run_api_check(ApiCheckOptions::parse(args)?);
+    }
+
     if args.next().is_some() {
         return Err(AppError::usage(format!(
             "Unexpected extra arguments for `{subcommand}`.\n\n{}",
@@ -63,6 +71,7 @@ fn run() -> Result<(), AppError> {
         "hardening" => run_hardening(),
         "hardening-supply-chain" => run_hardening_supply_chain(),
         "hardening-loom" => run_hardening_loom(),
+        "alloc-audit" => run_alloc_audit(),
         "help" | "--help" | "-h" => {
             println!("{}", usage());
             Ok(())
@@ -210,6 +219,513 @@ fn run_hardening_loom() -> Result<(), AppError> {
     run_steps("hardening-loom", &steps)
 }
 
+fn run_alloc_audit() -> Result<(), AppError> {
+    let steps = [Step {
+        name: "Hot-path allocations per message",
+        program: "cargo",
+        args: &[
+            "test",
+            "--manifest-path",
+            "crates/rustak/Cargo.toml",
+            "--features",
+            "alloc-audit",
+            "--test",
+            "alloc_audit",
+        ],
+        env: &[],
+    }];
+    run_steps("alloc-audit", &steps)
+}
+
+/// Criterion groups covering the framing/codec hot path. `udp_receive` is
+/// left out because it depends on kernel socket scheduling.
+const PERF_GATE_GROUPS: [&str; 3] = ["decode_pipeline", "serialisation", "transport_throughput"];
+const PERF_GATE_BASELINE: &str = "benches/perf_baseline.txt";
+const PERF_GATE_DEFAULT_THRESHOLD_PERCENT: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+struct PerfGateOptions {
+    baseline: PathBuf,
+    threshold_percent: f64,
+    update_baseline: bool,
+}
+
+impl PerfGateOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, AppError> {
+        let mut options = Self {
+            baseline: PathBuf::from(PERF_GATE_BASELINE),
+            threshold_percent: PERF_GATE_DEFAULT_THRESHOLD_PERCENT,
+            update_baseline: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--update-baseline" => options.update_baseline = true,
+                "--baseline" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| AppError::usage("`--baseline` requires a path"))?;
+                    options.baseline = PathBuf::from(path);
+                }
+                "--threshold" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| AppError::usage("`--threshold` requires a percentage"))?;
+                    options.threshold_percent = value
+                        .parse()
+                        .ok()
+                        .filter(|percent: &f64| percent.is_finite() && *percent >= 0.0)
+                        .ok_or_else(|| {
+                            AppError::usage(format!("Invalid `--threshold` percentage `{value}`"))
+                        })?;
+                }
+                _ => {
+                    return Err(AppError::usage(format!(
+                        "Unexpected argument `{arg}` for `perf-gate`.\n\n{}",
+                        usage()
+                    )))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PerfRegression {
+    bench: String,
+    baseline_ns: f64,
+    current_ns: f64,
+}
+
+impl PerfRegression {
+    fn percent(&self) -> f64 {
+        (self.current_ns / self.baseline_ns - 1.0) * 100.0
+    }
+}
+
+fn run_perf_gate(options: PerfGateOptions) -> Result<(), AppError> {
+    let started = SystemTime::now();
+    let steps = [Step {
+        name: "Hot-path criterion benches",
+        program: "cargo",
+        args: &[
+            "bench",
+            "--manifest-path",
+            "crates/rustak/Cargo.toml",
+            "--bench",
+            "decode_pipeline",
+            "--bench",
+            "serialisation",
+            "--bench",
+            "transport_throughput",
+            "--",
+            "--noplot",
+        ],
+        env: &[],
+    }];
+    run_steps("perf-gate", &steps)?;
+
+    let criterion_dir = env::var_os("CARGO_TARGET_DIR")
+        .map_or_else(|| PathBuf::from("target"), PathBuf::from)
+        .join("criterion");
+    let current = collect_criterion_medians(&criterion_dir, started)?;
+    if current.is_empty() {
+        return Err(AppError::command(format!(
+            "No criterion estimates from this run found under `{}`.",
+            criterion_dir.display()
+        )));
+    }
+
+    if options.update_baseline {
+        fs::write(&options.baseline, render_baseline(&current)).map_err(|error| {
+            AppError::command(format!(
+                "Failed to write baseline `{}`: {error}",
+                options.baseline.display()
+            ))
+        })?;
+        println!(
+            "Wrote {} benchmark medians to `{}`.",
+            current.len(),
+            options.baseline.display()
+        );
+        return Ok(());
+    }
+
+    let baseline_text = fs::read_to_string(&options.baseline).map_err(|error| {
+        AppError::command(format!(
+            "Failed to read baseline `{}`: {error}\nRecord one on the gating machine with `cargo run -p xtask -- perf-gate --update-baseline`.",
+            options.baseline.display()
+        ))
+    })?;
+    let baseline = parse_baseline(&baseline_text)?;
+    check_bench_coverage(&baseline, &current)?;
+
+    for (bench, current_ns) in &current {
+        if let Some((_, baseline_ns)) = baseline.iter().find(|(name, _)| name == bench) {
+            println!(
+                "   {bench}: {current_ns:.0} ns (baseline {baseline_ns:.0} ns, {:+.1}%)",
+                (current_ns / baseline_ns - 1.0) * 100.0
+            );
+        }
+    }
+
+    let regressions = find_regressions(&baseline, &current, options.threshold_percent);
+    if regressions.is_empty() {
+        println!(
+            "perf-gate passed: no hot-path bench regressed more than {}%.",
+            options.threshold_percent
+        );
+        return Ok(());
+    }
+
+    let details = regressions
+        .iter()
+        .map(|regression| {
+            format!(
+                "  {}: {:.0} ns -> {:.0} ns (+{:.1}%)",
+                regression.bench,
+                regression.baseline_ns,
+                regression.current_ns,
+                regression.percent()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(AppError::command(format!(
+        "perf-gate failed: {} bench(es) regressed more than {}%:\n{details}",
+        regressions.len(),
+        options.threshold_percent
+    )))
+}
+
+/// Reads `<group>/<bench>/new/estimates.json` for every gated group,
+/// skipping estimates older than `since` that an earlier run left behind.
+fn collect_criterion_medians(
+    criterion_dir: &Path,
+    since: SystemTime,
+) -> Result<Vec<(String, f64)>, AppError> {
+    let mut medians = Vec::new();
+    for group in PERF_GATE_GROUPS {
+        let group_dir = criterion_dir.join(group);
+        let Ok(entries) = fs::read_dir(&group_dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let estimates = entry.path().join("new").join("estimates.json");
+            let fresh = fs::metadata(&estimates)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified >= since);
+            if !fresh {
+                continue;
+            }
+            let json = fs::read_to_string(&estimates).map_err(|error| {
+                AppError::command(format!("Failed to read `{}`: {error}", estimates.display()))
+            })?;
+            let median = median_point_estimate(&json).ok_or_else(|| {
+                AppError::command(format!(
+                    "Failed to read median estimate from `{}`.",
+                    estimates.display()
+                ))
+            })?;
+            medians.push((
+                format!("{group}/{}", entry.file_name().to_string_lossy()),
+                median,
+            ));
+        }
+    }
+    medians.sort_by(|left, right| left.0.cmp(&right.0));
+    Ok(medians)
+}
+
+fn median_point_estimate(estimates_json: &str) -> Option<f64> {
+    let estimates: serde_json::Value = serde_json::from_str(estimates_json).ok()?;
+    estimates.get("median")?.get("point_estimate")?.as_f64()
+}
+
+fn render_baseline(medians: &[(String, f64)]) -> String {
+    let mut text = String::from(
+        "# perf-gate baseline: <group>/<bench> <median ns per iteration>\n# Regenerate with `cargo run -p xtask -- perf-gate --update-baseline`.\n",
+    );
+    for (bench, median) in medians {
+        text.push_str(&format!("{bench} {median:.1}\n"));
+    }
+    text
+}
+
+fn parse_baseline(text: &str) -> Result<Vec<(String, f64)>, AppError> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (bench, value) = line.split_once(char::is_whitespace).ok_or_else(|| {
+                AppError::command(format!("Malformed perf baseline line `{line}`."))
+            })?;
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value > 0.0)
+                .ok_or_else(|| {
+                    AppError::command(format!("Malformed perf baseline value in `{line}`."))
+                })?;
+            Ok((bench.to_owned(), value))
+        })
+        .collect()
+}
+
+/// Fails unless this run measured exactly the benches the baseline records.
+fn check_bench_coverage(
+    baseline: &[(String, f64)],
+    current: &[(String, f64)],
+) -> Result<(), AppError> {
+    let missing = |from: &[(String, f64)], within: &[(String, f64)]| {
+        from.iter()
+            .filter(|(bench, _)| !within.iter().any(|(name, _)| name == bench))
+            .map(|(bench, _)| bench.clone())
+            .collect::<Vec<_>>()
+    };
+    let not_run = missing(baseline, current);
+    let unrecorded = missing(current, baseline);
+    if not_run.is_empty() && unrecorded.is_empty() {
+        return Ok(());
+    }
+    let mut problems = Vec::new();
+    if !not_run.is_empty() {
+        problems.push(format!("baseline benches not run: {}", not_run.join(", ")));
+    }
+    if !unrecorded.is_empty() {
+        problems.push(format!(
+            "benches without a baseline entry: {}",
+            unrecorded.join(", ")
+        ));
+    }
+    Err(AppError::command(format!(
+        "perf-gate failed: {}.\nRegenerate the baseline with `cargo run -p xtask -- perf-gate --update-baseline` if the bench set changed on purpose.",
+        problems.join("; ")
+    )))
+}
+
+fn find_regressions(
+    baseline: &[(String, f64)],
+    current: &[(String, f64)],
+    threshold_percent: f64,
+) -> Vec<PerfRegression> {
+    current
+        .iter()
+        .filter_map(|(bench, current_ns)| {
+            let (_, baseline_ns) = baseline.iter().find(|(name, _)| name == bench)?;
+            let limit = baseline_ns * (1.0 + threshold_percent / 100.0);
+            (*current_ns > limit).then(|| PerfRegression {
+                bench: bench.clone(),
+                baseline_ns: *baseline_ns,
+                current_ns: *current_ns,
+            })
+        })
+        .collect()
+}
+
+/// Release tags `api-check` compares against when no `--baseline-rev` is given.
+const API_CHECK_RELEASE_TAG_PATTERN: &str = "v*";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ApiCheckOptions {
+    baseline_rev: Option<String>,
+    packages: Vec<String>,
+}
+
+impl ApiCheckOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, AppError> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--baseline-rev" => {
+                    let rev = args.next().ok_or_else(|| {
+                        AppError::usage("`--baseline-rev` requires a git revision")
+                    })?;
+                    options.baseline_rev = Some(rev);
+                }
+                "--package" | "-p" => {
+                    let package = args
+                        .next()
+                        .ok_or_else(|| AppError::usage("`--package` requires a crate name"))?;
+                    options.packages.push(package);
+                }
+                _ => {
+                    return Err(AppError::usage(format!(
+                        "Unexpected argument `{arg}` for `api-check`.\n\n{}",
+                        usage()
+                    )))
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// A workspace member that is published to crates.io.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PublishedCrate {
+    name: String,
+    member: String,
+}
+
+/// Runs cargo-semver-checks for every published crate against the last
+/// release. The release type is inferred from each crate's version bump, so
+/// breaking changes pass only when the version says they are intended.
+fn run_api_check(options: ApiCheckOptions) -> Result<(), AppError> {
+    ensure_cargo_subcommand("semver-checks", "cargo-semver-checks")?;
+
+    let baseline = api_check_baseline(options.baseline_rev)?;
+
+    let mut crates = published_crates()?;
+    if !options.packages.is_empty() {
+        if let Some(unknown) = options
+            .packages
+            .iter()
+            .find(|package| !crates.iter().any(|krate| &krate.name == *package))
+        {
+            return Err(AppError::usage(format!(
+                "`{unknown}` is not a published workspace crate."
+            )));
+        }
+        crates.retain(|krate| options.packages.contains(&krate.name));
+    }
+
+    println!(
+        "Running xtask `api-check` for {} crate(s) against `{baseline}`.",
+        crates.len()
+    );
+    for krate in &crates {
+        if !git_path_exists(&baseline, &format!("{}/Cargo.toml", krate.member))? {
+            println!(
+                "-> {}: not present at `{baseline}`; nothing to compare.",
+                krate.name
+            );
+            continue;
+        }
+        run_process(
+            &format!("Public API of {}", krate.name),
+            "cargo",
+            &[
+                "semver-checks",
+                "check-release",
+                "--package",
+                &krate.name,
+                "--baseline-rev",
+                &baseline,
+                "--all-features",
+            ],
+            &[],
+        )?;
+    }
+    println!("xtask `api-check` completed successfully.");
+    Ok(())
+}
+
+/// `--baseline-rev`, else the latest release tag. Before the first release
+/// this falls back to the merge base with the pull request's target branch
+/// (`GITHUB_BASE_REF`) or to `HEAD^`, and fails if neither resolves, so the
+/// check never passes without comparing anything.
+fn api_check_baseline(requested: Option<String>) -> Result<String, AppError> {
+    if let Some(rev) = requested {
+        return Ok(rev);
+    }
+    if let Some(tag) = git_output(&[
+        "describe",
+        "--tags",
+        "--abbrev=0",
+        "--match",
+        API_CHECK_RELEASE_TAG_PATTERN,
+    ])? {
+        return Ok(tag);
+    }
+    let fallback = match env::var("GITHUB_BASE_REF") {
+        Ok(base) if !base.is_empty() => {
+            git_output(&["merge-base", "HEAD", &format!("origin/{base}")])?
+        }
+        _ => git_output(&["rev-parse", "--verify", "HEAD^"])?,
+    };
+    let rev = fallback.ok_or_else(|| {
+        AppError::command(format!(
+            "No release tag matching `{API_CHECK_RELEASE_TAG_PATTERN}` and no parent revision to compare against; pass `--baseline-rev`."
+        ))
+    })?;
+    println!(
+        "No release tag matching `{API_CHECK_RELEASE_TAG_PATTERN}`; comparing against `{rev}`."
+    );
+    Ok(rev)
+}
+
+/// Trimmed stdout of a successful git command, `None` if it failed or
+/// printed nothing.
+fn git_output(args: &[&str]) -> Result<Option<String>, AppError> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|error| AppError::command(format!("Failed to run `git {}`: {error}", args[0])))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    Ok((!text.is_empty()).then_some(text))
+}
+
+fn git_path_exists(rev: &str, path: &str) -> Result<bool, AppError> {
+    Command::new("git")
+        .args(["cat-file", "-e", &format!("{rev}:{path}")])
+        .output()
+        .map(|output| output.status.success())
+        .map_err(|error| AppError::command(format!("Failed to run `git cat-file`: {error}")))
+}
+
+fn published_crates() -> Result<Vec<PublishedCrate>, AppError> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .output()
+        .map_err(|error| AppError::command(format!("Failed to run `cargo metadata`: {error}")))?;
+    if !output.status.success() {
+        return Err(AppError::command(format!(
+            "`cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    published_crates_from_metadata(&output.stdout)
+}
+
+/// Workspace packages in `cargo metadata` output whose `publish` is not
+/// `false`, which cargo reports as an empty registry list.
+fn published_crates_from_metadata(metadata: &[u8]) -> Result<Vec<PublishedCrate>, AppError> {
+    let metadata: serde_json::Value = serde_json::from_slice(metadata).map_err(|error| {
+        AppError::command(format!("Failed to parse `cargo metadata` output: {error}"))
+    })?;
+    let malformed = || AppError::command("Unexpected `cargo metadata` output.");
+    let root = metadata["workspace_root"]
+        .as_str()
+        .map(Path::new)
+        .ok_or_else(malformed)?;
+    let packages = metadata["packages"].as_array().ok_or_else(malformed)?;
+    packages
+        .iter()
+        .filter(|package| {
+            package["publish"]
+                .as_array()
+                .is_none_or(|registries| !registries.is_empty())
+        })
+        .map(|package| {
+            let name = package["name"].as_str().ok_or_else(malformed)?;
+            let manifest = package["manifest_path"].as_str().ok_or_else(malformed)?;
+            let member = Path::new(manifest)
+                .parent()
+                .and_then(|dir| dir.strip_prefix(root).ok())
+                .ok_or_else(malformed)?;
+            Ok(PublishedCrate {
+                name: name.to_owned(),
+                member: member.to_string_lossy().replace('\\', "/"),
+            })
+        })
+        .collect()
+}
+
 fn run_steps(name: &str, steps: &[Step]) -> Result<(), AppError> {
     println!("Running xtask `{name}` with {} step(s).", steps.len());
     for step in steps {
@@ -220,36 +736,38 @@ fn run_steps(name: &str, steps: &[Step]) -> Result<(), AppError> {
 }
 
 fn run_step(step: &Step) -> Result<(), AppError> {
-    let env_string = if step.env.is_empty() {
+    run_process(step.name, step.program, step.args, step.env)
+}
+
+fn run_process(
+    name: &str,
+    program: &str,
+    args: &[&str],
+    env: &[(&str, &str)],
+) -> Result<(), AppError> {
+    let env_string = if env.is_empty() {
         String::new()
     } else {
         format!(
             "{} ",
-            step.env
-                .iter()
+            env.iter()
                 .map(|(key, value)| format!("{key}={value}"))
                 .collect::<Vec<_>>()
                 .join(" ")
         )
     };
 
-    println!(
-        "-> {}: {}{} {}",
-        step.name,
-        env_string,
-        step.program,
-        step.args.join(" ")
-    );
+    println!("-> {}: {}{} {}", name, env_string, program, args.join(" "));
 
-    let mut command = Command::new(step.program);
-    command.args(step.args).envs(step.env.iter().copied());
+    let mut command = Command::new(program);
+    command.args(args).envs(env.iter().copied());
 
     let status = command.status().map_err(|error| {
         AppError::command(format!(
             "Failed to launch step `{}` ({} {}): {}",
-            step.name,
-            step.program,
-            step.args.join(" "),
+            name,
+            program,
+            args.join(" "),
             error
         ))
     })?;
@@ -259,7 +777,7 @@ fn run_step(step: &Step) -> Result<(), AppError> {
     } else {
         Err(AppError::command(format!(
             "Step `{}` failed with status {}.",
-            step.name, status
+            name, status
         )))
     }
 }
@@ -282,7 +800,7 @@ fn ensure_cargo_subcommand(
     }
 
     Err(AppError::command(format!(
-        "`cargo {subcommand}` is required for this hardening check; install it with `cargo install {install_package}`"
+        "`cargo {subcommand}` is required for this check; install it with `cargo install {install_package}`"
     )))
 }
 
@@ -357,5 +875,148 @@ fn directory_has_loom_markers(path: &Path) -> Result<bool, AppError> {
 }
 
 fn usage() -> &'static str {
-    "Usage: cargo run -p xtask -- <subcommand>\n\nSubcommands:\n  ci                      Run fmt, clippy, and workspace tests\n  fuzz-smoke              Run cargo-fuzz target listing (or fallback workspace smoke check)\n  release-check           Run workspace check, all-feature tests, and docs build\n  hardening               Run supply-chain + loom smoke checks\n  hardening-supply-chain  Run cargo-deny/audit/vet checks\n  hardening-loom          Run workspace check under cfg(loom)\n  help                    Print this help\n\nExit codes:\n  0  Success\n  1  Command execution failure\n  2  Usage error"
+    "Usage: cargo run -p xtask -- <subcommand>\n\nSubcommands:\n  ci                      Run fmt, clippy, and workspace tests\n  fuzz-smoke              Run cargo-fuzz target listing (or fallback workspace smoke check)\n  release-check           Run workspace check, all-feature tests, and docs build\n  hardening               Run supply-chain + loom smoke checks\n  hardening-supply-chain  Run cargo-deny/audit/vet checks\n  hardening-loom          Run workspace check under cfg(loom)\n  perf-gate               Run hot-path benches and fail on regressions vs benches/perf_baseline.txt\n                          [--threshold <percent>] [--baseline <path>] [--update-baseline]\n  alloc-audit             Report allocations per message for the frame/parse/map/serialize stages\n  api-check               Run cargo-semver-checks for every published crate against the last v* release tag\n                          [--baseline-rev <rev>] [--package <crate>]...\n  help                    Print this help\n\nExit codes:\n  0  Success\n  1  Command execution failure\n  2  Usage error"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{
+        check_bench_coverage, collect_criterion_medians, find_regressions, median_point_estimate,
+        parse_baseline, published_crates_from_metadata, render_baseline, ApiCheckOptions,
+        PublishedCrate,
+    };
+
+    #[test]
+    fn median_estimate_is_read_from_criterion_json() {
+        let json = r#"{"mean":{"confidence_interval":{"lower_bound":1.0,"upper_bound":3.0},"point_estimate":2.5,"standard_error":0.1},"median":{"confidence_interval":{"lower_bound":1.0,"upper_bound":3.0},"point_estimate":2.0,"standard_error":0.1}}"#;
+        assert_eq!(median_point_estimate(json), Some(2.0));
+        assert_eq!(median_point_estimate("{}"), None);
+        assert_eq!(
+            median_point_estimate(r#"{"median":{"point_estimate":"2.0"}}"#),
+            None
+        );
+        assert_eq!(median_point_estimate("not json"), None);
+    }
+
+    #[test]
+    fn coverage_fails_on_benches_missing_from_either_side() {
+        let baseline = vec![
+            ("decode_pipeline/xml".to_owned(), 1000.0),
+            ("serialisation/proto".to_owned(), 200.0),
+        ];
+        assert!(check_bench_coverage(&baseline, &baseline).is_ok());
+
+        let current = vec![
+            ("decode_pipeline/xml".to_owned(), 1000.0),
+            ("transport_throughput/new".to_owned(), 5000.0),
+        ];
+        let error = check_bench_coverage(&baseline, &current)
+            .expect_err("coverage mismatch")
+            .message;
+        assert!(
+            error.contains("baseline benches not run: serialisation/proto"),
+            "{error}"
+        );
+        assert!(
+            error.contains("benches without a baseline entry: transport_throughput/new"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn only_estimates_from_this_run_are_collected() {
+        let dir = std::env::temp_dir().join(format!("xtask_criterion_{}", std::process::id()));
+        let estimates = |bench: &str| {
+            let path = dir.join("serialisation").join(bench).join("new");
+            std::fs::create_dir_all(&path).expect("bench dir");
+            let path = path.join("estimates.json");
+            std::fs::write(&path, r#"{"median":{"point_estimate":42.0}}"#).expect("estimates");
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .expect("estimates")
+        };
+        let since = SystemTime::now() - Duration::from_secs(60);
+        estimates("stale")
+            .set_modified(since - Duration::from_secs(60))
+            .expect("backdate");
+        estimates("fresh");
+
+        let medians = collect_criterion_medians(&dir, since).expect("medians");
+        assert_eq!(medians, [("serialisation/fresh".to_owned(), 42.0)]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn baseline_round_trips_and_flags_regressions_past_threshold() {
+        let recorded = vec![
+            ("decode_pipeline/xml".to_owned(), 1000.0),
+            ("serialisation/proto".to_owned(), 200.0),
+        ];
+        let baseline = parse_baseline(&render_baseline(&recorded)).expect("baseline parses");
+        assert_eq!(baseline, recorded);
+
+        let current = vec![
+            ("decode_pipeline/xml".to_owned(), 1090.0),
+            ("serialisation/proto".to_owned(), 260.0),
+            ("transport_throughput/new".to_owned(), 5000.0),
+        ];
+        let regressions = find_regressions(&baseline, &current, 10.0);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].bench, "serialisation/proto");
+        assert!((regressions[0].percent() - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn malformed_baseline_lines_are_rejected() {
+        assert!(parse_baseline("decode_pipeline/xml fast").is_err());
+        assert!(parse_baseline("decode_pipeline/xml").is_err());
+        assert!(parse_baseline("decode_pipeline/xml 0").is_err());
+    }
+
+    #[test]
+    fn api_check_selects_published_members_and_parses_options() {
+        let metadata = r#"{
+            "workspace_root": "/src/takrust",
+            "packages": [
+                {"name": "xtask", "publish": [], "manifest_path": "/src/takrust/xtask/Cargo.toml"},
+                {"name": "rustak-core", "publish": null, "manifest_path": "/src/takrust/crates/rustak-core/Cargo.toml"},
+                {"name": "rustak-wire", "publish": ["crates-io"], "manifest_path": "/src/takrust/crates/rustak-wire/Cargo.toml"}
+            ]
+        }"#;
+        assert_eq!(
+            published_crates_from_metadata(metadata.as_bytes()).expect("metadata parses"),
+            [
+                PublishedCrate {
+                    name: "rustak-core".to_owned(),
+                    member: "crates/rustak-core".to_owned(),
+                },
+                PublishedCrate {
+                    name: "rustak-wire".to_owned(),
+                    member: "crates/rustak-wire".to_owned(),
+                },
+            ]
+        );
+        assert!(published_crates_from_metadata(b"{}").is_err());
+
+        let options = ApiCheckOptions::parse(
+            [
+                "--baseline-rev",
+                "v0.1.0",
+                "-p",
+                "rustak-core",
+                "--package",
+                "rustak-wire",
+            ]
+            .into_iter()
+            .map(str::to_owned),
+        )
+        .expect("options parse");
+        assert_eq!(options.baseline_rev.as_deref(), Some("v0.1.0"));
+        assert_eq!(options.packages, ["rustak-core", "rustak-wire"]);
+        assert!(ApiCheckOptions::parse(["--baseline-rev".to_owned()].into_iter()).is_err());
+        assert!(ApiCheckOptions::parse(["--workspace".to_owned()].into_iter()).is_err());
+    }
 }